@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use goal_rag::providers::local::LocalVectorStore;
+use goal_rag::storage::{ChunkContentRecord, ChunkContentRow, FileRegistryDb};
+use goal_rag::types::{Chunk, ChunkSource};
+use uuid::Uuid;
+
+const CHUNKS: usize = 500;
+
+fn chunks(text_len: usize) -> Vec<Chunk> {
+    let document_id = Uuid::new_v4();
+    let text: String = "lorem ipsum dolor sit amet ".chars().cycle().take(text_len).collect();
+    (0..CHUNKS)
+        .map(|i| {
+            let mut chunk = Chunk::new(document_id, text.clone(), ChunkSource::text("a.txt".to_string()), 0, text_len, i as u32);
+            chunk.embedding = vec![0.5; 768];
+            chunk
+        })
+        .collect()
+}
+
+/// Handing a batch of embedded chunks to the vector store and FTS stages
+fn bench_handoff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk_handoff");
+    group.throughput(Throughput::Elements(CHUNKS as u64));
+
+    for text_len in [1_000, 8_000] {
+        let input = chunks(text_len);
+
+        // Before: the batch is copied for the blocking task and each FTS record owns its text
+        group.bench_with_input(BenchmarkId::new("copied", text_len), &input, |bench, input| {
+            bench.iter_batched(
+                || input.clone(),
+                |chunks| {
+                    let moved = chunks.to_vec();
+                    let records: Vec<ChunkContentRecord> =
+                        chunks.iter().map(LocalVectorStore::chunk_to_content_record).collect();
+                    black_box((moved, records))
+                },
+                BatchSize::LargeInput,
+            );
+        });
+
+        group.bench_with_input(BenchmarkId::new("shared", text_len), &input, |bench, input| {
+            bench.iter_batched(
+                || input.clone(),
+                |chunks| {
+                    let chunks: Arc<[Chunk]> = chunks.into();
+                    let moved = Arc::clone(&chunks);
+                    let rows: Vec<ChunkContentRow<'_>> = chunks.iter().map(ChunkContentRow::from).collect();
+                    black_box((moved, rows.len()))
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+/// Full-text inserts, from owned records and from rows borrowed from the chunks
+fn bench_fts_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fts_insert");
+    group.throughput(Throughput::Elements(CHUNKS as u64));
+    group.sample_size(20);
+
+    let database = FileRegistryDb::in_memory().unwrap();
+    let input = chunks(4_000);
+
+    group.bench_function("records", |bench| {
+        bench.iter(|| {
+            let records: Vec<ChunkContentRecord> = input.iter().map(LocalVectorStore::chunk_to_content_record).collect();
+            database.insert_chunks_content(&records).unwrap();
+        });
+    });
+    group.bench_function("rows", |bench| {
+        bench.iter(|| database.insert_chunk_rows(input.iter().map(ChunkContentRow::from)).unwrap());
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_handoff, bench_fts_insert);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use goal_rag::retrieval::similarity::{cosine_similarity, dot, dot_scalar, CosineQuery};
+
+fn vector(len: usize, seed: usize) -> Vec<f32> {
+    (0..len).map(|i| ((i * 31 + seed * 17) % 97) as f32 / 97.0 - 0.5).collect()
+}
+
+fn bench_dot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dot_product");
+
+    for size in [384, 768, 1024, 1536] {
+        let a = vector(size, 1);
+        let b = vector(size, 2);
+
+        group.bench_with_input(BenchmarkId::new("simd", size), &size, |bench, _| {
+            bench.iter(|| dot(black_box(&a), black_box(&b)));
+        });
+        group.bench_with_input(BenchmarkId::new("scalar", size), &size, |bench, _| {
+            bench.iter(|| dot_scalar(black_box(&a), black_box(&b)));
+        });
+        group.bench_with_input(BenchmarkId::new("naive", size), &size, |bench, _| {
+            bench.iter(|| black_box(&a).iter().zip(black_box(&b)).map(|(x, y)| x * y).sum::<f32>());
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_cosine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cosine_similarity");
+
+    for size in [384, 768, 1536] {
+        let a = vector(size, 1);
+        let b = vector(size, 2);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |bench, _| {
+            bench.iter(|| cosine_similarity(black_box(&a), black_box(&b)));
+        });
+    }
+
+    group.finish();
+}
+
+/// Scoring a query against every candidate, as the brute-force retrievers do
+fn bench_brute_force(c: &mut Criterion) {
+    let mut group = c.benchmark_group("brute_force_768");
+
+    for candidates in [1_000, 10_000] {
+        let query = vector(768, 0);
+        let corpus: Vec<Vec<f32>> = (1..=candidates).map(|seed| vector(768, seed)).collect();
+        group.throughput(Throughput::Elements(candidates as u64));
+
+        group.bench_with_input(BenchmarkId::from_parameter(candidates), &candidates, |bench, _| {
+            bench.iter(|| {
+                let query = CosineQuery::new(black_box(&query));
+                corpus.iter().map(|candidate| query.score(candidate)).fold(f32::MIN, f32::max)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dot, bench_cosine, bench_brute_force);
+criterion_main!(benches);
@@ -25,7 +25,7 @@ class ApiClient {
 
     if (!response.ok) {
       const error = await response.json().catch(() => ({ message: 'Unknown error' }));
-      throw new Error(error.error?.message || error.message || `HTTP ${response.status}`);
+      throw new Error(error.detail || error.message || `HTTP ${response.status}`);
     }
 
     return response.json();
@@ -54,7 +54,7 @@ class ApiClient {
 
     if (!response.ok) {
       const error = await response.json().catch(() => ({ message: 'Upload failed' }));
-      throw new Error(error.error?.message || error.message || `HTTP ${response.status}`);
+      throw new Error(error.detail || error.message || `HTTP ${response.status}`);
     }
 
     return response.json();
@@ -77,6 +77,11 @@ class ApiClient {
     });
   }
 
+  // URL of a page's PNG thumbnail (404 when none was rendered)
+  pageThumbnailUrl(documentId: string, page: number): string {
+    return `${API_BASE}/documents/${documentId}/pages/${page}/thumbnail`;
+  }
+
   // Health check
   async healthCheck(): Promise<boolean> {
     try {
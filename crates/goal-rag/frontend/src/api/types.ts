@@ -23,6 +23,16 @@ export interface Citation {
   snippet_highlighted: string;
   similarity_score: number;
   rerank_score: number | null;
+  location?: SourceLocation | null;
+}
+
+/** Where a citation points in its source document */
+export interface SourceLocation {
+  original: string;
+  plaintext: string;
+  page: number | null;
+  char_start: number;
+  char_end: number;
 }
 
 export interface QueryRequest {
@@ -41,6 +51,7 @@ export interface QueryResponse {
   processing_time_ms: number;
   chunks_retrieved: number;
   chunks_used: number;
+  follow_up_questions?: string[];
 }
 
 export interface IngestResponse {
@@ -28,14 +28,8 @@ export function ChatInterface({ chatHistory, onQuery, hasDocuments }: ChatInterf
     scrollToBottom();
   }, [chatHistory]);
 
-  const handleSubmit = async (e: React.FormEvent) => {
-    e.preventDefault();
-    if (!input.trim() || isLoading) return;
-
-    const question = input.trim();
-    setInput('');
+  const ask = async (question: string) => {
     setIsLoading(true);
-
     try {
       await onQuery(question);
     } finally {
@@ -43,6 +37,15 @@ export function ChatInterface({ chatHistory, onQuery, hasDocuments }: ChatInterf
     }
   };
 
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!input.trim() || isLoading) return;
+
+    const question = input.trim();
+    setInput('');
+    await ask(question);
+  };
+
   const formatTime = (ms: number) => {
     if (ms < 1000) return `${ms}ms`;
     return `${(ms / 1000).toFixed(1)}s`;
@@ -118,6 +121,23 @@ export function ChatInterface({ chatHistory, onQuery, hasDocuments }: ChatInterf
                         ))}
                       </div>
                     )}
+
+                    {/* Follow-up suggestions */}
+                    {message.response?.follow_up_questions && message.response.follow_up_questions.length > 0 && (
+                      <div className="flex flex-wrap gap-2">
+                        {message.response.follow_up_questions.map((followUp, followUpIndex) => (
+                          <button
+                            key={followUpIndex}
+                            type="button"
+                            onClick={() => ask(followUp)}
+                            disabled={isLoading}
+                            className="text-sm text-primary-700 bg-primary-50 hover:bg-primary-100 disabled:opacity-50 px-3 py-1 rounded-full transition-colors"
+                          >
+                            {followUp}
+                          </button>
+                        ))}
+                      </div>
+                    )}
                   </>
                 )}
               </div>
@@ -1,5 +1,6 @@
 import { useState } from 'react';
 import { FileText, ChevronDown, ChevronUp, FileCode, FileSpreadsheet } from 'lucide-react';
+import { api } from '../api/client';
 import type { Citation } from '../api/types';
 
 interface CitationCardProps {
@@ -9,6 +10,7 @@ interface CitationCardProps {
 
 export function CitationCard({ citation, index }: CitationCardProps) {
   const [isExpanded, setIsExpanded] = useState(false);
+  const [hasThumbnail, setHasThumbnail] = useState(true);
 
   const getFileIcon = () => {
     const type = citation.file_type.toLowerCase();
@@ -96,10 +98,21 @@ export function CitationCard({ citation, index }: CitationCardProps) {
       {/* Expanded Content */}
       {isExpanded && (
         <div className="border-t border-gray-100">
-          <div className="px-3 py-3">
-            <p className="text-sm font-medium text-gray-700 mb-2">Source excerpt:</p>
-            <div className="bg-gray-50 rounded-lg p-3 text-sm text-gray-700 leading-relaxed">
-              {formatSnippet(citation.snippet, citation.snippet_highlighted)}
+          <div className="px-3 py-3 flex gap-3">
+            {citation.page_number && hasThumbnail && (
+              <img
+                src={api.pageThumbnailUrl(citation.document_id, citation.page_number)}
+                alt={`Page ${citation.page_number} of ${citation.filename}`}
+                className="flex-shrink-0 w-24 self-start border border-gray-200 rounded shadow-sm"
+                loading="lazy"
+                onError={() => setHasThumbnail(false)}
+              />
+            )}
+            <div className="flex-1 min-w-0">
+              <p className="text-sm font-medium text-gray-700 mb-2">Source excerpt:</p>
+              <div className="bg-gray-50 rounded-lg p-3 text-sm text-gray-700 leading-relaxed">
+                {formatSnippet(citation.snippet, citation.snippet_highlighted)}
+              </div>
             </div>
           </div>
 
@@ -118,6 +131,16 @@ export function CitationCard({ citation, index }: CitationCardProps) {
                 Rerank: {Math.round(citation.rerank_score * 100)}%
               </span>
             )}
+            {citation.location && (
+              <a
+                href={citation.location.original}
+                target="_blank"
+                rel="noopener noreferrer"
+                className="bg-green-50 text-green-700 px-2 py-1 rounded hover:bg-green-100"
+              >
+                Open source{citation.location.page ? ` at page ${citation.location.page}` : ''}
+              </a>
+            )}
           </div>
         </div>
       )}
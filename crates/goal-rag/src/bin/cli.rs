@@ -0,0 +1,811 @@
+//! RAG command-line client
+//!
+//! Talks to a running server over HTTP, or with `--embedded` runs the whole
+//! pipeline in-process against the configured data directory.
+//!
+//! Run with: cargo run -p goal-rag --features cli --bin ruvector-rag -- query "What is...?"
+//! Embedded: cargo run -p goal-rag --features cli --bin ruvector-rag -- --embedded ingest ./docs
+
+use anyhow::{bail, Context};
+use axum::{
+    extract::{Path as PathParam, Query, State},
+    Json,
+};
+use clap::{Parser, Subcommand};
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+use goal_rag::{
+    config::RagConfig,
+    config_check,
+    processing::{FileData, Job, JobPriority, ProcessingOptions},
+    retrieval::RetrievalStrategy,
+    server::routes::{admin, jobs},
+    storage::{EncryptionKey, FileRegistryDb, SnapshotManager},
+    types::query::{EmailFilter, IngestOptions, PathIngestRequest, QueryRequest},
+    types::response::DocumentSummary,
+    RagEngine,
+};
+
+/// How often job and reindex progress is polled
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Parser)]
+#[command(name = "ruvector-rag")]
+#[command(about = "Ingest documents and ask questions with source citations", long_about = None)]
+#[command(version)]
+struct Cli {
+    /// Server URL
+    #[arg(long, global = true, env = "RAG_SERVER", default_value = "http://localhost:8080")]
+    server: String,
+
+    /// API key for servers with authentication enabled
+    #[arg(long, global = true, env = "RAG_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
+    /// Run in-process instead of against a server
+    #[arg(long, global = true)]
+    embedded: bool,
+
+    /// Configuration file for embedded mode
+    #[arg(short, long, global = true, env = "RAG_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Print raw JSON responses
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Ingest files or directories
+    Ingest {
+        /// Files or directories (directories are walked recursively)
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Collection to ingest into
+        #[arg(long)]
+        collection: Option<String>,
+
+        /// Principal id or role allowed to read the documents (repeatable; default: everyone)
+        #[arg(long)]
+        acl: Vec<String>,
+
+        /// Chunk size override
+        #[arg(long)]
+        chunk_size: Option<usize>,
+
+        /// Chunk overlap override
+        #[arg(long)]
+        chunk_overlap: Option<usize>,
+
+        /// Queue as a bulk backfill, yielding to interactive uploads
+        #[arg(long)]
+        bulk: bool,
+
+        /// Return once the job is queued (server mode only)
+        #[arg(long)]
+        no_wait: bool,
+    },
+
+    /// Ingest a directory on the server's filesystem, skipping files already ingested
+    ///
+    /// The directory must be under the server's processing.local_ingest_roots.
+    IngestPath {
+        /// Directory or file, as seen by the server
+        path: String,
+
+        /// Glob of files to ingest, relative to the path (repeatable; default: all)
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Glob of files or directories to leave out (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Prefix of the stored filenames
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Collection to ingest into
+        #[arg(long)]
+        collection: Option<String>,
+
+        /// Principal id or role allowed to read the documents (repeatable; default: everyone)
+        #[arg(long)]
+        acl: Vec<String>,
+
+        /// Queue as a bulk backfill, yielding to interactive uploads
+        #[arg(long)]
+        bulk: bool,
+
+        /// Also queue files whose size and modification time are unchanged
+        #[arg(long)]
+        force: bool,
+
+        /// Follow symbolic links
+        #[arg(long)]
+        follow_links: bool,
+
+        /// Return once the job is queued (server mode only)
+        #[arg(long)]
+        no_wait: bool,
+    },
+
+    /// Ask a question
+    Query {
+        question: String,
+
+        /// Number of chunks to retrieve
+        #[arg(short = 'k', long)]
+        top_k: Option<usize>,
+
+        /// Restrict retrieval to these document IDs
+        #[arg(long = "document")]
+        document_ids: Vec<Uuid>,
+
+        /// Collection to search
+        #[arg(long)]
+        collection: Option<String>,
+
+        /// Language to answer in (e.g. "de", "German")
+        #[arg(long)]
+        language: Option<String>,
+
+        /// Translate retrieved passages into the answer language first
+        #[arg(long, requires = "language")]
+        translate: bool,
+
+        /// Retrieval strategy: standard, multi_query or hyde
+        #[arg(long, value_parser = parse_strategy)]
+        strategy: Option<RetrievalStrategy>,
+
+        /// Only use emails whose sender contains this text
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Only use emails whose subject contains this text
+        #[arg(long)]
+        subject: Option<String>,
+
+        /// Experiment variant to answer with
+        #[arg(long)]
+        variant: Option<String>,
+    },
+
+    /// Manage documents
+    Documents {
+        #[command(subcommand)]
+        command: DocumentCommands,
+    },
+
+    /// Inspect ingestion jobs
+    Jobs {
+        #[command(subcommand)]
+        command: JobCommands,
+    },
+
+    /// Re-chunk and re-embed all documents, then swap the index
+    Reindex {
+        /// Return once the reindex has started (server mode only)
+        #[arg(long)]
+        no_wait: bool,
+    },
+
+    /// Replace the SQLite registry with a snapshot (stop the server first)
+    RestoreSnapshot {
+        /// Snapshot file, or the name of one in the snapshot directory
+        snapshot: PathBuf,
+
+        /// Registry to replace (default: the configured one)
+        #[arg(long)]
+        database: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DocumentCommands {
+    /// List ingested documents
+    List,
+    /// Delete a document and its chunks
+    Delete { id: Uuid },
+}
+
+#[derive(Subcommand)]
+enum JobCommands {
+    /// List jobs and queue stats
+    List,
+    /// Follow a job until it finishes
+    Watch { id: Uuid },
+}
+
+/// Where commands are executed
+enum Client {
+    /// A running server
+    Remote { http: reqwest::Client, base_url: String },
+    /// The library, in this process
+    Embedded(RagEngine),
+}
+
+impl Client {
+    async fn connect(cli: &Cli) -> anyhow::Result<Self> {
+        if !cli.embedded {
+            let mut headers = reqwest::header::HeaderMap::new();
+            if let Some(ref key) = cli.api_key {
+                let value = format!("Bearer {}", key).parse().context("Invalid API key")?;
+                headers.insert(reqwest::header::AUTHORIZATION, value);
+            }
+            return Ok(Self::Remote {
+                http: reqwest::Client::builder().default_headers(headers).build()?,
+                base_url: cli.server.trim_end_matches('/').to_string(),
+            });
+        }
+
+        let config = load_config(cli.config.as_deref())?;
+        Ok(Self::Embedded(RagEngine::new(config).await?))
+    }
+
+    fn is_embedded(&self) -> bool {
+        matches!(self, Self::Embedded(_))
+    }
+
+    async fn submit_files(&self, files: Vec<LocalFile>, options: ProcessingOptions) -> anyhow::Result<Uuid> {
+        match self {
+            Self::Remote { http, base_url } => {
+                let options = serde_json::json!({
+                    "chunk_size": options.chunk_size,
+                    "chunk_overlap": options.chunk_overlap,
+                    "collection": options.collection,
+                    "acl": options.acl,
+                    "priority": options.priority,
+                });
+                let mut form = reqwest::multipart::Form::new().text("options", options.to_string());
+                for file in files {
+                    // Streamed from disk rather than read up front
+                    let body = tokio::fs::File::open(&file.path).await?;
+                    let part = reqwest::multipart::Part::stream_with_length(body, file.size).file_name(file.filename);
+                    form = form.part("files", part);
+                }
+                let response = http.post(format!("{}/api/ingest/async", base_url)).multipart(form).send().await?;
+                let body = check(response).await?;
+                body["job_id"]
+                    .as_str()
+                    .and_then(|id| id.parse().ok())
+                    .context("Server response is missing job_id")
+            }
+            Self::Embedded(engine) => {
+                let spool = engine.state().job_queue().spool();
+                let files = files
+                    .into_iter()
+                    .map(|file| {
+                        let input = std::fs::File::open(&file.path)?;
+                        let (hash, size) = spool.put_reader(input)?;
+                        Ok(FileData { filename: file.filename, hash, size })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let job = Job {
+                    id: Uuid::new_v4(),
+                    files,
+                    options: ProcessingOptions {
+                        parallel_embeddings: num_cpus::get().min(8),
+                        ..options
+                    },
+                };
+                Ok(engine.state().job_queue().submit(job).await)
+            }
+        }
+    }
+
+    async fn ingest_path(&self, request: PathIngestRequest) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { http, base_url } => {
+                check(http.post(format!("{}/api/ingest/path", base_url)).json(&request).send().await?).await
+            }
+            Self::Embedded(engine) => to_value(jobs::ingest_path(State(engine.state().clone()), Json(request)).await?),
+        }
+    }
+
+    async fn job(&self, id: Uuid) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { .. } => self.get(&format!("/api/jobs/{}", id)).await,
+            Self::Embedded(engine) => to_value(jobs::get_job_progress(State(engine.state().clone()), PathParam(id)).await?),
+        }
+    }
+
+    async fn jobs(&self) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { .. } => self.get("/api/jobs").await,
+            Self::Embedded(engine) => {
+                let params = serde_json::from_value(serde_json::json!({}))?;
+                to_value(jobs::list_jobs(State(engine.state().clone()), Query(params)).await?)
+            }
+        }
+    }
+
+    async fn query(&self, request: QueryRequest) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { http, base_url } => {
+                check(http.post(format!("{}/api/query", base_url)).json(&request).send().await?).await
+            }
+            Self::Embedded(engine) => Ok(serde_json::to_value(engine.query(request).await?)?),
+        }
+    }
+
+    async fn documents(&self) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { .. } => self.get("/api/documents").await,
+            Self::Embedded(engine) => {
+                let documents: Vec<DocumentSummary> = engine.list_documents().iter().map(DocumentSummary::from).collect();
+                Ok(serde_json::json!({ "total": documents.len(), "documents": documents }))
+            }
+        }
+    }
+
+    async fn delete_document(&self, id: Uuid) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { http, base_url } => {
+                check(http.delete(format!("{}/api/documents/{}", base_url, id)).send().await?).await
+            }
+            Self::Embedded(engine) => {
+                let (doc, deleted_chunks) = engine.delete_document(&id).await?;
+                Ok(serde_json::json!({
+                    "success": true,
+                    "document_id": id,
+                    "filename": doc.filename,
+                    "deleted_chunks": deleted_chunks
+                }))
+            }
+        }
+    }
+
+    async fn start_reindex(&self) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { http, base_url } => {
+                check(http.post(format!("{}/api/admin/reindex", base_url)).send().await?).await
+            }
+            Self::Embedded(engine) => to_value(admin::start_reindex(State(engine.state().clone())).await?),
+        }
+    }
+
+    async fn reindex_status(&self) -> anyhow::Result<Value> {
+        match self {
+            Self::Remote { .. } => self.get("/api/admin/reindex").await,
+            Self::Embedded(engine) => to_value(admin::get_reindex_status(State(engine.state().clone())).await?),
+        }
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<Value> {
+        let Self::Remote { http, base_url } = self else {
+            unreachable!("GET requests are only made in server mode");
+        };
+        check(http.get(format!("{}{}", base_url, path)).send().await?).await
+    }
+}
+
+/// Turn an error response into an error carrying the server's message
+async fn check(response: reqwest::Response) -> anyhow::Result<Value> {
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        let message = body["detail"].as_str().unwrap_or("no error message");
+        bail!("Server returned {}: {}", status, message);
+    }
+    Ok(body)
+}
+
+fn to_value<T: serde::Serialize>(Json(value): Json<T>) -> anyhow::Result<Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // Quiet by default, the CLI prints its own output
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "goal_rag=warn".into()),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    // Restores work on the registry file directly, never through a server or an engine
+    if let Commands::RestoreSnapshot { ref snapshot, ref database } = cli.command {
+        return restore_snapshot(cli.config.as_deref(), snapshot, database.as_deref()).await;
+    }
+
+    let client = Client::connect(&cli).await?;
+
+    match cli.command {
+        Commands::Ingest {
+            ref paths,
+            ref collection,
+            ref acl,
+            chunk_size,
+            chunk_overlap,
+            bulk,
+            no_wait,
+        } => {
+            let files = collect_files(paths)?;
+            if files.is_empty() {
+                bail!("No files found");
+            }
+            println!("Submitting {} file(s)", files.len());
+
+            let options = ProcessingOptions {
+                chunk_size,
+                chunk_overlap,
+                collection: collection.clone(),
+                acl: acl.clone(),
+                priority: if bulk { JobPriority::Bulk } else { JobPriority::Interactive },
+                ..Default::default()
+            };
+            let job_id = client.submit_files(files, options).await?;
+
+            // An embedded job only runs while this process is alive
+            if no_wait && !client.is_embedded() {
+                println!("Queued job {}", style(job_id).cyan());
+                return Ok(());
+            }
+            let job = watch_job(&client, job_id).await?;
+            print_job(&job, cli.json)?;
+        }
+        Commands::IngestPath {
+            ref path,
+            ref include,
+            ref exclude,
+            ref prefix,
+            ref collection,
+            ref acl,
+            bulk,
+            force,
+            follow_links,
+            no_wait,
+        } => {
+            let request = PathIngestRequest {
+                path: path.clone(),
+                include: include.clone(),
+                exclude: exclude.clone(),
+                follow_links,
+                prefix: prefix.clone(),
+                force,
+                options: IngestOptions {
+                    collection: collection.clone(),
+                    acl: acl.clone(),
+                    priority: if bulk { JobPriority::Bulk } else { JobPriority::Interactive },
+                    ..Default::default()
+                },
+            };
+            let queued = client.ingest_path(request).await?;
+            let job_id = queued["job_id"].as_str().and_then(|id| id.parse::<Uuid>().ok());
+            if cli.json {
+                if no_wait || job_id.is_none() {
+                    return print_json(&queued);
+                }
+            } else {
+                println!(
+                    "{} file(s) matched: {} queued, {} unchanged",
+                    queued["files_matched"], queued["files_queued"], queued["files_skipped"]
+                );
+                for file in queued["unreadable"].as_array().into_iter().flatten() {
+                    let filename = file["filename"].as_str().unwrap_or("?");
+                    println!("  {} {}: {}", style("unreadable").red(), filename, file["reason"].as_str().unwrap_or(""));
+                }
+            }
+            let Some(job_id) = job_id else { return Ok(()) };
+
+            // An embedded job only runs while this process is alive
+            if no_wait && !client.is_embedded() {
+                println!("Queued job {}", style(job_id).cyan());
+                return Ok(());
+            }
+            let job = watch_job(&client, job_id).await?;
+            print_job(&job, cli.json)?;
+        }
+        Commands::Query {
+            ref question,
+            top_k,
+            ref document_ids,
+            ref collection,
+            ref language,
+            translate,
+            strategy,
+            ref from,
+            ref subject,
+            ref variant,
+        } => {
+            let mut request = QueryRequest::new(question.as_str());
+            if let Some(top_k) = top_k {
+                request.top_k = top_k;
+            }
+            if !document_ids.is_empty() {
+                request.document_filter = Some(document_ids.clone());
+            }
+            request.collection = collection.clone();
+            request.language = language.clone();
+            request.translate_context = translate;
+            request.retrieval_strategy = strategy.unwrap_or_default();
+            request.variant = variant.clone();
+            if from.is_some() || subject.is_some() {
+                request.email_filter = Some(EmailFilter {
+                    from: from.clone(),
+                    subject: subject.clone(),
+                    ..Default::default()
+                });
+            }
+            let response = client.query(request).await?;
+            print_answer(&response, cli.json)?;
+        }
+        Commands::Documents {
+            command: DocumentCommands::List,
+        } => {
+            let response = client.documents().await?;
+            if cli.json {
+                return print_json(&response);
+            }
+            let documents = response["documents"].as_array().cloned().unwrap_or_default();
+            for doc in &documents {
+                println!(
+                    "{}  {}  {} chunks",
+                    style(doc["id"].as_str().unwrap_or("")).dim(),
+                    doc["filename"].as_str().unwrap_or(""),
+                    doc["total_chunks"]
+                );
+            }
+            println!("{} document(s)", documents.len());
+        }
+        Commands::Documents {
+            command: DocumentCommands::Delete { id },
+        } => {
+            let response = client.delete_document(id).await?;
+            if cli.json {
+                return print_json(&response);
+            }
+            println!("Deleted document {}", id);
+        }
+        Commands::Jobs {
+            command: JobCommands::List,
+        } => {
+            let response = client.jobs().await?;
+            if cli.json {
+                return print_json(&response);
+            }
+            for job in response["jobs"].as_array().into_iter().flatten() {
+                println!(
+                    "{}  {:<10}  {:>5.1}%  {} files",
+                    style(job["job_id"].as_str().unwrap_or("")).dim(),
+                    job["status"].as_str().unwrap_or(""),
+                    job["percent_complete"].as_f64().unwrap_or(0.0),
+                    job["total_files"]
+                );
+            }
+        }
+        Commands::Jobs {
+            command: JobCommands::Watch { id },
+        } => {
+            let job = watch_job(&client, id).await?;
+            print_job(&job, cli.json)?;
+        }
+        Commands::Reindex { no_wait } => {
+            let started = client.start_reindex().await?;
+            println!(
+                "Reindexing {} document(s)",
+                started["reindex"]["total_documents"].as_u64().unwrap_or(0)
+            );
+            if no_wait && !client.is_embedded() {
+                return Ok(());
+            }
+            let status = watch_reindex(&client).await?;
+            if cli.json {
+                return print_json(&status);
+            }
+            match status["reindex"]["error"].as_str() {
+                Some(error) => bail!("Reindex failed: {}", error),
+                None => println!("{}", style("Reindex complete").green()),
+            }
+        }
+        Commands::RestoreSnapshot { .. } => unreachable!("restores are handled before connecting"),
+    }
+
+    Ok(())
+}
+
+/// Copy a snapshot over the registry with SQLite's backup API
+async fn restore_snapshot(config: Option<&Path>, snapshot: &Path, database: Option<&Path>) -> anyhow::Result<()> {
+    let config = load_config(config)?;
+    let snapshot = if snapshot.exists() {
+        snapshot.to_path_buf()
+    } else {
+        SnapshotManager::new(config.snapshots.clone(), &config.vector_db.storage_dir())
+            .directory()
+            .join(snapshot)
+    };
+    if !snapshot.exists() {
+        bail!("Snapshot {} not found", snapshot.display());
+    }
+
+    let database = database.map(Path::to_path_buf).unwrap_or_else(|| config.vector_db.registry_path());
+    let key = EncryptionKey::load(&config.encryption, config.gcp.as_ref())
+        .await?
+        .filter(|_| config.encryption.database);
+    FileRegistryDb::restore_snapshot(&snapshot, &database, key.as_ref())?;
+    println!(
+        "{} {} from {}",
+        style("Restored").green(),
+        database.display(),
+        snapshot.display()
+    );
+    Ok(())
+}
+
+/// Load the embedded-mode configuration
+fn load_config(path: Option<&Path>) -> anyhow::Result<RagConfig> {
+    let path = path.map(Path::to_path_buf).or_else(|| {
+        ["config.toml", "crates/goal-rag/config.toml"]
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|p| p.exists())
+    });
+
+    let loaded = config_check::load(path.as_deref())?;
+    for issue in config_check::validate(&loaded.config) {
+        eprintln!("{} {}", style("config").yellow(), issue);
+    }
+    Ok(loaded.config)
+}
+
+/// Parse a retrieval strategy by its API name
+fn parse_strategy(name: &str) -> Result<RetrievalStrategy, String> {
+    serde_json::from_value(Value::String(name.replace('-', "_")))
+        .map_err(|_| format!("unknown strategy '{}' (expected standard, multi_query or hyde)", name))
+}
+
+/// A file to ingest
+struct LocalFile {
+    filename: String,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Find files, walking directories recursively
+fn collect_files(paths: &[PathBuf]) -> anyhow::Result<Vec<LocalFile>> {
+    let mut files = Vec::new();
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if filename.starts_with('.') {
+                continue;
+            }
+            let size = entry.metadata().with_context(|| format!("Failed to read {}", entry.path().display()))?.len();
+            files.push(LocalFile { filename, path: entry.into_path(), size });
+        }
+    }
+    Ok(files)
+}
+
+fn progress_bar(len: u64) -> ProgressBar {
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+            .expect("valid progress template")
+            .progress_chars("=> "),
+    );
+    bar
+}
+
+/// Poll a job until it completes or fails
+async fn watch_job(client: &Client, job_id: Uuid) -> anyhow::Result<Value> {
+    let bar = progress_bar(0);
+    loop {
+        let job = client.job(job_id).await?;
+        let done = ["files_processed", "files_skipped", "files_failed"]
+            .iter()
+            .map(|field| job[field].as_u64().unwrap_or(0))
+            .sum();
+        bar.set_length(job["total_files"].as_u64().unwrap_or(0));
+        bar.set_position(done);
+        bar.set_message(format!(
+            "{} {}",
+            job["stage"].as_str().unwrap_or(""),
+            job["current_file"].as_str().unwrap_or("")
+        ));
+
+        if matches!(job["status"].as_str(), Some("complete" | "failed")) {
+            bar.finish_and_clear();
+            return Ok(job);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Poll the reindex until it is no longer running
+async fn watch_reindex(client: &Client) -> anyhow::Result<Value> {
+    let bar = progress_bar(0);
+    loop {
+        let status = client.reindex_status().await?;
+        let reindex = &status["reindex"];
+        bar.set_length(reindex["total_documents"].as_u64().unwrap_or(0));
+        bar.set_position(reindex["documents_processed"].as_u64().unwrap_or(0));
+        bar.set_message(reindex["current_document"].as_str().unwrap_or("").to_string());
+
+        if status["running"] != Value::Bool(true) {
+            bar.finish_and_clear();
+            return Ok(status);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn print_json(value: &Value) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_job(job: &Value, json: bool) -> anyhow::Result<()> {
+    if json {
+        return print_json(job);
+    }
+
+    let status = job["status"].as_str().unwrap_or("unknown");
+    let status = if status == "complete" { style(status).green() } else { style(status).red() };
+    println!(
+        "Job {}: {} ({} processed, {} skipped, {} failed, {} chunks)",
+        job["job_id"].as_str().unwrap_or(""),
+        status,
+        job["files_processed"],
+        job["files_skipped"],
+        job["files_failed"],
+        job["total_chunks"]
+    );
+    for error in job["file_errors"].as_array().into_iter().flatten() {
+        println!(
+            "  {} {}: {}",
+            style("✗").red(),
+            error["filename"].as_str().unwrap_or(""),
+            error["error"].as_str().unwrap_or("")
+        );
+    }
+    if let Some(error) = job["error"].as_str() {
+        bail!("Job failed: {}", error);
+    }
+    Ok(())
+}
+
+fn print_answer(response: &Value, json: bool) -> anyhow::Result<()> {
+    if json {
+        return print_json(response);
+    }
+
+    println!("{}\n", response["answer"].as_str().unwrap_or(""));
+    let citations = response["citations"].as_array().cloned().unwrap_or_default();
+    if !citations.is_empty() {
+        println!("{}", style("Sources").bold());
+    }
+    for (i, citation) in citations.iter().enumerate() {
+        let page = citation["page_number"]
+            .as_u64()
+            .map(|p| format!(", page {}", p))
+            .unwrap_or_default();
+        println!(
+            "  [{}] {}{} ({:.0}%)",
+            i + 1,
+            citation["filename"].as_str().unwrap_or(""),
+            page,
+            citation["similarity_score"].as_f64().unwrap_or(0.0) * 100.0
+        );
+    }
+    Ok(())
+}
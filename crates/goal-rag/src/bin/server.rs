@@ -2,21 +2,30 @@
 //!
 //! Run with: cargo run -p goal-rag --bin goal-rag-server
 //! With config: cargo run -p goal-rag --bin goal-rag-server -- --config config.toml
+//! Worker only: cargo run -p goal-rag --bin goal-rag-server -- --worker
+//! Check the configuration: cargo run -p goal-rag --bin goal-rag-server -- --check-config
+//!
+//! Settings can be overridden with `RAG__<SECTION>__<KEY>` environment
+//! variables (e.g. `RAG__LLM__BASE_URL`).
 
-use goal_rag::{config::RagConfig, server::RagServer};
+use goal_rag::{config_check, server::RagServer, telemetry};
 use std::path::PathBuf;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "goal_rag=info,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Load configuration first, it decides where traces are exported
+    let paths_to_try = config_paths();
+    let config_path = paths_to_try.iter().find(|path| path.exists());
+    let loaded = config_check::load(config_path.map(PathBuf::as_path))?;
+
+    if std::env::args().any(|a| a == "--check-config") {
+        return check_config(&loaded).await;
+    }
+
+    let config = loaded.config;
+
+    // Initialize tracing (flushes exported spans when dropped)
+    let _telemetry = telemetry::init(&config.observability)?;
 
     println!(
         r#"
@@ -27,38 +36,58 @@ async fn main() -> anyhow::Result<()> {
 "#
     );
 
-    // Load configuration from file or use defaults
-    let config = load_config()?;
+    match &loaded.path {
+        Some(path) => tracing::info!("Loaded config from: {}", path.display()),
+        None => {
+            tracing::info!("No config file found, using defaults");
+            tracing::info!("  Searched: {:?}", paths_to_try);
+            tracing::info!("  Create config.toml or set RAG_CONFIG env var");
+        }
+    }
 
+    if !loaded.overrides.is_empty() {
+        tracing::info!("Overridden from the environment: {}", loaded.overrides.join(", "));
+    }
     tracing::info!("Configuration loaded");
     tracing::info!("  - Embedding model: {}", config.embeddings.model);
     tracing::info!("  - Embedding dimensions: {}", config.embeddings.dimensions);
     tracing::info!("  - LLM model: {}", config.llm.generate_model);
     tracing::info!("  - Chunk size: {}", config.chunking.chunk_size);
+    match config_check::effective_config(&config) {
+        Ok(effective) => tracing::debug!("Effective configuration:\n{}", effective),
+        Err(e) => tracing::warn!("Failed to render the effective configuration: {}", e),
+    }
 
-    // Check Ollama
-    tracing::info!("Checking Ollama at {}...", config.llm.base_url);
-    let client = reqwest::Client::new();
-    match client.get(format!("{}/api/tags", config.llm.base_url)).send().await {
-        Ok(resp) if resp.status().is_success() => {
-            tracing::info!("Ollama is running");
-        }
-        _ => {
-            tracing::warn!("Ollama not available at {}", config.llm.base_url);
-            tracing::warn!("Please start Ollama:");
-            tracing::warn!("  1. Install: brew install ollama");
-            tracing::warn!("  2. Start: ollama serve");
-            tracing::warn!("  3. Pull models: ollama pull nomic-embed-text && ollama pull llama3.2:3b");
+    // Refuse settings that can't work, mention the ones that look off
+    let mut issues = config_check::validate(&config);
+    issues.extend(config_check::check_services(&config).await);
+    for issue in &issues {
+        if issue.is_error() {
+            tracing::error!("Config {}", issue);
+        } else {
+            tracing::warn!("Config {}", issue);
         }
     }
+    let errors = issues.iter().filter(|issue| issue.is_error()).count();
+    if errors > 0 {
+        anyhow::bail!("{} configuration errors, run with --check-config for the full report", errors);
+    }
 
     // Create and start server
-    let server = RagServer::new(config).await?;
+    let worker_only = std::env::args().any(|a| a == "--worker");
+    let server = RagServer::new(config).await?.with_config_path(loaded.path.clone());
+
+    if worker_only {
+        println!("\nIngestion worker running, press Ctrl+C to stop\n");
+        server.run_worker().await?;
+        return Ok(());
+    }
 
     println!("\nServer starting...");
-    println!("  API: http://{}", server.address());
-    println!("  Health: http://{}/health", server.address());
-    println!("  API Info: http://{}/api/info", server.address());
+    let base = format!("{}://{}", server.scheme(), server.address());
+    println!("  API: {}", base);
+    println!("  Health: {}/health", base);
+    println!("  API Info: {}/api/info", base);
     println!("\nEndpoints:");
     println!("  POST /api/ingest    - Upload documents");
     println!("  POST /api/query     - Ask questions");
@@ -70,8 +99,8 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Load configuration from file or environment
-fn load_config() -> anyhow::Result<RagConfig> {
+/// Config file locations, in order of precedence
+fn config_paths() -> Vec<PathBuf> {
     // Check command line args for --config
     let args: Vec<String> = std::env::args().collect();
     let config_path = args
@@ -86,28 +115,40 @@ fn load_config() -> anyhow::Result<RagConfig> {
     // 3. ./config.toml (current directory)
     // 4. ./crates/goal-rag/config.toml (workspace root)
     // 5. Default values
-    let paths_to_try: Vec<PathBuf> = vec![
-        config_path.clone(),
+    vec![
+        config_path,
         std::env::var("RAG_CONFIG").ok().map(PathBuf::from),
         Some(PathBuf::from("config.toml")),
         Some(PathBuf::from("crates/goal-rag/config.toml")),
     ]
     .into_iter()
     .flatten()
-    .collect();
-
-    for path in &paths_to_try {
-        if path.exists() {
-            tracing::info!("Loading config from: {}", path.display());
-            let content = std::fs::read_to_string(path)?;
-            let config: RagConfig = toml::from_str(&content)?;
-            return Ok(config);
-        }
+    .collect()
+}
+
+/// Print the effective configuration and every issue found, failing on errors
+async fn check_config(loaded: &config_check::LoadedConfig) -> anyhow::Result<()> {
+    match &loaded.path {
+        Some(path) => println!("# Loaded from {}", path.display()),
+        None => println!("# No config file found, defaults"),
     }
+    if !loaded.overrides.is_empty() {
+        println!("# Overridden from the environment: {}", loaded.overrides.join(", "));
+    }
+    println!("{}", config_check::effective_config(&loaded.config)?);
 
-    // No config file found, use defaults
-    tracing::info!("No config file found, using defaults");
-    tracing::info!("  Searched: {:?}", paths_to_try);
-    tracing::info!("  Create config.toml or set RAG_CONFIG env var");
-    Ok(RagConfig::default())
+    let mut issues = config_check::validate(&loaded.config);
+    issues.extend(config_check::check_services(&loaded.config).await);
+    if issues.is_empty() {
+        println!("Configuration OK");
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let errors = issues.iter().filter(|issue| issue.is_error()).count();
+    if errors > 0 {
+        anyhow::bail!("{} configuration errors", errors);
+    }
+    Ok(())
 }
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 use crate::ingestion::ExternalParserConfig;
+use crate::processing::ProcessingStage;
+use crate::retrieval::RetrievalStrategy;
 
 /// Main RAG system configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,38 +28,2056 @@ pub struct RagConfig {
     pub external_parser: ExternalParserConfig,
     /// Processing configuration
     pub processing: ProcessingConfig,
+    /// Job queue backend configuration
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// Text analyzers for lexical search (default and per collection)
+    #[serde(default)]
+    pub analyzers: AnalyzersConfig,
+    /// Retrieval trace sampling for offline analysis
+    #[serde(default)]
+    pub traces: TraceConfig,
+    /// Append-only log of every answered query
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// OpenTelemetry span export
+    #[serde(default)]
+    pub observability: ObservabilityConfig,
+    /// Signed answer provenance
+    #[serde(default)]
+    pub provenance: ProvenanceConfig,
+    /// Encryption of the registry database and spooled files
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    /// Retrieval behavior
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    /// Fitting retrieved chunks into the model's context window
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Image captioning for embedded figures
+    #[serde(default)]
+    pub vision: VisionConfig,
+    /// Page thumbnails for citation previews
+    #[serde(default)]
+    pub thumbnails: ThumbnailsConfig,
+    /// Original files and extracted text kept for "open at citation"
+    #[serde(default)]
+    pub originals: OriginalsConfig,
+    /// Audio/video transcription
+    #[serde(default)]
+    pub transcription: TranscriptionConfig,
+    /// Archive (.zip, .tar.gz) extraction limits
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    /// History of replaced document versions
+    #[serde(default)]
+    pub versioning: VersioningConfig,
+    /// Online snapshots of the SQLite registry
+    #[serde(default)]
+    pub snapshots: SnapshotsConfig,
+    /// API authentication and document access control
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// PII detection and masking
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Passwords for encrypted PDFs
+    #[serde(default)]
+    pub pdf_passwords: PdfPasswordsConfig,
+    /// Malware scanning of uploads before parsing
+    #[serde(default)]
+    pub antivirus: AntivirusConfig,
+    /// Keyword and entity extraction at ingestion
+    #[serde(default)]
+    pub keywords: KeywordsConfig,
+    /// Knowledge graph of triples extracted from chunks
+    #[serde(default)]
+    pub graph: GraphConfig,
+    /// Synthetic question-answer pairs generated from chunks
+    #[serde(default)]
+    pub qa_generation: QaGenerationConfig,
+    /// Token usage, cost tracking and budgets
+    #[serde(default)]
+    pub usage: UsageConfig,
+    /// Requests and tokens per minute allowed per provider (by provider
+    /// name: "vertex-ai", "gemini", "ollama"), shared by all workers and queries
+    #[serde(default)]
+    pub rate_limits: std::collections::HashMap<String, ProviderRateLimit>,
+    /// Selectable generation models and fallback chains
+    #[serde(default)]
+    pub models: ModelsConfig,
+    /// Prompt-injection guard for queries and retrieved context
+    #[serde(default)]
+    pub guard: GuardConfig,
+    /// A/B experiments on query configuration
+    #[serde(default)]
+    pub experiments: ExperimentsConfig,
+    /// Event notifications to external systems
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    /// Operator-defined prompt templates
+    #[serde(default)]
+    pub prompts: PromptsConfig,
+    /// Saved queries run on a schedule
+    #[serde(default)]
+    pub reports: ReportsConfig,
+    /// Suggested follow-up questions
+    #[serde(default)]
+    pub follow_ups: FollowUpConfig,
+    /// Slack and Teams bots answering questions in chat
+    #[serde(default)]
+    pub integrations: IntegrationsConfig,
+    /// Google Drive and SharePoint sources synced on a schedule
+    #[serde(default)]
+    pub connectors: Vec<ConnectorConfig>,
     /// GCP configuration (required when backend = gcp)
     #[serde(default)]
-    pub gcp: Option<GcpConfig>,
+    pub gcp: Option<GcpConfig>,
+}
+
+
+/// Processing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessingConfig {
+    /// Timeout for processing a single file in seconds (default: 300 = 5 minutes)
+    /// This is the fallback timeout if tiered processing is disabled
+    pub file_timeout_secs: u64,
+    /// Number of files processed at once, across all jobs
+    pub parallel_files: Option<usize>,
+    /// Number of parallel embeddings per file
+    pub parallel_embeddings: Option<usize>,
+    /// Tiered processing configuration (size-based routing)
+    #[serde(default)]
+    pub tiered: TieredProcessingConfig,
+    /// Plain-text/CSV files at least this large (MB) are chunked and embedded
+    /// incrementally instead of being parsed as a whole (default: 64)
+    #[serde(default = "default_streaming_threshold_mb")]
+    pub streaming_threshold_mb: u64,
+    /// Chunks embedded and stored per batch when streaming (default: 256)
+    #[serde(default = "default_streaming_batch_chunks")]
+    pub streaming_batch_chunks: usize,
+    /// Chunks per batch handed from chunking to embedding to storage for
+    /// parsed files (default: 64)
+    #[serde(default = "default_pipeline_batch_chunks")]
+    pub pipeline_batch_chunks: usize,
+    /// Batches queued between two ingestion stages before the earlier one
+    /// waits (default: 2)
+    #[serde(default = "default_pipeline_depth")]
+    pub pipeline_depth: usize,
+    /// Directory uploads are spooled to until their job finishes (default:
+    /// `spool` in the vector store directory). Workers of a shared queue
+    /// must see the same directory.
+    #[serde(default)]
+    pub spool_dir: Option<PathBuf>,
+    /// Files of interactive jobs started for every file of a bulk job while
+    /// both wait for a slot (default: 4)
+    #[serde(default = "default_interactive_weight")]
+    pub interactive_weight: u32,
+    /// Retries for files failing transiently (jobs can override it)
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Server directories `/api/ingest/path` may ingest from, with their
+    /// subdirectories (empty = the endpoint is disabled)
+    #[serde(default)]
+    pub local_ingest_roots: Vec<PathBuf>,
+}
+
+fn default_streaming_threshold_mb() -> u64 { 64 }
+fn default_streaming_batch_chunks() -> usize { 256 }
+fn default_pipeline_batch_chunks() -> usize { 64 }
+fn default_pipeline_depth() -> usize { 2 }
+fn default_interactive_weight() -> u32 { 4 }
+
+impl ProcessingConfig {
+    /// Files processed at once (`parallel_files`, else the CPU count up to 8)
+    pub fn file_slots(&self) -> usize {
+        self.parallel_files.unwrap_or_else(|| num_cpus::get().min(8))
+    }
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            file_timeout_secs: 300, // 5 minutes
+            parallel_files: None,   // Auto-detect from CPU count
+            parallel_embeddings: None,
+            tiered: TieredProcessingConfig::default(),
+            streaming_threshold_mb: default_streaming_threshold_mb(),
+            streaming_batch_chunks: default_streaming_batch_chunks(),
+            pipeline_batch_chunks: default_pipeline_batch_chunks(),
+            pipeline_depth: default_pipeline_depth(),
+            spool_dir: None,
+            interactive_weight: default_interactive_weight(),
+            retry: RetryConfig::default(),
+            local_ingest_roots: Vec::new(),
+        }
+    }
+}
+
+/// Retry policies for the processing stages that can fail transiently
+///
+/// Chunking is deterministic and never retried.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct RetryConfig {
+    #[serde(default)]
+    pub parsing: RetryPolicy,
+    #[serde(default)]
+    pub embedding: RetryPolicy,
+    #[serde(default)]
+    pub storing: RetryPolicy,
+}
+
+impl RetryConfig {
+    /// Policy for a stage (a single attempt for stages without one)
+    pub fn for_stage(&self, stage: ProcessingStage) -> RetryPolicy {
+        match stage {
+            ProcessingStage::Parsing => self.parsing.clone(),
+            ProcessingStage::Embedding => self.embedding.clone(),
+            ProcessingStage::Storing => self.storing.clone(),
+            _ => RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Attempts and exponential backoff for one stage
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct RetryPolicy {
+    /// Attempts including the first one (default: 3, 1 disables retries)
+    #[serde(default = "default_retry_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one (default: 2000)
+    #[serde(default = "default_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Longest delay between attempts (default: 60000)
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_retry_max_attempts() -> u32 { 3 }
+fn default_retry_initial_backoff_ms() -> u64 { 2000 }
+fn default_retry_max_backoff_ms() -> u64 { 60_000 }
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_max_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the given (1-based) failed attempt
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        std::time::Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// Job queue backend selection
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueBackendKind {
+    /// In-process channel (single server, default)
+    #[default]
+    InProcess,
+    /// Shared Redis queue (requires the `redis-queue` feature)
+    Redis,
+}
+
+/// Job queue configuration
+///
+/// With a shared backend, API servers can set `run_workers = false` and
+/// ingestion runs in separate worker processes using the same config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueConfig {
+    /// Queue backend (default: in_process)
+    #[serde(default)]
+    pub backend: QueueBackendKind,
+    /// Redis connection URL (e.g., "redis://localhost:6379")
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    /// Key prefix for queue data in Redis (default: "goal-rag:jobs")
+    #[serde(default = "default_queue_key_prefix")]
+    pub key_prefix: String,
+    /// Lease duration in seconds before a silent worker's job is requeued (default: 120)
+    #[serde(default = "default_queue_lease_secs")]
+    pub lease_secs: u64,
+    /// Heartbeat interval in seconds, must be well below lease_secs (default: 30)
+    #[serde(default = "default_queue_heartbeat_secs")]
+    pub heartbeat_secs: u64,
+    /// Deliveries of a job before it is moved to the dead-letter list and
+    /// marked failed (default: 5)
+    #[serde(default = "default_queue_max_deliveries")]
+    pub max_deliveries: u32,
+    /// Run ingestion workers in this process (default: true)
+    #[serde(default = "default_run_workers")]
+    pub run_workers: bool,
+    /// Capacity of the in-process channel (default: 1000)
+    #[serde(default = "default_queue_capacity")]
+    pub capacity: usize,
+}
+
+fn default_queue_key_prefix() -> String { "goal-rag:jobs".to_string() }
+fn default_queue_lease_secs() -> u64 { 120 }
+fn default_queue_heartbeat_secs() -> u64 { 30 }
+fn default_queue_max_deliveries() -> u32 { 5 }
+fn default_run_workers() -> bool { true }
+fn default_queue_capacity() -> usize { 1000 }
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            backend: QueueBackendKind::InProcess,
+            redis_url: None,
+            key_prefix: default_queue_key_prefix(),
+            lease_secs: 120,
+            heartbeat_secs: 30,
+            max_deliveries: default_queue_max_deliveries(),
+            run_workers: true,
+            capacity: 1000,
+        }
+    }
+}
+
+/// Text analyzer used for lexical (FTS) indexing and query terms
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyzerKind {
+    /// SQLite unicode61 tokenization (whitespace/punctuation), default
+    #[default]
+    Standard,
+    /// Dictionary-based compound splitting (e.g., German)
+    Compound,
+    /// Character n-grams for scripts without word separators (e.g., Japanese)
+    Ngram,
+}
+
+/// Configuration for a single analyzer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    /// Analyzer kind (default: standard)
+    #[serde(default)]
+    pub kind: AnalyzerKind,
+    /// N-gram length for the ngram analyzer (default: 2)
+    #[serde(default = "default_ngram_size")]
+    pub ngram_size: usize,
+    /// Known words used to split compounds (compound analyzer)
+    #[serde(default)]
+    pub compound_words: Vec<String>,
+    /// File with one dictionary word per line (compound analyzer)
+    #[serde(default)]
+    pub compound_dictionary: Option<PathBuf>,
+    /// Minimum length of a compound part (default: 3)
+    #[serde(default = "default_min_subword_len")]
+    pub min_subword_len: usize,
+}
+
+fn default_ngram_size() -> usize { 2 }
+fn default_min_subword_len() -> usize { 3 }
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            kind: AnalyzerKind::Standard,
+            ngram_size: 2,
+            compound_words: Vec::new(),
+            compound_dictionary: None,
+            min_subword_len: 3,
+        }
+    }
+}
+
+/// Analyzer configuration for lexical search
+///
+/// Chunks are tagged with a collection at ingest time. Changing a collection's
+/// analyzer requires reindexing it (POST /api/admin/analyzers/reindex).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyzersConfig {
+    /// Analyzer for chunks without a configured collection
+    #[serde(default)]
+    pub default: AnalyzerConfig,
+    /// Per-collection analyzers keyed by collection name
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, AnalyzerConfig>,
+}
+
+/// Retrieval trace configuration
+///
+/// Sampled queries store their full candidate list, scores and final
+/// selection in SQLite (export via GET /api/admin/traces/export).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceConfig {
+    /// Enable trace persistence (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fraction of queries to trace, 0.0-1.0 (default: 0.05)
+    #[serde(default = "default_trace_sample_rate")]
+    pub sample_rate: f64,
+    /// Days to keep traces before cleanup on startup (default: 30)
+    #[serde(default = "default_trace_retention_days")]
+    pub retention_days: i64,
+    /// Prompt returned by `debug: true` queries (default: redacted)
+    #[serde(default)]
+    pub debug_prompt: DebugPrompt,
+}
+
+fn default_trace_sample_rate() -> f64 { 0.05 }
+fn default_trace_retention_days() -> i64 { 30 }
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: 0.05,
+            retention_days: 30,
+            debug_prompt: DebugPrompt::default(),
+        }
+    }
+}
+
+/// Answer audit log configuration
+///
+/// Every query records its caller, retrieved chunks and scores, a hash of the
+/// final prompt, the model, the answer and the latency (GET /api/audit).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Record queries (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Where records are appended (default: sqlite)
+    #[serde(default)]
+    pub sink: AuditSink,
+    /// Directory of the JSONL sink's daily files (default: `audit` in the
+    /// vector store directory)
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Days to keep records, 0 to keep them forever (default: 365)
+    #[serde(default = "default_audit_retention_days")]
+    pub retention_days: i64,
+}
+
+fn default_audit_retention_days() -> i64 { 365 }
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sink: AuditSink::default(),
+            dir: None,
+            retention_days: 365,
+        }
+    }
+}
+
+/// Storage of audit records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSink {
+    /// The `audit_log` table of the registry database
+    #[default]
+    Sqlite,
+    /// One `audit-YYYY-MM-DD.jsonl` file per day
+    Jsonl,
+}
+
+/// How the rendered prompt is returned by `debug: true` queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugPrompt {
+    /// As sent to the model
+    Full,
+    /// With PII masked by the collection's redaction policy
+    #[default]
+    Redacted,
+    /// Not returned
+    Omit,
+}
+
+/// OTLP transport protocol
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtlpProtocol {
+    /// gRPC (collector port 4317)
+    #[default]
+    Grpc,
+    /// HTTP/protobuf (collector port 4318)
+    Http,
+}
+
+/// OpenTelemetry configuration
+///
+/// When `otlp_endpoint` is set (and the server is built with the `otel`
+/// feature), request, query and ingestion spans are exported to a collector
+/// such as Jaeger or Tempo. Incoming W3C `traceparent` headers are honoured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObservabilityConfig {
+    /// OTLP collector endpoint, e.g. "http://localhost:4317" (default: none, export disabled)
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    /// Transport protocol (default: grpc)
+    #[serde(default)]
+    pub otlp_protocol: OtlpProtocol,
+    /// Service name reported to the collector (default: "goal-rag")
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Fraction of root traces to sample, 0.0-1.0 (default: 1.0)
+    #[serde(default = "default_otel_sample_ratio")]
+    pub sample_ratio: f64,
+    /// Export timeout in seconds (default: 10)
+    #[serde(default = "default_otlp_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_service_name() -> String { "goal-rag".to_string() }
+fn default_otel_sample_ratio() -> f64 { 1.0 }
+fn default_otlp_timeout_secs() -> u64 { 10 }
+
+impl Default for ObservabilityConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            otlp_protocol: OtlpProtocol::default(),
+            service_name: default_service_name(),
+            sample_ratio: 1.0,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Signature algorithm for answer provenance
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvenanceAlgorithm {
+    /// HMAC-SHA256 with a shared secret (verification needs the secret)
+    #[default]
+    HmacSha256,
+    /// Ed25519 (anyone with the public key can verify)
+    Ed25519,
+}
+
+/// Answer provenance signing
+///
+/// When enabled, query responses carry a signature over the answer text, the
+/// hashes of the cited chunks and the corpus snapshot id, verifiable later via
+/// `POST /api/provenance/verify`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProvenanceConfig {
+    /// Sign query answers (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Signature algorithm (default: hmac_sha256)
+    #[serde(default)]
+    pub algorithm: ProvenanceAlgorithm,
+    /// Signing key: HMAC secret, or base64 32-byte Ed25519 seed
+    #[serde(default)]
+    pub key: Option<String>,
+    /// File containing the signing key (used when `key` is not set)
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    /// Key identifier included in signatures, for key rotation
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+/// Encryption at rest
+///
+/// Encrypts the registry database with SQLCipher and, with AES-256-GCM,
+/// spooled uploads, the chunk text in the local vector index and the local
+/// document store, all under one 256-bit key. Requires building with
+/// `--features encryption`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// Encrypt data at rest (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Environment variable holding the base64 key (default: GOAL_RAG_ENCRYPTION_KEY)
+    #[serde(default = "default_encryption_key_env")]
+    pub key_env: String,
+    /// File holding the base64 key, used when the variable is not set
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    /// Cloud KMS key (`projects/*/locations/*/keyRings/*/cryptoKeys/*`) the
+    /// key is wrapped with; the variable or file then holds the base64
+    /// ciphertext. Requires the `gcp` feature and `[gcp]` credentials.
+    #[serde(default)]
+    pub kms_key: Option<String>,
+    /// Encrypt the registry database (default: true)
+    #[serde(default = "default_encrypt_database")]
+    pub database: bool,
+    /// Encrypt spooled uploads (default: true)
+    #[serde(default = "default_encrypt_spool")]
+    pub spool: bool,
+}
+
+fn default_encryption_key_env() -> String { "GOAL_RAG_ENCRYPTION_KEY".to_string() }
+fn default_encrypt_database() -> bool { true }
+fn default_encrypt_spool() -> bool { true }
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_env: default_encryption_key_env(),
+            key_file: None,
+            kms_key: None,
+            database: true,
+            spool: true,
+        }
+    }
+}
+
+/// Retrieval configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RetrievalConfig {
+    /// Parent window size in characters (default: disabled)
+    ///
+    /// When set, consecutive chunks are grouped into parent windows of up to
+    /// this size at ingestion. Retrieval still matches the small chunks, but
+    /// the prompt gets the whole parent window. Requires reindexing to apply
+    /// to existing documents.
+    #[serde(default)]
+    pub parent_window: Option<usize>,
+    /// Lowest best-match similarity to answer from (default: disabled)
+    ///
+    /// Compared with the search score before keyword and recency boosts.
+    ///
+    /// When no retrieved chunk scores this high, the question is not sent to
+    /// the LLM: the response reports insufficient evidence and lists the
+    /// near misses instead of an answer made up from weak context.
+    #[serde(default)]
+    pub answer_threshold: Option<f32>,
+    /// Per-collection `answer_threshold`, keyed by collection name
+    #[serde(default)]
+    pub collection_answer_thresholds: std::collections::HashMap<String, f32>,
+    /// Ask the LLM whether short phrases are searches, lookups or questions
+    /// (default: false, short phrases are string searches)
+    #[serde(default)]
+    pub intent_llm: bool,
+    /// Most chunks retrieved from any one document (default: no limit);
+    /// queries can set their own
+    #[serde(default)]
+    pub max_chunks_per_document: Option<usize>,
+    /// Ranking boost for recently ingested or updated documents
+    #[serde(default)]
+    pub recency: RecencyConfig,
+    /// Limits of POST /api/query/batch
+    #[serde(default)]
+    pub batch: BatchQueryConfig,
+}
+
+/// Batch queries
+///
+/// The questions of a batch are answered `concurrency` at a time, so their
+/// query embeddings share the embedding batch scheduler's batches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchQueryConfig {
+    /// Most questions per batch (default: 100)
+    #[serde(default = "default_batch_max_questions")]
+    pub max_questions: usize,
+    /// Questions answered at once (default: 4); requests can ask for fewer
+    #[serde(default = "default_batch_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_batch_max_questions() -> usize { 100 }
+fn default_batch_concurrency() -> usize { 4 }
+
+impl Default for BatchQueryConfig {
+    fn default() -> Self {
+        Self {
+            max_questions: default_batch_max_questions(),
+            concurrency: default_batch_concurrency(),
+        }
+    }
+}
+
+/// Recency boost
+///
+/// A chunk's score is raised by `boost` for a document modified just now,
+/// halving every `half_life_days`, so the newest revision of a policy
+/// outranks older copies that match about as well. A document's age counts
+/// from its modification at the source (`source_modified_at`, set by
+/// connectors) if known, from its ingestion otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecencyConfig {
+    /// Score added for a document ingested now (default: 0, disabled)
+    #[serde(default)]
+    pub boost: f32,
+    /// Days for the boost to halve (default: 30)
+    #[serde(default = "default_recency_half_life_days")]
+    pub half_life_days: f64,
+    /// Per-collection `half_life_days`, keyed by collection name
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, f64>,
+}
+
+fn default_recency_half_life_days() -> f64 { 30.0 }
+
+impl Default for RecencyConfig {
+    fn default() -> Self {
+        Self {
+            boost: 0.0,
+            half_life_days: default_recency_half_life_days(),
+            collections: std::collections::HashMap::new(),
+        }
+    }
+}
+
+impl RecencyConfig {
+    /// Boost of a document of a collection `age_days` old
+    pub fn boost_for(&self, collection: Option<&str>, age_days: f64) -> f32 {
+        let half_life = collection
+            .and_then(|name| self.collections.get(name).copied())
+            .unwrap_or(self.half_life_days);
+        if self.boost <= 0.0 || half_life <= 0.0 {
+            return 0.0;
+        }
+        self.boost * 0.5_f64.powf(age_days.max(0.0) / half_life) as f32
+    }
+}
+
+impl RetrievalConfig {
+    /// Answer threshold of a collection (the default one if it has none)
+    pub fn answer_threshold(&self, collection: Option<&str>) -> Option<f32> {
+        collection
+            .and_then(|name| self.collection_answer_thresholds.get(name).copied())
+            .or(self.answer_threshold)
+    }
+}
+
+/// How prompt tokens are counted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenizerKind {
+    /// OpenAI cl100k_base BPE (close enough for most current models)
+    #[default]
+    Cl100k,
+    /// Characters divided by `chars_per_token`
+    Estimate,
+}
+
+/// Context packing
+///
+/// Retrieved chunks are added to the prompt by score until the token budget
+/// is used up. The chunk that crosses the budget is trimmed if at least
+/// `min_chunk_tokens` of it fit, lower-scoring chunks are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Pack the context to the budget (default: true)
+    #[serde(default = "default_context_enabled")]
+    pub enabled: bool,
+    /// Token budget for retrieved chunks (default: `llm.context_size` minus
+    /// `reserve_tokens` on the local backend, unlimited on GCP)
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Tokens kept free for instructions, examples and the answer (default: 1024)
+    #[serde(default = "default_context_reserve_tokens")]
+    pub reserve_tokens: usize,
+    /// Smallest useful part of a trimmed chunk (default: 64)
+    #[serde(default = "default_context_min_chunk_tokens")]
+    pub min_chunk_tokens: usize,
+    #[serde(default)]
+    pub tokenizer: TokenizerKind,
+    /// Characters per token for the `estimate` tokenizer (default: 4.0)
+    #[serde(default = "default_chars_per_token")]
+    pub chars_per_token: f32,
+    /// Most chunks per batch for the map_reduce and refine answer strategies (default: 8)
+    #[serde(default = "default_context_batch_chunks")]
+    pub batch_chunks: usize,
+}
+
+fn default_context_batch_chunks() -> usize { 8 }
+fn default_context_enabled() -> bool { true }
+fn default_context_reserve_tokens() -> usize { 1024 }
+fn default_context_min_chunk_tokens() -> usize { 64 }
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_context_enabled(),
+            max_tokens: None,
+            reserve_tokens: default_context_reserve_tokens(),
+            min_chunk_tokens: default_context_min_chunk_tokens(),
+            tokenizer: TokenizerKind::default(),
+            chars_per_token: default_chars_per_token(),
+            batch_chunks: default_context_batch_chunks(),
+        }
+    }
+}
+
+impl ContextConfig {
+    /// Token budget for retrieved chunks, `None` when not packing
+    pub fn budget(&self, backend: &BackendProvider, llm: &LlmConfig) -> Option<usize> {
+        if !self.enabled {
+            return None;
+        }
+        match (self.max_tokens, backend) {
+            (Some(max_tokens), _) => Some(max_tokens),
+            (None, BackendProvider::Local) => Some(llm.context_size.saturating_sub(self.reserve_tokens)),
+            (None, BackendProvider::Gcp) => None,
+        }
+    }
+}
+
+/// Vision model used to caption figures
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VisionProviderKind {
+    /// Ollama multimodal model (llava, bakllava, ...)
+    #[default]
+    Ollama,
+    /// Gemini via Vertex AI (requires the gcp feature and [gcp] config)
+    Gemini,
+}
+
+/// Figure captioning
+///
+/// When enabled, images embedded in PDFs and DOCX files are described by a
+/// vision model and indexed as figure chunks linked to their page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisionConfig {
+    /// Caption embedded images at ingestion (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Vision provider (default: ollama)
+    #[serde(default)]
+    pub provider: VisionProviderKind,
+    /// Vision model name (default: llava)
+    #[serde(default = "default_vision_model")]
+    pub model: String,
+    /// Ollama base URL (default: llm.base_url)
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Maximum images captioned per document (default: 20)
+    #[serde(default = "default_vision_max_images")]
+    pub max_images_per_document: usize,
+    /// Smaller images (icons, logos, rules) are skipped (default: 8192 bytes)
+    #[serde(default = "default_vision_min_image_bytes")]
+    pub min_image_bytes: usize,
+    /// Timeout per caption request in seconds (default: 120)
+    #[serde(default = "default_vision_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_vision_model() -> String { "llava".to_string() }
+fn default_vision_max_images() -> usize { 20 }
+fn default_vision_min_image_bytes() -> usize { 8192 }
+fn default_vision_timeout_secs() -> u64 { 120 }
+
+impl Default for VisionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: VisionProviderKind::default(),
+            model: default_vision_model(),
+            base_url: None,
+            max_images_per_document: default_vision_max_images(),
+            min_image_bytes: default_vision_min_image_bytes(),
+            timeout_secs: default_vision_timeout_secs(),
+        }
+    }
+}
+
+/// Page thumbnails
+///
+/// When enabled, each PDF page is rendered to a small PNG at ingestion (with
+/// poppler's `pdftoppm`) and kept in the document store, so citations can show
+/// a preview of the cited page (GET /api/documents/:id/pages/:n/thumbnail).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailsConfig {
+    /// Render page thumbnails at ingestion (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest side of a thumbnail in pixels (default: 200)
+    #[serde(default = "default_thumbnail_size")]
+    pub size: u32,
+    /// Pages rendered per document, from the first (default: 200)
+    #[serde(default = "default_thumbnail_max_pages")]
+    pub max_pages: u32,
+}
+
+fn default_thumbnail_size() -> u32 { 200 }
+fn default_thumbnail_max_pages() -> u32 { 200 }
+
+impl Default for ThumbnailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: default_thumbnail_size(),
+            max_pages: default_thumbnail_max_pages(),
+        }
+    }
+}
+
+/// Original files and their extracted text
+///
+/// The GCP backend always keeps them in GCS. With the local backend they are
+/// kept on disk when enabled, so GET /api/documents/:id/original and
+/// /plaintext can open a document at a citation. Page thumbnails are stored
+/// in the same place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OriginalsConfig {
+    /// Keep uploaded files and their plain text with the local backend
+    /// (default: false)
+    #[serde(default)]
+    pub keep: bool,
+    /// Directory for originals, plain text and thumbnails with the local
+    /// backend (default: "documents" next to the registry)
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+}
+
+/// Speech-to-text backend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionBackend {
+    /// OpenAI-compatible `/v1/audio/transcriptions` endpoint (OpenAI,
+    /// whisper.cpp server, faster-whisper-server)
+    #[default]
+    Api,
+    /// Local whisper.cpp command line (`whisper-cli`)
+    WhisperCpp,
+}
+
+/// Audio/video transcription
+///
+/// When enabled, mp3/wav/mp4 and similar recordings are transcribed with
+/// Whisper and indexed as timestamped chunks. Video and whisper.cpp input is
+/// converted with ffmpeg first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionConfig {
+    /// Transcribe recordings at ingestion (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Transcription backend (default: api)
+    #[serde(default)]
+    pub backend: TranscriptionBackend,
+    /// Transcription endpoint for the api backend
+    #[serde(default = "default_transcription_api_url")]
+    pub api_url: String,
+    /// Bearer token for the api backend
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Model name for the api backend (default: whisper-1)
+    #[serde(default = "default_transcription_model")]
+    pub model: String,
+    /// whisper.cpp binary (default: whisper-cli)
+    #[serde(default = "default_whisper_cpp_binary")]
+    pub whisper_cpp_binary: String,
+    /// whisper.cpp ggml model file
+    #[serde(default = "default_whisper_cpp_model")]
+    pub whisper_cpp_model: PathBuf,
+    /// Spoken language hint, ISO 639-1 (default: auto-detect)
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Timeout per recording in seconds (default: 3600)
+    #[serde(default = "default_transcription_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_transcription_api_url() -> String { "http://localhost:8080/v1/audio/transcriptions".to_string() }
+fn default_transcription_model() -> String { "whisper-1".to_string() }
+fn default_whisper_cpp_binary() -> String { "whisper-cli".to_string() }
+fn default_whisper_cpp_model() -> PathBuf { PathBuf::from("models/ggml-base.bin") }
+fn default_transcription_timeout_secs() -> u64 { 3600 }
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: TranscriptionBackend::default(),
+            api_url: default_transcription_api_url(),
+            api_key: None,
+            model: default_transcription_model(),
+            whisper_cpp_binary: default_whisper_cpp_binary(),
+            whisper_cpp_model: default_whisper_cpp_model(),
+            language: None,
+            timeout_secs: default_transcription_timeout_secs(),
+        }
+    }
+}
+
+/// Archive extraction
+///
+/// Uploaded .zip, .tar and .tar.gz files are extracted into a temporary
+/// directory and each contained file is ingested as its own document. The
+/// limits guard against zip bombs; an archive exceeding one is rejected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// Expand archives at ingestion (default: true)
+    #[serde(default = "default_archive_enabled")]
+    pub enabled: bool,
+    /// Maximum files per archive, nested archives included (default: 1000)
+    #[serde(default = "default_archive_max_entries")]
+    pub max_entries: usize,
+    /// Maximum size of one extracted file (default: 100MB)
+    #[serde(default = "default_archive_max_entry_size")]
+    pub max_entry_size: u64,
+    /// Maximum total extracted size (default: 1GB)
+    #[serde(default = "default_archive_max_total_size")]
+    pub max_total_size: u64,
+    /// Maximum ratio of extracted to compressed size (default: 100)
+    #[serde(default = "default_archive_max_compression_ratio")]
+    pub max_compression_ratio: u64,
+    /// How deep archives inside archives are expanded (default: 2)
+    #[serde(default = "default_archive_max_depth")]
+    pub max_depth: usize,
+}
+
+fn default_archive_enabled() -> bool { true }
+fn default_archive_max_entries() -> usize { 1000 }
+fn default_archive_max_entry_size() -> u64 { 100 * 1024 * 1024 }
+fn default_archive_max_total_size() -> u64 { 1024 * 1024 * 1024 }
+fn default_archive_max_compression_ratio() -> u64 { 100 }
+fn default_archive_max_depth() -> usize { 2 }
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_archive_enabled(),
+            max_entries: default_archive_max_entries(),
+            max_entry_size: default_archive_max_entry_size(),
+            max_total_size: default_archive_max_total_size(),
+            max_compression_ratio: default_archive_max_compression_ratio(),
+            max_depth: default_archive_max_depth(),
+        }
+    }
+}
+
+/// Document version history
+///
+/// When a modified file replaces a document, the old document and its chunk
+/// text are kept as a superseded version: listed, diffed and queryable, but
+/// not in the vector index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersioningConfig {
+    /// Keep replaced versions (default: true)
+    #[serde(default = "default_versioning_enabled")]
+    pub enabled: bool,
+    /// Superseded versions kept per file, oldest dropped first (default: 10)
+    #[serde(default = "default_versioning_max_versions")]
+    pub max_versions: usize,
+}
+
+fn default_versioning_enabled() -> bool { true }
+fn default_versioning_max_versions() -> usize { 10 }
+
+impl Default for VersioningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_versioning_enabled(),
+            max_versions: default_versioning_max_versions(),
+        }
+    }
+}
+
+/// Snapshots of the SQLite registry
+///
+/// Taken with SQLite's backup API (POST /api/admin/snapshots or every
+/// `interval_secs`) and restored with the CLI's `restore-snapshot` while the
+/// server is stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotsConfig {
+    /// Directory for snapshots (default: "snapshots" next to the registry)
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// Take a snapshot every this many seconds (default: 0 = only on request)
+    #[serde(default)]
+    pub interval_secs: u64,
+    /// Snapshots kept, oldest deleted first (default: 7)
+    #[serde(default = "default_snapshots_keep")]
+    pub keep: usize,
+    /// Also copy each snapshot to the document store (GCS) (default: false)
+    #[serde(default)]
+    pub upload: bool,
+}
+
+fn default_snapshots_keep() -> usize { 7 }
+
+impl Default for SnapshotsConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            interval_secs: 0,
+            keep: default_snapshots_keep(),
+            upload: false,
+        }
+    }
+}
+
+/// Kind of personal data found by pattern matching
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    /// US social security number
+    Ssn,
+    Phone,
+    /// Card number passing the Luhn check
+    CreditCard,
+}
+
+impl PiiKind {
+    /// Label used in masks and finding counts
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Ssn => "SSN",
+            Self::Phone => "PHONE",
+            Self::CreditCard => "CREDIT_CARD",
+        }
+    }
+}
+
+/// What is detected and masked for a collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionPolicy {
+    /// Pattern-matched kinds to detect (default: all)
+    #[serde(default = "default_pii_kinds")]
+    pub detect: Vec<PiiKind>,
+    /// Entity types to detect with the NER service (e.g. "PERSON"; requires `ner_url`)
+    #[serde(default)]
+    pub entities: Vec<String>,
+    /// Mask findings in stored chunks, so they are never embedded or shown (default: false)
+    #[serde(default)]
+    pub mask_chunks: bool,
+    /// Mask findings in generated answers (default: false)
+    #[serde(default)]
+    pub mask_answers: bool,
+}
+
+fn default_pii_kinds() -> Vec<PiiKind> {
+    vec![PiiKind::Email, PiiKind::Ssn, PiiKind::Phone, PiiKind::CreditCard]
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            detect: default_pii_kinds(),
+            entities: Vec::new(),
+            mask_chunks: false,
+            mask_answers: false,
+        }
+    }
+}
+
+/// How keywords and entities are extracted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeywordExtractorKind {
+    /// RAKE keywords and capitalized-phrase entities, computed locally
+    #[default]
+    Rake,
+    /// Ask the generation model (falls back to RAKE when the call fails)
+    Llm,
+}
+
+/// Keyword and entity extraction
+///
+/// When enabled, each chunk's keywords and named entities are stored in its
+/// metadata and in a full-text index. Queries can then be restricted to
+/// chunks mentioning given entities, and chunks whose keywords match the
+/// question are ranked higher. The most frequent entities of a document are
+/// recorded in its `entities` metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeywordsConfig {
+    /// Extract keywords and entities at ingestion (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub extractor: KeywordExtractorKind,
+    /// Keywords kept per chunk (default: 10)
+    #[serde(default = "default_max_keywords")]
+    pub max_keywords: usize,
+    /// Entities kept per chunk (default: 10)
+    #[serde(default = "default_max_entities")]
+    pub max_entities: usize,
+    /// Entities recorded on the document (default: 25)
+    #[serde(default = "default_max_document_entities")]
+    pub max_document_entities: usize,
+    /// Similarity added to retrieved chunks whose keywords best match the
+    /// question, scaled by match strength; 0 disables (default: 0.05)
+    #[serde(default = "default_keyword_boost")]
+    pub boost: f32,
+}
+
+fn default_max_keywords() -> usize { 10 }
+fn default_max_entities() -> usize { 10 }
+fn default_max_document_entities() -> usize { 25 }
+fn default_keyword_boost() -> f32 { 0.05 }
+
+impl Default for KeywordsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            extractor: KeywordExtractorKind::default(),
+            max_keywords: default_max_keywords(),
+            max_entities: default_max_entities(),
+            max_document_entities: default_max_document_entities(),
+            boost: default_keyword_boost(),
+        }
+    }
+}
+
+/// How knowledge graph triples are extracted from chunks
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphExtractorKind {
+    /// Ask the generation model for (subject, relation, object) triples
+    #[default]
+    Llm,
+    /// Link capitalized-phrase entities of the same chunk (`mentioned_with`)
+    Cooccurrence,
+}
+
+/// Knowledge graph over ingested documents
+///
+/// When enabled, a background task extracts (subject, relation, object)
+/// triples from chunks that haven't been processed yet and stores them in the
+/// `graph_triples` table. The graph can be explored through /api/graph/query;
+/// with `augment_retrieval`, chunks linked to entities named in a question are
+/// added to the retrieved context so multi-hop questions can be answered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphConfig {
+    /// Build the graph and use it at query time (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub extractor: GraphExtractorKind,
+    /// Triples kept per chunk (default: 15)
+    #[serde(default = "default_max_triples_per_chunk")]
+    pub max_triples_per_chunk: usize,
+    /// Seconds between runs over new chunks; 0 builds only on request (default: 300)
+    #[serde(default = "default_graph_build_interval_secs")]
+    pub build_interval_secs: u64,
+    /// Add chunks linked to entities in the question to retrieval results (default: true)
+    #[serde(default = "default_graph_augment_retrieval")]
+    pub augment_retrieval: bool,
+    /// Relations followed from the question's entities (default: 2)
+    #[serde(default = "default_graph_max_hops")]
+    pub max_hops: usize,
+    /// Chunks added per query (default: 5)
+    #[serde(default = "default_max_graph_chunks")]
+    pub max_graph_chunks: usize,
+    /// Similarity given to added chunks, above the query threshold so they
+    /// reach the prompt unless reranking demotes them (default: 0.3)
+    #[serde(default = "default_graph_chunk_similarity")]
+    pub graph_chunk_similarity: f32,
+}
+
+fn default_max_triples_per_chunk() -> usize { 15 }
+fn default_graph_augment_retrieval() -> bool { true }
+fn default_graph_build_interval_secs() -> u64 { 300 }
+fn default_graph_max_hops() -> usize { 2 }
+fn default_max_graph_chunks() -> usize { 5 }
+fn default_graph_chunk_similarity() -> f32 { 0.3 }
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            extractor: GraphExtractorKind::default(),
+            max_triples_per_chunk: default_max_triples_per_chunk(),
+            build_interval_secs: default_graph_build_interval_secs(),
+            augment_retrieval: default_graph_augment_retrieval(),
+            max_hops: default_graph_max_hops(),
+            max_graph_chunks: default_max_graph_chunks(),
+            graph_chunk_similarity: default_graph_chunk_similarity(),
+        }
+    }
+}
+
+/// Synthetic question-answer pairs
+///
+/// When enabled, the LLM writes questions that chunks answer, with their
+/// answers, and the pairs are kept in the knowledge store with the question's
+/// embedding. With `augment_retrieval`, a query close to a generated question
+/// retrieves the chunk it came from; the pairs can also be exported from
+/// /api/qa-pairs as the seed of an evaluation set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaGenerationConfig {
+    /// Generate pairs and use them at query time (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Pairs asked for per chunk (default: 3)
+    #[serde(default = "default_qa_pairs_per_chunk")]
+    pub pairs_per_chunk: usize,
+    /// Shorter chunks are skipped (default: 200 characters)
+    #[serde(default = "default_qa_min_chunk_chars")]
+    pub min_chunk_chars: usize,
+    /// Seconds between runs over new chunks; 0 generates only on request (default: 0)
+    #[serde(default)]
+    pub interval_secs: u64,
+    /// Retrieve the chunks of generated questions matching the query (default: true)
+    #[serde(default = "default_qa_augment_retrieval")]
+    pub augment_retrieval: bool,
+    /// Lowest question similarity that retrieves a chunk (default: 0.8)
+    #[serde(default = "default_qa_min_similarity")]
+    pub min_similarity: f32,
+    /// Chunks added per query (default: 3)
+    #[serde(default = "default_qa_max_chunks")]
+    pub max_chunks: usize,
+}
+
+fn default_qa_pairs_per_chunk() -> usize { 3 }
+fn default_qa_min_chunk_chars() -> usize { 200 }
+fn default_qa_augment_retrieval() -> bool { true }
+fn default_qa_min_similarity() -> f32 { 0.8 }
+fn default_qa_max_chunks() -> usize { 3 }
+
+impl Default for QaGenerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pairs_per_chunk: default_qa_pairs_per_chunk(),
+            min_chunk_chars: default_qa_min_chunk_chars(),
+            interval_secs: 0,
+            augment_retrieval: default_qa_augment_retrieval(),
+            min_similarity: default_qa_min_similarity(),
+            max_chunks: default_qa_max_chunks(),
+        }
+    }
+}
+
+/// Prefix of model names served by the local Ollama server on any backend
+pub const OLLAMA_MODEL_PREFIX: &str = "ollama:";
+
+/// Generation model selection and fallback
+///
+/// Model names are models of the configured backend; `ollama:<name>` names a
+/// model of the local Ollama server, also on the GCP backend.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelsConfig {
+    /// Models queries may select with `model`, besides the default model and
+    /// those of experiment variants, budgets and fallback chains
+    #[serde(default)]
+    pub available: Vec<String>,
+    /// Models tried in order when the key model errors or times out (the
+    /// default model is keyed by its name)
+    #[serde(default)]
+    pub fallbacks: std::collections::HashMap<String, Vec<String>>,
+    /// Seconds before a generation attempt counts as failed (default: 0, the
+    /// provider's own timeout)
+    #[serde(default)]
+    pub attempt_timeout_secs: u64,
+}
+
+impl ModelsConfig {
+    /// Every model named here
+    pub fn models(&self) -> Vec<&str> {
+        let mut models: Vec<&str> = self
+            .available
+            .iter()
+            .chain(self.fallbacks.keys())
+            .chain(self.fallbacks.values().flatten())
+            .map(String::as_str)
+            .collect();
+        models.sort_unstable();
+        models.dedup();
+        models
+    }
+}
+
+/// Token usage and cost tracking
+///
+/// When enabled, every embedding and generation call is counted in tokens
+/// (estimated with the `context` tokenizer, providers don't all report usage)
+/// and priced with `pricing`. Totals per day, model, collection and caller
+/// are kept in SQLite and reported by GET /api/usage. Budgets cap the cost
+/// of queries per day or month; once spent, queries are rejected or answered
+/// with a cheaper model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageConfig {
+    /// Track usage (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Prices per model name, merged over built-in list prices of common
+    /// Gemini, Vertex AI and OpenAI models; unlisted models (Ollama, ONNX) are free
+    #[serde(default)]
+    pub pricing: std::collections::HashMap<String, ModelPricing>,
+    /// Cost limits
+    #[serde(default)]
+    pub budgets: Vec<BudgetConfig>,
+    /// Seconds between writes of buffered counts to the database (default: 30)
+    #[serde(default = "default_usage_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+fn default_usage_flush_interval_secs() -> u64 { 30 }
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pricing: std::collections::HashMap::new(),
+            budgets: Vec::new(),
+            flush_interval_secs: default_usage_flush_interval_secs(),
+        }
+    }
+}
+
+impl UsageConfig {
+    /// Generation models budgets downgrade to
+    pub fn downgrade_models(&self) -> Vec<&str> {
+        self.budgets.iter().filter_map(|b| b.downgrade_model.as_deref()).collect()
+    }
+}
+
+/// Rate limit of one provider; unset limits are not enforced
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProviderRateLimit {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelPricing {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+    /// Input read from a context cache (default: a quarter of the input price)
+    #[serde(default)]
+    pub cached_input_per_million: Option<f64>,
+    /// Context cache storage, per million tokens and hour
+    #[serde(default)]
+    pub cache_storage_per_million_hour: Option<f64>,
+}
+
+impl ModelPricing {
+    /// Price of input read from a context cache
+    pub fn cached_input(&self) -> f64 {
+        self.cached_input_per_million.unwrap_or(self.input_per_million / 4.0)
+    }
+}
+
+/// What a budget limits
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// All usage together
+    #[default]
+    Global,
+    /// Usage of queries on a collection
+    Collection,
+    /// Usage of a caller (the principal of its API key)
+    Principal,
+}
+
+/// What happens to queries once a budget is spent
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Refuse the query
+    #[default]
+    Reject,
+    /// Answer with `downgrade_model`
+    Downgrade,
+}
+
+/// Cost limit for a scope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// What the budget limits (default: global)
+    #[serde(default)]
+    pub scope: BudgetScope,
+    /// Collection name or principal id; without one, every collection or
+    /// principal gets a budget of its own
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Largest cost per UTC day in USD
+    #[serde(default)]
+    pub daily_limit_usd: Option<f64>,
+    /// Largest cost per calendar month (UTC) in USD
+    #[serde(default)]
+    pub monthly_limit_usd: Option<f64>,
+    /// Reject or downgrade queries once spent (default: reject)
+    #[serde(default)]
+    pub action: BudgetAction,
+    /// Generation model used by `downgrade` (same backend as the default LLM)
+    #[serde(default)]
+    pub downgrade_model: Option<String>,
+}
+
+/// PII detection and redaction
+///
+/// When enabled, chunks are scanned at ingestion and the number of findings
+/// per kind is recorded in the document's `pii` metadata. Policies can mask
+/// findings (`[EMAIL]`, `[SSN]`, ...) in stored chunks and answers, with a
+/// default policy and overrides per collection. Names and other entities are
+/// found with an optional NER service speaking the Presidio analyzer API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    /// Scan ingested text for PII (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Policy for documents without a configured collection
+    #[serde(default)]
+    pub default: RedactionPolicy,
+    /// Per-collection policies keyed by collection name
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, RedactionPolicy>,
+    /// NER service endpoint (Presidio `/analyze`), used for policy `entities`
+    #[serde(default)]
+    pub ner_url: Option<String>,
+    /// Minimum NER confidence score (default: 0.6)
+    #[serde(default = "default_ner_min_score")]
+    pub ner_min_score: f32,
+    /// NER request timeout in seconds (default: 30)
+    #[serde(default = "default_ner_timeout_secs")]
+    pub ner_timeout_secs: u64,
+}
+
+/// Passwords tried on encrypted PDFs
+///
+/// A password given for the file in the ingest request is tried first, then
+/// those of the document's collection, then the defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PdfPasswordsConfig {
+    /// Passwords tried for every collection
+    #[serde(default)]
+    pub default: Vec<String>,
+    /// Passwords per collection name
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, Vec<String>>,
+}
+
+/// Malware scanning of uploads
+///
+/// Files are scanned by clamd when `clamd` is set, otherwise by `command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AntivirusConfig {
+    /// Scan every file before it is parsed (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// clamd address: `host:port`, or the path of its Unix socket
+    #[serde(default)]
+    pub clamd: Option<String>,
+    /// Scanner program and arguments, run with the file's path appended;
+    /// exit code 0 means clean and 1 infected (e.g. `["clamdscan", "--fdpass", "--no-summary"]`)
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Time limit per scan in seconds (default: 60)
+    #[serde(default = "default_scan_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Where infected files are kept (default: `quarantine` in the vector
+    /// store directory)
+    #[serde(default)]
+    pub quarantine_dir: Option<PathBuf>,
+    /// Process files unscanned when the scanner fails instead of failing them
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+fn default_scan_timeout_secs() -> u64 { 60 }
+
+impl Default for AntivirusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            clamd: None,
+            command: Vec::new(),
+            timeout_secs: default_scan_timeout_secs(),
+            quarantine_dir: None,
+            fail_open: false,
+        }
+    }
+}
+
+fn default_ner_min_score() -> f32 { 0.6 }
+fn default_ner_timeout_secs() -> u64 { 30 }
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default: RedactionPolicy::default(),
+            collections: std::collections::HashMap::new(),
+            ner_url: None,
+            ner_min_score: default_ner_min_score(),
+            ner_timeout_secs: default_ner_timeout_secs(),
+        }
+    }
+}
+
+impl RedactionConfig {
+    /// Policy for a collection (the default policy if it has none)
+    pub fn policy(&self, collection: Option<&str>) -> &RedactionPolicy {
+        collection
+            .and_then(|name| self.collections.get(name))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// API authentication
+///
+/// When enabled, every `/api` request must identify a principal, either with
+/// an API key (`Authorization: Bearer <key>` or `X-API-Key`) or, behind a
+/// trusted proxy, with the `X-Forwarded-User` and `X-Forwarded-Groups`
+/// headers. Documents ingested with an access list are only visible to
+/// principals whose id or a role is on it; documents without one are visible
+/// to everyone. Administrative routes (`/api/admin`, deleting and editing
+/// documents, path ingestion, file record cleanup, graph and QA pair builds,
+/// prompt changes, webhooks) additionally require `admin_role`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Require authentication on `/api` routes (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Accepted API keys and the principal each one identifies
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Accept the principal from proxy headers (default: false)
+    #[serde(default)]
+    pub trust_proxy_headers: bool,
+    /// Proxies allowed to send those headers, as CIDRs or single addresses
+    ///
+    /// Proxy headers from any other peer are ignored, so clients reaching the
+    /// server directly can't claim a user. Empty means no proxy is trusted.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// Role that can see every document and use the administrative routes (default: "admin")
+    #[serde(default = "default_admin_role")]
+    pub admin_role: String,
+}
+
+/// An API key and the principal it authenticates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    /// Principal id (user or service name)
+    pub principal: String,
+    /// Roles and groups of the principal
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+fn default_admin_role() -> String { "admin".to_string() }
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_keys: Vec::new(),
+            trust_proxy_headers: false,
+            trusted_proxies: Vec::new(),
+            admin_role: default_admin_role(),
+        }
+    }
+}
+
+/// Prompt-injection guard
+///
+/// Retrieved chunks are passed to the LLM wrapped in document delimiters,
+/// with instruction-like text ("ignore previous instructions", fake system
+/// turns) removed. Queries matching the same patterns are rejected. Both are
+/// recorded in the guard audit log (GET /api/admin/guard/events).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardConfig {
+    /// Enable the guard (default: true)
+    #[serde(default = "default_guard_enabled")]
+    pub enabled: bool,
+    /// Reject queries that look like injections instead of only logging them (default: true)
+    #[serde(default = "default_guard_block_queries")]
+    pub block_queries: bool,
+    /// Additional case-insensitive regexes treated as injections
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+fn default_guard_enabled() -> bool { true }
+fn default_guard_block_queries() -> bool { true }
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_guard_enabled(),
+            block_queries: default_guard_block_queries(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// A/B experiments on query configuration
+///
+/// Variants override part of the query setup. A query names its variant
+/// with `variant`, or is assigned one at random by weight (stable per
+/// authenticated caller) while experiments are enabled. The variant that
+/// served an answer is stored with the interaction, so feedback can be
+/// compared per variant (GET /api/experiments).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExperimentsConfig {
+    /// Assign variants to queries that don't name one (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Registered variants
+    #[serde(default)]
+    pub variants: Vec<VariantConfig>,
+}
+
+/// A named query configuration; unset fields keep the request's values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantConfig {
+    pub name: String,
+    /// Relative share of assigned queries (default: 1.0)
+    #[serde(default = "default_variant_weight")]
+    pub weight: f64,
+    /// Number of chunks to retrieve
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Minimum similarity threshold
+    #[serde(default)]
+    pub similarity_threshold: Option<f32>,
+    /// Rerank results
+    #[serde(default)]
+    pub rerank: Option<bool>,
+    /// Query expansion strategy
+    #[serde(default)]
+    pub retrieval_strategy: Option<RetrievalStrategy>,
+    /// Generation model, served by the configured backend
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Prompt replacing the built-in RAG prompt, with `{context}`,
+    /// `{sources}` and `{question}` placeholders
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+}
+
+fn default_variant_weight() -> f64 { 1.0 }
+
+/// Webhook notifications
+///
+/// Endpoints listed here (and ones registered with POST /api/webhooks) are
+/// sent a signed JSON POST on document, job, answer cache and report events.
+/// Failed deliveries are retried with exponential backoff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhooksConfig {
+    /// Endpoints notified in addition to the registered ones
+    #[serde(default)]
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// Delivery attempts per event before giving up (default: 5)
+    #[serde(default = "default_webhook_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one (default: 1000)
+    #[serde(default = "default_webhook_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Request timeout in seconds (default: 10)
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Hosts registered endpoints may use even though they resolve to a
+    /// private, loopback or link-local address (default: none)
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+/// A webhook receiver
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    /// HMAC-SHA256 key for the `X-Webhook-Signature` header (unsigned if absent)
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    /// Events to send (all events if empty)
+    #[serde(default)]
+    pub events: Vec<crate::server::webhooks::WebhookEventKind>,
+}
+
+/// Chat integrations
+///
+/// Each platform is enabled by its section. Requests are verified with the
+/// platform's signature instead of an API key; questions are answered as the
+/// principal of `api_key` (an `auth.api_keys` entry, required when auth is
+/// enabled), so its document access and usage budget apply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrationsConfig {
+    /// Public base URL of the server for citation links, e.g.
+    /// `"https://rag.example.com"` (links to GCS originals only if unset)
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// Slack Events API and slash commands
+    #[serde(default)]
+    pub slack: Option<SlackConfig>,
+    /// Microsoft Teams outgoing webhook
+    #[serde(default)]
+    pub teams: Option<TeamsConfig>,
+}
+
+/// Slack app settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackConfig {
+    /// Signing secret of the Slack app
+    pub signing_secret: String,
+    /// Bot token (`xoxb-...`) for posting answers to mentions and direct
+    /// messages; slash commands answer without it
+    #[serde(default)]
+    pub bot_token: Option<String>,
+    /// API key whose principal the questions are answered as
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Collection questions are answered from (default: all documents)
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+/// Teams outgoing webhook settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamsConfig {
+    /// Security token shown when the outgoing webhook was created (base64)
+    pub secret: String,
+    /// API key whose principal the questions are answered as
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Collection questions are answered from (default: all documents)
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Seconds to wait for an answer before replying that it will follow
+    /// (Teams drops replies after 5s; default: 4)
+    #[serde(default = "default_teams_reply_timeout_secs")]
+    pub reply_timeout_secs: u64,
+    /// Incoming webhook of the channel, where answers that took longer than
+    /// `reply_timeout_secs` are posted (they are dropped without one)
+    #[serde(default)]
+    pub incoming_webhook_url: Option<String>,
+}
+
+fn default_teams_reply_timeout_secs() -> u64 { 4 }
+
+/// Source connector
+///
+/// Connectors are synced by the processes that run workers: each pass lists
+/// the files changed since the cursor of the last one (kept in
+/// `connector_state`), downloads new and changed files and ingests them as
+/// `<name>/<path>`, so unchanged content is skipped and changed files replace
+/// their old chunks. Files removed at the source are deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectorConfig {
+    /// Unique name, also the filename prefix of its documents
+    pub name: String,
+    /// Source and its credentials, selected by `kind`
+    #[serde(flatten)]
+    pub source: ConnectorSource,
+    /// Seconds between sync passes (default: 900, 0 = on request only)
+    #[serde(default = "default_connector_interval_secs")]
+    pub interval_secs: u64,
+    /// Collection documents are ingested into
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Principal ids and roles allowed to read the documents (empty = everyone)
+    #[serde(default)]
+    pub acl: Vec<String>,
+    /// Delete documents whose files were removed at the source (default: true)
+    #[serde(default = "default_connector_delete_removed")]
+    pub delete_removed: bool,
+    /// Files larger than this are skipped (default: 100 MiB)
+    #[serde(default = "default_connector_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+fn default_connector_interval_secs() -> u64 { 900 }
+fn default_connector_delete_removed() -> bool { true }
+fn default_connector_max_file_bytes() -> u64 { 100 * 1024 * 1024 }
+
+/// Document service a connector syncs from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConnectorSource {
+    GoogleDrive(GoogleDriveConfig),
+    /// SharePoint document library or OneDrive, through Microsoft Graph
+    #[serde(rename = "sharepoint", alias = "onedrive")]
+    SharePoint(SharePointConfig),
+}
+
+impl ConnectorSource {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::GoogleDrive(_) => "google_drive",
+            Self::SharePoint(_) => "sharepoint",
+        }
+    }
+}
+
+/// Google Drive, read with an OAuth client's refresh token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleDriveConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Refresh token granted the `drive.readonly` scope
+    pub refresh_token: String,
+    /// Folder synced with its subfolders (default: every file the account can read)
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Shared drive to sync instead of the account's own files
+    #[serde(default)]
+    pub shared_drive_id: Option<String>,
+}
+
+/// SharePoint or OneDrive drive, read with an Entra ID app's client
+/// credentials (`Files.Read.All` or `Sites.Read.All` application permission)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharePointConfig {
+    pub tenant_id: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Drive id of the document library or OneDrive
+    /// (`GET /sites/{site-id}/drives` or `/users/{user}/drive`)
+    pub drive_id: String,
+    /// Folder synced with its subfolders, e.g. `"/Policies"` (default: the whole drive)
+    #[serde(default)]
+    pub folder: Option<String>,
 }
 
+/// Scheduled reports
+///
+/// Saved queries with a cron schedule are run by the processes that run
+/// workers; each run stores a report, notifies `report.generated` webhooks
+/// and e-mails the query's recipients through `smtp`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportsConfig {
+    /// Run saved queries on their schedules (default: true)
+    #[serde(default = "default_reports_scheduler")]
+    pub scheduler: bool,
+    /// Reports kept per saved query, oldest dropped first (default: 20)
+    #[serde(default = "default_reports_keep")]
+    pub keep: usize,
+    /// Mail server for e-mailed reports (needs the `email-reports` feature)
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+}
 
-/// Processing configuration
+/// SMTP relay
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessingConfig {
-    /// Timeout for processing a single file in seconds (default: 300 = 5 minutes)
-    /// This is the fallback timeout if tiered processing is disabled
-    pub file_timeout_secs: u64,
-    /// Number of parallel file workers
-    pub parallel_files: Option<usize>,
-    /// Number of parallel embeddings per file
-    pub parallel_embeddings: Option<usize>,
-    /// Tiered processing configuration (size-based routing)
+pub struct SmtpConfig {
+    pub host: String,
+    /// Port (default: 587)
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
     #[serde(default)]
-    pub tiered: TieredProcessingConfig,
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Sender address, e.g. `"Reports <rag@example.com>"`
+    pub from: String,
+    /// Upgrade the connection with STARTTLS (default: true)
+    #[serde(default = "default_smtp_starttls")]
+    pub starttls: bool,
 }
 
-impl Default for ProcessingConfig {
+fn default_reports_scheduler() -> bool { true }
+fn default_reports_keep() -> usize { 20 }
+fn default_smtp_port() -> u16 { 587 }
+fn default_smtp_starttls() -> bool { true }
+
+impl Default for ReportsConfig {
     fn default() -> Self {
         Self {
-            file_timeout_secs: 300, // 5 minutes
-            parallel_files: None,   // Auto-detect from CPU count
-            parallel_embeddings: None,
-            tiered: TieredProcessingConfig::default(),
+            scheduler: default_reports_scheduler(),
+            keep: default_reports_keep(),
+            smtp: None,
+        }
+    }
+}
+
+fn default_webhook_max_attempts() -> u32 { 5 }
+fn default_webhook_initial_backoff_ms() -> u64 { 1000 }
+fn default_webhook_timeout_secs() -> u64 { 10 }
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            max_attempts: default_webhook_max_attempts(),
+            initial_backoff_ms: default_webhook_initial_backoff_ms(),
+            timeout_secs: default_webhook_timeout_secs(),
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+/// Prompt templates
+///
+/// Templates are TOML files (`<name>.toml` with `system` and `user` Jinja
+/// templates) in `dir`, managed with /api/prompts and reloaded when the
+/// directory changes. Queries use the template they name, else their
+/// collection's, else `default_template`, else the built-in prompts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptsConfig {
+    /// Template directory (default: `prompts` next to the vector storage)
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Template for queries that don't select one
+    #[serde(default)]
+    pub default_template: Option<String>,
+    /// Templates keyed by collection name
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, String>,
+    /// How often the directory is checked for changes, 0 to disable (default: 10)
+    #[serde(default = "default_prompts_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_prompts_reload_interval_secs() -> u64 { 10 }
+
+impl Default for PromptsConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            default_template: None,
+            collections: std::collections::HashMap::new(),
+            reload_interval_secs: default_prompts_reload_interval_secs(),
+        }
+    }
+}
+
+/// Suggested follow-up questions
+///
+/// When enabled, the LLM suggests questions the retrieved chunks can answer
+/// alongside each answer. The call runs while the answer is generated, so it
+/// adds tokens but little latency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowUpConfig {
+    /// Suggest follow-up questions (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Questions suggested per answer (default: 3)
+    #[serde(default = "default_follow_up_count")]
+    pub count: usize,
+}
+
+fn default_follow_up_count() -> usize { 3 }
+
+impl Default for FollowUpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count: default_follow_up_count(),
         }
     }
 }
 
+impl ExperimentsConfig {
+    /// Variant by name
+    pub fn variant(&self, name: &str) -> Option<&VariantConfig> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+
+    /// Generation models used by variants
+    pub fn models(&self) -> Vec<&str> {
+        let mut models: Vec<&str> = self.variants.iter().filter_map(|v| v.model.as_deref()).collect();
+        models.sort_unstable();
+        models.dedup();
+        models
+    }
+}
+
 /// Tiered processing configuration for size-based file routing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TieredProcessingConfig {
@@ -177,8 +2197,23 @@ pub struct ServerConfig {
     pub port: u16,
     /// Enable CORS
     pub enable_cors: bool,
+    /// Origins, methods and headers CORS allows
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// Security headers added to every response
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    /// Body size, timeout and concurrency limits
+    #[serde(default)]
+    pub limits: RequestLimitsConfig,
+    /// Dependency checks behind `/ready`
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
     /// Maximum upload size in bytes (default: 100MB)
     pub max_upload_size: usize,
+    /// Serve HTTPS instead of plain HTTP (requires the tls feature)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 impl Default for ServerConfig {
@@ -187,37 +2222,480 @@ impl Default for ServerConfig {
             host: "0.0.0.0".to_string(),
             port: 8080,
             enable_cors: true,
+            cors: CorsConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            limits: RequestLimitsConfig::default(),
+            readiness: ReadinessConfig::default(),
             max_upload_size: 100 * 1024 * 1024, // 100MB
+            tls: None,
+        }
+    }
+}
+
+/// Cross-origin requests the server allows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Allowed origins, e.g. `https://app.example.com` (default: none, only
+    /// same-origin requests); `*` allows any origin and can't be combined
+    /// with credentials
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Allowed methods (default: any)
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// Allowed request headers (default: any)
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// Allow cookies and `Authorization` on cross-origin requests
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: Vec::new(),
+            allowed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age_secs: None,
+        }
+    }
+}
+
+/// Security headers on every response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityHeadersConfig {
+    /// Send `X-Content-Type-Options`, `X-Frame-Options` and `Referrer-Policy`
+    #[serde(default = "default_security_headers")]
+    pub enabled: bool,
+    /// `Strict-Transport-Security` max-age, sent on HTTPS requests (those
+    /// served with `[server.tls]` or forwarded with `X-Forwarded-Proto: https`);
+    /// 0 disables it
+    #[serde(default = "default_hsts_max_age")]
+    pub hsts_max_age_secs: u64,
+}
+
+fn default_security_headers() -> bool { true }
+fn default_hsts_max_age() -> u64 { 365 * 24 * 60 * 60 }
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_security_headers(),
+            hsts_max_age_secs: default_hsts_max_age(),
+        }
+    }
+}
+
+/// Limits keeping the server responsive under load
+///
+/// Zero disables a limit. Shed requests get 429 with `Retry-After`, timed
+/// out ones 504.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLimitsConfig {
+    /// Largest request body outside the upload routes (default: 2MB)
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Time a query may take to answer
+    #[serde(default = "default_query_timeout")]
+    pub query_timeout_secs: u64,
+    /// Time an upload may take (synchronous ingestion included)
+    #[serde(default = "default_ingest_timeout")]
+    pub ingest_timeout_secs: u64,
+    /// Time any other API request may take
+    #[serde(default)]
+    pub default_timeout_secs: u64,
+    /// Requests handled at once; more are refused
+    #[serde(default)]
+    pub max_in_flight: usize,
+    /// Queries answered at once; more are refused
+    #[serde(default)]
+    pub max_concurrent_queries: usize,
+    /// Refuse queries while the LLM's rate limit would hold them back longer than this
+    #[serde(default)]
+    pub max_llm_wait_secs: u64,
+    /// Refuse uploads while this many jobs are pending or processing
+    #[serde(default)]
+    pub max_queued_jobs: usize,
+    /// `Retry-After` sent with refused requests
+    #[serde(default = "default_retry_after")]
+    pub retry_after_secs: u64,
+}
+
+fn default_max_body_size() -> usize { 2 * 1024 * 1024 }
+fn default_query_timeout() -> u64 { 120 }
+fn default_ingest_timeout() -> u64 { 600 }
+fn default_retry_after() -> u64 { 5 }
+
+impl Default for RequestLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_size: default_max_body_size(),
+            query_timeout_secs: default_query_timeout(),
+            ingest_timeout_secs: default_ingest_timeout(),
+            default_timeout_secs: 0,
+            max_in_flight: 0,
+            max_concurrent_queries: 0,
+            max_llm_wait_secs: 0,
+            max_queued_jobs: 0,
+            retry_after_secs: default_retry_after(),
+        }
+    }
+}
+
+/// Dependency checks behind `/ready`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessConfig {
+    /// How long a check result is reused
+    #[serde(default = "default_readiness_cache")]
+    pub cache_secs: u64,
+    /// Time each component has to answer
+    #[serde(default = "default_readiness_timeout")]
+    pub timeout_secs: u64,
+    /// Components reported but not required to be ready (`embeddings`,
+    /// `llm`, `vector_store`, `database`, `document_store`)
+    #[serde(default)]
+    pub optional_components: Vec<String>,
+    /// Warm-up run before the server reports ready
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+}
+
+fn default_readiness_cache() -> u64 { 10 }
+fn default_readiness_timeout() -> u64 { 3 }
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            cache_secs: default_readiness_cache(),
+            timeout_secs: default_readiness_timeout(),
+            optional_components: Vec::new(),
+            warmup: WarmupConfig::default(),
+        }
+    }
+}
+
+/// Startup warm-up
+///
+/// Until it finishes `/ready` answers 503, so the first queries routed to the
+/// server don't pay for cold database pages, an unloaded vector index or
+/// providers that have yet to open connections and load models.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    /// Warm up before reporting ready (default: true)
+    #[serde(default = "default_warmup_enabled")]
+    pub enabled: bool,
+    /// Text embedded and searched for by the canary query
+    #[serde(default = "default_warmup_query")]
+    pub canary_query: String,
+    /// Also generate a short completion with the LLM (default: true)
+    #[serde(default = "default_warmup_generate")]
+    pub generate: bool,
+    /// Longest the warm-up may take before the server reports ready anyway
+    #[serde(default = "default_warmup_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_warmup_enabled() -> bool { true }
+fn default_warmup_query() -> String { "warm-up query".to_string() }
+fn default_warmup_generate() -> bool { true }
+fn default_warmup_timeout() -> u64 { 300 }
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_warmup_enabled(),
+            canary_query: default_warmup_query(),
+            generate: default_warmup_generate(),
+            timeout_secs: default_warmup_timeout(),
         }
     }
 }
 
+/// TLS termination in the server itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, server certificate first
+    pub cert_file: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_file: PathBuf,
+    /// PEM CA certificates client certificates must chain to; when set,
+    /// clients without a valid certificate are refused (mutual TLS)
+    #[serde(default)]
+    pub client_ca_file: Option<PathBuf>,
+}
+
 /// Embedding configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingConfig {
-    /// Model to use (default: all-MiniLM-L6-v2)
+    /// Provider that embeds chunks and queries (default: the backend's)
+    #[serde(default)]
+    pub provider: EmbeddingProviderKind,
+    /// Model used by the ONNX provider (sentence-transformers name or HuggingFace repository)
     pub model: String,
     /// Embedding dimensions (384 for MiniLM, 768 for larger models)
     pub dimensions: usize,
+    /// What to do when the model's vectors don't have `dimensions` values,
+    /// checked with one embedding at startup (default: fail)
+    #[serde(default)]
+    pub dimension_check: DimensionCheck,
     /// Batch size for embedding generation
     pub batch_size: usize,
     /// Maximum sequence length
     pub max_length: usize,
     /// Cache directory for models
     pub cache_dir: PathBuf,
+    /// Handling of chunks longer than the embedding model's input limit
+    #[serde(default)]
+    pub long_input: LongInputConfig,
+    /// Seconds between runs of the background task that embeds chunks whose
+    /// embedding failed during ingestion (default: 300, 0 = only on request)
+    #[serde(default = "default_repair_interval_secs")]
+    pub repair_interval_secs: u64,
+    /// Embedding models for collections that don't use the default model
+    /// (keyed by collection name)
+    #[serde(default)]
+    pub collections: std::collections::HashMap<String, CollectionEmbeddingConfig>,
+    /// Start a reindex when a query targets chunks embedded with a model other
+    /// than the configured one; such queries are refused either way (default: false)
+    #[serde(default)]
+    pub auto_reindex: bool,
+    /// Cron expression (UTC) on which to reindex every document, e.g.
+    /// `"0 3 * * 0"` for Sundays at 03:00 (default: only on request)
+    #[serde(default)]
+    pub reindex_schedule: Option<String>,
+    /// In-process ONNX Runtime settings (with `provider = "onnx"`)
+    #[serde(default)]
+    pub onnx: OnnxConfig,
+    /// Coalescing of concurrent embed requests into batches
+    #[serde(default)]
+    pub batching: EmbeddingBatchingConfig,
+}
+
+/// Embedding request batching
+///
+/// Chunks of concurrently processed files are embedded one request each.
+/// With batching enabled they are queued and sent to the provider together,
+/// which keeps GPU-backed providers busy with few large calls instead of many
+/// small ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingBatchingConfig {
+    /// Queue embed requests and send them in batches (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Largest batch sent to the provider (default: 64)
+    #[serde(default = "default_batching_max_batch_size")]
+    pub max_batch_size: usize,
+    /// Longest a request waits for a batch to fill, in milliseconds (default: 10)
+    #[serde(default = "default_batching_max_latency_ms")]
+    pub max_latency_ms: u64,
+    /// Batches sent to the provider at once (default: 2)
+    #[serde(default = "default_batching_max_concurrent_batches")]
+    pub max_concurrent_batches: usize,
+}
+
+fn default_batching_max_batch_size() -> usize { 64 }
+fn default_batching_max_latency_ms() -> u64 { 10 }
+fn default_batching_max_concurrent_batches() -> usize { 2 }
+
+impl Default for EmbeddingBatchingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_batch_size: default_batching_max_batch_size(),
+            max_latency_ms: default_batching_max_latency_ms(),
+            max_concurrent_batches: default_batching_max_concurrent_batches(),
+        }
+    }
+}
+
+fn default_repair_interval_secs() -> u64 { 300 }
+
+/// Embedding provider selection
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingProviderKind {
+    /// Ollama on the local backend, Vertex AI on GCP
+    #[default]
+    Backend,
+    /// ONNX model run in-process (`embeddings.model` and `embeddings.onnx`)
+    Onnx,
+}
+
+/// Handling of an embedding model whose vectors don't have the configured size
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DimensionCheck {
+    /// Refuse to start
+    #[default]
+    Fail,
+    /// Use the dimensions the model produces
+    Adopt,
+    /// Don't probe the model
+    Off,
+}
+
+/// ONNX Runtime execution provider
+///
+/// GPU providers need the matching `onnx-*` cargo feature; when one can't be
+/// registered, inference falls back to the CPU with a warning.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnnxExecutionProvider {
+    #[default]
+    Cpu,
+    /// NVIDIA GPUs (feature `onnx-cuda`)
+    Cuda,
+    /// Apple Neural Engine and GPUs (feature `onnx-coreml`)
+    CoreMl,
+    /// DirectX 12 GPUs on Windows (feature `onnx-directml`)
+    DirectMl,
+}
+
+/// How token embeddings are pooled into one vector
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnnxPooling {
+    /// Attention-masked mean of all tokens (sentence-transformers models)
+    #[default]
+    Mean,
+    /// First token ([CLS], BGE and similar models)
+    Cls,
+}
+
+/// ONNX embedding model configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnnxConfig {
+    /// Local model file (default: downloaded from HuggingFace into `cache_dir`)
+    #[serde(default)]
+    pub model_path: Option<PathBuf>,
+    /// Local tokenizer.json (default: next to `model_path`, or downloaded)
+    #[serde(default)]
+    pub tokenizer_path: Option<PathBuf>,
+    /// HuggingFace repository to download from (default:
+    /// `sentence-transformers/<model>`, or `model` itself if it contains a `/`)
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Path of the model file in the repository (default: onnx/model.onnx)
+    #[serde(default = "default_onnx_model_file")]
+    pub model_file: String,
+    /// Execution provider (default: cpu)
+    #[serde(default)]
+    pub execution_provider: OnnxExecutionProvider,
+    /// GPU to run on (default: 0)
+    #[serde(default)]
+    pub device_id: i32,
+    /// Threads per inference (default: 4)
+    #[serde(default = "default_onnx_intra_threads")]
+    pub intra_threads: usize,
+    /// Token pooling (default: mean)
+    #[serde(default)]
+    pub pooling: OnnxPooling,
+}
+
+fn default_onnx_model_file() -> String { "onnx/model.onnx".to_string() }
+fn default_onnx_intra_threads() -> usize { 4 }
+
+impl Default for OnnxConfig {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            tokenizer_path: None,
+            repo: None,
+            model_file: default_onnx_model_file(),
+            execution_provider: OnnxExecutionProvider::Cpu,
+            device_id: 0,
+            intra_threads: default_onnx_intra_threads(),
+            pooling: OnnxPooling::Mean,
+        }
+    }
+}
+
+/// Embedding model of a collection
+///
+/// Vectors of different models can't be compared, so queries on a collection
+/// only search documents embedded with the same model. A model producing
+/// fewer dimensions than the index is zero-padded, which leaves similarities
+/// between its own vectors unchanged; larger models are rejected at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionEmbeddingConfig {
+    /// Model name (Ollama model on the local backend, Vertex AI model on GCP)
+    pub model: String,
+    /// Dimensions the model produces (default: `embeddings.dimensions`)
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+}
+
+/// How an over-length input's window embeddings are combined
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LongInputStrategy {
+    /// Embed only the first window (provider behaviour, tail is lost)
+    Truncate,
+    /// Embed every window and store the normalized mean
+    #[default]
+    Mean,
+    /// Store every window's vector, a chunk scores as its best window
+    /// (local backend; Vertex AI stores the mean)
+    MaxSim,
+}
+
+/// Splitting of chunks longer than the embedding model accepts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LongInputConfig {
+    /// Combination strategy (default: mean)
+    #[serde(default)]
+    pub strategy: LongInputStrategy,
+    /// Model input limit in tokens (default: 512)
+    #[serde(default = "default_long_input_max_tokens")]
+    pub max_tokens: usize,
+    /// Tokens shared by consecutive windows (default: 64)
+    #[serde(default = "default_long_input_overlap_tokens")]
+    pub overlap_tokens: usize,
+    /// Characters per token used to estimate token counts (default: 4.0)
+    #[serde(default = "default_chars_per_token")]
+    pub chars_per_token: f32,
+}
+
+fn default_long_input_max_tokens() -> usize { 512 }
+fn default_long_input_overlap_tokens() -> usize { 64 }
+fn default_chars_per_token() -> f32 { 4.0 }
+
+impl Default for LongInputConfig {
+    fn default() -> Self {
+        Self {
+            strategy: LongInputStrategy::Mean,
+            max_tokens: default_long_input_max_tokens(),
+            overlap_tokens: default_long_input_overlap_tokens(),
+            chars_per_token: default_chars_per_token(),
+        }
+    }
 }
 
 impl Default for EmbeddingConfig {
     fn default() -> Self {
         Self {
+            provider: EmbeddingProviderKind::Backend,
             model: "nomic-embed-text".to_string(),
             dimensions: 768,
+            dimension_check: DimensionCheck::default(),
             batch_size: 32,
             max_length: 256,
             cache_dir: dirs::cache_dir()
                 .unwrap_or_else(|| PathBuf::from("."))
                 .join("ruvector-rag")
                 .join("models"),
+            long_input: LongInputConfig::default(),
+            repair_interval_secs: default_repair_interval_secs(),
+            collections: std::collections::HashMap::new(),
+            auto_reindex: false,
+            reindex_schedule: None,
+            onnx: OnnxConfig::default(),
+            batching: EmbeddingBatchingConfig::default(),
         }
     }
 }
@@ -225,14 +2703,40 @@ impl Default for EmbeddingConfig {
 /// Text chunking configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkingConfig {
-    /// Target chunk size in characters
+    /// Target chunk size in `size_unit`
     pub chunk_size: usize,
-    /// Overlap between chunks in characters
+    /// Overlap between chunks in `size_unit`
     pub chunk_overlap: usize,
+    /// Unit of `chunk_size` and `chunk_overlap` (default: chars)
+    #[serde(default)]
+    pub size_unit: ChunkSizeUnit,
     /// Minimum chunk size (skip smaller chunks)
     pub min_chunk_size: usize,
     /// Respect sentence boundaries
     pub respect_sentences: bool,
+    /// Maximum size of a source code chunk in tokens (counted with the
+    /// `[context]` tokenizer); code is split on function and class
+    /// boundaries where the language is supported
+    #[serde(default = "default_code_chunk_tokens")]
+    pub code_chunk_tokens: usize,
+    /// Also embed CSV and spreadsheet rows as sentences ("Region is North,
+    /// Q1 is 1,200"), one extra chunk per group of rows
+    #[serde(default)]
+    pub row_descriptions: bool,
+}
+
+fn default_code_chunk_tokens() -> usize { 384 }
+
+/// How text chunk sizes are measured
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkSizeUnit {
+    /// Characters
+    #[default]
+    Chars,
+    /// Tokens of the embedding model's tokenizer, or of the `[context]`
+    /// tokenizer for models whose tokenizer isn't available locally
+    Tokens,
 }
 
 impl Default for ChunkingConfig {
@@ -240,8 +2744,11 @@ impl Default for ChunkingConfig {
         Self {
             chunk_size: 1024,      // Larger chunks = more context
             chunk_overlap: 200,    // More overlap = better continuity
+            size_unit: ChunkSizeUnit::default(),
             min_chunk_size: 100,
             respect_sentences: true,
+            code_chunk_tokens: default_code_chunk_tokens(),
+            row_descriptions: false,
         }
     }
 }
@@ -290,8 +2797,13 @@ pub struct VectorDbConfig {
     pub hnsw_ef_construction: usize,
     /// HNSW ef_search parameter
     pub hnsw_ef_search: usize,
+    /// Recently read chunks kept in memory by the chunk store (default: 10000)
+    #[serde(default = "default_chunk_cache_entries")]
+    pub chunk_cache_entries: usize,
 }
 
+fn default_chunk_cache_entries() -> usize { 10_000 }
+
 impl Default for VectorDbConfig {
     fn default() -> Self {
         // Use absolute path to avoid path traversal detection
@@ -305,10 +2817,26 @@ impl Default for VectorDbConfig {
             hnsw_m: 32,
             hnsw_ef_construction: 200,
             hnsw_ef_search: 100,
+            chunk_cache_entries: default_chunk_cache_entries(),
         }
     }
 }
 
+impl VectorDbConfig {
+    /// Directory holding the vector index and the SQLite registry
+    pub fn storage_dir(&self) -> PathBuf {
+        self.storage_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// SQLite database of file records, jobs, chunk text and the rest
+    pub fn registry_path(&self) -> PathBuf {
+        self.storage_dir().join("rag_registry.db")
+    }
+}
+
 /// Backend provider selection
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -348,12 +2876,26 @@ pub struct GcpConfig {
     pub vector_search_public_domain: Option<String>,
     /// Deployed index ID within the endpoint
     pub deployed_index_id: String,
+    /// Upsert requests of 100 datapoints sent at once (default: 4)
+    #[serde(default = "default_vector_search_upsert_concurrency")]
+    pub vector_search_upsert_concurrency: usize,
+    /// Seconds streamed changes are served from a local overlay until Vertex
+    /// confirms them (default: 300, 0 disables the overlay)
+    #[serde(default = "default_vector_search_consistency_secs")]
+    pub vector_search_consistency_secs: u64,
     /// Embedding model (default: "text-embedding-005")
     #[serde(default = "default_embedding_model")]
     pub embedding_model: String,
     /// Generation model (default: "gemini-2.5-pro")
     #[serde(default = "default_generation_model")]
     pub generation_model: String,
+    /// Request answers as JSON with an explicit citations array instead of
+    /// parsing citations from free text (default: true)
+    #[serde(default = "default_gemini_json_mode")]
+    pub gemini_json_mode: bool,
+    /// Gemini context caching of repeatedly queried contexts
+    #[serde(default)]
+    pub context_cache: GeminiCacheConfig,
     /// Document AI processor ID for PDF extraction (optional)
     /// e.g., "projects/my-project/locations/us/processors/abc123"
     /// If not set, Document AI fallback is disabled
@@ -362,16 +2904,114 @@ pub struct GcpConfig {
     /// Enable Document AI as fallback for failed PDF parsing (default: true if processor is set)
     #[serde(default = "default_use_document_ai")]
     pub use_document_ai_fallback: bool,
+    /// Batch processing of PDFs over the Document AI online limits
+    #[serde(default)]
+    pub document_ai_batch: DocumentAiBatchConfig,
+    /// Seconds between background incremental syncs of the file registry
+    /// from the bucket (0 = only on startup and on request)
+    #[serde(default = "default_gcs_sync_interval_secs")]
+    pub sync_interval_secs: u64,
+}
+
+/// Document AI batch processing
+///
+/// PDFs too large or long for an online request are staged in the GCS
+/// bucket and processed by a long-running batch operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentAiBatchConfig {
+    /// Fall back to batch processing (default: true)
+    #[serde(default = "default_document_ai_batch_enabled")]
+    pub enabled: bool,
+    /// Bucket prefix of staged input and output (default: "docai-batch/")
+    #[serde(default = "default_document_ai_staging_prefix")]
+    pub staging_prefix: String,
+    /// Seconds between checks of the operation (default: 10)
+    #[serde(default = "default_document_ai_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Seconds a batch operation may take before it is cancelled (default: 3600)
+    #[serde(default = "default_document_ai_batch_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_document_ai_batch_enabled() -> bool { true }
+fn default_document_ai_staging_prefix() -> String { "docai-batch/".to_string() }
+fn default_document_ai_poll_interval_secs() -> u64 { 10 }
+fn default_document_ai_batch_timeout_secs() -> u64 { 3600 }
+
+impl Default for DocumentAiBatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_document_ai_batch_enabled(),
+            staging_prefix: default_document_ai_staging_prefix(),
+            poll_interval_secs: default_document_ai_poll_interval_secs(),
+            timeout_secs: default_document_ai_batch_timeout_secs(),
+        }
+    }
 }
 
 fn default_embedding_model() -> String {
     "text-embedding-005".to_string()
 }
 
+fn default_vector_search_upsert_concurrency() -> usize {
+    4
+}
+
+fn default_vector_search_consistency_secs() -> u64 {
+    300
+}
+
 fn default_generation_model() -> String {
     "gemini-2.5-pro".to_string()
 }
 
+fn default_gemini_json_mode() -> bool {
+    true
+}
+
+fn default_gcs_sync_interval_secs() -> u64 {
+    0
+}
+
+/// Gemini context caching
+///
+/// Packed contexts of at least `min_tokens` are uploaded once as a Vertex AI
+/// cached content; further questions on the same context by the same model
+/// refer to it until it expires, and its input is billed at the cached price.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiCacheConfig {
+    /// Cache contexts (default: false)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds a cached context lives (default: 3600); storage is billed
+    /// for the whole TTL
+    #[serde(default = "default_gemini_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Smallest context cached, in estimated tokens (default: 4096, Vertex
+    /// refuses smaller caches)
+    #[serde(default = "default_gemini_cache_min_tokens")]
+    pub min_tokens: usize,
+    /// Caches tracked at once; the one expiring first is forgotten to make
+    /// room (default: 256)
+    #[serde(default = "default_gemini_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_gemini_cache_ttl_secs() -> u64 { 3600 }
+fn default_gemini_cache_min_tokens() -> usize { 4096 }
+fn default_gemini_cache_max_entries() -> usize { 256 }
+
+impl Default for GeminiCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_gemini_cache_ttl_secs(),
+            min_tokens: default_gemini_cache_min_tokens(),
+            max_entries: default_gemini_cache_max_entries(),
+        }
+    }
+}
+
 fn default_gcs_originals_prefix() -> String {
     "originals/".to_string()
 }
@@ -383,3 +3023,26 @@ fn default_gcs_plaintext_prefix() -> String {
 fn default_use_document_ai() -> bool {
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recency_boost_halves_per_half_life() {
+        let mut recency = RecencyConfig { boost: 0.2, half_life_days: 30.0, ..Default::default() };
+        recency.collections.insert("policies".to_string(), 90.0);
+
+        assert!((recency.boost_for(None, 0.0) - 0.2).abs() < 1e-6);
+        assert!((recency.boost_for(None, 30.0) - 0.1).abs() < 1e-6);
+        assert!((recency.boost_for(None, 60.0) - 0.05).abs() < 1e-6);
+        // Documents dated in the future get the full boost, not more
+        assert!((recency.boost_for(None, -5.0) - 0.2).abs() < 1e-6);
+
+        assert!((recency.boost_for(Some("policies"), 90.0) - 0.1).abs() < 1e-6);
+        assert!((recency.boost_for(Some("other"), 30.0) - 0.1).abs() < 1e-6);
+
+        recency.boost = 0.0;
+        assert_eq!(recency.boost_for(None, 0.0), 0.0);
+    }
+}
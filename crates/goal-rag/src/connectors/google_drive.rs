@@ -0,0 +1,354 @@
+//! Google Drive connector
+//!
+//! Uses the Drive v3 API with an OAuth client's refresh token. The first
+//! pass lists every file and takes a start page token; later passes read the
+//! changes since it. Paths are built from the parent folders, so files in a
+//! configured folder are synced with its subfolders. Google Docs, Sheets and
+//! Slides are exported as DOCX, XLSX and PPTX; other Google-native files
+//! (forms, drawings, shortcuts) are not synced. A renamed folder renames its
+//! files when they next change or on a full sync.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{client, Change, ChangeSet, Connector, RemoteFile, TokenCache};
+use crate::config::GoogleDriveConfig;
+use crate::error::Result;
+
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const API_URL: &str = "https://www.googleapis.com/drive/v3";
+const FOLDER_MIME: &str = "application/vnd.google-apps.folder";
+const FILE_FIELDS: &str = "id,name,mimeType,parents,md5Checksum,version,size,webViewLink,modifiedTime,trashed";
+const PAGE_SIZE: &str = "1000";
+
+/// A Drive file or folder
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveFile {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    mime_type: String,
+    #[serde(default)]
+    parents: Vec<String>,
+    #[serde(default)]
+    md5_checksum: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    size: Option<String>,
+    #[serde(default)]
+    web_view_link: Option<String>,
+    #[serde(default)]
+    modified_time: Option<String>,
+    #[serde(default)]
+    trashed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+    #[serde(default)]
+    files: Vec<DriveFile>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveChange {
+    file_id: String,
+    #[serde(default)]
+    removed: bool,
+    file: Option<DriveFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeList {
+    #[serde(default)]
+    changes: Vec<DriveChange>,
+    next_page_token: Option<String>,
+    new_start_page_token: Option<String>,
+}
+
+/// Format Google-native files are exported as, with its extension
+fn export_format(mime_type: &str) -> Option<(&'static str, &'static str)> {
+    match mime_type {
+        "application/vnd.google-apps.document" => {
+            Some(("application/vnd.openxmlformats-officedocument.wordprocessingml.document", "docx"))
+        }
+        "application/vnd.google-apps.spreadsheet" => {
+            Some(("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "xlsx"))
+        }
+        "application/vnd.google-apps.presentation" => {
+            Some(("application/vnd.openxmlformats-officedocument.presentationml.presentation", "pptx"))
+        }
+        _ => None,
+    }
+}
+
+/// Folders by id, for building paths
+#[derive(Debug, Default)]
+struct Folders(HashMap<String, (String, Option<String>)>);
+
+impl Folders {
+    fn from_files(folders: Vec<DriveFile>) -> Self {
+        Self(folders.into_iter().map(|f| (f.id, (f.name, f.parents.into_iter().next()))).collect())
+    }
+
+    /// Path of a file from the top folder, or from `root` when given (`None`
+    /// when the file is not under it)
+    fn path(&self, file: &DriveFile, root: Option<&str>) -> Option<String> {
+        let mut parts = vec![file.name.replace('/', "_")];
+        let mut parent = file.parents.first().cloned();
+        let mut depth = 0;
+        while let Some(id) = parent {
+            if root == Some(id.as_str()) {
+                return Some(join(parts));
+            }
+            let Some((name, next)) = self.0.get(&id) else { break };
+            parts.push(name.replace('/', "_"));
+            parent = next.clone();
+            depth += 1;
+            if depth > 64 {
+                break;
+            }
+        }
+        root.is_none().then(|| join(parts))
+    }
+}
+
+fn join(mut parts: Vec<String>) -> String {
+    parts.reverse();
+    format!("/{}", parts.join("/"))
+}
+
+/// Google Drive (or a shared drive)
+pub struct GoogleDrive {
+    config: GoogleDriveConfig,
+    token: TokenCache,
+}
+
+impl GoogleDrive {
+    pub fn new(config: GoogleDriveConfig) -> Self {
+        Self { config, token: TokenCache::new() }
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let form = [
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+            ("refresh_token", self.config.refresh_token.as_str()),
+            ("grant_type", "refresh_token"),
+        ];
+        self.token.get(TOKEN_URL, &form).await
+    }
+
+    /// Query parameters selecting the shared drive, if configured
+    fn drive_params(&self) -> Vec<(&'static str, String)> {
+        match &self.config.shared_drive_id {
+            Some(drive_id) => vec![
+                ("driveId", drive_id.clone()),
+                ("supportsAllDrives", "true".to_string()),
+                ("includeItemsFromAllDrives", "true".to_string()),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let token = self.access_token().await?;
+        let response = client()
+            .get(format!("{}{}", API_URL, path))
+            .bearer_auth(token)
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    /// Every file (or folder, with `folders`) that isn't trashed
+    async fn list_files(&self, folders: bool) -> Result<Vec<DriveFile>> {
+        let q = if folders {
+            format!("mimeType = '{}' and trashed = false", FOLDER_MIME)
+        } else {
+            format!("mimeType != '{}' and trashed = false", FOLDER_MIME)
+        };
+        let mut query = self.drive_params();
+        query.push(("q", q));
+        query.push(("pageSize", PAGE_SIZE.to_string()));
+        query.push(("fields", format!("nextPageToken,files({})", FILE_FIELDS)));
+        if self.config.shared_drive_id.is_some() {
+            query.push(("corpora", "drive".to_string()));
+        }
+
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut page_query = query.clone();
+            if let Some(token) = page_token.take() {
+                page_query.push(("pageToken", token));
+            }
+            let page: FileList = self.get("/files", &page_query).await?;
+            files.extend(page.files);
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => return Ok(files),
+            }
+        }
+    }
+
+    /// The change for a file, or `None` for files that aren't synced
+    fn change(&self, file: DriveFile, folders: &Folders) -> Option<Change> {
+        if file.trashed {
+            return Some(Change::Removed(file.id));
+        }
+        if file.mime_type == FOLDER_MIME {
+            return None;
+        }
+        let export = export_format(&file.mime_type);
+        if export.is_none() && file.mime_type.starts_with("application/vnd.google-apps.") {
+            return None;
+        }
+        let Some(mut path) = folders.path(&file, self.config.folder_id.as_deref()) else {
+            // Moved out of the synced folder
+            return Some(Change::Removed(file.id));
+        };
+
+        let download_url = match export {
+            Some((mime_type, extension)) => {
+                path = format!("{}.{}", path, extension);
+                format!("{}/files/{}/export?mimeType={}", API_URL, file.id, mime_type)
+            }
+            None => format!("{}/files/{}?alt=media&supportsAllDrives=true", API_URL, file.id),
+        };
+        Some(Change::Upsert(RemoteFile {
+            version: file.md5_checksum.or(file.version),
+            size: file.size.and_then(|size| size.parse().ok()),
+            web_url: file.web_view_link,
+            modified: file.modified_time,
+            download_url,
+            path,
+            id: file.id,
+        }))
+    }
+}
+
+#[async_trait]
+impl Connector for GoogleDrive {
+    async fn list_changes(&self, cursor: Option<&str>) -> Result<ChangeSet> {
+        let Some(cursor) = cursor else {
+            // Take the token first, so changes made while listing are read next time
+            #[derive(Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct StartToken {
+                start_page_token: String,
+            }
+            let mut query = self.drive_params();
+            query.retain(|(name, _)| *name != "includeItemsFromAllDrives");
+            let start: StartToken = self.get("/changes/startPageToken", &query).await?;
+            let folders = Folders::from_files(self.list_files(true).await?);
+            let changes = self
+                .list_files(false)
+                .await?
+                .into_iter()
+                .filter_map(|file| self.change(file, &folders))
+                .filter(|change| matches!(change, Change::Upsert(_)))
+                .collect();
+            return Ok(ChangeSet { changes, cursor: start.start_page_token, complete: true });
+        };
+
+        let mut query = self.drive_params();
+        query.push(("pageSize", PAGE_SIZE.to_string()));
+        query.push(("includeRemoved", "true".to_string()));
+        query.push((
+            "fields",
+            format!("nextPageToken,newStartPageToken,changes(fileId,removed,file({}))", FILE_FIELDS),
+        ));
+
+        let mut drive_changes = Vec::new();
+        let mut page_token = cursor.to_string();
+        let next_cursor = loop {
+            let mut page_query = query.clone();
+            page_query.push(("pageToken", page_token));
+            let page: ChangeList = self.get("/changes", &page_query).await?;
+            drive_changes.extend(page.changes);
+            match (page.next_page_token, page.new_start_page_token) {
+                (Some(token), _) => page_token = token,
+                (None, Some(token)) => break token,
+                (None, None) => break cursor.to_string(),
+            }
+        };
+
+        // Folders are only needed for paths of changed files
+        let changed_files = drive_changes.iter().any(|c| c.file.as_ref().is_some_and(|f| f.mime_type != FOLDER_MIME));
+        let folders = if changed_files { Folders::from_files(self.list_files(true).await?) } else { Folders::default() };
+        let changes = drive_changes
+            .into_iter()
+            .filter_map(|change| match change.file {
+                Some(file) if !change.removed => self.change(file, &folders),
+                _ => Some(Change::Removed(change.file_id)),
+            })
+            .collect();
+        Ok(ChangeSet { changes, cursor: next_cursor, complete: false })
+    }
+
+    async fn fetch(&self, file: &RemoteFile) -> Result<Vec<u8>> {
+        let token = self.access_token().await?;
+        let response = client().get(&file.download_url).bearer_auth(token).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drive_file(id: &str, name: &str, mime_type: &str, parent: &str) -> DriveFile {
+        DriveFile {
+            id: id.to_string(),
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            parents: vec![parent.to_string()],
+            md5_checksum: None,
+            version: Some("7".to_string()),
+            size: None,
+            web_view_link: None,
+            modified_time: None,
+            trashed: false,
+        }
+    }
+
+    #[test]
+    fn test_change_paths() {
+        let folders = Folders::from_files(vec![
+            drive_file("hr", "HR", FOLDER_MIME, "root"),
+            drive_file("policies", "Policies", FOLDER_MIME, "hr"),
+            drive_file("other", "Other", FOLDER_MIME, "root"),
+        ]);
+        let config = GoogleDriveConfig {
+            client_id: String::new(),
+            client_secret: String::new(),
+            refresh_token: String::new(),
+            folder_id: Some("hr".to_string()),
+            shared_drive_id: None,
+        };
+        let drive = GoogleDrive::new(config);
+
+        let doc = drive_file("d1", "Leave", "application/vnd.google-apps.document", "policies");
+        let Some(Change::Upsert(file)) = drive.change(doc, &folders) else { panic!("not synced") };
+        assert_eq!(file.path, "/Policies/Leave.docx");
+        assert_eq!(file.version.as_deref(), Some("7"));
+        assert!(file.download_url.ends_with("/files/d1/export?mimeType=application/vnd.openxmlformats-officedocument.wordprocessingml.document"));
+
+        // Outside the synced folder, and Google-native files without an export format
+        let outside = drive_file("p1", "plan.pdf", "application/pdf", "other");
+        assert_eq!(drive.change(outside, &folders), Some(Change::Removed("p1".to_string())));
+        let form = drive_file("f1", "Survey", "application/vnd.google-apps.form", "hr");
+        assert_eq!(drive.change(form, &folders), None);
+    }
+}
@@ -0,0 +1,646 @@
+//! Source connectors (Google Drive, SharePoint/OneDrive)
+//!
+//! A [`Connector`] lists the files changed at its source since a cursor and
+//! downloads their content. [`Connectors`] runs a sync pass per configured
+//! connector: new and changed files are ingested through
+//! `RagEngine::ingest_bytes` as `<name>/<path>`, so duplicate content is
+//! skipped and changed files replace their old chunks, and documents of
+//! files removed at the source are deleted. Each connector's cursor and the
+//! version and document of every file it synced are kept in SQLite
+//! (`connector_state`, `connector_items`); files whose version is unchanged
+//! are not downloaded again. A pass in which files failed keeps the previous
+//! cursor, so they are retried by the next one.
+
+pub mod google_drive;
+pub mod sharepoint;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use reqwest::Client;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::{ConnectorConfig, ConnectorSource};
+use crate::engine::{IngestOutcome, RagEngine};
+use crate::error::{Error, Result};
+use crate::processing::{TaskHandle, TaskKind};
+use crate::server::state::AppState;
+use crate::types::query::IngestOptions;
+use crate::types::document::SOURCE_MODIFIED_METADATA_KEY;
+use crate::types::FileType;
+
+/// How often the scheduler checks for connectors due a pass
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Timeout for API calls and downloads
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Metadata keys set on connector documents
+pub const CONNECTOR_METADATA_KEY: &str = "connector";
+pub const SOURCE_ID_METADATA_KEY: &str = "source_id";
+pub const SOURCE_URL_METADATA_KEY: &str = "source_url";
+
+/// A file at the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteFile {
+    /// Id at the source, stable across renames and moves
+    pub id: String,
+    /// Path from the synced root, starting with `/` (with the extension of
+    /// the format native documents are exported as)
+    pub path: String,
+    /// Content version (checksum, revision or content tag)
+    pub version: Option<String>,
+    pub size: Option<u64>,
+    /// Where the file opens in the source's web UI
+    pub web_url: Option<String>,
+    /// When the file was last modified (RFC 3339)
+    pub modified: Option<String>,
+    /// Where its content is downloaded from
+    pub download_url: String,
+}
+
+/// A change at the source
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// File added or changed (renamed, moved or rewritten)
+    Upsert(RemoteFile),
+    /// File deleted, trashed or moved out of the synced folder
+    Removed(String),
+}
+
+/// Changes since a cursor
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    pub changes: Vec<Change>,
+    /// Cursor for the next pass
+    pub cursor: String,
+    /// Whether every file was listed (first pass, expired cursor), so that
+    /// files synced before and not listed are gone
+    pub complete: bool,
+}
+
+/// A document service files are synced from
+#[async_trait]
+pub trait Connector: Send + Sync {
+    /// Files changed since `cursor`, or every file without one
+    async fn list_changes(&self, cursor: Option<&str>) -> Result<ChangeSet>;
+
+    /// Content of a file
+    async fn fetch(&self, file: &RemoteFile) -> Result<Vec<u8>>;
+}
+
+/// Connector for a configured source
+pub fn build(source: &ConnectorSource) -> Box<dyn Connector> {
+    match source {
+        ConnectorSource::GoogleDrive(config) => Box::new(google_drive::GoogleDrive::new(config.clone())),
+        ConnectorSource::SharePoint(config) => Box::new(sharepoint::SharePoint::new(config.clone())),
+    }
+}
+
+/// Stored cursor and last pass of a connector
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectorState {
+    pub cursor: Option<String>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// A file synced by a connector
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncedItem {
+    pub item_id: String,
+    /// Filename it was ingested as
+    pub filename: String,
+    /// Version as of its last sync
+    pub version: Option<String>,
+    /// Its document (none when its content duplicated another file)
+    pub document_id: Option<Uuid>,
+}
+
+/// Current or last pass of a connector
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConnectorProgress {
+    /// Task id for `/api/admin/tasks`
+    pub task_id: Uuid,
+    /// `full` or `incremental`
+    pub mode: String,
+    pub running: bool,
+    /// New or changed files listed
+    pub files_changed: usize,
+    /// Files ingested as new or updated documents
+    pub files_ingested: usize,
+    /// Unchanged, duplicate, unsupported or too large files
+    pub files_skipped: usize,
+    /// Files that failed (retried by the next pass)
+    pub files_failed: usize,
+    /// Files removed at the source
+    pub files_removed: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// A configured connector and its sync state
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConnectorStatus {
+    pub name: String,
+    /// `google_drive` or `sharepoint`
+    pub kind: String,
+    /// Seconds between passes (0 = on request only)
+    pub interval_secs: u64,
+    pub collection: Option<String>,
+    /// Files synced
+    pub files: usize,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Error of the last pass, if it failed
+    pub last_error: Option<String>,
+    /// Current or last pass since startup
+    pub progress: Option<ConnectorProgress>,
+}
+
+/// Runs connector passes, one at a time per connector
+pub struct Connectors {
+    running: Mutex<HashSet<String>>,
+    last: RwLock<HashMap<String, ConnectorProgress>>,
+}
+
+impl Connectors {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(HashSet::new()),
+            last: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Configured connectors with their stored state and last pass
+    pub fn list(&self, state: &AppState) -> Result<Vec<ConnectorStatus>> {
+        let config = state.config();
+        let mut statuses = Vec::with_capacity(config.connectors.len());
+        for connector in &config.connectors {
+            let stored = state.database().connector_state(&connector.name)?;
+            statuses.push(ConnectorStatus {
+                name: connector.name.clone(),
+                kind: connector.source.kind().to_string(),
+                interval_secs: connector.interval_secs,
+                collection: connector.collection.clone(),
+                files: state.database().count_connector_items(&connector.name)?,
+                last_run_at: stored.last_run_at,
+                last_error: stored.last_error,
+                progress: self.last.read().get(&connector.name).cloned(),
+            });
+        }
+        Ok(statuses)
+    }
+
+    /// Start a pass of connector `name` in the background, listing every
+    /// file when `full`
+    pub fn start(self: &Arc<Self>, state: AppState, name: &str, full: bool) -> Result<()> {
+        let config = find(&state, name)?;
+        self.claim(name)?;
+        let connectors = Arc::clone(self);
+        tokio::spawn(async move {
+            // Failures are logged and kept in the connector's state
+            let _ = connectors.run_claimed(&state, &config, full).await;
+        });
+        Ok(())
+    }
+
+    /// Run a pass of connector `name`, listing every file when `full`
+    pub async fn run(&self, state: &AppState, name: &str, full: bool) -> Result<ConnectorProgress> {
+        let config = find(state, name)?;
+        self.claim(name)?;
+        self.run_claimed(state, &config, full).await
+    }
+
+    /// Run every connector with an interval when it is due
+    ///
+    /// Due is measured from the last pass, including those of earlier
+    /// processes; failed passes are retried after the interval too.
+    pub fn spawn_scheduler(self: &Arc<Self>, state: AppState) {
+        let connectors = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SCHEDULER_TICK);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                for config in state.config().connectors.iter().filter(|c| c.interval_secs > 0) {
+                    let last_run = match state.database().connector_state(&config.name) {
+                        Ok(stored) => stored.last_run_at,
+                        Err(e) => {
+                            tracing::warn!("Failed to read state of connector '{}': {}", config.name, e);
+                            continue;
+                        }
+                    };
+                    let interval = chrono::Duration::seconds(config.interval_secs.min(i64::MAX as u64) as i64);
+                    if last_run.is_some_and(|at| at + interval > now) || connectors.claim(&config.name).is_err() {
+                        continue;
+                    }
+                    if let Err(e) = connectors.run_claimed(&state, config, false).await {
+                        tracing::debug!("Scheduled sync of connector '{}' did not complete: {}", config.name, e);
+                    }
+                }
+            }
+        });
+    }
+
+    fn claim(&self, name: &str) -> Result<()> {
+        if !self.running.lock().insert(name.to_string()) {
+            return Err(Error::Config(format!("Connector '{}' is already syncing", name)));
+        }
+        Ok(())
+    }
+
+    async fn run_claimed(&self, state: &AppState, config: &ConnectorConfig, full: bool) -> Result<ConnectorProgress> {
+        let task = state.tasks().start(TaskKind::ConnectorSync, "listing", 0, "files");
+        let mut progress = ConnectorProgress {
+            task_id: task.id(),
+            mode: if full { "full" } else { "incremental" }.to_string(),
+            running: true,
+            files_changed: 0,
+            files_ingested: 0,
+            files_skipped: 0,
+            files_failed: 0,
+            files_removed: 0,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        self.last.write().insert(config.name.clone(), progress.clone());
+
+        let result = self.sync(state, config, &task, &mut progress, full).await;
+        progress.running = false;
+        progress.completed_at = Some(Utc::now());
+        let mut stored = state.database().connector_state(&config.name).unwrap_or_default();
+        stored.last_run_at = progress.completed_at;
+        match &result {
+            Ok(cursor) => {
+                task.complete();
+                stored.last_error = None;
+                if cursor.is_some() {
+                    stored.cursor = cursor.clone();
+                }
+                if progress.files_changed > 0 || progress.files_removed > 0 || full {
+                    tracing::info!(
+                        "Connector '{}' {} sync complete: {} of {} changed files ingested, {} skipped, {} failed, {} removed",
+                        config.name, progress.mode, progress.files_ingested, progress.files_changed,
+                        progress.files_skipped, progress.files_failed, progress.files_removed
+                    );
+                }
+            }
+            Err(e) => {
+                progress.error = Some(e.to_string());
+                stored.last_error = Some(e.to_string());
+                task.fail(e.to_string());
+                tracing::error!("Connector '{}' sync failed: {}", config.name, e);
+            }
+        }
+        if let Err(e) = state.database().set_connector_state(&config.name, &stored) {
+            tracing::warn!("Failed to save state of connector '{}': {}", config.name, e);
+        }
+
+        self.last.write().insert(config.name.clone(), progress.clone());
+        self.running.lock().remove(&config.name);
+        result.map(|_| progress)
+    }
+
+    /// Sync the changes since the stored cursor; returns the cursor to keep
+    /// (none when files failed, to list them again)
+    async fn sync(
+        &self,
+        state: &AppState,
+        config: &ConnectorConfig,
+        task: &TaskHandle,
+        progress: &mut ConnectorProgress,
+        full: bool,
+    ) -> Result<Option<String>> {
+        let database = state.database();
+        let connector = build(&config.source);
+        let cursor = if full { None } else { database.connector_state(&config.name)?.cursor };
+        let changes = connector.list_changes(cursor.as_deref()).await?;
+        let mut known = database.connector_items(&config.name)?;
+        let plan = plan_changes(&config.name, &known, changes.changes, changes.complete);
+        progress.files_changed = plan.sync.len();
+        progress.files_skipped = plan.unchanged;
+        self.last.write().insert(config.name.clone(), progress.clone());
+
+        let engine = RagEngine::from_state(state.clone());
+        task.set_phase("syncing");
+        task.report(0, plan.sync.len());
+        for (done, file) in plan.sync.iter().enumerate() {
+            task.set_current(Some(file.path.clone()));
+            match self.sync_file(&engine, config, connector.as_ref(), file, &mut known).await {
+                Ok(true) => progress.files_ingested += 1,
+                Ok(false) => progress.files_skipped += 1,
+                Err(e) => {
+                    tracing::warn!("Connector '{}' failed to sync {}: {}", config.name, file.path, e);
+                    progress.files_failed += 1;
+                }
+            }
+            task.report(done + 1, plan.sync.len());
+            self.last.write().insert(config.name.clone(), progress.clone());
+        }
+        task.set_current(None);
+
+        task.set_phase("removing");
+        for item_id in &plan.removed {
+            let Some(item) = known.remove(item_id) else { continue };
+            if config.delete_removed {
+                if let Err(e) = delete_item_document(&engine, config, &item).await {
+                    tracing::warn!("Connector '{}' failed to delete {}: {}", config.name, item.filename, e);
+                    progress.files_failed += 1;
+                    continue;
+                }
+            }
+            database.delete_connector_item(&config.name, item_id)?;
+            progress.files_removed += 1;
+        }
+
+        Ok((progress.files_failed == 0).then_some(changes.cursor))
+    }
+
+    /// Download and ingest a new or changed file; returns whether a document
+    /// was created or updated
+    async fn sync_file(
+        &self,
+        engine: &RagEngine,
+        config: &ConnectorConfig,
+        connector: &dyn Connector,
+        file: &RemoteFile,
+        known: &mut HashMap<String, SyncedItem>,
+    ) -> Result<bool> {
+        let filename = unique_filename(&config.name, file, known);
+        let previous = known.get(&file.id).cloned();
+        let mut item = SyncedItem {
+            item_id: file.id.clone(),
+            filename: filename.clone(),
+            version: file.version.clone(),
+            document_id: previous.as_ref().filter(|p| p.filename == filename).and_then(|p| p.document_id),
+        };
+
+        let extension = filename.rsplit_once('.').map_or("", |(_, ext)| ext);
+        let too_large = file.size.is_some_and(|size| size > config.max_file_bytes);
+        if too_large || !FileType::from_extension(extension).is_supported() {
+            tracing::debug!("Connector '{}' skipped {} (unsupported or too large)", config.name, file.path);
+            record_item(engine, config, item, known)?;
+            return Ok(false);
+        }
+
+        // A renamed or moved file replaces the document of its old name (first,
+        // or its unchanged content would be skipped as a duplicate of it)
+        if let Some(previous) = previous.filter(|p| p.filename != filename) {
+            delete_item_document(engine, config, &previous).await?;
+        }
+
+        let data = connector.fetch(file).await?;
+        if data.len() as u64 > config.max_file_bytes {
+            record_item(engine, config, item, known)?;
+            return Ok(false);
+        }
+        let options = ingest_options(config, file);
+        let outcome = engine.ingest_bytes(&filename, &data, &options).await?;
+        let ingested = !matches!(outcome, IngestOutcome::Skipped { .. });
+        if let Some(document) = outcome.document() {
+            item.document_id = Some(document.id);
+        }
+        record_item(engine, config, item, known)?;
+        Ok(ingested)
+    }
+}
+
+impl Default for Connectors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn find(state: &AppState, name: &str) -> Result<ConnectorConfig> {
+    state
+        .config()
+        .connectors
+        .iter()
+        .find(|c| c.name == name)
+        .cloned()
+        .ok_or_else(|| Error::NotFound(format!("Connector '{}'", name)))
+}
+
+fn record_item(engine: &RagEngine, config: &ConnectorConfig, item: SyncedItem, known: &mut HashMap<String, SyncedItem>) -> Result<()> {
+    engine.state().database().upsert_connector_item(&config.name, &item)?;
+    known.insert(item.item_id.clone(), item);
+    Ok(())
+}
+
+/// Delete the document of a synced file, if it still is the connector's
+async fn delete_item_document(engine: &RagEngine, config: &ConnectorConfig, item: &SyncedItem) -> Result<()> {
+    let Some(id) = item.document_id else { return Ok(()) };
+    let Some(document) = engine.state().get_document(&id) else { return Ok(()) };
+    if !document.filename.starts_with(&format!("{}/", config.name)) {
+        return Ok(());
+    }
+    engine.delete_document(&id).await?;
+    Ok(())
+}
+
+/// Options a file is ingested with: the connector's collection and access
+/// list, and where the file came from
+fn ingest_options(config: &ConnectorConfig, file: &RemoteFile) -> IngestOptions {
+    let mut options = IngestOptions {
+        collection: config.collection.clone(),
+        acl: config.acl.clone(),
+        ..Default::default()
+    };
+    options.metadata.insert(CONNECTOR_METADATA_KEY.to_string(), config.name.clone().into());
+    options.metadata.insert(SOURCE_ID_METADATA_KEY.to_string(), file.id.clone().into());
+    if let Some(url) = &file.web_url {
+        options.metadata.insert(SOURCE_URL_METADATA_KEY.to_string(), url.clone().into());
+    }
+    if let Some(modified) = &file.modified {
+        options.metadata.insert(SOURCE_MODIFIED_METADATA_KEY.to_string(), modified.clone().into());
+    }
+    options
+}
+
+/// What a pass does with a change set
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    /// New files and files whose version or path changed
+    sync: Vec<RemoteFile>,
+    /// Ids of synced files that are gone
+    removed: Vec<String>,
+    /// Listed files already synced as they are
+    unchanged: usize,
+}
+
+/// Sort changes against the `known` synced files of `connector` (the last
+/// change of a file wins); with a `complete` listing, known files not listed
+/// are removed
+fn plan_changes(connector: &str, known: &HashMap<String, SyncedItem>, changes: Vec<Change>, complete: bool) -> Plan {
+    let mut latest: Vec<Change> = Vec::new();
+    for change in changes {
+        let id = change_id(&change).to_string();
+        latest.retain(|c| change_id(c) != id);
+        latest.push(change);
+    }
+
+    let mut plan = Plan::default();
+    let mut listed = HashSet::new();
+    for change in latest {
+        match change {
+            Change::Upsert(file) => {
+                listed.insert(file.id.clone());
+                let unchanged = known.get(&file.id).is_some_and(|item| {
+                    item.version.is_some() && item.version == file.version && item.filename == unique_filename(connector, &file, known)
+                });
+                if unchanged {
+                    plan.unchanged += 1;
+                } else {
+                    plan.sync.push(file);
+                }
+            }
+            Change::Removed(id) if known.contains_key(&id) => plan.removed.push(id),
+            Change::Removed(_) => {}
+        }
+    }
+    if complete {
+        let mut gone: Vec<String> = known.keys().filter(|id| !listed.contains(*id)).cloned().collect();
+        gone.sort();
+        plan.removed.extend(gone.into_iter().filter(|id| !plan.removed.contains(id)));
+    }
+    plan
+}
+
+fn change_id(change: &Change) -> &str {
+    match change {
+        Change::Upsert(file) => &file.id,
+        Change::Removed(id) => id,
+    }
+}
+
+/// `<connector>/<path>`, with the file's id added before the extension when
+/// another synced file already has that name
+fn unique_filename(connector: &str, file: &RemoteFile, known: &HashMap<String, SyncedItem>) -> String {
+    let filename = format!("{}/{}", connector, file.path.trim_start_matches('/'));
+    let taken = known.values().any(|item| item.filename == filename && item.item_id != file.id);
+    if !taken {
+        return filename;
+    }
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => format!("{} ({}).{}", stem, file.id, ext),
+        _ => format!("{} ({})", filename, file.id),
+    }
+}
+
+/// Client for the connectors' APIs
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default())
+}
+
+/// Bearer token from an OAuth token endpoint, renewed a minute before it
+/// expires
+struct TokenCache {
+    token: tokio::sync::Mutex<Option<(String, std::time::Instant)>>,
+}
+
+impl TokenCache {
+    fn new() -> Self {
+        Self { token: tokio::sync::Mutex::new(None) }
+    }
+
+    /// The cached token, or a new one from `token_url` with `form`
+    async fn get(&self, token_url: &str, form: &[(&str, &str)]) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some((value, expires)) = token.as_ref() {
+            if std::time::Instant::now() < *expires {
+                return Ok(value.clone());
+            }
+        }
+
+        let response = client().post(token_url).form(form).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Unauthorized(format!("Token request failed ({}): {}", status, body)));
+        }
+        let granted: serde_json::Value = response.json().await?;
+        let value = granted["access_token"]
+            .as_str()
+            .ok_or_else(|| Error::Unauthorized("Token response has no access_token".to_string()))?
+            .to_string();
+        let lifetime = granted["expires_in"].as_u64().unwrap_or(3600).saturating_sub(60);
+        *token = Some((value.clone(), std::time::Instant::now() + Duration::from_secs(lifetime)));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, path: &str, version: &str) -> RemoteFile {
+        RemoteFile {
+            id: id.to_string(),
+            path: path.to_string(),
+            version: Some(version.to_string()),
+            size: None,
+            web_url: None,
+            modified: None,
+            download_url: String::new(),
+        }
+    }
+
+    fn item(id: &str, filename: &str, version: &str) -> (String, SyncedItem) {
+        let item = SyncedItem {
+            item_id: id.to_string(),
+            filename: filename.to_string(),
+            version: Some(version.to_string()),
+            document_id: None,
+        };
+        (id.to_string(), item)
+    }
+
+    #[test]
+    fn test_plan_changes() {
+        let known: HashMap<String, SyncedItem> = [
+            item("a", "drive/a.pdf", "1"),
+            item("b", "drive/b.pdf", "1"),
+            item("c", "drive/c.pdf", "1"),
+            item("d", "drive/d.pdf", "1"),
+        ]
+        .into();
+
+        let changes = vec![
+            Change::Upsert(file("a", "/a.pdf", "1")),
+            Change::Upsert(file("b", "/b.pdf", "1")),
+            Change::Upsert(file("b", "/b.pdf", "2")),
+            Change::Upsert(file("c", "/moved/c.pdf", "1")),
+            Change::Removed("d".to_string()),
+            Change::Removed("unknown".to_string()),
+            Change::Upsert(file("e", "/e.pdf", "1")),
+        ];
+        let plan = plan_changes("drive", &known, changes, false);
+        let synced: Vec<&str> = plan.sync.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(synced, ["b", "c", "e"]);
+        assert_eq!(plan.sync[0].version.as_deref(), Some("2"));
+        assert_eq!(plan.removed, ["d"]);
+        assert_eq!(plan.unchanged, 1);
+
+        // A complete listing without a file means it is gone
+        let plan = plan_changes("drive", &known, vec![Change::Upsert(file("a", "/a.pdf", "1"))], true);
+        assert_eq!(plan.removed, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_unique_filename() {
+        let known: HashMap<String, SyncedItem> = [item("a", "drive/Reports/q1.pdf", "1")].into();
+        assert_eq!(unique_filename("drive", &file("a", "/Reports/q1.pdf", "2"), &known), "drive/Reports/q1.pdf");
+        assert_eq!(unique_filename("drive", &file("b", "/Reports/q1.pdf", "1"), &known), "drive/Reports/q1 (b).pdf");
+        assert_eq!(unique_filename("drive", &file("c", "/notes", "1"), &HashMap::new()), "drive/notes");
+    }
+}
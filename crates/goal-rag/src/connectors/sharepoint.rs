@@ -0,0 +1,260 @@
+//! SharePoint and OneDrive connector
+//!
+//! Reads a drive (a SharePoint document library or a OneDrive) through
+//! Microsoft Graph with an app's client credentials. Passes follow the
+//! drive's delta query: the first lists every item, later ones resume from
+//! the delta link of the previous pass, and an expired link (`410 Gone`)
+//! starts over with a complete listing. Delta items carry no paths, so the
+//! paths of their folders are built from the folders in the same response
+//! or looked up once per pass.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{client, Change, ChangeSet, Connector, RemoteFile, TokenCache};
+use crate::config::SharePointConfig;
+use crate::error::{Error, Result};
+
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0";
+const GRAPH_SCOPE: &str = "https://graph.microsoft.com/.default";
+
+/// A drive item in a delta or item response
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveItem {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    web_url: Option<String>,
+    #[serde(default)]
+    last_modified_date_time: Option<String>,
+    #[serde(default)]
+    c_tag: Option<String>,
+    #[serde(default)]
+    e_tag: Option<String>,
+    #[serde(default)]
+    parent_reference: Option<ParentReference>,
+    #[serde(default)]
+    file: Option<serde_json::Value>,
+    #[serde(default)]
+    folder: Option<serde_json::Value>,
+    #[serde(default)]
+    root: Option<serde_json::Value>,
+    #[serde(default)]
+    deleted: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ParentReference {
+    #[serde(default)]
+    id: Option<String>,
+    /// `/drives/{drive-id}/root:/Folder/Sub` (item responses only)
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaPage {
+    #[serde(default)]
+    value: Vec<DriveItem>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    delta_link: Option<String>,
+}
+
+/// Path below the drive root of a `parentReference.path`
+fn root_relative(path: &str) -> String {
+    let relative = path.split_once("root:").map_or("", |(_, rest)| rest);
+    relative.trim_end_matches('/').to_string()
+}
+
+/// SharePoint document library or OneDrive
+pub struct SharePoint {
+    config: SharePointConfig,
+    token: TokenCache,
+    /// Folder paths below the root by id, per pass
+    folder_paths: Mutex<HashMap<String, String>>,
+}
+
+impl SharePoint {
+    pub fn new(config: SharePointConfig) -> Self {
+        Self {
+            config,
+            token: TokenCache::new(),
+            folder_paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let token_url = format!("https://login.microsoftonline.com/{}/oauth2/v2.0/token", self.config.tenant_id);
+        let form = [
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+            ("scope", GRAPH_SCOPE),
+            ("grant_type", "client_credentials"),
+        ];
+        self.token.get(&token_url, &form).await
+    }
+
+    /// Synced folder without surrounding slashes, `""` for the whole drive
+    fn folder(&self) -> &str {
+        self.config.folder.as_deref().unwrap_or("").trim_matches('/')
+    }
+
+    /// Items from a delta link to the end; `None` when the link expired
+    async fn delta(&self, url: &str) -> Result<Option<(Vec<DriveItem>, String)>> {
+        let token = self.access_token().await?;
+        let mut items = Vec::new();
+        let mut url = url.to_string();
+        loop {
+            let response = client().get(&url).bearer_auth(&token).send().await?;
+            if response.status() == StatusCode::GONE {
+                return Ok(None);
+            }
+            let page: DeltaPage = response.error_for_status()?.json().await?;
+            items.extend(page.value);
+            match (page.next_link, page.delta_link) {
+                (Some(next), _) => url = next,
+                (None, Some(delta)) => return Ok(Some((items, delta))),
+                (None, None) => return Err(Error::Internal("Graph delta response without a next or delta link".to_string())),
+            }
+        }
+    }
+
+    /// Path below the root of folder `id`, from the folders of this response
+    /// or else from Graph
+    async fn folder_path(&self, id: &str, listed: &HashMap<String, DriveItem>) -> Result<String> {
+        // Walk up to a folder whose path is known, naming the folders on the way
+        let mut chain: Vec<(String, String)> = Vec::new();
+        let mut current = id.to_string();
+        let mut path = loop {
+            if let Some(path) = self.folder_paths.lock().await.get(&current) {
+                break path.clone();
+            }
+            match listed.get(&current) {
+                Some(folder) if folder.root.is_none() && chain.len() < 64 => {
+                    chain.push((current.clone(), folder.name.clone()));
+                    match folder.parent_reference.as_ref().and_then(|parent| parent.id.clone()) {
+                        Some(parent) => current = parent,
+                        None => break String::new(),
+                    }
+                }
+                Some(_) => break String::new(),
+                None => {
+                    let item = self.item(&current).await?;
+                    let path = match (&item.root, item.parent_reference.as_ref().and_then(|p| p.path.as_deref())) {
+                        (None, Some(parent)) => format!("{}/{}", root_relative(parent), item.name),
+                        _ => String::new(),
+                    };
+                    self.folder_paths.lock().await.insert(current.clone(), path.clone());
+                    break path;
+                }
+            }
+        };
+
+        let mut paths = self.folder_paths.lock().await;
+        for (folder_id, name) in chain.into_iter().rev() {
+            path = format!("{}/{}", path, name);
+            paths.insert(folder_id, path.clone());
+        }
+        Ok(path)
+    }
+
+    async fn item(&self, id: &str) -> Result<DriveItem> {
+        let token = self.access_token().await?;
+        let url = format!("{}/drives/{}/items/{}", GRAPH_URL, self.config.drive_id, id);
+        let response = client()
+            .get(url)
+            .bearer_auth(token)
+            .query(&[("$select", "id,name,parentReference,root")])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    async fn change(&self, item: DriveItem, listed: &HashMap<String, DriveItem>) -> Result<Option<Change>> {
+        if item.deleted.is_some() {
+            return Ok(Some(Change::Removed(item.id)));
+        }
+        if item.file.is_none() {
+            return Ok(None);
+        }
+        let parent = item.parent_reference.as_ref().and_then(|parent| parent.id.clone());
+        let Some(parent) = parent else { return Ok(None) };
+        let path = format!("{}/{}", self.folder_path(&parent, listed).await?, item.name);
+
+        let folder = self.folder();
+        let path = if folder.is_empty() {
+            path
+        } else {
+            match path.strip_prefix(&format!("/{}/", folder)) {
+                Some(relative) => format!("/{}", relative),
+                // Moved out of the synced folder
+                None => return Ok(Some(Change::Removed(item.id))),
+            }
+        };
+        Ok(Some(Change::Upsert(RemoteFile {
+            download_url: format!("{}/drives/{}/items/{}/content", GRAPH_URL, self.config.drive_id, item.id),
+            version: item.c_tag.or(item.e_tag),
+            size: item.size,
+            web_url: item.web_url,
+            modified: item.last_modified_date_time,
+            path,
+            id: item.id,
+        })))
+    }
+}
+
+#[async_trait]
+impl Connector for SharePoint {
+    async fn list_changes(&self, cursor: Option<&str>) -> Result<ChangeSet> {
+        self.folder_paths.lock().await.clear();
+        let start = format!("{}/drives/{}/root/delta", GRAPH_URL, self.config.drive_id);
+        let (items, cursor, complete) = match cursor {
+            Some(link) => match self.delta(link).await? {
+                Some((items, cursor)) => (items, cursor, false),
+                None => {
+                    tracing::info!("Delta link of drive {} expired, listing every item", self.config.drive_id);
+                    let (items, cursor) = self.delta(&start).await?.ok_or_else(|| Error::Internal("Graph delta query expired".to_string()))?;
+                    (items, cursor, true)
+                }
+            },
+            None => {
+                let (items, cursor) = self.delta(&start).await?.ok_or_else(|| Error::Internal("Graph delta query expired".to_string()))?;
+                (items, cursor, true)
+            }
+        };
+
+        let listed: HashMap<String, DriveItem> = items
+            .iter()
+            .filter(|item| item.folder.is_some() || item.root.is_some())
+            .map(|item| (item.id.clone(), item.clone()))
+            .collect();
+        let mut changes = Vec::new();
+        for item in items {
+            if let Some(change) = self.change(item, &listed).await? {
+                if complete && matches!(change, Change::Removed(_)) {
+                    continue;
+                }
+                changes.push(change);
+            }
+        }
+        Ok(ChangeSet { changes, cursor, complete })
+    }
+
+    async fn fetch(&self, file: &RemoteFile) -> Result<Vec<u8>> {
+        // Graph redirects to a pre-authenticated download URL
+        let token = self.access_token().await?;
+        let response = client().get(&file.download_url).bearer_auth(token).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
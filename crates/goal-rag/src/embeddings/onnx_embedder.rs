@@ -7,7 +7,7 @@ use ort::value::Tensor;
 use std::path::PathBuf;
 use tokenizers::Tokenizer;
 
-use crate::config::EmbeddingConfig;
+use crate::config::{EmbeddingConfig, OnnxPooling};
 use crate::error::{Error, Result};
 
 /// ONNX-based text embedder
@@ -182,58 +182,12 @@ impl OnnxEmbedder {
         let dims: Vec<usize> = tensor_shape.iter().map(|&d| d as usize).collect();
         let hidden_size = dims.get(2).copied().unwrap_or(self.dimensions);
 
-        // Mean pooling with attention mask
-        let mut embeddings = Vec::with_capacity(batch_size);
-
-        for i in 0..batch_size {
-            let mut sum = vec![0.0f32; hidden_size];
-            let mut count = 0.0f32;
-
-            for j in 0..max_len {
-                let mask_val = attention_mask[i * max_len + j] as f32;
-                if mask_val > 0.0 {
-                    for k in 0..hidden_size {
-                        let idx = i * max_len * hidden_size + j * hidden_size + k;
-                        if idx < tensor_data.len() {
-                            sum[k] += tensor_data[idx] * mask_val;
-                        }
-                    }
-                    count += mask_val;
-                }
-            }
-
-            // Normalize by count
-            if count > 0.0 {
-                for val in &mut sum {
-                    *val /= count;
-                }
-            }
-
-            // L2 normalize
-            let norm: f32 = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
-            if norm > 0.0 {
-                for val in &mut sum {
-                    *val /= norm;
-                }
-            }
-
-            embeddings.push(sum);
-        }
-
-        Ok(embeddings)
+        crate::providers::onnx::pool(tensor_data, &attention_mask, batch_size, max_len, hidden_size, OnnxPooling::Mean)
     }
 
     /// Compute cosine similarity between two embeddings
     pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-        let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-
-        if norm_a == 0.0 || norm_b == 0.0 {
-            0.0
-        } else {
-            dot / (norm_a * norm_b)
-        }
+        crate::retrieval::similarity::cosine_similarity(a, b)
     }
 }
 
@@ -243,31 +197,7 @@ async fn download_model(model_name: &str, path: &PathBuf) -> Result<()> {
         "https://huggingface.co/sentence-transformers/{}/resolve/main/onnx/model.onnx",
         model_name
     );
-
-    tracing::info!("Downloading model from: {}", url);
-
-    let response = reqwest::get(&url).await.map_err(|e| {
-        Error::Embedding(format!("Failed to download model: {}", e))
-    })?;
-
-    if !response.status().is_success() {
-        return Err(Error::Embedding(format!(
-            "Model download failed: HTTP {}",
-            response.status()
-        )));
-    }
-
-    let bytes = response.bytes().await.map_err(|e| {
-        Error::Embedding(format!("Failed to read model bytes: {}", e))
-    })?;
-
-    std::fs::write(path, &bytes).map_err(|e| {
-        Error::Embedding(format!("Failed to save model: {}", e))
-    })?;
-
-    tracing::info!("Model downloaded successfully ({} bytes)", bytes.len());
-
-    Ok(())
+    crate::providers::onnx::download(&url, path).await
 }
 
 /// Download tokenizer
@@ -276,29 +206,5 @@ async fn download_tokenizer(model_name: &str, path: &PathBuf) -> Result<()> {
         "https://huggingface.co/sentence-transformers/{}/resolve/main/tokenizer.json",
         model_name
     );
-
-    tracing::info!("Downloading tokenizer from: {}", url);
-
-    let response = reqwest::get(&url).await.map_err(|e| {
-        Error::Embedding(format!("Failed to download tokenizer: {}", e))
-    })?;
-
-    if !response.status().is_success() {
-        return Err(Error::Embedding(format!(
-            "Tokenizer download failed: HTTP {}",
-            response.status()
-        )));
-    }
-
-    let bytes = response.bytes().await.map_err(|e| {
-        Error::Embedding(format!("Failed to read tokenizer bytes: {}", e))
-    })?;
-
-    std::fs::write(path, &bytes).map_err(|e| {
-        Error::Embedding(format!("Failed to save tokenizer: {}", e))
-    })?;
-
-    tracing::info!("Tokenizer downloaded successfully");
-
-    Ok(())
+    crate::providers::onnx::download(&url, path).await
 }
@@ -0,0 +1,2622 @@
+//! Embeddable RAG engine
+//!
+//! `RagEngine` is the library entry point for applications that want document
+//! ingestion and question answering without running the HTTP server. The
+//! server's handlers are thin wrappers over the same methods.
+//!
+//! ```no_run
+//! # async fn example() -> goal_rag::Result<()> {
+//! use goal_rag::{QueryRequest, RagConfig, RagEngine};
+//!
+//! let engine = RagEngine::new(RagConfig::default()).await?;
+//! engine.ingest_path("./docs", &Default::default()).await?;
+//!
+//! let response = engine.query(QueryRequest::new("What is the refund policy?")).await?;
+//! println!("{}", response.answer);
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::{DebugPrompt, RagConfig, VariantConfig};
+use crate::error::{Error, Result};
+use crate::generation::prompt::NOT_IN_DOCUMENTS;
+use crate::generation::templates::HistoryEntry;
+use crate::generation::{follow_ups, format, provenance, ContextPacker, PackedContext, PromptBuilder, PromptVars};
+use crate::ingestion::{
+    antivirus, archive, encrypted, figures, language, thumbnails, transcription, ExternalParser, IngestPipeline, ParsedDocument,
+};
+use crate::learning::{experiments, graph, knowledge_store::QAInteraction, qa_generation, CachedCitation};
+#[cfg(feature = "gcp")]
+use crate::providers::document_store::DocumentStoreProvider;
+use crate::processing::{keywords, merge_overlapping, reconstruct_text, redaction};
+use crate::providers::vector_store::VectorSearchResult;
+use crate::providers::{usage, GeneratedAnswer, LlmProvider, UsageScope};
+use crate::retrieval::{expansion, intent, similarity::CosineQuery, QueryExplain};
+use crate::server::audit;
+use crate::server::state::{AppState, FileStatus};
+use crate::server::webhooks::{WebhookEvent, WebhookEventKind};
+use crate::storage::ChunkContentRecord;
+use crate::types::{
+    acl::can_read,
+    document::{ARCHIVE_METADATA_KEY, COLLECTION_METADATA_KEY, SOURCE_METADATA_KEYS},
+    query::{AnswerFormat, AnswerStrategy, IngestOptions, QueryRequest, QueryType, StringSearchMode},
+    response::{
+        AnswerProvenance, BulkDeleteResponse, CacheInfo, Citation, DocumentSummary, IngestError, IngestResponse,
+        InsufficientEvidence, QueryResponse, SkippedUpload, StructuredAnswer, VersionDiffResponse,
+    },
+    Chunk, Document, Principal,
+};
+
+/// Candidate multiplier when an email, metadata, entity or per-document filter drops chunks
+const EMAIL_FILTER_OVERSAMPLE: usize = 5;
+
+/// Batches answered at once by the map_reduce answer strategy
+const MAP_CONCURRENCY: usize = 4;
+
+/// Outcome of ingesting one file
+#[derive(Debug, Clone)]
+pub enum IngestOutcome {
+    /// New file, document and chunks created
+    New { document: Document, chunks: u32 },
+    /// Changed file, old chunks replaced
+    Updated {
+        document: Document,
+        chunks: u32,
+        old_chunks_deleted: usize,
+    },
+    /// Unchanged file or duplicate content
+    Skipped { reason: String },
+}
+
+impl IngestOutcome {
+    /// The ingested document, unless the file was skipped
+    pub fn document(&self) -> Option<&Document> {
+        match self {
+            Self::New { document, .. } | Self::Updated { document, .. } => Some(document),
+            Self::Skipped { .. } => None,
+        }
+    }
+}
+
+/// RAG engine: ingestion, retrieval and answer generation
+#[derive(Clone)]
+pub struct RagEngine {
+    state: AppState,
+}
+
+impl RagEngine {
+    /// Create an engine, wiring providers and storage from configuration
+    ///
+    /// Starts the background ingestion worker unless `queue.run_workers` is
+    /// false, so this must be called from within a Tokio runtime.
+    pub async fn new(config: RagConfig) -> Result<Self> {
+        Ok(Self::from_state(AppState::new(config).await?))
+    }
+
+    /// Wrap existing application state
+    pub fn from_state(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Underlying state (providers, registries, job queue)
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    pub fn config(&self) -> Arc<RagConfig> {
+        self.state.config()
+    }
+
+    /// Ingest one file from memory
+    ///
+    /// Legacy formats are converted first. Files whose content is unchanged or
+    /// duplicates another file are skipped, changed files replace their old
+    /// chunks. Processing is bounded by `processing.file_timeout_secs`.
+    pub async fn ingest_bytes(&self, filename: &str, data: &[u8], options: &IngestOptions) -> Result<IngestOutcome> {
+        let state = &self.state;
+        tracing::info!("Processing file: {} ({} bytes)", filename, data.len());
+
+        // Infected files are quarantined and recorded as failed before parsing
+        let config = state.config();
+        let scanned = antivirus::scan_upload(&config.antivirus, &config.vector_db.storage_dir(), filename, data).await;
+        if let Err(ref e @ Error::Malware { .. }) = scanned {
+            let file_type = crate::types::FileType::from_extension(filename.rsplit('.').next().unwrap_or(""));
+            state.record_file_failed(filename, "", data.len() as u64, file_type, &e.to_string(), "scanning", None);
+        }
+        scanned?;
+
+        // Encrypted PDFs are parsed decrypted
+        let decrypted = encrypted::decrypt_upload(
+            &config.pdf_passwords,
+            filename,
+            data,
+            options.collection.as_deref(),
+            &options.passwords,
+        )
+        .await?;
+        let data = decrypted.as_deref().unwrap_or(data);
+
+        let (processed_filename, processed_data) = convert(state, filename, data).await?;
+
+        // Process the file with deduplication and timeout
+        let file_timeout = Duration::from_secs(state.config().processing.file_timeout_secs);
+        let file_start = Instant::now();
+
+        let outcome = timeout(
+            file_timeout,
+            process_file_with_dedup(state, &processed_filename, &processed_data, options),
+        )
+        .await
+        .map_err(|_| {
+            tracing::error!(
+                "TIMEOUT processing '{}' after {:.1}s (limit: {}s, size: {} bytes). \
+                Skipping file. Possible causes: large file, slow embedding service, or parsing hang.",
+                filename,
+                file_start.elapsed().as_secs_f64(),
+                file_timeout.as_secs(),
+                processed_data.len()
+            );
+            Error::FileParse {
+                filename: filename.to_string(),
+                message: format!(
+                    "Processing timeout after {}s - file may be too large or complex (size: {} bytes)",
+                    file_timeout.as_secs(),
+                    processed_data.len()
+                ),
+            }
+        })??;
+
+        match outcome {
+            IngestOutcome::New { ref document, .. } => {
+                state.add_document(document.clone());
+                state.webhooks().emit(WebhookEvent::document(WebhookEventKind::DocumentIngested, document));
+                tracing::info!(
+                    "Ingested new file: {} in {:.1}s",
+                    processed_filename,
+                    file_start.elapsed().as_secs_f64()
+                );
+            }
+            IngestOutcome::Updated {
+                ref document,
+                chunks,
+                old_chunks_deleted,
+            } => {
+                state.add_document(document.clone());
+                state.webhooks().emit(WebhookEvent::document(WebhookEventKind::DocumentUpdated, document));
+                tracing::info!(
+                    "Updated file: {} (deleted {} old chunks, created {} new) in {:.1}s",
+                    processed_filename,
+                    old_chunks_deleted,
+                    chunks,
+                    file_start.elapsed().as_secs_f64()
+                );
+            }
+            IngestOutcome::Skipped { ref reason } => {
+                tracing::info!("Skipped file: {} ({})", filename, reason);
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Ingest every file in a .zip, .tar or .tar.gz archive
+    ///
+    /// Files are extracted within the `[archive]` limits and ingested as
+    /// separate documents named `<archive>/<path>`, with the archive name in
+    /// their `archive` metadata. Fails only if the archive can't be extracted;
+    /// per-file outcomes are returned by name.
+    pub async fn ingest_archive(
+        &self,
+        filename: &str,
+        data: &[u8],
+        options: &IngestOptions,
+    ) -> Result<Vec<(String, Result<IngestOutcome>)>> {
+        let config = self.state.config().archive.clone();
+        let name = filename.to_string();
+        // Extracted from a file on disk, the blocking task can't borrow `data`
+        let spilled = tempfile::NamedTempFile::new()?;
+        tokio::fs::write(spilled.path(), data).await?;
+        let extracted = tokio::task::spawn_blocking(move || archive::extract_file(&name, spilled.path(), &config))
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+        tracing::info!("Extracted {} files from archive {}", extracted.entries.len(), filename);
+
+        let mut options = options.clone();
+        options
+            .metadata
+            .insert(ARCHIVE_METADATA_KEY.to_string(), serde_json::Value::String(filename.to_string()));
+
+        let mut outcomes = Vec::with_capacity(extracted.entries.len());
+        for entry in &extracted.entries {
+            let entry_name = format!("{}/{}", filename, entry.path);
+            let result = match tokio::fs::read(&entry.file).await {
+                Ok(data) => self.ingest_bytes(&entry_name, &data, &options).await,
+                Err(e) => Err(e.into()),
+            };
+            outcomes.push((entry_name, result));
+        }
+        Ok(outcomes)
+    }
+
+    /// Ingest one file into a report, expanding archives
+    pub(crate) async fn ingest_into(&self, report: &mut IngestReport, filename: String, data: &[u8], options: &IngestOptions) {
+        if self.state.config().archive.enabled && archive::is_archive(&filename) {
+            match self.ingest_archive(&filename, data, options).await {
+                Ok(outcomes) => {
+                    for (name, result) in outcomes {
+                        report.record(name, result);
+                    }
+                }
+                Err(e) => report.record(filename, Err(e)),
+            }
+        } else {
+            let result = self.ingest_bytes(&filename, data, options).await;
+            report.record(filename, result);
+        }
+    }
+
+    /// Ingest a file, or every file under a directory
+    ///
+    /// Per-file failures are reported in the response rather than aborting.
+    pub async fn ingest_path(&self, path: impl AsRef<Path>, options: &IngestOptions) -> Result<IngestResponse> {
+        let start = Instant::now();
+        let mut report = IngestReport::default();
+
+        for entry in walkdir::WalkDir::new(path.as_ref()).sort_by_file_name() {
+            let entry = entry.map_err(|e| Error::Internal(format!("Failed to walk directory: {}", e)))?;
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if !entry.file_type().is_file() || filename.starts_with('.') {
+                continue;
+            }
+
+            match tokio::fs::read(entry.path()).await {
+                Ok(data) => self.ingest_into(&mut report, filename, &data, options).await,
+                Err(e) => report.record(filename, Err(e.into())),
+            }
+        }
+
+        Ok(report.finish(start))
+    }
+
+    /// Answer a question with citations
+    ///
+    /// Short literal phrases are answered with a string search instead.
+    pub async fn query(&self, request: QueryRequest) -> Result<QueryResponse> {
+        let scope = usage_scope(&request);
+        let answer = |request| async move { self.answer(request, false).await.map(|(response, _)| response) };
+        scope.run(audit::audited(&self.state, "query", request, answer)).await
+    }
+
+    /// Answer a question, without usage scope or audit record
+    ///
+    /// With `use_cache`, a question answered before from documents that
+    /// haven't changed since is answered from the answer cache, and generated
+    /// answers are cached. The cache info is `None` for responses that bypass
+    /// the cache (lookups, string searches, corrections, refusals and debug
+    /// queries).
+    pub(crate) async fn answer(
+        &self,
+        mut request: QueryRequest,
+        use_cache: bool,
+    ) -> Result<(QueryResponse, Option<CacheInfo>)> {
+        let state = &self.state;
+        let start = Instant::now();
+
+        tracing::info!("Query: \"{}\"", request.question);
+
+        // Route by intent: documents by name, literal phrases, or questions
+        let intent = detect_intent(state, &request).await;
+        if intent == QueryType::DocumentLookup {
+            if let Some(response) = document_lookup(state, &request, start) {
+                return Ok((response, None));
+            }
+        }
+
+        // For string search queries, use literal text matching
+        if intent == QueryType::StringSearch {
+            let response = string_search_query(
+                state,
+                &request.question,
+                request.collection.as_deref(),
+                request.principal.as_ref(),
+                start,
+            )
+            .await?;
+            return Ok((response, None));
+        }
+
+        screen_query(state, &request)?;
+        check_model(state, &request)?;
+        if let Some(response) = apply_correction(state, &mut request, start).await? {
+            return Ok((response, None));
+        }
+        experiments::assign(&state.config().experiments, &mut request)?;
+        check_budget(state, &mut request)?;
+
+        // Debug queries always retrieve, for the trace
+        let doc_timestamps = (use_cache && !request.debug).then(|| state.get_document_timestamps());
+        if let Some(ref doc_timestamps) = doc_timestamps {
+            if let Some((response, cache_info)) = cached_answer(state, &request, doc_timestamps, start) {
+                return Ok((response, Some(cache_info)));
+            }
+        }
+
+        let mut trace = state.start_trace("query", &request);
+        let mut explain = request.debug.then(|| QueryExplain::new(&request));
+
+        // Retrieve candidates (expanded into several searches if requested)
+        let mut search_results = retrieve(state, &request, explain.as_mut()).await?;
+
+        if let Some(trace) = trace.as_mut() {
+            trace.record_candidates(&search_results);
+        }
+        audit::record_candidates(&search_results);
+
+        // Decline rather than answer from weak context
+        if let Some(evidence) = check_evidence(state, &request, &search_results) {
+            let processing_time_ms = start.elapsed().as_millis() as u64;
+            if let Some(trace) = trace {
+                state.record_trace(trace.finish(None, processing_time_ms));
+            }
+            let mut response = QueryResponse::insufficient_evidence(evidence, processing_time_ms);
+            response.debug = explain;
+            return Ok((response, None));
+        }
+
+        // Filter by similarity threshold
+        search_results.retain(|r| r.similarity >= request.similarity_threshold);
+
+        // Take top_k results
+        search_results.truncate(request.top_k);
+
+        if let Some(explain) = explain.as_mut() {
+            explain.record_selection(&search_results);
+        }
+
+        if search_results.is_empty() {
+            let processing_time_ms = start.elapsed().as_millis() as u64;
+            if let Some(trace) = trace {
+                state.record_trace(trace.finish(None, processing_time_ms));
+            }
+            let mut response = QueryResponse::not_found(processing_time_ms);
+            response.debug = explain;
+            return Ok((response, None));
+        }
+
+        if let Some(trace) = trace.as_mut() {
+            trace.record_selection(&search_results);
+        }
+        audit::record_selection(&search_results);
+
+        // Create citations from search results
+        let mut citations: Vec<Citation> = search_results
+            .iter()
+            .map(|r| {
+                let mut citation = Citation::from_chunk(&r.chunk, r.similarity);
+                // Highlight query terms in snippet
+                let terms: Vec<&str> = request.question.split_whitespace().collect();
+                citation.highlight_terms(&terms);
+                // Enrich with document URLs (GCS links)
+                if let Some(doc) = state.get_document(&r.chunk.document_id) {
+                    citation.enrich_with_document(&doc);
+                }
+                citation
+            })
+            .collect();
+
+        // Build context for LLM (parent windows, translated into the answer language if requested)
+        let parents = parent_context(state, &search_results);
+        let context_results = parents.as_deref().unwrap_or(&search_results);
+        let translated = translate_context(state, &request, context_results).await;
+        let context_results = translated.as_deref().unwrap_or(context_results);
+        // Batched strategies answer from every chunk, the standard one from those that fit
+        let packed = match request.answer_strategy {
+            AnswerStrategy::Standard => pack_context(state, context_results, &mut citations),
+            AnswerStrategy::MapReduce | AnswerStrategy::Refine => None,
+        };
+        if let Some(explain) = explain.as_mut() {
+            explain.record_packing(&search_results, context_results, packed.as_ref());
+        }
+        let context_results = packed.as_ref().map_or(context_results, |p| &p.results);
+        let guarded = guard_context(state, context_results);
+        let context_results = guarded.as_deref().unwrap_or(context_results);
+        let context = PromptBuilder::build_context(context_results);
+        let question = request.prompt_question();
+
+        // Find similar past Q&A for learning (only answers drawn from documents the caller can read)
+        let similar_qa = state.knowledge_store().find_similar(&request.question, 3);
+        let readable = readable_documents(state, request.principal.as_ref(), None);
+        let past_qa: Vec<(String, String)> = similar_qa
+            .iter()
+            .filter(|qa| qa.feedback_score.unwrap_or(0) >= 0 || qa.verified_answer().is_some())  // Only use positive/neutral feedback or corrected answers
+            .filter(|qa| readable.as_ref().map_or(true, |r| qa.document_ids.iter().all(|id| r.contains(id))))
+            .map(|qa| (qa.question.clone(), qa.verified_answer().unwrap_or(&qa.answer).to_string()))
+            .collect();
+
+        if let (Some(explain), AnswerStrategy::Standard) = (explain.as_mut(), request.answer_strategy) {
+            explain_prompt(state, &request, explain, &question, &context, &citations, &past_qa).await?;
+        }
+
+        // Generate answer (using provider abstraction - Ollama or Gemini), suggesting
+        // follow-up questions meanwhile
+        let generation = async {
+            match request.answer_strategy {
+                AnswerStrategy::Standard => {
+                    generate_answer(state, &request, &question, &context, &citations, &past_qa)
+                        .instrument(tracing::info_span!("generate", chunks = citations.len(), examples = past_qa.len()))
+                        .await
+                }
+                AnswerStrategy::MapReduce | AnswerStrategy::Refine => {
+                    generate_answer_in_batches(state, &request, &question, context_results, &citations)
+                        .instrument(tracing::info_span!("generate_batched", chunks = citations.len()))
+                        .await
+                }
+            }
+        };
+        let (generated, follow_ups) = futures::join!(generation, suggest_follow_ups(state, &request, &context));
+        let (answer, model) = generated?;
+
+        // Link the citations the model reported, or parse them from the answer
+        let (clean_answer, linked_citations, structured) = finish_answer(state, &request, answer, &mut citations).await;
+
+        let processing_time_ms = start.elapsed().as_millis() as u64;
+
+        let mut response = QueryResponse::new(clean_answer.clone(), linked_citations.clone(), processing_time_ms);
+        response.structured = structured;
+        response.chunks_retrieved = search_results.len();
+        if let Some(ref packed) = packed {
+            response.chunks_dropped = packed.dropped;
+            response.chunks_truncated = packed.truncated;
+        }
+        response.variant = request.variant.clone();
+        response.model = Some(model);
+        response.follow_up_questions = follow_ups;
+        response.provenance = sign_answer(state, &response, &search_results);
+        response.debug = explain;
+
+        let cache_info = doc_timestamps.map(|doc_timestamps| {
+            cache_answer(state, &request, &response, doc_timestamps);
+            CacheInfo { from_cache: false, hit_count: None }
+        });
+
+        // Store this Q&A for learning
+        let interaction = QAInteraction {
+            id: Uuid::new_v4(),
+            question: request.question.clone(),
+            answer: clean_answer,
+            citations_used: linked_citations.iter().map(|c| c.filename.clone()).collect(),
+            relevance_score: search_results.first().map(|r| r.similarity).unwrap_or(0.0),
+            feedback_score: None,  // Will be updated via feedback endpoint
+            created_at: chrono::Utc::now(),
+            document_ids: search_results.iter().map(|r| r.chunk.document_id).collect(),
+            variant: request.variant.clone(),
+            synthetic: None,
+            correction: None,
+        };
+        let interaction_id = state.knowledge_store().store_interaction(interaction);
+        response.interaction_id = Some(interaction_id);
+
+        if let Some(mut trace) = trace {
+            trace.record_citations(&linked_citations);
+            state.record_trace(trace.finish(Some(interaction_id), processing_time_ms));
+        }
+
+        // Include raw chunks if requested
+        if request.include_chunks {
+            response.raw_chunks = Some(search_results.into_iter().map(|r| r.chunk).collect());
+        }
+
+        tracing::info!(
+            "Query completed in {}ms, {} citations",
+            processing_time_ms,
+            response.citations.len()
+        );
+
+        Ok((response, cache_info))
+    }
+
+    /// Delete a document and its chunks
+    ///
+    /// Returns the removed document and the number of chunks deleted.
+    pub async fn delete_document(&self, id: &Uuid) -> Result<(Document, usize)> {
+        let doc = self
+            .state
+            .remove_document(id)
+            .ok_or_else(|| Error::DocumentNotFound(id.to_string()))?;
+
+        // Delete all chunks for this document (uses provider abstraction)
+        let deleted_chunks = self.state.vector_store_provider().delete_by_document(id).await?;
+        self.state.database().delete_pending_embeddings_for_document(id)?;
+        self.state.invalidate_cached_answers(id);
+        self.delete_stored_files(&doc).await;
+
+        tracing::info!("Deleted document '{}' and {} chunks", doc.filename, deleted_chunks);
+        self.state
+            .webhooks()
+            .emit(WebhookEvent::document(WebhookEventKind::DocumentDeleted, &doc));
+
+        Ok((doc, deleted_chunks))
+    }
+
+    /// Replace the text of a chunk (to fix a bad extraction), re-embedding it
+    /// and updating the vector store and full-text indexes
+    ///
+    /// Returns the updated chunk record.
+    pub async fn update_chunk(&self, id: &Uuid, content: &str) -> Result<ChunkContentRecord> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(Error::Config("Chunk text is empty".to_string()));
+        }
+        let record = self
+            .state
+            .database()
+            .get_chunk_content(id)?
+            .ok_or_else(|| Error::NotFound(format!("Chunk {}", id)))?;
+
+        // Keep the stored metadata (keywords, ACL, ...) where the backend has it
+        let mut chunk = self
+            .state
+            .vector_store()
+            .and_then(|store| store.get_chunk(id).ok().flatten())
+            .or_else(|| self.state.get_chunk(id))
+            .unwrap_or_else(|| {
+                let mut chunk = record.to_chunk();
+                if let Some(ref collection) = record.collection {
+                    chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), serde_json::json!(collection));
+                }
+                chunk
+            });
+        chunk.content = content.to_string();
+
+        let embeddings = self.state.embedder_for(chunk.collection()).embed_parts(&chunk.content).await?;
+        chunk.set_embeddings(embeddings);
+        chunk.set_embedding_model(self.state.embedding_model_for(chunk.collection()));
+        self.state.vector_store_provider().update_chunk(&chunk).await?;
+        self.state.store_chunks(std::slice::from_ref(&chunk));
+        self.state.invalidate_cached_answers(&chunk.document_id);
+
+        tracing::info!("Updated text of chunk {} of '{}'", id, record.filename);
+        self.state
+            .database()
+            .get_chunk_content(id)?
+            .ok_or_else(|| Error::NotFound(format!("Chunk {}", id)))
+    }
+
+    /// Delete documents with their chunks, file records, stored originals and
+    /// cached answers, or only count them for a dry run
+    ///
+    /// Vectors are removed first: when that fails nothing else has changed and
+    /// the deletion can be retried. The database rows then go in one
+    /// transaction; stored originals are removed last, best effort.
+    pub async fn delete_documents(&self, docs: Vec<Document>, dry_run: bool) -> Result<BulkDeleteResponse> {
+        let ids: HashSet<Uuid> = docs.iter().map(|doc| doc.id).collect();
+        let mut response = BulkDeleteResponse {
+            dry_run,
+            document_ids: docs.iter().map(|doc| doc.id).collect(),
+            ..Default::default()
+        };
+
+        if dry_run {
+            for doc in &docs {
+                response.chunks += self.state.database().get_chunks_count_for_document(&doc.id)?;
+                response.cached_answers += self.state.cached_answers_citing(&doc.id);
+            }
+            response.file_records = self.state.count_file_records_for(&ids);
+            if let Some(file_store) = self.state.file_store() {
+                for doc in &docs {
+                    if file_store.exists(&doc.id).await.unwrap_or(false) {
+                        response.stored_objects += 1;
+                    }
+                }
+            }
+            return Ok(response);
+        }
+
+        let vector_store = self.state.vector_store_provider();
+        for doc in &docs {
+            response.chunks += vector_store.delete_by_document(&doc.id).await?;
+        }
+        let id_list: Vec<Uuid> = ids.iter().copied().collect();
+        response.file_records = self.state.database().purge_documents(&id_list)?;
+        let removed = self.state.remove_documents(&ids);
+        for doc in &docs {
+            response.cached_answers += self.state.invalidate_cached_answers(&doc.id);
+        }
+        for doc in &docs {
+            if self.delete_stored_files(doc).await {
+                response.stored_objects += 1;
+            }
+        }
+
+        tracing::info!("Deleted {} documents and {} chunks", removed.len(), response.chunks);
+        for doc in &removed {
+            self.state
+                .webhooks()
+                .emit(WebhookEvent::document(WebhookEventKind::DocumentDeleted, doc));
+        }
+
+        Ok(response)
+    }
+
+    /// Delete a document's stored original, plain text and page thumbnails,
+    /// best effort
+    ///
+    /// Returns whether a stored original was deleted.
+    async fn delete_stored_files(&self, doc: &Document) -> bool {
+        let Some(file_store) = self.state.file_store() else {
+            return false;
+        };
+        let deleted = match file_store.delete_document(&doc.id).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to delete stored original of {}: {}", doc.filename, e);
+                false
+            }
+        };
+        let _ = file_store.delete_plain_text(&doc.id).await;
+        if let Err(e) = file_store.delete_thumbnails(&doc.id).await {
+            tracing::warn!("Failed to delete thumbnails of {}: {}", doc.filename, e);
+        }
+        deleted
+    }
+
+    pub fn get_document(&self, id: &Uuid) -> Option<Document> {
+        self.state.get_document(id)
+    }
+
+    /// The current or a superseded document, with when it was superseded
+    pub fn find_document_version(&self, id: &Uuid) -> Result<Option<(Document, Option<DateTime<Utc>>)>> {
+        if let Some(doc) = self.state.get_document(id) {
+            return Ok(Some((doc, None)));
+        }
+        let version = self.state.database().get_document_version(id)?;
+        Ok(version.map(|v| (v.document, Some(v.superseded_at))))
+    }
+
+    /// Plain text of a current or superseded document: the stored extraction
+    /// when there is one (chunk offsets refer to it), else rebuilt from its
+    /// chunks
+    pub async fn document_text(&self, doc: &Document, current: bool) -> Result<String> {
+        if let Some(file_store) = self.state.file_store() {
+            if let Some(text) = file_store.get_plain_text(&doc.id).await? {
+                return Ok(text);
+            }
+        }
+        Ok(reconstruct_text(doc, &self.version_chunks(&doc.id, current)?).content)
+    }
+
+    /// Versions of a file, newest first: the current document (while the file
+    /// is ingested) and the superseded versions kept
+    pub fn document_versions(&self, filename: &str) -> Result<Vec<(Document, Option<DateTime<Utc>>)>> {
+        let mut versions: Vec<_> = self.state.find_by_filename(filename).map(|doc| (doc, None)).into_iter().collect();
+        let superseded = self.state.database().list_document_versions(filename)?;
+        versions.extend(superseded.into_iter().map(|v| (v.document, Some(v.superseded_at))));
+        Ok(versions)
+    }
+
+    /// Line diff between the text of two versions of a file
+    ///
+    /// `to` defaults to the newest version and `from` to the version before
+    /// `to`. The text is rebuilt from the stored chunks of each version.
+    pub fn diff_document_versions(&self, filename: &str, from: Option<u32>, to: Option<u32>) -> Result<VersionDiffResponse> {
+        let versions = self.document_versions(filename)?;
+        let find = |number: u32| {
+            versions
+                .iter()
+                .find(|(doc, _)| doc.version() == number)
+                .ok_or_else(|| Error::DocumentNotFound(format!("Version {} of {}", number, filename)))
+        };
+
+        let to = match to {
+            Some(to) => to,
+            None => versions
+                .first()
+                .map(|(doc, _)| doc.version())
+                .ok_or_else(|| Error::DocumentNotFound(filename.to_string()))?,
+        };
+        let from = match from {
+            Some(from) => from,
+            None => versions
+                .iter()
+                .map(|(doc, _)| doc.version())
+                .filter(|&version| version < to)
+                .max()
+                .ok_or_else(|| Error::Config(format!("{} has no version before {}", filename, to)))?,
+        };
+        let (old, old_superseded) = find(from)?;
+        let (new, new_superseded) = find(to)?;
+        let old_text = reconstruct_text(old, &self.version_chunks(&old.id, old_superseded.is_none())?).content;
+        let new_text = reconstruct_text(new, &self.version_chunks(&new.id, new_superseded.is_none())?).content;
+
+        let diff = similar::TextDiff::from_lines(&old_text, &new_text);
+        let (mut added, mut removed) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                similar::ChangeTag::Insert => added += 1,
+                similar::ChangeTag::Delete => removed += 1,
+                similar::ChangeTag::Equal => {}
+            }
+        }
+        let unified = diff
+            .unified_diff()
+            .context_radius(3)
+            .header(&format!("{} (version {})", filename, from), &format!("{} (version {})", filename, to))
+            .to_string();
+
+        Ok(VersionDiffResponse {
+            filename: filename.to_string(),
+            from,
+            from_id: old.id,
+            to,
+            to_id: new.id,
+            added,
+            removed,
+            diff: unified,
+        })
+    }
+
+    /// Stored chunks of the current document or of a superseded version
+    fn version_chunks(&self, id: &Uuid, current: bool) -> Result<Vec<ChunkContentRecord>> {
+        if current {
+            self.state.database().list_chunks_for_document(id)
+        } else {
+            self.state.database().list_version_chunks(id)
+        }
+    }
+
+    pub fn list_documents(&self) -> Vec<Document> {
+        self.state.list_documents()
+    }
+}
+
+/// Collects per-file outcomes into an `IngestResponse`
+#[derive(Default)]
+pub(crate) struct IngestReport {
+    documents: Vec<DocumentSummary>,
+    errors: Vec<IngestError>,
+    skipped_uploads: Vec<SkippedUpload>,
+    total_chunks: u32,
+}
+
+impl IngestReport {
+    pub(crate) fn record(&mut self, filename: String, result: Result<IngestOutcome>) {
+        match result {
+            Ok(IngestOutcome::New { document, chunks } | IngestOutcome::Updated { document, chunks, .. }) => {
+                self.total_chunks += chunks;
+                self.documents.push(DocumentSummary::from(&document));
+            }
+            // Not an error, but skipped files are not listed as documents
+            Ok(IngestOutcome::Skipped { .. }) => {}
+            Err(e) => {
+                tracing::error!("Failed to process {}: {}", filename, e);
+                let error = match e {
+                    Error::FileParse { message, .. } => message,
+                    e => e.to_string(),
+                };
+                self.errors.push(IngestError { filename, error });
+            }
+        }
+    }
+
+    /// A file dropped before it was ingested
+    pub(crate) fn skip_upload(&mut self, filename: String, reason: String) {
+        tracing::info!("Skipped upload of {}: {}", filename, reason);
+        self.skipped_uploads.push(SkippedUpload { filename, reason });
+    }
+
+    pub(crate) fn finish(self, start: Instant) -> IngestResponse {
+        IngestResponse {
+            success: !self.documents.is_empty(),
+            documents: self.documents,
+            total_chunks_created: self.total_chunks,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+            errors: self.errors,
+            skipped_uploads: self.skipped_uploads,
+        }
+    }
+}
+
+/// Documents a caller may read, or `None` if nothing is hidden from it
+///
+/// Narrowed to `document_filter` when one is given. Admins and requests
+/// without a principal (authentication disabled) read everything.
+pub(crate) fn readable_documents(
+    state: &AppState,
+    principal: Option<&Principal>,
+    document_filter: Option<&[Uuid]>,
+) -> Option<HashSet<Uuid>> {
+    let hidden = hidden_documents(state, principal);
+    if hidden.is_empty() {
+        return None;
+    }
+
+    Some(match document_filter {
+        Some(filter) => filter.iter().filter(|id| !hidden.contains(id)).copied().collect(),
+        None => state.document_ids().into_iter().filter(|id| !hidden.contains(id)).collect(),
+    })
+}
+
+/// Documents whose access list excludes the caller
+///
+/// Empty for admins and requests without a principal (authentication
+/// disabled). Only restricted documents are looked at, from the cached
+/// access lists.
+pub(crate) fn hidden_documents(state: &AppState, principal: Option<&Principal>) -> HashSet<Uuid> {
+    let Some(principal) = principal.filter(|p| !p.admin) else {
+        return HashSet::new();
+    };
+    state
+        .document_acls()
+        .iter()
+        .filter(|(_, acl)| !principal.can_read_acl(acl))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Whether a tracked file belongs to one of the `hidden` documents
+pub(crate) fn hides_file(state: &AppState, hidden: &HashSet<Uuid>, filename: &str) -> bool {
+    !hidden.is_empty()
+        && state
+            .get_file_record(filename)
+            .and_then(|record| record.document_id)
+            .is_some_and(|id| hidden.contains(&id))
+}
+
+/// Documents embedded with `model`, or `None` if all collections share one model
+///
+/// Narrowed to `document_filter` when one is given.
+fn documents_embedded_with(state: &AppState, model: &str, document_filter: Option<&[Uuid]>) -> Option<Vec<Uuid>> {
+    state.embedders().collections().next()?;
+
+    Some(
+        state
+            .list_documents()
+            .iter()
+            .filter(|doc| state.embedding_model_for(doc.collection()) == model)
+            .map(|doc| doc.id)
+            .filter(|id| document_filter.map_or(true, |filter| filter.contains(id)))
+            .collect(),
+    )
+}
+
+/// Documents of `collection`, or `None` if the query isn't limited to one
+///
+/// Narrowed to `document_filter` when one is given.
+fn documents_in_collection(
+    state: &AppState,
+    collection: Option<&str>,
+    document_filter: Option<&[Uuid]>,
+) -> Option<Vec<Uuid>> {
+    let collection = collection?;
+
+    Some(
+        state
+            .list_documents()
+            .iter()
+            .filter(|doc| doc.collection() == Some(collection))
+            .map(|doc| doc.id)
+            .filter(|id| document_filter.map_or(true, |filter| filter.contains(id)))
+            .collect(),
+    )
+}
+
+/// Retrieve candidate chunks for a query
+///
+/// Searches for `top_k * 2` chunks per expanded query and enriches minimal
+/// chunks (Vertex AI returns ids only) from the local store. With an email
+/// or entity filter, more candidates are searched and non-matching chunks
+/// dropped. Chunks whose extracted keywords match the question get a small
+/// boost, as do chunks of recently ingested documents (`retrieval.recency`),
+/// and chunks linked in the knowledge graph to entities in the question or
+/// whose generated questions match the query are added. With a per-document limit, more candidates are searched and only
+/// the best `max_chunks_per_document` chunks of each document kept. Only
+/// documents the request's principal can read, of the request's collection
+/// if it names one, and whose collection is embedded with the query's model
+/// are searched; the query is refused while chunks embedded with an outdated
+/// model remain.
+///
+/// Timings, candidates, scores and filters are recorded in `explain`.
+pub(crate) async fn retrieve(
+    state: &AppState,
+    request: &QueryRequest,
+    mut explain: Option<&mut QueryExplain>,
+) -> Result<Vec<VectorSearchResult>> {
+    if let Some(version) = request.document_version.filter(|id| state.get_document(id).is_none()) {
+        let start = Instant::now();
+        let results = retrieve_version(state, request, &version).await?;
+        if let Some(explain) = explain {
+            // The version's chunks are embedded along with the question
+            explain.record_search(std::slice::from_ref(&request.question), start.elapsed().as_millis() as u64, 0);
+            explain.add_filter("document_version", format!("superseded version {}", version));
+            explain.record_candidates(&results);
+        }
+        return Ok(results);
+    }
+
+    let collection = request.collection.as_deref();
+    state.check_embedding_model(collection)?;
+    let model = state.embedding_model_for(collection);
+
+    // A version that is the current document narrows the search to it
+    let version_filter = request.document_version.map(|id| vec![id]);
+    let requested = version_filter.as_deref().or(request.document_filter.as_deref());
+
+    let mut allowed = readable_documents(state, request.principal.as_ref(), requested);
+    let narrowing = [
+        documents_embedded_with(state, model, requested),
+        documents_in_collection(state, collection, requested),
+    ];
+    for ids in narrowing.into_iter().flatten() {
+        allowed = Some(match allowed {
+            Some(allowed) => ids.into_iter().filter(|id| allowed.contains(id)).collect(),
+            None => ids.into_iter().collect(),
+        });
+    }
+    let readable: Option<Vec<Uuid>> = allowed.map(|ids| ids.into_iter().collect());
+    if readable.as_ref().is_some_and(Vec::is_empty) {
+        return Ok(Vec::new());
+    }
+    let document_filter = readable.as_deref().or(requested);
+
+    let queries = expansion::expand(state.llm_provider().as_ref(), &request.question, request.retrieval_strategy).await;
+
+    let per_document = request.max_chunks_per_document.or(state.config().retrieval.max_chunks_per_document);
+    if per_document == Some(0) {
+        return Err(Error::Config("max_chunks_per_document must be greater than 0".to_string()));
+    }
+    let filtered = request.email_filter.is_some()
+        || request.metadata_filter.is_some()
+        || !request.entities.is_empty()
+        || per_document.is_some();
+    let oversample = if filtered { EMAIL_FILTER_OVERSAMPLE } else { 1 };
+    let embedding_start = Instant::now();
+    let embeddings = expansion::embed_queries(state.embedder_for(collection).as_ref(), &queries).await?;
+    let search_start = Instant::now();
+    let mut search_results = expansion::search_union(
+        state.vector_store_provider().as_ref(),
+        &embeddings,
+        request.top_k * 2 * oversample, // Get more for filtering
+        document_filter,
+    )
+    .await?;
+    if let Some(explain) = explain.as_mut() {
+        explain.record_search(
+            &queries,
+            search_start.duration_since(embedding_start).as_millis() as u64,
+            search_start.elapsed().as_millis() as u64,
+        );
+        if let Some(ids) = document_filter {
+            explain.add_filter("documents", format!("search limited to {} documents", ids.len()));
+        }
+    }
+
+    for result in &mut search_results {
+        if result.chunk.content.is_empty() || result.chunk.document_id.is_nil() {
+            if let Some(mut full_chunk) = state.get_chunk(&result.chunk.id) {
+                tracing::debug!("Enriched minimal chunk {} from local store", result.chunk.id);
+                full_chunk.metadata.extend(std::mem::take(&mut result.chunk.metadata));
+                result.chunk = full_chunk;
+            } else {
+                tracing::warn!("Chunk {} not found in local store, using minimal data", result.chunk.id);
+            }
+        }
+    }
+
+    graph::augment_results(state, &request.question, document_filter, &mut search_results);
+    qa_generation::augment_results(state, &embeddings, model, document_filter, &mut search_results);
+    if let Some(explain) = explain.as_mut() {
+        explain.record_candidates(&search_results);
+    }
+
+    // Chunks of unreadable, unknown, other collections' or differently embedded documents never reach the
+    // prompt, whatever the store's filtering
+    if let Some(ref readable) = readable {
+        search_results.retain(|r| readable.contains(&r.chunk.document_id));
+        if let Some(explain) = explain.as_mut() {
+            let detail = match collection {
+                Some(collection) => {
+                    format!("documents of {} readable by the caller and embedded with {}", collection, model)
+                }
+                None => format!("documents readable by the caller and embedded with {}", model),
+            };
+            explain.record_filter("access", detail, &search_results);
+        }
+    }
+
+    if let Some(ref filter) = request.email_filter {
+        search_results.retain(|r| filter.matches(&r.chunk));
+        if let Some(explain) = explain.as_mut() {
+            explain.record_filter("email", serde_json::to_string(filter).unwrap_or_default(), &search_results);
+        }
+    }
+
+    if let Some(ref filter) = request.metadata_filter {
+        search_results.retain(|r| filter.matches(&r.chunk));
+        if let Some(explain) = explain.as_mut() {
+            explain.record_filter("metadata", serde_json::to_string(filter).unwrap_or_default(), &search_results);
+        }
+    }
+
+    if !request.entities.is_empty() {
+        let tagged = state.database().chunk_ids_with_entities(&request.entities)?;
+        search_results.retain(|r| tagged.contains(&r.chunk.id));
+        if let Some(explain) = explain.as_mut() {
+            explain.record_filter("entities", request.entities.join(", "), &search_results);
+        }
+    }
+
+    let keyword_scores = boost_keyword_matches(state, &request.question, &mut search_results);
+    boost_recent_documents(state, &mut search_results);
+
+    if let Some(max) = per_document {
+        limit_per_document(&mut search_results, max);
+        if let Some(explain) = explain.as_mut() {
+            explain.record_filter("per_document", format!("at most {} chunks per document", max), &search_results);
+        }
+    }
+
+    if let Some(explain) = explain {
+        explain.record_scores(&keyword_scores, &search_results);
+    }
+
+    Ok(search_results)
+}
+
+/// Retrieve candidate chunks from a superseded document version
+///
+/// Superseded versions keep their chunk text but not their vectors: the
+/// chunks are embedded with the collection's model and ranked against the
+/// question, best `top_k * 2` first.
+async fn retrieve_version(state: &AppState, request: &QueryRequest, id: &Uuid) -> Result<Vec<VectorSearchResult>> {
+    let version = state
+        .database()
+        .get_document_version(id)?
+        .filter(|version| can_read(request.principal.as_ref(), &version.document))
+        .ok_or_else(|| Error::DocumentNotFound(id.to_string()))?;
+    let records = state.database().list_version_chunks(id)?;
+    if records.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let embedder = state.embedder_for(version.document.collection());
+    let texts: Vec<String> = records.iter().map(|record| record.content.clone()).collect();
+    let embeddings = embedder.embed_batch(&texts).await?;
+    let question = embedder.embed(&request.question).await?;
+    let question = CosineQuery::new(&question);
+
+    let mut results: Vec<VectorSearchResult> = records
+        .iter()
+        .zip(&embeddings)
+        .map(|(record, embedding)| {
+            let mut chunk = record.to_chunk();
+            // Title, author, tags and date for the metadata filter and citations
+            for key in SOURCE_METADATA_KEYS {
+                if let Some(value) = version.document.metadata.get(key) {
+                    chunk.metadata.insert(key.to_string(), value.clone());
+                }
+            }
+            VectorSearchResult::new(chunk, question.score(embedding))
+        })
+        .collect();
+    if let Some(ref filter) = request.email_filter {
+        results.retain(|r| filter.matches(&r.chunk));
+    }
+    if let Some(ref filter) = request.metadata_filter {
+        results.retain(|r| filter.matches(&r.chunk));
+    }
+    results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    results.truncate(request.top_k * 2);
+    Ok(results)
+}
+
+/// Raise the similarity of chunks whose keywords or entities match the
+/// question by up to `keywords.boost`, relative to the best match
+///
+/// Returns the BM25 keyword scores of the matching chunks.
+fn boost_keyword_matches(state: &AppState, question: &str, results: &mut [VectorSearchResult]) -> HashMap<Uuid, f64> {
+    let rag_config = state.config();
+    let config = &rag_config.keywords;
+    if !config.enabled || config.boost <= 0.0 || results.is_empty() {
+        return HashMap::new();
+    }
+
+    let ids: Vec<Uuid> = results.iter().map(|r| r.chunk.id).collect();
+    let scores = match state.database().keyword_scores(question, &ids) {
+        Ok(scores) => scores,
+        Err(e) => {
+            tracing::warn!("Keyword scoring failed: {}", e);
+            return HashMap::new();
+        }
+    };
+    let best = scores.values().copied().fold(0.0_f64, f64::max);
+    if best <= 0.0 {
+        return scores;
+    }
+
+    for result in results.iter_mut() {
+        if let Some(score) = scores.get(&result.chunk.id) {
+            result.similarity += config.boost * (score / best).max(0.0) as f32;
+        }
+    }
+    results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    scores
+}
+
+/// Raise the similarity of chunks by the recency boost of their document,
+/// by when it was last modified at its source (or ingested) and the
+/// half-life of its collection
+fn boost_recent_documents(state: &AppState, results: &mut [VectorSearchResult]) {
+    let config = state.config();
+    let recency = &config.retrieval.recency;
+    if recency.boost <= 0.0 || results.is_empty() {
+        return;
+    }
+
+    let now = chrono::Utc::now();
+    let mut boosts: HashMap<Uuid, f32> = HashMap::new();
+    for result in results.iter_mut() {
+        let document_id = result.chunk.document_id;
+        let boost = *boosts.entry(document_id).or_insert_with(|| {
+            state.get_document(&document_id).map_or(0.0, |document| {
+                let age_days = (now - document.updated_at()).num_seconds() as f64 / 86_400.0;
+                recency.boost_for(document.collection(), age_days)
+            })
+        });
+        result.similarity += boost;
+    }
+    results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+}
+
+/// Keep the `max` best chunks of each document (results are sorted by score)
+fn limit_per_document(results: &mut Vec<VectorSearchResult>, max: usize) {
+    let mut counts: HashMap<Uuid, usize> = HashMap::new();
+    results.retain(|r| {
+        let count = counts.entry(r.chunk.document_id).or_insert(0);
+        *count += 1;
+        *count <= max
+    });
+}
+
+/// Near misses of a question whose best candidate scores below the answer
+/// threshold of its collection, if it has one
+///
+/// `None` means the question may be answered (or that nothing was retrieved
+/// at all, which is reported as not found). Scores are taken before the
+/// keyword and recency boosts, which rank matches but are no evidence.
+fn check_evidence(
+    state: &AppState,
+    request: &QueryRequest,
+    candidates: &[VectorSearchResult],
+) -> Option<InsufficientEvidence> {
+    let threshold = state.config().retrieval.answer_threshold(request.collection.as_deref())?;
+    let best_score = candidates.iter().map(|r| r.raw_similarity).reduce(f32::max)?;
+    if best_score >= threshold {
+        return None;
+    }
+
+    tracing::info!(
+        "Not answering: best match scores {:.3}, below the answer threshold {:.3}",
+        best_score,
+        threshold
+    );
+    let mut near_misses: Vec<&VectorSearchResult> = candidates.iter().collect();
+    near_misses.sort_by(|a, b| b.raw_similarity.total_cmp(&a.raw_similarity));
+    near_misses.truncate(request.top_k);
+    let near_misses = near_misses
+        .into_iter()
+        .map(|r| {
+            let mut citation = Citation::from_chunk(&r.chunk, r.raw_similarity);
+            if let Some(doc) = state.get_document(&r.chunk.document_id) {
+                citation.enrich_with_document(&doc);
+            }
+            citation
+        })
+        .collect();
+    Some(InsufficientEvidence { threshold, best_score, near_misses })
+}
+
+/// Replace chunks with their parent windows for the prompt (small-to-big retrieval)
+///
+/// Returns `None` unless `retrieval.parent_window` is set. Results stay in
+/// place so source numbers still match citations; a chunk whose parent is
+/// already in the context keeps its own text.
+fn parent_context(state: &AppState, search_results: &[VectorSearchResult]) -> Option<Vec<VectorSearchResult>> {
+    state.config().retrieval.parent_window.filter(|&w| w > 0)?;
+
+    let mut seen = HashSet::new();
+    let expanded = search_results
+        .iter()
+        .map(|result| {
+            let mut result = result.clone();
+            let Some(parent_id) = result.chunk.parent_id.filter(|id| seen.insert(*id)) else {
+                return result;
+            };
+
+            match state.database().list_chunks_for_parent(&parent_id) {
+                Ok(siblings) if !siblings.is_empty() => {
+                    let parts: Vec<&str> = siblings.iter().map(|c| c.content.as_str()).collect();
+                    result.chunk.content = merge_overlapping(&parts);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to load parent window of chunk {}: {}", result.chunk.id, e),
+            }
+            result
+        })
+        .collect();
+
+    Some(expanded)
+}
+
+/// Convert legacy and externally parsed formats to something the pipeline reads
+async fn convert(state: &AppState, filename: &str, data: &[u8]) -> Result<(String, Vec<u8>)> {
+    let text_filename = || {
+        let stem = Path::new(filename)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("document");
+        format!("{}.txt", stem)
+    };
+
+    let external_parser = state.external_parser();
+    if external_parser.has_plugin(filename) {
+        // Parser plugins run first in the escalation chain
+        let characteristics = external_parser.analyze_file(filename, data);
+        let result = external_parser.parse_with_full_escalation(filename, data, &characteristics).await?;
+        tracing::info!("Parsed {} with '{}' ({} chars)", filename, result.method, result.content.len());
+        Ok((text_filename(), result.content.into_bytes()))
+    } else if ExternalParser::needs_conversion(filename) {
+        tracing::info!("Converting legacy format: {}", filename);
+        match convert_legacy_format(state, filename, data).await {
+            Ok(converted) => Ok(converted),
+            Err(e) => {
+                tracing::warn!("Conversion failed, trying external parsing: {}", e);
+                // Fall back to external parsing
+                let content = parse_with_external(state, filename, data)
+                    .await
+                    .map_err(|e2| Error::FileParse {
+                        filename: filename.to_string(),
+                        message: format!("Failed to process legacy format: {} / {}", e, e2),
+                    })?;
+                Ok((text_filename(), content.into_bytes()))
+            }
+        }
+    } else if ExternalParser::needs_external_parsing(filename) {
+        // Use external API for other unsupported formats
+        let content = parse_with_external(state, filename, data)
+            .await
+            .map_err(|e| Error::FileParse {
+                filename: filename.to_string(),
+                message: format!("External parsing failed: {}", e),
+            })?;
+        Ok((text_filename(), content.into_bytes()))
+    } else {
+        Ok((filename.to_string(), data.to_vec()))
+    }
+}
+
+/// How to handle a query: as the request says, else as classified from
+/// its text (asking the LLM about short phrases if `retrieval.intent_llm`)
+async fn detect_intent(state: &AppState, request: &QueryRequest) -> QueryType {
+    if let Some(intent) = request.intent {
+        return intent;
+    }
+    let llm = state.config().retrieval.intent_llm.then(|| state.llm_provider().as_ref());
+    intent::classify(llm, &request.question).await
+}
+
+/// Documents the caller can read whose filename contains the name a lookup
+/// asks for
+///
+/// `None` when nothing matches, so the query is answered as a question.
+pub(crate) fn document_lookup(state: &AppState, request: &QueryRequest, start: Instant) -> Option<QueryResponse> {
+    let target = QueryType::lookup_target(&request.question).unwrap_or_else(|| request.question.trim().to_string());
+    // "Q3 budget" finds "q3_budget.xlsx"
+    let normalize = |name: &str| name.to_lowercase().replace(['_', '-'], " ");
+    let needle = normalize(&target);
+
+    let mut documents: Vec<Document> = state
+        .list_documents()
+        .into_iter()
+        .filter(|doc| can_read(request.principal.as_ref(), doc))
+        .filter(|doc| request.collection.as_deref().map_or(true, |c| doc.collection() == Some(c)))
+        .filter(|doc| normalize(&doc.filename).contains(&needle))
+        .collect();
+    if documents.is_empty() {
+        tracing::info!("No document named \"{}\", answering as a question", target);
+        return None;
+    }
+    documents.sort_by(|a, b| b.ingested_at.cmp(&a.ingested_at));
+
+    tracing::info!("Document lookup \"{}\": {} documents", target, documents.len());
+    let documents = documents.iter().map(DocumentSummary::from).collect();
+    Some(QueryResponse::documents_found(&target, documents, start.elapsed().as_millis() as u64))
+}
+
+/// Answer from a verified correction of the same question
+///
+/// A corrected answer is returned as is, flagged `curated`, citing the best
+/// chunks of the corrected documents (or of the documents the original
+/// answer drew on). A correction naming only documents narrows retrieval to
+/// them and returns `None`, as does a correction drawn from documents the
+/// caller can't read.
+async fn apply_correction(
+    state: &AppState,
+    request: &mut QueryRequest,
+    start: Instant,
+) -> Result<Option<QueryResponse>> {
+    let Some(corrected) = state.knowledge_store().find_correction(&request.question) else {
+        return Ok(None);
+    };
+    let Some(correction) = corrected.correction.as_ref() else {
+        return Ok(None);
+    };
+
+    let sources = if correction.document_ids.is_empty() { &corrected.document_ids } else { &correction.document_ids };
+    let readable: Vec<Uuid> = sources
+        .iter()
+        .filter_map(|id| state.get_document(id))
+        .filter(|doc| can_read(request.principal.as_ref(), doc))
+        .filter(|doc| request.collection.as_deref().map_or(true, |c| doc.collection() == Some(c)))
+        .map(|doc| doc.id)
+        .collect();
+    if readable.is_empty() && !sources.is_empty() {
+        return Ok(None);
+    }
+
+    let Some(answer) = corrected.verified_answer() else {
+        if request.document_filter.is_none() {
+            tracing::info!("Searching {} documents named by a correction", readable.len());
+            request.document_filter = Some(readable);
+        }
+        return Ok(None);
+    };
+
+    let mut citations = Vec::new();
+    if !readable.is_empty() {
+        let mut cited = request.clone();
+        cited.document_filter = Some(readable);
+        let mut results = retrieve(state, &cited, None).await?;
+        results.truncate(request.top_k);
+        citations = results
+            .iter()
+            .map(|r| {
+                let mut citation = Citation::from_chunk(&r.chunk, r.similarity);
+                if let Some(doc) = state.get_document(&r.chunk.document_id) {
+                    citation.enrich_with_document(&doc);
+                }
+                citation
+            })
+            .collect();
+    }
+
+    tracing::info!("Answering with the verified correction of interaction {}", corrected.id);
+    let mut response = QueryResponse::new(answer.to_string(), citations, start.elapsed().as_millis() as u64);
+    response.interaction_id = Some(corrected.id);
+    response.curated = true;
+    Ok(Some(response))
+}
+
+/// Response from the answer cache, if the question was answered before
+/// from documents that haven't changed since
+fn cached_answer(
+    state: &AppState,
+    request: &QueryRequest,
+    doc_timestamps: &HashMap<Uuid, DateTime<Utc>>,
+    start: Instant,
+) -> Option<(QueryResponse, CacheInfo)> {
+    let cached = state.answer_cache().get(&request.cache_key(), doc_timestamps)?;
+    tracing::info!("Cache hit for query");
+
+    let citations: Vec<Citation> = cached
+        .citations
+        .iter()
+        .map(|c| Citation {
+            chunk_id: c.chunk_id,
+            document_id: c.document_id,
+            filename: c.filename.clone(),
+            title: c.title.clone(),
+            date: c.date.clone(),
+            file_type: crate::types::FileType::Unknown,
+            page_number: None,
+            section_title: None,
+            line_start: None,
+            line_end: None,
+            start_seconds: None,
+            end_seconds: None,
+            snippet: c.snippet.clone(),
+            snippet_highlighted: c.snippet.clone(),
+            similarity_score: c.similarity_score,
+            rerank_score: None,
+            document_url: None,
+            plaintext_url: None,
+            location: None,
+        })
+        .collect();
+
+    // JSON answers are cached as their structure
+    let structured = match request.format {
+        AnswerFormat::Json => serde_json::from_str::<StructuredAnswer>(&cached.answer).ok(),
+        AnswerFormat::Markdown | AnswerFormat::Plain => None,
+    };
+    let answer = structured.as_ref().map_or_else(|| cached.answer.clone(), |s| s.answer.clone());
+    let mut response = QueryResponse::new(answer, citations, start.elapsed().as_millis() as u64);
+    response.structured = structured;
+    response.chunks_retrieved = cached.citations.len();
+    response.chunks_used = cached.citations.len();
+    response.variant = request.variant.clone();
+
+    Some((response, CacheInfo { from_cache: true, hit_count: Some(cached.hit_count) }))
+}
+
+/// Cache a generated answer with the citations it links
+fn cache_answer(
+    state: &AppState,
+    request: &QueryRequest,
+    response: &QueryResponse,
+    doc_timestamps: HashMap<Uuid, DateTime<Utc>>,
+) {
+    let citations: Vec<CachedCitation> = response
+        .citations
+        .iter()
+        .map(|c| CachedCitation {
+            chunk_id: c.chunk_id,
+            document_id: c.document_id,
+            filename: c.filename.clone(),
+            title: c.title.clone(),
+            date: c.date.clone(),
+            snippet: c.snippet.clone(),
+            similarity_score: c.similarity_score,
+        })
+        .collect();
+    let answer = match response.structured {
+        Some(ref structured) => serde_json::to_string(structured).unwrap_or_else(|_| response.answer.clone()),
+        None => response.answer.clone(),
+    };
+    state.answer_cache().put(&request.cache_key(), answer, citations, doc_timestamps);
+}
+
+/// Handle string search queries (literal text matching)
+async fn string_search_query(
+    state: &AppState,
+    query: &str,
+    collection: Option<&str>,
+    principal: Option<&Principal>,
+    start: Instant,
+) -> Result<QueryResponse> {
+    tracing::info!("String search: \"{}\"", query);
+
+    // Perform literal string search (uses SQLite FTS for GCP, HNSW for local)
+    let mut results = state.vector_store_provider().string_search(query, 10, collection, StringSearchMode::Exact).await?;
+    if let Some(readable) = readable_documents(state, principal, None) {
+        results.retain(|r| readable.contains(&r.document_id));
+    }
+
+    let processing_time_ms = start.elapsed().as_millis() as u64;
+
+    if results.is_empty() {
+        let mut response = QueryResponse::not_found(processing_time_ms);
+        response.intent = QueryType::StringSearch;
+        return Ok(response);
+    }
+
+    // Build citations from string search results
+    let citations: Vec<Citation> = results
+        .iter()
+        .map(|r| {
+            let mut citation = Citation {
+                chunk_id: r.chunk_id,
+                document_id: r.document_id,
+                filename: r.filename.clone(),
+                title: None,
+                date: None,
+                file_type: r.file_type.clone(),
+                page_number: r.page_number,
+                section_title: None,
+                line_start: None,
+                line_end: None,
+                start_seconds: None,
+                end_seconds: None,
+                snippet: r.preview.clone(),
+                snippet_highlighted: r.highlighted_snippet.clone(),
+                similarity_score: 1.0, // Exact match
+                rerank_score: None,
+                document_url: None,
+                plaintext_url: None,
+                location: None,
+            };
+            if let Some(doc) = state.get_document(&r.document_id) {
+                citation.enrich_with_document(&doc);
+            }
+            citation
+        })
+        .collect();
+
+    // Build answer summarizing string search results
+    let total_matches: usize = results.iter().map(|r| r.match_count).sum();
+    let unique_docs: std::collections::HashSet<Uuid> = results.iter().map(|r| r.document_id).collect();
+
+    let answer = format!(
+        "Found {} occurrences of \"{}\" across {} document(s).",
+        total_matches, query, unique_docs.len()
+    );
+
+    let mut response = QueryResponse::new(answer, citations, processing_time_ms);
+    response.chunks_retrieved = results.len();
+    response.chunks_used = results.len();
+    response.intent = QueryType::StringSearch;
+
+    tracing::info!(
+        "String search completed in {}ms, {} matches across {} docs",
+        processing_time_ms,
+        total_matches,
+        unique_docs.len()
+    );
+
+    Ok(response)
+}
+
+/// Retrieved chunks translated into the requested answer language
+///
+/// Returns `None` unless the request asks for translation. Chunks from
+/// documents already in the target language are left as they are, chunks that
+/// fail to translate are used untranslated.
+pub(crate) async fn translate_context(
+    state: &AppState,
+    request: &QueryRequest,
+    search_results: &[VectorSearchResult],
+) -> Option<Vec<VectorSearchResult>> {
+    let language = request.language.as_deref().filter(|_| request.translate_context)?;
+    let target = language::resolve(language);
+    let llm = state.llm_provider();
+
+    let translations = search_results.iter().map(|result| async move {
+        let mut result = result.clone();
+        let source = state
+            .get_document(&result.chunk.document_id)
+            .and_then(|doc| doc.language().and_then(whatlang::Lang::from_code));
+        if source.is_some() && source == target {
+            return result;
+        }
+
+        let prompt = PromptBuilder::build_translation_prompt(&result.chunk.content, language);
+        match llm.complete(&prompt).await {
+            Ok(translated) => result.chunk.content = translated,
+            Err(e) => tracing::warn!("Failed to translate chunk {}: {}", result.chunk.id, e),
+        }
+        result
+    });
+
+    Some(
+        futures::future::join_all(translations)
+            .instrument(tracing::info_span!("translate_context", chunks = search_results.len()))
+            .await,
+    )
+}
+
+/// Generate an answer with the model and prompt of the request's variant
+///
+/// A prompt template named by the request takes precedence over the
+/// variant's; otherwise the collection's or default template is used when
+/// configured. Without either the built-in prompts are used. Past Q&A
+/// examples are given to templates as `history`. Returns the answer and the
+/// model that wrote it (a fallback when the chosen model failed).
+pub(crate) async fn generate_answer(
+    state: &AppState,
+    request: &QueryRequest,
+    question: &str,
+    context: &str,
+    citations: &[Citation],
+    past_qa: &[(String, String)],
+) -> Result<(GeneratedAnswer, String)> {
+    let config = state.config();
+    let variant = request.variant.as_deref().and_then(|name| config.experiments.variant(name));
+    let llm = state.answer_llm(answer_model(request, variant));
+    let answer = prompt_and_generate(state, request, variant, &llm, question, context, citations, past_qa).await?;
+    Ok((answer, llm.answered_by()))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn prompt_and_generate(
+    state: &AppState,
+    request: &QueryRequest,
+    variant: Option<&VariantConfig>,
+    llm: &dyn LlmProvider,
+    question: &str,
+    context: &str,
+    citations: &[Citation],
+    past_qa: &[(String, String)],
+) -> Result<GeneratedAnswer> {
+
+    if let Some(prompt) = template_prompt(state, request, variant, question, context, citations, past_qa)? {
+        audit::record_prompt(&prompt);
+        return Ok(llm.complete(&prompt).await?.into());
+    }
+    if audit::is_recording() {
+        audit::record_prompt(&llm.answer_prompt(question, context, citations, past_qa));
+    }
+    if !past_qa.is_empty() {
+        tracing::info!("Using {} learned examples for better answer", past_qa.len());
+    }
+    llm.generate_cited(question, context, citations, past_qa).await
+}
+
+/// Prompt from the variant's template or a configured one, if either applies
+fn template_prompt(
+    state: &AppState,
+    request: &QueryRequest,
+    variant: Option<&VariantConfig>,
+    question: &str,
+    context: &str,
+    citations: &[Citation],
+    past_qa: &[(String, String)],
+) -> Result<Option<String>> {
+    if request.prompt_template.is_none() {
+        if let Some(template) = variant.and_then(|v| v.prompt_template.as_deref()) {
+            return Ok(Some(PromptBuilder::render_template(template, question, context, citations)));
+        }
+    }
+    if let Some(template) = state
+        .prompts()
+        .select(request.prompt_template.as_deref(), request.collection.as_deref())?
+    {
+        let mut vars = PromptVars {
+            context: context.to_string(),
+            question: question.to_string(),
+            sources: PromptBuilder::format_sources_list(citations),
+            history: past_qa
+                .iter()
+                .map(|(question, answer)| HistoryEntry { question: question.clone(), answer: answer.clone() })
+                .collect(),
+            metadata: request.prompt_metadata.clone().into_iter().collect(),
+        };
+        vars.metadata.insert("collection".to_string(), request.collection.clone().into());
+        vars.metadata.insert("language".to_string(), request.language.clone().into());
+        tracing::debug!("Answering with prompt template '{}'", template.name);
+        return Ok(Some(state.prompts().render(&template, &vars)?));
+    }
+    Ok(None)
+}
+
+/// Record the prompt of a standard answer in a debug explanation
+///
+/// PII is masked with the collection's redaction policy unless
+/// `traces.debug_prompt` is `full`; nothing is recorded with `omit`.
+async fn explain_prompt(
+    state: &AppState,
+    request: &QueryRequest,
+    explain: &mut QueryExplain,
+    question: &str,
+    context: &str,
+    citations: &[Citation],
+    past_qa: &[(String, String)],
+) -> Result<()> {
+    let config = state.config();
+    let mode = config.traces.debug_prompt;
+    if mode == DebugPrompt::Omit {
+        return Ok(());
+    }
+
+    let variant = request.variant.as_deref().and_then(|name| config.experiments.variant(name));
+    let prompt = match template_prompt(state, request, variant, question, context, citations, past_qa)? {
+        Some(prompt) => prompt,
+        None => state
+            .answer_llm(answer_model(request, variant))
+            .answer_prompt(question, context, citations, past_qa),
+    };
+    let redacted = mode == DebugPrompt::Redacted;
+    let prompt = if redacted {
+        state.redactor().redact_text(request.collection.as_deref(), &prompt).await
+    } else {
+        prompt
+    };
+    explain.record_prompt(prompt, redacted);
+    Ok(())
+}
+
+/// Link the citations of a generated answer and put it in the requested format
+///
+/// The citations the model reported are linked, or parsed from the answer;
+/// `json` replies are read first. Markup is stripped from `plain` and `json`
+/// answers, which are then redacted. Returns the answer, its citations and,
+/// for `json`, the structured answer.
+async fn finish_answer(
+    state: &AppState,
+    request: &QueryRequest,
+    mut answer: GeneratedAnswer,
+    citations: &mut Vec<Citation>,
+) -> (String, Vec<Citation>, Option<StructuredAnswer>) {
+    let bullet_points = (request.format == AnswerFormat::Json).then(|| format::read_json_reply(&mut answer, citations.len()));
+
+    let (clean_answer, linked_citations) = crate::generation::citation::link_generated_citations(&answer, citations);
+    let clean_answer = match request.format {
+        AnswerFormat::Markdown => clean_answer,
+        AnswerFormat::Plain | AnswerFormat::Json => format::strip_markdown(&clean_answer),
+    };
+    let redactor = state.redactor();
+    let collection = request.collection.as_deref();
+    let clean_answer = redactor.redact_answer(collection, clean_answer).await;
+
+    let structured = match bullet_points {
+        Some(points) => {
+            let mut redacted = Vec::with_capacity(points.len());
+            for point in points {
+                redacted.push(redactor.redact_answer(collection, point).await);
+            }
+            Some(StructuredAnswer::new(clean_answer.clone(), redacted, &linked_citations))
+        }
+        None => None,
+    };
+    (clean_answer, linked_citations, structured)
+}
+
+/// Follow-up questions the context answers, if `follow_ups` is enabled
+pub(crate) async fn suggest_follow_ups(state: &AppState, request: &QueryRequest, context: &str) -> Vec<String> {
+    let config = state.config();
+    if !config.follow_ups.enabled {
+        return Vec::new();
+    }
+    follow_ups::suggest(
+        state.llm_provider().as_ref(),
+        &request.question,
+        context,
+        config.follow_ups.count,
+        request.language.as_deref(),
+    )
+    .await
+}
+
+/// Answer from batches of the context chunks (map_reduce and refine strategies)
+///
+/// Chunks are split into consecutive batches that each fit the context
+/// budget. Map-reduce answers the batches separately and merges the partial
+/// answers; refine answers the first batch and revises the answer with each
+/// further one. Both use the variant's model but always the built-in prompts.
+/// Returns the answer and the model that wrote its last part.
+async fn generate_answer_in_batches(
+    state: &AppState,
+    request: &QueryRequest,
+    question: &str,
+    context_results: &[VectorSearchResult],
+    citations: &[Citation],
+) -> Result<(GeneratedAnswer, String)> {
+    let config = state.config();
+    let variant = request.variant.as_deref().and_then(|name| config.experiments.variant(name));
+    let llm = state.answer_llm(answer_model(request, variant));
+    let answer = generate_batches(state, request, &llm, question, context_results, citations).await?;
+    Ok((answer.into(), llm.answered_by()))
+}
+
+async fn generate_batches(
+    state: &AppState,
+    request: &QueryRequest,
+    llm: &dyn LlmProvider,
+    question: &str,
+    context_results: &[VectorSearchResult],
+    citations: &[Citation],
+) -> Result<String> {
+    let config = state.config();
+
+    let budget = config.context.budget(&config.backend, &config.llm).unwrap_or(usize::MAX);
+    let batches: Vec<(String, Vec<Citation>)> = ContextPacker::new(&config.context, budget)
+        .batches(context_results, config.context.batch_chunks)
+        .into_iter()
+        .map(|batch| {
+            let citations = batch.kept.iter().filter_map(|&i| citations.get(i).cloned()).collect();
+            (PromptBuilder::build_context(&batch.results), citations)
+        })
+        .collect();
+    tracing::info!(
+        "Answering from {} chunks in {} batches ({})",
+        context_results.len(), batches.len(), request.answer_strategy.as_str()
+    );
+
+    if request.answer_strategy == AnswerStrategy::Refine {
+        let mut answer: Option<String> = None;
+        for (context, citations) in &batches {
+            answer = Some(match answer {
+                None => llm.generate_answer(question, context, citations).await?,
+                Some(answer) if answer.trim().starts_with(NOT_IN_DOCUMENTS) => {
+                    llm.generate_answer(question, context, citations).await?
+                }
+                Some(answer) => {
+                    llm.complete(&PromptBuilder::build_refine_prompt(question, &answer, context, citations)).await?
+                }
+            });
+        }
+        return Ok(answer.unwrap_or_else(|| NOT_IN_DOCUMENTS.to_string()));
+    }
+
+    let partials: Vec<String> = stream::iter(batches.iter())
+        .map(|(context, citations)| llm.generate_answer(question, context, citations))
+        .buffered(MAP_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<_>>()?;
+
+    reduce_partial_answers(llm, question, partials, config.context.batch_chunks).await
+}
+
+/// Merge partial answers, in rounds of `group` answers when there are many
+async fn reduce_partial_answers(
+    llm: &dyn LlmProvider,
+    question: &str,
+    mut partials: Vec<String>,
+    group: usize,
+) -> Result<String> {
+    partials.retain(|answer| !answer.trim().is_empty() && !answer.trim().starts_with(NOT_IN_DOCUMENTS));
+
+    loop {
+        match partials.len() {
+            0 => return Ok(NOT_IN_DOCUMENTS.to_string()),
+            1 => return Ok(partials.remove(0)),
+            _ => {}
+        }
+
+        let mut merged = Vec::new();
+        for answers in partials.chunks(group.max(2)) {
+            match answers {
+                [answer] => merged.push(answer.clone()),
+                answers => merged.push(llm.complete(&PromptBuilder::build_reduce_prompt(question, answers)).await?),
+            }
+        }
+        partials = merged;
+    }
+}
+
+/// Fit the context chunks into the token budget, keeping their citations in step
+///
+/// `None` when packing is disabled or there is no budget.
+fn pack_context(
+    state: &AppState,
+    search_results: &[VectorSearchResult],
+    citations: &mut Vec<Citation>,
+) -> Option<PackedContext> {
+    let config = state.config();
+    let budget = config.context.budget(&config.backend, &config.llm)?;
+    let packed = ContextPacker::new(&config.context, budget).pack(search_results);
+
+    if packed.dropped > 0 || packed.truncated > 0 {
+        tracing::info!(
+            "Packed context into {} of {} tokens: {} chunks kept, {} trimmed, {} dropped",
+            packed.tokens, budget, packed.kept.len(), packed.truncated, packed.dropped
+        );
+        if citations.len() == search_results.len() {
+            *citations = packed.kept.iter().map(|&i| citations[i].clone()).collect();
+        }
+    }
+    Some(packed)
+}
+
+/// Generation model of a query: the budget downgrade, else the requested
+/// model, else the variant's
+fn answer_model<'a>(request: &'a QueryRequest, variant: Option<&'a VariantConfig>) -> Option<&'a str> {
+    request
+        .budget_model
+        .as_deref()
+        .or(request.model.as_deref())
+        .or_else(|| variant.and_then(|v| v.model.as_deref()))
+}
+
+/// Refuse queries asking for a generation model that isn't configured
+fn check_model(state: &AppState, request: &QueryRequest) -> Result<()> {
+    match request.model.as_deref() {
+        Some(model) if !state.has_model(model) => Err(Error::Config(format!(
+            "Unknown model '{}' (add it to models.available to allow it)",
+            model
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Usage attribution of a query's provider calls
+pub(crate) fn usage_scope(request: &QueryRequest) -> UsageScope {
+    UsageScope::new(request.collection.as_deref(), request.principal.as_ref().map(|p| p.id.as_str()))
+}
+
+/// Check a query against the usage budgets
+///
+/// Spent `reject` budgets refuse it; spent `downgrade` budgets set the model
+/// it is answered with.
+pub(crate) fn check_budget(state: &AppState, request: &mut QueryRequest) -> Result<()> {
+    let decision = state
+        .usage()
+        .check_budget(request.collection.as_deref(), request.principal.as_ref().map(|p| p.id.as_str()))?;
+    request.budget_model = usage::apply_budget(decision)?;
+    if let Some(ref model) = request.budget_model {
+        tracing::info!("Usage budget spent, answering with {}", model);
+    }
+    Ok(())
+}
+
+/// Screen a question with the prompt-injection guard
+///
+/// Flagged questions are recorded in the guard audit log and rejected unless
+/// the guard only logs them.
+pub(crate) fn screen_query(state: &AppState, request: &QueryRequest) -> Result<()> {
+    let Some(event) = state.guard().screen_query(&request.question, request.principal.as_ref()) else {
+        return Ok(());
+    };
+    let blocked = event.blocked;
+    state.record_guard_events(vec![event]);
+    if blocked {
+        return Err(Error::QueryRejected(
+            "The question contains instructions aimed at the assistant and was not processed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Retrieved chunks sanitized and delimited by the prompt-injection guard
+///
+/// Returns `None` when the guard is disabled. Chunks with text removed are
+/// recorded in the guard audit log.
+fn guard_context(state: &AppState, search_results: &[VectorSearchResult]) -> Option<Vec<VectorSearchResult>> {
+    let (guarded, events) = state.guard().sanitize_context(search_results)?;
+    state.record_guard_events(events);
+    Some(guarded)
+}
+
+/// Figure chunks for the images embedded in a PDF or DOCX
+///
+/// Empty unless a vision provider is configured. Images that fail to caption
+/// are skipped; chunk numbering continues from `start_index`.
+pub(crate) async fn figure_chunks(
+    state: &AppState,
+    doc: &Document,
+    parsed: &ParsedDocument,
+    filename: &str,
+    data: &[u8],
+    start_index: u32,
+) -> Vec<Chunk> {
+    let Some(vision) = state.vision_provider() else {
+        return Vec::new();
+    };
+    if !figures::supports(filename) {
+        return Vec::new();
+    }
+
+    let rag_config = state.config();
+    let config = &rag_config.vision;
+    let (name, bytes) = (filename.to_string(), data.to_vec());
+    let (max_images, min_bytes) = (config.max_images_per_document, config.min_image_bytes);
+    let images = match tokio::task::spawn_blocking(move || figures::extract_images(&name, &bytes, max_images, min_bytes)).await {
+        Ok(Ok(images)) => images,
+        Ok(Err(e)) => {
+            tracing::warn!("Failed to extract images from {}: {}", filename, e);
+            return Vec::new();
+        }
+        Err(e) => {
+            tracing::warn!("Image extraction task failed for {}: {}", filename, e);
+            return Vec::new();
+        }
+    };
+    if images.is_empty() {
+        return Vec::new();
+    }
+
+    let timeout_secs = config.timeout_secs;
+    let captions: Vec<(figures::ExtractedImage, Option<String>)> = stream::iter(images)
+        .map(|image| async move {
+            let page_text = image
+                .page_number
+                .and_then(|page| parsed.pages.iter().find(|p| p.page_number == page))
+                .map(|p| p.content.as_str())
+                .unwrap_or("");
+            let prompt = figures::caption_prompt(&figures::page_labels(page_text));
+            let caption = match timeout(
+                Duration::from_secs(timeout_secs),
+                vision.describe_image(&image.data, &image.mime_type, &prompt),
+            )
+            .await
+            {
+                Ok(Ok(caption)) if !caption.trim().is_empty() => Some(caption),
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => {
+                    tracing::warn!("Failed to caption image {} of {}: {}", image.index, doc.filename, e);
+                    None
+                }
+                Err(_) => {
+                    tracing::warn!("Captioning image {} of {} timed out", image.index, doc.filename);
+                    None
+                }
+            };
+            (image, caption)
+        })
+        .buffered(2)
+        .collect()
+        .instrument(tracing::info_span!("caption_figures", filename = %doc.filename))
+        .await;
+
+    let chunks: Vec<Chunk> = captions
+        .into_iter()
+        .filter_map(|(image, caption)| caption.map(|caption| (image, caption)))
+        .enumerate()
+        .map(|(i, (image, caption))| figures::figure_chunk(doc, &image, &caption, start_index + i as u32))
+        .collect();
+
+    tracing::info!("Indexed {} figure captions for {}", chunks.len(), doc.filename);
+    chunks
+}
+
+/// Keep the original file and its plain text with the local backend when
+/// `originals.keep` is set (the GCP backend stores them in GCS itself)
+///
+/// Failures are logged and don't stop the ingestion.
+pub(crate) async fn store_original(state: &AppState, doc: &Document, filename: &str, data: &[u8], text: &str) {
+    #[cfg(feature = "gcp")]
+    if state.document_store().is_some() {
+        return;
+    }
+    let Some(store) = state.file_store().filter(|_| state.config().originals.keep) else {
+        return;
+    };
+
+    if let Err(e) = store.store_document(&doc.id, filename, data).await {
+        tracing::warn!("Failed to store original of {}: {}", filename, e);
+    }
+    if let Err(e) = store.store_plain_text(&doc.id, filename, text).await {
+        tracing::warn!("Failed to store plain text of {}: {}", filename, e);
+    }
+}
+
+/// Render and store the page thumbnails of a PDF for citation previews
+///
+/// Does nothing unless thumbnails are enabled. Failures are logged and don't
+/// stop the ingestion.
+pub(crate) async fn store_thumbnails(state: &AppState, doc: &Document, filename: &str, data: &[u8]) {
+    let config = state.config().thumbnails.clone();
+    let Some(store) = state.file_store().filter(|_| config.enabled) else {
+        return;
+    };
+    if !thumbnails::supports(filename) {
+        return;
+    }
+
+    let bytes = data.to_vec();
+    let pages = match tokio::task::spawn_blocking(move || thumbnails::render_pdf_pages(&bytes, config.size, config.max_pages)).await {
+        Ok(Ok(pages)) => pages,
+        Ok(Err(e)) => {
+            tracing::warn!("Failed to render thumbnails of {}: {}", filename, e);
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Thumbnail rendering task failed for {}: {}", filename, e);
+            return;
+        }
+    };
+
+    let mut stored = 0;
+    for (page, png) in &pages {
+        match store.store_thumbnail(&doc.id, *page, png).await {
+            Ok(_) => stored += 1,
+            Err(e) => tracing::warn!("Failed to store thumbnail of page {} of {}: {}", page, doc.filename, e),
+        }
+    }
+    tracing::debug!("Stored {} page thumbnails for {}", stored, doc.filename);
+}
+
+/// Transcribe an audio or video file into a parsed document
+///
+/// The content hash covers the recording's bytes. Recordings the dedup check
+/// would skip are not transcribed; an empty document is returned instead.
+pub(crate) async fn transcribe(state: &AppState, filename: &str, data: &[u8]) -> Result<ParsedDocument> {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    let file_type = crate::types::FileType::from_extension(&ext);
+    let content_hash = transcription::media_hash(data);
+
+    if !matches!(state.check_file_status(filename, &content_hash), FileStatus::New | FileStatus::Modified(_)) {
+        return Ok(transcription::pending(file_type, content_hash));
+    }
+
+    let transcript = state
+        .transcriber()
+        .transcribe(filename, data)
+        .instrument(tracing::info_span!("transcribe", filename = %filename))
+        .await?;
+    tracing::info!("Transcribed {}: {} segments", filename, transcript.segments.len());
+
+    Ok(transcript.into_parsed(file_type, content_hash))
+}
+
+/// Sign a freshly generated answer over its cited chunks and the corpus state
+fn sign_answer(
+    state: &AppState,
+    response: &QueryResponse,
+    search_results: &[VectorSearchResult],
+) -> Option<AnswerProvenance> {
+    let signer = state.provenance_signer()?;
+    let citation_hashes = response
+        .citations
+        .iter()
+        .map(|citation| {
+            let content = search_results
+                .iter()
+                .find(|r| r.chunk.id == citation.chunk_id)
+                .map(|r| r.chunk.content.as_str())
+                .unwrap_or(&citation.snippet);
+            provenance::citation_hash(&citation.chunk_id, content)
+        })
+        .collect();
+    Some(signer.sign(&response.answer, citation_hashes, state.corpus_snapshot_id()))
+}
+
+/// Process a single file with deduplication check
+async fn process_file_with_dedup(
+    state: &AppState,
+    filename: &str,
+    data: &[u8],
+    options: &IngestOptions,
+) -> Result<IngestOutcome> {
+    let config = state.config();
+
+    // Create ingestion pipeline
+    let pipeline = IngestPipeline::new(
+        options.chunk_size.unwrap_or(config.chunking.chunk_size),
+        options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+    )
+    .with_parent_window(config.retrieval.parent_window)
+    .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+    .with_size_unit(
+        options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+        state.embedder_for(options.collection.as_deref()),
+        &config.context,
+    )
+    .with_row_descriptions(config.chunking.row_descriptions);
+
+    // Parse the file to get content hash (recordings are transcribed)
+    let parsed = if transcription::is_media(filename) {
+        transcribe(state, filename, data).await?
+    } else {
+        pipeline.parse_file(filename, data)?
+    };
+
+    // Check file status for deduplication
+    match state.check_file_status(filename, &parsed.content_hash) {
+        FileStatus::Unchanged(existing) => {
+            Ok(IngestOutcome::Skipped {
+                reason: format!(
+                "unchanged (hash: {}...)",
+                &existing.content_hash[..12]
+            ),
+            })
+        }
+        FileStatus::Duplicate(existing) => {
+            Ok(IngestOutcome::Skipped {
+                reason: format!(
+                "duplicate of '{}'",
+                existing.filename
+            ),
+            })
+        }
+        FileStatus::ExistsInRegistry(record) => {
+            Ok(IngestOutcome::Skipped {
+                reason: format!(
+                "already in GCS (hash: {}..., uploaded: {})",
+                &record.content_hash[..record.content_hash.len().min(12)],
+                record.first_seen_at.format("%Y-%m-%d")
+            ),
+            })
+        }
+        FileStatus::DuplicateInRegistry(record) => {
+            Ok(IngestOutcome::Skipped {
+                reason: format!(
+                "duplicate of '{}' in GCS",
+                record.filename
+            ),
+            })
+        }
+        FileStatus::Modified(existing) => {
+            // Delete old document and its chunks (kept as a superseded version)
+            let deleted = state.supersede_document(&existing).await?;
+            tracing::info!(
+                "File '{}' modified, deleted {} old chunks",
+                filename,
+                deleted
+            );
+
+            // Process the new version
+            let (doc, chunk_count) = process_file_internal(state, filename, data, &parsed, options).await?;
+            Ok(IngestOutcome::Updated {
+                document: doc,
+                chunks: chunk_count,
+                old_chunks_deleted: deleted,
+            })
+        }
+        FileStatus::New => {
+            // Process new file
+            let (doc, chunk_count) = process_file_internal(state, filename, data, &parsed, options).await?;
+            Ok(IngestOutcome::New {
+                document: doc,
+                chunks: chunk_count,
+            })
+        }
+    }
+}
+
+/// Internal file processing (after dedup check)
+async fn process_file_internal(
+    state: &AppState,
+    filename: &str,
+    data: &[u8],
+    parsed: &crate::ingestion::ParsedDocument,
+    options: &IngestOptions,
+) -> Result<(Document, u32)> {
+    let config = state.config();
+
+    // Create ingestion pipeline
+    let pipeline = IngestPipeline::new(
+        options.chunk_size.unwrap_or(config.chunking.chunk_size),
+        options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+    )
+    .with_parent_window(config.retrieval.parent_window)
+    .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+    .with_size_unit(
+        options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+        state.embedder_for(options.collection.as_deref()),
+        &config.context,
+    )
+    .with_row_descriptions(config.chunking.row_descriptions);
+
+    // Create document record
+    let mut doc = Document::new(
+        filename.to_string(),
+        parsed.file_type.clone(),
+        parsed.content_hash.clone(),
+        data.len() as u64,
+    );
+    doc.total_pages = parsed.total_pages;
+    doc.metadata = options.metadata.clone();
+    doc.set_collection(options.collection.as_deref());
+    doc.set_acl(&options.acl);
+    doc.detect_language(&parsed.content);
+    doc.set_source_metadata(&parsed.metadata);
+
+    // Store original file and plain text in GCS (GCP backend only)
+    #[cfg(feature = "gcp")]
+    if let Some(document_store) = state.document_store() {
+        // Store original file
+        match document_store.store_document(&doc.id, filename, data).await {
+            Ok(original_uri) => {
+                doc.metadata.insert("original_uri".to_string(), serde_json::Value::String(original_uri));
+                tracing::debug!("Stored original file in GCS: {}", filename);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to store original file in GCS: {}", e);
+                // Continue processing - GCS storage is not critical
+            }
+        }
+
+        // Store extracted plain text
+        match document_store.store_plain_text(&doc.id, filename, &parsed.content).await {
+            Ok(plaintext_uri) => {
+                doc.metadata.insert("plaintext_uri".to_string(), serde_json::Value::String(plaintext_uri));
+                tracing::debug!("Stored plain text in GCS: {}", filename);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to store plain text in GCS: {}", e);
+                // Continue processing - GCS storage is not critical
+            }
+        }
+    }
+
+    // Create chunks
+    let mut chunks = pipeline.create_chunks(&doc, parsed)?;
+    let figures = figure_chunks(state, &doc, parsed, filename, data, chunks.len() as u32).await;
+    chunks.extend(figures);
+    store_original(state, &doc, filename, data, &parsed.content).await;
+    store_thumbnails(state, &doc, filename, data).await;
+    redaction::redact_document(state.redactor(), &mut doc, &mut chunks).await;
+    keywords::tag_document(state.keyword_extractor(), &mut doc, &mut chunks).await;
+
+    // Generate embeddings in parallel for better performance (5-10x faster)
+    // Use configurable concurrency to avoid overwhelming the embedding service
+    let parallel_embeddings = config.processing.parallel_embeddings.unwrap_or(8);
+    let embedding_provider = state.embedder_for(doc.collection());
+    let embedding_model = state.embedding_model_for(doc.collection()).to_string();
+
+    if chunks.len() <= 1 {
+        // Single chunk - no need for parallel processing
+        for chunk in chunks.iter_mut() {
+            let embeddings = embedding_provider.embed_parts(&chunk.content).await?;
+            chunk.set_embeddings(embeddings);
+            chunk.set_embedding_model(&embedding_model);
+        }
+    } else {
+        // Multiple chunks - process in parallel with concurrency limit
+        // Results are applied in chunk order, so they must arrive in it
+        let embeddings: Vec<Result<Vec<Vec<f32>>>> = stream::iter(chunks.iter())
+            .map(|chunk| embedding_provider.embed_parts(&chunk.content))
+            .buffered(parallel_embeddings)
+            .collect()
+            .await;
+
+        // Apply embeddings to chunks, fail on first error
+        for (chunk, embedding_result) in chunks.iter_mut().zip(embeddings.into_iter()) {
+            chunk.set_embeddings(embedding_result?);
+            chunk.set_embedding_model(&embedding_model);
+        }
+    }
+
+    // Store chunks in vector database (uses Vertex AI for GCP backend)
+    let chunk_count = chunks.len() as u32;
+    state.vector_store_provider().insert_chunks(chunks.into()).await?;
+
+    doc.total_chunks = chunk_count;
+
+    tracing::info!(
+        "Processed '{}': {} pages, {} chunks",
+        filename,
+        doc.total_pages.unwrap_or(1),
+        chunk_count
+    );
+
+    Ok((doc, chunk_count))
+}
+
+/// Convert legacy format (DOC, PPT, XLS) using LibreOffice
+async fn convert_legacy_format(
+    state: &AppState,
+    filename: &str,
+    data: &[u8],
+) -> Result<(String, Vec<u8>)> {
+    let converted = state
+        .external_parser()
+        .convert_with_libreoffice(filename, data)
+        .await?;
+
+    // Generate new filename with modern extension
+    let stem = std::path::Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("document");
+
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    let new_ext = match ext.as_str() {
+        "doc" => "docx",
+        "ppt" => "pptx",
+        "xls" => "xlsx",
+        _ => "docx",
+    };
+
+    let new_filename = format!("{}.{}", stem, new_ext);
+    Ok((new_filename, converted))
+}
+
+/// Parse document using external API (Unstructured.io)
+async fn parse_with_external(
+    state: &AppState,
+    filename: &str,
+    data: &[u8],
+) -> Result<String> {
+    let parsed = state
+        .external_parser()
+        .parse_with_unstructured(filename, data)
+        .await?;
+
+    Ok(parsed.content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::TestApp;
+    use crate::types::ChunkSource;
+
+    #[test]
+    fn test_limit_per_document_keeps_best_chunks() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let result = |document_id: Uuid, similarity: f32| {
+            let chunk = Chunk::new(document_id, String::new(), ChunkSource::text("doc.txt".to_string()), 0, 0, 0);
+            VectorSearchResult::new(chunk, similarity)
+        };
+        let mut results = vec![result(a, 0.9), result(a, 0.8), result(b, 0.7), result(a, 0.6), result(b, 0.5)];
+
+        limit_per_document(&mut results, 2);
+        let kept: Vec<(Uuid, f32)> = results.iter().map(|r| (r.chunk.document_id, r.similarity)).collect();
+        assert_eq!(kept, vec![(a, 0.9), (a, 0.8), (b, 0.7), (b, 0.5)]);
+
+        limit_per_document(&mut results, 1);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_stays_in_collection() {
+        let app = TestApp::new().await;
+        let options = |collection: &str| IngestOptions {
+            collection: Some(collection.to_string()),
+            ..Default::default()
+        };
+        let legal = app.ingest("legal.txt", "The refund policy allows returns within thirty days.", &options("legal"));
+        let legal = legal.await;
+        app.ingest("hr.txt", "The refund policy for travel expenses needs receipts.", &options("hr")).await;
+
+        let mut request = QueryRequest::new("refund policy");
+        let results = retrieve(&app.state, &request, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.chunk.document_id).collect::<HashSet<_>>().len(), 2);
+
+        request.collection = Some("legal".to_string());
+        let results = retrieve(&app.state, &request, None).await.unwrap();
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| r.chunk.document_id == legal.id));
+    }
+
+    /// Generator recording what it is asked, replying "answer N" to the Nth call
+    #[derive(Default)]
+    struct RecordingLlm {
+        calls: parking_lot::Mutex<Vec<String>>,
+    }
+
+    impl RecordingLlm {
+        fn record(&self, call: String) -> String {
+            let mut calls = self.calls.lock();
+            calls.push(call);
+            format!("answer {}", calls.len())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for RecordingLlm {
+        async fn generate_answer(&self, _question: &str, context: &str, _citations: &[Citation]) -> Result<String> {
+            Ok(self.record(format!("generate: {}", context)))
+        }
+        async fn generate_with_learning(
+            &self,
+            question: &str,
+            context: &str,
+            citations: &[Citation],
+            _past_qa: &[(String, String)],
+        ) -> Result<String> {
+            self.generate_answer(question, context, citations).await
+        }
+        async fn complete(&self, prompt: &str) -> Result<String> {
+            Ok(self.record(format!("complete: {}", prompt)))
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        fn name(&self) -> &str {
+            "recording"
+        }
+        fn model(&self) -> &str {
+            "recording"
+        }
+    }
+
+    /// Chunks with the given texts and their citations
+    fn context(texts: &[&str]) -> (Vec<VectorSearchResult>, Vec<Citation>) {
+        let results: Vec<VectorSearchResult> = texts
+            .iter()
+            .map(|text| {
+                let source = ChunkSource::text("doc.txt".to_string());
+                VectorSearchResult::new(Chunk::new(Uuid::new_v4(), text.to_string(), source, 0, 0, 0), 0.9)
+            })
+            .collect();
+        let citations = results.iter().map(|r| Citation::from_chunk(&r.chunk, r.similarity)).collect();
+        (results, citations)
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce_batches_and_merges_in_rounds() {
+        let app = TestApp::with_config(|config| config.context.batch_chunks = 2).await;
+        let mut request = QueryRequest::new("What changed?");
+        request.answer_strategy = AnswerStrategy::MapReduce;
+        let (results, citations) = context(&["alpha", "beta", "gamma", "delta", "epsilon"]);
+        let llm = RecordingLlm::default();
+
+        let answer = generate_batches(&app.state, &request, &llm, "What changed?", &results, &citations).await.unwrap();
+
+        // Three batches of at most two chunks, then two rounds merging two answers each
+        let calls = llm.calls.lock().clone();
+        assert_eq!(calls.len(), 5);
+        assert!(calls[0].starts_with("generate:") && calls[0].contains("alpha") && calls[0].contains("beta"));
+        assert!(calls[1].starts_with("generate:") && calls[1].contains("gamma") && calls[1].contains("delta"));
+        assert!(calls[2].starts_with("generate:") && calls[2].contains("epsilon") && !calls[2].contains("delta"));
+        assert!(calls[3].starts_with("complete:") && calls[3].contains("answer 1") && calls[3].contains("answer 2"));
+        assert!(calls[4].starts_with("complete:") && calls[4].contains("answer 4") && calls[4].contains("answer 3"));
+        assert_eq!(answer, "answer 5");
+    }
+
+    #[tokio::test]
+    async fn test_reduce_skips_empty_partials() {
+        let llm = RecordingLlm::default();
+        let partials = vec!["a".to_string(), NOT_IN_DOCUMENTS.to_string(), " ".to_string()];
+        assert_eq!(reduce_partial_answers(&llm, "q", partials, 2).await.unwrap(), "a");
+
+        let partials = vec![NOT_IN_DOCUMENTS.to_string()];
+        assert_eq!(reduce_partial_answers(&llm, "q", partials, 2).await.unwrap(), NOT_IN_DOCUMENTS);
+        assert!(llm.calls.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refine_passes_run_in_order() {
+        let app = TestApp::with_config(|config| config.context.batch_chunks = 1).await;
+        let mut request = QueryRequest::new("What changed?");
+        request.answer_strategy = AnswerStrategy::Refine;
+        let (results, citations) = context(&["alpha", "beta", "gamma"]);
+        let llm = RecordingLlm::default();
+
+        let answer = generate_batches(&app.state, &request, &llm, "What changed?", &results, &citations).await.unwrap();
+
+        // The first batch is answered, each later one refines the previous answer
+        let calls = llm.calls.lock().clone();
+        assert_eq!(calls.len(), 3);
+        assert!(calls[0].starts_with("generate:") && calls[0].contains("alpha"));
+        assert!(calls[1].starts_with("complete:") && calls[1].contains("answer 1") && calls[1].contains("beta"));
+        assert!(calls[2].starts_with("complete:") && calls[2].contains("answer 2") && calls[2].contains("gamma"));
+        assert!(!calls[2].contains("alpha"));
+        assert_eq!(answer, "answer 3");
+    }
+
+    #[tokio::test]
+    async fn test_answer_uses_cache_only_when_asked() {
+        let app = TestApp::new().await;
+        app.ingest("legal.txt", "The refund policy allows returns within thirty days.", &Default::default()).await;
+        let engine = app.engine();
+        let request = || QueryRequest::new("What is the refund policy?");
+
+        let (first, cache_info) = engine.answer(request(), true).await.unwrap();
+        assert!(cache_info.is_some_and(|info| !info.from_cache));
+        assert!(!first.citations.is_empty());
+        let generated = app.ollama.prompts().len();
+
+        let (second, cache_info) = engine.answer(request(), true).await.unwrap();
+        assert_eq!(cache_info.map(|info| (info.from_cache, info.hit_count)), Some((true, Some(1))));
+        assert_eq!(second.answer, first.answer);
+        assert_eq!(second.citations.len(), first.citations.len());
+        assert_eq!(app.ollama.prompts().len(), generated);
+
+        // Plain queries neither read nor report the cache
+        let (_, cache_info) = engine.answer(request(), false).await.unwrap();
+        assert!(cache_info.is_none());
+        assert!(app.ollama.prompts().len() > generated);
+    }
+
+    #[tokio::test]
+    async fn test_check_evidence_refuses_below_threshold() {
+        let app = TestApp::with_config(|config| config.retrieval.answer_threshold = Some(0.5)).await;
+        let request = QueryRequest::new("refund policy");
+        let result = |similarity: f32| {
+            let chunk = Chunk::new(Uuid::new_v4(), String::new(), ChunkSource::text("doc.txt".to_string()), 0, 0, 0);
+            VectorSearchResult::new(chunk, similarity)
+        };
+
+        let evidence = check_evidence(&app.state, &request, &[result(0.3), result(0.4)]).unwrap();
+        assert_eq!(evidence.best_score, 0.4);
+        let scores: Vec<f32> = evidence.near_misses.iter().map(|c| c.similarity_score).collect();
+        assert_eq!(scores, vec![0.4, 0.3]);
+
+        assert!(check_evidence(&app.state, &request, &[result(0.3), result(0.6)]).is_none());
+        assert!(check_evidence(&app.state, &request, &[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_evidence_ignores_boosts() {
+        let app = TestApp::with_config(|config| {
+            config.retrieval.answer_threshold = Some(0.9);
+            config.retrieval.recency.boost = 1.0;
+        })
+        .await;
+        app.ingest("legal.txt", "The refund policy allows returns within thirty days.", &Default::default()).await;
+
+        // The fresh document's boost lifts a weak match over the threshold
+        let request = QueryRequest::new("Which airline lounge has the refund counter?");
+        let results = retrieve(&app.state, &request, None).await.unwrap();
+        assert!(results.iter().any(|r| r.similarity >= 0.9));
+        assert!(results.iter().all(|r| r.raw_similarity < 0.9));
+
+        let evidence = check_evidence(&app.state, &request, &results).unwrap();
+        assert!(evidence.best_score < 0.9);
+    }
+}
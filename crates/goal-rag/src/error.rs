@@ -1,12 +1,17 @@
 //! Error types for the RAG system
+//!
+//! Every variant has a stable `code`. API errors are RFC 7807 problem
+//! details (`application/problem+json`) carrying the code, the HTTP status
+//! and whether the request may succeed when retried.
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 /// Result type alias for RAG operations
 pub type Result<T> = std::result::Result<T, Error>;
@@ -26,10 +31,30 @@ pub enum Error {
     #[error("Unsupported file type: {0}")]
     UnsupportedFileType(String),
 
+    /// An encrypted file none of the known passwords opens
+    #[error("Password required: '{0}' is encrypted and no known password opens it")]
+    PasswordRequired(String),
+
+    /// A file the virus scanner flagged (and quarantined)
+    #[error("Malware detected in '{filename}': {signature}")]
+    Malware { filename: String, signature: String },
+
+    /// A parser service or tool is missing or not answering
+    #[error("Parser unavailable: {0}")]
+    ParserUnavailable(String),
+
     /// Embedding error
     #[error("Embedding generation failed: {0}")]
     Embedding(String),
 
+    /// An embedding model returned vectors of the wrong size
+    #[error("Embedding has {actual} dimensions, expected {expected}")]
+    EmbeddingDimensionMismatch { expected: usize, actual: usize },
+
+    /// A provider turned the request down for its rate limits
+    #[error("{provider} rate limited the request: {message}")]
+    ProviderRateLimited { provider: String, message: String },
+
     /// Vector database error
     #[error("Vector database error: {0}")]
     VectorDb(String),
@@ -42,6 +67,38 @@ pub enum Error {
     #[error("Document not found: {0}")]
     DocumentNotFound(String),
 
+    /// Other resource not found (interaction, task, ...)
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// Missing or invalid credentials
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Authenticated, but not allowed to do this
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Query rejected by the prompt-injection guard
+    #[error("Query rejected: {0}")]
+    QueryRejected(String),
+
+    /// Usage budget spent
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    /// The server is shedding load; retry after the given delay
+    #[error("Overloaded: {message}")]
+    Overloaded { message: String, retry_after_secs: u64 },
+
+    /// The request took longer than its route allows
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    /// Stored data is missing or damaged (registry, spool)
+    #[error("Storage corruption: {0}")]
+    StorageCorruption(String),
+
     /// IO error
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -91,6 +148,27 @@ impl Error {
     pub fn internal(message: impl Into<String>) -> Self {
         Self::Internal(message.into())
     }
+
+    /// Whether the operation may succeed when retried (service, network and
+    /// IO failures, rate limits, timeouts)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Embedding(_)
+            | Error::VectorDb(_)
+            | Error::Llm(_)
+            | Error::Http(_)
+            | Error::Io(_)
+            | Error::ParserUnavailable(_)
+            | Error::ProviderRateLimited { .. }
+            | Error::Overloaded { .. }
+            | Error::Timeout(_) => true,
+            Error::FileParse { message, .. } | Error::Internal(message) => {
+                let message = message.to_lowercase();
+                message.contains("timeout") || message.contains("timed out")
+            }
+            _ => false,
+        }
+    }
 }
 
 impl From<ruvector_core::RuvectorError> for Error {
@@ -99,58 +177,194 @@ impl From<ruvector_core::RuvectorError> for Error {
     }
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
-        let (status, error_type, message) = match &self {
-            Error::Config(msg) => (StatusCode::BAD_REQUEST, "config_error", msg.clone()),
-            Error::FileParse { filename, message } => (
-                StatusCode::BAD_REQUEST,
-                "parse_error",
-                format!("Failed to parse '{}': {}", filename, message),
-            ),
-            Error::UnsupportedFileType(ext) => (
-                StatusCode::BAD_REQUEST,
-                "unsupported_type",
-                format!("Unsupported file type: {}", ext),
-            ),
-            Error::Embedding(msg) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "embedding_error", msg.clone())
-            }
-            Error::VectorDb(msg) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "vector_db_error", msg.clone())
-            }
-            Error::Llm(msg) => (StatusCode::SERVICE_UNAVAILABLE, "llm_error", msg.clone()),
-            Error::DocumentNotFound(id) => (
-                StatusCode::NOT_FOUND,
-                "not_found",
-                format!("Document not found: {}", id),
-            ),
-            Error::Io(err) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "io_error",
-                err.to_string(),
-            ),
-            Error::Json(err) => (StatusCode::BAD_REQUEST, "json_error", err.to_string()),
-            Error::Http(err) => (
-                StatusCode::BAD_GATEWAY,
-                "http_error",
-                err.to_string(),
-            ),
-            Error::RuVector(msg) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "vector_error", msg.clone())
+impl Error {
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config_error",
+            Error::FileParse { .. } => "parse_error",
+            Error::UnsupportedFileType(_) => "unsupported_type",
+            Error::PasswordRequired(_) => "password_required",
+            Error::Malware { .. } => "malware",
+            Error::ParserUnavailable(_) => "parser_unavailable",
+            Error::Embedding(_) => "embedding_error",
+            Error::EmbeddingDimensionMismatch { .. } => "embedding_dimension_mismatch",
+            Error::ProviderRateLimited { .. } => "provider_rate_limited",
+            Error::VectorDb(_) => "vector_db_error",
+            Error::Llm(_) => "llm_error",
+            Error::DocumentNotFound(_) | Error::NotFound(_) => "not_found",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::QueryRejected(_) => "query_rejected",
+            Error::BudgetExceeded(_) => "budget_exceeded",
+            Error::Overloaded { .. } => "overloaded",
+            Error::Timeout(_) => "timeout",
+            Error::StorageCorruption(_) => "storage_corruption",
+            Error::Io(_) => "io_error",
+            Error::Json(_) => "json_error",
+            Error::Http(_) => "http_error",
+            Error::RuVector(_) => "vector_error",
+            Error::Internal(_) => "internal_error",
+        }
+    }
+
+    /// HTTP status of the error response
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::Config(_)
+            | Error::FileParse { .. }
+            | Error::UnsupportedFileType(_)
+            | Error::PasswordRequired(_)
+            | Error::Malware { .. }
+            | Error::QueryRejected(_)
+            | Error::Json(_) => StatusCode::BAD_REQUEST,
+            Error::DocumentNotFound(_) | Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::BudgetExceeded(_) | Error::ProviderRateLimited { .. } | Error::Overloaded { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
             }
-            Error::Internal(msg) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", msg.clone())
+            Error::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Llm(_) | Error::ParserUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Http(_) => StatusCode::BAD_GATEWAY,
+            Error::Embedding(_)
+            | Error::EmbeddingDimensionMismatch { .. }
+            | Error::VectorDb(_)
+            | Error::StorageCorruption(_)
+            | Error::Io(_)
+            | Error::RuVector(_)
+            | Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Short summary of the kind of error
+    fn title(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "Invalid configuration or request",
+            Error::FileParse { .. } => "File could not be parsed",
+            Error::UnsupportedFileType(_) => "Unsupported file type",
+            Error::PasswordRequired(_) => "Password required",
+            Error::Malware { .. } => "Malware detected",
+            Error::ParserUnavailable(_) => "Parser unavailable",
+            Error::Embedding(_) => "Embedding failed",
+            Error::EmbeddingDimensionMismatch { .. } => "Embedding dimension mismatch",
+            Error::ProviderRateLimited { .. } => "Provider rate limited",
+            Error::VectorDb(_) | Error::RuVector(_) => "Vector database error",
+            Error::Llm(_) => "Language model unavailable",
+            Error::DocumentNotFound(_) | Error::NotFound(_) => "Not found",
+            Error::Unauthorized(_) => "Unauthorized",
+            Error::Forbidden(_) => "Forbidden",
+            Error::QueryRejected(_) => "Query rejected",
+            Error::BudgetExceeded(_) => "Budget exceeded",
+            Error::Overloaded { .. } => "Server overloaded",
+            Error::Timeout(_) => "Request timed out",
+            Error::StorageCorruption(_) => "Storage corruption",
+            Error::Io(_) => "I/O error",
+            Error::Json(_) => "Invalid JSON",
+            Error::Http(_) => "Upstream request failed",
+            Error::Internal(_) => "Internal error",
+        }
+    }
+
+    /// What went wrong in this case
+    fn detail(&self) -> String {
+        match self {
+            Error::Config(msg)
+            | Error::ParserUnavailable(msg)
+            | Error::Embedding(msg)
+            | Error::VectorDb(msg)
+            | Error::Llm(msg)
+            | Error::NotFound(msg)
+            | Error::Unauthorized(msg)
+            | Error::Forbidden(msg)
+            | Error::QueryRejected(msg)
+            | Error::BudgetExceeded(msg)
+            | Error::Overloaded { message: msg, .. }
+            | Error::Timeout(msg)
+            | Error::StorageCorruption(msg)
+            | Error::RuVector(msg)
+            | Error::Internal(msg) => msg.clone(),
+            Error::FileParse { filename, message } => format!("Failed to parse '{}': {}", filename, message),
+            Error::ProviderRateLimited { provider, message } => format!("{}: {}", provider, message),
+            Error::Io(err) => err.to_string(),
+            Error::Json(err) => err.to_string(),
+            Error::Http(err) => err.to_string(),
+            Error::UnsupportedFileType(_)
+            | Error::PasswordRequired(_)
+            | Error::Malware { .. }
+            | Error::EmbeddingDimensionMismatch { .. }
+            | Error::DocumentNotFound(_) => {
+                self.to_string()
             }
+        }
+    }
+
+    /// The error as RFC 7807 problem details
+    pub fn problem(&self) -> ProblemDetails {
+        ProblemDetails {
+            problem_type: format!("urn:goal-rag:error:{}", self.code()),
+            title: self.title().to_string(),
+            status: self.status().as_u16(),
+            detail: self.detail(),
+            code: self.code().to_string(),
+            retryable: self.is_transient(),
+        }
+    }
+}
+
+/// Error body returned by all endpoints on failure (RFC 7807)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    /// Problem type URI, `urn:goal-rag:error:<code>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short summary of the problem type
+    pub title: String,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation of this occurrence
+    pub detail: String,
+    /// Stable error code (e.g., "not_found", "provider_rate_limited")
+    pub code: String,
+    /// Whether the same request may succeed later
+    pub retryable: bool,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let problem = self.problem();
+        let mut response = (self.status(), Json(problem)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if let Error::Overloaded { retry_after_secs, .. } = self {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem_details() {
+        let error = Error::ProviderRateLimited {
+            provider: "vertex-ai".to_string(),
+            message: "quota exceeded".to_string(),
         };
+        let problem = error.problem();
+        assert_eq!(problem.problem_type, "urn:goal-rag:error:provider_rate_limited");
+        assert_eq!(problem.status, 429);
+        assert_eq!(problem.detail, "vertex-ai: quota exceeded");
+        assert!(problem.retryable);
 
-        let body = Json(json!({
-            "error": {
-                "type": error_type,
-                "message": message,
-            }
-        }));
+        let problem = Error::EmbeddingDimensionMismatch { expected: 768, actual: 1024 }.problem();
+        assert_eq!((problem.code.as_str(), problem.status), ("embedding_dimension_mismatch", 500));
+        assert!(!problem.retryable);
 
-        (status, body).into_response()
+        let response = Error::NotFound("task".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
     }
 }
@@ -1,7 +1,36 @@
 //! Citation extraction and linking
 
 use regex::Regex;
-use crate::types::response::Citation;
+use crate::providers::llm::GeneratedAnswer;
+use crate::types::response::{Citation, CitationMarker};
+
+/// `[Source: ...]` markers, and numbered markers (`[2]`, `[1, 3]`) referring
+/// to the sources as numbered in the prompt
+const MARKER_PATTERN: &str = r"\[Source:\s*([^\]]+)\]|\[(\d{1,3}(?:\s*,\s*\d{1,3})*)\]";
+
+/// Link the citations of a generated answer to source chunks
+///
+/// Sources the model reported citing (JSON mode) are linked in the order
+/// given, along with any numbered markers of the text; answers without them
+/// are parsed with `extract_and_link_citations`.
+pub fn link_generated_citations(
+    answer: &GeneratedAnswer,
+    available_citations: &mut Vec<Citation>,
+) -> (String, Vec<Citation>) {
+    let mut linked_citations: Vec<Citation> = Vec::new();
+    for number in answer.cited_sources.iter().flatten() {
+        if let Some(citation) = number.checked_sub(1).and_then(|i| available_citations.get(i)) {
+            if !linked_citations.iter().any(|c| c.chunk_id == citation.chunk_id) {
+                linked_citations.push(citation.clone());
+            }
+        }
+    }
+    if linked_citations.is_empty() {
+        return extract_and_link_citations(&answer.text, available_citations);
+    }
+    let clean_answer = link_numbered_markers(&answer.text, available_citations, &mut linked_citations);
+    (clean_answer, linked_citations)
+}
 
 /// Extract citations from LLM response and link them to source chunks
 pub fn extract_and_link_citations(
@@ -14,7 +43,7 @@ pub fn extract_and_link_citations(
     ).expect("Invalid regex");
 
     let mut linked_citations = Vec::new();
-    let mut clean_answer = answer.to_string();
+    let mut clean_answer = link_numbered_markers(answer, available_citations, &mut linked_citations);
 
     // Find all citation matches
     for cap in citation_pattern.captures_iter(answer) {
@@ -61,6 +90,114 @@ pub fn extract_and_link_citations(
     (clean_answer, linked_citations)
 }
 
+/// Rewrite numbered markers as source markers, linking the sources they
+/// cite; numbers without a source are left alone
+fn link_numbered_markers(answer: &str, available_citations: &[Citation], linked_citations: &mut Vec<Citation>) -> String {
+    let number_pattern = Regex::new(r"\[(\d{1,3}(?:\s*,\s*\d{1,3})*)\]").expect("Invalid regex");
+    number_pattern
+        .replace_all(answer, |caps: &regex::Captures| {
+            let sources: Vec<&Citation> = caps[1]
+                .split(',')
+                .filter_map(|n| n.trim().parse::<usize>().ok())
+                .filter_map(|n| n.checked_sub(1).and_then(|i| available_citations.get(i)))
+                .collect();
+            if sources.is_empty() {
+                return caps[0].to_string();
+            }
+            for citation in &sources {
+                if !linked_citations.iter().any(|c: &Citation| c.chunk_id == citation.chunk_id) {
+                    linked_citations.push((*citation).clone());
+                }
+            }
+            sources.iter().map(|c| c.format_inline()).collect::<Vec<_>>().join("")
+        })
+        .into_owned()
+}
+
+/// Rewrite the citation markers of an answer as `[n]`, `n` being the
+/// 1-based position of the cited entry in `citations`, and locate them
+///
+/// Markers citing nothing in `citations` are dropped. Each marker records
+/// the start of the sentence it backs, so the claim can be highlighted with
+/// its sources.
+pub fn align_citations(answer: &str, citations: &[Citation]) -> (String, Vec<CitationMarker>) {
+    let marker_pattern = Regex::new(MARKER_PATTERN).expect("Invalid regex");
+    let mut aligned = String::with_capacity(answer.len());
+    let mut markers: Vec<CitationMarker> = Vec::new();
+    let mut copied = 0;
+
+    for cap in marker_pattern.captures_iter(answer) {
+        let found = cap.get(0).expect("whole match");
+        aligned.push_str(&answer[copied..found.start()]);
+        copied = found.end();
+
+        // Numbered markers left in the answer had no source
+        let mut indexes: Vec<usize> = Vec::new();
+        for source in cap.get(1).map_or("", |sources| sources.as_str()).split(';') {
+            let (filename, page, line_start) = parse_source(source);
+            if let Some(index) = find_matching_index(citations, filename, page, line_start) {
+                if !indexes.contains(&index) {
+                    indexes.push(index);
+                }
+            }
+        }
+
+        if indexes.is_empty() {
+            let next = answer[copied..].chars().next();
+            if aligned.ends_with(' ') && next.map_or(true, |c| c.is_whitespace() || c.is_ascii_punctuation()) {
+                aligned.pop();
+            }
+            continue;
+        }
+
+        let claim_start = match markers.last() {
+            // Adjacent markers back the same claim
+            Some(previous) if previous.end == aligned.len() => previous.claim_start,
+            _ => claim_start(&aligned),
+        };
+        for index in indexes {
+            let start = aligned.len();
+            aligned.push_str(&format!("[{}]", index + 1));
+            markers.push(CitationMarker {
+                start,
+                end: aligned.len(),
+                citation: (index + 1) as u32,
+                claim_start,
+            });
+        }
+    }
+    aligned.push_str(&answer[copied..]);
+
+    (aligned, markers)
+}
+
+/// Filename, page and first line of a `[Source: ...]` marker's contents
+fn parse_source(source: &str) -> (&str, Option<u32>, Option<u32>) {
+    let mut parts = source.split(',').map(str::trim);
+    let filename = parts.next().unwrap_or_default();
+    let (mut page, mut line_start) = (None, None);
+    for part in parts {
+        let lower = part.to_lowercase();
+        if let Some(number) = lower.strip_prefix("page") {
+            page = page.or(number.trim().parse().ok());
+        } else if let Some(range) = lower.strip_prefix("lines").or(lower.strip_prefix("line")) {
+            line_start = line_start.or(range.trim().split('-').next().and_then(|n| n.trim().parse().ok()));
+        }
+    }
+    (filename, page, line_start)
+}
+
+/// Start of the sentence ending at the end of `text`
+fn claim_start(text: &str) -> usize {
+    let body = text.trim_end();
+    let body = body.strip_suffix(['.', '!', '?']).unwrap_or(body);
+    let boundary = body.char_indices().rev().find(|&(i, c)| {
+        c == '\n' || (matches!(c, '.' | '!' | '?') && body[i + c.len_utf8()..].starts_with(char::is_whitespace))
+    });
+    let start = boundary.map_or(0, |(i, c)| i + c.len_utf8());
+    start + (body[start..].len() - body[start..].trim_start().len())
+}
+
 /// Find a citation matching the given criteria
 fn find_matching_citation(
     citations: &[Citation],
@@ -68,8 +205,22 @@ fn find_matching_citation(
     page: Option<u32>,
     line_start: Option<u32>,
 ) -> Option<Citation> {
+    find_matching_index(citations, filename, page, line_start).map(|i| citations[i].clone())
+}
+
+/// Position of the citation matching the given criteria
+fn find_matching_index(
+    citations: &[Citation],
+    filename: &str,
+    page: Option<u32>,
+    line_start: Option<u32>,
+) -> Option<usize> {
+    if filename.is_empty() {
+        return None;
+    }
+
     // Try exact match first
-    for citation in citations {
+    for (i, citation) in citations.iter().enumerate() {
         let filename_matches = citation.filename.contains(filename)
             || filename.contains(&citation.filename)
             || filename.to_lowercase() == citation.filename.to_lowercase();
@@ -78,32 +229,28 @@ fn find_matching_citation(
             // Check page match if specified
             if let Some(p) = page {
                 if citation.page_number == Some(p) {
-                    return Some(citation.clone());
+                    return Some(i);
                 }
             }
 
             // Check line match if specified
             if let Some(start) = line_start {
                 if citation.line_start == Some(start) {
-                    return Some(citation.clone());
+                    return Some(i);
                 }
             }
 
             // Filename only match
             if page.is_none() && line_start.is_none() {
-                return Some(citation.clone());
+                return Some(i);
             }
         }
     }
 
     // Fuzzy match - just find by filename
-    for citation in citations {
-        if citation.filename.contains(filename) || filename.contains(&citation.filename) {
-            return Some(citation.clone());
-        }
-    }
-
-    None
+    citations
+        .iter()
+        .position(|citation| citation.filename.contains(filename) || filename.contains(&citation.filename))
 }
 
 /// Highlight query terms in citation snippets
@@ -154,6 +301,8 @@ pub fn truncate_snippet(snippet: &str, max_len: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{Chunk, ChunkSource};
+    use uuid::Uuid;
 
     #[test]
     fn test_highlight_snippet() {
@@ -165,6 +314,50 @@ mod tests {
         assert!(highlighted.contains("<mark>warming</mark>"));
     }
 
+    #[test]
+    fn test_align_citations() {
+        let citation = |filename: &str, page: u32| {
+            let source = ChunkSource::pdf(filename.to_string(), page, 10);
+            Citation::from_chunk(&Chunk::new(Uuid::new_v4(), String::new(), source, 0, 0, 0), 0.9)
+        };
+        let citations = vec![citation("policy.pdf", 2), citation("faq.md", 1)];
+
+        let answer = "Refunds take 14 days [Source: policy.pdf, Page 2]. Ask support first. \
+            Shipping is free [Source: faq.md, Page 1][Source: gone.pdf, Page 9][7].";
+        let (aligned, markers) = align_citations(answer, &citations);
+
+        assert_eq!(aligned, "Refunds take 14 days [1]. Ask support first. Shipping is free [2].");
+        assert_eq!(markers.len(), 2);
+        assert_eq!(&aligned[markers[0].start..markers[0].end], "[1]");
+        assert_eq!(&aligned[markers[0].claim_start..markers[0].start], "Refunds take 14 days ");
+        assert_eq!(markers[1].citation, 2);
+        assert_eq!(&aligned[markers[1].claim_start..markers[1].start], "Shipping is free ");
+    }
+
+    #[test]
+    fn test_link_generated_citations() {
+        let citation = |filename: &str, page: u32| {
+            let source = ChunkSource::pdf(filename.to_string(), page, 10);
+            Citation::from_chunk(&Chunk::new(Uuid::new_v4(), String::new(), source, 0, 0, 0), 0.9)
+        };
+        let mut citations = vec![citation("policy.pdf", 2), citation("faq.md", 1), citation("terms.pdf", 4)];
+
+        let answer = GeneratedAnswer {
+            text: "Refunds take 14 days [3].".to_string(),
+            cited_sources: Some(vec![2, 9]),
+            cached_tokens: 0,
+        };
+        let (clean, linked) = link_generated_citations(&answer, &mut citations);
+        assert_eq!(clean, format!("Refunds take 14 days {}.", citations[2].format_inline()));
+        let files: Vec<&str> = linked.iter().map(|c| c.filename.as_str()).collect();
+        assert_eq!(files, ["faq.md", "terms.pdf"]);
+
+        // Without reported sources the text is parsed
+        let answer = GeneratedAnswer::from("Refunds take 14 days [Source: policy.pdf, Page 2]".to_string());
+        let (_, linked) = link_generated_citations(&answer, &mut citations);
+        assert_eq!(linked[0].filename, "policy.pdf");
+    }
+
     #[test]
     fn test_truncate_snippet() {
         let snippet = "This is a very long snippet that needs to be truncated.";
@@ -0,0 +1,103 @@
+//! Suggested follow-up questions
+//!
+//! The LLM is shown the question and the retrieved context and asked for
+//! questions the same context answers, so a suggestion clicked by the user
+//! leads to an answer rather than a "not in the documents".
+
+use tracing::Instrument;
+
+use crate::providers::llm::LlmProvider;
+
+/// Context shown to the LLM, in characters (the rest of a large batched
+/// context is left out)
+const MAX_CONTEXT_CHARS: usize = 16_000;
+
+/// Questions the context answers, other than `question`
+///
+/// Returns no suggestions when the LLM call fails.
+pub async fn suggest(
+    llm: &dyn LlmProvider,
+    question: &str,
+    context: &str,
+    count: usize,
+    language: Option<&str>,
+) -> Vec<String> {
+    if count == 0 || context.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let prompt = follow_up_prompt(question, truncate(context, MAX_CONTEXT_CHARS), count, language);
+    match llm
+        .complete(&prompt)
+        .instrument(tracing::info_span!("suggest_follow_ups", count))
+        .await
+    {
+        Ok(reply) => parse_questions(&reply, question, count),
+        Err(e) => {
+            tracing::warn!("Follow-up question generation failed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn follow_up_prompt(question: &str, context: &str, count: usize, language: Option<&str>) -> String {
+    let language = language
+        .map(|language| format!(" Write them in {}.", language))
+        .unwrap_or_default();
+    format!(
+        "A user asked a question about the documents below. Suggest {count} short follow-up questions \
+        the user might ask next that the documents below answer. Don't repeat the original question. \
+        Write one question per line, with no numbering and no other text.{language}\n\n\
+        Documents:\n{context}\n\nOriginal question: {question}"
+    )
+}
+
+fn parse_questions(reply: &str, question: &str, count: usize) -> Vec<String> {
+    let asked = question.trim().to_lowercase();
+    let mut questions: Vec<String> = Vec::new();
+    for line in reply.lines() {
+        let line = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•'))
+            .trim()
+            .trim_matches('"');
+        if !line.ends_with('?') || line.len() < 5 {
+            continue;
+        }
+        let lower = line.to_lowercase();
+        if lower == asked || questions.iter().any(|q| q.to_lowercase() == lower) {
+            continue;
+        }
+        questions.push(line.to_string());
+        if questions.len() == count {
+            break;
+        }
+    }
+    questions
+}
+
+fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_questions() {
+        let reply = "Here are some questions:\n\
+            1. What is the notice period?\n\
+            - How are refunds handled?\n\
+            * what is the notice period?\n\
+            \"Who approves travel?\"\n\
+            When does the contract end?";
+        assert_eq!(
+            parse_questions(reply, "How do I cancel?", 3),
+            vec!["What is the notice period?", "How are refunds handled?", "Who approves travel?"]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Answer formats
+//!
+//! Answers are Markdown unless the query asks otherwise. `plain` answers are
+//! asked to do without markup, and any Markdown the model writes anyway is
+//! stripped, for chat and email integrations. `json` answers are asked for an
+//! object with the answer, its key points and the sources it cites; the reply
+//! is checked here, and one that isn't such an object is structured from its
+//! text instead.
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::providers::llm::GeneratedAnswer;
+use crate::types::query::AnswerFormat;
+
+const PLAIN_INSTRUCTION: &str = "ANSWER FORMAT: Plain text only. Do not use Markdown: no headings, bold, italics, \
+    tables or code blocks. Write short paragraphs, and start list items with \"- \". Keep [Source: ...] citations as given.";
+
+const JSON_INSTRUCTION: &str = "ANSWER FORMAT: Reply with one JSON object and nothing else, shaped as \
+    {\"answer\": \"...\", \"bullet_points\": [\"...\"], \"citations\": [1, 2]}. \"answer\" is the full answer \
+    as plain text without Markdown, keeping [Source: ...] citations; \"bullet_points\" are its key points, one \
+    short sentence each, without citations; \"citations\" are the numbers of the listed sources the answer uses.";
+
+/// Instruction appended to the question for a format (none for Markdown)
+pub fn instruction(format: AnswerFormat) -> Option<&'static str> {
+    match format {
+        AnswerFormat::Markdown => None,
+        AnswerFormat::Plain => Some(PLAIN_INSTRUCTION),
+        AnswerFormat::Json => Some(JSON_INSTRUCTION),
+    }
+}
+
+/// Reply to the JSON format instruction
+#[derive(Deserialize)]
+struct JsonReply {
+    answer: String,
+    #[serde(default)]
+    bullet_points: Vec<String>,
+    #[serde(default)]
+    citations: Vec<serde_json::Value>,
+}
+
+/// Read a `json` reply, citing `sources` numbered sources
+///
+/// The reply's answer replaces its text and the source numbers in range
+/// become the cited sources (unless the provider already reported them).
+/// Returns the key points. A reply that isn't the requested object is kept
+/// as text, with its list items as the key points.
+pub fn read_json_reply(answer: &mut GeneratedAnswer, sources: usize) -> Vec<String> {
+    let reply = match parse_json_reply(&answer.text) {
+        Some(reply) => reply,
+        None => {
+            tracing::warn!("Answer is not the requested JSON object, structuring it from the text");
+            return list_items(&answer.text);
+        }
+    };
+
+    let mut cited: Vec<usize> = Vec::new();
+    for number in reply.citations.iter().filter_map(source_number) {
+        if (1..=sources).contains(&number) && !cited.contains(&number) {
+            cited.push(number);
+        }
+    }
+    answer.text = reply.answer;
+    if answer.cited_sources.is_none() && !cited.is_empty() {
+        answer.cited_sources = Some(cited);
+    }
+
+    let marker = Regex::new(r"\s*\[(?:Source:[^\]]*|\d{1,3}(?:\s*,\s*\d{1,3})*)\]").expect("Invalid regex");
+    reply
+        .bullet_points
+        .iter()
+        .map(|point| strip_markdown(&marker.replace_all(point, "")))
+        .map(|point| point.trim().trim_start_matches("- ").to_string())
+        .filter(|point| !point.is_empty())
+        .collect()
+}
+
+/// The JSON object of a reply, which models sometimes wrap in a code fence
+/// or surround with text
+fn parse_json_reply(text: &str) -> Option<JsonReply> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    let reply: JsonReply = serde_json::from_str(text.get(start..=end)?).ok()?;
+    (!reply.answer.trim().is_empty()).then_some(reply)
+}
+
+/// Source number given as a number or a string ("2" or "[2]")
+fn source_number(value: &serde_json::Value) -> Option<usize> {
+    match value {
+        serde_json::Value::Number(number) => number.as_u64().map(|n| n as usize),
+        serde_json::Value::String(text) => text.trim().trim_matches(['[', ']']).trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// List items of a Markdown or plain text answer
+fn list_items(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let item = ["- ", "* ", "+ ", "• "].iter().find_map(|bullet| line.strip_prefix(bullet))?;
+            Some(strip_markdown(item).trim().to_string())
+        })
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Remove Markdown from an answer, keeping its text, list items, links and
+/// citations
+///
+/// Headings, emphasis, code, block quotes and rules lose their markup; table
+/// rows become their cells separated by semicolons.
+pub fn strip_markdown(text: &str) -> String {
+    let link = Regex::new(r"!?\[([^\]]+)\]\(([^)\s]+)\)").expect("Invalid regex");
+    let strong = Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").expect("Invalid regex");
+    let emphasis = Regex::new(r"\*([^*\s][^*]*)\*").expect("Invalid regex");
+    let code = Regex::new(r"`+([^`]+)`+").expect("Invalid regex");
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || is_rule(trimmed) {
+            continue;
+        }
+        let line = if trimmed.starts_with('|') {
+            let cells: Vec<&str> = trimmed.split('|').map(str::trim).filter(|cell| !cell.is_empty()).collect();
+            if cells.iter().all(|cell| cell.chars().all(|c| matches!(c, '-' | ':'))) {
+                continue;
+            }
+            cells.join("; ")
+        } else if trimmed.starts_with('#') {
+            trimmed.trim_start_matches('#').trim().to_string()
+        } else if let Some(quoted) = trimmed.strip_prefix('>') {
+            quoted.trim().to_string()
+        } else if let Some(item) = trimmed.strip_prefix("* ").or_else(|| trimmed.strip_prefix("+ ")) {
+            format!("- {}", item)
+        } else {
+            line.trim_end().to_string()
+        };
+
+        let line = link.replace_all(&line, |caps: &regex::Captures| {
+            if caps[1] == caps[2] { caps[1].to_string() } else { format!("{} ({})", &caps[1], &caps[2]) }
+        });
+        let line = strong.replace_all(&line, "$1$2");
+        let line = emphasis.replace_all(&line, "$1");
+        let line = code.replace_all(&line, "$1");
+
+        // Keep single blank lines between paragraphs
+        if !(line.trim().is_empty() && lines.last().map_or(true, |last| last.trim().is_empty())) {
+            lines.push(line.into_owned());
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Horizontal rule (`---`, `***`, `___`)
+fn is_rule(line: &str) -> bool {
+    line.len() >= 3 && ['-', '*', '_'].iter().any(|&c| line.chars().all(|l| l == c || l == ' '))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markdown() {
+        let markdown = "## Refunds\n\nRefunds take **14 days** [Source: policy.pdf, Page 2].\n\n\n\
+            * Ask `support` first\n* See [the FAQ](https://example.com/faq)\n\n---\n\
+            | Plan | Days |\n|---|---|\n| Basic | 14 |";
+        assert_eq!(
+            strip_markdown(markdown),
+            "Refunds\n\nRefunds take 14 days [Source: policy.pdf, Page 2].\n\n\
+            - Ask support first\n- See the FAQ (https://example.com/faq)\n\nPlan; Days\nBasic; 14"
+        );
+    }
+
+    #[test]
+    fn test_read_json_reply() {
+        let reply = "```json\n{\"answer\": \"Refunds take 14 days [Source: policy.pdf, Page 2].\", \
+            \"bullet_points\": [\"**Refunds** take 14 days [1]\", \" \"], \"citations\": [2, \"1\", 2, 9]}\n```";
+        let mut answer = GeneratedAnswer::from(reply.to_string());
+        let points = read_json_reply(&mut answer, 3);
+        assert_eq!(answer.text, "Refunds take 14 days [Source: policy.pdf, Page 2].");
+        assert_eq!(answer.cited_sources, Some(vec![2, 1]));
+        assert_eq!(points, ["Refunds take 14 days"]);
+
+        // Free text is kept, its list items becoming the key points
+        let mut answer = GeneratedAnswer::from("Two options:\n- Refund\n- *Exchange*".to_string());
+        assert_eq!(read_json_reply(&mut answer, 3), ["Refund", "Exchange"]);
+        assert_eq!(answer.cited_sources, None);
+    }
+}
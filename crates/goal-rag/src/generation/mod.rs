@@ -1,9 +1,19 @@
 //! Answer generation with LLM and citation handling
 
 pub mod citation;
+pub mod follow_ups;
+pub mod format;
+pub mod guard;
 pub mod ollama;
+pub mod packing;
 pub mod prompt;
+pub mod provenance;
+pub mod templates;
 
-pub use citation::extract_and_link_citations;
+pub use citation::{extract_and_link_citations, link_generated_citations};
+pub use guard::Guard;
 pub use ollama::OllamaClient;
+pub use packing::{ContextPacker, PackedContext};
 pub use prompt::PromptBuilder;
+pub use provenance::ProvenanceSigner;
+pub use templates::{PromptTemplate, PromptTemplates, PromptVars};
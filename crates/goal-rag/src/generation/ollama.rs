@@ -27,6 +27,9 @@ struct GenerateRequest {
     prompt: String,
     stream: bool,
     options: GenerateOptions,
+    /// Base64 images for multimodal models
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -149,26 +152,26 @@ impl OllamaClient {
         }).await
     }
 
-    /// Generate an answer with citations and retry logic
-    pub async fn generate_answer(
-        &self,
-        question: &str,
-        context: &str,
-        citations: &[Citation],
-    ) -> Result<String> {
+    /// Generate a completion for a prompt, with retry logic
+    pub async fn generate(&self, prompt: &str) -> Result<String> {
+        self.generate_with_images(prompt, &[]).await
+    }
+
+    /// Generate a completion for a prompt with attached base64 images
+    ///
+    /// Requires a multimodal model such as llava.
+    pub async fn generate_with_images(&self, prompt: &str, images: &[String]) -> Result<String> {
         let url = format!("{}/api/generate", self.config.base_url);
-        let prompt = PromptBuilder::build_rag_prompt(question, context, citations);
         let model = self.config.generate_model.clone();
         let temperature = self.config.temperature;
         let client = self.client.clone();
 
-        tracing::info!("Generating answer with model: {}", model);
-
         self.retry_request(|| {
             let url = url.clone();
-            let prompt = prompt.clone();
+            let prompt = prompt.to_string();
             let model = model.clone();
             let client = client.clone();
+            let images = images.to_vec();
 
             async move {
                 let request = GenerateRequest {
@@ -178,6 +181,7 @@ impl OllamaClient {
                     options: GenerateOptions {
                         temperature,
                     },
+                    images,
                 };
 
                 let response = client
@@ -206,6 +210,20 @@ impl OllamaClient {
         }).await
     }
 
+    /// Generate an answer with citations and retry logic
+    pub async fn generate_answer(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+    ) -> Result<String> {
+        let prompt = PromptBuilder::build_rag_prompt(question, context, citations);
+
+        tracing::info!("Generating answer with model: {}", self.config.generate_model);
+
+        self.generate(&prompt).await
+    }
+
     /// Generate answer with learned context from past Q&A
     pub async fn generate_with_learning(
         &self,
@@ -214,54 +232,11 @@ impl OllamaClient {
         citations: &[Citation],
         past_qa: &[(String, String)],  // (question, answer) pairs from learning
     ) -> Result<String> {
-        let url = format!("{}/api/generate", self.config.base_url);
         let prompt = PromptBuilder::build_rag_prompt_with_learning(question, context, citations, past_qa);
-        let model = self.config.generate_model.clone();
-        let temperature = self.config.temperature;
-        let client = self.client.clone();
 
         tracing::info!("Generating answer with {} past Q&A examples", past_qa.len());
 
-        self.retry_request(|| {
-            let url = url.clone();
-            let prompt = prompt.clone();
-            let model = model.clone();
-            let client = client.clone();
-
-            async move {
-                let request = GenerateRequest {
-                    model,
-                    prompt,
-                    stream: false,
-                    options: GenerateOptions {
-                        temperature,
-                    },
-                };
-
-                let response = client
-                    .post(&url)
-                    .json(&request)
-                    .send()
-                    .await
-                    .map_err(|e| Error::Llm(format!("Generation request failed: {}", e)))?;
-
-                if !response.status().is_success() {
-                    let status = response.status();
-                    let body = response.text().await.unwrap_or_default();
-                    return Err(Error::Llm(format!(
-                        "Generation failed: HTTP {} - {}",
-                        status, body
-                    )));
-                }
-
-                let generate_response: GenerateResponse = response
-                    .json()
-                    .await
-                    .map_err(|e| Error::Llm(format!("Failed to parse generation response: {}", e)))?;
-
-                Ok(generate_response.response)
-            }
-        }).await
+        self.generate(&prompt).await
     }
 
     /// Generate a streaming response (returns chunks)
@@ -0,0 +1,241 @@
+//! Fitting retrieved chunks into a token budget
+//!
+//! Chunks are taken by score until the budget is used up. The chunk that
+//! crosses the budget is trimmed when enough of it fits; the rest are
+//! dropped. Kept chunks stay in their original order so their numbering
+//! matches the citations. For batched answering, all chunks are instead
+//! split into consecutive batches that each fit the budget.
+
+use std::sync::OnceLock;
+
+use tiktoken_rs::CoreBPE;
+
+use crate::config::{ContextConfig, TokenizerKind};
+use crate::providers::vector_store::VectorSearchResult;
+
+use super::PromptBuilder;
+
+/// Appended to trimmed chunks
+const TRUNCATION_MARKER: &str = " [...]";
+
+/// Counts and trims text in tokens
+#[derive(Debug, Clone, Copy)]
+pub struct TokenCounter {
+    tokenizer: TokenizerKind,
+    chars_per_token: f32,
+}
+
+impl TokenCounter {
+    pub fn new(tokenizer: TokenizerKind, chars_per_token: f32) -> Self {
+        Self {
+            tokenizer,
+            chars_per_token: chars_per_token.max(0.1),
+        }
+    }
+
+    /// Tokens in `text`
+    pub fn count(&self, text: &str) -> usize {
+        match self.bpe() {
+            Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+            None => (text.chars().count() as f32 / self.chars_per_token).ceil() as usize,
+        }
+    }
+
+    /// The first `max_tokens` tokens of `text`
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        if let Some(bpe) = self.bpe() {
+            let tokens = bpe.encode_with_special_tokens(text);
+            if tokens.len() <= max_tokens {
+                return text.to_string();
+            }
+            // A cut inside a multi-byte character doesn't decode, back off a token or two
+            for end in (max_tokens.saturating_sub(3)..=max_tokens).rev() {
+                if let Ok(prefix) = bpe.decode(tokens[..end].to_vec()) {
+                    return prefix;
+                }
+            }
+        }
+
+        let max_chars = (max_tokens as f32 * self.chars_per_token) as usize;
+        text.chars().take(max_chars).collect()
+    }
+
+    fn bpe(&self) -> Option<&'static CoreBPE> {
+        static CL100K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+        if self.tokenizer != TokenizerKind::Cl100k {
+            return None;
+        }
+        CL100K
+            .get_or_init(|| match tiktoken_rs::cl100k_base() {
+                Ok(bpe) => Some(bpe),
+                Err(e) => {
+                    tracing::warn!("Failed to load cl100k tokenizer, estimating token counts: {}", e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// Chunks that fit the budget
+#[derive(Debug, Clone, Default)]
+pub struct PackedContext {
+    /// Kept chunks (trimmed where needed), in their original order
+    pub results: Vec<VectorSearchResult>,
+    /// Positions of the kept chunks in the input
+    pub kept: Vec<usize>,
+    /// Chunks that were trimmed to fit
+    pub truncated: usize,
+    /// Chunks left out
+    pub dropped: usize,
+    /// Tokens used by the kept chunks
+    pub tokens: usize,
+}
+
+/// Packs retrieved chunks into a token budget
+pub struct ContextPacker {
+    counter: TokenCounter,
+    budget: usize,
+    min_chunk_tokens: usize,
+}
+
+impl ContextPacker {
+    pub fn new(config: &ContextConfig, budget: usize) -> Self {
+        Self {
+            counter: TokenCounter::new(config.tokenizer, config.chars_per_token),
+            budget,
+            min_chunk_tokens: config.min_chunk_tokens.max(1),
+        }
+    }
+
+    /// Keep the highest-scoring chunks that fit the budget
+    pub fn pack(&self, results: &[VectorSearchResult]) -> PackedContext {
+        let mut order: Vec<usize> = (0..results.len()).collect();
+        order.sort_by(|&a, &b| results[b].similarity.total_cmp(&results[a].similarity));
+
+        let mut packed: Vec<Option<VectorSearchResult>> = vec![None; results.len()];
+        let mut remaining = self.budget;
+        let mut truncated = 0;
+
+        for i in order {
+            let result = &results[i];
+            // Cost as formatted in the prompt, with the source line
+            let tokens = self.counter.count(&PromptBuilder::build_context(std::slice::from_ref(result)));
+            if tokens <= remaining {
+                remaining -= tokens;
+                packed[i] = Some(result.clone());
+                continue;
+            }
+
+            let overhead = tokens.saturating_sub(self.counter.count(&result.chunk.content))
+                + self.counter.count(TRUNCATION_MARKER);
+            let available = remaining.saturating_sub(overhead);
+            if available < self.min_chunk_tokens {
+                continue;
+            }
+
+            let mut result = result.clone();
+            result.chunk.content = format!("{}{}", self.counter.truncate(&result.chunk.content, available), TRUNCATION_MARKER);
+            remaining = remaining.saturating_sub(available + overhead);
+            packed[i] = Some(result);
+            truncated += 1;
+        }
+
+        let kept: Vec<usize> = packed.iter().enumerate().filter(|(_, r)| r.is_some()).map(|(i, _)| i).collect();
+        PackedContext {
+            dropped: results.len() - kept.len(),
+            results: packed.into_iter().flatten().collect(),
+            kept,
+            truncated,
+            tokens: self.budget - remaining,
+        }
+    }
+
+    /// Split all chunks into consecutive batches of at most `max_chunks` that
+    /// each fit the budget
+    ///
+    /// A chunk larger than the budget gets a batch of its own, trimmed to fit.
+    pub fn batches(&self, results: &[VectorSearchResult], max_chunks: usize) -> Vec<PackedContext> {
+        let max_chunks = max_chunks.max(1);
+        let mut batches = Vec::new();
+        let mut batch = PackedContext::default();
+
+        for (i, result) in results.iter().enumerate() {
+            let tokens = self.counter.count(&PromptBuilder::build_context(std::slice::from_ref(result)));
+            let full = batch.results.len() >= max_chunks || batch.tokens.saturating_add(tokens) > self.budget;
+            if full && !batch.results.is_empty() {
+                batches.push(std::mem::take(&mut batch));
+            }
+
+            if tokens > self.budget {
+                let single = self.pack(std::slice::from_ref(result));
+                batch.truncated += single.truncated;
+                batch.dropped += single.dropped;
+                if single.results.is_empty() {
+                    continue;
+                }
+                batch.results.extend(single.results);
+                batch.tokens = self.budget;
+            } else {
+                batch.results.push(result.clone());
+                batch.tokens += tokens;
+            }
+            batch.kept.push(i);
+        }
+
+        if !batch.results.is_empty() {
+            batches.push(batch);
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Chunk, ChunkSource};
+    use uuid::Uuid;
+
+    fn result(content: &str, similarity: f32) -> VectorSearchResult {
+        let chunk = Chunk::new(Uuid::new_v4(), content.to_string(), ChunkSource::text("doc.txt".to_string()), 0, 0, 0);
+        VectorSearchResult::new(chunk, similarity)
+    }
+
+    #[test]
+    fn test_pack_by_score() {
+        let config = ContextConfig {
+            tokenizer: TokenizerKind::Estimate,
+            min_chunk_tokens: 10,
+            ..Default::default()
+        };
+        let counter = TokenCounter::new(TokenizerKind::Estimate, 4.0);
+        let long = "word ".repeat(200);
+        let results = vec![result(&long, 0.5), result("short and relevant", 0.9), result(&long, 0.4)];
+        let one = counter.count(&PromptBuilder::build_context(&results[1..2]));
+        let first = counter.count(&PromptBuilder::build_context(&results[0..1]));
+
+        // Everything fits
+        let packed = ContextPacker::new(&config, 10_000).pack(&results);
+        assert_eq!((packed.kept.clone(), packed.dropped, packed.truncated), (vec![0, 1, 2], 0, 0));
+
+        // The best chunk, then a trimmed second one; the order of the input is kept
+        let packed = ContextPacker::new(&config, one + first / 2).pack(&results);
+        assert_eq!(packed.kept, vec![0, 1]);
+        assert_eq!((packed.dropped, packed.truncated), (1, 1));
+        assert!(packed.results[0].chunk.content.ends_with(TRUNCATION_MARKER));
+        assert!(packed.tokens <= one + first / 2);
+
+        // Too little room to trim into
+        let packed = ContextPacker::new(&config, one + 5).pack(&results);
+        assert_eq!((packed.kept, packed.dropped, packed.truncated), (vec![1], 2, 0));
+
+        // Batches keep every chunk, splitting on the budget and the chunk limit
+        let batches = ContextPacker::new(&config, first + one).batches(&results, 8);
+        let kept: Vec<Vec<usize>> = batches.iter().map(|b| b.kept.clone()).collect();
+        assert_eq!(kept, vec![vec![0, 1], vec![2]]);
+        let batches = ContextPacker::new(&config, 10_000).batches(&results, 2);
+        assert_eq!(batches.len(), 2);
+        let batches = ContextPacker::new(&config, first / 2).batches(&results, 8);
+        assert_eq!(batches.iter().map(|b| b.truncated).collect::<Vec<_>>(), vec![1, 0, 1]);
+    }
+}
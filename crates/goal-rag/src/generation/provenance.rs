@@ -0,0 +1,229 @@
+//! Signed answer provenance
+//!
+//! A provenance record binds an answer to the chunks it cited and to the
+//! corpus it was generated from. The signature covers the answer text, a hash
+//! of every cited chunk and the corpus snapshot id, so a downstream system can
+//! later prove that an answer it stored is exactly what we produced and which
+//! corpus state it came from.
+//!
+//! The signed message is a version tag followed by length-prefixed fields
+//! (big-endian u64 length, then bytes): answer, corpus snapshot id, signing
+//! time (RFC 3339), then each citation hash in citation order.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::Utc;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{ProvenanceAlgorithm, ProvenanceConfig};
+use crate::error::{Error, Result};
+use crate::types::AnswerProvenance;
+
+/// Version tag prepended to every signed message
+const MESSAGE_VERSION: &[u8] = b"goal-rag/provenance/v1";
+
+/// Hash identifying a cited chunk and its exact text
+pub fn citation_hash(chunk_id: &Uuid, content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(chunk_id.as_bytes());
+    hasher.update(content.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Hash identifying the corpus state from (document id, content hash) pairs
+pub fn corpus_snapshot_id<'a>(documents: impl IntoIterator<Item = (Uuid, &'a str)>) -> String {
+    let mut documents: Vec<(Uuid, &str)> = documents.into_iter().collect();
+    documents.sort();
+
+    let mut hasher = Sha256::new();
+    for (id, content_hash) in documents {
+        hasher.update(id.as_bytes());
+        hasher.update(content_hash.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+enum SigningMaterial {
+    Hmac(Vec<u8>),
+    Ed25519(SigningKey),
+}
+
+/// Signs and verifies answer provenance
+pub struct ProvenanceSigner {
+    material: SigningMaterial,
+    key_id: Option<String>,
+}
+
+impl ProvenanceSigner {
+    /// Build a signer from configuration, `None` if signing is disabled
+    pub fn from_config(config: &ProvenanceConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let key = match (&config.key, &config.key_file) {
+            (Some(key), _) => key.trim().to_string(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| Error::Config(format!("Failed to read provenance key {:?}: {}", path, e)))?
+                .trim()
+                .to_string(),
+            (None, None) => {
+                return Err(Error::Config(
+                    "Provenance signing requires provenance.key or provenance.key_file".to_string(),
+                ))
+            }
+        };
+        if key.is_empty() {
+            return Err(Error::Config("Provenance key is empty".to_string()));
+        }
+
+        let material = match config.algorithm {
+            ProvenanceAlgorithm::HmacSha256 => SigningMaterial::Hmac(key.into_bytes()),
+            ProvenanceAlgorithm::Ed25519 => {
+                let seed: [u8; 32] = BASE64
+                    .decode(&key)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| {
+                        Error::Config("Ed25519 provenance key must be a base64-encoded 32-byte seed".to_string())
+                    })?;
+                SigningMaterial::Ed25519(SigningKey::from_bytes(&seed))
+            }
+        };
+
+        Ok(Some(Self {
+            material,
+            key_id: config.key_id.clone(),
+        }))
+    }
+
+    /// Algorithm name as reported in provenance records
+    pub fn algorithm(&self) -> &'static str {
+        match self.material {
+            SigningMaterial::Hmac(_) => "hmac-sha256",
+            SigningMaterial::Ed25519(_) => "ed25519",
+        }
+    }
+
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// Base64 public key for offline verification (Ed25519 only)
+    pub fn public_key(&self) -> Option<String> {
+        match self.material {
+            SigningMaterial::Hmac(_) => None,
+            SigningMaterial::Ed25519(ref key) => Some(BASE64.encode(key.verifying_key().as_bytes())),
+        }
+    }
+
+    /// Sign an answer
+    pub fn sign(&self, answer: &str, citation_hashes: Vec<String>, corpus_snapshot_id: String) -> AnswerProvenance {
+        let mut provenance = AnswerProvenance {
+            algorithm: self.algorithm().to_string(),
+            key_id: self.key_id.clone(),
+            corpus_snapshot_id,
+            citation_hashes,
+            signed_at: Utc::now(),
+            signature: String::new(),
+        };
+
+        let message = signed_message(answer, &provenance);
+        let signature = match self.material {
+            SigningMaterial::Hmac(ref key) => hmac(key, &message).finalize().into_bytes().to_vec(),
+            SigningMaterial::Ed25519(ref key) => key.sign(&message).to_bytes().to_vec(),
+        };
+        provenance.signature = BASE64.encode(signature);
+        provenance
+    }
+
+    /// Check that a provenance record was produced by this signer for this answer
+    pub fn verify(&self, answer: &str, provenance: &AnswerProvenance) -> bool {
+        if provenance.algorithm != self.algorithm() {
+            return false;
+        }
+        let Ok(signature) = BASE64.decode(&provenance.signature) else {
+            return false;
+        };
+
+        let message = signed_message(answer, provenance);
+        match self.material {
+            SigningMaterial::Hmac(ref key) => hmac(key, &message).verify_slice(&signature).is_ok(),
+            SigningMaterial::Ed25519(ref key) => {
+                let verifying_key: VerifyingKey = key.verifying_key();
+                Signature::from_slice(&signature)
+                    .map(|signature| verifying_key.verify(&message, &signature).is_ok())
+                    .unwrap_or(false)
+            }
+        }
+    }
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac
+}
+
+/// Canonical byte encoding of the signed fields
+fn signed_message(answer: &str, provenance: &AnswerProvenance) -> Vec<u8> {
+    let signed_at = provenance.signed_at.to_rfc3339();
+    let fields = [answer, provenance.corpus_snapshot_id.as_str(), signed_at.as_str()]
+        .into_iter()
+        .chain(provenance.citation_hashes.iter().map(String::as_str));
+
+    let mut message = MESSAGE_VERSION.to_vec();
+    for field in fields {
+        message.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        message.extend_from_slice(field.as_bytes());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(algorithm: ProvenanceAlgorithm, key: &str) -> ProvenanceSigner {
+        ProvenanceSigner::from_config(&ProvenanceConfig {
+            enabled: true,
+            algorithm,
+            key: Some(key.to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let seed = BASE64.encode([7u8; 32]);
+        for signer in [signer(ProvenanceAlgorithm::HmacSha256, "secret"), signer(ProvenanceAlgorithm::Ed25519, &seed)] {
+            let hashes = vec![citation_hash(&Uuid::nil(), "chunk text")];
+            let provenance = signer.sign("The answer [1]", hashes, "snapshot".to_string());
+            assert!(signer.verify("The answer [1]", &provenance));
+
+            // Any change to the answer, citations or snapshot breaks the signature
+            assert!(!signer.verify("The answer [2]", &provenance));
+            let mut tampered = provenance.clone();
+            tampered.citation_hashes[0] = citation_hash(&Uuid::nil(), "other text");
+            assert!(!signer.verify("The answer [1]", &tampered));
+            let mut tampered = provenance.clone();
+            tampered.corpus_snapshot_id = "other".to_string();
+            assert!(!signer.verify("The answer [1]", &tampered));
+        }
+    }
+
+    #[test]
+    fn test_snapshot_id_is_order_independent() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        assert_eq!(
+            corpus_snapshot_id([(a, "h1"), (b, "h2")]),
+            corpus_snapshot_id([(b, "h2"), (a, "h1")])
+        );
+        assert_ne!(corpus_snapshot_id([(a, "h1")]), corpus_snapshot_id([(a, "h2")]));
+    }
+}
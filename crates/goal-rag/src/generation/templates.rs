@@ -0,0 +1,343 @@
+//! Operator-defined prompt templates
+//!
+//! Templates are Jinja (minijinja) sources stored as `<name>.toml` files with
+//! an optional `system` part and a `user` part. They are loaded at startup,
+//! edited through /api/prompts and reloaded when the directory changes on
+//! disk. The rendered system part is placed before the user part, since the
+//! providers take a single prompt.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use minijinja::Environment;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::PromptsConfig;
+use crate::error::{Error, Result};
+
+/// A prompt template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PromptTemplate {
+    /// Name (letters, digits, `-` and `_`; the file is `<name>.toml`)
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Instructions placed before the user part
+    #[serde(default)]
+    pub system: Option<String>,
+    /// Prompt with the question and context
+    pub user: String,
+}
+
+/// A question and answer from earlier interactions
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub question: String,
+    pub answer: String,
+}
+
+/// Variables available to templates
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PromptVars {
+    /// Numbered retrieved chunks
+    pub context: String,
+    pub question: String,
+    /// Numbered source list (`[1] file.pdf, Page 3`)
+    pub sources: String,
+    pub history: Vec<HistoryEntry>,
+    /// Request metadata plus `collection` and `language`
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Directory entries seen by the last load (name, modified, size)
+type Fingerprint = Vec<(String, Option<SystemTime>, u64)>;
+
+/// Prompt templates loaded from a directory
+pub struct PromptTemplates {
+    dir: PathBuf,
+    config: RwLock<PromptsConfig>,
+    env: Environment<'static>,
+    templates: RwLock<HashMap<String, PromptTemplate>>,
+    fingerprint: Mutex<Fingerprint>,
+}
+
+impl PromptTemplates {
+    /// Load the templates in `config.dir` (or `default_dir` when unset)
+    pub fn new(config: PromptsConfig, default_dir: PathBuf) -> Result<Self> {
+        let dir = config.dir.clone().unwrap_or(default_dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let templates = Self {
+            dir,
+            config: RwLock::new(config),
+            env: Environment::new(),
+            templates: RwLock::new(HashMap::new()),
+            fingerprint: Mutex::new(Vec::new()),
+        };
+        templates.reload()?;
+        templates.warn_missing();
+        Ok(templates)
+    }
+
+    /// Take over reloaded template choices (the directory stays as it is)
+    pub fn reconfigure(&self, config: &PromptsConfig) {
+        {
+            let mut current = self.config.write();
+            current.default_template = config.default_template.clone();
+            current.collections = config.collections.clone();
+        }
+        self.warn_missing();
+    }
+
+    fn warn_missing(&self) {
+        let config = self.config.read();
+        for name in config.default_template.iter().chain(config.collections.values()) {
+            if self.get(name).is_none() {
+                tracing::warn!("Prompt template '{}' is configured but not in {:?}", name, self.dir);
+            }
+        }
+    }
+
+    /// Template directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Re-read all templates from disk, returning how many were loaded
+    ///
+    /// Files that fail to parse are skipped with a warning.
+    pub fn reload(&self) -> Result<usize> {
+        let fingerprint = self.scan()?;
+        let mut loaded = HashMap::new();
+        for (file, _, _) in &fingerprint {
+            let path = self.dir.join(file);
+            let Some(name) = file.strip_suffix(".toml") else { continue };
+            match self.read(&path, name) {
+                Ok(template) => {
+                    loaded.insert(template.name.clone(), template);
+                }
+                Err(e) => tracing::warn!("Skipping prompt template {:?}: {}", path, e),
+            }
+        }
+
+        let count = loaded.len();
+        *self.templates.write() = loaded;
+        *self.fingerprint.lock() = fingerprint;
+        tracing::info!("Loaded {} prompt templates from {:?}", count, self.dir);
+        Ok(count)
+    }
+
+    /// All templates, ordered by name
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        let mut templates: Vec<PromptTemplate> = self.templates.read().values().cloned().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Template by name
+    pub fn get(&self, name: &str) -> Option<PromptTemplate> {
+        self.templates.read().get(name).cloned()
+    }
+
+    /// Create or replace a template after checking its name and syntax
+    pub fn save(&self, template: PromptTemplate) -> Result<PromptTemplate> {
+        self.validate(&template)?;
+
+        let file = toml::to_string(&template)
+            .map_err(|e| Error::Internal(format!("Failed to serialize prompt template: {}", e)))?;
+        std::fs::write(self.path(&template.name), file)?;
+
+        self.templates.write().insert(template.name.clone(), template.clone());
+        *self.fingerprint.lock() = self.scan()?;
+        tracing::info!("Saved prompt template '{}'", template.name);
+        Ok(template)
+    }
+
+    /// Remove a template, returning whether it existed
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        if self.templates.write().remove(name).is_none() {
+            return Ok(false);
+        }
+        let path = self.path(name);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        *self.fingerprint.lock() = self.scan()?;
+        tracing::info!("Deleted prompt template '{}'", name);
+        Ok(true)
+    }
+
+    /// Template for a query: the one it names, else its collection's, else the default
+    ///
+    /// A template named by the request must exist. Missing configured
+    /// templates fall back to the built-in prompts.
+    pub fn select(&self, requested: Option<&str>, collection: Option<&str>) -> Result<Option<PromptTemplate>> {
+        if let Some(name) = requested {
+            return self
+                .get(name)
+                .map(Some)
+                .ok_or_else(|| Error::Config(format!("Unknown prompt template '{}'", name)));
+        }
+
+        let config = self.config.read();
+        let configured = collection
+            .and_then(|c| config.collections.get(c))
+            .or(config.default_template.as_ref());
+        Ok(configured.and_then(|name| self.get(name)))
+    }
+
+    /// Render a template's system and user parts
+    pub fn render(&self, template: &PromptTemplate, vars: &PromptVars) -> Result<String> {
+        let render = |source: &str| {
+            self.env
+                .render_str(source, vars)
+                .map_err(|e| Error::Config(format!("Failed to render prompt template '{}': {}", template.name, e)))
+        };
+
+        let user = render(&template.user)?;
+        match template.system.as_deref().filter(|s| !s.trim().is_empty()) {
+            Some(system) => Ok(format!("{}\n\n{}", render(system)?.trim_end(), user)),
+            None => Ok(user),
+        }
+    }
+
+    /// Reload every `interval` when files in the directory change
+    pub fn spawn_watcher(self: &Arc<Self>, interval: Duration) {
+        let templates = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let changed = match templates.scan() {
+                    Ok(fingerprint) => fingerprint != *templates.fingerprint.lock(),
+                    Err(e) => {
+                        tracing::warn!("Failed to scan prompt templates in {:?}: {}", templates.dir, e);
+                        continue;
+                    }
+                };
+                if changed {
+                    if let Err(e) = templates.reload() {
+                        tracing::warn!("Failed to reload prompt templates: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    fn validate(&self, template: &PromptTemplate) -> Result<()> {
+        let valid_name = !template.name.is_empty()
+            && template.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(Error::Config(format!(
+                "Invalid prompt template name '{}' (use letters, digits, '-' and '_')",
+                template.name
+            )));
+        }
+        if template.user.trim().is_empty() {
+            return Err(Error::Config(format!("Prompt template '{}' has an empty user part", template.name)));
+        }
+
+        for (part, source) in [("system", template.system.as_deref()), ("user", Some(template.user.as_str()))] {
+            if let Some(source) = source {
+                self.env.template_from_str(source).map_err(|e| {
+                    Error::Config(format!("Invalid {} part in prompt template '{}': {}", part, template.name, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, path: &Path, name: &str) -> Result<PromptTemplate> {
+        let source = std::fs::read_to_string(path)?;
+        let mut template: PromptTemplate =
+            toml::from_str(&source).map_err(|e| Error::Config(format!("Invalid TOML: {}", e)))?;
+        template.name = name.to_string();
+        self.validate(&template)?;
+        Ok(template)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.toml", name))
+    }
+
+    fn scan(&self) -> Result<Fingerprint> {
+        let mut fingerprint = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let file = entry.file_name().to_string_lossy().into_owned();
+            if !file.ends_with(".toml") {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            fingerprint.push((file, metadata.modified().ok(), metadata.len()));
+        }
+        fingerprint.sort();
+        Ok(fingerprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, system: Option<&str>, user: &str) -> PromptTemplate {
+        PromptTemplate {
+            name: name.to_string(),
+            description: None,
+            system: system.map(String::from),
+            user: user.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_save_select_render() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = PromptsConfig::default();
+        config.default_template = Some("plain".to_string());
+        config.collections.insert("legal".to_string(), "legal".to_string());
+        let templates = PromptTemplates::new(config, dir.path().to_path_buf()).unwrap();
+
+        assert!(templates.save(template("bad name", None, "{{ question }}")).is_err());
+        assert!(templates.save(template("broken", None, "{% if %}")).is_err());
+
+        templates.save(template("plain", None, "Q: {{ question }}")).unwrap();
+        templates
+            .save(template(
+                "legal",
+                Some("You answer for {{ metadata.collection }}."),
+                "{% for h in history %}{{ h.question }}={{ h.answer }}\n{% endfor %}{{ context }}\nQ: {{ question }}",
+            ))
+            .unwrap();
+
+        assert_eq!(templates.select(None, Some("legal")).unwrap().unwrap().name, "legal");
+        assert_eq!(templates.select(None, Some("other")).unwrap().unwrap().name, "plain");
+        assert_eq!(templates.select(Some("legal"), None).unwrap().unwrap().name, "legal");
+        assert!(templates.select(Some("missing"), None).is_err());
+
+        let mut vars = PromptVars {
+            context: "[1] doc".to_string(),
+            question: "why?".to_string(),
+            history: vec![HistoryEntry { question: "a".to_string(), answer: "b".to_string() }],
+            ..Default::default()
+        };
+        vars.metadata.insert("collection".to_string(), "legal".into());
+        let legal = templates.get("legal").unwrap();
+        assert_eq!(
+            templates.render(&legal, &vars).unwrap(),
+            "You answer for legal.\n\na=b\n[1] doc\nQ: why?"
+        );
+
+        // Templates survive a reload from disk, deleted ones don't
+        assert!(templates.delete("plain").unwrap());
+        assert!(!templates.delete("plain").unwrap());
+        assert_eq!(templates.reload().unwrap(), 1);
+        assert_eq!(templates.get("legal"), Some(legal));
+        assert!(templates.select(None, None).unwrap().is_none());
+    }
+}
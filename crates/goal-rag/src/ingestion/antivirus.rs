@@ -0,0 +1,183 @@
+//! Malware scanning of uploads before they are parsed
+//!
+//! With `[antivirus]` enabled every ingested file is scanned first, by a
+//! ClamAV daemon (streamed with `INSTREAM`) or by an external command given
+//! the file's path, which exits with 0 when the file is clean and 1 when it
+//! is infected (as `clamscan` and `clamdscan` do). Infected files are
+//! copied to the quarantine directory and fail with `Error::Malware`, so the
+//! file registry records them at the `scanning` stage. A scanner that can't
+//! be reached fails the file as transient unless `fail_open` is set.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::config::AntivirusConfig;
+use crate::error::{Error, Result};
+
+/// Bytes sent per `INSTREAM` chunk
+const CLAMD_CHUNK: usize = 64 * 1024;
+
+/// Result of scanning a file
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// Infected, with the signature the scanner reported
+    Infected(String),
+}
+
+/// Scan an ingested file, quarantining it if infected
+///
+/// Infected files are copied to `quarantine_dir` (default: `quarantine` in
+/// `storage_dir`) and fail with `Error::Malware`.
+pub async fn scan_upload(config: &AntivirusConfig, storage_dir: &Path, filename: &str, data: &[u8]) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let limit = Duration::from_secs(config.timeout_secs.max(1));
+    let verdict = match tokio::time::timeout(limit, scan(config, data)).await {
+        Ok(verdict) => verdict,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, format!("timed out after {}s", limit.as_secs()))),
+    };
+
+    match verdict {
+        Ok(Verdict::Clean) => Ok(()),
+        Ok(Verdict::Infected(signature)) => {
+            let dir = config.quarantine_dir.clone().unwrap_or_else(|| storage_dir.join("quarantine"));
+            let path = quarantine(&dir, filename, data).await?;
+            tracing::warn!("Quarantined {} as {} ({})", filename, path.display(), signature);
+            Err(Error::Malware { filename: filename.to_string(), signature })
+        }
+        Err(e) if config.fail_open => {
+            tracing::warn!("Virus scan of {} failed, processing it unscanned: {}", filename, e);
+            Ok(())
+        }
+        Err(e) => Err(Error::ParserUnavailable(format!("virus scanner: {}", e))),
+    }
+}
+
+/// Scan with the configured scanner
+pub async fn scan(config: &AntivirusConfig, data: &[u8]) -> io::Result<Verdict> {
+    match &config.clamd {
+        Some(address) => scan_with_clamd(address, data).await,
+        None => scan_with_command(&config.command, data).await,
+    }
+}
+
+/// Stream a file to clamd at `host:port` or a Unix socket path
+async fn scan_with_clamd(address: &str, data: &[u8]) -> io::Result<Verdict> {
+    #[cfg(unix)]
+    if address.starts_with('/') {
+        return instream(tokio::net::UnixStream::connect(address).await?, data).await;
+    }
+    instream(tokio::net::TcpStream::connect(address).await?, data).await
+}
+
+async fn instream<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, data: &[u8]) -> io::Result<Verdict> {
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(CLAMD_CHUNK) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    stream.flush().await?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    parse_clamd_reply(&String::from_utf8_lossy(&reply))
+}
+
+/// Read clamd's `stream: OK` / `stream: <signature> FOUND` reply
+fn parse_clamd_reply(reply: &str) -> io::Result<Verdict> {
+    let reply = reply.trim_end_matches(['\0', '\n']).trim();
+    let result = reply.strip_prefix("stream:").unwrap_or(reply).trim();
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(signature.to_string()))
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, format!("clamd: {}", reply)))
+    }
+}
+
+/// Run the scanner command with the path of a temporary copy of the file
+async fn scan_with_command(command: &[String], data: &[u8]) -> io::Result<Verdict> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "neither clamd nor command is set"))?;
+    let file = tempfile::NamedTempFile::new()?;
+    tokio::fs::write(file.path(), data).await?;
+
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .arg(file.path())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+
+    match output.status.code() {
+        Some(0) => Ok(Verdict::Clean),
+        Some(1) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Ok(Verdict::Infected(command_signature(&stdout).unwrap_or("unknown").to_string()))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} exited with {}: {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim()),
+        )),
+    }
+}
+
+/// Signature from a `<path>: <signature> FOUND` output line
+fn command_signature(stdout: &str) -> Option<&str> {
+    stdout
+        .lines()
+        .filter_map(|line| line.trim().strip_suffix(" FOUND"))
+        .find_map(|line| line.rsplit_once(": ").map(|(_, signature)| signature))
+}
+
+/// Copy an infected file to the quarantine directory
+async fn quarantine(dir: &Path, filename: &str, data: &[u8]) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let hash = hex::encode(Sha256::digest(data));
+    let base_name: String = filename
+        .rsplit('/')
+        .next()
+        .unwrap_or(filename)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    // The suffix keeps the file from being opened by its type by accident
+    let path = dir.join(format!("{}-{}.quarantine", &hash[..16], base_name));
+    tokio::fs::write(&path, data).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scanner_output() {
+        assert_eq!(parse_clamd_reply("stream: OK\0").unwrap(), Verdict::Clean);
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Test-Signature FOUND\0").unwrap(),
+            Verdict::Infected("Eicar-Test-Signature".to_string())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+
+        let stdout = "/tmp/.tmpX1: Win.Test.EICAR_HDB-1 FOUND\n\n----------- SCAN SUMMARY -----------\n";
+        assert_eq!(command_signature(stdout), Some("Win.Test.EICAR_HDB-1"));
+        assert_eq!(command_signature("/tmp/.tmpX1: OK\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_scan_passes() {
+        let config = AntivirusConfig::default();
+        assert!(scan_upload(&config, Path::new("/nonexistent"), "a.pdf", b"data").await.is_ok());
+    }
+}
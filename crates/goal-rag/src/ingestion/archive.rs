@@ -0,0 +1,347 @@
+//! Archive (.zip, .tar, .tar.gz) extraction
+//!
+//! Entries are written into a temporary directory, never outside it, and
+//! handed out as paths to the extracted files, which callers read one at a
+//! time. Entry count, per-file size, total size and compression ratio are
+//! limited so a zip bomb is rejected instead of filling the disk. Archives
+//! inside archives are expanded up to a depth, read from their extracted file.
+
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::config::ArchiveConfig;
+use crate::error::{Error, Result};
+
+/// A file extracted from an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path inside the archive (`/`-separated); nested archives prefix their name
+    pub path: String,
+    /// The extracted file, removed when its [`Extracted`] is dropped
+    pub file: PathBuf,
+}
+
+/// Files extracted from an archive, on disk until this is dropped
+#[derive(Debug)]
+pub struct Extracted {
+    pub entries: Vec<ArchiveEntry>,
+    _dirs: Vec<TempDir>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(filename: &str) -> Option<ArchiveKind> {
+    let lower = filename.to_lowercase();
+    if lower.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if lower.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Whether a file is an archive that is expanded at ingestion
+pub fn is_archive(filename: &str) -> bool {
+    kind(filename).is_some()
+}
+
+/// Running totals across an archive and the archives nested in it
+#[derive(Default)]
+struct Budget {
+    entries: usize,
+    bytes: u64,
+}
+
+/// Extract every file in an archive
+///
+/// Directories, links and hidden entries (`.DS_Store`, `__MACOSX/`) are
+/// skipped. Fails if the archive is unreadable or breaks a limit. Blocking.
+pub fn extract(filename: &str, data: &[u8], config: &ArchiveConfig) -> Result<Extracted> {
+    extract_from(filename, io::Cursor::new(data), data.len() as u64, config)
+}
+
+/// Extract every file in an archive stored at `path` (see [`extract`])
+pub fn extract_file(filename: &str, path: &Path, config: &ArchiveConfig) -> Result<Extracted> {
+    let file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    extract_from(filename, file, size, config)
+}
+
+fn extract_from(filename: &str, reader: impl Read + Seek, size: u64, config: &ArchiveConfig) -> Result<Extracted> {
+    let mut budget = Budget::default();
+    let mut dirs = Vec::new();
+    let entries = extract_nested(filename, reader, size, config, 0, &mut budget, &mut dirs)?;
+    Ok(Extracted { entries, _dirs: dirs })
+}
+
+fn extract_nested(
+    filename: &str,
+    reader: impl Read + Seek,
+    size: u64,
+    config: &ArchiveConfig,
+    depth: usize,
+    budget: &mut Budget,
+    dirs: &mut Vec<TempDir>,
+) -> Result<Vec<ArchiveEntry>> {
+    let Some(kind) = kind(filename) else {
+        return Err(Error::UnsupportedFileType(format!("{} - Not an archive", filename)));
+    };
+
+    let dir = tempfile::tempdir()
+        .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
+    let mut limits = Limits {
+        filename,
+        config,
+        budget,
+        max_bytes: size.saturating_mul(config.max_compression_ratio),
+        bytes: 0,
+    };
+
+    let files = match kind {
+        ArchiveKind::Zip => extract_zip(reader, dir.path(), &mut limits)?,
+        ArchiveKind::Tar => extract_tar(reader, dir.path(), &mut limits)?,
+        ArchiveKind::TarGz => extract_tar(flate2::read::GzDecoder::new(reader), dir.path(), &mut limits)?,
+    };
+
+    let mut entries = Vec::new();
+    for path in files {
+        let file = dir.path().join(&path);
+        if is_archive(&path) && depth < config.max_depth {
+            let nested_archive = fs::File::open(&file)
+                .map_err(|e| Error::Internal(format!("Failed to read extracted file: {}", e)))?;
+            let nested_size = nested_archive.metadata()?.len();
+            for nested in extract_nested(&path, nested_archive, nested_size, config, depth + 1, budget, dirs)? {
+                entries.push(ArchiveEntry {
+                    path: format!("{}/{}", path, nested.path),
+                    file: nested.file,
+                });
+            }
+            // The nested archive itself is not needed anymore
+            let _ = fs::remove_file(&file);
+        } else {
+            entries.push(ArchiveEntry { path, file });
+        }
+    }
+
+    dirs.push(dir);
+    Ok(entries)
+}
+
+/// Limit checks while extracting one archive
+struct Limits<'a> {
+    filename: &'a str,
+    config: &'a ArchiveConfig,
+    budget: &'a mut Budget,
+    /// Extracted bytes allowed by the compression ratio
+    max_bytes: u64,
+    bytes: u64,
+}
+
+impl Limits<'_> {
+    fn violation(&self, reason: String) -> Error {
+        Error::file_parse(self.filename, format!("Archive rejected: {}", reason))
+    }
+
+    /// Copy one entry to `target`, enforcing the size limits
+    fn write(&mut self, reader: impl Read, target: &Path, name: &str) -> Result<()> {
+        self.budget.entries += 1;
+        if self.budget.entries > self.config.max_entries {
+            return Err(self.violation(format!("more than {} files", self.config.max_entries)));
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::Internal(format!("Failed to create extraction dir: {}", e)))?;
+        }
+        let mut file = fs::File::create(target)
+            .map_err(|e| Error::Internal(format!("Failed to create extracted file: {}", e)))?;
+
+        // Read one byte past the limit to detect oversized entries without trusting headers
+        let written = io::copy(&mut reader.take(self.config.max_entry_size + 1), &mut file)
+            .map_err(|e| self.violation(format!("failed to extract {}: {}", name, e)))?;
+
+        if written > self.config.max_entry_size {
+            return Err(self.violation(format!("{} is larger than {} bytes", name, self.config.max_entry_size)));
+        }
+        self.bytes += written;
+        self.budget.bytes += written;
+        if self.budget.bytes > self.config.max_total_size {
+            return Err(self.violation(format!("more than {} bytes extracted", self.config.max_total_size)));
+        }
+        if self.bytes > self.max_bytes {
+            return Err(self.violation(format!(
+                "compression ratio above {}:1",
+                self.config.max_compression_ratio
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn extract_zip(reader: impl Read + Seek, dir: &Path, limits: &mut Limits) -> Result<Vec<String>> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(|e| Error::file_parse(limits.filename, e.to_string()))?;
+
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|e| Error::file_parse(limits.filename, e.to_string()))?;
+        let is_link = entry.unix_mode().is_some_and(|mode| mode & 0o170000 == 0o120000);
+        if entry.is_dir() || is_link {
+            continue;
+        }
+        let Some(path) = safe_path(Path::new(entry.name())) else {
+            tracing::debug!("Skipping archive entry {}", entry.name());
+            continue;
+        };
+
+        let name = relative_name(&path);
+        limits.write(entry, &dir.join(&path), &name)?;
+        files.push(name);
+    }
+    Ok(files)
+}
+
+fn extract_tar(reader: impl Read, dir: &Path, limits: &mut Limits) -> Result<Vec<String>> {
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| Error::file_parse(limits.filename, e.to_string()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::file_parse(limits.filename, e.to_string()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(path) = entry.path().ok().and_then(|p| safe_path(&p)) else {
+            continue;
+        };
+
+        let name = relative_name(&path);
+        limits.write(entry, &dir.join(&path), &name)?;
+        files.push(name);
+    }
+    Ok(files)
+}
+
+/// Relative path of an entry, or `None` if it would leave the extraction
+/// directory or is a hidden/system file
+fn safe_path(path: &Path) -> Option<PathBuf> {
+    let mut safe = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => {
+                let part = part.to_str()?;
+                if part.starts_with('.') || part == "__MACOSX" {
+                    return None;
+                }
+                safe.push(part);
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!safe.as_os_str().is_empty()).then_some(safe)
+}
+
+fn relative_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut buffer = io::Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::SimpleFileOptions::default();
+            for (name, data) in files {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(data).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_safe_path() {
+        assert_eq!(safe_path(Path::new("docs/./a.txt")), Some(PathBuf::from("docs/a.txt")));
+        assert_eq!(safe_path(Path::new("../etc/passwd")), None);
+        assert_eq!(safe_path(Path::new("/etc/passwd")), None);
+        assert_eq!(safe_path(Path::new("__MACOSX/._a.txt")), None);
+        assert_eq!(safe_path(Path::new("docs/.DS_Store")), None);
+    }
+
+    #[test]
+    fn test_extract_nested_zip() {
+        let inner = zip_of(&[("notes.txt", b"inner notes")]);
+        let outer = zip_of(&[
+            ("docs/readme.md", b"# Readme"),
+            ("../escape.txt", b"nope"),
+            ("bundle.zip", &inner),
+        ]);
+
+        let extracted = extract("upload.zip", &outer, &ArchiveConfig::default()).unwrap();
+        let paths: Vec<&str> = extracted.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["docs/readme.md", "bundle.zip/notes.txt"]);
+        assert_eq!(fs::read(&extracted.entries[1].file).unwrap(), b"inner notes");
+
+        let flat = ArchiveConfig { max_depth: 0, ..Default::default() };
+        let flat = extract("upload.zip", &outer, &flat).unwrap();
+        assert_eq!(flat.entries[1].path, "bundle.zip");
+
+        // Extracted files live as long as the result
+        let file = extracted.entries[0].file.clone();
+        assert!(file.is_file());
+        drop(extracted);
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_extract_file() {
+        let data = zip_of(&[("a.txt", b"from disk")]);
+        let mut archive = tempfile::NamedTempFile::new().unwrap();
+        archive.write_all(&data).unwrap();
+
+        let extracted = extract_file("upload.zip", archive.path(), &ArchiveConfig::default()).unwrap();
+        assert_eq!(extracted.entries.len(), 1);
+        assert_eq!(fs::read(&extracted.entries[0].file).unwrap(), b"from disk");
+    }
+
+    #[test]
+    fn test_limits_reject_bombs() {
+        let data = zip_of(&[("a.txt", &[b'a'; 10_000]), ("b.txt", b"b")]);
+
+        let config = ArchiveConfig { max_entries: 1, ..Default::default() };
+        assert!(extract("x.zip", &data, &config).is_err());
+
+        let config = ArchiveConfig { max_entry_size: 5_000, ..Default::default() };
+        assert!(extract("x.zip", &data, &config).is_err());
+
+        let config = ArchiveConfig { max_compression_ratio: 2, ..Default::default() };
+        let err = extract("x.zip", &data, &config).unwrap_err().to_string();
+        assert!(err.contains("compression ratio"));
+
+        assert_eq!(extract("x.zip", &data, &ArchiveConfig::default()).unwrap().entries.len(), 2);
+    }
+}
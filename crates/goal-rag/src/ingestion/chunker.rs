@@ -1,18 +1,29 @@
 //! Text chunking with page and position tracking
 
+use std::sync::Arc;
+
 use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
 
-use crate::types::{Chunk, ChunkSource, Document, FileType};
+use crate::config::TokenizerKind;
+use crate::generation::packing::TokenCounter;
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document, FileType};
+use super::code;
 use super::parser::ParsedDocument;
 
+/// Counts the tokens of a text
+pub type TokenCount = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
 /// Text chunker with configurable size and overlap
 pub struct TextChunker {
-    /// Target chunk size in characters
+    /// Target chunk size in characters, or tokens with `tokens`
     chunk_size: usize,
     /// Overlap between chunks
     overlap: usize,
     /// Minimum chunk size
     min_size: usize,
+    /// Measures chunk size and overlap in tokens
+    tokens: Option<TokenCount>,
 }
 
 impl TextChunker {
@@ -22,6 +33,21 @@ impl TextChunker {
             chunk_size,
             overlap,
             min_size: 50,
+            tokens: None,
+        }
+    }
+
+    /// Measure chunk size and overlap in tokens counted by `count`
+    pub fn with_tokens(mut self, count: TokenCount) -> Self {
+        self.tokens = Some(count);
+        self
+    }
+
+    /// Size of `text` in the chunker's unit
+    fn size_of(&self, text: &str) -> usize {
+        match &self.tokens {
+            Some(count) => count(text),
+            None => text.len(),
         }
     }
 
@@ -58,7 +84,7 @@ impl TextChunker {
     }
 
     /// Chunk text with source information
-    fn chunk_text_with_source(
+    pub(crate) fn chunk_text_with_source(
         &self,
         text: &str,
         doc: &Document,
@@ -68,107 +94,118 @@ impl TextChunker {
         start_index: u32,
     ) -> Vec<Chunk> {
         let mut chunks = Vec::new();
-        let sentences = self.split_into_sentences(text);
+        let pieces = self.split_into_pieces(text);
 
+        // The chunk being built is always the text between `current_start`
+        // and `char_pos`, so its offsets match the source exactly
         let mut current_chunk = String::new();
         let mut current_start = 0usize;
         let mut chunk_index = start_index;
         let mut char_pos = 0usize;
+        let mut current_size = 0usize;
+
+        let mut push_chunk = |chunk: &str, start: usize, end: usize, chunks: &mut Vec<Chunk>| {
+            if chunk.len() < self.min_size {
+                return;
+            }
+            // Offsets of the trimmed content, so citations start at its first word
+            let start = start + (chunk.len() - chunk.trim_start().len());
+            let end = end - (chunk.len() - chunk.trim_end().len());
+            let source = self.create_source(doc, page_number, page_count, start, end);
+            chunks.push(Chunk::new(
+                doc.id,
+                chunk.trim().to_string(),
+                source,
+                base_offset + start,
+                base_offset + end,
+                chunk_index,
+            ));
+            chunk_index += 1;
+        };
 
-        for sentence in sentences {
-            let sentence_len = sentence.len();
+        for piece in pieces {
+            let piece_size = self.size_of(piece);
 
-            // If adding this sentence exceeds chunk size, save current chunk
+            // If adding this piece exceeds chunk size, save current chunk
             if !current_chunk.is_empty()
-                && current_chunk.len() + sentence_len > self.chunk_size
+                && current_size + piece_size > self.chunk_size
             {
-                if current_chunk.len() >= self.min_size {
-                    let source = self.create_source(
-                        doc,
-                        page_number,
-                        page_count,
-                        current_start,
-                        char_pos,
-                    );
-
-                    chunks.push(Chunk::new(
-                        doc.id,
-                        current_chunk.trim().to_string(),
-                        source,
-                        base_offset + current_start,
-                        base_offset + char_pos,
-                        chunk_index,
-                    ));
-
-                    chunk_index += 1;
-                }
+                push_chunk(&current_chunk, current_start, char_pos, &mut chunks);
 
                 // Start new chunk with overlap
-                let overlap_text = self.get_overlap_text(&current_chunk);
-                current_chunk = overlap_text;
-                current_start = char_pos.saturating_sub(self.overlap);
+                let overlap = self.overlap_bytes(&current_chunk, current_size);
+                let overlap_start = self.overlap_start(&current_chunk, overlap);
+                current_start += overlap_start;
+                current_chunk.replace_range(..overlap_start, "");
+                current_size = self.size_of(&current_chunk);
             }
 
-            current_chunk.push_str(sentence);
-            char_pos += sentence_len;
+            current_chunk.push_str(piece);
+            current_size += piece_size;
+            char_pos += piece.len();
         }
 
         // Save final chunk
-        if current_chunk.len() >= self.min_size {
-            let source = self.create_source(
-                doc,
-                page_number,
-                page_count,
-                current_start,
-                char_pos,
-            );
-
-            chunks.push(Chunk::new(
-                doc.id,
-                current_chunk.trim().to_string(),
-                source,
-                base_offset + current_start,
-                base_offset + char_pos,
-                chunk_index,
-            ));
-        }
+        push_chunk(&current_chunk, current_start, char_pos, &mut chunks);
 
         chunks
     }
 
-    /// Split text into sentences
-    fn split_into_sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
+    /// Split text into sentences, and sentences longer than a chunk into words
+    /// (with the spaces and punctuation that follow them)
+    fn split_into_pieces<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let mut pieces = Vec::new();
         // Use unicode segmentation for proper sentence boundaries
-        text.split_sentence_bounds().collect()
-    }
-
-    /// Get overlap text from the end of a chunk
-    fn get_overlap_text(&self, text: &str) -> String {
-        if text.len() <= self.overlap {
-            return text.to_string();
-        }
-
-        // Find a good break point (sentence or word boundary)
-        let mut start = text.len().saturating_sub(self.overlap);
+        for sentence in text.split_sentence_bounds() {
+            if self.size_of(sentence) <= self.chunk_size {
+                pieces.push(sentence);
+                continue;
+            }
 
-        // Ensure we're at a valid UTF-8 character boundary
-        while start > 0 && !text.is_char_boundary(start) {
-            start -= 1;
+            let mut start = 0;
+            for (i, word) in sentence.split_word_bound_indices() {
+                if i > start && word.starts_with(char::is_alphanumeric) {
+                    pieces.push(&sentence[start..i]);
+                    start = i;
+                }
+            }
+            pieces.push(&sentence[start..]);
         }
+        pieces
+    }
 
-        let overlap_text = &text[start..];
-
-        // Try to start at a sentence boundary
-        if let Some(pos) = overlap_text.find(". ") {
-            return overlap_text[pos + 2..].to_string();
+    /// Bytes at the end of a chunk of `size` that make up the overlap
+    ///
+    /// Token overlap is converted at the chunk's own bytes per token.
+    fn overlap_bytes(&self, text: &str, size: usize) -> usize {
+        match self.tokens {
+            Some(_) => self.overlap.saturating_mul(text.len()) / size.max(1),
+            None => self.overlap,
         }
+    }
 
-        // Fall back to word boundary
-        if let Some(pos) = overlap_text.find(' ') {
-            return overlap_text[pos + 1..].to_string();
+    /// Where the overlap carried into the next chunk starts
+    ///
+    /// The overlap is at most the last `overlap` bytes of the chunk (never all
+    /// of it) and starts at a sentence boundary, or a word boundary if no
+    /// sentence starts in it. Returns the chunk's length (no overlap) when
+    /// neither does.
+    fn overlap_start(&self, text: &str, overlap: usize) -> usize {
+        if overlap == 0 {
+            return text.len();
         }
-
-        overlap_text.to_string()
+        let earliest = text.len().saturating_sub(overlap).max(1);
+        let has_content = |i: usize| !text[i..].trim().is_empty();
+
+        text.split_sentence_bound_indices()
+            .map(|(i, _)| i)
+            .find(|&i| i >= earliest && has_content(i))
+            .or_else(|| {
+                text.split_word_bound_indices()
+                    .find(|&(i, word)| i >= earliest && word.starts_with(char::is_alphanumeric))
+                    .map(|(i, _)| i)
+            })
+            .unwrap_or(text.len())
     }
 
     /// Create source information for a chunk
@@ -193,6 +230,8 @@ impl TextChunker {
             line_start: None,
             line_end: None,
             code_context: None,
+            time_range: None,
+            kind: ChunkKind::Text,
         };
 
         // For code files, calculate line numbers
@@ -207,87 +246,160 @@ impl TextChunker {
     }
 }
 
+/// Group consecutive chunks into parent windows of up to `window` characters
+///
+/// Sets `parent_id` on each chunk. Windows never span pages; a chunk longer
+/// than the window is its own parent.
+pub fn assign_parent_windows(chunks: &mut [Chunk], window: usize) {
+    let mut current: Option<(Uuid, usize, Option<u32>)> = None;
+
+    for chunk in chunks.iter_mut() {
+        let page = chunk.source.page_number;
+        let parent_id = match current {
+            Some((id, start, p)) if p == page && chunk.char_end.saturating_sub(start) <= window => id,
+            _ => {
+                let id = Uuid::new_v4();
+                current = Some((id, chunk.char_start, page));
+                id
+            }
+        };
+        chunk.parent_id = Some(parent_id);
+    }
+}
+
 /// Chunk code files with function/class awareness
 pub struct CodeChunker {
-    base: TextChunker,
+    /// Maximum chunk size in tokens
+    max_tokens: usize,
+    counter: TokenCounter,
 }
 
 impl CodeChunker {
-    /// Create a new code chunker
-    pub fn new(chunk_size: usize, overlap: usize) -> Self {
+    /// Create a new code chunker, sized by an estimate of 4 characters per token
+    pub fn new(chunk_size: usize, _overlap: usize) -> Self {
         Self {
-            base: TextChunker::new(chunk_size, overlap),
+            max_tokens: (chunk_size / 4).max(1),
+            counter: TokenCounter::new(TokenizerKind::Estimate, 4.0),
         }
     }
 
-    /// Chunk code by functions/methods when possible
-    pub fn chunk_code(&self, doc: &Document, content: &str, language: &str) -> Vec<Chunk> {
-        // For now, use simple line-based chunking
-        // In production, use tree-sitter for syntax-aware chunking
+    /// Size chunks with this token limit and counter
+    pub fn with_tokens(mut self, max_tokens: usize, counter: TokenCounter) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self.counter = counter;
+        self
+    }
 
-        let lines: Vec<&str> = content.lines().collect();
+    /// Chunk code on function and class boundaries when the language is
+    /// supported, by lines otherwise
+    pub fn chunk_code(&self, doc: &Document, content: &str, language: &str) -> Vec<Chunk> {
         let mut chunks = Vec::new();
-        let mut current_chunk = String::new();
-        let mut current_start_line = 1u32;
-        let mut chunk_index = 0u32;
-        let mut char_offset = 0usize;
-
-        for (line_num, line) in lines.iter().enumerate() {
-            let line_with_newline = format!("{}\n", line);
-
-            if !current_chunk.is_empty()
-                && current_chunk.len() + line_with_newline.len() > self.base.chunk_size
-            {
-                let char_start = char_offset - current_chunk.len();
-
-                let mut source = ChunkSource::code(
-                    doc.filename.clone(),  // Original filename for citations
-                    language.to_string(),
-                    current_start_line,
-                    line_num as u32,
-                );
-                source.internal_filename = doc.internal_filename.clone();
-
-                chunks.push(Chunk::new(
-                    doc.id,
-                    current_chunk.trim().to_string(),
-                    source,
-                    char_start,
-                    char_offset,
-                    chunk_index,
-                ));
-
-                chunk_index += 1;
-                current_chunk = String::new();
-                current_start_line = line_num as u32 + 1;
-            }
-
-            current_chunk.push_str(&line_with_newline);
-            char_offset += line_with_newline.len();
-        }
+        let mut line = 1u32;
+        let mut counted = 0usize;
 
-        // Final chunk
-        if !current_chunk.trim().is_empty() {
-            let char_start = char_offset - current_chunk.len();
+        for span in code::split(content, language, self.max_tokens, &self.counter) {
+            let text = &content[span.start..span.end];
+            let leading = text.len() - text.trim_start_matches(['\n', '\r']).len();
+            line += content[counted..span.start + leading].matches('\n').count() as u32;
+            counted = span.start + leading;
+            let text = text.trim_end();
+            let line_end = line + text[leading..].matches('\n').count() as u32;
 
             let mut source = ChunkSource::code(
                 doc.filename.clone(),  // Original filename for citations
                 language.to_string(),
-                current_start_line,
-                lines.len() as u32,
+                line,
+                line_end,
             );
             source.internal_filename = doc.internal_filename.clone();
+            if !span.symbols.is_empty() {
+                source.code_context = Some(span.symbols.join(", "));
+            }
 
             chunks.push(Chunk::new(
                 doc.id,
-                current_chunk.trim().to_string(),
+                text.trim().to_string(),
                 source,
-                char_start,
-                char_offset,
-                chunk_index,
+                span.start,
+                span.end,
+                chunks.len() as u32,
             ));
         }
 
         chunks
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_sizing() {
+        let doc = Document::new("a.txt".into(), FileType::Txt, "hash".into(), 0);
+        let text = "One two three four five six. ".repeat(20);
+        let words: TokenCount = Arc::new(|text: &str| text.split_whitespace().count());
+        let chunker = TextChunker::new(30, 6).with_tokens(words);
+
+        // 120 words in chunks of up to 30, where 30 characters would make dozens
+        let chunks = chunker.chunk_text_with_source(&text, &doc, None, None, 0, 0);
+        assert!((4..=5).contains(&chunks.len()));
+        for chunk in &chunks {
+            assert!(chunk.content.split_whitespace().count() <= 30);
+        }
+    }
+
+    #[test]
+    fn test_chunks_end_at_word_boundaries() {
+        let doc = Document::new("a.txt".into(), FileType::Txt, "hash".into(), 0);
+        // One long sentence, so it has to be split between words
+        let text = "alpha beta gamma delta ".repeat(40);
+        let chunks = TextChunker::new(100, 30).chunk_text_with_source(&text, &doc, None, None, 0, 0);
+
+        assert!(chunks.len() > 5);
+        for chunk in &chunks {
+            assert!(chunk.content.len() <= 100);
+            assert!(chunk.content.split(' ').all(|w| ["alpha", "beta", "gamma", "delta"].contains(&w)));
+            assert_eq!(&text[chunk.char_start..chunk.char_end], chunk.content);
+        }
+        // Overlap is carried in whole words
+        assert!(chunks[1].char_start < chunks[0].char_end);
+    }
+
+    #[test]
+    fn test_overlap_starts_at_sentence() {
+        let doc = Document::new("a.txt".into(), FileType::Txt, "hash".into(), 0);
+        let text = "The first sentence is here. A second one follows it. And then a third sentence. ".repeat(4);
+        let chunks = TextChunker::new(120, 40).chunk_text_with_source(&text, &doc, None, None, 0, 0);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks[1..] {
+            assert!(chunk.content.starts_with(char::is_uppercase));
+            assert!(chunk.content.ends_with('.'));
+            assert_eq!(&text[chunk.char_start..chunk.char_end], chunk.content);
+        }
+    }
+
+    #[test]
+    fn test_assign_parent_windows() {
+        let doc_id = Uuid::new_v4();
+        let mut chunks: Vec<Chunk> = (0..10)
+            .map(|i| {
+                let mut source = ChunkSource::text("a.txt".into());
+                source.page_number = Some(if i < 8 { 1 } else { 2 });
+                Chunk::new(doc_id, "x".repeat(200), source, i * 200, (i + 1) * 200, i as u32)
+            })
+            .collect();
+
+        assign_parent_windows(&mut chunks, 600);
+
+        // Three 200-char chunks per 600-char window, new window on page change
+        let parents: Vec<Uuid> = chunks.iter().map(|c| c.parent_id.unwrap()).collect();
+        assert_eq!(parents[0], parents[2]);
+        assert_ne!(parents[2], parents[3]);
+        assert_eq!(parents[3], parents[5]);
+        assert_eq!(parents[6], parents[7]);
+        assert_ne!(parents[7], parents[8]);
+        assert_eq!(parents[8], parents[9]);
+    }
+}
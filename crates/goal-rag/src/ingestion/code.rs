@@ -0,0 +1,373 @@
+//! Syntax-aware splitting of source files
+//!
+//! With the `code-chunking` feature, files in a supported language are
+//! parsed with tree-sitter and split on definition boundaries: each
+//! top-level function, class, impl or type, with the comments right above
+//! it, is a unit, and consecutive units are merged up to the token limit.
+//! A definition too large for one chunk is split at its nested definitions
+//! (methods), or by lines when it has none. Spans carry the names of the
+//! symbols they define. Other languages, files that don't parse and builds
+//! without the feature are split by lines.
+
+use crate::generation::packing::TokenCounter;
+
+/// Whole lines of a source file to chunk together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSpan {
+    /// Byte range in the source
+    pub start: usize,
+    pub end: usize,
+    /// Definitions in the span, qualified by their class or impl
+    /// (e.g. `Parser::parse`)
+    pub symbols: Vec<String>,
+}
+
+/// A definition or a run of other statements
+#[derive(Debug, Clone)]
+struct Unit {
+    start: usize,
+    end: usize,
+    symbol: Option<String>,
+    /// Nested definitions covering the unit, for splitting it
+    children: Vec<Unit>,
+}
+
+/// Split a source file into spans of at most `max_tokens` tokens (single
+/// lines longer than that excepted)
+pub fn split(source: &str, language: &str, max_tokens: usize, counter: &TokenCounter) -> Vec<CodeSpan> {
+    let max_tokens = max_tokens.max(1);
+    let units = syntax::units(source, language).unwrap_or_else(|| {
+        vec![Unit { start: 0, end: source.len(), symbol: None, children: Vec::new() }]
+    });
+
+    let mut spans = Vec::new();
+    pack(source, units, max_tokens, counter, &mut spans);
+    spans.retain(|span| !source[span.start..span.end].trim().is_empty());
+    spans
+}
+
+/// Merge consecutive units into spans up to the limit, splitting units
+/// that don't fit on their own
+fn pack(source: &str, units: Vec<Unit>, max_tokens: usize, counter: &TokenCounter, spans: &mut Vec<CodeSpan>) {
+    let mut current: Option<(CodeSpan, usize)> = None;
+
+    for unit in units {
+        let tokens = counter.count(&source[unit.start..unit.end]);
+        if tokens > max_tokens {
+            spans.extend(current.take().map(|(span, _)| span));
+            split_unit(source, unit, max_tokens, counter, spans);
+            continue;
+        }
+
+        if let Some((ref mut span, ref mut used)) = current {
+            if *used + tokens <= max_tokens {
+                span.end = unit.end;
+                span.symbols.extend(unit.symbol);
+                *used += tokens;
+                continue;
+            }
+        }
+        spans.extend(current.take().map(|(span, _)| span));
+        let span = CodeSpan { start: unit.start, end: unit.end, symbols: unit.symbol.into_iter().collect() };
+        current = Some((span, tokens));
+    }
+
+    spans.extend(current.map(|(span, _)| span));
+}
+
+/// Split a unit too large for one span
+fn split_unit(source: &str, unit: Unit, max_tokens: usize, counter: &TokenCounter, spans: &mut Vec<CodeSpan>) {
+    if !unit.children.is_empty() {
+        let first = spans.len();
+        pack(source, unit.children, max_tokens, counter, spans);
+        // The span with the header names the enclosing definition too
+        if let (Some(symbol), Some(span)) = (unit.symbol, spans.get_mut(first)) {
+            span.symbols.insert(0, symbol);
+        }
+        return;
+    }
+
+    let symbols: Vec<String> = unit.symbol.into_iter().collect();
+    let mut start = unit.start;
+    let mut line_start = unit.start;
+    let mut used = 0;
+    for line in source[unit.start..unit.end].split_inclusive('\n') {
+        let tokens = counter.count(line);
+        if used > 0 && used + tokens > max_tokens {
+            spans.push(CodeSpan { start, end: line_start, symbols: symbols.clone() });
+            start = line_start;
+            used = 0;
+        }
+        used += tokens;
+        line_start += line.len();
+    }
+    if start < unit.end {
+        spans.push(CodeSpan { start, end: unit.end, symbols });
+    }
+}
+
+#[cfg(feature = "code-chunking")]
+mod syntax {
+    use tree_sitter::{Language, Node, Parser};
+
+    use super::Unit;
+
+    /// Node kinds that are definitions in a language
+    struct Grammar {
+        language: Language,
+        definitions: &'static [&'static str],
+        /// Separator of qualified names
+        separator: &'static str,
+    }
+
+    const JAVASCRIPT: &[&str] = &[
+        "function_declaration",
+        "generator_function_declaration",
+        "class_declaration",
+        "method_definition",
+        "lexical_declaration",
+        "export_statement",
+    ];
+
+    const TYPESCRIPT: &[&str] = &[
+        "function_declaration",
+        "generator_function_declaration",
+        "class_declaration",
+        "abstract_class_declaration",
+        "method_definition",
+        "lexical_declaration",
+        "export_statement",
+        "interface_declaration",
+        "type_alias_declaration",
+        "enum_declaration",
+    ];
+
+    fn grammar(language: &str) -> Option<Grammar> {
+        let (language, definitions, separator): (Language, &'static [&'static str], &'static str) = match language {
+            "rust" => (
+                tree_sitter_rust::language(),
+                &[
+                    "function_item",
+                    "impl_item",
+                    "struct_item",
+                    "enum_item",
+                    "union_item",
+                    "trait_item",
+                    "mod_item",
+                    "type_item",
+                    "const_item",
+                    "static_item",
+                    "macro_definition",
+                ],
+                "::",
+            ),
+            "python" => (
+                tree_sitter_python::language(),
+                &["function_definition", "class_definition", "decorated_definition"],
+                ".",
+            ),
+            "javascript" => (tree_sitter_javascript::language(), JAVASCRIPT, "."),
+            "typescript" => (tree_sitter_typescript::language_typescript(), TYPESCRIPT, "."),
+            "react" => (tree_sitter_typescript::language_tsx(), TYPESCRIPT, "."),
+            "go" => (
+                tree_sitter_go::language(),
+                &["function_declaration", "method_declaration", "type_declaration"],
+                ".",
+            ),
+            "java" => (
+                tree_sitter_java::language(),
+                &[
+                    "class_declaration",
+                    "interface_declaration",
+                    "enum_declaration",
+                    "record_declaration",
+                    "method_declaration",
+                    "constructor_declaration",
+                ],
+                ".",
+            ),
+            "c" => (
+                tree_sitter_c::language(),
+                &["function_definition", "struct_specifier", "enum_specifier", "type_definition"],
+                "::",
+            ),
+            "cpp" => (
+                tree_sitter_cpp::language(),
+                &[
+                    "function_definition",
+                    "class_specifier",
+                    "struct_specifier",
+                    "enum_specifier",
+                    "namespace_definition",
+                    "template_declaration",
+                    "type_definition",
+                ],
+                "::",
+            ),
+            _ => return None,
+        };
+        Some(Grammar { language, definitions, separator })
+    }
+
+    /// Top-level units of a source file, `None` if the language is
+    /// unsupported or the file doesn't parse
+    pub(super) fn units(source: &str, language: &str) -> Option<Vec<Unit>> {
+        let grammar = grammar(language)?;
+        let mut parser = Parser::new();
+        parser.set_language(grammar.language).ok()?;
+        let tree = parser.parse(source, None)?;
+        let root = tree.root_node();
+        if root.has_error() {
+            tracing::debug!("Syntax errors in {} source, chunking by lines", language);
+            return None;
+        }
+        Some(units_in(root, source, &grammar, 0, source.len(), None))
+    }
+
+    /// Units covering `start..end`, one per definition among the named
+    /// children of `node` (other statements are grouped)
+    fn units_in(node: Node, source: &str, grammar: &Grammar, start: usize, end: usize, parent: Option<&str>) -> Vec<Unit> {
+        // Line where each unit starts, with its definition node
+        let mut boundaries: Vec<(usize, Option<Node>)> = Vec::new();
+        let mut comment_start = None;
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            // Comments and attributes go with the definition below them
+            if child.kind().contains("comment") || child.kind() == "attribute_item" {
+                comment_start.get_or_insert(child.start_byte());
+                continue;
+            }
+            let line = line_start(source, comment_start.take().unwrap_or(child.start_byte())).max(start);
+            let definition = grammar.definitions.contains(&child.kind()).then_some(child);
+            match boundaries.last() {
+                // Statements on the line of the previous unit, or after other statements, join it
+                Some(&(last, previous)) if line <= last || (definition.is_none() && previous.is_none()) => {}
+                _ => boundaries.push((line, definition)),
+            }
+        }
+
+        let mut units = Vec::new();
+        for (i, &(line, definition)) in boundaries.iter().enumerate() {
+            let unit_start = if i == 0 { start } else { line };
+            let unit_end = boundaries.get(i + 1).map_or(end, |&(next, _)| next);
+            let symbol = definition.and_then(|d| symbol_name(d, source)).map(|name| match parent {
+                Some(parent) => format!("{}{}{}", parent, grammar.separator, name),
+                None => name,
+            });
+            let children = definition
+                .and_then(body)
+                .map(|body| units_in(body, source, grammar, unit_start, unit_end, symbol.as_deref()))
+                .filter(|children| children.iter().any(|c| c.symbol.is_some()))
+                .unwrap_or_default();
+            units.push(Unit { start: unit_start, end: unit_end, symbol, children });
+        }
+        if units.is_empty() {
+            units.push(Unit { start, end, symbol: None, children: Vec::new() });
+        }
+        units
+    }
+
+    /// Name of a definition (the type of an impl, the declarator of a C function)
+    fn symbol_name(node: Node, source: &str) -> Option<String> {
+        // Decorated and exported definitions
+        if let Some(inner) = node.child_by_field_name("definition").or_else(|| node.child_by_field_name("declaration")) {
+            return symbol_name(inner, source);
+        }
+
+        let mut name = ["name", "declarator", "type"]
+            .iter()
+            .find_map(|field| node.child_by_field_name(field))
+            // Declarations naming their first declarator or spec (`const f = ...`, Go `type T ...`)
+            .or_else(|| node.named_child(0).and_then(|child| child.child_by_field_name("name")))?;
+        while let Some(inner) = name.child_by_field_name("declarator").or_else(|| name.child_by_field_name("name")) {
+            name = inner;
+        }
+        let text = name.utf8_text(source.as_bytes()).ok()?;
+        Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Start of the line containing byte `pos`
+    fn line_start(source: &str, pos: usize) -> usize {
+        source[..pos].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// Body holding the nested definitions of a class, impl or module
+    fn body(node: Node) -> Option<Node> {
+        node.child_by_field_name("definition")
+            .or_else(|| node.child_by_field_name("declaration"))
+            .unwrap_or(node)
+            .child_by_field_name("body")
+    }
+}
+
+#[cfg(not(feature = "code-chunking"))]
+mod syntax {
+    use super::Unit;
+
+    pub(super) fn units(_source: &str, _language: &str) -> Option<Vec<Unit>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TokenizerKind;
+
+    fn counter() -> TokenCounter {
+        TokenCounter::new(TokenizerKind::Estimate, 4.0)
+    }
+
+    #[test]
+    fn test_split_by_lines() {
+        let source = "line one\nline two\nline three\nline four\n";
+        let spans = split(source, "unknown", 6, &counter());
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&source[spans[0].start..spans[0].end], "line one\nline two\n");
+        assert_eq!(spans[1].end, source.len());
+        assert!(spans.iter().all(|span| span.symbols.is_empty()));
+    }
+
+    #[cfg(feature = "code-chunking")]
+    #[test]
+    fn test_split_on_definitions() {
+        let source = r#"use std::fmt;
+
+/// A parser
+struct Parser {
+    input: String,
+}
+
+impl Parser {
+    fn new(input: String) -> Self {
+        Self { input }
+    }
+
+    /// Parse the input into words
+    fn parse(&self) -> Vec<&str> {
+        self.input.split(' ').collect()
+    }
+}
+
+fn main() {
+    let parser = Parser::new("a b".into());
+    println!("{:?}", parser.parse());
+}
+"#;
+        let spans = split(source, "rust", 40, &counter());
+        let symbols: Vec<Vec<String>> = spans.iter().map(|span| span.symbols.clone()).collect();
+        assert_eq!(
+            symbols,
+            vec![
+                vec!["Parser".to_string()],
+                vec!["Parser".to_string(), "Parser::new".to_string()],
+                vec!["Parser::parse".to_string()],
+                vec!["main".to_string()],
+            ]
+        );
+        // Doc comments stay with their definition, spans are whole lines covering the file
+        assert!(source[spans[2].start..].starts_with("    /// Parse"));
+        assert_eq!(spans.first().unwrap().start, 0);
+        assert_eq!(spans.last().unwrap().end, source.len());
+    }
+}
@@ -0,0 +1,917 @@
+//! Email (.eml, .msg) and mailbox (.mbox) parsing
+//!
+//! Each message is rendered as a header block (From, To, Cc, Date, Subject)
+//! followed by its body and the text of its attachments, which are parsed
+//! with the normal file parser (so attached PDFs, spreadsheets and nested
+//! emails are indexed too). Mailboxes become one page per message.
+//!
+//! Chunks are tagged with the sender, date and subject of their message
+//! (read back from the header block, so tags survive reindexing) for use as
+//! query filters.
+
+use base64::Engine as _;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::types::document::{EMAIL_DATE_METADATA_KEY, EMAIL_FROM_METADATA_KEY, EMAIL_SUBJECT_METADATA_KEY};
+use crate::types::{Chunk, FileType};
+use super::parser::{FileParser, PageContent, ParsedDocument};
+
+/// Header lines written at the top of each rendered message
+const RENDERED_HEADERS: [&str; 5] = ["From", "To", "Cc", "Date", "Subject"];
+
+/// A file attached to a message
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A parsed email message
+#[derive(Debug, Clone, Default)]
+pub struct EmailMessage {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub cc: Option<String>,
+    /// Date header as sent
+    pub date: Option<String>,
+    pub subject: Option<String>,
+    /// Plain-text body (HTML bodies are converted)
+    pub body: String,
+    pub attachments: Vec<Attachment>,
+}
+
+impl EmailMessage {
+    /// Render headers, body and attachment text
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let values = [&self.from, &self.to, &self.cc, &self.date, &self.subject];
+        for (name, value) in RENDERED_HEADERS.iter().zip(values) {
+            if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+                out.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+        out.push('\n');
+        out.push_str(self.body.trim());
+        out.push('\n');
+
+        for attachment in &self.attachments {
+            out.push_str(&format!("\nAttachment: {}\n", attachment.filename));
+            match FileParser::parse(&attachment.filename, &attachment.data) {
+                Ok(parsed) if !parsed.content.trim().is_empty() => {
+                    out.push_str(parsed.content.trim());
+                    out.push('\n');
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("Attachment {} not indexed: {}", attachment.filename, e);
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Parse an .eml, .msg or .mbox file
+pub fn parse(filename: &str, data: &[u8]) -> Result<ParsedDocument> {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    let (file_type, messages) = match ext.as_str() {
+        "msg" => (FileType::Email, vec![parse_msg(filename, data)?]),
+        "mbox" => (
+            FileType::Mbox,
+            split_mbox(data).iter().map(|raw| parse_eml(raw)).collect(),
+        ),
+        _ => (FileType::Email, vec![parse_eml(data)]),
+    };
+
+    if messages.is_empty() {
+        return Err(Error::file_parse(filename, "Mailbox contains no messages"));
+    }
+
+    let mut content = String::new();
+    let mut pages = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        let text = message.render();
+        pages.push(PageContent {
+            page_number: i as u32 + 1,
+            content: text.clone(),
+            char_offset: content.len(),
+        });
+        content.push_str(&text);
+    }
+
+    // A single message is not paginated
+    let (pages, total_pages) = match file_type {
+        FileType::Mbox => {
+            let count = pages.len() as u32;
+            (pages, Some(count))
+        }
+        _ => (Vec::new(), None),
+    };
+
+    let mut metadata = HashMap::new();
+    metadata.insert("messages".to_string(), messages.len().to_string());
+
+    Ok(ParsedDocument {
+        file_type,
+        content_hash: hex::encode(Sha256::digest(content.as_bytes())),
+        content,
+        total_pages,
+        pages,
+        metadata,
+        sections: Vec::new(),
+    })
+}
+
+/// Tag email chunks with the sender, date and subject of their message
+///
+/// The message is found by page number (mailboxes) and its headers read from
+/// the rendered header block. The subject also becomes the section title.
+pub fn tag_chunks(chunks: &mut [Chunk], parsed: &ParsedDocument) {
+    let headers_for = |page: Option<u32>| -> HashMap<String, String> {
+        let text = match page {
+            Some(n) => parsed
+                .pages
+                .iter()
+                .find(|p| p.page_number == n)
+                .map(|p| p.content.as_str())
+                .unwrap_or(""),
+            None => parsed.content.as_str(),
+        };
+        rendered_headers(text)
+    };
+
+    let mut cache: HashMap<Option<u32>, HashMap<String, String>> = HashMap::new();
+    for chunk in chunks {
+        let page = chunk.source.page_number.filter(|_| !parsed.pages.is_empty());
+        let headers = cache.entry(page).or_insert_with(|| headers_for(page));
+
+        if let Some(from) = headers.get("from") {
+            chunk.metadata.insert(EMAIL_FROM_METADATA_KEY.to_string(), serde_json::json!(from));
+        }
+        if let Some(date) = headers.get("date") {
+            let date = parse_date(date).map(|d| d.to_rfc3339()).unwrap_or_else(|| date.clone());
+            chunk.metadata.insert(EMAIL_DATE_METADATA_KEY.to_string(), serde_json::json!(date));
+        }
+        if let Some(subject) = headers.get("subject") {
+            chunk.metadata.insert(EMAIL_SUBJECT_METADATA_KEY.to_string(), serde_json::json!(subject));
+            if chunk.source.section_title.is_none() {
+                chunk.source.section_title = Some(subject.clone());
+            }
+        }
+    }
+}
+
+/// Headers of a rendered message, keyed by lowercase name
+fn rendered_headers(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(name, _)| RENDERED_HEADERS.contains(name))
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
+/// Parse an RFC 2822 date, tolerating a trailing zone comment
+pub fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let date = date.split(" (").next().unwrap_or(date).trim();
+    chrono::DateTime::parse_from_rfc2822(date)
+        .or_else(|_| chrono::DateTime::parse_from_rfc3339(date))
+        .ok()
+        .map(|d| d.with_timezone(&chrono::Utc))
+}
+
+// ==================== mbox ====================
+
+/// Split a mailbox into raw messages
+///
+/// Messages start at `From ` lines at the top of the file or after a blank
+/// line. Quoted `>From ` lines in bodies are unescaped.
+pub fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    let text = String::from_utf8_lossy(data);
+    let mut messages = Vec::new();
+    let mut current: Option<String> = None;
+    let mut previous_blank = true;
+
+    for line in text.split_inclusive('\n') {
+        if previous_blank && line.starts_with("From ") {
+            if let Some(message) = current.take() {
+                messages.push(message.into_bytes());
+            }
+            current = Some(String::new());
+        } else if let Some(message) = current.as_mut() {
+            let unquoted = line.trim_start_matches('>');
+            if unquoted.len() < line.len() && unquoted.starts_with("From ") {
+                message.push_str(&line[1..]);
+            } else {
+                message.push_str(line);
+            }
+        }
+        previous_blank = line.trim().is_empty();
+    }
+    if let Some(message) = current {
+        messages.push(message.into_bytes());
+    }
+
+    messages.retain(|m| !String::from_utf8_lossy(m).trim().is_empty());
+    messages
+}
+
+// ==================== RFC 822 / MIME ====================
+
+/// A MIME entity: headers and undecoded body
+struct Part<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl<'a> Part<'a> {
+    fn parse(data: &'a [u8]) -> Self {
+        let (head, body) = split_head(data);
+        Self {
+            headers: parse_headers(&String::from_utf8_lossy(head)),
+            body,
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Lowercase MIME type and its parameters
+    fn content_type(&self) -> (String, HashMap<String, String>) {
+        match self.header("content-type") {
+            Some(value) => parse_header_params(value),
+            None => ("text/plain".to_string(), HashMap::new()),
+        }
+    }
+
+    fn filename(&self) -> Option<String> {
+        let (disposition, params) = self
+            .header("content-disposition")
+            .map(parse_header_params)
+            .unwrap_or_default();
+        params
+            .get("filename")
+            .cloned()
+            .or_else(|| self.content_type().1.remove("name"))
+            .map(|name| decode_words(&name))
+            .or_else(|| (disposition == "attachment").then(|| "attachment".to_string()))
+    }
+
+    /// Body with the transfer encoding removed
+    fn decoded_body(&self) -> Vec<u8> {
+        let encoding = self.header("content-transfer-encoding").unwrap_or("").trim().to_lowercase();
+        match encoding.as_str() {
+            "base64" => {
+                let cleaned: Vec<u8> = self.body.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+                base64::engine::general_purpose::STANDARD
+                    .decode(&cleaned)
+                    .unwrap_or_else(|_| self.body.to_vec())
+            }
+            "quoted-printable" => decode_quoted_printable(self.body),
+            _ => self.body.to_vec(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let charset = self.content_type().1.get("charset").cloned().unwrap_or_default();
+        decode_charset(&self.decoded_body(), &charset)
+    }
+}
+
+/// Parse an RFC 822 message (.eml)
+pub fn parse_eml(data: &[u8]) -> EmailMessage {
+    let part = Part::parse(data);
+    let header = |name: &str| part.header(name).map(decode_words).filter(|v| !v.is_empty());
+
+    let mut message = EmailMessage {
+        from: header("from"),
+        to: header("to"),
+        cc: header("cc"),
+        date: header("date"),
+        subject: header("subject"),
+        ..Default::default()
+    };
+
+    let mut plain = Vec::new();
+    let mut html = Vec::new();
+    collect_parts(&part, &mut plain, &mut html, &mut message.attachments);
+
+    message.body = if !plain.is_empty() {
+        plain.join("\n\n")
+    } else {
+        html.iter()
+            .filter_map(|h| FileParser::parse("body.html", h.as_bytes()).ok())
+            .map(|parsed| parsed.content)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    message
+}
+
+/// Walk the MIME tree, sorting parts into plain bodies, HTML bodies and attachments
+fn collect_parts(part: &Part, plain: &mut Vec<String>, html: &mut Vec<String>, attachments: &mut Vec<Attachment>) {
+    let (mime_type, params) = part.content_type();
+
+    if mime_type.starts_with("multipart/") {
+        if let Some(boundary) = params.get("boundary") {
+            for child in split_multipart(part.body, boundary) {
+                collect_parts(&Part::parse(child), plain, html, attachments);
+            }
+        }
+        return;
+    }
+
+    let filename = part.filename();
+    let inline_text = filename.is_none() && matches!(mime_type.as_str(), "text/plain" | "text/html");
+
+    if inline_text {
+        if mime_type == "text/html" {
+            html.push(part.text());
+        } else {
+            plain.push(part.text());
+        }
+    } else if mime_type == "message/rfc822" {
+        let data = part.decoded_body();
+        let subject = parse_eml(&data).subject.unwrap_or_else(|| "message".to_string());
+        attachments.push(Attachment {
+            filename: filename.unwrap_or_else(|| format!("{}.eml", subject.replace(['/', '\\'], "_"))),
+            content_type: mime_type,
+            data,
+        });
+    } else if let Some(filename) = filename {
+        attachments.push(Attachment {
+            filename,
+            content_type: mime_type,
+            data: part.decoded_body(),
+        });
+    }
+}
+
+/// Split a message into header bytes and body bytes at the first blank line
+fn split_head(data: &[u8]) -> (&[u8], &[u8]) {
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'\n' {
+            let rest = &data[i + 1..];
+            if rest.starts_with(b"\r\n") {
+                return (&data[..i], &rest[2..]);
+            }
+            if rest.starts_with(b"\n") {
+                return (&data[..i], &rest[1..]);
+            }
+        }
+        i += 1;
+    }
+    (data, &[])
+}
+
+/// Parse header lines, unfolding continuation lines
+fn parse_headers(head: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// Split a header value like `text/plain; charset="utf-8"` into value and parameters
+fn parse_header_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut parts = value.split(';');
+    let main = parts.next().unwrap_or("").trim().to_lowercase();
+    let mut params = HashMap::new();
+    for param in parts {
+        if let Some((key, val)) = param.split_once('=') {
+            let key = key.trim().to_lowercase();
+            let val = val.trim().trim_matches('"');
+            // RFC 2231: filename*=UTF-8''name%20with%20spaces
+            match key.strip_suffix('*') {
+                Some(key) => {
+                    let encoded = val.splitn(3, '\'').last().unwrap_or(val);
+                    params.insert(key.to_string(), percent_decode(encoded));
+                }
+                None => {
+                    params.insert(key, val.to_string());
+                }
+            }
+        }
+    }
+    (main, params)
+}
+
+/// Bodies of a multipart entity
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let line_end = body[pos..].iter().position(|&b| b == b'\n').map(|p| pos + p + 1).unwrap_or(body.len());
+        let line = String::from_utf8_lossy(&body[pos..line_end]);
+        let line = line.trim_end();
+
+        if line.starts_with(&delimiter) {
+            if let Some(s) = start {
+                // The line break before a delimiter belongs to the delimiter
+                let mut end = pos;
+                if end > s && body[end - 1] == b'\n' {
+                    end -= 1;
+                }
+                if end > s && body[end - 1] == b'\r' {
+                    end -= 1;
+                }
+                parts.push(&body[s..end]);
+            }
+            if line[delimiter.len()..].starts_with("--") {
+                return parts;
+            }
+            start = Some(line_end);
+        }
+        pos = line_end;
+    }
+
+    if let Some(s) = start {
+        parts.push(&body[s..]);
+    }
+    parts
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`)
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut last_was_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        let decoded = candidate[2..].split_once('?').and_then(|(charset, tail)| {
+            let (encoding, tail) = tail.split_once('?')?;
+            let (text, after) = tail.split_once("?=")?;
+            let bytes = match encoding.to_ascii_uppercase().as_str() {
+                "B" => base64::engine::general_purpose::STANDARD.decode(text).ok()?,
+                "Q" => decode_quoted_printable(text.replace('_', " ").as_bytes()),
+                _ => return None,
+            };
+            Some((decode_charset(&bytes, charset), after))
+        });
+
+        match decoded {
+            Some((text, after)) => {
+                // Whitespace between adjacent encoded words is dropped
+                if !(last_was_word && before.trim().is_empty()) {
+                    out.push_str(before);
+                }
+                out.push_str(&text);
+                rest = after;
+                last_was_word = true;
+            }
+            None => {
+                out.push_str(before);
+                out.push_str("=?");
+                rest = &candidate[2..];
+                last_was_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_quoted_printable(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'=' {
+            let next = &data[i + 1..];
+            if next.starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if next.starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            if next.len() >= 2 {
+                if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&next[..2]), 16) {
+                    out.push(byte);
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Decode text in a declared charset (UTF-8 and Latin-1 family; others are read as UTF-8)
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.trim().to_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "latin-1" | "windows-1252" | "cp1252" | "us-ascii" => {
+            bytes.iter().map(|&b| b as char).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+// ==================== Outlook .msg ====================
+
+/// Parse an Outlook message (.msg, an OLE compound file)
+pub fn parse_msg(filename: &str, data: &[u8]) -> Result<EmailMessage> {
+    let file = CompoundFile::open(data).map_err(|e| Error::file_parse(filename, e))?;
+    let root = file.root_children();
+
+    let string_prop = |children: &[usize], id: &str| -> Option<String> {
+        if let Some(bytes) = file.child_stream(children, &format!("__substg1.0_{}001F", id)) {
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            return Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string());
+        }
+        file.child_stream(children, &format!("__substg1.0_{}001E", id))
+            .map(|bytes| String::from_utf8_lossy(&bytes).trim_end_matches('\0').to_string())
+    };
+
+    // Transport headers carry the original Date and addresses when present
+    let transport = string_prop(&root, "007D")
+        .map(|h| parse_headers(&h))
+        .unwrap_or_default();
+    let transport_header = |name: &str| {
+        transport
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| decode_words(v))
+    };
+
+    let sender_name = string_prop(&root, "0C1A");
+    let sender_email = string_prop(&root, "5D01").or_else(|| string_prop(&root, "0C1F"));
+    let from = transport_header("from").or(match (sender_name, sender_email) {
+        (Some(name), Some(email)) if name != email => Some(format!("{} <{}>", name, email)),
+        (name, email) => email.or(name),
+    });
+
+    let body = match string_prop(&root, "1000") {
+        Some(body) => body,
+        None => file
+            .child_stream(&root, "__substg1.0_10130102")
+            .and_then(|html| FileParser::parse("body.html", &html).ok())
+            .map(|parsed| parsed.content)
+            .unwrap_or_default(),
+    };
+
+    let attachments = root
+        .iter()
+        .filter(|&&i| file.entries[i].name.starts_with("__attach_version1.0_"))
+        .filter_map(|&i| {
+            let children = file.children(i);
+            let data = file.child_stream(&children, "__substg1.0_37010102")?;
+            let filename = string_prop(&children, "3707")
+                .or_else(|| string_prop(&children, "3704"))
+                .unwrap_or_else(|| "attachment".to_string());
+            let content_type = string_prop(&children, "370E").unwrap_or_default();
+            Some(Attachment { filename, content_type, data })
+        })
+        .collect();
+
+    Ok(EmailMessage {
+        from,
+        to: transport_header("to").or_else(|| string_prop(&root, "0E04")),
+        cc: transport_header("cc").or_else(|| string_prop(&root, "0E03")),
+        date: transport_header("date").or_else(|| file.submit_time().map(|d| d.to_rfc2822())),
+        subject: string_prop(&root, "0037").or_else(|| transport_header("subject")),
+        body,
+        attachments,
+    })
+}
+
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const NO_STREAM: u32 = 0xFFFF_FFFF;
+
+struct DirEntry {
+    name: String,
+    kind: u8,
+    left: u32,
+    right: u32,
+    child: u32,
+    start: u32,
+    size: u64,
+}
+
+/// Minimal read-only reader for OLE compound files
+struct CompoundFile<'a> {
+    data: &'a [u8],
+    sector_size: usize,
+    mini_sector_size: usize,
+    mini_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    entries: Vec<DirEntry>,
+}
+
+impl<'a> CompoundFile<'a> {
+    fn open(data: &'a [u8]) -> std::result::Result<Self, String> {
+        if data.len() < 512 || data[..8] != CFB_SIGNATURE {
+            return Err("Not an Outlook .msg file".to_string());
+        }
+        let u16_at = |o: usize| u16::from_le_bytes([data[o], data[o + 1]]);
+        let u32_at = |o: usize| u32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+
+        let sector_size = 1usize << u16_at(0x1E).min(16);
+        let mini_sector_size = 1usize << u16_at(0x20).min(16);
+        let mini_cutoff = u32_at(0x38) as u64;
+        let mut file = Self {
+            data,
+            sector_size,
+            mini_sector_size,
+            mini_cutoff,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            entries: Vec::new(),
+        };
+
+        // FAT sector list: 109 entries in the header, the rest in DIFAT sectors
+        let mut fat_sectors: Vec<u32> = (0..109).map(|i| u32_at(0x4C + i * 4)).collect();
+        let mut difat = u32_at(0x44);
+        let per_sector = sector_size / 4;
+        for _ in 0..u32_at(0x48).min((data.len() / sector_size) as u32) {
+            let Some(sector) = file.sector(difat) else { break };
+            let ids: Vec<u32> = sector.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect();
+            fat_sectors.extend(&ids[..per_sector - 1]);
+            difat = ids[per_sector - 1];
+        }
+        fat_sectors.truncate(u32_at(0x2C) as usize);
+        file.fat = fat_sectors
+            .iter()
+            .filter_map(|&s| file.sector(s))
+            .flat_map(|s| s.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+            .collect();
+
+        file.mini_fat = file
+            .chain(u32_at(0x3C))
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+
+        file.entries = file
+            .chain(u32_at(0x30))
+            .chunks_exact(128)
+            .map(|e| {
+                let name_len = (u16::from_le_bytes([e[64], e[65]]) as usize).min(64);
+                let units: Vec<u16> = e[..name_len.saturating_sub(2)]
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                let u32_in = |o: usize| u32::from_le_bytes([e[o], e[o + 1], e[o + 2], e[o + 3]]);
+                DirEntry {
+                    name: String::from_utf16_lossy(&units),
+                    kind: e[66],
+                    left: u32_in(68),
+                    right: u32_in(72),
+                    child: u32_in(76),
+                    start: u32_in(116),
+                    size: u64::from(u32_in(120)),
+                }
+            })
+            .collect();
+
+        if file.entries.first().map(|e| e.kind) != Some(5) {
+            return Err("Compound file has no root entry".to_string());
+        }
+        let root = &file.entries[0];
+        let mut mini_stream = file.chain(root.start);
+        mini_stream.truncate(root.size as usize);
+        file.mini_stream = mini_stream;
+
+        Ok(file)
+    }
+
+    fn sector(&self, id: u32) -> Option<&'a [u8]> {
+        let start = (id as usize + 1).checked_mul(self.sector_size)?;
+        self.data.get(start..start + self.sector_size)
+    }
+
+    /// Concatenated sectors of a FAT chain
+    fn chain(&self, start: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut id = start;
+        let mut steps = 0;
+        while id != END_OF_CHAIN && steps <= self.fat.len() {
+            let Some(sector) = self.sector(id) else { break };
+            out.extend_from_slice(sector);
+            id = self.fat.get(id as usize).copied().unwrap_or(END_OF_CHAIN);
+            steps += 1;
+        }
+        out
+    }
+
+    fn stream(&self, entry: &DirEntry) -> Vec<u8> {
+        let size = entry.size as usize;
+        let mut out = if entry.size < self.mini_cutoff {
+            let mut out = Vec::new();
+            let mut id = entry.start;
+            let mut steps = 0;
+            while id != END_OF_CHAIN && steps <= self.mini_fat.len() && out.len() < size {
+                let start = id as usize * self.mini_sector_size;
+                let Some(sector) = self.mini_stream.get(start..start + self.mini_sector_size) else { break };
+                out.extend_from_slice(sector);
+                id = self.mini_fat.get(id as usize).copied().unwrap_or(END_OF_CHAIN);
+                steps += 1;
+            }
+            out
+        } else {
+            self.chain(entry.start)
+        };
+        out.truncate(size);
+        out
+    }
+
+    /// Entry indices of a storage's children (a red-black tree of siblings)
+    fn children(&self, storage: usize) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack = vec![self.entries[storage].child];
+        while let Some(id) = stack.pop() {
+            if id == NO_STREAM || out.len() > self.entries.len() {
+                continue;
+            }
+            let Some(entry) = self.entries.get(id as usize) else { continue };
+            out.push(id as usize);
+            stack.push(entry.left);
+            stack.push(entry.right);
+        }
+        out
+    }
+
+    fn root_children(&self) -> Vec<usize> {
+        self.children(0)
+    }
+
+    fn child_stream(&self, children: &[usize], name: &str) -> Option<Vec<u8>> {
+        children
+            .iter()
+            .map(|&i| &self.entries[i])
+            .find(|e| e.kind == 2 && e.name.eq_ignore_ascii_case(name))
+            .map(|e| self.stream(e))
+    }
+
+    /// PR_CLIENT_SUBMIT_TIME from the message property stream
+    fn submit_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let props = self.child_stream(&self.root_children(), "__properties_version1.0")?;
+        // 32-byte header on the top-level message, then 16-byte entries
+        props.get(32..)?.chunks_exact(16).find_map(|entry| {
+            let tag = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+            if tag != 0x0039_0040 {
+                return None;
+            }
+            let filetime = u64::from_le_bytes(entry[8..16].try_into().ok()?);
+            // FILETIME counts 100ns intervals since 1601-01-01
+            let unix_seconds = (filetime / 10_000_000).checked_sub(11_644_473_600)?;
+            chrono::DateTime::from_timestamp(unix_seconds as i64, 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChunkSource, Document};
+
+    const MULTIPART: &str = "From: =?UTF-8?B?SsO2cmc=?= <jorg@example.com>\r\n\
+        To: team@example.com\r\n\
+        Subject: Q3 budget\r\n \
+        review\r\n\
+        Date: Tue, 1 Oct 2024 09:30:00 +0200\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        --outer\r\n\
+        Content-Type: multipart/alternative; boundary=inner\r\n\
+        \r\n\
+        --inner\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        The budget is =E2=82=AC40k, see the attached =\r\n\
+        notes.\r\n\
+        --inner\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <p>The budget is 40k</p>\r\n\
+        --inner--\r\n\
+        --outer\r\n\
+        Content-Type: text/plain; name=\"notes.txt\"\r\n\
+        Content-Disposition: attachment; filename=\"notes.txt\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        SGlyaW5nIGZyZWV6ZSB1bnRpbCBKYW51YXJ5Lg==\r\n\
+        --outer--\r\n";
+
+    #[test]
+    fn test_parse_eml_multipart() {
+        let message = parse_eml(MULTIPART.as_bytes());
+        assert_eq!(message.from.as_deref(), Some("Jörg <jorg@example.com>"));
+        assert_eq!(message.subject.as_deref(), Some("Q3 budget review"));
+        assert_eq!(message.body, "The budget is €40k, see the attached notes.");
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].filename, "notes.txt");
+        assert_eq!(message.attachments[0].data, b"Hiring freeze until January.");
+
+        let rendered = message.render();
+        assert!(rendered.starts_with("From: Jörg <jorg@example.com>\nTo: team@example.com\n"));
+        assert!(rendered.contains("Attachment: notes.txt\nHiring freeze until January."));
+    }
+
+    #[test]
+    fn test_split_mbox_and_tag_chunks() {
+        let mbox = "From alice@example.com Mon Sep 30 10:00:00 2024\n\
+            From: alice@example.com\nSubject: First\nDate: Mon, 30 Sep 2024 10:00:00 +0000\n\n\
+            Hello.\n>From here on, quoted.\n\n\
+            From bob@example.com Tue Oct  1 10:00:00 2024\n\
+            From: bob@example.com\nSubject: Second\n\nBye.\n";
+
+        let messages = split_mbox(mbox.as_bytes());
+        assert_eq!(messages.len(), 2);
+        assert!(String::from_utf8_lossy(&messages[0]).contains("\nFrom here on, quoted."));
+
+        let parsed = parse("archive.mbox", mbox.as_bytes()).unwrap();
+        assert_eq!(parsed.file_type, FileType::Mbox);
+        assert_eq!(parsed.total_pages, Some(2));
+
+        let doc = Document::new("archive.mbox".into(), FileType::Mbox, parsed.content_hash.clone(), 0);
+        let mut source = ChunkSource::text("archive.mbox".into());
+        source.page_number = Some(2);
+        let mut chunks = vec![Chunk::new(doc.id, "Bye.".into(), source, 0, 4, 0)];
+        tag_chunks(&mut chunks, &parsed);
+
+        assert_eq!(chunks[0].metadata[EMAIL_FROM_METADATA_KEY], "bob@example.com");
+        assert_eq!(chunks[0].metadata[EMAIL_SUBJECT_METADATA_KEY], "Second");
+        assert_eq!(chunks[0].source.section_title.as_deref(), Some("Second"));
+        assert!(!chunks[0].metadata.contains_key(EMAIL_DATE_METADATA_KEY));
+    }
+
+    #[test]
+    fn test_email_filter() {
+        use crate::types::query::EmailFilter;
+
+        let mut chunk = Chunk::new(uuid::Uuid::new_v4(), "Bye.".into(), ChunkSource::text("a.eml".into()), 0, 4, 0);
+        assert!(!EmailFilter::default().matches(&chunk));
+
+        chunk.metadata.insert(EMAIL_FROM_METADATA_KEY.into(), "Bob <bob@example.com>".into());
+        chunk.metadata.insert(EMAIL_SUBJECT_METADATA_KEY.into(), "Q3 budget".into());
+        chunk.metadata.insert(EMAIL_DATE_METADATA_KEY.into(), "2024-10-01T07:30:00+00:00".into());
+
+        let filter = |from: Option<&str>, after: Option<&str>| EmailFilter {
+            from: from.map(str::to_string),
+            after: after.map(|d| d.parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(filter(Some("BOB@"), None).matches(&chunk));
+        assert!(!filter(Some("alice"), None).matches(&chunk));
+        assert!(filter(None, Some("2024-09-01T00:00:00Z")).matches(&chunk));
+        assert!(!filter(None, Some("2024-11-01T00:00:00Z")).matches(&chunk));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let date = parse_date("Tue, 1 Oct 2024 09:30:00 +0200 (CEST)").unwrap();
+        assert_eq!(date.to_rfc3339(), "2024-10-01T07:30:00+00:00");
+        assert!(parse_date("yesterday").is_none());
+    }
+}
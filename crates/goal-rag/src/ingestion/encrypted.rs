@@ -0,0 +1,148 @@
+//! Password-protected PDFs
+//!
+//! Encrypted PDFs are decrypted before parsing with the passwords known for
+//! the file: the one given for it in the ingest request, then those
+//! configured for its collection and the default list. The empty password
+//! is tried first, which opens files that only restrict printing or
+//! copying. `qpdf` does the decryption when installed, lopdf otherwise.
+//! Files no password opens fail with `Error::PasswordRequired`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::Command;
+
+use crate::config::PdfPasswordsConfig;
+use crate::error::{Error, Result};
+
+/// Whether a PDF has an encryption dictionary
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF") && data.windows(8).any(|w| w == b"/Encrypt")
+}
+
+/// Decrypt an ingested file if it is an encrypted PDF, `None` otherwise
+///
+/// `file_passwords` are the request's passwords by filename; archive
+/// entries (`<archive>/<path>`) also match by their own name.
+pub async fn decrypt_upload(
+    config: &PdfPasswordsConfig,
+    filename: &str,
+    data: &[u8],
+    collection: Option<&str>,
+    file_passwords: &HashMap<String, String>,
+) -> Result<Option<Vec<u8>>> {
+    if !filename.to_lowercase().ends_with(".pdf") || !is_encrypted(data) {
+        return Ok(None);
+    }
+
+    let base_name = filename.rsplit('/').next().unwrap_or(filename);
+    let file_password = file_passwords.get(filename).or_else(|| file_passwords.get(base_name));
+    let passwords = candidate_passwords(config, collection, file_password.map(String::as_str));
+    let (name, data) = (filename.to_string(), data.to_vec());
+    tokio::task::spawn_blocking(move || decrypt_pdf(&name, &data, &passwords))
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))?
+}
+
+/// Passwords to try for a file, in order and without repeats
+pub fn candidate_passwords(
+    config: &PdfPasswordsConfig,
+    collection: Option<&str>,
+    file_password: Option<&str>,
+) -> Vec<String> {
+    let collection_passwords = collection.and_then(|c| config.collections.get(c)).into_iter().flatten();
+    let mut passwords: Vec<String> = Vec::new();
+    for password in std::iter::once("")
+        .chain(file_password)
+        .chain(collection_passwords.map(String::as_str))
+        .chain(config.default.iter().map(String::as_str))
+    {
+        if !passwords.iter().any(|p| p == password) {
+            passwords.push(password.to_string());
+        }
+    }
+    passwords
+}
+
+/// Decrypt a PDF, `None` if it isn't encrypted
+pub fn decrypt_pdf(filename: &str, data: &[u8], passwords: &[String]) -> Result<Option<Vec<u8>>> {
+    if !is_encrypted(data) {
+        return Ok(None);
+    }
+
+    for password in passwords {
+        let decrypted = match decrypt_with_qpdf(data, password) {
+            Ok(decrypted) => decrypted,
+            Err(e) => {
+                tracing::debug!("qpdf unavailable for {} ({}), decrypting with lopdf", filename, e);
+                decrypt_with_lopdf(data, password)
+            }
+        };
+        if let Some(decrypted) = decrypted {
+            tracing::info!("Decrypted {}", filename);
+            return Ok(Some(decrypted));
+        }
+    }
+
+    Err(Error::PasswordRequired(filename.to_string()))
+}
+
+/// Decrypt with `qpdf`, `Ok(None)` if the password is wrong and an error if
+/// qpdf can't be run
+fn decrypt_with_qpdf(data: &[u8], password: &str) -> std::io::Result<Option<Vec<u8>>> {
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("input.pdf");
+    let output = dir.path().join("output.pdf");
+    // The password goes through a file so it doesn't show in the process list
+    let password_file = dir.path().join("password");
+    std::fs::write(&input, data)?;
+    std::fs::File::create(&password_file)?.write_all(password.as_bytes())?;
+
+    let result = Command::new("qpdf")
+        .arg(format!("--password-file={}", password_file.display()))
+        .arg("--decrypt")
+        .arg(&input)
+        .arg(&output)
+        .output()?;
+
+    // Exit code 3 means decrypted with warnings
+    match result.status.code() {
+        Some(0) | Some(3) => Ok(Some(std::fs::read(&output)?)),
+        _ if String::from_utf8_lossy(&result.stderr).contains("invalid password") => Ok(None),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        )),
+    }
+}
+
+/// Decrypt with lopdf, `None` if the password is wrong or the file can't be read
+fn decrypt_with_lopdf(data: &[u8], password: &str) -> Option<Vec<u8>> {
+    let mut doc = lopdf::Document::load_mem(data).ok()?;
+    if doc.is_encrypted() {
+        doc.decrypt(password).ok()?;
+        doc.trailer.remove(b"Encrypt");
+    }
+    let mut out = Vec::new();
+    doc.save_to(&mut out).ok()?;
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_passwords() {
+        let config = PdfPasswordsConfig {
+            default: vec!["shared".to_string(), "finance".to_string()],
+            collections: HashMap::from([("finance".to_string(), vec!["finance".to_string()])]),
+        };
+        assert_eq!(
+            candidate_passwords(&config, Some("finance"), Some("q3")),
+            vec!["", "q3", "finance", "shared"]
+        );
+        assert_eq!(candidate_passwords(&config, None, None), vec!["", "shared", "finance"]);
+        assert!(!is_encrypted(b"%PDF-1.7\nplain"));
+        assert!(decrypt_pdf("a.pdf", b"%PDF-1.7\nplain", &[]).unwrap().is_none());
+    }
+}
@@ -5,6 +5,7 @@
 //! - pandoc - Universal document converter
 //! - LibreOffice - Legacy format conversion
 //! - Unstructured.io API - Cloud-based parsing fallback
+//! - Parser plugins - operator-supplied commands or WASM modules (see `plugins`)
 
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -13,6 +14,7 @@ use std::time::Duration;
 
 use crate::error::{Error, Result};
 use crate::processing::{FileCharacteristics, PdfAnalysis};
+use super::plugins::{ParserPlugin, ParserPluginConfig};
 
 /// External parser configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +29,9 @@ pub struct ExternalParserConfig {
     pub use_libreoffice_fallback: bool,
     /// Use local tools (pdftotext, pandoc) first before API
     pub prefer_local_tools: bool,
+    /// Custom parsers by extension or MIME type, tried first
+    #[serde(default)]
+    pub plugins: Vec<ParserPluginConfig>,
 }
 
 impl Default for ExternalParserConfig {
@@ -37,6 +42,7 @@ impl Default for ExternalParserConfig {
             unstructured_url: "https://api.unstructured.io/general/v0/general".to_string(),
             use_libreoffice_fallback: true,
             prefer_local_tools: true, // Use local tools by default
+            plugins: Vec::new(),
         }
     }
 }
@@ -45,6 +51,7 @@ impl Default for ExternalParserConfig {
 pub struct ExternalParser {
     client: Client,
     config: ExternalParserConfig,
+    plugins: Vec<ParserPlugin>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,8 +77,9 @@ impl ExternalParser {
             .timeout(Duration::from_secs(120))
             .build()
             .expect("Failed to create HTTP client");
+        let plugins = config.plugins.iter().cloned().filter_map(ParserPlugin::new).collect();
 
-        Self { client, config }
+        Self { client, config, plugins }
     }
 
     /// Check if external parsing is available
@@ -79,6 +87,11 @@ impl ExternalParser {
         self.config.enabled
     }
 
+    /// Whether a parser plugin handles the file
+    pub fn has_plugin(&self, filename: &str) -> bool {
+        self.plugins.iter().any(|plugin| plugin.matches(filename))
+    }
+
     /// Parse document using Unstructured.io API
     pub async fn parse_with_unstructured(
         &self,
@@ -108,15 +121,17 @@ impl ExternalParser {
         let response = request
             .send()
             .await
-            .map_err(|e| Error::Internal(format!("Unstructured API request failed: {}", e)))?;
+            .map_err(|e| Error::ParserUnavailable(format!("Unstructured API request failed: {}", e)))?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(Error::Internal(format!(
-                "Unstructured API error: {} - {}",
-                status, body
-            )));
+            let message = format!("Unstructured API error: {} - {}", status, body);
+            return Err(match status.as_u16() {
+                429 => Error::ProviderRateLimited { provider: "unstructured".to_string(), message },
+                500.. => Error::ParserUnavailable(message),
+                _ => Error::Internal(message),
+            });
         }
 
         let elements: Vec<UnstructuredElement> = response
@@ -194,7 +209,8 @@ impl ExternalParser {
             .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
 
         // Write input file
-        let input_path = temp_dir.join(filename);
+        // Archive entries are named "<archive>/<path>"; only the file name is used
+        let input_path = temp_dir.join(std::path::Path::new(filename).file_name().unwrap_or("input".as_ref()));
         fs::write(&input_path, data)
             .map_err(|e| Error::Internal(format!("Failed to write temp file: {}", e)))?;
 
@@ -217,7 +233,7 @@ impl ExternalParser {
                 input_path.to_str().unwrap(),
             ])
             .output()
-            .map_err(|e| Error::Internal(format!("LibreOffice conversion failed: {}", e)))?;
+            .map_err(|e| Error::ParserUnavailable(format!("LibreOffice conversion failed: {}", e)))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -296,6 +312,15 @@ impl ExternalParser {
             .unwrap_or(false)
     }
 
+    /// Check if pdfimages is available (for figure extraction)
+    pub fn has_pdfimages() -> bool {
+        Command::new("pdfimages")
+            .arg("-v")
+            .output()
+            .map(|_| true) // pdfimages -v outputs to stderr, just check if command exists
+            .unwrap_or(false)
+    }
+
     /// Check if pandoc is available
     pub fn has_pandoc() -> bool {
         Command::new("pandoc")
@@ -324,7 +349,7 @@ impl ExternalParser {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|e| Error::Internal(format!("Failed to spawn pdftotext: {}", e)))?;
+            .map_err(|e| Error::ParserUnavailable(format!("Failed to spawn pdftotext: {}", e)))?;
 
         // Write PDF data to stdin
         if let Some(mut stdin) = child.stdin.take() {
@@ -355,7 +380,7 @@ impl ExternalParser {
         use std::fs;
 
         if !Self::has_pdftoppm() || !Self::has_tesseract() {
-            return Err(Error::Internal(
+            return Err(Error::ParserUnavailable(
                 "OCR requires pdftoppm and tesseract. Install with: apt install poppler-utils tesseract-ocr".to_string()
             ));
         }
@@ -551,7 +576,8 @@ impl ExternalParser {
         fs::create_dir_all(&temp_dir)
             .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
 
-        let input_path = temp_dir.join(filename);
+        // Archive entries are named "<archive>/<path>"; only the file name is used
+        let input_path = temp_dir.join(std::path::Path::new(filename).file_name().unwrap_or("input".as_ref()));
         fs::write(&input_path, data)
             .map_err(|e| Error::Internal(format!("Failed to write temp file: {}", e)))?;
 
@@ -761,15 +787,25 @@ impl ExternalParser {
             characteristics.is_scanned_pdf
         );
 
+        // Plugins registered for the file come first
+        let plugins: Vec<&ParserPlugin> = self.plugins.iter().filter(|p| p.matches(filename)).collect();
+        let strategies = plugins
+            .iter()
+            .map(|plugin| plugin.attempt_name())
+            .chain(strategies.into_iter().map(str::to_string));
+
         for strategy in strategies {
             let attempt_start = Instant::now();
-            let result = match strategy {
+            let result = match strategy.as_str() {
                 "native" => self.try_native_parsing(filename, data).await,
                 "pdftotext" => self.try_pdftotext(data),
                 "pandoc" => self.try_pandoc(filename, data),
                 "ocr" => self.try_ocr(data, &ext),
                 "unstructured" => self.try_unstructured(filename, data).await,
-                _ => Err(Error::Internal(format!("Unknown strategy: {}", strategy))),
+                name => match plugins.iter().find(|plugin| plugin.attempt_name() == name) {
+                    Some(plugin) => plugin.parse(filename, data).await,
+                    None => Err(Error::Internal(format!("Unknown strategy: {}", strategy))),
+                },
             };
 
             let duration_ms = attempt_start.elapsed().as_millis() as u64;
@@ -0,0 +1,278 @@
+//! Embedded image extraction for figure captioning
+//!
+//! Images are pulled out of PDFs (with poppler's `pdfimages`) and DOCX files
+//! (from `word/media/`), described by a vision model, and indexed as
+//! `ChunkKind::Figure` chunks on the image's page so questions about figures
+//! and charts can be answered.
+
+use std::fs;
+use std::io::Read;
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::types::document::COLLECTION_METADATA_KEY;
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document};
+use super::ExternalParser;
+
+/// Longest page caption line passed to the vision model as a hint
+const MAX_LABEL_LEN: usize = 160;
+
+/// An image embedded in a document
+#[derive(Debug, Clone)]
+pub struct ExtractedImage {
+    /// Page the image appears on (PDF only)
+    pub page_number: Option<u32>,
+    /// Position among the document's extracted images, starting at 1
+    pub index: usize,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Whether figures can be extracted from a file type
+pub fn supports(filename: &str) -> bool {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    matches!(ext.as_str(), "pdf" | "docx")
+}
+
+/// Extract embedded images from a PDF or DOCX
+///
+/// Images smaller than `min_bytes` (icons, logos, rules) are skipped and at
+/// most `max_images` are returned, in document order.
+pub fn extract_images(filename: &str, data: &[u8], max_images: usize, min_bytes: usize) -> Result<Vec<ExtractedImage>> {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    let images = match ext.as_str() {
+        "pdf" => extract_pdf_images(data)?,
+        "docx" => extract_docx_images(filename, data)?,
+        _ => Vec::new(),
+    };
+
+    Ok(images
+        .into_iter()
+        .filter(|image| image.data.len() >= min_bytes)
+        .take(max_images)
+        .enumerate()
+        .map(|(i, image)| ExtractedImage { index: i + 1, ..image })
+        .collect())
+}
+
+fn extract_pdf_images(data: &[u8]) -> Result<Vec<ExtractedImage>> {
+    if !ExternalParser::has_pdfimages() {
+        return Err(Error::Internal("pdfimages not installed (install poppler-utils)".to_string()));
+    }
+
+    let temp_dir = std::env::temp_dir().join(format!("goal-rag-figures-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
+
+    let result = (|| {
+        let pdf_path = temp_dir.join("input.pdf");
+        fs::write(&pdf_path, data)
+            .map_err(|e| Error::Internal(format!("Failed to write temp PDF: {}", e)))?;
+
+        // -p adds the page number to each file name: img-PPP-NNN.png
+        let output = Command::new("pdfimages")
+            .args([
+                "-png",
+                "-p",
+                pdf_path.to_str().unwrap(),
+                temp_dir.join("img").to_str().unwrap(),
+            ])
+            .output()
+            .map_err(|e| Error::Internal(format!("pdfimages failed: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::Internal(format!("pdfimages error: {}", stderr)));
+        }
+
+        let mut files: Vec<(u32, u32, std::path::PathBuf)> = fs::read_dir(&temp_dir)
+            .map_err(|e| Error::Internal(format!("Failed to read temp dir: {}", e)))?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                parse_pdfimages_name(&name).map(|(page, n)| (page, n, e.path()))
+            })
+            .collect();
+        files.sort();
+
+        Ok(files
+            .into_iter()
+            .filter_map(|(page, _, path)| {
+                fs::read(path).ok().map(|data| ExtractedImage {
+                    page_number: Some(page),
+                    index: 0,
+                    mime_type: "image/png".to_string(),
+                    data,
+                })
+            })
+            .collect())
+    })();
+
+    fs::remove_dir_all(&temp_dir).ok();
+    result
+}
+
+/// Page and image number from a `pdfimages -p` output name (`img-004-012.png`)
+fn parse_pdfimages_name(name: &str) -> Option<(u32, u32)> {
+    let stem = name.strip_prefix("img-")?.strip_suffix(".png")?;
+    let (page, number) = stem.split_once('-')?;
+    Some((page.parse().ok()?, number.parse().ok()?))
+}
+
+fn extract_docx_images(filename: &str, data: &[u8]) -> Result<Vec<ExtractedImage>> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+        .map_err(|e| Error::file_parse(filename, e.to_string()))?;
+
+    let mut names: Vec<(u32, String, &'static str)> = archive
+        .file_names()
+        .filter(|name| name.starts_with("word/media/"))
+        .filter_map(|name| image_mime_type(name).map(|mime| (media_number(name), name.to_string(), mime)))
+        .collect();
+    names.sort();
+
+    let mut images = Vec::new();
+    for (_, name, mime_type) in names {
+        let mut data = Vec::new();
+        if let Ok(mut file) = archive.by_name(&name) {
+            if file.read_to_end(&mut data).is_ok() {
+                images.push(ExtractedImage {
+                    page_number: None,
+                    index: 0,
+                    mime_type: mime_type.to_string(),
+                    data,
+                });
+            }
+        }
+    }
+
+    Ok(images)
+}
+
+/// MIME type for raster images vision models accept (EMF/WMF/SVG are skipped)
+fn image_mime_type(name: &str) -> Option<&'static str> {
+    match name.rsplit('.').next().unwrap_or("").to_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Trailing number of a media name (`word/media/image12.png` -> 12)
+fn media_number(name: &str) -> u32 {
+    let stem = name.rsplit('/').next().unwrap_or(name);
+    let stem = stem.split('.').next().unwrap_or(stem);
+    let digits: String = stem.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+    digits.chars().rev().collect::<String>().parse().unwrap_or(0)
+}
+
+/// Figure caption lines in page text ("Figure 3: Revenue by region")
+///
+/// Passed to the vision model so captions carry the document's own figure
+/// numbers.
+pub fn page_labels(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| {
+            let lower = line.to_lowercase();
+            let rest = lower
+                .strip_prefix("figure")
+                .or_else(|| lower.strip_prefix("fig."))
+                .or_else(|| lower.strip_prefix("fig"));
+            rest.is_some_and(|r| r.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .map(|line| line.chars().take(MAX_LABEL_LEN).collect())
+        .collect()
+}
+
+/// Prompt asking the vision model to describe an image
+pub fn caption_prompt(labels: &[String]) -> String {
+    let mut prompt = String::from(
+        "Describe this image from a document so it can be found by search. \
+        Say what kind of figure it is (chart, diagram, photo, screenshot, table), \
+        what it shows, and any visible title, labels, axis names and key values. \
+        If it has a figure number, start with it (e.g. \"Figure 3:\"). \
+        Answer in plain text, at most 150 words.",
+    );
+    if !labels.is_empty() {
+        prompt.push_str("\n\nFigure captions on the same page:\n");
+        for label in labels {
+            prompt.push_str("- ");
+            prompt.push_str(label);
+            prompt.push('\n');
+        }
+    }
+    prompt
+}
+
+/// Create the chunk for a captioned image
+pub fn figure_chunk(doc: &Document, image: &ExtractedImage, caption: &str, chunk_index: u32) -> Chunk {
+    let mut source = ChunkSource::text(doc.filename.clone());
+    source.internal_filename = doc.internal_filename.clone();
+    source.file_type = doc.file_type.clone();
+    source.page_number = image.page_number;
+    source.page_count = doc.total_pages;
+    source.kind = ChunkKind::Figure;
+
+    let content = match image.page_number {
+        Some(page) => format!("[Image {} on page {}] {}", image.index, page, caption.trim()),
+        None => format!("[Image {}] {}", image.index, caption.trim()),
+    };
+
+    let mut chunk = Chunk::new(doc.id, content, source, 0, 0, chunk_index);
+    if let Some(collection) = doc.metadata.get(COLLECTION_METADATA_KEY) {
+        chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), collection.clone());
+    }
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_pdfimages_name() {
+        assert_eq!(parse_pdfimages_name("img-004-012.png"), Some((4, 12)));
+        assert_eq!(parse_pdfimages_name("img-004.png"), None);
+        assert_eq!(parse_pdfimages_name("input.pdf"), None);
+    }
+
+    #[test]
+    fn test_extract_docx_images() {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::SimpleFileOptions::default();
+            for (name, size) in [
+                ("word/document.xml", 10),
+                ("word/media/image10.png", 100),
+                ("word/media/image2.jpeg", 100),
+                ("word/media/image3.emf", 100),
+                ("word/media/image4.png", 5),
+            ] {
+                zip.start_file(name, options).unwrap();
+                zip.write_all(&vec![0u8; size]).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+
+        let images = extract_images("report.docx", buffer.get_ref(), 10, 50).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].mime_type, "image/jpeg");
+        assert_eq!(images[0].index, 1);
+        assert_eq!(images[1].mime_type, "image/png");
+        assert_eq!(images[1].index, 2);
+        assert!(images.iter().all(|i| i.page_number.is_none()));
+
+        assert_eq!(extract_images("report.docx", buffer.get_ref(), 1, 50).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_page_labels() {
+        let text = "Results\nFigure 3: Revenue by region\nThe figure shows growth.\nFig. 4 - Margins\nfigures are approximate";
+        assert_eq!(page_labels(text), vec!["Figure 3: Revenue by region", "Fig. 4 - Margins"]);
+        assert!(caption_prompt(&page_labels(text)).contains("- Figure 3: Revenue by region"));
+    }
+}
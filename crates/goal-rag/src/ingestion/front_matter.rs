@@ -0,0 +1,214 @@
+//! Title, author, tags and date of Markdown and HTML documents
+//!
+//! Markdown files may start with a YAML front-matter block between `---`
+//! lines; HTML pages carry `<title>`, `<meta>` and OpenGraph tags. Both are
+//! reduced to the same four fields in `ParsedDocument::metadata`, which end up
+//! on the document and its chunks (see `Document::set_source_metadata`). Only
+//! flat front matter is understood: scalars, `[a, b]` lists and `- item`
+//! lists.
+
+use std::collections::HashMap;
+
+use crate::types::document::{AUTHOR_METADATA_KEY, DATE_METADATA_KEY, TAGS_METADATA_KEY, TITLE_METADATA_KEY};
+
+/// Front-matter keys read for each field, first match wins
+const TITLE_KEYS: &[&str] = &["title"];
+const AUTHOR_KEYS: &[&str] = &["author", "authors"];
+const TAGS_KEYS: &[&str] = &["tags", "keywords", "categories"];
+const DATE_KEYS: &[&str] = &["date", "published", "created"];
+
+/// `<meta>` names and properties read for each field, first match wins
+const HTML_TITLE_KEYS: &[&str] = &["og:title", "twitter:title", "dc.title"];
+const HTML_AUTHOR_KEYS: &[&str] = &["author", "article:author", "dc.creator"];
+const HTML_TAGS_KEYS: &[&str] = &["keywords", "article:tag", "og:article:tag"];
+const HTML_DATE_KEYS: &[&str] = &["article:published_time", "og:published_time", "date", "dc.date", "pubdate"];
+
+/// Split YAML front matter off a Markdown document
+///
+/// Returns the extracted fields and the rest of the document, or no fields
+/// and the whole document when it has no front matter.
+pub fn split_markdown(text: &str) -> (HashMap<String, String>, &str) {
+    let body = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = body.split_inclusive('\n');
+    let Some(first) = lines.next().filter(|line| line.trim_end() == "---") else {
+        return (HashMap::new(), text);
+    };
+
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+    let mut list_key: Option<String> = None;
+    let mut offset = first.len();
+    for line in lines {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return (fields(&values, [TITLE_KEYS, AUTHOR_KEYS, TAGS_KEYS, DATE_KEYS]), &body[offset..]);
+        }
+
+        if let Some(item) = line.trim_start().strip_prefix("- ").filter(|_| line.starts_with([' ', '-'])) {
+            if let Some(key) = &list_key {
+                values.entry(key.clone()).or_default().push(unquote(item).to_string());
+            }
+            continue;
+        }
+        list_key = None;
+        let Some((key, value)) = line.split_once(':').filter(|_| !line.starts_with([' ', '#'])) else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        if value.is_empty() {
+            list_key = Some(key);
+        } else if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items = list.split(',').map(|item| unquote(item).to_string()).filter(|item| !item.is_empty());
+            values.insert(key, items.collect());
+        } else {
+            values.insert(key, vec![unquote(value).to_string()]);
+        }
+    }
+
+    // An unclosed block is part of the text
+    (HashMap::new(), text)
+}
+
+/// Fields from the `<title>`, `<meta>` and OpenGraph tags of an HTML page
+pub fn html_fields(document: &scraper::Html) -> HashMap<String, String> {
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+
+    let meta = scraper::Selector::parse("meta").unwrap();
+    for element in document.select(&meta) {
+        let element = element.value();
+        let name = element.attr("property").or_else(|| element.attr("name"));
+        if let (Some(name), Some(content)) = (name, element.attr("content")) {
+            let content = content.trim();
+            if !content.is_empty() {
+                values.entry(name.to_lowercase()).or_default().push(content.to_string());
+            }
+        }
+    }
+
+    let title = scraper::Selector::parse("title").unwrap();
+    if let Some(element) = document.select(&title).next() {
+        let text = element.text().collect::<String>();
+        if !text.trim().is_empty() {
+            values.insert("title".to_string(), vec![text.trim().to_string()]);
+        }
+    }
+
+    let title_keys: Vec<&str> = HTML_TITLE_KEYS.iter().copied().chain(TITLE_KEYS.iter().copied()).collect();
+    fields(&values, [title_keys.as_slice(), HTML_AUTHOR_KEYS, HTML_TAGS_KEYS, HTML_DATE_KEYS])
+}
+
+/// Title, author, tags and date from the first matching keys
+fn fields(values: &HashMap<String, Vec<String>>, keys: [&[&str]; 4]) -> HashMap<String, String> {
+    let [title, author, tags, date] = keys;
+    let first = |keys: &[&str]| keys.iter().find_map(|key| values.get(*key).filter(|v| !v.is_empty()));
+
+    let mut fields = HashMap::new();
+    if let Some(title) = first(title) {
+        fields.insert(TITLE_METADATA_KEY.to_string(), title[0].clone());
+    }
+    if let Some(authors) = first(author) {
+        fields.insert(AUTHOR_METADATA_KEY.to_string(), authors.join(", "));
+    }
+    if let Some(tags) = first(tags) {
+        // Keywords come as one comma-separated value
+        let tags: Vec<&str> = tags.iter().flat_map(|t| t.split(',')).map(str::trim).filter(|t| !t.is_empty()).collect();
+        if !tags.is_empty() {
+            fields.insert(TAGS_METADATA_KEY.to_string(), tags.join(", "));
+        }
+    }
+    if let Some(date) = first(date) {
+        fields.insert(DATE_METADATA_KEY.to_string(), normalize_date(&date[0]));
+    }
+    fields
+}
+
+/// `YYYY-MM-DD` for the date formats seen in front matter and meta tags,
+/// the value itself otherwise
+pub fn normalize_date(value: &str) -> String {
+    let value = value.trim();
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(value) {
+        return date.date_naive().to_string();
+    }
+    for format in ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y"] {
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(value, format) {
+            return date.to_string();
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = chrono::NaiveDateTime::parse_from_str(value, format) {
+            return date.date().to_string();
+        }
+    }
+    value.to_string()
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_markdown() {
+        let text = "---\ntitle: \"Security Policy v3\"\nauthor: Jane Doe\ntags: [security, policy]\ndate: 2024-08-01T09:00:00Z\n---\n# Policy\n\nBody.\n";
+        let (fields, body) = split_markdown(text);
+        assert_eq!(fields[TITLE_METADATA_KEY], "Security Policy v3");
+        assert_eq!(fields[AUTHOR_METADATA_KEY], "Jane Doe");
+        assert_eq!(fields[TAGS_METADATA_KEY], "security, policy");
+        assert_eq!(fields[DATE_METADATA_KEY], "2024-08-01");
+        assert_eq!(body, "# Policy\n\nBody.\n");
+
+        let (fields, _) = split_markdown("---\ntags:\n  - a\n  - 'b'\n---\n");
+        assert_eq!(fields[TAGS_METADATA_KEY], "a, b");
+
+        // No or unclosed front matter leaves the text alone
+        assert_eq!(split_markdown("# Title\n---\n").1, "# Title\n---\n");
+        assert_eq!(split_markdown("---\ntitle: x\n").1, "---\ntitle: x\n");
+    }
+
+    #[test]
+    fn test_fields_reach_chunks() {
+        use crate::ingestion::IngestPipeline;
+        use crate::types::query::MetadataFilter;
+
+        let data = b"---\ntitle: Security Policy v3\ntags: [security]\ndate: 2024-08-01\n---\nAll laptops must use full-disk encryption and a screen lock.\n";
+        let (doc, chunks) = IngestPipeline::default().ingest("policy_final_v3_FINAL.md", data).unwrap();
+        assert_eq!(doc.title(), Some("Security Policy v3"));
+        assert!(!chunks[0].content.contains("title:"));
+        assert_eq!(chunks[0].source_name(), "Security Policy v3 (2024-08-01)");
+
+        let filter = |tags: &[&str], after: &str| MetadataFilter {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            after: Some(after.parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(filter(&["Security"], "2024-01-01").matches(&chunks[0]));
+        assert!(!filter(&["security", "hr"], "2024-01-01").matches(&chunks[0]));
+        assert!(!filter(&[], "2024-09-01").matches(&chunks[0]));
+    }
+
+    #[test]
+    fn test_html_fields() {
+        let html = scraper::Html::parse_document(
+            r#"<html><head><title>policy_final</title>
+            <meta property="og:title" content="Security Policy v3">
+            <meta name="author" content="Jane Doe">
+            <meta name="keywords" content="security, policy">
+            <meta property="article:published_time" content="2024-08-01T09:00:00+02:00">
+            </head><body>Text</body></html>"#,
+        );
+        let fields = html_fields(&html);
+        assert_eq!(fields[TITLE_METADATA_KEY], "Security Policy v3");
+        assert_eq!(fields[AUTHOR_METADATA_KEY], "Jane Doe");
+        assert_eq!(fields[TAGS_METADATA_KEY], "security, policy");
+        assert_eq!(fields[DATE_METADATA_KEY], "2024-08-01");
+    }
+}
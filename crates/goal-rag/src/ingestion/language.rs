@@ -0,0 +1,100 @@
+//! Document language detection
+//!
+//! Languages are identified by ISO 639-3 code ("eng", "deu"). Queries may
+//! name a language by ISO 639-1 code, ISO 639-3 code or English name.
+
+use whatlang::Lang;
+
+/// Bytes of text sampled for detection
+pub const DETECTION_SAMPLE_BYTES: usize = 16 * 1024;
+
+/// ISO 639-1 codes for languages whatlang detects
+const ISO_639_1: &[(&str, Lang)] = &[
+    ("ar", Lang::Ara),
+    ("bn", Lang::Ben),
+    ("cs", Lang::Ces),
+    ("da", Lang::Dan),
+    ("de", Lang::Deu),
+    ("el", Lang::Ell),
+    ("en", Lang::Eng),
+    ("es", Lang::Spa),
+    ("fi", Lang::Fin),
+    ("fr", Lang::Fra),
+    ("he", Lang::Heb),
+    ("hi", Lang::Hin),
+    ("hu", Lang::Hun),
+    ("id", Lang::Ind),
+    ("it", Lang::Ita),
+    ("ja", Lang::Jpn),
+    ("ko", Lang::Kor),
+    ("nl", Lang::Nld),
+    ("no", Lang::Nob),
+    ("pl", Lang::Pol),
+    ("pt", Lang::Por),
+    ("ro", Lang::Ron),
+    ("ru", Lang::Rus),
+    ("sv", Lang::Swe),
+    ("th", Lang::Tha),
+    ("tr", Lang::Tur),
+    ("uk", Lang::Ukr),
+    ("vi", Lang::Vie),
+    ("zh", Lang::Cmn),
+];
+
+/// Detect the language of a text, if detection is reliable
+pub fn detect(text: &str) -> Option<Lang> {
+    let mut end = text.len().min(DETECTION_SAMPLE_BYTES);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    whatlang::detect(&text[..end])
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang())
+}
+
+/// Resolve a language given as ISO 639-1/639-3 code or English name
+///
+/// Region subtags are ignored ("pt-BR" resolves to Portuguese).
+pub fn resolve(language: &str) -> Option<Lang> {
+    let language = language.trim().to_lowercase();
+    let primary = language.split(['-', '_']).next().unwrap_or(&language);
+
+    ISO_639_1
+        .iter()
+        .find(|(code, _)| *code == primary)
+        .map(|(_, lang)| *lang)
+        .or_else(|| Lang::from_code(primary))
+        .or_else(|| Lang::all().iter().copied().find(|lang| lang.eng_name().eq_ignore_ascii_case(&language)))
+}
+
+/// Name to use for a language in prompts
+///
+/// Unrecognized languages are passed through as given.
+pub fn prompt_name(language: &str) -> String {
+    resolve(language)
+        .map(|lang| lang.eng_name().to_string())
+        .unwrap_or_else(|| language.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let german = "Die Rückerstattung erfolgt innerhalb von vierzehn Tagen nach Eingang der Ware bei uns im Lager.";
+        assert_eq!(detect(german), Some(Lang::Deu));
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("de"), Some(Lang::Deu));
+        assert_eq!(resolve("pt-BR"), Some(Lang::Por));
+        assert_eq!(resolve("fra"), Some(Lang::Fra));
+        assert_eq!(resolve("Spanish"), Some(Lang::Spa));
+        assert_eq!(resolve("klingon"), None);
+        assert_eq!(prompt_name("klingon"), "klingon");
+    }
+}
@@ -0,0 +1,212 @@
+//! Walking server-local directory trees for bulk ingest
+//!
+//! Files are selected with include and exclude globs over their paths
+//! relative to the walked directory (`/`-separated). `*` and `?` stay within
+//! a path segment, `**` crosses segments, and `{a,b}` matches either
+//! alternative; a pattern without a `/` matches the file name at any depth.
+//! Top-level subdirectories are walked in parallel.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::error::{Error, Result};
+
+/// Include and exclude globs
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let compile = |patterns: &[String]| patterns.iter().map(|p| glob_regex(p)).collect::<Result<Vec<_>>>();
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Whether a file is selected (every file when there are no includes)
+    pub fn matches(&self, relative: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(relative))) && !self.excluded(relative)
+    }
+
+    /// Whether a file or directory is excluded
+    pub fn excluded(&self, relative: &str) -> bool {
+        self.exclude.iter().any(|re| re.is_match(relative))
+    }
+}
+
+/// Translate a glob into an anchored regex
+fn glob_regex(pattern: &str) -> Result<Regex> {
+    let pattern = pattern.trim().trim_start_matches("./").trim_start_matches('/');
+    // `dir/**` also matches `dir` itself, so excluded directories are pruned
+    let pattern = pattern.strip_suffix("/**").unwrap_or(pattern);
+    if pattern.is_empty() {
+        return Err(Error::Config("Empty glob pattern".to_string()));
+    }
+
+    let mut re = String::from("^");
+    if !pattern.contains('/') {
+        re.push_str("(?:.*/)?");
+    }
+    let mut chars = pattern.chars().peekable();
+    let mut in_group = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '{' if !in_group => {
+                in_group = true;
+                re.push_str("(?:");
+            }
+            '}' if in_group => {
+                in_group = false;
+                re.push(')');
+            }
+            ',' if in_group => re.push('|'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if in_group {
+        return Err(Error::Config(format!("Unclosed '{{' in glob pattern '{}'", pattern)));
+    }
+    // A matching directory matches everything below it
+    re.push_str("(?:/.*)?$");
+    Regex::new(&re).map_err(|e| Error::Config(format!("Invalid glob pattern '{}': {}", pattern, e)))
+}
+
+/// A file found under the walked directory
+#[derive(Debug, Clone)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    /// Path relative to the walked directory, `/`-separated
+    pub relative: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Files under `root` (or `root` itself when it is a file) selected by the
+/// filter, sorted by relative path
+///
+/// Hidden files and directories are skipped, and excluded directories are
+/// not descended into. Blocking: run it on a blocking thread.
+pub fn walk(root: &Path, filter: &PathFilter, follow_links: bool) -> Result<Vec<LocalFile>> {
+    let metadata = std::fs::metadata(root)?;
+    if metadata.is_file() {
+        let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        return Ok(vec![LocalFile {
+            path: root.to_path_buf(),
+            relative: name,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }]);
+    }
+
+    let mut files = Vec::new();
+    let mut subdirectories = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let metadata = if follow_links { std::fs::metadata(&path) } else { std::fs::symlink_metadata(&path) };
+        let Ok(metadata) = metadata else { continue };
+        if metadata.is_dir() {
+            if !filter.excluded(&name) {
+                subdirectories.push(path);
+            }
+        } else if metadata.is_file() && filter.matches(&name) {
+            files.push(LocalFile { path, relative: name, size: metadata.len(), modified: metadata.modified().ok() });
+        }
+    }
+
+    let nested: Vec<Vec<LocalFile>> =
+        subdirectories.par_iter().map(|directory| walk_subdirectory(root, directory, filter, follow_links)).collect();
+    files.extend(nested.into_iter().flatten());
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(files)
+}
+
+fn walk_subdirectory(root: &Path, directory: &Path, filter: &PathFilter, follow_links: bool) -> Vec<LocalFile> {
+    let relative = |path: &Path| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+    };
+
+    let mut files = Vec::new();
+    let walker = walkdir::WalkDir::new(directory).follow_links(follow_links).into_iter().filter_entry(|entry| {
+        let hidden = entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.');
+        !hidden && !(entry.file_type().is_dir() && filter.excluded(&relative(entry.path())))
+    });
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("Skipped unreadable entry under {}: {}", directory.display(), e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = relative(entry.path());
+        if !filter.matches(&relative) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else { continue };
+        files.push(LocalFile {
+            path: entry.into_path(),
+            relative,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        PathFilter::new(&strings(include), &strings(exclude)).unwrap()
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let docs = filter(&["*.{pdf,docx}", "notes/**/*.md"], &["**/drafts/**", "*~"]);
+        assert!(docs.matches("report.pdf"));
+        assert!(docs.matches("hr/2024/policy.docx"));
+        assert!(docs.matches("notes/a/b/todo.md"));
+        assert!(docs.matches("notes/todo.md"));
+        assert!(!docs.matches("readme.md"));
+        assert!(!docs.matches("hr/drafts/policy.docx"));
+        assert!(!docs.matches("report.pdf~"));
+        assert!(docs.excluded("hr/drafts"));
+
+        // Directory patterns match everything below them
+        let everything = filter(&[], &["build", "src/generated"]);
+        assert!(everything.matches("src/main.rs"));
+        assert!(!everything.matches("build/out.txt"));
+        assert!(!everything.matches("src/generated/api.rs"));
+
+        assert!(PathFilter::new(&["*.{pdf".to_string()], &[]).is_err());
+    }
+}
@@ -1,11 +1,30 @@
 //! Document ingestion pipeline with multi-format parsing
 
+pub mod antivirus;
+pub mod archive;
 mod chunker;
+mod code;
+pub mod email;
+pub mod encrypted;
 pub mod external_parser;
+pub mod figures;
+pub mod front_matter;
+pub mod language;
+pub mod local_tree;
+pub mod notebook;
 mod parser;
+pub mod plugins;
 mod processor;
+pub mod rst;
+pub mod sections;
+mod streaming;
+pub mod tables;
+mod tabular;
+pub mod thumbnails;
+pub mod transcription;
 
-pub use chunker::TextChunker;
+pub use chunker::{assign_parent_windows, TextChunker};
 pub use external_parser::{ExternalParser, ExternalParserConfig, ParsedExternalDocument, ParserAttempt, EscalationResult};
-pub use parser::{FileParser, PageContent, ParsedDocument};
+pub use parser::{FileParser, PageContent, ParsedDocument, Section};
 pub use processor::IngestPipeline;
+pub use streaming::{stream_content_hash, StreamFormat, StreamingChunker};
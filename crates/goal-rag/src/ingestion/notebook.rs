@@ -0,0 +1,280 @@
+//! Jupyter notebook (.ipynb) parsing and chunking
+//!
+//! Each cell becomes a page in percent format (`# %% In [5]`,
+//! `# %% [markdown]`) so the cell type and execution count survive in the
+//! stored text. Code cells are chunked as code in the kernel language,
+//! markdown cells as text, and every chunk is titled with its cell.
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::types::{Chunk, ChunkSource, Document, FileType};
+use super::chunker::{CodeChunker, TextChunker};
+use super::parser::{PageContent, ParsedDocument};
+
+/// Cell marker prefix (jupytext percent format)
+const CELL_MARKER: &str = "# %%";
+
+/// Parsed metadata key holding the kernel language
+const KERNEL_LANGUAGE_KEY: &str = "kernel_language";
+
+#[derive(Deserialize)]
+struct Notebook {
+    #[serde(default)]
+    cells: Vec<RawCell>,
+    #[serde(default)]
+    metadata: NotebookMetadata,
+}
+
+#[derive(Deserialize, Default)]
+struct NotebookMetadata {
+    #[serde(default)]
+    kernelspec: Option<KernelSpec>,
+    #[serde(default)]
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Deserialize)]
+struct KernelSpec {
+    #[serde(default)]
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawCell {
+    cell_type: String,
+    /// A string or a list of lines
+    #[serde(default)]
+    source: serde_json::Value,
+    #[serde(default)]
+    execution_count: Option<u32>,
+}
+
+/// Kind of notebook cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Code,
+    Markdown,
+    Raw,
+}
+
+/// A cell read back from its page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell<'a> {
+    pub kind: CellKind,
+    /// Execution count of a code cell (`None` if never run)
+    pub execution_count: Option<u32>,
+    pub source: &'a str,
+    /// Byte offset of `source` within the page
+    pub offset: usize,
+}
+
+impl Cell<'_> {
+    /// Cell title used as the chunk section title
+    pub fn title(&self, number: u32) -> String {
+        match (self.kind, self.execution_count) {
+            (CellKind::Code, Some(count)) => format!("Cell {} (In [{}])", number, count),
+            (CellKind::Code, None) => format!("Cell {} (code, not run)", number),
+            (CellKind::Markdown, _) => format!("Cell {} (markdown)", number),
+            (CellKind::Raw, _) => format!("Cell {} (raw)", number),
+        }
+    }
+}
+
+/// Parse a notebook into one page per non-empty cell
+pub fn parse(filename: &str, data: &[u8]) -> Result<ParsedDocument> {
+    let notebook: Notebook = serde_json::from_slice(data)
+        .map_err(|e| Error::file_parse(filename, format!("Invalid notebook JSON: {}", e)))?;
+
+    let mut content = String::new();
+    let mut pages = Vec::new();
+
+    for cell in &notebook.cells {
+        let source = match &cell.source {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+            _ => String::new(),
+        };
+        if source.trim().is_empty() {
+            continue;
+        }
+
+        let marker = match (cell.cell_type.as_str(), cell.execution_count) {
+            ("code", Some(count)) => format!("{} In [{}]", CELL_MARKER, count),
+            ("code", None) => format!("{} In [ ]", CELL_MARKER),
+            ("markdown", _) => format!("{} [markdown]", CELL_MARKER),
+            _ => format!("{} [raw]", CELL_MARKER),
+        };
+
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        let text = format!("{}\n{}", marker, source.trim_end());
+        pages.push(PageContent {
+            page_number: pages.len() as u32 + 1,
+            content: text.clone(),
+            char_offset: content.len(),
+        });
+        content.push_str(&text);
+    }
+
+    if pages.is_empty() {
+        return Err(Error::file_parse(filename, "Notebook has no non-empty cells"));
+    }
+
+    let language = notebook
+        .metadata
+        .kernelspec
+        .and_then(|k| k.language)
+        .or_else(|| notebook.metadata.language_info.and_then(|l| l.name))
+        .unwrap_or_else(|| "python".to_string());
+
+    let mut metadata = HashMap::new();
+    metadata.insert(KERNEL_LANGUAGE_KEY.to_string(), language.to_lowercase());
+
+    Ok(ParsedDocument {
+        file_type: FileType::Notebook,
+        content_hash: hex::encode(Sha256::digest(content.as_bytes())),
+        total_pages: Some(pages.len() as u32),
+        content,
+        pages,
+        metadata,
+        sections: Vec::new(),
+    })
+}
+
+/// Read the cell marker and source from a page
+pub fn parse_cell(page: &str) -> Cell<'_> {
+    let (first, rest) = page.split_once('\n').unwrap_or((page, ""));
+    let offset = page.len() - rest.len();
+    let cell = |kind, execution_count, source, offset| Cell { kind, execution_count, source, offset };
+
+    let Some(marker) = first.strip_prefix(CELL_MARKER).map(str::trim) else {
+        return cell(CellKind::Markdown, None, page, 0);
+    };
+
+    match marker {
+        "[markdown]" => cell(CellKind::Markdown, None, rest, offset),
+        "[raw]" => cell(CellKind::Raw, None, rest, offset),
+        _ => {
+            let count = marker
+                .strip_prefix("In [")
+                .and_then(|m| m.strip_suffix(']'))
+                .and_then(|n| n.trim().parse().ok());
+            cell(CellKind::Code, count, rest, offset)
+        }
+    }
+}
+
+/// Chunk a parsed notebook cell by cell
+///
+/// Code cells are chunked as code in the notebook language (line numbers
+/// within the cell), markdown and raw cells as text. The first chunk of each
+/// cell keeps its marker line so reindexing from stored chunks still sees the
+/// cell boundaries, and every chunk is titled with its cell.
+pub fn cell_chunks(
+    doc: &Document,
+    parsed: &ParsedDocument,
+    text_chunker: &TextChunker,
+    code_chunker: &CodeChunker,
+) -> Vec<Chunk> {
+    let language = parsed.metadata.get(KERNEL_LANGUAGE_KEY).map(String::as_str).unwrap_or("python");
+    let mut chunks = Vec::new();
+
+    for page in &parsed.pages {
+        let cell = parse_cell(&page.content);
+        let base = page.char_offset + cell.offset;
+
+        let mut cell_chunks = match cell.kind {
+            CellKind::Code => code_chunker.chunk_code(doc, cell.source, language),
+            CellKind::Markdown | CellKind::Raw => {
+                text_chunker.chunk_text_with_source(cell.source, doc, None, None, 0, 0)
+            }
+        };
+
+        // Short markdown cells fall under the text chunker's minimum size
+        if cell_chunks.is_empty() {
+            let source = ChunkSource::text(doc.filename.clone());
+            cell_chunks.push(Chunk::new(doc.id, cell.source.trim().to_string(), source, 0, cell.source.len(), 0));
+        }
+
+        let title = cell.title(page.page_number);
+        for (i, mut chunk) in cell_chunks.into_iter().enumerate() {
+            chunk.source.file_type = doc.file_type.clone();
+            chunk.source.internal_filename = doc.internal_filename.clone();
+            chunk.source.page_number = Some(page.page_number);
+            chunk.source.page_count = parsed.total_pages;
+            chunk.source.section_title = Some(title.clone());
+            chunk.source.code_context = (cell.kind == CellKind::Code).then(|| language.to_string());
+            chunk.char_start += base;
+            chunk.char_end += base;
+            if i == 0 && cell.offset > 0 {
+                chunk.content = format!("{}{}", &page.content[..cell.offset], chunk.content);
+                chunk.char_start = page.char_offset;
+            }
+            chunk.chunk_index = chunks.len() as u32;
+            chunks.push(chunk);
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+        "nbformat": 4,
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Load data\n", "Read the CSV export."]},
+            {"cell_type": "code", "execution_count": 3, "source": "import pandas as pd\ndf = pd.read_csv('sales.csv')", "outputs": []},
+            {"cell_type": "code", "execution_count": null, "source": [], "outputs": []},
+            {"cell_type": "code", "execution_count": null, "source": ["df.head()"], "outputs": []}
+        ]
+    }"##;
+
+    #[test]
+    fn test_parse_notebook() {
+        let parsed = parse("analysis.ipynb", NOTEBOOK.as_bytes()).unwrap();
+        assert_eq!(parsed.file_type, FileType::Notebook);
+        assert_eq!(parsed.total_pages, Some(3));
+        assert_eq!(parsed.pages[0].content, "# %% [markdown]\n# Load data\nRead the CSV export.");
+        assert!(parsed.pages[1].content.starts_with("# %% In [3]\nimport pandas"));
+        assert!(parsed.pages[2].content.starts_with("# %% In [ ]\n"));
+        assert_eq!(&parsed.content[parsed.pages[1].char_offset..][..11], "# %% In [3]");
+    }
+
+    #[test]
+    fn test_parse_cell_and_chunks() {
+        let cell = parse_cell("# %% In [3]\nimport pandas as pd");
+        assert_eq!(cell.kind, CellKind::Code);
+        assert_eq!(cell.execution_count, Some(3));
+        assert_eq!(cell.source, "import pandas as pd");
+        assert_eq!(cell.offset, 12);
+        assert_eq!(parse_cell("# %% In [ ]\nx").execution_count, None);
+        assert_eq!(parse_cell("# %% [markdown]\n# Title").kind, CellKind::Markdown);
+
+        let parsed = parse("analysis.ipynb", NOTEBOOK.as_bytes()).unwrap();
+        let doc = Document::new("analysis.ipynb".into(), FileType::Notebook, parsed.content_hash.clone(), 0);
+        let chunks = cell_chunks(&doc, &parsed, &TextChunker::new(512, 50), &CodeChunker::new(512, 50));
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].source.section_title.as_deref(), Some("Cell 1 (markdown)"));
+        assert_eq!(chunks[1].source.section_title.as_deref(), Some("Cell 2 (In [3])"));
+        assert_eq!(chunks[1].source.line_start, Some(1));
+        assert_eq!(chunks[2].source.section_title.as_deref(), Some("Cell 3 (code, not run)"));
+        assert!(chunks.iter().all(|c| c.source.file_type == FileType::Notebook));
+        assert!(chunks[1].content.starts_with("# %% In [3]\nimport pandas"));
+        assert_eq!(&parsed.content[chunks[1].char_start..chunks[1].char_start + 11], "# %% In [3]");
+    }
+}
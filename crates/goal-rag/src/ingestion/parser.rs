@@ -121,6 +121,8 @@ pub struct ParsedDocument {
     pub pages: Vec<PageContent>,
     /// Document metadata
     pub metadata: HashMap<String, String>,
+    /// Section headings found in the content (PDF outline or numbered headings)
+    pub sections: Vec<Section>,
 }
 
 /// Content from a single page
@@ -134,6 +136,17 @@ pub struct PageContent {
     pub char_offset: usize,
 }
 
+/// A section heading in the parsed content
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// Byte offset of the heading in the content
+    pub offset: usize,
+    /// Nesting level (1 for top-level sections)
+    pub level: u32,
+    /// Heading text
+    pub title: String,
+}
+
 /// Multi-format file parser
 pub struct FileParser;
 
@@ -228,6 +241,16 @@ impl FileParser {
                     "image - Requires tesseract OCR for text extraction".to_string()
                 ))
             }
+            FileType::Audio | FileType::Video => {
+                // Recordings are transcribed, not parsed
+                Err(Error::UnsupportedFileType(format!(
+                    "{} - Requires transcription (enable [transcription])",
+                    extension
+                )))
+            }
+            FileType::Email | FileType::Mbox => super::email::parse(filename, data),
+            FileType::Notebook => super::notebook::parse(filename, data),
+            FileType::Rst => super::rst::parse(data),
             FileType::Code(ref lang) => Self::parse_code(data, lang.clone()),
             FileType::Unknown => Err(Error::UnsupportedFileType(format!("{} - Unknown file type", extension))),
         }
@@ -261,11 +284,10 @@ impl FileParser {
             char_offset: 0,
         }];
 
-        // Try to count pages using lopdf
-        let total_pages = match lopdf::Document::load_mem(data) {
-            Ok(doc) => Some(doc.get_pages().len() as u32),
-            Err(_) => Some(1),
-        };
+        // Try to count pages using lopdf, and read the outline for section titles
+        let pdf = lopdf::Document::load_mem(data).ok();
+        let total_pages = Some(pdf.as_ref().map_or(1, |doc| doc.get_pages().len() as u32));
+        let sections = super::sections::pdf_sections(pdf.as_ref(), &content);
 
         Ok(ParsedDocument {
             file_type: FileType::Pdf,
@@ -274,6 +296,7 @@ impl FileParser {
             total_pages,
             pages,
             metadata: HashMap::new(),
+            sections,
         })
     }
 
@@ -419,6 +442,7 @@ impl FileParser {
             total_pages: Some(page_number),
             pages,
             metadata: HashMap::new(),
+            sections: Vec::new(),
         })
     }
 
@@ -491,6 +515,7 @@ impl FileParser {
             total_pages,
             pages,
             metadata: HashMap::new(),
+            sections: Vec::new(),
         })
     }
 
@@ -552,9 +577,14 @@ impl FileParser {
             .join("\n")
     }
 
-    /// Parse plain text or markdown
+    /// Parse plain text or markdown (front matter becomes metadata)
     fn parse_text(data: &[u8], file_type: FileType) -> Result<ParsedDocument> {
-        let content = String::from_utf8_lossy(data).to_string();
+        let text = String::from_utf8_lossy(data);
+        let (metadata, content) = match file_type {
+            FileType::Markdown => super::front_matter::split_markdown(&text),
+            _ => (HashMap::new(), text.as_ref()),
+        };
+        let content = content.to_string();
 
         let pages = vec![PageContent {
             page_number: 1,
@@ -564,11 +594,13 @@ impl FileParser {
 
         Ok(ParsedDocument {
             file_type,
-            content_hash: hash_content(&content),
+            // Over the whole file, so edited front matter counts as a change
+            content_hash: hash_content(&text),
             content,
             total_pages: None,
             pages,
-            metadata: HashMap::new(),
+            metadata,
+            sections: Vec::new(),
         })
     }
 
@@ -605,7 +637,8 @@ impl FileParser {
             content,
             total_pages: None,
             pages,
-            metadata: HashMap::new(),
+            metadata: super::front_matter::html_fields(&document),
+            sections: Vec::new(),
         })
     }
 
@@ -639,6 +672,7 @@ impl FileParser {
             total_pages: None,
             pages,
             metadata: HashMap::new(),
+            sections: Vec::new(),
         })
     }
 
@@ -697,6 +731,7 @@ impl FileParser {
             total_pages: Some(page_number),
             pages,
             metadata: HashMap::new(),
+            sections: Vec::new(),
         })
     }
 
@@ -717,6 +752,7 @@ impl FileParser {
             total_pages: None,
             pages,
             metadata: HashMap::new(),
+            sections: Vec::new(),
         })
     }
 }
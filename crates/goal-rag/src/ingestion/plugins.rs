@@ -0,0 +1,268 @@
+//! Operator-supplied parsers for formats without a built-in parser
+//!
+//! Plugins are registered under `[[external_parser.plugins]]` for file
+//! extensions or MIME types (guessed from the filename) and run first in the
+//! escalation chain for the files they match, with their attempts recorded
+//! like the built-in parsers'. A plugin is either:
+//!
+//! - an external command, given the file on stdin and writing the extracted
+//!   text to stdout, with the filename in `RAG_PLUGIN_FILENAME`; a non-zero
+//!   exit fails the attempt with its stderr;
+//! - a WebAssembly module (`wasm-plugins` feature) without imports that
+//!   exports `memory`, `alloc(len: i32) -> i32` and
+//!   `parse(ptr: i32, len: i32) -> i64`; the file is copied to the buffer
+//!   `alloc` returns and `parse` returns the UTF-8 text's pointer and length
+//!   packed as `ptr << 32 | len`.
+//!
+//! Both are stopped after `timeout_secs`.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::error::{Error, Result};
+
+/// A parser plugin in the configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParserPluginConfig {
+    /// Name recorded in parser attempts (as `plugin:<name>`)
+    pub name: String,
+    /// File extensions handled, without the dot
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// MIME types handled (`type/*` matches a whole type)
+    #[serde(default)]
+    pub mime_types: Vec<String>,
+    /// Program and arguments to run
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// WebAssembly module to run instead of a command
+    #[serde(default)]
+    pub wasm: Option<std::path::PathBuf>,
+    /// Time limit per file in seconds (default: 120)
+    #[serde(default = "default_plugin_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_plugin_timeout_secs() -> u64 { 120 }
+
+/// A plugin ready to run
+pub struct ParserPlugin {
+    config: ParserPluginConfig,
+    runner: Runner,
+}
+
+enum Runner {
+    Command,
+    #[cfg(feature = "wasm-plugins")]
+    Wasm(wasm::WasmParser),
+}
+
+impl ParserPlugin {
+    /// Set up a plugin, `None` (with a warning) if it can't run
+    pub fn new(config: ParserPluginConfig) -> Option<Self> {
+        let runner = match (&config.wasm, config.command.is_empty()) {
+            (Some(path), _) => load_wasm(&config.name, path)?,
+            (None, false) => Runner::Command,
+            (None, true) => {
+                tracing::warn!("Parser plugin '{}' disabled: neither command nor wasm is set", config.name);
+                return None;
+            }
+        };
+        Some(Self { config, runner })
+    }
+
+    /// Name in parser attempts
+    pub fn attempt_name(&self) -> String {
+        format!("plugin:{}", self.config.name)
+    }
+
+    /// Whether the plugin handles a file, by extension or guessed MIME type
+    pub fn matches(&self, filename: &str) -> bool {
+        let ext = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+        if self.config.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext)) {
+            return true;
+        }
+        mime_guess::from_path(filename).iter().any(|mime| {
+            self.config.mime_types.iter().any(|pattern| match pattern.strip_suffix("/*") {
+                Some(type_) => mime.type_().as_str().eq_ignore_ascii_case(type_),
+                None => mime.essence_str().eq_ignore_ascii_case(pattern),
+            })
+        })
+    }
+
+    /// Extract the text of a file
+    pub async fn parse(&self, filename: &str, data: &[u8]) -> Result<String> {
+        let limit = Duration::from_secs(self.config.timeout_secs.max(1));
+        match &self.runner {
+            Runner::Command => self.run_command(filename, data, limit).await,
+            #[cfg(feature = "wasm-plugins")]
+            Runner::Wasm(parser) => {
+                let (parser, data) = (parser.clone(), data.to_vec());
+                tokio::task::spawn_blocking(move || parser.parse(&data, limit))
+                    .await
+                    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))?
+                    .map_err(|e| Error::file_parse(filename, format!("{}: {}", self.attempt_name(), e)))
+            }
+        }
+    }
+
+    async fn run_command(&self, filename: &str, data: &[u8], limit: Duration) -> Result<String> {
+        let (program, args) = self.config.command.split_first().expect("plugin commands are not empty");
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .env("RAG_PLUGIN_FILENAME", filename)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::ParserUnavailable(format!("{}: failed to run {}: {}", self.attempt_name(), program, e)))?;
+
+        // Written concurrently so a plugin streaming output doesn't block on a full pipe
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = data.to_vec();
+        let writer = tokio::spawn(async move {
+            // A plugin may exit without reading all of its input
+            let _ = stdin.write_all(&input).await;
+        });
+
+        let output = tokio::time::timeout(limit, child.wait_with_output())
+            .await
+            .map_err(|_| Error::file_parse(filename, format!("{} timed out after {}s", self.attempt_name(), limit.as_secs())))?
+            .map_err(|e| Error::file_parse(filename, format!("{}: {}", self.attempt_name(), e)))?;
+        writer.abort();
+
+        if !output.status.success() {
+            return Err(Error::file_parse(
+                filename,
+                format!(
+                    "{} exited with {}: {}",
+                    self.attempt_name(),
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+fn load_wasm(name: &str, path: &std::path::Path) -> Option<Runner> {
+    match wasm::WasmParser::load(path) {
+        Ok(parser) => Some(Runner::Wasm(parser)),
+        Err(e) => {
+            tracing::warn!("Parser plugin '{}' disabled: {}", name, e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+fn load_wasm(name: &str, path: &std::path::Path) -> Option<Runner> {
+    tracing::warn!("Parser plugin '{}' disabled: {} needs the wasm-plugins feature", name, path.display());
+    None
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod wasm {
+    use std::path::Path;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    use wasmtime::{Config, Engine, Instance, Module, Store};
+
+    /// A compiled module; each file runs in a fresh instance
+    #[derive(Clone)]
+    pub struct WasmParser {
+        engine: Engine,
+        module: Module,
+    }
+
+    impl WasmParser {
+        pub fn load(path: &Path) -> anyhow::Result<Self> {
+            let mut config = Config::new();
+            config.epoch_interruption(true);
+            let engine = Engine::new(&config)?;
+            let module = Module::from_file(&engine, path)?;
+            Ok(Self { engine, module })
+        }
+
+        pub fn parse(&self, data: &[u8], limit: Duration) -> anyhow::Result<String> {
+            let mut store = Store::new(&self.engine, ());
+            store.set_epoch_deadline(1);
+            // Interrupts the module once the time limit passes (along with
+            // other files the plugin is parsing at that moment)
+            let engine = self.engine.clone();
+            let (done, finished) = std::sync::mpsc::channel::<()>();
+            std::thread::spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(limit) {
+                    engine.increment_epoch();
+                }
+            });
+
+            let instance = Instance::new(&mut store, &self.module, &[])?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow::anyhow!("module exports no memory"))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let parse = instance.get_typed_func::<(i32, i32), i64>(&mut store, "parse")?;
+
+            let len = i32::try_from(data.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, data)?;
+            let packed = parse.call(&mut store, (ptr, len))? as u64;
+            let _ = done.send(());
+
+            let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            let text = memory
+                .data(&store)
+                .get(out_ptr..out_ptr + out_len)
+                .ok_or_else(|| anyhow::anyhow!("output is outside the module's memory"))?;
+            Ok(String::from_utf8_lossy(text).into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(extensions: &[&str], mime_types: &[&str]) -> ParserPlugin {
+        ParserPlugin::new(ParserPluginConfig {
+            name: "acme".to_string(),
+            extensions: extensions.iter().map(|s| s.to_string()).collect(),
+            mime_types: mime_types.iter().map(|s| s.to_string()).collect(),
+            command: vec!["cat".to_string()],
+            wasm: None,
+            timeout_secs: 10,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_matches_extension_and_mime() {
+        assert!(plugin(&["acme"], &[]).matches("reports/q3.ACME"));
+        assert!(!plugin(&["acme"], &[]).matches("q3.pdf"));
+        assert!(plugin(&[], &["application/pdf"]).matches("q3.pdf"));
+        assert!(plugin(&[], &["image/*"]).matches("scan.png"));
+        assert!(!plugin(&[], &["image/*"]).matches("notes.txt"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_plugin() {
+        let text = plugin(&["acme"], &[]).parse("a.acme", b"extracted text").await.unwrap();
+        assert_eq!(text, "extracted text");
+
+        let failing = ParserPlugin::new(ParserPluginConfig {
+            command: vec!["false".to_string()],
+            ..plugin(&[], &[]).config
+        })
+        .unwrap();
+        assert!(failing.parse("a.acme", b"").await.is_err());
+    }
+}
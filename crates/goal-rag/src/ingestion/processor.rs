@@ -1,10 +1,17 @@
 //! Ingestion pipeline orchestration
 
+use std::sync::Arc;
+
+use crate::config::{ChunkSizeUnit, ContextConfig};
 use crate::error::Result;
+use crate::generation::packing::TokenCounter;
+use crate::providers::embedding::EmbeddingProvider;
 use crate::types::{Chunk, Document, FileType};
+use crate::types::document::{COLLECTION_METADATA_KEY, SOURCE_METADATA_KEYS};
 
-use super::chunker::{CodeChunker, TextChunker};
+use super::chunker::{assign_parent_windows, CodeChunker, TextChunker};
 use super::parser::{FileParser, ParsedDocument};
+use super::{email, notebook, sections, tables, tabular, transcription};
 
 /// Main ingestion pipeline
 pub struct IngestPipeline {
@@ -12,6 +19,12 @@ pub struct IngestPipeline {
     chunker: TextChunker,
     /// Code chunker
     code_chunker: CodeChunker,
+    /// Parent window size for small-to-big retrieval
+    parent_window: Option<usize>,
+    /// Maximum size of a table or transcript chunk
+    chunk_size: usize,
+    /// Add sentence descriptions of spreadsheet rows
+    row_descriptions: bool,
 }
 
 impl IngestPipeline {
@@ -20,7 +33,50 @@ impl IngestPipeline {
         Self {
             chunker: TextChunker::new(chunk_size, chunk_overlap),
             code_chunker: CodeChunker::new(chunk_size, chunk_overlap),
+            parent_window: None,
+            chunk_size,
+            row_descriptions: false,
+        }
+    }
+
+    /// Group chunks into parent windows of this many characters
+    pub fn with_parent_window(mut self, parent_window: Option<usize>) -> Self {
+        self.parent_window = parent_window.filter(|&w| w > 0);
+        self
+    }
+
+    /// Size source code chunks in tokens of the context tokenizer
+    pub fn with_code_tokens(mut self, max_tokens: usize, context: &ContextConfig) -> Self {
+        let counter = TokenCounter::new(context.tokenizer, context.chars_per_token);
+        self.code_chunker = self.code_chunker.with_tokens(max_tokens, counter);
+        self
+    }
+
+    /// Measure text chunk size and overlap in `unit`
+    ///
+    /// Tokens are counted with the embedding model's tokenizer, or with the
+    /// context tokenizer when the model's isn't available locally. Tables and
+    /// transcripts are still split by characters, at `chars_per_token`.
+    pub fn with_size_unit(
+        mut self,
+        unit: ChunkSizeUnit,
+        embedder: Arc<dyn EmbeddingProvider>,
+        context: &ContextConfig,
+    ) -> Self {
+        if unit == ChunkSizeUnit::Tokens {
+            let counter = TokenCounter::new(context.tokenizer, context.chars_per_token);
+            self.chunker = self.chunker.with_tokens(Arc::new(move |text: &str| {
+                embedder.count_tokens(text).unwrap_or_else(|| counter.count(text))
+            }));
+            self.chunk_size = (self.chunk_size as f32 * context.chars_per_token) as usize;
         }
+        self
+    }
+
+    /// Describe CSV and spreadsheet rows in sentences, in chunks of their own
+    pub fn with_row_descriptions(mut self, row_descriptions: bool) -> Self {
+        self.row_descriptions = row_descriptions;
+        self
     }
 
     /// Parse a file
@@ -30,13 +86,66 @@ impl IngestPipeline {
 
     /// Create chunks from a parsed document
     pub fn create_chunks(&self, doc: &Document, parsed: &ParsedDocument) -> Result<Vec<Chunk>> {
-        let chunks = match &doc.file_type {
+        let mut chunks = match &doc.file_type {
             FileType::Code(language) => {
                 self.code_chunker.chunk_code(doc, &parsed.content, language)
             }
+            FileType::Audio | FileType::Video => {
+                transcription::transcript_chunks(doc, &parsed.content, self.chunk_size)
+            }
+            FileType::Notebook => notebook::cell_chunks(doc, parsed, &self.chunker, &self.code_chunker),
+            file_type if tabular::is_tabular(file_type) => {
+                tabular::row_chunks(doc, parsed, self.chunk_size, self.row_descriptions)
+            }
             _ => self.chunker.chunk_document(doc, parsed),
         };
 
+        if let Some(window) = self.parent_window {
+            assign_parent_windows(&mut chunks, window);
+        }
+
+        // Tables also get dedicated chunks, kept intact as Markdown
+        if !matches!(doc.file_type, FileType::Code(_) | FileType::Audio | FileType::Video | FileType::Notebook)
+            && !tabular::is_tabular(&doc.file_type)
+        {
+            let table_chunks = tables::table_chunks(doc, parsed, self.chunk_size, chunks.len() as u32);
+            if !table_chunks.is_empty() {
+                tracing::debug!("Extracted {} table chunks from {}", table_chunks.len(), doc.filename);
+            }
+            chunks.extend(table_chunks);
+        }
+
+        // Email chunks carry their message's sender, date and subject for filtering
+        if matches!(doc.file_type, FileType::Email | FileType::Mbox) {
+            email::tag_chunks(&mut chunks, parsed);
+        }
+
+        // PDF chunks are titled by the section they start in; text rebuilt for
+        // reindexing has no outline, so fall back to numbered headings
+        if doc.file_type == FileType::Pdf {
+            if parsed.sections.is_empty() {
+                sections::assign(&mut chunks, &sections::detect_headings(&parsed.content));
+            } else {
+                sections::assign(&mut chunks, &parsed.sections);
+            }
+        }
+
+        // Chunks inherit the document's collection so FTS uses the right analyzer
+        if let Some(collection) = doc.metadata.get(COLLECTION_METADATA_KEY) {
+            for chunk in &mut chunks {
+                chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), collection.clone());
+            }
+        }
+
+        // and its title, author, tags and date for filters and citations
+        for key in SOURCE_METADATA_KEYS {
+            if let Some(value) = doc.metadata.get(key) {
+                for chunk in &mut chunks {
+                    chunk.metadata.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+
         Ok(chunks)
     }
 
@@ -51,6 +160,8 @@ impl IngestPipeline {
             data.len() as u64,
         );
         doc.total_pages = parsed.total_pages;
+        doc.detect_language(&parsed.content);
+        doc.set_source_metadata(&parsed.metadata);
 
         let chunks = self.create_chunks(&doc, &parsed)?;
         doc.total_chunks = chunks.len() as u32;
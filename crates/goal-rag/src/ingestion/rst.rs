@@ -0,0 +1,282 @@
+//! reStructuredText (.rst) parsing
+//!
+//! Markup is reduced to plain text: section adornments, comments, link
+//! targets and directive options are dropped, admonitions become labelled
+//! paragraphs and inline roles keep only their text. Section titles are
+//! kept on their own lines so chunks stay readable.
+
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::error::Result;
+use crate::types::FileType;
+use super::parser::{PageContent, ParsedDocument};
+
+/// Directives whose body is prose, shown with a label
+const ADMONITIONS: &[&str] = &[
+    "note", "warning", "tip", "important", "caution", "danger", "attention", "hint", "error",
+    "seealso", "deprecated", "versionadded", "versionchanged",
+];
+
+/// Directives whose body is not useful as text
+const SKIPPED_DIRECTIVES: &[&str] = &[
+    "image", "figure", "toctree", "include", "raw", "meta", "contents", "sectnum", "only",
+    "highlight", "default-role", "role", "automodule", "autoclass", "autofunction",
+];
+
+/// Parse a reStructuredText document
+pub fn parse(data: &[u8]) -> Result<ParsedDocument> {
+    let content = to_text(&String::from_utf8_lossy(data));
+
+    let pages = vec![PageContent {
+        page_number: 1,
+        content: content.clone(),
+        char_offset: 0,
+    }];
+
+    Ok(ParsedDocument {
+        file_type: FileType::Rst,
+        content_hash: hex::encode(Sha256::digest(content.as_bytes())),
+        content,
+        total_pages: None,
+        pages,
+        metadata: HashMap::new(),
+        sections: Vec::new(),
+    })
+}
+
+/// Convert reStructuredText to plain text
+pub fn to_text(source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out: Vec<String> = Vec::new();
+    // Indentation of a skipped block (comment, skipped directive) being dropped
+    let mut skip_indent: Option<usize> = None;
+    // Directive options (`:alt: ...`) directly follow the directive line
+    let mut in_options = false;
+
+    for line in &lines {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+
+        if let Some(block) = skip_indent {
+            if trimmed.is_empty() || indent > block {
+                continue;
+            }
+            skip_indent = None;
+        }
+        if in_options {
+            if is_field(trimmed) {
+                continue;
+            }
+            in_options = false;
+        }
+
+        if is_adornment(trimmed) {
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("..").filter(|r| r.is_empty() || r.starts_with(' ')) {
+            let rest = rest.trim();
+            match directive(rest) {
+                Some((name, argument)) => {
+                    in_options = true;
+                    if ADMONITIONS.contains(&name.as_str()) {
+                        let label = admonition_label(&name);
+                        out.push(if argument.is_empty() {
+                            format!("{}:", label)
+                        } else {
+                            format!("{}: {}", label, inline(argument))
+                        });
+                    } else if SKIPPED_DIRECTIVES.contains(&name.as_str()) {
+                        skip_indent = Some(indent);
+                    } else if !argument.is_empty() && !matches!(name.as_str(), "code-block" | "code" | "sourcecode") {
+                        out.push(inline(argument));
+                    }
+                }
+                // Comments, hyperlink targets, footnotes and substitution definitions
+                None => {
+                    if rest.starts_with('[') {
+                        if let Some((_, text)) = rest.split_once(']') {
+                            out.push(inline(text.trim()));
+                        }
+                    } else {
+                        skip_indent = Some(indent);
+                    }
+                }
+            }
+            continue;
+        }
+
+        let text = if let Some(stripped) = trimmed.strip_suffix("::") {
+            // "Example::" introduces a literal block and reads as "Example:"
+            if stripped.is_empty() {
+                continue;
+            }
+            format!("{}:", stripped.trim_end())
+        } else {
+            trimmed.to_string()
+        };
+
+        out.push(if text.is_empty() { text } else { inline(&text) });
+    }
+
+    collapse_blank_lines(&out)
+}
+
+/// A line of one punctuation character repeated (section over/underline, transition)
+fn is_adornment(line: &str) -> bool {
+    let mut chars = line.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    line.len() >= 3 && first.is_ascii_punctuation() && chars.all(|c| c == first)
+}
+
+/// A field list line such as `:alt: Diagram` or `:linenos:`
+fn is_field(line: &str) -> bool {
+    line.strip_prefix(':')
+        .and_then(|rest| rest.split_once(':'))
+        .is_some_and(|(name, _)| !name.is_empty() && !name.contains(' '))
+}
+
+/// Directive name and argument from the text after `..`
+fn directive(rest: &str) -> Option<(String, &str)> {
+    let (name, argument) = rest.split_once("::")?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) || name.starts_with('|') {
+        return None;
+    }
+    // Domain directives (`py:function`) are named by their last part
+    let name = name.rsplit(':').next().unwrap_or(name).to_lowercase();
+    Some((name, argument.trim()))
+}
+
+fn admonition_label(name: &str) -> String {
+    match name {
+        "seealso" => "See also".to_string(),
+        "versionadded" => "New in version".to_string(),
+        "versionchanged" => "Changed in version".to_string(),
+        _ => {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Strip inline markup, keeping the text
+fn inline(text: &str) -> String {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            // :role:`text <target>` and :role:`text`
+            (r":[\w:.+-]+:`([^`<]*?)\s*<[^`>]*>`", "$1"),
+            (r":[\w:.+-]+:`~?([^`]*)`", "$1"),
+            // `text <url>`_ and `text`_
+            (r"`([^`<]*?)\s*<[^`>]*>`__?", "$1"),
+            (r"`([^`]+)`__?", "$1"),
+            // ``literal``, **strong**, *emphasis*, `interpreted`
+            (r"``([^`]+)``", "$1"),
+            (r"\*\*([^*]+)\*\*", "$1"),
+            (r"\*([^*\s][^*]*)\*", "$1"),
+            (r"`([^`]+)`", "$1"),
+            // Footnote and citation references, substitutions
+            (r"\s?\[(?:#\w*|\*|\d+|[A-Za-z][\w.-]*)\]_", ""),
+            (r"\|([^|\s][^|]*)\|_?", "$1"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+        .collect()
+    });
+
+    let mut text = text.to_string();
+    for (regex, replacement) in patterns {
+        text = regex.replace_all(&text, *replacement).into_owned();
+    }
+    text
+}
+
+fn collapse_blank_lines(lines: &[String]) -> String {
+    let mut text = String::new();
+    let mut blank = false;
+    for line in lines {
+        if line.trim().is_empty() {
+            blank = !text.is_empty();
+            continue;
+        }
+        if blank {
+            text.push('\n');
+            blank = false;
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rst_to_text() {
+        let source = "\
+=========
+Deploying
+=========
+
+.. _deploy:
+
+Run the **release** script with ``make deploy``. See :ref:`the setup guide <setup>`
+and `the docs <https://example.com>`_.
+
+.. note:: Deploys are frozen on Fridays.
+
+.. image:: diagram.png
+   :alt: Deploy pipeline
+
+.. code-block:: bash
+   :linenos:
+
+   make deploy ENV=prod
+
+Example::
+
+    ./deploy.sh
+
+.. This comment is dropped
+   entirely.
+
+Rollback
+--------
+Use :func:`rollback` [#]_.
+";
+        let text = to_text(source);
+        assert_eq!(
+            text,
+            "Deploying\n\n\
+            Run the release script with make deploy. See the setup guide\n\
+            and the docs.\n\n\
+            Note: Deploys are frozen on Fridays.\n\n\
+            make deploy ENV=prod\n\n\
+            Example:\n\n\
+            ./deploy.sh\n\n\
+            Rollback\n\
+            Use rollback."
+        );
+    }
+
+    #[test]
+    fn test_adornment_and_directive() {
+        assert!(is_adornment("-----"));
+        assert!(!is_adornment("--"));
+        assert!(!is_adornment("-=-=-"));
+        assert_eq!(directive("py:function:: connect(host)"), Some(("function".to_string(), "connect(host)")));
+        assert_eq!(directive("_deploy: https://example.com"), None);
+        assert_eq!(directive("|name| image:: logo.png"), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Section titles for PDF chunks
+//!
+//! PDFs are extracted as plain text, so their structure has to be recovered:
+//! the outline (bookmarks) gives the section titles when the document has
+//! one, and numbered heading lines such as "4.2 Termination Clauses" are
+//! used otherwise. Titles are located in the extracted text and every chunk
+//! gets the section it starts in as `section_title`, with its parent sections
+//! in `heading_hierarchy`.
+
+use std::collections::HashSet;
+
+use lopdf::{Document, Object, ObjectId};
+
+use crate::types::Chunk;
+use super::parser::Section;
+
+/// Headings longer than this many words are taken for body text
+const MAX_HEADING_WORDS: usize = 12;
+
+/// Sections of a PDF, from its outline or else from numbered headings
+pub fn pdf_sections(doc: Option<&Document>, content: &str) -> Vec<Section> {
+    let located = doc.map(|doc| locate(content, &outline(doc))).unwrap_or_default();
+    if located.is_empty() {
+        detect_headings(content)
+    } else {
+        located
+    }
+}
+
+/// Outline entries as (level, title) in document order
+pub fn outline(doc: &Document) -> Vec<(u32, String)> {
+    let mut entries = Vec::new();
+    let first = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| doc.dereference(catalog.get(b"Outlines").ok()?).ok())
+        .and_then(|(_, outlines)| outlines.as_dict().ok()?.get(b"First").ok());
+    if let Some(first) = first {
+        walk_outline(doc, first, 1, &mut HashSet::new(), &mut entries);
+    }
+    entries
+}
+
+fn walk_outline(doc: &Document, first: &Object, level: u32, seen: &mut HashSet<ObjectId>, entries: &mut Vec<(u32, String)>) {
+    let mut next = Some(first);
+    while let Some((id, item)) = next.and_then(|object| doc.dereference(object).ok()) {
+        // Broken outlines can link back to earlier items
+        let (Ok(item), true) = (item.as_dict(), id.map_or(true, |id| seen.insert(id))) else {
+            return;
+        };
+        if let Some(title) = item.get(b"Title").ok().and_then(|t| doc.dereference(t).ok()).and_then(|(_, t)| t.as_str().ok()) {
+            let title = decode_pdf_string(title);
+            let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !title.is_empty() {
+                entries.push((level, title));
+            }
+        }
+        if let Ok(child) = item.get(b"First") {
+            walk_outline(doc, child, level + 1, seen, entries);
+        }
+        next = item.get(b"Next").ok();
+    }
+}
+
+/// PDF text strings are UTF-16BE with a byte order mark, or PDFDocEncoding
+/// (read as Latin-1)
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Find outline titles in the text, in order, at the start of a line
+///
+/// Table-of-contents lines (dot leaders or a trailing page number) are
+/// skipped so a title resolves to its heading, not its TOC entry. Entries
+/// that can't be found are dropped.
+pub fn locate(content: &str, entries: &[(u32, String)]) -> Vec<Section> {
+    let lines: Vec<(usize, String)> = line_offsets(content)
+        .map(|(offset, line)| (offset, normalize(line)))
+        .collect();
+
+    let mut sections = Vec::new();
+    let mut from = 0;
+    for (level, title) in entries {
+        let wanted = normalize(title);
+        let found = lines[from..]
+            .iter()
+            .position(|(_, line)| is_heading_line(line, &wanted));
+        if let Some(index) = found {
+            sections.push(Section { offset: lines[from + index].0, level: *level, title: title.clone() });
+            from += index + 1;
+        }
+    }
+    sections
+}
+
+/// Numbered heading lines such as "4.2 Termination Clauses"
+///
+/// The level is the number of components in the section number.
+pub fn detect_headings(content: &str) -> Vec<Section> {
+    line_offsets(content)
+        .filter_map(|(offset, line)| {
+            let line = line.trim();
+            let (number, title) = line.split_once(char::is_whitespace)?;
+            let number = number.trim_end_matches('.');
+            let parts: Vec<&str> = number.split('.').collect();
+            if parts.len() > 4 || !parts.iter().all(|p| !p.is_empty() && p.len() <= 3 && p.bytes().all(|b| b.is_ascii_digit())) {
+                return None;
+            }
+
+            let title = title.trim();
+            let words = title.split_whitespace().count();
+            if words == 0
+                || words > MAX_HEADING_WORDS
+                || !title.starts_with(|c: char| c.is_uppercase())
+                || title.ends_with(['.', ',', ';', ':'])
+                || is_toc_line(title)
+            {
+                return None;
+            }
+
+            Some(Section { offset, level: parts.len() as u32, title: line.to_string() })
+        })
+        .collect()
+}
+
+/// Set each chunk's section title and hierarchy from the section it starts in
+///
+/// Chunks that already have a section title are left alone.
+pub fn assign(chunks: &mut [Chunk], sections: &[Section]) {
+    if sections.is_empty() {
+        return;
+    }
+
+    for chunk in chunks.iter_mut().filter(|c| c.source.section_title.is_none()) {
+        // The section the chunk starts in, or the first one starting inside it
+        let index = match sections.iter().rposition(|s| s.offset <= chunk.char_start) {
+            Some(index) => index,
+            None if sections[0].offset < chunk.char_end => 0,
+            None => continue,
+        };
+
+        let mut hierarchy = vec![sections[index].title.clone()];
+        let mut level = sections[index].level;
+        for section in sections[..index].iter().rev() {
+            if section.level < level {
+                hierarchy.push(section.title.clone());
+                level = section.level;
+            }
+        }
+        hierarchy.reverse();
+
+        chunk.source.section_title = Some(sections[index].title.clone());
+        chunk.source.heading_hierarchy = hierarchy;
+    }
+}
+
+fn line_offsets(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.trim_end()))
+    })
+}
+
+/// Whether a line starts with a title as a whole phrase ("4 Term" but not
+/// "4 Terminology") and isn't its table-of-contents entry ("4 Term ..... 3")
+fn is_heading_line(line: &str, title: &str) -> bool {
+    line.strip_prefix(title).is_some_and(|rest| {
+        let page_number = !rest.trim().is_empty() && rest.trim().bytes().all(|b| b.is_ascii_digit());
+        !rest.starts_with(char::is_alphanumeric) && !rest.contains("..") && !page_number
+    })
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn is_toc_line(line: &str) -> bool {
+    line.contains("..") || line.split_whitespace().count() > 1 && line.ends_with(|c: char| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestion::{IngestPipeline, PageContent, ParsedDocument};
+    use crate::types::{Document, FileType};
+
+    const CONTRACT: &str = "Services Agreement\nContents\n4 Term ..... 3\n4.2 Termination Clauses ..... 3\n\
+        4 Term\nThis agreement runs for two years.\n4.1 Renewal\nIt renews for one year at a time.\n\
+        4.2 Termination Clauses\nEither party may terminate with 90 days notice.\n";
+
+    #[test]
+    fn test_detect_headings() {
+        let sections = detect_headings(CONTRACT);
+        let titles: Vec<(&str, u32)> = sections.iter().map(|s| (s.title.as_str(), s.level)).collect();
+        assert_eq!(titles, [("4 Term", 1), ("4.1 Renewal", 2), ("4.2 Termination Clauses", 2)]);
+        assert!(CONTRACT[sections[2].offset..].starts_with("4.2 Termination Clauses\nEither"));
+
+        // Sentences starting with a number are not headings
+        assert!(detect_headings("3 days later the notice takes effect.\n2024 Annual Report 7\n").is_empty());
+    }
+
+    #[test]
+    fn test_locate_skips_contents() {
+        let entries = [(1, "4 Term".to_string()), (2, "4.2  Termination clauses".to_string()), (2, "Missing".to_string())];
+        let sections = locate(CONTRACT, &entries);
+        assert_eq!(sections.len(), 2);
+        assert!(CONTRACT[sections[0].offset..].starts_with("4 Term\nThis"));
+        assert!(CONTRACT[sections[1].offset..].starts_with("4.2 Termination Clauses\nEither"));
+    }
+
+    #[test]
+    fn test_assign_section_titles() {
+        let parsed = ParsedDocument {
+            file_type: FileType::Pdf,
+            content: CONTRACT.to_string(),
+            content_hash: String::new(),
+            total_pages: Some(1),
+            pages: vec![PageContent { page_number: 1, content: CONTRACT.to_string(), char_offset: 0 }],
+            metadata: Default::default(),
+            sections: detect_headings(CONTRACT),
+        };
+        let doc = Document::new("contract.pdf".to_string(), FileType::Pdf, String::new(), 0);
+        let chunks = IngestPipeline::new(60, 0).create_chunks(&doc, &parsed).unwrap();
+
+        let last = chunks.iter().find(|c| c.content.contains("90 days")).unwrap();
+        assert_eq!(last.source.section_title.as_deref(), Some("4.2 Termination Clauses"));
+        assert_eq!(last.source.heading_hierarchy, ["4 Term", "4.2 Termination Clauses"]);
+    }
+}
@@ -0,0 +1,388 @@
+//! Streaming chunking for very large plain-text and CSV files
+//!
+//! The regular pipeline parses a whole file into one `String` before chunking,
+//! which does not work for multi-gigabyte logs and exports. The streaming
+//! chunker reads its source incrementally and hands out chunks in bounded
+//! batches, so memory stays proportional to the batch size rather than the
+//! file size. Content hashes match the regular parsers, so deduplication works
+//! the same for streamed and parsed files.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Read};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::types::document::COLLECTION_METADATA_KEY;
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document, FileType};
+
+/// Chunks shorter than this are dropped (same as the regular text chunker)
+const MIN_CHUNK_SIZE: usize = 50;
+
+/// Formats that can be chunked without parsing the whole file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Line-oriented text (logs, plain text)
+    Text,
+    /// CSV records, rendered like the CSV parser ("a | b | c")
+    Csv,
+}
+
+impl StreamFormat {
+    /// Streaming format for a file type, if it supports streaming
+    pub fn for_file_type(file_type: &FileType) -> Option<Self> {
+        match file_type {
+            FileType::Txt => Some(Self::Text),
+            FileType::Csv => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A line of text or a CSV row
+struct Unit {
+    text: String,
+    /// 1-based line or data row number
+    number: u32,
+    /// Byte offset of the unit in the rendered content
+    offset: usize,
+}
+
+enum Source<R: BufRead> {
+    Text {
+        reader: R,
+        /// Bytes of a UTF-8 sequence split by the line length limit
+        carry: Vec<u8>,
+        line: u32,
+        /// Whether the previous piece ended a line
+        at_line_start: bool,
+        max_line: usize,
+    },
+    Csv {
+        reader: csv::Reader<R>,
+        header: String,
+        row: u32,
+    },
+}
+
+/// Incremental chunker over a buffered reader
+///
+/// Text is split on line boundaries (over-long lines are split at
+/// `chunk_size`), with trailing lines repeated as overlap. CSV chunks hold
+/// whole rows and repeat the header instead of overlapping.
+pub struct StreamingChunker<R: BufRead> {
+    source: Source<R>,
+    doc: Document,
+    chunk_size: usize,
+    overlap: usize,
+    chunk_index: u32,
+    /// Units in the chunk being built
+    current: VecDeque<Unit>,
+    current_len: usize,
+    /// Length of the rendered content so far
+    offset: usize,
+    bytes_read: u64,
+    hasher: Sha256,
+    finished: bool,
+}
+
+impl<R: BufRead> StreamingChunker<R> {
+    /// Create a chunker for a document (CSV headers are read immediately)
+    pub fn new(reader: R, format: StreamFormat, doc: &Document, chunk_size: usize, overlap: usize) -> Result<Self> {
+        let chunk_size = chunk_size.max(MIN_CHUNK_SIZE);
+        let mut hasher = Sha256::new();
+        let mut offset = 0;
+
+        let source = match format {
+            StreamFormat::Text => Source::Text {
+                reader,
+                carry: Vec::new(),
+                line: 0,
+                at_line_start: true,
+                max_line: chunk_size,
+            },
+            StreamFormat::Csv => {
+                let mut reader = csv::Reader::from_reader(reader);
+                // Same rendering as the CSV parser, which also hashes the header line
+                let header = match reader.headers() {
+                    Ok(headers) => {
+                        let header = headers.iter().collect::<Vec<_>>().join(" | ");
+                        hasher.update(header.as_bytes());
+                        hasher.update(b"\n");
+                        offset = header.len() + 1;
+                        header
+                    }
+                    Err(_) => String::new(),
+                };
+                Source::Csv { reader, header, row: 0 }
+            }
+        };
+
+        Ok(Self {
+            source,
+            doc: doc.clone(),
+            chunk_size,
+            overlap,
+            chunk_index: 0,
+            current: VecDeque::new(),
+            current_len: 0,
+            offset,
+            bytes_read: 0,
+            hasher,
+            finished: false,
+        })
+    }
+
+    /// Bytes consumed from the source so far
+    pub fn bytes_read(&self) -> u64 {
+        match &self.source {
+            Source::Text { .. } => self.bytes_read,
+            Source::Csv { reader, .. } => reader.position().byte(),
+        }
+    }
+
+    /// Next batch of at most `max_chunks` chunks (empty when the source is exhausted)
+    pub fn next_batch(&mut self, max_chunks: usize) -> Result<Vec<Chunk>> {
+        let mut batch = Vec::new();
+        while batch.len() < max_chunks.max(1) && !self.finished {
+            let Some(unit) = self.next_unit()? else {
+                self.finished = true;
+                if let Some(chunk) = self.flush(false) {
+                    batch.push(chunk);
+                }
+                break;
+            };
+
+            if !self.current.is_empty() && self.current_len + unit.text.len() > self.chunk_size {
+                if let Some(chunk) = self.flush(true) {
+                    batch.push(chunk);
+                }
+            }
+            self.current_len += unit.text.len();
+            self.current.push_back(unit);
+        }
+        Ok(batch)
+    }
+
+    /// SHA-256 of the rendered content, complete once the source is exhausted
+    pub fn content_hash(&self) -> String {
+        format!("{:x}", self.hasher.clone().finalize())
+    }
+
+    /// Emit the current chunk, keeping overlap lines if `keep_overlap`
+    fn flush(&mut self, keep_overlap: bool) -> Option<Chunk> {
+        let (first, last) = match (self.current.front(), self.current.back()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return None,
+        };
+        let char_start = first.offset;
+        let char_end = last.offset + last.text.len();
+        let (first_number, last_number) = (first.number, last.number);
+
+        let mut content = String::with_capacity(self.current_len + 1);
+        if let Source::Csv { header, .. } = &self.source {
+            if !header.is_empty() {
+                content.push_str(header);
+                content.push('\n');
+            }
+        }
+        for unit in &self.current {
+            content.push_str(&unit.text);
+            if matches!(self.source, Source::Csv { .. }) {
+                content.push('\n');
+            }
+        }
+        let content = content.trim().to_string();
+
+        // CSV rows don't overlap, the header already gives each chunk context
+        if keep_overlap && matches!(self.source, Source::Text { .. }) {
+            while self.current_len > self.overlap {
+                match self.current.pop_front() {
+                    Some(unit) => self.current_len -= unit.text.len(),
+                    None => break,
+                }
+            }
+        } else {
+            self.current.clear();
+            self.current_len = 0;
+        }
+
+        if content.len() < MIN_CHUNK_SIZE {
+            return None;
+        }
+
+        let mut source = ChunkSource {
+            filename: self.doc.filename.clone(),
+            internal_filename: self.doc.internal_filename.clone(),
+            file_type: self.doc.file_type.clone(),
+            page_number: None,
+            page_count: None,
+            section_title: None,
+            heading_hierarchy: Vec::new(),
+            sheet_name: None,
+            row_range: None,
+            line_start: None,
+            line_end: None,
+            code_context: None,
+            time_range: None,
+            kind: ChunkKind::Text,
+        };
+        match self.source {
+            Source::Text { .. } => {
+                source.line_start = Some(first_number);
+                source.line_end = Some(last_number);
+            }
+            Source::Csv { .. } => source.row_range = Some((first_number, last_number)),
+        }
+
+        let mut chunk = Chunk::new(self.doc.id, content, source, char_start, char_end, self.chunk_index);
+        if let Some(collection) = self.doc.metadata.get(COLLECTION_METADATA_KEY) {
+            chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), collection.clone());
+        }
+        self.chunk_index += 1;
+        Some(chunk)
+    }
+
+    /// Read the next line (or line piece) or CSV row
+    fn next_unit(&mut self) -> Result<Option<Unit>> {
+        let offset = self.offset;
+        let unit = match &mut self.source {
+            Source::Text { reader, carry, line, at_line_start, max_line } => {
+                let mut buf = std::mem::take(carry);
+                let limit = max_line.saturating_sub(buf.len()).max(1) as u64;
+                let read = (&mut *reader)
+                    .take(limit)
+                    .read_until(b'\n', &mut buf)
+                    .map_err(|e| Error::Internal(format!("Failed to read stream: {}", e)))?;
+                self.bytes_read += read as u64;
+
+                if buf.is_empty() {
+                    return Ok(None);
+                }
+
+                let ends_line = buf.last() == Some(&b'\n');
+                // Keep a UTF-8 sequence cut by the length limit for the next piece
+                if !ends_line && read > 0 {
+                    if let Err(e) = std::str::from_utf8(&buf) {
+                        if e.error_len().is_none() && e.valid_up_to() > 0 {
+                            *carry = buf.split_off(e.valid_up_to());
+                        }
+                    }
+                }
+
+                if *at_line_start {
+                    *line += 1;
+                }
+                *at_line_start = ends_line;
+
+                let text = String::from_utf8_lossy(&buf).into_owned();
+                Unit { text, number: *line, offset }
+            }
+            Source::Csv { reader, row, .. } => {
+                let mut record = csv::StringRecord::new();
+                loop {
+                    match reader.read_record(&mut record) {
+                        Ok(true) => break,
+                        Ok(false) => return Ok(None),
+                        // Malformed rows are skipped, like the CSV parser does
+                        Err(e) if !e.is_io_error() => continue,
+                        Err(e) => return Err(Error::Internal(format!("Failed to read CSV stream: {}", e))),
+                    }
+                }
+                *row += 1;
+                let text = record.iter().collect::<Vec<_>>().join(" | ");
+                Unit { text, number: *row, offset }
+            }
+        };
+
+        self.hasher.update(unit.text.as_bytes());
+        self.offset += unit.text.len();
+        if matches!(self.source, Source::Csv { .. }) {
+            self.hasher.update(b"\n");
+            self.offset += 1;
+        }
+        Ok(Some(unit))
+    }
+}
+
+/// Hash a source the same way the streaming chunker (and the regular parser) would
+///
+/// Used to check for duplicates before spending time on embeddings.
+pub fn stream_content_hash<R: BufRead>(reader: R, format: StreamFormat) -> Result<String> {
+    let doc = Document::new(String::new(), FileType::Unknown, String::new(), 0);
+    let mut chunker = StreamingChunker::new(reader, format, &doc, 64 * 1024, 0)?;
+    while chunker.next_unit()?.is_some() {}
+    Ok(chunker.content_hash())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestion::FileParser;
+
+    fn doc(file_type: FileType) -> Document {
+        Document::new("big.txt".to_string(), file_type, String::new(), 0)
+    }
+
+    #[test]
+    fn test_text_batches_and_hash() {
+        let text: String = (1..=200)
+            .map(|i| format!("2024-01-01 12:00:{:02} INFO request {} handled\n", i % 60, i))
+            .collect();
+        let mut chunker =
+            StreamingChunker::new(text.as_bytes(), StreamFormat::Text, &doc(FileType::Txt), 500, 100).unwrap();
+
+        let mut chunks = Vec::new();
+        loop {
+            let batch = chunker.next_batch(3).unwrap();
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 3);
+            chunks.extend(batch);
+        }
+
+        assert!(chunks.len() > 10);
+        assert!(chunks.iter().all(|c| c.content.len() <= 500));
+        assert_eq!(chunks[0].source.line_start, Some(1));
+        // Overlap repeats trailing lines of the previous chunk
+        assert!(chunks[1].source.line_start.unwrap() <= chunks[0].source.line_end.unwrap());
+        assert_eq!(chunks.last().unwrap().source.line_end, Some(200));
+        assert_eq!(chunker.bytes_read(), text.len() as u64);
+
+        let parsed = FileParser::parse("big.txt", text.as_bytes()).unwrap();
+        assert_eq!(chunker.content_hash(), parsed.content_hash);
+        assert_eq!(stream_content_hash(text.as_bytes(), StreamFormat::Text).unwrap(), parsed.content_hash);
+    }
+
+    #[test]
+    fn test_long_line_split_keeps_utf8() {
+        let text = "ä".repeat(300);
+        let mut chunker =
+            StreamingChunker::new(text.as_bytes(), StreamFormat::Text, &doc(FileType::Txt), 101, 0).unwrap();
+        let chunks = chunker.next_batch(100).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| !c.content.contains('\u{FFFD}')));
+        assert!(chunks.iter().all(|c| c.source.line_start == Some(1)));
+    }
+
+    #[test]
+    fn test_csv_rows_with_header() {
+        let mut csv = String::from("id,name,comment\n");
+        for i in 1..=50 {
+            csv.push_str(&format!("{},user{},\"multi\nline comment {}\"\n", i, i, i));
+        }
+        let mut chunker =
+            StreamingChunker::new(csv.as_bytes(), StreamFormat::Csv, &doc(FileType::Csv), 300, 50).unwrap();
+        let chunks = chunker.next_batch(1000).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.content.starts_with("id | name | comment")));
+        assert_eq!(chunks[0].source.row_range.map(|r| r.0), Some(1));
+        assert_eq!(chunks.last().unwrap().source.row_range.map(|r| r.1), Some(50));
+
+        let parsed = FileParser::parse("data.csv", csv.as_bytes()).unwrap();
+        assert_eq!(chunker.content_hash(), parsed.content_hash);
+    }
+}
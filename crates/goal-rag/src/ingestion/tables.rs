@@ -0,0 +1,327 @@
+//! Table detection and Markdown rendering
+//!
+//! PDF text from `pdftotext -layout` keeps table columns aligned with runs of
+//! spaces; spreadsheets and Document AI tables arrive as pipe-delimited rows.
+//! Detected tables become dedicated chunks (`ChunkKind::Table`) so numeric
+//! questions retrieve the whole table instead of flattened fragments.
+
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document, FileType};
+use super::parser::ParsedDocument;
+
+/// Minimum rows (header included) for aligned text to count as a table
+const MIN_ALIGNED_ROWS: usize = 3;
+
+/// Longer average cells indicate prose that happens to contain double spaces
+const MAX_AVG_CELL_LEN: usize = 40;
+
+/// A table found in text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// Rows of cells; the first row is the header
+    pub rows: Vec<Vec<String>>,
+    /// Byte offset of the first row in the scanned text
+    pub start: usize,
+    /// Byte offset after the last row
+    pub end: usize,
+}
+
+impl Table {
+    pub fn to_markdown(&self) -> String {
+        render_markdown(&self.rows)
+    }
+}
+
+/// Render rows as a Markdown table with the first row as header
+pub fn render_markdown(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = String::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        out.push('|');
+        for column in 0..columns {
+            let cell = row.get(column).map(|c| c.trim()).unwrap_or("");
+            out.push(' ');
+            out.push_str(&cell.replace('|', "\\|").replace('\n', " "));
+            out.push_str(" |");
+        }
+        out.push('\n');
+
+        if i == 0 {
+            out.push('|');
+            out.push_str(&" --- |".repeat(columns));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    /// Cells separated by `|`
+    Pipe,
+    /// Markdown header separator (`|---|---|`)
+    Separator,
+    /// Cells separated by runs of spaces or tabs
+    Aligned,
+    Blank,
+    Other,
+}
+
+fn classify(line: &str) -> LineKind {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        LineKind::Blank
+    } else if trimmed.contains('-') && trimmed.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ')) {
+        LineKind::Separator
+    } else if trimmed.contains('|') && pipe_cells(trimmed).len() >= 2 {
+        LineKind::Pipe
+    } else if aligned_cells(trimmed).len() >= 2 {
+        LineKind::Aligned
+    } else {
+        LineKind::Other
+    }
+}
+
+/// Split a pipe-delimited row
+///
+/// Outer pipes are stripped only for Markdown rows (leading `|`); a trailing
+/// pipe alone marks an empty last cell, as in spreadsheet rows.
+pub(super) fn pipe_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = match line.strip_prefix('|') {
+        Some(inner) => inner.strip_suffix('|').unwrap_or(inner),
+        None => line,
+    };
+    line.split('|').map(|c| c.trim().to_string()).collect()
+}
+
+fn aligned_cells(line: &str) -> Vec<String> {
+    line.replace('\t', "  ")
+        .trim()
+        .split("  ")
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Detect tables in text
+///
+/// Pipe-delimited blocks need a header and one row. Aligned blocks need
+/// three rows, short cells and digits in at least half the rows; a single
+/// blank line between aligned rows is allowed, and trailing rows without
+/// digits (usually prose after the table) are dropped.
+pub fn detect(text: &str) -> Vec<Table> {
+    let mut tables = Vec::new();
+    let mut block: Vec<(LineKind, &str, usize)> = Vec::new();
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let kind = classify(line);
+        let continues = match block.last() {
+            None => false,
+            Some((last, _, _)) => matches!(
+                (last, kind),
+                (LineKind::Pipe | LineKind::Separator, LineKind::Pipe | LineKind::Separator)
+                    | (LineKind::Aligned, LineKind::Aligned | LineKind::Blank)
+                    | (LineKind::Blank, LineKind::Aligned)
+            ),
+        };
+
+        if !continues {
+            tables.extend(finish_block(&block));
+            block.clear();
+        }
+        if continues || matches!(kind, LineKind::Pipe | LineKind::Aligned) {
+            block.push((kind, line, offset));
+        }
+        offset += line.len();
+    }
+    tables.extend(finish_block(&block));
+
+    tables
+}
+
+fn finish_block(block: &[(LineKind, &str, usize)]) -> Option<Table> {
+    let mut lines: Vec<(&str, usize)> = block
+        .iter()
+        .filter(|(kind, _, _)| matches!(kind, LineKind::Pipe | LineKind::Aligned))
+        .map(|(_, line, offset)| (*line, *offset))
+        .collect();
+    let pipe = block.first()?.0 == LineKind::Pipe;
+
+    if !pipe {
+        while lines.last().is_some_and(|(line, _)| !line.chars().any(|c| c.is_ascii_digit())) {
+            lines.pop();
+        }
+    }
+
+    let start = lines.first()?.1;
+    let end = lines.last().map(|(line, offset)| offset + line.len())?;
+
+    if pipe {
+        let rows: Vec<Vec<String>> = lines.iter().map(|(line, _)| pipe_cells(line)).collect();
+        return (rows.len() >= 2).then_some(Table { rows, start, end });
+    }
+
+    let rows: Vec<Vec<String>> = lines.iter().map(|(line, _)| aligned_cells(line)).collect();
+    let cells: Vec<&String> = rows.iter().flatten().collect();
+    let avg_cell_len = cells.iter().map(|c| c.chars().count()).sum::<usize>() / cells.len().max(1);
+    let numeric_rows = rows
+        .iter()
+        .filter(|row| row.iter().any(|c| c.chars().any(|ch| ch.is_ascii_digit())))
+        .count();
+
+    (rows.len() >= MIN_ALIGNED_ROWS && avg_cell_len <= MAX_AVG_CELL_LEN && numeric_rows * 2 >= rows.len())
+        .then_some(Table { rows, start, end })
+}
+
+/// Create table chunks for a parsed document
+///
+/// Each detected table becomes one chunk, or several of up to `max_size`
+/// characters split between rows with the header repeated. Chunk numbering
+/// continues from `start_index`.
+pub fn table_chunks(doc: &Document, parsed: &ParsedDocument, max_size: usize, start_index: u32) -> Vec<Chunk> {
+    let spreadsheet = matches!(doc.file_type, FileType::Xlsx | FileType::Xls);
+    let pages: Vec<(&str, Option<u32>, usize)> = if parsed.pages.is_empty() {
+        vec![(parsed.content.as_str(), None, 0)]
+    } else {
+        parsed
+            .pages
+            .iter()
+            .map(|p| (p.content.as_str(), Some(p.page_number), p.char_offset))
+            .collect()
+    };
+
+    let mut chunks = Vec::new();
+    let mut chunk_index = start_index;
+
+    for (text, page_number, base_offset) in pages {
+        let sheet_name = text
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix("Sheet: "))
+            .filter(|_| spreadsheet)
+            .map(str::to_string);
+
+        for table in detect(text) {
+            let (header, body) = table.rows.split_first().expect("tables have a header row");
+
+            for (first_row, last_row, rows) in row_groups(header, body, max_size) {
+                let mut source = ChunkSource::text(doc.filename.clone());
+                source.internal_filename = doc.internal_filename.clone();
+                source.file_type = doc.file_type.clone();
+                source.page_number = page_number;
+                source.page_count = parsed.total_pages;
+                source.sheet_name = sheet_name.clone();
+                source.row_range = Some((first_row, last_row));
+                source.kind = ChunkKind::Table;
+
+                chunks.push(Chunk::new(
+                    doc.id,
+                    render_markdown(&rows),
+                    source,
+                    base_offset + table.start,
+                    base_offset + table.end,
+                    chunk_index,
+                ));
+                chunk_index += 1;
+            }
+        }
+    }
+
+    chunks
+}
+
+/// Split body rows into groups that render within `max_size`, header first
+///
+/// Returns 1-based body row ranges with the rows of each group.
+pub(super) fn row_groups(header: &[String], body: &[Vec<String>], max_size: usize) -> Vec<(u32, u32, Vec<Vec<String>>)> {
+    let row_len = |row: &[String]| row.iter().map(|c| c.len() + 3).sum::<usize>() + 2;
+    let header_len = row_len(header) * 2;
+
+    let mut groups = Vec::new();
+    let mut rows = vec![header.to_vec()];
+    let mut size = header_len;
+    let mut first = 1u32;
+
+    for (i, row) in body.iter().enumerate() {
+        let len = row_len(row);
+        if rows.len() > 1 && size + len > max_size {
+            groups.push((first, i as u32, std::mem::replace(&mut rows, vec![header.to_vec()])));
+            size = header_len;
+            first = i as u32 + 1;
+        }
+        rows.push(row.clone());
+        size += len;
+    }
+
+    if rows.len() > 1 {
+        groups.push((first, body.len() as u32, rows));
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_layout_table() {
+        let text = "Quarterly results were strong.\n\n\
+            Region        Q1       Q2\n\
+            North        1,200    1,350\n\
+            \n\
+            South          980    1,010\n\
+            \n\
+            Totals were up.  Margins held steady across the year.\n";
+
+        let tables = detect(text);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].rows[0], vec!["Region", "Q1", "Q2"]);
+        assert_eq!(tables[0].rows[2], vec!["South", "980", "1,010"]);
+        assert!(text[tables[0].start..tables[0].end].starts_with("Region"));
+        assert!(text[tables[0].start..tables[0].end].trim_end().ends_with("1,010"));
+    }
+
+    #[test]
+    fn test_detect_pipe_table_and_markdown() {
+        let text = "Sheet: Prices\nItem | Price | Stock\nApple | 1.20 | \n| Pear | 0.80 | 12 |\n";
+        let tables = detect(text);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].rows.len(), 3);
+        assert_eq!(tables[0].rows[1], vec!["Apple", "1.20", ""]);
+        assert_eq!(
+            tables[0].to_markdown(),
+            "| Item | Price | Stock |\n| --- | --- | --- |\n| Apple | 1.20 |  |\n| Pear | 0.80 | 12 |\n"
+        );
+
+        // Markdown separator rows are not data
+        let markdown = "| a | b |\n|---|:-:|\n| 1 | 2 |\n";
+        assert_eq!(detect(markdown)[0].rows, vec![vec!["a", "b"], vec!["1", "2"]]);
+    }
+
+    #[test]
+    fn test_prose_is_not_a_table() {
+        let text = "This sentence is followed by two spaces.  Then another one follows here.\n\
+            Old typewriter habits  leave double spaces  in many documents.\n\
+            None of these lines  form a table at all.\n";
+        assert!(detect(text).is_empty());
+    }
+
+    #[test]
+    fn test_row_groups_repeat_header() {
+        let header = vec!["Name".to_string(), "Value".to_string()];
+        let body: Vec<Vec<String>> = (0..10).map(|i| vec![format!("row{}", i), i.to_string()]).collect();
+
+        let groups = row_groups(&header, &body, 80);
+        assert!(groups.len() > 1);
+        assert!(groups.iter().all(|(_, _, rows)| rows[0] == header));
+        assert_eq!(groups.first().unwrap().0, 1);
+        assert_eq!(groups.last().unwrap().1, 10);
+        let total: usize = groups.iter().map(|(_, _, rows)| rows.len() - 1).sum();
+        assert_eq!(total, 10);
+    }
+}
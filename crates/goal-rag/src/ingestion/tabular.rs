@@ -0,0 +1,169 @@
+//! Row-aware chunking for CSV and spreadsheet files
+//!
+//! The parsers render each sheet as pipe-delimited rows under its header
+//! row. Chunking that text like prose splits rows and leaves most chunks
+//! without column names, so tabular files are chunked by whole rows instead:
+//! every chunk is a Markdown table starting with the header, and records its
+//! sheet and (1-based, header excluded) row range. Optionally each group of
+//! rows also gets a chunk describing the rows in sentences ("Region is North,
+//! Q1 is 1,200"), which embeds closer to natural-language questions than
+//! table syntax.
+
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document, FileType};
+use super::parser::ParsedDocument;
+use super::tables::{pipe_cells, render_markdown, row_groups};
+
+/// Whether a file type is chunked by rows
+pub fn is_tabular(file_type: &FileType) -> bool {
+    matches!(file_type, FileType::Csv | FileType::Xlsx | FileType::Xls)
+}
+
+/// Chunk a CSV or spreadsheet by rows
+///
+/// Chunks hold up to `max_size` characters of rows. With `describe`, a
+/// description chunk follows each table chunk for the same rows.
+pub fn row_chunks(doc: &Document, parsed: &ParsedDocument, max_size: usize, describe: bool) -> Vec<Chunk> {
+    let spreadsheet = matches!(doc.file_type, FileType::Xlsx | FileType::Xls);
+    let sheets: Vec<(&str, Option<u32>, usize)> = if parsed.pages.is_empty() {
+        vec![(parsed.content.as_str(), None, 0)]
+    } else {
+        parsed
+            .pages
+            .iter()
+            .map(|p| (p.content.as_str(), Some(p.page_number).filter(|_| spreadsheet), p.char_offset))
+            .collect()
+    };
+
+    let mut chunks = Vec::new();
+    for (text, page_number, base_offset) in sheets {
+        let mut sheet_name = None;
+        // Cells and byte range of each non-empty row
+        let mut rows: Vec<(Vec<String>, usize, usize)> = Vec::new();
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let start = base_offset + offset;
+            offset += line.len();
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            match line.strip_prefix("Sheet: ") {
+                Some(name) if spreadsheet && rows.is_empty() && sheet_name.is_none() => {
+                    sheet_name = Some(name.to_string());
+                }
+                _ => rows.push((pipe_cells(line), start, start + line.len())),
+            }
+        }
+
+        let Some(((header, header_start, header_end), body)) = rows.split_first() else {
+            continue;
+        };
+        let source = |row_range: Option<(u32, u32)>, kind: ChunkKind| {
+            let mut source = ChunkSource::text(doc.filename.clone());
+            source.internal_filename = doc.internal_filename.clone();
+            source.file_type = doc.file_type.clone();
+            source.page_number = page_number;
+            source.page_count = parsed.total_pages;
+            source.sheet_name = sheet_name.clone();
+            source.row_range = row_range;
+            source.kind = kind;
+            source
+        };
+
+        // A sheet with only a header still gets a chunk
+        if body.is_empty() {
+            let content = render_markdown(std::slice::from_ref(header));
+            let index = chunks.len() as u32;
+            chunks.push(Chunk::new(doc.id, content, source(None, ChunkKind::Table), *header_start, *header_end, index));
+            continue;
+        }
+
+        let cells: Vec<Vec<String>> = body.iter().map(|(cells, _, _)| cells.clone()).collect();
+        for (first, last, group) in row_groups(header, &cells, max_size) {
+            let start = body[first as usize - 1].1;
+            let end = body[last as usize - 1].2;
+            let index = chunks.len() as u32;
+            chunks.push(Chunk::new(
+                doc.id,
+                render_markdown(&group),
+                source(Some((first, last)), ChunkKind::Table),
+                start,
+                end,
+                index,
+            ));
+
+            if describe {
+                let content = describe_rows(header, &group[1..], first, sheet_name.as_deref());
+                let index = chunks.len() as u32;
+                chunks.push(Chunk::new(doc.id, content, source(Some((first, last)), ChunkKind::Text), start, end, index));
+            }
+        }
+    }
+
+    chunks
+}
+
+/// Describe rows in sentences, one per row, naming each non-empty cell by its column
+fn describe_rows(header: &[String], rows: &[Vec<String>], first_row: u32, sheet: Option<&str>) -> String {
+    let mut out = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(column, value)| match header.get(column).filter(|name| !name.is_empty()) {
+                Some(name) => format!("{} is {}", name, value),
+                None => format!("column {} is {}", column + 1, value),
+            })
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+
+        let row_number = first_row + i as u32;
+        match sheet {
+            Some(sheet) => out.push_str(&format!("In sheet {}, row {}: ", sheet, row_number)),
+            None => out.push_str(&format!("Row {}: ", row_number)),
+        }
+        out.push_str(&cells.join(", "));
+        out.push_str(".\n");
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestion::PageContent;
+
+    #[test]
+    fn test_row_chunks_keep_header() {
+        let mut content = String::from("Sheet: Sales\nRegion | Q1 | Q2\n");
+        for i in 0..20 {
+            content.push_str(&format!("Region {} | {} | \n", i, i * 100));
+        }
+        let parsed = ParsedDocument {
+            file_type: FileType::Xlsx,
+            content: content.clone(),
+            content_hash: String::new(),
+            total_pages: Some(1),
+            pages: vec![PageContent { page_number: 1, content, char_offset: 0 }],
+            metadata: Default::default(),
+            sections: Vec::new(),
+        };
+        let doc = Document::new("sales.xlsx".into(), FileType::Xlsx, String::new(), 0);
+
+        let chunks = row_chunks(&doc, &parsed, 200, true);
+        let tables: Vec<&Chunk> = chunks.iter().filter(|c| c.source.kind == ChunkKind::Table).collect();
+        assert!(tables.len() > 1);
+        assert!(tables.iter().all(|c| c.content.starts_with("| Region | Q1 | Q2 |")));
+        assert!(tables.iter().all(|c| c.source.sheet_name.as_deref() == Some("Sales")));
+        assert_eq!(tables[0].source.row_range.map(|r| r.0), Some(1));
+        assert_eq!(tables.last().unwrap().source.row_range.map(|r| r.1), Some(20));
+
+        // Descriptions skip empty cells
+        let description = &chunks[1];
+        assert_eq!(description.source.row_range, tables[0].source.row_range);
+        assert!(description.content.starts_with("In sheet Sales, row 1: Region is Region 0, Q1 is 0.\n"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Page thumbnails for citation previews
+//!
+//! PDF pages are rendered to small PNGs with poppler's `pdftoppm` and kept in
+//! the document store, so a citation can show the page it cites.
+
+use std::fs;
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use super::ExternalParser;
+
+/// Whether thumbnails can be rendered for a file
+pub fn supports(filename: &str) -> bool {
+    filename.rsplit('.').next().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Render the first `max_pages` pages of a PDF as PNGs whose longest side is
+/// `size` pixels
+///
+/// Returns (page number, PNG data) in page order.
+pub fn render_pdf_pages(data: &[u8], size: u32, max_pages: u32) -> Result<Vec<(u32, Vec<u8>)>> {
+    if !ExternalParser::has_pdftoppm() {
+        return Err(Error::Internal("pdftoppm not installed (install poppler-utils)".to_string()));
+    }
+
+    let temp_dir = std::env::temp_dir().join(format!("goal-rag-thumbnails-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
+
+    let result = (|| {
+        let pdf_path = temp_dir.join("input.pdf");
+        fs::write(&pdf_path, data)
+            .map_err(|e| Error::Internal(format!("Failed to write temp PDF: {}", e)))?;
+
+        // Output files are page-N.png, N zero-padded to the page count's width
+        let output = Command::new("pdftoppm")
+            .args([
+                "-png",
+                "-scale-to",
+                &size.to_string(),
+                "-l",
+                &max_pages.to_string(),
+                pdf_path.to_str().unwrap(),
+                temp_dir.join("page").to_str().unwrap(),
+            ])
+            .output()
+            .map_err(|e| Error::Internal(format!("pdftoppm failed: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::Internal(format!("pdftoppm error: {}", stderr)));
+        }
+
+        let mut pages: Vec<(u32, std::path::PathBuf)> = fs::read_dir(&temp_dir)
+            .map_err(|e| Error::Internal(format!("Failed to read temp dir: {}", e)))?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| parse_page_name(&e.file_name().to_string_lossy()).map(|page| (page, e.path())))
+            .collect();
+        pages.sort();
+
+        Ok(pages
+            .into_iter()
+            .filter_map(|(page, path)| fs::read(path).ok().map(|png| (page, png)))
+            .collect())
+    })();
+
+    fs::remove_dir_all(&temp_dir).ok();
+    result
+}
+
+/// Page number from a `pdftoppm` output name (`page-007.png`)
+fn parse_page_name(name: &str) -> Option<u32> {
+    name.strip_prefix("page-")?.strip_suffix(".png")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_page_name() {
+        assert_eq!(parse_page_name("page-1.png"), Some(1));
+        assert_eq!(parse_page_name("page-012.png"), Some(12));
+        assert_eq!(parse_page_name("input.pdf"), None);
+        assert!(supports("Report.PDF"));
+        assert!(!supports("report.docx"));
+    }
+}
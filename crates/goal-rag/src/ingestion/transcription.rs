@@ -0,0 +1,475 @@
+//! Audio and video transcription
+//!
+//! Recordings are transcribed with an OpenAI-compatible Whisper endpoint
+//! (OpenAI, whisper.cpp server, faster-whisper-server) or the local
+//! whisper.cpp CLI. The transcript text keeps one segment per line prefixed
+//! with its time range, and is chunked on segment boundaries so every chunk
+//! records the start and end seconds it covers.
+
+use reqwest::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::{TranscriptionBackend, TranscriptionConfig};
+use crate::error::{Error, Result};
+use crate::types::document::format_timestamp;
+use crate::types::{Chunk, ChunkSource, Document, FileType};
+use super::parser::ParsedDocument;
+
+/// A transcribed span of speech
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    /// Start offset in seconds
+    pub start: f64,
+    /// End offset in seconds
+    pub end: f64,
+    pub text: String,
+}
+
+/// Transcript of a recording
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub segments: Vec<TranscriptSegment>,
+    /// Spoken language reported by the model
+    pub language: Option<String>,
+}
+
+impl Transcript {
+    /// Transcript text, one `[HH:MM:SS - HH:MM:SS] text` line per segment
+    pub fn to_text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| format!("[{} - {}] {}\n", format_timestamp(s.start), format_timestamp(s.end.ceil()), s.text.trim()))
+            .collect()
+    }
+
+    /// Parsed document for the ingestion pipeline
+    ///
+    /// `content_hash` identifies the recording itself (see [`media_hash`]),
+    /// since repeated transcriptions of the same file can differ slightly.
+    pub fn into_parsed(self, file_type: FileType, content_hash: String) -> ParsedDocument {
+        let content = self.to_text();
+        let mut metadata = HashMap::new();
+        if let Some(language) = self.language {
+            metadata.insert("transcript_language".to_string(), language);
+        }
+        ParsedDocument {
+            file_type,
+            content,
+            content_hash,
+            total_pages: None,
+            pages: Vec::new(),
+            metadata,
+            sections: Vec::new(),
+        }
+    }
+}
+
+/// Whether a file is an audio or video recording
+pub fn is_media(filename: &str) -> bool {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    matches!(FileType::from_extension(&ext), FileType::Audio | FileType::Video)
+}
+
+/// Content hash of a recording's bytes
+pub fn media_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Placeholder for a recording that is not transcribed because the
+/// deduplication check will skip it
+pub fn pending(file_type: FileType, content_hash: String) -> ParsedDocument {
+    Transcript::default().into_parsed(file_type, content_hash)
+}
+
+/// Split a transcript line into start seconds, end seconds and text
+fn parse_line(line: &str) -> Option<(f64, f64, &str)> {
+    let (range, text) = line.trim_end().strip_prefix('[')?.split_once("] ")?;
+    let (start, end) = range.split_once(" - ")?;
+    Some((parse_timestamp(start)?, parse_timestamp(end)?, text))
+}
+
+fn parse_timestamp(s: &str) -> Option<f64> {
+    let mut seconds = 0u64;
+    for part in s.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(seconds as f64)
+}
+
+/// Create timestamped chunks from transcript text
+///
+/// Consecutive segments are grouped into chunks of up to `max_size`
+/// characters; each chunk's source records the time range it covers.
+pub fn transcript_chunks(doc: &Document, text: &str, max_size: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut group: Option<(usize, usize, Option<(f64, f64)>)> = None;
+    let mut offset = 0;
+
+    let flush = |group: (usize, usize, Option<(f64, f64)>), chunks: &mut Vec<Chunk>| {
+        let (start, end, time_range) = group;
+        let content = text[start..end].trim_end();
+        if content.is_empty() {
+            return;
+        }
+        let mut source = ChunkSource::text(doc.filename.clone());
+        source.internal_filename = doc.internal_filename.clone();
+        source.file_type = doc.file_type.clone();
+        source.time_range = time_range;
+        let index = chunks.len() as u32;
+        chunks.push(Chunk::new(doc.id, content.to_string(), source, start, start + content.len(), index));
+    };
+
+    for line in text.split_inclusive('\n') {
+        let times = parse_line(line).map(|(start, end, _)| (start, end));
+
+        let full = group.is_some_and(|(start, _, _)| offset - start + line.len() > max_size);
+        if full {
+            flush(group.take().unwrap(), &mut chunks);
+        }
+
+        let (_, end, time_range) = group.get_or_insert((offset, offset, None));
+        *end = offset + line.len();
+        if let Some((start, stop)) = times {
+            *time_range = Some(match *time_range {
+                Some((first, _)) => (first, stop),
+                None => (start, stop),
+            });
+        }
+        offset += line.len();
+    }
+    if let Some(group) = group {
+        flush(group, &mut chunks);
+    }
+
+    chunks
+}
+
+#[derive(Deserialize)]
+struct ApiTranscription {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    duration: Option<f64>,
+    #[serde(default)]
+    segments: Vec<ApiSegment>,
+}
+
+#[derive(Deserialize)]
+struct ApiSegment {
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+impl From<ApiTranscription> for Transcript {
+    fn from(response: ApiTranscription) -> Self {
+        let mut segments: Vec<TranscriptSegment> = response
+            .segments
+            .into_iter()
+            .map(|s| TranscriptSegment { start: s.start, end: s.end, text: s.text })
+            .collect();
+
+        // Plain json responses have no segments; keep the text as one span
+        if segments.is_empty() && !response.text.trim().is_empty() {
+            segments.push(TranscriptSegment {
+                start: 0.0,
+                end: response.duration.unwrap_or(0.0),
+                text: response.text,
+            });
+        }
+
+        Self { segments, language: response.language }
+    }
+}
+
+/// Output of `whisper-cli -oj`
+#[derive(Deserialize)]
+struct WhisperCppOutput {
+    #[serde(default)]
+    result: Option<WhisperCppResult>,
+    transcription: Vec<WhisperCppSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppResult {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppSegment {
+    /// Offsets in milliseconds
+    offsets: WhisperCppOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppOffsets {
+    from: u64,
+    to: u64,
+}
+
+impl From<WhisperCppOutput> for Transcript {
+    fn from(output: WhisperCppOutput) -> Self {
+        Self {
+            segments: output
+                .transcription
+                .into_iter()
+                .filter(|s| !s.text.trim().is_empty())
+                .map(|s| TranscriptSegment {
+                    start: s.offsets.from as f64 / 1000.0,
+                    end: s.offsets.to as f64 / 1000.0,
+                    text: s.text,
+                })
+                .collect(),
+            language: output.result.and_then(|r| r.language),
+        }
+    }
+}
+
+/// Whisper transcription client
+pub struct Transcriber {
+    client: Client,
+    config: TranscriptionConfig,
+}
+
+impl Transcriber {
+    /// Create a new transcriber
+    pub fn new(config: TranscriptionConfig) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .expect("Failed to create HTTP client");
+        Self { client, config }
+    }
+
+    /// Whether transcription is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Check if ffmpeg is available (for video and whisper.cpp input)
+    pub fn has_ffmpeg() -> bool {
+        std::process::Command::new("ffmpeg")
+            .arg("-version")
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    /// Transcribe an audio or video recording
+    pub async fn transcribe(&self, filename: &str, data: &[u8]) -> Result<Transcript> {
+        if !self.config.enabled {
+            return Err(Error::file_parse(
+                filename,
+                "Audio/video transcription is disabled (enable [transcription])".to_string(),
+            ));
+        }
+
+        let temp_dir = std::env::temp_dir().join(format!("goal-rag-transcribe-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&temp_dir)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to create temp dir: {}", e)))?;
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(self.config.timeout_secs),
+            self.transcribe_in(&temp_dir, filename, data),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(Error::file_parse(
+                filename,
+                format!("Transcription timeout after {}s", self.config.timeout_secs),
+            ))
+        });
+
+        tokio::fs::remove_dir_all(&temp_dir).await.ok();
+        result
+    }
+
+    async fn transcribe_in(&self, dir: &Path, filename: &str, data: &[u8]) -> Result<Transcript> {
+        let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+        let input = dir.join(format!("input.{}", ext));
+        tokio::fs::write(&input, data)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to write temp file: {}", e)))?;
+        let video = FileType::from_extension(&ext) == FileType::Video;
+
+        match self.config.backend {
+            TranscriptionBackend::Api if video => {
+                // Upload the audio track only; video files exceed API size limits
+                let audio = dir.join("audio.mp3");
+                ffmpeg(&input, &audio, &["-c:a", "libmp3lame", "-b:a", "48k"]).await?;
+                let audio_data = tokio::fs::read(&audio)
+                    .await
+                    .map_err(|e| Error::Internal(format!("Failed to read extracted audio: {}", e)))?;
+                self.transcribe_api("audio.mp3", audio_data).await
+            }
+            TranscriptionBackend::Api => self.transcribe_api(filename, data.to_vec()).await,
+            TranscriptionBackend::WhisperCpp => {
+                // whisper.cpp reads 16 kHz mono WAV
+                let audio = dir.join("audio.wav");
+                ffmpeg(&input, &audio, &["-f", "wav"]).await?;
+                self.transcribe_whisper_cpp(dir, &audio).await
+            }
+        }
+    }
+
+    async fn transcribe_api(&self, filename: &str, data: Vec<u8>) -> Result<Transcript> {
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", reqwest::multipart::Part::bytes(data).file_name(filename.to_string()))
+            .text("model", self.config.model.clone())
+            .text("response_format", "verbose_json");
+        if let Some(ref language) = self.config.language {
+            form = form.text("language", language.clone());
+        }
+
+        let mut request = self.client.post(&self.config.api_url).multipart(form);
+        if let Some(ref api_key) = self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Transcription request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Internal(format!("Transcription API error: {} - {}", status, body)));
+        }
+
+        let transcription: ApiTranscription = response
+            .json()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to parse transcription response: {}", e)))?;
+
+        Ok(transcription.into())
+    }
+
+    async fn transcribe_whisper_cpp(&self, dir: &Path, audio: &Path) -> Result<Transcript> {
+        let output_base = dir.join("transcript");
+        let mut command = Command::new(&self.config.whisper_cpp_binary);
+        command
+            .arg("-m")
+            .arg(&self.config.whisper_cpp_model)
+            .arg("-f")
+            .arg(audio)
+            .arg("-oj")
+            .arg("-of")
+            .arg(&output_base)
+            .arg("-np")
+            .kill_on_drop(true);
+        if let Some(ref language) = self.config.language {
+            command.arg("-l").arg(language);
+        }
+
+        let output = command
+            .output()
+            .await
+            .map_err(|e| Error::Internal(format!("{} failed: {}", self.config.whisper_cpp_binary, e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::Internal(format!("whisper.cpp error: {}", stderr)));
+        }
+
+        let json = tokio::fs::read(output_base.with_extension("json"))
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read whisper.cpp output: {}", e)))?;
+        let output: WhisperCppOutput = serde_json::from_slice(&json)
+            .map_err(|e| Error::Internal(format!("Failed to parse whisper.cpp output: {}", e)))?;
+
+        Ok(output.into())
+    }
+}
+
+/// Extract a 16 kHz mono audio track with ffmpeg
+async fn ffmpeg(input: &Path, output: &Path, codec_args: &[&str]) -> Result<()> {
+    let result = Command::new("ffmpeg")
+        .args(["-nostdin", "-y", "-i"])
+        .arg(input)
+        .args(["-vn", "-ac", "1", "-ar", "16000"])
+        .args(codec_args)
+        .arg(output)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::Internal(format!("ffmpeg failed (is it installed?): {}", e)))?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(Error::Internal(format!("ffmpeg error: {}", stderr)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        let segment = |start: f64, end: f64, text: &str| TranscriptSegment { start, end, text: text.to_string() };
+        Transcript {
+            segments: vec![
+                segment(0.0, 4.2, " Welcome to the quarterly review."),
+                segment(4.2, 9.8, " Revenue grew twelve percent."),
+                segment(3725.5, 3731.0, " Thanks everyone."),
+            ],
+            language: Some("en".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_transcript_text_round_trip() {
+        let text = transcript().to_text();
+        assert!(text.starts_with("[00:00:00 - 00:00:05] Welcome to the quarterly review.\n"));
+        assert_eq!(parse_line("[01:02:05 - 01:02:11] Thanks everyone."), Some((3725.0, 3731.0, "Thanks everyone.")));
+        assert_eq!(parse_line("no timestamp"), None);
+    }
+
+    #[test]
+    fn test_transcript_chunks_record_time_ranges() {
+        let doc = Document::new("meeting.mp3".to_string(), FileType::Audio, "hash".to_string(), 100);
+        let text = transcript().to_text();
+
+        let chunks = transcript_chunks(&doc, &text, 1000);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source.time_range, Some((0.0, 3731.0)));
+
+        let chunks = transcript_chunks(&doc, &text, 60);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].source.time_range, Some((4.0, 10.0)));
+        assert_eq!(chunks[1].content, "[00:00:04 - 00:00:10] Revenue grew twelve percent.");
+        assert_eq!(&text[chunks[1].char_start..chunks[1].char_end], chunks[1].content);
+        assert_eq!(chunks[2].chunk_index, 2);
+    }
+
+    #[test]
+    fn test_parse_backend_responses() {
+        let api: ApiTranscription = serde_json::from_str(
+            r#"{"text":"Hi there","language":"english","segments":[{"id":0,"start":0.0,"end":1.5,"text":" Hi there"}]}"#,
+        )
+        .unwrap();
+        let transcript = Transcript::from(api);
+        assert_eq!(transcript.segments.len(), 1);
+        assert_eq!(transcript.language.as_deref(), Some("english"));
+
+        let plain: ApiTranscription = serde_json::from_str(r#"{"text":"Hi there","duration":2.0}"#).unwrap();
+        assert_eq!(Transcript::from(plain).segments[0].end, 2.0);
+
+        let cpp: WhisperCppOutput = serde_json::from_str(
+            r#"{"result":{"language":"en"},"transcription":[{"timestamps":{"from":"00:00:00,000","to":"00:00:02,500"},"offsets":{"from":0,"to":2500},"text":" Hello"}]}"#,
+        )
+        .unwrap();
+        let transcript = Transcript::from(cpp);
+        assert_eq!(transcript.segments[0].end, 2.5);
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Chat integrations
+//!
+//! Slack and Teams bots forward questions asked in chat to the query pipeline
+//! and post the answers, as plain text, with links to the cited sources. The
+//! platforms call the endpoints in `server::routes::integrations`; requests
+//! are verified with the platform's signature, and questions are answered as
+//! the principal of the integration's API key, so document access, budgets
+//! and request limits apply as for API callers.
+
+pub mod slack;
+pub mod teams;
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::engine::RagEngine;
+use crate::error::{Error, Result};
+use crate::server::auth;
+use crate::server::state::AppState;
+use crate::types::query::{AnswerFormat, QueryRequest};
+use crate::types::response::QueryResponse;
+
+/// Timeout for posting answers to the platforms
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reply when a question can't be answered
+const ERROR_REPLY: &str = "Sorry, I couldn't answer that right now. Please try again later.";
+
+/// A cited source and the link opening it, when there is one
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLink {
+    /// Filename, with the page when known
+    pub label: String,
+    pub url: Option<String>,
+}
+
+/// Answer a question asked in chat, as the principal of `api_key`
+pub async fn answer(state: &AppState, question: &str, api_key: Option<&str>, collection: Option<&str>) -> Result<QueryResponse> {
+    let config = state.config();
+    let principal = match api_key {
+        Some(key) => Some(
+            auth::key_principal(&config.auth, key)
+                .ok_or_else(|| Error::Unauthorized("Integration api_key is not one of auth.api_keys".to_string()))?,
+        ),
+        None if config.auth.enabled => {
+            return Err(Error::Unauthorized("Integration has no api_key while auth is enabled".to_string()));
+        }
+        None => None,
+    };
+
+    let mut request = QueryRequest::new(question).with_format(AnswerFormat::Plain).with_principal(principal);
+    request.collection = collection.map(String::from);
+    RagEngine::from_state(state.clone()).query(request).await
+}
+
+/// Reply text for a failed answer (the error itself is logged)
+pub fn error_reply(error: &Error) -> String {
+    tracing::warn!("Chat question failed: {}", error);
+    match error {
+        Error::QueryRejected(reason) | Error::BudgetExceeded(reason) => format!("Sorry, I can't answer that: {}", reason),
+        _ => ERROR_REPLY.to_string(),
+    }
+}
+
+/// Sources cited by an answer, one per document page
+///
+/// Links point at the server's original-file endpoint when `public_url` is
+/// set, else at the document's own URL (GCS) if it has one.
+pub fn source_links(response: &QueryResponse, public_url: Option<&str>) -> Vec<SourceLink> {
+    let mut links: Vec<SourceLink> = Vec::new();
+    for citation in &response.citations {
+        let label = match citation.page_number {
+            Some(page) => format!("{}, p. {}", citation.filename, page),
+            None => citation.filename.clone(),
+        };
+        if links.iter().any(|link| link.label == label) {
+            continue;
+        }
+        let url = match (public_url, &citation.location) {
+            (Some(base), Some(location)) => Some(format!("{}{}", base.trim_end_matches('/'), location.original)),
+            _ => citation.document_url.clone(),
+        };
+        links.push(SourceLink { label, url });
+    }
+    links
+}
+
+/// Client for posting to the platforms
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| Client::builder().timeout(POST_TIMEOUT).build().unwrap_or_default())
+}
@@ -0,0 +1,266 @@
+//! Slack Events API and slash commands
+//!
+//! Requests carry `X-Slack-Signature`, `v0=` and the hex HMAC-SHA256 of
+//! `"v0:{timestamp}:{body}"` under the app's signing secret. Slack wants an
+//! answer within 3 seconds, so events and commands are acknowledged at once:
+//! mentions and direct messages are answered in a thread with
+//! `chat.postMessage`, slash commands through their `response_url`.
+
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{client, source_links};
+use crate::error::{Error, Result};
+use crate::types::response::QueryResponse;
+
+/// Header with `v0=<hex HMAC>`
+const SIGNATURE_HEADER: &str = "x-slack-signature";
+/// Header with the signing timestamp (Unix seconds)
+const TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+/// Header set on redelivered events
+const RETRY_HEADER: &str = "x-slack-retry-num";
+/// Oldest request accepted, against replays
+const MAX_AGE_SECS: i64 = 300;
+const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// Whether a request was signed with the signing secret within the last
+/// five minutes of `now` (Unix seconds)
+pub fn verify(signing_secret: &str, headers: &HeaderMap, body: &[u8], now: i64) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(timestamp), Some(signature)) = (header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)) else {
+        return false;
+    };
+    let Some(signature) = signature.strip_prefix("v0=").and_then(|hex_mac| hex::decode(hex_mac).ok()) else {
+        return false;
+    };
+    if timestamp.parse::<i64>().map_or(true, |at| (now - at).abs() > MAX_AGE_SECS) {
+        return false;
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(b"v0:");
+    mac.update(timestamp.as_bytes());
+    mac.update(b":");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Whether Slack is redelivering an event it already sent
+pub fn is_retry(headers: &HeaderMap) -> bool {
+    headers.contains_key(RETRY_HEADER)
+}
+
+/// Body of an Events API request
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventEnvelope {
+    /// Sent once when the request URL is configured
+    UrlVerification { challenge: String },
+    EventCallback { event: Event },
+    #[serde(other)]
+    Other,
+}
+
+/// A Slack event
+#[derive(Debug, Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub channel_type: Option<String>,
+    #[serde(default)]
+    pub ts: Option<String>,
+    #[serde(default)]
+    pub thread_ts: Option<String>,
+    #[serde(default)]
+    pub bot_id: Option<String>,
+    #[serde(default)]
+    pub subtype: Option<String>,
+}
+
+impl Event {
+    /// Question of a mention or direct message, `None` for other events and
+    /// for messages of bots (including this one)
+    pub fn question(&self) -> Option<String> {
+        let asked = match self.kind.as_str() {
+            "app_mention" => true,
+            "message" => self.channel_type.as_deref() == Some("im"),
+            _ => false,
+        };
+        if !asked || self.bot_id.is_some() || self.subtype.is_some() {
+            return None;
+        }
+        let question = question_text(&self.text);
+        (!question.is_empty()).then_some(question)
+    }
+
+    /// Thread to answer in (the message's own when it starts one)
+    pub fn reply_thread(&self) -> Option<&str> {
+        self.thread_ts.as_deref().or(self.ts.as_deref())
+    }
+}
+
+/// A slash command invocation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlashCommand {
+    pub command: String,
+    pub text: String,
+    pub response_url: String,
+    pub user_id: String,
+}
+
+/// Read a slash command from its form body
+pub fn parse_command(body: &[u8]) -> Option<SlashCommand> {
+    let mut command = SlashCommand {
+        command: String::new(),
+        text: String::new(),
+        response_url: String::new(),
+        user_id: String::new(),
+    };
+    for pair in std::str::from_utf8(body).ok()?.split('&') {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = form_decode(value)?;
+        match name {
+            "command" => command.command = value,
+            "text" => command.text = question_text(&value),
+            "response_url" => command.response_url = value,
+            "user_id" => command.user_id = value,
+            _ => {}
+        }
+    }
+    (!command.response_url.is_empty()).then_some(command)
+}
+
+/// Decode a form value (`+` for spaces, `%XX` escapes)
+fn form_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = value.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 2;
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Message text without user mentions (`<@U123>`)
+fn question_text(text: &str) -> String {
+    let mention = Regex::new(r"<@[A-Z0-9]+(?:\|[^>]*)?>").expect("Invalid regex");
+    let text = mention.replace_all(text, " ");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The answer and its sources as Slack `mrkdwn`
+pub fn answer_message(response: &QueryResponse, public_url: Option<&str>) -> String {
+    let mut message = escape(&response.answer);
+    let sources = source_links(response, public_url);
+    if !sources.is_empty() {
+        message.push_str("\n\n*Sources*");
+        for source in sources {
+            let label = escape(&source.label).replace('|', "/");
+            match source.url {
+                Some(url) => message.push_str(&format!("\n• <{}|{}>", url, label)),
+                None => message.push_str(&format!("\n• {}", label)),
+            }
+        }
+    }
+    message
+}
+
+/// Escape the characters Slack reads as markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Post a message to a channel, in a thread if given
+pub async fn post_message(bot_token: &str, channel: &str, thread_ts: Option<&str>, text: &str) -> Result<()> {
+    let mut message = serde_json::json!({ "channel": channel, "text": text, "unfurl_links": false });
+    if let Some(thread_ts) = thread_ts {
+        message["thread_ts"] = thread_ts.into();
+    }
+    let reply: serde_json::Value = client()
+        .post(POST_MESSAGE_URL)
+        .bearer_auth(bot_token)
+        .json(&message)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    // Slack reports errors in the body of a 200 response
+    if reply["ok"].as_bool() != Some(true) {
+        return Err(Error::Internal(format!("Slack chat.postMessage failed: {}", reply["error"])));
+    }
+    Ok(())
+}
+
+/// Answer a slash command in the channel it was used in
+pub async fn respond(response_url: &str, text: &str) -> Result<()> {
+    client()
+        .post(response_url)
+        .json(&serde_json::json!({ "response_type": "in_channel", "text": text }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        let secret = "8f742231b10e8888abcd99yyyzzz85a5";
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&command=%2Fask&text=What+is+the+refund+policy%3F&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1";
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(b"v0:1531420618:");
+        mac.update(body);
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, "1531420618".parse().unwrap());
+        headers.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        assert!(verify(secret, &headers, body, 1_531_420_700));
+        assert!(!verify(secret, &headers, b"tampered", 1_531_420_700));
+        assert!(!verify("other", &headers, body, 1_531_420_700));
+        // Replayed after more than five minutes
+        assert!(!verify(secret, &headers, body, 1_531_421_000));
+
+        let command = parse_command(body).unwrap();
+        assert_eq!(command.command, "/ask");
+        assert_eq!(command.text, "What is the refund policy?");
+        assert_eq!(command.response_url, "https://hooks.slack.com/commands/1");
+    }
+
+    #[test]
+    fn test_event_question() {
+        let envelope: EventEnvelope = serde_json::from_str(
+            r#"{"type": "event_callback", "event": {"type": "app_mention", "text": "<@U0LAN0Z89> where is the   VPN guide?",
+                "channel": "C1", "ts": "1.2"}}"#,
+        )
+        .unwrap();
+        let EventEnvelope::EventCallback { event } = envelope else { panic!("not an event callback") };
+        assert_eq!(event.question().as_deref(), Some("where is the VPN guide?"));
+        assert_eq!(event.reply_thread(), Some("1.2"));
+
+        // The bot's own answers are not questions
+        let answer: Event = serde_json::from_str(r#"{"type": "message", "channel_type": "im", "text": "hi", "bot_id": "B1"}"#).unwrap();
+        assert_eq!(answer.question(), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Microsoft Teams outgoing webhook
+//!
+//! Teams POSTs a message activity when the webhook is mentioned, with
+//! `Authorization: HMAC <base64>`, the HMAC-SHA256 of the body under the
+//! base64-decoded security token. The reply is the response to that request,
+//! which Teams drops after 5 seconds; slower answers go to the channel's
+//! incoming webhook when one is configured.
+
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{client, source_links};
+use crate::error::Result;
+use crate::types::response::QueryResponse;
+
+/// Whether a request was signed with the webhook's security token
+pub fn verify(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let signature = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("HMAC "))
+        .and_then(|signature| STANDARD.decode(signature.trim()).ok());
+    let (Some(signature), Ok(key)) = (signature, STANDARD.decode(secret.trim())) else {
+        return false;
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Message activity sent by Teams
+#[derive(Debug, Deserialize)]
+pub struct Activity {
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// Message as HTML, starting with the `<at>` mention of the webhook
+    #[serde(default)]
+    pub text: String,
+}
+
+impl Activity {
+    /// Question of a message, without the mention and markup
+    pub fn question(&self) -> Option<String> {
+        if self.kind != "message" {
+            return None;
+        }
+        let mention = Regex::new(r"(?s)<at>.*?</at>").expect("Invalid regex");
+        let tag = Regex::new(r"<[^>]+>").expect("Invalid regex");
+        let text = mention.replace_all(&self.text, " ");
+        let text = tag.replace_all(&text, " ");
+        let text = text
+            .replace("&nbsp;", " ")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&amp;", "&");
+        let question = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!question.is_empty()).then_some(question)
+    }
+}
+
+/// Reply to an outgoing webhook request
+pub fn reply(text: &str) -> serde_json::Value {
+    serde_json::json!({ "type": "message", "text": text })
+}
+
+/// The answer and its sources as Teams Markdown
+///
+/// Teams joins single line breaks, so lines are separated by blank ones.
+pub fn answer_message(response: &QueryResponse, public_url: Option<&str>) -> String {
+    let mut message = response
+        .answer
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let sources = source_links(response, public_url);
+    if !sources.is_empty() {
+        message.push_str("\n\n**Sources**\n");
+        for source in sources {
+            let label = source.label.replace(['[', ']'], "");
+            match source.url {
+                Some(url) => message.push_str(&format!("\n- [{}]({})", label, url)),
+                None => message.push_str(&format!("\n- {}", label)),
+            }
+        }
+    }
+    message
+}
+
+/// Post a message to a channel through its incoming webhook
+pub async fn post_to_channel(incoming_webhook_url: &str, text: &str) -> Result<()> {
+    client()
+        .post(incoming_webhook_url)
+        .json(&serde_json::json!({ "text": text }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_and_question() {
+        let secret = STANDARD.encode(b"teams-webhook-token");
+        let body = br#"{"type": "message", "text": "<at>DocsBot</at>&nbsp;What is the <b>travel</b> policy?"}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"teams-webhook-token").unwrap();
+        mac.update(body);
+        let signature = STANDARD.encode(mac.finalize().into_bytes());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("HMAC {}", signature).parse().unwrap());
+        assert!(verify(&secret, &headers, body));
+        assert!(!verify(&secret, &headers, b"{}"));
+        assert!(!verify(&STANDARD.encode(b"other"), &headers, body));
+
+        let activity: Activity = serde_json::from_slice(body).unwrap();
+        assert_eq!(activity.question().as_deref(), Some("What is the travel policy?"));
+    }
+}
@@ -33,6 +33,8 @@ pub struct CachedCitation {
     pub chunk_id: Uuid,
     pub document_id: Uuid,
     pub filename: String,
+    pub title: Option<String>,
+    pub date: Option<String>,
     pub snippet: String,
     pub similarity_score: f32,
 }
@@ -173,6 +175,11 @@ impl AnswerCache {
         tracing::debug!("Cached answer: {}", &key[..12]);
     }
 
+    /// Number of cached answers citing a document
+    pub fn count_by_document(&self, doc_id: &Uuid) -> usize {
+        self.doc_to_questions.read().get(doc_id).map_or(0, |keys| keys.len())
+    }
+
     /// Invalidate all cache entries that cite a specific document
     ///
     /// Called when a document is modified or deleted
@@ -268,6 +275,8 @@ mod tests {
             chunk_id: Uuid::new_v4(),
             document_id: doc_id,
             filename: "test.pdf".to_string(),
+            title: None,
+            date: None,
             snippet: "test content".to_string(),
             similarity_score: 0.9,
         }];
@@ -292,6 +301,8 @@ mod tests {
             chunk_id: Uuid::new_v4(),
             document_id: doc_id,
             filename: "test.pdf".to_string(),
+            title: None,
+            date: None,
             snippet: "test content".to_string(),
             similarity_score: 0.9,
         }];
@@ -0,0 +1,214 @@
+//! A/B experiments on query configuration
+//!
+//! Queries are assigned a configured variant, which overrides retrieval
+//! settings on the request and selects the generation model and prompt.
+//! Feedback on the stored interactions is aggregated per variant.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::{ExperimentsConfig, VariantConfig};
+use crate::error::{Error, Result};
+use crate::types::QueryRequest;
+
+use super::knowledge_store::QAInteraction;
+
+/// Resolve the request's variant and apply its overrides
+///
+/// A named variant must be registered. Without one, a variant is drawn by
+/// weight when experiments are enabled: at random for anonymous callers and
+/// stable per principal otherwise, so a user keeps seeing one setup.
+pub fn assign(config: &ExperimentsConfig, request: &mut QueryRequest) -> Result<()> {
+    let variant = match request.variant.as_deref() {
+        Some(name) => Some(
+            config
+                .variant(name)
+                .ok_or_else(|| Error::Config(format!("Unknown experiment variant '{}'", name)))?,
+        ),
+        None if config.enabled => {
+            let seed = match request.principal {
+                Some(ref principal) => {
+                    let hash = Sha256::digest(principal.id.as_bytes());
+                    u64::from_be_bytes(hash[..8].try_into().unwrap_or_default())
+                }
+                // Random v4 UUIDs are uniformly distributed, no extra RNG needed
+                None => Uuid::new_v4().as_u128() as u64,
+            };
+            choose(&config.variants, seed)
+        }
+        None => None,
+    };
+
+    if let Some(variant) = variant {
+        apply(variant, request);
+    }
+    Ok(())
+}
+
+/// Variant for a seed, with probability proportional to weight
+fn choose(variants: &[VariantConfig], seed: u64) -> Option<&VariantConfig> {
+    let total: f64 = variants.iter().map(|v| v.weight.max(0.0)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut point = (seed % 1_000_000) as f64 / 1_000_000.0 * total;
+    for variant in variants.iter().filter(|v| v.weight > 0.0) {
+        if point < variant.weight {
+            return Some(variant);
+        }
+        point -= variant.weight;
+    }
+    variants.iter().rev().find(|v| v.weight > 0.0)
+}
+
+/// Override the request's settings with the variant's
+fn apply(variant: &VariantConfig, request: &mut QueryRequest) {
+    request.variant = Some(variant.name.clone());
+    if let Some(top_k) = variant.top_k {
+        request.top_k = top_k;
+    }
+    if let Some(threshold) = variant.similarity_threshold {
+        request.similarity_threshold = threshold;
+    }
+    if let Some(rerank) = variant.rerank {
+        request.rerank = rerank;
+    }
+    if let Some(strategy) = variant.retrieval_strategy {
+        request.retrieval_strategy = strategy;
+    }
+}
+
+/// Answers and feedback for one variant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct VariantReport {
+    /// Variant name (`null` for answers served without a variant)
+    pub variant: Option<String>,
+    /// Answers served
+    pub answers: usize,
+    /// Answers with feedback
+    pub rated: usize,
+    pub positive: usize,
+    pub neutral: usize,
+    pub negative: usize,
+    /// Share of rated answers with positive feedback
+    pub positive_rate: Option<f32>,
+    /// Mean similarity of the top retrieved chunk
+    pub mean_relevance: f32,
+}
+
+/// Per-variant answer and feedback counts, ordered by variant name
+pub fn report(interactions: &[QAInteraction]) -> Vec<VariantReport> {
+    let mut reports: BTreeMap<Option<String>, (VariantReport, f32)> = BTreeMap::new();
+
+    for interaction in interactions {
+        let (report, relevance) = reports.entry(interaction.variant.clone()).or_insert_with(|| {
+            let report = VariantReport {
+                variant: interaction.variant.clone(),
+                ..Default::default()
+            };
+            (report, 0.0)
+        });
+        report.answers += 1;
+        *relevance += interaction.relevance_score;
+        match interaction.feedback_score {
+            Some(score) if score > 0 => report.positive += 1,
+            Some(score) if score < 0 => report.negative += 1,
+            Some(_) => report.neutral += 1,
+            None => continue,
+        }
+        report.rated += 1;
+    }
+
+    reports
+        .into_values()
+        .map(|(mut report, relevance)| {
+            report.mean_relevance = relevance / report.answers as f32;
+            report.positive_rate = (report.rated > 0).then(|| report.positive as f32 / report.rated as f32);
+            report
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(name: &str, weight: f64) -> VariantConfig {
+        VariantConfig {
+            name: name.to_string(),
+            weight,
+            top_k: None,
+            similarity_threshold: None,
+            rerank: None,
+            retrieval_strategy: None,
+            model: None,
+            prompt_template: None,
+        }
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut wide = variant("wide", 3.0);
+        wide.top_k = Some(25);
+        let config = ExperimentsConfig {
+            enabled: true,
+            variants: vec![variant("control", 1.0), wide, variant("off", 0.0)],
+        };
+
+        let mut request = QueryRequest::new("q").with_variant("wide");
+        assign(&config, &mut request).unwrap();
+        assert_eq!(request.top_k, 25);
+
+        assert!(assign(&config, &mut QueryRequest::new("q").with_variant("missing")).is_err());
+
+        assert_eq!(choose(&config.variants, 0).unwrap().name, "control");
+        assert_eq!(choose(&config.variants, 249_999).unwrap().name, "control");
+        assert_eq!(choose(&config.variants, 250_000).unwrap().name, "wide");
+        assert_eq!(choose(&config.variants, 999_999).unwrap().name, "wide");
+
+        let principal = crate::types::Principal::new("alice", Vec::new());
+        let assigned: Vec<Option<String>> = (0..3)
+            .map(|_| {
+                let mut request = QueryRequest::new("q").with_principal(Some(principal.clone()));
+                assign(&config, &mut request).unwrap();
+                request.variant
+            })
+            .collect();
+        assert!(assigned[0].is_some() && assigned.iter().all(|v| *v == assigned[0]));
+    }
+
+    #[test]
+    fn test_report() {
+        let interaction = |variant: Option<&str>, feedback: Option<i32>| QAInteraction {
+            id: Uuid::new_v4(),
+            question: "q".to_string(),
+            answer: "a".to_string(),
+            citations_used: Vec::new(),
+            relevance_score: 0.5,
+            feedback_score: feedback,
+            created_at: chrono::Utc::now(),
+            document_ids: Vec::new(),
+            variant: variant.map(String::from),
+            synthetic: None,
+            correction: None,
+        };
+        let reports = report(&[
+            interaction(Some("a"), Some(1)),
+            interaction(Some("a"), Some(-1)),
+            interaction(Some("a"), None),
+            interaction(None, None),
+        ]);
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].variant, None);
+        assert_eq!(reports[0].positive_rate, None);
+        assert_eq!(reports[1].answers, 3);
+        assert_eq!(reports[1].rated, 2);
+        assert_eq!(reports[1].positive_rate, Some(0.5));
+        assert_eq!(reports[1].mean_relevance, 0.5);
+    }
+}
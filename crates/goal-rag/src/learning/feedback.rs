@@ -1,10 +1,12 @@
 //! Feedback types for learning
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Type of feedback
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 pub enum FeedbackType {
     /// Answer was helpful
     Positive,
@@ -25,7 +27,7 @@ impl FeedbackType {
 }
 
 /// Feedback request from user
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Feedback {
     /// The interaction ID to provide feedback for
     pub interaction_id: Uuid,
@@ -33,4 +35,43 @@ pub struct Feedback {
     pub feedback_type: FeedbackType,
     /// Optional comment
     pub comment: Option<String>,
+    /// What the answer should have been
+    #[serde(default)]
+    pub corrected_answer: Option<String>,
+    /// Documents that answer the question
+    #[serde(default)]
+    pub document_ids: Vec<Uuid>,
+}
+
+impl Feedback {
+    /// Whether the feedback corrects the answer rather than only rating it
+    pub fn is_correction(&self) -> bool {
+        self.corrected_answer.as_deref().is_some_and(|answer| !answer.trim().is_empty()) || !self.document_ids.is_empty()
+    }
+}
+
+/// A correction of an answer
+///
+/// Once verified, a corrected answer is returned, flagged as curated, for
+/// the same question instead of a generated one; a correction naming only
+/// documents makes retrieval for the question search those documents.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Correction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub document_ids: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Principal that submitted it (none without authentication)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submitted_by: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+    /// Corrections by admins are verified when submitted, others by an admin later
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<DateTime<Utc>>,
 }
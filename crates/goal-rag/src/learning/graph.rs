@@ -0,0 +1,488 @@
+//! Knowledge graph over ingested documents
+//!
+//! A background task extracts (subject, relation, object) triples from chunks
+//! the graph builder hasn't seen yet and stores them in the `graph_triples`
+//! table, keyed by normalized entity names. Deleting a chunk deletes its
+//! triples. At query time the entities named in a question are looked up and
+//! their neighborhood is followed for a few hops; the chunks the relations
+//! came from are added to the retrieved context, which lets answers combine
+//! facts from documents that never mention each other.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::{GraphConfig, GraphExtractorKind};
+use crate::error::{Error, Result};
+use crate::generation::PromptBuilder;
+use crate::processing::keywords::{capitalized_entities, is_stopword};
+use crate::processing::{TaskHandle, TaskKind};
+use crate::providers::vector_store::VectorSearchResult;
+use crate::server::state::AppState;
+use crate::storage::{ChunkContentRecord, FileRegistryDb};
+
+/// Unprocessed chunks loaded per round
+const BATCH_SIZE: usize = 64;
+
+/// Longest entity name or relation kept, in characters
+const MAX_TERM_CHARS: usize = 120;
+
+/// Longest entity looked up in a question, in words
+const MAX_ENTITY_WORDS: usize = 5;
+
+/// Relation used by the co-occurrence extractor
+const MENTIONED_WITH: &str = "mentioned_with";
+
+/// A (subject, relation, object) fact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Triple {
+    pub subject: String,
+    pub relation: String,
+    pub object: String,
+}
+
+/// A stored triple and the chunk it was extracted from
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct GraphEdge {
+    pub subject: String,
+    pub relation: String,
+    pub object: String,
+    pub chunk_id: Uuid,
+    pub document_id: Uuid,
+}
+
+/// Normalized entity name: lower-cased, single-spaced, without surrounding punctuation
+pub fn entity_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Outcome of a graph build
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GraphBuildReport {
+    /// Task id for `/api/admin/tasks`
+    pub task_id: Uuid,
+    /// Chunks processed
+    pub chunks: usize,
+    /// Triples stored
+    pub triples: usize,
+    /// Chunks whose extraction failed (retried on the next run)
+    pub failed: usize,
+    /// Chunks still unprocessed when the run finished
+    pub remaining: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Builds the knowledge graph, one run at a time
+pub struct GraphBuilder {
+    config: GraphConfig,
+    running: AtomicBool,
+    last: RwLock<Option<GraphBuildReport>>,
+}
+
+impl GraphBuilder {
+    pub fn new(config: GraphConfig) -> Self {
+        Self {
+            config,
+            running: AtomicBool::new(false),
+            last: RwLock::new(None),
+        }
+    }
+
+    /// Whether the graph is built and used
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Report of the current or most recent run
+    pub fn last_report(&self) -> Option<GraphBuildReport> {
+        self.last.read().clone()
+    }
+
+    /// Whether a build is in progress
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start processing unprocessed chunks in the background, first dropping
+    /// the whole graph if `rebuild` is set
+    pub fn start(self: &Arc<Self>, state: AppState, rebuild: bool) -> Result<GraphBuildReport> {
+        if !self.is_enabled() {
+            return Err(Error::Config("Knowledge graph is not enabled".to_string()));
+        }
+
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::Config("A graph build is already running".to_string()));
+        }
+
+        let pending = match rebuild_and_count(state.database(), rebuild) {
+            Ok(pending) => pending,
+            Err(e) => {
+                self.running.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let task = state.tasks().start(TaskKind::GraphBuild, "extracting", pending, "chunks");
+        let report = GraphBuildReport {
+            task_id: task.id(),
+            chunks: 0,
+            triples: 0,
+            failed: 0,
+            remaining: pending,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        *self.last.write() = Some(report.clone());
+
+        let builder = Arc::clone(self);
+        let run_report = report.clone();
+        tokio::spawn(async move {
+            builder.run(&state, task, run_report).await;
+        });
+
+        Ok(report)
+    }
+
+    /// Process new chunks every `interval`
+    pub fn spawn_periodic(self: &Arc<Self>, state: AppState, interval: Duration) {
+        let builder = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match state.database().count_chunks_without_graph() {
+                    Ok(0) => continue,
+                    Ok(pending) => tracing::info!("Extracting graph triples from {} new chunks", pending),
+                    Err(e) => {
+                        tracing::warn!("Failed to count chunks without graph triples: {}", e);
+                        continue;
+                    }
+                }
+                if let Err(e) = builder.start(state.clone(), false) {
+                    tracing::debug!("Skipping scheduled graph build: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Triples of a chunk's text
+    pub async fn extract(&self, state: &AppState, text: &str) -> Result<Vec<Triple>> {
+        let max = self.config.max_triples_per_chunk;
+        let triples = match self.config.extractor {
+            GraphExtractorKind::Llm => {
+                let prompt = PromptBuilder::build_triple_prompt(text, max);
+                let response = state.llm_provider().complete(&prompt).await?;
+                parse_llm_triples(&response)
+                    .ok_or_else(|| Error::Internal("Unparseable triple extraction response".to_string()))?
+            }
+            GraphExtractorKind::Cooccurrence => cooccurrence_triples(text),
+        };
+        Ok(clean_triples(triples, max))
+    }
+
+    async fn run(&self, state: &AppState, task: TaskHandle, mut report: GraphBuildReport) {
+        let result = self.build(state, &task, &mut report).await;
+
+        report.remaining = state.database().count_chunks_without_graph().unwrap_or(report.remaining);
+        report.completed_at = Some(Utc::now());
+        match result {
+            Ok(()) => {
+                task.complete();
+                tracing::info!(
+                    "Graph build complete: {} chunks, {} triples, {} failed, {} remaining",
+                    report.chunks, report.triples, report.failed, report.remaining
+                );
+            }
+            Err(e) => {
+                report.error = Some(e.to_string());
+                task.fail(e.to_string());
+                tracing::error!("Graph build failed: {}", e);
+            }
+        }
+
+        *self.last.write() = Some(report);
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    async fn build(&self, state: &AppState, task: &TaskHandle, report: &mut GraphBuildReport) -> Result<()> {
+        let database = state.database();
+        let mut total = report.remaining;
+
+        // Chunks that fail stay unprocessed, page past them
+        let mut tried = HashSet::new();
+        let mut skipped = 0;
+        loop {
+            let batch: Vec<ChunkContentRecord> = database
+                .list_chunks_without_graph(BATCH_SIZE, skipped)?
+                .into_iter()
+                .filter(|chunk| tried.insert(chunk.id))
+                .collect();
+            if batch.is_empty() {
+                break;
+            }
+
+            for chunk in batch {
+                task.set_current(Some(chunk.filename.clone()));
+                match self.extract(state, &chunk.content).await {
+                    Ok(triples) => {
+                        database.replace_graph_triples(&chunk.id, &chunk.document_id, &triples)?;
+                        report.chunks += 1;
+                        report.triples += triples.len();
+                    }
+                    Err(e) => {
+                        tracing::warn!("[{}] Triple extraction failed for chunk {}: {}", chunk.filename, chunk.id, e);
+                        report.failed += 1;
+                        skipped += 1;
+                    }
+                }
+                total = total.max(tried.len());
+                task.report(report.chunks + report.failed, total);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn rebuild_and_count(database: &FileRegistryDb, rebuild: bool) -> Result<usize> {
+    if rebuild {
+        database.clear_graph()?;
+    }
+    database.count_chunks_without_graph()
+}
+
+/// Triples around the seed entities, following relations for up to `hops` steps
+///
+/// Edges are returned nearest first.
+pub fn neighborhood(database: &FileRegistryDb, seeds: &[String], hops: usize, limit: usize) -> Result<Vec<GraphEdge>> {
+    let mut visited: HashSet<String> = seeds.iter().map(|s| entity_key(s)).filter(|k| !k.is_empty()).collect();
+    let mut frontier: Vec<String> = visited.iter().cloned().collect();
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+
+    for _ in 0..hops.max(1) {
+        if frontier.is_empty() || edges.len() >= limit {
+            break;
+        }
+        let mut next = Vec::new();
+        for edge in database.graph_edges(&frontier, limit)? {
+            for key in [entity_key(&edge.subject), entity_key(&edge.object)] {
+                if visited.insert(key.clone()) {
+                    next.push(key);
+                }
+            }
+            let id = (edge.chunk_id, edge.subject.clone(), edge.relation.clone(), edge.object.clone());
+            if edges.len() < limit && seen.insert(id) {
+                edges.push(edge);
+            }
+        }
+        frontier = next;
+    }
+
+    Ok(edges)
+}
+
+/// Graph entities named in a question (runs of up to five words)
+pub fn entities_in(database: &FileRegistryDb, question: &str) -> Result<Vec<String>> {
+    let words: Vec<String> = question
+        .split_whitespace()
+        .map(entity_key)
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut candidates = Vec::new();
+    for start in 0..words.len() {
+        for end in start + 1..=(start + MAX_ENTITY_WORDS).min(words.len()) {
+            let run = &words[start..end];
+            if run.iter().all(|w| is_stopword(w)) {
+                continue;
+            }
+            candidates.push(run.join(" "));
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+
+    let known = database.known_graph_entities(&candidates)?;
+    let mut entities: Vec<String> = known.into_iter().collect();
+    entities.sort();
+    Ok(entities)
+}
+
+/// Add chunks related to the question's entities to retrieval results
+///
+/// Added chunks get `graph_chunk_similarity` and are limited to documents in
+/// `document_filter` when one is given. Does nothing unless the graph and
+/// retrieval augmentation are enabled.
+pub fn augment_results(
+    state: &AppState,
+    question: &str,
+    document_filter: Option<&[Uuid]>,
+    results: &mut Vec<VectorSearchResult>,
+) {
+    let rag_config = state.config();
+    let config = &rag_config.graph;
+    if !config.enabled || !config.augment_retrieval || config.max_graph_chunks == 0 {
+        return;
+    }
+
+    let edges = entities_in(state.database(), question).and_then(|seeds| {
+        if seeds.is_empty() {
+            return Ok(Vec::new());
+        }
+        tracing::debug!("Question mentions graph entities {:?}", seeds);
+        neighborhood(state.database(), &seeds, config.max_hops, config.max_graph_chunks * 10)
+    });
+    let edges = match edges {
+        Ok(edges) => edges,
+        Err(e) => {
+            tracing::warn!("Graph lookup failed: {}", e);
+            return;
+        }
+    };
+
+    let mut present: HashSet<Uuid> = results.iter().map(|r| r.chunk.id).collect();
+    let mut added = 0;
+    for edge in edges {
+        if added >= config.max_graph_chunks {
+            break;
+        }
+        if document_filter.is_some_and(|filter| !filter.contains(&edge.document_id)) || !present.insert(edge.chunk_id) {
+            continue;
+        }
+        if let Some(chunk) = state.get_chunk(&edge.chunk_id) {
+            results.push(VectorSearchResult::new(chunk, config.graph_chunk_similarity));
+            added += 1;
+        }
+    }
+    if added > 0 {
+        tracing::debug!("Added {} chunks from the knowledge graph", added);
+    }
+}
+
+/// Triples linking the capitalized-phrase entities of a text
+fn cooccurrence_triples(text: &str) -> Vec<Triple> {
+    let entities = capitalized_entities(text, 10);
+    let mut triples = Vec::new();
+    for (i, subject) in entities.iter().enumerate() {
+        for object in &entities[i + 1..] {
+            triples.push(Triple {
+                subject: subject.clone(),
+                relation: MENTIONED_WITH.to_string(),
+                object: object.clone(),
+            });
+        }
+    }
+    triples
+}
+
+/// Drop empty, self-referencing and duplicate triples, trim long terms, keep `max`
+fn clean_triples(triples: Vec<Triple>, max: usize) -> Vec<Triple> {
+    let trim = |s: &str| s.trim().chars().take(MAX_TERM_CHARS).collect::<String>();
+    let mut seen = HashSet::new();
+    triples
+        .into_iter()
+        .map(|t| Triple {
+            subject: trim(&t.subject),
+            relation: trim(&t.relation),
+            object: trim(&t.object),
+        })
+        .filter(|t| {
+            let (subject, object) = (entity_key(&t.subject), entity_key(&t.object));
+            !subject.is_empty()
+                && !object.is_empty()
+                && !t.relation.is_empty()
+                && subject != object
+                && seen.insert((subject, t.relation.to_lowercase(), object))
+        })
+        .take(max)
+        .collect()
+}
+
+/// Triples in a model response, as `{"triples": [...]}` or a bare array
+fn parse_llm_triples(response: &str) -> Option<Vec<Triple>> {
+    let start = response.find('[')?;
+    let end = response.rfind(']')?;
+    serde_json::from_str(response.get(start..=end)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{wait_until, TestApp};
+
+    #[tokio::test]
+    async fn test_run_keeps_its_own_report() {
+        let app = TestApp::with_config(|config| {
+            config.graph.enabled = true;
+            config.graph.extractor = GraphExtractorKind::Cooccurrence;
+        })
+        .await;
+        let builder = app.state.graph();
+        let report = builder.start(app.state.clone(), false).unwrap();
+
+        // The run used to read its report back from `last` and panic when it was gone
+        *builder.last.write() = None;
+        wait_until(|| !builder.is_running()).await;
+
+        let last = builder.last_report().expect("the run stores its report");
+        assert_eq!(last.task_id, report.task_id);
+        assert!(last.completed_at.is_some());
+        assert_eq!(last.error, None);
+    }
+
+    fn triple(subject: &str, relation: &str, object: &str) -> Triple {
+        Triple {
+            subject: subject.to_string(),
+            relation: relation.to_string(),
+            object: object.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_neighborhood_and_question_entities() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let (doc_a, doc_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (chunk_a, chunk_b) = (Uuid::new_v4(), Uuid::new_v4());
+        db.replace_graph_triples(&chunk_a, &doc_a, &[triple("Acme Corp", "acquired", "Globex")]).unwrap();
+        db.replace_graph_triples(&chunk_b, &doc_b, &[triple("Globex", "is headquartered in", "Berlin")]).unwrap();
+
+        assert_eq!(entities_in(&db, "Where is the company that Acme Corp. acquired?").unwrap(), vec!["acme corp"]);
+
+        // Two hops reach Berlin through Globex, one doesn't
+        let edges = neighborhood(&db, &["acme corp".to_string()], 2, 10).unwrap();
+        assert_eq!(edges.iter().map(|e| e.chunk_id).collect::<Vec<_>>(), vec![chunk_a, chunk_b]);
+        assert_eq!(neighborhood(&db, &["ACME  corp".to_string()], 1, 10).unwrap().len(), 1);
+
+        let parsed = parse_llm_triples(
+            r#"{"triples": [{"subject": "Acme", "relation": "owns", "object": "Globex"},
+                            {"subject": "Acme", "relation": "owns", "object": "acme."}]}"#,
+        )
+        .unwrap();
+        assert_eq!(clean_triples(parsed, 10), vec![triple("Acme", "owns", "Globex")]);
+        assert_eq!(
+            cooccurrence_triples("Acme Corp bought Globex in 2020."),
+            vec![triple("Acme Corp", MENTIONED_WITH, "Globex")]
+        );
+    }
+}
@@ -1,12 +1,16 @@
 //! Knowledge store for learning from Q&A interactions
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::RwLock;
 use uuid::Uuid;
 
+use crate::retrieval::similarity::CosineQuery;
+
+use super::feedback::Correction;
+
 /// A stored Q&A interaction for learning
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QAInteraction {
@@ -18,6 +22,32 @@ pub struct QAInteraction {
     pub feedback_score: Option<i32>,  // -1, 0, or 1 from user feedback
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub document_ids: Vec<Uuid>,
+    /// Experiment variant that served the answer
+    #[serde(default)]
+    pub variant: Option<String>,
+    /// Set on pairs generated from a chunk rather than answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synthetic: Option<SyntheticSource>,
+    /// Correction submitted with feedback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correction: Option<Correction>,
+}
+
+impl QAInteraction {
+    /// Corrected answer, once verified
+    pub fn verified_answer(&self) -> Option<&str> {
+        self.correction.as_ref().filter(|c| c.verified).and_then(|c| c.answer.as_deref())
+    }
+}
+
+/// Where a generated pair came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticSource {
+    pub chunk_id: Uuid,
+    /// Model that embedded the question
+    pub embedding_model: String,
+    /// Embedding of the question, matched against queries
+    pub embedding: Vec<f32>,
 }
 
 /// Knowledge store that persists learned Q&A pairs
@@ -71,6 +101,119 @@ impl KnowledgeStore {
         id
     }
 
+    /// Store generated pairs, saving once
+    pub fn store_synthetic(&self, pairs: Vec<QAInteraction>) {
+        if pairs.is_empty() {
+            return;
+        }
+        {
+            let mut index = self.question_index.write().unwrap();
+            let mut interactions = self.interactions.write().unwrap();
+            for pair in pairs {
+                for keyword in self.extract_keywords(&pair.question) {
+                    index.entry(keyword).or_default().push(pair.id);
+                }
+                interactions.insert(pair.id, pair);
+            }
+        }
+
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save knowledge store: {}", e);
+        }
+    }
+
+    /// Generated pairs, oldest first
+    pub fn synthetic_pairs(&self) -> Vec<QAInteraction> {
+        let mut pairs: Vec<QAInteraction> = self
+            .interactions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|i| i.synthetic.is_some())
+            .cloned()
+            .collect();
+        pairs.sort_by_key(|pair| pair.created_at);
+        pairs
+    }
+
+    /// Chunks that pairs were generated from
+    pub fn synthetic_chunk_ids(&self) -> HashSet<Uuid> {
+        self.interactions
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|i| i.synthetic.as_ref().map(|source| source.chunk_id))
+            .collect()
+    }
+
+    /// Drop the generated pairs `stale` picks, returning how many
+    pub fn remove_synthetic_where(&self, stale: impl Fn(&SyntheticSource) -> bool) -> usize {
+        self.remove_where(|i| i.synthetic.as_ref().is_some_and(&stale))
+    }
+
+    /// Drop interactions, generated pairs and corrections drawn from any of
+    /// the given documents, returning how many
+    pub fn remove_citing(&self, document_ids: &HashSet<Uuid>) -> usize {
+        self.remove_where(|i| {
+            i.document_ids.iter().any(|id| document_ids.contains(id))
+                || i.correction.as_ref().is_some_and(|c| c.document_ids.iter().any(|id| document_ids.contains(id)))
+        })
+    }
+
+    fn remove_where(&self, pick: impl Fn(&QAInteraction) -> bool) -> usize {
+        let removed: Vec<Uuid> = {
+            let mut interactions = self.interactions.write().unwrap();
+            let ids: Vec<Uuid> = interactions
+                .values()
+                .filter(|i| pick(i))
+                .map(|i| i.id)
+                .collect();
+            for id in &ids {
+                interactions.remove(id);
+            }
+            ids
+        };
+        if removed.is_empty() {
+            return 0;
+        }
+
+        let mut index = self.question_index.write().unwrap();
+        for ids in index.values_mut() {
+            ids.retain(|id| !removed.contains(id));
+        }
+        index.retain(|_, ids| !ids.is_empty());
+        drop(index);
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save knowledge store: {}", e);
+        }
+        removed.len()
+    }
+
+    /// Chunks of the generated questions closest to a query embedding
+    ///
+    /// Only questions embedded with `model` and at least `min_similarity`
+    /// similar are considered; each chunk is returned once, with its best
+    /// similarity, best first.
+    pub fn match_synthetic(&self, embedding: &[f32], model: &str, min_similarity: f32, limit: usize) -> Vec<(Uuid, f32)> {
+        let mut best: HashMap<Uuid, f32> = HashMap::new();
+        let query = CosineQuery::new(embedding);
+        for source in self.interactions.read().unwrap().values().filter_map(|i| i.synthetic.as_ref()) {
+            if source.embedding_model != model || source.embedding.len() != embedding.len() {
+                continue;
+            }
+            let similarity = query.score(&source.embedding);
+            if similarity >= min_similarity {
+                let entry = best.entry(source.chunk_id).or_insert(similarity);
+                *entry = entry.max(similarity);
+            }
+        }
+
+        let mut matches: Vec<(Uuid, f32)> = best.into_iter().collect();
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches.truncate(limit);
+        matches
+    }
+
     /// Find similar past questions
     pub fn find_similar(&self, question: &str, limit: usize) -> Vec<QAInteraction> {
         let keywords = self.extract_keywords(question);
@@ -94,6 +237,7 @@ impl KnowledgeStore {
             .into_iter()
             .take(limit)
             .filter_map(|(id, _)| interactions.get(&id).cloned())
+            .filter(|i| i.synthetic.is_none())  // Generated pairs were never answered
             .filter(|i| i.feedback_score.unwrap_or(0) >= 0)  // Only positive/neutral feedback
             .collect()
     }
@@ -111,6 +255,85 @@ impl KnowledgeStore {
         }
     }
 
+    /// Attach a correction to an interaction, replacing any earlier one
+    pub fn set_correction(&self, interaction_id: Uuid, correction: Correction) -> bool {
+        self.update(interaction_id, |interaction| interaction.correction = Some(correction))
+    }
+
+    /// Mark an interaction's correction verified
+    pub fn verify_correction(&self, interaction_id: Uuid, verified_by: Option<String>) -> bool {
+        let mut verified = false;
+        self.update(interaction_id, |interaction| {
+            if let Some(ref mut correction) = interaction.correction {
+                correction.verified = true;
+                correction.verified_by = verified_by;
+                correction.verified_at = Some(chrono::Utc::now());
+                verified = true;
+            }
+        });
+        verified
+    }
+
+    /// Drop an interaction's correction
+    pub fn remove_correction(&self, interaction_id: Uuid) -> bool {
+        let mut removed = false;
+        self.update(interaction_id, |interaction| removed = interaction.correction.take().is_some());
+        removed
+    }
+
+    /// Interactions with a correction, newest correction first
+    pub fn corrections(&self) -> Vec<QAInteraction> {
+        let mut corrected: Vec<QAInteraction> = self
+            .interactions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|i| i.correction.is_some())
+            .cloned()
+            .collect();
+        corrected.sort_by_key(|i| std::cmp::Reverse(i.correction.as_ref().map(|c| c.submitted_at)));
+        corrected
+    }
+
+    /// Most recently verified correction of the same question
+    ///
+    /// Questions match when they have the same words, ignoring case and
+    /// punctuation.
+    pub fn find_correction(&self, question: &str) -> Option<QAInteraction> {
+        let question = normalize_question(question);
+        self.interactions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|i| i.correction.as_ref().is_some_and(|c| c.verified))
+            .filter(|i| normalize_question(&i.question) == question)
+            .max_by_key(|i| i.correction.as_ref().and_then(|c| c.verified_at))
+            .cloned()
+    }
+
+    /// An interaction by id
+    pub fn get(&self, interaction_id: &Uuid) -> Option<QAInteraction> {
+        self.interactions.read().unwrap().get(interaction_id).cloned()
+    }
+
+    fn update(&self, interaction_id: Uuid, change: impl FnOnce(&mut QAInteraction)) -> bool {
+        let mut interactions = self.interactions.write().unwrap();
+        let Some(interaction) = interactions.get_mut(&interaction_id) else {
+            return false;
+        };
+        change(interaction);
+        drop(interactions);
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save knowledge store: {}", e);
+        }
+        true
+    }
+
+    /// All answered interactions (without generated pairs)
+    pub fn interactions(&self) -> Vec<QAInteraction> {
+        self.interactions.read().unwrap().values().filter(|i| i.synthetic.is_none()).cloned().collect()
+    }
+
     /// Get statistics about stored knowledge
     pub fn stats(&self) -> KnowledgeStats {
         let interactions = self.interactions.read().unwrap();
@@ -122,6 +345,7 @@ impl KnowledgeStore {
             total_interactions: total,
             positive_feedback: positive,
             negative_feedback: negative,
+            synthetic_pairs: interactions.values().filter(|i| i.synthetic.is_some()).count(),
             unique_keywords: self.question_index.read().unwrap().len(),
         }
     }
@@ -182,10 +406,66 @@ impl KnowledgeStore {
     }
 }
 
+/// Lower-cased words of a question, single-spaced
+fn normalize_question(question: &str) -> String {
+    question
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[derive(Debug, Serialize)]
 pub struct KnowledgeStats {
     pub total_interactions: usize,
     pub positive_feedback: usize,
     pub negative_feedback: usize,
+    /// Generated pairs (counted in `total_interactions`)
+    pub synthetic_pairs: usize,
     pub unique_keywords: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verified_correction_matches_same_question() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = KnowledgeStore::new(dir.path().join("knowledge.json"));
+        let id = store.store_interaction(QAInteraction {
+            id: Uuid::new_v4(),
+            question: "What is the refund window?".to_string(),
+            answer: "14 days.".to_string(),
+            citations_used: Vec::new(),
+            relevance_score: 0.7,
+            feedback_score: None,
+            created_at: chrono::Utc::now(),
+            document_ids: Vec::new(),
+            variant: None,
+            synthetic: None,
+            correction: None,
+        });
+        store.set_correction(id, Correction {
+            answer: Some("30 days from delivery.".to_string()),
+            document_ids: Vec::new(),
+            comment: None,
+            submitted_by: Some("bob".to_string()),
+            submitted_at: chrono::Utc::now(),
+            verified: false,
+            verified_by: None,
+            verified_at: None,
+        });
+
+        // Pending corrections don't apply
+        assert!(store.find_correction("what is the refund window").is_none());
+        assert!(store.verify_correction(id, Some("alice".to_string())));
+        let corrected = store.find_correction("  What is the REFUND window ").unwrap();
+        assert_eq!(corrected.verified_answer(), Some("30 days from delivery."));
+        assert!(store.find_correction("What is the return window?").is_none());
+
+        assert!(store.remove_correction(id));
+        assert!(!store.remove_correction(id));
+    }
+}
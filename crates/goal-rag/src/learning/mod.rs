@@ -3,7 +3,12 @@
 pub mod knowledge_store;
 pub mod feedback;
 pub mod answer_cache;
+pub mod experiments;
+pub mod graph;
+pub mod qa_generation;
 
 pub use knowledge_store::KnowledgeStore;
-pub use feedback::{Feedback, FeedbackType};
+pub use feedback::{Correction, Feedback, FeedbackType};
+pub use graph::{GraphBuildReport, GraphBuilder, GraphEdge, Triple};
+pub use qa_generation::{QaGenerationReport, QaGenerator, QaPair};
 pub use answer_cache::{AnswerCache, CachedAnswer, CachedCitation, CacheStats};
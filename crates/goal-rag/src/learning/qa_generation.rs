@@ -0,0 +1,377 @@
+//! Synthetic question-answer pairs generated from chunks
+//!
+//! A background task asks the LLM for questions each chunk answers, with
+//! their answers, embeds the questions with the chunk's collection model and
+//! stores the pairs in the knowledge store. At query time a question close to
+//! a generated one retrieves the chunk the pair came from, which helps when
+//! users phrase questions differently from the documents. The pairs double as
+//! the seed of an evaluation set (GET /api/qa-pairs).
+//!
+//! Chunks that already have pairs are skipped; chunks whose generation fails
+//! or yields nothing are tried again on the next run.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::QaGenerationConfig;
+use crate::error::{Error, Result};
+use crate::generation::PromptBuilder;
+use crate::processing::{TaskHandle, TaskKind};
+use crate::providers::vector_store::VectorSearchResult;
+use crate::server::state::AppState;
+use crate::storage::ChunkContentRecord;
+
+use super::knowledge_store::{QAInteraction, SyntheticSource};
+
+/// Longest question or answer kept, in characters
+const MAX_PAIR_CHARS: usize = 1000;
+
+/// A generated question and its answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QaPair {
+    pub question: String,
+    pub answer: String,
+}
+
+/// Outcome of a generation run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QaGenerationReport {
+    /// Task id for `/api/admin/tasks`
+    pub task_id: Uuid,
+    /// Chunks pairs were generated for
+    pub chunks: usize,
+    /// Pairs stored
+    pub pairs: usize,
+    /// Chunks whose generation failed (retried on the next run)
+    pub failed: usize,
+    /// Pairs dropped because their chunk was deleted
+    pub pruned: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Generates question-answer pairs, one run at a time
+pub struct QaGenerator {
+    config: QaGenerationConfig,
+    running: AtomicBool,
+    last: RwLock<Option<QaGenerationReport>>,
+}
+
+impl QaGenerator {
+    pub fn new(config: QaGenerationConfig) -> Self {
+        Self {
+            config,
+            running: AtomicBool::new(false),
+            last: RwLock::new(None),
+        }
+    }
+
+    /// Whether pairs are generated and used
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Report of the current or most recent run
+    pub fn last_report(&self) -> Option<QaGenerationReport> {
+        self.last.read().clone()
+    }
+
+    /// Whether a run is in progress
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start generating pairs for chunks without any, in the background
+    pub fn start(self: &Arc<Self>, state: AppState) -> Result<QaGenerationReport> {
+        if !self.is_enabled() {
+            return Err(Error::Config("Question-answer generation is not enabled".to_string()));
+        }
+
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::Config("Question-answer generation is already running".to_string()));
+        }
+
+        let task = state.tasks().start(TaskKind::QaGeneration, "generating", 0, "chunks");
+        let report = QaGenerationReport {
+            task_id: task.id(),
+            chunks: 0,
+            pairs: 0,
+            failed: 0,
+            pruned: 0,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        *self.last.write() = Some(report.clone());
+
+        let generator = Arc::clone(self);
+        let run_report = report.clone();
+        tokio::spawn(async move {
+            generator.run(&state, task, run_report).await;
+        });
+
+        Ok(report)
+    }
+
+    /// Generate pairs for new chunks every `interval`
+    pub fn spawn_periodic(self: &Arc<Self>, state: AppState, interval: Duration) {
+        let generator = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match generator.pending_chunks(&state) {
+                    Ok(chunks) if chunks.is_empty() => continue,
+                    Ok(chunks) => tracing::info!("Generating question-answer pairs for {} new chunks", chunks.len()),
+                    Err(e) => {
+                        tracing::warn!("Failed to list chunks without question-answer pairs: {}", e);
+                        continue;
+                    }
+                }
+                if let Err(e) = generator.start(state.clone()) {
+                    tracing::debug!("Skipping scheduled question-answer generation: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Pairs for a chunk's text
+    pub async fn generate(&self, state: &AppState, text: &str) -> Result<Vec<QaPair>> {
+        let prompt = PromptBuilder::build_qa_generation_prompt(text, self.config.pairs_per_chunk);
+        let response = state.llm_provider().complete(&prompt).await?;
+        let pairs = parse_llm_pairs(&response)
+            .ok_or_else(|| Error::Internal("Unparseable question-answer generation response".to_string()))?;
+        Ok(clean_pairs(pairs, self.config.pairs_per_chunk))
+    }
+
+    /// Long enough chunks of current documents without pairs
+    pub fn pending_chunks(&self, state: &AppState) -> Result<Vec<ChunkContentRecord>> {
+        let done = state.knowledge_store().synthetic_chunk_ids();
+        let mut pending = Vec::new();
+        for doc in state.list_documents() {
+            pending.extend(
+                state
+                    .database()
+                    .list_chunks_for_document(&doc.id)?
+                    .into_iter()
+                    .filter(|chunk| chunk.content.chars().count() >= self.config.min_chunk_chars)
+                    .filter(|chunk| !done.contains(&chunk.id)),
+            );
+        }
+        Ok(pending)
+    }
+
+    async fn run(&self, state: &AppState, task: TaskHandle, mut report: QaGenerationReport) {
+        let result = self.generate_all(state, &task, &mut report).await;
+
+        report.completed_at = Some(Utc::now());
+        match result {
+            Ok(()) => {
+                task.complete();
+                tracing::info!(
+                    "Question-answer generation complete: {} chunks, {} pairs, {} failed, {} pruned",
+                    report.chunks, report.pairs, report.failed, report.pruned
+                );
+            }
+            Err(e) => {
+                report.error = Some(e.to_string());
+                task.fail(e.to_string());
+                tracing::error!("Question-answer generation failed: {}", e);
+            }
+        }
+
+        *self.last.write() = Some(report);
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    async fn generate_all(&self, state: &AppState, task: &TaskHandle, report: &mut QaGenerationReport) -> Result<()> {
+        let store = state.knowledge_store();
+        report.pruned = store.remove_synthetic_where(|source| state.get_chunk(&source.chunk_id).is_none());
+
+        let pending = self.pending_chunks(state)?;
+        let total = pending.len();
+        task.report(0, total);
+
+        for (done, chunk) in pending.into_iter().enumerate() {
+            task.set_current(Some(chunk.filename.clone()));
+            match self.generate_for_chunk(state, &chunk).await {
+                Ok(pairs) => {
+                    report.chunks += 1;
+                    report.pairs += pairs.len();
+                    store.store_synthetic(pairs);
+                }
+                Err(e) => {
+                    tracing::warn!("[{}] Question-answer generation failed for chunk {}: {}", chunk.filename, chunk.id, e);
+                    report.failed += 1;
+                }
+            }
+            task.report(done + 1, total);
+        }
+
+        Ok(())
+    }
+
+    async fn generate_for_chunk(&self, state: &AppState, chunk: &ChunkContentRecord) -> Result<Vec<QAInteraction>> {
+        let pairs = self.generate(state, &chunk.content).await?;
+        if pairs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let collection = chunk.collection.as_deref();
+        let questions: Vec<String> = pairs.iter().map(|pair| pair.question.clone()).collect();
+        let embeddings = state.embedder_for(collection).embed_batch(&questions).await?;
+        let model = state.embedding_model_for(collection).to_string();
+
+        Ok(pairs
+            .into_iter()
+            .zip(embeddings)
+            .map(|(pair, embedding)| QAInteraction {
+                id: Uuid::new_v4(),
+                question: pair.question,
+                answer: pair.answer,
+                citations_used: vec![chunk.filename.clone()],
+                relevance_score: 1.0,
+                feedback_score: None,
+                created_at: Utc::now(),
+                document_ids: vec![chunk.document_id],
+                variant: None,
+                synthetic: Some(SyntheticSource {
+                    chunk_id: chunk.id,
+                    embedding_model: model.clone(),
+                    embedding,
+                }),
+                correction: None,
+            })
+            .collect())
+    }
+}
+
+/// Add the chunks of generated questions matching the query to retrieval results
+///
+/// Chunks already retrieved keep the higher of both similarities. Added
+/// chunks are limited to documents in `document_filter` when one is given.
+/// Does nothing unless generation and retrieval augmentation are enabled.
+pub fn augment_results(
+    state: &AppState,
+    embeddings: &[Vec<f32>],
+    model: &str,
+    document_filter: Option<&[Uuid]>,
+    results: &mut Vec<VectorSearchResult>,
+) {
+    let rag_config = state.config();
+    let config = &rag_config.qa_generation;
+    if !config.enabled || !config.augment_retrieval || config.max_chunks == 0 {
+        return;
+    }
+
+    let mut matches: Vec<(Uuid, f32)> = embeddings
+        .iter()
+        .flat_map(|embedding| {
+            state.knowledge_store().match_synthetic(embedding, model, config.min_similarity, config.max_chunks)
+        })
+        .collect();
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut seen = HashSet::new();
+    let mut added = 0;
+    for (chunk_id, similarity) in matches {
+        if added >= config.max_chunks || !seen.insert(chunk_id) {
+            continue;
+        }
+        if let Some(existing) = results.iter_mut().find(|r| r.chunk.id == chunk_id) {
+            existing.similarity = existing.similarity.max(similarity);
+            existing.raw_similarity = existing.raw_similarity.max(similarity);
+            continue;
+        }
+        let Some(chunk) = state.get_chunk(&chunk_id) else {
+            continue;
+        };
+        if document_filter.is_some_and(|filter| !filter.contains(&chunk.document_id)) {
+            continue;
+        }
+        results.push(VectorSearchResult::new(chunk, similarity));
+        added += 1;
+    }
+    if added > 0 {
+        tracing::debug!("Added {} chunks from generated questions", added);
+    }
+}
+
+/// Drop empty and duplicate pairs, trim long ones, keep `max`
+fn clean_pairs(pairs: Vec<QaPair>, max: usize) -> Vec<QaPair> {
+    let trim = |s: &str| s.trim().chars().take(MAX_PAIR_CHARS).collect::<String>();
+    let mut seen = HashSet::new();
+    pairs
+        .into_iter()
+        .map(|pair| QaPair {
+            question: trim(&pair.question),
+            answer: trim(&pair.answer),
+        })
+        .filter(|pair| !pair.question.is_empty() && !pair.answer.is_empty() && seen.insert(pair.question.to_lowercase()))
+        .take(max)
+        .collect()
+}
+
+/// Pairs in a model response, as `{"pairs": [...]}` or a bare array
+fn parse_llm_pairs(response: &str) -> Option<Vec<QaPair>> {
+    let start = response.find('[')?;
+    let end = response.rfind(']')?;
+    serde_json::from_str(response.get(start..=end)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{wait_until, TestApp};
+
+    #[tokio::test]
+    async fn test_run_keeps_its_own_report() {
+        let app = TestApp::with_config(|config| config.qa_generation.enabled = true).await;
+        let generator = app.state.qa_generator();
+        let report = generator.start(app.state.clone()).unwrap();
+
+        // The run used to read its report back from `last` and panic when it was gone
+        *generator.last.write() = None;
+        wait_until(|| !generator.is_running()).await;
+
+        let last = generator.last_report().expect("the run stores its report");
+        assert_eq!(last.task_id, report.task_id);
+        assert!(last.completed_at.is_some());
+        assert_eq!(last.error, None);
+    }
+
+    #[test]
+    fn test_parse_and_clean_pairs() {
+        let parsed = parse_llm_pairs(
+            r#"```json
+{"pairs": [{"question": "When was the policy adopted? ", "answer": "In 2021."},
+           {"question": "when was the policy adopted?", "answer": "2021"},
+           {"question": "", "answer": "Orphan answer"}]}
+```"#,
+        )
+        .unwrap();
+        assert_eq!(
+            clean_pairs(parsed, 3),
+            vec![QaPair {
+                question: "When was the policy adopted?".to_string(),
+                answer: "In 2021.".to_string(),
+            }]
+        );
+        assert!(parse_llm_pairs("I can't help with that.").is_none());
+    }
+}
@@ -5,19 +5,25 @@
 //! with precise source citations.
 
 pub mod config;
+pub mod config_check;
+pub mod connectors;
 pub mod embeddings;
+pub mod engine;
 pub mod error;
 pub mod generation;
 pub mod ingestion;
+pub mod integrations;
 pub mod learning;
 pub mod processing;
 pub mod providers;
 pub mod retrieval;
 pub mod server;
 pub mod storage;
+pub mod telemetry;
 pub mod types;
 
 pub use config::RagConfig;
+pub use engine::{IngestOutcome, RagEngine};
 pub use error::{Error, Result};
 pub use types::{
     document::{Chunk, ChunkSource, Document, FileType},
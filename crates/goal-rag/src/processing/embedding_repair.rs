@@ -0,0 +1,276 @@
+//! Repair of chunks without a usable embedding
+//!
+//! Chunks whose embedding fails or times out during ingestion are kept in the
+//! `pending_embeddings` table instead of being indexed with a placeholder
+//! vector. The repair task embeds and indexes them. When started on request it
+//! also re-embeds all-zero vectors that earlier versions wrote to the local
+//! index.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use tokio::time::timeout;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::providers::EmbeddingProvider;
+use crate::server::state::AppState;
+
+use super::tasks::{TaskHandle, TaskKind};
+
+/// Pending chunks loaded per round
+const BATCH_SIZE: usize = 256;
+
+/// Time allowed for embedding one chunk
+const EMBED_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Outcome of a repair run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RepairReport {
+    /// Task id for `/api/admin/tasks`
+    pub task_id: Uuid,
+    /// Pending chunks embedded and indexed
+    pub repaired: usize,
+    /// Zero vectors replaced (local backend, on request only)
+    pub zero_vectors_repaired: usize,
+    /// Chunks that failed again (pending chunks stay pending)
+    pub failed: usize,
+    /// Chunks still pending when the run finished
+    pub remaining: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Runs embedding repairs, one at a time
+pub struct EmbeddingRepair {
+    running: AtomicBool,
+    last: RwLock<Option<RepairReport>>,
+}
+
+impl EmbeddingRepair {
+    pub fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            last: RwLock::new(None),
+        }
+    }
+
+    /// Report of the current or most recent run
+    pub fn last_report(&self) -> Option<RepairReport> {
+        self.last.read().clone()
+    }
+
+    /// Whether a repair is in progress
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start a repair in the background, also scanning the local index for
+    /// zero vectors if requested
+    pub fn start(self: &Arc<Self>, state: AppState, scan_zero_vectors: bool) -> Result<RepairReport> {
+        let pending = state.database().count_pending_embeddings()?;
+
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::Config("An embedding repair is already running".to_string()));
+        }
+
+        let task = state.tasks().start(TaskKind::EmbeddingRepair, "embedding", pending, "chunks");
+        let report = RepairReport {
+            task_id: task.id(),
+            repaired: 0,
+            zero_vectors_repaired: 0,
+            failed: 0,
+            remaining: pending,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        *self.last.write() = Some(report.clone());
+
+        let repair = Arc::clone(self);
+        let run_report = report.clone();
+        tokio::spawn(async move {
+            repair.run(&state, task, run_report, scan_zero_vectors).await;
+        });
+
+        Ok(report)
+    }
+
+    /// Repair pending chunks every `interval` (zero vectors are only scanned on request)
+    pub fn spawn_periodic(self: &Arc<Self>, state: AppState, interval: Duration) {
+        let repair = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match state.database().count_pending_embeddings() {
+                    Ok(0) => continue,
+                    Ok(pending) => tracing::info!("Repairing {} chunks pending embedding", pending),
+                    Err(e) => {
+                        tracing::warn!("Failed to count pending embeddings: {}", e);
+                        continue;
+                    }
+                }
+                if let Err(e) = repair.start(state.clone(), false) {
+                    tracing::debug!("Skipping scheduled embedding repair: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn run(&self, state: &AppState, task: TaskHandle, mut report: RepairReport, scan_zero_vectors: bool) {
+        let result = repair(state, &task, &mut report, scan_zero_vectors).await;
+
+        report.remaining = state.database().count_pending_embeddings().unwrap_or(report.remaining);
+        report.completed_at = Some(Utc::now());
+        match result {
+            Ok(()) => {
+                task.complete();
+                tracing::info!(
+                    "Embedding repair complete: {} pending and {} zero-vector chunks repaired, {} failed, {} still pending",
+                    report.repaired, report.zero_vectors_repaired, report.failed, report.remaining
+                );
+            }
+            Err(e) => {
+                report.error = Some(e.to_string());
+                task.fail(e.to_string());
+                tracing::error!("Embedding repair failed: {}", e);
+            }
+        }
+
+        *self.last.write() = Some(report);
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Default for EmbeddingRepair {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn repair(state: &AppState, task: &TaskHandle, report: &mut RepairReport, scan_zero_vectors: bool) -> Result<()> {
+    let database = state.database();
+    let mut total = report.remaining;
+
+    // Chunks that fail again stay in the table, page past them
+    let mut tried = HashSet::new();
+    let mut skipped = 0;
+    loop {
+        let batch: Vec<_> = database
+            .list_pending_embeddings(BATCH_SIZE, skipped)?
+            .into_iter()
+            .filter(|chunk| tried.insert(chunk.id))
+            .collect();
+        if batch.is_empty() {
+            break;
+        }
+
+        for mut chunk in batch {
+            task.set_current(Some(chunk.source.filename.clone()));
+            let embedder = state.embedder_for(chunk.collection());
+            match embed(embedder.as_ref(), &chunk.content).await {
+                Ok(embeddings) => {
+                    chunk.set_embeddings(embeddings);
+                    chunk.set_embedding_model(state.embedding_model_for(chunk.collection()));
+                    state.vector_store_provider().insert_chunk(&chunk).await?;
+                    state.store_chunks(std::slice::from_ref(&chunk));
+                    database.delete_pending_embedding(&chunk.id)?;
+                    report.repaired += 1;
+                }
+                Err(e) => {
+                    tracing::warn!("[{}] Embedding repair failed for chunk {}: {}", chunk.source.filename, chunk.id, e);
+                    database.record_pending_embedding_failure(&chunk.id, &e.to_string())?;
+                    report.failed += 1;
+                    skipped += 1;
+                }
+            }
+            total = total.max(tried.len());
+            task.report(report.repaired + report.failed, total);
+        }
+    }
+
+    let Some(store) = state.vector_store().filter(|_| scan_zero_vectors) else {
+        return Ok(());
+    };
+
+    task.set_phase("scanning");
+    let zero_chunks = {
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || store.zero_vector_chunks())
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??
+    };
+    if zero_chunks.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!("Re-embedding {} zero-vector chunks", zero_chunks.len());
+    task.set_phase("embedding");
+    total += zero_chunks.len();
+    for mut chunk in zero_chunks {
+        task.set_current(Some(chunk.source.filename.clone()));
+        let embedder = state.embedder_for(chunk.collection());
+        match embed(embedder.as_ref(), &chunk.content).await {
+            Ok(embeddings) => {
+                chunk.set_embeddings(embeddings);
+                // Text is already in the FTS table, only the vector is replaced
+                let store = store.clone();
+                tokio::task::spawn_blocking(move || {
+                    store.delete_chunk(&chunk.id.to_string())?;
+                    store.insert_chunk(&chunk)
+                })
+                .await
+                .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+                report.zero_vectors_repaired += 1;
+            }
+            Err(e) => {
+                tracing::warn!("[{}] Re-embedding zero-vector chunk {} failed: {}", chunk.source.filename, chunk.id, e);
+                report.failed += 1;
+            }
+        }
+        task.report(report.repaired + report.zero_vectors_repaired + report.failed, total);
+    }
+
+    Ok(())
+}
+
+async fn embed(embedder: &dyn EmbeddingProvider, text: &str) -> Result<Vec<Vec<f32>>> {
+    timeout(EMBED_TIMEOUT, embedder.embed_parts(text))
+        .await
+        .map_err(|_| Error::embedding(format!("Timed out after {}s", EMBED_TIMEOUT.as_secs())))?
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::test_support::{wait_until, TestApp};
+
+    #[tokio::test]
+    async fn test_run_keeps_its_own_report() {
+        let app = TestApp::new().await;
+        let repair = app.state.embedding_repair();
+        let report = repair.start(app.state.clone(), false).unwrap();
+
+        // The run used to read its report back from `last` and panic when it was gone
+        *repair.last.write() = None;
+        wait_until(|| !repair.is_running()).await;
+
+        let last = repair.last_report().expect("the run stores its report");
+        assert_eq!(last.task_id, report.task_id);
+        assert!(last.completed_at.is_some());
+        assert_eq!(last.error, None);
+    }
+}
@@ -126,7 +126,7 @@ impl FileCharacteristics {
         // Determine parser strategy based on extension
         let recommended_parser = match extension.as_str() {
             // Native support
-            "pdf" | "docx" | "pptx" | "xlsx" | "txt" | "md" | "csv" | "html" => {
+            "pdf" | "docx" | "pptx" | "xlsx" | "txt" | "md" | "csv" | "html" | "eml" | "msg" | "mbox" | "ipynb" | "rst" => {
                 if size_bytes > 50 * 1024 * 1024 {
                     ParserStrategy::LocalToolsFirst
                 } else {
@@ -241,7 +241,7 @@ impl PdfAnalysis {
         }
 
         // Check for encryption marker
-        let is_encrypted = data.windows(8).any(|w| w == b"/Encrypt");
+        let is_encrypted = crate::ingestion::encrypted::is_encrypted(data);
 
         // Check for complex font encoding (ToUnicode CMaps)
         let has_complex_fonts = data.windows(11).any(|w| w == b"/ToUnicode ");
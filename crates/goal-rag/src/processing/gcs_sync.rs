@@ -0,0 +1,289 @@
+//! Incremental sync of the file registry from the GCS bucket
+//!
+//! The registry mirrors the documents stored in the bucket. Rather than
+//! downloading every metadata object on each sync, the generation number of
+//! each metadata and plaintext object is kept in `gcs_sync_objects`: a pass
+//! lists both prefixes (names and generations only) and reads just the
+//! documents whose objects were added, rewritten or removed since they were
+//! last synced. A full sync reads every document. Documents that fail are
+//! retried by the next pass. With `gcp.sync_interval_secs` set, incremental
+//! passes run in the background; the current or last pass is reported on
+//! `/api/files/sync/status`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "gcp")]
+use std::sync::Arc;
+#[cfg(feature = "gcp")]
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[cfg(feature = "gcp")]
+use crate::error::{Error, Result};
+#[cfg(feature = "gcp")]
+use crate::server::state::AppState;
+
+#[cfg(feature = "gcp")]
+use super::tasks::{TaskHandle, TaskKind};
+
+/// Current or last sync pass
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GcsSyncProgress {
+    /// Task id for `/api/admin/tasks`
+    pub task_id: Uuid,
+    /// `full` or `incremental`
+    pub mode: String,
+    pub running: bool,
+    /// Metadata and plaintext objects listed in the bucket
+    pub objects_listed: usize,
+    /// Documents with objects added, rewritten or removed since their last sync
+    pub documents_changed: usize,
+    pub files_synced: usize,
+    /// Documents that failed (retried by the next pass)
+    pub files_failed: usize,
+    /// Documents whose metadata is gone from the bucket (their records are kept)
+    pub documents_removed: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Runs registry syncs, one at a time
+pub struct GcsSync {
+    running: AtomicBool,
+    last: RwLock<Option<GcsSyncProgress>>,
+}
+
+impl GcsSync {
+    pub fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            last: RwLock::new(None),
+        }
+    }
+
+    /// Progress of the current or most recent pass
+    pub fn last_progress(&self) -> Option<GcsSyncProgress> {
+        self.last.read().clone()
+    }
+
+    /// Whether a pass is in progress
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(feature = "gcp")]
+impl GcsSync {
+    /// Run a sync pass, reading every document when `full`
+    pub async fn run(&self, state: &AppState, full: bool) -> Result<GcsSyncProgress> {
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::Config("A GCS sync is already running".to_string()));
+        }
+
+        let task = state.tasks().start(TaskKind::GcsSync, "listing", 0, "documents");
+        let mut progress = GcsSyncProgress {
+            task_id: task.id(),
+            mode: if full { "full" } else { "incremental" }.to_string(),
+            running: true,
+            objects_listed: 0,
+            documents_changed: 0,
+            files_synced: 0,
+            files_failed: 0,
+            documents_removed: 0,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        *self.last.write() = Some(progress.clone());
+
+        let start = std::time::Instant::now();
+        let result = self.sync(state, &task, &mut progress, full).await;
+        progress.running = false;
+        progress.completed_at = Some(Utc::now());
+        match &result {
+            Ok(()) => {
+                task.complete();
+                let duration_ms = start.elapsed().as_millis() as u64;
+                if let Err(e) = state.database().update_sync_status(progress.files_synced, duration_ms) {
+                    tracing::warn!("Failed to update sync status: {}", e);
+                }
+                if progress.documents_changed > 0 || full {
+                    tracing::info!(
+                        "GCS {} sync complete: {} of {} changed documents synced, {} failed, {} removed, took {}ms",
+                        progress.mode, progress.files_synced, progress.documents_changed,
+                        progress.files_failed, progress.documents_removed, duration_ms
+                    );
+                }
+            }
+            Err(e) => {
+                progress.error = Some(e.to_string());
+                task.fail(e.to_string());
+                tracing::error!("GCS sync failed: {}", e);
+            }
+        }
+
+        *self.last.write() = Some(progress.clone());
+        self.running.store(false, Ordering::SeqCst);
+        result.map(|()| progress)
+    }
+
+    /// Run an incremental pass every `interval`
+    pub fn spawn_periodic(self: &Arc<Self>, state: AppState, interval: Duration) {
+        let sync = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if sync.is_running() {
+                    continue;
+                }
+                if let Err(e) = sync.run(&state, false).await {
+                    tracing::debug!("Scheduled GCS sync did not complete: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn sync(&self, state: &AppState, task: &TaskHandle, progress: &mut GcsSyncProgress, full: bool) -> Result<()> {
+        let store = state
+            .document_store()
+            .ok_or_else(|| Error::Internal("GCS document store not available".to_string()))?;
+
+        let listed = store.list_generations().await?;
+        let known = state.database().gcs_object_generations()?;
+        let changed = changed_documents(&known, &listed, full, |object| store.document_of(object));
+        progress.objects_listed = listed.len();
+        progress.documents_changed = changed.len();
+        *self.last.write() = Some(progress.clone());
+        task.set_phase("syncing");
+        task.report(0, changed.len());
+
+        for (done, (doc_id, objects)) in changed.iter().enumerate() {
+            match store.file_info(doc_id).await {
+                Ok(Some(info)) => match state.record_gcs_file(&info) {
+                    Ok(()) => progress.files_synced += 1,
+                    Err(e) => {
+                        tracing::warn!("Failed to sync file {}: {}", info.filename, e);
+                        progress.files_failed += 1;
+                        continue;
+                    }
+                },
+                Ok(None) if !objects.has_metadata => {
+                    tracing::info!("Document {} is no longer in the GCS bucket", doc_id);
+                    progress.documents_removed += 1;
+                }
+                Ok(None) => {
+                    tracing::debug!("Skipping invalid metadata of {}", doc_id);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to read metadata of {}: {}", doc_id, e);
+                    progress.files_failed += 1;
+                    continue;
+                }
+            }
+
+            let synced: Vec<(&str, i64)> = objects.listed.iter().map(|(name, generation)| (name.as_str(), *generation)).collect();
+            let removed: Vec<&str> = objects.removed.iter().map(String::as_str).collect();
+            state.database().set_gcs_object_generations(&synced, &removed)?;
+
+            task.report(done + 1, changed.len());
+            *self.last.write() = Some(progress.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Default for GcsSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Objects of a changed document
+#[cfg_attr(not(feature = "gcp"), allow(dead_code))]
+#[derive(Debug, Default, PartialEq)]
+struct DocumentObjects {
+    /// Objects in the bucket now, with their generations
+    listed: Vec<(String, i64)>,
+    /// Objects synced before that are gone
+    removed: Vec<String>,
+    /// Whether its metadata object is in the bucket
+    has_metadata: bool,
+}
+
+/// Documents with an object added, rewritten or removed since the `known`
+/// generations (every document when `full`)
+#[cfg_attr(not(feature = "gcp"), allow(dead_code))]
+fn changed_documents(
+    known: &HashMap<String, i64>,
+    listed: &HashMap<String, i64>,
+    full: bool,
+    document_of: impl Fn(&str) -> Option<Uuid>,
+) -> BTreeMap<Uuid, DocumentObjects> {
+    let mut documents: BTreeMap<Uuid, (bool, DocumentObjects)> = BTreeMap::new();
+    for (name, &generation) in listed {
+        let Some(doc_id) = document_of(name) else { continue };
+        let (changed, objects) = documents.entry(doc_id).or_default();
+        *changed |= full || known.get(name) != Some(&generation);
+        objects.listed.push((name.clone(), generation));
+        objects.has_metadata |= name.ends_with(".meta.json");
+    }
+    for name in known.keys().filter(|name| !listed.contains_key(*name)) {
+        let Some(doc_id) = document_of(name) else { continue };
+        let (changed, objects) = documents.entry(doc_id).or_default();
+        *changed = true;
+        objects.removed.push(name.clone());
+    }
+
+    documents
+        .into_iter()
+        .filter(|(_, (changed, _))| *changed)
+        .map(|(doc_id, (_, mut objects))| {
+            objects.listed.sort();
+            objects.removed.sort();
+            (doc_id, objects)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_documents() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let meta = |id: Uuid| format!("originals/{}.meta.json", id);
+        let text = |id: Uuid| format!("plaintext/{}.txt", id);
+        let document_of = |name: &str| {
+            let id = name.rsplit('/').next()?.split('.').next()?;
+            Uuid::parse_str(id).ok()
+        };
+
+        let known: HashMap<String, i64> = [(meta(a), 1), (meta(b), 1), (meta(c), 1), (text(c), 1)].into();
+        // a unchanged, b processed since, c's plaintext removed
+        let listed: HashMap<String, i64> = [(meta(a), 1), (meta(b), 1), (text(b), 4), (meta(c), 1)].into();
+
+        let changed = changed_documents(&known, &listed, false, document_of);
+        assert_eq!(changed.keys().copied().collect::<Vec<_>>(), {
+            let mut ids = vec![b, c];
+            ids.sort();
+            ids
+        });
+        assert_eq!(changed[&b].listed, vec![(meta(b), 1), (text(b), 4)]);
+        assert_eq!(changed[&c].removed, vec![text(c)]);
+        assert!(changed[&c].has_metadata);
+
+        assert_eq!(changed_documents(&known, &listed, true, document_of).len(), 3);
+    }
+}
@@ -4,15 +4,20 @@
 
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use super::queue_backend::QueueBackend;
+use super::scheduler::FairScheduler;
 use super::{FileCharacteristics, FileTier};
+use crate::config::{ChunkSizeUnit, RetryConfig};
+use crate::error::Error;
 use crate::storage::{
     FileRegistryDb, JobFileRecord, JobFileStatus, JobOptions, JobRecord,
-    PersistedJobStage, PersistedJobStatus,
+    PersistedJobStage, PersistedJobStatus, SpoolStore,
 };
 
 /// Processing stage
@@ -21,6 +26,7 @@ use crate::storage::{
 pub enum ProcessingStage {
     Queued,
     Uploading,
+    Scanning,
     Parsing,
     Chunking,
     Embedding,
@@ -29,6 +35,32 @@ pub enum ProcessingStage {
     Failed,
 }
 
+impl ProcessingStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Uploading => "uploading",
+            Self::Scanning => "scanning",
+            Self::Parsing => "parsing",
+            Self::Chunking => "chunking",
+            Self::Embedding => "embedding",
+            Self::Storing => "storing",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Stage a file processing error most likely came from
+    pub fn of_error(error: &Error) -> Self {
+        match error {
+            Error::Malware { .. } => Self::Scanning,
+            Error::Embedding(_) => Self::Embedding,
+            Error::VectorDb(_) | Error::RuVector(_) => Self::Storing,
+            _ => Self::Parsing,
+        }
+    }
+}
+
 /// Job status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -39,12 +71,38 @@ pub enum JobStatus {
     Failed,
 }
 
+/// Scheduling lane of a job
+///
+/// Files of interactive jobs get most of the processing slots while both
+/// lanes have work (see `processing.interactive_weight`).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    /// Uploads and re-ingests someone is waiting for
+    #[default]
+    Interactive,
+    /// Backfills and other large batches
+    Bulk,
+}
+
+impl JobPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Interactive => "interactive",
+            Self::Bulk => "bulk",
+        }
+    }
+}
+
 /// Error details for a file that failed to process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileError {
     pub filename: String,
     pub error: String,
     pub stage: ProcessingStage,
+    /// Error code (`password_required`, `parse_error`, ...)
+    #[serde(default)]
+    pub code: String,
 }
 
 /// Parser attempt record for tracking escalation
@@ -80,6 +138,9 @@ pub struct FileProgressRecord {
     pub duration_ms: Option<u64>,
     /// Error message if failed
     pub error: Option<String>,
+    /// Bytes read so far (streamed files report progress by bytes)
+    #[serde(default)]
+    pub bytes_processed: u64,
 }
 
 /// File processing status
@@ -94,6 +155,10 @@ pub enum FileProcessingStatus {
     Complete,
     Skipped,
     Failed,
+    /// Encrypted and none of the known passwords opens it
+    PasswordRequired,
+    /// Flagged by the virus scanner and quarantined
+    Quarantined,
 }
 
 /// Progress information for a job
@@ -148,6 +213,15 @@ impl JobProgress {
 
         let file_progress = self.files_processed as f32 / self.total_files as f32;
 
+        // Streamed files report how far through the file they are
+        let streamed: f32 = self.file_progress.iter()
+            .filter(|f| f.completed_at.is_none() && f.bytes_processed > 0 && f.size_bytes > 0)
+            .map(|f| (f.bytes_processed as f32 / f.size_bytes as f32).min(1.0))
+            .sum();
+        if streamed > 0.0 {
+            return (file_progress + streamed / self.total_files as f32) * 100.0;
+        }
+
         // If we're embedding, factor in chunk progress
         if self.stage == ProcessingStage::Embedding && self.total_chunks > 0 {
             let chunk_progress = self.chunks_embedded as f32 / self.total_chunks as f32;
@@ -160,26 +234,65 @@ impl JobProgress {
 }
 
 /// A processing job
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Job {
     pub id: Uuid,
     pub files: Vec<FileData>,
     pub options: ProcessingOptions,
 }
 
-/// File data for processing
-#[derive(Debug, Clone)]
+/// A file to process, spooled to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileData {
     pub filename: String,
-    pub data: Vec<u8>,
+    /// SHA-256 of the contents, their key in the spool store
+    pub hash: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+impl FileData {
+    /// Spool `data` and reference it
+    pub fn spool(spool: &SpoolStore, filename: String, data: &[u8]) -> crate::error::Result<Self> {
+        Ok(Self {
+            filename,
+            hash: spool.put(data)?,
+            size: data.len() as u64,
+        })
+    }
+
+    /// Spool everything read from `reader` and reference it (blocking)
+    pub fn spool_reader(spool: &SpoolStore, filename: String, reader: impl std::io::Read) -> crate::error::Result<Self> {
+        let (hash, size) = spool.put_reader(reader)?;
+        Ok(Self { filename, hash, size })
+    }
 }
 
 /// Processing options
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProcessingOptions {
     pub chunk_size: Option<usize>,
     pub chunk_overlap: Option<usize>,
+    /// Unit of chunk size and overlap (`None` = server config)
+    #[serde(default)]
+    pub chunk_size_unit: Option<ChunkSizeUnit>,
     pub parallel_embeddings: usize,
+    /// Collection to ingest into (selects the lexical search analyzer)
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Principal ids and roles allowed to read the documents (empty = everyone)
+    #[serde(default)]
+    pub acl: Vec<String>,
+    /// Retries for transient failures (`None` = server config)
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+    /// Scheduling lane
+    #[serde(default)]
+    pub priority: JobPriority,
+    /// Passwords of encrypted PDFs by filename; not persisted, so files of a
+    /// resumed job fall back to the configured passwords
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub passwords: HashMap<String, String>,
 }
 
 impl Default for Job {
@@ -196,30 +309,61 @@ impl Default for Job {
 pub struct JobQueue {
     /// Active jobs with progress
     jobs: Arc<DashMap<Uuid, JobProgress>>,
-    /// Channel for sending jobs to workers
-    sender: mpsc::Sender<Job>,
+    /// Queue backend for handing jobs to workers (in-process or shared)
+    backend: Arc<dyn QueueBackend>,
     /// Number of workers
     worker_count: usize,
     /// Jobs in queue
     queue_size: Arc<AtomicUsize>,
     /// Database for persistence
     database: Arc<FileRegistryDb>,
+    /// Spooled file contents of queued jobs
+    spool: Arc<SpoolStore>,
+    /// File processing slots shared by all running jobs
+    scheduler: Arc<FairScheduler>,
+    /// Progress waiting to be published to a shared backend, in order
+    status_updates: OnceLock<mpsc::UnboundedSender<JobProgress>>,
 }
 
 impl JobQueue {
     /// Create a new job queue with database persistence
-    pub fn new(worker_count: usize, database: Arc<FileRegistryDb>) -> (Self, mpsc::Receiver<Job>) {
-        let (sender, receiver) = mpsc::channel(1000);
-
-        let queue = Self {
+    pub fn new(
+        worker_count: usize,
+        database: Arc<FileRegistryDb>,
+        backend: Arc<dyn QueueBackend>,
+        spool: Arc<SpoolStore>,
+        scheduler: FairScheduler,
+    ) -> Self {
+        Self {
             jobs: Arc::new(DashMap::new()),
-            sender,
+            backend,
             worker_count,
             queue_size: Arc::new(AtomicUsize::new(0)),
             database,
-        };
+            spool,
+            scheduler: Arc::new(scheduler),
+            status_updates: OnceLock::new(),
+        }
+    }
 
-        (queue, receiver)
+    /// Number of jobs a worker runs at once
+    pub fn worker_count(&self) -> usize {
+        self.worker_count
+    }
+
+    /// Get the file slot scheduler
+    pub fn scheduler(&self) -> &Arc<FairScheduler> {
+        &self.scheduler
+    }
+
+    /// Get the spool store holding the files of queued jobs
+    pub fn spool(&self) -> &Arc<SpoolStore> {
+        &self.spool
+    }
+
+    /// Get the queue backend (workers dequeue from here)
+    pub fn backend(&self) -> &Arc<dyn QueueBackend> {
+        &self.backend
     }
 
     /// Submit a job for processing (with persistence)
@@ -239,27 +383,28 @@ impl JobQueue {
             Some(JobOptions {
                 chunk_size: job.options.chunk_size,
                 chunk_overlap: job.options.chunk_overlap,
+                chunk_size_unit: job.options.chunk_size_unit,
                 parallel_embeddings: job.options.parallel_embeddings,
+                collection: job.options.collection.clone(),
+                acl: job.options.acl.clone(),
+                retry: job.options.retry.clone(),
+                priority: job.options.priority,
             }),
         );
         if let Err(e) = self.database.create_job(&job_record) {
             tracing::error!("Failed to persist job to database: {}", e);
         }
 
-        // Persist each file with its data for resumability
+        // Persist each file with its spooled contents for resumability
         for file in &job.files {
-            let file_record = JobFileRecord::new(
-                file.filename.clone(),
-                file.data.len() as u64,
-                Some(file.data.clone()),
-            );
+            let file_record = JobFileRecord::spooled(file.filename.clone(), file.size, file.hash.clone());
             if let Err(e) = self.database.add_job_file(job_id, &file_record) {
                 tracing::error!("Failed to persist job file {}: {}", file.filename, e);
             }
         }
 
         // Send to workers
-        if let Err(e) = self.sender.send(job).await {
+        if let Err(e) = self.backend.enqueue(job).await {
             tracing::error!("Failed to submit job: {}", e);
             self.update_status(job_id, JobStatus::Failed, Some(e.to_string()));
         }
@@ -267,6 +412,17 @@ impl JobQueue {
         job_id
     }
 
+    /// Start tracking a job received from a shared backend
+    ///
+    /// Jobs enqueued by another process have no progress entry here yet.
+    pub fn ensure_tracked(&self, job: &Job) {
+        if self.jobs.contains_key(&job.id) {
+            return;
+        }
+        self.jobs.insert(job.id, JobProgress::new(job.id, job.files.len()));
+        self.queue_size.fetch_add(1, Ordering::SeqCst);
+    }
+
     /// Get incomplete jobs from database (for resuming on startup)
     pub fn get_incomplete_jobs(&self) -> Vec<JobRecord> {
         match self.database.get_incomplete_jobs() {
@@ -293,7 +449,7 @@ impl JobQueue {
     pub async fn resume_job(&self, job_record: JobRecord) -> Option<Uuid> {
         let job_id = job_record.id;
 
-        // Get pending files with their data
+        // Get pending files with their spooled contents
         let pending_files = self.get_pending_files(job_id);
         if pending_files.is_empty() {
             tracing::info!("Job {} has no pending files, marking complete", job_id);
@@ -326,12 +482,7 @@ impl JobQueue {
         // Convert to Job with only pending files
         let files: Vec<FileData> = pending_files
             .into_iter()
-            .filter_map(|f| {
-                f.file_data.map(|data| FileData {
-                    filename: f.filename,
-                    data,
-                })
-            })
+            .filter_map(|f| self.spooled_file(job_id, f))
             .collect();
 
         if files.is_empty() {
@@ -345,7 +496,13 @@ impl JobQueue {
             options: job_record.options.map(|o| ProcessingOptions {
                 chunk_size: o.chunk_size,
                 chunk_overlap: o.chunk_overlap,
+                chunk_size_unit: o.chunk_size_unit,
                 parallel_embeddings: o.parallel_embeddings,
+                collection: o.collection,
+                acl: o.acl,
+                retry: o.retry,
+                priority: o.priority,
+                passwords: HashMap::new(),
             }).unwrap_or_default(),
         };
 
@@ -355,7 +512,7 @@ impl JobQueue {
         );
 
         // Send to workers
-        if let Err(e) = self.sender.send(job).await {
+        if let Err(e) = self.backend.enqueue(job).await {
             tracing::error!("Failed to resume job {}: {}", job_id, e);
             self.update_status(job_id, JobStatus::Failed, Some(e.to_string()));
             return None;
@@ -364,8 +521,104 @@ impl JobQueue {
         Some(job_id)
     }
 
+    /// The spooled file of a pending job file
+    ///
+    /// Files of jobs queued before spooling still have their bytes in the
+    /// registry; those are moved to the spool.
+    fn spooled_file(&self, job_id: Uuid, file: JobFileRecord) -> Option<FileData> {
+        if let Some(hash) = file.spool_hash {
+            if self.spool.contains(&hash) {
+                return Some(FileData { filename: file.filename, hash, size: file.file_size });
+            }
+            tracing::warn!("Spooled file of {} in job {} is missing", file.filename, job_id);
+            return None;
+        }
+
+        let data = file.file_data?;
+        let spooled = FileData::spool(&self.spool, file.filename.clone(), &data).and_then(|spooled| {
+            self.database.set_job_file_spooled(job_id, &spooled.filename, &spooled.hash)?;
+            Ok(spooled)
+        });
+        match spooled {
+            Ok(spooled) => Some(spooled),
+            Err(e) => {
+                tracing::error!("Failed to spool {} of job {}: {}", file.filename, job_id, e);
+                None
+            }
+        }
+    }
+
     /// Persist current job state to database
     fn persist_job_state(&self, job_id: Uuid) {
+        self.write_job_record(job_id);
+        if self.backend.is_shared() {
+            if let Some(progress) = self.get_progress(job_id) {
+                self.publish(progress);
+            }
+        }
+    }
+
+    /// Publish progress to the shared backend so API servers without workers
+    /// see it; updates are sent one at a time so a newer one is never
+    /// overwritten by an older
+    fn publish(&self, progress: JobProgress) {
+        let sender = match self.status_updates.get() {
+            Some(sender) => sender,
+            None => {
+                let Ok(runtime) = tokio::runtime::Handle::try_current() else { return };
+                let (sender, mut receiver) = mpsc::unbounded_channel::<JobProgress>();
+                let backend = self.backend.clone();
+                runtime.spawn(async move {
+                    while let Some(progress) = receiver.recv().await {
+                        if let Err(e) = backend.publish_status(&progress).await {
+                            tracing::warn!("Failed to publish status of job {}: {}", progress.job_id, e);
+                        }
+                    }
+                });
+                self.status_updates.get_or_init(|| sender)
+            }
+        };
+        let _ = sender.send(progress);
+    }
+
+    /// Job progress, taking what a worker in another process published when
+    /// it is newer than the local entry
+    pub async fn find_progress(&self, job_id: Uuid) -> Option<JobProgress> {
+        let local = self.get_progress(job_id);
+        if !self.backend.is_shared() {
+            return local;
+        }
+        let published = match self.backend.job_status(job_id).await {
+            Ok(published) => published,
+            Err(e) => {
+                tracing::warn!("Failed to read published status of job {}: {}", job_id, e);
+                None
+            }
+        };
+        match (local, published) {
+            (Some(local), Some(published)) if published.updated_at <= local.updated_at => Some(local),
+            (local, None) => local,
+            (_, Some(published)) => {
+                self.adopt(published.clone());
+                Some(published)
+            }
+        }
+    }
+
+    /// Track progress published by another process and keep the job's
+    /// record in the registry current
+    fn adopt(&self, progress: JobProgress) {
+        let job_id = progress.job_id;
+        let finished = |status: JobStatus| matches!(status, JobStatus::Complete | JobStatus::Failed);
+        let was_open = self.jobs.get(&job_id).is_some_and(|local| !finished(local.status));
+        if was_open && finished(progress.status) {
+            self.queue_size.fetch_sub(1, Ordering::SeqCst);
+        }
+        self.jobs.insert(job_id, progress);
+        self.write_job_record(job_id);
+    }
+
+    fn write_job_record(&self, job_id: Uuid) {
         if let Some(progress) = self.jobs.get(&job_id) {
             let job_record = JobRecord {
                 id: job_id,
@@ -378,6 +631,7 @@ impl JobQueue {
                 stage: match progress.stage {
                     ProcessingStage::Queued => PersistedJobStage::Queued,
                     ProcessingStage::Uploading => PersistedJobStage::Uploading,
+                    ProcessingStage::Scanning => PersistedJobStage::Scanning,
                     ProcessingStage::Parsing => PersistedJobStage::Parsing,
                     ProcessingStage::Chunking => PersistedJobStage::Chunking,
                     ProcessingStage::Embedding => PersistedJobStage::Embedding,
@@ -451,6 +705,28 @@ impl JobQueue {
         }
     }
 
+    /// Record a failed attempt of a file that may be retried
+    pub fn record_file_attempt(&self, job_id: Uuid, filename: &str, attempts: u32, error: &str) {
+        if let Err(e) = self.database.record_job_file_attempt(job_id, filename, attempts, error) {
+            tracing::error!("Failed to record attempt {} of {}: {}", attempts, filename, e);
+        }
+    }
+
+    /// Mark a file as failed after exhausting its retries (dead-lettered)
+    pub fn mark_file_dead_letter(
+        &self,
+        job_id: Uuid,
+        filename: &str,
+        error: &str,
+        stage: ProcessingStage,
+        duration_ms: u64,
+    ) {
+        self.mark_file_failed(job_id, filename, error, duration_ms);
+        if let Err(e) = self.database.dead_letter_job_file(job_id, filename, stage.as_str()) {
+            tracing::error!("Failed to dead-letter file {}: {}", filename, e);
+        }
+    }
+
     /// Mark a file as skipped in the database
     pub fn mark_file_skipped(&self, job_id: Uuid, filename: &str, reason: &str) {
         if let Err(e) = self.database.update_job_file_status(
@@ -465,10 +741,19 @@ impl JobQueue {
         }
     }
 
-    /// Clear file data after job completion (to save space)
+    /// Release the files of a finished job, removing spooled files no other job uses
     pub fn clear_job_file_data(&self, job_id: Uuid) {
-        if let Err(e) = self.database.clear_job_file_data(job_id) {
-            tracing::error!("Failed to clear file data for job {}: {}", job_id, e);
+        let released = match self.database.clear_job_file_data(job_id) {
+            Ok(released) => released,
+            Err(e) => {
+                tracing::error!("Failed to clear file data for job {}: {}", job_id, e);
+                return;
+            }
+        };
+        for hash in released {
+            if let Err(e) = self.spool.remove(&hash) {
+                tracing::warn!("Failed to remove spooled file {}: {}", hash, e);
+            }
         }
     }
 
@@ -551,13 +836,14 @@ impl JobQueue {
     }
 
     /// Add a file error
-    pub fn add_file_error(&self, job_id: Uuid, filename: &str, error: &str, stage: ProcessingStage) {
+    pub fn add_file_error(&self, job_id: Uuid, filename: &str, error: &Error, stage: ProcessingStage) {
         if let Some(mut progress) = self.jobs.get_mut(&job_id) {
             progress.files_failed += 1;
             progress.file_errors.push(FileError {
                 filename: filename.to_string(),
                 error: error.to_string(),
                 stage,
+                code: error.code().to_string(),
             });
             progress.updated_at = chrono::Utc::now();
             drop(progress); // Release lock before persisting
@@ -596,6 +882,7 @@ impl JobQueue {
                 completed_at: None,
                 duration_ms: None,
                 error: None,
+                bytes_processed: 0,
             };
             progress.file_progress.push(file_record);
             progress.updated_at = chrono::Utc::now();
@@ -632,6 +919,25 @@ impl JobQueue {
         }
     }
 
+    /// Update byte-level progress of a streamed file
+    pub fn update_file_bytes(
+        &self,
+        job_id: Uuid,
+        filename: &str,
+        status: FileProcessingStatus,
+        bytes_processed: u64,
+    ) {
+        if let Some(mut progress) = self.jobs.get_mut(&job_id) {
+            if let Some(file_record) = progress.file_progress.iter_mut()
+                .find(|f| f.filename == filename)
+            {
+                file_record.status = status;
+                file_record.bytes_processed = bytes_processed;
+            }
+            progress.updated_at = chrono::Utc::now();
+        }
+    }
+
     /// Complete file progress tracking
     pub fn complete_file_progress(
         &self,
@@ -0,0 +1,274 @@
+//! Keyword and entity extraction stage
+//!
+//! Chunks are tagged after redaction (masked PII is not picked up) and before
+//! embedding. Keywords are the best-scoring RAKE phrases: runs of words
+//! between stopwords and punctuation, scored by word degree over frequency.
+//! Entities are runs of capitalized words. With the `llm` extractor the
+//! generation model is asked for both instead.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+
+use crate::config::{KeywordExtractorKind, KeywordsConfig};
+use crate::generation::PromptBuilder;
+use crate::providers::LlmProvider;
+use crate::types::document::{ENTITIES_METADATA_KEY, KEYWORDS_METADATA_KEY};
+use crate::types::{Chunk, Document};
+
+/// Chunks sent to the LLM at once
+const LLM_CONCURRENCY: usize = 4;
+
+/// Words that end RAKE candidate phrases and can't start an entity
+const STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "an", "and", "any", "are", "as",
+    "at", "be", "because", "been", "before", "being", "below", "between", "both", "but", "by", "can", "could",
+    "did", "do", "does", "doing", "down", "during", "each", "either", "else", "few", "for", "from", "further",
+    "had", "has", "have", "having", "he", "her", "here", "hers", "him", "his", "how", "however", "i", "if",
+    "in", "into", "is", "it", "its", "itself", "just", "may", "me", "might", "more", "most", "must", "my",
+    "no", "nor", "not", "now", "of", "off", "on", "once", "only", "or", "other", "our", "ours", "out", "over",
+    "own", "per", "same", "shall", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "theirs", "them", "then", "there", "these", "they", "this", "those", "through", "thus", "to", "too",
+    "under", "until", "up", "upon", "us", "very", "via", "was", "we", "were", "what", "when", "where",
+    "whether", "which", "while", "who", "whom", "why", "will", "with", "within", "without", "would", "yet",
+    "you", "your", "yours",
+];
+
+/// Longest keyword phrase in words
+const MAX_PHRASE_WORDS: usize = 4;
+
+/// Keywords and entities of a text
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Extraction {
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub entities: Vec<String>,
+}
+
+/// Extracts keywords and entities for chunks
+pub struct KeywordExtractor {
+    config: KeywordsConfig,
+    llm: Arc<dyn LlmProvider>,
+}
+
+impl KeywordExtractor {
+    pub fn new(config: KeywordsConfig, llm: Arc<dyn LlmProvider>) -> Self {
+        Self { config, llm }
+    }
+
+    /// Whether extraction is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Keywords and entities of a text
+    pub async fn extract(&self, text: &str) -> Extraction {
+        if self.config.extractor == KeywordExtractorKind::Llm {
+            let prompt = PromptBuilder::build_keyword_prompt(text, self.config.max_keywords, self.config.max_entities);
+            match self.llm.complete(&prompt).await {
+                Ok(response) => match parse_llm_extraction(&response) {
+                    Some(mut extraction) => {
+                        extraction.keywords.truncate(self.config.max_keywords);
+                        extraction.entities.truncate(self.config.max_entities);
+                        return extraction;
+                    }
+                    None => tracing::warn!("Unparseable keyword extraction response, using RAKE"),
+                },
+                Err(e) => tracing::warn!("Keyword extraction failed, using RAKE: {}", e),
+            }
+        }
+
+        Extraction {
+            keywords: rake(text, self.config.max_keywords),
+            entities: capitalized_entities(text, self.config.max_entities),
+        }
+    }
+
+    /// Store keywords and entities in the chunks' metadata, counting entities
+    pub async fn tag_chunks(&self, chunks: &mut [Chunk], report: &mut EntityReport) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let extractions: Vec<Extraction> = stream::iter(chunks.iter())
+            .map(|chunk| self.extract(&chunk.content))
+            .buffered(LLM_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (chunk, extraction) in chunks.iter_mut().zip(extractions) {
+            report.record(&extraction.entities);
+            chunk.metadata.insert(KEYWORDS_METADATA_KEY.to_string(), serde_json::json!(extraction.keywords));
+            chunk.metadata.insert(ENTITIES_METADATA_KEY.to_string(), serde_json::json!(extraction.entities));
+        }
+    }
+
+    /// Record a document's most frequent entities in its `entities` metadata
+    pub fn apply(&self, report: &EntityReport, doc: &mut Document) {
+        if self.is_enabled() {
+            report.apply(doc, self.config.max_document_entities);
+        }
+    }
+}
+
+/// Entity counts across a document's chunks
+#[derive(Debug, Default)]
+pub struct EntityReport {
+    /// Lower-cased entity -> (first spelling, count, first seen)
+    counts: HashMap<String, (String, usize, usize)>,
+}
+
+impl EntityReport {
+    /// Count entities
+    pub fn record(&mut self, entities: &[String]) {
+        for entity in entities {
+            let seen = self.counts.len();
+            self.counts.entry(entity.to_lowercase()).or_insert_with(|| (entity.clone(), 0, seen)).1 += 1;
+        }
+    }
+
+    /// The `max` most frequent entities, in the document's `entities` metadata
+    pub fn apply(&self, doc: &mut Document, max: usize) {
+        let mut entities: Vec<&(String, usize, usize)> = self.counts.values().collect();
+        entities.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+        let entities: Vec<&str> = entities.into_iter().take(max).map(|(name, _, _)| name.as_str()).collect();
+        doc.metadata.insert(ENTITIES_METADATA_KEY.to_string(), serde_json::json!(entities));
+    }
+}
+
+/// Keyword extraction stage for a document's chunks
+///
+/// Does nothing unless extraction is enabled.
+pub async fn tag_document(extractor: &KeywordExtractor, doc: &mut Document, chunks: &mut [Chunk]) {
+    if !extractor.is_enabled() {
+        return;
+    }
+    let mut report = EntityReport::default();
+    extractor.tag_chunks(chunks, &mut report).await;
+    extractor.apply(&report, doc);
+}
+
+/// The `max` best RAKE keyword phrases of a text, lower-cased
+pub fn rake(text: &str, max: usize) -> Vec<String> {
+    let mut phrases: Vec<Vec<String>> = Vec::new();
+    for fragment in text.split(|c: char| ".,;:!?()[]{}\"|/\u{201c}\u{201d}".contains(c) || c == '\n') {
+        let mut phrase = Vec::new();
+        for word in fragment.split_whitespace() {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+            let keep = word.chars().count() > 1 && !is_stopword(&word) && !word.chars().all(|c| c.is_numeric());
+            if keep {
+                phrase.push(word);
+            } else if !phrase.is_empty() {
+                phrases.push(std::mem::take(&mut phrase));
+            }
+        }
+        if !phrase.is_empty() {
+            phrases.push(phrase);
+        }
+    }
+    phrases.retain(|p| p.len() <= MAX_PHRASE_WORDS);
+
+    // Word score: co-occurrence degree over frequency
+    let mut frequency: HashMap<&str, f32> = HashMap::new();
+    let mut degree: HashMap<&str, f32> = HashMap::new();
+    for phrase in &phrases {
+        for word in phrase {
+            *frequency.entry(word).or_default() += 1.0;
+            *degree.entry(word).or_default() += phrase.len() as f32;
+        }
+    }
+
+    let mut scored: HashMap<String, f32> = HashMap::new();
+    for phrase in &phrases {
+        let score = phrase.iter().map(|w| degree[w.as_str()] / frequency[w.as_str()]).sum();
+        scored.insert(phrase.join(" "), score);
+    }
+
+    let mut keywords: Vec<(String, f32)> = scored.into_iter().collect();
+    keywords.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    keywords.into_iter().take(max).map(|(phrase, _)| phrase).collect()
+}
+
+/// The `max` most frequent runs of capitalized words, in order of appearance on ties
+///
+/// Single capitalized words at the start of a sentence are skipped, as are
+/// leading stopwords ("The", "In").
+pub fn capitalized_entities(text: &str, max: usize) -> Vec<String> {
+    let mut report = EntityReport::default();
+    let mut run: Vec<&str> = Vec::new();
+    let mut run_at_sentence_start = false;
+    let mut sentence_start = true;
+
+    for raw in text.split_whitespace() {
+        let at_sentence_start = std::mem::replace(&mut sentence_start, false);
+        let word = raw.trim_matches(|c: char| !c.is_alphanumeric());
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase) && word.chars().count() > 1;
+
+        if capitalized && run.is_empty() && is_stopword(&word.to_lowercase()) {
+            sentence_start = at_sentence_start;
+        } else if capitalized {
+            if run.is_empty() {
+                run_at_sentence_start = at_sentence_start;
+            }
+            run.push(word);
+        } else {
+            record_entity(&mut report, &mut run, run_at_sentence_start);
+        }
+
+        let end = raw.trim_end_matches(&['"', '\'', ')', ']', '\u{201d}'][..]);
+        if end.ends_with(&['.', '!', '?', ':'][..]) {
+            record_entity(&mut report, &mut run, run_at_sentence_start);
+            sentence_start = true;
+        } else if raw.ends_with(&[',', ';', ')', ']'][..]) {
+            record_entity(&mut report, &mut run, run_at_sentence_start);
+        }
+    }
+    record_entity(&mut report, &mut run, run_at_sentence_start);
+
+    let mut entities: Vec<(String, usize, usize)> = report.counts.into_values().collect();
+    entities.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+    entities.into_iter().take(max).map(|(name, _, _)| name).collect()
+}
+
+fn record_entity(report: &mut EntityReport, run: &mut Vec<&str>, at_sentence_start: bool) {
+    if run.len() > 1 || (run.len() == 1 && !at_sentence_start) {
+        report.record(&[run.join(" ")]);
+    }
+    run.clear();
+}
+
+pub(crate) fn is_stopword(word: &str) -> bool {
+    STOPWORDS.contains(&word)
+}
+
+/// `{"keywords": [...], "entities": [...]}` in a model response
+fn parse_llm_extraction(response: &str) -> Option<Extraction> {
+    let start = response.find('{')?;
+    let end = response.rfind('}')?;
+    serde_json::from_str(response.get(start..=end)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rake_and_entities() {
+        let text = "The Acme Corporation signed a supply agreement with Globex in Berlin. \
+                    Under the supply agreement, Acme Corporation then ships industrial robots to Berlin.";
+
+        let keywords = rake(text, 4);
+        assert_eq!(keywords[0], "ships industrial robots");
+        assert!(keywords.contains(&"supply agreement".to_string()));
+
+        let entities = capitalized_entities(text, 10);
+        assert_eq!(entities, vec!["Acme Corporation", "Berlin", "Globex"]);
+
+        let parsed = parse_llm_extraction("Sure:\n{\"keywords\": [\"supply\"], \"entities\": [\"Acme\"]}").unwrap();
+        assert_eq!(parsed.entities, vec!["Acme"]);
+        assert!(parse_llm_extraction("no json").is_none());
+    }
+}
@@ -1,14 +1,32 @@
 //! Background processing with job queue and progress tracking
 
+mod embedding_repair;
 mod file_tier;
+mod gcs_sync;
 mod job_queue;
+pub mod keywords;
+mod queue_backend;
+pub mod redaction;
+mod reindex;
+mod scheduler;
+mod stages;
+mod tasks;
 mod worker;
 
+pub use embedding_repair::{EmbeddingRepair, RepairReport};
+pub use gcs_sync::{GcsSync, GcsSyncProgress};
 pub use file_tier::{
     FileCharacteristics, FileTier, ParserStrategy, PdfAnalysis,
 };
 pub use job_queue::{
-    FileData, FileError, FileProcessingStatus, FileProgressRecord, Job, JobQueue, JobProgress,
+    FileData, FileError, FileProcessingStatus, FileProgressRecord, Job, JobPriority, JobQueue, JobProgress,
     JobStatus, ParserAttemptRecord, ProcessingOptions, ProcessingStage, QueueStats,
 };
+pub use queue_backend::{InProcessQueue, LeasedJob, QueueBackend};
+#[cfg(feature = "redis-queue")]
+pub use queue_backend::RedisQueue;
+pub use reindex::{ReindexManager, ReindexProgress, ReindexStatus};
+pub use scheduler::{FairScheduler, SlotPermit};
+pub(crate) use reindex::{merge_overlapping, reconstruct_text};
+pub use tasks::{TaskHandle, TaskKind, TaskProgress, TaskRegistry, TaskStatus};
 pub use worker::ProcessingWorker;
@@ -0,0 +1,572 @@
+//! Queue backends for distributing jobs between API servers and workers
+//!
+//! The in-process backend keeps the original single-server behaviour (a tokio
+//! channel). The Redis backend lets several API servers enqueue into a shared
+//! queue while ingestion workers run as separate processes, with leases and
+//! heartbeats so jobs held by a crashed worker are handed out again.
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, Mutex};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+use super::job_queue::{Job, JobPriority, JobProgress};
+
+/// A job handed to a worker together with its lease
+#[derive(Debug, Clone)]
+pub struct LeasedJob {
+    /// The job to process
+    pub job: Job,
+    /// Worker holding the lease
+    pub worker_id: String,
+    /// Number of times this job has been delivered (1 on first delivery)
+    pub delivery_count: u32,
+}
+
+/// Trait for job queue storage
+///
+/// Implementations:
+/// - `InProcessQueue`: tokio channel, jobs only visible to this process
+/// - `RedisQueue`: shared Redis lists with per-job leases (feature `redis-queue`)
+#[async_trait]
+pub trait QueueBackend: Send + Sync {
+    /// Add a job to the queue
+    async fn enqueue(&self, job: Job) -> Result<()>;
+
+    /// Wait for the next job of the `priority` lane and take a lease on it
+    ///
+    /// Returns `None` when the queue is closed and no more jobs will arrive.
+    async fn dequeue(&self, worker_id: &str, priority: JobPriority) -> Result<Option<LeasedJob>>;
+
+    /// Extend the lease on a job that is still being processed
+    ///
+    /// Returns `false` when `worker_id` no longer holds the lease (it expired
+    /// and the job may be running elsewhere); the worker should stop the job.
+    async fn heartbeat(&self, job_id: Uuid, worker_id: &str) -> Result<bool>;
+
+    /// Remove a finished job (completed or permanently failed) from the queue
+    ///
+    /// Returns `false`, leaving the job alone, when `worker_id` no longer
+    /// holds its lease.
+    async fn ack(&self, job_id: Uuid, worker_id: &str) -> Result<bool>;
+
+    /// Return jobs whose lease expired to the pending queue
+    ///
+    /// Returns the number of jobs reclaimed.
+    async fn reclaim_expired(&self) -> Result<usize>;
+
+    /// Share a job's progress with the other processes using the queue
+    async fn publish_status(&self, _progress: &JobProgress) -> Result<()> {
+        Ok(())
+    }
+
+    /// Progress of a job as last published by any process
+    async fn job_status(&self, _job_id: Uuid) -> Result<Option<JobProgress>> {
+        Ok(None)
+    }
+
+    /// Whether jobs survive a process restart (resume from SQLite is skipped if so)
+    fn is_shared(&self) -> bool;
+
+    /// Get backend name for logging
+    fn name(&self) -> &str;
+}
+
+/// In-process queue backed by a tokio channel per priority lane
+///
+/// Leases are implicit: a job belongs to the worker that received it and is
+/// lost if the process dies (SQLite job persistence covers restarts).
+pub struct InProcessQueue {
+    interactive: Lane,
+    bulk: Lane,
+}
+
+struct Lane {
+    sender: mpsc::Sender<Job>,
+    receiver: Mutex<mpsc::Receiver<Job>>,
+}
+
+impl Lane {
+    fn new(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity);
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+impl InProcessQueue {
+    /// Create a new in-process queue with the given capacity per lane
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            interactive: Lane::new(capacity),
+            bulk: Lane::new(capacity),
+        }
+    }
+
+    fn lane(&self, priority: JobPriority) -> &Lane {
+        match priority {
+            JobPriority::Interactive => &self.interactive,
+            JobPriority::Bulk => &self.bulk,
+        }
+    }
+}
+
+#[async_trait]
+impl QueueBackend for InProcessQueue {
+    async fn enqueue(&self, job: Job) -> Result<()> {
+        self.lane(job.options.priority)
+            .sender
+            .send(job)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to enqueue job: {}", e)))
+    }
+
+    async fn dequeue(&self, worker_id: &str, priority: JobPriority) -> Result<Option<LeasedJob>> {
+        let job = self.lane(priority).receiver.lock().await.recv().await;
+        Ok(job.map(|job| LeasedJob {
+            job,
+            worker_id: worker_id.to_string(),
+            delivery_count: 1,
+        }))
+    }
+
+    async fn heartbeat(&self, _job_id: Uuid, _worker_id: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn ack(&self, _job_id: Uuid, _worker_id: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn reclaim_expired(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "in-process"
+    }
+}
+
+#[cfg(feature = "redis-queue")]
+pub use redis_backend::RedisQueue;
+
+#[cfg(feature = "redis-queue")]
+mod redis_backend {
+    use super::*;
+    use crate::processing::JobStatus;
+    use redis::AsyncCommands;
+    use std::time::Duration;
+
+    /// Redis-backed shared queue
+    ///
+    /// Layout (all keys under `prefix`):
+    /// - `{prefix}:pending`      list of interactive job IDs waiting for a worker
+    /// - `{prefix}:pending:bulk` list of bulk job IDs waiting for a worker
+    /// - `{prefix}:processing`   list of job IDs currently leased
+    /// - `{prefix}:job:{id}`     serialized job payload
+    /// - `{prefix}:lease:{id}`   worker ID holding the lease, expires after `lease_ttl`
+    /// - `{prefix}:deliveries`   hash of job ID -> delivery count
+    /// - `{prefix}:dead`         list of job IDs given up after `max_deliveries`
+    /// - `{prefix}:status:{id}`  last published progress, expires after a week
+    pub struct RedisQueue {
+        conn: redis::aio::ConnectionManager,
+        prefix: String,
+        lease_ttl: Duration,
+        max_deliveries: u32,
+        /// Pause between polls of an empty lane
+        poll_interval: Duration,
+        /// How often an idle worker looks for expired leases
+        reclaim_interval: Duration,
+    }
+
+    /// How long published job progress is kept
+    const STATUS_TTL_SECS: u64 = 7 * 24 * 3600;
+
+    /// Move the oldest pending job to the processing list, count the delivery
+    /// and take the lease in one step, so `reclaim_expired` never sees a
+    /// leased job without its lease. Jobs delivered more than the maximum go
+    /// to the dead-letter list instead.
+    ///
+    /// KEYS: pending, processing, deliveries, dead
+    /// ARGV: lease key prefix, worker ID, max deliveries, lease seconds
+    /// Returns `false` or `{job_id, delivery_count, dead (0 or 1)}`.
+    const DEQUEUE_SCRIPT: &str = r#"
+local id = redis.call('LMOVE', KEYS[1], KEYS[2], 'RIGHT', 'LEFT')
+if not id then
+  return false
+end
+local count = redis.call('HINCRBY', KEYS[3], id, 1)
+if count > tonumber(ARGV[3]) then
+  redis.call('LREM', KEYS[2], 1, id)
+  redis.call('LPUSH', KEYS[4], id)
+  redis.call('HDEL', KEYS[3], id)
+  return {id, count, 1}
+end
+redis.call('SET', ARGV[1] .. id, ARGV[2], 'EX', ARGV[4])
+return {id, count, 0}
+"#;
+
+    /// Extend a lease only if the worker still holds it
+    ///
+    /// KEYS: lease
+    /// ARGV: worker ID, lease seconds
+    /// Returns 1 if the lease was extended, 0 if it is gone or held by another worker.
+    const HEARTBEAT_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+  return 0
+end
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 1
+"#;
+
+    /// Drop a finished job only if the worker still holds its lease
+    ///
+    /// KEYS: lease, processing, job, deliveries
+    /// ARGV: worker ID, job ID
+    /// Returns 1 if the job was removed, 0 if the lease is gone or held by another worker.
+    const ACK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+  return 0
+end
+redis.call('LREM', KEYS[2], 1, ARGV[2])
+redis.call('DEL', KEYS[3], KEYS[1])
+redis.call('HDEL', KEYS[4], ARGV[2])
+return 1
+"#;
+
+    /// Move every processing job without a lease back to the front of its
+    /// lane (interactive if the payload is gone or unreadable) in one step, so
+    /// a job is never requeued twice or leased while being reclaimed.
+    ///
+    /// KEYS: processing, pending, pending bulk
+    /// ARGV: lease key prefix, job key prefix
+    /// Returns the IDs of the reclaimed jobs.
+    const RECLAIM_SCRIPT: &str = r#"
+local reclaimed = {}
+for _, id in ipairs(redis.call('LRANGE', KEYS[1], 0, -1)) do
+  if redis.call('EXISTS', ARGV[1] .. id) == 0 then
+    redis.call('LREM', KEYS[1], 1, id)
+    local lane = KEYS[2]
+    local payload = redis.call('GET', ARGV[2] .. id)
+    if payload then
+      local ok, job = pcall(cjson.decode, payload)
+      if ok and type(job) == 'table' and type(job.options) == 'table' and job.options.priority == 'bulk' then
+        lane = KEYS[3]
+      end
+    end
+    redis.call('RPUSH', lane, id)
+    table.insert(reclaimed, id)
+  end
+end
+return reclaimed
+"#;
+
+    impl RedisQueue {
+        /// Connect to Redis
+        pub async fn connect(url: &str, prefix: &str, lease_ttl: Duration, max_deliveries: u32) -> Result<Self> {
+            let client = redis::Client::open(url)
+                .map_err(|e| Error::Config(format!("Invalid Redis URL: {}", e)))?;
+            let conn = redis::aio::ConnectionManager::new(client)
+                .await
+                .map_err(|e| Error::Internal(format!("Failed to connect to Redis: {}", e)))?;
+
+            tracing::info!("Redis job queue connected (prefix: {})", prefix);
+
+            Ok(Self {
+                conn,
+                prefix: prefix.to_string(),
+                lease_ttl,
+                max_deliveries: max_deliveries.max(1),
+                poll_interval: Duration::from_millis(500),
+                reclaim_interval: Duration::from_secs(5),
+            })
+        }
+
+        fn pending_key(&self, priority: JobPriority) -> String {
+            match priority {
+                JobPriority::Interactive => format!("{}:pending", self.prefix),
+                JobPriority::Bulk => format!("{}:pending:bulk", self.prefix),
+            }
+        }
+
+        fn processing_key(&self) -> String {
+            format!("{}:processing", self.prefix)
+        }
+
+        fn job_key(&self, job_id: &str) -> String {
+            format!("{}:job:{}", self.prefix, job_id)
+        }
+
+        fn lease_key(&self, job_id: &str) -> String {
+            format!("{}:lease:{}", self.prefix, job_id)
+        }
+
+        fn deliveries_key(&self) -> String {
+            format!("{}:deliveries", self.prefix)
+        }
+
+        fn dead_key(&self) -> String {
+            format!("{}:dead", self.prefix)
+        }
+
+        fn status_key(&self, job_id: &str) -> String {
+            format!("{}:status:{}", self.prefix, job_id)
+        }
+
+        /// Record a job given up after too many deliveries as failed
+        async fn fail_dead_letter(&self, job_id: &str, delivery_count: u32) -> Result<()> {
+            let mut conn = self.conn.clone();
+            let payload: Option<String> = conn
+                .get(self.job_key(job_id))
+                .await
+                .map_err(|e| redis_err("Failed to load job payload", e))?;
+            let Some(job) = payload.and_then(|payload| serde_json::from_str::<Job>(&payload).ok()) else {
+                return Ok(());
+            };
+            let mut progress = self.job_status(job.id).await?.unwrap_or_else(|| JobProgress::new(job.id, job.files.len()));
+            progress.status = JobStatus::Failed;
+            progress.error = Some(format!("Given up after {} deliveries (moved to {})", delivery_count - 1, self.dead_key()));
+            progress.updated_at = chrono::Utc::now();
+            self.publish_status(&progress).await
+        }
+    }
+
+    fn redis_err(context: &str, e: redis::RedisError) -> Error {
+        Error::Internal(format!("{}: {}", context, e))
+    }
+
+    #[async_trait]
+    impl QueueBackend for RedisQueue {
+        async fn enqueue(&self, job: Job) -> Result<()> {
+            let mut conn = self.conn.clone();
+            let job_id = job.id.to_string();
+            let payload = serde_json::to_string(&job)?;
+
+            let _: () = redis::pipe()
+                .atomic()
+                .set(self.job_key(&job_id), payload)
+                .lpush(self.pending_key(job.options.priority), &job_id)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| redis_err("Failed to enqueue job", e))?;
+
+            Ok(())
+        }
+
+        async fn dequeue(&self, worker_id: &str, priority: JobPriority) -> Result<Option<LeasedJob>> {
+            let mut conn = self.conn.clone();
+            let script = redis::Script::new(DEQUEUE_SCRIPT);
+            let mut last_reclaim = std::time::Instant::now();
+
+            loop {
+                let dequeued: Option<(String, u32, u8)> = script
+                    .key(self.pending_key(priority))
+                    .key(self.processing_key())
+                    .key(self.deliveries_key())
+                    .key(self.dead_key())
+                    .arg(format!("{}:lease:", self.prefix))
+                    .arg(worker_id)
+                    .arg(self.max_deliveries)
+                    .arg(self.lease_ttl.as_secs().max(1))
+                    .invoke_async(&mut conn)
+                    .await
+                    .map_err(|e| redis_err("Failed to dequeue job", e))?;
+
+                let Some((job_id, delivery_count, dead)) = dequeued else {
+                    // Nothing pending - use the idle time to recover abandoned jobs
+                    if last_reclaim.elapsed() >= self.reclaim_interval {
+                        last_reclaim = std::time::Instant::now();
+                        if let Err(e) = self.reclaim_expired().await {
+                            tracing::warn!("Failed to reclaim expired jobs: {}", e);
+                        }
+                    }
+                    tokio::time::sleep(self.poll_interval).await;
+                    continue;
+                };
+
+                if dead == 1 {
+                    tracing::error!(
+                        "Job {} was delivered {} times without finishing, moved to the dead-letter list",
+                        job_id,
+                        delivery_count - 1
+                    );
+                    if let Err(e) = self.fail_dead_letter(&job_id, delivery_count).await {
+                        tracing::warn!("Failed to record dead-lettered job {}: {}", job_id, e);
+                    }
+                    continue;
+                }
+
+                let payload: Option<String> = conn
+                    .get(self.job_key(&job_id))
+                    .await
+                    .map_err(|e| redis_err("Failed to load job payload", e))?;
+
+                let Some(payload) = payload else {
+                    tracing::warn!("Job {} has no payload in Redis, dropping", job_id);
+                    let _: () = conn
+                        .lrem(self.processing_key(), 1, &job_id)
+                        .await
+                        .map_err(|e| redis_err("Failed to drop job", e))?;
+                    continue;
+                };
+
+                let job: Job = serde_json::from_str(&payload)?;
+                return Ok(Some(LeasedJob {
+                    job,
+                    worker_id: worker_id.to_string(),
+                    delivery_count,
+                }));
+            }
+        }
+
+        async fn heartbeat(&self, job_id: Uuid, worker_id: &str) -> Result<bool> {
+            let mut conn = self.conn.clone();
+            let extended: u8 = redis::Script::new(HEARTBEAT_SCRIPT)
+                .key(self.lease_key(&job_id.to_string()))
+                .arg(worker_id)
+                .arg(self.lease_ttl.as_secs().max(1))
+                .invoke_async(&mut conn)
+                .await
+                .map_err(|e| redis_err("Failed to extend job lease", e))?;
+            Ok(extended == 1)
+        }
+
+        async fn ack(&self, job_id: Uuid, worker_id: &str) -> Result<bool> {
+            let mut conn = self.conn.clone();
+            let job_id = job_id.to_string();
+
+            let removed: u8 = redis::Script::new(ACK_SCRIPT)
+                .key(self.lease_key(&job_id))
+                .key(self.processing_key())
+                .key(self.job_key(&job_id))
+                .key(self.deliveries_key())
+                .arg(worker_id)
+                .arg(&job_id)
+                .invoke_async(&mut conn)
+                .await
+                .map_err(|e| redis_err("Failed to ack job", e))?;
+
+            Ok(removed == 1)
+        }
+
+        async fn reclaim_expired(&self) -> Result<usize> {
+            let mut conn = self.conn.clone();
+            let reclaimed: Vec<String> = redis::Script::new(RECLAIM_SCRIPT)
+                .key(self.processing_key())
+                .key(self.pending_key(JobPriority::Interactive))
+                .key(self.pending_key(JobPriority::Bulk))
+                .arg(format!("{}:lease:", self.prefix))
+                .arg(format!("{}:job:", self.prefix))
+                .invoke_async(&mut conn)
+                .await
+                .map_err(|e| redis_err("Failed to reclaim expired jobs", e))?;
+
+            for job_id in &reclaimed {
+                tracing::warn!("Reclaimed job {} after lease expired", job_id);
+            }
+            Ok(reclaimed.len())
+        }
+
+        async fn publish_status(&self, progress: &JobProgress) -> Result<()> {
+            let mut conn = self.conn.clone();
+            let payload = serde_json::to_string(progress)?;
+            let _: () = conn
+                .set_ex(self.status_key(&progress.job_id.to_string()), payload, STATUS_TTL_SECS)
+                .await
+                .map_err(|e| redis_err("Failed to publish job status", e))?;
+            Ok(())
+        }
+
+        async fn job_status(&self, job_id: Uuid) -> Result<Option<JobProgress>> {
+            let mut conn = self.conn.clone();
+            let payload: Option<String> = conn
+                .get(self.status_key(&job_id.to_string()))
+                .await
+                .map_err(|e| redis_err("Failed to load job status", e))?;
+            Ok(payload.map(|payload| serde_json::from_str(&payload)).transpose()?)
+        }
+
+        fn is_shared(&self) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "redis"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn job(priority: JobPriority) -> Job {
+        let mut job = Job::default();
+        job.options.priority = priority;
+        job
+    }
+
+    #[tokio::test]
+    async fn test_in_process_lanes_are_separate() {
+        let queue = InProcessQueue::new(4);
+        let bulk = job(JobPriority::Bulk);
+        let interactive = job(JobPriority::Interactive);
+        queue.enqueue(bulk.clone()).await.unwrap();
+        queue.enqueue(interactive.clone()).await.unwrap();
+
+        // Each lane hands out only its own jobs, whatever the enqueue order
+        let leased = queue.dequeue("w1", JobPriority::Interactive).await.unwrap().unwrap();
+        assert_eq!(leased.job.id, interactive.id);
+        let leased = queue.dequeue("w1", JobPriority::Bulk).await.unwrap().unwrap();
+        assert_eq!(leased.job.id, bulk.id);
+
+        let empty = tokio::time::timeout(Duration::from_millis(50), queue.dequeue("w1", JobPriority::Interactive));
+        assert!(empty.await.is_err(), "interactive lane should be empty");
+    }
+
+    #[tokio::test]
+    async fn test_in_process_lane_is_fifo() {
+        let queue = InProcessQueue::new(4);
+        let jobs: Vec<Job> = (0..3).map(|_| job(JobPriority::Bulk)).collect();
+        for job in &jobs {
+            queue.enqueue(job.clone()).await.unwrap();
+        }
+        for job in &jobs {
+            let leased = queue.dequeue("w1", JobPriority::Bulk).await.unwrap().unwrap();
+            assert_eq!(leased.job.id, job.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_process_lease_belongs_to_receiver() {
+        let queue = InProcessQueue::new(4);
+        queue.enqueue(job(JobPriority::Interactive)).await.unwrap();
+
+        let leased = queue.dequeue("w1", JobPriority::Interactive).await.unwrap().unwrap();
+        assert_eq!(leased.worker_id, "w1");
+        assert_eq!(leased.delivery_count, 1);
+        assert!(queue.heartbeat(leased.job.id, "w1").await.unwrap());
+        assert!(queue.ack(leased.job.id, "w1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_in_process_reclaim_never_redelivers() {
+        let queue = InProcessQueue::new(4);
+        queue.enqueue(job(JobPriority::Interactive)).await.unwrap();
+        let _leased = queue.dequeue("w1", JobPriority::Interactive).await.unwrap().unwrap();
+
+        // Leases are implicit, so a leased job is never handed out again
+        assert_eq!(queue.reclaim_expired().await.unwrap(), 0);
+        let again = tokio::time::timeout(Duration::from_millis(50), queue.dequeue("w2", JobPriority::Interactive));
+        assert!(again.await.is_err());
+        assert!(!queue.is_shared());
+    }
+}
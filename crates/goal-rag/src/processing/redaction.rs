@@ -0,0 +1,337 @@
+//! PII detection and redaction stage
+//!
+//! Chunks are scanned after chunking and before embedding: emails, SSNs,
+//! phone numbers and card numbers by pattern, other entities (names,
+//! locations) with an optional NER service. Findings are counted on the
+//! document, and the collection's policy decides whether chunk text and
+//! generated answers are masked.
+
+use regex::Regex;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::{PiiKind, RedactionConfig, RedactionPolicy};
+use crate::error::{Error, Result};
+use crate::types::document::{COLLECTION_METADATA_KEY, PII_METADATA_KEY};
+use crate::types::{Chunk, Document};
+
+/// A piece of personal data in a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Upper-case label used in masks (`EMAIL`, `PERSON`, ...)
+    pub label: String,
+    /// Byte range in the text
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Detects and masks PII according to the configured policies
+pub struct Redactor {
+    client: Client,
+    config: RedactionConfig,
+}
+
+impl Redactor {
+    /// Create a new redactor
+    pub fn new(config: RedactionConfig) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.ner_timeout_secs))
+            .build()
+            .expect("Failed to create HTTP client");
+        Self { client, config }
+    }
+
+    /// Whether PII scanning is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Find PII in a text with a policy's patterns and entities
+    ///
+    /// NER failures are logged and only pattern findings returned.
+    pub async fn detect(&self, text: &str, policy: &RedactionPolicy) -> Vec<Finding> {
+        let mut findings = detect_patterns(text, &policy.detect);
+
+        if let (Some(url), false) = (self.config.ner_url.as_deref(), policy.entities.is_empty()) {
+            match self.detect_entities(url, text, &policy.entities).await {
+                Ok(entities) => findings.extend(entities),
+                Err(e) => tracing::warn!("NER detection failed, using patterns only: {}", e),
+            }
+        }
+
+        findings.sort_by_key(|f| (f.start, std::cmp::Reverse(f.end)));
+        findings
+    }
+
+    /// Entities found by a Presidio-compatible analyzer
+    async fn detect_entities(&self, url: &str, text: &str, entities: &[String]) -> Result<Vec<Finding>> {
+        #[derive(Deserialize)]
+        struct Entity {
+            entity_type: String,
+            start: usize,
+            end: usize,
+        }
+
+        let response = self
+            .client
+            .post(url)
+            .json(&serde_json::json!({
+                "text": text,
+                "language": "en",
+                "entities": entities,
+                "score_threshold": self.config.ner_min_score,
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!("NER service returned {}", response.status())));
+        }
+        let found: Vec<Entity> = response.json().await?;
+
+        // Offsets are in characters; findings use byte offsets
+        let bytes: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).collect();
+        Ok(found
+            .into_iter()
+            .filter(|e| e.start < e.end && e.end < bytes.len())
+            .map(|e| Finding {
+                label: e.entity_type.to_uppercase(),
+                start: bytes[e.start],
+                end: bytes[e.end],
+            })
+            .collect())
+    }
+
+    /// Scan chunks, counting findings and masking them if the policy says so
+    ///
+    /// Masked chunks also have PII patterns replaced in their section title
+    /// and metadata (email sender, subject).
+    pub async fn redact_chunks(&self, collection: Option<&str>, chunks: &mut [Chunk], report: &mut PiiReport) {
+        if !self.is_enabled() {
+            return;
+        }
+        let policy = self.config.policy(collection);
+        report.masked |= policy.mask_chunks;
+
+        for chunk in chunks.iter_mut() {
+            let findings = self.detect(&chunk.content, policy).await;
+            report.record(&findings);
+            if !policy.mask_chunks {
+                continue;
+            }
+
+            if !findings.is_empty() {
+                chunk.content = mask(&chunk.content, &findings);
+            }
+            if let Some(ref title) = chunk.source.section_title {
+                chunk.source.section_title = Some(mask_patterns(title, &policy.detect));
+            }
+            for (key, value) in chunk.metadata.iter_mut() {
+                if key.as_str() == COLLECTION_METADATA_KEY {
+                    continue;
+                }
+                if let Some(text) = value.as_str() {
+                    *value = serde_json::Value::String(mask_patterns(text, &policy.detect));
+                }
+            }
+        }
+    }
+
+    /// Mask PII in a generated answer if the collection's policy says so
+    pub async fn redact_answer(&self, collection: Option<&str>, answer: String) -> String {
+        if !self.is_enabled() {
+            return answer;
+        }
+        let policy = self.config.policy(collection);
+        if !policy.mask_answers {
+            return answer;
+        }
+
+        let findings = self.detect(&answer, policy).await;
+        if findings.is_empty() {
+            answer
+        } else {
+            tracing::debug!("Masked {} PII findings in answer", findings.len());
+            mask(&answer, &findings)
+        }
+    }
+
+    /// Mask PII in a text with the collection's policy, whether or not
+    /// scanning is enabled (for debug output such as rendered prompts)
+    pub async fn redact_text(&self, collection: Option<&str>, text: &str) -> String {
+        let findings = self.detect(text, self.config.policy(collection)).await;
+        mask(text, &findings)
+    }
+}
+
+/// Finding counts for a document
+#[derive(Debug, Clone, Default)]
+pub struct PiiReport {
+    counts: BTreeMap<String, usize>,
+    masked: bool,
+}
+
+impl PiiReport {
+    /// Count findings
+    pub fn record(&mut self, findings: &[Finding]) {
+        for finding in findings {
+            *self.counts.entry(finding.label.to_lowercase()).or_default() += 1;
+        }
+    }
+
+    /// Record the counts in the document's `pii` metadata
+    pub fn apply(&self, doc: &mut Document) {
+        doc.metadata.insert(
+            PII_METADATA_KEY.to_string(),
+            serde_json::json!({ "findings": self.counts, "masked": self.masked }),
+        );
+    }
+}
+
+/// Redaction stage for a document's chunks
+///
+/// Does nothing unless redaction is enabled.
+pub async fn redact_document(redactor: &Redactor, doc: &mut Document, chunks: &mut [Chunk]) {
+    if !redactor.is_enabled() {
+        return;
+    }
+    let mut report = PiiReport::default();
+    let collection = doc.collection().map(str::to_string);
+    redactor.redact_chunks(collection.as_deref(), chunks, &mut report).await;
+
+    if !report.counts.is_empty() {
+        tracing::info!("PII in '{}': {:?}{}", doc.filename, report.counts, if report.masked { " (masked)" } else { "" });
+    }
+    report.apply(doc);
+}
+
+/// Find pattern-matched PII kinds in a text, ordered by position
+pub fn detect_patterns(text: &str, kinds: &[PiiKind]) -> Vec<Finding> {
+    static PATTERNS: OnceLock<Vec<(PiiKind, Regex)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            (PiiKind::Email, r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+            (PiiKind::CreditCard, r"\b(?:\d[ -]?){12,18}\d\b"),
+            (PiiKind::Ssn, r"\b\d{3}-\d{2}-\d{4}\b"),
+            (PiiKind::Phone, r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)\s?|\b\d{3}[\s.-]?)\d{3}[\s.-]?\d{4}\b"),
+        ]
+        .into_iter()
+        .map(|(kind, pattern)| (kind, Regex::new(pattern).unwrap()))
+        .collect()
+    });
+
+    let mut findings: Vec<Finding> = patterns
+        .iter()
+        .filter(|(kind, _)| kinds.contains(kind))
+        .flat_map(|(kind, regex)| {
+            regex
+                .find_iter(text)
+                .filter(|m| is_valid(*kind, m.as_str()))
+                .map(|m| Finding {
+                    label: kind.label().to_string(),
+                    start: m.start(),
+                    end: m.end(),
+                })
+        })
+        .collect();
+    findings.sort_by_key(|f| (f.start, std::cmp::Reverse(f.end)));
+    findings
+}
+
+/// Checks that cut false positives (invalid SSN ranges, failed Luhn)
+fn is_valid(kind: PiiKind, text: &str) -> bool {
+    match kind {
+        PiiKind::Ssn => {
+            let parts: Vec<&str> = text.split('-').collect();
+            let area: u32 = parts[0].parse().unwrap_or(0);
+            area != 0 && area != 666 && area < 900 && parts[1] != "00" && parts[2] != "0000"
+        }
+        PiiKind::CreditCard => {
+            let digits: Vec<u32> = text.chars().filter_map(|c| c.to_digit(10)).collect();
+            (13..=19).contains(&digits.len()) && luhn(&digits)
+        }
+        PiiKind::Email | PiiKind::Phone => true,
+    }
+}
+
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Replace findings with `[LABEL]`; overlapping findings are masked once
+pub fn mask(text: &str, findings: &[Finding]) -> String {
+    let mut sorted: Vec<&Finding> = findings.iter().collect();
+    sorted.sort_by_key(|f| (f.start, std::cmp::Reverse(f.end)));
+
+    let mut masked = String::with_capacity(text.len());
+    let mut pos = 0;
+    for finding in sorted {
+        if finding.start < pos || finding.end > text.len() {
+            continue;
+        }
+        masked.push_str(&text[pos..finding.start]);
+        masked.push('[');
+        masked.push_str(&finding.label);
+        masked.push(']');
+        pos = finding.end;
+    }
+    masked.push_str(&text[pos..]);
+    masked
+}
+
+fn mask_patterns(text: &str, kinds: &[PiiKind]) -> String {
+    let findings = detect_patterns(text, kinds);
+    if findings.is_empty() {
+        text.to_string()
+    } else {
+        mask(text, &findings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: &[PiiKind] = &[PiiKind::Email, PiiKind::Ssn, PiiKind::Phone, PiiKind::CreditCard];
+
+    #[test]
+    fn test_detect_and_mask() {
+        let text = "Contact jane.doe@example.com or (555) 123-4567. SSN 123-45-6789, card 4111 1111 1111 1111.";
+        let findings = detect_patterns(text, ALL);
+        let labels: Vec<&str> = findings.iter().map(|f| f.label.as_str()).collect();
+        assert_eq!(labels, vec!["EMAIL", "PHONE", "SSN", "CREDIT_CARD"]);
+        assert_eq!(
+            mask(text, &findings),
+            "Contact [EMAIL] or [PHONE]. SSN [SSN], card [CREDIT_CARD]."
+        );
+
+        assert_eq!(detect_patterns(text, &[PiiKind::Email]).len(), 1);
+    }
+
+    #[test]
+    fn test_false_positives() {
+        // Invalid SSN area, card number failing Luhn, plain numbers
+        assert!(detect_patterns("Ticket 666-12-3456", &[PiiKind::Ssn]).is_empty());
+        assert!(detect_patterns("Order 4111 1111 1111 1112", &[PiiKind::CreditCard]).is_empty());
+        assert!(detect_patterns("Revenue grew 12% to 1,234 units in 2024", ALL).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = PiiReport::default();
+        report.record(&detect_patterns("a@b.io, c@d.io, 555-123-4567", ALL));
+        let mut doc = Document::new("notes.txt".into(), crate::types::FileType::Txt, "hash".into(), 0);
+        report.apply(&mut doc);
+        assert_eq!(doc.metadata[PII_METADATA_KEY]["findings"]["email"], 2);
+        assert_eq!(doc.metadata[PII_METADATA_KEY]["findings"]["phone"], 1);
+        assert_eq!(doc.metadata[PII_METADATA_KEY]["masked"], false);
+    }
+}
@@ -0,0 +1,659 @@
+//! Background reindexing with atomic index swap
+//!
+//! Rebuilds the whole index from the stored text with the current chunking
+//! and embedding configuration. Text comes from the chunks stored in SQLite,
+//! or from the plain text kept in the document store (GCS with the GCP
+//! backend) for documents without stored chunks.
+//!
+//! With the local backend the new HNSW index is built next to the live one
+//! while the old index keeps serving queries; once every document is done the
+//! chunk table, index files and providers are swapped in one step. Vertex AI
+//! indexes are managed in GCP and cannot be built side by side, so there each
+//! document's vectors are replaced as soon as its new chunks are embedded.
+//!
+//! Runs start on request (`POST /api/admin/reindex`) or on the cron schedule
+//! in `embeddings.reindex_schedule`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::BackendProvider;
+use crate::error::{Error, Result};
+use crate::ingestion::{IngestPipeline, PageContent, ParsedDocument};
+use crate::providers::{local::LocalVectorStore, EmbeddingProvider, VectorStoreProvider};
+use crate::retrieval::VectorStore;
+use crate::server::schedule::CronSchedule;
+use crate::server::state::AppState;
+use crate::storage::ChunkContentRecord;
+use crate::types::document::COLLECTION_METADATA_KEY;
+use crate::types::{Chunk, ChunkKind, ChunkSource, Document};
+
+use super::keywords::EntityReport;
+use super::redaction::PiiReport;
+use super::tasks::{TaskHandle, TaskKind};
+
+/// Minimum shared text (bytes) for two stored chunks to count as overlapping
+const MIN_OVERLAP: usize = 8;
+
+/// How often the scheduler checks whether a reindex is due
+const SCHEDULER_TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Reindex status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReindexStatus {
+    Running,
+    Swapping,
+    Complete,
+    Failed,
+}
+
+/// Progress information for a reindex run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexProgress {
+    pub id: Uuid,
+    /// Task id for `/api/admin/tasks` (throughput and ETA)
+    pub task_id: Uuid,
+    pub status: ReindexStatus,
+    pub total_documents: usize,
+    pub documents_processed: usize,
+    /// Documents without stored text (nothing to reindex)
+    pub documents_skipped: usize,
+    pub current_document: Option<String>,
+    pub chunks_created: usize,
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    pub embedding_model: String,
+    pub dimensions: usize,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl ReindexProgress {
+    pub fn percent_complete(&self) -> f32 {
+        if self.total_documents == 0 {
+            return 0.0;
+        }
+        self.documents_processed as f32 / self.total_documents as f32 * 100.0
+    }
+}
+
+/// Tracks the (single) reindex run
+pub struct ReindexManager {
+    running: AtomicBool,
+    progress: RwLock<Option<ReindexProgress>>,
+    task: RwLock<Option<TaskHandle>>,
+}
+
+impl ReindexManager {
+    pub fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            progress: RwLock::new(None),
+            task: RwLock::new(None),
+        }
+    }
+
+    /// Progress of the current or most recent run
+    pub fn progress(&self) -> Option<ReindexProgress> {
+        self.progress.read().clone()
+    }
+
+    /// Whether a reindex is in progress
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start a reindex in the background
+    pub fn start(self: &Arc<Self>, state: AppState) -> Result<ReindexProgress> {
+        let config = state.config();
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::Config("A reindex is already running".to_string()));
+        }
+
+        let total_documents = state.documents().len();
+        let task = state.tasks().start(TaskKind::Reindex, "rebuilding", total_documents, "documents");
+
+        let progress = ReindexProgress {
+            id: Uuid::new_v4(),
+            task_id: task.id(),
+            status: ReindexStatus::Running,
+            total_documents,
+            documents_processed: 0,
+            documents_skipped: 0,
+            current_document: None,
+            chunks_created: 0,
+            chunk_size: config.chunking.chunk_size,
+            chunk_overlap: config.chunking.chunk_overlap,
+            embedding_model: state.embedding_model_for(None).to_string(),
+            dimensions: config.embeddings.dimensions,
+            error: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        };
+        *self.progress.write() = Some(progress.clone());
+        *self.task.write() = Some(task.clone());
+
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            let result = manager.run(&state).await;
+            manager.update(|p| {
+                p.current_document = None;
+                p.completed_at = Some(Utc::now());
+                match result {
+                    Ok(()) => p.status = ReindexStatus::Complete,
+                    Err(ref e) => {
+                        p.status = ReindexStatus::Failed;
+                        p.error = Some(e.to_string());
+                    }
+                }
+            });
+            match result {
+                Ok(()) => {
+                    task.complete();
+                    tracing::info!("Reindex complete");
+                }
+                Err(e) => {
+                    task.fail(e.to_string());
+                    tracing::error!("Reindex failed, previous index kept: {}", e);
+                }
+            }
+            manager.running.store(false, Ordering::SeqCst);
+        });
+
+        Ok(progress)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ReindexProgress)) {
+        if let Some(ref mut progress) = *self.progress.write() {
+            f(progress);
+
+            // Mirror into the generic task for throughput and ETA
+            if let Some(ref task) = *self.task.read() {
+                task.report(progress.documents_processed, progress.total_documents);
+                task.set_current(progress.current_document.clone());
+                if progress.status == ReindexStatus::Swapping {
+                    task.set_phase("swapping");
+                }
+            }
+        }
+    }
+
+    /// Start a reindex whenever `embeddings.reindex_schedule` is due
+    ///
+    /// A run still going when the next one is due is not interrupted; that
+    /// slot is skipped.
+    pub fn spawn_scheduler(self: &Arc<Self>, state: AppState) {
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SCHEDULER_TICK);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            // The schedule and its next slot, recomputed when a config reload changes it
+            let mut next_run: Option<(String, DateTime<Utc>)> = None;
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                let due = match (next_run.clone(), state.config().embeddings.reindex_schedule.clone()) {
+                    (_, None) => {
+                        next_run = None;
+                        false
+                    }
+                    (Some((scheduled, at)), Some(expression)) if scheduled == expression => {
+                        if at <= now {
+                            next_run = next_slot(expression, now);
+                        }
+                        at <= now
+                    }
+                    (_, Some(expression)) => {
+                        next_run = next_slot(expression, now);
+                        false
+                    }
+                };
+                if !due {
+                    continue;
+                }
+                match manager.start(state.clone()) {
+                    Ok(progress) => tracing::info!("Started scheduled reindex {}", progress.id),
+                    Err(e) => tracing::warn!("Scheduled reindex not started: {}", e),
+                }
+            }
+        });
+    }
+
+    async fn run(&self, state: &AppState) -> Result<()> {
+        let config = state.config();
+        // The live provider carries the configured model, rate limits and batching
+        let embedder = state.embedding_provider();
+        let pipeline = IngestPipeline::new(config.chunking.chunk_size, config.chunking.chunk_overlap)
+            .with_parent_window(config.retrieval.parent_window)
+            .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+            .with_size_unit(config.chunking.size_unit, Arc::clone(&embedder), &config.context)
+            .with_row_descriptions(config.chunking.row_descriptions);
+        let parallel_embeddings = config.processing.parallel_embeddings.unwrap_or(8);
+
+        tracing::info!(
+            "Reindexing {} documents (chunk_size: {}, overlap: {}, model: {})",
+            state.documents().len(),
+            config.chunking.chunk_size,
+            config.chunking.chunk_overlap,
+            state.embedding_model_for(None)
+        );
+
+        match config.backend {
+            BackendProvider::Local => self.rebuild_and_swap(state, &pipeline, &embedder, parallel_embeddings).await,
+            BackendProvider::Gcp => self.rebuild_in_place(state, &pipeline, &embedder, parallel_embeddings).await,
+        }
+    }
+
+    /// Build a new local index next to the live one, then swap it in
+    async fn rebuild_and_swap(
+        &self,
+        state: &AppState,
+        pipeline: &IngestPipeline,
+        embedder: &Arc<dyn EmbeddingProvider>,
+        parallel_embeddings: usize,
+    ) -> Result<()> {
+        let config = state.config();
+        let live_path = config.vector_db.storage_path.clone();
+        let build_path = sibling_path(&live_path, "reindex");
+
+        // Leftover from an interrupted run
+        remove_path(&build_path)?;
+
+        let mut build_config = (*config).clone();
+        build_config.vector_db.storage_path = build_path.clone();
+        let cipher = state.vector_store().and_then(|live| live.cipher().cloned());
+        let store = Arc::new(VectorStore::new(&build_config)?.with_cipher(cipher));
+        tracing::info!("Building the new index in {:?}", build_path);
+
+        let mut records: Vec<ChunkContentRecord> = Vec::new();
+        let mut chunk_counts: HashMap<Uuid, u32> = HashMap::new();
+        let mut done: HashSet<Uuid> = HashSet::new();
+
+        // Documents ingested during the rebuild are picked up by the next pass
+        loop {
+            let pending = pending_documents(state, &done);
+            if pending.is_empty() {
+                break;
+            }
+            self.update(|p| p.total_documents = done.len() + pending.len());
+
+            for doc in pending {
+                done.insert(doc.id);
+                self.update(|p| p.current_document = Some(doc.filename.clone()));
+
+                let chunks = self
+                    .rebuild_document(state, pipeline, embedder, &doc, parallel_embeddings)
+                    .await
+                    .map_err(|e| Error::Internal(format!("Failed to reindex '{}': {}", doc.filename, e)))?;
+
+                if let Some(chunks) = chunks {
+                    let chunks: Arc<[Chunk]> = chunks.into();
+                    let store = Arc::clone(&store);
+                    let chunks_to_insert = Arc::clone(&chunks);
+                    tokio::task::spawn_blocking(move || {
+                        chunks_to_insert.iter().try_for_each(|chunk| store.insert_chunk(chunk))
+                    })
+                    .await
+                    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+                    chunk_counts.insert(doc.id, chunks.len() as u32);
+                    records.extend(chunks.iter().map(LocalVectorStore::chunk_to_content_record));
+                    self.update(|p| p.chunks_created += chunks.len());
+                }
+
+                self.update(|p| p.documents_processed += 1);
+            }
+        }
+
+        self.update(|p| {
+            p.status = ReindexStatus::Swapping;
+            p.current_document = None;
+        });
+
+        // Documents deleted during the rebuild must not come back
+        let live_ids: HashSet<Uuid> = state.documents().iter().map(|entry| *entry.key()).collect();
+        for id in done.iter().filter(|id| !live_ids.contains(id)) {
+            store.delete_by_document(id)?;
+        }
+        records.retain(|r| live_ids.contains(&r.document_id));
+        chunk_counts.retain(|id, _| live_ids.contains(id));
+
+        let database = state.database().clone();
+        tokio::task::spawn_blocking(move || database.replace_all_chunks_content(&records))
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+        swap_paths(&live_path, &build_path)?;
+
+        let provider: Arc<dyn VectorStoreProvider> =
+            Arc::new(LocalVectorStore::new(Arc::clone(&store), state.database().clone()));
+        state.swap_index(Some(store), Arc::clone(&provider), Arc::clone(embedder));
+        state.set_document_chunk_counts(&chunk_counts);
+
+        // Documents that slipped in between the last pass and the swap went to the old index
+        for doc in pending_documents(state, &done) {
+            done.insert(doc.id);
+            provider.delete_by_document(&doc.id).await?;
+            if let Some(chunks) = self
+                .rebuild_document(state, pipeline, embedder, &doc, parallel_embeddings)
+                .await?
+            {
+                let count = chunks.len();
+                provider.insert_chunks(chunks.into()).await?;
+                state.set_document_chunk_counts(&HashMap::from([(doc.id, count as u32)]));
+                self.update(|p| p.chunks_created += count);
+            }
+            self.update(|p| {
+                p.total_documents += 1;
+                p.documents_processed += 1;
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Replace each document's vectors in the live index as it is rebuilt
+    async fn rebuild_in_place(
+        &self,
+        state: &AppState,
+        pipeline: &IngestPipeline,
+        embedder: &Arc<dyn EmbeddingProvider>,
+        parallel_embeddings: usize,
+    ) -> Result<()> {
+        let provider = state.vector_store_provider();
+        let mut done: HashSet<Uuid> = HashSet::new();
+        loop {
+            let pending = pending_documents(state, &done);
+            if pending.is_empty() {
+                break;
+            }
+            self.update(|p| p.total_documents = done.len() + pending.len());
+
+            for doc in pending {
+                done.insert(doc.id);
+                self.update(|p| p.current_document = Some(doc.filename.clone()));
+
+                let chunks = self
+                    .rebuild_document(state, pipeline, embedder, &doc, parallel_embeddings)
+                    .await
+                    .map_err(|e| Error::Internal(format!("Failed to reindex '{}': {}", doc.filename, e)))?;
+                if let Some(chunks) = chunks {
+                    let count = chunks.len();
+                    provider.delete_by_document(&doc.id).await?;
+                    provider.insert_chunks(chunks.into()).await?;
+                    state.set_document_chunk_counts(&HashMap::from([(doc.id, count as u32)]));
+                    self.update(|p| p.chunks_created += count);
+                }
+                self.update(|p| p.documents_processed += 1);
+            }
+        }
+
+        // Drops cached answers and vectors of the previous generation
+        state.swap_index(state.vector_store(), provider, Arc::clone(embedder));
+        Ok(())
+    }
+
+    /// Re-chunk and embed one document, `None` if it has no stored text
+    async fn rebuild_document(
+        &self,
+        state: &AppState,
+        pipeline: &IngestPipeline,
+        embedder: &Arc<dyn EmbeddingProvider>,
+        doc: &Document,
+        parallel_embeddings: usize,
+    ) -> Result<Option<Vec<Chunk>>> {
+        let database = state.database().clone();
+        let doc_id = doc.id;
+        let stored = tokio::task::spawn_blocking(move || database.list_chunks_for_document(&doc_id))
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+        let parsed = if stored.is_empty() {
+            // Documents whose chunks aren't kept locally, e.g. in GCS with the GCP backend
+            let text = match state.file_store() {
+                Some(store) => store.get_plain_text(&doc.id).await?,
+                None => None,
+            };
+            match text {
+                Some(text) if !text.trim().is_empty() => plain_text_document(doc, text),
+                _ => {
+                    self.update(|p| p.documents_skipped += 1);
+                    return Ok(None);
+                }
+            }
+        } else {
+            reconstruct_text(doc, &stored)
+        };
+        let mut chunks = pipeline.create_chunks(doc, &parsed)?;
+
+        // Figure captions can't be derived from the text; carry them over
+        for record in stored.iter().filter(|r| r.kind == ChunkKind::Figure) {
+            let mut source = ChunkSource::text(doc.filename.clone());
+            source.internal_filename = doc.internal_filename.clone();
+            source.file_type = doc.file_type.clone();
+            source.page_number = record.page_number;
+            source.page_count = doc.total_pages;
+            source.kind = ChunkKind::Figure;
+            let mut chunk = Chunk::new(doc.id, record.content.clone(), source, 0, 0, chunks.len() as u32);
+            if let Some(ref collection) = record.collection {
+                chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), serde_json::Value::String(collection.clone()));
+            }
+            chunks.push(chunk);
+        }
+
+        // Stored text may predate the collection's masking policy
+        let mut pii = PiiReport::default();
+        state.redactor().redact_chunks(doc.collection(), &mut chunks, &mut pii).await;
+        state.keyword_extractor().tag_chunks(&mut chunks, &mut EntityReport::default()).await;
+
+        // Collections with a model of their own keep the live provider
+        let (embedder, embedding_model) = match state.embedders().provider(doc.collection()) {
+            Some(provider) => (provider, state.embedding_model_for(doc.collection()).to_string()),
+            None => (Arc::clone(embedder), state.embedding_model_for(None).to_string()),
+        };
+        let embedder = state.usage().meter_embedder(embedder, &embedding_model, doc.collection());
+        let embeddings: Vec<Result<Vec<Vec<f32>>>> = stream::iter(chunks.iter())
+            .map(|chunk| embedder.embed_parts(&chunk.content))
+            .buffered(parallel_embeddings)
+            .collect()
+            .await;
+
+        for (chunk, embedding) in chunks.iter_mut().zip(embeddings) {
+            chunk.set_embeddings(embedding?);
+            chunk.set_embedding_model(&embedding_model);
+        }
+
+        Ok(Some(chunks))
+    }
+}
+
+impl Default for ReindexManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Next run of a cron schedule after `after` (`None` for an invalid schedule)
+fn next_slot(expression: String, after: DateTime<Utc>) -> Option<(String, DateTime<Utc>)> {
+    let at = CronSchedule::parse(&expression).ok()?.next_after(after)?;
+    Some((expression, at))
+}
+
+/// Documents not yet reindexed, in ingestion order
+fn pending_documents(state: &AppState, done: &HashSet<Uuid>) -> Vec<Document> {
+    let mut pending: Vec<Document> = state
+        .list_documents()
+        .into_iter()
+        .filter(|d| !done.contains(&d.id))
+        .collect();
+    pending.sort_by_key(|d| d.ingested_at);
+    pending
+}
+
+/// Rebuild a document's text from its stored chunks
+///
+/// Chunks of the same page are joined with their overlap removed; pages keep
+/// their numbers so the new chunks carry the same page references. Table
+/// chunks duplicate text already in the page and are re-extracted; figure
+/// chunks are carried over separately.
+pub(crate) fn reconstruct_text(doc: &Document, stored: &[ChunkContentRecord]) -> ParsedDocument {
+    let mut pages: Vec<(Option<u32>, Vec<&str>)> = Vec::new();
+    for record in stored.iter().filter(|r| r.kind == ChunkKind::Text) {
+        match pages.last_mut() {
+            Some((page, parts)) if *page == record.page_number => parts.push(record.content.as_str()),
+            _ => pages.push((record.page_number, vec![record.content.as_str()])),
+        }
+    }
+
+    let mut content = String::new();
+    let mut page_contents = Vec::new();
+    for (page_number, parts) in pages {
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        let text = merge_overlapping(&parts);
+        if let Some(page_number) = page_number {
+            page_contents.push(PageContent {
+                page_number,
+                content: text.clone(),
+                char_offset: content.len(),
+            });
+        }
+        content.push_str(&text);
+    }
+
+    ParsedDocument {
+        file_type: doc.file_type.clone(),
+        content,
+        content_hash: doc.content_hash.clone(),
+        total_pages: doc.total_pages,
+        pages: page_contents,
+        metadata: HashMap::new(),
+        sections: Vec::new(),
+    }
+}
+
+/// A document's stored plain text, without page boundaries
+fn plain_text_document(doc: &Document, content: String) -> ParsedDocument {
+    ParsedDocument {
+        file_type: doc.file_type.clone(),
+        content,
+        content_hash: doc.content_hash.clone(),
+        total_pages: doc.total_pages,
+        pages: Vec::new(),
+        metadata: HashMap::new(),
+        sections: Vec::new(),
+    }
+}
+
+/// Join consecutive chunks, dropping the text each one repeats from its predecessor
+pub(crate) fn merge_overlapping(parts: &[&str]) -> String {
+    let mut text = String::new();
+    for part in parts {
+        if text.is_empty() {
+            text.push_str(part);
+            continue;
+        }
+        match overlap_len(&text, part) {
+            0 => {
+                text.push('\n');
+                text.push_str(part);
+            }
+            n => text.push_str(&part[n..]),
+        }
+    }
+    text
+}
+
+/// Length of the longest prefix of `next` that is also a suffix of `prev`
+fn overlap_len(prev: &str, next: &str) -> usize {
+    let max = prev.len().min(next.len());
+    (MIN_OVERLAP..=max)
+        .rev()
+        .find(|&len| {
+            next.is_char_boundary(len)
+                && prev.is_char_boundary(prev.len() - len)
+                && prev.ends_with(&next[..len])
+        })
+        .unwrap_or(0)
+}
+
+/// Path next to `path` with a suffix appended to its file name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(format!(".{}", suffix));
+    path.with_file_name(name)
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path)?,
+        Ok(_) => std::fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Move the rebuilt index into place, keeping the old one as a backup
+///
+/// Open handles keep working across renames, so in-flight queries on the old
+/// index are unaffected.
+fn swap_paths(live_path: &Path, build_path: &Path) -> Result<()> {
+    let backup_path = sibling_path(live_path, "pre-reindex");
+    remove_path(&backup_path)?;
+    if live_path.exists() {
+        std::fs::rename(live_path, &backup_path)?;
+    }
+    std::fs::rename(build_path, live_path)?;
+    tracing::info!("Swapped in rebuilt index, previous index kept at {:?}", backup_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_overlapping() {
+        let parts = [
+            "The quick brown fox jumps",
+            "brown fox jumps over the lazy dog.",
+            "Unrelated second part.",
+        ];
+        assert_eq!(
+            merge_overlapping(&parts),
+            "The quick brown fox jumps over the lazy dog.\nUnrelated second part."
+        );
+    }
+
+    #[test]
+    fn test_sibling_path() {
+        assert_eq!(
+            sibling_path(Path::new("/data/vectors.db"), "reindex"),
+            PathBuf::from("/data/vectors.db.reindex")
+        );
+    }
+
+    #[test]
+    fn test_next_slot() {
+        let after = "2024-01-01T12:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        let (expression, at) = next_slot("0 3 * * *".to_string(), after).unwrap();
+        assert_eq!(expression, "0 3 * * *");
+        assert_eq!(at, "2024-01-02T03:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert!(next_slot("not a schedule".to_string(), after).is_none());
+    }
+}
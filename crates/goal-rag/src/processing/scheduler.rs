@@ -0,0 +1,155 @@
+//! Weighted sharing of file processing slots between job priorities
+//!
+//! Every file being processed holds a slot, whatever job it belongs to. When
+//! files of both lanes wait for a slot, interactive files get
+//! `interactive_weight` freed slots for every one going to a bulk file, so a
+//! large backfill keeps moving without holding up an urgent upload. Within a
+//! lane, files are served in the order they asked.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use super::job_queue::JobPriority;
+
+/// File slots shared by all running jobs
+pub struct FairScheduler {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<SchedulerState>,
+    interactive_weight: u32,
+}
+
+struct SchedulerState {
+    available: usize,
+    interactive: VecDeque<oneshot::Sender<SlotPermit>>,
+    bulk: VecDeque<oneshot::Sender<SlotPermit>>,
+    /// Interactive files served since the last bulk one
+    streak: u32,
+}
+
+impl FairScheduler {
+    pub fn new(slots: usize, interactive_weight: u32) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(SchedulerState {
+                    available: slots.max(1),
+                    interactive: VecDeque::new(),
+                    bulk: VecDeque::new(),
+                    streak: 0,
+                }),
+                interactive_weight: interactive_weight.max(1),
+            }),
+        }
+    }
+
+    /// Wait for a slot for a file of a job of `priority`
+    pub async fn acquire(&self, priority: JobPriority) -> SlotPermit {
+        let receiver = {
+            let mut state = self.shared.state.lock();
+            if state.available > 0 {
+                state.available -= 1;
+                return SlotPermit { shared: Some(self.shared.clone()) };
+            }
+            let (sender, receiver) = oneshot::channel();
+            match priority {
+                JobPriority::Interactive => state.interactive.push_back(sender),
+                JobPriority::Bulk => state.bulk.push_back(sender),
+            }
+            receiver
+        };
+        // Senders are only dropped after handing over a permit
+        receiver.await.expect("scheduler dropped a waiting file")
+    }
+
+    /// Files waiting for a slot (interactive, bulk)
+    pub fn waiting(&self) -> (usize, usize) {
+        let state = self.shared.state.lock();
+        (state.interactive.len(), state.bulk.len())
+    }
+}
+
+impl Shared {
+    /// Hand a freed slot to the next waiting file, or put it back
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        while let Some(sender) = state.next_waiter(self.interactive_weight) {
+            match sender.send(SlotPermit { shared: Some(self.clone()) }) {
+                Ok(()) => return,
+                // The file stopped waiting (its job was cancelled or timed out)
+                Err(mut permit) => permit.shared = None,
+            }
+        }
+        state.available += 1;
+    }
+}
+
+impl SchedulerState {
+    fn next_waiter(&mut self, interactive_weight: u32) -> Option<oneshot::Sender<SlotPermit>> {
+        let bulk_turn = self.interactive.is_empty() || self.streak >= interactive_weight;
+        if bulk_turn {
+            if let Some(sender) = self.bulk.pop_front() {
+                self.streak = 0;
+                return Some(sender);
+            }
+        }
+        let sender = self.interactive.pop_front()?;
+        self.streak += 1;
+        Some(sender)
+    }
+}
+
+/// A file processing slot, given back when dropped
+pub struct SlotPermit {
+    shared: Option<Arc<Shared>>,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interactive_files_overtake_bulk_by_weight() {
+        let scheduler = Arc::new(FairScheduler::new(1, 2));
+        let held = scheduler.acquire(JobPriority::Bulk).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+        for (name, priority) in [
+            ("b1", JobPriority::Bulk),
+            ("b2", JobPriority::Bulk),
+            ("i1", JobPriority::Interactive),
+            ("i2", JobPriority::Interactive),
+            ("i3", JobPriority::Interactive),
+        ] {
+            let scheduler = scheduler.clone();
+            let order = order.clone();
+            waiters.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(priority).await;
+                order.lock().push(name);
+            }));
+            // Queue them in this order
+            while scheduler.waiting().0 + scheduler.waiting().1 < waiters.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        drop(held);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert_eq!(*order.lock(), vec!["i1", "i2", "b1", "i3", "b2"]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Pipelined ingestion stages
+//!
+//! A file used to be chunked completely, then embedded batch after batch and
+//! only then stored, so the embedder waited for chunking (and figure
+//! extraction) and the vector store waited for the last embedding. Chunk
+//! batches now flow through three concurrent stages: producing them (chunking,
+//! reading a streamed file, extracting figures), redacting, tagging and
+//! embedding them, and storing them. The stages are connected by bounded
+//! channels of `processing.pipeline_depth` batches, so a slow embedder or
+//! store holds the stages before it back instead of letting chunks pile up in
+//! memory.
+//!
+//! If a stage fails, the chunks already stored for the document are removed
+//! again.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::stream::{Stream, StreamExt};
+use futures_util::future::join_all;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::Instrument;
+
+use crate::error::Result;
+use crate::server::state::AppState;
+use crate::types::{Chunk, Document};
+
+use super::job_queue::JobQueue;
+use super::keywords::EntityReport;
+use super::redaction::PiiReport;
+
+/// Time one group of embeddings may take before its chunks are left for repair
+const EMBED_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Chunk, embed and store stages for one document
+pub(crate) struct IngestStages<'a> {
+    state: &'a AppState,
+    job_queue: &'a JobQueue,
+    job_id: uuid::Uuid,
+    filename: &'a str,
+    document_id: uuid::Uuid,
+    collection: Option<&'a str>,
+    parallel_embeddings: usize,
+    depth: usize,
+    keep_chunks: bool,
+}
+
+/// What the stages did with a document's chunks
+#[derive(Debug, Default)]
+pub(crate) struct StageTotals {
+    /// Chunks stored or left for embedding repair
+    pub chunks: usize,
+    /// Chunks left for embedding repair
+    pub pending: usize,
+    pub pii: PiiReport,
+    pub entities: EntityReport,
+}
+
+impl StageTotals {
+    /// Record the PII and entities found on the document
+    pub fn apply(&self, state: &AppState, doc: &mut Document) {
+        if state.redactor().is_enabled() {
+            self.pii.apply(doc);
+        }
+        state.keyword_extractor().apply(&self.entities, doc);
+    }
+}
+
+impl<'a> IngestStages<'a> {
+    pub fn new(
+        state: &'a AppState,
+        job_queue: &'a JobQueue,
+        job_id: uuid::Uuid,
+        filename: &'a str,
+        doc: &'a Document,
+        parallel_embeddings: usize,
+    ) -> Self {
+        Self {
+            state,
+            job_queue,
+            job_id,
+            filename,
+            document_id: doc.id,
+            collection: doc.collection(),
+            parallel_embeddings: parallel_embeddings.max(1),
+            depth: state.config().processing.pipeline_depth.max(1),
+            keep_chunks: true,
+        }
+    }
+
+    /// Leave stored chunks out of the chunk store (streamed files)
+    pub fn without_chunk_store(mut self) -> Self {
+        self.keep_chunks = false;
+        self
+    }
+
+    /// Run the stages over `batches` until it ends or a stage fails
+    pub async fn run(self, batches: impl Stream<Item = Result<Vec<Chunk>>>) -> Result<StageTotals> {
+        let (chunk_tx, chunk_rx) = mpsc::channel(self.depth);
+        let (embedded_tx, embedded_rx) = mpsc::channel(self.depth);
+        let mut totals = StageTotals::default();
+        let mut stored = 0;
+
+        let result = tokio::try_join!(
+            produce(batches, chunk_tx),
+            self.embed(chunk_rx, embedded_tx, &mut totals.pii, &mut totals.entities),
+            self.store(embedded_rx, &mut stored, &mut totals.pending),
+        );
+
+        if let Err(e) = result {
+            self.remove_partial(stored + totals.pending).await;
+            return Err(e);
+        }
+        totals.chunks = stored + totals.pending;
+        Ok(totals)
+    }
+
+    /// Redact, tag and embed batches, in groups of `parallel_embeddings`
+    async fn embed(
+        &self,
+        mut batches: mpsc::Receiver<Vec<Chunk>>,
+        embedded: mpsc::Sender<Vec<Chunk>>,
+        pii: &mut PiiReport,
+        entities: &mut EntityReport,
+    ) -> Result<()> {
+        let embedding_provider = self.state.embedder_for(self.collection);
+        let embedding_model = self.state.embedding_model_for(self.collection).to_string();
+        let mut group_num = 0;
+
+        while let Some(mut batch) = batches.recv().await {
+            self.state.redactor().redact_chunks(self.collection, &mut batch, pii).await;
+            self.state.keyword_extractor().tag_chunks(&mut batch, entities).await;
+
+            for group in batch.chunks_mut(self.parallel_embeddings) {
+                group_num += 1;
+                let group_start = Instant::now();
+                let embedding_futures: Vec<_> = group
+                    .iter()
+                    .map(|chunk| embedding_provider.embed_parts(&chunk.content))
+                    .collect();
+
+                match timeout(
+                    EMBED_TIMEOUT,
+                    join_all(embedding_futures).instrument(tracing::info_span!("embed_batch", batch = group_num, chunks = group.len())),
+                )
+                .await
+                {
+                    Ok(results) => {
+                        for (chunk, result) in group.iter_mut().zip(results) {
+                            match result {
+                                Ok(embeddings) => {
+                                    chunk.set_embeddings(embeddings);
+                                    chunk.set_embedding_model(&embedding_model);
+                                }
+                                Err(e) => {
+                                    tracing::warn!("[{}] Embedding failed, chunk queued for repair: {}", self.filename, e);
+                                }
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        tracing::error!(
+                            "[{}] Embedding batch {} took >{}s, chunks queued for repair",
+                            self.filename, group_num, EMBED_TIMEOUT.as_secs()
+                        );
+                    }
+                }
+
+                if group_start.elapsed().as_secs() > 10 {
+                    tracing::info!("[{}] Batch {} took {:.1}s", self.filename, group_num, group_start.elapsed().as_secs_f64());
+                }
+                self.job_queue.increment_chunks_embedded(self.job_id, group.len());
+            }
+
+            // The store stage only hangs up when it failed, and reports why
+            if embedded.send(batch).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store embedded batches, deferring chunks without an embedding
+    async fn store(&self, mut batches: mpsc::Receiver<Vec<Chunk>>, stored: &mut usize, pending: &mut usize) -> Result<()> {
+        let vector_store = self.state.vector_store_provider();
+        while let Some(mut batch) = batches.recv().await {
+            *pending += defer_pending_embeddings(self.state, self.filename, &mut batch)?;
+            if batch.is_empty() {
+                continue;
+            }
+
+            let batch: Arc<[Chunk]> = batch.into();
+            vector_store.insert_chunks(Arc::clone(&batch)).await?;
+            *stored += batch.len();
+            // Store chunks locally for metadata lookup (needed for Vertex AI)
+            if self.keep_chunks {
+                self.state.store_chunks(&batch);
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_partial(&self, chunks: usize) {
+        if chunks == 0 {
+            return;
+        }
+        tracing::warn!("[{}] Ingestion failed, removing {} stored chunks", self.filename, chunks);
+        if let Err(e) = self.state.vector_store_provider().delete_by_document(&self.document_id).await {
+            tracing::error!("[{}] Failed to remove partial chunks: {}", self.filename, e);
+        }
+        if let Err(e) = self.state.database().delete_pending_embeddings_for_document(&self.document_id) {
+            tracing::error!("[{}] Failed to remove pending chunks: {}", self.filename, e);
+        }
+    }
+}
+
+/// Hand batches to the embed stage, waiting while it is `depth` batches behind
+async fn produce(batches: impl Stream<Item = Result<Vec<Chunk>>>, chunks: mpsc::Sender<Vec<Chunk>>) -> Result<()> {
+    let mut batches = std::pin::pin!(batches);
+    while let Some(batch) = batches.next().await {
+        let batch = batch?;
+        // The embed stage only hangs up when a later stage failed
+        if !batch.is_empty() && chunks.send(batch).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Split chunks into batches of `size` for the stages
+pub(crate) fn batches_of(chunks: Vec<Chunk>, size: usize) -> impl Stream<Item = Result<Vec<Chunk>>> + Send {
+    let mut chunks = chunks.into_iter();
+    let mut batches = Vec::new();
+    loop {
+        let batch: Vec<Chunk> = chunks.by_ref().take(size.max(1)).collect();
+        if batch.is_empty() {
+            break;
+        }
+        batches.push(Ok(batch));
+    }
+    futures::stream::iter(batches)
+}
+
+/// Persist chunks whose embedding failed for the repair task and drop them
+/// from `chunks`, so no placeholder vector reaches the index
+///
+/// Returns the number of chunks deferred.
+fn defer_pending_embeddings(state: &AppState, filename: &str, chunks: &mut Vec<Chunk>) -> Result<usize> {
+    let (pending, embedded): (Vec<Chunk>, Vec<Chunk>) =
+        std::mem::take(chunks).into_iter().partition(|c| c.embedding.is_empty());
+    *chunks = embedded;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    tracing::warn!(
+        "[{}] {} chunk(s) stored without an embedding, pending repair",
+        filename,
+        pending.len()
+    );
+    state.database().insert_pending_embeddings(&pending, "embedding failed during ingestion")?;
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChunkSource;
+
+    #[tokio::test]
+    async fn test_batches_of() {
+        let chunks: Vec<Chunk> = (0..5)
+            .map(|i| Chunk::new(uuid::Uuid::nil(), "text".to_string(), ChunkSource::text("a.txt".to_string()), 0, 4, i))
+            .collect();
+        let sizes: Vec<usize> = batches_of(chunks, 2).map(|batch| batch.unwrap().len()).collect().await;
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(batches_of(Vec::new(), 2).count().await, 0);
+    }
+}
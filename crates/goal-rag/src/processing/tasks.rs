@@ -0,0 +1,320 @@
+//! Unified progress reporting for long-running tasks
+//!
+//! Ingestion jobs, reindexing and other maintenance work all report the same
+//! shape of progress: items done out of a total, the current phase, throughput
+//! and an ETA. Maintenance tasks register here directly; ingestion jobs keep
+//! their detailed `JobProgress` and are converted when listed.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::job_queue::{JobProgress, JobStatus};
+
+/// Finished tasks kept for status queries
+const MAX_FINISHED_TASKS: usize = 50;
+
+/// Minimum time between throughput samples (seconds)
+const SAMPLE_INTERVAL_SECS: f64 = 1.0;
+
+/// Weight of the newest sample in the throughput moving average
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+/// Kind of long-running task
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    Ingest,
+    Reindex,
+    EmbeddingRepair,
+    GraphBuild,
+    QaGeneration,
+    GcsSync,
+    ConnectorSync,
+}
+
+/// Task status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Pending,
+    Running,
+    Complete,
+    Failed,
+}
+
+impl TaskStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Complete | Self::Failed)
+    }
+}
+
+/// Progress of a long-running task
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskProgress {
+    pub id: Uuid,
+    pub kind: TaskKind,
+    pub status: TaskStatus,
+    /// Current phase (e.g., "embedding", "swapping")
+    pub phase: String,
+    pub items_done: usize,
+    pub items_total: usize,
+    /// What an item is (e.g., "files", "documents")
+    pub unit: String,
+    /// Item currently being worked on
+    pub current_item: Option<String>,
+    /// Smoothed items per second
+    pub throughput_per_sec: f64,
+    /// Estimated seconds until done, if there is enough data for an estimate
+    pub eta_secs: Option<u64>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    sample: Option<(DateTime<Utc>, usize)>,
+}
+
+impl TaskProgress {
+    pub fn new(kind: TaskKind, phase: &str, items_total: usize, unit: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            kind,
+            status: TaskStatus::Running,
+            phase: phase.to_string(),
+            items_done: 0,
+            items_total,
+            unit: unit.to_string(),
+            current_item: None,
+            throughput_per_sec: 0.0,
+            eta_secs: None,
+            error: None,
+            started_at: now,
+            updated_at: now,
+            completed_at: None,
+            sample: Some((now, 0)),
+        }
+    }
+
+    /// Summarize an ingestion job
+    pub fn from_job(job: &JobProgress) -> Self {
+        let status = match job.status {
+            JobStatus::Pending => TaskStatus::Pending,
+            JobStatus::Processing => TaskStatus::Running,
+            JobStatus::Complete => TaskStatus::Complete,
+            JobStatus::Failed => TaskStatus::Failed,
+        };
+        let items_done = job.files_processed + job.files_skipped + job.files_failed;
+        let phase = serde_json::to_value(job.stage)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+
+        // Jobs don't keep samples, use the average rate since the job was created
+        let elapsed = (job.updated_at - job.created_at).num_milliseconds() as f64 / 1000.0;
+        let throughput_per_sec = if elapsed > 0.0 { items_done as f64 / elapsed } else { 0.0 };
+
+        let mut progress = Self {
+            id: job.job_id,
+            kind: TaskKind::Ingest,
+            status,
+            phase,
+            items_done,
+            items_total: job.total_files,
+            unit: "files".to_string(),
+            current_item: job.current_file.clone(),
+            throughput_per_sec,
+            eta_secs: None,
+            error: job.error.clone(),
+            started_at: job.created_at,
+            updated_at: job.updated_at,
+            completed_at: status.is_finished().then_some(job.updated_at),
+            sample: None,
+        };
+        progress.eta_secs = progress.estimate_eta();
+        progress
+    }
+
+    pub fn percent_complete(&self) -> f32 {
+        if self.status == TaskStatus::Complete {
+            return 100.0;
+        }
+        if self.items_total == 0 {
+            return 0.0;
+        }
+        (self.items_done as f32 / self.items_total as f32 * 100.0).min(100.0)
+    }
+
+    /// Record the number of items done, updating throughput and ETA
+    pub fn record(&mut self, items_done: usize) {
+        let now = Utc::now();
+        self.items_done = items_done;
+        self.updated_at = now;
+
+        let (sampled_at, sampled_done) = *self.sample.get_or_insert((self.started_at, 0));
+        let elapsed = (now - sampled_at).num_milliseconds() as f64 / 1000.0;
+        if elapsed >= SAMPLE_INTERVAL_SECS {
+            let rate = items_done.saturating_sub(sampled_done) as f64 / elapsed;
+            self.throughput_per_sec = if self.throughput_per_sec > 0.0 {
+                THROUGHPUT_SMOOTHING * rate + (1.0 - THROUGHPUT_SMOOTHING) * self.throughput_per_sec
+            } else {
+                rate
+            };
+            self.sample = Some((now, items_done));
+        }
+        self.eta_secs = self.estimate_eta();
+    }
+
+    fn estimate_eta(&self) -> Option<u64> {
+        if self.status.is_finished() {
+            return Some(0);
+        }
+        if self.throughput_per_sec <= 0.0 || self.items_total < self.items_done {
+            return None;
+        }
+        let remaining = (self.items_total - self.items_done) as f64;
+        Some((remaining / self.throughput_per_sec).ceil() as u64)
+    }
+}
+
+/// Registry of maintenance tasks
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: DashMap<Uuid, TaskProgress>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a running task
+    pub fn start(self: &Arc<Self>, kind: TaskKind, phase: &str, items_total: usize, unit: &str) -> TaskHandle {
+        self.prune();
+        let progress = TaskProgress::new(kind, phase, items_total, unit);
+        let id = progress.id;
+        self.tasks.insert(id, progress);
+        TaskHandle { id, registry: Arc::clone(self) }
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<TaskProgress> {
+        self.tasks.get(id).map(|t| t.clone())
+    }
+
+    /// All tasks, most recently started first
+    pub fn list(&self) -> Vec<TaskProgress> {
+        let mut tasks: Vec<TaskProgress> = self.tasks.iter().map(|t| t.clone()).collect();
+        tasks.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        tasks
+    }
+
+    /// Drop the oldest finished tasks beyond the retention limit
+    fn prune(&self) {
+        let mut finished: Vec<(Uuid, DateTime<Utc>)> = self
+            .tasks
+            .iter()
+            .filter(|t| t.status.is_finished())
+            .map(|t| (t.id, t.updated_at))
+            .collect();
+        if finished.len() < MAX_FINISHED_TASKS {
+            return;
+        }
+        finished.sort_by_key(|(_, updated_at)| *updated_at);
+        for (id, _) in &finished[..=finished.len() - MAX_FINISHED_TASKS] {
+            self.tasks.remove(id);
+        }
+    }
+}
+
+/// Reports progress for one registered task
+#[derive(Clone)]
+pub struct TaskHandle {
+    id: Uuid,
+    registry: Arc<TaskRegistry>,
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn progress(&self) -> Option<TaskProgress> {
+        self.registry.get(&self.id)
+    }
+
+    /// Report items done out of the (possibly grown) total
+    pub fn report(&self, items_done: usize, items_total: usize) {
+        self.update(|t| {
+            t.items_total = items_total;
+            t.record(items_done);
+        });
+    }
+
+    pub fn set_phase(&self, phase: &str) {
+        self.update(|t| t.phase = phase.to_string());
+    }
+
+    pub fn set_current(&self, item: Option<String>) {
+        self.update(|t| t.current_item = item);
+    }
+
+    pub fn complete(&self) {
+        self.finish(TaskStatus::Complete, None);
+    }
+
+    pub fn fail(&self, error: String) {
+        self.finish(TaskStatus::Failed, Some(error));
+    }
+
+    fn finish(&self, status: TaskStatus, error: Option<String>) {
+        self.update(|t| {
+            t.status = status;
+            t.error = error;
+            t.current_item = None;
+            t.completed_at = Some(Utc::now());
+            t.eta_secs = Some(0);
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut TaskProgress)) {
+        if let Some(mut task) = self.registry.tasks.get_mut(&self.id) {
+            f(&mut task);
+            task.updated_at = Utc::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta_from_throughput() {
+        let mut task = TaskProgress::new(TaskKind::Reindex, "embedding", 100, "documents");
+        // Pretend the task started 10 seconds ago
+        task.started_at = Utc::now() - chrono::Duration::seconds(10);
+        task.sample = Some((task.started_at, 0));
+
+        task.record(20);
+        assert!((task.throughput_per_sec - 2.0).abs() < 0.1);
+        assert!(matches!(task.eta_secs, Some(40..=41)));
+        assert_eq!(task.percent_complete(), 20.0);
+    }
+
+    #[test]
+    fn test_registry_prunes_finished() {
+        let registry = Arc::new(TaskRegistry::new());
+        for _ in 0..MAX_FINISHED_TASKS + 5 {
+            registry.start(TaskKind::Reindex, "running", 1, "documents").complete();
+        }
+        let running = registry.start(TaskKind::Reindex, "running", 1, "documents");
+
+        let tasks = registry.list();
+        assert!(tasks.len() <= MAX_FINISHED_TASKS);
+        assert!(tasks.iter().any(|t| t.id == running.id()));
+    }
+}
@@ -1,19 +1,32 @@
 //! Background worker for processing jobs
 
+use futures::StreamExt;
 use futures_util::future::join_all;
+use std::io::Read;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Semaphore};
+use tokio::sync::Semaphore;
 use tokio::time::timeout;
+use tracing::Instrument;
 
+use crate::config::ChunkSizeUnit;
+use crate::engine::{figure_chunks, store_original, store_thumbnails, transcribe};
 use crate::error::{Error, Result};
-use crate::ingestion::{ExternalParser, IngestPipeline, ParserAttempt};
+use crate::ingestion::{
+    antivirus, encrypted, stream_content_hash, transcription, ExternalParser, IngestPipeline, ParserAttempt,
+    StreamFormat, StreamingChunker,
+};
 #[cfg(feature = "gcp")]
 use crate::providers::document_store::DocumentStoreProvider;
 use crate::server::state::{AppState, FileStatus};
+use crate::server::webhooks::{WebhookEvent, WebhookEventKind};
 use crate::types::{Document, FileType, SkipReason};
 
-use super::job_queue::{FileData, FileProcessingStatus, Job, JobQueue, JobStatus, ProcessingStage};
+use super::job_queue::{
+    FileData, FileProcessingStatus, Job, JobPriority, JobQueue, JobStatus, ProcessingOptions, ProcessingStage,
+};
+use super::queue_backend::{LeasedJob, QueueBackend};
+use super::stages::{batches_of, IngestStages};
 use super::FileCharacteristics;
 
 /// Result of processing a file
@@ -58,7 +71,6 @@ pub struct ProcessingWorker {
     job_queue: Arc<JobQueue>,
     parallel_files: usize,
     parallel_embeddings: usize,
-    file_timeout: Duration,
 }
 
 impl ProcessingWorker {
@@ -67,11 +79,9 @@ impl ProcessingWorker {
         let cpu_count = num_cpus::get();
         let config = state.config();
 
-        let parallel_files = config.processing.parallel_files
-            .unwrap_or_else(|| cpu_count.min(8));
+        let parallel_files = config.processing.file_slots();
         let parallel_embeddings = config.processing.parallel_embeddings
             .unwrap_or_else(|| cpu_count.min(4));
-        let file_timeout = Duration::from_secs(config.processing.file_timeout_secs);
 
         tracing::info!(
             "Worker configured: {} parallel files, {} parallel embeddings per file, {}s timeout",
@@ -85,62 +95,195 @@ impl ProcessingWorker {
             job_queue,
             parallel_files,
             parallel_embeddings,
-            file_timeout,
         }
     }
 
-    /// Start processing jobs from the queue
-    pub async fn run(self, mut receiver: mpsc::Receiver<Job>) {
+    /// Start processing jobs from the queue backend
+    ///
+    /// Each priority lane is dequeued on its own, running up to
+    /// `worker_count` jobs at once (one less for bulk jobs, so an interactive
+    /// job always gets started). Their files share the job queue's slots.
+    pub async fn run(self) {
+        let backend = self.job_queue.backend().clone();
+        let worker_id = format!(
+            "{}-{}",
+            std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string()),
+            uuid::Uuid::new_v4()
+        );
+        let jobs = self.job_queue.worker_count().max(1);
+
         tracing::info!(
-            "Processing worker started: {} parallel files, {} embeddings/file",
+            "Processing worker {} started: {} parallel files, {} embeddings/file, {} jobs at once (queue: {})",
+            worker_id,
             self.parallel_files,
-            self.parallel_embeddings
+            self.parallel_embeddings,
+            jobs,
+            backend.name()
         );
 
-        while let Some(job) = receiver.recv().await {
-            let job_id = job.id;
-            tracing::info!("Processing job {} with {} files", job_id, job.files.len());
+        let worker = Arc::new(self);
+        tokio::join!(
+            worker.clone().run_lane(JobPriority::Interactive, &worker_id, jobs),
+            worker.clone().run_lane(JobPriority::Bulk, &worker_id, jobs.saturating_sub(1).max(1)),
+        );
 
-            self.job_queue.update_status(job_id, JobStatus::Processing, None);
+        tracing::info!("Processing worker {} stopped (queue closed)", worker_id);
+    }
 
-            match self.process_job_parallel(job).await {
-                Ok(()) => {
-                    self.job_queue.update_stage(job_id, ProcessingStage::Complete);
-                    tracing::info!("Job {} completed successfully", job_id);
-                }
+    /// Dequeue and run the jobs of one lane, at most `jobs` at a time
+    async fn run_lane(self: Arc<Self>, priority: JobPriority, worker_id: &str, jobs: usize) {
+        let backend = self.job_queue.backend().clone();
+        let running = Arc::new(Semaphore::new(jobs));
+
+        loop {
+            let permit = running.clone().acquire_owned().await.expect("lane semaphore is never closed");
+            let leased = match backend.dequeue(worker_id, priority).await {
+                Ok(Some(leased)) => leased,
+                Ok(None) => break,
                 Err(e) => {
-                    self.job_queue.update_status(job_id, JobStatus::Failed, Some(e.to_string()));
-                    tracing::error!("Job {} failed: {}", job_id, e);
+                    tracing::error!("Failed to dequeue {} job: {}", priority.as_str(), e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
                 }
+            };
+
+            let worker = self.clone();
+            let worker_id = worker_id.to_string();
+            tokio::spawn(async move {
+                worker.run_job(leased, &worker_id).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Process a leased job and acknowledge it
+    async fn run_job(&self, leased: LeasedJob, worker_id: &str) {
+        let backend = self.job_queue.backend().clone();
+        let heartbeat_interval = Duration::from_secs(
+            self.state.config().queue.heartbeat_secs.max(1),
+        );
+
+        let job = leased.job;
+        let job_id = job.id;
+        tracing::info!(
+            "Processing {} job {} with {} files (delivery {})",
+            job.options.priority.as_str(), job_id, job.files.len(), leased.delivery_count
+        );
+
+        self.job_queue.ensure_tracked(&job);
+        self.job_queue.update_status(job_id, JobStatus::Processing, None);
+
+        // Keep the lease alive while the job runs; the heartbeat task ends when the lease is lost
+        let mut heartbeat = Self::spawn_heartbeat(
+            backend.clone(),
+            job_id,
+            worker_id.to_string(),
+            heartbeat_interval,
+        );
+
+        let job_span = tracing::info_span!("ingest_job", job_id = %job_id, files = job.files.len());
+        let outcome = tokio::select! {
+            outcome = self.process_job_parallel(job).instrument(job_span) => outcome,
+            _ = &mut heartbeat => {
+                // Another worker may already have the job, so leave it and its status to them
+                tracing::warn!("Lost the lease on job {}, abandoning it", job_id);
+                return;
+            }
+        };
+        match outcome {
+            Ok(()) => {
+                self.job_queue.update_stage(job_id, ProcessingStage::Complete);
+                tracing::info!("Job {} completed successfully", job_id);
+                self.emit_job_event(WebhookEventKind::JobCompleted, job_id, None);
+            }
+            Err(e) => {
+                self.job_queue.update_status(job_id, JobStatus::Failed, Some(e.to_string()));
+                tracing::error!("Job {} failed: {}", job_id, e);
+                self.emit_job_event(WebhookEventKind::JobFailed, job_id, Some(e.to_string()));
+            }
+        }
+
+        heartbeat.abort();
+        match backend.ack(job_id, worker_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("Job {} finished after its lease was lost, left to the worker holding it", job_id)
             }
+            Err(e) => tracing::error!("Failed to ack job {}: {}", job_id, e),
         }
     }
 
+    /// Notify webhooks that a job finished, with its file counts
+    fn emit_job_event(&self, kind: WebhookEventKind, job_id: uuid::Uuid, error: Option<String>) {
+        let progress = self.job_queue.get_progress(job_id);
+        let progress = progress.as_ref();
+        self.state.webhooks().emit(WebhookEvent::new(
+            kind,
+            serde_json::json!({
+                "job_id": job_id,
+                "total_files": progress.map(|p| p.total_files),
+                "files_processed": progress.map(|p| p.files_processed),
+                "files_skipped": progress.map(|p| p.files_skipped),
+                "files_failed": progress.map(|p| p.files_failed),
+                "total_chunks": progress.map(|p| p.total_chunks),
+                "error": error,
+            }),
+        ));
+    }
+
+    /// Periodically extend the lease on a job until aborted or the lease is lost
+    fn spawn_heartbeat(
+        backend: Arc<dyn QueueBackend>,
+        job_id: uuid::Uuid,
+        worker_id: String,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // First tick fires immediately
+            loop {
+                ticker.tick().await;
+                match backend.heartbeat(job_id, &worker_id).await {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => tracing::warn!("Heartbeat failed for job {}: {}", job_id, e),
+                }
+            }
+        })
+    }
+
     /// Process a job with parallel file processing
     async fn process_job_parallel(&self, job: Job) -> Result<()> {
         let job_id = job.id;
         let parallel_embeddings = job.options.parallel_embeddings.max(1).min(self.parallel_embeddings);
-        let default_file_timeout = self.file_timeout;
         let config = self.state.config();
+        let default_file_timeout = Duration::from_secs(config.processing.file_timeout_secs);
         let tiered_config = &config.processing.tiered;
         let tiered_enabled = tiered_config.enabled;
+        let retry = job.options.retry.clone().unwrap_or_else(|| config.processing.retry.clone());
 
-        // Create semaphore to limit concurrent file processing
-        let semaphore = Arc::new(Semaphore::new(self.parallel_files));
+        // Files of all running jobs share the queue's slots, weighted by lane
+        let priority = job.options.priority;
+
+        // Per-file spans hang off the job span so parallel files show up in one trace
+        let job_span = tracing::Span::current();
 
         // Create futures for all files
         let file_futures: Vec<_> = job.files.into_iter().map(|file_data| {
             let state = self.state.clone();
+            let options = job.options.clone();
+            let retry = retry.clone();
             let job_queue = self.job_queue.clone();
-            let sem = semaphore.clone();
             let filename = file_data.filename.clone();
-            let file_size = file_data.data.len();
+            let file_size = file_data.size as usize;
 
             // Calculate tier-based timeout for this file
             let file_timeout = if tiered_enabled {
                 // Analyze file to determine characteristics
                 let external_parser = state.external_parser();
-                let characteristics = external_parser.analyze_file(&filename, &file_data.data);
+                // A missing spooled file fails when it is processed
+                let data = job_queue.spool().open(&file_data.hash).ok();
+                let characteristics = external_parser.analyze_file(&filename, data.as_deref().unwrap_or_default());
                 let tier_timeout = tiered_config.timeout_for_tier(&characteristics.tier);
 
                 tracing::info!(
@@ -158,9 +301,22 @@ impl ProcessingWorker {
                 default_file_timeout
             };
 
+            // Streamed files are processed batch by batch, scale the timeout with their size
+            let streaming_threshold = (config.processing.streaming_threshold_mb * 1024 * 1024).max(1);
+            let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+            let file_timeout = if StreamFormat::for_file_type(&FileType::from_extension(&ext)).is_some()
+                && file_size as u64 >= streaming_threshold
+            {
+                file_timeout.saturating_mul((file_size as u64 / streaming_threshold).min(u32::MAX as u64) as u32)
+            } else {
+                file_timeout
+            };
+
+            let file_span = tracing::info_span!(parent: &job_span, "process_file", file = %filename, size = file_size);
+
             async move {
-                // Acquire semaphore permit
-                let _permit = sem.acquire().await.unwrap();
+                // Wait for a file slot
+                let _permit = job_queue.scheduler().acquire(priority).await;
 
                 tracing::info!(
                     "Starting parallel processing: {} ({} bytes, timeout: {}s)",
@@ -171,7 +327,9 @@ impl ProcessingWorker {
 
                 // Analyze file and start progress tracking
                 let external_parser = state.external_parser();
-                let file_characteristics = external_parser.analyze_file(&filename, &file_data.data);
+                let data = job_queue.spool().open(&file_data.hash).ok();
+                let file_characteristics = external_parser.analyze_file(&filename, data.as_deref().unwrap_or_default());
+                drop(data);
                 job_queue.start_file_progress(
                     job_id,
                     &filename,
@@ -181,33 +339,57 @@ impl ProcessingWorker {
                 // Mark file as processing in database for resumability
                 job_queue.mark_file_processing(job_id, &filename);
 
-                // Process the file with tier-aware timeout
-                let process_future = Self::process_single_file(
-                    &state,
-                    &job_queue,
-                    job_id,
-                    file_data,
-                    parallel_embeddings,
-                );
+                // Process the file with tier-aware timeout, retrying transient
+                // failures with the policy of the stage that failed
+                let mut attempt = 1;
+                let result = loop {
+                    let attempt_start = std::time::Instant::now();
+                    let process_future = Self::process_single_file(
+                        &state,
+                        &job_queue,
+                        job_id,
+                        &file_data,
+                        parallel_embeddings,
+                        &options,
+                    );
 
-                let result = match timeout(file_timeout, process_future).await {
-                    Ok(inner_result) => inner_result,
-                    Err(_) => {
-                        let elapsed = start_time.elapsed();
-                        tracing::error!(
-                            "TIMEOUT processing '{}' after {:.1}s (tier limit: {}s, size: {} bytes). \
-                            Possible causes: large file, slow embedding service, or parsing hang.",
-                            filename,
-                            elapsed.as_secs_f64(),
-                            file_timeout.as_secs(),
-                            file_size
-                        );
-                        Err(Error::Internal(format!(
-                            "Processing timeout after {}s - file may be too large or complex (size: {} bytes)",
-                            file_timeout.as_secs(),
-                            file_size
-                        )))
+                    let result = match timeout(file_timeout, process_future).await {
+                        Ok(inner_result) => inner_result,
+                        Err(_) => {
+                            let elapsed = attempt_start.elapsed();
+                            tracing::error!(
+                                "TIMEOUT processing '{}' after {:.1}s (tier limit: {}s, size: {} bytes). \
+                                Possible causes: large file, slow embedding service, or parsing hang.",
+                                filename,
+                                elapsed.as_secs_f64(),
+                                file_timeout.as_secs(),
+                                file_size
+                            );
+                            Err(Error::Internal(format!(
+                                "Processing timeout after {}s - file may be too large or complex (size: {} bytes)",
+                                file_timeout.as_secs(),
+                                file_size
+                            )))
+                        }
+                    };
+
+                    let error = match result {
+                        Err(ref e) if e.is_transient() => e,
+                        result => break result,
+                    };
+                    job_queue.record_file_attempt(job_id, &filename, attempt, &error.to_string());
+                    let policy = retry.for_stage(ProcessingStage::of_error(error));
+                    if attempt >= policy.max_attempts {
+                        break result;
                     }
+
+                    let delay = policy.backoff(attempt);
+                    tracing::warn!(
+                        "[{}] Attempt {}/{} failed ({}), retrying in {}ms",
+                        filename, attempt, policy.max_attempts, error, delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
                 };
 
                 let elapsed = start_time.elapsed();
@@ -219,15 +401,16 @@ impl ProcessingWorker {
                     );
                 }
 
-                (filename, result)
+                (filename, attempt, result)
             }
+            .instrument(file_span)
         }).collect();
 
         // Wait for all files to complete
         let results = join_all(file_futures).await;
 
         // Process results
-        for (filename, result) in results {
+        for (filename, attempts, result) in results {
             match result {
                 Ok(FileProcessResult::New { document, file_size, characteristics, parser_method, parser_attempts }) => {
                     // Record success in file registry
@@ -240,6 +423,9 @@ impl ProcessingWorker {
                         document.total_chunks,
                         Some(job_id),
                     );
+                    self.state
+                        .webhooks()
+                        .emit(WebhookEvent::document(WebhookEventKind::DocumentIngested, &document));
                     self.state.add_document(document);
                     self.job_queue.increment_files_processed(job_id);
 
@@ -288,6 +474,9 @@ impl ProcessingWorker {
                         document.total_chunks,
                         Some(job_id),
                     );
+                    self.state
+                        .webhooks()
+                        .emit(WebhookEvent::document(WebhookEventKind::DocumentUpdated, &document));
                     self.state.add_document(document);
                     self.job_queue.increment_files_processed(job_id);
 
@@ -348,6 +537,7 @@ impl ProcessingWorker {
                 }
                 Err(e) => {
                     let error_msg = e.to_string();
+                    let stage = ProcessingStage::of_error(&e);
                     // Record failure in file registry
                     let ext = filename.rsplit('.').next().unwrap_or("");
                     let file_type = FileType::from_extension(ext);
@@ -358,25 +548,35 @@ impl ProcessingWorker {
                         0,
                         file_type,
                         &error_msg,
-                        "parsing",
+                        stage.as_str(),
                         Some(job_id),
                     );
-                    tracing::error!("Failed to process {}: {}", filename, error_msg);
+                    tracing::error!("Failed to process {} after {} attempt(s): {}", filename, attempts, error_msg);
                     self.job_queue.add_file_error(
                         job_id,
                         &filename,
-                        &error_msg,
-                        ProcessingStage::Parsing,
+                        &e,
+                        stage,
                     );
+                    let status = match e {
+                        Error::PasswordRequired(_) => FileProcessingStatus::PasswordRequired,
+                        Error::Malware { .. } => FileProcessingStatus::Quarantined,
+                        _ => FileProcessingStatus::Failed,
+                    };
                     self.job_queue.complete_file_progress(
                         job_id,
                         &filename,
-                        FileProcessingStatus::Failed,
+                        status,
                         Some(&error_msg),
                     );
-                    // Mark file as failed in database for resumability
+                    // Mark file as failed in database for resumability; transient
+                    // failures that exhausted their retries go to the dead-letter list
                     let duration_ms = std::time::Instant::now().elapsed().as_millis() as u64;
-                    self.job_queue.mark_file_failed(job_id, &filename, &error_msg, duration_ms);
+                    if e.is_transient() {
+                        self.job_queue.mark_file_dead_letter(job_id, &filename, &error_msg, stage, duration_ms);
+                    } else {
+                        self.job_queue.mark_file_failed(job_id, &filename, &error_msg, duration_ms);
+                    }
                 }
             }
         }
@@ -389,13 +589,27 @@ impl ProcessingWorker {
         state: &AppState,
         job_queue: &Arc<JobQueue>,
         job_id: uuid::Uuid,
-        file_data: FileData,
+        file_data: &FileData,
         parallel_embeddings: usize,
+        options: &ProcessingOptions,
     ) -> Result<FileProcessResult> {
         let config = state.config();
         let external_parser = state.external_parser();
         let filename = &file_data.filename;
-        let data = &file_data.data;
+        // Mapped from the spool, pages are read from disk as the parsers get to them
+        let spooled = job_queue.spool().open(&file_data.hash)?;
+        // Infected files are quarantined before any parser sees them
+        antivirus::scan_upload(&config.antivirus, &config.vector_db.storage_dir(), filename, &spooled).await?;
+        // Encrypted PDFs are parsed decrypted
+        let decrypted = encrypted::decrypt_upload(
+            &config.pdf_passwords,
+            filename,
+            &spooled,
+            options.collection.as_deref(),
+            &options.passwords,
+        )
+        .await?;
+        let data: &[u8] = decrypted.as_deref().unwrap_or(&spooled);
         let file_size = data.len();
 
         // Analyze file to determine characteristics and processing strategy
@@ -420,21 +634,43 @@ impl ProcessingWorker {
         // Store original filename for reporting/citations
         let original_filename = filename.to_string();
 
+        // Very large plain-text/CSV files are chunked and embedded incrementally
+        if let Some(format) = StreamFormat::for_file_type(&FileType::from_extension(&ext)) {
+            if file_size as u64 >= config.processing.streaming_threshold_mb * 1024 * 1024 {
+                return Self::process_streaming_file(
+                    state,
+                    job_queue,
+                    job_id,
+                    file_data,
+                    format,
+                    parallel_embeddings,
+                    options,
+                    characteristics,
+                ).await;
+            }
+        }
+
         // For ALL PDFs, use escalation parsing to ensure robust handling
         // Small simple-looking PDFs can still have parsing issues (font encoding, etc.)
-        if ext == "pdf" {
+        // Files a parser plugin handles go through it too, plugins first
+        if ext == "pdf" || external_parser.has_plugin(filename) {
             tracing::info!(
-                "[{}] Using escalation parsing for PDF (encrypted: {}, scanned: {}, complexity: {:.2})",
+                "[{}] Using escalation parsing (encrypted: {}, scanned: {}, complexity: {:.2})",
                 filename, characteristics.is_encrypted, characteristics.is_scanned_pdf, characteristics.complexity_score
             );
 
-            match external_parser.parse_with_full_escalation(filename, data, &characteristics).await {
+            match external_parser
+                .parse_with_full_escalation(filename, data, &characteristics)
+                .instrument(tracing::info_span!("external_parse"))
+                .await
+            {
                 Ok(result) => {
                     tracing::info!(
                         "[{}] Escalation succeeded with '{}': {} chars, {} attempts",
                         filename, result.method, result.content.len(), result.attempts.len()
                     );
-                    let text_filename = format!("{}.txt", filename.trim_end_matches(".pdf"));
+                    let stem = filename.rsplit_once('.').map_or(filename.as_str(), |(stem, _)| stem);
+                    let text_filename = format!("{}.txt", stem);
                     return Self::process_text_content_with_metadata(
                         state,
                         job_queue,
@@ -444,6 +680,7 @@ impl ProcessingWorker {
                         result.content.as_bytes(),
                         Some(data),
                         parallel_embeddings,
+                        options,
                         Some(characteristics),
                         Some(result.method),
                         result.attempts,
@@ -453,9 +690,9 @@ impl ProcessingWorker {
                     tracing::error!("[{}] Escalation parsing failed: {}", filename, e);
                     // Try Document AI as last resort if available
                     #[cfg(feature = "gcp")]
-                    if let Some(doc_ai) = state.document_ai() {
+                    if let Some(doc_ai) = state.document_ai().filter(|_| ext == "pdf") {
                         tracing::info!("[{}] Trying Document AI as final fallback...", filename);
-                        match timeout(Duration::from_secs(300), doc_ai.process_pdf(data, filename)).await {
+                        match timeout(doc_ai.timeout(), doc_ai.process_pdf(data, filename)).await {
                             Ok(Ok(result)) => {
                                 tracing::info!(
                                     "[{}] Document AI succeeded: {} chars from {} pages",
@@ -476,9 +713,10 @@ impl ProcessingWorker {
                                     job_id,
                                     &original_filename,
                                     Some(&text_filename),
-                                    result.text.as_bytes(),
+                                    result.text_with_tables().as_bytes(),
                                     Some(data),
                                     parallel_embeddings,
+                                    options,
                                     Some(characteristics),
                                     Some("document_ai".to_string()),
                                     attempts,
@@ -488,7 +726,7 @@ impl ProcessingWorker {
                                 tracing::error!("[{}] Document AI failed: {}", filename, doc_ai_err);
                             }
                             Err(_) => {
-                                tracing::error!("[{}] Document AI timeout after 300s", filename);
+                                tracing::error!("[{}] Document AI timeout after {:?}", filename, doc_ai.timeout());
                             }
                         }
                     }
@@ -497,6 +735,26 @@ impl ProcessingWorker {
             }
         }
 
+        // Audio and video are transcribed instead of parsed
+        if transcription::is_media(filename) {
+            tracing::info!("[{}] Transcribing recording...", filename);
+            let parsed = transcribe(state, filename, data).await?;
+            return Self::process_parsed_file(
+                state,
+                job_queue,
+                job_id,
+                &original_filename,
+                None,
+                data,
+                &parsed,
+                file_size as u64,
+                parallel_embeddings,
+                options,
+                characteristics,
+                "whisper",
+            ).await;
+        }
+
         // Check if we need to convert legacy format
         let (processed_filename, processed_data) = if ExternalParser::needs_conversion(filename) {
             tracing::info!("[{}] Converting legacy format with LibreOffice...", filename);
@@ -577,6 +835,7 @@ impl ProcessingWorker {
                             text.as_bytes(),
                             Some(data),
                             parallel_embeddings,
+                            options,
                         ).await;
                     }
                     Err(e) => {
@@ -636,6 +895,7 @@ impl ProcessingWorker {
                             text.as_bytes(),
                             Some(data),
                             parallel_embeddings,
+                            options,
                         ).await;
                     }
                     Err(e) => {
@@ -684,13 +944,23 @@ impl ProcessingWorker {
 
         // Create pipeline
         let pipeline = IngestPipeline::new(
-            config.chunking.chunk_size,
-            config.chunking.chunk_overlap,
-        );
+            options.chunk_size.unwrap_or(config.chunking.chunk_size),
+            options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+        )
+        .with_parent_window(config.retrieval.parent_window)
+        .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+        .with_size_unit(
+            options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+            state.embedder_for(options.collection.as_deref()),
+            &config.context,
+        )
+        .with_row_descriptions(config.chunking.row_descriptions);
 
         // Parse file to get content hash
         // Note: PDFs are handled earlier by escalation parsing and never reach here
-        let parsed = match pipeline.parse_file(&processed_filename, &processed_data) {
+        let parsed = match tracing::info_span!("parse")
+            .in_scope(|| pipeline.parse_file(&processed_filename, &processed_data))
+        {
             Ok(p) => p,
             Err(e) => {
                 // Non-PDF parsing failed - try pandoc fallback for supported formats
@@ -730,6 +1000,7 @@ impl ProcessingWorker {
                                     text.as_bytes(),
                                     Some(data),
                                     parallel_embeddings,
+                                    options,
                                 ).await;
                             }
                             Ok(_) => {
@@ -768,6 +1039,7 @@ impl ProcessingWorker {
                                 parsed_ext.content.as_bytes(),
                                 Some(data),
                                 parallel_embeddings,
+                                options,
                             ).await;
                         }
                         Ok(Ok(_)) => {
@@ -793,8 +1065,41 @@ impl ProcessingWorker {
             }
         };
 
+        Self::process_parsed_file(
+            state,
+            job_queue,
+            job_id,
+            &original_filename,
+            internal_filename.as_deref(),
+            &processed_data,
+            &parsed,
+            file_size as u64,
+            parallel_embeddings,
+            options,
+            characteristics,
+            "native",
+        ).await
+    }
+
+    /// Deduplicate and process a parsed file
+    /// - `data`: File bytes to store (converted file if different from the upload)
+    /// - `file_size`: Size of the uploaded file, for reporting
+    async fn process_parsed_file(
+        state: &AppState,
+        job_queue: &Arc<JobQueue>,
+        job_id: uuid::Uuid,
+        original_filename: &str,
+        internal_filename: Option<&str>,
+        data: &[u8],
+        parsed: &crate::ingestion::ParsedDocument,
+        file_size: u64,
+        parallel_embeddings: usize,
+        options: &ProcessingOptions,
+        characteristics: FileCharacteristics,
+        parser_method: &str,
+    ) -> Result<FileProcessResult> {
         // Check file status for deduplication (use original filename for tracking)
-        match state.check_file_status(&original_filename, &parsed.content_hash) {
+        match state.check_file_status(original_filename, &parsed.content_hash) {
             FileStatus::Unchanged(existing) => {
                 Ok(FileProcessResult::Skipped {
                     reason: format!(
@@ -803,7 +1108,7 @@ impl ProcessingWorker {
                     ),
                     skip_reason: SkipReason::Unchanged,
                     content_hash: existing.content_hash.clone(),
-                    file_size: file_size as u64,
+                    file_size,
                     file_type: parsed.file_type.clone(),
                 })
             }
@@ -812,7 +1117,7 @@ impl ProcessingWorker {
                     reason: format!("duplicate of '{}'", existing.filename),
                     skip_reason: SkipReason::Duplicate { existing_filename: existing.filename.clone() },
                     content_hash: existing.content_hash.clone(),
-                    file_size: file_size as u64,
+                    file_size,
                     file_type: parsed.file_type.clone(),
                 })
             }
@@ -825,7 +1130,7 @@ impl ProcessingWorker {
                     ),
                     skip_reason: SkipReason::Unchanged,
                     content_hash: record.content_hash.clone(),
-                    file_size: file_size as u64,
+                    file_size,
                     file_type: parsed.file_type.clone(),
                 })
             }
@@ -834,13 +1139,13 @@ impl ProcessingWorker {
                     reason: format!("duplicate of '{}' in GCS", record.filename),
                     skip_reason: SkipReason::Duplicate { existing_filename: record.filename.clone() },
                     content_hash: record.content_hash.clone(),
-                    file_size: file_size as u64,
+                    file_size,
                     file_type: parsed.file_type.clone(),
                 })
             }
             FileStatus::Modified(existing) => {
-                // Delete old document and its chunks
-                let deleted = state.delete_document_with_chunks(&existing.id).await?;
+                // Delete old document and its chunks (kept as a superseded version)
+                let deleted = state.supersede_document(&existing).await?;
                 tracing::info!(
                     "File '{}' modified, deleted {} old chunks",
                     original_filename,
@@ -852,18 +1157,19 @@ impl ProcessingWorker {
                     state,
                     job_queue,
                     job_id,
-                    &original_filename,
-                    internal_filename.as_deref(),
-                    &processed_data,
-                    &parsed,
+                    original_filename,
+                    internal_filename,
+                    data,
+                    parsed,
                     parallel_embeddings,
+                    options,
                 ).await?;
                 Ok(FileProcessResult::Updated {
                     document: doc,
-                    file_size: file_size as u64,
+                    file_size,
                     old_chunks_deleted: deleted,
                     characteristics: Some(characteristics),
-                    parser_method: Some("native".to_string()),
+                    parser_method: Some(parser_method.to_string()),
                     parser_attempts: vec![],
                 })
             }
@@ -873,17 +1179,18 @@ impl ProcessingWorker {
                     state,
                     job_queue,
                     job_id,
-                    &original_filename,
-                    internal_filename.as_deref(),
-                    &processed_data,
-                    &parsed,
+                    original_filename,
+                    internal_filename,
+                    data,
+                    parsed,
                     parallel_embeddings,
+                    options,
                 ).await?;
                 Ok(FileProcessResult::New {
                     document: doc,
-                    file_size: file_size as u64,
+                    file_size,
                     characteristics: Some(characteristics),
-                    parser_method: Some("native".to_string()),
+                    parser_method: Some(parser_method.to_string()),
                     parser_attempts: vec![],
                 })
             }
@@ -904,6 +1211,7 @@ impl ProcessingWorker {
         text_data: &[u8],
         original_data: Option<&[u8]>,
         parallel_embeddings: usize,
+        options: &ProcessingOptions,
     ) -> Result<FileProcessResult> {
         let config = state.config();
         let content = String::from_utf8_lossy(text_data).to_string();
@@ -967,8 +1275,8 @@ impl ProcessingWorker {
                 });
             }
             crate::server::state::FileStatus::Modified(existing) => {
-                // Delete old document and its chunks, then continue processing
-                let deleted = state.delete_document_with_chunks(&existing.id).await?;
+                // Delete old document and its chunks (kept as a superseded version), then continue processing
+                let deleted = state.supersede_document(&existing).await?;
                 tracing::info!(
                     "[{}] File modified, deleted {} old chunks, reprocessing",
                     original_filename,
@@ -998,12 +1306,23 @@ impl ProcessingWorker {
                 text_data.len() as u64,
             )
         };
+        doc.set_collection(options.collection.as_deref());
+        doc.set_acl(&options.acl);
+        doc.detect_language(&content);
 
         // Create pipeline for chunking
         let pipeline = IngestPipeline::new(
-            config.chunking.chunk_size,
-            config.chunking.chunk_overlap,
-        );
+            options.chunk_size.unwrap_or(config.chunking.chunk_size),
+            options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+        )
+        .with_parent_window(config.retrieval.parent_window)
+        .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+        .with_size_unit(
+            options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+            state.embedder_for(options.collection.as_deref()),
+            &config.context,
+        )
+        .with_row_descriptions(config.chunking.row_descriptions);
 
         // Create a parsed document structure
         let parsed = crate::ingestion::ParsedDocument {
@@ -1017,66 +1336,31 @@ impl ProcessingWorker {
                 char_offset: 0,
             }],
             metadata: std::collections::HashMap::new(),
+            sections: Vec::new(),
         };
 
-        // Create chunks
+        // Chunk, embed and store as pipelined stages
         tracing::info!("[{}] Creating chunks from extracted text...", original_filename);
-        let mut chunks = pipeline.create_chunks(&doc, &parsed)?;
-        let total_chunks = chunks.len();
-        tracing::info!("[{}] Created {} chunks, generating embeddings...", original_filename, total_chunks);
-
-        // Generate embeddings using provider abstraction
-        let chunk_batches: Vec<_> = chunks.chunks_mut(parallel_embeddings).collect();
-        let embedding_provider = state.embedding_provider();
-        let embed_timeout = Duration::from_secs(60);
-        let mut batch_num = 0;
-        let total_batches = chunk_batches.len();
-
-        for batch in chunk_batches {
-            batch_num += 1;
-            let batch_start = std::time::Instant::now();
-
-            let embedding_futures: Vec<_> = batch
-                .iter()
-                .map(|chunk| embedding_provider.embed(&chunk.content))
-                .collect();
-
-            let batch_result = timeout(embed_timeout, join_all(embedding_futures)).await;
-
-            match batch_result {
-                Ok(results) => {
-                    for (chunk, result) in batch.iter_mut().zip(results) {
-                        match result {
-                            Ok(embedding) => chunk.embedding = embedding,
-                            Err(e) => {
-                                tracing::warn!("[{}] Embedding failed: {}", original_filename, e);
-                                chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    tracing::error!("[{}] Embedding batch {}/{} timed out", original_filename, batch_num, total_batches);
-                    for chunk in batch.iter_mut() {
-                        chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                    }
-                }
-            }
-
-            if batch_start.elapsed().as_secs() > 10 {
-                tracing::info!("[{}] Batch {}/{} took {:.1}s", original_filename, batch_num, total_batches, batch_start.elapsed().as_secs_f64());
-            }
-
-            job_queue.increment_chunks_embedded(job_id, batch.len());
+        let chunks = pipeline.create_chunks(&doc, &parsed)?;
+        let first_figure = chunks.len() as u32;
+        let doc_ref = &doc;
+        let parsed_ref = &parsed;
+        let figures = futures::stream::once(async move {
+            Ok::<_, Error>(match original_data {
+                Some(original) => figure_chunks(state, doc_ref, parsed_ref, original_filename, original, first_figure).await,
+                None => Vec::new(),
+            })
+        });
+        let totals = IngestStages::new(state, job_queue, job_id, original_filename, &doc, parallel_embeddings)
+            .run(batches_of(chunks, config.processing.pipeline_batch_chunks).chain(figures))
+            .await?;
+        totals.apply(state, &mut doc);
+        let total_chunks = totals.chunks;
+        if let Some(original) = original_data {
+            store_original(state, &doc, original_filename, original, &content).await;
+            store_thumbnails(state, &doc, original_filename, original).await;
         }
 
-        // Store chunks using provider (Vertex AI for GCP backend)
-        tracing::info!("[{}] Storing {} chunks...", original_filename, total_chunks);
-        state.vector_store_provider().insert_chunks(&chunks).await?;
-
-        // Store chunks locally for metadata lookup (needed for Vertex AI)
-        state.store_chunks(&chunks);
-
         // Store original file and plain text in GCS (GCP backend only)
         #[cfg(feature = "gcp")]
         if let Some(document_store) = state.document_store() {
@@ -1128,6 +1412,7 @@ impl ProcessingWorker {
         text_data: &[u8],
         original_data: Option<&[u8]>,
         parallel_embeddings: usize,
+        options: &ProcessingOptions,
         characteristics: Option<FileCharacteristics>,
         parser_method: Option<String>,
         parser_attempts: Vec<ParserAttempt>,
@@ -1190,7 +1475,7 @@ impl ProcessingWorker {
                 });
             }
             crate::server::state::FileStatus::Modified(existing) => {
-                let deleted = state.delete_document_with_chunks(&existing.id).await?;
+                let deleted = state.supersede_document(&existing).await?;
                 tracing::info!(
                     "[{}] File modified, deleted {} old chunks, reprocessing",
                     original_filename, deleted
@@ -1216,12 +1501,23 @@ impl ProcessingWorker {
                 text_data.len() as u64,
             )
         };
+        doc.set_collection(options.collection.as_deref());
+        doc.set_acl(&options.acl);
+        doc.detect_language(&content);
 
         // Create pipeline for chunking
         let pipeline = IngestPipeline::new(
-            config.chunking.chunk_size,
-            config.chunking.chunk_overlap,
-        );
+            options.chunk_size.unwrap_or(config.chunking.chunk_size),
+            options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+        )
+        .with_parent_window(config.retrieval.parent_window)
+        .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+        .with_size_unit(
+            options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+            state.embedder_for(options.collection.as_deref()),
+            &config.context,
+        )
+        .with_row_descriptions(config.chunking.row_descriptions);
 
         // Create parsed document structure
         let parsed = crate::ingestion::ParsedDocument {
@@ -1235,64 +1531,31 @@ impl ProcessingWorker {
                 char_offset: 0,
             }],
             metadata: std::collections::HashMap::new(),
+            sections: Vec::new(),
         };
 
-        // Create chunks
+        // Chunk, embed and store as pipelined stages
         tracing::info!("[{}] Creating chunks from extracted text...", original_filename);
-        let mut chunks = pipeline.create_chunks(&doc, &parsed)?;
-        let total_chunks = chunks.len();
-        tracing::info!("[{}] Created {} chunks, generating embeddings...", original_filename, total_chunks);
-
-        // Generate embeddings
-        let chunk_batches: Vec<_> = chunks.chunks_mut(parallel_embeddings).collect();
-        let embedding_provider = state.embedding_provider();
-        let embed_timeout = Duration::from_secs(60);
-        let mut batch_num = 0;
-        let total_batches = chunk_batches.len();
-
-        for batch in chunk_batches {
-            batch_num += 1;
-            let batch_start = std::time::Instant::now();
-
-            let embedding_futures: Vec<_> = batch
-                .iter()
-                .map(|chunk| embedding_provider.embed(&chunk.content))
-                .collect();
-
-            let batch_result = timeout(embed_timeout, join_all(embedding_futures)).await;
-
-            match batch_result {
-                Ok(results) => {
-                    for (chunk, result) in batch.iter_mut().zip(results) {
-                        match result {
-                            Ok(embedding) => chunk.embedding = embedding,
-                            Err(e) => {
-                                tracing::warn!("[{}] Embedding failed: {}", original_filename, e);
-                                chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    tracing::error!("[{}] Embedding batch {}/{} timed out", original_filename, batch_num, total_batches);
-                    for chunk in batch.iter_mut() {
-                        chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                    }
-                }
-            }
-
-            if batch_start.elapsed().as_secs() > 10 {
-                tracing::info!("[{}] Batch {}/{} took {:.1}s", original_filename, batch_num, total_batches, batch_start.elapsed().as_secs_f64());
-            }
-
-            job_queue.increment_chunks_embedded(job_id, batch.len());
+        let chunks = pipeline.create_chunks(&doc, &parsed)?;
+        let first_figure = chunks.len() as u32;
+        let doc_ref = &doc;
+        let parsed_ref = &parsed;
+        let figures = futures::stream::once(async move {
+            Ok::<_, Error>(match original_data {
+                Some(original) => figure_chunks(state, doc_ref, parsed_ref, original_filename, original, first_figure).await,
+                None => Vec::new(),
+            })
+        });
+        let totals = IngestStages::new(state, job_queue, job_id, original_filename, &doc, parallel_embeddings)
+            .run(batches_of(chunks, config.processing.pipeline_batch_chunks).chain(figures))
+            .await?;
+        totals.apply(state, &mut doc);
+        let total_chunks = totals.chunks;
+        if let Some(original) = original_data {
+            store_original(state, &doc, original_filename, original, &content).await;
+            store_thumbnails(state, &doc, original_filename, original).await;
         }
 
-        // Store chunks
-        tracing::info!("[{}] Storing {} chunks...", original_filename, total_chunks);
-        state.vector_store_provider().insert_chunks(&chunks).await?;
-        state.store_chunks(&chunks);
-
         // Store original file and plain text in GCS (GCP backend only)
         #[cfg(feature = "gcp")]
         if let Some(document_store) = state.document_store() {
@@ -1341,14 +1604,23 @@ impl ProcessingWorker {
         data: &[u8],
         parsed: &crate::ingestion::ParsedDocument,
         parallel_embeddings: usize,
+        options: &ProcessingOptions,
     ) -> Result<Document> {
         let config = state.config();
 
         // Create pipeline
         let pipeline = IngestPipeline::new(
-            config.chunking.chunk_size,
-            config.chunking.chunk_overlap,
-        );
+            options.chunk_size.unwrap_or(config.chunking.chunk_size),
+            options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap),
+        )
+        .with_parent_window(config.retrieval.parent_window)
+        .with_code_tokens(config.chunking.code_chunk_tokens, &config.context)
+        .with_size_unit(
+            options.chunk_size_unit.unwrap_or(config.chunking.size_unit),
+            state.embedder_for(options.collection.as_deref()),
+            &config.context,
+        )
+        .with_row_descriptions(config.chunking.row_descriptions);
 
         // Create document with original and internal filenames
         let mut doc = if let Some(internal) = internal_filename {
@@ -1368,84 +1640,26 @@ impl ProcessingWorker {
             )
         };
         doc.total_pages = parsed.total_pages;
+        doc.set_collection(options.collection.as_deref());
+        doc.set_acl(&options.acl);
+        doc.detect_language(&parsed.content);
+        doc.set_source_metadata(&parsed.metadata);
 
-        // Create chunks
+        // Chunk, embed and store as pipelined stages
         tracing::info!("[{}] Creating chunks...", original_filename);
-        let mut chunks = pipeline.create_chunks(&doc, parsed)?;
-        let total_chunks = chunks.len();
-        tracing::info!("[{}] Created {} chunks, generating embeddings...", original_filename, total_chunks);
-
-        // Generate embeddings in parallel batches with timeout (using provider abstraction)
-        let chunk_batches: Vec<_> = chunks.chunks_mut(parallel_embeddings).collect();
-        let embedding_provider = state.embedding_provider();
-        let embed_timeout = Duration::from_secs(60); // 60s per batch
-        let mut batch_num = 0;
-        let total_batches = chunk_batches.len();
-
-        for batch in chunk_batches {
-            batch_num += 1;
-            let batch_start = std::time::Instant::now();
-
-            let embedding_futures: Vec<_> = batch
-                .iter()
-                .map(|chunk| embedding_provider.embed(&chunk.content))
-                .collect();
-
-            // Wrap the batch in a timeout
-            let batch_result = timeout(embed_timeout, join_all(embedding_futures)).await;
-
-            match batch_result {
-                Ok(results) => {
-                    let mut failed_count = 0;
-                    for (chunk, result) in batch.iter_mut().zip(results) {
-                        match result {
-                            Ok(embedding) => {
-                                chunk.embedding = embedding;
-                            }
-                            Err(e) => {
-                                failed_count += 1;
-                                tracing::warn!("[{}] Embedding failed for chunk: {}", original_filename, e);
-                                // Use zero vector as fallback
-                                chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                            }
-                        }
-                    }
-                    if failed_count > 0 {
-                        tracing::warn!(
-                            "[{}] Batch {}/{}: {} embeddings failed, using fallback",
-                            original_filename, batch_num, total_batches, failed_count
-                        );
-                    }
-                }
-                Err(_) => {
-                    tracing::error!(
-                        "[{}] TIMEOUT: Embedding batch {}/{} took >{}s, using fallback embeddings",
-                        original_filename, batch_num, total_batches, embed_timeout.as_secs()
-                    );
-                    // Use zero vectors for all chunks in this batch
-                    for chunk in batch.iter_mut() {
-                        chunk.embedding = vec![0.0; config.embeddings.dimensions];
-                    }
-                }
-            }
-
-            let batch_elapsed = batch_start.elapsed();
-            if batch_elapsed.as_secs() > 10 {
-                tracing::info!(
-                    "[{}] Batch {}/{} took {:.1}s",
-                    original_filename, batch_num, total_batches, batch_elapsed.as_secs_f64()
-                );
-            }
-
-            job_queue.increment_chunks_embedded(job_id, batch.len());
-        }
-
-        // Store chunks using provider (Vertex AI for GCP backend)
-        tracing::info!("[{}] Storing {} chunks in vector database...", original_filename, total_chunks);
-        state.vector_store_provider().insert_chunks(&chunks).await?;
-
-        // Store chunks locally for metadata lookup (needed for Vertex AI)
-        state.store_chunks(&chunks);
+        let chunks = pipeline.create_chunks(&doc, parsed)?;
+        let first_figure = chunks.len() as u32;
+        let doc_ref = &doc;
+        let figures = futures::stream::once(async move {
+            Ok::<_, Error>(figure_chunks(state, doc_ref, parsed, original_filename, data, first_figure).await)
+        });
+        let totals = IngestStages::new(state, job_queue, job_id, original_filename, &doc, parallel_embeddings)
+            .run(batches_of(chunks, config.processing.pipeline_batch_chunks).chain(figures))
+            .await?;
+        totals.apply(state, &mut doc);
+        let total_chunks = totals.chunks;
+        store_original(state, &doc, original_filename, data, &parsed.content).await;
+        store_thumbnails(state, &doc, original_filename, data).await;
 
         // Store original file and plain text in GCS (GCP backend only)
         #[cfg(feature = "gcp")]
@@ -1484,4 +1698,162 @@ impl ProcessingWorker {
 
         Ok(doc)
     }
+
+    /// Chunk, embed and store a large plain-text/CSV file in bounded batches
+    ///
+    /// The file is read from the spool and never decoded as a whole: a first
+    /// pass hashes it for deduplication, a second pass streams chunks through
+    /// embedding and storage. Progress is reported in bytes read. If a batch
+    /// fails, chunks already stored for the document are removed again.
+    async fn process_streaming_file(
+        state: &AppState,
+        job_queue: &Arc<JobQueue>,
+        job_id: uuid::Uuid,
+        file_data: &FileData,
+        format: StreamFormat,
+        parallel_embeddings: usize,
+        options: &ProcessingOptions,
+        characteristics: FileCharacteristics,
+    ) -> Result<FileProcessResult> {
+        let config = state.config();
+        let original_filename = file_data.filename.as_str();
+        let file_size = file_data.size;
+        let file_type = FileType::from_extension(original_filename.rsplit('.').next().unwrap_or(""));
+
+        tracing::info!(
+            "[{}] Streaming {:?} file ({} MB), hashing for deduplication...",
+            original_filename, format, file_size / (1024 * 1024)
+        );
+        let content_hash = stream_content_hash(job_queue.spool().reader(&file_data.hash)?, format)?;
+
+        let old_chunks_deleted = match state.check_file_status(original_filename, &content_hash) {
+            FileStatus::Unchanged(existing) => {
+                return Ok(FileProcessResult::Skipped {
+                    reason: format!(
+                        "unchanged (hash: {}...)",
+                        &existing.content_hash[..12.min(existing.content_hash.len())]
+                    ),
+                    skip_reason: SkipReason::Unchanged,
+                    content_hash: existing.content_hash.clone(),
+                    file_size,
+                    file_type,
+                });
+            }
+            FileStatus::Duplicate(existing) => {
+                return Ok(FileProcessResult::Skipped {
+                    reason: format!("duplicate of '{}'", existing.filename),
+                    skip_reason: SkipReason::Duplicate { existing_filename: existing.filename.clone() },
+                    content_hash: existing.content_hash.clone(),
+                    file_size,
+                    file_type,
+                });
+            }
+            FileStatus::ExistsInRegistry(record) => {
+                return Ok(FileProcessResult::Skipped {
+                    reason: format!(
+                        "already in GCS (hash: {}..., uploaded: {})",
+                        &record.content_hash[..12.min(record.content_hash.len())],
+                        record.first_seen_at.format("%Y-%m-%d")
+                    ),
+                    skip_reason: SkipReason::Unchanged,
+                    content_hash: record.content_hash.clone(),
+                    file_size,
+                    file_type,
+                });
+            }
+            FileStatus::DuplicateInRegistry(record) => {
+                return Ok(FileProcessResult::Skipped {
+                    reason: format!("duplicate of '{}' in GCS", record.filename),
+                    skip_reason: SkipReason::Duplicate { existing_filename: record.filename.clone() },
+                    content_hash: record.content_hash.clone(),
+                    file_size,
+                    file_type,
+                });
+            }
+            FileStatus::Modified(existing) => {
+                let deleted = state.supersede_document(&existing).await?;
+                tracing::info!("[{}] File modified, deleted {} old chunks", original_filename, deleted);
+                Some(deleted)
+            }
+            FileStatus::New => None,
+        };
+
+        let mut doc = Document::new(original_filename.to_string(), file_type, content_hash, file_size);
+        doc.set_collection(options.collection.as_deref());
+        doc.set_acl(&options.acl);
+        // Only the head of a streamed file is decoded for detection
+        let mut head = Vec::new();
+        job_queue
+            .spool()
+            .reader(&file_data.hash)?
+            .take(crate::ingestion::language::DETECTION_SAMPLE_BYTES as u64)
+            .read_to_end(&mut head)?;
+        doc.detect_language(&String::from_utf8_lossy(&head));
+
+        // Streamed files are split by characters; token sizes are converted
+        // at the context tokenizer's `chars_per_token`
+        let chars_per_unit = match options.chunk_size_unit.unwrap_or(config.chunking.size_unit) {
+            ChunkSizeUnit::Chars => 1.0,
+            ChunkSizeUnit::Tokens => config.context.chars_per_token,
+        };
+        let chunker = StreamingChunker::new(
+            job_queue.spool().reader(&file_data.hash)?,
+            format,
+            &doc,
+            (options.chunk_size.unwrap_or(config.chunking.chunk_size) as f32 * chars_per_unit) as usize,
+            (options.chunk_overlap.unwrap_or(config.chunking.chunk_overlap) as f32 * chars_per_unit) as usize,
+        )?;
+
+        let batch_size = config.processing.streaming_batch_chunks.max(1);
+        let parent_window = config.retrieval.parent_window.filter(|&w| w > 0);
+        // Reading the file is the first stage; it runs ahead of embedding by
+        // at most `pipeline_depth` batches
+        let batches = futures::stream::try_unfold(chunker, move |mut chunker| async move {
+            let mut batch = chunker.next_batch(batch_size)?;
+            if batch.is_empty() {
+                return Ok(None);
+            }
+            // Parent windows are grouped per batch
+            if let Some(window) = parent_window {
+                crate::ingestion::assign_parent_windows(&mut batch, window);
+            }
+            job_queue.update_file_bytes(job_id, original_filename, FileProcessingStatus::Embedding, chunker.bytes_read());
+            tracing::info!(
+                "[{}] Read {:.1}% of {} MB",
+                original_filename,
+                chunker.bytes_read() as f64 / file_size.max(1) as f64 * 100.0,
+                file_size / (1024 * 1024)
+            );
+            Ok::<_, Error>(Some((batch, chunker)))
+        });
+
+        // Chunks are not kept in the chunk store, that would defeat streaming
+        let totals = IngestStages::new(state, job_queue, job_id, original_filename, &doc, parallel_embeddings)
+            .without_chunk_store()
+            .run(batches)
+            .await?;
+        let total_chunks = totals.chunks;
+
+        doc.total_chunks = total_chunks as u32;
+        totals.apply(state, &mut doc);
+        tracing::info!("[{}] COMPLETE: {} chunks streamed", original_filename, total_chunks);
+
+        Ok(match old_chunks_deleted {
+            Some(old_chunks_deleted) => FileProcessResult::Updated {
+                document: doc,
+                file_size,
+                old_chunks_deleted,
+                characteristics: Some(characteristics),
+                parser_method: Some("streaming".to_string()),
+                parser_attempts: vec![],
+            },
+            None => FileProcessResult::New {
+                document: doc,
+                file_size,
+                characteristics: Some(characteristics),
+                parser_method: Some("streaming".to_string()),
+                parser_attempts: vec![],
+            },
+        })
+    }
 }
@@ -0,0 +1,150 @@
+//! Embedding providers per collection
+//!
+//! Collections may be embedded with a model other than the default one (see
+//! `embeddings.collections`). All vectors share one index, so a model with
+//! fewer dimensions than the index is wrapped in `PaddedEmbedder`, which
+//! appends zeros. Cosine similarity between two padded vectors equals that of
+//! the originals; vectors of different models are never compared because
+//! retrieval is scoped to documents embedded with the query's model.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::error::{Error, Result};
+
+use super::embedding::EmbeddingProvider;
+
+/// Embedding provider wrapper that zero-pads vectors to the index dimensions
+pub struct PaddedEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    dimensions: usize,
+}
+
+impl PaddedEmbedder {
+    /// Wrap a provider producing at most `dimensions` values
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, dimensions: usize) -> Self {
+        Self { inner, dimensions }
+    }
+
+    fn pad(&self, mut vector: Vec<f32>) -> Result<Vec<f32>> {
+        if vector.len() > self.dimensions {
+            return Err(Error::EmbeddingDimensionMismatch {
+                expected: self.dimensions,
+                actual: vector.len(),
+            });
+        }
+        vector.resize(self.dimensions, 0.0);
+        Ok(vector)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for PaddedEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.pad(self.inner.embed(text).await?)
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed_batch(texts).await?.into_iter().map(|v| self.pad(v)).collect()
+    }
+
+    async fn embed_parts(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed_parts(text).await?.into_iter().map(|v| self.pad(v)).collect()
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.inner.count_tokens(text)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// Embedding model and provider of each collection
+pub struct CollectionEmbedders {
+    default_model: String,
+    models: HashMap<String, (String, Arc<dyn EmbeddingProvider>)>,
+}
+
+impl CollectionEmbedders {
+    /// Collections without a model of their own use `default_model`
+    pub fn new(default_model: String) -> Self {
+        Self {
+            default_model,
+            models: HashMap::new(),
+        }
+    }
+
+    /// Embed a collection with its own model
+    pub fn insert(&mut self, collection: String, model: String, provider: Arc<dyn EmbeddingProvider>) {
+        self.models.insert(collection, (model, provider));
+    }
+
+    /// Model that embeds a collection
+    pub fn model(&self, collection: Option<&str>) -> &str {
+        collection
+            .and_then(|c| self.models.get(c))
+            .map(|(model, _)| model.as_str())
+            .unwrap_or(&self.default_model)
+    }
+
+    /// Provider of a collection's own model (`None` for the default model)
+    pub fn provider(&self, collection: Option<&str>) -> Option<Arc<dyn EmbeddingProvider>> {
+        collection.and_then(|c| self.models.get(c)).map(|(_, provider)| provider.clone())
+    }
+
+    /// Collections with a model of their own
+    pub fn collections(&self) -> impl Iterator<Item = &str> {
+        self.models.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for FixedEmbedder {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(vec![0.6, 0.8])
+        }
+        fn dimensions(&self) -> usize {
+            2
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        fn name(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collection_models() {
+        let padded: Arc<dyn EmbeddingProvider> = Arc::new(PaddedEmbedder::new(Arc::new(FixedEmbedder), 4));
+        assert_eq!(padded.embed("x").await.unwrap(), vec![0.6, 0.8, 0.0, 0.0]);
+        assert_eq!(padded.dimensions(), 4);
+        assert!(PaddedEmbedder::new(Arc::new(FixedEmbedder), 1).embed("x").await.is_err());
+
+        let mut embedders = CollectionEmbedders::new("nomic-embed-text".to_string());
+        embedders.insert("code".to_string(), "small".to_string(), padded);
+        assert_eq!(embedders.model(Some("code")), "small");
+        assert_eq!(embedders.model(Some("other")), "nomic-embed-text");
+        assert_eq!(embedders.model(None), "nomic-embed-text");
+        assert!(embedders.provider(Some("code")).is_some());
+        assert!(embedders.provider(None).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+//! Embedding dimension discovery
+//!
+//! `embeddings.dimensions` sizes the vector index, but nothing guaranteed the
+//! model actually returns that many values: a larger model's vectors were
+//! refused chunk by chunk and a smaller one's were zero-padded without notice.
+//! At startup each embedding model is asked for one vector and the configured
+//! dimensions are checked against its length (`embeddings.dimension_check`):
+//! a mismatch stops startup or, with `adopt`, replaces the configured value.
+//! The index dimensions are recorded in the database once vectors are stored,
+//! so a later model or configuration change that no longer fits them fails
+//! with a migration error instead of mixing vector sizes in one index.
+
+use crate::config::DimensionCheck;
+use crate::error::{Error, Result};
+
+use super::embedding::EmbeddingProvider;
+
+const PROBE_TEXT: &str = "dimension check";
+
+/// Number of values `provider` returns, or `None` if it couldn't be asked
+pub async fn probe_dimensions(provider: &dyn EmbeddingProvider) -> Option<usize> {
+    match provider.embed(PROBE_TEXT).await {
+        Ok(vector) if !vector.is_empty() => Some(vector.len()),
+        Ok(_) => {
+            tracing::warn!("{} returned an empty embedding for the dimension probe", provider.name());
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Failed to probe the dimensions of {}: {}", provider.name(), e);
+            None
+        }
+    }
+}
+
+/// Dimensions to use for `model`, configured with `configured` and found to
+/// produce `probed` (if it could be asked)
+pub fn resolve_dimensions(
+    model: &str,
+    setting: &str,
+    configured: usize,
+    probed: Option<usize>,
+    check: DimensionCheck,
+) -> Result<usize> {
+    match probed {
+        Some(probed) if probed != configured => match check {
+            DimensionCheck::Fail => Err(Error::Config(format!(
+                "Embedding model {} produces {} dimensions, {} is {} - set it to {} \
+                 (or embeddings.dimension_check = \"adopt\")",
+                model, probed, setting, configured, probed
+            ))),
+            DimensionCheck::Adopt => {
+                tracing::warn!(
+                    "Embedding model {} produces {} dimensions, using them instead of {} = {}",
+                    model, probed, setting, configured
+                );
+                Ok(probed)
+            }
+            DimensionCheck::Off => Ok(configured),
+        },
+        _ => Ok(configured),
+    }
+}
+
+/// Check the index dimensions against those of the vectors already stored
+pub fn check_stored_dimensions(dimensions: usize, stored: Option<usize>) -> Result<()> {
+    match stored {
+        Some(stored) if stored != dimensions => Err(Error::Config(format!(
+            "The vector index holds {} dimensional embeddings, the embedding model now produces {} - \
+             restore the previous model and embeddings.dimensions, or move the vector store \
+             (vector_db.storage_path) and registry aside and re-ingest the documents",
+            stored, dimensions
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_dimensions() {
+        let setting = "embeddings.dimensions";
+        assert_eq!(resolve_dimensions("m", setting, 768, Some(768), DimensionCheck::Fail).unwrap(), 768);
+        assert_eq!(resolve_dimensions("m", setting, 768, None, DimensionCheck::Fail).unwrap(), 768);
+        assert!(resolve_dimensions("m", setting, 768, Some(1024), DimensionCheck::Fail).is_err());
+        assert_eq!(resolve_dimensions("m", setting, 768, Some(1024), DimensionCheck::Adopt).unwrap(), 1024);
+        assert_eq!(resolve_dimensions("m", setting, 768, Some(1024), DimensionCheck::Off).unwrap(), 768);
+
+        assert!(check_stored_dimensions(768, None).is_ok());
+        assert!(check_stored_dimensions(768, Some(768)).is_ok());
+        assert!(check_stored_dimensions(1024, Some(768)).is_err());
+    }
+}
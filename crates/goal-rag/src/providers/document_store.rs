@@ -1,9 +1,14 @@
 //! Document store provider trait for storing raw document files
 
 use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt};
 use uuid::Uuid;
 use crate::error::Result;
 
+/// Bytes of a stored document, read as they are sent
+pub type DocumentStream = BoxStream<'static, Result<Bytes>>;
+
 /// Metadata about a stored document
 #[derive(Debug, Clone)]
 pub struct StoredDocumentInfo {
@@ -37,6 +42,24 @@ pub trait DocumentStoreProvider: Send + Sync {
     /// Retrieve document data
     async fn get_document(&self, doc_id: &Uuid) -> Result<Vec<u8>>;
 
+    /// Size of a stored document in bytes, if it is stored
+    async fn document_size(&self, doc_id: &Uuid) -> Result<Option<u64>>;
+
+    /// Retrieve bytes `start..=end` of a document
+    async fn get_document_range(&self, doc_id: &Uuid, start: u64, end: u64) -> Result<Vec<u8>>;
+
+    /// Stream bytes `start..=end` of a document, or all of it for `None`
+    ///
+    /// The default loads the bytes in one piece; stores that can read a
+    /// document incrementally override it.
+    async fn stream_document(&self, doc_id: &Uuid, range: Option<(u64, u64)>) -> Result<DocumentStream> {
+        let data = match range {
+            Some((start, end)) => self.get_document_range(doc_id, start, end).await?,
+            None => self.get_document(doc_id).await?,
+        };
+        Ok(stream::once(async move { Ok(Bytes::from(data)) }).boxed())
+    }
+
     /// Check if document exists
     async fn exists(&self, doc_id: &Uuid) -> Result<bool>;
 
@@ -49,6 +72,28 @@ pub trait DocumentStoreProvider: Send + Sync {
     /// Get storage URI for a document
     async fn get_uri(&self, doc_id: &Uuid) -> Result<Option<String>>;
 
+    /// Store the plain text extracted from a document
+    ///
+    /// Returns the storage URI
+    async fn store_plain_text(&self, doc_id: &Uuid, filename: &str, text: &str) -> Result<String>;
+
+    /// Retrieve the plain text of a document, if it was stored
+    async fn get_plain_text(&self, doc_id: &Uuid) -> Result<Option<String>>;
+
+    /// Delete the plain text of a document
+    async fn delete_plain_text(&self, doc_id: &Uuid) -> Result<()>;
+
+    /// Store the PNG thumbnail of a page (1-indexed)
+    ///
+    /// Returns the storage URI
+    async fn store_thumbnail(&self, doc_id: &Uuid, page: u32, png: &[u8]) -> Result<String>;
+
+    /// Retrieve the PNG thumbnail of a page, if one was stored
+    async fn get_thumbnail(&self, doc_id: &Uuid, page: u32) -> Result<Option<Vec<u8>>>;
+
+    /// Delete all page thumbnails of a document
+    async fn delete_thumbnails(&self, doc_id: &Uuid) -> Result<()>;
+
     /// Check if the provider is healthy
     async fn health_check(&self) -> Result<bool>;
 
@@ -25,6 +25,22 @@ pub trait EmbeddingProvider: Send + Sync {
         Ok(embeddings)
     }
 
+    /// Generate one or more embeddings for a chunk's text
+    ///
+    /// Returns several vectors when a long input was split into windows and
+    /// each window should be searchable on its own (see `LongInputEmbedder`).
+    /// Default implementation returns the single `embed` vector.
+    async fn embed_parts(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        Ok(vec![self.embed(text).await?])
+    }
+
+    /// Tokens in `text` as the model's tokenizer counts them
+    ///
+    /// `None` when the tokenizer isn't available locally (remote APIs).
+    fn count_tokens(&self, _text: &str) -> Option<usize> {
+        None
+    }
+
     /// Get embedding dimensions (e.g., 768 for nomic-embed-text and text-embedding-005)
     fn dimensions(&self) -> usize;
 
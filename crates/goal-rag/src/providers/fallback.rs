@@ -0,0 +1,188 @@
+//! Generation with fallback models
+//!
+//! `FallbackLlm` tries the models of a chain in order: when a call fails or
+//! takes longer than the attempt timeout, the same call is made on the next
+//! model. Each call falls back on its own, so a map-reduce answer may be
+//! written partly by a fallback model; `answered_by` reports the model of the
+//! last successful call.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::types::response::Citation;
+
+use super::llm::{GeneratedAnswer, LlmProvider};
+
+/// LLM provider that falls back along a chain of models
+pub struct FallbackLlm {
+    chain: Vec<Arc<dyn LlmProvider>>,
+    timeout: Option<Duration>,
+    answered_by: Mutex<Option<String>>,
+}
+
+impl FallbackLlm {
+    /// Models are tried in the order given; `timeout` limits each attempt
+    pub fn new(chain: Vec<Arc<dyn LlmProvider>>, timeout: Option<Duration>) -> Self {
+        Self {
+            chain,
+            timeout,
+            answered_by: Mutex::new(None),
+        }
+    }
+
+    /// Model of the last successful call (the first model before any call)
+    pub fn answered_by(&self) -> String {
+        self.answered_by
+            .lock()
+            .clone()
+            .unwrap_or_else(|| self.chain.first().map(|llm| llm.model().to_string()).unwrap_or_default())
+    }
+
+    async fn attempt<'a, T, F, Fut>(&'a self, call: F) -> Result<T>
+    where
+        F: Fn(&'a dyn LlmProvider) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = Error::Llm("No generation model configured".to_string());
+        for (i, llm) in self.chain.iter().enumerate() {
+            let result = match self.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, call(llm.as_ref())).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Error::Llm(format!("{} timed out after {:?}", llm.model(), timeout))),
+                },
+                None => call(llm.as_ref()).await,
+            };
+            match result {
+                Ok(answer) => {
+                    *self.answered_by.lock() = Some(llm.model().to_string());
+                    return Ok(answer);
+                }
+                Err(e) => {
+                    if let Some(next) = self.chain.get(i + 1) {
+                        tracing::warn!("Generation with {} failed ({}), falling back to {}", llm.model(), e, next.model());
+                    }
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for FallbackLlm {
+    async fn generate_answer(&self, question: &str, context: &str, citations: &[Citation]) -> Result<String> {
+        self.attempt(|llm| llm.generate_answer(question, context, citations)).await
+    }
+
+    async fn generate_with_learning(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<String> {
+        self.attempt(|llm| llm.generate_with_learning(question, context, citations, past_qa)).await
+    }
+
+    async fn generate_cited(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<GeneratedAnswer> {
+        self.attempt(|llm| llm.generate_cited(question, context, citations, past_qa)).await
+    }
+
+    fn answer_prompt(&self, question: &str, context: &str, citations: &[Citation], past_qa: &[(String, String)]) -> String {
+        self.chain
+            .first()
+            .map_or_else(String::new, |llm| llm.answer_prompt(question, context, citations, past_qa))
+    }
+
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        self.attempt(|llm| llm.complete(prompt)).await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        match self.chain.first() {
+            Some(llm) => llm.health_check().await,
+            None => Ok(false),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.chain.first().map_or("none", |llm| llm.name())
+    }
+
+    fn model(&self) -> &str {
+        self.chain.first().map_or("", |llm| llm.model())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubLlm {
+        model: &'static str,
+        fails: bool,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl LlmProvider for StubLlm {
+        async fn generate_answer(&self, question: &str, _context: &str, _citations: &[Citation]) -> Result<String> {
+            self.complete(question).await
+        }
+        async fn generate_with_learning(
+            &self,
+            question: &str,
+            _context: &str,
+            _citations: &[Citation],
+            _past_qa: &[(String, String)],
+        ) -> Result<String> {
+            self.complete(question).await
+        }
+        async fn complete(&self, prompt: &str) -> Result<String> {
+            tokio::time::sleep(self.delay).await;
+            if self.fails {
+                return Err(Error::Llm(format!("{} is down", self.model)));
+            }
+            Ok(format!("{}: {}", self.model, prompt))
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(!self.fails)
+        }
+        fn name(&self) -> &str {
+            "stub"
+        }
+        fn model(&self) -> &str {
+            self.model
+        }
+    }
+
+    fn stub(model: &'static str, fails: bool, delay_ms: u64) -> Arc<dyn LlmProvider> {
+        Arc::new(StubLlm { model, fails, delay: Duration::from_millis(delay_ms) })
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_on_error_and_timeout() {
+        let llm = FallbackLlm::new(
+            vec![stub("pro", true, 0), stub("flash", false, 200), stub("local", false, 0)],
+            Some(Duration::from_millis(50)),
+        );
+        assert_eq!(llm.answered_by(), "pro");
+        assert_eq!(llm.complete("hi").await.unwrap(), "local: hi");
+        assert_eq!(llm.answered_by(), "local");
+
+        let down = FallbackLlm::new(vec![stub("pro", true, 0)], None);
+        assert!(down.complete("hi").await.is_err());
+    }
+}
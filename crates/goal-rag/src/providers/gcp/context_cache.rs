@@ -0,0 +1,164 @@
+//! Gemini context caching
+//!
+//! Queries over the same documents send the same packed context again and
+//! again. `GeminiContextCache` uploads a context, with the grounding rules
+//! around it, once as a Vertex AI cached content; later questions on that
+//! context by the same model refer to it by name until it expires. Contexts
+//! are matched by hash, so any change to the retrieved chunks makes a new
+//! cache. Creating a cache is recorded as `cache` usage, priced for storage
+//! over its TTL; reads are reported by each answer's usage metadata.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use super::auth::GcpAuth;
+use crate::config::GeminiCacheConfig;
+use crate::error::{Error, Result};
+use crate::providers::UsageTracker;
+
+/// Caches are forgotten this long before Vertex deletes them, so a question
+/// never refers to one that expires while it is answered
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Cached contents of Gemini models
+pub struct GeminiContextCache {
+    auth: Arc<GcpAuth>,
+    location: String,
+    config: GeminiCacheConfig,
+    usage: Option<Arc<UsageTracker>>,
+    /// Cached content by hash of model and instruction
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+struct CacheEntry {
+    name: String,
+    expires_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct CachedContent {
+    name: String,
+    #[serde(rename = "usageMetadata", default)]
+    usage_metadata: Option<CacheUsage>,
+}
+
+#[derive(Deserialize)]
+struct CacheUsage {
+    #[serde(rename = "totalTokenCount", default)]
+    total_token_count: u64,
+}
+
+impl GeminiContextCache {
+    pub fn new(auth: Arc<GcpAuth>, location: String, config: &GeminiCacheConfig) -> Self {
+        Self {
+            auth,
+            location,
+            config: config.clone(),
+            usage: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record cache storage in the usage tracker
+    pub fn with_usage(mut self, usage: Arc<UsageTracker>) -> Self {
+        self.usage = usage.is_enabled().then_some(usage);
+        self
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.ttl_secs.max(1))
+    }
+
+    /// Name of the cached content holding `instruction` for `model`, created
+    /// if needed
+    ///
+    /// `None` when caching is disabled, the instruction is too small to cache
+    /// or the cache couldn't be created; the question is then sent with the
+    /// whole prompt.
+    pub async fn cached_content(&self, model: &str, instruction: &str) -> Option<String> {
+        // About four characters per token; only decides whether to cache
+        let tokens = instruction.len() / 4;
+        if !self.config.enabled || tokens < self.config.min_tokens {
+            return None;
+        }
+
+        let key = cache_key(model, instruction);
+        {
+            let mut entries = self.entries.lock();
+            let now = Instant::now();
+            entries.retain(|_, entry| entry.expires_at > now);
+            if let Some(entry) = entries.get(&key) {
+                return Some(entry.name.clone());
+            }
+        }
+
+        let created = match self.create(model, instruction).await {
+            Ok(created) => created,
+            Err(e) => {
+                tracing::warn!("Failed to cache Gemini context, sending it in full: {}", e);
+                return None;
+            }
+        };
+        let cached_tokens = created.usage_metadata.map_or(tokens as u64, |usage| usage.total_token_count);
+        tracing::debug!("Cached {} context tokens for {} as {}", cached_tokens, model, created.name);
+        if let Some(usage) = &self.usage {
+            usage.record_cache_storage("gemini", model, cached_tokens, self.ttl());
+        }
+
+        let mut entries = self.entries.lock();
+        if entries.len() >= self.config.max_entries.max(1) {
+            let first_expiring = entries.iter().min_by_key(|(_, entry)| entry.expires_at).map(|(key, _)| key.clone());
+            if let Some(first_expiring) = first_expiring {
+                entries.remove(&first_expiring);
+            }
+        }
+        let expires_at = Instant::now() + self.ttl().saturating_sub(EXPIRY_MARGIN);
+        entries.insert(key, CacheEntry { name: created.name.clone(), expires_at });
+        Some(created.name)
+    }
+
+    async fn create(&self, model: &str, instruction: &str) -> Result<CachedContent> {
+        let client = self.auth.authorized_client().await?;
+        let project = self.auth.project_id();
+        let url = format!(
+            "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/cachedContents",
+            self.location, project, self.location
+        );
+        let body = json!({
+            "model": format!("projects/{}/locations/{}/publishers/google/models/{}", project, self.location, model),
+            "displayName": "goal-rag context",
+            "systemInstruction": { "parts": [{ "text": instruction }] },
+            "ttl": format!("{}s", self.ttl().as_secs()),
+        });
+
+        let response = client
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Llm(format!("Gemini cache request failed: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Llm(format!("Gemini cache creation failed ({}): {}", status, body)));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| Error::Llm(format!("Failed to parse Gemini cache response: {}", e)))
+    }
+}
+
+fn cache_key(model: &str, instruction: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(instruction.as_bytes());
+    hex::encode(hasher.finalize())
+}
@@ -2,17 +2,42 @@
 //!
 //! Document AI provides high-quality text extraction with:
 //! - OCR for scanned documents
-//! - Table detection and extraction
+//! - Table detection and extraction (rendered as Markdown, see `text_with_tables`)
 //! - Form field recognition
 //! - Layout preservation
 //! - Support for large documents (up to 2000 pages)
+//!
+//! Documents are sent inline to the online `process` method, which caps the
+//! pages and size of a request. With a staging bucket configured, PDFs over
+//! those limits go through `batchProcess` instead: the PDF is staged in GCS,
+//! the long-running operation is polled until done, and the sharded output
+//! documents are read back and merged. Staged objects are removed afterwards.
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
 use super::auth::GcpAuth;
+use crate::config::DocumentAiBatchConfig;
 use crate::error::{Error, Result};
+use crate::ingestion::tables;
+
+/// Largest document sent to the online method
+const ONLINE_MAX_BYTES: usize = 20 * 1024 * 1024;
+
+/// Time allowed for an online request
+const ONLINE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// GCS staging of batch requests
+struct BatchStaging {
+    bucket: String,
+    prefix: String,
+    poll_interval: Duration,
+    timeout: Duration,
+}
 
 /// Google Document AI client for PDF processing
 pub struct DocumentAiClient {
@@ -20,6 +45,8 @@ pub struct DocumentAiClient {
     /// Full processor resource name
     /// e.g., "projects/my-project/locations/us/processors/abc123"
     processor_name: String,
+    /// Batch processing of documents over the online limits
+    batch: Option<BatchStaging>,
 }
 
 impl DocumentAiClient {
@@ -32,14 +59,29 @@ impl DocumentAiClient {
         Self {
             auth,
             processor_name,
+            batch: None,
         }
     }
 
-    /// Get the API endpoint URL for processing
-    fn endpoint(&self) -> String {
-        // Document AI API endpoint format:
-        // https://LOCATION-documentai.googleapis.com/v1/PROCESSOR_NAME:process
+    /// Process documents over the online limits in batch, staging them under
+    /// the config's prefix in `bucket`
+    pub fn with_batch(mut self, bucket: String, config: &DocumentAiBatchConfig) -> Self {
+        self.batch = config.enabled.then(|| BatchStaging {
+            bucket,
+            prefix: config.staging_prefix.clone(),
+            poll_interval: Duration::from_secs(config.poll_interval_secs.max(1)),
+            timeout: Duration::from_secs(config.timeout_secs),
+        });
+        self
+    }
 
+    /// Time `process_pdf` may take, batch processing included
+    pub fn timeout(&self) -> Duration {
+        ONLINE_TIMEOUT + self.batch.as_ref().map_or(Duration::ZERO, |batch| batch.timeout)
+    }
+
+    /// Document AI API host of the processor's location
+    fn api_base(&self) -> String {
         // Extract location from processor name
         // Format: projects/PROJECT/locations/LOCATION/processors/PROCESSOR_ID
         let location = self.processor_name
@@ -47,11 +89,14 @@ impl DocumentAiClient {
             .nth(3)
             .unwrap_or("us");
 
-        format!(
-            "https://{}-documentai.googleapis.com/v1/{}:process",
-            location,
-            self.processor_name
-        )
+        format!("https://{}-documentai.googleapis.com/v1", location)
+    }
+
+    /// Get the API endpoint URL for processing
+    fn endpoint(&self) -> String {
+        // Document AI API endpoint format:
+        // https://LOCATION-documentai.googleapis.com/v1/PROCESSOR_NAME:process
+        format!("{}/{}:process", self.api_base(), self.processor_name)
     }
 
     /// Process a PDF document and extract text
@@ -63,6 +108,15 @@ impl DocumentAiClient {
     /// # Returns
     /// Extracted text content with page information
     pub async fn process_pdf(&self, pdf_data: &[u8], filename: &str) -> Result<DocumentAiResult> {
+        if let Some(batch) = self.batch.as_ref().filter(|_| pdf_data.len() > ONLINE_MAX_BYTES) {
+            tracing::info!(
+                "[{}] {} MB is over the online request limit, using batch processing",
+                filename,
+                pdf_data.len() / (1024 * 1024)
+            );
+            return self.process_batch(batch, pdf_data, filename).await;
+        }
+
         let client = self.auth.authorized_client().await?;
 
         // Encode PDF as base64
@@ -84,6 +138,7 @@ impl DocumentAiClient {
 
         let response = client
             .post(self.endpoint())
+            .timeout(ONLINE_TIMEOUT)
             .json(&request)
             .send()
             .await
@@ -92,6 +147,10 @@ impl DocumentAiClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
+            if let Some(batch) = self.batch.as_ref().filter(|_| exceeds_online_limits(status, &body)) {
+                tracing::info!("[{}] Document AI online limits exceeded, using batch processing", filename);
+                return self.process_batch(batch, pdf_data, filename).await;
+            }
             return Err(Error::Internal(format!(
                 "Document AI processing failed ({}): {}",
                 status, body
@@ -103,66 +162,104 @@ impl DocumentAiClient {
             .await
             .map_err(|e| Error::Internal(format!("Failed to parse Document AI response: {}", e)))?;
 
-        // Extract text from response
-        let document = process_response.document;
-        let full_text = document.text.unwrap_or_default();
+        document_result(vec![process_response.document], filename)
+    }
+
+    /// Process a PDF with a batch operation, staging it and its output in GCS
+    async fn process_batch(&self, batch: &BatchStaging, pdf_data: &[u8], filename: &str) -> Result<DocumentAiResult> {
+        let staging = format!("{}{}/", batch.prefix, Uuid::new_v4());
+        let input = format!("{}input.pdf", staging);
+        upload_object(&self.auth.authorized_client().await?, &batch.bucket, &input, pdf_data.to_vec()).await?;
 
-        if full_text.trim().is_empty() {
-            return Err(Error::Internal(
-                "Document AI returned empty text - document may be empty or unreadable".to_string()
-            ));
+        let result = self.run_batch(batch, &staging, &input, filename).await;
+        // Staged input and output are removed whatever the outcome
+        if let Err(e) = remove_objects(&self.auth.authorized_client().await?, &batch.bucket, &staging).await {
+            tracing::warn!("[{}] Failed to remove Document AI staging objects: {}", filename, e);
         }
+        result
+    }
+
+    async fn run_batch(&self, batch: &BatchStaging, staging: &str, input: &str, filename: &str) -> Result<DocumentAiResult> {
+        let output = format!("{}output/", staging);
+        let request = json!({
+            "inputDocuments": {
+                "gcsDocuments": {
+                    "documents": [{
+                        "gcsUri": format!("gs://{}/{}", batch.bucket, input),
+                        "mimeType": "application/pdf",
+                    }]
+                }
+            },
+            "documentOutputConfig": {
+                "gcsOutputConfig": { "gcsUri": format!("gs://{}/{}", batch.bucket, output) }
+            },
+            "skipHumanReview": true,
+        });
 
-        // Build page-level content
-        let mut pages = Vec::new();
-        let mut total_pages = 0u32;
-
-        if let Some(doc_pages) = document.pages {
-            total_pages = doc_pages.len() as u32;
-
-            for (i, page) in doc_pages.iter().enumerate() {
-                let page_number = page.page_number.unwrap_or((i + 1) as i32) as u32;
-
-                // Extract text from page layout
-                let page_text = if let Some(ref layout) = page.layout {
-                    extract_text_from_layout(layout, &full_text)
-                } else {
-                    // Fallback: try to get text from blocks/paragraphs
-                    extract_text_from_page_elements(page, &full_text)
-                };
-
-                pages.push(DocumentAiPage {
-                    page_number,
-                    content: page_text,
-                    width: page.dimension.as_ref().map(|d| d.width).unwrap_or(0.0),
-                    height: page.dimension.as_ref().map(|d| d.height).unwrap_or(0.0),
-                });
+        let client = self.auth.authorized_client().await?;
+        let response = client
+            .post(format!("{}/{}:batchProcess", self.api_base(), self.processor_name))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Document AI batch request failed: {}", e)))?;
+        let operation: Value = json_response(response, "Document AI batch request").await?;
+        let name = operation["name"]
+            .as_str()
+            .ok_or_else(|| Error::Internal("Document AI batch request returned no operation".to_string()))?
+            .to_string();
+        tracing::info!("[{}] Document AI batch operation started: {}", filename, name);
+
+        // Poll with a fresh client each time, the operation can outlive a token
+        let deadline = Instant::now() + batch.timeout;
+        loop {
+            if Instant::now() >= deadline {
+                let client = self.auth.authorized_client().await?;
+                let _ = client.post(format!("{}/{}:cancel", self.api_base(), name)).send().await;
+                return Err(Error::Internal(format!(
+                    "Document AI batch operation {} did not finish within {}s",
+                    name,
+                    batch.timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(batch.poll_interval).await;
+
+            let client = self.auth.authorized_client().await?;
+            let response = client
+                .get(format!("{}/{}", self.api_base(), name))
+                .send()
+                .await
+                .map_err(|e| Error::Internal(format!("Document AI operation request failed: {}", e)))?;
+            let operation: Value = json_response(response, "Document AI operation request").await?;
+            if let Some(error) = operation.get("error") {
+                return Err(Error::Internal(format!("Document AI batch processing failed: {}", error)));
+            }
+            if operation["done"].as_bool().unwrap_or(false) {
+                break;
             }
         }
 
-        // If no pages were extracted, use full text as single page
-        if pages.is_empty() && !full_text.is_empty() {
-            pages.push(DocumentAiPage {
-                page_number: 1,
-                content: full_text.clone(),
-                width: 0.0,
-                height: 0.0,
-            });
-            total_pages = 1;
+        // Output is sharded into documents of consecutive pages
+        let client = self.auth.authorized_client().await?;
+        let mut shards: Vec<String> = list_objects(&client, &batch.bucket, &output)
+            .await?
+            .into_iter()
+            .filter(|name| name.ends_with(".json"))
+            .collect();
+        shards.sort_by_key(|name| shard_index(name));
+        let mut documents = Vec::with_capacity(shards.len());
+        for shard in &shards {
+            let response = client
+                .get(object_url(&batch.bucket, shard))
+                .query(&[("alt", "media")])
+                .send()
+                .await
+                .map_err(|e| Error::Internal(format!("Failed to download Document AI output: {}", e)))?;
+            documents.push(json_response::<Document>(response, "Document AI output download").await?);
         }
+        tracing::info!("[{}] Document AI batch output read ({} shards)", filename, documents.len());
 
-        tracing::info!(
-            "[{}] Document AI extracted {} chars from {} pages",
-            filename,
-            full_text.len(),
-            total_pages
-        );
-
-        Ok(DocumentAiResult {
-            text: full_text,
-            pages,
-            total_pages,
-        })
+        document_result(documents, filename)
     }
 
     /// Check if Document AI is properly configured and accessible
@@ -177,6 +274,194 @@ impl DocumentAiClient {
     }
 }
 
+/// Whether an online request failed for the page or size limits of the
+/// online method (batch processing has higher ones)
+fn exceeds_online_limits(status: reqwest::StatusCode, body: &str) -> bool {
+    if status == reqwest::StatusCode::PAYLOAD_TOO_LARGE {
+        return true;
+    }
+    let body = body.to_lowercase();
+    status == reqwest::StatusCode::BAD_REQUEST
+        && (body.contains("page_limit_exceeded")
+            || (body.contains("page") && (body.contains("limit") || body.contains("exceed")))
+            || body.contains("payload size"))
+}
+
+/// Position of an output shard (`input-0.json`, `input-1.json`, ...)
+fn shard_index(name: &str) -> (usize, String) {
+    let stem = name.trim_end_matches(".json");
+    let index = stem.rsplit('-').next().and_then(|n| n.parse().ok()).unwrap_or(usize::MAX);
+    (index, name.to_string())
+}
+
+/// Parse a successful JSON response, or fail with its body
+async fn json_response<T: serde::de::DeserializeOwned>(response: reqwest::Response, action: &str) -> Result<T> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Internal(format!("{} failed ({}): {}", action, status, body)));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to parse {} response: {}", action, e)))
+}
+
+/// JSON API URL of a GCS object
+fn object_url(bucket: &str, object: &str) -> String {
+    format!("https://storage.googleapis.com/storage/v1/b/{}/o/{}", bucket, encode_object_name(object))
+}
+
+/// Percent-encode an object name for a URL path (`/` included)
+fn encode_object_name(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+async fn upload_object(client: &reqwest::Client, bucket: &str, object: &str, data: Vec<u8>) -> Result<()> {
+    let response = client
+        .post(format!("https://storage.googleapis.com/upload/storage/v1/b/{}/o", bucket))
+        .query(&[("uploadType", "media"), ("name", object)])
+        .header(reqwest::header::CONTENT_TYPE, "application/pdf")
+        .body(data)
+        .send()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to stage document in GCS: {}", e)))?;
+    json_response::<Value>(response, "Document AI staging upload").await.map(|_| ())
+}
+
+/// Names of the objects under a prefix
+async fn list_objects(client: &reqwest::Client, bucket: &str, prefix: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = client
+            .get(format!("https://storage.googleapis.com/storage/v1/b/{}/o", bucket))
+            .query(&[("prefix", prefix), ("fields", "items/name,nextPageToken")]);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token)]);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to list GCS objects: {}", e)))?;
+        let listing: Value = json_response(response, "GCS object listing").await?;
+        names.extend(
+            listing["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|item| item["name"].as_str().map(str::to_string)),
+        );
+        match listing["nextPageToken"].as_str() {
+            Some(token) => page_token = Some(token.to_string()),
+            None => return Ok(names),
+        }
+    }
+}
+
+async fn remove_objects(client: &reqwest::Client, bucket: &str, prefix: &str) -> Result<()> {
+    for name in list_objects(client, bucket, prefix).await? {
+        client
+            .delete(object_url(bucket, &name))
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to delete GCS object: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Text and pages of processed documents, the shards of one document in order
+fn document_result(documents: Vec<Document>, filename: &str) -> Result<DocumentAiResult> {
+    let mut full_text = String::new();
+    let mut pages = Vec::new();
+    let mut total_pages = 0u32;
+
+    for document in documents {
+        let (text, shard_pages) = document_pages(document, total_pages);
+        total_pages += shard_pages.len() as u32;
+        if !full_text.is_empty() && !text.is_empty() {
+            full_text.push('\n');
+        }
+        full_text.push_str(&text);
+        pages.extend(shard_pages);
+    }
+
+    if full_text.trim().is_empty() {
+        return Err(Error::Internal(
+            "Document AI returned empty text - document may be empty or unreadable".to_string()
+        ));
+    }
+
+    // If no pages were extracted, use full text as single page
+    if pages.is_empty() {
+        pages.push(DocumentAiPage {
+            page_number: 1,
+            content: full_text.clone(),
+            width: 0.0,
+            height: 0.0,
+            tables: Vec::new(),
+        });
+        total_pages = 1;
+    }
+
+    tracing::info!(
+        "[{}] Document AI extracted {} chars from {} pages",
+        filename,
+        full_text.len(),
+        total_pages
+    );
+
+    Ok(DocumentAiResult {
+        text: full_text,
+        pages,
+        total_pages,
+    })
+}
+
+/// Text and page-level content of a document, its pages numbered after
+/// `preceding_pages` when they don't carry their number
+fn document_pages(document: Document, preceding_pages: u32) -> (String, Vec<DocumentAiPage>) {
+    let full_text = document.text.unwrap_or_default();
+    let mut pages = Vec::new();
+
+    for (i, page) in document.pages.iter().flatten().enumerate() {
+        let page_number = page
+            .page_number
+            .map_or(preceding_pages + i as u32 + 1, |number| number as u32);
+
+        // Extract text from page layout
+        let page_text = if let Some(ref layout) = page.layout {
+            extract_text_from_layout(layout, &full_text)
+        } else {
+            // Fallback: try to get text from blocks/paragraphs
+            extract_text_from_page_elements(page, &full_text)
+        };
+
+        let page_tables = page
+            .tables
+            .iter()
+            .flatten()
+            .map(|table| extract_table_rows(table, &full_text))
+            .filter(|rows| rows.len() >= 2)
+            .collect();
+
+        pages.push(DocumentAiPage {
+            page_number,
+            content: page_text,
+            width: page.dimension.as_ref().map(|d| d.width).unwrap_or(0.0),
+            height: page.dimension.as_ref().map(|d| d.height).unwrap_or(0.0),
+            tables: page_tables,
+        });
+    }
+
+    (full_text, pages)
+}
+
 /// Extract text from a layout element using text anchors
 fn extract_text_from_layout(layout: &Layout, full_text: &str) -> String {
     if let Some(ref text_anchor) = layout.text_anchor {
@@ -204,6 +489,31 @@ fn extract_text_from_anchor(anchor: &TextAnchor, full_text: &str) -> String {
     text
 }
 
+/// Extract a table's cell text, header rows first
+fn extract_table_rows(table: &Table, full_text: &str) -> Vec<Vec<String>> {
+    table
+        .header_rows
+        .iter()
+        .chain(table.body_rows.iter())
+        .flatten()
+        .map(|row| {
+            row.cells
+                .iter()
+                .flatten()
+                .map(|cell| {
+                    cell.layout
+                        .as_ref()
+                        .map(|layout| extract_text_from_layout(layout, full_text))
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect()
+        })
+        .collect()
+}
+
 /// Extract text from page elements (blocks, paragraphs, lines)
 fn extract_text_from_page_elements(page: &Page, full_text: &str) -> String {
     let mut text = String::new();
@@ -244,6 +554,21 @@ pub struct DocumentAiResult {
     pub total_pages: u32,
 }
 
+impl DocumentAiResult {
+    /// Full text followed by the detected tables as Markdown
+    ///
+    /// Table cells are flattened in `text`; the Markdown copies let the
+    /// ingestion pipeline store them as intact table chunks.
+    pub fn text_with_tables(&self) -> String {
+        let mut text = self.text.clone();
+        for table in self.pages.iter().flat_map(|p| &p.tables) {
+            text.push_str("\n\n");
+            text.push_str(&tables::render_markdown(table));
+        }
+        text
+    }
+}
+
 /// Page content from Document AI
 #[derive(Debug, Clone)]
 pub struct DocumentAiPage {
@@ -255,6 +580,8 @@ pub struct DocumentAiPage {
     pub width: f64,
     /// Page height in points
     pub height: f64,
+    /// Tables on the page as rows of cell text, header rows first
+    pub tables: Vec<Vec<Vec<String>>>,
 }
 
 // ============================================================================
@@ -295,6 +622,7 @@ struct Page {
     layout: Option<Layout>,
     blocks: Option<Vec<Block>>,
     paragraphs: Option<Vec<Paragraph>>,
+    tables: Option<Vec<Table>>,
 }
 
 #[derive(Deserialize)]
@@ -333,6 +661,24 @@ struct Paragraph {
     layout: Option<Layout>,
 }
 
+#[derive(Deserialize)]
+struct Table {
+    #[serde(rename = "headerRows")]
+    header_rows: Option<Vec<TableRow>>,
+    #[serde(rename = "bodyRows")]
+    body_rows: Option<Vec<TableRow>>,
+}
+
+#[derive(Deserialize)]
+struct TableRow {
+    cells: Option<Vec<TableCell>>,
+}
+
+#[derive(Deserialize)]
+struct TableCell {
+    layout: Option<Layout>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -356,6 +702,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_batch_fallback_and_shards() {
+        let bad_request = reqwest::StatusCode::BAD_REQUEST;
+        assert!(exceeds_online_limits(bad_request, "Document pages exceed the limit: 15 got 120"));
+        assert!(exceeds_online_limits(reqwest::StatusCode::PAYLOAD_TOO_LARGE, ""));
+        assert!(!exceeds_online_limits(bad_request, "Unsupported input file format."));
+
+        let mut shards = vec!["out/1/input-10.json", "out/1/input-2.json", "out/1/input-0.json"];
+        shards.sort_by_key(|name| shard_index(name));
+        assert_eq!(shards, ["out/1/input-0.json", "out/1/input-2.json", "out/1/input-10.json"]);
+        assert_eq!(encode_object_name("docai/a b.pdf"), "docai%2Fa%20b.pdf");
+
+        let shard = |text: &str| Document {
+            text: Some(text.to_string()),
+            pages: Some(vec![Page {
+                page_number: None,
+                dimension: None,
+                layout: Some(Layout {
+                    text_anchor: Some(TextAnchor {
+                        text_segments: Some(vec![TextSegment { start_index: None, end_index: None }]),
+                    }),
+                }),
+                blocks: None,
+                paragraphs: None,
+                tables: None,
+            }]),
+        };
+        let result = document_result(vec![shard("first"), shard("second")], "big.pdf").unwrap();
+        assert_eq!(result.text, "first\nsecond");
+        assert_eq!(result.total_pages, 2);
+        assert_eq!((result.pages[1].page_number, result.pages[1].content.as_str()), (2, "second"));
+    }
+
     #[test]
     fn test_eu_location() {
         let processor = "projects/my-project/locations/eu/processors/xyz789";
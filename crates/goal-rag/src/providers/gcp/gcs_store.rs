@@ -3,6 +3,7 @@
 //! Stores raw documents in GCS for scalable, durable storage.
 
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -15,7 +16,7 @@ use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, Up
 
 use super::auth::GcpAuth;
 use crate::error::{Error, Result};
-use crate::providers::document_store::{DocumentStoreProvider, StoredDocumentInfo};
+use crate::providers::document_store::{DocumentStoreProvider, DocumentStream, StoredDocumentInfo};
 
 /// Google Cloud Storage document store
 pub struct GcsDocumentStore {
@@ -27,6 +28,8 @@ pub struct GcsDocumentStore {
     originals_prefix: String,
     /// Prefix for extracted plain text
     plaintext_prefix: String,
+    /// Prefix for page thumbnails
+    thumbnails_prefix: String,
 }
 
 impl GcsDocumentStore {
@@ -57,6 +60,7 @@ impl GcsDocumentStore {
             bucket,
             originals_prefix: originals_prefix.unwrap_or_else(|| "originals/".to_string()),
             plaintext_prefix: plaintext_prefix.unwrap_or_else(|| "plaintext/".to_string()),
+            thumbnails_prefix: "thumbnails/".to_string(),
         })
     }
 
@@ -70,6 +74,11 @@ impl GcsDocumentStore {
         format!("{}{}.txt", self.plaintext_prefix, doc_id)
     }
 
+    /// Get the full object path for a page thumbnail
+    fn thumbnail_object_path(&self, doc_id: &Uuid, page: u32) -> String {
+        format!("{}{}/{}.png", self.thumbnails_prefix, doc_id, page)
+    }
+
     /// Get GCS URI for an original document
     fn gcs_uri(&self, doc_id: &Uuid, extension: &str) -> String {
         format!("gs://{}/{}", self.bucket, self.object_path(doc_id, extension))
@@ -197,6 +206,23 @@ impl GcsDocumentStore {
         }
     }
 
+    /// Stored metadata of an original document
+    async fn metadata(&self, doc_id: &Uuid) -> Option<DocumentMetadata> {
+        let meta_data = self
+            .client
+            .download_object(
+                &GetObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: self.object_path(doc_id, "meta.json"),
+                    ..Default::default()
+                },
+                &Range::default(),
+            )
+            .await
+            .ok()?;
+        serde_json::from_slice(&meta_data).ok()
+    }
+
     /// Check if plain text exists for a document
     async fn plaintext_exists(&self, doc_id: &Uuid) -> bool {
         let object_path = self.plaintext_object_path(doc_id);
@@ -354,6 +380,63 @@ impl DocumentStoreProvider for GcsDocumentStore {
             .map_err(|e| Error::Internal(format!("Failed to download from GCS: {}", e)))
     }
 
+    async fn document_size(&self, doc_id: &Uuid) -> Result<Option<u64>> {
+        Ok(self.metadata(doc_id).await.map(|metadata| metadata.size))
+    }
+
+    async fn get_document_range(&self, doc_id: &Uuid, start: u64, end: u64) -> Result<Vec<u8>> {
+        let metadata = self
+            .metadata(doc_id)
+            .await
+            .ok_or_else(|| Error::Internal(format!("No stored original for {}", doc_id)))?;
+        let extension = std::path::Path::new(&metadata.filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("bin");
+
+        self.client
+            .download_object(
+                &GetObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: self.object_path(doc_id, extension),
+                    ..Default::default()
+                },
+                &Range(Some(start), Some(end)),
+            )
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to download from GCS: {}", e)))
+    }
+
+    async fn stream_document(&self, doc_id: &Uuid, range: Option<(u64, u64)>) -> Result<DocumentStream> {
+        use futures::{StreamExt, TryStreamExt};
+
+        let metadata = self
+            .metadata(doc_id)
+            .await
+            .ok_or_else(|| Error::Internal(format!("No stored original for {}", doc_id)))?;
+        let extension = std::path::Path::new(&metadata.filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("bin");
+        let range = range.map_or_else(Range::default, |(start, end)| Range(Some(start), Some(end)));
+
+        let stream = self
+            .client
+            .download_streamed_object(
+                &GetObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: self.object_path(doc_id, extension),
+                    ..Default::default()
+                },
+                &range,
+            )
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to download from GCS: {}", e)))?;
+        Ok(stream
+            .map_err(|e| Error::Internal(format!("Failed to download from GCS: {}", e)))
+            .boxed())
+    }
+
     async fn exists(&self, doc_id: &Uuid) -> Result<bool> {
         let meta_path = self.object_path(doc_id, "meta.json");
 
@@ -513,6 +596,81 @@ impl DocumentStoreProvider for GcsDocumentStore {
         }
     }
 
+    async fn store_plain_text(&self, doc_id: &Uuid, filename: &str, text: &str) -> Result<String> {
+        GcsDocumentStore::store_plain_text(self, doc_id, filename, text).await
+    }
+
+    async fn get_plain_text(&self, doc_id: &Uuid) -> Result<Option<String>> {
+        GcsDocumentStore::get_plain_text(self, doc_id).await
+    }
+
+    async fn delete_plain_text(&self, doc_id: &Uuid) -> Result<()> {
+        GcsDocumentStore::delete_plain_text(self, doc_id).await
+    }
+
+    async fn store_thumbnail(&self, doc_id: &Uuid, page: u32, png: &[u8]) -> Result<String> {
+        let object_path = self.thumbnail_object_path(doc_id, page);
+        let mut media = Media::new(object_path.clone());
+        media.content_type = "image/png".into();
+
+        self.client
+            .upload_object(
+                &UploadObjectRequest {
+                    bucket: self.bucket.clone(),
+                    ..Default::default()
+                },
+                png.to_vec(),
+                &UploadType::Simple(media),
+            )
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to upload thumbnail to GCS: {}", e)))?;
+
+        Ok(format!("gs://{}/{}", self.bucket, object_path))
+    }
+
+    async fn get_thumbnail(&self, doc_id: &Uuid, page: u32) -> Result<Option<Vec<u8>>> {
+        match self
+            .client
+            .download_object(
+                &GetObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: self.thumbnail_object_path(doc_id, page),
+                    ..Default::default()
+                },
+                &Range::default(),
+            )
+            .await
+        {
+            Ok(data) => Ok(Some(data)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn delete_thumbnails(&self, doc_id: &Uuid) -> Result<()> {
+        let list_request = ListObjectsRequest {
+            bucket: self.bucket.clone(),
+            prefix: Some(format!("{}{}/", self.thumbnails_prefix, doc_id)),
+            ..Default::default()
+        };
+        let objects = self
+            .client
+            .list_objects(&list_request)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to list thumbnails: {}", e)))?;
+
+        for item in objects.items.unwrap_or_default() {
+            let _ = self
+                .client
+                .delete_object(&DeleteObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: item.name,
+                    ..Default::default()
+                })
+                .await;
+        }
+        Ok(())
+    }
+
     async fn health_check(&self) -> Result<bool> {
         // Try to list objects (with limit 1) to check bucket access
         let list_request = ListObjectsRequest {
@@ -675,6 +833,63 @@ impl GcsDocumentStore {
         }))
     }
 
+    /// Generation numbers of the metadata and plaintext objects, by name
+    ///
+    /// A generation changes whenever an object is rewritten, so comparing
+    /// them with an earlier listing finds the changed documents without
+    /// downloading anything.
+    pub async fn list_generations(&self) -> Result<HashMap<String, i64>> {
+        let mut generations = HashMap::new();
+        for (prefix, suffix) in [(&self.originals_prefix, ".meta.json"), (&self.plaintext_prefix, ".txt")] {
+            let mut page_token: Option<String> = None;
+            loop {
+                let list_request = ListObjectsRequest {
+                    bucket: self.bucket.clone(),
+                    prefix: Some(prefix.clone()),
+                    page_token: page_token.take(),
+                    ..Default::default()
+                };
+                let objects = self
+                    .client
+                    .list_objects(&list_request)
+                    .await
+                    .map_err(|e| Error::Internal(format!("Failed to list GCS objects: {}", e)))?;
+                generations.extend(
+                    objects
+                        .items
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|item| item.name.ends_with(suffix))
+                        .map(|item| (item.name, item.generation)),
+                );
+                match objects.next_page_token {
+                    Some(token) => page_token = Some(token),
+                    None => break,
+                }
+            }
+        }
+        Ok(generations)
+    }
+
+    /// Document a metadata or plaintext object belongs to
+    pub fn document_of(&self, object: &str) -> Option<Uuid> {
+        let id = object
+            .strip_prefix(self.originals_prefix.as_str())
+            .and_then(|name| name.strip_suffix(".meta.json"))
+            .or_else(|| {
+                object
+                    .strip_prefix(self.plaintext_prefix.as_str())
+                    .and_then(|name| name.strip_suffix(".txt"))
+            })?;
+        Uuid::parse_str(id).ok()
+    }
+
+    /// File info of a document from its metadata, `None` when the metadata
+    /// is missing or invalid
+    pub async fn file_info(&self, doc_id: &Uuid) -> Result<Option<GcsFileInfo>> {
+        self.process_meta_file(&self.object_path(doc_id, "meta.json")).await
+    }
+
     /// Get count of files in bucket (quick check)
     pub async fn get_file_counts(&self) -> Result<(usize, usize)> {
         // Count originals
@@ -3,11 +3,14 @@
 //! High-performance LLM for RAG answer generation with citations.
 
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use std::sync::Arc;
 
 use super::auth::GcpAuth;
+use super::context_cache::GeminiContextCache;
 use crate::error::{Error, Result};
-use crate::providers::llm::LlmProvider;
+use crate::providers::llm::{GeneratedAnswer, LlmProvider};
+use crate::providers::vision::VisionProvider;
 use crate::types::response::Citation;
 
 /// Gemini client via Vertex AI
@@ -15,6 +18,8 @@ pub struct GeminiClient {
     auth: Arc<GcpAuth>,
     model: String,
     location: String,
+    json_mode: bool,
+    cache: Option<Arc<GeminiContextCache>>,
 }
 
 impl GeminiClient {
@@ -29,9 +34,25 @@ impl GeminiClient {
             auth,
             model: model.unwrap_or_else(|| "gemini-2.5-pro".to_string()),
             location,
+            json_mode: true,
+            cache: None,
         }
     }
 
+    /// Send the context of answers through a context cache, so repeated
+    /// questions on it don't resend it
+    pub fn with_context_cache(mut self, cache: Arc<GeminiContextCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Whether answers are requested as JSON with an explicit citations
+    /// array (default: true); without it citations are parsed from the text
+    pub fn with_json_mode(mut self, enabled: bool) -> Self {
+        self.json_mode = enabled;
+        self
+    }
+
     /// Get the API endpoint URL
     fn endpoint(&self) -> String {
         format!(
@@ -44,7 +65,18 @@ impl GeminiClient {
     }
 
     /// Build the RAG prompt with strict grounding rules
-    fn build_prompt(&self, question: &str, context: &str, citations: &[Citation]) -> String {
+    ///
+    /// In JSON mode sources are cited by number and listed in the response's
+    /// `citations` array.
+    fn build_prompt(&self, question: &str, context: &str, citations: &[Citation], json: bool) -> String {
+        let mut prompt = self.context_prompt(context, citations, json);
+        prompt.push_str(&question_prompt(question, json));
+        prompt
+    }
+
+    /// Grounding rules, context and sources: the part of the prompt that
+    /// doesn't depend on the question
+    fn context_prompt(&self, context: &str, citations: &[Citation], json: bool) -> String {
         let mut prompt = String::new();
 
         // System instruction for grounded responses
@@ -55,7 +87,11 @@ impl GeminiClient {
         prompt.push_str("2. If information is not in context, say: \"This information is not available in the provided documents.\"\n");
         prompt.push_str("3. NEVER use external knowledge, general knowledge, or training data\n");
         prompt.push_str("4. NEVER make inferences or assumptions beyond what is explicitly stated\n");
-        prompt.push_str("5. Every claim MUST have a citation: [Source: filename, Page X]\n");
+        if json {
+            prompt.push_str("5. Every claim MUST cite its source by number: [1], [2]\n");
+        } else {
+            prompt.push_str("5. Every claim MUST have a citation: [Source: filename, Page X]\n");
+        }
         prompt.push_str("6. Stay close to the source text - do not paraphrase in ways that change meaning\n\n");
 
         prompt.push_str("## Context from Documents\n\n");
@@ -82,18 +118,121 @@ impl GeminiClient {
             prompt.push('\n');
         }
 
-        prompt.push_str("## Question\n\n");
-        prompt.push_str(question);
-        prompt.push_str("\n\n");
-        prompt.push_str("## Grounded Answer (cite sources inline with [Source: filename, Page X])\n\n");
-
         prompt
     }
+
+    /// Conversation answering a question, past Q&A examples as earlier turns
+    ///
+    /// The context is left out when `cached` (it is in the cached content).
+    fn answer_contents(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+        json: bool,
+        cached: bool,
+    ) -> Vec<Content> {
+        let mut contents = Vec::new();
+        for (q, a) in past_qa.iter().take(3) {
+            // Limit to 3 examples
+            contents.push(user_content(q.clone()));
+            contents.push(Content {
+                role: "model".to_string(),
+                parts: vec![Part::Text { text: a.clone() }],
+            });
+        }
+        if cached {
+            contents.push(user_content(question_prompt(question, json)));
+        } else {
+            contents.push(user_content(self.build_prompt(question, context, citations, json)));
+        }
+        contents
+    }
+
+    /// Send a generateContent request and return the first text part
+    async fn generate_content(&self, contents: Vec<Content>, action: &str) -> Result<String> {
+        self.generate(contents, None, None, action).await.map(|(text, _)| text)
+    }
+
+    /// Send a generateContent request, on top of the `cached_content` and
+    /// constraining the response to the JSON `schema` if given, and return
+    /// the first text part with the input tokens read from the cache
+    async fn generate(
+        &self,
+        contents: Vec<Content>,
+        cached_content: Option<String>,
+        schema: Option<serde_json::Value>,
+        action: &str,
+    ) -> Result<(String, u64)> {
+        let client = self.auth.authorized_client().await?;
+
+        let request = GenerateRequest {
+            contents,
+            cached_content,
+            generation_config: GenerationConfig {
+                temperature: 0.1, // Very low for grounded, factual responses
+                max_output_tokens: 2048,
+                top_p: 0.85, // Tighter for more deterministic output
+                response_mime_type: schema.as_ref().map(|_| "application/json".to_string()),
+                response_schema: schema,
+            },
+        };
+
+        let response = client
+            .post(self.endpoint())
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| Error::Llm(format!("Gemini request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("Gemini {} failed ({}): {}", action, status, body);
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Err(Error::ProviderRateLimited { provider: "gemini".to_string(), message });
+            }
+            return Err(Error::Llm(message));
+        }
+
+        let gen_response: GenerateResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::Llm(format!("Failed to parse Gemini response: {}", e)))?;
+
+        let cached_tokens = gen_response.usage_metadata.map_or(0, |usage| usage.cached_content_token_count);
+        gen_response
+            .candidates
+            .into_iter()
+            .next()
+            .and_then(|c| c.content.parts.into_iter().next())
+            .map(|p| (p.text, cached_tokens))
+            .ok_or_else(|| Error::Llm("No text in Gemini response".to_string()))
+    }
+}
+
+/// Question and answer instructions ending the prompt
+fn question_prompt(question: &str, json: bool) -> String {
+    let mut prompt = String::new();
+    prompt.push_str("## Question\n\n");
+    prompt.push_str(question);
+    prompt.push_str("\n\n");
+    if json {
+        prompt.push_str("## Response\n\n");
+        prompt.push_str("Respond with a JSON object: \"answer\" is the grounded answer citing sources inline with [n], ");
+        prompt.push_str("\"citations\" lists every source the answer uses, by its number, with the quote supporting it.\n");
+    } else {
+        prompt.push_str("## Grounded Answer (cite sources inline with [Source: filename, Page X])\n\n");
+    }
+    prompt
 }
 
 #[derive(serde::Serialize)]
 struct GenerateRequest {
     contents: Vec<Content>,
+    #[serde(rename = "cachedContent", skip_serializing_if = "Option::is_none")]
+    cached_content: Option<String>,
     #[serde(rename = "generationConfig")]
     generation_config: GenerationConfig,
 }
@@ -105,8 +244,22 @@ struct Content {
 }
 
 #[derive(serde::Serialize)]
-struct Part {
-    text: String,
+#[serde(untagged)]
+enum Part {
+    Text {
+        text: String,
+    },
+    InlineData {
+        #[serde(rename = "inlineData")]
+        inline_data: Blob,
+    },
+}
+
+#[derive(serde::Serialize)]
+struct Blob {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    data: String,
 }
 
 #[derive(serde::Serialize)]
@@ -116,11 +269,79 @@ struct GenerationConfig {
     max_output_tokens: u32,
     #[serde(rename = "topP")]
     top_p: f32,
+    #[serde(rename = "responseMimeType", skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(rename = "responseSchema", skip_serializing_if = "Option::is_none")]
+    response_schema: Option<serde_json::Value>,
+}
+
+/// JSON-mode answer
+#[derive(serde::Deserialize)]
+struct CitedAnswer {
+    answer: String,
+    #[serde(default)]
+    citations: Vec<CitedSource>,
+}
+
+#[derive(serde::Deserialize)]
+struct CitedSource {
+    source: usize,
+}
+
+/// Schema of `CitedAnswer`, in the OpenAPI subset Vertex AI accepts
+fn cited_answer_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "answer": { "type": "STRING" },
+            "citations": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "source": { "type": "INTEGER" },
+                        "quote": { "type": "STRING" }
+                    },
+                    "required": ["source"]
+                }
+            }
+        },
+        "required": ["answer", "citations"],
+        "propertyOrdering": ["answer", "citations"]
+    })
+}
+
+/// Answer text and cited source numbers of a JSON-mode response; a response
+/// that isn't the requested JSON is kept as free text
+fn parse_cited_answer(response: String, cached_tokens: u64) -> GeneratedAnswer {
+    match serde_json::from_str::<CitedAnswer>(&response) {
+        Ok(cited) => {
+            let mut sources: Vec<usize> = Vec::new();
+            for source in cited.citations.into_iter().map(|c| c.source) {
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+            GeneratedAnswer { text: cited.answer, cited_sources: Some(sources), cached_tokens }
+        }
+        Err(e) => {
+            tracing::warn!("Gemini JSON answer did not parse ({}), reading citations from the text", e);
+            GeneratedAnswer { cached_tokens, ..response.into() }
+        }
+    }
 }
 
 #[derive(serde::Deserialize)]
 struct GenerateResponse {
     candidates: Vec<Candidate>,
+    #[serde(rename = "usageMetadata", default)]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(serde::Deserialize)]
+struct UsageMetadata {
+    #[serde(rename = "cachedContentTokenCount", default)]
+    cached_content_token_count: u64,
 }
 
 #[derive(serde::Deserialize)]
@@ -138,6 +359,13 @@ struct ResponsePart {
     text: String,
 }
 
+fn user_content(text: String) -> Content {
+    Content {
+        role: "user".to_string(),
+        parts: vec![Part::Text { text }],
+    }
+}
+
 #[async_trait]
 impl LlmProvider for GeminiClient {
     async fn generate_answer(
@@ -146,49 +374,8 @@ impl LlmProvider for GeminiClient {
         context: &str,
         citations: &[Citation],
     ) -> Result<String> {
-        let client = self.auth.authorized_client().await?;
-        let prompt = self.build_prompt(question, context, citations);
-
-        let request = GenerateRequest {
-            contents: vec![Content {
-                role: "user".to_string(),
-                parts: vec![Part { text: prompt }],
-            }],
-            generation_config: GenerationConfig {
-                temperature: 0.1, // Very low for grounded, factual responses
-                max_output_tokens: 2048,
-                top_p: 0.85, // Tighter for more deterministic output
-            },
-        };
-
-        let response = client
-            .post(self.endpoint())
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::Llm(format!("Gemini request failed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::Llm(format!(
-                "Gemini generation failed ({}): {}",
-                status, body
-            )));
-        }
-
-        let gen_response: GenerateResponse = response
-            .json()
-            .await
-            .map_err(|e| Error::Llm(format!("Failed to parse Gemini response: {}", e)))?;
-
-        gen_response
-            .candidates
-            .into_iter()
-            .next()
-            .and_then(|c| c.content.parts.into_iter().next())
-            .map(|p| p.text)
-            .ok_or_else(|| Error::Llm("No text in Gemini response".to_string()))
+        let prompt = self.build_prompt(question, context, citations, false);
+        self.generate_content(vec![user_content(prompt)], "generation").await
     }
 
     async fn generate_with_learning(
@@ -198,68 +385,48 @@ impl LlmProvider for GeminiClient {
         citations: &[Citation],
         past_qa: &[(String, String)],
     ) -> Result<String> {
-        let client = self.auth.authorized_client().await?;
+        // Multi-turn conversation with learning examples
+        let contents = self.answer_contents(question, context, citations, past_qa, false, false);
+        self.generate_content(contents, "generation with learning").await
+    }
 
-        // Build multi-turn conversation with learning examples
-        let mut contents = Vec::new();
+    async fn generate_cited(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<GeneratedAnswer> {
+        let json = self.json_mode;
+        let cached_content = match &self.cache {
+            Some(cache) => cache.cached_content(&self.model, &self.context_prompt(context, citations, json)).await,
+            None => None,
+        };
+        let contents = self.answer_contents(question, context, citations, past_qa, json, cached_content.is_some());
+        if !json {
+            let (text, cached_tokens) = self.generate(contents, cached_content, None, "generation").await?;
+            return Ok(GeneratedAnswer { cached_tokens, ..text.into() });
+        }
+        let (response, cached_tokens) =
+            self.generate(contents, cached_content, Some(cited_answer_schema()), "JSON generation").await?;
+        Ok(parse_cited_answer(response, cached_tokens))
+    }
 
-        // Add past Q&A as examples
+    fn answer_prompt(&self, question: &str, context: &str, citations: &[Citation], past_qa: &[(String, String)]) -> String {
+        // Examples are sent as earlier turns of the conversation
+        let mut prompt = String::new();
         for (q, a) in past_qa.iter().take(3) {
-            // Limit to 3 examples
-            contents.push(Content {
-                role: "user".to_string(),
-                parts: vec![Part { text: q.clone() }],
-            });
-            contents.push(Content {
-                role: "model".to_string(),
-                parts: vec![Part { text: a.clone() }],
-            });
+            prompt.push_str(&format!("[user]\n{}\n\n[model]\n{}\n\n", q, a));
         }
-
-        // Add current question
-        let prompt = self.build_prompt(question, context, citations);
-        contents.push(Content {
-            role: "user".to_string(),
-            parts: vec![Part { text: prompt }],
-        });
-
-        let request = GenerateRequest {
-            contents,
-            generation_config: GenerationConfig {
-                temperature: 0.1, // Very low for grounded, factual responses
-                max_output_tokens: 2048,
-                top_p: 0.85, // Tighter for more deterministic output
-            },
-        };
-
-        let response = client
-            .post(self.endpoint())
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::Llm(format!("Gemini request failed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::Llm(format!(
-                "Gemini generation with learning failed ({}): {}",
-                status, body
-            )));
+        if !prompt.is_empty() {
+            prompt.push_str("[user]\n");
         }
+        prompt.push_str(&self.build_prompt(question, context, citations, self.json_mode));
+        prompt
+    }
 
-        let gen_response: GenerateResponse = response
-            .json()
-            .await
-            .map_err(|e| Error::Llm(format!("Failed to parse Gemini response: {}", e)))?;
-
-        gen_response
-            .candidates
-            .into_iter()
-            .next()
-            .and_then(|c| c.content.parts.into_iter().next())
-            .map(|p| p.text)
-            .ok_or_else(|| Error::Llm("No text in Gemini response".to_string()))
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        self.generate_content(vec![user_content(prompt.to_string())], "completion").await
     }
 
     async fn health_check(&self) -> Result<bool> {
@@ -274,3 +441,46 @@ impl LlmProvider for GeminiClient {
         &self.model
     }
 }
+
+#[async_trait]
+impl VisionProvider for GeminiClient {
+    async fn describe_image(&self, image: &[u8], mime_type: &str, prompt: &str) -> Result<String> {
+        let content = Content {
+            role: "user".to_string(),
+            parts: vec![
+                Part::InlineData {
+                    inline_data: Blob {
+                        mime_type: mime_type.to_string(),
+                        data: BASE64.encode(image),
+                    },
+                },
+                Part::Text { text: prompt.to_string() },
+            ],
+        };
+        self.generate_content(vec![content], "image captioning").await
+    }
+
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cited_answer() {
+        let response = r#"{"answer": "Refunds take 14 days [2].", "citations": [{"source": 2, "quote": "14 days"}, {"source": 2}, {"source": 1}]}"#;
+        let answer = parse_cited_answer(response.to_string(), 0);
+        assert_eq!(answer.text, "Refunds take 14 days [2].");
+        assert_eq!(answer.cited_sources, Some(vec![2, 1]));
+
+        let answer = parse_cited_answer("Refunds take 14 days [Source: policy.pdf]".to_string(), 4096);
+        assert_eq!((answer.cited_sources, answer.cached_tokens), (None, 4096));
+    }
+}
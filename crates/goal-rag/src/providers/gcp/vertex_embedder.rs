@@ -38,6 +38,15 @@ fn jitter_ms() -> u64 {
     (nanos % 1000) as u64
 }
 
+/// Error for a 429 (rate limited) or 503 (overloaded) response
+fn rate_limited(status: reqwest::StatusCode, message: String) -> Error {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        Error::ProviderRateLimited { provider: "vertex-ai".to_string(), message }
+    } else {
+        Error::Embedding(message)
+    }
+}
+
 /// Vertex AI embedding provider
 pub struct VertexAiEmbedder {
     auth: Arc<GcpAuth>,
@@ -136,7 +145,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    last_error = Some(format!("Vertex AI request failed: {}", e));
+                    last_error = Some(Error::Embedding(format!("Vertex AI request failed: {}", e)));
                     continue;
                 }
             };
@@ -146,7 +155,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
             // Retry on 429 (Too Many Requests) or 503 (Service Unavailable)
             if status.as_u16() == 429 || status.as_u16() == 503 {
                 let body = response.text().await.unwrap_or_default();
-                last_error = Some(format!("Vertex AI rate limited ({}): {}", status, body));
+                last_error = Some(rate_limited(status, format!("Vertex AI rate limited ({}): {}", status, body)));
                 continue;
             }
 
@@ -171,7 +180,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
                 .ok_or_else(|| Error::Embedding("No embedding in response".to_string()));
         }
 
-        Err(Error::Embedding(last_error.unwrap_or_else(|| "Max retries exceeded".to_string())))
+        Err(last_error.unwrap_or_else(|| Error::Embedding("Max retries exceeded".to_string())))
     }
 
     async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
@@ -220,7 +229,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
                 {
                     Ok(r) => r,
                     Err(e) => {
-                        last_error = Some(format!("Vertex AI batch request failed: {}", e));
+                        last_error = Some(Error::Embedding(format!("Vertex AI batch request failed: {}", e)));
                         continue;
                     }
                 };
@@ -230,7 +239,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
                 // Retry on 429 (Too Many Requests) or 503 (Service Unavailable)
                 if status.as_u16() == 429 || status.as_u16() == 503 {
                     let body = response.text().await.unwrap_or_default();
-                    last_error = Some(format!("Vertex AI batch rate limited ({}): {}", status, body));
+                    last_error = Some(rate_limited(status, format!("Vertex AI batch rate limited ({}): {}", status, body)));
                     continue;
                 }
 
@@ -260,7 +269,7 @@ impl EmbeddingProvider for VertexAiEmbedder {
 
             // If we exhausted retries, return error
             if let Some(error) = last_error {
-                return Err(Error::Embedding(error));
+                return Err(error);
             }
 
             // Delay between batches to avoid rate limiting (500ms)
@@ -0,0 +1,272 @@
+//! Vertex AI Vector Search index lifecycle
+//!
+//! Creates the streaming index and the index endpoint, deploys the index and
+//! changes the replicas serving it, so a new environment can be set up
+//! without gcloud. Each call starts a long-running operation and returns it;
+//! creating an index takes minutes and deploying one can take half an hour,
+//! so callers poll `operation` rather than wait. The resource names an
+//! operation reports go into `[gcp]` (`vector_search_index`,
+//! `vector_search_endpoint`, `vector_search_public_domain`).
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use super::auth::GcpAuth;
+use crate::error::{Error, Result};
+
+/// Index to create
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateVectorIndexRequest {
+    pub display_name: String,
+    /// Vector dimensions (default: the configured embedding dimensions)
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+    /// Neighbors found before reordering (default: 150)
+    #[serde(default)]
+    pub approximate_neighbors_count: Option<u32>,
+    /// `SHARD_SIZE_SMALL`, `SHARD_SIZE_MEDIUM` or `SHARD_SIZE_LARGE` (default: medium)
+    #[serde(default)]
+    pub shard_size: Option<String>,
+}
+
+/// Index endpoint to create
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateIndexEndpointRequest {
+    pub display_name: String,
+    /// Serve queries on a public domain (default: true)
+    #[serde(default = "default_public_endpoint")]
+    pub public_endpoint: bool,
+}
+
+fn default_public_endpoint() -> bool { true }
+
+/// Deployment of an index on an endpoint; unset names come from `[gcp]`
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct DeployVectorIndexRequest {
+    #[serde(default)]
+    pub index: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub deployed_index_id: Option<String>,
+    /// Machine serving the index (default: e2-standard-16)
+    #[serde(default)]
+    pub machine_type: Option<String>,
+    #[serde(default)]
+    pub min_replicas: Option<u32>,
+    #[serde(default)]
+    pub max_replicas: Option<u32>,
+}
+
+/// New replica bounds of the deployed index
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ResizeVectorIndexRequest {
+    pub min_replicas: u32,
+    #[serde(default)]
+    pub max_replicas: Option<u32>,
+}
+
+/// A long-running Vertex AI operation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VertexOperation {
+    /// Operation resource name, for `GET /api/admin/vector-index/operation`
+    pub name: String,
+    #[serde(default)]
+    pub done: bool,
+    /// Failure, when done and failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub error: Option<Value>,
+    /// Created or changed resource, when done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
+}
+
+/// State of the configured index and its deployment
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VectorIndexStatus {
+    pub index: String,
+    pub index_update_method: Option<String>,
+    pub vectors_count: Option<u64>,
+    pub endpoint: String,
+    pub public_domain: Option<String>,
+    pub deployed_index_id: String,
+    /// Whether the endpoint serves the deployed index id
+    pub deployed: bool,
+    pub min_replicas: Option<u64>,
+    pub max_replicas: Option<u64>,
+    /// Time up to which streamed updates are served
+    pub index_sync_time: Option<String>,
+}
+
+/// Vector Search index and endpoint management
+pub struct VertexIndexManager {
+    auth: Arc<GcpAuth>,
+    location: String,
+}
+
+impl VertexIndexManager {
+    pub fn new(auth: Arc<GcpAuth>, location: String) -> Self {
+        Self { auth, location }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("https://{}-aiplatform.googleapis.com/v1/{}", self.location, path)
+    }
+
+    fn parent(&self) -> String {
+        format!("projects/{}/locations/{}", self.auth.project_id(), self.location)
+    }
+
+    /// Create a stream-updated index (cosine distance, as search expects)
+    pub async fn create_index(&self, request: &CreateVectorIndexRequest, dimensions: usize) -> Result<VertexOperation> {
+        let body = json!({
+            "displayName": request.display_name,
+            "indexUpdateMethod": "STREAM_UPDATE",
+            "metadata": {
+                "config": {
+                    "dimensions": request.dimensions.unwrap_or(dimensions),
+                    "approximateNeighborsCount": request.approximate_neighbors_count.unwrap_or(150),
+                    "distanceMeasureType": "COSINE_DISTANCE",
+                    "shardSize": request.shard_size.as_deref().unwrap_or("SHARD_SIZE_MEDIUM"),
+                    "algorithmConfig": { "treeAhConfig": {} }
+                }
+            }
+        });
+        self.call(reqwest::Method::POST, &format!("{}/indexes", self.parent()), Some(body)).await
+    }
+
+    /// Create an index endpoint
+    pub async fn create_endpoint(&self, request: &CreateIndexEndpointRequest) -> Result<VertexOperation> {
+        let body = json!({
+            "displayName": request.display_name,
+            "publicEndpointEnabled": request.public_endpoint,
+        });
+        self.call(reqwest::Method::POST, &format!("{}/indexEndpoints", self.parent()), Some(body)).await
+    }
+
+    /// Deploy an index on an endpoint
+    pub async fn deploy_index(
+        &self,
+        endpoint: &str,
+        index: &str,
+        deployed_index_id: &str,
+        request: &DeployVectorIndexRequest,
+    ) -> Result<VertexOperation> {
+        let min_replicas = request.min_replicas.unwrap_or(1);
+        let body = json!({
+            "deployedIndex": {
+                "id": deployed_index_id,
+                "index": index,
+                "dedicatedResources": {
+                    "machineSpec": { "machineType": request.machine_type.as_deref().unwrap_or("e2-standard-16") },
+                    "minReplicaCount": min_replicas,
+                    "maxReplicaCount": request.max_replicas.unwrap_or(min_replicas),
+                }
+            }
+        });
+        self.call(reqwest::Method::POST, &format!("{}:deployIndex", endpoint), Some(body)).await
+    }
+
+    /// Change the replicas serving a deployed index
+    pub async fn resize(
+        &self,
+        endpoint: &str,
+        deployed_index_id: &str,
+        request: &ResizeVectorIndexRequest,
+    ) -> Result<VertexOperation> {
+        let deployed = self.deployed_index(endpoint, deployed_index_id).await?.ok_or_else(|| {
+            Error::NotFound(format!("Index {} is not deployed on {}", deployed_index_id, endpoint))
+        })?;
+        let mut resources = deployed.get("dedicatedResources").cloned().unwrap_or_else(|| json!({}));
+        resources["minReplicaCount"] = json!(request.min_replicas);
+        resources["maxReplicaCount"] = json!(request.max_replicas.unwrap_or(request.min_replicas));
+        let body = json!({
+            "id": deployed_index_id,
+            "index": deployed.get("index"),
+            "dedicatedResources": resources,
+        });
+        self.call(reqwest::Method::POST, &format!("{}:mutateDeployedIndex", endpoint), Some(body)).await
+    }
+
+    /// Current state of a long-running operation
+    pub async fn operation(&self, name: &str) -> Result<VertexOperation> {
+        self.call(reqwest::Method::GET, name, None).await
+    }
+
+    /// State of an index and its deployment on an endpoint
+    pub async fn status(&self, index: &str, endpoint: &str, deployed_index_id: &str) -> Result<VectorIndexStatus> {
+        let index_info = self.get(index).await?;
+        let endpoint_info = self.get(endpoint).await?;
+        let deployed = deployed_in(&endpoint_info, deployed_index_id);
+        let resources = deployed.and_then(|d| d.get("dedicatedResources"));
+        let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+
+        Ok(VectorIndexStatus {
+            index: index.to_string(),
+            index_update_method: text(index_info.get("indexUpdateMethod")),
+            vectors_count: index_info
+                .pointer("/indexStats/vectorsCount")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok())),
+            endpoint: endpoint.to_string(),
+            public_domain: text(endpoint_info.get("publicEndpointDomainName")),
+            deployed_index_id: deployed_index_id.to_string(),
+            deployed: deployed.is_some(),
+            min_replicas: resources.and_then(|r| r.get("minReplicaCount")).and_then(Value::as_u64),
+            max_replicas: resources.and_then(|r| r.get("maxReplicaCount")).and_then(Value::as_u64),
+            index_sync_time: text(deployed.and_then(|d| d.get("indexSyncTime"))),
+        })
+    }
+
+    async fn deployed_index(&self, endpoint: &str, deployed_index_id: &str) -> Result<Option<Value>> {
+        let info = self.get(endpoint).await?;
+        Ok(deployed_in(&info, deployed_index_id).cloned())
+    }
+
+    async fn get(&self, name: &str) -> Result<Value> {
+        self.request(reqwest::Method::GET, name, None).await
+    }
+
+    async fn call(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<VertexOperation> {
+        let response = self.request(method, path, body).await?;
+        serde_json::from_value(response)
+            .map_err(|e| Error::VectorDb(format!("Failed to parse Vertex operation: {}", e)))
+    }
+
+    async fn request(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let client = self.auth.authorized_client().await?;
+        let mut request = client.request(method, self.url(path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::VectorDb(format!("Vertex request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::VectorDb(format!("Vertex request {} failed ({}): {}", path, status, body)));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| Error::VectorDb(format!("Failed to parse Vertex response: {}", e)))
+    }
+}
+
+/// The deployed index of an endpoint with the given id
+fn deployed_in<'a>(endpoint: &'a Value, deployed_index_id: &str) -> Option<&'a Value> {
+    endpoint
+        .get("deployedIndexes")?
+        .as_array()?
+        .iter()
+        .find(|d| d.get("id").and_then(Value::as_str) == Some(deployed_index_id))
+}
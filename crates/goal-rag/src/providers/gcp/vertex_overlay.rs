@@ -0,0 +1,201 @@
+//! Local overlay over Vertex AI Vector Search's eventual consistency
+//!
+//! Streamed upserts and removals take a while to show in query results.
+//! Until Vertex confirms them, recently upserted chunks are searched locally
+//! (brute force over their embeddings) and merged into the results, and
+//! removed or replaced datapoints are filtered out of them. Entries are
+//! confirmed with `readIndexDatapoints` and dropped once Vertex serves them
+//! (an upsert once it serves the new vector), or after the consistency
+//! window at the latest.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::providers::vector_store::VectorSearchResult;
+use crate::retrieval::similarity::CosineQuery;
+use crate::types::Chunk;
+
+/// Most upserted chunks held; beyond it new upserts wait for Vertex
+const MAX_PENDING_UPSERTS: usize = 50_000;
+
+/// How often pending entries are checked with Vertex
+pub(super) const CONFIRM_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Upserts and removals Vertex may not serve yet
+pub(super) struct ConsistencyOverlay {
+    window: Duration,
+    state: Mutex<OverlayState>,
+}
+
+struct OverlayState {
+    upserted: HashMap<Uuid, (Instant, Chunk)>,
+    removed: HashMap<Uuid, Instant>,
+    last_confirmed: Instant,
+    confirming: bool,
+}
+
+impl ConsistencyOverlay {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::new(OverlayState {
+                upserted: HashMap::new(),
+                removed: HashMap::new(),
+                last_confirmed: Instant::now(),
+                confirming: false,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    /// Number of upserts and removals not yet confirmed
+    pub fn pending(&self) -> (usize, usize) {
+        let state = self.state.lock();
+        (state.upserted.len(), state.removed.len())
+    }
+
+    pub fn record_upserts(&self, chunks: &[Chunk]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        for chunk in chunks {
+            state.removed.remove(&chunk.id);
+            if state.upserted.len() >= MAX_PENDING_UPSERTS && !state.upserted.contains_key(&chunk.id) {
+                continue;
+            }
+            state.upserted.insert(chunk.id, (now, chunk.clone()));
+        }
+    }
+
+    pub fn record_removals(&self, ids: &[Uuid]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        for id in ids {
+            state.upserted.remove(id);
+            state.removed.insert(*id, now);
+        }
+    }
+
+    /// Merge pending upserts into Vertex results and drop removed datapoints
+    pub fn apply(
+        &self,
+        query: &[f32],
+        top_k: usize,
+        document_filter: Option<&[Uuid]>,
+        mut results: Vec<VectorSearchResult>,
+    ) -> Vec<VectorSearchResult> {
+        let mut state = self.state.lock();
+        state.expire(self.window);
+        if state.upserted.is_empty() && state.removed.is_empty() {
+            return results;
+        }
+
+        // Upserted chunks replace whatever Vertex still has for them
+        let query = CosineQuery::new(query);
+        results.retain(|r| !state.removed.contains_key(&r.chunk.id) && !state.upserted.contains_key(&r.chunk.id));
+        results.extend(
+            state
+                .upserted
+                .values()
+                .filter(|(_, chunk)| document_filter.map_or(true, |ids| ids.contains(&chunk.document_id)))
+                .map(|(_, chunk)| VectorSearchResult::new(chunk.clone(), query.score(&chunk.embedding))),
+        );
+        results.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(top_k);
+        results
+    }
+
+    /// Pending ids to check with Vertex, if a check is due and none is running
+    pub fn start_confirmation(&self) -> Option<Vec<Uuid>> {
+        let mut state = self.state.lock();
+        if state.confirming || state.last_confirmed.elapsed() < CONFIRM_INTERVAL {
+            return None;
+        }
+        state.expire(self.window);
+        if state.upserted.is_empty() && state.removed.is_empty() {
+            return None;
+        }
+        state.confirming = true;
+        Some(state.upserted.keys().chain(state.removed.keys()).copied().collect())
+    }
+
+    /// Drop the entries Vertex now reflects, given the vectors it serves for
+    /// the checked ids (`None` when the check failed)
+    pub fn finish_confirmation(&self, checked: &[Uuid], served: Option<&HashMap<Uuid, Vec<f32>>>) {
+        let mut state = self.state.lock();
+        state.confirming = false;
+        state.last_confirmed = Instant::now();
+        let Some(served) = served else {
+            return;
+        };
+        for id in checked {
+            let vector = served.get(id);
+            let upsert_served = state
+                .upserted
+                .get(id)
+                .is_some_and(|(_, chunk)| vector.is_some_and(|v| same_vector(v, &chunk.embedding)));
+            if upsert_served {
+                state.upserted.remove(id);
+            }
+            if vector.is_none() {
+                state.removed.remove(id);
+            }
+        }
+    }
+}
+
+impl OverlayState {
+    fn expire(&mut self, window: Duration) {
+        self.upserted.retain(|_, (at, _)| at.elapsed() < window);
+        self.removed.retain(|_, at| at.elapsed() < window);
+    }
+}
+
+/// Vectors equal up to the precision Vertex stores
+fn same_vector(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() <= 1e-4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChunkSource;
+
+    fn chunk(embedding: Vec<f32>) -> Chunk {
+        let mut chunk = Chunk::new(Uuid::new_v4(), "text".to_string(), ChunkSource::text("a.txt".to_string()), 0, 4, 0);
+        chunk.embedding = embedding;
+        chunk
+    }
+
+    #[test]
+    fn test_overlay_merges_until_confirmed() {
+        let overlay = ConsistencyOverlay::new(Duration::from_secs(60));
+        let stale = chunk(vec![1.0, 0.0]);
+        let fresh = chunk(vec![0.0, 1.0]);
+        overlay.record_upserts(std::slice::from_ref(&fresh));
+        overlay.record_removals(&[stale.id]);
+
+        let vertex = vec![VectorSearchResult::new(stale.clone(), 0.9)];
+        let results = overlay.apply(&[0.0, 1.0], 10, None, vertex);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, fresh.id);
+
+        // Vertex serves the upsert and no longer the removed datapoint
+        overlay.state.lock().last_confirmed -= CONFIRM_INTERVAL;
+        let checked = overlay.start_confirmation().unwrap();
+        let served = HashMap::from([(fresh.id, fresh.embedding.clone())]);
+        overlay.finish_confirmation(&checked, Some(&served));
+        assert_eq!(overlay.pending(), (0, 0));
+    }
+}
@@ -1,19 +1,35 @@
 //! Vertex AI Vector Search provider
 //!
 //! Provides managed HNSW vector similarity search with SQLite FTS for text search.
+//!
+//! Upserts are streamed in batches of 100, several batches at a time. Until
+//! Vertex serves streamed changes, a local overlay answers for them (see
+//! `vertex_overlay`).
 
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 use super::auth::GcpAuth;
+use super::vertex_overlay::ConsistencyOverlay;
 use crate::error::{Error, Result};
 use crate::providers::vector_store::{VectorSearchResult, VectorStoreProvider};
-use crate::storage::{ChunkContentRecord, FileRegistryDb};
+use crate::retrieval::string_search;
+use crate::storage::{ChunkContentRecord, ChunkContentRow, FileRegistryDb};
 use crate::types::Chunk;
+use crate::types::document::FILTER_METADATA_KEYS;
+use crate::types::query::StringSearchMode;
 use crate::types::response::StringSearchResult;
 
+/// Datapoints per upsert or remove request
+const MUTATION_BATCH: usize = 100;
+
+/// Default time streamed changes are served from the local overlay
+const DEFAULT_CONSISTENCY_WINDOW: Duration = Duration::from_secs(300);
+
 /// Vertex AI Vector Search provider
 pub struct VertexVectorSearch {
     auth: Arc<GcpAuth>,
@@ -29,6 +45,10 @@ pub struct VertexVectorSearch {
     data_endpoint: Option<String>,
     /// SQLite database for chunk content (FTS) and document-chunk mapping
     database: Arc<FileRegistryDb>,
+    /// Changes Vertex may not serve yet
+    overlay: Arc<ConsistencyOverlay>,
+    /// Upsert batches in flight at once
+    upsert_concurrency: usize,
 }
 
 impl VertexVectorSearch {
@@ -60,6 +80,8 @@ impl VertexVectorSearch {
             deployed_index_id,
             data_endpoint: None,
             database,
+            overlay: Arc::new(ConsistencyOverlay::new(DEFAULT_CONSISTENCY_WINDOW)),
+            upsert_concurrency: 4,
         }
     }
 
@@ -69,38 +91,181 @@ impl VertexVectorSearch {
         self
     }
 
+    /// Serve streamed changes locally for up to `window` (zero disables the overlay)
+    pub fn with_consistency_window(mut self, window: Duration) -> Self {
+        self.overlay = Arc::new(ConsistencyOverlay::new(window));
+        self
+    }
+
+    /// Send up to `concurrency` upsert batches at once
+    pub fn with_upsert_concurrency(mut self, concurrency: usize) -> Self {
+        self.upsert_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Upserts and removals not yet confirmed as served by Vertex
+    pub fn pending_changes(&self) -> (usize, usize) {
+        self.overlay.pending()
+    }
+
     /// Store multiple chunk contents in SQLite
     fn store_chunks_content(&self, chunks: &[Chunk]) -> Result<()> {
-        let records: Vec<ChunkContentRecord> = chunks.iter().map(|chunk| {
-            ChunkContentRecord {
-                id: chunk.id,
-                document_id: chunk.document_id,
-                chunk_index: chunk.chunk_index,
-                content: chunk.content.clone(),
-                filename: chunk.source.filename.clone(),
-                file_type: chunk.source.file_type.clone(),
-                page_number: chunk.source.page_number,
-                section_title: chunk.source.section_title.clone(),
-                char_start: chunk.char_start,
-                char_end: chunk.char_end,
+        self.database.insert_chunk_rows(chunks.iter().map(ChunkContentRow::from))
+    }
+
+    /// Store chunk contents, then upsert their datapoints
+    async fn insert_batch(&self, chunks: &[Chunk]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        // Store chunk content in SQLite for FTS and document mapping
+        self.store_chunks_content(chunks)?;
+        self.upsert_datapoints(chunks).await
+    }
+
+    /// SQLite record of a chunk's content
+    fn content_record(chunk: &Chunk) -> ChunkContentRecord {
+        ChunkContentRecord {
+            id: chunk.id,
+            document_id: chunk.document_id,
+            chunk_index: chunk.chunk_index,
+            content: chunk.content.clone(),
+            filename: chunk.source.filename.clone(),
+            file_type: chunk.source.file_type.clone(),
+            page_number: chunk.source.page_number,
+            section_title: chunk.source.section_title.clone(),
+            char_start: chunk.char_start,
+            char_end: chunk.char_end,
+            collection: chunk.collection().map(|c| c.to_string()),
+            parent_id: chunk.parent_id,
+            kind: chunk.source.kind,
+            keywords: chunk.keywords(),
+            entities: chunk.entities(),
+            embedding_model: chunk.embedding_model().map(str::to_string),
+        }
+    }
+
+    /// Upsert the vectors of chunks into the index
+    async fn upsert_datapoints(&self, chunks: &[Chunk]) -> Result<()> {
+        let client = self.auth.authorized_client().await?;
+
+        // Use data endpoint if available, otherwise use Index resource for upserts
+        let endpoint = self.data_endpoint.clone().unwrap_or_else(|| {
+            format!(
+                "https://{}-aiplatform.googleapis.com/v1/{}:upsertDatapoints",
+                self.location, self.index
+            )
+        });
+
+        // Stream the batches, a few requests at a time
+        stream::iter(chunks.chunks(MUTATION_BATCH))
+            .map(|batch| {
+                let request = UpsertRequest {
+                    datapoints: batch.iter().map(Self::chunk_to_datapoint).collect(),
+                };
+                let (client, endpoint) = (&client, &endpoint);
+                async move {
+                    let response = client
+                        .post(endpoint)
+                        .json(&request)
+                        .send()
+                        .await
+                        .map_err(|e| Error::VectorDb(format!("Vertex upsert failed: {}", e)))?;
+
+                    if !response.status().is_success() {
+                        let status = response.status();
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(Error::VectorDb(format!(
+                            "Vertex upsert failed ({}): {}",
+                            status, body
+                        )));
+                    }
+                    self.overlay.record_upserts(batch);
+                    Ok(())
+                }
+            })
+            .buffer_unordered(self.upsert_concurrency)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove datapoints from the index
+    async fn remove_datapoints(&self, ids: &[Uuid]) -> Result<()> {
+        let client = self.auth.authorized_client().await?;
+        let endpoint = format!(
+            "https://{}-aiplatform.googleapis.com/v1/{}:removeDatapoints",
+            self.location, self.index
+        );
+
+        for batch in ids.chunks(MUTATION_BATCH) {
+            let request = RemoveRequest {
+                datapoint_ids: batch.iter().map(Uuid::to_string).collect(),
+            };
+            let response = client
+                .post(&endpoint)
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| Error::VectorDb(format!("Vertex remove failed: {}", e)))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(Error::VectorDb(format!("Vertex remove failed ({}): {}", status, body)));
             }
-        }).collect();
-        self.database.insert_chunks_content(&records)
+            self.overlay.record_removals(batch);
+        }
+
+        Ok(())
+    }
+
+    /// Check pending overlay entries with Vertex in the background, when due
+    fn confirm_overlay(&self) {
+        let Some(ids) = self.overlay.start_confirmation() else {
+            return;
+        };
+        let auth = Arc::clone(&self.auth);
+        let overlay = Arc::clone(&self.overlay);
+        let url = self.endpoint_url("readIndexDatapoints");
+        let deployed_index_id = self.deployed_index_id.clone();
+
+        tokio::spawn(async move {
+            let mut served = HashMap::new();
+            for batch in ids.chunks(MUTATION_BATCH) {
+                match read_datapoints(&auth, &url, &deployed_index_id, batch).await {
+                    Ok(vectors) => served.extend(vectors),
+                    Err(e) => {
+                        tracing::debug!("Could not confirm Vertex datapoints: {}", e);
+                        overlay.finish_confirmation(&ids, None);
+                        return;
+                    }
+                }
+            }
+            overlay.finish_confirmation(&ids, Some(&served));
+        });
     }
 
     /// Get search endpoint URL
     fn search_endpoint(&self) -> String {
+        self.endpoint_url("findNeighbors")
+    }
+
+    /// URL of a query method of the index endpoint
+    fn endpoint_url(&self, method: &str) -> String {
         if let Some(ref domain) = self.public_domain {
             // Use public endpoint domain for queries
             format!(
-                "https://{}/v1/{}:findNeighbors",
-                domain, self.index_endpoint
+                "https://{}/v1/{}:{}",
+                domain, self.index_endpoint, method
             )
         } else {
             // Fall back to standard API endpoint
             format!(
-                "https://{}-aiplatform.googleapis.com/v1/{}:findNeighbors",
-                self.location, self.index_endpoint
+                "https://{}-aiplatform.googleapis.com/v1/{}:{}",
+                self.location, self.index_endpoint, method
             )
         }
     }
@@ -115,7 +280,7 @@ impl VertexVectorSearch {
         }];
 
         // Store chunk metadata in crowding tag (up to 1KB)
-        let metadata = serde_json::json!({
+        let mut metadata = serde_json::json!({
             "chunk_id": chunk.id.to_string(),
             "document_id": chunk.document_id.to_string(),
             "filename": chunk.source.filename,
@@ -127,6 +292,11 @@ impl VertexVectorSearch {
             "section_title": chunk.source.section_title,
             "file_type": chunk.source.file_type,
         });
+        for key in FILTER_METADATA_KEYS {
+            if let Some(value) = chunk.metadata.get(key) {
+                metadata[key] = value.clone();
+            }
+        }
 
         DataPoint {
             datapoint_id: chunk.id.to_string(),
@@ -222,62 +392,81 @@ struct UpsertRequest {
 }
 
 #[derive(serde::Serialize)]
-#[allow(dead_code)]
 struct RemoveRequest {
     datapoint_ids: Vec<String>,
 }
 
-#[async_trait]
-impl VectorStoreProvider for VertexVectorSearch {
-    async fn insert_chunk(&self, chunk: &Chunk) -> Result<()> {
-        self.insert_chunks(std::slice::from_ref(chunk)).await
-    }
-
-    async fn insert_chunks(&self, chunks: &[Chunk]) -> Result<()> {
-        if chunks.is_empty() {
-            return Ok(());
-        }
-
-        // Store chunk content in SQLite for FTS and document mapping
-        self.store_chunks_content(chunks)?;
+#[derive(serde::Serialize)]
+struct ReadDatapointsRequest<'a> {
+    deployed_index_id: &'a str,
+    ids: Vec<String>,
+}
 
-        let client = self.auth.authorized_client().await?;
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadDatapointsResponse {
+    #[serde(default)]
+    datapoints: Vec<ServedDatapoint>,
+}
 
-        // Use data endpoint if available, otherwise use Index resource for upserts
-        let endpoint = self.data_endpoint.clone().unwrap_or_else(|| {
-            format!(
-                "https://{}-aiplatform.googleapis.com/v1/{}:upsertDatapoints",
-                self.location, self.index
-            )
-        });
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServedDatapoint {
+    datapoint_id: String,
+    #[serde(default)]
+    feature_vector: Vec<f32>,
+}
 
-        // Convert chunks to datapoints
-        let datapoints: Vec<DataPoint> = chunks.iter().map(Self::chunk_to_datapoint).collect();
+/// Vectors the deployed index serves for the given ids
+async fn read_datapoints(
+    auth: &GcpAuth,
+    url: &str,
+    deployed_index_id: &str,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<f32>>> {
+    let request = ReadDatapointsRequest {
+        deployed_index_id,
+        ids: ids.iter().map(Uuid::to_string).collect(),
+    };
+    let response = auth
+        .authorized_client()
+        .await?
+        .post(url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| Error::VectorDb(format!("Vertex read failed: {}", e)))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::VectorDb(format!("Vertex read failed ({}): {}", status, body)));
+    }
 
-        // Batch upserts (max 100 per request)
-        for batch in datapoints.chunks(100) {
-            let request = UpsertRequest {
-                datapoints: batch.to_vec(),
-            };
+    let response: ReadDatapointsResponse = response
+        .json()
+        .await
+        .map_err(|e| Error::VectorDb(format!("Failed to parse Vertex response: {}", e)))?;
+    Ok(response
+        .datapoints
+        .into_iter()
+        .filter_map(|d| Uuid::parse_str(&d.datapoint_id).ok().map(|id| (id, d.feature_vector)))
+        .collect())
+}
 
-            let response = client
-                .post(&endpoint)
-                .json(&request)
-                .send()
-                .await
-                .map_err(|e| Error::VectorDb(format!("Vertex upsert failed: {}", e)))?;
+#[async_trait]
+impl VectorStoreProvider for VertexVectorSearch {
+    async fn insert_chunk(&self, chunk: &Chunk) -> Result<()> {
+        self.insert_batch(std::slice::from_ref(chunk)).await
+    }
 
-            if !response.status().is_success() {
-                let status = response.status();
-                let body = response.text().await.unwrap_or_default();
-                return Err(Error::VectorDb(format!(
-                    "Vertex upsert failed ({}): {}",
-                    status, body
-                )));
-            }
-        }
+    async fn insert_chunks(&self, chunks: Arc<[Chunk]>) -> Result<()> {
+        self.insert_batch(&chunks).await
+    }
 
-        Ok(())
+    async fn update_chunk(&self, chunk: &Chunk) -> Result<()> {
+        // Datapoints are replaced by id
+        self.database.replace_chunk_content(&Self::content_record(chunk))?;
+        self.upsert_datapoints(std::slice::from_ref(chunk)).await
     }
 
     async fn search(
@@ -367,102 +556,42 @@ impl VectorStoreProvider for VertexVectorSearch {
                     self.create_minimal_chunk(chunk_id)
                 };
 
-                results.push(VectorSearchResult { chunk, similarity });
+                results.push(VectorSearchResult::new(chunk, similarity));
             }
         }
 
-        Ok(results)
+        if !self.overlay.is_enabled() {
+            return Ok(results);
+        }
+        self.confirm_overlay();
+        Ok(self.overlay.apply(query_embedding, top_k, document_filter, results))
     }
 
     async fn string_search(
         &self,
         query: &str,
         limit: usize,
+        collection: Option<&str>,
+        mode: StringSearchMode,
     ) -> Result<Vec<StringSearchResult>> {
         // Use SQLite FTS for string search
-        let results = self.database.string_search_chunks(query, limit)?;
-
-        // Convert to StringSearchResult format
-        let search_results: Vec<StringSearchResult> = results.into_iter().map(|r| {
-            // Find match positions
-            let query_lower = query.to_lowercase();
-            let content_lower = r.content.to_lowercase();
-            let mut positions = Vec::new();
-            let mut search_start = 0;
-            while let Some(pos) = content_lower[search_start..].find(&query_lower) {
-                positions.push(search_start + pos);
-                search_start = search_start + pos + 1;
-                if search_start >= content_lower.len() {
-                    break;
-                }
-            }
-
-            // Create highlighted snippet
-            let highlighted = self.highlight_matches(&r.content, query);
-
-            // Create preview
-            let preview = if let Some(&first_pos) = positions.first() {
-                let start = first_pos.saturating_sub(50);
-                let end = (first_pos + query.len() + 50).min(r.content.len());
-                let mut preview = r.content[start..end].to_string();
-                if start > 0 {
-                    preview = format!("...{}", preview);
-                }
-                if end < r.content.len() {
-                    preview = format!("{}...", preview);
-                }
-                preview
-            } else {
-                r.content.chars().take(100).collect()
-            };
-
-            StringSearchResult {
-                chunk_id: r.chunk_id,
-                document_id: r.document_id,
-                filename: r.filename,
-                file_type: r.file_type,
-                page_number: r.page_number,
-                match_count: positions.len(),
-                match_positions: positions,
-                highlighted_snippet: highlighted,
-                preview,
-            }
-        }).collect();
-
-        Ok(search_results)
+        let results = self.database.string_search_chunks(query, limit, collection, mode)?;
+        Ok(results.into_iter().map(string_search::to_result).collect())
     }
 
     async fn delete_by_document(&self, document_id: &Uuid) -> Result<usize> {
-        // Get chunk IDs from SQLite
-        let chunk_count = self.database.get_chunks_count_for_document(document_id)?;
-
-        if chunk_count == 0 {
+        // Datapoint ids are the chunk ids, which only SQLite knows
+        let chunk_ids = self.database.chunk_ids_for_document(document_id)?;
+        if chunk_ids.is_empty() {
             return Ok(0);
         }
 
-        // Get chunk IDs by querying SQLite
-        // We need to query the chunks_content table for this document
-        let conn = {
-            // This is a workaround since we don't have direct access to get chunk IDs
-            // We'll delete from SQLite and try to delete from Vertex
-            let deleted = self.database.delete_chunks_by_document(document_id)?;
-            deleted
-        };
+        // Vertex first: once the SQLite rows are gone the datapoints can't be found again
+        self.remove_datapoints(&chunk_ids).await?;
+        let deleted = self.database.delete_chunks_by_document(document_id)?;
+        tracing::info!("Deleted {} chunks of document {} from Vertex and SQLite", deleted, document_id);
 
-        // Note: We can't easily delete from Vertex without knowing the exact datapoint IDs
-        // The datapoint_id is the chunk.id (UUID), which we just deleted from SQLite
-        // In a full implementation, we would:
-        // 1. First query SQLite for chunk IDs before deleting
-        // 2. Call Vertex AI delete API with those IDs
-        // 3. Then delete from SQLite
-
-        // For now, we at least clean up the SQLite records
-        tracing::info!(
-            "Deleted {} chunks from SQLite for document {}. Vertex cleanup pending.",
-            conn, document_id
-        );
-
-        Ok(conn)
+        Ok(deleted)
     }
 
     async fn len(&self) -> Result<usize> {
@@ -479,30 +608,6 @@ impl VectorStoreProvider for VertexVectorSearch {
     }
 }
 
-impl VertexVectorSearch {
-    /// Highlight search query in text using <mark> tags
-    fn highlight_matches(&self, text: &str, query: &str) -> String {
-        let query_lower = query.to_lowercase();
-        let text_lower = text.to_lowercase();
-        let mut result = String::with_capacity(text.len() + query.len() * 20);
-        let mut last_end = 0;
-
-        for (idx, _) in text_lower.match_indices(&query_lower) {
-            // Add text before the match
-            result.push_str(&text[last_end..idx]);
-            // Add highlighted match (preserving original case)
-            result.push_str("<mark>");
-            result.push_str(&text[idx..idx + query.len()]);
-            result.push_str("</mark>");
-            last_end = idx + query.len();
-        }
-
-        // Add remaining text
-        result.push_str(&text[last_end..]);
-        result
-    }
-}
-
 impl VertexVectorSearch {
     /// Create a minimal chunk with just the ID
     /// The caller should look up full chunk data from local store
@@ -525,11 +630,15 @@ impl VertexVectorSearch {
                 line_start: None,
                 line_end: None,
                 code_context: None,
+                time_range: None,
+                kind: crate::types::ChunkKind::Text,
             },
             char_start: 0,
             char_end: 0,
             chunk_index: 0,
             metadata: HashMap::new(),
+            sub_embeddings: Vec::new(),
+            parent_id: None,
         }
     }
 
@@ -589,6 +698,19 @@ impl VertexVectorSearch {
             .map(|v| serde_json::from_value(v.clone()).unwrap_or(crate::types::FileType::Unknown))
             .unwrap_or(crate::types::FileType::Unknown);
 
+        let kind = metadata
+            .get("kind")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let time_range = match (
+            metadata.get("start_seconds").and_then(|v| v.as_f64()),
+            metadata.get("end_seconds").and_then(|v| v.as_f64()),
+        ) {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None,
+        };
+
         let source = crate::types::ChunkSource {
             filename,
             internal_filename: None,
@@ -602,8 +724,22 @@ impl VertexVectorSearch {
             line_start: None,
             line_end: None,
             code_context: None,
+            time_range,
+            kind,
         };
 
+        // Email fields, keywords, entities and the document title, author, tags and
+        // date are kept for query filters and citations
+        let chunk_metadata = FILTER_METADATA_KEYS
+            .iter()
+            .filter_map(|key| metadata.get(*key).map(|value| (key.to_string(), value.clone())))
+            .collect();
+
+        let parent_id = metadata
+            .get("parent_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| Uuid::parse_str(s).ok());
+
         Ok(Chunk {
             id: chunk_id,
             document_id,
@@ -613,7 +749,9 @@ impl VertexVectorSearch {
             char_start,
             char_end,
             chunk_index,
-            metadata: HashMap::new(),
+            metadata: chunk_metadata,
+            sub_embeddings: Vec::new(),
+            parent_id,
         })
     }
 }
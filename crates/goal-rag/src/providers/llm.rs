@@ -2,8 +2,27 @@
 
 use async_trait::async_trait;
 use crate::error::Result;
+use crate::generation::PromptBuilder;
 use crate::types::response::Citation;
 
+/// Generated answer and the sources the model reports citing
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedAnswer {
+    pub text: String,
+    /// 1-based numbers of the cited sources, as numbered in the prompt, when
+    /// the provider returns them explicitly; `None` leaves the citations to
+    /// be parsed from the text
+    pub cited_sources: Option<Vec<usize>>,
+    /// Input tokens the provider read from a context cache
+    pub cached_tokens: u64,
+}
+
+impl From<String> for GeneratedAnswer {
+    fn from(text: String) -> Self {
+        Self { text, cited_sources: None, cached_tokens: 0 }
+    }
+}
+
 /// Trait for LLM-based answer generation
 ///
 /// Implementations:
@@ -28,6 +47,45 @@ pub trait LlmProvider: Send + Sync {
         past_qa: &[(String, String)],
     ) -> Result<String>;
 
+    /// Generate an answer, with its citations reported explicitly when the
+    /// provider has a structured output (JSON) mode
+    ///
+    /// The default generates free text with `generate_answer` (or
+    /// `generate_with_learning` when there are past Q&A examples).
+    async fn generate_cited(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<GeneratedAnswer> {
+        let text = if past_qa.is_empty() {
+            self.generate_answer(question, context, citations).await?
+        } else {
+            self.generate_with_learning(question, context, citations, past_qa).await?
+        };
+        Ok(text.into())
+    }
+
+    /// Prompt `generate_answer` (or `generate_with_learning` when there are
+    /// past Q&A examples) sends to the model
+    fn answer_prompt(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> String {
+        if past_qa.is_empty() {
+            PromptBuilder::build_rag_prompt(question, context, citations)
+        } else {
+            PromptBuilder::build_rag_prompt_with_learning(question, context, citations, past_qa)
+        }
+    }
+
+    /// Generate a completion for a raw prompt (translation, rewriting, etc.)
+    async fn complete(&self, prompt: &str) -> Result<String>;
+
     /// Check if the provider is healthy and available
     async fn health_check(&self) -> Result<bool>;
 
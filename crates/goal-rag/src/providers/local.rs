@@ -10,12 +10,14 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::error::{Error, Result};
+use crate::retrieval::string_search;
 use crate::retrieval::VectorStore;
-use crate::storage::{ChunkContentRecord, FileRegistryDb};
+use crate::storage::{ChunkContentRecord, ChunkContentRow, FileRegistryDb, SpoolCipher};
 use crate::types::Chunk;
+use crate::types::query::StringSearchMode;
 use crate::types::response::StringSearchResult;
 
-use super::document_store::{DocumentStoreProvider, StoredDocumentInfo};
+use super::document_store::{DocumentStoreProvider, DocumentStream, StoredDocumentInfo};
 use super::vector_store::{VectorSearchResult, VectorStoreProvider};
 
 /// Local vector store wrapping ruvector-core HNSW index
@@ -135,7 +137,7 @@ impl LocalVectorStore {
     }
 
     /// Convert Chunk to ChunkContentRecord for SQLite storage
-    fn chunk_to_content_record(chunk: &Chunk) -> ChunkContentRecord {
+    pub fn chunk_to_content_record(chunk: &Chunk) -> ChunkContentRecord {
         ChunkContentRecord {
             id: chunk.id,
             document_id: chunk.document_id,
@@ -147,6 +149,12 @@ impl LocalVectorStore {
             section_title: chunk.source.section_title.clone(),
             char_start: chunk.char_start,
             char_end: chunk.char_end,
+            collection: chunk.collection().map(|c| c.to_string()),
+            parent_id: chunk.parent_id,
+            kind: chunk.source.kind,
+            keywords: chunk.keywords(),
+            entities: chunk.entities(),
+            embedding_model: chunk.embedding_model().map(str::to_string),
         }
     }
 }
@@ -162,8 +170,7 @@ impl VectorStoreProvider for LocalVectorStore {
             .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
 
         // Then store in SQLite for FTS - if this fails, we need to rollback HNSW
-        let record = Self::chunk_to_content_record(chunk);
-        if let Err(e) = self.database.insert_chunk_content(&record) {
+        if let Err(e) = self.database.insert_chunk_rows([ChunkContentRow::from(chunk)]) {
             // Rollback: delete from HNSW
             tracing::warn!("SQLite insert failed, rolling back HNSW insert: {}", e);
             let store = self.store.clone();
@@ -178,17 +185,17 @@ impl VectorStoreProvider for LocalVectorStore {
         Ok(())
     }
 
-    async fn insert_chunks(&self, chunks: &[Chunk]) -> Result<()> {
+    async fn insert_chunks(&self, chunks: Arc<[Chunk]>) -> Result<()> {
         if chunks.is_empty() {
             return Ok(());
         }
 
         // Store in HNSW first (collect successful IDs for potential rollback)
         let store = self.store.clone();
-        let chunks_vec = chunks.to_vec();
+        let batch = Arc::clone(&chunks);
         let inserted_ids: Vec<String> = tokio::task::spawn_blocking(move || {
-            let mut ids = Vec::with_capacity(chunks_vec.len());
-            for chunk in &chunks_vec {
+            let mut ids = Vec::with_capacity(batch.len());
+            for chunk in batch.iter() {
                 store.insert_chunk(chunk)?;
                 ids.push(chunk.id.to_string());
             }
@@ -198,12 +205,7 @@ impl VectorStoreProvider for LocalVectorStore {
         .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
 
         // Then store in SQLite for FTS with transaction
-        let records: Vec<ChunkContentRecord> = chunks
-            .iter()
-            .map(Self::chunk_to_content_record)
-            .collect();
-
-        if let Err(e) = self.database.insert_chunks_content(&records) {
+        if let Err(e) = self.database.insert_chunk_rows(chunks.iter().map(ChunkContentRow::from)) {
             // Rollback: delete all inserted chunks from HNSW
             tracing::warn!(
                 "SQLite batch insert failed, rolling back {} HNSW inserts: {}",
@@ -223,6 +225,20 @@ impl VectorStoreProvider for LocalVectorStore {
         Ok(())
     }
 
+    async fn update_chunk(&self, chunk: &Chunk) -> Result<()> {
+        // Old window vectors go with the old entry
+        let store = self.store.clone();
+        let chunk_clone = chunk.clone();
+        tokio::task::spawn_blocking(move || {
+            store.delete_chunk(&chunk_clone.id.to_string())?;
+            store.insert_chunk(&chunk_clone)
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+        self.database.replace_chunk_content(&Self::chunk_to_content_record(chunk))
+    }
+
     async fn search(
         &self,
         query_embedding: &[f32],
@@ -237,10 +253,7 @@ impl VectorStoreProvider for LocalVectorStore {
             let results = store.search(&query, top_k, filter.as_deref())?;
             Ok(results
                 .into_iter()
-                .map(|r| VectorSearchResult {
-                    chunk: r.chunk,
-                    similarity: r.similarity,
-                })
+                .map(|r| VectorSearchResult::new(r.chunk, r.similarity))
                 .collect())
         })
         .await
@@ -251,41 +264,12 @@ impl VectorStoreProvider for LocalVectorStore {
         &self,
         query: &str,
         limit: usize,
+        collection: Option<&str>,
+        mode: StringSearchMode,
     ) -> Result<Vec<StringSearchResult>> {
         // Use SQLite FTS5 for efficient text search (not HNSW linear scan)
-        let fts_results = self.database.string_search_chunks(query, limit)?;
-
-        // Convert FTS results to StringSearchResult
-        let query_lower = query.to_lowercase();
-        let results: Vec<StringSearchResult> = fts_results
-            .into_iter()
-            .map(|r| {
-                // Find match positions
-                let content_lower = r.content.to_lowercase();
-                let match_positions: Vec<usize> = content_lower
-                    .match_indices(&query_lower)
-                    .map(|(pos, _)| pos)
-                    .collect();
-
-                // Create highlighted snippet
-                let highlighted = Self::highlight_matches(&r.content, query);
-                let preview = Self::create_preview(&r.content, query);
-
-                StringSearchResult {
-                    chunk_id: r.chunk_id,
-                    document_id: r.document_id,
-                    filename: r.filename,
-                    file_type: r.file_type,
-                    page_number: r.page_number,
-                    match_count: match_positions.len(),
-                    match_positions,
-                    preview,
-                    highlighted_snippet: highlighted,
-                }
-            })
-            .collect();
-
-        Ok(results)
+        let results = self.database.string_search_chunks(query, limit, collection, mode)?;
+        Ok(results.into_iter().map(string_search::to_result).collect())
     }
 
     async fn delete_by_document(&self, document_id: &Uuid) -> Result<usize> {
@@ -317,100 +301,57 @@ impl VectorStoreProvider for LocalVectorStore {
     }
 }
 
-impl LocalVectorStore {
-    /// Highlight query matches in content using <mark> tags
-    /// Uses character-based indexing to handle UTF-8 safely
-    fn highlight_matches(content: &str, query: &str) -> String {
-        if query.is_empty() {
-            return content.to_string();
-        }
-
-        let query_lower = query.to_lowercase();
-        let content_lower = content.to_lowercase();
-
-        // Find all match positions in the lowercase version (byte positions)
-        let matches: Vec<usize> = content_lower
-            .match_indices(&query_lower)
-            .map(|(pos, _)| pos)
-            .collect();
-
-        if matches.is_empty() {
-            return content.to_string();
-        }
-
-        // Build result by finding corresponding positions in original string
-        // We need to map byte positions from lowercase to original
-        let mut result = String::with_capacity(content.len() + matches.len() * 13);
-        let mut last_end = 0;
-
-        for &match_start in &matches {
-            // Find the actual match length in original string
-            // by iterating characters until we've consumed the same bytes
-            let match_in_original = &content[match_start..];
-            let query_char_count = query_lower.chars().count();
-            let actual_match: String = match_in_original.chars().take(query_char_count).collect();
-            let actual_len = actual_match.len();
-
-            // Append text before match
-            result.push_str(&content[last_end..match_start]);
-            // Append highlighted match
-            result.push_str("<mark>");
-            result.push_str(&content[match_start..match_start + actual_len]);
-            result.push_str("</mark>");
-
-            last_end = match_start + actual_len;
-        }
-
-        // Append remaining text
-        result.push_str(&content[last_end..]);
-        result
-    }
-
-    /// Create preview with context around first match
-    /// Uses character-based indexing to handle UTF-8 safely
-    fn create_preview(content: &str, query: &str) -> String {
-        let query_lower = query.to_lowercase();
-        let content_lower = content.to_lowercase();
-
-        if let Some(byte_pos) = content_lower.find(&query_lower) {
-            // Convert byte position to character position for safe slicing
-            let char_pos = content[..byte_pos].chars().count();
-            let content_chars: Vec<char> = content.chars().collect();
-            let query_char_count = query.chars().count();
-            let total_chars = content_chars.len();
-
-            // Calculate preview bounds in characters
-            let preview_start = char_pos.saturating_sub(50);
-            let preview_end = (char_pos + query_char_count + 50).min(total_chars);
-
-            // Build preview string
-            let preview: String = content_chars[preview_start..preview_end].iter().collect();
-
-            let mut result = preview;
-            if preview_start > 0 {
-                result = format!("...{}", result);
-            }
-            if preview_end < total_chars {
-                result = format!("{}...", result);
-            }
-            result
-        } else {
-            content.chars().take(100).collect()
-        }
-    }
-}
+/// Bytes read at a time when streaming a stored document
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
 
 /// Local document store using filesystem
+///
+/// With encryption at rest, originals, plain text and thumbnails are written
+/// encrypted and files that aren't are refused when read. The `.meta.json`
+/// sidecars only hold the ID, name and size.
 pub struct LocalDocumentStore {
     /// Directory to store documents
     storage_dir: PathBuf,
+    /// Encrypts stored files (encryption at rest)
+    cipher: Option<SpoolCipher>,
 }
 
 impl LocalDocumentStore {
     /// Create a new local document store
     pub fn new(storage_dir: PathBuf) -> Result<Self> {
         std::fs::create_dir_all(&storage_dir)?;
-        Ok(Self { storage_dir })
+        Ok(Self { storage_dir, cipher: None })
+    }
+
+    /// Encrypt files stored from now on
+    pub fn with_cipher(mut self, cipher: Option<SpoolCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Write a file, encrypted if encryption is enabled
+    async fn write_file(&self, path: &std::path::Path, data: &[u8]) -> Result<()> {
+        match &self.cipher {
+            Some(cipher) => tokio::fs::write(path, cipher.encrypt(data)?).await?,
+            None => tokio::fs::write(path, data).await?,
+        }
+        Ok(())
+    }
+
+    /// Read a file written by `write_file` (`None` if it doesn't exist)
+    async fn read_file(&self, path: &std::path::Path) -> Result<Option<Vec<u8>>> {
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match &self.cipher {
+            Some(_) if !SpoolCipher::is_encrypted(&data) => {
+                Err(Error::StorageCorruption(format!("Stored file {:?} is not encrypted", path)))
+            }
+            Some(cipher) => cipher.decrypt(&data).map(Some),
+            None => Ok(Some(data)),
+        }
     }
 
     /// Get path for a document
@@ -422,6 +363,16 @@ impl LocalDocumentStore {
     fn meta_path(&self, doc_id: &Uuid) -> PathBuf {
         self.storage_dir.join(format!("{}.meta.json", doc_id))
     }
+
+    /// Get plain text path for a document
+    fn plaintext_path(&self, doc_id: &Uuid) -> PathBuf {
+        self.storage_dir.join(format!("{}.txt", doc_id))
+    }
+
+    /// Get the directory holding a document's page thumbnails
+    fn thumbnail_dir(&self, doc_id: &Uuid) -> PathBuf {
+        self.storage_dir.join("thumbnails").join(doc_id.to_string())
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -443,7 +394,7 @@ impl DocumentStoreProvider for LocalDocumentStore {
         let meta_path = self.meta_path(doc_id);
 
         // Write document data
-        tokio::fs::write(&doc_path, data).await?;
+        self.write_file(&doc_path, data).await?;
 
         // Write metadata
         let meta = DocumentMeta {
@@ -459,9 +410,81 @@ impl DocumentStoreProvider for LocalDocumentStore {
 
     async fn get_document(&self, doc_id: &Uuid) -> Result<Vec<u8>> {
         let doc_path = self.doc_path(doc_id);
-        tokio::fs::read(&doc_path)
+        self.read_file(&doc_path)
+            .await?
+            .ok_or_else(|| Error::Internal(format!("Failed to read document {}: not found", doc_id)))
+    }
+
+    async fn document_size(&self, doc_id: &Uuid) -> Result<Option<u64>> {
+        // An encrypted file is larger than the document; its sidecar has the size
+        if self.cipher.is_some() {
+            return match tokio::fs::read_to_string(self.meta_path(doc_id)).await {
+                Ok(json) => Ok(Some(serde_json::from_str::<DocumentMeta>(&json)?.size)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            };
+        }
+        match tokio::fs::metadata(self.doc_path(doc_id)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_document_range(&self, doc_id: &Uuid, start: u64, end: u64) -> Result<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        // Frames are authenticated as a whole, so decrypt the file and slice it
+        if self.cipher.is_some() {
+            let data = self.get_document(doc_id).await?;
+            let start = (start as usize).min(data.len());
+            let end = (end as usize).saturating_add(1).min(data.len()).max(start);
+            return Ok(data[start..end].to_vec());
+        }
+
+        let mut file = tokio::fs::File::open(self.doc_path(doc_id))
             .await
-            .map_err(|e| Error::Internal(format!("Failed to read document {}: {}", doc_id, e)))
+            .map_err(|e| Error::Internal(format!("Failed to read document {}: {}", doc_id, e)))?;
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        let mut data = Vec::with_capacity((end - start + 1) as usize);
+        file.take(end - start + 1).read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn stream_document(&self, doc_id: &Uuid, range: Option<(u64, u64)>) -> Result<DocumentStream> {
+        use futures::stream::{self, StreamExt};
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        // Encrypted files can only be decrypted whole
+        if self.cipher.is_some() {
+            let data = match range {
+                Some((start, end)) => self.get_document_range(doc_id, start, end).await?,
+                None => self.get_document(doc_id).await?,
+            };
+            return Ok(stream::once(async move { Ok(bytes::Bytes::from(data)) }).boxed());
+        }
+
+        let mut file = tokio::fs::File::open(self.doc_path(doc_id))
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read document {}: {}", doc_id, e)))?;
+        let length = match range {
+            Some((start, end)) => {
+                file.seek(std::io::SeekFrom::Start(start)).await?;
+                end - start + 1
+            }
+            None => u64::MAX,
+        };
+        let reader = file.take(length);
+        Ok(stream::try_unfold(reader, |mut reader| async move {
+            let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            buffer.truncate(read);
+            Ok::<_, Error>(Some((bytes::Bytes::from(buffer), reader)))
+        })
+        .boxed())
     }
 
     async fn exists(&self, doc_id: &Uuid) -> Result<bool> {
@@ -516,6 +539,49 @@ impl DocumentStoreProvider for LocalDocumentStore {
         }
     }
 
+    async fn store_plain_text(&self, doc_id: &Uuid, _filename: &str, text: &str) -> Result<String> {
+        let path = self.plaintext_path(doc_id);
+        self.write_file(&path, text.as_bytes()).await?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    async fn get_plain_text(&self, doc_id: &Uuid) -> Result<Option<String>> {
+        match self.read_file(&self.plaintext_path(doc_id)).await? {
+            Some(data) => String::from_utf8(data)
+                .map(Some)
+                .map_err(|_| Error::StorageCorruption(format!("Plain text of document {} is not UTF-8", doc_id))),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete_plain_text(&self, doc_id: &Uuid) -> Result<()> {
+        let path = self.plaintext_path(doc_id);
+        if path.exists() {
+            tokio::fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    async fn store_thumbnail(&self, doc_id: &Uuid, page: u32, png: &[u8]) -> Result<String> {
+        let dir = self.thumbnail_dir(doc_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!("{}.png", page));
+        self.write_file(&path, png).await?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    async fn get_thumbnail(&self, doc_id: &Uuid, page: u32) -> Result<Option<Vec<u8>>> {
+        self.read_file(&self.thumbnail_dir(doc_id).join(format!("{}.png", page))).await
+    }
+
+    async fn delete_thumbnails(&self, doc_id: &Uuid) -> Result<()> {
+        let dir = self.thumbnail_dir(doc_id);
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+        Ok(())
+    }
+
     async fn health_check(&self) -> Result<bool> {
         Ok(self.storage_dir.exists())
     }
@@ -524,3 +590,63 @@ impl DocumentStoreProvider for LocalDocumentStore {
         "local-filesystem"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(stream: DocumentStream) -> Vec<u8> {
+        use futures::TryStreamExt;
+        stream.try_concat().await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_stream_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalDocumentStore::new(dir.path().to_path_buf()).unwrap();
+        let id = Uuid::new_v4();
+        // Several read chunks, the last one partial
+        let data: Vec<u8> = (0..STREAM_CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        store.store_document(&id, "large.bin", &data).await.unwrap();
+
+        assert_eq!(collect(store.stream_document(&id, None).await.unwrap()).await, data);
+        let (start, end) = (STREAM_CHUNK_SIZE as u64 - 10, STREAM_CHUNK_SIZE as u64 * 2 + 20);
+        let range = collect(store.stream_document(&id, Some((start, end))).await.unwrap()).await;
+        assert_eq!(range, &data[start as usize..=end as usize]);
+        assert!(store.stream_document(&Uuid::new_v4(), None).await.is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn test_encrypted_document_store() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let dir = tempfile::tempdir().unwrap();
+        let key = crate::storage::EncryptionKey::from_base64(&BASE64.encode([7u8; 32])).unwrap();
+        let store = LocalDocumentStore::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_cipher(Some(SpoolCipher::new(key)));
+        let id = Uuid::new_v4();
+
+        store.store_document(&id, "secret.txt", b"top secret original").await.unwrap();
+        store.store_plain_text(&id, "secret.txt", "top secret text").await.unwrap();
+        store.store_thumbnail(&id, 1, b"png bytes").await.unwrap();
+        for path in [store.doc_path(&id), store.plaintext_path(&id), store.thumbnail_dir(&id).join("1.png")] {
+            let raw = std::fs::read(path).unwrap();
+            assert!(SpoolCipher::is_encrypted(&raw));
+            assert!(!raw.windows(10).any(|w| w == b"top secret"));
+        }
+
+        assert_eq!(store.get_document(&id).await.unwrap(), b"top secret original");
+        assert_eq!(store.document_size(&id).await.unwrap(), Some(19));
+        assert_eq!(store.get_document_range(&id, 4, 9).await.unwrap(), b"secret");
+        assert_eq!(collect(store.stream_document(&id, Some((4, 9))).await.unwrap()).await, b"secret");
+        assert_eq!(store.get_plain_text(&id).await.unwrap().as_deref(), Some("top secret text"));
+        assert_eq!(store.get_thumbnail(&id, 1).await.unwrap().as_deref(), Some(&b"png bytes"[..]));
+        assert_eq!(store.get_thumbnail(&id, 2).await.unwrap(), None);
+
+        // A plaintext file left in the store is refused
+        std::fs::write(store.plaintext_path(&id), "planted").unwrap();
+        assert!(matches!(store.get_plain_text(&id).await, Err(Error::StorageCorruption(_))));
+    }
+}
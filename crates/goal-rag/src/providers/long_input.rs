@@ -0,0 +1,225 @@
+//! Splitting of inputs longer than the embedding model accepts
+//!
+//! Embedding providers silently truncate over-length input, so the tail of a
+//! long chunk never makes it into its vector. `LongInputEmbedder` estimates the
+//! token count, splits long input into overlapping windows and embeds each one.
+//! The chunk stays a single logical unit for citations; its window vectors are
+//! combined according to `LongInputStrategy`.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::config::{LongInputConfig, LongInputStrategy};
+use crate::error::Result;
+
+use super::embedding::EmbeddingProvider;
+
+/// Embedding provider wrapper that splits over-length input into windows
+pub struct LongInputEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    config: LongInputConfig,
+}
+
+impl LongInputEmbedder {
+    /// Wrap a provider
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, config: LongInputConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// Estimated character limit of the model input
+    fn max_chars(&self) -> usize {
+        ((self.config.max_tokens as f32 * self.config.chars_per_token) as usize).max(1)
+    }
+
+    /// Split text into windows that fit the model input
+    ///
+    /// Windows end at whitespace where possible and share
+    /// `overlap_tokens` worth of text with their predecessor.
+    pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let max = self.max_chars();
+        if text.len() <= max {
+            return vec![text];
+        }
+
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        if chars.len() <= max {
+            return vec![text];
+        }
+
+        let overlap = ((self.config.overlap_tokens as f32 * self.config.chars_per_token) as usize).min(max / 2);
+        let mut windows = Vec::new();
+        let mut start = 0;
+
+        loop {
+            let mut end = (start + max).min(chars.len());
+            if end < chars.len() {
+                // Prefer a word boundary in the second half of the window
+                if let Some(ws) = (start + max / 2..end).rev().find(|&i| chars[i].1.is_whitespace()) {
+                    end = ws;
+                }
+            }
+
+            let from = chars[start].0;
+            let to = chars.get(end).map(|(i, _)| *i).unwrap_or(text.len());
+            windows.push(&text[from..to]);
+
+            if end >= chars.len() {
+                break;
+            }
+            let next = end.saturating_sub(overlap);
+            start = if next > start { next } else { end };
+        }
+
+        windows
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LongInputEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut parts = self.embed_parts(text).await?;
+        if parts.len() == 1 {
+            return Ok(parts.remove(0));
+        }
+        Ok(mean_pool(&parts))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        // Keep the provider's batching when nothing needs splitting
+        if texts.iter().all(|t| self.split(t).len() == 1) {
+            return self.inner.embed_batch(texts).await;
+        }
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+
+    async fn embed_parts(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        let windows = self.split(text);
+        if windows.len() == 1 {
+            return Ok(vec![self.inner.embed(text).await?]);
+        }
+
+        tracing::debug!(
+            "Input of {} chars exceeds ~{} tokens, split into {} windows ({:?})",
+            text.len(),
+            self.config.max_tokens,
+            windows.len(),
+            self.config.strategy
+        );
+
+        match self.config.strategy {
+            LongInputStrategy::Truncate => Ok(vec![self.inner.embed(windows[0]).await?]),
+            LongInputStrategy::Mean => {
+                let windows: Vec<String> = windows.iter().map(|w| w.to_string()).collect();
+                let embeddings = self.inner.embed_batch(&windows).await?;
+                Ok(vec![mean_pool(&embeddings)])
+            }
+            LongInputStrategy::MaxSim => {
+                let windows: Vec<String> = windows.iter().map(|w| w.to_string()).collect();
+                self.inner.embed_batch(&windows).await
+            }
+        }
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.inner.count_tokens(text)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// L2-normalized mean of several vectors
+pub fn mean_pool(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let Some(first) = vectors.first() else {
+        return Vec::new();
+    };
+
+    let mut mean = vec![0.0f32; first.len()];
+    for vector in vectors {
+        for (m, v) in mean.iter_mut().zip(vector) {
+            *m += v;
+        }
+    }
+
+    let norm = mean.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for m in &mut mean {
+            *m /= norm;
+        }
+    }
+    mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct LengthEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for LengthEmbedder {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32, 1.0])
+        }
+        fn dimensions(&self) -> usize {
+            2
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        fn name(&self) -> &str {
+            "length"
+        }
+    }
+
+    fn embedder(strategy: LongInputStrategy) -> LongInputEmbedder {
+        LongInputEmbedder::new(
+            Arc::new(LengthEmbedder),
+            LongInputConfig { strategy, max_tokens: 10, overlap_tokens: 2, chars_per_token: 4.0 },
+        )
+    }
+
+    #[test]
+    fn test_split_windows() {
+        let embedder = embedder(LongInputStrategy::Mean);
+        assert_eq!(embedder.split("short text"), vec!["short text"]);
+
+        let text = "word ".repeat(30);
+        let windows = embedder.split(&text);
+        assert!(windows.len() > 1);
+        assert!(windows.iter().all(|w| w.chars().count() <= 40));
+        // Overlapping windows cover the whole text
+        assert!(text.starts_with(windows[0]));
+        assert!(text.trim_end().ends_with(windows.last().unwrap().trim_end()));
+    }
+
+    #[tokio::test]
+    async fn test_strategies() {
+        let text = "lorem ipsum ".repeat(20);
+
+        let parts = embedder(LongInputStrategy::MaxSim).embed_parts(&text).await.unwrap();
+        assert!(parts.len() > 1);
+
+        let parts = embedder(LongInputStrategy::Mean).embed_parts(&text).await.unwrap();
+        assert_eq!(parts.len(), 1);
+        let norm: f32 = parts[0].iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+
+        let parts = embedder(LongInputStrategy::Truncate).embed_parts(&text).await.unwrap();
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0][0] <= 40.0);
+    }
+}
@@ -4,16 +4,32 @@
 //! local (Ollama) and cloud (GCP) backends.
 
 pub mod embedding;
+pub mod dimensions;
+pub mod long_input;
+pub mod collection_embedders;
+pub mod scheduler;
+pub mod fallback;
+pub mod usage;
+pub mod rate_limit;
 pub mod llm;
+pub mod vision;
 pub mod vector_store;
 pub mod document_store;
 pub mod ollama;
+pub mod onnx;
 pub mod local;
 
 #[cfg(feature = "gcp")]
 pub mod gcp;
 
 pub use embedding::EmbeddingProvider;
-pub use llm::LlmProvider;
+pub use long_input::LongInputEmbedder;
+pub use collection_embedders::{CollectionEmbedders, PaddedEmbedder};
+pub use scheduler::{EmbeddingScheduler, SchedulerMetrics, SchedulerStats};
+pub use usage::{UsageScope, UsageTracker};
+pub use rate_limit::{RateLimiter, RateLimits};
+pub use fallback::FallbackLlm;
+pub use llm::{GeneratedAnswer, LlmProvider};
+pub use vision::VisionProvider;
 pub use vector_store::VectorStoreProvider;
 pub use document_store::DocumentStoreProvider;
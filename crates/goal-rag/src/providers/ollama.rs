@@ -3,15 +3,17 @@
 //! Wraps the existing OllamaClient to implement the provider traits.
 
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use std::sync::Arc;
 
-use crate::config::LlmConfig;
+use crate::config::{LlmConfig, VisionConfig};
 use crate::error::Result;
 use crate::generation::OllamaClient;
 use crate::types::response::Citation;
 
 use super::embedding::EmbeddingProvider;
 use super::llm::LlmProvider;
+use super::vision::VisionProvider;
 
 /// Ollama embedding provider using nomic-embed-text or similar models
 pub struct OllamaEmbedder {
@@ -119,6 +121,10 @@ impl LlmProvider for OllamaLlm {
             .await
     }
 
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        self.client.generate(prompt).await
+    }
+
     async fn health_check(&self) -> Result<bool> {
         self.client.health_check().await
     }
@@ -132,6 +138,47 @@ impl LlmProvider for OllamaLlm {
     }
 }
 
+/// Ollama vision provider for image captioning (llava or similar)
+pub struct OllamaVision {
+    client: OllamaClient,
+    model: String,
+}
+
+impl OllamaVision {
+    /// Create a vision provider from the LLM and vision configuration
+    ///
+    /// Uses `vision.base_url` when set, otherwise the LLM server.
+    pub fn new(llm: &LlmConfig, vision: &VisionConfig) -> Self {
+        let config = LlmConfig {
+            base_url: vision.base_url.clone().unwrap_or_else(|| llm.base_url.clone()),
+            generate_model: vision.model.clone(),
+            timeout_secs: vision.timeout_secs,
+            ..llm.clone()
+        };
+        Self {
+            client: OllamaClient::new(&config),
+            model: vision.model.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl VisionProvider for OllamaVision {
+    async fn describe_image(&self, image: &[u8], _mime_type: &str, prompt: &str) -> Result<String> {
+        self.client
+            .generate_with_images(prompt, &[BASE64.encode(image)])
+            .await
+    }
+
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
 /// Combined Ollama provider that shares a single client for both embeddings and LLM
 pub struct OllamaProvider {
     embedder: OllamaEmbedder,
@@ -0,0 +1,366 @@
+//! In-process ONNX embedding provider
+//!
+//! Runs a sentence-transformers style model with ONNX Runtime, so no
+//! embedding server is needed. Inputs are tokenized with the model's
+//! HuggingFace tokenizer and run in batches of `embeddings.batch_size` on a
+//! blocking thread; token embeddings are pooled (attention-masked mean or
+//! [CLS]) and L2-normalized. The model is read from `embeddings.onnx.model_path`
+//! or downloaded from HuggingFace once into `embeddings.cache_dir`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ort::execution_providers::{
+    CUDAExecutionProvider, CoreMLExecutionProvider, DirectMLExecutionProvider, ExecutionProviderDispatch,
+};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Tensor;
+use parking_lot::Mutex;
+use tokenizers::{Tokenizer, TruncationParams};
+
+use crate::config::{EmbeddingConfig, OnnxConfig, OnnxExecutionProvider, OnnxPooling};
+use crate::error::{Error, Result};
+
+use super::embedding::EmbeddingProvider;
+
+/// HuggingFace download base URL
+const HF_BASE_URL: &str = "https://huggingface.co";
+
+/// Environment variable with a HuggingFace token for gated or private models
+const HF_TOKEN_ENV: &str = "HF_TOKEN";
+
+/// Embedding provider running an ONNX model in-process
+pub struct OnnxEmbedder {
+    model: Arc<OnnxModel>,
+    name: String,
+    dimensions: usize,
+    batch_size: usize,
+    /// Untruncated copy of the model's tokenizer, for counting tokens
+    counter: Tokenizer,
+}
+
+/// Loaded model, shared with the blocking inference threads
+struct OnnxModel {
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+    pooling: OnnxPooling,
+    /// BERT-style models take segment ids, most others don't
+    token_type_ids: bool,
+}
+
+impl OnnxEmbedder {
+    /// Load (downloading if needed) the configured model
+    ///
+    /// Reports the dimensions the model produces, which are checked against
+    /// `config.dimensions` with the other embedding models.
+    pub async fn new(config: &EmbeddingConfig) -> Result<Self> {
+        let (model_path, tokenizer_path) = model_files(config).await?;
+        tracing::info!(
+            "Loading ONNX embedding model {:?} ({:?} execution provider)",
+            model_path,
+            config.onnx.execution_provider
+        );
+
+        let session = Session::builder()
+            .map_err(|e| Error::Embedding(format!("Failed to create session builder: {}", e)))?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .map_err(|e| Error::Embedding(format!("Failed to set optimization level: {}", e)))?
+            .with_intra_threads(config.onnx.intra_threads.max(1))
+            .map_err(|e| Error::Embedding(format!("Failed to set threads: {}", e)))?
+            .with_execution_providers(execution_providers(&config.onnx))
+            .map_err(|e| Error::Embedding(format!("Failed to register execution provider: {}", e)))?
+            .commit_from_file(&model_path)
+            .map_err(|e| Error::Embedding(format!("Failed to load model: {}", e)))?;
+        let token_type_ids = session.inputs.iter().any(|input| input.name == "token_type_ids");
+
+        let mut tokenizer = Tokenizer::from_file(&tokenizer_path)
+            .map_err(|e| Error::Embedding(format!("Failed to load tokenizer: {}", e)))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_length.max(1),
+                ..Default::default()
+            }))
+            .map_err(|e| Error::Embedding(format!("Failed to configure tokenizer: {}", e)))?;
+        tokenizer.with_padding(None);
+        let mut counter = tokenizer.clone();
+        counter
+            .with_truncation(None)
+            .map_err(|e| Error::Embedding(format!("Failed to configure tokenizer: {}", e)))?;
+
+        let mut embedder = Self {
+            model: Arc::new(OnnxModel {
+                session: Mutex::new(session),
+                tokenizer,
+                pooling: config.onnx.pooling,
+                token_type_ids,
+            }),
+            name: format!("onnx ({})", config.model),
+            dimensions: config.dimensions,
+            batch_size: config.batch_size.max(1),
+            counter,
+        };
+
+        embedder.dimensions = embedder.embed("dimension check").await?.len();
+
+        tracing::info!("ONNX embedder initialized ({} dimensions)", embedder.dimensions);
+        Ok(embedder)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OnnxEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_batch(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| Error::Embedding("Empty embedding result".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            let model = Arc::clone(&self.model);
+            let batch = batch.to_vec();
+            let vectors = tokio::task::spawn_blocking(move || model.run(&batch))
+                .await
+                .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+            embeddings.extend(vectors);
+        }
+        Ok(embeddings)
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.counter.encode(text, false).ok().map(|encoding| encoding.get_ids().len())
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl OnnxModel {
+    /// Embed one batch
+    fn run(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|e| Error::Embedding(format!("Tokenization failed: {}", e)))?;
+
+        // Pad to the longest input of the batch
+        let batch = encodings.len();
+        let seq_len = encodings.iter().map(|e| e.get_ids().len()).max().unwrap_or(0).max(1);
+        let mut input_ids = vec![0i64; batch * seq_len];
+        let mut attention_mask = vec![0i64; batch * seq_len];
+        let mut token_type_ids = vec![0i64; batch * seq_len];
+        for (i, encoding) in encodings.iter().enumerate() {
+            let offset = i * seq_len;
+            for (j, &id) in encoding.get_ids().iter().enumerate() {
+                input_ids[offset + j] = id as i64;
+            }
+            for (j, &mask) in encoding.get_attention_mask().iter().enumerate() {
+                attention_mask[offset + j] = mask as i64;
+            }
+            for (j, &type_id) in encoding.get_type_ids().iter().enumerate() {
+                token_type_ids[offset + j] = type_id as i64;
+            }
+        }
+
+        let tensor = |name: &str, values: Vec<i64>| {
+            Tensor::from_array((vec![batch, seq_len], values.into_boxed_slice()))
+                .map(|t| t.into_dyn())
+                .map_err(|e| Error::Embedding(format!("Failed to create {} tensor: {}", name, e)))
+        };
+        let mut inputs = vec![
+            ("input_ids", tensor("input_ids", input_ids)?),
+            ("attention_mask", tensor("attention_mask", attention_mask.clone())?),
+        ];
+        if self.token_type_ids {
+            inputs.push(("token_type_ids", tensor("token_type_ids", token_type_ids)?));
+        }
+
+        let mut session = self.session.lock();
+        let outputs = session
+            .run(inputs)
+            .map_err(|e| Error::Embedding(format!("Inference failed: {}", e)))?;
+
+        let outputs: Vec<_> = outputs.iter().collect();
+        let (_, output) = outputs
+            .iter()
+            .find(|(name, _)| *name == "last_hidden_state")
+            .or_else(|| outputs.first())
+            .ok_or_else(|| Error::Embedding("Model returned no output".to_string()))?;
+        let (shape, data) = output
+            .try_extract_tensor::<f32>()
+            .map_err(|e| Error::Embedding(format!("Failed to extract tensor: {}", e)))?;
+        let shape: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+
+        match shape[..] {
+            // Token embeddings
+            [_, tokens, hidden] if tokens == seq_len => pool(data, &attention_mask, batch, seq_len, hidden, self.pooling),
+            // Models exported with their pooling layer
+            [_, hidden] => Ok(data.chunks(hidden.max(1)).take(batch).map(normalized).collect()),
+            _ => Err(Error::Embedding(format!("Unexpected model output shape {:?}", shape))),
+        }
+    }
+}
+
+/// Execution providers to register, in order of preference
+///
+/// ONNX Runtime falls back to the CPU when a provider can't be registered.
+fn execution_providers(config: &OnnxConfig) -> Vec<ExecutionProviderDispatch> {
+    match config.execution_provider {
+        OnnxExecutionProvider::Cpu => Vec::new(),
+        OnnxExecutionProvider::Cuda => vec![CUDAExecutionProvider::default().with_device_id(config.device_id).build()],
+        OnnxExecutionProvider::CoreMl => vec![CoreMLExecutionProvider::default().build()],
+        OnnxExecutionProvider::DirectMl => {
+            vec![DirectMLExecutionProvider::default().with_device_id(config.device_id).build()]
+        }
+    }
+}
+
+/// Pool `[batch, seq_len, hidden]` token embeddings into L2-normalized vectors
+pub(crate) fn pool(
+    hidden_states: &[f32],
+    attention_mask: &[i64],
+    batch: usize,
+    seq_len: usize,
+    hidden: usize,
+    pooling: OnnxPooling,
+) -> Result<Vec<Vec<f32>>> {
+    if hidden_states.len() < batch * seq_len * hidden || attention_mask.len() < batch * seq_len {
+        return Err(Error::Embedding("Model output smaller than its input".to_string()));
+    }
+
+    let token = |i: usize, j: usize| &hidden_states[(i * seq_len + j) * hidden..(i * seq_len + j + 1) * hidden];
+    let vectors = (0..batch)
+        .map(|i| match pooling {
+            OnnxPooling::Cls => normalized(token(i, 0)),
+            OnnxPooling::Mean => {
+                let mut sum = vec![0.0f32; hidden];
+                let mut count = 0.0f32;
+                for j in (0..seq_len).filter(|&j| attention_mask[i * seq_len + j] > 0) {
+                    for (s, v) in sum.iter_mut().zip(token(i, j)) {
+                        *s += v;
+                    }
+                    count += 1.0;
+                }
+                if count > 0.0 {
+                    sum.iter_mut().for_each(|s| *s /= count);
+                }
+                normalized(&sum)
+            }
+        })
+        .collect();
+    Ok(vectors)
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter().map(|v| v / norm).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+/// Model and tokenizer files, downloaded into the cache directory if not configured
+async fn model_files(config: &EmbeddingConfig) -> Result<(PathBuf, PathBuf)> {
+    if let Some(ref model_path) = config.onnx.model_path {
+        let tokenizer_path = config
+            .onnx
+            .tokenizer_path
+            .clone()
+            .unwrap_or_else(|| model_path.with_file_name("tokenizer.json"));
+        return Ok((model_path.clone(), tokenizer_path));
+    }
+
+    let repo = config.onnx.repo.clone().unwrap_or_else(|| {
+        if config.model.contains('/') {
+            config.model.clone()
+        } else {
+            format!("sentence-transformers/{}", config.model)
+        }
+    });
+    let dir = config.cache_dir.join(repo.replace('/', "--"));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| Error::Config(format!("Failed to create model cache directory {:?}: {}", dir, e)))?;
+
+    let model_path = dir.join("model.onnx");
+    if !model_path.exists() {
+        download(&format!("{}/{}/resolve/main/{}", HF_BASE_URL, repo, config.onnx.model_file), &model_path).await?;
+    }
+    let tokenizer_path = match config.onnx.tokenizer_path {
+        Some(ref path) => path.clone(),
+        None => {
+            let path = dir.join("tokenizer.json");
+            if !path.exists() {
+                download(&format!("{}/{}/resolve/main/tokenizer.json", HF_BASE_URL, repo), &path).await?;
+            }
+            path
+        }
+    };
+
+    Ok((model_path, tokenizer_path))
+}
+
+/// Download a file, replacing `path` only once it is complete
+pub(crate) async fn download(url: &str, path: &Path) -> Result<()> {
+    tracing::info!("Downloading {}", url);
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Ok(token) = std::env::var(HF_TOKEN_ENV) {
+        request = request.bearer_auth(token);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| Error::Embedding(format!("Failed to download {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Embedding(format!("Download of {} failed: HTTP {}", url, response.status())));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| Error::Embedding(format!("Failed to read {}: {}", url, e)))?;
+
+    let partial = path.with_extension("part");
+    std::fs::write(&partial, &bytes)
+        .and_then(|_| std::fs::rename(&partial, path))
+        .map_err(|e| Error::Embedding(format!("Failed to save {:?}: {}", path, e)))?;
+
+    tracing::info!("Downloaded {:?} ({} bytes)", path, bytes.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pooling() {
+        // Two inputs of two tokens, the second padded after its first token
+        let hidden_states = [3.0, 0.0, 0.0, 4.0, 1.0, 0.0, 9.0, 9.0];
+        let mask = [1, 1, 1, 0];
+
+        let mean = pool(&hidden_states, &mask, 2, 2, 2, OnnxPooling::Mean).unwrap();
+        assert_eq!(mean, vec![vec![0.6, 0.8], vec![1.0, 0.0]]);
+
+        let cls = pool(&hidden_states, &mask, 2, 2, 2, OnnxPooling::Cls).unwrap();
+        assert_eq!(cls, vec![vec![1.0, 0.0], vec![1.0, 0.0]]);
+
+        assert!(pool(&hidden_states, &mask, 3, 2, 2, OnnxPooling::Mean).is_err());
+    }
+}
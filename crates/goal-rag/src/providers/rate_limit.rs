@@ -0,0 +1,466 @@
+//! Shared request and token rate limits per provider
+//!
+//! Every file slot embeds and generates on its own, so `parallel_files` and
+//! embedding batches in flight multiply into bursts a hosted API answers
+//! with 429s. `RateLimits` keeps one `RateLimiter` per provider name
+//! (`rate_limits.<name>` in the config) and wraps providers so that every
+//! call, from any worker or query, first takes its share of the provider's
+//! requests and tokens per minute, waiting while the budget refills.
+//!
+//! Input tokens are counted with the `context` tokenizer before the call;
+//! generated tokens are charged once the answer is back, so a long answer
+//! delays the calls after it. A call the provider still rejects for its rate
+//! empties the request budget, holding back the other callers as well.
+//!
+//! Every provider is wrapped, limited or not, so that limits changed by a
+//! configuration reload apply to providers that had none.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::config::{ContextConfig, ProviderRateLimit};
+use crate::error::{Error, Result};
+use crate::generation::packing::TokenCounter;
+use crate::types::response::Citation;
+
+use super::embedding::EmbeddingProvider;
+use super::llm::{GeneratedAnswer, LlmProvider};
+use super::vision::VisionProvider;
+
+/// Tokens charged for an image on top of the prompt (Gemini bills a
+/// standard-size image as 258 tokens)
+const IMAGE_TOKENS: u64 = 258;
+
+/// Requests and tokens per minute allowed for one provider
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    updated: Instant,
+}
+
+/// Budget refilling continuously up to a minute's worth
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_sec: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Self {
+            capacity,
+            available: capacity,
+            per_sec: capacity / 60.0,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available = (self.available + elapsed.as_secs_f64() * self.per_sec).min(self.capacity);
+    }
+
+    /// Time until `amount` is available; more than a minute's worth only
+    /// waits for a full bucket and leaves it in debt
+    fn wait_for(&self, amount: f64) -> Duration {
+        let needed = amount.min(self.capacity) - self.available;
+        if needed <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(needed / self.per_sec)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit: &ProviderRateLimit) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                requests: limit.requests_per_minute.map(Bucket::per_minute),
+                tokens: limit.tokens_per_minute.map(Bucket::per_minute),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        let state = self.state.lock();
+        state.requests.is_some() || state.tokens.is_some()
+    }
+
+    /// Change the limits, keeping what is left of the current budget
+    pub fn set_limit(&self, limit: &ProviderRateLimit) {
+        let mut state = self.state.lock();
+        state.refill(Instant::now());
+        state.requests = resize(state.requests.take(), limit.requests_per_minute);
+        state.tokens = resize(state.tokens.take(), limit.tokens_per_minute);
+    }
+
+    /// Wait until a request of `tokens` input tokens fits in the budget, and
+    /// take it
+    pub async fn acquire(&self, tokens: u64) {
+        loop {
+            let wait = self.state.lock().try_take(Instant::now(), tokens);
+            match wait {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+
+    /// How long a request arriving now would wait for its turn
+    pub fn backlog(&self) -> Duration {
+        let mut state = self.state.lock();
+        state.refill(Instant::now());
+        state.requests.as_ref().map_or(Duration::ZERO, |bucket| bucket.wait_for(1.0))
+    }
+
+    /// Charge tokens used beyond those acquired (generated output)
+    pub fn charge(&self, tokens: u64) {
+        let mut state = self.state.lock();
+        state.refill(Instant::now());
+        if let Some(bucket) = state.tokens.as_mut() {
+            bucket.available -= tokens as f64;
+        }
+    }
+
+    /// The provider turned a request down for its rate: spend the request
+    /// budget so callers wait for it to refill
+    pub fn back_off(&self) {
+        let mut state = self.state.lock();
+        state.refill(Instant::now());
+        if let Some(bucket) = state.requests.as_mut() {
+            bucket.available = bucket.available.min(0.0);
+        }
+    }
+}
+
+impl LimiterState {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.updated = now;
+        for bucket in [self.requests.as_mut(), self.tokens.as_mut()].into_iter().flatten() {
+            bucket.refill(elapsed);
+        }
+    }
+
+    /// Take a request of `tokens`, or how long to wait before trying again
+    fn try_take(&mut self, now: Instant, tokens: u64) -> Option<Duration> {
+        self.refill(now);
+        let wait = [(self.requests.as_ref(), 1.0), (self.tokens.as_ref(), tokens as f64)]
+            .into_iter()
+            .filter_map(|(bucket, amount)| bucket.map(|bucket| bucket.wait_for(amount)))
+            .max()
+            .unwrap_or(Duration::ZERO);
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+        if let Some(bucket) = self.requests.as_mut() {
+            bucket.available -= 1.0;
+        }
+        if let Some(bucket) = self.tokens.as_mut() {
+            bucket.available -= tokens as f64;
+        }
+        None
+    }
+}
+
+fn resize(bucket: Option<Bucket>, limit: Option<u32>) -> Option<Bucket> {
+    let mut resized = Bucket::per_minute(limit?);
+    if let Some(bucket) = bucket {
+        resized.available = bucket.available.min(resized.capacity);
+    }
+    Some(resized)
+}
+
+/// Whether an error is the provider refusing a request for its rate
+fn is_rate_limited(error: &Error) -> bool {
+    if let Error::ProviderRateLimited { .. } = error {
+        return true;
+    }
+    let message = error.to_string().to_lowercase();
+    message.contains("429") || message.contains("rate limit") || message.contains("resource_exhausted")
+}
+
+/// Rate limiters by provider name
+pub struct RateLimits {
+    limiters: Mutex<HashMap<String, Arc<RateLimiter>>>,
+    counter: TokenCounter,
+}
+
+impl RateLimits {
+    pub fn new(config: &HashMap<String, ProviderRateLimit>, context: &ContextConfig) -> Self {
+        let limiters = config
+            .iter()
+            .map(|(name, limit)| (name.clone(), Arc::new(RateLimiter::new(limit))))
+            .collect();
+        Self {
+            limiters: Mutex::new(limiters),
+            counter: TokenCounter::new(context.tokenizer, context.chars_per_token),
+        }
+    }
+
+    /// Limiter shared by every provider called `name`
+    pub fn limiter(&self, name: &str) -> Arc<RateLimiter> {
+        let mut limiters = self.limiters.lock();
+        let limiter = limiters
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(RateLimiter::new(&ProviderRateLimit::default())));
+        Arc::clone(limiter)
+    }
+
+    /// Names of the limited providers
+    pub fn providers(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .limiters
+            .lock()
+            .iter()
+            .filter(|(_, limiter)| limiter.is_limited())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Apply changed limits (providers not in `config` become unlimited)
+    pub fn reconfigure(&self, config: &HashMap<String, ProviderRateLimit>) {
+        let mut limiters = self.limiters.lock();
+        for name in config.keys() {
+            limiters
+                .entry(name.clone())
+                .or_insert_with(|| Arc::new(RateLimiter::new(&ProviderRateLimit::default())));
+        }
+        for (name, limiter) in limiters.iter() {
+            limiter.set_limit(&config.get(name).copied().unwrap_or_default());
+        }
+    }
+
+    /// Wrap an LLM provider in its limiter
+    pub fn limit_llm(&self, inner: Arc<dyn LlmProvider>) -> Arc<dyn LlmProvider> {
+        let limiter = self.limiter(inner.name());
+        Arc::new(RateLimitedLlm { inner, limiter, counter: self.counter })
+    }
+
+    /// Wrap an embedding provider in its limiter
+    pub fn limit_embedder(&self, inner: Arc<dyn EmbeddingProvider>) -> Arc<dyn EmbeddingProvider> {
+        let limiter = self.limiter(inner.name());
+        Arc::new(RateLimitedEmbedder { inner, limiter, counter: self.counter })
+    }
+
+    /// Wrap a vision provider in its limiter
+    pub fn limit_vision(&self, inner: Arc<dyn VisionProvider>) -> Arc<dyn VisionProvider> {
+        let limiter = self.limiter(inner.name());
+        Arc::new(RateLimitedVision { inner, limiter, counter: self.counter })
+    }
+}
+
+/// Run a call within the limiter's budget, counting its tokens only when
+/// the provider is limited
+async fn limited<T>(
+    limiter: &RateLimiter,
+    tokens: impl FnOnce() -> u64,
+    call: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    if !limiter.is_limited() {
+        return call.await;
+    }
+    limiter.acquire(tokens()).await;
+    let result = call.await;
+    if let Err(e) = &result {
+        if is_rate_limited(e) {
+            limiter.back_off();
+        }
+    }
+    result
+}
+
+/// LLM provider wrapper that waits for its provider's rate limit
+pub struct RateLimitedLlm {
+    inner: Arc<dyn LlmProvider>,
+    limiter: Arc<RateLimiter>,
+    counter: TokenCounter,
+}
+
+impl RateLimitedLlm {
+    fn count(&self, texts: &[&str]) -> u64 {
+        texts.iter().map(|text| self.counter.count(text) as u64).sum()
+    }
+
+    async fn call(&self, input: &[&str], call: impl std::future::Future<Output = Result<String>>) -> Result<String> {
+        let output = limited(&self.limiter, || self.count(input), call).await?;
+        if self.limiter.is_limited() {
+            self.limiter.charge(self.count(&[&output]));
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RateLimitedLlm {
+    async fn generate_answer(&self, question: &str, context: &str, citations: &[Citation]) -> Result<String> {
+        self.call(&[question, context], self.inner.generate_answer(question, context, citations))
+            .await
+    }
+
+    async fn generate_with_learning(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<String> {
+        let examples: Vec<&str> = past_qa.iter().flat_map(|(q, a)| [q.as_str(), a.as_str()]).collect();
+        let input = [&[question, context][..], &examples[..]].concat();
+        self.call(&input, self.inner.generate_with_learning(question, context, citations, past_qa))
+            .await
+    }
+
+    async fn generate_cited(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<GeneratedAnswer> {
+        let examples: Vec<&str> = past_qa.iter().flat_map(|(q, a)| [q.as_str(), a.as_str()]).collect();
+        let input = [&[question, context][..], &examples[..]].concat();
+        let call = self.inner.generate_cited(question, context, citations, past_qa);
+        let answer = limited(&self.limiter, || self.count(&input), call).await?;
+        if self.limiter.is_limited() {
+            self.limiter.charge(self.count(&[&answer.text]));
+        }
+        Ok(answer)
+    }
+
+    fn answer_prompt(&self, question: &str, context: &str, citations: &[Citation], past_qa: &[(String, String)]) -> String {
+        self.inner.answer_prompt(question, context, citations, past_qa)
+    }
+
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        self.call(&[prompt], self.inner.complete(prompt)).await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+/// Embedding provider wrapper that waits for its provider's rate limit
+///
+/// A batch is one request of all its texts' tokens.
+pub struct RateLimitedEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    limiter: Arc<RateLimiter>,
+    counter: TokenCounter,
+}
+
+impl RateLimitedEmbedder {
+    fn count<'a>(&self, texts: impl IntoIterator<Item = &'a str>) -> u64 {
+        texts.into_iter().map(|text| self.counter.count(text) as u64).sum()
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RateLimitedEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        limited(&self.limiter, || self.count([text]), self.inner.embed(text)).await
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let tokens = || self.count(texts.iter().map(String::as_str));
+        limited(&self.limiter, tokens, self.inner.embed_batch(texts)).await
+    }
+
+    async fn embed_parts(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        limited(&self.limiter, || self.count([text]), self.inner.embed_parts(text)).await
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.inner.count_tokens(text)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// Vision provider wrapper that waits for its provider's rate limit
+pub struct RateLimitedVision {
+    inner: Arc<dyn VisionProvider>,
+    limiter: Arc<RateLimiter>,
+    counter: TokenCounter,
+}
+
+#[async_trait]
+impl VisionProvider for RateLimitedVision {
+    async fn describe_image(&self, image: &[u8], mime_type: &str, prompt: &str) -> Result<String> {
+        let tokens = || self.counter.count(prompt) as u64 + IMAGE_TOKENS;
+        let caption = limited(&self.limiter, tokens, self.inner.describe_image(image, mime_type, prompt)).await?;
+        if self.limiter.is_limited() {
+            self.limiter.charge(self.counter.count(&caption) as u64);
+        }
+        Ok(caption)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_refill_over_the_minute() {
+        let start = Instant::now();
+        let mut state = LimiterState {
+            requests: Some(Bucket::per_minute(2)),
+            tokens: Some(Bucket::per_minute(600)),
+            updated: start,
+        };
+        let mut take = |secs: u64, tokens: u64| {
+            state
+                .try_take(start + Duration::from_secs(secs), tokens)
+                .map(|wait| wait.as_secs_f64().round() as u64)
+        };
+
+        assert_eq!(take(0, 100), None);
+        // Out of tokens before requests: 500 left, 10 per second
+        assert_eq!(take(0, 700), Some(10));
+        assert_eq!(take(10, 700), None);
+        // Requests spent, one back every 30 seconds
+        assert_eq!(take(20, 0), Some(10));
+        // Still paying back the oversized request
+        assert_eq!(take(70, 600), Some(10));
+    }
+}
@@ -0,0 +1,296 @@
+//! Coalescing of concurrent embed requests into batches
+//!
+//! `EmbeddingScheduler` queues single-text requests from all callers and
+//! sends them to the wrapped provider with `embed_batch`. A batch is sent
+//! once it holds `max_batch_size` texts or its oldest request has waited
+//! `max_latency_ms`; at most `max_concurrent_batches` are in flight, and
+//! requests arriving meanwhile fill the next batch.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::time::Instant;
+
+use crate::config::EmbeddingBatchingConfig;
+use crate::error::{Error, Result};
+
+use super::embedding::EmbeddingProvider;
+
+/// A queued text and where its vector goes
+struct EmbedRequest {
+    text: String,
+    queued_at: Instant,
+    reply: oneshot::Sender<Result<Vec<f32>>>,
+}
+
+/// Embedding provider wrapper that batches concurrent requests
+pub struct EmbeddingScheduler {
+    inner: Arc<dyn EmbeddingProvider>,
+    sender: mpsc::UnboundedSender<EmbedRequest>,
+    stats: Arc<SchedulerStats>,
+}
+
+impl EmbeddingScheduler {
+    /// Wrap a provider and start the batching task
+    ///
+    /// Schedulers sharing `stats` report together. The task stops when the
+    /// scheduler is dropped.
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, config: &EmbeddingBatchingConfig, stats: Arc<SchedulerStats>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(Arc::clone(&inner), config.clone(), receiver, Arc::clone(&stats)));
+        Self { inner, sender, stats }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for EmbeddingScheduler {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let (reply, response) = oneshot::channel();
+        let request = EmbedRequest {
+            text: text.to_string(),
+            queued_at: Instant::now(),
+            reply,
+        };
+        self.stats.queue_depth.fetch_add(1, Ordering::Relaxed);
+        if self.sender.send(request).is_err() {
+            self.stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            return Err(Error::Embedding("Embedding scheduler stopped".to_string()));
+        }
+        response
+            .await
+            .map_err(|_| Error::Embedding("Embedding scheduler dropped the request".to_string()))?
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        // Queued one by one, so they can share batches with other callers
+        try_join_all(texts.iter().map(|text| self.embed(text))).await
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.inner.count_tokens(text)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// Wrap a provider in a scheduler when batching is enabled
+pub fn schedule(
+    provider: Arc<dyn EmbeddingProvider>,
+    config: &EmbeddingBatchingConfig,
+    stats: &Arc<SchedulerStats>,
+) -> Arc<dyn EmbeddingProvider> {
+    if config.enabled {
+        Arc::new(EmbeddingScheduler::new(provider, config, Arc::clone(stats)))
+    } else {
+        provider
+    }
+}
+
+/// Collect queued requests into batches and send them
+async fn run(
+    inner: Arc<dyn EmbeddingProvider>,
+    config: EmbeddingBatchingConfig,
+    mut receiver: mpsc::UnboundedReceiver<EmbedRequest>,
+    stats: Arc<SchedulerStats>,
+) {
+    let max_batch_size = config.max_batch_size.max(1);
+    let max_latency = Duration::from_millis(config.max_latency_ms);
+    let permits = Arc::new(Semaphore::new(config.max_concurrent_batches.max(1)));
+
+    while let Some(first) = receiver.recv().await {
+        let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+            break;
+        };
+
+        // Requests that queued while waiting for a free slot, then whatever
+        // arrives before the oldest one has waited long enough
+        let deadline = first.queued_at + max_latency;
+        let mut batch = vec![first];
+        while batch.len() < max_batch_size {
+            match receiver.try_recv() {
+                Ok(request) => batch.push(request),
+                Err(_) => match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(request)) => batch.push(request),
+                    _ => break,
+                },
+            }
+        }
+        stats.queue_depth.fetch_sub(batch.len(), Ordering::Relaxed);
+
+        let inner = Arc::clone(&inner);
+        let stats = Arc::clone(&stats);
+        tokio::spawn(async move {
+            stats.in_flight.fetch_add(1, Ordering::Relaxed);
+            let started = Instant::now();
+            let texts: Vec<String> = batch.iter().map(|r| r.text.clone()).collect();
+            let result = inner.embed_batch(&texts).await;
+            stats.record(&batch, started, result.is_ok());
+            stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+            drop(permit);
+
+            match result {
+                Ok(vectors) if vectors.len() == batch.len() => {
+                    for (request, vector) in batch.into_iter().zip(vectors) {
+                        let _ = request.reply.send(Ok(vector));
+                    }
+                }
+                Ok(vectors) => {
+                    let message = format!("Provider returned {} vectors for {} texts", vectors.len(), batch.len());
+                    for request in batch {
+                        let _ = request.reply.send(Err(Error::Embedding(message.clone())));
+                    }
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    for request in batch {
+                        let _ = request.reply.send(Err(Error::Embedding(message.clone())));
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Scheduler counters, shared by the schedulers of all embedders
+#[derive(Debug, Default)]
+pub struct SchedulerStats {
+    queue_depth: AtomicUsize,
+    in_flight: AtomicUsize,
+    batches: AtomicU64,
+    failed_batches: AtomicU64,
+    requests: AtomicU64,
+    max_batch_size: AtomicUsize,
+    queue_wait_us: AtomicU64,
+    max_queue_wait_us: AtomicU64,
+    batch_latency_us: AtomicU64,
+}
+
+impl SchedulerStats {
+    fn record(&self, batch: &[EmbedRequest], started: Instant, ok: bool) {
+        let waited: u64 = batch.iter().map(|r| micros(started - r.queued_at)).sum();
+        let longest = batch.iter().map(|r| micros(started - r.queued_at)).max().unwrap_or(0);
+
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.failed_batches.fetch_add(1, Ordering::Relaxed);
+        }
+        self.requests.fetch_add(batch.len() as u64, Ordering::Relaxed);
+        self.max_batch_size.fetch_max(batch.len(), Ordering::Relaxed);
+        self.queue_wait_us.fetch_add(waited, Ordering::Relaxed);
+        self.max_queue_wait_us.fetch_max(longest, Ordering::Relaxed);
+        self.batch_latency_us.fetch_add(micros(started.elapsed()), Ordering::Relaxed);
+    }
+
+    /// Current counters and averages
+    pub fn snapshot(&self) -> SchedulerMetrics {
+        let batches = self.batches.load(Ordering::Relaxed);
+        let requests = self.requests.load(Ordering::Relaxed);
+        let per = |total: u64, count: u64| if count == 0 { 0.0 } else { total as f64 / count as f64 };
+
+        SchedulerMetrics {
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            in_flight_batches: self.in_flight.load(Ordering::Relaxed),
+            batches,
+            failed_batches: self.failed_batches.load(Ordering::Relaxed),
+            requests,
+            avg_batch_size: per(requests, batches),
+            max_batch_size: self.max_batch_size.load(Ordering::Relaxed),
+            avg_queue_wait_ms: per(self.queue_wait_us.load(Ordering::Relaxed), requests) / 1000.0,
+            max_queue_wait_ms: self.max_queue_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            avg_batch_latency_ms: per(self.batch_latency_us.load(Ordering::Relaxed), batches) / 1000.0,
+        }
+    }
+}
+
+/// Embedding scheduler metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulerMetrics {
+    /// Requests waiting for a batch
+    pub queue_depth: usize,
+    /// Batches being embedded
+    pub in_flight_batches: usize,
+    /// Batches sent since startup
+    pub batches: u64,
+    /// Batches the provider failed
+    pub failed_batches: u64,
+    /// Texts embedded since startup
+    pub requests: u64,
+    pub avg_batch_size: f64,
+    pub max_batch_size: usize,
+    /// Time from queueing to the batch being sent
+    pub avg_queue_wait_ms: f64,
+    pub max_queue_wait_ms: f64,
+    /// Provider time per batch
+    pub avg_batch_latency_ms: f64,
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns each text's length, records the batch sizes it was called with
+    struct RecordingEmbedder {
+        batches: parking_lot::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for RecordingEmbedder {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32])
+        }
+        async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.batches.lock().push(texts.len());
+            Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+        }
+        fn dimensions(&self) -> usize {
+            1
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        fn name(&self) -> &str {
+            "recording"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesces_requests() {
+        let inner = Arc::new(RecordingEmbedder { batches: parking_lot::Mutex::new(Vec::new()) });
+        let config = EmbeddingBatchingConfig {
+            enabled: true,
+            max_batch_size: 4,
+            max_latency_ms: 50,
+            max_concurrent_batches: 1,
+        };
+        let stats = Arc::new(SchedulerStats::default());
+        let scheduler = EmbeddingScheduler::new(inner.clone(), &config, stats.clone());
+
+        let texts: Vec<String> = (1..=6).map(|n| "x".repeat(n)).collect();
+        let vectors = try_join_all(texts.iter().map(|t| scheduler.embed(t))).await.unwrap();
+        assert_eq!(vectors, (1..=6).map(|n| vec![n as f32]).collect::<Vec<_>>());
+
+        assert_eq!(*inner.batches.lock(), vec![4, 2]);
+        let metrics = stats.snapshot();
+        assert_eq!((metrics.batches, metrics.requests, metrics.queue_depth), (2, 6, 0));
+        assert_eq!(metrics.max_batch_size, 4);
+    }
+}
@@ -0,0 +1,752 @@
+//! Token usage, cost tracking and budgets
+//!
+//! `MeteredLlm` and `MeteredEmbedder` wrap providers and count the tokens of
+//! each successful call. Not every backend reports usage, so tokens are
+//! counted with the `context` tokenizer; generation input is the question and
+//! context, without the prompt template around them. Calls are attributed to
+//! the collection and principal of the `UsageScope` they run in (queries set
+//! one, ingestion knows its collection, background tasks run unscoped).
+//! Counts are buffered and added to the daily totals in SQLite every
+//! `usage.flush_interval_secs`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::{BudgetAction, BudgetConfig, BudgetScope, ContextConfig, ModelPricing, UsageConfig};
+use crate::error::{Error, Result};
+use crate::generation::packing::TokenCounter;
+use crate::storage::FileRegistryDb;
+use crate::types::response::Citation;
+
+use super::embedding::EmbeddingProvider;
+use super::llm::{GeneratedAnswer, LlmProvider};
+
+/// List prices in USD per million tokens (Vertex AI embeddings are priced per
+/// character, converted at about four characters per token)
+const DEFAULT_PRICING: &[(&str, f64, f64)] = &[
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-flash-lite", 0.10, 0.40),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gpt-4o", 2.50, 10.0),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("text-embedding-3-small", 0.02, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0),
+    ("text-embedding-005", 0.10, 0.0),
+    ("text-multilingual-embedding-002", 0.10, 0.0),
+];
+
+/// Context cache storage list prices in USD per million tokens and hour
+const DEFAULT_CACHE_STORAGE_PRICING: &[(&str, f64)] = &[
+    ("gemini-2.5-pro", 4.50),
+    ("gemini-2.5-flash", 1.0),
+    ("gemini-2.5-flash-lite", 1.0),
+    ("gemini-2.0-flash", 1.0),
+];
+
+/// Kind of a metered call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UsageKind {
+    Embed,
+    Generate,
+    /// Context cache storage
+    Cache,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Embed => "embed",
+            UsageKind::Generate => "generate",
+            UsageKind::Cache => "cache",
+        }
+    }
+}
+
+tokio::task_local! {
+    static SCOPE: UsageScope;
+}
+
+/// Collection and principal that calls are attributed to
+#[derive(Debug, Clone, Default)]
+pub struct UsageScope {
+    pub collection: Option<String>,
+    pub principal: Option<String>,
+}
+
+impl UsageScope {
+    pub fn new(collection: Option<&str>, principal: Option<&str>) -> Self {
+        Self {
+            collection: collection.map(str::to_string),
+            principal: principal.map(str::to_string),
+        }
+    }
+
+    /// Run a future with calls attributed to this scope
+    ///
+    /// Tasks spawned by the future don't inherit the scope.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        SCOPE.scope(self, future).await
+    }
+
+    /// Scope of the current task (empty outside `run`)
+    pub fn current() -> Self {
+        SCOPE.try_with(Clone::clone).unwrap_or_default()
+    }
+}
+
+/// Usage totals of one day, provider, model, kind, collection and principal
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct UsageRecord {
+    /// UTC day (YYYY-MM-DD)
+    pub day: String,
+    pub provider: String,
+    pub model: String,
+    /// `embed`, `generate` or `cache`
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Principal id of the API key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Input tokens read from a context cache (part of `input_tokens`)
+    pub cached_tokens: u64,
+    /// Estimated cost in USD
+    pub cost_usd: f64,
+}
+
+impl UsageRecord {
+    fn add(&mut self, other: &UsageRecord) {
+        self.calls += other.calls;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    /// Group key of a budget scope (empty for global)
+    fn scope_key(&self, scope: BudgetScope) -> &str {
+        match scope {
+            BudgetScope::Global => "",
+            BudgetScope::Collection => self.collection.as_deref().unwrap_or(""),
+            BudgetScope::Principal => self.principal.as_deref().unwrap_or(""),
+        }
+    }
+}
+
+/// Row dimensions usage can be grouped by
+pub const USAGE_DIMENSIONS: &[&str] = &["day", "provider", "model", "kind", "collection", "principal"];
+
+/// Sum rows that agree on `dimensions`, clearing the other dimensions
+pub fn aggregate(rows: Vec<UsageRecord>, dimensions: &[&str]) -> Vec<UsageRecord> {
+    let keep = |name: &str| dimensions.contains(&name);
+    let mut grouped: Vec<UsageRecord> = Vec::new();
+    let mut index: HashMap<(String, String, String, String, Option<String>, Option<String>), usize> = HashMap::new();
+
+    for row in rows {
+        let key = UsageRecord {
+            day: if keep("day") { row.day.clone() } else { String::new() },
+            provider: if keep("provider") { row.provider.clone() } else { String::new() },
+            model: if keep("model") { row.model.clone() } else { String::new() },
+            kind: if keep("kind") { row.kind.clone() } else { String::new() },
+            collection: if keep("collection") { row.collection.clone() } else { None },
+            principal: if keep("principal") { row.principal.clone() } else { None },
+            ..Default::default()
+        };
+        let id = (
+            key.day.clone(),
+            key.provider.clone(),
+            key.model.clone(),
+            key.kind.clone(),
+            key.collection.clone(),
+            key.principal.clone(),
+        );
+        let slot = *index.entry(id).or_insert_with(|| {
+            grouped.push(key);
+            grouped.len() - 1
+        });
+        grouped[slot].add(&row);
+    }
+    grouped
+}
+
+/// Outcome of checking a query against the budgets
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetDecision {
+    Allow,
+    /// Answer with this generation model
+    Downgrade(String),
+    /// Refuse the query, with the reason
+    Reject(String),
+}
+
+/// Spending against one budget
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BudgetStatus {
+    #[schema(value_type = Object)]
+    pub budget: BudgetConfig,
+    /// Spent today and this month by the budget's key, or by the largest
+    /// spender for budgets applying to every collection or principal
+    pub spent_today_usd: f64,
+    pub spent_month_usd: f64,
+    /// Keys whose budget is spent (`""` for global budgets)
+    pub exceeded: Vec<String>,
+}
+
+/// Counts calls and checks budgets
+pub struct UsageTracker {
+    config: UsageConfig,
+    pricing: HashMap<String, ModelPricing>,
+    counter: TokenCounter,
+    database: Arc<FileRegistryDb>,
+    /// Counts not yet written to the database
+    pending: Mutex<HashMap<(String, String, String, UsageKind, Option<String>, Option<String>), UsageRecord>>,
+}
+
+impl UsageTracker {
+    pub fn new(config: &UsageConfig, context: &ContextConfig, database: Arc<FileRegistryDb>) -> Self {
+        let mut pricing: HashMap<String, ModelPricing> = DEFAULT_PRICING
+            .iter()
+            .map(|(model, input, output)| {
+                let storage = DEFAULT_CACHE_STORAGE_PRICING.iter().find(|(name, _)| name == model).map(|(_, price)| *price);
+                let price = ModelPricing {
+                    input_per_million: *input,
+                    output_per_million: *output,
+                    cache_storage_per_million_hour: storage,
+                    ..Default::default()
+                };
+                (model.to_string(), price)
+            })
+            .collect();
+        pricing.extend(config.pricing.iter().map(|(model, price)| (model.clone(), *price)));
+
+        Self {
+            config: config.clone(),
+            pricing,
+            counter: TokenCounter::new(context.tokenizer, context.chars_per_token),
+            database,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether usage is tracked
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Price of a model: an exact entry, else the longest entry the name
+    /// starts with (`gemini-2.5-flash-001`), else free
+    pub fn price(&self, model: &str) -> ModelPricing {
+        if let Some(price) = self.pricing.get(model) {
+            return *price;
+        }
+        self.pricing
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+            .unwrap_or_default()
+    }
+
+    /// Tokens in a text
+    pub fn count(&self, text: &str) -> u64 {
+        self.counter.count(text) as u64
+    }
+
+    /// Record a call, attributed to the current `UsageScope`
+    pub fn record(
+        &self,
+        provider: &str,
+        model: &str,
+        kind: UsageKind,
+        collection: Option<&str>,
+        input_tokens: u64,
+        output_tokens: u64,
+    ) {
+        self.record_cached(provider, model, kind, collection, input_tokens, 0, output_tokens);
+    }
+
+    /// Record a call of which `cached_tokens` input tokens were read from a
+    /// context cache, at the cached input price
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_cached(
+        &self,
+        provider: &str,
+        model: &str,
+        kind: UsageKind,
+        collection: Option<&str>,
+        input_tokens: u64,
+        cached_tokens: u64,
+        output_tokens: u64,
+    ) {
+        let price = self.price(model);
+        let cached_tokens = cached_tokens.min(input_tokens);
+        let cost = ((input_tokens - cached_tokens) as f64 * price.input_per_million
+            + cached_tokens as f64 * price.cached_input()
+            + output_tokens as f64 * price.output_per_million)
+            / 1_000_000.0;
+        self.add(provider, model, kind, collection, UsageRecord {
+            calls: 1,
+            input_tokens,
+            output_tokens,
+            cached_tokens,
+            cost_usd: cost,
+            ..Default::default()
+        });
+    }
+
+    /// Record storing `tokens` in a context cache for `ttl`
+    pub fn record_cache_storage(&self, provider: &str, model: &str, tokens: u64, ttl: std::time::Duration) {
+        let price = self.price(model).cache_storage_per_million_hour.unwrap_or(0.0);
+        let cost = tokens as f64 * price * (ttl.as_secs_f64() / 3600.0) / 1_000_000.0;
+        self.add(provider, model, UsageKind::Cache, None, UsageRecord {
+            calls: 1,
+            input_tokens: tokens,
+            cost_usd: cost,
+            ..Default::default()
+        });
+    }
+
+    /// Add counts to the buffered totals, attributed to the current `UsageScope`
+    fn add(&self, provider: &str, model: &str, kind: UsageKind, collection: Option<&str>, counts: UsageRecord) {
+        let scope = UsageScope::current();
+        let collection = collection.map(str::to_string).or(scope.collection);
+        let day = Utc::now().date_naive().to_string();
+
+        let key = (day.clone(), provider.to_string(), model.to_string(), kind, collection.clone(), scope.principal.clone());
+        let mut pending = self.pending.lock();
+        let record = pending.entry(key).or_insert_with(|| UsageRecord {
+            day,
+            provider: provider.to_string(),
+            model: model.to_string(),
+            kind: kind.as_str().to_string(),
+            collection,
+            principal: scope.principal,
+            ..Default::default()
+        });
+        record.add(&counts);
+    }
+
+    /// Write buffered counts to the database
+    pub fn flush(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let records: Vec<UsageRecord> = pending.values().cloned().collect();
+        if let Err(e) = self.database.add_usage(&records) {
+            // Keep them for the next flush
+            let mut current = self.pending.lock();
+            for (key, record) in pending {
+                match current.get_mut(&key) {
+                    Some(existing) => existing.add(&record),
+                    None => {
+                        current.insert(key, record);
+                    }
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Flush buffered counts every `flush_interval_secs` (does nothing when disabled)
+    pub fn spawn_flusher(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let tracker = Arc::clone(self);
+        let interval = std::time::Duration::from_secs(self.config.flush_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = tracker.flush() {
+                    tracing::warn!("Failed to save token usage: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Usage rows between two days (inclusive), buffered counts included
+    pub fn rows(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<UsageRecord>> {
+        self.flush()?;
+        self.database.usage_rows(&from.to_string(), &to.to_string())
+    }
+
+    /// Cost since a day by budget scope key, buffered counts included
+    fn spent_since(&self, since: NaiveDate, scope: BudgetScope) -> Result<HashMap<String, f64>> {
+        let since = since.to_string();
+        let mut spent = self.database.usage_cost_since(&since, scope)?;
+        for record in self.pending.lock().values().filter(|r| r.day >= since) {
+            *spent.entry(record.scope_key(scope).to_string()).or_default() += record.cost_usd;
+        }
+        Ok(spent)
+    }
+
+    /// Check a query on `collection` by `principal` against the budgets
+    ///
+    /// A spent `reject` budget wins over a spent `downgrade` one.
+    pub fn check_budget(&self, collection: Option<&str>, principal: Option<&str>) -> Result<BudgetDecision> {
+        if !self.is_enabled() || self.config.budgets.is_empty() {
+            return Ok(BudgetDecision::Allow);
+        }
+
+        let (today, month) = periods();
+        let mut decision = BudgetDecision::Allow;
+        for budget in &self.config.budgets {
+            let key = match (budget.scope, budget.key.as_deref()) {
+                (BudgetScope::Global, _) => "",
+                (BudgetScope::Collection, Some(key)) if collection == Some(key) => key,
+                (BudgetScope::Principal, Some(key)) if principal == Some(key) => key,
+                (BudgetScope::Collection, None) => match collection {
+                    Some(collection) => collection,
+                    None => continue,
+                },
+                (BudgetScope::Principal, None) => match principal {
+                    Some(principal) => principal,
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let Some(reason) = self.exceeded(budget, key, today, month)? else {
+                continue;
+            };
+            match (budget.action, budget.downgrade_model.as_ref()) {
+                (BudgetAction::Downgrade, Some(model)) => {
+                    if decision == BudgetDecision::Allow {
+                        decision = BudgetDecision::Downgrade(model.clone());
+                    }
+                }
+                _ => return Ok(BudgetDecision::Reject(reason)),
+            }
+        }
+        Ok(decision)
+    }
+
+    /// Why the budget is spent for `key`, if it is
+    fn exceeded(&self, budget: &BudgetConfig, key: &str, today: NaiveDate, month: NaiveDate) -> Result<Option<String>> {
+        let target = match budget.scope {
+            BudgetScope::Global => String::new(),
+            BudgetScope::Collection => format!(" of collection '{}'", key),
+            BudgetScope::Principal => format!(" of '{}'", key),
+        };
+        for (period, since, limit) in [("Daily", today, budget.daily_limit_usd), ("Monthly", month, budget.monthly_limit_usd)] {
+            let Some(limit) = limit else { continue };
+            let spent = self.spent_since(since, budget.scope)?.get(key).copied().unwrap_or(0.0);
+            if spent >= limit {
+                return Ok(Some(format!("{} budget{} of ${:.2} is spent (${:.2})", period, target, limit, spent)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Spending against every budget
+    pub fn budget_status(&self) -> Result<Vec<BudgetStatus>> {
+        let (today, month) = periods();
+        let mut statuses = Vec::with_capacity(self.config.budgets.len());
+        for budget in &self.config.budgets {
+            let daily = self.spent_since(today, budget.scope)?;
+            let monthly = self.spent_since(month, budget.scope)?;
+            let keys: Vec<&str> = match (budget.scope, budget.key.as_deref()) {
+                (BudgetScope::Global, _) => vec![""],
+                (_, Some(key)) => vec![key],
+                (_, None) => monthly.keys().map(String::as_str).filter(|k| !k.is_empty()).collect(),
+            };
+
+            let spent = |costs: &HashMap<String, f64>| {
+                keys.iter().map(|k| costs.get(*k).copied().unwrap_or(0.0)).fold(0.0, f64::max)
+            };
+            let over = |key: &&str| {
+                let over_daily = budget.daily_limit_usd.is_some_and(|l| daily.get(*key).copied().unwrap_or(0.0) >= l);
+                let over_monthly = budget.monthly_limit_usd.is_some_and(|l| monthly.get(*key).copied().unwrap_or(0.0) >= l);
+                over_daily || over_monthly
+            };
+
+            statuses.push(BudgetStatus {
+                budget: budget.clone(),
+                spent_today_usd: spent(&daily),
+                spent_month_usd: spent(&monthly),
+                exceeded: keys.iter().filter(|key| over(key)).map(|k| k.to_string()).collect(),
+            });
+        }
+        Ok(statuses)
+    }
+
+    /// Wrap an LLM provider so its calls are counted (unchanged when disabled)
+    pub fn meter_llm(self: &Arc<Self>, inner: Arc<dyn LlmProvider>) -> Arc<dyn LlmProvider> {
+        if self.is_enabled() {
+            Arc::new(MeteredLlm { inner, tracker: Arc::clone(self) })
+        } else {
+            inner
+        }
+    }
+
+    /// Wrap an embedding provider of `model` so its calls are counted for
+    /// `collection` (unchanged when disabled)
+    pub fn meter_embedder(
+        self: &Arc<Self>,
+        inner: Arc<dyn EmbeddingProvider>,
+        model: &str,
+        collection: Option<&str>,
+    ) -> Arc<dyn EmbeddingProvider> {
+        if self.is_enabled() {
+            Arc::new(MeteredEmbedder {
+                inner,
+                model: model.to_string(),
+                collection: collection.map(str::to_string),
+                tracker: Arc::clone(self),
+            })
+        } else {
+            inner
+        }
+    }
+}
+
+/// Today and the first day of this month (UTC)
+fn periods() -> (NaiveDate, NaiveDate) {
+    let today = Utc::now().date_naive();
+    (today, today.with_day(1).unwrap_or(today))
+}
+
+/// LLM provider wrapper that records token usage
+pub struct MeteredLlm {
+    inner: Arc<dyn LlmProvider>,
+    tracker: Arc<UsageTracker>,
+}
+
+impl MeteredLlm {
+    fn record(&self, input: &[&str], output: &str) {
+        let input_tokens = input.iter().map(|text| self.tracker.count(text)).sum();
+        let output_tokens = self.tracker.count(output);
+        self.tracker.record(self.inner.name(), self.inner.model(), UsageKind::Generate, None, input_tokens, output_tokens);
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MeteredLlm {
+    async fn generate_answer(&self, question: &str, context: &str, citations: &[Citation]) -> Result<String> {
+        let answer = self.inner.generate_answer(question, context, citations).await?;
+        self.record(&[question, context], &answer);
+        Ok(answer)
+    }
+
+    async fn generate_with_learning(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<String> {
+        let answer = self.inner.generate_with_learning(question, context, citations, past_qa).await?;
+        let examples: Vec<&str> = past_qa.iter().flat_map(|(q, a)| [q.as_str(), a.as_str()]).collect();
+        self.record(&[&[question, context][..], &examples[..]].concat(), &answer);
+        Ok(answer)
+    }
+
+    async fn generate_cited(
+        &self,
+        question: &str,
+        context: &str,
+        citations: &[Citation],
+        past_qa: &[(String, String)],
+    ) -> Result<GeneratedAnswer> {
+        let answer = self.inner.generate_cited(question, context, citations, past_qa).await?;
+        let examples: Vec<&str> = past_qa.iter().flat_map(|(q, a)| [q.as_str(), a.as_str()]).collect();
+        let input: u64 = [&[question, context][..], &examples[..]].concat().iter().map(|text| self.tracker.count(text)).sum();
+        // The cached context is reported by the provider, in its own tokens
+        let input = input.max(answer.cached_tokens);
+        let output = self.tracker.count(&answer.text);
+        self.tracker.record_cached(
+            self.inner.name(), self.inner.model(), UsageKind::Generate, None, input, answer.cached_tokens, output,
+        );
+        Ok(answer)
+    }
+
+    fn answer_prompt(&self, question: &str, context: &str, citations: &[Citation], past_qa: &[(String, String)]) -> String {
+        self.inner.answer_prompt(question, context, citations, past_qa)
+    }
+
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        let response = self.inner.complete(prompt).await?;
+        self.record(&[prompt], &response);
+        Ok(response)
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+/// Embedding provider wrapper that records token usage
+pub struct MeteredEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    model: String,
+    collection: Option<String>,
+    tracker: Arc<UsageTracker>,
+}
+
+impl MeteredEmbedder {
+    fn record(&self, texts: &[&str]) {
+        let tokens = texts.iter().map(|text| self.tracker.count(text)).sum();
+        self.tracker.record(self.inner.name(), &self.model, UsageKind::Embed, self.collection.as_deref(), tokens, 0);
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MeteredEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let vector = self.inner.embed(text).await?;
+        self.record(&[text]);
+        Ok(vector)
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let vectors = self.inner.embed_batch(texts).await?;
+        self.record(&texts.iter().map(String::as_str).collect::<Vec<_>>());
+        Ok(vectors)
+    }
+
+    async fn embed_parts(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        let vectors = self.inner.embed_parts(text).await?;
+        self.record(&[text]);
+        Ok(vectors)
+    }
+
+    fn count_tokens(&self, text: &str) -> Option<usize> {
+        self.inner.count_tokens(text)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// Turn a budget decision into the generation model to use
+///
+/// `Err` for spent `reject` budgets; `Some(model)` when downgraded.
+pub fn apply_budget(decision: BudgetDecision) -> Result<Option<String>> {
+    match decision {
+        BudgetDecision::Allow => Ok(None),
+        BudgetDecision::Downgrade(model) => Ok(Some(model)),
+        BudgetDecision::Reject(reason) => Err(Error::BudgetExceeded(reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TokenizerKind;
+
+    #[tokio::test]
+    async fn test_usage_and_budgets() {
+        let config: UsageConfig = toml::from_str(
+            r#"
+            enabled = true
+            [pricing]
+            "local-model" = { input_per_million = 1000000.0 }
+            [[budgets]]
+            scope = "collection"
+            daily_limit_usd = 5.0
+            action = "downgrade"
+            downgrade_model = "cheap"
+            [[budgets]]
+            scope = "principal"
+            key = "alice"
+            monthly_limit_usd = 8.0
+            "#,
+        )
+        .unwrap();
+        let context = ContextConfig {
+            tokenizer: TokenizerKind::Estimate,
+            chars_per_token: 1.0,
+            ..Default::default()
+        };
+        let tracker = UsageTracker::new(&config, &context, Arc::new(FileRegistryDb::in_memory().unwrap()));
+
+        assert_eq!(tracker.price("gemini-2.5-flash-001").input_per_million, 0.30);
+        assert_eq!(tracker.price("gpt-4o-mini").input_per_million, 0.15);
+        assert_eq!(tracker.price("llama3").input_per_million, 0.0);
+
+        // $1 per token
+        UsageScope::new(Some("legal"), Some("alice"))
+            .run(async { tracker.record("ollama", "local-model", UsageKind::Generate, None, 6, 0) })
+            .await;
+        tracker.flush().unwrap();
+        tracker.record("ollama", "local-model", UsageKind::Embed, Some("legal"), 0, 0);
+
+        assert_eq!(tracker.check_budget(Some("hr"), Some("bob")).unwrap(), BudgetDecision::Allow);
+        assert_eq!(
+            tracker.check_budget(Some("legal"), Some("bob")).unwrap(),
+            BudgetDecision::Downgrade("cheap".to_string())
+        );
+
+        UsageScope::new(Some("hr"), Some("alice"))
+            .run(async { tracker.record("ollama", "local-model", UsageKind::Generate, None, 3, 0) })
+            .await;
+        assert!(matches!(tracker.check_budget(Some("hr"), Some("alice")).unwrap(), BudgetDecision::Reject(_)));
+
+        let today = Utc::now().date_naive();
+        let rows = tracker.rows(today, today).unwrap();
+        assert_eq!(rows.len(), 3);
+        let by_collection = aggregate(rows, &["collection"]);
+        let legal = by_collection.iter().find(|r| r.collection.as_deref() == Some("legal")).unwrap();
+        assert_eq!((legal.calls, legal.input_tokens, legal.cost_usd), (2, 6, 6.0));
+    }
+
+    #[test]
+    fn test_cached_input_pricing() {
+        let config: UsageConfig = toml::from_str(
+            r#"
+            enabled = true
+            [pricing]
+            "local-model" = { input_per_million = 4000000.0, cache_storage_per_million_hour = 1000000.0 }
+            "#,
+        )
+        .unwrap();
+        let tracker = UsageTracker::new(&config, &ContextConfig::default(), Arc::new(FileRegistryDb::in_memory().unwrap()));
+        assert_eq!(tracker.price("gemini-2.5-pro").cache_storage_per_million_hour, Some(4.50));
+
+        // $4 per fresh token, $1 per cached one
+        tracker.record_cached("gemini", "local-model", UsageKind::Generate, None, 10, 8, 0);
+        // $1 per token and hour
+        tracker.record_cache_storage("gemini", "local-model", 8, std::time::Duration::from_secs(1800));
+
+        let today = Utc::now().date_naive();
+        let rows = tracker.rows(today, today).unwrap();
+        let generate = rows.iter().find(|r| r.kind == "generate").unwrap();
+        assert_eq!((generate.cached_tokens, generate.cost_usd), (8, 16.0));
+        let cache = rows.iter().find(|r| r.kind == "cache").unwrap();
+        assert_eq!(cache.cost_usd, 4.0);
+    }
+}
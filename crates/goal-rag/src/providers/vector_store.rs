@@ -1,9 +1,11 @@
 //! Vector store provider trait for storing and searching embeddings
 
 use async_trait::async_trait;
+use std::sync::Arc;
 use uuid::Uuid;
 use crate::error::Result;
 use crate::types::Chunk;
+use crate::types::query::StringSearchMode;
 use crate::types::response::StringSearchResult;
 
 /// Search result from vector store
@@ -11,8 +13,18 @@ use crate::types::response::StringSearchResult;
 pub struct VectorSearchResult {
     /// The matched chunk
     pub chunk: Chunk,
-    /// Similarity score (0.0 to 1.0, higher is more similar)
+    /// Similarity score (0.0 to 1.0, higher is more similar), including
+    /// the keyword and recency boosts applied during retrieval
     pub similarity: f32,
+    /// Similarity as returned by the search, before any boost
+    pub raw_similarity: f32,
+}
+
+impl VectorSearchResult {
+    /// Result scored by the search alone
+    pub fn new(chunk: Chunk, similarity: f32) -> Self {
+        Self { chunk, similarity, raw_similarity: similarity }
+    }
 }
 
 /// Trait for vector storage and similarity search
@@ -26,13 +38,19 @@ pub trait VectorStoreProvider: Send + Sync {
     async fn insert_chunk(&self, chunk: &Chunk) -> Result<()>;
 
     /// Insert multiple chunks (batch)
-    async fn insert_chunks(&self, chunks: &[Chunk]) -> Result<()> {
-        for chunk in chunks {
+    ///
+    /// The batch is shared so an implementation can hand it to a blocking
+    /// task without copying the chunks.
+    async fn insert_chunks(&self, chunks: Arc<[Chunk]>) -> Result<()> {
+        for chunk in chunks.iter() {
             self.insert_chunk(chunk).await?;
         }
         Ok(())
     }
 
+    /// Replace a stored chunk whose text was edited, with its new vectors
+    async fn update_chunk(&self, chunk: &Chunk) -> Result<()>;
+
     /// Search for similar chunks by embedding similarity
     async fn search(
         &self,
@@ -41,11 +59,16 @@ pub trait VectorStoreProvider: Send + Sync {
         document_filter: Option<&[Uuid]>,
     ) -> Result<Vec<VectorSearchResult>>;
 
-    /// Perform literal string search across all chunks
+    /// Perform string search across chunks
+    ///
+    /// With a collection, only chunks in it match, and exact searches use
+    /// that collection's analyzer.
     async fn string_search(
         &self,
         query: &str,
         limit: usize,
+        collection: Option<&str>,
+        mode: StringSearchMode,
     ) -> Result<Vec<StringSearchResult>>;
 
     /// Delete all chunks for a document
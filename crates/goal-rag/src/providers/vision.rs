@@ -0,0 +1,21 @@
+//! Vision provider trait for describing images
+
+use async_trait::async_trait;
+use crate::error::Result;
+
+/// Trait for multimodal models that describe images
+///
+/// Implementations:
+/// - `OllamaVision`: Local Ollama server (llava, bakllava, etc.)
+/// - `GeminiClient`: Google Vertex AI (gemini-2.5-flash, etc.)
+#[async_trait]
+pub trait VisionProvider: Send + Sync {
+    /// Describe an image following the prompt
+    async fn describe_image(&self, image: &[u8], mime_type: &str, prompt: &str) -> Result<String>;
+
+    /// Get provider name for logging
+    fn name(&self) -> &str;
+
+    /// Get the model being used
+    fn model(&self) -> &str;
+}
@@ -0,0 +1,335 @@
+//! Language-specific text analyzers for lexical (FTS) search
+//!
+//! SQLite's default tokenizer only splits on punctuation and whitespace, which
+//! works for English but not for German compounds ("Donaudampfschiff") or
+//! Japanese text (no word separators). Collections can pick an analyzer that
+//! turns text into search terms before they reach FTS, and the same analyzer
+//! is applied to queries so both sides agree on what a term is.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::config::{AnalyzerConfig, AnalyzerKind, AnalyzersConfig};
+use crate::error::{Error, Result};
+
+/// Linking elements allowed between compound parts (German "Fugenelemente")
+const LINKING_ELEMENTS: &[&str] = &["s", "es", "n", "en"];
+
+/// Text analyzer producing FTS terms for indexing and querying
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    kind: AnalyzerKind,
+    ngram_size: usize,
+    dictionary: HashSet<String>,
+    min_subword_len: usize,
+}
+
+impl Analyzer {
+    /// Standard analyzer (SQLite unicode61 tokenization, no extra terms)
+    pub fn standard() -> Self {
+        Self {
+            kind: AnalyzerKind::Standard,
+            ngram_size: 2,
+            dictionary: HashSet::new(),
+            min_subword_len: 3,
+        }
+    }
+
+    /// Build an analyzer from configuration, loading the compound dictionary if set
+    pub fn from_config(config: &AnalyzerConfig) -> Result<Self> {
+        let mut dictionary: HashSet<String> = config
+            .compound_words
+            .iter()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        if let Some(ref path) = config.compound_dictionary {
+            let words = std::fs::read_to_string(path).map_err(|e| {
+                Error::Config(format!("Failed to read compound dictionary {:?}: {}", path, e))
+            })?;
+            dictionary.extend(
+                words
+                    .lines()
+                    .map(|w| w.trim().to_lowercase())
+                    .filter(|w| !w.is_empty() && !w.starts_with('#')),
+            );
+        }
+
+        if config.kind == AnalyzerKind::Compound && dictionary.is_empty() {
+            return Err(Error::Config(
+                "Compound analyzer requires compound_words or compound_dictionary".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            kind: config.kind.clone(),
+            ngram_size: config.ngram_size.max(1),
+            dictionary,
+            min_subword_len: config.min_subword_len.max(1),
+        })
+    }
+
+    /// Analyzer kind
+    pub fn kind(&self) -> &AnalyzerKind {
+        &self.kind
+    }
+
+    /// Whether this analyzer uses SQLite's own tokenization directly
+    pub fn is_standard(&self) -> bool {
+        self.kind == AnalyzerKind::Standard
+    }
+
+    /// Terms to index for a piece of text
+    pub fn index_terms(&self, text: &str) -> Vec<String> {
+        let mut terms = Vec::new();
+        for word in words(text) {
+            match self.kind {
+                AnalyzerKind::Standard => terms.push(word),
+                AnalyzerKind::Compound => {
+                    // Keep the full word so exact matches still rank well
+                    if let Some(parts) = self.split_compound(&word) {
+                        terms.push(word);
+                        terms.extend(parts);
+                    } else {
+                        terms.push(word);
+                    }
+                }
+                AnalyzerKind::Ngram => {
+                    if word.chars().any(is_cjk) {
+                        terms.extend(ngrams(&word, self.ngram_size));
+                    } else {
+                        terms.push(word);
+                    }
+                }
+            }
+        }
+        terms
+    }
+
+    /// Space-separated terms suitable for storing in the FTS terms column
+    pub fn index_text(&self, text: &str) -> String {
+        self.index_terms(text).join(" ")
+    }
+
+    /// Build an FTS5 MATCH expression for a user query
+    ///
+    /// Returns `None` if the query contains no searchable terms.
+    pub fn match_query(&self, query: &str) -> Option<String> {
+        if self.is_standard() {
+            // Whole query as a phrase (original string search behaviour)
+            let query = query.trim();
+            if query.is_empty() {
+                return None;
+            }
+            return Some(quote(query));
+        }
+
+        let clauses: Vec<String> = words(query)
+            .map(|word| match self.kind {
+                AnalyzerKind::Compound => match self.split_compound(&word) {
+                    // All parts must appear, in any order
+                    Some(parts) => parts.iter().map(|p| quote(p)).collect::<Vec<_>>().join(" AND "),
+                    None => quote(&word),
+                },
+                AnalyzerKind::Ngram if word.chars().any(is_cjk) => {
+                    if word.chars().count() < self.ngram_size {
+                        // Too short for a full n-gram - match any n-gram starting with it
+                        format!("{}*", quote(&word))
+                    } else {
+                        // Consecutive n-grams as a phrase match the original substring
+                        quote(&ngrams(&word, self.ngram_size).join(" "))
+                    }
+                }
+                _ => quote(&word),
+            })
+            .collect();
+
+        if clauses.is_empty() {
+            None
+        } else {
+            Some(clauses.join(" AND "))
+        }
+    }
+
+    /// Split a lowercase word into dictionary parts
+    ///
+    /// Returns `None` if the word is not a compound of at least two known parts.
+    fn split_compound(&self, word: &str) -> Option<Vec<String>> {
+        let chars: Vec<char> = word.chars().collect();
+        let parts = self.decompose(&chars)?;
+        if parts.len() < 2 {
+            return None;
+        }
+        Some(parts)
+    }
+
+    fn decompose(&self, chars: &[char]) -> Option<Vec<String>> {
+        let whole: String = chars.iter().collect();
+        if self.dictionary.contains(&whole) {
+            return Some(vec![whole]);
+        }
+
+        let min = self.min_subword_len;
+        if chars.len() < min * 2 {
+            return None;
+        }
+
+        // Prefer the longest leading part
+        for end in (min..=chars.len() - min).rev() {
+            let head: String = chars[..end].iter().collect();
+            if !self.dictionary.contains(&head) {
+                continue;
+            }
+
+            let rest = &chars[end..];
+            if let Some(tail) = self.decompose(rest) {
+                return Some(std::iter::once(head).chain(tail).collect());
+            }
+
+            for link in LINKING_ELEMENTS {
+                let link: Vec<char> = link.chars().collect();
+                if rest.len() >= link.len() + min && rest.starts_with(&link) {
+                    if let Some(tail) = self.decompose(&rest[link.len()..]) {
+                        return Some(std::iter::once(head).chain(tail).collect());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Analyzers for the default scope and each configured collection
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerRegistry {
+    default: Analyzer,
+    collections: HashMap<String, Analyzer>,
+}
+
+impl AnalyzerRegistry {
+    /// Build all analyzers from configuration
+    pub fn from_config(config: &AnalyzersConfig) -> Result<Self> {
+        let default = Analyzer::from_config(&config.default)?;
+        let mut collections = HashMap::new();
+        for (name, analyzer_config) in &config.collections {
+            let analyzer = Analyzer::from_config(analyzer_config)
+                .map_err(|e| Error::Config(format!("Analyzer for collection '{}': {}", name, e)))?;
+            tracing::info!("Collection '{}' uses {:?} analyzer", name, analyzer.kind());
+            collections.insert(name.clone(), analyzer);
+        }
+        Ok(Self { default, collections })
+    }
+
+    /// Analyzer for a collection, falling back to the default analyzer
+    pub fn for_collection(&self, collection: Option<&str>) -> &Analyzer {
+        collection
+            .and_then(|c| self.collections.get(c))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Lowercase alphanumeric words
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+/// Character n-grams of a word (the word itself if shorter than `n`)
+fn ngrams(word: &str, n: usize) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    if chars.len() <= n {
+        return vec![word.to_string()];
+    }
+    chars.windows(n).map(|w| w.iter().collect()).collect()
+}
+
+/// Whether a character belongs to a script written without word separators
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul syllables
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0xFF66..=0xFF9F   // Halfwidth Katakana
+    )
+}
+
+/// Quote a term or phrase for FTS5
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn german() -> Analyzer {
+        Analyzer::from_config(&AnalyzerConfig {
+            kind: AnalyzerKind::Compound,
+            compound_words: vec!["donau".into(), "dampf".into(), "schiff".into(), "arbeit".into(), "zeit".into()],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compound_split() {
+        let analyzer = german();
+        let terms = analyzer.index_terms("Das Donaudampfschiff fährt");
+        assert!(terms.contains(&"donaudampfschiff".to_string()));
+        assert!(terms.contains(&"schiff".to_string()));
+        assert!(terms.contains(&"dampf".to_string()));
+
+        // Linking "s" between parts
+        assert_eq!(
+            analyzer.split_compound("arbeitszeit"),
+            Some(vec!["arbeit".to_string(), "zeit".to_string()])
+        );
+        assert_eq!(analyzer.split_compound("schiff"), None);
+    }
+
+    #[test]
+    fn test_compound_query() {
+        let analyzer = german();
+        assert_eq!(
+            analyzer.match_query("Dampfschiff").as_deref(),
+            Some("\"dampf\" AND \"schiff\"")
+        );
+    }
+
+    #[test]
+    fn test_ngram() {
+        let analyzer = Analyzer::from_config(&AnalyzerConfig {
+            kind: AnalyzerKind::Ngram,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(analyzer.index_terms("東京都 Tokyo"), vec!["東京", "京都", "tokyo"]);
+        assert_eq!(analyzer.match_query("京都").as_deref(), Some("\"京都\""));
+        assert_eq!(analyzer.match_query("東").as_deref(), Some("\"東\"*"));
+    }
+
+    #[test]
+    fn test_registry_fallback() {
+        let mut config = AnalyzersConfig::default();
+        config.collections.insert(
+            "ja".to_string(),
+            AnalyzerConfig { kind: AnalyzerKind::Ngram, ..Default::default() },
+        );
+        let registry = AnalyzerRegistry::from_config(&config).unwrap();
+
+        assert_eq!(registry.for_collection(Some("ja")).kind(), &AnalyzerKind::Ngram);
+        assert!(registry.for_collection(Some("other")).is_standard());
+        assert!(registry.for_collection(None).is_standard());
+    }
+}
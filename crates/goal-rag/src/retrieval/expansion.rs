@@ -0,0 +1,214 @@
+//! Query expansion for retrieval
+//!
+//! Multi-query retrieval asks the LLM for paraphrases of the question, HyDE
+//! asks it for a hypothetical answer passage. Each text is embedded and
+//! searched separately, and the retrieved chunk sets are unioned before
+//! threshold filtering and ranking.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::providers::embedding::EmbeddingProvider;
+use crate::providers::llm::LlmProvider;
+use crate::providers::vector_store::{VectorSearchResult, VectorStoreProvider};
+
+/// Paraphrases generated for multi-query retrieval
+pub const PARAPHRASE_COUNT: usize = 3;
+
+/// How the question is turned into search queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RetrievalStrategy {
+    /// Embed the question as given
+    #[default]
+    Standard,
+    /// Question plus LLM-generated paraphrases
+    MultiQuery,
+    /// Question plus a hypothetical answer passage (HyDE)
+    Hyde,
+}
+
+impl RetrievalStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::MultiQuery => "multi_query",
+            Self::Hyde => "hyde",
+        }
+    }
+}
+
+/// Texts to embed and search for a question
+///
+/// The question itself always comes first. If the LLM call fails the
+/// question is searched alone.
+pub async fn expand(llm: &dyn LlmProvider, question: &str, strategy: RetrievalStrategy) -> Vec<String> {
+    let prompt = match strategy {
+        RetrievalStrategy::Standard => return vec![question.to_string()],
+        RetrievalStrategy::MultiQuery => paraphrase_prompt(question, PARAPHRASE_COUNT),
+        RetrievalStrategy::Hyde => hypothetical_answer_prompt(question),
+    };
+
+    let completion = match llm
+        .complete(&prompt)
+        .instrument(tracing::info_span!("expand_query", ?strategy))
+        .await
+    {
+        Ok(completion) => completion,
+        Err(e) => {
+            tracing::warn!("Query expansion ({:?}) failed, using question only: {}", strategy, e);
+            return vec![question.to_string()];
+        }
+    };
+
+    let mut queries = vec![question.to_string()];
+    match strategy {
+        RetrievalStrategy::MultiQuery => queries.extend(parse_paraphrases(&completion, question, PARAPHRASE_COUNT)),
+        _ => {
+            let passage = completion.trim();
+            if !passage.is_empty() {
+                queries.push(passage.to_string());
+            }
+        }
+    }
+
+    tracing::debug!("Expanded query into {} search texts", queries.len());
+    queries
+}
+
+/// Embed each search text
+pub async fn embed_queries(embedder: &dyn EmbeddingProvider, queries: &[String]) -> Result<Vec<Vec<f32>>> {
+    if queries.len() == 1 {
+        Ok(vec![embedder.embed(&queries[0]).instrument(tracing::info_span!("embed_query")).await?])
+    } else {
+        embedder
+            .embed_batch(queries)
+            .instrument(tracing::info_span!("embed_query", queries = queries.len()))
+            .await
+    }
+}
+
+/// Search each query embedding, unioning the results
+///
+/// Each search returns up to `limit` results. Chunks found by several
+/// searches keep their best similarity. Results are sorted by similarity.
+pub async fn search_union(
+    store: &dyn VectorStoreProvider,
+    embeddings: &[Vec<f32>],
+    limit: usize,
+    document_filter: Option<&[Uuid]>,
+) -> Result<Vec<VectorSearchResult>> {
+    let searches = embeddings
+        .iter()
+        .map(|embedding| store.search(embedding, limit, document_filter));
+    let result_sets = join_all(searches)
+        .instrument(tracing::info_span!("retrieve", top_k = limit, queries = embeddings.len()))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(union(result_sets))
+}
+
+/// Union result sets by chunk id, keeping the best similarity per chunk
+pub fn union(result_sets: Vec<Vec<VectorSearchResult>>) -> Vec<VectorSearchResult> {
+    let mut best: HashMap<Uuid, VectorSearchResult> = HashMap::new();
+    for result in result_sets.into_iter().flatten() {
+        match best.get_mut(&result.chunk.id) {
+            Some(existing) if existing.similarity >= result.similarity => {}
+            Some(existing) => *existing = result,
+            None => {
+                best.insert(result.chunk.id, result);
+            }
+        }
+    }
+
+    let mut results: Vec<VectorSearchResult> = best.into_values().collect();
+    results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    results
+}
+
+fn paraphrase_prompt(question: &str, count: usize) -> String {
+    format!(
+        "Rewrite the following question in {count} different ways, using different wording \
+        and synonyms while keeping its meaning. Write one rewrite per line, with no numbering \
+        and no other text.\n\nQuestion: {question}"
+    )
+}
+
+fn hypothetical_answer_prompt(question: &str) -> String {
+    format!(
+        "Write a short passage (3-5 sentences) from a document that would answer the following \
+        question. State facts plausibly even if you are unsure. Write only the passage.\n\n\
+        Question: {question}"
+    )
+}
+
+/// Extract paraphrases from a completion, one per line
+///
+/// Strips list markers and quotes, and drops blanks, repeats of the question
+/// and duplicates.
+fn parse_paraphrases(completion: &str, question: &str, count: usize) -> Vec<String> {
+    let mut paraphrases: Vec<String> = Vec::new();
+    for line in completion.lines() {
+        let line = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•'))
+            .trim()
+            .trim_matches('"')
+            .trim();
+        if line.is_empty()
+            || line.eq_ignore_ascii_case(question.trim())
+            || paraphrases.iter().any(|p| p.eq_ignore_ascii_case(line))
+        {
+            continue;
+        }
+        paraphrases.push(line.to_string());
+        if paraphrases.len() == count {
+            break;
+        }
+    }
+    paraphrases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Chunk, ChunkSource};
+
+    #[test]
+    fn test_parse_paraphrases() {
+        let completion = "1. How do I get my money back?\n\n2) \"What is the refund process?\"\n- What is the refund policy?\n* How do I get my money back?\nHow are refunds handled?\nExtra line";
+        let paraphrases = parse_paraphrases(completion, "What is the refund policy?", 3);
+        assert_eq!(
+            paraphrases,
+            vec![
+                "How do I get my money back?",
+                "What is the refund process?",
+                "How are refunds handled?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_union_keeps_best_similarity() {
+        let chunk = |content: &str| Chunk::new(Uuid::new_v4(), content.to_string(), ChunkSource::text("a.txt".into()), 0, content.len(), 0);
+        let a = chunk("a");
+        let b = chunk("b");
+        let result = |chunk: &Chunk, similarity| VectorSearchResult::new(chunk.clone(), similarity);
+
+        let merged = union(vec![
+            vec![result(&a, 0.5), result(&b, 0.4)],
+            vec![result(&b, 0.9)],
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].chunk.id, b.id);
+        assert_eq!(merged[0].similarity, 0.9);
+        assert_eq!(merged[1].chunk.id, a.id);
+    }
+}
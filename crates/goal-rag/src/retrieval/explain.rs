@@ -0,0 +1,309 @@
+//! Retrieval explanations for `debug: true` queries
+//!
+//! Unlike sampled traces, an explanation is built for a single query and
+//! returned with its answer: the search queries and their timings, every
+//! candidate with its vector, BM25 and final scores, the filters that
+//! removed candidates, which chunks were packed into the prompt (whole or
+//! trimmed) and which were left out, and the rendered prompt.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::generation::packing::PackedContext;
+use crate::providers::vector_store::VectorSearchResult;
+use crate::types::QueryRequest;
+
+/// What became of a candidate chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateOutcome {
+    /// In the prompt as retrieved
+    Packed,
+    /// In the prompt, trimmed to fit the token budget
+    Truncated,
+    /// Selected but left out to fit the token budget
+    Dropped,
+    /// Scored below the similarity threshold
+    BelowThreshold,
+    /// Above the threshold but not in the top k
+    BeyondTopK,
+    /// Removed by a filter (see `filtered_by`)
+    Filtered,
+    /// Retrieved, but no answer was generated
+    Unused,
+}
+
+/// A candidate chunk and its scores
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExplainCandidate {
+    pub chunk_id: Uuid,
+    pub document_id: Uuid,
+    pub filename: String,
+    pub page_number: Option<u32>,
+    /// Position in the vector search results (0-based)
+    pub retrieval_rank: usize,
+    /// Similarity from vector search
+    pub vector_score: f32,
+    /// BM25 score of the question against the chunk's keywords and entities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm25_score: Option<f64>,
+    /// Rerank score, when a reranker scored the chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+    /// Score the threshold and ranking used (vector score plus keyword and recency boosts)
+    pub score: f32,
+    pub outcome: CandidateOutcome,
+    /// Filter that removed the chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered_by: Option<String>,
+    /// Source number in the prompt (1-based)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_position: Option<usize>,
+}
+
+/// A filter applied during retrieval
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AppliedFilter {
+    /// Filter name (`documents`, `access`, `email`, `entities`, `similarity_threshold`, `top_k`)
+    pub name: String,
+    pub detail: String,
+    /// Candidates removed (absent for filters applied by the vector search itself)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<usize>,
+}
+
+/// Retrieval trace of a single query
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct QueryExplain {
+    /// Texts embedded and searched (the question, or its expansions)
+    pub search_queries: Vec<String>,
+    /// Time spent embedding the search queries
+    pub embedding_ms: u64,
+    /// Time spent in vector search
+    pub search_ms: u64,
+    pub top_k: usize,
+    pub similarity_threshold: f32,
+    /// Filters in the order they were applied
+    pub filters: Vec<AppliedFilter>,
+    /// Candidates in retrieval order
+    pub candidates: Vec<ExplainCandidate>,
+    /// Tokens of the packed context (absent when no token budget applied)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_tokens: Option<usize>,
+    /// Prompt sent to the model (absent for batched answer strategies or
+    /// with `traces.debug_prompt = "omit"`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Whether PII in the prompt was masked
+    pub prompt_redacted: bool,
+}
+
+impl QueryExplain {
+    /// Start an explanation for a query
+    pub fn new(request: &QueryRequest) -> Self {
+        Self {
+            top_k: request.top_k,
+            similarity_threshold: request.similarity_threshold,
+            ..Self::default()
+        }
+    }
+
+    /// Record the search queries and how long embedding and searching took
+    pub fn record_search(&mut self, queries: &[String], embedding_ms: u64, search_ms: u64) {
+        self.search_queries = queries.to_vec();
+        self.embedding_ms = embedding_ms;
+        self.search_ms = search_ms;
+    }
+
+    /// Record the candidates, in retrieval order
+    pub fn record_candidates(&mut self, results: &[VectorSearchResult]) {
+        self.candidates = results
+            .iter()
+            .enumerate()
+            .map(|(rank, r)| ExplainCandidate {
+                chunk_id: r.chunk.id,
+                document_id: r.chunk.document_id,
+                filename: r.chunk.source.filename.clone(),
+                page_number: r.chunk.source.page_number,
+                retrieval_rank: rank,
+                vector_score: r.similarity,
+                bm25_score: None,
+                rerank_score: None,
+                score: r.similarity,
+                outcome: CandidateOutcome::Unused,
+                filtered_by: None,
+                prompt_position: None,
+            })
+            .collect();
+    }
+
+    /// Record a filter the vector search applied
+    pub fn add_filter(&mut self, name: &str, detail: String) {
+        self.filters.push(AppliedFilter { name: name.to_string(), detail, removed: None });
+    }
+
+    /// Record a filter, marking the candidates missing from `remaining`
+    pub fn record_filter(&mut self, name: &str, detail: String, remaining: &[VectorSearchResult]) {
+        let mut removed = 0;
+        for candidate in self.pending_mut() {
+            if !remaining.iter().any(|r| r.chunk.id == candidate.chunk_id) {
+                candidate.outcome = CandidateOutcome::Filtered;
+                candidate.filtered_by = Some(name.to_string());
+                removed += 1;
+            }
+        }
+        self.filters.push(AppliedFilter { name: name.to_string(), detail, removed: Some(removed) });
+    }
+
+    /// Record BM25 keyword scores and the final scores after boosting
+    pub fn record_scores(&mut self, bm25: &HashMap<Uuid, f64>, results: &[VectorSearchResult]) {
+        for candidate in &mut self.candidates {
+            candidate.bm25_score = bm25.get(&candidate.chunk_id).copied();
+            if let Some(r) = results.iter().find(|r| r.chunk.id == candidate.chunk_id) {
+                candidate.score = r.similarity;
+            }
+        }
+    }
+
+    /// Record the chunks kept by the similarity threshold and top k
+    pub fn record_selection(&mut self, selected: &[VectorSearchResult]) {
+        let threshold = self.similarity_threshold;
+        let (mut below, mut beyond) = (0, 0);
+        for candidate in self.pending_mut() {
+            if selected.iter().any(|r| r.chunk.id == candidate.chunk_id) {
+                continue;
+            }
+            if candidate.score < threshold {
+                candidate.outcome = CandidateOutcome::BelowThreshold;
+                below += 1;
+            } else {
+                candidate.outcome = CandidateOutcome::BeyondTopK;
+                beyond += 1;
+            }
+        }
+        self.filters.push(AppliedFilter {
+            name: "similarity_threshold".to_string(),
+            detail: format!("score >= {}", threshold),
+            removed: Some(below),
+        });
+        self.filters.push(AppliedFilter {
+            name: "top_k".to_string(),
+            detail: format!("best {}", self.top_k),
+            removed: Some(beyond),
+        });
+    }
+
+    /// Record which selected chunks reached the prompt
+    ///
+    /// `context` is the selection as prepared for packing (parent windows,
+    /// translations); `packed` is `None` when every chunk was used whole.
+    pub fn record_packing(
+        &mut self,
+        selected: &[VectorSearchResult],
+        context: &[VectorSearchResult],
+        packed: Option<&PackedContext>,
+    ) {
+        let outcomes: Vec<(Option<usize>, CandidateOutcome)> = match packed {
+            None => (0..selected.len()).map(|i| (Some(i + 1), CandidateOutcome::Packed)).collect(),
+            Some(packed) => {
+                self.context_tokens = Some(packed.tokens);
+                let mut outcomes = vec![(None, CandidateOutcome::Dropped); selected.len()];
+                for (position, (&i, result)) in packed.kept.iter().zip(&packed.results).enumerate() {
+                    let trimmed = context.get(i).map_or(false, |c| c.chunk.content != result.chunk.content);
+                    let outcome = if trimmed { CandidateOutcome::Truncated } else { CandidateOutcome::Packed };
+                    if let Some(slot) = outcomes.get_mut(i) {
+                        *slot = (Some(position + 1), outcome);
+                    }
+                }
+                outcomes
+            }
+        };
+
+        for (r, (position, outcome)) in selected.iter().zip(outcomes) {
+            if let Some(candidate) = self.candidates.iter_mut().find(|c| c.chunk_id == r.chunk.id) {
+                candidate.outcome = outcome;
+                candidate.prompt_position = position;
+            }
+        }
+    }
+
+    /// Record the rendered prompt
+    pub fn record_prompt(&mut self, prompt: String, redacted: bool) {
+        self.prompt = Some(prompt);
+        self.prompt_redacted = redacted;
+    }
+
+    /// Candidates no filter or selection step has ruled out yet
+    fn pending_mut(&mut self) -> impl Iterator<Item = &mut ExplainCandidate> {
+        self.candidates.iter_mut().filter(|c| c.outcome == CandidateOutcome::Unused)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Chunk, ChunkSource};
+
+    fn result(content: &str, similarity: f32) -> VectorSearchResult {
+        let chunk = Chunk::new(Uuid::new_v4(), content.to_string(), ChunkSource::text("doc.txt".to_string()), 0, 0, 0);
+        VectorSearchResult::new(chunk, similarity)
+    }
+
+    #[test]
+    fn test_candidate_outcomes() {
+        let request = QueryRequest { top_k: 2, similarity_threshold: 0.5, ..QueryRequest::default() };
+        let mut explain = QueryExplain::new(&request);
+        let mut results = vec![
+            result("alpha", 0.9),
+            result("beta", 0.8),
+            result("gamma", 0.7),
+            result("delta", 0.6),
+            result("epsilon", 0.2),
+        ];
+        explain.record_candidates(&results);
+
+        // Access filter drops gamma
+        results.remove(2);
+        explain.record_filter("access", "readable documents".to_string(), &results);
+
+        let bm25 = HashMap::from([(results[1].chunk.id, 2.5)]);
+        explain.record_scores(&bm25, &results);
+
+        let mut selected = results.clone();
+        selected.retain(|r| r.similarity >= request.similarity_threshold);
+        selected.truncate(request.top_k);
+        explain.record_selection(&selected);
+
+        // Budget keeps alpha and trims beta
+        let mut trimmed = selected[1].clone();
+        trimmed.chunk.content = "be".to_string();
+        let packed = PackedContext {
+            results: vec![selected[0].clone(), trimmed],
+            kept: vec![0, 1],
+            truncated: 1,
+            dropped: 0,
+            tokens: 10,
+        };
+        explain.record_packing(&selected, &selected, Some(&packed));
+
+        let outcomes: Vec<CandidateOutcome> = explain.candidates.iter().map(|c| c.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                CandidateOutcome::Packed,
+                CandidateOutcome::Truncated,
+                CandidateOutcome::Filtered,
+                CandidateOutcome::BeyondTopK,
+                CandidateOutcome::BelowThreshold,
+            ]
+        );
+        assert_eq!(explain.candidates[1].bm25_score, Some(2.5));
+        assert_eq!(explain.candidates[1].prompt_position, Some(2));
+        assert_eq!(explain.candidates[2].filtered_by.as_deref(), Some("access"));
+        let removed: Vec<Option<usize>> = explain.filters.iter().map(|f| f.removed).collect();
+        assert_eq!(removed, vec![Some(1), Some(1), Some(1)]);
+        assert_eq!(explain.context_tokens, Some(10));
+    }
+}
@@ -0,0 +1,80 @@
+//! Query intent classification
+//!
+//! Queries are routed by what they ask for: documents by name go to the
+//! document registry, literal phrases to the string search and everything
+//! else to retrieval and generation. `QueryType::detect` settles most
+//! queries with heuristics; short phrases are the ambiguous case, and with
+//! `retrieval.intent_llm` the LLM is asked about them.
+
+use tracing::Instrument;
+
+use crate::providers::llm::LlmProvider;
+use crate::types::query::QueryType;
+
+/// Intent of a query, detected from its text
+///
+/// Falls back to the heuristic result when the LLM is not asked or gives
+/// no usable reply.
+pub async fn classify(llm: Option<&dyn LlmProvider>, query: &str) -> QueryType {
+    let detected = QueryType::detect(query);
+    let Some(llm) = llm.filter(|_| detected == QueryType::StringSearch) else {
+        return detected;
+    };
+
+    match llm
+        .complete(&intent_prompt(query))
+        .instrument(tracing::info_span!("classify_intent"))
+        .await
+    {
+        Ok(reply) => parse_intent(&reply).unwrap_or_else(|| {
+            tracing::debug!("Unusable intent reply {:?}, treating query as {:?}", reply.trim(), detected);
+            detected
+        }),
+        Err(e) => {
+            tracing::warn!("Intent classification failed, treating query as {:?}: {}", detected, e);
+            detected
+        }
+    }
+}
+
+fn intent_prompt(query: &str) -> String {
+    format!(
+        "A user typed the following into a document search box. Classify it with one word:\n\
+        - lookup: they want a document or file by its name\n\
+        - phrase: they want the places where these exact words appear\n\
+        - question: they want an answer or explanation drawn from the documents\n\
+        Reply with only the word.\n\nInput: {query}"
+    )
+}
+
+fn parse_intent(reply: &str) -> Option<QueryType> {
+    let word = reply
+        .split(|c: char| !c.is_alphabetic())
+        .find(|word| !word.is_empty())?
+        .to_lowercase();
+    match word.as_str() {
+        "lookup" => Some(QueryType::DocumentLookup),
+        "phrase" => Some(QueryType::StringSearch),
+        "question" => Some(QueryType::Question),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_parse_intents() {
+        assert_eq!(QueryType::detect("find the doc named Q3 budget"), QueryType::DocumentLookup);
+        assert_eq!(QueryType::lookup_target("Find the documents called \"Q3 budget\"?").as_deref(), Some("Q3 budget"));
+        assert_eq!(QueryType::lookup_target("budget-2024.xlsx").as_deref(), Some("budget-2024.xlsx"));
+        assert_eq!(QueryType::detect("find the document about travel expenses"), QueryType::Question);
+        assert_eq!(QueryType::detect("where is the file server configured?"), QueryType::Question);
+        assert_eq!(QueryType::detect("force majeure"), QueryType::StringSearch);
+
+        assert_eq!(parse_intent("Lookup."), Some(QueryType::DocumentLookup));
+        assert_eq!(parse_intent("  **question**"), Some(QueryType::Question));
+        assert_eq!(parse_intent("I am not sure"), None);
+    }
+}
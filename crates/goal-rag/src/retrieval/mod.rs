@@ -1,5 +1,16 @@
 //! Vector search and retrieval
 
+mod analyzer;
+pub mod expansion;
+pub mod explain;
+pub mod intent;
 mod search;
+pub mod similarity;
+pub mod string_search;
+pub mod trace;
 
+pub use analyzer::{Analyzer, AnalyzerRegistry};
+pub use expansion::RetrievalStrategy;
+pub use explain::QueryExplain;
 pub use search::{SearchResult, VectorStore};
+pub use trace::{RetrievalTrace, TraceCandidate};
@@ -1,6 +1,7 @@
 //! Vector store for chunk storage and search
 
 use std::collections::HashMap;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use uuid::Uuid;
 
 use ruvector_core::{VectorDB, VectorEntry, SearchQuery as CoreSearchQuery, DistanceMetric};
@@ -8,9 +9,13 @@ use ruvector_core::types::{DbOptions, HnswConfig};
 
 use crate::config::RagConfig;
 use crate::error::{Error, Result};
+use crate::storage::SpoolCipher;
 use crate::types::Chunk;
+use crate::types::document::FILTER_METADATA_KEYS;
 use crate::types::response::StringSearchResult;
 
+use super::similarity::{top_k_par, CosineQuery};
+
 /// Search result with chunk and similarity
 #[derive(Debug, Clone)]
 pub struct SearchResult {
@@ -39,8 +44,13 @@ pub struct VectorStore {
     dimensions: usize,
     /// Mapping from document IDs to chunk IDs for efficient deletion
     document_chunks: parking_lot::RwLock<HashMap<Uuid, Vec<String>>>,
+    /// Encrypts chunk text in the stored metadata (encryption at rest)
+    cipher: Option<SpoolCipher>,
 }
 
+/// Metadata key of the base64 AES-GCM encrypted chunk text
+const ENCRYPTED_CONTENT_KEY: &str = "content_encrypted";
+
 impl VectorStore {
     /// Create a new vector store
     pub fn new(config: &RagConfig) -> Result<Self> {
@@ -68,9 +78,50 @@ impl VectorStore {
             db,
             dimensions: config.embeddings.dimensions,
             document_chunks: parking_lot::RwLock::new(HashMap::new()),
+            cipher: None,
         })
     }
 
+    /// Encrypt the chunk text of entries inserted from now on
+    ///
+    /// Entries without encrypted text (inserted before encryption was
+    /// enabled) are still read; a reindex rewrites them encrypted.
+    pub fn with_cipher(mut self, cipher: Option<SpoolCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Cipher of the chunk text, if encryption at rest is enabled
+    pub fn cipher(&self) -> Option<&SpoolCipher> {
+        self.cipher.as_ref()
+    }
+
+    /// Metadata stored with a chunk's vectors
+    fn chunk_metadata(&self, chunk: &Chunk) -> Result<HashMap<String, serde_json::Value>> {
+        let mut metadata = chunk.to_vector_metadata();
+        if let Some(cipher) = &self.cipher {
+            metadata.remove("content");
+            let encrypted = cipher.encrypt(chunk.content.as_bytes())?;
+            metadata.insert(ENCRYPTED_CONTENT_KEY.to_string(), serde_json::json!(BASE64.encode(encrypted)));
+        }
+        Ok(metadata)
+    }
+
+    /// Chunk text of stored metadata, decrypting it if needed
+    fn metadata_content(&self, metadata: &HashMap<String, serde_json::Value>) -> Result<String> {
+        let Some(encrypted) = metadata.get(ENCRYPTED_CONTENT_KEY).and_then(|v| v.as_str()) else {
+            return Ok(metadata.get("content").and_then(|v| v.as_str()).unwrap_or("").to_string());
+        };
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            Error::Config("The vector index is encrypted but encryption is not configured".to_string())
+        })?;
+        let encrypted = BASE64
+            .decode(encrypted)
+            .map_err(|e| Error::StorageCorruption(format!("Encrypted chunk text is not base64: {}", e)))?;
+        String::from_utf8(cipher.decrypt(&encrypted)?)
+            .map_err(|_| Error::StorageCorruption("Decrypted chunk text is not UTF-8".to_string()))
+    }
+
     /// Insert a chunk into the vector store
     pub fn insert_chunk(&self, chunk: &Chunk) -> Result<()> {
         if chunk.embedding.is_empty() {
@@ -78,21 +129,35 @@ impl VectorStore {
         }
 
         let chunk_id = chunk.id.to_string();
+        let metadata = self.chunk_metadata(chunk)?;
 
         let entry = VectorEntry {
             id: Some(chunk_id.clone()),
             vector: chunk.embedding.clone(),
-            metadata: Some(chunk.to_vector_metadata()),
+            metadata: Some(metadata.clone()),
         };
 
         self.db.insert(entry).map_err(|e| Error::VectorDb(e.to_string()))?;
 
+        // Window vectors of an over-length chunk are extra entries for the same chunk
+        let mut entry_ids = vec![chunk_id.clone()];
+        for (i, sub_embedding) in chunk.sub_embeddings.iter().enumerate() {
+            let sub_id = format!("{}#{}", chunk_id, i + 1);
+            let entry = VectorEntry {
+                id: Some(sub_id.clone()),
+                vector: sub_embedding.clone(),
+                metadata: Some(metadata.clone()),
+            };
+            self.db.insert(entry).map_err(|e| Error::VectorDb(e.to_string()))?;
+            entry_ids.push(sub_id);
+        }
+
         // Track the document-to-chunk mapping
         let mut doc_chunks = self.document_chunks.write();
         doc_chunks
             .entry(chunk.document_id)
             .or_default()
-            .push(chunk_id);
+            .extend(entry_ids);
 
         Ok(())
     }
@@ -142,13 +207,49 @@ impl VectorStore {
             }
         }
 
+        // The filtered documents' chunks may rank below the candidates the
+        // index returned; score them all instead
+        if let Some(doc_ids) = document_filter {
+            if search_results.len() < top_k {
+                search_results = self.brute_force_search(query_embedding, top_k, doc_ids);
+            }
+        }
+
         // Sort by similarity and take top_k
-        search_results.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
+        search_results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+        // A chunk with window vectors scores as its best window (max-sim)
+        let mut seen = std::collections::HashSet::new();
+        search_results.retain(|r| seen.insert(r.chunk.id));
         search_results.truncate(top_k);
 
         Ok(search_results)
     }
 
+    /// Score every vector of the given documents against the query
+    fn brute_force_search(&self, query_embedding: &[f32], top_k: usize, document_ids: &[Uuid]) -> Vec<SearchResult> {
+        let entry_ids: Vec<String> = {
+            let doc_chunks = self.document_chunks.read();
+            document_ids.iter().filter_map(|id| doc_chunks.get(id)).flatten().cloned().collect()
+        };
+
+        let query = CosineQuery::new(query_embedding);
+        // Room for window vectors of the same chunk, deduplicated by the caller
+        top_k_par(&entry_ids, top_k * 2, |id| {
+            let entry = self.db.get(id).ok().flatten()?;
+            let metadata = entry.metadata?;
+            // Same scale as the index's cosine distance
+            let similarity = (1.0 + query.score(&entry.vector)) / 2.0;
+            Some((similarity, (id, metadata)))
+        })
+        .into_iter()
+        .filter_map(|(similarity, (id, metadata))| {
+            let chunk = self.metadata_to_chunk(id, &metadata).ok()?;
+            Some(SearchResult { chunk, similarity })
+        })
+        .collect()
+    }
+
     /// Delete all chunks for a document
     pub fn delete_by_document(&self, document_id: &Uuid) -> Result<usize> {
         // Get chunk IDs for this document from our tracking map
@@ -160,7 +261,10 @@ impl VectorStore {
         let mut deleted = 0;
 
         for chunk_id in &chunk_ids {
-            if self.db.delete(chunk_id).map_err(|e| Error::VectorDb(e.to_string()))? {
+            // Window vector entries ("<chunk>#<n>") are not counted as chunks
+            if self.db.delete(chunk_id).map_err(|e| Error::VectorDb(e.to_string()))?
+                && !chunk_id.contains('#')
+            {
                 deleted += 1;
             }
         }
@@ -174,6 +278,27 @@ impl VectorStore {
         Ok(deleted)
     }
 
+    /// A chunk with its vector and window vectors
+    pub fn get_chunk(&self, chunk_id: &Uuid) -> Result<Option<Chunk>> {
+        let id = chunk_id.to_string();
+        let Some(entry) = self.db.get(&id).map_err(|e| Error::VectorDb(e.to_string()))? else {
+            return Ok(None);
+        };
+        let Some(ref metadata) = entry.metadata else {
+            return Ok(None);
+        };
+
+        let mut chunk = self.metadata_to_chunk(&id, metadata)?;
+        chunk.embedding = entry.vector;
+        for i in 1.. {
+            match self.db.get(&format!("{}#{}", id, i)).map_err(|e| Error::VectorDb(e.to_string()))? {
+                Some(window) => chunk.sub_embeddings.push(window.vector),
+                None => break,
+            }
+        }
+        Ok(Some(chunk))
+    }
+
     /// Get chunk count
     pub fn len(&self) -> Result<usize> {
         self.db.len().map_err(|e| Error::VectorDb(e.to_string()))
@@ -184,8 +309,21 @@ impl VectorStore {
         Ok(self.len()? == 0)
     }
 
-    /// Delete a single chunk by ID
+    /// Delete a single chunk by ID (including its window vectors)
     pub fn delete_chunk(&self, chunk_id: &str) -> Result<bool> {
+        let sub_prefix = format!("{}#", chunk_id);
+        let sub_ids: Vec<String> = self
+            .document_chunks
+            .read()
+            .values()
+            .flatten()
+            .filter(|id| id.starts_with(&sub_prefix))
+            .cloned()
+            .collect();
+        for sub_id in &sub_ids {
+            self.db.delete(sub_id).map_err(|e| Error::VectorDb(e.to_string()))?;
+        }
+
         self.db.delete(chunk_id).map_err(|e| Error::VectorDb(e.to_string()))
     }
 
@@ -210,6 +348,10 @@ impl VectorStore {
 
         let mut chunks = Vec::with_capacity(all_results.len());
         for result in all_results {
+            // Window vectors share their chunk's metadata
+            if result.id.contains('#') {
+                continue;
+            }
             if let Some(ref metadata) = result.metadata {
                 match self.metadata_to_chunk(&result.id, metadata) {
                     Ok(chunk) => chunks.push(chunk),
@@ -231,6 +373,34 @@ impl VectorStore {
         Ok(chunks)
     }
 
+    /// Chunks stored with an all-zero vector (the placeholder older versions
+    /// indexed when embedding failed)
+    pub fn zero_vector_chunks(&self) -> Result<Vec<Chunk>> {
+        let total_count = self.len()?;
+        if total_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let all_results = self.db.search(CoreSearchQuery {
+            vector: vec![0.0; self.dimensions],
+            k: total_count,
+            filter: None,
+            ef_search: None,
+        }).map_err(|e| Error::VectorDb(e.to_string()))?;
+
+        Ok(all_results
+            .into_iter()
+            .filter(|r| !r.id.contains('#'))
+            .filter(|r| r.vector.as_ref().is_some_and(|v| v.iter().all(|x| *x == 0.0)))
+            .filter_map(|r| {
+                let metadata = r.metadata.as_ref()?;
+                self.metadata_to_chunk(&r.id, metadata)
+                    .map_err(|e| tracing::warn!("Failed to parse chunk metadata: {}", e))
+                    .ok()
+            })
+            .collect())
+    }
+
     /// Perform literal string search across all chunks
     ///
     /// Returns up to `limit` results sorted by match count (descending)
@@ -316,6 +486,7 @@ impl VectorStore {
                     page_number: m.chunk.source.page_number,
                     match_count: m.match_count,
                     match_positions: m.match_positions,
+                    highlighted_excerpt: self.highlight_matches(&preview, query),
                     highlighted_snippet: highlighted,
                     preview,
                 }
@@ -373,11 +544,7 @@ impl VectorStore {
             .unwrap_or("unknown")
             .to_string();
 
-        let content = metadata
-            .get("content")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+        let content = self.metadata_content(metadata)?;
 
         let chunk_index = metadata
             .get("chunk_index")
@@ -414,6 +581,11 @@ impl VectorStore {
             .and_then(|v| v.as_u64())
             .map(|l| l as u32);
 
+        let code_context = metadata
+            .get("code_context")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
         let file_type = metadata
             .get("file_type")
             .map(|v| serde_json::from_value(v.clone()).unwrap_or(crate::types::FileType::Unknown))
@@ -425,6 +597,19 @@ impl VectorStore {
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
+        let kind = metadata
+            .get("kind")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let time_range = match (
+            metadata.get("start_seconds").and_then(|v| v.as_f64()),
+            metadata.get("end_seconds").and_then(|v| v.as_f64()),
+        ) {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None,
+        };
+
         let source = crate::types::ChunkSource {
             filename,
             internal_filename,
@@ -437,9 +622,23 @@ impl VectorStore {
             row_range: None,
             line_start,
             line_end,
-            code_context: None,
+            code_context,
+            time_range,
+            kind,
         };
 
+        // Email fields, keywords, entities and the document title, author, tags and
+        // date are kept for query filters and citations
+        let chunk_metadata = FILTER_METADATA_KEYS
+            .iter()
+            .filter_map(|key| metadata.get(*key).map(|value| (key.to_string(), value.clone())))
+            .collect();
+
+        let parent_id = metadata
+            .get("parent_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| Uuid::parse_str(s).ok());
+
         Ok(Chunk {
             id: chunk_id,
             document_id,
@@ -449,7 +648,9 @@ impl VectorStore {
             char_start,
             char_end,
             chunk_index,
-            metadata: HashMap::new(),
+            metadata: chunk_metadata,
+            sub_embeddings: Vec::new(),
+            parent_id,
         })
     }
 }
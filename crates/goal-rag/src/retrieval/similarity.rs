@@ -0,0 +1,256 @@
+//! Vector similarity kernels
+//!
+//! Brute-force scoring (the Vertex overlay of fresh chunks, learned answers,
+//! semantic cache lookups) compares a query with every candidate vector, so
+//! the dot product is the hot loop. It runs with AVX2 and FMA when the CPU
+//! has them (detected at runtime), with NEON on aarch64 and as an unrolled
+//! scalar loop the compiler can auto-vectorize elsewhere. Vectors of different
+//! lengths are compared over the shorter one.
+//!
+//! `top_k_par` scores many candidates on the rayon pool, keeping the best `k`
+//! of each thread in a bounded heap instead of sorting every score.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use rayon::prelude::*;
+
+/// Dot product of `a` and `b`
+#[inline]
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is part of the aarch64 baseline
+        unsafe { dot_neon(a, b) }
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            // SAFETY: the CPU supports the features the function is compiled for
+            return unsafe { dot_avx2(a, b) };
+        }
+        dot_scalar(a, b)
+    }
+}
+
+/// Euclidean norm of `a`
+#[inline]
+pub fn norm(a: &[f32]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Cosine similarity of `a` and `b` (0 when either is all zeros)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    CosineQuery::new(a).score(b)
+}
+
+/// A query vector scored against many candidates, with its norm computed once
+pub struct CosineQuery<'a> {
+    vector: &'a [f32],
+    norm: f32,
+}
+
+impl<'a> CosineQuery<'a> {
+    pub fn new(vector: &'a [f32]) -> Self {
+        Self { vector, norm: norm(vector) }
+    }
+
+    /// Cosine similarity of the query and `candidate`
+    #[inline]
+    pub fn score(&self, candidate: &[f32]) -> f32 {
+        let candidate_norm = norm(candidate);
+        if self.norm == 0.0 || candidate_norm == 0.0 {
+            0.0
+        } else {
+            dot(self.vector, candidate) / (self.norm * candidate_norm)
+        }
+    }
+}
+
+/// The `k` best scoring candidates, best first
+///
+/// `score` returns `None` for candidates to leave out.
+pub fn top_k_par<C, T, F>(candidates: &[C], k: usize, score: F) -> Vec<(f32, T)>
+where
+    C: Sync,
+    T: Send,
+    F: Fn(&C) -> Option<(f32, T)> + Sync,
+{
+    candidates
+        .par_iter()
+        .fold(
+            || TopK::new(k),
+            |mut top, candidate| {
+                if let Some((score, item)) = score(candidate) {
+                    top.push(score, item);
+                }
+                top
+            },
+        )
+        .reduce(|| TopK::new(k), TopK::merge)
+        .into_sorted()
+}
+
+/// Bounded min-heap of the best `k` scores
+struct TopK<T> {
+    k: usize,
+    heap: BinaryHeap<Scored<T>>,
+}
+
+impl<T> TopK<T> {
+    fn new(k: usize) -> Self {
+        Self { k, heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)) }
+    }
+
+    fn push(&mut self, score: f32, item: T) {
+        if self.heap.len() < self.k {
+            self.heap.push(Scored { score, item });
+        } else if self.heap.peek().is_some_and(|worst| score > worst.score) {
+            self.heap.pop();
+            self.heap.push(Scored { score, item });
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for scored in other.heap {
+            self.push(scored.score, scored.item);
+        }
+        self
+    }
+
+    fn into_sorted(self) -> Vec<(f32, T)> {
+        // Ascending in the reversed order is best first
+        self.heap.into_sorted_vec().into_iter().map(|scored| (scored.score, scored.item)).collect()
+    }
+}
+
+/// Heap entry ordered so the lowest score is the greatest (on top of the heap)
+struct Scored<T> {
+    score: f32,
+    item: T,
+}
+
+impl<T> PartialEq for Scored<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score.total_cmp(&other.score) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Scored<T> {}
+
+impl<T> PartialOrd for Scored<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Scored<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score)
+    }
+}
+
+/// Portable dot product, unrolled into eight independent sums
+pub fn dot_scalar(a: &[f32], b: &[f32]) -> f32 {
+    let mut sums = [0.0f32; 8];
+    let mut a_chunks = a.chunks_exact(8);
+    let mut b_chunks = b.chunks_exact(8);
+    for (x, y) in (&mut a_chunks).zip(&mut b_chunks) {
+        for ((sum, x), y) in sums.iter_mut().zip(x).zip(y) {
+            *sum += x * y;
+        }
+    }
+    let tail: f32 = a_chunks.remainder().iter().zip(b_chunks.remainder()).map(|(x, y)| x * y).sum();
+    sums.iter().sum::<f32>() + tail
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn dot_avx2(a: &[f32], b: &[f32]) -> f32 {
+    use std::arch::x86_64::*;
+
+    let len = a.len().min(b.len());
+    let (pa, pb) = (a.as_ptr(), b.as_ptr());
+    let mut sum0 = _mm256_setzero_ps();
+    let mut sum1 = _mm256_setzero_ps();
+    let mut i = 0;
+    while i + 16 <= len {
+        sum0 = _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)), sum0);
+        sum1 = _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(i + 8)), _mm256_loadu_ps(pb.add(i + 8)), sum1);
+        i += 16;
+    }
+    if i + 8 <= len {
+        sum0 = _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)), sum0);
+        i += 8;
+    }
+
+    let mut lanes = [0.0f32; 8];
+    _mm256_storeu_ps(lanes.as_mut_ptr(), _mm256_add_ps(sum0, sum1));
+    lanes.iter().sum::<f32>() + dot_scalar(&a[i..len], &b[i..len])
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn dot_neon(a: &[f32], b: &[f32]) -> f32 {
+    use std::arch::aarch64::*;
+
+    let len = a.len().min(b.len());
+    let (pa, pb) = (a.as_ptr(), b.as_ptr());
+    let mut sum0 = vdupq_n_f32(0.0);
+    let mut sum1 = vdupq_n_f32(0.0);
+    let mut i = 0;
+    while i + 8 <= len {
+        sum0 = vfmaq_f32(sum0, vld1q_f32(pa.add(i)), vld1q_f32(pb.add(i)));
+        sum1 = vfmaq_f32(sum1, vld1q_f32(pa.add(i + 4)), vld1q_f32(pb.add(i + 4)));
+        i += 8;
+    }
+    if i + 4 <= len {
+        sum0 = vfmaq_f32(sum0, vld1q_f32(pa.add(i)), vld1q_f32(pb.add(i)));
+        i += 4;
+    }
+
+    vaddvq_f32(vaddq_f32(sum0, sum1)) + dot_scalar(&a[i..len], &b[i..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        for len in [0, 1, 3, 7, 8, 15, 16, 17, 384, 768, 1001] {
+            let a: Vec<f32> = (0..len).map(|i| ((i * 7 % 13) as f32 - 6.0) / 10.0).collect();
+            let b: Vec<f32> = (0..len).map(|i| ((i * 5 % 11) as f32 - 5.0) / 10.0).collect();
+            let expected: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+            assert!((dot(&a, &b) - expected).abs() <= 1e-3, "length {}", len);
+            assert!((dot_scalar(&a, &b) - expected).abs() <= 1e-3, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+
+        let query = CosineQuery::new(&[3.0, 4.0]);
+        assert!((query.score(&[6.0, 8.0]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_top_k_par() {
+        let candidates: Vec<u32> = (0..10_000).collect();
+        let top = top_k_par(&candidates, 3, |&n| (n % 7 != 0).then(|| ((n % 1000) as f32, n)));
+        assert_eq!(top.iter().map(|(score, _)| *score).collect::<Vec<_>>(), vec![999.0, 999.0, 999.0]);
+        assert!(top.iter().all(|(_, n)| n % 1000 == 999));
+
+        assert_eq!(top_k_par(&candidates[..2], 5, |&n| Some((n as f32, n))), vec![(1.0, 1), (0.0, 0)]);
+        assert!(top_k_par(&candidates, 0, |&n| Some((n as f32, n))).is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! String search modes
+//!
+//! Exact and prefix searches run on the FTS5 index of the chunk text and
+//! fuzzy searches on its trigram index: chunks sharing trigrams with the
+//! query words are candidates, kept if every query word is within a few
+//! edits of a word in the chunk. Regex searches scan the chunk text with a
+//! size-limited pattern (the regex crate runs in linear time, so a pattern
+//! can't blow up). `StringMatcher` finds the matched spans in a chunk for
+//! match counts, highlights and previews.
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::{Error, Result};
+use crate::storage::ChunkSearchResult;
+use crate::types::query::StringSearchMode;
+use crate::types::response::StringSearchResult;
+
+/// Longest regex pattern accepted, in bytes
+pub const MAX_PATTERN_LEN: usize = 256;
+
+/// Compiled size limit of a regex pattern, in bytes
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// Matches recorded per chunk
+const MAX_SPANS: usize = 1000;
+
+/// Characters shown before the first match in previews
+const PREVIEW_BEFORE: usize = 50;
+
+/// Characters shown after the first match in previews
+const PREVIEW_AFTER: usize = 100;
+
+/// Query of a string search, compiled for its mode
+#[derive(Debug)]
+pub struct StringMatcher {
+    mode: StringSearchMode,
+    /// Lowercase words of the query
+    words: Vec<String>,
+    /// Pattern finding the matches (all modes but fuzzy)
+    pattern: Option<Regex>,
+}
+
+impl StringMatcher {
+    /// Compile a query
+    ///
+    /// Fails for a regex that doesn't parse or is too large.
+    pub fn new(query: &str, mode: StringSearchMode) -> Result<Self> {
+        let query = query.trim();
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        let pattern = match mode {
+            _ if query.is_empty() => None,
+            StringSearchMode::Exact => {
+                let phrase = query.split_whitespace().map(regex::escape).collect::<Vec<_>>().join(r"\s+");
+                Some(case_insensitive(&phrase)?)
+            }
+            StringSearchMode::Prefix if words.is_empty() => None,
+            StringSearchMode::Prefix => {
+                let alternatives = words.iter().map(|word| regex::escape(word)).collect::<Vec<_>>().join("|");
+                Some(case_insensitive(&format!(r"\b(?:{})\w*", alternatives))?)
+            }
+            StringSearchMode::Fuzzy => None,
+            StringSearchMode::Regex => {
+                if query.len() > MAX_PATTERN_LEN {
+                    return Err(Error::Config(format!(
+                        "Regex is {} bytes long, the limit is {}",
+                        query.len(),
+                        MAX_PATTERN_LEN
+                    )));
+                }
+                let pattern = RegexBuilder::new(query)
+                    .size_limit(PATTERN_SIZE_LIMIT)
+                    .dfa_size_limit(PATTERN_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| Error::Config(format!("Invalid regex: {}", e)))?;
+                Some(pattern)
+            }
+        };
+
+        Ok(Self { mode, words, pattern })
+    }
+
+    /// True if the query can't match anything
+    pub fn is_empty(&self) -> bool {
+        match self.mode {
+            StringSearchMode::Fuzzy => self.words.is_empty(),
+            _ => self.pattern.is_none(),
+        }
+    }
+
+    /// FTS5 MATCH expression for a prefix search of the chunk text
+    pub fn prefix_query(&self) -> Option<String> {
+        let clauses: Vec<String> = self.words.iter().map(|word| format!("{}*", quote(word))).collect();
+        (!clauses.is_empty()).then(|| clauses.join(" AND "))
+    }
+
+    /// FTS5 MATCH expression finding fuzzy candidates in the trigram index
+    ///
+    /// `None` if no query word is long enough to have trigrams.
+    pub fn trigram_query(&self) -> Option<String> {
+        let mut trigrams: Vec<String> = Vec::new();
+        for word in &self.words {
+            let chars: Vec<char> = word.chars().collect();
+            for window in chars.windows(3) {
+                let trigram = quote(&window.iter().collect::<String>());
+                if !trigrams.contains(&trigram) {
+                    trigrams.push(trigram);
+                }
+            }
+        }
+        (!trigrams.is_empty()).then(|| trigrams.join(" OR "))
+    }
+
+    /// Byte ranges of the matches in `text`
+    pub fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        if let Some(ref pattern) = self.pattern {
+            return pattern
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| (found.start(), found.end()))
+                .take(MAX_SPANS)
+                .collect();
+        }
+        if self.mode != StringSearchMode::Fuzzy {
+            return Vec::new();
+        }
+
+        let query_words = self.query_chars();
+        text_words(text)
+            .into_iter()
+            .filter(|(_, word)| query_words.iter().any(|query_word| closeness(query_word, word).is_some()))
+            .map(|(start, word)| (start, start + word.len()))
+            .take(MAX_SPANS)
+            .collect()
+    }
+
+    /// How closely `text` matches a fuzzy query, from 0 to 1
+    ///
+    /// `None` if some query word has no close word in the text.
+    pub fn fuzzy_score(&self, text: &str) -> Option<f64> {
+        let words = text_words(text);
+        let query_words = self.query_chars();
+        if query_words.is_empty() {
+            return None;
+        }
+
+        let mut total = 0.0;
+        for query_word in &query_words {
+            total += words
+                .iter()
+                .filter_map(|(_, word)| closeness(query_word, word))
+                .fold(None, |best: Option<f64>, c| Some(best.map_or(c, |b| b.max(c))))?;
+        }
+        Some(total / query_words.len() as f64)
+    }
+
+    fn query_chars(&self) -> Vec<Vec<char>> {
+        self.words.iter().map(|word| word.chars().collect()).collect()
+    }
+}
+
+/// String search result for a matched chunk
+pub fn to_result(chunk: ChunkSearchResult) -> StringSearchResult {
+    let highlighted_excerpt = chunk
+        .snippet
+        .clone()
+        .unwrap_or_else(|| excerpt(&chunk.content, &chunk.spans, true));
+
+    StringSearchResult {
+        chunk_id: chunk.chunk_id,
+        document_id: chunk.document_id,
+        filename: chunk.filename,
+        file_type: chunk.file_type,
+        page_number: chunk.page_number,
+        match_count: chunk.spans.len(),
+        match_positions: chunk.spans.iter().map(|&(start, _)| start).collect(),
+        highlighted_snippet: highlight(&chunk.content, &chunk.spans),
+        preview: excerpt(&chunk.content, &chunk.spans, false),
+        highlighted_excerpt,
+    }
+}
+
+/// `text` with the spans wrapped in `<mark>` tags
+pub fn highlight(text: &str, spans: &[(usize, usize)]) -> String {
+    let mut highlighted = String::with_capacity(text.len() + spans.len() * 13);
+    let mut copied = 0;
+    for &(start, end) in spans {
+        if start < copied {
+            continue;
+        }
+        highlighted.push_str(&text[copied..start]);
+        highlighted.push_str("<mark>");
+        highlighted.push_str(&text[start..end]);
+        highlighted.push_str("</mark>");
+        copied = end;
+    }
+    highlighted.push_str(&text[copied..]);
+    highlighted
+}
+
+/// Text around the first match, optionally highlighted
+fn excerpt(text: &str, spans: &[(usize, usize)], highlighted: bool) -> String {
+    let Some(&(first_start, first_end)) = spans.first() else {
+        return text.chars().take(PREVIEW_BEFORE + PREVIEW_AFTER).collect();
+    };
+
+    let start = text[..first_start]
+        .char_indices()
+        .rev()
+        .nth(PREVIEW_BEFORE.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let end = text[first_end..]
+        .char_indices()
+        .nth(PREVIEW_AFTER)
+        .map_or(text.len(), |(i, _)| first_end + i);
+
+    let window = &text[start..end];
+    let mut excerpt = if highlighted {
+        let inner: Vec<(usize, usize)> = spans
+            .iter()
+            .filter(|&&(s, e)| s >= start && e <= end)
+            .map(|&(s, e)| (s - start, e - start))
+            .collect();
+        highlight(window, &inner)
+    } else {
+        window.to_string()
+    };
+    if start > 0 {
+        excerpt.insert_str(0, "...");
+    }
+    if end < text.len() {
+        excerpt.push_str("...");
+    }
+    excerpt
+}
+
+/// Closeness of a text word to a query word, `None` if too many edits apart
+fn closeness(query_word: &[char], word: &str) -> Option<f64> {
+    let word: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+    let allowed = max_edits(query_word.len());
+    if word.len().abs_diff(query_word.len()) > allowed {
+        return None;
+    }
+    let distance = edit_distance(query_word, &word);
+    (distance <= allowed).then(|| 1.0 - distance as f64 / query_word.len().max(word.len()) as f64)
+}
+
+/// Typos tolerated in a word of `len` characters
+fn max_edits(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Edit distance counting a swap of neighbouring characters as one edit
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Words of `text` with their byte offsets
+fn text_words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, &text[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, &text[s..]));
+    }
+    words
+}
+
+fn case_insensitive(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::Internal(format!("Invalid search pattern: {}", e)))
+}
+
+/// Quote text as an FTS5 string
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_modes() {
+        let text = "Configure the Recieve queue. Configuration recieved.";
+
+        let exact = StringMatcher::new("recieve  QUEUE", StringSearchMode::Exact).unwrap();
+        assert_eq!(exact.spans(text), vec![(14, 27)]);
+
+        let prefix = StringMatcher::new("config", StringSearchMode::Prefix).unwrap();
+        assert_eq!(prefix.prefix_query().as_deref(), Some("\"config\"*"));
+        assert_eq!(prefix.spans(text).len(), 2);
+
+        let fuzzy = StringMatcher::new("receive", StringSearchMode::Fuzzy).unwrap();
+        assert_eq!(fuzzy.spans(text), vec![(14, 21)]);
+        assert!(fuzzy.fuzzy_score(text).is_some());
+        assert!(fuzzy.fuzzy_score("nothing close").is_none());
+        assert!(fuzzy.trigram_query().unwrap().starts_with("\"rec\" OR \"ece\""));
+
+        let regex = StringMatcher::new(r"rec\w+ed", StringSearchMode::Regex).unwrap();
+        assert_eq!(highlight(text, &regex.spans(text)), "Configure the Recieve queue. Configuration <mark>recieved</mark>.");
+        assert!(StringMatcher::new("(unclosed", StringSearchMode::Regex).is_err());
+        assert!(StringMatcher::new(&"a".repeat(MAX_PATTERN_LEN + 1), StringSearchMode::Regex).is_err());
+    }
+}
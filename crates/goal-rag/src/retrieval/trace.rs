@@ -0,0 +1,257 @@
+//! Retrieval traces for offline ranking analysis
+//!
+//! A trace captures every candidate the vector search returned for a query,
+//! its score and position, whether it survived threshold/top-k selection and
+//! whether the answer cited it. Traces are sampled and stored in SQLite, then
+//! exported as NDJSON or Parquet.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::TraceConfig;
+use crate::providers::vector_store::VectorSearchResult;
+use crate::types::Citation;
+
+/// A candidate chunk considered for a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceCandidate {
+    pub chunk_id: Uuid,
+    pub document_id: Uuid,
+    pub filename: String,
+    /// Similarity score from vector search
+    pub similarity: f32,
+    /// Position in the vector search results (0-based)
+    pub retrieval_rank: usize,
+    /// Position in the final context after filtering and reordering
+    pub final_rank: Option<usize>,
+    /// Whether the chunk was passed to the LLM
+    pub selected: bool,
+    /// Whether the answer cited the chunk
+    pub cited: bool,
+}
+
+/// Full retrieval trace for one query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalTrace {
+    pub id: Uuid,
+    /// Endpoint that served the query (e.g., "query", "v2/query")
+    pub endpoint: String,
+    pub question: String,
+    pub top_k: usize,
+    pub similarity_threshold: f32,
+    pub candidates: Vec<TraceCandidate>,
+    /// Knowledge store interaction, for joining with user feedback
+    pub interaction_id: Option<Uuid>,
+    pub latency_ms: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RetrievalTrace {
+    /// Start a trace for a query
+    pub fn new(endpoint: &str, question: &str, top_k: usize, similarity_threshold: f32) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            endpoint: endpoint.to_string(),
+            question: question.to_string(),
+            top_k,
+            similarity_threshold,
+            candidates: Vec::new(),
+            interaction_id: None,
+            latency_ms: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Record raw vector search results, in retrieval order
+    pub fn record_candidates(&mut self, results: &[VectorSearchResult]) {
+        self.candidates = results
+            .iter()
+            .enumerate()
+            .map(|(rank, r)| TraceCandidate {
+                chunk_id: r.chunk.id,
+                document_id: r.chunk.document_id,
+                filename: r.chunk.source.filename.clone(),
+                similarity: r.similarity,
+                retrieval_rank: rank,
+                final_rank: None,
+                selected: false,
+                cited: false,
+            })
+            .collect();
+    }
+
+    /// Record the final selection passed to the LLM, in context order
+    pub fn record_selection(&mut self, results: &[VectorSearchResult]) {
+        for (rank, r) in results.iter().enumerate() {
+            if let Some(candidate) = self.candidates.iter_mut().find(|c| c.chunk_id == r.chunk.id) {
+                candidate.final_rank = Some(rank);
+                candidate.selected = true;
+            }
+        }
+    }
+
+    /// Record which selected chunks the answer cited
+    pub fn record_citations(&mut self, citations: &[Citation]) {
+        for citation in citations {
+            if let Some(candidate) = self.candidates.iter_mut().find(|c| c.chunk_id == citation.chunk_id) {
+                candidate.cited = true;
+            }
+        }
+    }
+
+    /// Complete the trace
+    pub fn finish(mut self, interaction_id: Option<Uuid>, latency_ms: u64) -> Self {
+        self.interaction_id = interaction_id;
+        self.latency_ms = latency_ms;
+        self
+    }
+}
+
+/// Decide whether a query should be traced
+pub fn should_sample(config: &TraceConfig) -> bool {
+    if !config.enabled || config.sample_rate <= 0.0 {
+        return false;
+    }
+    if config.sample_rate >= 1.0 {
+        return true;
+    }
+    // Random v4 UUIDs are uniformly distributed, no extra RNG needed
+    let bucket = (Uuid::new_v4().as_u128() % 10_000) as f64;
+    bucket < config.sample_rate * 10_000.0
+}
+
+/// Serialize traces as newline-delimited JSON (one trace per line)
+pub fn to_ndjson(traces: &[RetrievalTrace]) -> crate::error::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for trace in traces {
+        serde_json::to_writer(&mut out, trace)?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+/// Serialize traces as Parquet, one row per candidate
+#[cfg(feature = "parquet-export")]
+pub fn to_parquet(traces: &[RetrievalTrace]) -> crate::error::Result<Vec<u8>> {
+    use arrow_array::{
+        ArrayRef, BooleanArray, Float32Array, RecordBatch, StringArray, UInt32Array, UInt64Array,
+    };
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    use crate::error::Error;
+
+    let rows: Vec<(&RetrievalTrace, &TraceCandidate)> = traces
+        .iter()
+        .flat_map(|t| t.candidates.iter().map(move |c| (t, c)))
+        .collect();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("trace_id", DataType::Utf8, false),
+        Field::new("created_at", DataType::Utf8, false),
+        Field::new("endpoint", DataType::Utf8, false),
+        Field::new("question", DataType::Utf8, false),
+        Field::new("top_k", DataType::UInt32, false),
+        Field::new("similarity_threshold", DataType::Float32, false),
+        Field::new("latency_ms", DataType::UInt64, false),
+        Field::new("interaction_id", DataType::Utf8, true),
+        Field::new("chunk_id", DataType::Utf8, false),
+        Field::new("document_id", DataType::Utf8, false),
+        Field::new("filename", DataType::Utf8, false),
+        Field::new("similarity", DataType::Float32, false),
+        Field::new("retrieval_rank", DataType::UInt32, false),
+        Field::new("final_rank", DataType::UInt32, true),
+        Field::new("selected", DataType::Boolean, false),
+        Field::new("cited", DataType::Boolean, false),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(t, _)| t.id.to_string()))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(t, _)| t.created_at.to_rfc3339()))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(t, _)| t.endpoint.as_str()))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(t, _)| t.question.as_str()))),
+        Arc::new(UInt32Array::from_iter_values(rows.iter().map(|(t, _)| t.top_k as u32))),
+        Arc::new(Float32Array::from_iter_values(rows.iter().map(|(t, _)| t.similarity_threshold))),
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(|(t, _)| t.latency_ms))),
+        Arc::new(StringArray::from_iter(rows.iter().map(|(t, _)| t.interaction_id.map(|id| id.to_string())))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, c)| c.chunk_id.to_string()))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, c)| c.document_id.to_string()))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, c)| c.filename.as_str()))),
+        Arc::new(Float32Array::from_iter_values(rows.iter().map(|(_, c)| c.similarity))),
+        Arc::new(UInt32Array::from_iter_values(rows.iter().map(|(_, c)| c.retrieval_rank as u32))),
+        Arc::new(UInt32Array::from_iter(rows.iter().map(|(_, c)| c.final_rank.map(|r| r as u32)))),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|(_, c)| Some(c.selected)))),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|(_, c)| Some(c.cited)))),
+    ];
+
+    let batch = RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| Error::Internal(format!("Failed to build trace batch: {}", e)))?;
+
+    let mut out = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut out, schema, None)
+        .map_err(|e| Error::Internal(format!("Failed to create Parquet writer: {}", e)))?;
+    writer
+        .write(&batch)
+        .map_err(|e| Error::Internal(format!("Failed to write Parquet: {}", e)))?;
+    writer
+        .close()
+        .map_err(|e| Error::Internal(format!("Failed to finish Parquet: {}", e)))?;
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Chunk, ChunkSource, FileType};
+
+    fn result(similarity: f32) -> VectorSearchResult {
+        let source = ChunkSource {
+            filename: "doc.txt".to_string(),
+            internal_filename: None,
+            file_type: FileType::Txt,
+            page_number: None,
+            page_count: None,
+            section_title: None,
+            heading_hierarchy: Vec::new(),
+            sheet_name: None,
+            row_range: None,
+            line_start: None,
+            line_end: None,
+            code_context: None,
+            time_range: None,
+            kind: crate::types::ChunkKind::Text,
+        };
+        VectorSearchResult::new(Chunk::new(Uuid::new_v4(), "text".to_string(), source, 0, 4, 0), similarity)
+    }
+
+    #[test]
+    fn test_trace_selection() {
+        let results = vec![result(0.9), result(0.5), result(0.1)];
+        let mut trace = RetrievalTrace::new("query", "q", 2, 0.3);
+        trace.record_candidates(&results);
+        trace.record_selection(&results[..2]);
+
+        let citation = Citation::from_chunk(&results[1].chunk, 0.5);
+        trace.record_citations(&[citation]);
+
+        assert_eq!(trace.candidates.len(), 3);
+        assert_eq!(trace.candidates[0].final_rank, Some(0));
+        assert!(!trace.candidates[0].cited);
+        assert!(trace.candidates[1].cited);
+        assert!(!trace.candidates[2].selected);
+    }
+
+    #[test]
+    fn test_sampling_bounds() {
+        let mut config = TraceConfig { enabled: true, sample_rate: 1.0, ..Default::default() };
+        assert!(should_sample(&config));
+        config.sample_rate = 0.0;
+        assert!(!should_sample(&config));
+        config.enabled = false;
+        config.sample_rate = 1.0;
+        assert!(!should_sample(&config));
+    }
+}
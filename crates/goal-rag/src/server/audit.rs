@@ -0,0 +1,612 @@
+//! Answer audit log
+//!
+//! With `[audit] enabled`, every query to `/api/query` and `/api/v2/query`
+//! (and the batch and saved queries built on them) appends one record: the
+//! question and caller, every chunk retrieved with its score and whether it
+//! was passed to the model and cited, a SHA-256 hash of the final prompt, the
+//! model, the answer or error, and the latency. Together with the document
+//! versions this is enough to reconstruct why an answer was given.
+//!
+//! Records are never updated. The SQLite sink's table rejects updates; the
+//! JSONL sink appends to one file per day. Retention deletes whole records
+//! older than `retention_days`, and erasing documents (DELETE
+//! /api/admin/erase) deletes the records that retrieved them.
+//!
+//! Details are collected through a task-local scope around the query, so
+//! work the query spawns onto other tasks isn't recorded.
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::config::{AuditConfig, AuditSink};
+use crate::error::{Error, Result};
+use crate::providers::vector_store::VectorSearchResult;
+use crate::storage::FileRegistryDb;
+use crate::types::query::QueryType;
+use crate::types::response::{QueryResponse, QueryResponseType, QueryResponseV2};
+use crate::types::QueryRequest;
+
+use super::state::AppState;
+
+/// Records returned by a listing unless a limit is given
+const DEFAULT_LIMIT: usize = 100;
+/// Most records returned by a listing
+const MAX_LIMIT: usize = 1000;
+
+tokio::task_local! {
+    static DETAILS: Mutex<AuditDetails>;
+}
+
+/// A chunk retrieved for a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditChunk {
+    pub chunk_id: Uuid,
+    pub document_id: Uuid,
+    pub filename: String,
+    /// Similarity score from retrieval
+    pub similarity: f32,
+    /// Whether the chunk was passed to the model
+    pub selected: bool,
+    /// Whether the answer cited the chunk
+    pub cited: bool,
+}
+
+/// One audited query
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditRecord {
+    pub id: Uuid,
+    /// Endpoint that served the query (e.g., "query", "v2/query")
+    pub endpoint: String,
+    pub question: String,
+    /// Caller, when authentication is enabled
+    pub principal: Option<String>,
+    pub collection: Option<String>,
+    /// `answered`, `curated`, `cached`, `not_found`, `insufficient_evidence`,
+    /// `string_search`, `document_lookup` or `error`
+    pub outcome: String,
+    /// Chunks retrieved, in retrieval order
+    pub chunks: Vec<AuditChunk>,
+    /// SHA-256 of the prompt the answer was generated from (absent for
+    /// answers not generated from a single prompt)
+    pub prompt_sha256: Option<String>,
+    /// Generation model that wrote the answer
+    pub model: Option<String>,
+    pub answer: Option<String>,
+    pub error: Option<String>,
+    /// Knowledge store interaction, for joining with user feedback
+    pub interaction_id: Option<Uuid>,
+    pub latency_ms: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters for listing audit records
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct AuditFilter {
+    /// Records created at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Records created before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+    /// Records of this caller
+    pub principal: Option<String>,
+    /// The record of this knowledge store interaction
+    pub interaction_id: Option<Uuid>,
+    /// Maximum records to return, newest first (default 100, at most 1000)
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+    }
+
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.since.map_or(true, |since| record.created_at >= since)
+            && self.until.map_or(true, |until| record.created_at < until)
+            && self.principal.as_ref().map_or(true, |p| record.principal.as_ref() == Some(p))
+            && self.interaction_id.map_or(true, |id| record.interaction_id == Some(id))
+    }
+}
+
+/// What a query recorded while it ran
+#[derive(Debug, Default)]
+struct AuditDetails {
+    chunks: Vec<AuditChunk>,
+    prompt_sha256: Option<String>,
+}
+
+/// Whether the current query is being audited
+pub fn is_recording() -> bool {
+    DETAILS.try_with(|_| ()).is_ok()
+}
+
+/// Record the chunks retrieved for the current query, in retrieval order
+pub fn record_candidates(results: &[VectorSearchResult]) {
+    let _ = DETAILS.try_with(|details| {
+        details.lock().chunks = results
+            .iter()
+            .map(|r| AuditChunk {
+                chunk_id: r.chunk.id,
+                document_id: r.chunk.document_id,
+                filename: r.chunk.source.filename.clone(),
+                similarity: r.similarity,
+                selected: false,
+                cited: false,
+            })
+            .collect();
+    });
+}
+
+/// Record the chunks passed to the model
+pub fn record_selection(results: &[VectorSearchResult]) {
+    let _ = DETAILS.try_with(|details| {
+        let mut details = details.lock();
+        for r in results {
+            if let Some(chunk) = details.chunks.iter_mut().find(|c| c.chunk_id == r.chunk.id) {
+                chunk.selected = true;
+            }
+        }
+    });
+}
+
+/// Record the prompt the answer is generated from
+pub fn record_prompt(prompt: &str) {
+    let _ = DETAILS.try_with(|details| {
+        details.lock().prompt_sha256 = Some(hex::encode(Sha256::digest(prompt.as_bytes())));
+    });
+}
+
+/// A query response as recorded in the audit log
+pub trait AuditedResponse {
+    fn outcome(&self) -> &'static str;
+    fn answer_text(&self) -> &str;
+    fn model(&self) -> Option<&str>;
+    fn interaction_id(&self) -> Option<Uuid>;
+    /// Chunks the answer cites
+    fn cited(&self) -> Vec<AuditChunk>;
+}
+
+impl AuditedResponse for QueryResponse {
+    fn outcome(&self) -> &'static str {
+        if self.curated {
+            "curated"
+        } else if self.insufficient_evidence.is_some() {
+            "insufficient_evidence"
+        } else if self.intent == QueryType::StringSearch {
+            "string_search"
+        } else if self.intent == QueryType::DocumentLookup {
+            "document_lookup"
+        } else if self.model.is_none() && self.citations.is_empty() {
+            "not_found"
+        } else {
+            "answered"
+        }
+    }
+
+    fn answer_text(&self) -> &str {
+        &self.answer
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    fn interaction_id(&self) -> Option<Uuid> {
+        self.interaction_id
+    }
+
+    fn cited(&self) -> Vec<AuditChunk> {
+        self.citations
+            .iter()
+            .map(|c| AuditChunk {
+                chunk_id: c.chunk_id,
+                document_id: c.document_id,
+                filename: c.filename.clone(),
+                similarity: c.similarity_score,
+                selected: true,
+                cited: true,
+            })
+            .collect()
+    }
+}
+
+impl AuditedResponse for QueryResponseV2 {
+    fn outcome(&self) -> &'static str {
+        if self.curated {
+            return "curated";
+        }
+        if self.cache_info.as_ref().is_some_and(|info| info.from_cache) {
+            return "cached";
+        }
+        match self.query_type {
+            QueryResponseType::RagAnswer { .. } => "answered",
+            QueryResponseType::StringSearch { .. } => "string_search",
+            QueryResponseType::DocumentLookup { .. } => "document_lookup",
+            QueryResponseType::NotFound => "not_found",
+            QueryResponseType::InsufficientEvidence { .. } => "insufficient_evidence",
+        }
+    }
+
+    fn answer_text(&self) -> &str {
+        &self.answer
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    fn interaction_id(&self) -> Option<Uuid> {
+        self.interaction_id
+    }
+
+    fn cited(&self) -> Vec<AuditChunk> {
+        self.citations
+            .iter()
+            .map(|c| AuditChunk {
+                chunk_id: c.source.chunk_id,
+                document_id: c.source.document_id,
+                filename: c.source.filename.clone(),
+                similarity: c.relevance.score as f32 / 100.0,
+                selected: true,
+                cited: true,
+            })
+            .collect()
+    }
+}
+
+/// Answer a query, appending its record to the audit log when enabled
+pub async fn audited<T, F>(
+    state: &AppState,
+    endpoint: &str,
+    request: QueryRequest,
+    answer: impl FnOnce(QueryRequest) -> F,
+) -> Result<T>
+where
+    T: AuditedResponse,
+    F: Future<Output = Result<T>>,
+{
+    if !state.audit().is_enabled() {
+        return answer(request).await;
+    }
+
+    let start = Instant::now();
+    let question = request.question.clone();
+    let principal = request.principal.as_ref().map(|p| p.id.clone());
+    let collection = request.collection.clone();
+    let (result, details) = DETAILS
+        .scope(Mutex::new(AuditDetails::default()), async {
+            let result = answer(request).await;
+            let details = DETAILS.with(|details| std::mem::take(&mut *details.lock()));
+            (result, details)
+        })
+        .await;
+
+    let mut record = AuditRecord {
+        id: Uuid::new_v4(),
+        endpoint: endpoint.to_string(),
+        question,
+        principal,
+        collection,
+        outcome: "error".to_string(),
+        chunks: details.chunks,
+        prompt_sha256: details.prompt_sha256,
+        model: None,
+        answer: None,
+        error: None,
+        interaction_id: None,
+        latency_ms: start.elapsed().as_millis() as u64,
+        created_at: Utc::now(),
+    };
+    match &result {
+        Ok(response) => {
+            record.outcome = response.outcome().to_string();
+            record.answer = Some(response.answer_text().to_string());
+            record.model = response.model().map(str::to_string);
+            record.interaction_id = response.interaction_id();
+            mark_cited(&mut record.chunks, response.cited());
+        }
+        Err(e) => record.error = Some(e.to_string()),
+    }
+    state.audit().record(record);
+
+    result
+}
+
+/// Mark cited chunks, adding those that weren't retrieved by this query
+/// (cached and curated answers)
+fn mark_cited(chunks: &mut Vec<AuditChunk>, cited: Vec<AuditChunk>) {
+    for citation in cited {
+        match chunks.iter_mut().find(|c| c.chunk_id == citation.chunk_id) {
+            Some(chunk) => chunk.cited = true,
+            None => chunks.push(citation),
+        }
+    }
+}
+
+/// Append-only store of audit records
+pub struct AuditLog {
+    config: AuditConfig,
+    database: Arc<FileRegistryDb>,
+    /// Directory of the JSONL sink
+    dir: PathBuf,
+    /// Serializes appends to the JSONL files
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(config: AuditConfig, database: Arc<FileRegistryDb>, storage_dir: &Path) -> Result<Self> {
+        let dir = config.dir.clone().unwrap_or_else(|| storage_dir.join("audit"));
+        if config.enabled && config.sink == AuditSink::Jsonl {
+            fs::create_dir_all(&dir)?;
+        }
+        Ok(Self {
+            config,
+            database,
+            dir,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Append a record in the background
+    pub fn record(self: &Arc<Self>, record: AuditRecord) {
+        let log = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = log.append(&record) {
+                tracing::error!("Failed to append audit record {}: {}", record.id, e);
+            }
+        });
+    }
+
+    /// Append a record
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        match self.config.sink {
+            AuditSink::Sqlite => self.database.insert_audit_record(record),
+            AuditSink::Jsonl => {
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                let _guard = self.write_lock.lock();
+                let path = self.dir.join(file_name(record.created_at.date_naive()));
+                OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Records matching a filter, newest first
+    pub fn list(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>> {
+        if !self.is_enabled() {
+            return Err(Error::Config("The audit log is not enabled".to_string()));
+        }
+        match self.config.sink {
+            AuditSink::Sqlite => self.database.list_audit_records(filter),
+            AuditSink::Jsonl => self.list_jsonl(filter),
+        }
+    }
+
+    fn list_jsonl(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>> {
+        let since = filter.since.map(|since| since.date_naive());
+        let until = filter.until.map(|until| until.date_naive());
+        let mut days: Vec<(NaiveDate, PathBuf)> = self
+            .daily_files()?
+            .into_iter()
+            .filter(|(day, _)| since.map_or(true, |since| *day >= since) && until.map_or(true, |until| *day <= until))
+            .collect();
+        days.sort_by(|a, b| b.0.cmp(&a.0));
+
+        let limit = filter.limit();
+        let mut records = Vec::new();
+        for (_, path) in days {
+            let mut day: Vec<AuditRecord> = BufReader::new(fs::File::open(&path)?)
+                .lines()
+                .map_while(|line| line.ok())
+                .filter_map(|line| serde_json::from_str(&line).ok())
+                .filter(|record| filter.matches(record))
+                .collect();
+            day.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            records.extend(day);
+            if records.len() >= limit {
+                break;
+            }
+        }
+        records.truncate(limit);
+        Ok(records)
+    }
+
+    /// JSONL files by day
+    fn daily_files(&self) -> Result<Vec<(NaiveDate, PathBuf)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(day) = path.file_name().and_then(|name| name.to_str()).and_then(parse_file_name) {
+                files.push((day, path));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Delete records that retrieved or cited chunks of the given documents
+    pub fn erase(&self, document_ids: &HashSet<Uuid>) -> Result<usize> {
+        match self.config.sink {
+            AuditSink::Sqlite => {
+                let ids: Vec<Uuid> = document_ids.iter().copied().collect();
+                self.database.delete_audit_records_citing(&ids)
+            }
+            AuditSink::Jsonl => {
+                let _guard = self.write_lock.lock();
+                let mut deleted = 0;
+                for (_, path) in self.daily_files()? {
+                    let lines: Vec<String> =
+                        BufReader::new(fs::File::open(&path)?).lines().collect::<std::io::Result<_>>()?;
+                    let kept: Vec<&String> = lines
+                        .iter()
+                        .filter(|line| {
+                            !serde_json::from_str::<AuditRecord>(line)
+                                .is_ok_and(|record| record.chunks.iter().any(|c| document_ids.contains(&c.document_id)))
+                        })
+                        .collect();
+                    if kept.len() == lines.len() {
+                        continue;
+                    }
+                    deleted += lines.len() - kept.len();
+                    let temp = path.with_extension("jsonl.tmp");
+                    let mut file = fs::File::create(&temp)?;
+                    for line in kept {
+                        writeln!(file, "{}", line)?;
+                    }
+                    file.sync_all()?;
+                    fs::rename(&temp, &path)?;
+                }
+                Ok(deleted)
+            }
+        }
+    }
+
+    /// Delete records older than `retention_days`
+    pub fn apply_retention(&self) -> Result<usize> {
+        let days = self.config.retention_days;
+        if days <= 0 {
+            return Ok(0);
+        }
+        match self.config.sink {
+            AuditSink::Sqlite => self.database.cleanup_audit_log(days),
+            AuditSink::Jsonl => {
+                // Files are deleted once their last record is past the cutoff
+                let cutoff = (Utc::now() - chrono::Duration::days(days)).date_naive();
+                let mut deleted = 0;
+                for (day, path) in self.daily_files()? {
+                    if day < cutoff {
+                        fs::remove_file(path)?;
+                        deleted += 1;
+                    }
+                }
+                Ok(deleted)
+            }
+        }
+    }
+
+    /// Apply retention now and then daily
+    pub fn spawn_retention(self: &Arc<Self>) {
+        if !self.is_enabled() || self.config.retention_days <= 0 {
+            return;
+        }
+        let log = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+            loop {
+                ticker.tick().await;
+                let retention = Arc::clone(&log);
+                match tokio::task::spawn_blocking(move || retention.apply_retention()).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(n)) => tracing::info!(
+                        "Removed {} audit {} older than {} days",
+                        n,
+                        if log.config.sink == AuditSink::Jsonl { "files" } else { "records" },
+                        log.config.retention_days
+                    ),
+                    Ok(Err(e)) => tracing::warn!("Failed to apply audit log retention: {}", e),
+                    Err(e) => tracing::warn!("Audit log retention task failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn file_name(day: NaiveDate) -> String {
+    format!("audit-{}.jsonl", day.format("%Y-%m-%d"))
+}
+
+fn parse_file_name(name: &str) -> Option<NaiveDate> {
+    let day = name.strip_prefix("audit-")?.strip_suffix(".jsonl")?;
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(question: &str, created_at: DateTime<Utc>) -> AuditRecord {
+        AuditRecord {
+            id: Uuid::new_v4(),
+            endpoint: "query".to_string(),
+            question: question.to_string(),
+            principal: Some("alice".to_string()),
+            collection: None,
+            outcome: "answered".to_string(),
+            chunks: Vec::new(),
+            prompt_sha256: None,
+            model: None,
+            answer: None,
+            error: None,
+            interaction_id: None,
+            latency_ms: 12,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_jsonl_sink_lists_newest_first_and_expires_days() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Arc::new(FileRegistryDb::new(&dir.path().join("registry.db")).unwrap());
+        let config = AuditConfig {
+            enabled: true,
+            sink: AuditSink::Jsonl,
+            dir: Some(dir.path().join("audit")),
+            retention_days: 30,
+        };
+        let log = AuditLog::new(config, database, dir.path()).unwrap();
+
+        let now = Utc::now();
+        log.append(&record("old", now - chrono::Duration::days(40))).unwrap();
+        log.append(&record("first", now - chrono::Duration::minutes(2))).unwrap();
+        log.append(&record("second", now - chrono::Duration::minutes(1))).unwrap();
+
+        let listed = log.list(&AuditFilter::default()).unwrap();
+        let questions: Vec<&str> = listed.iter().map(|r| r.question.as_str()).collect();
+        assert_eq!(questions, ["second", "first", "old"]);
+
+        let filter = AuditFilter {
+            since: Some(now - chrono::Duration::days(1)),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(log.list(&filter).unwrap()[0].question, "second");
+
+        assert_eq!(log.apply_retention().unwrap(), 1);
+        assert_eq!(log.list(&AuditFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_mark_cited_adds_unretrieved_citations() {
+        let chunk = |id: Uuid| AuditChunk {
+            chunk_id: id,
+            document_id: Uuid::nil(),
+            filename: "policy.pdf".to_string(),
+            similarity: 0.8,
+            selected: true,
+            cited: false,
+        };
+        let (retrieved, cached) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut chunks = vec![chunk(retrieved)];
+        mark_cited(&mut chunks, vec![chunk(retrieved), AuditChunk { cited: true, ..chunk(cached) }]);
+        assert!(chunks[0].cited);
+        assert_eq!(chunks[1].chunk_id, cached);
+    }
+}
@@ -0,0 +1,223 @@
+//! API authentication middleware
+//!
+//! Resolves the caller of an `/api` request to a [`Principal`] and stores it
+//! in the request extensions, where handlers pick it up to filter documents.
+//! With `auth.enabled = false` no principal is set and nothing is filtered.
+//! [`require_admin`] guards the administrative routes on top of that.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+use crate::config::AuthConfig;
+use crate::error::Error;
+use crate::server::state::AppState;
+use crate::types::Principal;
+
+/// Header carrying an API key (alternative to `Authorization: Bearer`)
+const API_KEY_HEADER: &str = "x-api-key";
+/// Proxy header carrying the authenticated user
+const FORWARDED_USER_HEADER: &str = "x-forwarded-user";
+/// Proxy header carrying the user's groups (comma-separated)
+const FORWARDED_GROUPS_HEADER: &str = "x-forwarded-groups";
+
+/// Chat integration endpoints, verified with the platforms' signatures
+const INTEGRATIONS_PATH: &str = "/integrations/";
+
+/// Authenticate the request and attach its principal
+pub async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let rag_config = state.config();
+    let config = &rag_config.auth;
+    if !config.enabled || request.uri().path().trim_start_matches("/api").starts_with(INTEGRATIONS_PATH) {
+        return next.run(request).await;
+    }
+
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    match principal(config, request.headers(), peer) {
+        Some(principal) => {
+            tracing::debug!("Request authenticated as {}", principal.id);
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        None => Error::Unauthorized("Missing or invalid API key".to_string()).into_response(),
+    }
+}
+
+/// Reject authenticated callers without the admin role
+///
+/// Layered on routes that change the server rather than read documents. With
+/// auth disabled there is no principal and the request goes through.
+pub async fn require_admin(principal: Option<Extension<Principal>>, request: Request, next: Next) -> Response {
+    match principal {
+        Some(Extension(principal)) if !principal.admin => {
+            Error::Forbidden(format!("{} is not an admin", principal.id)).into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+/// Principal identified by the request headers, if any
+///
+/// Proxy headers are only accepted from a `peer` in `auth.trusted_proxies`.
+pub fn principal(config: &AuthConfig, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<Principal> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    let key = header(axum::http::header::AUTHORIZATION.as_str())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| header(API_KEY_HEADER));
+
+    let mut principal = match key {
+        Some(key) => return key_principal(config, key),
+        None if config.trust_proxy_headers && peer.is_some_and(|peer| is_trusted_proxy(config, peer)) => {
+            let user = header(FORWARDED_USER_HEADER).filter(|u| !u.is_empty())?;
+            let groups = header(FORWARDED_GROUPS_HEADER)
+                .map(|g| g.split(',').map(str::trim).filter(|g| !g.is_empty()).map(String::from).collect())
+                .unwrap_or_default();
+            Principal::new(user, groups)
+        }
+        None => return None,
+    };
+
+    principal.admin = principal.roles.iter().any(|role| *role == config.admin_role);
+    Some(principal)
+}
+
+/// Principal an API key identifies, if it is one of `auth.api_keys`
+pub fn key_principal(config: &AuthConfig, key: &str) -> Option<Principal> {
+    let entry = config.api_keys.iter().find(|entry| constant_time_eq(entry.key.as_bytes(), key.trim().as_bytes()))?;
+    let mut principal = Principal::new(entry.principal.clone(), entry.roles.clone());
+    principal.admin = principal.roles.iter().any(|role| *role == config.admin_role);
+    Some(principal)
+}
+
+/// Whether `peer` is one of `auth.trusted_proxies`
+fn is_trusted_proxy(config: &AuthConfig, peer: IpAddr) -> bool {
+    config.trusted_proxies.iter().filter_map(|entry| parse_cidr(entry)).any(|cidr| cidr_contains(cidr, peer))
+}
+
+/// Parse a CIDR (`10.0.0.0/8`) or a single address into network and prefix length
+pub fn parse_cidr(entry: &str) -> Option<(IpAddr, u32)> {
+    let entry = entry.trim();
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u32>().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(bits);
+    (prefix <= bits).then_some((addr, prefix))
+}
+
+/// Whether `addr` is inside the network `cidr` (IPv4-mapped IPv6 peers match IPv4 networks)
+fn cidr_contains((network, prefix): (IpAddr, u32), addr: IpAddr) -> bool {
+    match (network.to_canonical(), addr.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(addr)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(addr) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(addr)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(addr) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Compare keys without leaking the matching prefix length through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiKeyConfig;
+
+    #[test]
+    fn test_principal_from_headers() {
+        let mut config = AuthConfig {
+            enabled: true,
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                principal: "alice".to_string(),
+                roles: vec!["engineering".to_string(), "admin".to_string()],
+            }],
+            ..Default::default()
+        };
+
+        let proxy: IpAddr = "10.1.2.3".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!(principal(&config, &headers, None), None);
+
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        let alice = principal(&config, &headers, None).unwrap();
+        assert_eq!(alice.id, "alice");
+        assert!(alice.admin);
+
+        headers.insert("authorization", "Bearer wrong".parse().unwrap());
+        assert_eq!(principal(&config, &headers, None), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_USER_HEADER, "bob".parse().unwrap());
+        headers.insert(FORWARDED_GROUPS_HEADER, "sales, support".parse().unwrap());
+        assert_eq!(principal(&config, &headers, Some(proxy)), None);
+
+        config.trust_proxy_headers = true;
+        assert_eq!(principal(&config, &headers, Some(proxy)), None);
+
+        config.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        let bob = principal(&config, &headers, Some(proxy)).unwrap();
+        assert_eq!(bob.roles, vec!["sales", "support"]);
+        assert!(!bob.admin);
+        assert_eq!(principal(&config, &headers, Some("192.168.1.1".parse().unwrap())), None);
+        assert_eq!(principal(&config, &headers, None), None);
+    }
+
+    #[test]
+    fn test_trusted_proxy_cidrs() {
+        let config = AuthConfig {
+            trusted_proxies: vec!["172.16.0.0/12".to_string(), "fd00::1".to_string(), "bogus".to_string()],
+            ..Default::default()
+        };
+        let trusted = |addr: &str| is_trusted_proxy(&config, addr.parse().unwrap());
+        assert!(trusted("172.31.255.1"));
+        assert!(trusted("::ffff:172.16.0.9"));
+        assert!(trusted("fd00::1"));
+        assert!(!trusted("172.32.0.1"));
+        assert!(!trusted("fd00::2"));
+
+        assert_eq!(parse_cidr("0.0.0.0/0"), Some(("0.0.0.0".parse().unwrap(), 0)));
+        assert_eq!(parse_cidr("10.0.0.0/33"), None);
+        assert_eq!(parse_cidr("bogus"), None);
+    }
+
+    #[tokio::test]
+    async fn test_require_admin() {
+        use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+        use tower::ServiceExt;
+
+        let router = |principal: Option<Principal>| {
+            let router = Router::new()
+                .route("/", get(|| async { "ok" }))
+                .route_layer(middleware::from_fn(require_admin));
+            match principal {
+                Some(principal) => router.layer(Extension(principal)),
+                None => router,
+            }
+        };
+        let status = |router: Router| async move {
+            router.oneshot(Request::builder().uri("/").body(Body::empty()).unwrap()).await.unwrap().status()
+        };
+
+        let mut bob = Principal::new("bob", vec!["sales".to_string()]);
+        assert_eq!(status(router(Some(bob.clone()))).await, StatusCode::FORBIDDEN);
+        bob.admin = true;
+        assert_eq!(status(router(Some(bob))).await, StatusCode::OK);
+        assert_eq!(status(router(None)).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,255 @@
+//! Erasure of a data subject's documents from every store
+//!
+//! DELETE /api/admin/erase removes the documents a request matches together
+//! with everything derived from them: chunks and their full-text rows,
+//! vectors, file records, stored originals (GCS), superseded versions, cached
+//! answers, learned interactions and corrections, retrieval traces, guard
+//! events, audit records, saved query reports citing them and the registry
+//! snapshots taken while they existed.
+//!
+//! The report lists what was removed. With provenance signing configured it
+//! is signed like an answer, `summary` being the signed text, so it can be
+//! checked later with POST /api/provenance/verify.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::engine::RagEngine;
+use crate::error::{Error, Result};
+use crate::generation::provenance::citation_hash;
+use crate::types::query::StringSearchMode;
+use crate::types::{AnswerProvenance, Document};
+
+use super::state::AppState;
+
+/// Most chunks a content search matches
+const CONTENT_SEARCH_LIMIT: usize = 10_000;
+
+/// Documents to erase: those matching any of the given criteria
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ErasureRequest {
+    /// Filename, case-insensitive, with `*` matching any characters
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub document_ids: Vec<Uuid>,
+    /// Phrase whose exact occurrence in a chunk marks its document
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+impl ErasureRequest {
+    pub fn is_empty(&self) -> bool {
+        self.filename.as_deref().map_or(true, |f| f.trim().is_empty())
+            && self.document_ids.is_empty()
+            && self.content.as_deref().map_or(true, |c| c.trim().is_empty())
+    }
+}
+
+/// What an erasure removed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErasureReport {
+    pub id: Uuid,
+    /// Erased documents
+    pub document_ids: Vec<Uuid>,
+    pub filenames: Vec<String>,
+    /// Chunks removed from the vector store, chunk content and full-text index
+    pub chunks: usize,
+    /// File registry rows removed
+    pub file_records: usize,
+    /// Original files removed from document storage (GCS)
+    pub stored_objects: usize,
+    /// Superseded versions of the erased files
+    pub document_versions: usize,
+    /// Cached answers citing the documents
+    pub cached_answers: usize,
+    /// Learned interactions, generated question-answer pairs and corrections
+    pub interactions: usize,
+    pub traces: usize,
+    pub guard_events: usize,
+    pub audit_records: usize,
+    /// Saved query reports citing the documents
+    pub saved_query_reports: usize,
+    /// Registry snapshots taken while the documents existed
+    pub snapshots: usize,
+    /// Signed text of the report
+    pub summary: String,
+    pub erased_at: DateTime<Utc>,
+    /// Signature over `summary`, a hash of each erased document and the
+    /// remaining corpus (none without provenance signing configured)
+    pub provenance: Option<AnswerProvenance>,
+}
+
+/// Current documents an erasure request matches
+pub async fn matching_documents(state: &AppState, request: &ErasureRequest) -> Result<Vec<Document>> {
+    if request.is_empty() {
+        return Err(Error::Config("Give a filename, document ids or content to erase by".to_string()));
+    }
+
+    let mut ids: HashSet<Uuid> = request.document_ids.iter().copied().collect();
+    if let Some(content) = request.content.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        let matches = state
+            .vector_store_provider()
+            .string_search(content, CONTENT_SEARCH_LIMIT, None, StringSearchMode::Exact)
+            .await?;
+        ids.extend(matches.iter().map(|m| m.document_id));
+    }
+    let pattern = request.filename.as_deref().map(str::trim).filter(|f| !f.is_empty());
+
+    Ok(state
+        .list_documents()
+        .into_iter()
+        .filter(|doc| ids.contains(&doc.id) || pattern.is_some_and(|p| filename_matches(p, &doc.filename)))
+        .collect())
+}
+
+/// Erase the documents a request matches from every store
+///
+/// Documents are deleted first (vectors, then database rows, then stored
+/// originals); derived data goes afterwards. A failure part way leaves the
+/// derived data of already deleted documents, so the request can be repeated
+/// with the reported document ids.
+pub async fn erase(state: &AppState, request: &ErasureRequest) -> Result<ErasureReport> {
+    let docs = matching_documents(state, request).await?;
+    let ids: HashSet<Uuid> = docs.iter().map(|doc| doc.id).collect();
+    let id_list: Vec<Uuid> = docs.iter().map(|doc| doc.id).collect();
+    let filenames: Vec<String> = docs.iter().map(|doc| doc.filename.clone()).collect();
+    let citation_hashes: Vec<String> = docs.iter().map(|doc| citation_hash(&doc.id, &doc.content_hash)).collect();
+
+    let deleted = RagEngine::from_state(state.clone()).delete_documents(docs, false).await?;
+
+    let database = state.database();
+    let mut document_versions = 0;
+    for filename in filenames.iter().collect::<HashSet<_>>() {
+        document_versions += database.prune_document_versions(filename, 0)?;
+    }
+    let interactions = state.knowledge_store().remove_citing(&ids);
+    let traces = database.delete_traces_citing(&id_list)?;
+    let guard_events = database.delete_guard_events_for(&id_list)?;
+    let audit_records = state.audit().erase(&ids)?;
+    let saved_query_reports = database.delete_reports_citing(&id_list)?;
+
+    let erased_at = Utc::now();
+    // Every earlier snapshot holds the documents' rows
+    let snapshots = if id_list.is_empty() { 0 } else { state.snapshots().remove_before(erased_at)? };
+    // Keep a point to restore to
+    if snapshots > 0 {
+        if let Err(e) = state.snapshots().take(state).await {
+            tracing::warn!("Failed to take a snapshot after erasure: {}", e);
+        }
+    }
+    let summary = format!(
+        "Erased {} documents at {}: {}",
+        id_list.len(),
+        erased_at.to_rfc3339(),
+        id_list.iter().map(Uuid::to_string).collect::<Vec<_>>().join(", ")
+    );
+    let provenance = state
+        .provenance_signer()
+        .map(|signer| signer.sign(&summary, citation_hashes, state.corpus_snapshot_id()));
+
+    tracing::warn!(
+        "Erased {} documents: {} chunks, {} versions, {} interactions, {} traces, {} audit records, {} reports, {} snapshots",
+        id_list.len(), deleted.chunks, document_versions, interactions, traces, audit_records, saved_query_reports, snapshots
+    );
+
+    Ok(ErasureReport {
+        id: Uuid::new_v4(),
+        document_ids: id_list,
+        filenames,
+        chunks: deleted.chunks,
+        file_records: deleted.file_records,
+        stored_objects: deleted.stored_objects,
+        document_versions,
+        cached_answers: deleted.cached_answers,
+        interactions,
+        traces,
+        guard_events,
+        audit_records,
+        saved_query_reports,
+        snapshots,
+        summary,
+        erased_at,
+        provenance,
+    })
+}
+
+/// Case-insensitive match of a whole filename against a `*` pattern
+fn filename_matches(pattern: &str, filename: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let filename = filename.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let [first, middle @ .., last] = parts[..] else {
+        return pattern == filename;
+    };
+    if !filename.starts_with(first) {
+        return false;
+    }
+    let mut rest = &filename[first.len()..];
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filename_matches() {
+        assert!(filename_matches("Contract_Smith.pdf", "contract_smith.PDF"));
+        assert!(!filename_matches("contract_smith.pdf", "contract_smith.pdf.bak"));
+        assert!(filename_matches("*smith*", "hr/2023/Contract_Smith.pdf"));
+        assert!(filename_matches("contract_*.pdf", "contract_smith.pdf"));
+        assert!(!filename_matches("contract_*.pdf", "contract_smith.docx"));
+        // The prefix and suffix can't overlap
+        assert!(!filename_matches("ab*ba", "aba"));
+        assert!(ErasureRequest::default().is_empty());
+    }
+
+    #[test]
+    fn test_erasure_removes_reports_and_snapshots() {
+        use crate::config::SnapshotsConfig;
+        use crate::server::reports::Report;
+        use crate::storage::{FileRegistryDb, SnapshotManager};
+        use crate::types::{Chunk, ChunkSource, Citation, QueryResponse};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = FileRegistryDb::new(&dir.path().join("rag_registry.db")).unwrap();
+        let (erased, kept) = (Uuid::new_v4(), Uuid::new_v4());
+        let report = |document_id: Uuid| {
+            let chunk = Chunk::new(document_id, "text".to_string(), ChunkSource::text("a.txt".to_string()), 0, 4, 0);
+            Report {
+                id: Uuid::new_v4(),
+                saved_query_id: Uuid::new_v4(),
+                name: "weekly".to_string(),
+                new_documents: None,
+                response: QueryResponse::new("answer".to_string(), vec![Citation::from_chunk(&chunk, 0.9)], 1),
+                created_at: Utc::now(),
+            }
+        };
+        let (erased_report, kept_report) = (report(erased), report(kept));
+        db.insert_report(&erased_report, 10).unwrap();
+        db.insert_report(&kept_report, 10).unwrap();
+
+        assert_eq!(db.delete_reports_citing(&[erased]).unwrap(), 1);
+        assert!(db.list_reports(&erased_report.saved_query_id, 10).unwrap().is_empty());
+        assert_eq!(db.list_reports(&kept_report.saved_query_id, 10).unwrap().len(), 1);
+
+        let snapshots = SnapshotManager::new(SnapshotsConfig::default(), dir.path());
+        let snapshot = dir.path().join("snapshots").join("rag_registry-20240101T000000.000Z.db");
+        std::fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+        db.snapshot_to(&snapshot).unwrap();
+        assert_eq!(snapshots.list().unwrap().len(), 1);
+        assert_eq!(snapshots.remove_before(Utc::now()).unwrap(), 1);
+        assert!(snapshots.list().unwrap().is_empty());
+    }
+}
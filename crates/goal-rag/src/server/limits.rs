@@ -0,0 +1,184 @@
+//! Request timeouts, concurrency limits and load shedding
+//!
+//! `[server.limits]` sorts API requests into queries, uploads and the rest.
+//! Each class has its own timeout; requests beyond the global in-flight
+//! limit, queries while the LLM is saturated and uploads while the job queue
+//! is full are refused right away with 429 and `Retry-After` instead of
+//! piling up. Limits hold until the response head is sent, so streamed
+//! bodies (exports, task events) don't count against them.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::config::RequestLimitsConfig;
+use crate::error::Error;
+
+use super::state::AppState;
+
+/// Kind of API request, for its timeout and shedding rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouteClass {
+    /// Answers a question, usually with the LLM
+    Query,
+    /// Uploads files for ingestion
+    Ingest,
+    Other,
+}
+
+/// Limits shared by all requests
+pub struct RequestLimits {
+    state: AppState,
+    config: RequestLimitsConfig,
+    in_flight: AtomicUsize,
+    queries: AtomicUsize,
+}
+
+impl RequestLimits {
+    pub fn new(state: AppState, config: RequestLimitsConfig) -> Self {
+        Self {
+            state,
+            config,
+            in_flight: AtomicUsize::new(0),
+            queries: AtomicUsize::new(0),
+        }
+    }
+
+    fn shed(&self, message: String) -> Response {
+        tracing::debug!("Shedding request: {}", message);
+        Error::Overloaded { message, retry_after_secs: self.config.retry_after_secs }.into_response()
+    }
+
+    /// Why queries can't be taken now, if the LLM is saturated
+    fn llm_saturated(&self) -> Option<String> {
+        if self.config.max_llm_wait_secs == 0 {
+            return None;
+        }
+        let name = self.state.llm_provider().name();
+        let backlog = self.state.rate_limits().limiter(name).backlog();
+        (backlog > Duration::from_secs(self.config.max_llm_wait_secs))
+            .then(|| format!("{} rate limit backlog is {}s", name, backlog.as_secs()))
+    }
+
+    /// Why uploads can't be taken now, if the job queue is full
+    fn queue_full(&self) -> Option<String> {
+        if self.config.max_queued_jobs == 0 {
+            return None;
+        }
+        let stats = self.state.job_queue().stats();
+        let queued = stats.pending + stats.processing;
+        (queued >= self.config.max_queued_jobs).then(|| format!("{} jobs are queued", queued))
+    }
+
+    fn timeout(&self, class: RouteClass) -> u64 {
+        match class {
+            RouteClass::Query => self.config.query_timeout_secs,
+            RouteClass::Ingest => self.config.ingest_timeout_secs,
+            RouteClass::Other => self.config.default_timeout_secs,
+        }
+    }
+}
+
+/// Apply the limits to an API request
+pub async fn limit_requests(State(limits): State<Arc<RequestLimits>>, request: Request, next: Next) -> Response {
+    let Some(class) = classify(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let config = &limits.config;
+
+    let Some(_request) = take_slot(&limits.in_flight, config.max_in_flight) else {
+        return limits.shed(format!("More than {} requests in flight", config.max_in_flight));
+    };
+    let _query = match class {
+        RouteClass::Query => {
+            let Some(slot) = take_slot(&limits.queries, config.max_concurrent_queries) else {
+                return limits.shed(format!("More than {} queries in flight", config.max_concurrent_queries));
+            };
+            if let Some(reason) = limits.llm_saturated() {
+                return limits.shed(reason);
+            }
+            Some(slot)
+        }
+        RouteClass::Ingest => {
+            if let Some(reason) = limits.queue_full() {
+                return limits.shed(reason);
+            }
+            None
+        }
+        RouteClass::Other => None,
+    };
+
+    let timeout = limits.timeout(class);
+    if timeout == 0 {
+        return next.run(request).await;
+    }
+    let route = format!("{} {}", request.method(), request.uri().path());
+    match tokio::time::timeout(Duration::from_secs(timeout), next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("{} timed out after {}s", route, timeout);
+            Error::Timeout(format!("{} took longer than {}s", route, timeout)).into_response()
+        }
+    }
+}
+
+/// Class of a request, `None` outside the API
+fn classify(method: &Method, path: &str) -> Option<RouteClass> {
+    let path = path.strip_prefix("/api/")?;
+    if method != Method::POST {
+        return Some(RouteClass::Other);
+    }
+    Some(match path {
+        "query" | "query/batch" | "v2/query" | "string-search" | "graph/query" => RouteClass::Query,
+        _ if path.starts_with("integrations/") => RouteClass::Query,
+        "ingest" | "ingest/async" | "ingest/path" | "admin/import" => RouteClass::Ingest,
+        _ if path.starts_with("saved-queries/") && path.ends_with("/run") => RouteClass::Query,
+        _ => RouteClass::Other,
+    })
+}
+
+/// Counted place among at most `max` (0: unlimited), released on drop
+struct Slot<'a>(&'a AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn take_slot(counter: &AtomicUsize, max: usize) -> Option<Slot<'_>> {
+    let taken = counter.fetch_add(1, Ordering::Relaxed);
+    let slot = Slot(counter);
+    (max == 0 || taken < max).then_some(slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_slots() {
+        assert_eq!(classify(&Method::POST, "/api/v2/query"), Some(RouteClass::Query));
+        assert_eq!(classify(&Method::POST, "/api/saved-queries/abc/run"), Some(RouteClass::Query));
+        assert_eq!(classify(&Method::POST, "/api/ingest/async"), Some(RouteClass::Ingest));
+        assert_eq!(classify(&Method::POST, "/api/ingest/path"), Some(RouteClass::Ingest));
+        assert_eq!(classify(&Method::POST, "/api/integrations/slack/events"), Some(RouteClass::Query));
+        assert_eq!(classify(&Method::GET, "/api/query"), Some(RouteClass::Other));
+        assert_eq!(classify(&Method::GET, "/health"), None);
+
+        let counter = AtomicUsize::new(0);
+        let first = take_slot(&counter, 1);
+        assert!(first.is_some());
+        assert!(take_slot(&counter, 1).is_none());
+        drop(first);
+        assert!(take_slot(&counter, 1).is_some());
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,170 @@
+//! Conditional requests and pagination for listing endpoints
+//!
+//! Listings carry a weak ETag derived from the registry version (bumped on
+//! every document or file registry change) and whatever narrows the listing
+//! (query parameters, caller). A request whose `If-None-Match` holds the
+//! current ETag gets `304 Not Modified` without the listing being built.
+//!
+//! Pages are taken by `offset` or by `cursor`. A cursor holds the sort key
+//! and id of the last item of the previous page, so pages don't shift when
+//! items are added or removed in between.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Largest page a listing returns
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Weak ETag of a listing at a registry version
+pub fn etag(version: u64, parts: &[&str]) -> String {
+    let mut hasher = DefaultHasher::new();
+    version.hash(&mut hasher);
+    parts.hash(&mut hasher);
+    format!("W/\"{:x}-{:016x}\"", version, hasher.finish())
+}
+
+/// Whether the request's `If-None-Match` holds the ETag (weak comparison)
+pub fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value.split(',').any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// `304 Not Modified` when the client holds the current listing, else the
+/// listing built by `body`; both carry the ETag
+pub fn conditional<T: Serialize>(headers: &HeaderMap, etag: String, body: impl FnOnce() -> Result<T>) -> Result<Response> {
+    let mut response = if not_modified(headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(body()?).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    Ok(response)
+}
+
+/// Position after the last item of a page
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    key: String,
+    id: String,
+}
+
+/// One page of a listing
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page (None on the last page)
+    pub next_cursor: Option<String>,
+}
+
+/// Sort items by `(key, id)` and take `limit` of them, after `cursor` or
+/// from `offset`
+///
+/// Keys compare as strings: use fixed-width timestamps.
+pub fn paginate<T>(
+    mut items: Vec<(String, String, T)>,
+    descending: bool,
+    cursor: Option<&str>,
+    offset: usize,
+    limit: usize,
+) -> Result<Page<T>> {
+    items.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    if descending {
+        items.reverse();
+    }
+
+    let start = match cursor {
+        Some(cursor) => {
+            let (key, id) = decode_cursor(cursor)?;
+            let after = (key.as_str(), id.as_str());
+            items
+                .iter()
+                .position(|(key, id, _)| {
+                    let position = (key.as_str(), id.as_str());
+                    if descending { position < after } else { position > after }
+                })
+                .unwrap_or(items.len())
+        }
+        None => offset.min(items.len()),
+    };
+
+    let end = start.saturating_add(limit).min(items.len());
+    let next_cursor = (end < items.len() && end > start).then(|| {
+        let (key, id, _) = &items[end - 1];
+        encode_cursor(key, id)
+    });
+    let items = items.into_iter().skip(start).take(end - start).map(|(_, _, item)| item).collect();
+
+    Ok(Page { items, next_cursor })
+}
+
+/// Opaque cursor of the position after an item
+pub fn encode_cursor(key: &str, id: &str) -> String {
+    let cursor = Cursor { key: key.to_string(), id: id.to_string() };
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).unwrap_or_default())
+}
+
+/// Sort key and id of a cursor
+pub fn decode_cursor(cursor: &str) -> Result<(String, String)> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok())
+        .map(|cursor| (cursor.key, cursor.id))
+        .ok_or_else(|| Error::Config("Invalid cursor".to_string()))
+}
+
+/// Sort key of a timestamp (fixed width, so keys sort chronologically)
+pub fn time_key(time: &chrono::DateTime<chrono::Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_pages_and_etags() {
+        let items = |names: &[&str]| -> Vec<(String, String, String)> {
+            names.iter().map(|n| (n.to_string(), n.to_string(), n.to_string())).collect()
+        };
+
+        let first = paginate(items(&["c", "a", "d", "b"]), false, None, 0, 2).unwrap();
+        assert_eq!(first.items, vec!["a", "b"]);
+        let cursor = first.next_cursor.unwrap();
+
+        // "b" was removed and "ab" added since: the next page still starts after "b"
+        let second = paginate(items(&["c", "a", "d", "ab"]), false, Some(&cursor), 0, 2).unwrap();
+        assert_eq!(second.items, vec!["c", "d"]);
+        assert!(second.next_cursor.is_none());
+
+        let descending = paginate(items(&["c", "a", "d", "b"]), true, None, 1, 2).unwrap();
+        assert_eq!(descending.items, vec!["c", "b"]);
+        assert!(paginate(items(&["a"]), false, Some("not-a-cursor"), 0, 2).is_err());
+
+        let tag = etag(7, &["status=all"]);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&tag).unwrap());
+        assert!(not_modified(&headers, &tag));
+        assert!(!not_modified(&headers, &etag(8, &["status=all"])));
+        assert!(!not_modified(&HeaderMap::new(), &tag));
+    }
+}
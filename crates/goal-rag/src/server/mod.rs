@@ -1,15 +1,32 @@
 //! HTTP server for the RAG system
 
+pub mod audit;
+pub mod auth;
+pub mod erasure;
+mod limits;
+pub mod listing;
+pub mod openapi;
+pub mod ranges;
+pub mod readiness;
+pub mod reports;
 pub mod routes;
+pub mod schedule;
+mod security;
 pub mod state;
-
-use axum::{routing::get, Router};
+#[cfg(test)]
+pub(crate) mod test_support;
+mod tls;
+#[cfg(feature = "ui")]
+mod ui;
+pub(crate) mod upload;
+pub mod warmup;
+pub mod webhooks;
+
+use axum::{extract::DefaultBodyLimit, middleware, routing::get, Router};
 use std::net::SocketAddr;
-use tower_http::{
-    compression::CompressionLayer,
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower_http::{compression::CompressionLayer, trace::TraceLayer};
 
 use crate::config::RagConfig;
 use crate::error::Result;
@@ -25,35 +42,63 @@ impl RagServer {
     /// Create a new RAG server
     pub async fn new(config: RagConfig) -> Result<Self> {
         let state = AppState::new(config.clone()).await?;
+        state.readiness().spawn_warmup(state.clone());
         Ok(Self { config, state })
     }
 
+    /// Reload the configuration from `path` on `POST /api/admin/config/reload`
+    pub fn with_config_path(self, path: Option<PathBuf>) -> Self {
+        self.state.set_config_path(path);
+        self
+    }
+
     /// Create with default configuration
     pub async fn default() -> Result<Self> {
         Self::new(RagConfig::default()).await
     }
 
     /// Build the router with all routes
-    fn build_router(&self) -> Router {
-        // CORS layer - must be added first (outermost)
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any);
-
-        
+    fn build_router(&self) -> Result<Router> {
+        let server = &self.config.server;
+        let headers = security::SecurityHeaders::new(&server.security_headers, server.tls.is_some());
+        let limits = Arc::new(limits::RequestLimits::new(self.state.clone(), server.limits.clone()));
 
-        Router::new()
+        let router = Router::new()
             // Health check
             .route("/health", get(health_check))
             .route("/ready", get(readiness))
-            // API routes with body limit for multipart uploads
-            .nest("/api", routes::api_routes(self.config.server.max_upload_size))
+            // OpenAPI specification
+            .route("/api/openapi.json", get(openapi::openapi_json))
+            // API routes with body limit for multipart uploads, behind authentication
+            .nest(
+                "/api",
+                routes::api_routes(server.max_upload_size)
+                    .layer(DefaultBodyLimit::max(server.limits.max_body_size))
+                    .layer(middleware::from_fn_with_state(self.state.clone(), auth::authenticate)),
+            );
+
+        #[cfg(feature = "swagger-ui")]
+        let router = router.merge(
+            utoipa_swagger_ui::SwaggerUi::new("/api/docs")
+                .config(utoipa_swagger_ui::Config::from("/api/openapi.json")),
+        );
+
+        #[cfg(feature = "ui")]
+        let router = router.merge(ui::routes());
+
+        let router = router
             .with_state(self.state.clone())
             // Middleware layers (order matters - applied bottom to top)
-            .layer(TraceLayer::new_for_http())
+            .layer(middleware::from_fn_with_state(limits, limits::limit_requests))
+            .layer(TraceLayer::new_for_http().make_span_with(crate::telemetry::make_request_span))
             .layer(CompressionLayer::new())
-            .layer(cors)
+            .layer(middleware::from_fn_with_state(headers, security::add_security_headers));
+
+        // CORS must be the outermost layer to answer preflight requests
+        if !server.enable_cors {
+            return Ok(router);
+        }
+        Ok(router.layer(security::cors_layer(&server.cors)?))
     }
 
     /// Start the server
@@ -62,38 +107,94 @@ impl RagServer {
             .parse()
             .map_err(|e| crate::error::Error::Config(format!("Invalid address: {}", e)))?;
 
-        let router = self.build_router();
+        let router = self.build_router()?;
+        let scheme = self.scheme();
+
+        tracing::info!("Starting RAG server on {}://{}", scheme, addr);
+        tracing::info!("API documentation: {}://{}/api/docs", scheme, addr);
+        #[cfg(feature = "ui")]
+        tracing::info!("Web UI: {}://{}/ui/", scheme, addr);
 
-        tracing::info!("Starting RAG server on http://{}", addr);
-        tracing::info!("API documentation: http://{}/api", addr);
+        if let Some(ref tls) = self.config.server.tls {
+            if tls.client_ca_file.is_some() {
+                tracing::info!("Client certificates required");
+            }
+            return tls::serve(addr, router, tls).await;
+        }
 
         let listener = tokio::net::TcpListener::bind(addr)
             .await
             .map_err(|e| crate::error::Error::Config(format!("Failed to bind: {}", e)))?;
 
-        axum::serve(listener, router)
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .map_err(|e| crate::error::Error::Internal(format!("Server error: {}", e)))?;
 
         Ok(())
     }
 
+    /// Run as a dedicated ingestion worker without serving HTTP
+    ///
+    /// Workers are started by `AppState` when `queue.run_workers` is set; this
+    /// just keeps the process alive until Ctrl+C.
+    pub async fn run_worker(self) -> Result<()> {
+        if !self.config.queue.run_workers {
+            return Err(crate::error::Error::Config(
+                "Worker mode requires queue.run_workers = true".to_string(),
+            ));
+        }
+
+        tracing::info!("Running in worker-only mode (no HTTP server)");
+        tokio::signal::ctrl_c()
+            .await
+            .map_err(|e| crate::error::Error::Internal(format!("Signal error: {}", e)))?;
+        tracing::info!("Worker shutting down");
+
+        Ok(())
+    }
+
     /// Get the server address
     pub fn address(&self) -> String {
         format!("{}:{}", self.config.server.host, self.config.server.port)
     }
+
+    /// `https` when the server terminates TLS, otherwise `http`
+    pub fn scheme(&self) -> &'static str {
+        if self.config.server.tls.is_some() { "https" } else { "http" }
+    }
 }
 
 /// Health check endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "Server is up", body = String)
+    )
+)]
 async fn health_check() -> &'static str {
     "OK"
 }
 
 /// Readiness check endpoint
-async fn readiness(state: axum::extract::State<AppState>) -> axum::http::StatusCode {
-    if state.is_ready() {
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "system",
+    responses(
+        (status = 200, description = "Ready to serve requests", body = readiness::ReadinessReport),
+        (status = 503, description = "Starting up or a required dependency is down", body = readiness::ReadinessReport)
+    )
+)]
+async fn readiness(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> (axum::http::StatusCode, axum::Json<readiness::ReadinessReport>) {
+    let report = state.readiness().report(&state).await;
+    let status = if report.ready {
         axum::http::StatusCode::OK
     } else {
         axum::http::StatusCode::SERVICE_UNAVAILABLE
-    }
+    };
+    (status, axum::Json(report))
 }
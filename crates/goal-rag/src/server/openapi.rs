@@ -0,0 +1,194 @@
+//! OpenAPI 3 specification generated from the route handlers
+//!
+//! Served at `/api/openapi.json`; Swagger UI at `/api/docs` (with the
+//! `swagger-ui` feature). Handlers carry `#[utoipa::path]` attributes and the
+//! request/response types derive `ToSchema`, so the spec follows the code.
+
+use axum::Json;
+use std::sync::OnceLock;
+use utoipa::{OpenApi, ToSchema};
+
+use super::routes::{
+    admin, audit, chunks, connectors, documents, files, graph, ingest, integrations, jobs, learning, prompts, provenance,
+    query, reports, search, usage, webhooks,
+};
+
+/// Multipart upload for the ingest endpoints
+///
+/// Every part except `options` is treated as a file. Send `options` first:
+/// files it lists are checked like `/api/files/check` before they are read.
+#[derive(Debug, ToSchema)]
+pub struct IngestUpload {
+    /// Files to ingest (one part per file, filename taken from the part)
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub files: Vec<Vec<u8>>,
+    /// JSON-encoded ingest options
+    pub options: Option<crate::types::query::IngestOptions>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Goal RAG API",
+        description = "Document ingestion, retrieval and citation-aware answers"
+    ),
+    paths(
+        super::health_check,
+        super::readiness,
+        super::routes::info,
+        super::routes::capabilities,
+        documents::list_documents,
+        documents::get_document,
+        documents::get_original,
+        documents::get_plaintext,
+        documents::get_page_thumbnail,
+        documents::delete_document,
+        documents::list_document_versions,
+        documents::diff_document_versions,
+        chunks::list_document_chunks,
+        chunks::update_chunk,
+        documents::delete_documents,
+        documents::purge_collection,
+        ingest::ingest_files,
+        jobs::ingest_async,
+        jobs::ingest_path,
+        jobs::list_jobs,
+        jobs::list_incomplete_jobs,
+        jobs::get_job_progress,
+        jobs::get_job_files_progress,
+        jobs::resume_job,
+        jobs::get_parsers_status,
+        files::list_files,
+        files::check_files,
+        files::list_failed_files,
+        files::clear_failed_files,
+        files::list_dead_letter_files,
+        files::file_stats,
+        files::get_sync_status,
+        files::get_file_status,
+        files::delete_file_record,
+        query::query_rag,
+        query::query_batch,
+        query::query_rag_v2,
+        query::string_search,
+        reports::list_saved_queries,
+        reports::create_saved_query,
+        reports::get_saved_query,
+        reports::update_saved_query,
+        reports::delete_saved_query,
+        reports::run_saved_query,
+        reports::list_reports,
+        search::get_facets,
+        learning::submit_feedback,
+        learning::list_corrections,
+        learning::verify_correction,
+        learning::delete_correction,
+        learning::get_experiments,
+        learning::list_qa_pairs,
+        learning::start_qa_generation,
+        learning::get_qa_generation_status,
+        provenance::verify_provenance,
+        provenance::get_provenance_key,
+        admin::reindex_analyzers,
+        admin::export_traces,
+        admin::export_knowledge_base,
+        admin::take_snapshot,
+        admin::list_snapshots,
+        admin::import_knowledge_base,
+        admin::list_guard_events,
+        admin::erase,
+        audit::list_audit_records,
+        admin::start_reindex,
+        admin::get_reindex_status,
+        admin::reload_config,
+        admin::start_embedding_repair,
+        admin::get_embedding_repair_status,
+        connectors::list_connectors,
+        connectors::sync_connector,
+        admin::list_tasks,
+        admin::get_task,
+        admin::task_events,
+        admin::get_metrics,
+        usage::get_usage,
+        webhooks::list_webhooks,
+        webhooks::register_webhook,
+        webhooks::delete_webhook,
+        prompts::list_prompts,
+        prompts::create_prompt,
+        prompts::get_prompt,
+        prompts::update_prompt,
+        prompts::delete_prompt,
+        prompts::reload_prompts,
+        graph::query_graph,
+        graph::start_graph_build,
+        graph::get_graph_status,
+        integrations::slack_events,
+        integrations::slack_command,
+        integrations::teams_message,
+    ),
+    tags(
+        (name = "documents", description = "Document management"),
+        (name = "ingest", description = "File upload and processing"),
+        (name = "jobs", description = "Asynchronous ingestion jobs"),
+        (name = "files", description = "File registry and deduplication"),
+        (name = "query", description = "Question answering and search"),
+        (name = "admin", description = "Maintenance and diagnostics"),
+        (name = "integrations", description = "Slack and Teams bots (called by the platforms)"),
+        (name = "system", description = "Health and capabilities"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Routes only available with the `gcp` feature
+#[cfg(feature = "gcp")]
+#[derive(OpenApi)]
+#[openapi(paths(
+    files::sync_from_gcs,
+    files::get_gcs_counts,
+    super::routes::vector_index::get_vector_index,
+    super::routes::vector_index::create_vector_index,
+    super::routes::vector_index::create_index_endpoint,
+    super::routes::vector_index::deploy_vector_index,
+    super::routes::vector_index::resize_vector_index,
+    super::routes::vector_index::get_vector_index_operation,
+))]
+struct GcpApiDoc;
+
+/// The full specification for this build
+pub fn spec() -> &'static utoipa::openapi::OpenApi {
+    static SPEC: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    SPEC.get_or_init(build_spec)
+}
+
+#[cfg(not(feature = "gcp"))]
+fn build_spec() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+#[cfg(feature = "gcp")]
+fn build_spec() -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    doc.merge(GcpApiDoc::openapi());
+    doc
+}
+
+/// GET /api/openapi.json - OpenAPI 3 specification
+pub async fn openapi_json() -> Json<&'static utoipa::openapi::OpenApi> {
+    Json(spec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_routes() {
+        let spec = spec();
+        for path in ["/api/query", "/api/ingest", "/api/documents/{id}", "/api/admin/tasks"] {
+            assert!(spec.paths.paths.contains_key(path), "missing {}", path);
+        }
+        let json = serde_json::to_value(spec).unwrap();
+        assert!(json["components"]["schemas"]["QueryRequest"].is_object());
+        assert!(json["components"]["schemas"]["ProblemDetails"].is_object());
+    }
+}
@@ -0,0 +1,172 @@
+//! Byte-range requests for document downloads
+//!
+//! Originals and plain text are served with `Accept-Ranges: bytes`, so PDF
+//! viewers can fetch the pages they show and clients can read the span a
+//! citation points at. A single `bytes=` range is honoured (including suffix
+//! ranges); multiple ranges and other units get the whole resource, as RFC
+//! 9110 allows.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// Part of a resource a request asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No (usable) Range header: the whole resource
+    Full,
+    /// Bytes `start..=end`
+    Partial { start: u64, end: u64 },
+    /// A range starting past the end of the resource
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// The range requested by a request's `Range` header for a resource of
+    /// `size` bytes
+    pub fn from_headers(headers: &HeaderMap, size: u64) -> Self {
+        headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(Self::Full, |value| Self::parse(value, size))
+    }
+
+    fn parse(value: &str, size: u64) -> Self {
+        let Some(spec) = value.trim().strip_prefix("bytes=").filter(|spec| !spec.contains(',')) else {
+            return Self::Full;
+        };
+        let Some((start, end)) = spec.split_once('-') else {
+            return Self::Full;
+        };
+        let (start, end) = (start.trim(), end.trim());
+
+        // Suffix range: the last `end` bytes
+        if start.is_empty() {
+            return match end.parse::<u64>() {
+                Ok(0) => Self::Unsatisfiable,
+                Ok(_) if size == 0 => Self::Unsatisfiable,
+                Ok(length) => Self::Partial { start: size.saturating_sub(length), end: size - 1 },
+                Err(_) => Self::Full,
+            };
+        }
+
+        let Ok(start) = start.parse::<u64>() else {
+            return Self::Full;
+        };
+        if start >= size {
+            return Self::Unsatisfiable;
+        }
+        match end {
+            "" => Self::Partial { start, end: size - 1 },
+            end => match end.parse::<u64>() {
+                Ok(end) if end >= start => Self::Partial { start, end: end.min(size - 1) },
+                _ => Self::Full,
+            },
+        }
+    }
+}
+
+/// Response for a requested range of a resource of `size` bytes, `body`
+/// holding (or streaming) just the requested bytes
+pub fn ranged_response(
+    range: ByteRange,
+    size: u64,
+    body: impl Into<Body>,
+    content_type: &str,
+    disposition: Option<String>,
+) -> Response {
+    let (status, length, content_range) = match range {
+        ByteRange::Full => (StatusCode::OK, size, None),
+        ByteRange::Partial { start, end } => {
+            (StatusCode::PARTIAL_CONTENT, end - start + 1, Some(format!("bytes {}-{}/{}", start, end, size)))
+        }
+        ByteRange::Unsatisfiable => {
+            return (StatusCode::RANGE_NOT_SATISFIABLE, [(header::CONTENT_RANGE, format!("bytes */{}", size))])
+                .into_response();
+        }
+    };
+
+    let mut response = (status, body.into()).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    if let Ok(value) = HeaderValue::from_str(content_type) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Some(value) = content_range.and_then(|range| HeaderValue::from_str(&range).ok()) {
+        headers.insert(header::CONTENT_RANGE, value);
+    }
+    if let Some(value) = disposition.and_then(|disposition| HeaderValue::from_str(&disposition).ok()) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+/// Content-Disposition naming a file (non-ASCII characters replaced)
+///
+/// Files are shown `inline`, except types a browser would run in the API's
+/// origin (HTML, SVG, XML and JavaScript), which are downloaded.
+pub fn file_disposition(filename: &str, content_type: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' || c == ' ' { c } else { '_' })
+        .collect();
+    let kind = if is_active_content(content_type) { "attachment" } else { "inline" };
+    format!("{}; filename=\"{}\"", kind, name)
+}
+
+/// Whether a browser would execute content of this type
+fn is_active_content(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    matches!(essence.as_str(), "text/html" | "text/xml" | "text/xsl" | "application/xml")
+        || essence.ends_with("+xml")
+        || essence.contains("javascript")
+        || essence.contains("ecmascript")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let parse = |value| ByteRange::parse(value, 1000);
+        assert_eq!(parse("bytes=0-99"), ByteRange::Partial { start: 0, end: 99 });
+        assert_eq!(parse("bytes=900-"), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse("bytes=-100"), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse("bytes=990-2000"), ByteRange::Partial { start: 990, end: 999 });
+        assert_eq!(parse("bytes=1000-"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=-0"), ByteRange::Unsatisfiable);
+        // Invalid, multiple and non-byte ranges get everything
+        assert_eq!(parse("bytes=50-10"), ByteRange::Full);
+        assert_eq!(parse("bytes=0-1,5-6"), ByteRange::Full);
+        assert_eq!(parse("items=0-1"), ByteRange::Full);
+        assert_eq!(
+            file_disposition("hr/Politique été.pdf", "application/pdf"),
+            "inline; filename=\"Politique _t_.pdf\""
+        );
+        assert_eq!(file_disposition("page.html", "text/html"), "attachment; filename=\"page.html\"");
+        assert_eq!(file_disposition("logo.svg", "image/svg+xml"), "attachment; filename=\"logo.svg\"");
+        assert_eq!(file_disposition("feed.xml", "text/xml; charset=utf-8"), "attachment; filename=\"feed.xml\"");
+        assert_eq!(file_disposition("app.js", "text/javascript"), "attachment; filename=\"app.js\"");
+        assert_eq!(file_disposition("photo.png", "image/png"), "inline; filename=\"photo.png\"");
+    }
+
+    #[test]
+    fn test_ranged_response_headers() {
+        // Streamed bodies have no length of their own, so it comes from the range
+        let body = Body::from_stream(futures::stream::iter([Ok::<_, std::io::Error>(bytes::Bytes::from("0123"))]));
+        let response = ranged_response(ByteRange::Partial { start: 10, end: 13 }, 100, body, "application/pdf", None);
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-13/100");
+
+        let response = ranged_response(ByteRange::Full, 4, b"text".to_vec(), "text/plain", None);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+    }
+}
@@ -0,0 +1,327 @@
+//! Dependency checks behind the readiness probe
+//!
+//! `/ready` asks the embedding and LLM providers, the vector store, the
+//! registry database (for a write lock) and, with the GCP backend, the GCS
+//! bucket whether they answer, each within `server.readiness.timeout_secs`.
+//! Results are reused for `cache_secs` so frequent Kubernetes probes don't
+//! turn into provider traffic, and concurrent probes wait for one check.
+//! The server only reports started once the startup warm-up has finished.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::ReadinessConfig;
+use crate::error::{Error, Result};
+
+use super::state::AppState;
+use super::warmup::{self, WarmupReport};
+
+/// Health of one dependency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComponentHealth {
+    /// `embeddings`, `llm`, `vector_store`, `database` or `document_store`
+    pub component: String,
+    /// Provider behind the component
+    pub provider: String,
+    pub healthy: bool,
+    /// Whether the server is only ready with this component healthy
+    pub required: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Readiness with the health of every dependency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    /// Started and every required component healthy
+    pub ready: bool,
+    /// Startup and warm-up finished
+    pub started: bool,
+    pub components: Vec<ComponentHealth>,
+    /// Startup warm-up, when enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupReport>,
+    /// When the components were last checked
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Cached dependency checks
+pub struct Readiness {
+    config: ReadinessConfig,
+    cached: tokio::sync::Mutex<Option<(Instant, DateTime<Utc>, Vec<ComponentHealth>)>>,
+    warmup: parking_lot::RwLock<Option<WarmupReport>>,
+}
+
+impl Readiness {
+    pub fn new(config: ReadinessConfig) -> Self {
+        Self {
+            config,
+            cached: tokio::sync::Mutex::new(None),
+            warmup: parking_lot::RwLock::new(None),
+        }
+    }
+
+    /// Mark the server not ready and warm it up in the background, marking
+    /// it ready when done (or after `warmup.timeout_secs`)
+    pub fn spawn_warmup(self: &Arc<Self>, state: AppState) {
+        if !self.config.warmup.enabled {
+            return;
+        }
+        state.set_ready(false);
+        *self.warmup.write() = Some(WarmupReport::new());
+
+        let readiness = Arc::clone(self);
+        tokio::spawn(async move {
+            let config = &readiness.config.warmup;
+            let started = Instant::now();
+            let run = warmup::warm_up(&state, config, |step| {
+                if let Some(report) = readiness.warmup.write().as_mut() {
+                    report.steps.push(step);
+                }
+            });
+            let timed_out = tokio::time::timeout(Duration::from_secs(config.timeout_secs.max(1)), run)
+                .await
+                .is_err();
+            if timed_out {
+                tracing::warn!("Warm-up did not finish within {}s, reporting ready", config.timeout_secs);
+            } else {
+                tracing::info!("Warm-up finished in {}ms", started.elapsed().as_millis());
+            }
+
+            if let Some(report) = readiness.warmup.write().as_mut() {
+                report.running = false;
+                report.timed_out = timed_out;
+                report.completed_at = Some(Utc::now());
+            }
+            state.set_ready(true);
+        });
+    }
+
+    /// Readiness now, checking the components unless a recent result is cached
+    pub async fn report(&self, state: &AppState) -> ReadinessReport {
+        let (checked_at, components) = {
+            let mut cached = self.cached.lock().await;
+            match *cached {
+                Some((at, checked_at, ref components)) if at.elapsed() < Duration::from_secs(self.config.cache_secs) => {
+                    (checked_at, components.clone())
+                }
+                _ => {
+                    let components = self.check(state).await;
+                    let checked_at = Utc::now();
+                    *cached = Some((Instant::now(), checked_at, components.clone()));
+                    (checked_at, components)
+                }
+            }
+        };
+
+        let started = state.is_ready();
+        ReadinessReport {
+            ready: started && components.iter().all(|c| c.healthy || !c.required),
+            started,
+            components,
+            warmup: self.warmup.read().clone(),
+            checked_at,
+        }
+    }
+
+    async fn check(&self, state: &AppState) -> Vec<ComponentHealth> {
+        let embedder = state.embedding_provider();
+        let llm = state.llm_provider().clone();
+        let vectors = state.vector_store_provider();
+        let database = state.database().clone();
+
+        let (embeddings, llm, vectors, database) = futures::join!(
+            self.probe("embeddings", embedder.name(), embedder.health_check()),
+            self.probe("llm", llm.name(), llm.health_check()),
+            self.probe("vector_store", vectors.name(), vectors.health_check()),
+            self.probe("database", "sqlite", async move {
+                tokio::task::spawn_blocking(move || database.check_writable())
+                    .await
+                    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+                Ok(true)
+            }),
+        );
+        #[allow(unused_mut)]
+        let mut components = vec![embeddings, llm, vectors, database];
+
+        #[cfg(feature = "gcp")]
+        if let Some(store) = state.document_store() {
+            use crate::providers::DocumentStoreProvider;
+            components.push(self.probe("document_store", store.name(), store.health_check()).await);
+        }
+
+        for component in components.iter().filter(|c| !c.healthy) {
+            tracing::warn!(
+                "Readiness: {} ({}) unhealthy: {}",
+                component.component,
+                component.provider,
+                component.error.as_deref().unwrap_or("")
+            );
+        }
+        components
+    }
+
+    async fn probe(&self, component: &str, provider: &str, check: impl Future<Output = Result<bool>>) -> ComponentHealth {
+        let started = Instant::now();
+        let timeout = Duration::from_secs(self.config.timeout_secs.max(1));
+        let (healthy, error) = match tokio::time::timeout(timeout, check).await {
+            Ok(Ok(true)) => (true, None),
+            Ok(Ok(false)) => (false, Some("reported unhealthy".to_string())),
+            Ok(Err(e)) => (false, Some(e.to_string())),
+            Err(_) => (false, Some(format!("no answer within {}s", timeout.as_secs()))),
+        };
+        ComponentHealth {
+            component: component.to_string(),
+            provider: provider.to_string(),
+            healthy,
+            required: !self.config.optional_components.iter().any(|c| c == component),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{wait_until, TestApp};
+
+    fn readiness(configure: impl FnOnce(&mut ReadinessConfig)) -> Readiness {
+        let mut config = ReadinessConfig { cache_secs: 0, ..Default::default() };
+        configure(&mut config);
+        Readiness::new(config)
+    }
+
+    fn component<'a>(report: &'a ReadinessReport, name: &str) -> &'a ComponentHealth {
+        report.components.iter().find(|c| c.component == name).expect("component checked")
+    }
+
+    #[tokio::test]
+    async fn test_ready_with_every_component_healthy() {
+        let app = TestApp::new().await;
+        let report = readiness(|_| {}).report(&app.state).await;
+
+        assert!(report.ready);
+        assert!(report.started);
+        let names: Vec<_> = report.components.iter().map(|c| c.component.as_str()).collect();
+        assert_eq!(names, ["embeddings", "llm", "vector_store", "database"]);
+        assert!(report.components.iter().all(|c| c.healthy && c.required && c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_with_providers_down() {
+        let app = TestApp::new().await;
+        app.ollama.set_healthy(false);
+        let report = readiness(|_| {}).report(&app.state).await;
+
+        assert!(!report.ready);
+        assert!(report.started);
+        for name in ["embeddings", "llm"] {
+            let health = component(&report, name);
+            assert!(!health.healthy, "{} should be unhealthy", name);
+            assert!(health.error.is_some());
+        }
+        assert!(component(&report, "vector_store").healthy);
+        assert!(component(&report, "database").healthy);
+    }
+
+    #[tokio::test]
+    async fn test_optional_components_do_not_hold_back_readiness() {
+        let app = TestApp::new().await;
+        app.ollama.set_healthy(false);
+        let report = readiness(|config| {
+            config.optional_components = vec!["embeddings".to_string(), "llm".to_string()];
+        })
+        .report(&app.state)
+        .await;
+
+        assert!(report.ready);
+        let embeddings = component(&report, "embeddings");
+        assert!(!embeddings.healthy);
+        assert!(!embeddings.required);
+        assert!(component(&report, "database").required);
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_with_database_locked() {
+        let app = TestApp::new().await;
+        let lock = rusqlite::Connection::open(app.state.config().vector_db.registry_path()).unwrap();
+        lock.execute_batch("BEGIN EXCLUSIVE").unwrap();
+
+        let report = readiness(|config| config.timeout_secs = 1).report(&app.state).await;
+        assert!(!report.ready);
+        let database = component(&report, "database");
+        assert!(!database.healthy);
+        assert!(database.error.is_some());
+        assert!(component(&report, "embeddings").healthy);
+
+        // Let go so the blocked check finishes before the runtime shuts down
+        lock.execute_batch("ROLLBACK").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_until_started() {
+        let app = TestApp::new().await;
+        app.state.set_ready(false);
+        let readiness = readiness(|_| {});
+
+        let report = readiness.report(&app.state).await;
+        assert!(!report.ready);
+        assert!(!report.started);
+        assert!(report.components.iter().all(|c| c.healthy));
+
+        app.state.set_ready(true);
+        assert!(readiness.report(&app.state).await.ready);
+    }
+
+    #[tokio::test]
+    async fn test_checks_are_cached() {
+        let app = TestApp::new().await;
+        let readiness = readiness(|config| config.cache_secs = 3600);
+        let first = readiness.report(&app.state).await;
+        assert!(first.ready);
+
+        app.ollama.set_healthy(false);
+        let second = readiness.report(&app.state).await;
+        assert!(second.ready);
+        assert_eq!(second.checked_at, first.checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_failed_warmup_is_reported_and_does_not_block_startup() {
+        let app = TestApp::new().await;
+        app.ollama.set_healthy(false);
+        let readiness = Arc::new(readiness(|config| config.warmup.enabled = true));
+
+        readiness.spawn_warmup(app.state.clone());
+        assert!(!app.state.is_ready());
+        wait_until(|| app.state.is_ready()).await;
+
+        let report = readiness.report(&app.state).await;
+        assert!(report.started);
+        let warmup = report.warmup.expect("warm-up reported");
+        assert!(!warmup.running);
+        assert!(!warmup.timed_out);
+        assert!(warmup.completed_at.is_some());
+        let failed: Vec<_> = warmup.steps.iter().filter(|s| !s.ok).map(|s| s.step.as_str()).collect();
+        assert_eq!(failed, ["embedding", "generation"]);
+        assert!(warmup.steps.iter().filter(|s| !s.ok).all(|s| s.error.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_warmup_disabled_leaves_readiness_alone() {
+        let app = TestApp::new().await;
+        let readiness = Arc::new(readiness(|config| config.warmup.enabled = false));
+
+        readiness.spawn_warmup(app.state.clone());
+        let report = readiness.report(&app.state).await;
+        assert!(report.ready);
+        assert!(report.warmup.is_none());
+    }
+}
@@ -0,0 +1,387 @@
+//! Saved queries and scheduled reports
+//!
+//! A saved query is a named question with its query options and filters,
+//! optionally run on a cron schedule (see [`CronSchedule`]). Running one, on
+//! schedule or on request, answers it as the principal that saved it and
+//! stores the answer as a report, sends a `report.generated` webhook event
+//! and e-mails the report to the query's recipients. With
+//! `new_documents_only` a run only draws on documents ingested since the
+//! previous run (or since the query was saved), and is skipped when there
+//! are none, e.g. for a weekly summary of new compliance documents.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::SmtpConfig;
+use crate::engine::RagEngine;
+use crate::error::{Error, Result};
+use crate::storage::FileRegistryDb;
+use crate::types::query::QueryRequest;
+use crate::types::response::QueryResponse;
+use crate::types::Principal;
+
+use super::schedule::CronSchedule;
+use super::state::AppState;
+use super::webhooks::{WebhookEvent, WebhookEventKind};
+
+/// How often the scheduler looks for due queries
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// What to run and where to send the results
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SavedQueryDefinition {
+    pub name: String,
+    /// Question with its options and filters
+    pub query: QueryRequest,
+    /// Cron expression in UTC (`minute hour day month weekday`, or
+    /// `@daily`, `@weekly`, ...); run on request only if absent
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Only answer from documents ingested since the previous run
+    #[serde(default)]
+    pub new_documents_only: bool,
+    /// Addresses the reports are e-mailed to (needs `reports.smtp`)
+    #[serde(default)]
+    pub email: Vec<String>,
+}
+
+/// A saved query
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SavedQuery {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub definition: SavedQueryDefinition,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// When the schedule runs the query next
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// Principal the query is answered as (stored separately)
+    #[serde(skip)]
+    pub owner: Option<Principal>,
+}
+
+impl SavedQuery {
+    /// Whether a caller may see and run the query: its owner, admins, and
+    /// everyone when authentication is off
+    pub fn visible_to(&self, principal: Option<&Principal>) -> bool {
+        match (principal, &self.owner) {
+            (None, _) => true,
+            (Some(principal), _) if principal.admin => true,
+            (Some(principal), Some(owner)) => principal.id == owner.id,
+            (Some(_), None) => false,
+        }
+    }
+
+    fn schedule_next_run(&mut self) {
+        self.next_run_at = self
+            .definition
+            .schedule
+            .as_deref()
+            .and_then(|expression| CronSchedule::parse(expression).ok())
+            .and_then(|schedule| schedule.next_after(self.last_run_at.unwrap_or(self.created_at)));
+    }
+}
+
+/// The answer of one run of a saved query
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Report {
+    pub id: Uuid,
+    pub saved_query_id: Uuid,
+    pub name: String,
+    /// New documents the run drew on (`new_documents_only`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_documents: Option<usize>,
+    pub response: QueryResponse,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Stores, schedules and runs saved queries
+pub struct SavedQueries {
+    database: Arc<FileRegistryDb>,
+    queries: RwLock<Vec<SavedQuery>>,
+}
+
+impl SavedQueries {
+    /// Load the saved queries from the database
+    pub fn new(database: Arc<FileRegistryDb>) -> Result<Self> {
+        let mut queries = database.list_saved_queries()?;
+        queries.iter_mut().for_each(SavedQuery::schedule_next_run);
+        Ok(Self { database, queries: RwLock::new(queries) })
+    }
+
+    pub fn list(&self) -> Vec<SavedQuery> {
+        self.queries.read().clone()
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<SavedQuery> {
+        self.queries.read().iter().find(|q| q.id == *id).cloned()
+    }
+
+    /// Save a new query, answered as `owner`
+    pub fn create(&self, definition: SavedQueryDefinition, owner: Option<Principal>) -> Result<SavedQuery> {
+        validate(&definition)?;
+        let mut query = SavedQuery {
+            id: Uuid::new_v4(),
+            definition,
+            created_at: Utc::now(),
+            last_run_at: None,
+            next_run_at: None,
+            owner,
+        };
+        query.schedule_next_run();
+        self.database.upsert_saved_query(&query)?;
+        self.queries.write().push(query.clone());
+        tracing::info!("Saved query {} '{}'", query.id, query.definition.name);
+        Ok(query)
+    }
+
+    /// Replace the definition of a saved query
+    pub fn update(&self, id: &Uuid, definition: SavedQueryDefinition) -> Result<SavedQuery> {
+        validate(&definition)?;
+        let mut query = self.get(id).ok_or_else(|| Error::NotFound(format!("Saved query {}", id)))?;
+        query.definition = definition;
+        query.schedule_next_run();
+        self.database.upsert_saved_query(&query)?;
+        self.replace(query.clone());
+        Ok(query)
+    }
+
+    /// Delete a saved query and its reports, returning whether it existed
+    pub fn delete(&self, id: &Uuid) -> Result<bool> {
+        let deleted = self.database.delete_saved_query(id)?;
+        self.queries.write().retain(|q| q.id != *id);
+        Ok(deleted)
+    }
+
+    /// Reports of a saved query, newest first
+    pub fn reports(&self, id: &Uuid, limit: usize) -> Result<Vec<Report>> {
+        self.database.list_reports(id, limit)
+    }
+
+    /// Run a saved query now
+    ///
+    /// Returns `None` when `new_documents_only` is set and no document was
+    /// ingested since the previous run.
+    pub async fn run(&self, state: &AppState, id: &Uuid) -> Result<Option<Report>> {
+        let saved = self.get(id).ok_or_else(|| Error::NotFound(format!("Saved query {}", id)))?;
+        let started_at = Utc::now();
+        let mut request = saved.definition.query.clone().with_principal(saved.owner.clone());
+
+        let mut new_documents = None;
+        if saved.definition.new_documents_only {
+            let since = saved.last_run_at.unwrap_or(saved.created_at);
+            let ids: Vec<Uuid> = state
+                .list_documents()
+                .into_iter()
+                .filter(|doc| doc.ingested_at > since)
+                .filter(|doc| request.collection.as_deref().map_or(true, |c| doc.collection() == Some(c)))
+                .filter(|doc| request.document_filter.as_ref().map_or(true, |ids| ids.contains(&doc.id)))
+                .map(|doc| doc.id)
+                .collect();
+            if ids.is_empty() {
+                tracing::info!("Skipped saved query '{}': no documents ingested since {}", saved.definition.name, since);
+                self.record_run(&saved, started_at)?;
+                return Ok(None);
+            }
+            new_documents = Some(ids.len());
+            request.document_filter = Some(ids);
+        }
+
+        let response = RagEngine::from_state(state.clone()).query(request).await?;
+        let report = Report {
+            id: Uuid::new_v4(),
+            saved_query_id: saved.id,
+            name: saved.definition.name.clone(),
+            new_documents,
+            response,
+            created_at: Utc::now(),
+        };
+        let config = state.config();
+        self.database.insert_report(&report, config.reports.keep)?;
+        self.record_run(&saved, started_at)?;
+        tracing::info!("Generated report {} for saved query '{}'", report.id, saved.definition.name);
+
+        state.webhooks().emit(WebhookEvent::new(
+            WebhookEventKind::ReportGenerated,
+            serde_json::json!({
+                "report_id": report.id,
+                "saved_query_id": saved.id,
+                "name": report.name,
+                "question": saved.definition.query.question,
+                "answer": report.response.answer,
+                "sources": report.response.sources,
+                "new_documents": report.new_documents,
+            }),
+        ));
+
+        if !saved.definition.email.is_empty() {
+            match config.reports.smtp.clone() {
+                Some(smtp) => {
+                    let (recipients, subject) = (saved.definition.email.clone(), format!("Report: {}", report.name));
+                    let body = email_body(&saved, &report);
+                    tokio::spawn(async move {
+                        if let Err(e) = send_email(&smtp, &recipients, &subject, body).await {
+                            tracing::warn!("Failed to e-mail report '{}': {}", subject, e);
+                        }
+                    });
+                }
+                None => tracing::warn!("Report '{}' not e-mailed: reports.smtp is not configured", report.name),
+            }
+        }
+
+        Ok(Some(report))
+    }
+
+    /// Run due queries every minute
+    ///
+    /// A failed run is retried on the next tick; the window of a
+    /// `new_documents_only` query only moves on after a successful run.
+    pub fn spawn_scheduler(self: &Arc<Self>, state: AppState) {
+        let queries = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SCHEDULER_TICK);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                let due: Vec<SavedQuery> = queries
+                    .list()
+                    .into_iter()
+                    .filter(|q| q.next_run_at.map_or(false, |at| at <= now))
+                    .collect();
+                for query in due {
+                    if let Err(e) = queries.run(&state, &query.id).await {
+                        tracing::warn!("Scheduled run of saved query '{}' failed: {}", query.definition.name, e);
+                    }
+                }
+            }
+        });
+    }
+
+    fn record_run(&self, saved: &SavedQuery, at: DateTime<Utc>) -> Result<()> {
+        // Re-read in case the query was edited while it ran
+        let Some(mut query) = self.get(&saved.id) else {
+            return Ok(());
+        };
+        query.last_run_at = Some(at);
+        query.schedule_next_run();
+        self.database.upsert_saved_query(&query)?;
+        self.replace(query);
+        Ok(())
+    }
+
+    fn replace(&self, query: SavedQuery) {
+        if let Some(existing) = self.queries.write().iter_mut().find(|q| q.id == query.id) {
+            *existing = query;
+        }
+    }
+}
+
+fn validate(definition: &SavedQueryDefinition) -> Result<()> {
+    if definition.name.trim().is_empty() {
+        return Err(Error::Config("Saved query name is empty".to_string()));
+    }
+    if definition.query.question.trim().is_empty() {
+        return Err(Error::Config("Saved query question is empty".to_string()));
+    }
+    if let Some(ref expression) = definition.schedule {
+        CronSchedule::parse(expression)?;
+    }
+    if let Some(address) = definition.email.iter().find(|address| !address.contains('@')) {
+        return Err(Error::Config(format!("Invalid e-mail address '{}'", address)));
+    }
+    Ok(())
+}
+
+/// Plain-text report: the question, the answer and its sources
+fn email_body(saved: &SavedQuery, report: &Report) -> String {
+    let mut body = format!("{}\n\nQuestion: {}\n\n{}\n", report.name, saved.definition.query.question, report.response.answer);
+    if let Some(count) = report.new_documents {
+        body.push_str(&format!("\nDrawn from {} documents ingested since the previous report.\n", count));
+    }
+    if !report.response.sources.is_empty() {
+        body.push_str("\nSources:\n");
+        for source in &report.response.sources {
+            body.push_str(&format!("- {} ({} passages)\n", source.filename, source.passages));
+        }
+    }
+    body
+}
+
+#[cfg(feature = "email-reports")]
+async fn send_email(smtp: &SmtpConfig, recipients: &[String], subject: &str, body: String) -> Result<()> {
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+    let invalid = |e: &dyn std::fmt::Display| Error::Config(format!("Invalid e-mail: {}", e));
+    let mut message = Message::builder().from(smtp.from.parse().map_err(|e| invalid(&e))?).subject(subject);
+    for recipient in recipients {
+        message = message.to(recipient.parse().map_err(|e| invalid(&e))?);
+    }
+    let message = message.body(body).map_err(|e| invalid(&e))?;
+
+    let transport = if smtp.starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)
+            .map_err(|e| Error::Config(format!("Invalid SMTP host '{}': {}", smtp.host, e)))?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host)
+    };
+    let mut transport = transport.port(smtp.port);
+    if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport
+        .build()
+        .send(message)
+        .await
+        .map_err(|e| Error::Internal(format!("SMTP delivery failed: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "email-reports"))]
+async fn send_email(_smtp: &SmtpConfig, _recipients: &[String], _subject: &str, _body: String) -> Result<()> {
+    Err(Error::Config("built without the email-reports feature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_query_visibility_and_schedule() {
+        let mut query = SavedQuery {
+            id: Uuid::new_v4(),
+            definition: SavedQueryDefinition {
+                name: "Weekly compliance".to_string(),
+                query: QueryRequest::new("Summarize the new compliance documents"),
+                schedule: Some("0 8 * * 1".to_string()),
+                new_documents_only: true,
+                email: vec![],
+            },
+            created_at: DateTime::parse_from_rfc3339("2024-01-03T12:00:00Z").unwrap().with_timezone(&Utc),
+            last_run_at: None,
+            next_run_at: None,
+            owner: Some(Principal::new("alice", vec![])),
+        };
+        query.schedule_next_run();
+        assert_eq!(query.next_run_at.unwrap().to_rfc3339(), "2024-01-08T08:00:00+00:00");
+
+        assert!(query.visible_to(None));
+        assert!(query.visible_to(Some(&Principal::new("alice", vec![]))));
+        assert!(!query.visible_to(Some(&Principal::new("bob", vec![]))));
+
+        // The owner is stored apart from the definition
+        let json = serde_json::to_value(&query).unwrap();
+        assert_eq!(json["name"], "Weekly compliance");
+        assert!(json.get("owner").is_none());
+        assert!(validate(&SavedQueryDefinition { schedule: Some("every monday".to_string()), ..query.definition }).is_err());
+    }
+}
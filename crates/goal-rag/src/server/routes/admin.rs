@@ -0,0 +1,639 @@
+//! Administrative endpoints
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::config_check;
+use crate::error::{Error, ProblemDetails, Result};
+use crate::processing::TaskProgress;
+use crate::retrieval::trace;
+use crate::server::erasure::{self, ErasureReport, ErasureRequest};
+use crate::server::state::AppState;
+use crate::storage::{backup, SnapshotInfo};
+use crate::types::response::ImportResponse;
+
+/// Request for analyzer reindexing
+#[derive(Debug, Default, serde::Deserialize, ToSchema)]
+pub struct ReindexAnalyzersRequest {
+    /// Collection to reindex (all collections if omitted)
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+/// POST /api/admin/analyzers/reindex - Rebuild analyzed FTS terms
+///
+/// Run after changing a collection's analyzer in the config. Chunk content and
+/// embeddings are untouched, only the lexical search terms are regenerated.
+#[utoipa::path(
+    post,
+    path = "/api/admin/analyzers/reindex",
+    tag = "admin",
+    request_body = ReindexAnalyzersRequest,
+    responses(
+        (status = 200, description = "Analyzer terms rebuilt", body = serde_json::Value)
+    )
+)]
+pub async fn reindex_analyzers(
+    State(state): State<AppState>,
+    Json(request): Json<ReindexAnalyzersRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let database = state.database().clone();
+    let collection = request.collection.clone();
+    let start = std::time::Instant::now();
+
+    let chunks_indexed = tokio::task::spawn_blocking(move || {
+        database.reindex_chunk_terms(collection.as_deref())
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    tracing::info!(
+        "Reindexed analyzer terms for {} ({} chunks, {}ms)",
+        request.collection.as_deref().unwrap_or("all collections"),
+        chunks_indexed,
+        start.elapsed().as_millis()
+    );
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "collection": request.collection,
+        "chunks_indexed": chunks_indexed,
+        "duration_ms": start.elapsed().as_millis() as u64
+    })))
+}
+
+/// Query parameters for trace export
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportTracesQuery {
+    /// Output format: "ndjson" (default) or "parquet"
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Only traces created at or after this time (RFC 3339)
+    #[serde(default)]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Maximum number of traces (default: 10000)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// GET /api/admin/traces/export - Export retrieval traces
+///
+/// NDJSON has one trace per line with nested candidates. Parquet has one row
+/// per candidate with trace fields repeated (requires the `parquet-export` feature).
+#[utoipa::path(
+    get,
+    path = "/api/admin/traces/export",
+    tag = "admin",
+    params(ExportTracesQuery),
+    responses(
+        (status = 200, description = "Trace export (NDJSON or Parquet)", content_type = "application/x-ndjson"),
+        (status = 400, description = "Unsupported format", body = ProblemDetails)
+    )
+)]
+pub async fn export_traces(
+    State(state): State<AppState>,
+    Query(params): Query<ExportTracesQuery>,
+) -> Result<Response> {
+    let database = state.database().clone();
+    let since = params.since;
+    let limit = params.limit.unwrap_or(10_000);
+
+    let traces = tokio::task::spawn_blocking(move || database.list_traces(since, limit))
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    let format = params.format.as_deref().unwrap_or("ndjson");
+    let (body, content_type, extension) = match format {
+        "ndjson" | "jsonl" => (trace::to_ndjson(&traces)?, "application/x-ndjson", "ndjson"),
+        #[cfg(feature = "parquet-export")]
+        "parquet" => (trace::to_parquet(&traces)?, "application/vnd.apache.parquet", "parquet"),
+        #[cfg(not(feature = "parquet-export"))]
+        "parquet" => {
+            return Err(Error::Config(
+                "Parquet export requires the 'parquet-export' feature".to_string(),
+            ))
+        }
+        other => {
+            return Err(Error::Config(format!(
+                "Unsupported trace export format '{}' (use ndjson or parquet)",
+                other
+            )))
+        }
+    };
+
+    tracing::info!("Exported {} retrieval traces as {}", traces.len(), extension);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"retrieval_traces.{}\"", extension),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// POST /api/admin/export - Export the knowledge base
+///
+/// Streams a tar archive of the documents, their plain text, chunks and
+/// embeddings, and the file registry, for backups or to promote a knowledge
+/// base to another instance with `/api/admin/import`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/export",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Tar archive of the knowledge base", content_type = "application/x-tar")
+    )
+)]
+pub async fn export_knowledge_base(State(state): State<AppState>) -> Response {
+    let (tx, rx) = tokio::sync::mpsc::channel(8);
+    tokio::task::spawn_blocking(move || {
+        let writer = ChannelWriter { tx: tx.clone(), buffer: Vec::with_capacity(ChannelWriter::CAPACITY) };
+        match backup::export_archive(&state, writer) {
+            Ok(summary) => tracing::info!(
+                "Exported {} documents, {} chunks and {} file records",
+                summary.documents,
+                summary.chunks,
+                summary.file_records
+            ),
+            Err(e) => {
+                // Failing the body tells the client the archive is incomplete
+                tracing::error!("Knowledge base export failed: {}", e);
+                let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+            }
+        }
+    });
+
+    let filename = format!("knowledge_base_{}.tar", chrono::Utc::now().format("%Y%m%d_%H%M%S"));
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+/// Writes into the response body of an export, a buffer at a time
+struct ChannelWriter {
+    tx: tokio::sync::mpsc::Sender<std::io::Result<bytes::Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    const CAPACITY: usize = 256 * 1024;
+}
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= Self::CAPACITY {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = bytes::Bytes::from(std::mem::replace(&mut self.buffer, Vec::with_capacity(Self::CAPACITY)));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "export client disconnected"))
+    }
+}
+
+/// POST /api/admin/import - Restore a knowledge base export
+///
+/// The request body is an archive from `/api/admin/export` (tar, or tar.gz).
+/// It is spooled to a temporary file first, so its size isn't limited by
+/// memory. Documents and file records that already exist are skipped;
+/// chunks embedded with another model than the collection's are re-embedded.
+#[utoipa::path(
+    post,
+    path = "/api/admin/import",
+    tag = "admin",
+    request_body(content = Vec<u8>, content_type = "application/x-tar", description = "Export archive"),
+    responses(
+        (status = 200, description = "Restored and skipped counts", body = ImportResponse),
+        (status = 400, description = "Invalid or unsupported archive", body = ProblemDetails)
+    )
+)]
+pub async fn import_knowledge_base(State(state): State<AppState>, body: Body) -> Result<Json<ImportResponse>> {
+    use tokio::io::AsyncWriteExt;
+
+    let spool = tempfile::NamedTempFile::new()?;
+    let mut file = tokio::fs::File::from_std(spool.reopen()?);
+    let mut stream = body.into_data_stream();
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|e| Error::Config(format!("Failed to read archive: {}", e)))?;
+        file.write_all(&data).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let response = backup::import_archive(&state, spool.path()).await?;
+    Ok(Json(response))
+}
+
+/// Query parameters for the guard audit log
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GuardEventsQuery {
+    /// Only events created at or after this time (RFC 3339)
+    #[serde(default)]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Maximum number of events (default: 100)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// GET /api/admin/guard/events - Prompt-injection guard audit log
+///
+/// Rejected or flagged queries and retrieved chunks that had instruction-like
+/// text removed, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/admin/guard/events",
+    tag = "admin",
+    params(GuardEventsQuery),
+    responses(
+        (status = 200, description = "Guard events", body = serde_json::Value)
+    )
+)]
+pub async fn list_guard_events(
+    State(state): State<AppState>,
+    Query(params): Query<GuardEventsQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let database = state.database().clone();
+    let since = params.since;
+    let limit = params.limit.unwrap_or(100);
+
+    let events = tokio::task::spawn_blocking(move || database.list_guard_events(since, limit))
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    Ok(Json(serde_json::json!({
+        "events": events,
+        "count": events.len()
+    })))
+}
+
+/// DELETE /api/admin/erase - Erase documents and everything derived from them
+///
+/// Removes the documents matching a filename pattern, ids or a content phrase
+/// from every store: chunks, full-text and vector indexes, stored originals,
+/// versions, cached answers, learned interactions, traces, guard events and
+/// audit records. Returns the erasure report, signed when provenance signing
+/// is configured.
+#[utoipa::path(
+    delete,
+    path = "/api/admin/erase",
+    tag = "admin",
+    request_body = ErasureRequest,
+    responses(
+        (status = 200, description = "Erasure report", body = ErasureReport),
+        (status = 400, description = "No filename, ids or content given", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn erase(
+    State(state): State<AppState>,
+    Json(request): Json<ErasureRequest>,
+) -> Result<Json<ErasureReport>> {
+    Ok(Json(erasure::erase(&state, &request).await?))
+}
+
+/// POST /api/admin/reindex - Rebuild the index with the current configuration
+///
+/// Re-chunks and re-embeds every document in the background. The existing
+/// index keeps serving queries until the rebuilt one is swapped in.
+#[utoipa::path(
+    post,
+    path = "/api/admin/reindex",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Reindex started", body = serde_json::Value),
+        (status = 400, description = "Reindex already running", body = ProblemDetails)
+    )
+)]
+pub async fn start_reindex(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let progress = state.reindex().start(state.clone())?;
+
+    tracing::info!(
+        "Started reindex {} ({} documents)",
+        progress.id,
+        progress.total_documents
+    );
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "reindex": progress,
+        "status_url": "/api/admin/reindex"
+    })))
+}
+
+/// GET /api/admin/reindex - Progress of the current or last reindex
+#[utoipa::path(
+    get,
+    path = "/api/admin/reindex",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Reindex progress", body = serde_json::Value)
+    )
+)]
+pub async fn get_reindex_status(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let Some(progress) = state.reindex().progress() else {
+        return Ok(Json(serde_json::json!({
+            "running": false,
+            "reindex": null
+        })));
+    };
+
+    let task = state.tasks().get(&progress.task_id);
+
+    Ok(Json(serde_json::json!({
+        "running": state.reindex().is_running(),
+        "percent_complete": progress.percent_complete(),
+        "throughput_per_sec": task.as_ref().map(|t| t.throughput_per_sec),
+        "eta_secs": task.as_ref().and_then(|t| t.eta_secs),
+        "reindex": progress
+    })))
+}
+
+/// POST /api/admin/config/reload - Re-read the configuration file
+///
+/// Applies the settings that can change at runtime (prompt template choices,
+/// rate limits, timeouts, retrieval tuning); settings that only apply after
+/// a restart are listed. Changes that need a reindex, such as the embedding
+/// model, dimensions or chunking, refuse the whole reload.
+#[utoipa::path(
+    post,
+    path = "/api/admin/config/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Configuration reloaded", body = serde_json::Value),
+        (status = 400, description = "Invalid configuration or a change that needs a reindex", body = ProblemDetails)
+    )
+)]
+pub async fn reload_config(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let path = state.config_path();
+    let loaded = config_check::load(path.as_deref())?;
+    let plan = state.reload_config(&loaded.config)?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "config_path": path,
+        "applied": plan.applied,
+        "requires_restart": plan.requires_restart
+    })))
+}
+
+/// POST /api/admin/snapshots - Snapshot the SQLite registry
+///
+/// Uses SQLite's online backup API, so the snapshot is consistent while
+/// ingestion keeps writing. Restore it with `ruvector-rag restore-snapshot`
+/// while the server is stopped.
+#[utoipa::path(
+    post,
+    path = "/api/admin/snapshots",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Snapshot taken", body = SnapshotInfo)
+    )
+)]
+pub async fn take_snapshot(State(state): State<AppState>) -> Result<Json<SnapshotInfo>> {
+    let snapshot = state.snapshots().take(&state).await?;
+    Ok(Json(snapshot))
+}
+
+/// GET /api/admin/snapshots - List registry snapshots, newest first
+#[utoipa::path(
+    get,
+    path = "/api/admin/snapshots",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Local snapshots", body = Vec<SnapshotInfo>)
+    )
+)]
+pub async fn list_snapshots(State(state): State<AppState>) -> Result<Json<Vec<SnapshotInfo>>> {
+    Ok(Json(state.snapshots().list()?))
+}
+
+/// POST /api/admin/repair-embeddings - Re-embed chunks without a usable vector
+///
+/// Embeds the chunks whose embedding failed during ingestion and, on the local
+/// backend, replaces all-zero vectors written by earlier versions. Runs in the
+/// background; progress is reported under `/api/admin/tasks`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/repair-embeddings",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Repair started", body = serde_json::Value),
+        (status = 400, description = "Repair already running", body = ProblemDetails)
+    )
+)]
+pub async fn start_embedding_repair(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let report = state.embedding_repair().start(state.clone(), true)?;
+
+    tracing::info!("Started embedding repair ({} chunks pending)", report.remaining);
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "repair": report,
+        "status_url": format!("/api/admin/tasks/{}", report.task_id)
+    })))
+}
+
+/// GET /api/admin/repair-embeddings - Pending chunks and the last repair run
+#[utoipa::path(
+    get,
+    path = "/api/admin/repair-embeddings",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Repair status", body = serde_json::Value)
+    )
+)]
+pub async fn get_embedding_repair_status(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let pending = state.database().count_pending_embeddings()?;
+
+    Ok(Json(serde_json::json!({
+        "running": state.embedding_repair().is_running(),
+        "pending": pending,
+        "repair": state.embedding_repair().last_report()
+    })))
+}
+
+/// GET /api/metrics - Embedding throughput metrics
+///
+/// Queue depth, batch sizes and latencies of the embedding batch scheduler
+/// (all zero unless `embeddings.batching` is enabled).
+#[utoipa::path(
+    get,
+    path = "/api/metrics",
+    tag = "system",
+    responses(
+        (status = 200, description = "Embedding metrics", body = serde_json::Value)
+    )
+)]
+pub async fn get_metrics(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let rag_config = state.config();
+    let config = &rag_config.embeddings.batching;
+
+    Json(serde_json::json!({
+        "embedding": {
+            "provider": state.embedding_provider().name(),
+            "batching": {
+                "enabled": config.enabled,
+                "max_batch_size": config.max_batch_size,
+                "max_latency_ms": config.max_latency_ms,
+                "max_concurrent_batches": config.max_concurrent_batches
+            },
+            "scheduler": state.embedding_scheduler().snapshot()
+        }
+    }))
+}
+
+/// Query parameters for task listing
+#[derive(Debug, Default, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListTasksQuery {
+    /// Only pending and running tasks
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// GET /api/admin/tasks - Progress of ingestion jobs and maintenance tasks
+#[utoipa::path(
+    get,
+    path = "/api/admin/tasks",
+    tag = "admin",
+    params(ListTasksQuery),
+    responses(
+        (status = 200, description = "Tasks with progress, throughput and ETA", body = serde_json::Value)
+    )
+)]
+pub async fn list_tasks(
+    State(state): State<AppState>,
+    Query(params): Query<ListTasksQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let tasks: Vec<serde_json::Value> = all_tasks(&state)
+        .iter()
+        .filter(|t| !params.active || !t.status.is_finished())
+        .map(task_json)
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "count": tasks.len(),
+        "tasks": tasks
+    })))
+}
+
+/// GET /api/admin/tasks/:id - Progress of one task or ingestion job
+#[utoipa::path(
+    get,
+    path = "/api/admin/tasks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Task or job ID")),
+    responses(
+        (status = 200, description = "Task progress", body = TaskProgress),
+        (status = 404, description = "Task not found", body = ProblemDetails)
+    )
+)]
+pub async fn get_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    let task = state
+        .tasks()
+        .get(&task_id)
+        .or_else(|| state.job_queue().get_progress(task_id).map(|job| TaskProgress::from_job(&job)))
+        .ok_or_else(|| Error::NotFound(format!("Task {}", task_id)))?;
+
+    Ok(Json(task_json(&task)))
+}
+
+/// GET /api/admin/tasks/events - Server-sent events with task progress
+///
+/// Emits a `task` event whenever a task changes, checked once per second.
+#[utoipa::path(
+    get,
+    path = "/api/admin/tasks/events",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Stream of `task` events", content_type = "text/event-stream")
+    )
+)]
+pub async fn task_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    let ticker = tokio::time::interval(Duration::from_secs(1));
+    let seen: HashMap<Uuid, chrono::DateTime<chrono::Utc>> = HashMap::new();
+
+    let events = stream::unfold((ticker, seen), move |(mut ticker, mut seen)| {
+        let state = state.clone();
+        async move {
+            ticker.tick().await;
+
+            let tasks = all_tasks(&state);
+            seen.retain(|id, _| tasks.iter().any(|t| t.id == *id));
+
+            let mut events = Vec::new();
+            for task in &tasks {
+                if seen.get(&task.id) == Some(&task.updated_at) {
+                    continue;
+                }
+                seen.insert(task.id, task.updated_at);
+                let event = Event::default()
+                    .event("task")
+                    .id(task.id.to_string())
+                    .json_data(task_json(task))
+                    .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()));
+                events.push(Ok(event));
+            }
+
+            Some((stream::iter(events), (ticker, seen)))
+        }
+    })
+    .flatten();
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Maintenance tasks and ingestion jobs, most recently started first
+fn all_tasks(state: &AppState) -> Vec<TaskProgress> {
+    let mut tasks = state.tasks().list();
+    tasks.extend(state.job_queue().list_jobs().iter().map(TaskProgress::from_job));
+    tasks.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+    tasks
+}
+
+fn task_json(task: &TaskProgress) -> serde_json::Value {
+    let mut value = serde_json::to_value(task).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        object.insert("percent_complete".to_string(), serde_json::json!(task.percent_complete()));
+    }
+    value
+}
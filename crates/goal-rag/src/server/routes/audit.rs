@@ -0,0 +1,43 @@
+//! Answer audit log
+
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::server::audit::{AuditFilter, AuditRecord};
+use crate::server::state::AppState;
+use crate::types::Principal;
+
+/// GET /api/audit - Answer audit log
+///
+/// Every query with the chunks it retrieved, the hash of its final prompt,
+/// the model and the answer, newest first. Callers see their own queries;
+/// admins see everyone's.
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    tag = "admin",
+    params(AuditFilter),
+    responses(
+        (status = 200, description = "Audit records, newest first", body = Vec<AuditRecord>),
+        (status = 400, description = "Audit log not enabled", body = ProblemDetails)
+    )
+)]
+pub async fn list_audit_records(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(mut filter): Query<AuditFilter>,
+) -> Result<Json<Vec<AuditRecord>>> {
+    if let Some(Extension(p)) = principal.filter(|Extension(p)| !p.admin) {
+        filter.principal = Some(p.id);
+    }
+
+    let audit = state.audit().clone();
+    let records = tokio::task::spawn_blocking(move || audit.list(&filter))
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    Ok(Json(records))
+}
@@ -0,0 +1,206 @@
+//! Chunk inspection and editing endpoints
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::engine::RagEngine;
+use crate::error::{Error, ProblemDetails, Result};
+use crate::server::listing;
+use crate::server::state::AppState;
+use crate::storage::ChunkContentRecord;
+use crate::types::acl::can_read;
+use crate::types::{ChunkKind, FileType, Principal};
+
+/// Chunks per page when the request doesn't set `limit`
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Query parameters for listing a document's chunks
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListChunksQuery {
+    /// Maximum chunks per page (default: 50)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Chunks to skip (ignored with `cursor`)
+    #[serde(default)]
+    pub offset: usize,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Include the embedding vectors (default: false)
+    #[serde(default)]
+    pub embeddings: bool,
+}
+
+/// A stored chunk
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChunkDetail {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub chunk_index: u32,
+    /// Text as indexed
+    pub content: String,
+    pub filename: String,
+    pub file_type: FileType,
+    pub page_number: Option<u32>,
+    pub section_title: Option<String>,
+    /// Character range in the extracted document text
+    pub char_start: usize,
+    pub char_end: usize,
+    pub collection: Option<String>,
+    /// Parent window of a small-to-big chunk
+    pub parent_id: Option<Uuid>,
+    pub kind: ChunkKind,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<String>,
+    /// Model that embedded the chunk
+    pub embedding_model: Option<String>,
+    /// Embedding vector (on request; the Vertex AI backend only has vectors
+    /// of chunks ingested since the server started)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl From<ChunkContentRecord> for ChunkDetail {
+    fn from(record: ChunkContentRecord) -> Self {
+        Self {
+            id: record.id,
+            document_id: record.document_id,
+            chunk_index: record.chunk_index,
+            content: record.content,
+            filename: record.filename,
+            file_type: record.file_type,
+            page_number: record.page_number,
+            section_title: record.section_title,
+            char_start: record.char_start,
+            char_end: record.char_end,
+            collection: record.collection,
+            parent_id: record.parent_id,
+            kind: record.kind,
+            keywords: record.keywords,
+            entities: record.entities,
+            embedding_model: record.embedding_model,
+            embedding: None,
+        }
+    }
+}
+
+/// A page of a document's chunks
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkListResponse {
+    pub chunks: Vec<ChunkDetail>,
+    /// Chunks of the document
+    pub total_count: usize,
+    /// Cursor of the next page (absent on the last page)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Request to replace the text of a chunk
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateChunkRequest {
+    /// Corrected text
+    pub content: String,
+}
+
+/// GET /api/documents/:id/chunks - Chunks of a document in order
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/chunks",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID"), ListChunksQuery),
+    responses(
+        (status = 200, description = "A page of the document's chunks", body = ChunkListResponse),
+        (status = 400, description = "Invalid cursor", body = ProblemDetails),
+        (status = 404, description = "Document not found", body = ProblemDetails)
+    )
+)]
+pub async fn list_document_chunks(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ListChunksQuery>,
+) -> Result<Json<ChunkListResponse>> {
+    readable_document(&state, principal.as_ref().map(|Extension(p)| p), &id)?;
+
+    let database = state.database().clone();
+    let records = tokio::task::spawn_blocking(move || database.list_chunks_for_document(&id))
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+    let total_count = records.len();
+
+    let items = records
+        .into_iter()
+        .map(|record| (format!("{:010}", record.chunk_index), record.id.to_string(), record))
+        .collect();
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(listing::MAX_PAGE_SIZE);
+    let page = listing::paginate(items, false, params.cursor.as_deref(), params.offset, limit)?;
+
+    let store = state.vector_store().filter(|_| params.embeddings);
+    let chunks = page
+        .items
+        .into_iter()
+        .map(|record| {
+            let id = record.id;
+            let mut detail = ChunkDetail::from(record);
+            if params.embeddings {
+                // Vectors are in the local index, or in the chunk store kept for Vertex AI lookups
+                let chunk = match store {
+                    Some(ref store) => store.get_chunk(&id)?,
+                    None => state.get_chunk(&id),
+                };
+                detail.embedding = chunk.map(|chunk| chunk.embedding).filter(|e| !e.is_empty());
+            }
+            Ok(detail)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(ChunkListResponse {
+        chunks,
+        total_count,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+/// PATCH /api/chunks/:id - Replace the text of a chunk
+///
+/// For fixing badly extracted text: the chunk is re-embedded and replaced in
+/// the vector store and the full-text indexes, and cached answers citing its
+/// document are dropped.
+#[utoipa::path(
+    patch,
+    path = "/api/chunks/{id}",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Chunk ID")),
+    request_body = UpdateChunkRequest,
+    responses(
+        (status = 200, description = "Updated chunk", body = ChunkDetail),
+        (status = 400, description = "Empty text", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "Chunk not found", body = ProblemDetails)
+    )
+)]
+pub async fn update_chunk(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateChunkRequest>,
+) -> Result<Json<ChunkDetail>> {
+    let updated = RagEngine::from_state(state).update_chunk(&id, &request.content).await?;
+    Ok(Json(ChunkDetail::from(updated)))
+}
+
+/// Fails with `DocumentNotFound` unless the document exists and the caller can read it
+fn readable_document(state: &AppState, principal: Option<&Principal>, id: &Uuid) -> Result<()> {
+    state
+        .get_document(id)
+        .filter(|doc| can_read(principal, doc))
+        .map(|_| ())
+        .ok_or_else(|| Error::DocumentNotFound(id.to_string()))
+}
@@ -0,0 +1,65 @@
+//! Source connector status and manual syncs
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::connectors::ConnectorStatus;
+use crate::error::{ProblemDetails, Result};
+use crate::server::state::AppState;
+
+/// Query parameters for a manual sync
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConnectorSyncQuery {
+    /// List every file instead of the changes since the last pass; files
+    /// whose version is unchanged are still not downloaded (default: false)
+    #[serde(default)]
+    pub full: bool,
+}
+
+/// GET /api/admin/connectors - Configured connectors and their last passes
+#[utoipa::path(
+    get,
+    path = "/api/admin/connectors",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Connectors with their sync state", body = Vec<ConnectorStatus>)
+    )
+)]
+pub async fn list_connectors(State(state): State<AppState>) -> Result<Json<Vec<ConnectorStatus>>> {
+    Ok(Json(state.connectors().list(&state)?))
+}
+
+/// POST /api/admin/connectors/:name/sync - Sync a connector now
+///
+/// Runs in the background; progress is reported under `/api/admin/tasks` and
+/// `/api/admin/connectors`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/connectors/{name}/sync",
+    tag = "admin",
+    params(("name" = String, Path, description = "Connector name"), ConnectorSyncQuery),
+    responses(
+        (status = 200, description = "Sync started", body = serde_json::Value),
+        (status = 400, description = "Connector already syncing", body = ProblemDetails),
+        (status = 404, description = "Connector not configured", body = ProblemDetails)
+    )
+)]
+pub async fn sync_connector(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<ConnectorSyncQuery>,
+) -> Result<Json<serde_json::Value>> {
+    state.connectors().start(state.clone(), &name, query.full)?;
+    tracing::info!("Started {} sync of connector '{}'", if query.full { "full" } else { "incremental" }, name);
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "connector": name,
+        "status_url": "/api/admin/connectors"
+    })))
+}
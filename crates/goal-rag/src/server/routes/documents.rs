@@ -1,63 +1,341 @@
 //! Document management endpoints
 
 use axum::{
-    extract::{Path, State},
-    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::error::{Error, Result};
+use crate::engine::RagEngine;
+use crate::error::{Error, ProblemDetails, Result};
+use crate::server::listing;
+use crate::server::ranges::{file_disposition, ranged_response, ByteRange};
 use crate::server::state::AppState;
-use crate::types::response::{DocumentListResponse, DocumentSummary};
+use crate::types::acl::can_read;
+use crate::types::response::{
+    BulkDeleteResponse, DocumentListResponse, DocumentSummary, DocumentVersionSummary, DocumentVersionsResponse,
+    VersionDiffResponse,
+};
+use crate::types::{Document, Principal};
+
+/// Query parameters for listing documents
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListDocumentsQuery {
+    /// Maximum documents per page (default: all)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Documents to skip (ignored with `cursor`)
+    #[serde(default)]
+    pub offset: usize,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
 
-/// GET /api/documents - List all documents
+/// GET /api/documents - List the documents the caller can read, newest first
+///
+/// Supports `If-None-Match`: an unchanged listing is answered with 304.
+#[utoipa::path(
+    get,
+    path = "/api/documents",
+    tag = "documents",
+    params(ListDocumentsQuery),
+    responses(
+        (status = 200, description = "Documents (ETag header set)", body = DocumentListResponse),
+        (status = 304, description = "Listing unchanged since the If-None-Match ETag"),
+        (status = 400, description = "Invalid cursor", body = ProblemDetails)
+    )
+)]
 pub async fn list_documents(
     State(state): State<AppState>,
-) -> Result<Json<DocumentListResponse>> {
-    let documents: Vec<DocumentSummary> = state
-        .list_documents()
-        .iter()
-        .map(DocumentSummary::from)
-        .collect();
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<ListDocumentsQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let caller = principal.map(Principal::cache_key).unwrap_or_default();
+    let etag = listing::etag(state.registry_version(), &[&caller, &format!("{:?}", params)]);
 
-    let total_count = documents.len();
+    listing::conditional(&headers, etag, || {
+        let documents: Vec<_> = state
+            .list_documents()
+            .iter()
+            .filter(|doc| can_read(principal, doc))
+            .map(|doc| (listing::time_key(&doc.ingested_at), doc.id.to_string(), DocumentSummary::from(doc)))
+            .collect();
+        let total_count = documents.len();
+        let limit = params.limit.unwrap_or(total_count);
 
-    Ok(Json(DocumentListResponse {
-        documents,
-        total_count,
-    }))
+        let page = listing::paginate(documents, true, params.cursor.as_deref(), params.offset, limit)?;
+        Ok(DocumentListResponse {
+            documents: page.items,
+            total_count,
+            next_cursor: page.next_cursor,
+        })
+    })
 }
 
 /// GET /api/documents/:id - Get a specific document
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID")),
+    responses(
+        (status = 200, description = "Document details", body = DocumentSummary),
+        (status = 404, description = "Document not found", body = ProblemDetails)
+    )
+)]
 pub async fn get_document(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<DocumentSummary>> {
+    // Documents the caller can't read are reported as missing
     let doc = state
         .get_document(&id)
+        .filter(|doc| can_read(principal.as_ref().map(|Extension(p)| p), doc))
         .ok_or_else(|| Error::DocumentNotFound(id.to_string()))?;
 
     Ok(Json(DocumentSummary::from(&doc)))
 }
 
-/// DELETE /api/documents/:id - Delete a document
-pub async fn delete_document(
+/// GET /api/documents/:id/pages/:page/thumbnail - PNG preview of a page
+///
+/// Rendered at ingestion when `thumbnails.enabled` is set (PDFs only), so a
+/// citation's page can be shown next to it. Superseded versions keep theirs.
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/pages/{page}/thumbnail",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID (current or superseded version)"),
+        ("page" = u32, Path, description = "Page number (1-indexed)")
+    ),
+    responses(
+        (status = 200, description = "PNG thumbnail of the page", content_type = "image/png"),
+        (status = 404, description = "Document or thumbnail not found", body = ProblemDetails)
+    )
+)]
+pub async fn get_page_thumbnail(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path((id, page)): Path<(Uuid, u32)>,
+) -> Result<Response> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    readable_version(&RagEngine::from_state(state.clone()), principal, &id)?;
+
+    let not_found = || Error::NotFound(format!("thumbnail of page {} of {}", page, id));
+    let store = state.file_store().filter(|_| state.config().thumbnails.enabled).ok_or_else(not_found)?;
+    let png = store.get_thumbnail(&id, page).await?.ok_or_else(not_found)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            // A document id's pages never change; private because of document ACLs
+            (header::CACHE_CONTROL, "private, max-age=86400"),
+        ],
+        png,
+    )
+        .into_response())
+}
+
+/// GET /api/documents/:id/original - The document's original file
+///
+/// Streamed from the document store (GCS, or the local `originals` directory
+/// when `originals.keep` is set) with Range support, so viewers can open it at
+/// a citation's page. HTML, SVG, XML and JavaScript files are sent as
+/// downloads, and every file under a sandboxing Content-Security-Policy.
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/original",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID (current or superseded version)")),
+    responses(
+        (status = 200, description = "The original file"),
+        (status = 206, description = "The requested byte range of the file"),
+        (status = 404, description = "Document or stored original not found", body = ProblemDetails),
+        (status = 416, description = "Range starts past the end of the file")
+    )
+)]
+pub async fn get_original(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>> {
-    // Remove document from registry
-    let doc = state
-        .remove_document(&id)
-        .ok_or_else(|| Error::DocumentNotFound(id.to_string()))?;
+    headers: HeaderMap,
+) -> Result<Response> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let (doc, _) = readable_document(&RagEngine::from_state(state.clone()), principal, &id)?;
+
+    let not_found = || Error::NotFound(format!("stored original of {}", id));
+    let store = state.file_store().ok_or_else(not_found)?;
+    let size = store.document_size(&id).await?.ok_or_else(not_found)?;
 
-    // Delete all chunks for this document (uses provider abstraction)
-    let deleted_chunks = state.vector_store_provider().delete_by_document(&id).await?;
+    let range = ByteRange::from_headers(&headers, size);
+    let body = match range {
+        ByteRange::Full => Body::from_stream(store.stream_document(&id, None).await?),
+        ByteRange::Partial { start, end } => Body::from_stream(store.stream_document(&id, Some((start, end))).await?),
+        ByteRange::Unsatisfiable => Body::empty(),
+    };
+    let content_type = mime_guess::from_path(&doc.filename).first_or_octet_stream();
+    let disposition = file_disposition(&doc.filename, content_type.as_ref());
+    let mut response = ranged_response(range, size, body, content_type.as_ref(), Some(disposition));
+    // Uploaded files are untrusted: no scripts, no same-origin access, no sniffing
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    Ok(response)
+}
 
-    tracing::info!(
-        "Deleted document '{}' and {} chunks",
-        doc.filename,
-        deleted_chunks
-    );
+/// GET /api/documents/:id/plaintext - The text extracted from a document
+///
+/// Citation `location` offsets are byte offsets into this text; Range
+/// requests return just the cited span. Documents without stored plain text
+/// get it rebuilt from their chunks, where offsets are approximate.
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/plaintext",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID (current or superseded version)")),
+    responses(
+        (status = 200, description = "The extracted text", content_type = "text/plain"),
+        (status = 206, description = "The requested byte range of the text"),
+        (status = 404, description = "Document not found", body = ProblemDetails),
+        (status = 416, description = "Range starts past the end of the text")
+    )
+)]
+pub async fn get_plaintext(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let engine = RagEngine::from_state(state);
+    let (doc, current) = readable_document(&engine, principal, &id)?;
+    let text = engine.document_text(&doc, current).await?.into_bytes();
+
+    let size = text.len() as u64;
+    let range = ByteRange::from_headers(&headers, size);
+    let body = match range {
+        ByteRange::Full => text,
+        ByteRange::Partial { start, end } => text[start as usize..=end as usize].to_vec(),
+        ByteRange::Unsatisfiable => Vec::new(),
+    };
+    Ok(ranged_response(range, size, body, "text/plain; charset=utf-8", None))
+}
+
+/// GET /api/documents/:id/versions - Versions of a document's file
+///
+/// The id may be the current document or any version of it. Versions are
+/// listed newest first; superseded ones can be queried with
+/// `document_version` and compared with `/api/documents/{id}/diff`.
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/versions",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID (current or superseded version)")),
+    responses(
+        (status = 200, description = "Versions of the document's file", body = DocumentVersionsResponse),
+        (status = 404, description = "Document not found", body = ProblemDetails)
+    )
+)]
+pub async fn list_document_versions(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DocumentVersionsResponse>> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let engine = RagEngine::from_state(state);
+    let filename = readable_version(&engine, principal, &id)?;
+
+    let versions = engine
+        .document_versions(&filename)?
+        .into_iter()
+        .filter(|(doc, _)| can_read(principal, doc))
+        .map(|(doc, superseded_at)| DocumentVersionSummary {
+            document: DocumentSummary::from(&doc),
+            version: doc.version(),
+            current: superseded_at.is_none(),
+            superseded_at,
+        })
+        .collect();
+
+    Ok(Json(DocumentVersionsResponse { filename, versions }))
+}
+
+/// Query parameters for diffing document versions
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VersionDiffQuery {
+    /// Older version number (default: the version before `to`)
+    pub from: Option<u32>,
+    /// Newer version number (default: the newest version)
+    pub to: Option<u32>,
+}
+
+/// GET /api/documents/:id/diff - Line diff between two versions of a document
+#[utoipa::path(
+    get,
+    path = "/api/documents/{id}/diff",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID (current or superseded version)"), VersionDiffQuery),
+    responses(
+        (status = 200, description = "Unified diff of the versions' text", body = VersionDiffResponse),
+        (status = 400, description = "No earlier version to compare with", body = ProblemDetails),
+        (status = 404, description = "Document or version not found", body = ProblemDetails)
+    )
+)]
+pub async fn diff_document_versions(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<VersionDiffQuery>,
+) -> Result<Json<VersionDiffResponse>> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let engine = RagEngine::from_state(state);
+    let filename = readable_version(&engine, principal, &id)?;
+
+    Ok(Json(engine.diff_document_versions(&filename, params.from, params.to)?))
+}
+
+/// Filename of a current or superseded document the caller can read
+fn readable_version(engine: &RagEngine, principal: Option<&Principal>, id: &Uuid) -> Result<String> {
+    readable_document(engine, principal, id).map(|(doc, _)| doc.filename)
+}
+
+/// A current or superseded document the caller can read, and whether it is
+/// the current one
+fn readable_document(engine: &RagEngine, principal: Option<&Principal>, id: &Uuid) -> Result<(Document, bool)> {
+    engine
+        .find_document_version(id)?
+        .filter(|(doc, _)| can_read(principal, doc))
+        .map(|(doc, superseded_at)| (doc, superseded_at.is_none()))
+        .ok_or_else(|| Error::DocumentNotFound(id.to_string()))
+}
+
+/// DELETE /api/documents/:id - Delete a document
+#[utoipa::path(
+    delete,
+    path = "/api/documents/{id}",
+    tag = "documents",
+    params(("id" = Uuid, Path, description = "Document ID")),
+    responses(
+        (status = 200, description = "Document and its chunks deleted", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "Document not found", body = ProblemDetails)
+    )
+)]
+pub async fn delete_document(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Json<serde_json::Value>> {
+    let (doc, deleted_chunks) = RagEngine::from_state(state).delete_document(&id).await?;
 
     Ok(Json(serde_json::json!({
         "success": true,
@@ -66,3 +344,131 @@ pub async fn delete_document(
         "deleted_chunks": deleted_chunks
     })))
 }
+
+/// Documents selected by a bulk deletion (all conditions must match)
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct DocumentFilter {
+    /// Documents of this collection
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Documents whose filename contains this (case-insensitive)
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Documents ingested before this time
+    #[serde(default)]
+    pub ingested_before: Option<DateTime<Utc>>,
+    /// Documents ingested after this time
+    #[serde(default)]
+    pub ingested_after: Option<DateTime<Utc>>,
+}
+
+impl DocumentFilter {
+    fn is_empty(&self) -> bool {
+        self.collection.is_none()
+            && self.filename.is_none()
+            && self.ingested_before.is_none()
+            && self.ingested_after.is_none()
+    }
+
+    fn matches(&self, doc: &Document) -> bool {
+        self.collection.as_deref().map_or(true, |c| doc.collection() == Some(c))
+            && self
+                .filename
+                .as_ref()
+                .map_or(true, |f| doc.filename.to_lowercase().contains(&f.to_lowercase()))
+            && self.ingested_before.map_or(true, |t| doc.ingested_at < t)
+            && self.ingested_after.map_or(true, |t| doc.ingested_at > t)
+    }
+}
+
+/// Request body for bulk document deletion
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkDeleteRequest {
+    /// Documents to delete
+    #[serde(default)]
+    pub ids: Vec<Uuid>,
+    /// Or (with `ids`: and) the documents matching this filter
+    #[serde(default)]
+    pub filter: Option<DocumentFilter>,
+    /// Only report what would be deleted
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Query parameters for purging a collection
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeQuery {
+    /// Only report what would be deleted
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// DELETE /api/documents - Delete documents by id or filter
+///
+/// Removes the documents, their chunks (vector store and chunk content), file
+/// records, stored originals and cached answers. Ids that don't exist are
+/// ignored.
+#[utoipa::path(
+    delete,
+    path = "/api/documents",
+    tag = "documents",
+    request_body = BulkDeleteRequest,
+    responses(
+        (status = 200, description = "Deleted (or, for a dry run, matching) documents and counts", body = BulkDeleteResponse),
+        (status = 400, description = "Neither ids nor a filter given", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn delete_documents(
+    State(state): State<AppState>,
+    Json(request): Json<BulkDeleteRequest>,
+) -> Result<Json<BulkDeleteResponse>> {
+    let filter = request.filter.unwrap_or_default();
+    if request.ids.is_empty() && filter.is_empty() {
+        return Err(Error::Config("Give document ids or a filter to delete by".to_string()));
+    }
+
+    let ids: HashSet<Uuid> = request.ids.into_iter().collect();
+    let docs: Vec<Document> = state
+        .list_documents()
+        .into_iter()
+        .filter(|doc| ids.is_empty() || ids.contains(&doc.id))
+        .filter(|doc| filter.matches(doc))
+        .collect();
+
+    let response = RagEngine::from_state(state).delete_documents(docs, request.dry_run).await?;
+    Ok(Json(response))
+}
+
+/// DELETE /api/collections/:id - Delete every document of a collection
+///
+/// Same as a bulk deletion filtered by collection.
+#[utoipa::path(
+    delete,
+    path = "/api/collections/{id}",
+    tag = "documents",
+    params(("id" = String, Path, description = "Collection name"), PurgeQuery),
+    responses(
+        (status = 200, description = "Deleted (or, for a dry run, matching) documents and counts", body = BulkDeleteResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "No documents in the collection", body = ProblemDetails)
+    )
+)]
+pub async fn purge_collection(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Query(params): Query<PurgeQuery>,
+) -> Result<Json<BulkDeleteResponse>> {
+    let docs: Vec<Document> = state
+        .list_documents()
+        .into_iter()
+        .filter(|doc| doc.collection() == Some(collection.as_str()))
+        .collect();
+    if docs.is_empty() {
+        return Err(Error::DocumentNotFound(format!("Collection '{}' has no documents", collection)));
+    }
+
+    let response = RagEngine::from_state(state).delete_documents(docs, params.dry_run).await?;
+    Ok(Json(response))
+}
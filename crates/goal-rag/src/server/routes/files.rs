@@ -2,30 +2,43 @@
 
 use axum::{
     extract::{Path, Query, State},
-    Json,
+    http::HeaderMap,
+    response::Response,
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::error::{Error, Result};
+use crate::engine::{hidden_documents, hides_file};
+use crate::error::{Error, ProblemDetails, Result};
+use crate::processing::GcsSyncProgress;
+use crate::server::listing;
 use crate::server::state::{AppState, FileRegistryStats};
-use crate::storage::SyncStatus;
+use crate::storage::{DeadLetterFile, FileSort, RegistryQuery, SyncStatus};
 use crate::types::{
     FileCheckItem, FileCheckRequest, FileCheckResponse, FileCheckResult, FileCheckSummary,
-    FileRecord, FileRecordStatus, FileRecordSummary, FileUploadAdvice,
+    FileRecord, FileRecordStatus, FileRecordSummary, FileUploadAdvice, Principal,
 };
 
 /// Query parameters for listing files
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListFilesQuery {
-    /// Filter by status: success, failed, skipped, all
+    /// Filter by status: success, failed, skipped, processing, all
     #[serde(default = "default_status")]
     pub status: String,
-    /// Limit results
+    /// Only files whose name contains this (case-insensitive)
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Page size (at most 1000)
     #[serde(default = "default_limit")]
     pub limit: usize,
-    /// Offset for pagination
+    /// Offset for pagination (ignored with `cursor`)
     #[serde(default)]
     pub offset: usize,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
     /// Sort by: filename, date, size
     #[serde(default = "default_sort")]
     pub sort: String,
@@ -51,7 +64,7 @@ fn default_order() -> String {
 }
 
 /// Response for file list
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FileListResponse {
     /// Files in the list
     pub files: Vec<FileRecordSummary>,
@@ -61,12 +74,15 @@ pub struct FileListResponse {
     pub offset: usize,
     /// Current limit
     pub limit: usize,
+    /// Cursor of the next page (absent on the last page)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
     /// Statistics
     pub stats: FileRegistryStats,
 }
 
 /// Response for failed files list
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FailedFilesResponse {
     /// Failed files with details
     pub files: Vec<FailedFileDetail>,
@@ -77,75 +93,117 @@ pub struct FailedFilesResponse {
 }
 
 /// Detail for a failed file
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FailedFileDetail {
     pub filename: String,
     pub file_type: String,
     pub file_size: u64,
     pub error_message: String,
     pub failed_at_stage: String,
+    /// Why the file was rejected rather than failing to process
+    /// ("malware" for files the virus scanner quarantined)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
     pub last_attempt: String,
     pub upload_count: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub suggested_action: Option<String>,
 }
 
-/// GET /api/files - List all tracked files
+/// GET /api/files - List tracked files, filtered, sorted and paged in SQL
+///
+/// Files of documents the caller can't read are left out.
+#[utoipa::path(
+    get,
+    path = "/api/files",
+    tag = "files",
+    params(ListFilesQuery),
+    responses(
+        (status = 200, description = "Tracked files (ETag header set)", body = FileListResponse),
+        (status = 304, description = "Listing unchanged since the If-None-Match ETag"),
+        (status = 400, description = "Invalid cursor", body = ProblemDetails)
+    )
+)]
 pub async fn list_files(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Query(params): Query<ListFilesQuery>,
-) -> Json<FileListResponse> {
-    let mut records: Vec<FileRecord> = match params.status.as_str() {
-        "success" => state.list_successful_files(),
-        "failed" => state.list_failed_files(),
-        "skipped" => state.list_skipped_files(),
-        _ => state.list_file_records(),
-    };
-
-    let total = records.len();
-
-    // Sort
-    match params.sort.as_str() {
-        "filename" => records.sort_by(|a, b| a.filename.cmp(&b.filename)),
-        "size" => records.sort_by(|a, b| a.file_size.cmp(&b.file_size)),
-        _ => records.sort_by(|a, b| b.last_processed_at.cmp(&a.last_processed_at)),
-    }
-
-    if params.order == "asc" {
-        records.reverse();
-    }
-
-    // Paginate
-    let records: Vec<FileRecordSummary> = records
-        .into_iter()
-        .skip(params.offset)
-        .take(params.limit)
-        .map(|r| FileRecordSummary::from(&r))
-        .collect();
-
-    let stats = state.file_registry_stats();
-
-    Json(FileListResponse {
-        files: records,
-        total,
-        offset: params.offset,
-        limit: params.limit,
-        stats,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let caller = principal.map(Principal::cache_key).unwrap_or_default();
+    let etag = listing::etag(state.registry_version(), &[&caller, &format!("{:?}", params)]);
+
+    listing::conditional(&headers, etag, || {
+        let sort = match params.sort.as_str() {
+            "filename" => FileSort::Filename,
+            "size" => FileSort::Size,
+            _ => FileSort::Date,
+        };
+        let query = RegistryQuery {
+            status: match params.status.as_str() {
+                status @ ("success" | "failed" | "skipped" | "processing") => Some(status.to_string()),
+                _ => None,
+            },
+            filename: params.filename.clone().filter(|f| !f.is_empty()),
+            sort,
+            descending: params.order != "asc",
+            limit: params.limit.min(listing::MAX_PAGE_SIZE),
+            offset: params.offset,
+            after: params.cursor.as_deref().map(listing::decode_cursor).transpose()?,
+            hidden_documents: hidden_documents(&state, principal).into_iter().collect(),
+        };
+        let page = state.database().list_file_records_page(&query)?;
+
+        let next_cursor = page
+            .has_more
+            .then(|| page.items.last().map(|r| listing::encode_cursor(&sort.key(r), &r.filename)))
+            .flatten();
+        Ok(FileListResponse {
+            files: page.items.iter().map(FileRecordSummary::from).collect(),
+            total: page.total,
+            offset: params.offset,
+            limit: query.limit,
+            next_cursor,
+            stats: state.file_registry_stats(),
+        })
     })
 }
 
 /// GET /api/files/:filename - Get specific file status
+#[utoipa::path(
+    get,
+    path = "/api/files/{filename}",
+    tag = "files",
+    params(("filename" = String, Path, description = "Original filename")),
+    responses(
+        (status = 200, description = "File record", body = FileRecord),
+        (status = 404, description = "File not tracked", body = ProblemDetails)
+    )
+)]
 pub async fn get_file_status(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Path(filename): Path<String>,
 ) -> Result<Json<FileRecord>> {
+    let hidden = hidden_documents(&state, principal.as_ref().map(|Extension(p)| p));
     state
         .get_file_record(&filename)
+        .filter(|record| !hides_file(&state, &hidden, &record.filename))
         .map(Json)
         .ok_or_else(|| Error::DocumentNotFound(format!("File '{}' not found in registry", filename)))
 }
 
 /// POST /api/files/check - Check status of files before upload
+#[utoipa::path(
+    post,
+    path = "/api/files/check",
+    tag = "files",
+    request_body = FileCheckRequest,
+    responses(
+        (status = 200, description = "Upload advice per file", body = FileCheckResponse)
+    )
+)]
 pub async fn check_files(
     State(state): State<AppState>,
     Json(request): Json<FileCheckRequest>,
@@ -184,7 +242,7 @@ pub async fn check_files(
 }
 
 /// Check a single file for upload status
-fn check_single_file(state: &AppState, item: &FileCheckItem) -> (FileUploadAdvice, Option<FileRecordSummary>) {
+pub(crate) fn check_single_file(state: &AppState, item: &FileCheckItem) -> (FileUploadAdvice, Option<FileRecordSummary>) {
     // First check if we have a record by filename
     if let Some(record) = state.get_file_record(&item.filename) {
         let summary = FileRecordSummary::from(&record);
@@ -278,10 +336,21 @@ fn check_single_file(state: &AppState, item: &FileCheckItem) -> (FileUploadAdvic
 }
 
 /// GET /api/files/failed - List failed files with details
+#[utoipa::path(
+    get,
+    path = "/api/files/failed",
+    tag = "files",
+    responses(
+        (status = 200, description = "Failed files", body = FailedFilesResponse)
+    )
+)]
 pub async fn list_failed_files(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
 ) -> Json<FailedFilesResponse> {
-    let failed = state.list_failed_files();
+    let hidden = hidden_documents(&state, principal.as_ref().map(|Extension(p)| p));
+    let mut failed = state.list_failed_files();
+    failed.retain(|record| record.document_id.map_or(true, |id| !hidden.contains(&id)));
     let total = failed.len();
 
     let files: Vec<FailedFileDetail> = failed
@@ -298,6 +367,7 @@ pub async fn list_failed_files(
                 file_size: record.file_size,
                 error_message: record.error_message.clone().unwrap_or_else(|| "Unknown error".to_string()),
                 failed_at_stage: record.failed_at_stage.clone().unwrap_or_else(|| "unknown".to_string()),
+                reason: (record.failed_at_stage.as_deref() == Some("scanning")).then(|| "malware".to_string()),
                 last_attempt: record.last_processed_at.to_rfc3339(),
                 upload_count: record.upload_count,
                 suggested_action,
@@ -323,6 +393,9 @@ pub async fn list_failed_files(
     if error_messages.iter().any(|e| e.contains("No text content")) {
         suggestions.push("Some documents may be image-only. Ensure OCR tools are installed.".to_string());
     }
+    if files.iter().any(|f| f.reason.as_deref() == Some("malware")) {
+        suggestions.push("Some files were quarantined as malware. Obtain clean copies before uploading them again.".to_string());
+    }
     if error_messages.iter().any(|e| e.contains("rate limit") || e.contains("429")) {
         suggestions.push("Rate limiting detected. Processing will resume automatically.".to_string());
     }
@@ -338,7 +411,9 @@ pub async fn list_failed_files(
 fn suggest_action_for_failure(error: &str, stage: &str) -> Option<String> {
     let error_lower = error.to_lowercase();
 
-    if error_lower.contains("no text content") || error_lower.contains("empty") {
+    if stage == "scanning" {
+        Some("File was quarantined by the virus scanner. Do not open it; upload a clean copy.".to_string())
+    } else if error_lower.contains("no text content") || error_lower.contains("empty") {
         Some("Document may be image-based. Ensure OCR is installed and retry.".to_string())
     } else if error_lower.contains("timeout") {
         Some("Document took too long to process. Try splitting into smaller files.".to_string())
@@ -360,6 +435,15 @@ fn suggest_action_for_failure(error: &str, stage: &str) -> Option<String> {
 }
 
 /// DELETE /api/files/failed - Clear all failed file records
+#[utoipa::path(
+    delete,
+    path = "/api/files/failed",
+    tag = "files",
+    responses(
+        (status = 200, description = "Failed records cleared", body = ClearFailedResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
 pub async fn clear_failed_files(
     State(state): State<AppState>,
 ) -> Json<ClearFailedResponse> {
@@ -370,13 +454,64 @@ pub async fn clear_failed_files(
     })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ClearFailedResponse {
     pub cleared: usize,
     pub message: String,
 }
 
+/// Query parameters for the dead-letter list
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeadLetterQuery {
+    /// Maximum number of files (default: 100)
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+/// Response for the dead-letter list
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeadLetterResponse {
+    pub files: Vec<DeadLetterFile>,
+    pub total: usize,
+}
+
+/// GET /api/files/dead-letter - List job files that exhausted their retries
+#[utoipa::path(
+    get,
+    path = "/api/files/dead-letter",
+    tag = "files",
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "Files that failed transiently on every attempt", body = DeadLetterResponse)
+    )
+)]
+pub async fn list_dead_letter_files(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<DeadLetterQuery>,
+) -> Result<Json<DeadLetterResponse>> {
+    let hidden = hidden_documents(&state, principal.as_ref().map(|Extension(p)| p));
+    let mut files = state.database().list_dead_letter_files(params.limit)?;
+    files.retain(|file| !hides_file(&state, &hidden, &file.filename));
+    Ok(Json(DeadLetterResponse {
+        total: files.len(),
+        files,
+    }))
+}
+
 /// DELETE /api/files/:filename - Remove a specific file record
+#[utoipa::path(
+    delete,
+    path = "/api/files/{filename}",
+    tag = "files",
+    params(("filename" = String, Path, description = "Original filename")),
+    responses(
+        (status = 200, description = "File record removed", body = DeleteFileResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "File not tracked", body = ProblemDetails)
+    )
+)]
 pub async fn delete_file_record(
     State(state): State<AppState>,
     Path(filename): Path<String>,
@@ -390,13 +525,21 @@ pub async fn delete_file_record(
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct DeleteFileResponse {
     pub filename: String,
     pub message: String,
 }
 
 /// GET /api/files/stats - Get file registry statistics
+#[utoipa::path(
+    get,
+    path = "/api/files/stats",
+    tag = "files",
+    responses(
+        (status = 200, description = "File registry statistics", body = FileStatsResponse)
+    )
+)]
 pub async fn file_stats(
     State(state): State<AppState>,
 ) -> Json<FileStatsResponse> {
@@ -446,7 +589,7 @@ fn categorize_error(error: &str) -> String {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FileStatsResponse {
     pub total_files: usize,
     pub successful: usize,
@@ -460,26 +603,55 @@ pub struct FileStatsResponse {
 // GCS Sync Endpoints
 // ============================================================================
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SyncQuery {
+    /// Only read documents whose objects changed since the last sync
+    /// (default: false, read every document)
+    #[serde(default)]
+    pub incremental: bool,
+}
+
 /// POST /api/files/sync - Sync file registry from GCS bucket
 #[cfg(feature = "gcp")]
+#[utoipa::path(
+    post,
+    path = "/api/files/sync",
+    tag = "files",
+    params(SyncQuery),
+    responses(
+        (status = 200, description = "Sync result", body = SyncResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
 pub async fn sync_from_gcs(
     State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
 ) -> Result<Json<SyncResponse>> {
-    let (synced, failed) = state.sync_from_gcs().await?;
+    let progress = state.gcs_sync().run(&state, !query.incremental).await?;
 
     Ok(Json(SyncResponse {
         success: true,
-        files_synced: synced,
-        files_failed: failed,
+        files_synced: progress.files_synced,
+        files_failed: progress.files_failed,
         message: format!(
-            "Synced {} files from GCS bucket ({} failed)",
-            synced, failed
+            "Synced {} of {} changed files from GCS bucket ({} failed)",
+            progress.files_synced, progress.documents_changed, progress.files_failed
         ),
         sync_status: state.get_sync_status(),
+        progress: Some(progress),
     }))
 }
 
 /// GET /api/files/sync/status - Get last sync status
+#[utoipa::path(
+    get,
+    path = "/api/files/sync/status",
+    tag = "files",
+    responses(
+        (status = 200, description = "Last sync status", body = SyncStatusResponse)
+    )
+)]
 pub async fn get_sync_status(
     State(state): State<AppState>,
 ) -> Json<SyncStatusResponse> {
@@ -488,11 +660,12 @@ pub async fn get_sync_status(
 
     Json(SyncStatusResponse {
         last_sync: sync_status,
+        progress: state.gcs_sync().last_progress(),
         database_stats: db_stats,
     })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SyncResponse {
     pub success: bool,
     pub files_synced: usize,
@@ -500,17 +673,31 @@ pub struct SyncResponse {
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sync_status: Option<SyncStatus>,
+    /// The pass that ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<GcsSyncProgress>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SyncStatusResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_sync: Option<SyncStatus>,
+    /// Current or last sync pass since startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<GcsSyncProgress>,
     pub database_stats: crate::storage::FileRegistryDbStats,
 }
 
 /// GET /api/files/gcs-counts - Get file counts from GCS bucket
 #[cfg(feature = "gcp")]
+#[utoipa::path(
+    get,
+    path = "/api/files/gcs-counts",
+    tag = "files",
+    responses(
+        (status = 200, description = "GCS object counts", body = GcsCountsResponse)
+    )
+)]
 pub async fn get_gcs_counts(
     State(state): State<AppState>,
 ) -> Result<Json<GcsCountsResponse>> {
@@ -526,7 +713,7 @@ pub async fn get_gcs_counts(
     }))
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct GcsCountsResponse {
     /// Number of original files in GCS
     pub originals_count: usize,
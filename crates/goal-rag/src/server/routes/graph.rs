@@ -0,0 +1,152 @@
+//! Knowledge graph endpoints
+
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::learning::graph::{self, GraphBuildReport, GraphEdge};
+use crate::server::state::AppState;
+use crate::types::acl::can_read;
+use crate::types::Principal;
+
+/// Edges returned when the request doesn't set a limit
+const DEFAULT_EDGE_LIMIT: usize = 50;
+
+/// Largest edge limit accepted
+const MAX_EDGE_LIMIT: usize = 500;
+
+/// Graph query: start from entities, or from the entities named in a question
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct GraphQueryRequest {
+    /// Entities to start from
+    #[serde(default)]
+    pub entities: Vec<String>,
+    /// Question whose entities to start from (used when `entities` is empty)
+    #[serde(default)]
+    pub question: Option<String>,
+    /// Relations to follow (default: `graph.max_hops`)
+    #[serde(default)]
+    pub hops: Option<usize>,
+    /// Maximum edges returned (default: 50)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Neighborhood of the starting entities
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GraphQueryResponse {
+    /// Entities the traversal started from (normalized)
+    pub entities: Vec<String>,
+    /// Triples reached, nearest first
+    pub edges: Vec<GraphEdge>,
+}
+
+/// POST /api/graph/query - Explore the knowledge graph
+///
+/// Triples from documents the caller can't read are left out.
+#[utoipa::path(
+    post,
+    path = "/api/graph/query",
+    tag = "query",
+    request_body = GraphQueryRequest,
+    responses(
+        (status = 200, description = "Triples around the entities", body = GraphQueryResponse),
+        (status = 400, description = "Graph disabled or no entities given", body = ProblemDetails)
+    )
+)]
+pub async fn query_graph(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(request): Json<GraphQueryRequest>,
+) -> Result<Json<GraphQueryResponse>> {
+    if !state.graph().is_enabled() {
+        return Err(Error::Config("Knowledge graph is not enabled".to_string()));
+    }
+
+    let entities: Vec<String> = if !request.entities.is_empty() {
+        request.entities.iter().map(|e| graph::entity_key(e)).filter(|e| !e.is_empty()).collect()
+    } else if let Some(ref question) = request.question {
+        graph::entities_in(state.database(), question)?
+    } else {
+        return Err(Error::Config("Give entities or a question to start from".to_string()));
+    };
+
+    let hops = request.hops.unwrap_or(state.config().graph.max_hops);
+    let limit = request.limit.unwrap_or(DEFAULT_EDGE_LIMIT).min(MAX_EDGE_LIMIT);
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let edges = graph::neighborhood(state.database(), &entities, hops, limit)?
+        .into_iter()
+        .filter(|edge| {
+            state
+                .get_document(&edge.document_id)
+                .is_some_and(|doc| can_read(principal, &doc))
+        })
+        .collect();
+
+    Ok(Json(GraphQueryResponse { entities, edges }))
+}
+
+/// Query parameters for starting a graph build
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GraphBuildQuery {
+    /// Drop the graph and extract triples from every chunk again
+    #[serde(default)]
+    pub rebuild: bool,
+}
+
+/// POST /api/graph/build - Extract triples from chunks not yet in the graph
+///
+/// Runs in the background; progress is reported under `/api/admin/tasks`.
+#[utoipa::path(
+    post,
+    path = "/api/graph/build",
+    tag = "admin",
+    params(GraphBuildQuery),
+    responses(
+        (status = 200, description = "Build started", body = serde_json::Value),
+        (status = 400, description = "Graph disabled or build already running", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn start_graph_build(
+    State(state): State<AppState>,
+    Query(query): Query<GraphBuildQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let report: GraphBuildReport = state.graph().start(state.clone(), query.rebuild)?;
+
+    tracing::info!("Started graph build ({} chunks pending, rebuild: {})", report.remaining, query.rebuild);
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "build": report,
+        "status_url": format!("/api/admin/tasks/{}", report.task_id)
+    })))
+}
+
+/// GET /api/graph/build - Graph size and the last build run
+#[utoipa::path(
+    get,
+    path = "/api/graph/build",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Graph status", body = serde_json::Value)
+    )
+)]
+pub async fn get_graph_status(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let database = state.database();
+    let (triples, entities) = database.graph_counts()?;
+
+    Ok(Json(serde_json::json!({
+        "enabled": state.graph().is_enabled(),
+        "running": state.graph().is_running(),
+        "triples": triples,
+        "entities": entities,
+        "pending_chunks": database.count_chunks_without_graph()?,
+        "build": state.graph().last_report()
+    })))
+}
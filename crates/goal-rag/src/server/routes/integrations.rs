@@ -0,0 +1,178 @@
+//! Chat integration endpoints (Slack, Teams)
+//!
+//! These are called by the platforms, not API clients: they skip API key
+//! authentication and are verified with the platform's signature instead
+//! (see `crate::integrations`).
+
+use std::time::Duration;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::integrations::{self, slack, teams};
+use crate::server::state::AppState;
+
+/// Reply while an answer is being looked up
+const WORKING_REPLY: &str = "Looking that up…";
+
+/// POST /api/integrations/slack/events - Slack Events API
+///
+/// Mentions of the app and direct messages are answered in a thread with the
+/// bot token.
+#[utoipa::path(
+    post,
+    path = "/api/integrations/slack/events",
+    tag = "integrations",
+    request_body(content = String, description = "Slack event envelope (JSON)", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Event accepted (URL verification echoes the challenge)"),
+        (status = 401, description = "Missing, stale or invalid Slack signature", body = ProblemDetails),
+        (status = 404, description = "Slack integration not configured", body = ProblemDetails)
+    )
+)]
+pub async fn slack_events(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Result<Response> {
+    let config = state.config();
+    let integration = &config.integrations;
+    let slack_config = integration.slack.as_ref().ok_or_else(|| Error::NotFound("Slack integration".to_string()))?;
+    if !slack::verify(&slack_config.signing_secret, &headers, &body, chrono::Utc::now().timestamp()) {
+        return Err(Error::Unauthorized("Invalid Slack signature".to_string()));
+    }
+
+    let event = match serde_json::from_slice(&body)? {
+        slack::EventEnvelope::UrlVerification { challenge } => {
+            return Ok(Json(serde_json::json!({ "challenge": challenge })).into_response());
+        }
+        slack::EventEnvelope::EventCallback { event } => event,
+        slack::EventEnvelope::Other => return Ok(StatusCode::OK.into_response()),
+    };
+    // Redeliveries of events that took long to acknowledge were answered already
+    let (Some(question), Some(channel), false) = (event.question(), event.channel.clone(), slack::is_retry(&headers)) else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    let Some(bot_token) = slack_config.bot_token.clone() else {
+        tracing::warn!("Slack question ignored: integrations.slack.bot_token is not set");
+        return Ok(StatusCode::OK.into_response());
+    };
+
+    let (api_key, collection) = (slack_config.api_key.clone(), slack_config.collection.clone());
+    let public_url = integration.public_url.clone();
+    let thread = event.reply_thread().map(String::from);
+    tokio::spawn(async move {
+        let text = match integrations::answer(&state, &question, api_key.as_deref(), collection.as_deref()).await {
+            Ok(response) => slack::answer_message(&response, public_url.as_deref()),
+            Err(e) => integrations::error_reply(&e),
+        };
+        if let Err(e) = slack::post_message(&bot_token, &channel, thread.as_deref(), &text).await {
+            tracing::warn!("Failed to post Slack answer to {}: {}", channel, e);
+        }
+    });
+    Ok(StatusCode::OK.into_response())
+}
+
+/// POST /api/integrations/slack/commands - Slack slash command
+///
+/// The command's text is the question; the answer is posted to the channel
+/// through the command's response URL.
+#[utoipa::path(
+    post,
+    path = "/api/integrations/slack/commands",
+    tag = "integrations",
+    request_body(content = String, description = "Slash command form", content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Command acknowledged (only visible to the caller)"),
+        (status = 401, description = "Missing, stale or invalid Slack signature", body = ProblemDetails),
+        (status = 404, description = "Slack integration not configured", body = ProblemDetails)
+    )
+)]
+pub async fn slack_command(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Result<Json<serde_json::Value>> {
+    let config = state.config();
+    let integration = &config.integrations;
+    let slack_config = integration.slack.as_ref().ok_or_else(|| Error::NotFound("Slack integration".to_string()))?;
+    if !slack::verify(&slack_config.signing_secret, &headers, &body, chrono::Utc::now().timestamp()) {
+        return Err(Error::Unauthorized("Invalid Slack signature".to_string()));
+    }
+    let command = slack::parse_command(&body).ok_or_else(|| Error::Config("Invalid slash command".to_string()))?;
+    if command.text.is_empty() {
+        let usage = format!("Ask a question about the documents, e.g. `{} What is the refund policy?`", command.command);
+        return Ok(Json(serde_json::json!({ "response_type": "ephemeral", "text": usage })));
+    }
+
+    tracing::info!("Slack {} from {}", command.command, command.user_id);
+    let (api_key, collection) = (slack_config.api_key.clone(), slack_config.collection.clone());
+    let public_url = integration.public_url.clone();
+    let question = command.text.clone();
+    tokio::spawn(async move {
+        let text = match integrations::answer(&state, &question, api_key.as_deref(), collection.as_deref()).await {
+            Ok(response) => format!("> {}\n{}", question, slack::answer_message(&response, public_url.as_deref())),
+            Err(e) => integrations::error_reply(&e),
+        };
+        if let Err(e) = slack::respond(&command.response_url, &text).await {
+            tracing::warn!("Failed to answer Slack {}: {}", command.command, e);
+        }
+    });
+    Ok(Json(serde_json::json!({ "response_type": "ephemeral", "text": WORKING_REPLY })))
+}
+
+/// POST /api/integrations/teams/messages - Teams outgoing webhook
+///
+/// Answers within `reply_timeout_secs` are the reply; slower ones are posted
+/// to the channel's incoming webhook when one is configured.
+#[utoipa::path(
+    post,
+    path = "/api/integrations/teams/messages",
+    tag = "integrations",
+    request_body(content = String, description = "Teams message activity (JSON)", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Reply message activity", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid HMAC signature", body = ProblemDetails),
+        (status = 404, description = "Teams integration not configured", body = ProblemDetails)
+    )
+)]
+pub async fn teams_message(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Result<Json<serde_json::Value>> {
+    let config = state.config();
+    let integration = &config.integrations;
+    let teams_config = integration.teams.as_ref().ok_or_else(|| Error::NotFound("Teams integration".to_string()))?;
+    if !teams::verify(&teams_config.secret, &headers, &body) {
+        return Err(Error::Unauthorized("Invalid Teams signature".to_string()));
+    }
+    let activity: teams::Activity = serde_json::from_slice(&body)?;
+    let Some(question) = activity.question() else {
+        return Ok(Json(teams::reply("Ask me a question about the documents.")));
+    };
+
+    let (api_key, collection) = (teams_config.api_key.clone(), teams_config.collection.clone());
+    let public_url = integration.public_url.clone();
+    let mut answering = tokio::spawn(async move {
+        match integrations::answer(&state, &question, api_key.as_deref(), collection.as_deref()).await {
+            Ok(response) => teams::answer_message(&response, public_url.as_deref()),
+            Err(e) => integrations::error_reply(&e),
+        }
+    });
+
+    let timeout = Duration::from_secs(teams_config.reply_timeout_secs);
+    match tokio::time::timeout(timeout, &mut answering).await {
+        Ok(Ok(text)) => Ok(Json(teams::reply(&text))),
+        Ok(Err(e)) => Err(Error::Internal(format!("Teams answer task failed: {}", e))),
+        Err(_) => match teams_config.incoming_webhook_url.clone() {
+            Some(url) => {
+                tokio::spawn(async move {
+                    let Ok(text) = answering.await else { return };
+                    if let Err(e) = teams::post_to_channel(&url, &text).await {
+                        tracing::warn!("Failed to post Teams answer: {}", e);
+                    }
+                });
+                Ok(Json(teams::reply("Still looking that up, the answer will be posted here.")))
+            }
+            None => {
+                answering.abort();
+                Ok(Json(teams::reply("Sorry, that took too long to answer here. Please try again later.")))
+            }
+        },
+    }
+}
@@ -1,36 +1,84 @@
 //! Job management and progress endpoints
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use axum::{
-    extract::{Multipart, Path, State},
-    Json,
+    extract::{Multipart, Path, Query, State},
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::error::{Error, Result};
-use crate::processing::{FileData, Job, ProcessingOptions};
+use crate::config::{ChunkSizeUnit, RetryConfig};
+use crate::engine::{hidden_documents, hides_file};
+use crate::error::{Error, ProblemDetails, Result};
+use crate::ingestion::archive;
+use crate::ingestion::local_tree::{self, PathFilter};
+use crate::processing::{FileData, Job, JobPriority, JobProgress, ProcessingOptions};
+use crate::server::listing;
+use crate::server::openapi::IngestUpload;
+use crate::server::routes::files::check_single_file;
 use crate::server::state::AppState;
+use crate::server::upload::{spool_field, UploadFilter};
+use crate::storage::{JobRecord, JobSort, RegistryQuery};
+use crate::types::response::SkippedUpload;
+use crate::types::{FileCheckItem, FileUploadAdvice, PathIngestRequest, Principal};
+
+/// Progress of a job the caller may see
+///
+/// Jobs with a file of a document the caller can't read are not found.
+async fn visible_progress(
+    state: &AppState,
+    principal: Option<&Principal>,
+    job_id: Uuid,
+) -> Result<JobProgress> {
+    let hidden = hidden_documents(state, principal);
+    state
+        .job_queue()
+        .find_progress(job_id)
+        .await
+        .filter(|progress| !progress.file_progress.iter().any(|f| hides_file(state, &hidden, &f.filename)))
+        .ok_or_else(|| Error::DocumentNotFound(format!("Job {} not found", job_id)))
+}
 
 /// Response from async ingest
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AsyncIngestResponse {
-    pub job_id: Uuid,
+    /// Job processing the files (none when every file was skipped)
+    pub job_id: Option<Uuid>,
     pub files_queued: usize,
     pub message: String,
+    /// Files dropped while being uploaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_uploads: Vec<SkippedUpload>,
 }
 
 /// POST /api/ingest/async - Upload files for async processing
+#[utoipa::path(
+    post,
+    path = "/api/ingest/async",
+    tag = "ingest",
+    request_body(content = IngestUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Files queued for processing", body = AsyncIngestResponse)
+    )
+)]
 pub async fn ingest_async(
     State(state): State<AppState>,
     mut multipart: Multipart,
 ) -> Result<Json<AsyncIngestResponse>> {
     let mut files = Vec::new();
+    let mut skipped_uploads = Vec::new();
     let mut options = ProcessingOptions {
         parallel_embeddings: num_cpus::get().min(8),
         ..Default::default()
     };
+    let spool = state.job_queue().spool().clone();
+    let mut filter = UploadFilter::new(&state);
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| {
         Error::Internal(format!("Failed to read multipart field: {}", e))
     })? {
         let name = field.name().unwrap_or("").to_string();
@@ -43,6 +91,13 @@ pub async fn ingest_async(
             if let Ok(opts) = serde_json::from_slice::<IngestOptions>(&data) {
                 options.chunk_size = opts.chunk_size;
                 options.chunk_overlap = opts.chunk_overlap;
+                options.chunk_size_unit = opts.chunk_size_unit;
+                options.collection = opts.collection;
+                options.acl = opts.acl;
+                options.retry = opts.retry;
+                options.priority = opts.priority;
+                options.passwords = opts.passwords;
+                filter.declare(opts.files);
             }
             continue;
         }
@@ -53,20 +108,63 @@ pub async fn ingest_async(
             .map(|s| s.to_string())
             .unwrap_or_else(|| format!("file_{}.bin", Uuid::new_v4()));
 
-        // Read file content
-        let data = match field.bytes().await {
-            Ok(d) => d.to_vec(),
+        if let Some(reason) = filter.skip_unread(&filename) {
+            tracing::info!("Skipped upload of {}: {}", filename, reason);
+            skipped_uploads.push(SkippedUpload { filename, reason });
+            continue;
+        }
+
+        // Stream file content to the spool
+        let writer = match spool_field(&mut field, &spool).await {
+            Ok(writer) => writer,
             Err(e) => {
                 tracing::warn!("Failed to read file {}: {}", filename, e);
                 continue;
             }
         };
 
-        tracing::info!("Queued file: {} ({} bytes)", filename, data.len());
-        files.push(FileData { filename, data });
+        // Archives are queued as their files, named "<archive>/<path>"
+        if state.config().archive.enabled && archive::is_archive(&filename) {
+            let (_, data) = writer.into_data().await?;
+            let config = state.config().archive.clone();
+            let name = filename.clone();
+            let spool = spool.clone();
+            let entries = tokio::task::spawn_blocking(move || {
+                archive::extract(&name, &data, &config)?
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        let file = std::fs::File::open(&entry.file)?;
+                        FileData::spool_reader(&spool, format!("{}/{}", name, entry.path), file)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+            tracing::info!("Queued {} files from archive {}", entries.len(), filename);
+            files.extend(entries);
+            continue;
+        }
+
+        let (hash, size) = writer.commit().await?;
+        if let Some(reason) = filter.skip_received(&filename, &hash) {
+            tracing::info!("Skipped upload of {}: {}", filename, reason);
+            skipped_uploads.push(SkippedUpload { filename, reason });
+            continue;
+        }
+        tracing::info!("Queued file: {} ({} bytes)", filename, size);
+        files.push(FileData { filename, hash, size });
     }
 
     if files.is_empty() {
+        if !skipped_uploads.is_empty() {
+            return Ok(Json(AsyncIngestResponse {
+                job_id: None,
+                files_queued: 0,
+                message: "All files were skipped, nothing to process.".to_string(),
+                skipped_uploads,
+            }));
+        }
         return Err(Error::Internal("No files provided".to_string()));
     }
 
@@ -82,21 +180,209 @@ pub async fn ingest_async(
     let job_id = state.job_queue().submit(job).await;
 
     Ok(Json(AsyncIngestResponse {
-        job_id,
+        job_id: Some(job_id),
         files_queued: files_count,
         message: format!("Job queued successfully. Use /api/jobs/{} to check progress.", job_id),
+        skipped_uploads,
     }))
 }
 
+/// Response from a server-side path ingest
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PathIngestResponse {
+    /// Job processing the files (none when nothing needed ingesting)
+    pub job_id: Option<Uuid>,
+    /// Files selected by the include and exclude patterns
+    pub files_matched: usize,
+    pub files_queued: usize,
+    /// Files already ingested and unchanged since
+    pub files_skipped: usize,
+    pub message: String,
+    /// Files that could not be read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unreadable: Vec<SkippedUpload>,
+}
+
+/// POST /api/ingest/path - Ingest a directory tree on the server
+///
+/// The path must be under one of `processing.local_ingest_roots`. Files the
+/// registry has ingested with the same size, and that were not modified
+/// since, are skipped, so re-running a path only queues new and changed files.
+#[utoipa::path(
+    post,
+    path = "/api/ingest/path",
+    tag = "ingest",
+    request_body = PathIngestRequest,
+    responses(
+        (status = 200, description = "Files queued for processing", body = PathIngestResponse),
+        (status = 400, description = "Path missing or outside the ingest roots, or invalid pattern", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn ingest_path(
+    State(state): State<AppState>,
+    Json(request): Json<PathIngestRequest>,
+) -> Result<Json<PathIngestResponse>> {
+    Ok(Json(queue_path(&state, request).await?))
+}
+
+/// Walk a server-local path and queue its new and changed files as a job
+pub async fn queue_path(state: &AppState, request: PathIngestRequest) -> Result<PathIngestResponse> {
+    let roots = allowed_roots(state)?;
+    // Same error either way, so callers can't probe for paths outside the roots
+    let outside = || {
+        Error::Config(format!("{} is not an existing path under processing.local_ingest_roots", request.path))
+    };
+    let root = tokio::fs::canonicalize(&request.path).await.map_err(|_| outside())?;
+    if !roots.iter().any(|allowed| root.starts_with(allowed)) {
+        return Err(outside());
+    }
+    let filter = PathFilter::new(&request.include, &request.exclude)?;
+
+    let follow_links = request.follow_links;
+    let walk_root = root.clone();
+    let found = tokio::task::spawn_blocking(move || {
+        let files = local_tree::walk(&walk_root, &filter, follow_links)?;
+        // Links may lead out of the ingest roots
+        Ok::<_, Error>(if follow_links {
+            files
+                .into_iter()
+                .filter(|file| file.path.canonicalize().is_ok_and(|path| roots.iter().any(|r| path.starts_with(r))))
+                .collect()
+        } else {
+            files
+        })
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    let prefix = request.prefix.as_deref().map(|p| p.trim_matches('/')).filter(|p| !p.is_empty());
+    let files_matched = found.len();
+    let mut pending = Vec::new();
+    for file in found {
+        let filename = match prefix {
+            Some(prefix) => format!("{}/{}", prefix, file.relative),
+            None => file.relative.clone(),
+        };
+        let item = FileCheckItem { filename: filename.clone(), content_hash: None, file_size: file.size };
+        if let (FileUploadAdvice::Skip { .. }, Some(record)) = check_single_file(state, &item) {
+            let modified = file.modified.map(chrono::DateTime::<chrono::Utc>::from);
+            if !request.force && modified.map_or(true, |modified| modified <= record.last_processed_at) {
+                continue;
+            }
+        }
+        pending.push((filename, file.path));
+    }
+    let files_skipped = files_matched - pending.len();
+
+    // Spool in parallel; the pipeline still skips files whose content is unchanged
+    let spool = state.job_queue().spool().clone();
+    let spooled = tokio::task::spawn_blocking(move || {
+        use rayon::prelude::*;
+        pending
+            .into_par_iter()
+            .map(|(filename, path)| {
+                let spooled = std::fs::File::open(&path)
+                    .map_err(Error::from)
+                    .and_then(|file| spool.put_reader(std::io::BufReader::new(file)));
+                match spooled {
+                    Ok((hash, size)) => Ok(FileData { filename, hash, size }),
+                    Err(e) => Err(SkippedUpload { filename, reason: e.to_string() }),
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))?;
+    let (files, unreadable): (Vec<_>, Vec<_>) = spooled.into_iter().partition(|result| result.is_ok());
+    let files: Vec<FileData> = files.into_iter().filter_map(|result| result.ok()).collect();
+    let unreadable: Vec<SkippedUpload> = unreadable.into_iter().filter_map(|result| result.err()).collect();
+    for failure in &unreadable {
+        tracing::warn!("Failed to read {}: {}", failure.filename, failure.reason);
+    }
+
+    if files.is_empty() {
+        return Ok(PathIngestResponse {
+            job_id: None,
+            files_matched,
+            files_queued: 0,
+            files_skipped,
+            message: "No new or changed files, nothing to process.".to_string(),
+            unreadable,
+        });
+    }
+
+    let opts = request.options;
+    let options = ProcessingOptions {
+        chunk_size: opts.chunk_size,
+        chunk_overlap: opts.chunk_overlap,
+        chunk_size_unit: opts.chunk_size_unit,
+        collection: opts.collection,
+        acl: opts.acl,
+        retry: opts.retry,
+        priority: opts.priority,
+        passwords: opts.passwords,
+        parallel_embeddings: num_cpus::get().min(8),
+        ..Default::default()
+    };
+    let files_queued = files.len();
+    let job_id = state.job_queue().submit(Job { id: Uuid::new_v4(), files, options }).await;
+    tracing::info!(
+        "Queued {} of {} files under {} as job {} ({} unchanged)",
+        files_queued,
+        files_matched,
+        root.display(),
+        job_id,
+        files_skipped
+    );
+
+    Ok(PathIngestResponse {
+        job_id: Some(job_id),
+        files_matched,
+        files_queued,
+        files_skipped,
+        message: format!("Job queued successfully. Use /api/jobs/{} to check progress.", job_id),
+        unreadable,
+    })
+}
+
+/// Canonical `processing.local_ingest_roots`
+fn allowed_roots(state: &AppState) -> Result<Vec<PathBuf>> {
+    let configured = &state.config().processing.local_ingest_roots;
+    if configured.is_empty() {
+        return Err(Error::Config(
+            "Path ingest is disabled; set processing.local_ingest_roots to allow it".to_string(),
+        ));
+    }
+    Ok(configured
+        .iter()
+        .filter_map(|root| match root.canonicalize() {
+            Ok(root) => Some(root),
+            Err(e) => {
+                tracing::warn!("Ignoring ingest root {}: {}", root.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
 /// GET /api/jobs/:id - Get job progress
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Job progress", body = JobProgressResponse),
+        (status = 404, description = "Job not found", body = ProblemDetails)
+    )
+)]
 pub async fn get_job_progress(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Path(job_id): Path<Uuid>,
 ) -> Result<Json<JobProgressResponse>> {
-    let progress = state
-        .job_queue()
-        .get_progress(job_id)
-        .ok_or_else(|| Error::DocumentNotFound(format!("Job {} not found", job_id)))?;
+    let progress = visible_progress(&state, principal.as_ref().map(|Extension(p)| p), job_id).await?;
 
     let file_errors: Vec<FileErrorResponse> = progress
         .file_errors
@@ -162,61 +448,114 @@ pub async fn get_job_progress(
     }))
 }
 
-/// GET /api/jobs - List all jobs
+/// Query parameters for listing jobs
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListJobsQuery {
+    /// Filter by status: pending, processing, complete, failed
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Only jobs with a file whose name contains this (case-insensitive)
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Page size (at most 1000)
+    #[serde(default = "default_jobs_limit")]
+    pub limit: usize,
+    /// Offset for pagination (ignored with `cursor`)
+    #[serde(default)]
+    pub offset: usize,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Sort by: created, updated
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// Sort order: asc, desc (default)
+    #[serde(default)]
+    pub order: Option<String>,
+}
+
+fn default_jobs_limit() -> usize {
+    50
+}
+
+/// GET /api/jobs - List jobs, filtered, sorted and paged in SQL
+///
+/// Jobs with a file of a document the caller can't read are left out.
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    tag = "jobs",
+    params(ListJobsQuery),
+    responses(
+        (status = 200, description = "A page of jobs and queue statistics", body = JobListResponse),
+        (status = 400, description = "Unknown status or invalid cursor", body = ProblemDetails)
+    )
+)]
 pub async fn list_jobs(
     State(state): State<AppState>,
-) -> Json<JobListResponse> {
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<ListJobsQuery>,
+) -> Result<Json<JobListResponse>> {
+    if let Some(ref status) = params.status {
+        if !["pending", "processing", "complete", "failed"].contains(&status.as_str()) {
+            return Err(Error::Config(format!("Unknown job status '{}'", status)));
+        }
+    }
+    let sort = match params.sort.as_deref() {
+        Some("updated") => JobSort::Updated,
+        _ => JobSort::Created,
+    };
+    let query = RegistryQuery {
+        status: params.status.clone(),
+        filename: params.filename.clone().filter(|f| !f.is_empty()),
+        sort,
+        descending: params.order.as_deref() != Some("asc"),
+        limit: params.limit.min(listing::MAX_PAGE_SIZE),
+        offset: params.offset,
+        after: params.cursor.as_deref().map(listing::decode_cursor).transpose()?,
+        hidden_documents: hidden_documents(&state, principal.as_ref().map(|Extension(p)| p)).into_iter().collect(),
+    };
+    let page = state.database().list_jobs_page(&query)?;
+    let next_cursor = page
+        .has_more
+        .then(|| page.items.last().map(|job| listing::encode_cursor(&sort.key(job), &job.id.to_string())))
+        .flatten();
+
+    // Aggregate stats across all jobs in the queue
     let jobs_list = state.job_queue().list_jobs();
     let stats = state.job_queue().stats();
-
-    // Calculate aggregate stats
     let total_files_processed: usize = jobs_list.iter().map(|j| j.files_processed).sum();
     let total_files_skipped: usize = jobs_list.iter().map(|j| j.files_skipped).sum();
     let total_files_failed: usize = jobs_list.iter().map(|j| j.files_failed).sum();
 
-    // Collect all file errors across all jobs
-    let all_file_errors: Vec<FileErrorWithJob> = jobs_list
+    // Live progress where the queue still tracks the job, else the stored record
+    let mut jobs: Vec<JobSummary> = Vec::with_capacity(page.items.len());
+    for record in &page.items {
+        jobs.push(match state.job_queue().find_progress(record.id).await {
+            Some(progress) => JobSummary::from(progress),
+            None => JobSummary::from(record),
+        });
+    }
+
+    let all_file_errors: Vec<FileErrorWithJob> = jobs
         .iter()
         .flat_map(|job| {
             job.file_errors.iter().map(move |e| FileErrorWithJob {
                 job_id: job.job_id,
                 filename: e.filename.clone(),
                 error: e.error.clone(),
-                stage: format!("{:?}", e.stage).to_lowercase(),
+                stage: e.stage.clone(),
             })
         })
         .collect();
 
-    let jobs: Vec<JobSummary> = jobs_list
-        .into_iter()
-        .map(|p| {
-            let file_errors: Vec<FileErrorResponse> = p
-                .file_errors
-                .iter()
-                .map(|e| FileErrorResponse {
-                    filename: e.filename.clone(),
-                    error: e.error.clone(),
-                    stage: format!("{:?}", e.stage).to_lowercase(),
-                })
-                .collect();
-
-            JobSummary {
-                job_id: p.job_id,
-                status: format!("{:?}", p.status).to_lowercase(),
-                stage: format!("{:?}", p.stage).to_lowercase(),
-                percent_complete: p.percent_complete(),
-                total_files: p.total_files,
-                files_processed: p.files_processed,
-                files_skipped: p.files_skipped,
-                files_failed: p.files_failed,
-                error: p.error,
-                file_errors,
-            }
-        })
-        .collect();
-
-    Json(JobListResponse {
+    Ok(Json(JobListResponse {
         jobs,
+        total: page.total,
+        offset: params.offset,
+        limit: query.limit,
+        next_cursor,
         total_jobs: stats.total_jobs,
         pending: stats.pending,
         processing: stats.processing,
@@ -227,10 +566,10 @@ pub async fn list_jobs(
         total_files_skipped,
         total_files_failed,
         all_file_errors,
-    })
+    }))
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct JobProgressResponse {
     pub job_id: Uuid,
     pub status: String,
@@ -250,21 +589,21 @@ pub struct JobProgressResponse {
     pub updated_at: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FileErrorResponse {
     pub filename: String,
     pub error: String,
     pub stage: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SkippedFileInfo {
     pub filename: String,
     pub reason: String,
     pub reason_type: String,  // "duplicate", "unchanged", "empty", "unsupported", "error"
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct JobSummary {
     pub job_id: Uuid,
     pub status: String,
@@ -280,9 +619,66 @@ pub struct JobSummary {
     pub file_errors: Vec<FileErrorResponse>,
 }
 
-#[derive(Debug, Serialize)]
+impl From<JobProgress> for JobSummary {
+    fn from(p: JobProgress) -> Self {
+        let file_errors: Vec<FileErrorResponse> = p
+            .file_errors
+            .iter()
+            .map(|e| FileErrorResponse {
+                filename: e.filename.clone(),
+                error: e.error.clone(),
+                stage: format!("{:?}", e.stage).to_lowercase(),
+            })
+            .collect();
+
+        Self {
+            job_id: p.job_id,
+            status: format!("{:?}", p.status).to_lowercase(),
+            stage: format!("{:?}", p.stage).to_lowercase(),
+            percent_complete: p.percent_complete(),
+            total_files: p.total_files,
+            files_processed: p.files_processed,
+            files_skipped: p.files_skipped,
+            files_failed: p.files_failed,
+            error: p.error,
+            file_errors,
+        }
+    }
+}
+
+impl From<&JobRecord> for JobSummary {
+    fn from(job: &JobRecord) -> Self {
+        let percent_complete = if job.total_files == 0 {
+            0.0
+        } else {
+            job.files_processed as f32 / job.total_files as f32 * 100.0
+        };
+        Self {
+            job_id: job.id,
+            status: format!("{:?}", job.status).to_lowercase(),
+            stage: format!("{:?}", job.stage).to_lowercase(),
+            percent_complete,
+            total_files: job.total_files,
+            files_processed: job.files_processed,
+            files_skipped: job.files_skipped,
+            files_failed: job.files_failed,
+            error: job.error.clone(),
+            file_errors: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct JobListResponse {
     pub jobs: Vec<JobSummary>,
+    /// Jobs matching the filters (all pages)
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    /// Cursor of the next page (absent on the last page)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Jobs tracked by the queue
     pub total_jobs: usize,
     pub pending: usize,
     pub processing: usize,
@@ -293,11 +689,11 @@ pub struct JobListResponse {
     pub total_files_processed: usize,
     pub total_files_skipped: usize,
     pub total_files_failed: usize,
-    /// All file errors across all jobs (for quick overview)
+    /// File errors of the jobs on this page (for quick overview)
     pub all_file_errors: Vec<FileErrorWithJob>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FileErrorWithJob {
     pub job_id: Uuid,
     pub filename: String,
@@ -309,17 +705,39 @@ pub struct FileErrorWithJob {
 struct IngestOptions {
     chunk_size: Option<usize>,
     chunk_overlap: Option<usize>,
+    #[serde(default)]
+    chunk_size_unit: Option<ChunkSizeUnit>,
+    #[serde(default)]
+    collection: Option<String>,
+    #[serde(default)]
+    acl: Vec<String>,
+    #[serde(default)]
+    retry: Option<RetryConfig>,
+    #[serde(default)]
+    files: Vec<FileCheckItem>,
+    #[serde(default)]
+    priority: JobPriority,
+    #[serde(default)]
+    passwords: HashMap<String, String>,
 }
 
 /// GET /api/jobs/:id/files - Get per-file progress with tier and parser details
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}/files",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Per-file progress", body = JobFilesProgressResponse),
+        (status = 404, description = "Job not found", body = ProblemDetails)
+    )
+)]
 pub async fn get_job_files_progress(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Path(job_id): Path<Uuid>,
 ) -> Result<Json<JobFilesProgressResponse>> {
-    let progress = state
-        .job_queue()
-        .get_progress(job_id)
-        .ok_or_else(|| Error::DocumentNotFound(format!("Job {} not found", job_id)))?;
+    let progress = visible_progress(&state, principal.as_ref().map(|Extension(p)| p), job_id).await?;
 
     let files: Vec<FileProgressResponse> = progress
         .file_progress
@@ -382,7 +800,7 @@ pub async fn get_job_files_progress(
 }
 
 /// Response for per-file progress
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct JobFilesProgressResponse {
     pub job_id: Uuid,
     pub total_files: usize,
@@ -391,7 +809,7 @@ pub struct JobFilesProgressResponse {
     pub status_summary: StatusSummary,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FileProgressResponse {
     pub filename: String,
     pub size_bytes: u64,
@@ -405,7 +823,7 @@ pub struct FileProgressResponse {
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ParserAttemptResponse {
     pub parser_name: String,
     pub success: bool,
@@ -414,7 +832,7 @@ pub struct ParserAttemptResponse {
     pub duration_ms: u64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct TierSummary {
     pub fast: usize,
     pub medium: usize,
@@ -422,7 +840,7 @@ pub struct TierSummary {
     pub complex: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct StatusSummary {
     pub queued: usize,
     pub parsing: usize,
@@ -435,6 +853,14 @@ pub struct StatusSummary {
 }
 
 /// GET /api/system/parsers - Get available parsers and their status
+#[utoipa::path(
+    get,
+    path = "/api/system/parsers",
+    tag = "system",
+    responses(
+        (status = 200, description = "Parser availability", body = ParsersStatusResponse)
+    )
+)]
 pub async fn get_parsers_status() -> Json<ParsersStatusResponse> {
     use crate::ingestion::ExternalParser;
 
@@ -554,7 +980,7 @@ fn get_parser_recommendations(
     recommendations
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ParsersStatusResponse {
     pub parsers: Vec<ParserInfo>,
     pub available_count: usize,
@@ -563,7 +989,7 @@ pub struct ParsersStatusResponse {
     pub recommendations: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ParserInfo {
     pub name: String,
     pub level: u8,
@@ -573,6 +999,16 @@ pub struct ParserInfo {
 }
 
 /// POST /api/jobs/:id/resume - Resume an incomplete/failed job
+#[utoipa::path(
+    post,
+    path = "/api/jobs/{id}/resume",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Job resumed", body = ResumeJobResponse),
+        (status = 404, description = "Job not found", body = ProblemDetails)
+    )
+)]
 pub async fn resume_job(
     State(state): State<AppState>,
     Path(job_id): Path<Uuid>,
@@ -621,7 +1057,7 @@ pub async fn resume_job(
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ResumeJobResponse {
     pub job_id: Uuid,
     pub pending_files: usize,
@@ -630,10 +1066,27 @@ pub struct ResumeJobResponse {
 }
 
 /// GET /api/jobs/incomplete - Get all incomplete jobs that can be resumed
+#[utoipa::path(
+    get,
+    path = "/api/jobs/incomplete",
+    tag = "jobs",
+    responses(
+        (status = 200, description = "Incomplete jobs", body = IncompleteJobsResponse)
+    )
+)]
 pub async fn list_incomplete_jobs(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
 ) -> Json<IncompleteJobsResponse> {
-    let incomplete_jobs = state.job_queue().get_incomplete_jobs();
+    let hidden = hidden_documents(&state, principal.as_ref().map(|Extension(p)| p));
+    let mut incomplete_jobs = state.job_queue().get_incomplete_jobs();
+    incomplete_jobs.retain(|job| {
+        hidden.is_empty()
+            || state
+                .database()
+                .get_job_files(job.id)
+                .is_ok_and(|files| !files.iter().any(|f| hides_file(&state, &hidden, &f.filename)))
+    });
 
     let jobs: Vec<IncompleteJobInfo> = incomplete_jobs
         .into_iter()
@@ -646,8 +1099,8 @@ pub async fn list_incomplete_jobs(
                 total_files: job.total_files,
                 files_processed: job.files_processed,
                 files_remaining: job.total_files.saturating_sub(job.files_processed),
-                pending_files_with_data: pending_files.iter().filter(|f| f.file_data.is_some()).count(),
-                can_resume: !pending_files.is_empty() && pending_files.iter().any(|f| f.file_data.is_some()),
+                pending_files_with_data: pending_files.iter().filter(|f| f.has_data()).count(),
+                can_resume: !pending_files.is_empty() && pending_files.iter().any(|f| f.has_data()),
                 created_at: job.created_at.to_rfc3339(),
                 updated_at: job.updated_at.to_rfc3339(),
                 error: job.error,
@@ -662,13 +1115,13 @@ pub async fn list_incomplete_jobs(
     })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct IncompleteJobsResponse {
     pub jobs: Vec<IncompleteJobInfo>,
     pub total_incomplete: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct IncompleteJobInfo {
     pub job_id: Uuid,
     pub status: String,
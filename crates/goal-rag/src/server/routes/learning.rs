@@ -0,0 +1,342 @@
+//! Answer feedback, experiment results and generated question-answer pairs
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::learning::experiments::{self, VariantReport};
+use crate::learning::{Correction, Feedback, QaGenerationReport};
+use crate::server::state::AppState;
+use crate::types::acl::can_read;
+use crate::types::Principal;
+
+/// Experiment results
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExperimentsResponse {
+    /// Whether queries are currently assigned variants
+    pub enabled: bool,
+    /// Answers and feedback per variant
+    pub variants: Vec<VariantReport>,
+}
+
+/// POST /api/feedback - Rate or correct an answer
+///
+/// Negatively rated answers are no longer used as examples for similar
+/// questions; ratings are counted per experiment variant. A corrected answer
+/// or the documents that answer the question can be given too. Corrections
+/// by admins apply at once; others, and all corrections without
+/// authentication, once verified with `/api/feedback/corrections/{id}/verify`.
+#[utoipa::path(
+    post,
+    path = "/api/feedback",
+    tag = "query",
+    request_body = Feedback,
+    responses(
+        (status = 200, description = "Feedback recorded", body = serde_json::Value),
+        (status = 400, description = "Unknown corrected document", body = ProblemDetails),
+        (status = 404, description = "Unknown interaction", body = ProblemDetails)
+    )
+)]
+pub async fn submit_feedback(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(feedback): Json<Feedback>,
+) -> Result<Json<serde_json::Value>> {
+    let principal = principal.map(|Extension(p)| p);
+    if let Some(id) = feedback.document_ids.iter().find(|id| {
+        state.get_document(id).map_or(true, |doc| !can_read(principal.as_ref(), &doc))
+    }) {
+        return Err(Error::Config(format!("Unknown document {}", id)));
+    }
+
+    let score = feedback.feedback_type.to_score();
+    if !state.knowledge_store().update_feedback(feedback.interaction_id, score) {
+        return Err(Error::NotFound(format!("Interaction {}", feedback.interaction_id)));
+    }
+
+    tracing::info!("Feedback {:?} for interaction {}", feedback.feedback_type, feedback.interaction_id);
+
+    let correction = feedback.is_correction().then(|| {
+        let verified = principal.as_ref().is_some_and(|p| p.admin);
+        let now = Utc::now();
+        Correction {
+            answer: feedback.corrected_answer.as_deref().map(str::trim).filter(|a| !a.is_empty()).map(String::from),
+            document_ids: feedback.document_ids.clone(),
+            comment: feedback.comment.clone(),
+            submitted_by: principal.as_ref().map(|p| p.id.clone()),
+            submitted_at: now,
+            verified,
+            verified_by: principal.as_ref().filter(|_| verified).map(|p| p.id.clone()),
+            verified_at: verified.then_some(now),
+        }
+    });
+    if let Some(ref correction) = correction {
+        state.knowledge_store().set_correction(feedback.interaction_id, correction.clone());
+        tracing::info!(
+            "Correction for interaction {} ({})",
+            feedback.interaction_id,
+            if correction.verified { "verified" } else { "pending verification" }
+        );
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "interaction_id": feedback.interaction_id,
+        "score": score,
+        "correction": correction
+    })))
+}
+
+/// Query parameters for listing corrections
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CorrectionsQuery {
+    /// Only verified (true) or pending (false) corrections
+    pub verified: Option<bool>,
+}
+
+/// A corrected interaction
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CorrectionEntry {
+    pub interaction_id: Uuid,
+    pub question: String,
+    /// Answer that was corrected
+    pub answer: String,
+    pub correction: Correction,
+}
+
+/// GET /api/feedback/corrections - Submitted corrections, newest first
+///
+/// Corrections of answers drawn from documents the caller can't read are
+/// left out.
+#[utoipa::path(
+    get,
+    path = "/api/feedback/corrections",
+    tag = "query",
+    params(CorrectionsQuery),
+    responses(
+        (status = 200, description = "Corrections", body = Vec<CorrectionEntry>)
+    )
+)]
+pub async fn list_corrections(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<CorrectionsQuery>,
+) -> Json<Vec<CorrectionEntry>> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let entries = state
+        .knowledge_store()
+        .corrections()
+        .into_iter()
+        .filter(|i| {
+            i.document_ids
+                .iter()
+                .all(|id| state.get_document(id).map_or(true, |doc| can_read(principal, &doc)))
+        })
+        .filter_map(|i| {
+            let correction = i.correction?;
+            Some(CorrectionEntry { interaction_id: i.id, question: i.question, answer: i.answer, correction })
+        })
+        .filter(|entry| query.verified.map_or(true, |verified| entry.correction.verified == verified))
+        .collect();
+
+    Json(entries)
+}
+
+/// Only admins review corrections when authentication is on
+fn check_reviewer(principal: &Option<Extension<Principal>>) -> Result<()> {
+    match principal {
+        Some(Extension(p)) if !p.admin => Err(Error::Forbidden("Only admins can review corrections".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// POST /api/feedback/corrections/:id/verify - Verify a correction
+///
+/// From then on the corrected answer is returned for the same question.
+#[utoipa::path(
+    post,
+    path = "/api/feedback/corrections/{id}/verify",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Interaction ID")),
+    responses(
+        (status = 200, description = "Correction verified", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "No correction for the interaction", body = ProblemDetails)
+    )
+)]
+pub async fn verify_correction(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    check_reviewer(&principal)?;
+    let verified_by = principal.map(|Extension(p)| p.id);
+    if !state.knowledge_store().verify_correction(id, verified_by) {
+        return Err(Error::NotFound(format!("Correction for interaction {}", id)));
+    }
+    tracing::info!("Verified correction for interaction {}", id);
+
+    Ok(Json(serde_json::json!({ "success": true, "interaction_id": id })))
+}
+
+/// DELETE /api/feedback/corrections/:id - Reject or withdraw a correction
+#[utoipa::path(
+    delete,
+    path = "/api/feedback/corrections/{id}",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Interaction ID")),
+    responses(
+        (status = 200, description = "Correction removed", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "No correction for the interaction", body = ProblemDetails)
+    )
+)]
+pub async fn delete_correction(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    check_reviewer(&principal)?;
+    if !state.knowledge_store().remove_correction(id) {
+        return Err(Error::NotFound(format!("Correction for interaction {}", id)));
+    }
+    tracing::info!("Removed correction for interaction {}", id);
+
+    Ok(Json(serde_json::json!({ "success": true, "interaction_id": id })))
+}
+
+/// GET /api/experiments - Answers and feedback per experiment variant
+#[utoipa::path(
+    get,
+    path = "/api/experiments",
+    tag = "query",
+    responses(
+        (status = 200, description = "Per-variant results", body = ExperimentsResponse)
+    )
+)]
+pub async fn get_experiments(State(state): State<AppState>) -> Json<ExperimentsResponse> {
+    let interactions = state.knowledge_store().interactions();
+
+    Json(ExperimentsResponse {
+        enabled: state.config().experiments.enabled,
+        variants: experiments::report(&interactions),
+    })
+}
+
+/// Query parameters for listing generated pairs
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QaPairsQuery {
+    /// Only pairs generated from this document
+    pub document_id: Option<Uuid>,
+    /// Maximum pairs returned, oldest first (default: all)
+    pub limit: Option<usize>,
+}
+
+/// A generated question-answer pair and its source
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QaPairEntry {
+    pub id: Uuid,
+    pub question: String,
+    pub answer: String,
+    pub document_id: Uuid,
+    pub chunk_id: Uuid,
+    pub filename: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// GET /api/qa-pairs - Generated question-answer pairs
+///
+/// Pairs from documents the caller can't read are left out. Each pair names
+/// the chunk that answers it, so the list can seed an evaluation set.
+#[utoipa::path(
+    get,
+    path = "/api/qa-pairs",
+    tag = "query",
+    params(QaPairsQuery),
+    responses(
+        (status = 200, description = "Generated pairs, oldest first", body = Vec<QaPairEntry>)
+    )
+)]
+pub async fn list_qa_pairs(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<QaPairsQuery>,
+) -> Json<Vec<QaPairEntry>> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let pairs = state
+        .knowledge_store()
+        .synthetic_pairs()
+        .into_iter()
+        .filter_map(|pair| {
+            let source = pair.synthetic?;
+            let document_id = *pair.document_ids.first()?;
+            Some(QaPairEntry {
+                id: pair.id,
+                question: pair.question,
+                answer: pair.answer,
+                document_id,
+                chunk_id: source.chunk_id,
+                filename: pair.citations_used.into_iter().next().unwrap_or_default(),
+                created_at: pair.created_at,
+            })
+        })
+        .filter(|pair| query.document_id.map_or(true, |id| pair.document_id == id))
+        .filter(|pair| state.get_document(&pair.document_id).is_some_and(|doc| can_read(principal, &doc)))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Json(pairs)
+}
+
+/// POST /api/qa-pairs/generate - Generate pairs for chunks without any
+///
+/// Runs in the background; progress is reported under `/api/admin/tasks`.
+#[utoipa::path(
+    post,
+    path = "/api/qa-pairs/generate",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Generation started", body = serde_json::Value),
+        (status = 400, description = "Generation disabled or already running", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn start_qa_generation(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let report: QaGenerationReport = state.qa_generator().start(state.clone())?;
+
+    tracing::info!("Started question-answer generation");
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "generation": report,
+        "status_url": format!("/api/admin/tasks/{}", report.task_id)
+    })))
+}
+
+/// GET /api/qa-pairs/generate - Pair count and the last generation run
+#[utoipa::path(
+    get,
+    path = "/api/qa-pairs/generate",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Generation status", body = serde_json::Value)
+    )
+)]
+pub async fn get_qa_generation_status(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let generator = state.qa_generator();
+
+    Json(serde_json::json!({
+        "enabled": generator.is_enabled(),
+        "running": generator.is_running(),
+        "pairs": state.knowledge_store().stats().synthetic_pairs,
+        "generation": generator.last_report()
+    }))
+}
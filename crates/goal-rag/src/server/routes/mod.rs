@@ -1,17 +1,35 @@
 //! API routes for the RAG server
 
+pub mod admin;
+pub mod audit;
+pub mod chunks;
+pub mod connectors;
 pub mod documents;
 pub mod files;
+pub mod graph;
 pub mod ingest;
+pub mod integrations;
 pub mod jobs;
+pub mod learning;
+pub mod prompts;
+pub mod provenance;
 pub mod query;
+pub mod reports;
+pub mod search;
+pub mod usage;
+#[cfg(feature = "gcp")]
+pub mod vector_index;
+pub mod webhooks;
 
 use axum::{
-    extract::DefaultBodyLimit,
-    routing::{delete, get, post},
+    extract::{DefaultBodyLimit, State},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use crate::ingestion::transcription::Transcriber;
 use crate::ingestion::ExternalParser;
+use crate::server::auth::require_admin;
 use crate::server::state::AppState;
 
 /// Build all API routes
@@ -20,7 +38,12 @@ pub fn api_routes(max_upload_size: usize) -> Router<AppState> {
         // Document management
         .route("/documents", get(documents::list_documents))
         .route("/documents/:id", get(documents::get_document))
-        .route("/documents/:id", delete(documents::delete_document))
+        .route("/documents/:id/original", get(documents::get_original))
+        .route("/documents/:id/plaintext", get(documents::get_plaintext))
+        .route("/documents/:id/pages/:page/thumbnail", get(documents::get_page_thumbnail))
+        .route("/documents/:id/versions", get(documents::list_document_versions))
+        .route("/documents/:id/diff", get(documents::diff_document_versions))
+        .route("/documents/:id/chunks", get(chunks::list_document_chunks))
         // Ingestion - with larger body limit for file uploads
         .route(
             "/ingest",
@@ -43,32 +66,133 @@ pub fn api_routes(max_upload_size: usize) -> Router<AppState> {
         .route("/files", get(files::list_files))
         .route("/files/check", post(files::check_files))
         .route("/files/failed", get(files::list_failed_files))
-        .route("/files/failed", delete(files::clear_failed_files))
+        .route("/files/dead-letter", get(files::list_dead_letter_files))
         .route("/files/stats", get(files::file_stats))
         .route("/files/sync/status", get(files::get_sync_status))
         .route("/files/:filename", get(files::get_file_status))
-        .route("/files/:filename", delete(files::delete_file_record))
         // Query
         .route("/query", post(query::query_rag))
+        .route("/query/batch", post(query::query_batch))
         // V2 Query (frontend-friendly format)
         .route("/v2/query", post(query::query_rag_v2))
         // String search
         .route("/string-search", post(query::string_search))
+        // Saved queries and scheduled reports
+        .route("/saved-queries", get(reports::list_saved_queries))
+        .route("/saved-queries", post(reports::create_saved_query))
+        .route("/saved-queries/:id", get(reports::get_saved_query))
+        .route("/saved-queries/:id", put(reports::update_saved_query))
+        .route("/saved-queries/:id", delete(reports::delete_saved_query))
+        .route("/saved-queries/:id/run", post(reports::run_saved_query))
+        .route("/saved-queries/:id/reports", get(reports::list_reports))
+        .route("/search/facets", get(search::get_facets))
+        // Feedback and experiments
+        .route("/feedback", post(learning::submit_feedback))
+        .route("/feedback/corrections", get(learning::list_corrections))
+        .route("/feedback/corrections/:id/verify", post(learning::verify_correction))
+        .route("/feedback/corrections/:id", delete(learning::delete_correction))
+        .route("/experiments", get(learning::get_experiments))
+        .route("/qa-pairs", get(learning::list_qa_pairs))
+        .route("/qa-pairs/generate", get(learning::get_qa_generation_status))
+        // Answer provenance
+        .route("/provenance/verify", post(provenance::verify_provenance))
+        .route("/provenance/key", get(provenance::get_provenance_key))
+        .route("/audit", get(audit::list_audit_records))
+        .route("/metrics", get(admin::get_metrics))
+        .route("/usage", get(usage::get_usage))
+        // Prompt templates
+        .route("/prompts", get(prompts::list_prompts))
+        .route("/prompts/:name", get(prompts::get_prompt))
+        .route("/graph/query", post(graph::query_graph))
+        .route("/graph/build", get(graph::get_graph_status))
+        // Chat integrations (signed by the platforms instead of API keys)
+        .route("/integrations/slack/events", post(integrations::slack_events))
+        .route("/integrations/slack/commands", post(integrations::slack_command))
+        .route("/integrations/teams/messages", post(integrations::teams_message))
         // Info and capabilities
         .route("/info", get(info))
         .route("/capabilities", get(capabilities));
 
     // Add GCP-specific routes when gcp feature is enabled
+    #[cfg(feature = "gcp")]
+    let router = router.route("/files/gcs-counts", get(files::get_gcs_counts));
+
+    router.nest("/admin", admin_routes()).merge(restricted_routes())
+}
+
+/// Routes outside `/admin` that change the corpus or the server, restricted to admins
+fn restricted_routes() -> Router<AppState> {
+    let router = Router::new()
+        .route("/documents/:id", delete(documents::delete_document))
+        .route("/documents", delete(documents::delete_documents))
+        .route("/collections/:id", delete(documents::purge_collection))
+        .route("/chunks/:id", patch(chunks::update_chunk))
+        .route("/ingest/path", post(jobs::ingest_path))
+        .route("/files/failed", delete(files::clear_failed_files))
+        .route("/files/:filename", delete(files::delete_file_record))
+        .route("/qa-pairs/generate", post(learning::start_qa_generation))
+        .route("/graph/build", post(graph::start_graph_build))
+        .route("/webhooks", get(webhooks::list_webhooks))
+        .route("/webhooks", post(webhooks::register_webhook))
+        .route("/webhooks/:id", delete(webhooks::delete_webhook))
+        .route("/prompts", post(prompts::create_prompt))
+        .route("/prompts/reload", post(prompts::reload_prompts))
+        .route("/prompts/:name", put(prompts::update_prompt))
+        .route("/prompts/:name", delete(prompts::delete_prompt));
+
+    #[cfg(feature = "gcp")]
+    let router = router.route("/files/sync", post(files::sync_from_gcs));
+
+    router.route_layer(middleware::from_fn(require_admin))
+}
+
+/// Administrative routes, nested under `/admin` and restricted to admins
+fn admin_routes() -> Router<AppState> {
+    let router = Router::new()
+        .route("/analyzers/reindex", post(admin::reindex_analyzers))
+        .route("/traces/export", get(admin::export_traces))
+        .route("/export", post(admin::export_knowledge_base))
+        .route("/snapshots", post(admin::take_snapshot))
+        .route("/snapshots", get(admin::list_snapshots))
+        .route(
+            "/import",
+            post(admin::import_knowledge_base).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/guard/events", get(admin::list_guard_events))
+        .route("/erase", delete(admin::erase))
+        .route("/reindex", post(admin::start_reindex))
+        .route("/reindex", get(admin::get_reindex_status))
+        .route("/config/reload", post(admin::reload_config))
+        .route("/repair-embeddings", post(admin::start_embedding_repair))
+        .route("/repair-embeddings", get(admin::get_embedding_repair_status))
+        .route("/connectors", get(connectors::list_connectors))
+        .route("/connectors/:name/sync", post(connectors::sync_connector))
+        .route("/tasks", get(admin::list_tasks))
+        .route("/tasks/events", get(admin::task_events))
+        .route("/tasks/:id", get(admin::get_task));
+
     #[cfg(feature = "gcp")]
     let router = router
-        .route("/files/sync", post(files::sync_from_gcs))
-        .route("/files/gcs-counts", get(files::get_gcs_counts));
+        .route("/vector-index", get(vector_index::get_vector_index))
+        .route("/vector-index", post(vector_index::create_vector_index))
+        .route("/vector-index/endpoint", post(vector_index::create_index_endpoint))
+        .route("/vector-index/deploy", post(vector_index::deploy_vector_index))
+        .route("/vector-index/resize", post(vector_index::resize_vector_index))
+        .route("/vector-index/operation", get(vector_index::get_vector_index_operation));
 
-    router
+    router.route_layer(middleware::from_fn(require_admin))
 }
 
 /// API info endpoint
-async fn info() -> axum::Json<serde_json::Value> {
+#[utoipa::path(
+    get,
+    path = "/api/info",
+    tag = "system",
+    responses(
+        (status = 200, description = "API summary", body = serde_json::Value)
+    )
+)]
+pub async fn info() -> axum::Json<serde_json::Value> {
     axum::Json(serde_json::json!({
         "name": "ruvector-rag",
         "version": env!("CARGO_PKG_VERSION"),
@@ -76,29 +200,103 @@ async fn info() -> axum::Json<serde_json::Value> {
         "endpoints": {
             "POST /api/ingest": "Upload and process documents (sync)",
             "POST /api/ingest/async": "Upload documents for async processing",
-            "GET /api/jobs": "List all jobs and queue stats",
+            "POST /api/ingest/path": "Queue new and changed files of a server directory (include/exclude globs)",
+            "GET /api/jobs": "List jobs and queue stats (status/filename/sort, limit/offset/cursor)",
             "GET /api/jobs/incomplete": "List incomplete jobs that can be resumed",
             "GET /api/jobs/:id": "Get job progress",
             "GET /api/jobs/:id/files": "Get per-file progress with tier and parser details",
             "POST /api/jobs/:id/resume": "Resume an incomplete/failed job",
             "GET /api/system/parsers": "Get available parsers and their status",
             "POST /api/query": "Query with citations (v1)",
+            "POST /api/query/batch": "Answer up to retrieval.batch.max_questions queries concurrently, one result per question",
             "POST /api/v2/query": "Query with citations (v2 - frontend-friendly format)",
-            "POST /api/string-search": "Literal string search",
-            "GET /api/documents": "List all documents",
+            "POST /api/string-search": "String search (exact, prefix, fuzzy or regex)",
+            "GET /api/saved-queries": "List saved queries with their next scheduled run",
+            "POST /api/saved-queries": "Save a query with an optional cron schedule, webhook and e-mail delivery",
+            "GET /api/saved-queries/:id": "Get a saved query",
+            "PUT /api/saved-queries/:id": "Replace a saved query",
+            "DELETE /api/saved-queries/:id": "Delete a saved query and its reports",
+            "POST /api/saved-queries/:id/run": "Run a saved query now and store the report",
+            "GET /api/saved-queries/:id/reports": "Reports of a saved query, newest first (?limit=)",
+            "GET /api/search/facets": "Counts by file type, collection, ingest month and entity (?q=&collection=&file_type=&month=)",
+            "POST /api/feedback": "Rate an answer by interaction id, optionally with a corrected answer or the right documents",
+            "GET /api/feedback/corrections": "Submitted answer corrections (?verified=true|false)",
+            "POST /api/feedback/corrections/:id/verify": "Verify a correction; the corrected answer is then returned as curated",
+            "DELETE /api/feedback/corrections/:id": "Reject or withdraw a correction",
+            "GET /api/experiments": "Answers and feedback per A/B experiment variant",
+            "GET /api/qa-pairs": "Question-answer pairs generated from chunks, as an evaluation set seed (?document_id=&limit=)",
+            "POST /api/qa-pairs/generate": "Generate question-answer pairs for chunks without any in the background",
+            "GET /api/qa-pairs/generate": "Generated pair count and last generation run",
+            "POST /api/provenance/verify": "Verify a signed answer against its citations and the current corpus",
+            "GET /api/provenance/key": "Get the answer signing algorithm and public key",
+            "GET /api/documents": "List documents (limit/offset/cursor, ETag)",
             "GET /api/documents/:id": "Get document details",
             "DELETE /api/documents/:id": "Delete a document",
-            "GET /api/files": "List all tracked files with status",
+            "GET /api/documents/:id/original": "Original file (Range requests supported; stored in GCS or with originals.keep)",
+            "GET /api/documents/:id/plaintext": "Extracted text that citation offsets refer to (Range requests supported)",
+            "GET /api/documents/:id/pages/:n/thumbnail": "PNG preview of a page (PDFs, with thumbnails enabled)",
+            "GET /api/documents/:id/versions": "Versions of a document's file (superseded ones kept on re-ingestion)",
+            "GET /api/documents/:id/diff": "Line diff between two versions (from/to version numbers)",
+            "GET /api/documents/:id/chunks": "Chunks of a document in order (limit/offset/cursor, ?embeddings=true)",
+            "PATCH /api/chunks/:id": "Replace the text of a chunk, re-embedding and re-indexing it",
+            "DELETE /api/documents": "Delete documents by ids or filter (dry_run returns counts)",
+            "DELETE /api/collections/:id": "Delete every document of a collection (?dry_run=true returns counts)",
+            "GET /api/files": "List tracked files (status/filename/sort, limit/offset/cursor, ETag)",
             "POST /api/files/check": "Check file status before upload (deduplication)",
             "GET /api/files/failed": "List failed files with error details",
             "DELETE /api/files/failed": "Clear all failed file records for retry",
+            "GET /api/files/dead-letter": "List job files that exhausted their retries",
             "GET /api/files/stats": "Get file registry statistics",
             "GET /api/files/:filename": "Get specific file status",
             "DELETE /api/files/:filename": "Remove file record for re-upload",
             "POST /api/files/sync": "Sync file registry from GCS bucket (GCP only)",
             "GET /api/files/sync/status": "Get last GCS sync status",
             "GET /api/files/gcs-counts": "Get file counts from GCS bucket (GCP only)",
-            "GET /api/capabilities": "Check document extraction capabilities"
+            "GET /api/admin/vector-index": "Vertex AI Vector Search index and deployment state (GCP only)",
+            "POST /api/admin/vector-index": "Create a stream-updated Vector Search index (GCP only)",
+            "POST /api/admin/vector-index/endpoint": "Create a Vector Search index endpoint (GCP only)",
+            "POST /api/admin/vector-index/deploy": "Deploy the index on the endpoint (GCP only)",
+            "POST /api/admin/vector-index/resize": "Change the replicas serving the deployed index (GCP only)",
+            "GET /api/admin/vector-index/operation": "Poll an index lifecycle operation by name (GCP only)",
+            "GET /api/capabilities": "Check document extraction capabilities",
+            "POST /api/admin/analyzers/reindex": "Rebuild lexical search terms after changing a collection's analyzer",
+            "GET /api/admin/traces/export": "Export sampled retrieval traces (?format=ndjson|parquet&since=&limit=)",
+            "GET /api/admin/guard/events": "Prompt-injection guard audit log (?since=&limit=)",
+            "DELETE /api/admin/erase": "Erase documents by filename pattern, ids or content from every store, with a signed report",
+            "GET /api/audit": "Answer audit log: chunks, prompt hash, model and answer per query (?since=&until=&principal=&interaction_id=&limit=)",
+            "POST /api/admin/snapshots": "Snapshot the SQLite registry (online backup; restore with the CLI's restore-snapshot)",
+            "GET /api/admin/snapshots": "List registry snapshots",
+            "POST /api/admin/export": "Stream a tar archive of documents, text, chunks, embeddings and file registry",
+            "POST /api/admin/import": "Restore an export archive (tar or tar.gz body); existing documents are skipped",
+            "POST /api/admin/reindex": "Re-chunk and re-embed all documents in the background, then swap the index",
+            "GET /api/admin/reindex": "Get reindex progress",
+            "POST /api/admin/config/reload": "Re-read the configuration file and apply the settings that can change at runtime",
+            "POST /api/admin/repair-embeddings": "Re-embed chunks whose embedding failed and zero vectors in the background",
+            "GET /api/admin/repair-embeddings": "Get pending chunk count and last repair run",
+            "GET /api/admin/connectors": "Google Drive and SharePoint connectors and their last sync passes",
+            "POST /api/admin/connectors/:name/sync": "Sync a connector now in the background (?full=true lists every file)",
+            "GET /api/admin/tasks": "Progress, throughput and ETA of ingestion jobs and maintenance tasks (?active=true)",
+            "GET /api/admin/tasks/:id": "Get progress of one task",
+            "GET /api/admin/tasks/events": "Stream task progress as server-sent events",
+            "GET /api/metrics": "Embedding batch scheduler queue depth, batch sizes and latencies",
+            "GET /api/usage": "Token usage, estimated cost and budget status (?from=&to=&group_by=&collection=)",
+            "GET /api/webhooks": "List registered webhooks",
+            "POST /api/webhooks": "Register a webhook for document, job and cache events",
+            "DELETE /api/webhooks/:id": "Remove a registered webhook",
+            "GET /api/prompts": "List prompt templates",
+            "POST /api/prompts": "Create a prompt template",
+            "GET /api/prompts/:name": "Get a prompt template",
+            "PUT /api/prompts/:name": "Create or replace a prompt template",
+            "DELETE /api/prompts/:name": "Delete a prompt template",
+            "POST /api/prompts/reload": "Re-read prompt templates from disk",
+            "POST /api/graph/query": "Triples around entities, or around the entities in a question",
+            "POST /api/graph/build": "Extract knowledge graph triples from new chunks (?rebuild=true for all)",
+            "GET /api/graph/build": "Knowledge graph size and last build",
+            "POST /api/integrations/slack/events": "Slack Events API: answers app mentions and direct messages in a thread",
+            "POST /api/integrations/slack/commands": "Slack slash command: answers the command text in the channel",
+            "POST /api/integrations/teams/messages": "Teams outgoing webhook: replies with the answer",
+            "GET /api/openapi.json": "OpenAPI 3 specification (generated from the handlers)",
+            "GET /api/docs": "Swagger UI"
         },
         "features": {
             "gcs_storage": "Original files and plain text stored in GCS",
@@ -111,11 +309,21 @@ async fn info() -> axum::Json<serde_json::Value> {
 }
 
 /// Document extraction capabilities endpoint
-async fn capabilities() -> axum::Json<serde_json::Value> {
+#[utoipa::path(
+    get,
+    path = "/api/capabilities",
+    tag = "system",
+    responses(
+        (status = 200, description = "Extraction capabilities and dependency health", body = serde_json::Value)
+    )
+)]
+pub async fn capabilities(State(state): State<AppState>) -> axum::Json<serde_json::Value> {
+    let readiness = state.readiness().report(&state).await;
     let has_pdftotext = ExternalParser::has_pdftotext();
     let has_tesseract = ExternalParser::has_tesseract();
     let has_pdftoppm = ExternalParser::has_pdftoppm();
     let has_pandoc = ExternalParser::has_pandoc();
+    let has_ffmpeg = Transcriber::has_ffmpeg();
 
     // Check for LibreOffice
     let has_libreoffice = std::process::Command::new("libreoffice")
@@ -150,6 +358,11 @@ async fn capabilities() -> axum::Json<serde_json::Value> {
                 "available": has_libreoffice,
                 "purpose": "Legacy format conversion (DOC, PPT, XLS)",
                 "install": "apt install libreoffice"
+            },
+            "ffmpeg": {
+                "available": has_ffmpeg,
+                "purpose": "Audio extraction from video for transcription",
+                "install": "apt install ffmpeg"
             }
         },
         "formats": {
@@ -220,17 +433,122 @@ async fn capabilities() -> axum::Json<serde_json::Value> {
                 "formats": ["png", "jpg", "jpeg", "gif", "webp", "bmp", "tiff"],
                 "status": if has_tesseract { "available" } else { "unavailable" }
             },
+            "audio": {
+                "native": false,
+                "transcription": true,
+                "formats": ["mp3", "wav", "m4a", "flac", "ogg", "opus"],
+                "status": "available"
+            },
+            "video": {
+                "native": false,
+                "transcription": has_ffmpeg,
+                "formats": ["mp4", "mov", "mkv", "webm", "avi"],
+                "status": if has_ffmpeg { "available" } else { "unavailable" }
+            },
+            "email": {
+                "native": true,
+                "formats": ["eml", "msg", "mbox"],
+                "attachments": true,
+                "status": "full"
+            },
+            "archives": {
+                "native": true,
+                "formats": ["zip", "tar", "tar.gz", "tgz"],
+                "extracts_to": "one document per contained file",
+                "status": "full"
+            },
+            "notebooks": {
+                "native": true,
+                "formats": ["ipynb"],
+                "chunks": "one or more per cell, code and markdown chunked separately",
+                "status": "full"
+            },
+            "rst": { "native": true, "status": "full" },
             "txt": { "native": true, "status": "full" },
             "md": { "native": true, "status": "full" },
             "html": { "native": true, "status": "full" },
             "csv": { "native": true, "status": "full" },
             "code": { "native": true, "status": "full", "extensions": ["rs", "py", "js", "ts", "go", "java", "cpp", "c", "cs", "rb", "php", "swift", "kt", "sql", "sh", "yaml", "json", "xml", "toml"] }
         },
+        "components": readiness.components,
         "recommendations": {
             "for_scanned_pdfs": if !has_tesseract { Some("Install tesseract-ocr for OCR support") } else { None },
             "for_legacy_office": if !has_libreoffice { Some("Install libreoffice for DOC/PPT/XLS support") } else { None },
             "for_better_pdf": if !has_pdftotext { Some("Install poppler-utils for better PDF extraction") } else { None },
-            "for_documents": if !has_pandoc { Some("Install pandoc for RTF/EPUB/ODT support") } else { None }
+            "for_documents": if !has_pandoc { Some("Install pandoc for RTF/EPUB/ODT support") } else { None },
+            "for_video": if !has_ffmpeg { Some("Install ffmpeg for video transcription") } else { None }
         }
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+        Extension,
+    };
+    use tower::ServiceExt;
+
+    use crate::server::test_support::TestApp;
+    use crate::types::{query::IngestOptions, Principal};
+
+    async fn status(app: &TestApp, principal: &Principal, method: Method, uri: &str) -> StatusCode {
+        let router = Router::new()
+            .nest("/api", api_routes(1024 * 1024))
+            .layer(Extension(principal.clone()))
+            .with_state(app.state.clone());
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_restricted_routes_need_admin() {
+        let app = TestApp::new().await;
+        let options = IngestOptions { acl: vec!["sales".to_string()], ..Default::default() };
+        let doc = app.ingest("notes.txt", "Quarterly sales notes for the northern region.", &options).await;
+        let reader = Principal::new("bob", vec!["sales".to_string()]);
+        assert!(reader.can_read(&doc));
+
+        let document = format!("/api/documents/{}", doc.id);
+        let chunk = format!("/api/chunks/{}", uuid::Uuid::new_v4());
+        let restricted = [
+            (Method::DELETE, document.as_str()),
+            (Method::DELETE, "/api/documents"),
+            (Method::DELETE, "/api/collections/sales"),
+            (Method::PATCH, chunk.as_str()),
+            (Method::POST, "/api/ingest/path"),
+            (Method::DELETE, "/api/files/failed"),
+            (Method::DELETE, "/api/files/notes.txt"),
+            (Method::POST, "/api/qa-pairs/generate"),
+            (Method::POST, "/api/graph/build"),
+            (Method::GET, "/api/webhooks"),
+            (Method::POST, "/api/prompts"),
+            (Method::POST, "/api/prompts/reload"),
+            (Method::PUT, "/api/prompts/brief"),
+            (Method::DELETE, "/api/prompts/brief"),
+            (Method::POST, "/api/admin/reindex"),
+            (Method::GET, "/api/admin/tasks"),
+        ];
+        for (method, uri) in restricted {
+            assert_eq!(status(&app, &reader, method.clone(), uri).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+        }
+        assert!(app.state.get_document(&doc.id).is_some());
+
+        // Reads next to the restricted routes stay open
+        assert_eq!(status(&app, &reader, Method::GET, &document).await, StatusCode::OK);
+        assert_eq!(status(&app, &reader, Method::GET, "/api/files/failed").await, StatusCode::OK);
+        assert_eq!(status(&app, &reader, Method::GET, "/api/prompts").await, StatusCode::OK);
+
+        let mut admin = Principal::new("carol", Vec::new());
+        admin.admin = true;
+        assert_eq!(status(&app, &admin, Method::DELETE, &document).await, StatusCode::OK);
+        assert!(app.state.get_document(&doc.id).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+//! Prompt template management
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::generation::PromptTemplate;
+use crate::server::state::AppState;
+
+/// GET /api/prompts - List prompt templates
+#[utoipa::path(
+    get,
+    path = "/api/prompts",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Prompt templates", body = Vec<PromptTemplate>)
+    )
+)]
+pub async fn list_prompts(State(state): State<AppState>) -> Json<Vec<PromptTemplate>> {
+    Json(state.prompts().list())
+}
+
+/// POST /api/prompts - Create a prompt template
+#[utoipa::path(
+    post,
+    path = "/api/prompts",
+    tag = "admin",
+    request_body = PromptTemplate,
+    responses(
+        (status = 200, description = "Template created", body = PromptTemplate),
+        (status = 400, description = "Invalid name or template syntax, or name taken", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn create_prompt(
+    State(state): State<AppState>,
+    Json(template): Json<PromptTemplate>,
+) -> Result<Json<PromptTemplate>> {
+    if state.prompts().get(&template.name).is_some() {
+        return Err(Error::Config(format!("Prompt template '{}' already exists", template.name)));
+    }
+    Ok(Json(state.prompts().save(template)?))
+}
+
+/// GET /api/prompts/:name - Get a prompt template
+#[utoipa::path(
+    get,
+    path = "/api/prompts/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "Template name")),
+    responses(
+        (status = 200, description = "Prompt template", body = PromptTemplate),
+        (status = 404, description = "Template not found", body = ProblemDetails)
+    )
+)]
+pub async fn get_prompt(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PromptTemplate>> {
+    state
+        .prompts()
+        .get(&name)
+        .map(Json)
+        .ok_or_else(|| Error::NotFound(format!("Prompt template {}", name)))
+}
+
+/// PUT /api/prompts/:name - Create or replace a prompt template
+///
+/// The name in the path is used; a name in the body is ignored.
+#[utoipa::path(
+    put,
+    path = "/api/prompts/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "Template name")),
+    request_body = PromptTemplate,
+    responses(
+        (status = 200, description = "Template saved", body = PromptTemplate),
+        (status = 400, description = "Invalid name or template syntax", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn update_prompt(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(mut template): Json<PromptTemplate>,
+) -> Result<Json<PromptTemplate>> {
+    template.name = name;
+    Ok(Json(state.prompts().save(template)?))
+}
+
+/// DELETE /api/prompts/:name - Delete a prompt template
+#[utoipa::path(
+    delete,
+    path = "/api/prompts/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "Template name")),
+    responses(
+        (status = 200, description = "Template deleted", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "Template not found", body = ProblemDetails)
+    )
+)]
+pub async fn delete_prompt(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    if !state.prompts().delete(&name)? {
+        return Err(Error::NotFound(format!("Prompt template {}", name)));
+    }
+
+    Ok(Json(serde_json::json!({ "success": true, "name": name })))
+}
+
+/// POST /api/prompts/reload - Re-read templates from disk
+#[utoipa::path(
+    post,
+    path = "/api/prompts/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Templates reloaded", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn reload_prompts(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
+    let loaded = state.prompts().reload()?;
+
+    Ok(Json(serde_json::json!({ "success": true, "loaded": loaded })))
+}
@@ -0,0 +1,89 @@
+//! Answer provenance verification
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::generation::ProvenanceSigner;
+use crate::server::state::AppState;
+use crate::types::AnswerProvenance;
+
+/// Request to verify a previously returned answer
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyProvenanceRequest {
+    /// Answer text exactly as returned by the query endpoint
+    pub answer: String,
+    /// Provenance record returned with the answer
+    pub provenance: AnswerProvenance,
+}
+
+/// Verification result
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VerifyProvenanceResponse {
+    /// Signature matches the answer, citation hashes and snapshot id
+    pub valid: bool,
+    /// The corpus is still in the state the answer was generated from
+    pub corpus_snapshot_current: bool,
+    /// Current corpus snapshot id
+    pub current_corpus_snapshot_id: String,
+}
+
+/// Key used to sign answers
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProvenanceKeyResponse {
+    pub algorithm: String,
+    pub key_id: Option<String>,
+    /// Base64 Ed25519 public key (absent for HMAC)
+    pub public_key: Option<String>,
+}
+
+fn signer(state: &AppState) -> Result<&std::sync::Arc<ProvenanceSigner>> {
+    state
+        .provenance_signer()
+        .ok_or_else(|| Error::Config("Answer provenance signing is not enabled".to_string()))
+}
+
+/// POST /api/provenance/verify - Verify a signed answer
+#[utoipa::path(
+    post,
+    path = "/api/provenance/verify",
+    tag = "query",
+    request_body = VerifyProvenanceRequest,
+    responses(
+        (status = 200, description = "Verification result", body = VerifyProvenanceResponse),
+        (status = 400, description = "Provenance signing not enabled", body = ProblemDetails)
+    )
+)]
+pub async fn verify_provenance(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyProvenanceRequest>,
+) -> Result<Json<VerifyProvenanceResponse>> {
+    let valid = signer(&state)?.verify(&request.answer, &request.provenance);
+    let current_corpus_snapshot_id = state.corpus_snapshot_id();
+
+    Ok(Json(VerifyProvenanceResponse {
+        valid,
+        corpus_snapshot_current: current_corpus_snapshot_id == request.provenance.corpus_snapshot_id,
+        current_corpus_snapshot_id,
+    }))
+}
+
+/// GET /api/provenance/key - Signing algorithm and public key
+#[utoipa::path(
+    get,
+    path = "/api/provenance/key",
+    tag = "query",
+    responses(
+        (status = 200, description = "Signing key details", body = ProvenanceKeyResponse),
+        (status = 400, description = "Provenance signing not enabled", body = ProblemDetails)
+    )
+)]
+pub async fn get_provenance_key(State(state): State<AppState>) -> Result<Json<ProvenanceKeyResponse>> {
+    let signer = signer(&state)?;
+    Ok(Json(ProvenanceKeyResponse {
+        algorithm: signer.algorithm().to_string(),
+        key_id: signer.key_id().map(String::from),
+        public_key: signer.public_key(),
+    }))
+}
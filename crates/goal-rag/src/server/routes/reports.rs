@@ -0,0 +1,192 @@
+//! Saved queries and their reports
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::server::reports::{Report, SavedQuery, SavedQueryDefinition};
+use crate::server::state::AppState;
+use crate::types::Principal;
+
+/// Query parameters for listing reports
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct ReportsQuery {
+    /// Maximum reports to return, newest first (default 10)
+    pub limit: Option<usize>,
+}
+
+/// A saved query the caller may see, or 404
+fn visible(state: &AppState, id: &Uuid, principal: &Option<Extension<Principal>>) -> Result<SavedQuery> {
+    state
+        .saved_queries()
+        .get(id)
+        .filter(|query| query.visible_to(principal.as_ref().map(|Extension(p)| p)))
+        .ok_or_else(|| Error::NotFound(format!("Saved query {}", id)))
+}
+
+/// Reports can only be e-mailed with an SMTP server configured
+fn check_email(state: &AppState, definition: &SavedQueryDefinition) -> Result<()> {
+    if !definition.email.is_empty() && state.config().reports.smtp.is_none() {
+        return Err(Error::Config("E-mailing reports needs [reports.smtp] configured".to_string()));
+    }
+    Ok(())
+}
+
+/// GET /api/saved-queries - List saved queries
+///
+/// Callers see the queries they saved; admins see all of them.
+#[utoipa::path(
+    get,
+    path = "/api/saved-queries",
+    tag = "query",
+    responses(
+        (status = 200, description = "Saved queries with their next scheduled run", body = Vec<SavedQuery>)
+    )
+)]
+pub async fn list_saved_queries(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> Json<Vec<SavedQuery>> {
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    Json(state.saved_queries().list().into_iter().filter(|q| q.visible_to(principal)).collect())
+}
+
+/// POST /api/saved-queries - Save a query
+///
+/// The query is answered as the caller whenever it runs, so it only sees the
+/// documents the caller can see.
+#[utoipa::path(
+    post,
+    path = "/api/saved-queries",
+    tag = "query",
+    request_body = SavedQueryDefinition,
+    responses(
+        (status = 200, description = "Query saved", body = SavedQuery),
+        (status = 400, description = "Invalid schedule or e-mail address", body = ProblemDetails)
+    )
+)]
+pub async fn create_saved_query(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(definition): Json<SavedQueryDefinition>,
+) -> Result<Json<SavedQuery>> {
+    check_email(&state, &definition)?;
+    let owner = principal.map(|Extension(p)| p);
+    Ok(Json(state.saved_queries().create(definition, owner)?))
+}
+
+/// GET /api/saved-queries/:id - Get a saved query
+#[utoipa::path(
+    get,
+    path = "/api/saved-queries/{id}",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Saved query ID")),
+    responses(
+        (status = 200, description = "Saved query", body = SavedQuery),
+        (status = 404, description = "Saved query not found", body = ProblemDetails)
+    )
+)]
+pub async fn get_saved_query(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<SavedQuery>> {
+    Ok(Json(visible(&state, &id, &principal)?))
+}
+
+/// PUT /api/saved-queries/:id - Replace a saved query's definition
+#[utoipa::path(
+    put,
+    path = "/api/saved-queries/{id}",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Saved query ID")),
+    request_body = SavedQueryDefinition,
+    responses(
+        (status = 200, description = "Saved query updated", body = SavedQuery),
+        (status = 400, description = "Invalid schedule or e-mail address", body = ProblemDetails),
+        (status = 404, description = "Saved query not found", body = ProblemDetails)
+    )
+)]
+pub async fn update_saved_query(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Json(definition): Json<SavedQueryDefinition>,
+) -> Result<Json<SavedQuery>> {
+    visible(&state, &id, &principal)?;
+    check_email(&state, &definition)?;
+    Ok(Json(state.saved_queries().update(&id, definition)?))
+}
+
+/// DELETE /api/saved-queries/:id - Delete a saved query and its reports
+#[utoipa::path(
+    delete,
+    path = "/api/saved-queries/{id}",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Saved query ID")),
+    responses(
+        (status = 200, description = "Saved query deleted", body = serde_json::Value),
+        (status = 404, description = "Saved query not found", body = ProblemDetails)
+    )
+)]
+pub async fn delete_saved_query(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    visible(&state, &id, &principal)?;
+    state.saved_queries().delete(&id)?;
+    tracing::info!("Deleted saved query {}", id);
+
+    Ok(Json(serde_json::json!({ "success": true, "id": id })))
+}
+
+/// POST /api/saved-queries/:id/run - Run a saved query now
+///
+/// Stores and delivers the report as a scheduled run would. Returns `null`
+/// for a `new_documents_only` query when nothing was ingested since its last
+/// run.
+#[utoipa::path(
+    post,
+    path = "/api/saved-queries/{id}/run",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Saved query ID")),
+    responses(
+        (status = 200, description = "Generated report, or null when there were no new documents", body = Option<Report>),
+        (status = 404, description = "Saved query not found", body = ProblemDetails)
+    )
+)]
+pub async fn run_saved_query(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Option<Report>>> {
+    visible(&state, &id, &principal)?;
+    Ok(Json(state.saved_queries().run(&state, &id).await?))
+}
+
+/// GET /api/saved-queries/:id/reports - Reports of a saved query
+#[utoipa::path(
+    get,
+    path = "/api/saved-queries/{id}/reports",
+    tag = "query",
+    params(("id" = Uuid, Path, description = "Saved query ID"), ReportsQuery),
+    responses(
+        (status = 200, description = "Reports, newest first", body = Vec<Report>),
+        (status = 404, description = "Saved query not found", body = ProblemDetails)
+    )
+)]
+pub async fn list_reports(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ReportsQuery>,
+) -> Result<Json<Vec<Report>>> {
+    visible(&state, &id, &principal)?;
+    Ok(Json(state.saved_queries().reports(&id, params.limit.unwrap_or(10))?))
+}
@@ -0,0 +1,199 @@
+//! Faceted search endpoint
+
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::learning::graph::entity_key;
+use crate::server::state::AppState;
+use crate::types::acl::can_read;
+use crate::types::{Document, Principal};
+
+/// Entities reported when the request doesn't set `entities`
+const DEFAULT_ENTITIES: usize = 10;
+
+/// Query parameters for facet counts
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FacetsQuery {
+    /// Only count chunks containing this phrase (exact string search)
+    #[serde(default)]
+    pub q: Option<String>,
+    /// Only this collection
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Only this file type (extension, e.g. `pdf`)
+    #[serde(default)]
+    pub file_type: Option<String>,
+    /// Only documents ingested in this month (YYYY-MM)
+    #[serde(default)]
+    pub month: Option<String>,
+    /// Number of top entities to return (default: 10)
+    #[serde(default)]
+    pub entities: Option<usize>,
+}
+
+/// Documents and chunks sharing a facet value
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FacetCount {
+    /// Value to filter by (file type extension, collection, YYYY-MM or entity)
+    pub value: String,
+    /// Documents with matching chunks
+    pub documents: usize,
+    /// Matching chunks
+    pub chunks: usize,
+}
+
+/// Facet counts for a query and filters
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FacetsResponse {
+    pub query: Option<String>,
+    /// Documents with matching chunks
+    pub total_documents: usize,
+    /// Matching chunks
+    pub total_chunks: usize,
+    pub file_types: Vec<FacetCount>,
+    /// Collections (documents outside a collection are not listed)
+    pub collections: Vec<FacetCount>,
+    /// Months documents were ingested, newest first
+    pub ingest_months: Vec<FacetCount>,
+    /// Entities mentioned in the most documents
+    pub entities: Vec<FacetCount>,
+}
+
+/// GET /api/search/facets - Counts by file type, collection, ingest month and entity
+///
+/// Counts cover the documents the caller can read, narrowed by the query
+/// and filters, so UIs can show a filter sidebar next to search results.
+#[utoipa::path(
+    get,
+    path = "/api/search/facets",
+    tag = "query",
+    params(FacetsQuery),
+    responses(
+        (status = 200, description = "Facet counts", body = FacetsResponse),
+        (status = 400, description = "Invalid month", body = ProblemDetails)
+    )
+)]
+pub async fn get_facets(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<FacetsQuery>,
+) -> Result<Json<FacetsResponse>> {
+    if let Some(ref month) = params.month {
+        NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| Error::Config(format!("Invalid month '{}' (expected YYYY-MM)", month)))?;
+    }
+
+    let principal = principal.as_ref().map(|Extension(p)| p);
+    let documents: HashMap<Uuid, Document> = state
+        .list_documents()
+        .into_iter()
+        .filter(|doc| can_read(principal, doc))
+        .filter(|doc| params.month.as_ref().map_or(true, |month| ingest_month(doc) == *month))
+        .map(|doc| (doc.id, doc))
+        .collect();
+
+    let database = state.database().clone();
+    let (query, collection, file_type) = (params.q.clone(), params.collection.clone(), params.file_type.clone());
+    let (counts, mentions) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let (query, collection, file_type) = (query.as_deref(), collection.as_deref(), file_type.as_deref());
+        Ok((
+            database.facet_counts(query, collection, file_type)?,
+            database.facet_entities(query, collection, file_type)?,
+        ))
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+    let mut file_types = Facet::default();
+    let mut collections = Facet::default();
+    let mut months = Facet::default();
+    let (mut total_documents, mut total_chunks) = (HashSet::new(), 0);
+    for count in counts {
+        let Some(doc) = documents.get(&count.document_id) else {
+            continue;
+        };
+        total_documents.insert(doc.id);
+        total_chunks += count.chunks;
+        file_types.add(&count.file_type, doc.id, count.chunks);
+        if let Some(ref collection) = count.collection {
+            collections.add(collection, doc.id, count.chunks);
+        }
+        months.add(&ingest_month(doc), doc.id, count.chunks);
+    }
+
+    let mut entities = Facet::default();
+    for (document_id, entity) in mentions {
+        if documents.contains_key(&document_id) {
+            entities.add_entity(&entity, document_id);
+        }
+    }
+
+    let mut ingest_months = months.into_counts();
+    ingest_months.sort_by(|a, b| b.value.cmp(&a.value));
+    let mut entities = entities.into_counts();
+    entities.truncate(params.entities.unwrap_or(DEFAULT_ENTITIES));
+
+    Ok(Json(FacetsResponse {
+        query: params.q,
+        total_documents: total_documents.len(),
+        total_chunks,
+        file_types: file_types.into_counts(),
+        collections: collections.into_counts(),
+        ingest_months,
+        entities,
+    }))
+}
+
+fn ingest_month(doc: &Document) -> String {
+    doc.ingested_at.format("%Y-%m").to_string()
+}
+
+/// Documents and chunks per value
+#[derive(Default)]
+struct Facet {
+    /// Value, documents and chunks by key
+    values: HashMap<String, (String, HashSet<Uuid>, usize)>,
+}
+
+impl Facet {
+    fn add(&mut self, value: &str, document_id: Uuid, chunks: usize) {
+        self.insert(value.to_string(), value, document_id, chunks);
+    }
+
+    /// Add a chunk mentioning an entity, spellings differing in case or
+    /// surrounding punctuation counted as one (the first one seen is shown)
+    fn add_entity(&mut self, entity: &str, document_id: Uuid) {
+        self.insert(entity_key(entity), entity, document_id, 1);
+    }
+
+    fn insert(&mut self, key: String, value: &str, document_id: Uuid, chunks: usize) {
+        let entry = self.values.entry(key).or_insert_with(|| (value.to_string(), HashSet::new(), 0));
+        entry.1.insert(document_id);
+        entry.2 += chunks;
+    }
+
+    /// Counts with the most documents first
+    fn into_counts(self) -> Vec<FacetCount> {
+        let mut counts: Vec<FacetCount> = self
+            .values
+            .into_values()
+            .map(|(value, documents, chunks)| FacetCount { value, documents: documents.len(), chunks })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.documents
+                .cmp(&a.documents)
+                .then_with(|| b.chunks.cmp(&a.chunks))
+                .then_with(|| a.value.cmp(&b.value))
+        });
+        counts
+    }
+}
@@ -0,0 +1,108 @@
+//! Token usage and cost endpoint
+
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{Error, ProblemDetails, Result};
+use crate::providers::usage::{self, BudgetStatus, UsageRecord, USAGE_DIMENSIONS};
+use crate::server::state::AppState;
+use crate::types::Principal;
+
+/// Days reported when the request doesn't set `from`
+const DEFAULT_DAYS: i64 = 30;
+
+/// Query parameters for the usage report
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsageQuery {
+    /// First day (YYYY-MM-DD, default: 30 days ago)
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    /// Last day (YYYY-MM-DD, default: today)
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+    /// Comma-separated dimensions to sum rows by: day, provider, model,
+    /// kind, collection, principal (default: all)
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Only usage of this collection
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+/// Usage report
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageResponse {
+    /// Whether usage is tracked
+    pub enabled: bool,
+    pub from: String,
+    pub to: String,
+    pub rows: Vec<UsageRecord>,
+    /// Sum of all rows
+    pub total: UsageRecord,
+    /// Spending against the configured budgets
+    pub budgets: Vec<BudgetStatus>,
+}
+
+/// GET /api/usage - Token usage and estimated cost per day, model, collection and caller
+///
+/// Callers that aren't admins only see their own usage.
+#[utoipa::path(
+    get,
+    path = "/api/usage",
+    tag = "system",
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "Usage rows, totals and budget status", body = UsageResponse),
+        (status = 400, description = "Unknown group_by dimension or invalid range", body = ProblemDetails)
+    )
+)]
+pub async fn get_usage(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>> {
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_DAYS - 1));
+    if from > to {
+        return Err(Error::Config(format!("from ({}) is after to ({})", from, to)));
+    }
+
+    let dimensions: Vec<&str> = match query.group_by {
+        Some(ref group_by) => group_by.split(',').map(str::trim).filter(|d| !d.is_empty()).collect(),
+        None => USAGE_DIMENSIONS.to_vec(),
+    };
+    if let Some(unknown) = dimensions.iter().find(|d| !USAGE_DIMENSIONS.contains(d)) {
+        return Err(Error::Config(format!(
+            "Unknown group_by dimension '{}' (expected {})",
+            unknown,
+            USAGE_DIMENSIONS.join(", ")
+        )));
+    }
+
+    let own = principal.as_ref().map(|Extension(p)| p).filter(|p| !p.admin);
+    let rows: Vec<UsageRecord> = state
+        .usage()
+        .rows(from, to)?
+        .into_iter()
+        .filter(|row| own.map_or(true, |p| row.principal.as_deref() == Some(p.id.as_str())))
+        .filter(|row| query.collection.is_none() || row.collection == query.collection)
+        .collect();
+
+    let total = usage::aggregate(rows.clone(), &[]).pop().unwrap_or_default();
+    let budgets = if own.is_some() { Vec::new() } else { state.usage().budget_status()? };
+
+    Ok(Json(UsageResponse {
+        enabled: state.usage().is_enabled(),
+        from: from.to_string(),
+        to: to.to_string(),
+        rows: usage::aggregate(rows, &dimensions),
+        total,
+        budgets,
+    }))
+}
@@ -0,0 +1,167 @@
+//! Vertex AI Vector Search index lifecycle (GCP backend)
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::config::GcpConfig;
+use crate::error::{Error, ProblemDetails, Result};
+use crate::providers::gcp::{
+    CreateIndexEndpointRequest, CreateVectorIndexRequest, DeployVectorIndexRequest, ResizeVectorIndexRequest,
+    VectorIndexStatus, VertexIndexManager, VertexOperation,
+};
+use crate::server::state::AppState;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct OperationQuery {
+    /// Operation resource name returned by a lifecycle call
+    pub name: String,
+}
+
+/// Index manager and `[gcp]` settings
+fn manager(state: &AppState) -> Result<(&VertexIndexManager, GcpConfig)> {
+    let manager = state
+        .vector_index()
+        .ok_or_else(|| Error::Config("Vector index management needs the gcp backend".to_string()))?;
+    let gcp = state
+        .config()
+        .gcp
+        .clone()
+        .ok_or_else(|| Error::Config("The gcp backend needs a [gcp] section".to_string()))?;
+    Ok((manager, gcp))
+}
+
+/// GET /api/admin/vector-index - State of the configured index and deployment
+#[utoipa::path(
+    get,
+    path = "/api/admin/vector-index",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Index and deployment state", body = VectorIndexStatus),
+        (status = 400, description = "Not using the gcp backend", body = ProblemDetails)
+    )
+)]
+pub async fn get_vector_index(
+    State(state): State<AppState>,
+) -> Result<Json<VectorIndexStatus>> {
+    let (manager, gcp) = manager(&state)?;
+    let status = manager
+        .status(&gcp.vector_search_index, &gcp.vector_search_endpoint, &gcp.deployed_index_id)
+        .await?;
+    Ok(Json(status))
+}
+
+/// POST /api/admin/vector-index - Create a stream-updated index
+///
+/// Returns the long-running operation; once done its response names the
+/// index to set as `gcp.vector_search_index`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/vector-index",
+    tag = "admin",
+    request_body = CreateVectorIndexRequest,
+    responses(
+        (status = 200, description = "Index creation started", body = VertexOperation),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn create_vector_index(
+    State(state): State<AppState>,
+    Json(request): Json<CreateVectorIndexRequest>,
+) -> Result<Json<VertexOperation>> {
+    let (manager, _) = manager(&state)?;
+    let dimensions = state.config().embeddings.dimensions;
+    Ok(Json(manager.create_index(&request, dimensions).await?))
+}
+
+/// POST /api/admin/vector-index/endpoint - Create an index endpoint
+#[utoipa::path(
+    post,
+    path = "/api/admin/vector-index/endpoint",
+    tag = "admin",
+    request_body = CreateIndexEndpointRequest,
+    responses(
+        (status = 200, description = "Endpoint creation started", body = VertexOperation),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn create_index_endpoint(
+    State(state): State<AppState>,
+    Json(request): Json<CreateIndexEndpointRequest>,
+) -> Result<Json<VertexOperation>> {
+    let (manager, _) = manager(&state)?;
+    Ok(Json(manager.create_endpoint(&request).await?))
+}
+
+/// POST /api/admin/vector-index/deploy - Deploy the index on the endpoint
+///
+/// Index, endpoint and deployed index id default to the `[gcp]` settings.
+#[utoipa::path(
+    post,
+    path = "/api/admin/vector-index/deploy",
+    tag = "admin",
+    request_body = DeployVectorIndexRequest,
+    responses(
+        (status = 200, description = "Deployment started", body = VertexOperation),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn deploy_vector_index(
+    State(state): State<AppState>,
+    Json(request): Json<DeployVectorIndexRequest>,
+) -> Result<Json<VertexOperation>> {
+    let (manager, gcp) = manager(&state)?;
+    let index = request.index.as_deref().unwrap_or(&gcp.vector_search_index);
+    let endpoint = request.endpoint.as_deref().unwrap_or(&gcp.vector_search_endpoint);
+    let deployed_index_id = request.deployed_index_id.as_deref().unwrap_or(&gcp.deployed_index_id);
+    Ok(Json(manager.deploy_index(endpoint, index, deployed_index_id, &request).await?))
+}
+
+/// POST /api/admin/vector-index/resize - Change the replicas serving the index
+#[utoipa::path(
+    post,
+    path = "/api/admin/vector-index/resize",
+    tag = "admin",
+    request_body = ResizeVectorIndexRequest,
+    responses(
+        (status = 200, description = "Resize started", body = VertexOperation),
+        (status = 404, description = "Index not deployed", body = ProblemDetails)
+    )
+)]
+pub async fn resize_vector_index(
+    State(state): State<AppState>,
+    Json(request): Json<ResizeVectorIndexRequest>,
+) -> Result<Json<VertexOperation>> {
+    let (manager, gcp) = manager(&state)?;
+    if request.max_replicas.is_some_and(|max| max < request.min_replicas) {
+        return Err(Error::Config("max_replicas is below min_replicas".to_string()));
+    }
+    let operation = manager
+        .resize(&gcp.vector_search_endpoint, &gcp.deployed_index_id, &request)
+        .await?;
+    Ok(Json(operation))
+}
+
+/// GET /api/admin/vector-index/operation - Poll a lifecycle operation
+#[utoipa::path(
+    get,
+    path = "/api/admin/vector-index/operation",
+    tag = "admin",
+    params(OperationQuery),
+    responses(
+        (status = 200, description = "Operation state", body = VertexOperation)
+    )
+)]
+pub async fn get_vector_index_operation(
+    State(state): State<AppState>,
+    Query(query): Query<OperationQuery>,
+) -> Result<Json<VertexOperation>> {
+    let (manager, _) = manager(&state)?;
+    if !query.name.starts_with("projects/") || query.name.contains("..") {
+        return Err(Error::Config(format!("{} is not an operation name", query.name)));
+    }
+    Ok(Json(manager.operation(&query.name).await?))
+}
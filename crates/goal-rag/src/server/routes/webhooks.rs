@@ -0,0 +1,95 @@
+//! Webhook registration
+//!
+//! Webhooks are managed by admins: a registered endpoint receives events
+//! about the whole corpus its owner can read.
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::WebhookEndpoint;
+use crate::error::{Error, ProblemDetails, Result};
+use crate::server::state::AppState;
+use crate::server::webhooks::RegisteredWebhook;
+use crate::types::Principal;
+
+/// A newly registered webhook
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegisterWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: RegisteredWebhook,
+    /// Signing secret; only returned here, store it to verify deliveries
+    pub secret: String,
+}
+
+/// GET /api/webhooks - List registered webhooks
+///
+/// Endpoints from the config file are not listed; secrets are never returned.
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Registered webhooks", body = Vec<RegisteredWebhook>),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn list_webhooks(State(state): State<AppState>) -> Json<Vec<RegisteredWebhook>> {
+    Json(state.webhooks().registered())
+}
+
+/// POST /api/webhooks - Register a webhook
+///
+/// A signing secret is generated when none is given. The URL may not resolve
+/// to a private, loopback or link-local address unless its host is in
+/// `webhooks.allowed_hosts`. Document events are only sent for documents the
+/// caller can read.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    tag = "admin",
+    request_body = WebhookEndpoint,
+    responses(
+        (status = 200, description = "Webhook registered", body = RegisterWebhookResponse),
+        (status = 400, description = "Invalid or non-public URL", body = ProblemDetails),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails)
+    )
+)]
+pub async fn register_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(mut endpoint): Json<WebhookEndpoint>,
+) -> Result<Json<RegisterWebhookResponse>> {
+    let secret = endpoint
+        .secret
+        .get_or_insert_with(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()))
+        .clone();
+    let webhook = state.webhooks().register(endpoint, principal.map(|Extension(p)| p)).await?;
+
+    Ok(Json(RegisterWebhookResponse { webhook, secret }))
+}
+
+/// DELETE /api/webhooks/:id - Remove a registered webhook
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "Webhook removed", body = serde_json::Value),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails),
+        (status = 404, description = "Webhook not found", body = ProblemDetails)
+    )
+)]
+pub async fn delete_webhook(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Json<serde_json::Value>> {
+    if !state.webhooks().unregister(&id)? {
+        return Err(Error::NotFound(format!("Webhook {}", id)));
+    }
+    tracing::info!("Removed webhook {}", id);
+
+    Ok(Json(serde_json::json!({ "success": true, "id": id })))
+}
@@ -0,0 +1,159 @@
+//! Cron schedules for saved queries
+//!
+//! Standard five-field expressions (`minute hour day-of-month month
+//! day-of-week`, in UTC) with `*`, lists, ranges and steps, plus the
+//! `@hourly`, `@daily`, `@weekly` and `@monthly` shorthands. Day of week runs
+//! from 0 (Sunday) to 7 (Sunday again). As in cron, when both day fields are
+//! restricted a day matching either one is due.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+
+use crate::error::{Error, Result};
+
+/// How far ahead the next run is searched for (`0 0 29 2 *` runs every four years)
+const SEARCH_YEARS: i32 = 5;
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day of month or day of week left as `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(Error::Config(format!(
+                "Invalid schedule '{}': expected 5 fields (minute hour day month weekday)",
+                expression
+            )));
+        };
+
+        let field = |text: &str, min: u32, max: u32| {
+            parse_field(text, min, max)
+                .ok_or_else(|| Error::Config(format!("Invalid schedule '{}': bad field '{}'", expression, text)))
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// First run strictly after `after` (to the minute)
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(366 * SEARCH_YEARS as i64);
+        let mut time = start;
+        while time < limit {
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(time) {
+                time = Utc.with_ymd_and_hms(time.year(), time.month(), time.day(), 0, 0, 0).single()? + Duration::days(1);
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse a comma-separated field into a bit set of the values it allows
+fn parse_field(text: &str, min: u32, max: u32) -> Option<u64> {
+    let mut set = 0u64;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&s| s > 0)?),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+                // `5/15` runs from 5 to the end of the range
+                None if item.contains('/') => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Some(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_run() {
+        // Mondays at 08:30; 2024-01-03 is a Wednesday
+        let weekly = CronSchedule::parse("30 8 * * 1").unwrap();
+        assert_eq!(weekly.next_after(at("2024-01-03T12:00:00Z")), Some(at("2024-01-08T08:30:00Z")));
+        assert_eq!(weekly.next_after(at("2024-01-08T08:30:00Z")), Some(at("2024-01-15T08:30:00Z")));
+
+        let every_quarter_hour = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(every_quarter_hour.next_after(at("2024-01-05T17:50:00Z")), Some(at("2024-01-08T09:00:00Z")));
+
+        // Either day field matches when both are set
+        let either = CronSchedule::parse("0 0 1 * 0").unwrap();
+        assert_eq!(either.next_after(at("2024-01-02T00:00:00Z")), Some(at("2024-01-07T00:00:00Z")));
+
+        assert_eq!(CronSchedule::parse("@monthly").unwrap(), CronSchedule::parse("0 0 1 * *").unwrap());
+        assert!(CronSchedule::parse("0 0 30 2 *").unwrap().next_after(at("2024-01-01T00:00:00Z")).is_none());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+    }
+}
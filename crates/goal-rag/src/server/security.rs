@@ -0,0 +1,112 @@
+//! CORS policy and security response headers
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+use crate::config::{CorsConfig, SecurityHeadersConfig};
+use crate::error::{Error, Result};
+
+/// CORS layer for `[server.cors]`
+///
+/// Unlisted methods and headers allow any; with credentials the request's
+/// own are mirrored instead, since browsers reject `*` on credentialed requests.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    let invalid = |what: &str, value: &str| Error::Config(format!("Invalid CORS {} {:?}", what, value));
+
+    let origins = if config.allowed_origins.iter().any(|origin| origin.trim() == "*") {
+        if config.allow_credentials {
+            return Err(Error::Config("CORS credentials can't be allowed for any origin".to_string()));
+        }
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin.trim()).map_err(|_| invalid("origin", origin)))
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = match config.allowed_methods.as_slice() {
+        [] if config.allow_credentials => AllowMethods::mirror_request(),
+        [] => AllowMethods::any(),
+        methods => AllowMethods::list(
+            methods
+                .iter()
+                .map(|method| Method::from_bytes(method.trim().as_bytes()).map_err(|_| invalid("method", method)))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+    };
+
+    let headers = match config.allowed_headers.as_slice() {
+        [] if config.allow_credentials => AllowHeaders::mirror_request(),
+        [] => AllowHeaders::any(),
+        headers => AllowHeaders::list(
+            headers
+                .iter()
+                .map(|name| HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid("header", name)))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+    };
+
+    let layer = CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.allow_credentials)
+        .expose_headers([header::ETAG]);
+    Ok(match config.max_age_secs {
+        Some(secs) => layer.max_age(Duration::from_secs(secs)),
+        None => layer,
+    })
+}
+
+/// Security headers added to responses
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    enabled: bool,
+    /// `Strict-Transport-Security` value, if sent at all
+    hsts: Option<HeaderValue>,
+    /// The server terminates TLS itself, so every request is HTTPS
+    tls: bool,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig, tls: bool) -> Self {
+        let hsts = (config.hsts_max_age_secs > 0)
+            .then(|| format!("max-age={}; includeSubDomains", config.hsts_max_age_secs))
+            .and_then(|value| HeaderValue::from_str(&value).ok());
+        Self { enabled: config.enabled, hsts, tls }
+    }
+}
+
+/// Add the security headers a handler didn't set itself
+pub async fn add_security_headers(State(config): State<SecurityHeaders>, request: Request, next: Next) -> Response {
+    let https = config.tls
+        || request
+            .headers()
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|proto| proto.eq_ignore_ascii_case("https"));
+
+    let mut response = next.run(request).await;
+    if !config.enabled {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.entry(header::X_CONTENT_TYPE_OPTIONS).or_insert(HeaderValue::from_static("nosniff"));
+    headers.entry(header::X_FRAME_OPTIONS).or_insert(HeaderValue::from_static("DENY"));
+    headers.entry(header::REFERRER_POLICY).or_insert(HeaderValue::from_static("no-referrer"));
+    if let Some(hsts) = config.hsts.filter(|_| https) {
+        headers.entry(header::STRICT_TRANSPORT_SECURITY).or_insert(hsts);
+    }
+    response
+}
@@ -4,24 +4,49 @@ use dashmap::DashMap;
 use parking_lot::RwLock;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::config::{BackendProvider, RagConfig};
+use crate::config::{
+    BackendProvider, DimensionCheck, EmbeddingProviderKind, LlmConfig, QueueBackendKind, RagConfig, VisionProviderKind, OLLAMA_MODEL_PREFIX,
+};
+use crate::config_check::{self, ReloadPlan};
+use crate::connectors::Connectors;
 use crate::error::{Error, Result};
-use crate::generation::OllamaClient;
+use crate::generation::guard::GuardEvent;
+use crate::generation::{provenance, Guard, OllamaClient, PromptTemplates, ProvenanceSigner};
+use crate::ingestion::transcription::Transcriber;
+use crate::learning::{GraphBuilder, QaGenerator};
+use crate::processing::keywords::KeywordExtractor;
+use crate::processing::redaction::Redactor;
 use crate::ingestion::ExternalParser;
 use crate::learning::{AnswerCache, KnowledgeStore};
-use crate::processing::{JobQueue, ProcessingWorker};
+use crate::processing::{
+    EmbeddingRepair, FairScheduler, GcsSync, InProcessQueue, JobQueue, ProcessingWorker, QueueBackend, ReindexManager, TaskRegistry,
+};
 use crate::providers::{
-    EmbeddingProvider, LlmProvider, VectorStoreProvider,
-    local::LocalVectorStore,
-    ollama::{OllamaEmbedder, OllamaLlm},
+    CollectionEmbedders, DocumentStoreProvider, EmbeddingProvider, LlmProvider, LongInputEmbedder, PaddedEmbedder,
+    VectorStoreProvider, VisionProvider,
+    dimensions::{check_stored_dimensions, probe_dimensions, resolve_dimensions},
+    local::{LocalDocumentStore, LocalVectorStore},
+    ollama::{OllamaEmbedder, OllamaLlm, OllamaVision},
+    onnx::OnnxEmbedder,
+    fallback::FallbackLlm,
+    scheduler::{self, SchedulerStats},
+    usage::UsageTracker,
+    rate_limit::RateLimits,
 };
 #[cfg(feature = "gcp")]
-use crate::providers::gcp::{DocumentAiClient, GcsDocumentStore};
-use crate::retrieval::VectorStore;
-use crate::storage::{FileRegistryDb, FileRegistryDbStats, SyncStatus};
+use crate::providers::gcp::{DocumentAiClient, GcsDocumentStore, GcsFileInfo, VertexIndexManager};
+use crate::retrieval::{trace, AnalyzerRegistry, RetrievalTrace, VectorStore};
+use crate::server::audit::AuditLog;
+use crate::server::readiness::Readiness;
+use crate::server::reports::SavedQueries;
+use crate::server::webhooks::{WebhookEvent, WebhookEventKind, Webhooks};
+use crate::storage::{
+    ChunkStore, EncryptionKey, FileRegistryDb, FileRegistryDbStats, SnapshotManager, SpoolCipher, SpoolStore, SyncStatus,
+};
 use crate::types::{Chunk, Document, FileRecord, FileRecordStatus, SkipReason};
 
 /// Shared application state
@@ -31,32 +56,91 @@ pub struct AppState {
 }
 
 struct AppStateInner {
-    /// Configuration
-    config: RagConfig,
-    /// Vector store for chunks (provider abstraction)
-    vector_store_provider: Arc<dyn VectorStoreProvider>,
+    /// Configuration (swapped when live settings are reloaded)
+    config: RwLock<Arc<RagConfig>>,
+    /// File the configuration was loaded from (None when using the defaults)
+    config_path: RwLock<Option<PathBuf>>,
+    /// Per-provider rate limits
+    rate_limits: Arc<RateLimits>,
+    /// Vector store for chunks (provider abstraction, swapped after reindexing)
+    vector_store_provider: RwLock<Arc<dyn VectorStoreProvider>>,
     /// Legacy local vector store (only for Local backend, None for GCP)
-    vector_store: Option<Arc<VectorStore>>,
-    /// Embedding provider (Ollama or Vertex AI)
-    embedding_provider: Arc<dyn EmbeddingProvider>,
+    vector_store: RwLock<Option<Arc<VectorStore>>>,
+    /// Embedding provider (Ollama or Vertex AI, swapped after reindexing)
+    embedding_provider: RwLock<Arc<dyn EmbeddingProvider>>,
+    /// Embedding models of collections that don't use the default model
+    embedders: CollectionEmbedders,
+    /// Embedding batch scheduler counters (all embedders)
+    embedding_scheduler: Arc<SchedulerStats>,
+    /// Chunks embedded with another model than their collection's, by collection
+    stale_embeddings: RwLock<std::collections::HashMap<Option<String>, usize>>,
     /// LLM provider (Ollama or Gemini)
     llm_provider: Arc<dyn LlmProvider>,
+    /// Additional generation models (experiment variants, budget downgrades, selectable and fallback models), by name
+    variant_llms: std::collections::HashMap<String, Arc<dyn LlmProvider>>,
+    /// Token usage and budgets
+    usage: Arc<UsageTracker>,
+    /// Vision provider for figure captions (None when captioning is disabled)
+    vision_provider: Option<Arc<dyn VisionProvider>>,
+    /// Store for originals, plain text and page thumbnails (None with the
+    /// local backend unless originals or thumbnails are kept)
+    file_store: Option<Arc<dyn DocumentStoreProvider>>,
     /// Ollama client (legacy, for backwards compatibility)
     ollama: Arc<OllamaClient>,
     /// External parser for legacy formats
     external_parser: Arc<ExternalParser>,
+    /// Whisper transcriber for audio/video
+    transcriber: Arc<Transcriber>,
+    /// PII detection and masking
+    redactor: Arc<Redactor>,
+    /// Keyword and entity extraction
+    keyword_extractor: Arc<KeywordExtractor>,
+    /// Prompt-injection guard
+    guard: Arc<Guard>,
+    /// Webhook notifications
+    webhooks: Arc<Webhooks>,
+    /// Saved queries and their scheduled reports
+    saved_queries: Arc<SavedQueries>,
+    /// Answer audit log
+    audit: Arc<AuditLog>,
+    /// Cached dependency checks for `/ready`
+    readiness: Arc<Readiness>,
+    /// Operator-defined prompt templates
+    prompts: Arc<PromptTemplates>,
     /// Job queue for async processing
     job_queue: Arc<JobQueue>,
     /// Knowledge store for learning
     knowledge_store: KnowledgeStore,
     /// Answer cache with document-based invalidation
     answer_cache: AnswerCache,
+    /// Background reindex tracking
+    reindex: Arc<ReindexManager>,
+    /// Re-embeds chunks whose embedding failed
+    embedding_repair: Arc<EmbeddingRepair>,
+    gcs_sync: Arc<GcsSync>,
+    /// Google Drive and SharePoint connector passes
+    connectors: Arc<Connectors>,
+    /// Registry snapshots
+    snapshots: Arc<SnapshotManager>,
+    /// Knowledge graph builder
+    graph: Arc<GraphBuilder>,
+    /// Synthetic question-answer pair generator
+    qa_generator: Arc<QaGenerator>,
+    /// Long-running maintenance task progress
+    tasks: Arc<TaskRegistry>,
+    /// Answer provenance signer (None when signing is disabled)
+    provenance_signer: Option<Arc<ProvenanceSigner>>,
     /// Document registry (in-memory cache, backed by database)
     documents: DashMap<Uuid, Document>,
     /// Chunk metadata store (for Vertex AI lookups)
-    chunks: DashMap<Uuid, Chunk>,
+    chunks: ChunkStore,
     /// File registry (in-memory cache for fast lookups)
     file_registry: DashMap<String, FileRecord>,
+    /// Bumped on every document or file registry change (listing ETags)
+    registry_version: AtomicU64,
+    /// Access lists of restricted documents and the registry version they
+    /// were collected at
+    document_acls: RwLock<Option<(u64, Arc<std::collections::HashMap<Uuid, Vec<String>>>)>>,
     /// SQLite database for persistent storage
     database: Arc<FileRegistryDb>,
     /// Path for documents JSON (legacy, for backwards compatibility)
@@ -66,6 +150,9 @@ struct AppStateInner {
     /// GCS document store (only for GCP backend)
     #[cfg(feature = "gcp")]
     document_store: Option<Arc<GcsDocumentStore>>,
+    /// Vector Search index management (only for GCP backend)
+    #[cfg(feature = "gcp")]
+    vector_index: Option<Arc<VertexIndexManager>>,
     /// Document AI client for advanced PDF extraction (only for GCP backend)
     #[cfg(feature = "gcp")]
     document_ai: Option<Arc<DocumentAiClient>>,
@@ -73,7 +160,7 @@ struct AppStateInner {
 
 impl AppState {
     /// Create new application state
-    pub async fn new(config: RagConfig) -> Result<Self> {
+    pub async fn new(mut config: RagConfig) -> Result<Self> {
         tracing::info!("Initializing RAG application state (backend: {:?})...", config.backend);
 
         // Local vector store - only created for Local backend
@@ -89,19 +176,53 @@ impl AppState {
         #[cfg(feature = "gcp")]
         let mut gcs_document_store: Option<Arc<GcsDocumentStore>> = None;
         #[cfg(feature = "gcp")]
+        let mut vector_index: Option<Arc<VertexIndexManager>> = None;
+        #[cfg(feature = "gcp")]
         let mut document_ai_client: Option<Arc<DocumentAiClient>> = None;
 
+        // Gemini vision needs GCP auth, so it is created with the GCP providers
+        #[allow(unused_mut)]
+        let mut vision_provider: Option<Arc<dyn VisionProvider>> = None;
+
+        // Generation models of experiment variants, budget downgrades, requests and fallbacks,
+        // on the same backend as the default LLM unless named "ollama:<model>"
+        let extra_models: Vec<&str> = config
+            .experiments
+            .models()
+            .into_iter()
+            .chain(config.usage.downgrade_models())
+            .chain(config.models.models())
+            .collect();
+        let mut variant_llms: std::collections::HashMap<String, Arc<dyn LlmProvider>> =
+            std::collections::HashMap::new();
+
+        // Embedders of collections with a model of their own: (collection, model, dimensions, provider)
+        let mut collection_providers: Vec<(String, String, usize, Arc<dyn EmbeddingProvider>)> = Vec::new();
+
         // Initialize SQLite database early (needed for both backends)
-        let storage_dir = config.vector_db.storage_path
-            .parent()
-            .map(|p| p.to_path_buf())
-            .unwrap_or_else(|| PathBuf::from("."));
-        let db_path = storage_dir.join("rag_registry.db");
-        let database = Arc::new(FileRegistryDb::new(&db_path)?);
+        let storage_dir = config.vector_db.storage_dir();
+        let db_path = config.vector_db.registry_path();
+        let analyzers = AnalyzerRegistry::from_config(&config.analyzers)?;
+        let encryption_key = EncryptionKey::load(&config.encryption, config.gcp.as_ref()).await?;
+        if encryption_key.is_some() {
+            tracing::info!(
+                "Encryption at rest enabled (database: {}, spool: {})",
+                config.encryption.database, config.encryption.spool
+            );
+        }
+        let database_key = encryption_key.as_ref().filter(|_| config.encryption.database);
+        let database = Arc::new(FileRegistryDb::open(&db_path, database_key)?.with_analyzers(analyzers));
         tracing::info!("Database initialized at {:?}", db_path);
+        // Chunk text in the local index and the local document store
+        let content_cipher = encryption_key.clone().map(SpoolCipher::new);
+
+        let usage = Arc::new(UsageTracker::new(&config.usage, &config.context, Arc::clone(&database)));
+        if usage.is_enabled() {
+            tracing::info!("Token usage tracking enabled ({} budgets)", config.usage.budgets.len());
+        }
 
         // Initialize providers based on backend
-        let (embedding_provider, llm_provider, vector_store_provider): (
+        let (embedding_provider, llm_provider, mut vector_store_provider): (
             Arc<dyn EmbeddingProvider>,
             Arc<dyn LlmProvider>,
             Arc<dyn VectorStoreProvider>,
@@ -110,14 +231,34 @@ impl AppState {
                 tracing::info!("Using local backend (Ollama + HNSW)");
 
                 // Create local vector store
-                let vector_store = Arc::new(VectorStore::new(&config)?);
+                let vector_store = Arc::new(VectorStore::new(&config)?.with_cipher(content_cipher.clone()));
                 tracing::info!("Local vector store initialized");
 
                 let embedder = Arc::new(OllamaEmbedder::new(
                     &config.llm,
                     config.embeddings.dimensions,
                 ));
+                for (collection, embedding) in &config.embeddings.collections {
+                    let dimensions = embedding.dimensions.unwrap_or(config.embeddings.dimensions);
+                    let llm_config = LlmConfig {
+                        embed_model: embedding.model.clone(),
+                        ..config.llm.clone()
+                    };
+                    collection_providers.push((
+                        collection.clone(),
+                        embedding.model.clone(),
+                        dimensions,
+                        Arc::new(OllamaEmbedder::new(&llm_config, dimensions)),
+                    ));
+                }
                 let llm = Arc::new(OllamaLlm::new(&config.llm));
+                for &model in &extra_models {
+                    let llm_config = LlmConfig {
+                        generate_model: model.strip_prefix(OLLAMA_MODEL_PREFIX).unwrap_or(model).to_string(),
+                        ..config.llm.clone()
+                    };
+                    variant_llms.insert(model.to_string(), Arc::new(OllamaLlm::new(&llm_config)));
+                }
                 // Pass database for SQLite FTS-based string search
                 let vector_provider = Arc::new(LocalVectorStore::new(
                     Arc::clone(&vector_store),
@@ -132,7 +273,9 @@ impl AppState {
             BackendProvider::Gcp => {
                 #[cfg(feature = "gcp")]
                 {
-                    use crate::providers::gcp::{GcpAuth, GeminiClient, VertexAiEmbedder, VertexVectorSearch};
+                    use crate::providers::gcp::{
+                        GcpAuth, GeminiClient, GeminiContextCache, VertexAiEmbedder, VertexVectorSearch,
+                    };
 
                     let gcp_config = config.gcp.as_ref().ok_or_else(|| {
                         Error::Config("GCP backend selected but gcp config is missing".to_string())
@@ -151,21 +294,69 @@ impl AppState {
                         Some(gcp_config.embedding_model.clone()),
                     ));
 
-                    let llm = Arc::new(GeminiClient::new(
-                        Arc::clone(&auth),
-                        gcp_config.location.clone(),
-                        Some(gcp_config.generation_model.clone()),
-                    ));
+                    for (collection, embedding) in &config.embeddings.collections {
+                        collection_providers.push((
+                            collection.clone(),
+                            embedding.model.clone(),
+                            embedding.dimensions.unwrap_or(config.embeddings.dimensions),
+                            Arc::new(VertexAiEmbedder::new(
+                                Arc::clone(&auth),
+                                gcp_config.location.clone(),
+                                Some(embedding.model.clone()),
+                            )),
+                        ));
+                    }
+
+                    // One cache for every Gemini model (caches are per model)
+                    let context_cache = Arc::new(
+                        GeminiContextCache::new(Arc::clone(&auth), gcp_config.location.clone(), &gcp_config.context_cache)
+                            .with_usage(Arc::clone(&usage)),
+                    );
+                    let gemini = |model: String| {
+                        GeminiClient::new(Arc::clone(&auth), gcp_config.location.clone(), Some(model))
+                            .with_json_mode(gcp_config.gemini_json_mode)
+                            .with_context_cache(Arc::clone(&context_cache))
+                    };
+
+                    let llm = Arc::new(gemini(gcp_config.generation_model.clone()));
+                    for &model in &extra_models {
+                        let llm: Arc<dyn LlmProvider> = match model.strip_prefix(OLLAMA_MODEL_PREFIX) {
+                            Some(name) => Arc::new(OllamaLlm::new(&LlmConfig {
+                                generate_model: name.to_string(),
+                                ..config.llm.clone()
+                            })),
+                            None => Arc::new(gemini(model.to_string())),
+                        };
+                        variant_llms.insert(model.to_string(), llm);
+                    }
 
-                    let vector_provider = Arc::new(VertexVectorSearch::new(
+                    if config.vision.enabled && config.vision.provider == VisionProviderKind::Gemini {
+                        vision_provider = Some(Arc::new(GeminiClient::new(
+                            Arc::clone(&auth),
+                            gcp_config.location.clone(),
+                            Some(config.vision.model.clone()),
+                        )));
+                    }
+
+                    let vector_provider = Arc::new(
+                        VertexVectorSearch::new(
+                            Arc::clone(&auth),
+                            gcp_config.location.clone(),
+                            gcp_config.vector_search_index.clone(),
+                            gcp_config.vector_search_endpoint.clone(),
+                            gcp_config.vector_search_public_domain.clone(),
+                            gcp_config.deployed_index_id.clone(),
+                            Arc::clone(&database),  // Pass database for FTS and chunk tracking
+                        )
+                        .with_upsert_concurrency(gcp_config.vector_search_upsert_concurrency)
+                        .with_consistency_window(std::time::Duration::from_secs(
+                            gcp_config.vector_search_consistency_secs,
+                        )),
+                    );
+                    vector_index = Some(Arc::new(VertexIndexManager::new(
                         Arc::clone(&auth),
                         gcp_config.location.clone(),
-                        gcp_config.vector_search_index.clone(),
-                        gcp_config.vector_search_endpoint.clone(),
-                        gcp_config.vector_search_public_domain.clone(),
-                        gcp_config.deployed_index_id.clone(),
-                        Arc::clone(&database),  // Pass database for FTS and chunk tracking
-                    ));
+                    )));
 
                     tracing::info!("GCP backend: using Vertex AI Vector Search (no local HNSW)");
 
@@ -184,7 +375,7 @@ impl AppState {
                             let doc_ai = DocumentAiClient::new(
                                 Arc::clone(&auth),
                                 processor_name.clone(),
-                            );
+                            ).with_batch(gcp_config.gcs_bucket.clone(), &gcp_config.document_ai_batch);
                             document_ai_client = Some(Arc::new(doc_ai));
                             tracing::info!(
                                 "Document AI initialized (processor: {})",
@@ -213,10 +404,145 @@ impl AppState {
             }
         };
 
+        if config.vision.enabled {
+            if config.vision.provider == VisionProviderKind::Ollama {
+                vision_provider = Some(Arc::new(OllamaVision::new(&config.llm, &config.vision)));
+            }
+            match vision_provider {
+                Some(ref vision) => tracing::info!(
+                    "Figure captioning enabled ({} {})",
+                    vision.name(),
+                    vision.model()
+                ),
+                None => tracing::warn!(
+                    "Figure captioning with Gemini requires the GCP backend - captioning disabled"
+                ),
+            }
+        }
+
+        // Hold every call to its provider's rate limit, then count the tokens of
+        // every generation call
+        let rate_limits = Arc::new(RateLimits::new(&config.rate_limits, &config.context));
+        for provider in rate_limits.providers() {
+            tracing::info!("Rate limiting {} calls", provider);
+        }
+        let llm_provider = usage.meter_llm(rate_limits.limit_llm(llm_provider));
+        let variant_llms: std::collections::HashMap<String, Arc<dyn LlmProvider>> = variant_llms
+            .into_iter()
+            .map(|(model, llm)| (model, usage.meter_llm(rate_limits.limit_llm(llm))))
+            .collect();
+        let vision_provider = vision_provider.map(|vision| rate_limits.limit_vision(vision));
+
+        // In-process ONNX model instead of the backend's embedding service
+        let embedding_provider: Arc<dyn EmbeddingProvider> = match config.embeddings.provider {
+            EmbeddingProviderKind::Onnx => Arc::new(OnnxEmbedder::new(&config.embeddings).await?),
+            EmbeddingProviderKind::Backend => rate_limits.limit_embedder(embedding_provider),
+        };
+
+        let default_embedding_model = match (config.embeddings.provider, &config.backend) {
+            (EmbeddingProviderKind::Onnx, _) => config.embeddings.model.clone(),
+            (EmbeddingProviderKind::Backend, BackendProvider::Local) => config.llm.embed_model.clone(),
+            (EmbeddingProviderKind::Backend, BackendProvider::Gcp) => {
+                config.gcp.as_ref().map(|gcp| gcp.embedding_model.clone()).unwrap_or_default()
+            }
+        };
+
+        // Check the index dimensions against the model's vectors and those already stored
+        let dimension_check = config.embeddings.dimension_check;
+        let index_dimensions = if dimension_check == DimensionCheck::Off {
+            config.embeddings.dimensions
+        } else {
+            resolve_dimensions(
+                &default_embedding_model,
+                "embeddings.dimensions",
+                config.embeddings.dimensions,
+                probe_dimensions(embedding_provider.as_ref()).await,
+                dimension_check,
+            )?
+        };
+        let has_vectors = database.embedding_models_by_collection()?.iter().any(|(_, _, count)| *count > 0);
+        let stored_dimensions = if has_vectors { database.embedding_index_dimensions()? } else { None };
+        check_stored_dimensions(index_dimensions, stored_dimensions)?;
+        database.set_embedding_index_dimensions(index_dimensions)?;
+        if index_dimensions != config.embeddings.dimensions {
+            config.embeddings.dimensions = index_dimensions;
+            // The local index was opened with the configured dimensions
+            if local_vector_store.take().is_some() {
+                drop(vector_store_provider);
+                let vector_store = Arc::new(VectorStore::new(&config)?.with_cipher(content_cipher.clone()));
+                vector_store_provider = Arc::new(LocalVectorStore::new(Arc::clone(&vector_store), Arc::clone(&database)));
+                local_vector_store = Some(vector_store);
+            }
+        }
+        tracing::info!("Embedding index holds {} dimensional vectors", index_dimensions);
+
+        // Coalesce concurrent requests, then split over-length chunks instead of
+        // letting the provider truncate them
+        let embedding_scheduler = Arc::new(SchedulerStats::default());
+        let batching = &config.embeddings.batching;
+        if batching.enabled {
+            tracing::info!(
+                "Embedding batching enabled (max {} texts, {} ms, {} batches in flight)",
+                batching.max_batch_size,
+                batching.max_latency_ms,
+                batching.max_concurrent_batches
+            );
+        }
+        let embedding_provider: Arc<dyn EmbeddingProvider> = Arc::new(LongInputEmbedder::new(
+            scheduler::schedule(embedding_provider, batching, &embedding_scheduler),
+            config.embeddings.long_input.clone(),
+        ));
+
+        let mut embedders = CollectionEmbedders::new(default_embedding_model);
+        for (collection, model, dimensions, provider) in collection_providers {
+            let dimensions = if dimension_check == DimensionCheck::Off {
+                dimensions
+            } else {
+                resolve_dimensions(
+                    &model,
+                    &format!("embeddings.collections.{}.dimensions", collection),
+                    dimensions,
+                    probe_dimensions(provider.as_ref()).await,
+                    dimension_check,
+                )?
+            };
+            if dimensions > config.embeddings.dimensions {
+                return Err(Error::Config(format!(
+                    "Embedding model {} of collection '{}' produces {} dimensions, the index holds {}",
+                    model, collection, dimensions, config.embeddings.dimensions
+                )));
+            }
+            let mut provider: Arc<dyn EmbeddingProvider> = Arc::new(LongInputEmbedder::new(
+                scheduler::schedule(rate_limits.limit_embedder(provider), batching, &embedding_scheduler),
+                config.embeddings.long_input.clone(),
+            ));
+            if dimensions < config.embeddings.dimensions {
+                provider = Arc::new(PaddedEmbedder::new(provider, config.embeddings.dimensions));
+            }
+            tracing::info!("Collection '{}' embedded with {} ({} dimensions)", collection, model, dimensions);
+            embedders.insert(collection, model, provider);
+        }
+        let stale_embeddings = Self::count_stale_embeddings(&database, &embedders)?;
+        for (collection, count) in &stale_embeddings {
+            tracing::warn!(
+                "{} chunks of collection {:?} were embedded with another model - reindex before querying it",
+                count,
+                collection
+            );
+        }
+
         // Initialize external parser for legacy formats
         let external_parser = Arc::new(ExternalParser::new(config.external_parser.clone()));
         tracing::info!("External parser initialized (enabled: {})", config.external_parser.enabled);
 
+        let transcriber = Arc::new(Transcriber::new(config.transcription.clone()));
+        let redactor = Arc::new(Redactor::new(config.redaction.clone()));
+        let keyword_extractor = Arc::new(KeywordExtractor::new(config.keywords.clone(), llm_provider.clone()));
+        let guard = Arc::new(Guard::new(&config.guard));
+        if config.transcription.enabled {
+            tracing::info!("Audio/video transcription enabled ({:?} backend)", config.transcription.backend);
+        }
+
         // Initialize knowledge store for learning
         let knowledge_path = storage_dir.join("knowledge.json");
         let knowledge_store = KnowledgeStore::new(knowledge_path);
@@ -224,8 +550,26 @@ impl AppState {
 
         // Initialize answer cache (1000 entries, 1 hour TTL)
         let answer_cache = AnswerCache::new(1000, 3600);
+
+        let webhooks = Arc::new(Webhooks::new(config.webhooks.clone(), Arc::clone(&database))?);
+        if webhooks.endpoint_count() > 0 {
+            tracing::info!("Webhooks enabled ({} endpoints)", webhooks.endpoint_count());
+        }
+        let saved_queries = Arc::new(SavedQueries::new(Arc::clone(&database))?);
+        let audit = Arc::new(AuditLog::new(config.audit.clone(), Arc::clone(&database), &storage_dir)?);
+        let readiness = Arc::new(Readiness::new(config.server.readiness.clone()));
+        if audit.is_enabled() {
+            tracing::info!("Answer audit log enabled ({:?} sink)", config.audit.sink);
+        }
         tracing::info!("Answer cache initialized");
 
+        let prompts = Arc::new(PromptTemplates::new(config.prompts.clone(), storage_dir.join("prompts"))?);
+        let chunks = ChunkStore::open(
+            Arc::clone(&database),
+            &storage_dir.join("chunk_vectors.f32"),
+            config.vector_db.chunk_cache_entries,
+        )?;
+
         // Load file registry from database into memory cache
         let file_registry = DashMap::new();
         match database.list_file_records() {
@@ -247,12 +591,26 @@ impl AppState {
 
         // Initialize job queue and start workers
         let worker_count = num_cpus::get().min(4);  // Max 4 workers
-        let (job_queue, receiver) = JobQueue::new(worker_count, database.clone());
-        let job_queue = Arc::new(job_queue);
-        tracing::info!("Job queue initialized with {} workers", worker_count);
+        let queue_backend = Self::create_queue_backend(&config).await?;
+        let spool_dir = config.processing.spool_dir.clone().unwrap_or_else(|| storage_dir.join("spool"));
+        let spool_cipher = encryption_key.clone().filter(|_| config.encryption.spool).map(SpoolCipher::new);
+        let spool = Arc::new(SpoolStore::new(spool_dir).with_cipher(spool_cipher));
+        let scheduler = FairScheduler::new(config.processing.file_slots(), config.processing.interactive_weight);
+        let job_queue = Arc::new(JobQueue::new(worker_count, database.clone(), queue_backend.clone(), spool, scheduler));
+        tracing::info!(
+            "Job queue initialized with {} workers (backend: {}, run_workers: {})",
+            worker_count,
+            queue_backend.name(),
+            config.queue.run_workers
+        );
 
         // Check for incomplete jobs from previous session
-        let incomplete_jobs = job_queue.get_incomplete_jobs();
+        // Shared backends keep their own pending jobs, so SQLite resume only applies in-process
+        let incomplete_jobs = if queue_backend.is_shared() {
+            Vec::new()
+        } else {
+            job_queue.get_incomplete_jobs()
+        };
         if !incomplete_jobs.is_empty() {
             tracing::info!(
                 "Found {} incomplete jobs from previous session - will resume after startup",
@@ -260,38 +618,154 @@ impl AppState {
             );
         }
 
+        let provenance_signer = ProvenanceSigner::from_config(&config.provenance)?.map(Arc::new);
+        if let Some(ref signer) = provenance_signer {
+            tracing::info!("Signing answers with {} provenance", signer.algorithm());
+        }
+
+        let snapshots = Arc::new(SnapshotManager::new(config.snapshots.clone(), &storage_dir));
+
+        // Originals and thumbnails live in GCS with the GCP backend, on disk otherwise
+        #[cfg(feature = "gcp")]
+        let gcs_file_store = gcs_document_store.clone().map(|store| store as Arc<dyn DocumentStoreProvider>);
+        #[cfg(not(feature = "gcp"))]
+        let gcs_file_store: Option<Arc<dyn DocumentStoreProvider>> = None;
+        let file_store = match gcs_file_store {
+            Some(store) => Some(store),
+            None if config.originals.keep || config.thumbnails.enabled => {
+                let dir = config.originals.directory.clone().unwrap_or_else(|| storage_dir.join("documents"));
+                let store = LocalDocumentStore::new(dir)?.with_cipher(content_cipher.clone());
+                Some(Arc::new(store) as Arc<dyn DocumentStoreProvider>)
+            }
+            None => None,
+        };
+        let graph = Arc::new(GraphBuilder::new(config.graph.clone()));
+        let qa_generator = Arc::new(QaGenerator::new(config.qa_generation.clone()));
+
         // Create the state first (without the worker running)
         let state = Self {
             inner: Arc::new(AppStateInner {
-                config,
-                vector_store_provider,
-                vector_store: local_vector_store,
-                embedding_provider,
+                config: RwLock::new(Arc::new(config)),
+                config_path: RwLock::new(None),
+                rate_limits,
+                vector_store_provider: RwLock::new(vector_store_provider),
+                vector_store: RwLock::new(local_vector_store),
+                embedding_provider: RwLock::new(embedding_provider),
+                embedders,
+                embedding_scheduler,
+                stale_embeddings: RwLock::new(stale_embeddings),
                 llm_provider,
+                variant_llms,
+                usage,
+                vision_provider,
+                file_store,
                 ollama,
                 external_parser,
+                transcriber,
+                redactor,
+                keyword_extractor,
+                guard,
+                webhooks,
+                saved_queries,
+                audit,
+                readiness,
+                prompts,
                 job_queue: job_queue.clone(),
                 knowledge_store,
                 answer_cache,
+                reindex: Arc::new(ReindexManager::new()),
+                embedding_repair: Arc::new(EmbeddingRepair::new()),
+                gcs_sync: Arc::new(GcsSync::new()),
+                connectors: Arc::new(Connectors::new()),
+                snapshots,
+                graph,
+                qa_generator,
+                tasks: Arc::new(TaskRegistry::new()),
+                provenance_signer,
                 documents,
-                chunks: DashMap::new(),
+                chunks,
                 file_registry,
+                // Starts at the startup time, so ETags from before a restart don't match
+                registry_version: AtomicU64::new(chrono::Utc::now().timestamp_millis().max(0) as u64),
+                document_acls: RwLock::new(None),
                 database,
                 documents_path,
                 ready: RwLock::new(true),
                 #[cfg(feature = "gcp")]
                 document_store: gcs_document_store,
                 #[cfg(feature = "gcp")]
+                vector_index,
+                #[cfg(feature = "gcp")]
                 document_ai: document_ai_client,
             }),
         };
 
+        let reload_interval = state.config().prompts.reload_interval_secs;
+        if reload_interval > 0 {
+            state.prompts().spawn_watcher(std::time::Duration::from_secs(reload_interval));
+        }
+        state.usage().spawn_flusher();
+        state.audit().spawn_retention();
+        state.spawn_trace_retention();
+
+        let snapshot_interval = state.config().snapshots.interval_secs;
+        if snapshot_interval > 0 {
+            state
+                .snapshots()
+                .spawn_periodic(state.clone(), std::time::Duration::from_secs(snapshot_interval));
+        }
+
         // Start background worker with a clone of the state
-        let worker_state = state.clone();
-        let worker = ProcessingWorker::new(worker_state, job_queue.clone());
-        tokio::spawn(async move {
-            worker.run(receiver).await;
-        });
+        if state.config().queue.run_workers {
+            let worker_state = state.clone();
+            let worker = ProcessingWorker::new(worker_state, job_queue.clone());
+            tokio::spawn(async move {
+                worker.run().await;
+            });
+
+            let repair_interval = state.config().embeddings.repair_interval_secs;
+            if repair_interval > 0 {
+                state
+                    .embedding_repair()
+                    .spawn_periodic(state.clone(), std::time::Duration::from_secs(repair_interval));
+            }
+
+            #[cfg(feature = "gcp")]
+            {
+                let sync_interval = state.config().gcp.as_ref().map_or(0, |gcp| gcp.sync_interval_secs);
+                if sync_interval > 0 && state.document_store().is_some() {
+                    state
+                        .gcs_sync()
+                        .spawn_periodic(state.clone(), std::time::Duration::from_secs(sync_interval));
+                }
+            }
+
+            let graph_interval = state.config().graph.build_interval_secs;
+            if state.config().graph.enabled && graph_interval > 0 {
+                state
+                    .graph()
+                    .spawn_periodic(state.clone(), std::time::Duration::from_secs(graph_interval));
+            }
+
+            let qa_interval = state.config().qa_generation.interval_secs;
+            if state.config().qa_generation.enabled && qa_interval > 0 {
+                state
+                    .qa_generator()
+                    .spawn_periodic(state.clone(), std::time::Duration::from_secs(qa_interval));
+            }
+
+            if state.config().reports.scheduler {
+                state.saved_queries().spawn_scheduler(state.clone());
+            }
+
+            if !state.config().connectors.is_empty() {
+                state.connectors().spawn_scheduler(state.clone());
+            }
+
+            state.reindex().spawn_scheduler(state.clone());
+        } else {
+            tracing::info!("Workers disabled in this process (queue.run_workers = false)");
+        }
 
         // Resume incomplete jobs from previous session
         if !incomplete_jobs.is_empty() {
@@ -323,6 +797,57 @@ impl AppState {
         Ok(state)
     }
 
+    /// Delete retrieval traces past `traces.retention_days` now and then daily
+    fn spawn_trace_retention(&self) {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(24 * 60 * 60));
+            loop {
+                ticker.tick().await;
+                let config = state.config().traces.clone();
+                if !config.enabled || config.retention_days <= 0 {
+                    continue;
+                }
+                let database = Arc::clone(state.database());
+                match tokio::task::spawn_blocking(move || database.cleanup_old_traces(config.retention_days)).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(n)) => tracing::info!("Removed {} retrieval traces older than {} days", n, config.retention_days),
+                    Ok(Err(e)) => tracing::warn!("Failed to clean up retrieval traces: {}", e),
+                    Err(e) => tracing::warn!("Retrieval trace retention task failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Create the job queue backend selected in config
+    async fn create_queue_backend(config: &RagConfig) -> Result<Arc<dyn QueueBackend>> {
+        match config.queue.backend {
+            QueueBackendKind::InProcess => Ok(Arc::new(InProcessQueue::new(config.queue.capacity))),
+            QueueBackendKind::Redis => {
+                #[cfg(feature = "redis-queue")]
+                {
+                    let url = config.queue.redis_url.as_deref().ok_or_else(|| {
+                        Error::Config("Redis queue selected but queue.redis_url is missing".to_string())
+                    })?;
+                    let queue = crate::processing::RedisQueue::connect(
+                        url,
+                        &config.queue.key_prefix,
+                        std::time::Duration::from_secs(config.queue.lease_secs),
+                        config.queue.max_deliveries,
+                    ).await?;
+                    Ok(Arc::new(queue))
+                }
+                #[cfg(not(feature = "redis-queue"))]
+                {
+                    Err(Error::Config(
+                        "Redis queue selected but redis-queue feature is not enabled. \
+                         Rebuild with --features redis-queue".to_string()
+                    ))
+                }
+            }
+        }
+    }
+
     /// Load documents from disk
     fn load_documents(path: &PathBuf) -> DashMap<Uuid, Document> {
         let documents = DashMap::new();
@@ -374,80 +899,60 @@ impl AppState {
         &self.inner.database
     }
 
-    /// Sync file registry from GCS bucket
+    /// Sync file registry from GCS bucket, reading every document
     /// Returns (files_synced, failed_count)
     #[cfg(feature = "gcp")]
     pub async fn sync_from_gcs(&self) -> Result<(usize, usize)> {
-        let document_store = self.document_store()
-            .ok_or_else(|| Error::Internal("GCS document store not available".to_string()))?;
-
-        let start = std::time::Instant::now();
-        let files = document_store.sync_from_bucket().await?;
-
-        let mut synced = 0;
-        let mut failed = 0;
-
-        for file_info in &files {
-            // Update database
-            if let Err(e) = self.inner.database.sync_from_gcs(
-                &file_info.filename,
-                file_info.document_id,
-                file_info.content_hash.as_deref().unwrap_or(""),
-                file_info.file_size,
-                &file_info.file_type,
-                file_info.has_plaintext,
-                &file_info.original_uri,
-                file_info.plaintext_uri.as_deref(),
-            ) {
-                tracing::warn!("Failed to sync file {}: {}", file_info.filename, e);
-                failed += 1;
-                continue;
-            }
-
-            // Update in-memory cache
-            let status = if file_info.has_plaintext {
-                FileRecordStatus::Success
-            } else {
-                FileRecordStatus::Failed
-            };
-
-            let record = FileRecord {
-                id: file_info.document_id,
-                filename: file_info.filename.clone(),
-                content_hash: file_info.content_hash.clone().unwrap_or_default(),
-                file_size: file_info.file_size,
-                file_type: crate::types::FileType::from_extension(&file_info.file_type),
-                status,
-                document_id: Some(file_info.document_id),
-                chunks_created: None,
-                skip_reason: None,
-                error_message: if file_info.has_plaintext { None } else {
-                    Some("No plaintext found - processing may have failed".to_string())
-                },
-                failed_at_stage: None,
-                job_id: None,
-                first_seen_at: chrono::Utc::now(),
-                last_processed_at: chrono::Utc::now(),
-                upload_count: 1,
-                original_url: Some(file_info.original_uri.clone()),
-                plaintext_url: file_info.plaintext_uri.clone(),
-            };
+        let progress = self.gcs_sync().run(self, true).await?;
+        Ok((progress.files_synced, progress.files_failed))
+    }
 
-            self.inner.file_registry.insert(file_info.filename.clone(), record);
-            synced += 1;
-        }
+    /// Record a document found in the GCS bucket in the database and registry
+    #[cfg(feature = "gcp")]
+    pub(crate) fn record_gcs_file(&self, file_info: &GcsFileInfo) -> Result<()> {
+        self.inner.database.sync_from_gcs(
+            &file_info.filename,
+            file_info.document_id,
+            file_info.content_hash.as_deref().unwrap_or(""),
+            file_info.file_size,
+            &file_info.file_type,
+            file_info.has_plaintext,
+            &file_info.original_uri,
+            file_info.plaintext_uri.as_deref(),
+        )?;
 
-        let duration_ms = start.elapsed().as_millis() as u64;
-        if let Err(e) = self.inner.database.update_sync_status(synced, duration_ms) {
-            tracing::warn!("Failed to update sync status: {}", e);
-        }
+        // Update in-memory cache
+        let status = if file_info.has_plaintext {
+            FileRecordStatus::Success
+        } else {
+            FileRecordStatus::Failed
+        };
 
-        tracing::info!(
-            "GCS sync complete: {} files synced, {} failed, took {}ms",
-            synced, failed, duration_ms
-        );
+        let record = FileRecord {
+            id: file_info.document_id,
+            filename: file_info.filename.clone(),
+            content_hash: file_info.content_hash.clone().unwrap_or_default(),
+            file_size: file_info.file_size,
+            file_type: crate::types::FileType::from_extension(&file_info.file_type),
+            status,
+            document_id: Some(file_info.document_id),
+            chunks_created: None,
+            skip_reason: None,
+            error_message: if file_info.has_plaintext { None } else {
+                Some("No plaintext found - processing may have failed".to_string())
+            },
+            failed_at_stage: None,
+            job_id: None,
+            first_seen_at: chrono::Utc::now(),
+            last_processed_at: chrono::Utc::now(),
+            upload_count: 1,
+            original_url: Some(file_info.original_uri.clone()),
+            plaintext_url: file_info.plaintext_uri.clone(),
+        };
 
-        Ok((synced, failed))
+        self.inner.file_registry.insert(file_info.filename.clone(), record);
+        self.bump_registry();
+        Ok(())
     }
 
     /// Get GCS sync status
@@ -460,6 +965,90 @@ impl AppState {
         &self.inner.external_parser
     }
 
+    /// Get Whisper transcriber for audio/video
+    pub fn transcriber(&self) -> &Transcriber {
+        &self.inner.transcriber
+    }
+
+    /// Get PII redactor
+    pub fn redactor(&self) -> &Redactor {
+        &self.inner.redactor
+    }
+
+    /// Get keyword and entity extractor
+    pub fn keyword_extractor(&self) -> &KeywordExtractor {
+        &self.inner.keyword_extractor
+    }
+
+    /// Get prompt-injection guard
+    pub fn guard(&self) -> &Guard {
+        &self.inner.guard
+    }
+
+    /// Get webhook dispatcher
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.inner.webhooks
+    }
+
+    /// Get the saved queries
+    pub fn saved_queries(&self) -> &Arc<SavedQueries> {
+        &self.inner.saved_queries
+    }
+
+    /// Get answer audit log
+    pub fn audit(&self) -> &Arc<AuditLog> {
+        &self.inner.audit
+    }
+
+    /// Get the readiness checks
+    pub fn readiness(&self) -> &Arc<Readiness> {
+        &self.inner.readiness
+    }
+
+    /// Get prompt templates
+    pub fn prompts(&self) -> &Arc<PromptTemplates> {
+        &self.inner.prompts
+    }
+
+    /// Number of cached answers citing a document
+    pub fn cached_answers_citing(&self, doc_id: &Uuid) -> usize {
+        self.inner.answer_cache.count_by_document(doc_id)
+    }
+
+    /// Drop cached answers citing a document, notifying webhooks if any were dropped
+    pub fn invalidate_cached_answers(&self, doc_id: &Uuid) -> usize {
+        let invalidated = self.inner.answer_cache.invalidate_by_document(doc_id);
+        if invalidated > 0 {
+            self.inner.webhooks.emit(WebhookEvent::new(
+                WebhookEventKind::CacheInvalidated,
+                serde_json::json!({ "document_id": doc_id, "answers": invalidated }),
+            ));
+        }
+        invalidated
+    }
+
+    /// Store guard events in the audit log (in the background)
+    pub fn record_guard_events(&self, events: Vec<GuardEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        for event in &events {
+            tracing::warn!(
+                "Guard {} {} ({}): \"{}\"",
+                if event.blocked { "blocked" } else { "flagged" },
+                event.kind.as_str(),
+                event.rules.join(", "),
+                event.excerpt
+            );
+        }
+        let database = self.inner.database.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = database.insert_guard_events(&events) {
+                tracing::warn!("Failed to store {} guard events: {}", events.len(), e);
+            }
+        });
+    }
+
     /// Get job queue
     pub fn job_queue(&self) -> &Arc<JobQueue> {
         &self.inner.job_queue
@@ -475,6 +1064,23 @@ impl AppState {
         &self.inner.answer_cache
     }
 
+    /// Start a retrieval trace if this query is sampled
+    pub fn start_trace(&self, endpoint: &str, request: &crate::types::QueryRequest) -> Option<RetrievalTrace> {
+        trace::should_sample(&self.config().traces).then(|| {
+            RetrievalTrace::new(endpoint, &request.question, request.top_k, request.similarity_threshold)
+        })
+    }
+
+    /// Persist a retrieval trace in the background
+    pub fn record_trace(&self, trace: RetrievalTrace) {
+        let database = self.inner.database.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = database.insert_trace(&trace) {
+                tracing::warn!("Failed to store retrieval trace {}: {}", trace.id, e);
+            }
+        });
+    }
+
     /// Get document timestamps for cache validation
     pub fn get_document_timestamps(&self) -> std::collections::HashMap<Uuid, chrono::DateTime<chrono::Utc>> {
         self.inner
@@ -484,15 +1090,77 @@ impl AppState {
             .collect()
     }
 
+    /// Identifier of the current corpus state
+    ///
+    /// Changes whenever a document is added, removed or re-ingested with
+    /// different content.
+    pub fn corpus_snapshot_id(&self) -> String {
+        let documents: Vec<(Uuid, String)> = self
+            .inner
+            .documents
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().content_hash.clone()))
+            .collect();
+        provenance::corpus_snapshot_id(documents.iter().map(|(id, hash)| (*id, hash.as_str())))
+    }
+
     /// Get configuration
-    pub fn config(&self) -> &RagConfig {
-        &self.inner.config
+    ///
+    /// A snapshot: a reload swaps in a new configuration without changing
+    /// the one returned here.
+    pub fn config(&self) -> Arc<RagConfig> {
+        self.inner.config.read().clone()
+    }
+
+    /// File the configuration was loaded from
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.inner.config_path.read().clone()
+    }
+
+    /// Remember the file the configuration was loaded from, for reloads
+    pub fn set_config_path(&self, path: Option<PathBuf>) {
+        *self.inner.config_path.write() = path;
+    }
+
+    /// Take over the live settings of a reloaded configuration
+    ///
+    /// Refused as a whole when the new configuration is invalid or changes
+    /// settings that need a reindex (embedding model or dimensions,
+    /// chunking); settings that only apply after a restart are reported and
+    /// left as they are.
+    pub fn reload_config(&self, new: &RagConfig) -> Result<ReloadPlan> {
+        let errors: Vec<String> = config_check::validate(new)
+            .into_iter()
+            .filter(|issue| issue.is_error())
+            .map(|issue| issue.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::Config(format!("Invalid configuration: {}", errors.join("; "))));
+        }
+
+        let mut config = self.inner.config.write();
+        let plan = config_check::plan_reload(&config, new)?;
+        if !plan.requires_reindex.is_empty() {
+            return Err(Error::Config(format!(
+                "Changing {} requires a reindex - restart with the new configuration and run POST /api/admin/reindex",
+                plan.requires_reindex.join(", ")
+            )));
+        }
+        if plan.applied.is_empty() {
+            return Ok(plan);
+        }
+
+        self.inner.prompts.reconfigure(&plan.config.prompts);
+        self.inner.rate_limits.reconfigure(&plan.config.rate_limits);
+        *config = Arc::new(plan.config.clone());
+        tracing::info!("Reloaded configuration: {}", plan.applied.join(", "));
+        Ok(plan)
     }
 
     /// Get vector store (only available for Local backend)
     /// Prefer using vector_store_provider() for new code
-    pub fn vector_store(&self) -> Option<&Arc<VectorStore>> {
-        self.inner.vector_store.as_ref()
+    pub fn vector_store(&self) -> Option<Arc<VectorStore>> {
+        self.inner.vector_store.read().clone()
     }
 
     /// Get Ollama client (for embeddings and generation)
@@ -502,8 +1170,104 @@ impl AppState {
     }
 
     /// Get embedding provider (Ollama or Vertex AI based on config)
-    pub fn embedding_provider(&self) -> &Arc<dyn EmbeddingProvider> {
-        &self.inner.embedding_provider
+    pub fn embedding_provider(&self) -> Arc<dyn EmbeddingProvider> {
+        self.inner.embedding_provider.read().clone()
+    }
+
+    /// Get the embedding models of collections with their own model
+    pub fn embedders(&self) -> &CollectionEmbedders {
+        &self.inner.embedders
+    }
+
+    /// Get embedding batch scheduler counters
+    pub fn embedding_scheduler(&self) -> &Arc<SchedulerStats> {
+        &self.inner.embedding_scheduler
+    }
+
+    /// Get the embedding provider of a collection (the default provider unless it has its own model)
+    ///
+    /// Its calls are counted for the collection when usage is tracked.
+    pub fn embedder_for(&self, collection: Option<&str>) -> Arc<dyn EmbeddingProvider> {
+        let provider = self.inner.embedders.provider(collection).unwrap_or_else(|| self.embedding_provider());
+        self.inner.usage.meter_embedder(provider, self.embedding_model_for(collection), collection)
+    }
+
+    /// Get the name of the model that embeds a collection
+    pub fn embedding_model_for(&self, collection: Option<&str>) -> &str {
+        self.inner.embedders.model(collection)
+    }
+
+    /// Refuse queries that would compare vectors of different models
+    ///
+    /// A query embedded for `collection` searches every collection embedded
+    /// with the same model. If any of their chunks were embedded with another
+    /// model (the configuration changed since), the query is refused; with
+    /// `embeddings.auto_reindex` a reindex is started first.
+    pub fn check_embedding_model(&self, collection: Option<&str>) -> Result<()> {
+        if self.inner.stale_embeddings.read().is_empty() {
+            return Ok(());
+        }
+        // Deleted or repaired chunks may have cleared it
+        self.refresh_stale_embeddings();
+
+        let model = self.embedding_model_for(collection);
+        let stale: usize = self
+            .inner
+            .stale_embeddings
+            .read()
+            .iter()
+            .filter(|(c, _)| self.embedding_model_for(c.as_deref()) == model)
+            .map(|(_, count)| count)
+            .sum();
+        if stale == 0 {
+            return Ok(());
+        }
+
+        let reindexing = if self.reindex().is_running() {
+            true
+        } else if self.config().embeddings.auto_reindex {
+            match self.reindex().start(self.clone()) {
+                Ok(progress) => {
+                    tracing::info!("Started reindex {} for {} chunks not embedded with {}", progress.id, stale, model);
+                    true
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to start reindex for stale embeddings: {}", e);
+                    false
+                }
+            }
+        } else {
+            false
+        };
+
+        Err(Error::Config(if reindexing {
+            format!("{} chunks are being re-embedded with {}, retry when the reindex completes", stale, model)
+        } else {
+            format!("{} chunks were embedded with another model than {}, reindex first (POST /api/admin/reindex)", stale, model)
+        }))
+    }
+
+    fn refresh_stale_embeddings(&self) {
+        match Self::count_stale_embeddings(&self.inner.database, &self.inner.embedders) {
+            Ok(stale) => *self.inner.stale_embeddings.write() = stale,
+            Err(e) => tracing::warn!("Failed to count stale embeddings: {}", e),
+        }
+    }
+
+    /// Chunks per collection embedded with another model than the collection's
+    fn count_stale_embeddings(
+        database: &FileRegistryDb,
+        embedders: &CollectionEmbedders,
+    ) -> Result<std::collections::HashMap<Option<String>, usize>> {
+        let mut stale = std::collections::HashMap::new();
+        for (collection, model, count) in database.embedding_models_by_collection()? {
+            // Chunks stored before models were tracked used the default model
+            let model = model.as_deref().unwrap_or(embedders.model(None));
+            if model != embedders.model(collection.as_deref()) {
+                *stale.entry(collection).or_default() += count;
+            }
+        }
+        Ok(stale)
     }
 
     /// Get LLM provider (Ollama or Gemini based on config)
@@ -511,9 +1275,127 @@ impl AppState {
         &self.inner.llm_provider
     }
 
+    /// Get the per-provider rate limits
+    pub fn rate_limits(&self) -> &Arc<RateLimits> {
+        &self.inner.rate_limits
+    }
+
+    /// Get the token usage tracker
+    pub fn usage(&self) -> &Arc<UsageTracker> {
+        &self.inner.usage
+    }
+
+    /// Get the LLM provider for a generation model (the default provider if none or unknown)
+    pub fn llm_for_model(&self, model: Option<&str>) -> &Arc<dyn LlmProvider> {
+        model
+            .and_then(|name| self.inner.variant_llms.get(name))
+            .unwrap_or(&self.inner.llm_provider)
+    }
+
+    /// Whether queries can select a generation model
+    pub fn has_model(&self, model: &str) -> bool {
+        model == self.inner.llm_provider.model() || self.inner.variant_llms.contains_key(model)
+    }
+
+    /// Get the LLM for a generation model (the default if none), falling back
+    /// along the model's chain in `models.fallbacks`
+    pub fn answer_llm(&self, model: Option<&str>) -> FallbackLlm {
+        let first = self.llm_for_model(model);
+        let config = self.config();
+        let models = &config.models;
+        let mut chain = vec![Arc::clone(first)];
+        for name in models.fallbacks.get(model.unwrap_or(first.model())).into_iter().flatten() {
+            let llm = self.llm_for_model(Some(name));
+            if !chain.iter().any(|c| Arc::ptr_eq(c, llm)) {
+                chain.push(Arc::clone(llm));
+            }
+        }
+        let timeout = (models.attempt_timeout_secs > 0)
+            .then(|| std::time::Duration::from_secs(models.attempt_timeout_secs));
+        FallbackLlm::new(chain, timeout)
+    }
+
+    /// Get vision provider for figure captions (None when captioning is disabled)
+    pub fn vision_provider(&self) -> Option<&Arc<dyn VisionProvider>> {
+        self.inner.vision_provider.as_ref()
+    }
+
+    /// Get the store for originals, plain text and page thumbnails: GCS with
+    /// the GCP backend, a local directory when `originals.keep` or
+    /// `thumbnails.enabled` is set, None otherwise
+    pub fn file_store(&self) -> Option<&Arc<dyn DocumentStoreProvider>> {
+        self.inner.file_store.as_ref()
+    }
+
     /// Get vector store provider (Local HNSW or Vertex AI Vector Search)
-    pub fn vector_store_provider(&self) -> &Arc<dyn VectorStoreProvider> {
-        &self.inner.vector_store_provider
+    pub fn vector_store_provider(&self) -> Arc<dyn VectorStoreProvider> {
+        self.inner.vector_store_provider.read().clone()
+    }
+
+    /// Get reindex manager
+    pub fn reindex(&self) -> &Arc<ReindexManager> {
+        &self.inner.reindex
+    }
+
+    /// Get embedding repair runner
+    pub fn embedding_repair(&self) -> &Arc<EmbeddingRepair> {
+        &self.inner.embedding_repair
+    }
+
+    /// Get GCS registry sync runner
+    pub fn gcs_sync(&self) -> &Arc<GcsSync> {
+        &self.inner.gcs_sync
+    }
+
+    /// Get source connector runner
+    pub fn connectors(&self) -> &Arc<Connectors> {
+        &self.inner.connectors
+    }
+
+    /// Get registry snapshot manager
+    pub fn snapshots(&self) -> &Arc<SnapshotManager> {
+        &self.inner.snapshots
+    }
+
+    /// Get knowledge graph builder
+    pub fn graph(&self) -> &Arc<GraphBuilder> {
+        &self.inner.graph
+    }
+
+    /// Get question-answer pair generator
+    pub fn qa_generator(&self) -> &Arc<QaGenerator> {
+        &self.inner.qa_generator
+    }
+
+    /// Get long-running task registry
+    pub fn tasks(&self) -> &Arc<TaskRegistry> {
+        &self.inner.tasks
+    }
+
+    /// Get answer provenance signer (None when signing is disabled)
+    pub fn provenance_signer(&self) -> Option<&Arc<ProvenanceSigner>> {
+        self.inner.provenance_signer.as_ref()
+    }
+
+    /// Replace the serving index and embedder with a freshly built generation
+    ///
+    /// Queries in flight keep their clone of the old providers until they finish.
+    pub fn swap_index(
+        &self,
+        vector_store: Option<Arc<VectorStore>>,
+        vector_store_provider: Arc<dyn VectorStoreProvider>,
+        embedding_provider: Arc<dyn EmbeddingProvider>,
+    ) {
+        *self.inner.vector_store.write() = vector_store;
+        *self.inner.vector_store_provider.write() = vector_store_provider;
+        *self.inner.embedding_provider.write() = embedding_provider;
+        self.refresh_stale_embeddings();
+
+        // Stored vectors and cached answers refer to the old generation
+        if let Err(e) = self.inner.chunks.clear_embeddings() {
+            tracing::warn!("Failed to clear stored chunk vectors: {}", e);
+        }
+        self.inner.answer_cache.clear();
     }
 
     /// Get GCS document store (only available with GCP backend)
@@ -522,6 +1404,12 @@ impl AppState {
         self.inner.document_store.as_ref()
     }
 
+    /// Get Vector Search index management (only available with GCP backend)
+    #[cfg(feature = "gcp")]
+    pub fn vector_index(&self) -> Option<&Arc<VertexIndexManager>> {
+        self.inner.vector_index.as_ref()
+    }
+
     /// Get Document AI client (only available with GCP backend and processor configured)
     #[cfg(feature = "gcp")]
     pub fn document_ai(&self) -> Option<&Arc<DocumentAiClient>> {
@@ -544,8 +1432,29 @@ impl AppState {
     }
 
     /// Add a document to the registry (persisted to disk)
-    pub fn add_document(&self, doc: Document) {
+    ///
+    /// Documents without a version follow the superseded versions of their file.
+    pub fn add_document(&self, mut doc: Document) {
+        if !doc.metadata.contains_key(crate::types::document::VERSION_METADATA_KEY) {
+            let previous = self.inner.database.latest_document_version(&doc.filename).unwrap_or_else(|e| {
+                tracing::warn!("Failed to look up versions of {}: {}", doc.filename, e);
+                None
+            });
+            doc.set_version(previous.map_or(1, |v| v + 1));
+        }
         self.inner.documents.insert(doc.id, doc);
+        self.bump_registry();
+        self.save_documents();
+    }
+
+    /// Update chunk counts after documents were re-chunked (persisted once)
+    pub fn set_document_chunk_counts(&self, counts: &std::collections::HashMap<Uuid, u32>) {
+        for (id, total_chunks) in counts {
+            if let Some(mut doc) = self.inner.documents.get_mut(id) {
+                doc.total_chunks = *total_chunks;
+            }
+        }
+        self.bump_registry();
         self.save_documents();
     }
 
@@ -557,12 +1466,51 @@ impl AppState {
     /// Remove a document (persisted to disk)
     pub fn remove_document(&self, id: &Uuid) -> Option<Document> {
         let removed = self.inner.documents.remove(id).map(|(_, d)| d);
+        self.inner.chunks.forget_document(id);
         if removed.is_some() {
+            self.bump_registry();
             self.save_documents();
         }
         removed
     }
 
+    /// Current registry version (changes whenever documents or file records do)
+    pub fn registry_version(&self) -> u64 {
+        self.inner.registry_version.load(Ordering::Relaxed)
+    }
+
+    fn bump_registry(&self) {
+        self.inner.registry_version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Remove documents and their file records from the in-memory registries
+    /// (documents persisted once; file records are deleted from the database
+    /// by the caller)
+    pub fn remove_documents(&self, ids: &std::collections::HashSet<Uuid>) -> Vec<Document> {
+        let removed: Vec<Document> = ids
+            .iter()
+            .filter_map(|id| self.inner.documents.remove(id).map(|(_, d)| d))
+            .collect();
+        for id in ids {
+            self.inner.chunks.forget_document(id);
+        }
+        self.inner
+            .file_registry
+            .retain(|_, record| record.document_id.map_or(true, |id| !ids.contains(&id)));
+        self.bump_registry();
+        self.save_documents();
+        removed
+    }
+
+    /// Number of file records pointing at the documents
+    pub fn count_file_records_for(&self, ids: &std::collections::HashSet<Uuid>) -> usize {
+        self.inner
+            .file_registry
+            .iter()
+            .filter(|entry| entry.value().document_id.is_some_and(|id| ids.contains(&id)))
+            .count()
+    }
+
     /// List all documents
     pub fn list_documents(&self) -> Vec<Document> {
         self.inner
@@ -572,6 +1520,37 @@ impl AppState {
             .collect()
     }
 
+    /// IDs of all documents
+    pub fn document_ids(&self) -> Vec<Uuid> {
+        self.inner.documents.iter().map(|entry| *entry.key()).collect()
+    }
+
+    /// Access lists of the documents that have one
+    ///
+    /// Collected again only after the registry changed, so per-request access
+    /// checks don't copy every document.
+    pub fn document_acls(&self) -> Arc<std::collections::HashMap<Uuid, Vec<String>>> {
+        let version = self.registry_version();
+        if let Some((cached_version, acls)) = self.inner.document_acls.read().as_ref() {
+            if *cached_version == version {
+                return Arc::clone(acls);
+            }
+        }
+
+        let acls: std::collections::HashMap<Uuid, Vec<String>> = self
+            .inner
+            .documents
+            .iter()
+            .filter_map(|entry| {
+                let acl = entry.value().acl();
+                (!acl.is_empty()).then(|| (*entry.key(), acl.into_iter().map(String::from).collect()))
+            })
+            .collect();
+        let acls = Arc::new(acls);
+        *self.inner.document_acls.write() = Some((version, Arc::clone(&acls)));
+        acls
+    }
+
     /// Find document by filename
     pub fn find_by_filename(&self, filename: &str) -> Option<Document> {
         self.inner
@@ -592,14 +1571,23 @@ impl AppState {
 
     /// Store chunks in the local chunk store (for Vertex AI metadata lookup)
     pub fn store_chunks(&self, chunks: &[Chunk]) {
-        for chunk in chunks {
-            self.inner.chunks.insert(chunk.id, chunk.clone());
+        if let Err(e) = self.try_store_chunks(chunks) {
+            tracing::warn!("Failed to store {} chunks: {}", chunks.len(), e);
         }
     }
 
+    /// Store chunks in the local chunk store, for callers that undo what they
+    /// wrote before if it fails
+    pub fn try_store_chunks(&self, chunks: &[Chunk]) -> Result<()> {
+        self.inner.chunks.store(chunks)
+    }
+
     /// Get a chunk by ID from the local store
     pub fn get_chunk(&self, id: &Uuid) -> Option<Chunk> {
-        self.inner.chunks.get(id).map(|c| c.clone())
+        self.inner.chunks.get(id).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read chunk {}: {}", id, e);
+            None
+        })
     }
 
     /// Check if file should be processed (returns action to take)
@@ -655,17 +1643,33 @@ impl AppState {
     /// Delete document and its chunks (async version using provider)
     pub async fn delete_document_with_chunks(&self, doc_id: &Uuid) -> crate::error::Result<usize> {
         // Invalidate cached answers that cite this document
-        self.inner.answer_cache.invalidate_by_document(doc_id);
+        self.invalidate_cached_answers(doc_id);
 
         // Delete chunks from vector store provider (works for both Local and GCP)
-        let deleted = self.inner.vector_store_provider.delete_by_document(doc_id).await?;
+        let deleted = self.vector_store_provider().delete_by_document(doc_id).await?;
+        self.inner.database.delete_pending_embeddings_for_document(doc_id)?;
 
         // Remove from document registry
         self.inner.documents.remove(doc_id);
+        self.bump_registry();
 
         Ok(deleted)
     }
 
+    /// Replace a document by a new version of its file: keep it and its chunk
+    /// text as a superseded version (when versioning is enabled), then delete
+    /// it and its chunks
+    pub async fn supersede_document(&self, doc: &Document) -> crate::error::Result<usize> {
+        let config = self.config();
+        let versioning = &config.versioning;
+        if versioning.enabled {
+            let chunks = self.inner.database.list_chunks_for_document(&doc.id)?;
+            self.inner.database.archive_document_version(doc, &chunks)?;
+            self.inner.database.prune_document_versions(&doc.filename, versioning.max_versions)?;
+        }
+        self.delete_document_with_chunks(&doc.id).await
+    }
+
     // ==================== File Registry Methods ====================
 
     /// Record a successful file processing
@@ -694,6 +1698,7 @@ impl AppState {
         }
         // Update in-memory cache
         self.inner.file_registry.insert(filename.to_string(), record);
+        self.bump_registry();
     }
 
     /// Record a skipped file
@@ -727,6 +1732,7 @@ impl AppState {
         }
         // Update in-memory cache
         self.inner.file_registry.insert(filename.to_string(), record);
+        self.bump_registry();
     }
 
     /// Record a failed file
@@ -761,6 +1767,7 @@ impl AppState {
         }
         // Update in-memory cache
         self.inner.file_registry.insert(filename.to_string(), record);
+        self.bump_registry();
     }
 
     /// Get file record by filename
@@ -786,6 +1793,15 @@ impl AppState {
             .collect()
     }
 
+    /// Restore a file record as exported from another instance
+    pub fn restore_file_record(&self, record: FileRecord) {
+        if let Err(e) = self.inner.database.upsert_file_record(&record) {
+            tracing::error!("Failed to save file record to database: {}", e);
+        }
+        self.inner.file_registry.insert(record.filename.clone(), record);
+        self.bump_registry();
+    }
+
     /// List successful file records
     pub fn list_successful_files(&self) -> Vec<FileRecord> {
         self.inner
@@ -839,7 +1855,9 @@ impl AppState {
             tracing::error!("Failed to delete file record from database: {}", e);
         }
         // Remove from in-memory cache
-        self.inner.file_registry.remove(filename).map(|(_, r)| r)
+        let removed = self.inner.file_registry.remove(filename).map(|(_, r)| r);
+        self.bump_registry();
+        removed
     }
 
     /// Clear all failed file records (for retry)
@@ -864,6 +1882,7 @@ impl AppState {
         for key in &failed_keys {
             self.inner.file_registry.remove(key);
         }
+        self.bump_registry();
 
         db_count.max(failed_keys.len())
     }
@@ -880,7 +1899,7 @@ impl AppState {
 }
 
 /// Statistics for file registry
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub struct FileRegistryStats {
     pub total: usize,
     pub success: usize,
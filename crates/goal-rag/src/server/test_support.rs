@@ -0,0 +1,155 @@
+//! Application state for tests
+//!
+//! Builds a local-backend `AppState` in a temporary directory whose Ollama
+//! provider is a stub server on a loopback port: embeddings are hashed
+//! bags of words (texts sharing words score high), generation returns queued
+//! replies and records every prompt, and the stub can be switched unhealthy.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, routing::{get, post}, Json, Router};
+use parking_lot::Mutex;
+
+use crate::config::{DimensionCheck, RagConfig};
+use crate::engine::RagEngine;
+use crate::types::query::IngestOptions;
+use crate::types::Document;
+
+use super::state::AppState;
+
+/// Dimensions of the stub embeddings
+pub(crate) const DIMENSIONS: usize = 64;
+
+/// Reply to prompts when none is queued
+pub(crate) const DEFAULT_REPLY: &str = "The documents answer this [1].";
+
+/// Stub Ollama server
+pub(crate) struct StubOllama {
+    healthy: AtomicBool,
+    prompts: Mutex<Vec<String>>,
+    replies: Mutex<VecDeque<String>>,
+}
+
+impl StubOllama {
+    /// Answer requests (true) or fail them with 503 (false)
+    pub(crate) fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::SeqCst);
+    }
+
+    /// Queue the reply to the next generation prompt
+    pub(crate) fn reply(&self, text: &str) {
+        self.replies.lock().push_back(text.to_string());
+    }
+
+    /// Generation prompts received so far, oldest first
+    pub(crate) fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().clone()
+    }
+}
+
+/// Wait for a background run to bring about `condition`, failing after ten seconds
+pub(crate) async fn wait_until(condition: impl Fn() -> bool) {
+    let waited = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        while !condition() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    });
+    waited.await.expect("condition not met within ten seconds");
+}
+
+/// Bag-of-words embedding of `text`, normalized
+pub(crate) fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0_f32; DIMENSIONS];
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+        vector[(hash % DIMENSIONS as u64) as usize] += 1.0;
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt().max(f32::EPSILON);
+    vector.iter().map(|v| v / norm).collect()
+}
+
+async fn tags(State(stub): State<Arc<StubOllama>>) -> StatusCode {
+    if stub.healthy.load(Ordering::SeqCst) { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE }
+}
+
+async fn embeddings(
+    State(stub): State<Arc<StubOllama>>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if !stub.healthy.load(Ordering::SeqCst) {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let prompt = body["prompt"].as_str().unwrap_or_default();
+    Ok(Json(serde_json::json!({ "embedding": embed(prompt) })))
+}
+
+async fn generate(
+    State(stub): State<Arc<StubOllama>>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if !stub.healthy.load(Ordering::SeqCst) {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    stub.prompts.lock().push(body["prompt"].as_str().unwrap_or_default().to_string());
+    let reply = stub.replies.lock().pop_front().unwrap_or_else(|| DEFAULT_REPLY.to_string());
+    Ok(Json(serde_json::json!({ "response": reply, "done": true })))
+}
+
+/// Application state over a stub Ollama server and a temporary directory
+pub(crate) struct TestApp {
+    pub state: AppState,
+    pub ollama: Arc<StubOllama>,
+    _dir: tempfile::TempDir,
+}
+
+impl TestApp {
+    pub(crate) async fn new() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    /// Start the stub and build the state, adjusting the test configuration first
+    pub(crate) async fn with_config(configure: impl FnOnce(&mut RagConfig)) -> Self {
+        let ollama = Arc::new(StubOllama {
+            healthy: AtomicBool::new(true),
+            prompts: Mutex::new(Vec::new()),
+            replies: Mutex::new(VecDeque::new()),
+        });
+        let router = Router::new()
+            .route("/api/tags", get(tags))
+            .route("/api/embeddings", post(embeddings))
+            .route("/api/generate", post(generate))
+            .with_state(Arc::clone(&ollama));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = RagConfig::default();
+        config.vector_db.storage_path = dir.path().join("vectors.db");
+        config.llm.base_url = format!("http://{}", addr);
+        config.llm.max_retries = 0;
+        config.llm.timeout_secs = 5;
+        config.embeddings.dimensions = DIMENSIONS;
+        config.embeddings.dimension_check = DimensionCheck::Off;
+        config.queue.run_workers = false;
+        configure(&mut config);
+
+        let state = AppState::new(config).await.unwrap();
+        Self { state, ollama, _dir: dir }
+    }
+
+    pub(crate) fn engine(&self) -> RagEngine {
+        RagEngine::from_state(self.state.clone())
+    }
+
+    /// Ingest a text file, returning its document
+    pub(crate) async fn ingest(&self, filename: &str, text: &str, options: &IngestOptions) -> Document {
+        let outcome = self.engine().ingest_bytes(filename, text.as_bytes(), options).await.unwrap();
+        outcome.document().cloned().expect("new file is ingested")
+    }
+}
@@ -0,0 +1,105 @@
+//! Native TLS termination
+//!
+//! For deployments without a reverse proxy in front, `[server.tls]` serves
+//! HTTPS with rustls. With `client_ca_file` set the handshake also requires
+//! a client certificate chaining to that CA, so only holders of an issued
+//! certificate reach the API at all; API keys and OIDC apply on top.
+
+use std::net::SocketAddr;
+
+use axum::Router;
+
+use crate::config::TlsConfig;
+use crate::error::{Error, Result};
+
+/// Serve `router` over HTTPS until the listener fails
+#[cfg(feature = "tls")]
+pub async fn serve(addr: SocketAddr, router: Router, tls: &TlsConfig) -> Result<()> {
+    let config = axum_server::tls_rustls::RustlsConfig::from_config(std::sync::Arc::new(server_config(tls)?));
+    axum_server::bind_rustls(addr, config)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .map_err(|e| Error::Internal(format!("Server error: {}", e)))
+}
+
+#[cfg(not(feature = "tls"))]
+pub async fn serve(_addr: SocketAddr, _router: Router, _tls: &TlsConfig) -> Result<()> {
+    Err(Error::Config("server.tls requires building with --features tls".to_string()))
+}
+
+#[cfg(feature = "tls")]
+fn server_config(tls: &TlsConfig) -> Result<rustls::ServerConfig> {
+    use std::sync::Arc;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::Config(format!("TLS setup failed: {}", e)))?;
+
+    let builder = match tls.client_ca_file {
+        Some(ref path) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for cert in read_certificates(path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| Error::Config(format!("Invalid client CA in {}: {}", path.display(), e)))?;
+            }
+            let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| Error::Config(format!("Invalid client CA in {}: {}", path.display(), e)))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let key = rustls_pemfile::private_key(&mut open(&tls.key_file)?)?
+        .ok_or_else(|| Error::Config(format!("No private key in {}", tls.key_file.display())))?;
+    let mut config = builder
+        .with_single_cert(read_certificates(&tls.cert_file)?, key)
+        .map_err(|e| Error::Config(format!("Invalid TLS certificate or key: {}", e)))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+#[cfg(feature = "tls")]
+fn read_certificates(path: &std::path::Path) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?).collect::<std::io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(Error::Config(format!("No certificates in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+#[cfg(feature = "tls")]
+fn open(path: &std::path::Path) -> Result<std::io::BufReader<std::fs::File>> {
+    std::fs::File::open(path)
+        .map(std::io::BufReader::new)
+        .map_err(|e| Error::Config(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_config_reports_missing_and_empty_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tls = TlsConfig {
+            cert_file: dir.path().join("cert.pem"),
+            key_file: dir.path().join("key.pem"),
+            client_ca_file: None,
+        };
+
+        let err = server_config(&tls).unwrap_err().to_string();
+        assert!(err.contains("Failed to read") && err.contains("key.pem"), "{}", err);
+
+        std::fs::write(&tls.key_file, "").unwrap();
+        let err = server_config(&tls).unwrap_err().to_string();
+        assert!(err.contains("No private key"), "{}", err);
+
+        tls.client_ca_file = Some(dir.path().join("ca.pem"));
+        std::fs::write(dir.path().join("ca.pem"), "").unwrap();
+        let err = server_config(&tls).unwrap_err().to_string();
+        assert!(err.contains("No certificates") && err.contains("ca.pem"), "{}", err);
+    }
+}
@@ -0,0 +1,40 @@
+//! Embedded web UI (`ui` feature)
+//!
+//! The static bundle in `ui/` is compiled into the binary and served under
+//! `/ui`: uploading files, watching ingestion jobs, browsing documents and
+//! their chunks, and running test queries. The pages only call the regular
+//! API, sending the API key entered in the page as a Bearer token, so they
+//! need no access of their own.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use include_dir::{include_dir, Dir};
+
+use super::state::AppState;
+
+static BUNDLE: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/ui");
+
+/// Routes serving the bundle
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/ui", get(|| async { Redirect::permanent("/ui/") }))
+        .route("/ui/", get(|| async { asset("index.html") }))
+        .route("/ui/*path", get(|Path(path): Path<String>| async move { asset(&path) }))
+}
+
+fn asset(path: &str) -> Response {
+    let Some(file) = BUNDLE.get_file(path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    (
+        [(header::CONTENT_TYPE, mime.to_string()), (header::CACHE_CONTROL, "no-cache".to_string())],
+        file.contents(),
+    )
+        .into_response()
+}
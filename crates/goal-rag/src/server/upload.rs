@@ -0,0 +1,76 @@
+//! Streaming multipart uploads for the ingest endpoints
+//!
+//! File parts are written to the spool store chunk by chunk and hashed on
+//! the way, so an upload is never held in memory whole. Clients can list the
+//! files in the `options` part (sent first) the way they would ask
+//! `/api/files/check`; parts the check says to skip are dropped unread. A
+//! part repeating the contents of an earlier part of the same request is
+//! dropped once it has been hashed.
+
+use std::collections::HashMap;
+
+use axum::extract::multipart::Field;
+
+use crate::error::{Error, Result};
+use crate::server::routes::files::check_single_file;
+use crate::server::state::AppState;
+use crate::storage::{SpoolStore, SpoolWriter};
+use crate::types::{FileCheckItem, FileUploadAdvice};
+
+/// Decides which file parts of a request are not worth ingesting
+pub(crate) struct UploadFilter<'a> {
+    state: &'a AppState,
+    /// Files announced in the options, by filename
+    declared: HashMap<String, FileCheckItem>,
+    /// Filenames of the parts received so far, by content hash
+    received: HashMap<String, String>,
+}
+
+impl<'a> UploadFilter<'a> {
+    pub(crate) fn new(state: &'a AppState) -> Self {
+        Self {
+            state,
+            declared: HashMap::new(),
+            received: HashMap::new(),
+        }
+    }
+
+    /// Take the files announced in the options
+    pub(crate) fn declare(&mut self, files: Vec<FileCheckItem>) {
+        self.declared
+            .extend(files.into_iter().map(|item| (item.filename.clone(), item)));
+    }
+
+    /// Why to drop a part before reading it, if at all
+    pub(crate) fn skip_unread(&self, filename: &str) -> Option<String> {
+        let item = self.declared.get(filename)?;
+        match check_single_file(self.state, item).0 {
+            FileUploadAdvice::Skip { reason, .. } => Some(reason),
+            FileUploadAdvice::Upload | FileUploadAdvice::Retry { .. } => None,
+        }
+    }
+
+    /// Why to drop a part once its contents are hashed, if at all
+    pub(crate) fn skip_received(&mut self, filename: &str, hash: &str) -> Option<String> {
+        match self.received.get(hash) {
+            Some(earlier) => Some(format!("Same content as '{}' in this upload", earlier)),
+            None => {
+                self.received.insert(hash.to_string(), filename.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// Stream a file part into the spool
+pub(crate) async fn spool_field(field: &mut Field<'_>, spool: &SpoolStore) -> Result<SpoolWriter> {
+    let mut writer = spool.writer().await?;
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to read file: {}", e)))?
+    {
+        writer.write(&chunk).await?;
+    }
+    Ok(writer)
+}
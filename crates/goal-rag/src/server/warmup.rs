@@ -0,0 +1,141 @@
+//! Startup warm-up before the server reports ready
+//!
+//! With a large corpus the first queries after a start were slow: SQLite
+//! pages of the chunk and full-text tables were still on disk, the vector
+//! index hadn't been touched and providers had yet to open connections or
+//! load their models. The server now starts not ready, runs one of each step
+//! a query takes (read the chunk tables, embed a canary query, search the
+//! vector index and the full-text index with it, generate a short reply) and
+//! only then lets `/ready` pass. Failed steps are reported but don't hold
+//! readiness back; the dependency checks decide whether a component is down.
+
+use std::future::Future;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::WarmupConfig;
+use crate::error::{Error, Result};
+use crate::types::query::StringSearchMode;
+
+use super::state::AppState;
+
+/// Results taken from each search
+const WARMUP_TOP_K: usize = 10;
+
+/// One warm-up step
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WarmupStep {
+    /// `chunks`, `embedding`, `vector_search`, `string_search` or `generation`
+    pub step: String,
+    pub ok: bool,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Progress of the startup warm-up
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WarmupReport {
+    pub running: bool,
+    pub steps: Vec<WarmupStep>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Whether the warm-up was cut short by `timeout_secs`
+    pub timed_out: bool,
+}
+
+impl WarmupReport {
+    pub fn new() -> Self {
+        Self {
+            running: true,
+            steps: Vec::new(),
+            started_at: Utc::now(),
+            completed_at: None,
+            timed_out: false,
+        }
+    }
+}
+
+impl Default for WarmupReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run the warm-up steps, reporting each to `record` as it finishes
+pub async fn warm_up(state: &AppState, config: &WarmupConfig, mut record: impl FnMut(WarmupStep)) {
+    let database = state.database().clone();
+    record(
+        step("chunks", async move {
+            let chunks = tokio::task::spawn_blocking(move || database.preload_chunks())
+                .await
+                .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+            Ok(format!("{} chunks", chunks))
+        })
+        .await,
+    );
+
+    let mut embedding = None;
+    record(
+        step("embedding", async {
+            let vector = state.embedding_provider().embed(&config.canary_query).await?;
+            let detail = format!("{} dimensions", vector.len());
+            embedding = Some(vector);
+            Ok(detail)
+        })
+        .await,
+    );
+
+    if let Some(embedding) = embedding {
+        record(
+            step("vector_search", async {
+                let results = state.vector_store_provider().search(&embedding, WARMUP_TOP_K, None).await?;
+                Ok(format!("{} results", results.len()))
+            })
+            .await,
+        );
+    }
+
+    record(
+        step("string_search", async {
+            let results = state
+                .vector_store_provider()
+                .string_search(&config.canary_query, WARMUP_TOP_K, None, StringSearchMode::Prefix)
+                .await?;
+            Ok(format!("{} results", results.len()))
+        })
+        .await,
+    );
+
+    if config.generate {
+        record(
+            step("generation", async {
+                let llm = state.llm_provider();
+                llm.complete("Reply with the single word OK.").await?;
+                Ok(llm.model().to_string())
+            })
+            .await,
+        );
+    }
+}
+
+async fn step(name: &str, run: impl Future<Output = Result<String>>) -> WarmupStep {
+    let started = Instant::now();
+    let result = run.await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(detail) => {
+            tracing::info!("Warm-up: {} took {}ms ({})", name, duration_ms, detail);
+            WarmupStep { step: name.to_string(), ok: true, duration_ms, detail: Some(detail), error: None }
+        }
+        Err(e) => {
+            tracing::warn!("Warm-up: {} failed after {}ms: {}", name, duration_ms, e);
+            WarmupStep { step: name.to_string(), ok: false, duration_ms, detail: None, error: Some(e.to_string()) }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Webhook notifications
+//!
+//! Document, job, answer cache and report events are POSTed as JSON to the
+//! configured and registered endpoints. Each delivery carries the event name,
+//! id and timestamp in headers, and an HMAC-SHA256 signature of
+//! `"{timestamp}.{body}"` when the endpoint has a secret. Deliveries run in
+//! the background and are retried with exponential backoff on network
+//! errors, 429 and 5xx responses.
+//!
+//! Endpoints registered through the API are only sent document events for
+//! documents their owner can read, and may not resolve to private, loopback
+//! or link-local addresses unless their host is in `webhooks.allowed_hosts`.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use parking_lot::RwLock;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::{WebhookEndpoint, WebhooksConfig};
+use crate::error::{Error, Result};
+use crate::storage::FileRegistryDb;
+use crate::types::{Document, Principal};
+
+/// Header with the event name
+const EVENT_HEADER: &str = "x-webhook-event";
+/// Header with the event id (stable across retries, for deduplication)
+const ID_HEADER: &str = "x-webhook-id";
+/// Header with the signing timestamp (Unix seconds)
+const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+/// Header with `sha256=<hex HMAC>`
+const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// Longest delay between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Events sent to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEventKind {
+    #[serde(rename = "document.ingested")]
+    DocumentIngested,
+    #[serde(rename = "document.updated")]
+    DocumentUpdated,
+    #[serde(rename = "document.deleted")]
+    DocumentDeleted,
+    #[serde(rename = "job.completed")]
+    JobCompleted,
+    #[serde(rename = "job.failed")]
+    JobFailed,
+    #[serde(rename = "cache.invalidated")]
+    CacheInvalidated,
+    #[serde(rename = "report.generated")]
+    ReportGenerated,
+}
+
+impl WebhookEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DocumentIngested => "document.ingested",
+            Self::DocumentUpdated => "document.updated",
+            Self::DocumentDeleted => "document.deleted",
+            Self::JobCompleted => "job.completed",
+            Self::JobFailed => "job.failed",
+            Self::CacheInvalidated => "cache.invalidated",
+            Self::ReportGenerated => "report.generated",
+        }
+    }
+}
+
+/// Body of a webhook delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    pub event: WebhookEventKind,
+    pub created_at: DateTime<Utc>,
+    /// Event details (document summary, job counts, ...)
+    pub data: serde_json::Value,
+    /// Access list of the document the event is about
+    #[serde(skip)]
+    pub acl: Option<Vec<String>>,
+}
+
+impl WebhookEvent {
+    pub fn new(event: WebhookEventKind, data: serde_json::Value) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event,
+            created_at: Utc::now(),
+            data,
+            acl: None,
+        }
+    }
+
+    /// Event about a document
+    pub fn document(event: WebhookEventKind, doc: &Document) -> Self {
+        let mut webhook_event = Self::new(
+            event,
+            serde_json::json!({
+                "document_id": doc.id,
+                "filename": doc.filename,
+                "file_type": doc.file_type,
+                "content_hash": doc.content_hash,
+                "total_chunks": doc.total_chunks,
+                "collection": doc.collection(),
+            }),
+        );
+        webhook_event.acl = Some(doc.acl().into_iter().map(String::from).collect());
+        webhook_event
+    }
+
+    /// Whether an endpoint registered by `owner` may receive the event
+    fn visible_to(&self, owner: Option<&Principal>) -> bool {
+        match (&self.acl, owner) {
+            (Some(acl), Some(owner)) => owner.can_read_acl(acl),
+            _ => true,
+        }
+    }
+}
+
+/// An endpoint registered through the API
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegisteredWebhook {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub created_at: DateTime<Utc>,
+    /// Principal that registered the endpoint (stored separately)
+    #[serde(skip)]
+    pub owner: Option<Principal>,
+}
+
+/// Sends events to webhook endpoints
+pub struct Webhooks {
+    client: Client,
+    config: WebhooksConfig,
+    database: Arc<FileRegistryDb>,
+    registered: RwLock<Vec<RegisteredWebhook>>,
+}
+
+impl Webhooks {
+    /// Create the dispatcher, loading registered endpoints from the database
+    pub fn new(config: WebhooksConfig, database: Arc<FileRegistryDb>) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        let registered = database.list_webhooks()?;
+        Ok(Self {
+            client,
+            config,
+            database,
+            registered: RwLock::new(registered),
+        })
+    }
+
+    /// Number of endpoints (configured and registered)
+    pub fn endpoint_count(&self) -> usize {
+        self.config.endpoints.len() + self.registered.read().len()
+    }
+
+    /// Endpoints registered through the API
+    pub fn registered(&self) -> Vec<RegisteredWebhook> {
+        self.registered.read().clone()
+    }
+
+    /// Register an endpoint on behalf of `owner`
+    pub async fn register(&self, endpoint: WebhookEndpoint, owner: Option<Principal>) -> Result<RegisteredWebhook> {
+        check_target(&endpoint.url, &self.config.allowed_hosts).await?;
+        let webhook = RegisteredWebhook {
+            id: Uuid::new_v4(),
+            endpoint,
+            created_at: Utc::now(),
+            owner,
+        };
+        self.database.insert_webhook(&webhook)?;
+        self.registered.write().push(webhook.clone());
+        tracing::info!("Registered webhook {} -> {}", webhook.id, webhook.endpoint.url);
+        Ok(webhook)
+    }
+
+    /// Remove a registered endpoint, returning whether it existed
+    pub fn unregister(&self, id: &Uuid) -> Result<bool> {
+        let removed = self.database.delete_webhook(id)?;
+        self.registered.write().retain(|w| w.id != *id);
+        Ok(removed)
+    }
+
+    /// Send an event to every endpoint subscribed to it (in the background)
+    pub fn emit(&self, event: WebhookEvent) {
+        // Registered endpoints are checked again before each delivery
+        let endpoints: Vec<(WebhookEndpoint, bool)> = self
+            .config
+            .endpoints
+            .iter()
+            .map(|e| (e.clone(), false))
+            .chain(
+                self.registered
+                    .read()
+                    .iter()
+                    .filter(|w| event.visible_to(w.owner.as_ref()))
+                    .map(|w| (w.endpoint.clone(), true)),
+            )
+            .filter(|(e, _)| e.events.is_empty() || e.events.contains(&event.event))
+            .collect();
+        if endpoints.is_empty() {
+            return;
+        }
+
+        let body = match serde_json::to_vec(&event) {
+            Ok(body) => Arc::new(body),
+            Err(e) => {
+                tracing::error!("Failed to serialize webhook event {}: {}", event.id, e);
+                return;
+            }
+        };
+
+        for (endpoint, registered) in endpoints {
+            let client = self.client.clone();
+            let body = body.clone();
+            let max_attempts = self.config.max_attempts.max(1);
+            let backoff = Duration::from_millis(self.config.initial_backoff_ms);
+            let allowed_hosts = self.config.allowed_hosts.clone();
+            let (id, kind) = (event.id, event.event);
+            tokio::spawn(async move {
+                // The host may resolve elsewhere than when it was registered
+                if registered {
+                    if let Err(e) = check_target(&endpoint.url, &allowed_hosts).await {
+                        tracing::warn!("Not delivering {} {} to {}: {}", kind.as_str(), id, endpoint.url, e);
+                        return;
+                    }
+                }
+                deliver(&client, &endpoint, id, kind, &body, max_attempts, backoff).await;
+            });
+        }
+    }
+}
+
+/// Deliver one event to one endpoint, retrying transient failures
+async fn deliver(
+    client: &Client,
+    endpoint: &WebhookEndpoint,
+    id: Uuid,
+    kind: WebhookEventKind,
+    body: &[u8],
+    max_attempts: u32,
+    mut backoff: Duration,
+) {
+    for attempt in 1..=max_attempts {
+        let timestamp = Utc::now().timestamp();
+        let mut request = client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, kind.as_str())
+            .header(ID_HEADER, id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string());
+        if let Some(ref secret) = endpoint.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, timestamp, body)));
+        }
+
+        let retryable = match request.body(body.to_vec()).send().await {
+            Ok(response) if response.status().is_success() => {
+                tracing::debug!("Delivered {} {} to {}", kind.as_str(), id, endpoint.url);
+                return;
+            }
+            Ok(response) => {
+                let status = response.status();
+                tracing::warn!(
+                    "Webhook {} returned {} for {} {} (attempt {}/{})",
+                    endpoint.url, status, kind.as_str(), id, attempt, max_attempts
+                );
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Webhook {} failed for {} {} (attempt {}/{}): {}",
+                    endpoint.url, kind.as_str(), id, attempt, max_attempts, e
+                );
+                true
+            }
+        };
+
+        if !retryable || attempt == max_attempts {
+            break;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    tracing::error!("Giving up on webhook {} for {} {}", endpoint.url, kind.as_str(), id);
+}
+
+/// Refuse an endpoint URL resolving to a non-public address
+///
+/// Hosts in `allowed_hosts` are accepted as they are.
+pub async fn check_target(url: &str, allowed_hosts: &[String]) -> Result<()> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| Error::Config(format!("Invalid webhook URL '{}': {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(Error::Config(format!("Webhook URL must be http or https: {}", url)));
+    }
+    // IPv6 literals are bracketed
+    let host = parsed
+        .host_str()
+        .map(|host| host.trim_matches(['[', ']']))
+        .ok_or_else(|| Error::Config(format!("Webhook URL has no host: {}", url)))?;
+    if allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+        return Ok(());
+    }
+
+    let addresses: Vec<IpAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            let port = parsed.port_or_known_default().unwrap_or(443);
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| Error::Config(format!("Webhook host {} does not resolve: {}", host, e)))?
+                .map(|address| address.ip())
+                .collect()
+        }
+    };
+    match addresses.into_iter().find(|ip| !is_public(*ip)) {
+        Some(ip) => Err(Error::Config(format!(
+            "Webhook URL {} resolves to the non-public address {} (add the host to webhooks.allowed_hosts)",
+            url, ip
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether an address is reachable on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT (100.64.0.0/10)
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                !(ip.is_loopback() || ip.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization_and_signature() {
+        let event = WebhookEvent::new(WebhookEventKind::CacheInvalidated, serde_json::json!({ "answers": 2 }));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "cache.invalidated");
+        assert_eq!(json["data"]["answers"], 2);
+
+        let kinds: Vec<WebhookEventKind> = serde_json::from_str(r#"["document.deleted", "job.failed"]"#).unwrap();
+        assert_eq!(kinds, vec![WebhookEventKind::DocumentDeleted, WebhookEventKind::JobFailed]);
+
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1_700_000_000, b"{}"),
+            "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_target() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]:8080/hook",
+            "http://[fe80::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "http://100.64.0.1/hook",
+            "ftp://8.8.8.8/hook",
+        ] {
+            assert!(check_target(url, &[]).await.is_err(), "{}", url);
+        }
+        assert!(check_target("https://8.8.8.8/hook", &[]).await.is_ok());
+        assert!(check_target("http://10.1.2.3/hook", &["10.1.2.3".to_string()]).await.is_ok());
+        assert!(check_target("http://[::1]/hook", &["::1".to_string()]).await.is_ok());
+    }
+
+    #[test]
+    fn test_document_events_follow_owner_access() {
+        let mut doc = Document::new("plan.pdf".into(), crate::types::FileType::Pdf, "hash".into(), 0);
+        doc.set_acl(&["engineering".to_string()]);
+        let event = WebhookEvent::document(WebhookEventKind::DocumentIngested, &doc);
+        assert!(serde_json::to_value(&event).unwrap().get("acl").is_none());
+
+        assert!(event.visible_to(None));
+        assert!(event.visible_to(Some(&Principal::new("alice", vec!["engineering".to_string()]))));
+        assert!(!event.visible_to(Some(&Principal::new("bob", vec!["sales".to_string()]))));
+
+        let job = WebhookEvent::new(WebhookEventKind::JobCompleted, serde_json::json!({}));
+        assert!(job.visible_to(Some(&Principal::new("bob", Vec::new()))));
+    }
+}
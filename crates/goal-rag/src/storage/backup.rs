@@ -0,0 +1,346 @@
+//! Export and import of the whole knowledge base
+//!
+//! An export is a tar stream, written one document at a time:
+//!
+//! - `manifest.json`: format version, export time, embedding model, counts
+//! - `documents/<id>/document.json`: the document
+//! - `documents/<id>/text.txt`: its plain text, rebuilt from its chunks
+//! - `documents/<id>/chunks.jsonl`: its chunks with their embeddings
+//! - `file_registry.jsonl`: the file records
+//!
+//! Neither side holds more than one document's chunks in memory. Imports
+//! accept the archive plain or gzipped; chunks whose embeddings came from
+//! another model than the collection's (or that were exported without
+//! vectors, from a Vertex AI backend) are embedded again. The plain text is
+//! for readers of the archive, the chunks are restored as they are.
+
+use std::io::{BufRead, BufReader, Read, Seek, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::error::{Error, Result};
+use crate::processing::reconstruct_text;
+use crate::server::state::AppState;
+use crate::types::response::ImportResponse;
+use crate::types::{Chunk, Document, FileRecord};
+
+/// Version of the archive layout
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const FILE_REGISTRY: &str = "file_registry.jsonl";
+
+/// File records restored at once
+const RECORD_BATCH: usize = 1000;
+
+/// First entry of an export
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Default embedding model of the exporting instance
+    pub embedding_model: String,
+    pub documents: usize,
+    pub file_records: usize,
+}
+
+/// Counts of an export
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub documents: usize,
+    pub chunks: usize,
+    pub file_records: usize,
+}
+
+/// Write the knowledge base to `writer` as a tar stream
+///
+/// Blocking: run it on a blocking thread.
+pub fn export_archive(state: &AppState, writer: impl Write) -> Result<ExportSummary> {
+    let documents = state.list_documents();
+    let file_records = state.list_file_records();
+    let vector_store = state.vector_store();
+    let mtime = Utc::now().timestamp().max(0) as u64;
+
+    let mut builder = tar::Builder::new(writer);
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        exported_at: Utc::now(),
+        embedding_model: state.embedding_model_for(None).to_string(),
+        documents: documents.len(),
+        file_records: file_records.len(),
+    };
+    append(&mut builder, MANIFEST, &serde_json::to_vec_pretty(&manifest)?, mtime)?;
+
+    let mut summary = ExportSummary::default();
+    for doc in &documents {
+        let stored = state.database().list_chunks_for_document(&doc.id)?;
+        let mut lines = Vec::new();
+        for record in &stored {
+            // Chunks come with their vectors from the local index; Vertex AI keeps them to itself
+            let chunk = match vector_store {
+                Some(ref store) => store.get_chunk(&record.id)?.unwrap_or_else(|| record.to_chunk()),
+                None => record.to_chunk(),
+            };
+            serde_json::to_writer(&mut lines, &chunk)?;
+            lines.push(b'\n');
+        }
+
+        let dir = format!("documents/{}", doc.id);
+        append(&mut builder, &format!("{}/document.json", dir), &serde_json::to_vec_pretty(doc)?, mtime)?;
+        let text = reconstruct_text(doc, &stored).content;
+        append(&mut builder, &format!("{}/text.txt", dir), text.as_bytes(), mtime)?;
+        append(&mut builder, &format!("{}/chunks.jsonl", dir), &lines, mtime)?;
+
+        summary.documents += 1;
+        summary.chunks += stored.len();
+    }
+
+    let mut lines = Vec::new();
+    for record in &file_records {
+        serde_json::to_writer(&mut lines, record)?;
+        lines.push(b'\n');
+    }
+    append(&mut builder, FILE_REGISTRY, &lines, mtime)?;
+    summary.file_records = file_records.len();
+
+    builder.into_inner()?.flush()?;
+    Ok(summary)
+}
+
+fn append<W: Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8], mtime: u64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// What the archive reader hands to the importer
+enum ImportItem {
+    Document(Document, Vec<Chunk>),
+    FileRecords(Vec<FileRecord>),
+}
+
+/// Restore an export written by [`export_archive`] from a file
+///
+/// Documents whose id or filename already exists are skipped, as are file
+/// records of filenames already registered.
+pub async fn import_archive(state: &AppState, path: &Path) -> Result<ImportResponse> {
+    let (tx, mut rx) = mpsc::channel(4);
+    let path = path.to_path_buf();
+    let reader = tokio::task::spawn_blocking(move || {
+        let result = std::fs::File::open(&path)
+            .map_err(Error::from)
+            .and_then(|file| read_archive(file, &tx));
+        if let Err(e) = result {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+
+    let mut response = ImportResponse::default();
+    while let Some(item) = rx.recv().await {
+        match item? {
+            ImportItem::Document(doc, chunks) => restore_document(state, doc, chunks, &mut response).await?,
+            ImportItem::FileRecords(records) => {
+                for record in records {
+                    if state.get_file_record(&record.filename).is_some() {
+                        response.skipped_file_records += 1;
+                        continue;
+                    }
+                    state.restore_file_record(record);
+                    response.file_records += 1;
+                }
+            }
+        }
+    }
+    reader.await.map_err(|e| Error::Internal(format!("Task join error: {}", e)))?;
+
+    tracing::info!(
+        "Imported {} documents ({} skipped), {} chunks ({} re-embedded) and {} file records",
+        response.documents,
+        response.skipped_documents.len(),
+        response.chunks,
+        response.reembedded_chunks,
+        response.file_records
+    );
+    Ok(response)
+}
+
+async fn restore_document(state: &AppState, doc: Document, mut chunks: Vec<Chunk>, response: &mut ImportResponse) -> Result<()> {
+    if state.get_document(&doc.id).is_some() || state.find_by_filename(&doc.filename).is_some() {
+        response.skipped_documents.push(doc.filename);
+        return Ok(());
+    }
+    check_chunks(&doc, &chunks)?;
+
+    let embedder = state.embedder_for(doc.collection());
+    let model = state.embedding_model_for(doc.collection());
+    for chunk in &mut chunks {
+        if chunk.embedding.is_empty() || chunk.embedding_model() != Some(model) {
+            chunk.set_embeddings(embedder.embed_parts(&chunk.content).await?);
+            chunk.set_embedding_model(model);
+            response.reembedded_chunks += 1;
+        }
+    }
+
+    // Nothing of the document is kept unless its vectors and chunks are all stored
+    let chunks: Arc<[Chunk]> = chunks.into();
+    let provider = state.vector_store_provider();
+    let stored = provider
+        .insert_chunks(Arc::clone(&chunks))
+        .await
+        .and_then(|()| state.try_store_chunks(&chunks));
+    if let Err(e) = stored {
+        if let Err(cleanup) = provider.delete_by_document(&doc.id).await {
+            tracing::warn!("Failed to remove vectors of {} after a failed import: {}", doc.filename, cleanup);
+        }
+        return Err(e);
+    }
+    response.chunks += chunks.len();
+    response.documents += 1;
+    state.add_document(doc);
+    Ok(())
+}
+
+/// Refuse chunks filed under a document they don't belong to
+fn check_chunks(doc: &Document, chunks: &[Chunk]) -> Result<()> {
+    match chunks.iter().find(|chunk| chunk.document_id != doc.id) {
+        Some(chunk) => Err(Error::Config(format!(
+            "Archive chunk {} of {} belongs to document {}",
+            chunk.id, doc.filename, chunk.document_id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Read an archive, plain or gzipped, and send its documents and file records
+fn read_archive(mut file: std::fs::File, tx: &mpsc::Sender<Result<ImportItem>>) -> Result<()> {
+    let mut magic = [0u8; 2];
+    let gzipped = file.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+    file.rewind()?;
+    let input: Box<dyn Read> = if gzipped {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    let send = |item: ImportItem| {
+        tx.blocking_send(Ok(item))
+            .map_err(|_| Error::Internal("Import was cancelled".to_string()))
+    };
+
+    let mut archive = tar::Archive::new(input);
+    let mut pending: Option<Document> = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+
+        if path == MANIFEST {
+            let manifest: Manifest = serde_json::from_reader(&mut entry)?;
+            if manifest.format_version > FORMAT_VERSION {
+                return Err(Error::Config(format!(
+                    "Archive format {} is newer than this server supports ({})",
+                    manifest.format_version, FORMAT_VERSION
+                )));
+            }
+        } else if path == FILE_REGISTRY {
+            let mut batch = Vec::new();
+            for line in BufReader::new(entry).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                batch.push(serde_json::from_str(&line)?);
+                if batch.len() == RECORD_BATCH {
+                    send(ImportItem::FileRecords(std::mem::take(&mut batch)))?;
+                }
+            }
+            if !batch.is_empty() {
+                send(ImportItem::FileRecords(batch))?;
+            }
+        } else if path.ends_with("/document.json") {
+            pending = Some(serde_json::from_reader(&mut entry)?);
+        } else if path.ends_with("/chunks.jsonl") {
+            let doc = pending
+                .take()
+                .ok_or_else(|| Error::Config(format!("{} comes before its document.json", path)))?;
+            let mut chunks = Vec::new();
+            for line in BufReader::new(entry).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    chunks.push(serde_json::from_str(&line)?);
+                }
+            }
+            send(ImportItem::Document(doc, chunks))?;
+        } else if !path.ends_with("/text.txt") {
+            tracing::warn!("Ignoring unknown archive entry {}", path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChunkSource, FileType};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_read_gzipped_archive() {
+        let doc = Document::new("notes.txt".to_string(), FileType::Txt, "hash".to_string(), 5);
+        let mut chunk = Chunk::new(doc.id, "hello".to_string(), ChunkSource::text("notes.txt".to_string()), 0, 5, 0);
+        chunk.embedding = vec![0.5, 0.5];
+        let record = FileRecord::success("notes.txt".to_string(), "hash".to_string(), 5, FileType::Txt, doc.id, 1, None);
+
+        let spool = tempfile::NamedTempFile::new().unwrap();
+        let gzip = flate2::write::GzEncoder::new(spool.reopen().unwrap(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(gzip);
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            exported_at: Utc::now(),
+            embedding_model: "model".to_string(),
+            documents: 1,
+            file_records: 1,
+        };
+        append(&mut builder, MANIFEST, &serde_json::to_vec(&manifest).unwrap(), 0).unwrap();
+        let dir = format!("documents/{}", doc.id);
+        append(&mut builder, &format!("{}/document.json", dir), &serde_json::to_vec(&doc).unwrap(), 0).unwrap();
+        append(&mut builder, &format!("{}/chunks.jsonl", dir), &serde_json::to_vec(&chunk).unwrap(), 0).unwrap();
+        append(&mut builder, FILE_REGISTRY, &serde_json::to_vec(&record).unwrap(), 0).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        read_archive(spool.reopen().unwrap(), &tx).unwrap();
+        drop(tx);
+
+        match rx.blocking_recv().unwrap().unwrap() {
+            ImportItem::Document(restored, chunks) => {
+                assert_eq!(restored.id, doc.id);
+                assert_eq!(chunks.len(), 1);
+                assert_eq!(chunks[0].embedding, vec![0.5, 0.5]);
+            }
+            ImportItem::FileRecords(_) => panic!("expected the document first"),
+        }
+        match rx.blocking_recv().unwrap().unwrap() {
+            ImportItem::FileRecords(records) => assert_eq!(records[0].filename, "notes.txt"),
+            ImportItem::Document(..) => panic!("expected the file records"),
+        }
+        assert!(rx.blocking_recv().is_none());
+    }
+
+    #[test]
+    fn test_chunks_of_another_document_are_refused() {
+        let doc = Document::new("notes.txt".to_string(), FileType::Txt, "hash".to_string(), 5);
+        let own = Chunk::new(doc.id, "hello".to_string(), ChunkSource::text("notes.txt".to_string()), 0, 5, 0);
+        let other = Chunk::new(Uuid::new_v4(), "other".to_string(), ChunkSource::text("other.txt".to_string()), 0, 5, 0);
+
+        assert!(check_chunks(&doc, &[own.clone()]).is_ok());
+        assert!(check_chunks(&doc, &[own, other]).is_err());
+    }
+}
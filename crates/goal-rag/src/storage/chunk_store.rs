@@ -0,0 +1,321 @@
+//! Chunk store for metadata lookups of search results
+//!
+//! Vertex AI returns bare chunk ids, so ingested chunks were kept in a map in
+//! process memory with their text, metadata and vectors — several gigabytes
+//! for a corpus of millions of chunks. The store now keeps them on disk: the
+//! text stays in `chunks_content`, the rest of the chunk is stored as JSON in
+//! `chunk_store`, and vectors are appended to a flat file of little-endian
+//! `f32`s that is memory-mapped for reads. Recently read chunks are held in a
+//! small LRU cache (`vector_db.chunk_cache_entries`).
+//!
+//! The vector file is only appended to; vectors of replaced or deleted chunks
+//! stay in it until the embeddings are cleared on a reindex switch-over.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use parking_lot::{Mutex, RwLock};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::types::document::COLLECTION_METADATA_KEY;
+use crate::types::Chunk;
+
+use super::database::FileRegistryDb;
+
+const F32_BYTES: usize = std::mem::size_of::<f32>();
+
+/// Chunks on disk with an LRU cache of hot chunks
+pub struct ChunkStore {
+    database: Arc<FileRegistryDb>,
+    vectors: VectorFile,
+    cache: Mutex<ChunkCache>,
+    /// Held while vectors are appended and their rows written, and while
+    /// they are cleared, so no row can point into a truncated file
+    writes: Mutex<()>,
+}
+
+impl ChunkStore {
+    /// Open the store, with its vector file at `vector_path`
+    pub fn open(database: Arc<FileRegistryDb>, vector_path: &Path, cache_entries: usize) -> Result<Self> {
+        Ok(Self {
+            database,
+            vectors: VectorFile::open(vector_path)?,
+            cache: Mutex::new(ChunkCache::new(cache_entries)),
+            writes: Mutex::new(()),
+        })
+    }
+
+    /// Store chunks, replacing earlier versions
+    pub fn store(&self, chunks: &[Chunk]) -> Result<()> {
+        let _writes = self.writes.lock();
+        let mut rows = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let vectors = if chunk.embedding.is_empty() {
+                None
+            } else {
+                let all: Vec<&[f32]> = std::iter::once(chunk.embedding.as_slice())
+                    .chain(chunk.sub_embeddings.iter().map(Vec::as_slice))
+                    .collect();
+                Some((self.vectors.append(&all)?, chunk.embedding.len(), all.len()))
+            };
+            // Text and vectors are stored apart (and not copied to get rid of them)
+            let bare = Chunk {
+                id: chunk.id,
+                document_id: chunk.document_id,
+                content: String::new(),
+                embedding: Vec::new(),
+                source: chunk.source.clone(),
+                char_start: chunk.char_start,
+                char_end: chunk.char_end,
+                chunk_index: chunk.chunk_index,
+                metadata: chunk.metadata.clone(),
+                sub_embeddings: Vec::new(),
+                parent_id: chunk.parent_id,
+            };
+            rows.push((chunk.id, serde_json::to_string(&bare)?, vectors));
+        }
+        self.database.put_chunk_store_rows(&rows)?;
+
+        let mut cache = self.cache.lock();
+        for chunk in chunks {
+            cache.remove(&chunk.id);
+        }
+        Ok(())
+    }
+
+    /// A chunk by id, `None` once its text is gone from `chunks_content`
+    ///
+    /// Chunks ingested before the store existed are rebuilt from their
+    /// `chunks_content` row (without metadata or vectors).
+    pub fn get(&self, id: &Uuid) -> Result<Option<Chunk>> {
+        if let Some(chunk) = self.cache.lock().get(id) {
+            return Ok(Some(chunk));
+        }
+
+        let Some(record) = self.database.get_chunk_content(id)? else {
+            return Ok(None);
+        };
+        let chunk = match self.database.chunk_store_row(id)? {
+            Some((chunk_json, vectors)) => {
+                let mut chunk: Chunk = serde_json::from_str(&chunk_json)?;
+                chunk.content = record.content;
+                if let Some((offset, dims, count)) = vectors {
+                    let mut vectors = self.vectors.read(offset, dims, count)?.into_iter();
+                    chunk.embedding = vectors.next().unwrap_or_default();
+                    chunk.sub_embeddings = vectors.collect();
+                }
+                chunk
+            }
+            None => {
+                let mut chunk = record.to_chunk();
+                if let Some(ref collection) = record.collection {
+                    chunk.metadata.insert(COLLECTION_METADATA_KEY.to_string(), serde_json::json!(collection));
+                }
+                chunk
+            }
+        };
+
+        self.cache.lock().insert(chunk.clone());
+        Ok(Some(chunk))
+    }
+
+    /// Drop cached chunks of a deleted document
+    pub fn forget_document(&self, document_id: &Uuid) {
+        self.cache.lock().retain(|chunk| chunk.document_id != *document_id);
+    }
+
+    /// Drop cached chunks
+    pub fn clear_cache(&self) {
+        self.cache.lock().clear();
+    }
+
+    /// Forget every stored vector (they belong to another embedding generation)
+    pub fn clear_embeddings(&self) -> Result<()> {
+        let _writes = self.writes.lock();
+        self.clear_cache();
+        self.database.clear_chunk_store_vectors()?;
+        self.vectors.truncate()
+    }
+}
+
+/// Append-only file of `f32` vectors, memory-mapped for reads
+struct VectorFile {
+    path: PathBuf,
+    writer: Mutex<File>,
+    /// Mapping of the file as of the last read past its end
+    map: RwLock<Option<Mmap>>,
+}
+
+impl VectorFile {
+    fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let writer = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Mutex::new(writer),
+            map: RwLock::new(None),
+        })
+    }
+
+    /// Append vectors of equal length, returning the byte offset of the first
+    fn append(&self, vectors: &[&[f32]]) -> Result<u64> {
+        let mut bytes = Vec::with_capacity(vectors.iter().map(|v| v.len() * F32_BYTES).sum());
+        for value in vectors.iter().flat_map(|v| v.iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut writer = self.writer.lock();
+        let offset = writer.metadata()?.len();
+        writer.write_all(&bytes)?;
+        Ok(offset)
+    }
+
+    /// Read `count` vectors of `dims` values starting at byte `offset`
+    fn read(&self, offset: u64, dims: usize, count: usize) -> Result<Vec<Vec<f32>>> {
+        let start = offset as usize;
+        let end = start + dims * count * F32_BYTES;
+        {
+            let map = self.map.read();
+            if let Some(map) = map.as_ref().filter(|map| map.len() >= end) {
+                return Ok(decode(&map[start..end], dims));
+            }
+        }
+
+        let mut map = self.map.write();
+        if map.as_ref().map_or(true, |map| map.len() < end) {
+            let file = File::open(&self.path)?;
+            // SAFETY: the file is only appended to, and truncated with this
+            // lock held after the mapping is dropped
+            *map = Some(unsafe { Mmap::map(&file)? });
+        }
+        match map.as_ref().filter(|map| map.len() >= end) {
+            Some(map) => Ok(decode(&map[start..end], dims)),
+            None => Err(Error::StorageCorruption(format!(
+                "Chunk vectors at {}..{} are past the end of {:?}",
+                start, end, self.path
+            ))),
+        }
+    }
+
+    fn truncate(&self) -> Result<()> {
+        let mut map = self.map.write();
+        *map = None;
+        self.writer.lock().set_len(0)?;
+        Ok(())
+    }
+}
+
+fn decode(bytes: &[u8], dims: usize) -> Vec<Vec<f32>> {
+    let values: Vec<f32> = bytes
+        .chunks_exact(F32_BYTES)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    values.chunks(dims.max(1)).map(<[f32]>::to_vec).collect()
+}
+
+/// Least recently used chunks
+struct ChunkCache {
+    capacity: usize,
+    next_use: u64,
+    entries: HashMap<Uuid, (Chunk, u64)>,
+    /// Chunk ids by last use
+    uses: BTreeMap<u64, Uuid>,
+}
+
+impl ChunkCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, next_use: 0, entries: HashMap::new(), uses: BTreeMap::new() }
+    }
+
+    fn get(&mut self, id: &Uuid) -> Option<Chunk> {
+        let use_id = self.next_use;
+        let (chunk, last_use) = self.entries.get_mut(id)?;
+        self.uses.remove(last_use);
+        *last_use = use_id;
+        self.uses.insert(use_id, *id);
+        self.next_use += 1;
+        Some(chunk.clone())
+    }
+
+    fn insert(&mut self, chunk: Chunk) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&chunk.id);
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.uses.pop_first() else { break };
+            self.entries.remove(&oldest);
+        }
+        self.uses.insert(self.next_use, chunk.id);
+        self.entries.insert(chunk.id, (chunk, self.next_use));
+        self.next_use += 1;
+    }
+
+    fn remove(&mut self, id: &Uuid) {
+        if let Some((_, last_use)) = self.entries.remove(id) {
+            self.uses.remove(&last_use);
+        }
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Chunk) -> bool) {
+        let uses = &mut self.uses;
+        self.entries.retain(|_, (chunk, last_use)| {
+            let kept = keep(chunk);
+            if !kept {
+                uses.remove(last_use);
+            }
+            kept
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.uses.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChunkSource;
+
+    fn chunk() -> Chunk {
+        Chunk::new(Uuid::new_v4(), "text".to_string(), ChunkSource::text("a.txt".to_string()), 0, 4, 0)
+    }
+
+    #[test]
+    fn test_vector_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = VectorFile::open(&dir.path().join("vectors.f32")).unwrap();
+        let first = file.append(&[&[1.0, 2.0]]).unwrap();
+        assert_eq!(file.read(first, 2, 1).unwrap(), vec![vec![1.0, 2.0]]);
+
+        // Appending past the mapped end remaps
+        let second = file.append(&[&[3.0, 4.0], &[5.0, 6.0]]).unwrap();
+        assert_eq!(second, 8);
+        assert_eq!(file.read(second, 2, 2).unwrap(), vec![vec![3.0, 4.0], vec![5.0, 6.0]]);
+        assert!(file.read(second, 2, 3).is_err());
+
+        file.truncate().unwrap();
+        assert_eq!(file.append(&[&[7.0]]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_chunk_cache_evicts_least_recently_used() {
+        let mut cache = ChunkCache::new(2);
+        let (a, b, c) = (chunk(), chunk(), chunk());
+        cache.insert(a.clone());
+        cache.insert(b.clone());
+        assert!(cache.get(&a.id).is_some());
+        cache.insert(c.clone());
+        assert!(cache.get(&b.id).is_none());
+        assert!(cache.get(&a.id).is_some() && cache.get(&c.id).is_some());
+    }
+}
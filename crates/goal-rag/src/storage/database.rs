@@ -4,27 +4,71 @@
 
 use chrono::{DateTime, Utc};
 use parking_lot::Mutex;
-use rusqlite::{Connection, params, OptionalExtension};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags, params, OptionalExtension};
+use rusqlite::types::Value;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 use uuid::Uuid;
 
+use super::encryption::EncryptionKey;
 use crate::error::{Error, Result};
-use crate::types::{FileRecord, FileRecordStatus, FileType};
+use crate::config::{BudgetScope, WebhookEndpoint};
+use crate::connectors::{ConnectorState, SyncedItem};
+use crate::generation::guard::{GuardEvent, GuardEventKind};
+use crate::learning::graph::{entity_key, GraphEdge, Triple};
+use crate::providers::usage::UsageRecord;
+use crate::server::audit::{AuditFilter, AuditRecord};
+use crate::server::reports::{Report, SavedQuery};
+use crate::server::webhooks::RegisteredWebhook;
+use crate::retrieval::{AnalyzerRegistry, RetrievalTrace};
+use crate::retrieval::string_search::StringMatcher;
+use crate::types::{Chunk, ChunkKind, Document, FileRecord, FileRecordStatus, FileType};
+use crate::types::query::StringSearchMode;
+
+/// Trigram candidates fetched per fuzzy search result
+const FUZZY_CANDIDATES: usize = 20;
+
+/// Chunks a regex search scans at most
+const REGEX_SCAN_CHUNKS: usize = 50_000;
+
+/// Tokens in FTS5 `snippet()` excerpts
+const SNIPPET_TOKENS: usize = 24;
+
+/// First bytes of an unencrypted SQLite database file
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
 
 /// SQLite-based file registry database
 pub struct FileRegistryDb {
     conn: Arc<Mutex<Connection>>,
+    /// Analyzers applied to chunk text for lexical search
+    analyzers: Arc<AnalyzerRegistry>,
+    /// SQLCipher key the database file is encrypted with
+    key: Option<EncryptionKey>,
 }
 
 impl FileRegistryDb {
     /// Create or open the database at the given path
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)
+        Self::open(path, None)
+    }
+
+    /// Create or open the database, encrypted with SQLCipher when a key is given
+    ///
+    /// An existing unencrypted database is encrypted in place first.
+    pub fn open<P: AsRef<Path>>(path: P, key: Option<&EncryptionKey>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(key) = key {
+            encrypt_plaintext_database(path, key)?;
+        }
+        let conn = open_keyed(path, key, OpenFlags::default())
             .map_err(|e| Error::Internal(format!("Failed to open database: {}", e)))?;
 
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
+            analyzers: Arc::new(AnalyzerRegistry::default()),
+            key: key.cloned(),
         };
 
         db.migrate()?;
@@ -39,12 +83,20 @@ impl FileRegistryDb {
 
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
+            analyzers: Arc::new(AnalyzerRegistry::default()),
+            key: None,
         };
 
         db.migrate()?;
         Ok(db)
     }
 
+    /// Use the given analyzers for FTS indexing and string search
+    pub fn with_analyzers(mut self, analyzers: AnalyzerRegistry) -> Self {
+        self.analyzers = Arc::new(analyzers);
+        self
+    }
+
     /// Run database migrations
     fn migrate(&self) -> Result<()> {
         let conn = self.conn.lock();
@@ -113,6 +165,19 @@ impl FileRegistryDb {
             -- Initialize sync status if not exists
             INSERT OR IGNORE INTO sync_status (id, last_gcs_sync, files_synced) VALUES (1, NULL, 0);
 
+            -- Generation of each GCS object as of its last sync
+            CREATE TABLE IF NOT EXISTS gcs_sync_objects (
+                object TEXT PRIMARY KEY,
+                generation INTEGER NOT NULL
+            );
+
+            -- Dimensions of the vectors in the index
+            CREATE TABLE IF NOT EXISTS embedding_index (
+                id INTEGER PRIMARY KEY,
+                dimensions INTEGER NOT NULL,
+                recorded_at TEXT NOT NULL
+            );
+
             -- Jobs table for job persistence and resumability
             CREATE TABLE IF NOT EXISTS jobs (
                 id TEXT PRIMARY KEY,
@@ -204,9 +269,293 @@ impl FileRegistryDb {
                 INSERT INTO chunks_fts(rowid, content, chunk_id, document_id, filename, file_type, page_number)
                 VALUES (NEW.rowid, NEW.content, NEW.id, NEW.document_id, NEW.filename, NEW.file_type, NEW.page_number);
             END;
+
+            -- Sampled retrieval traces for offline ranking analysis
+            CREATE TABLE IF NOT EXISTS retrieval_traces (
+                id TEXT PRIMARY KEY,
+                endpoint TEXT NOT NULL,
+                question TEXT NOT NULL,
+                top_k INTEGER NOT NULL,
+                similarity_threshold REAL NOT NULL,
+                candidates_json TEXT NOT NULL,
+                interaction_id TEXT,
+                latency_ms INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_retrieval_traces_created_at ON retrieval_traces(created_at);
+
+            -- Answer audit log (append-only; rows are only deleted by retention)
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id TEXT PRIMARY KEY,
+                endpoint TEXT NOT NULL,
+                question TEXT NOT NULL,
+                principal TEXT,
+                collection TEXT,
+                outcome TEXT NOT NULL,
+                chunks_json TEXT NOT NULL,
+                prompt_sha256 TEXT,
+                model TEXT,
+                answer TEXT,
+                error TEXT,
+                interaction_id TEXT,
+                latency_ms INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
+
+            CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log BEGIN
+                SELECT RAISE(ABORT, 'audit log is append-only');
+            END;
+
+            -- Prompt-injection guard audit log
+            CREATE TABLE IF NOT EXISTS guard_events (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                rules TEXT NOT NULL,
+                excerpt TEXT NOT NULL,
+                document_id TEXT,
+                principal TEXT,
+                blocked INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_guard_events_created_at ON guard_events(created_at);
+
+            -- Webhook endpoints registered through the API
+            CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                secret TEXT,
+                events_json TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            -- Chunks as stored by ingestion, without their text (in chunks_content)
+            -- and vectors (in the chunk store's vector file)
+            CREATE TABLE IF NOT EXISTS chunk_store (
+                chunk_id TEXT PRIMARY KEY,
+                chunk_json TEXT NOT NULL,
+                vector_offset INTEGER,
+                vector_dims INTEGER NOT NULL DEFAULT 0,
+                vector_count INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TRIGGER IF NOT EXISTS chunk_store_ad AFTER DELETE ON chunks_content BEGIN
+                DELETE FROM chunk_store WHERE chunk_id = OLD.id;
+            END;
+
+            -- Chunks whose embedding failed during ingestion, waiting for repair
+            CREATE TABLE IF NOT EXISTS pending_embeddings (
+                chunk_id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                chunk_json TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_pending_embeddings_document_id ON pending_embeddings(document_id);
         "#)
         .map_err(|e| Error::Internal(format!("Failed to run migrations: {}", e)))?;
 
+        // Collection column for per-collection analyzers (added after the initial schema)
+        add_column_if_missing(&conn, "chunks_content", "collection", "TEXT")?;
+        // Parent window of small-to-big chunks
+        add_column_if_missing(&conn, "chunks_content", "parent_id", "TEXT")?;
+        // Text or table chunk
+        add_column_if_missing(&conn, "chunks_content", "kind", "TEXT NOT NULL DEFAULT 'text'")?;
+        // Model that embedded the chunk (NULL for chunks stored before it was tracked)
+        add_column_if_missing(&conn, "chunks_content", "embedding_model", "TEXT")?;
+        // Principal that registered a webhook (NULL with auth disabled)
+        add_column_if_missing(&conn, "webhooks", "owner_json", "TEXT")?;
+        // Retry tracking and dead-lettering of job files
+        add_column_if_missing(&conn, "job_files", "attempts", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "job_files", "failed_stage", "TEXT")?;
+        add_column_if_missing(&conn, "job_files", "dead_letter", "INTEGER NOT NULL DEFAULT 0")?;
+        // Spool store key of the file contents (replaces the file_data blob)
+        add_column_if_missing(&conn, "job_files", "spool_hash", "TEXT")?;
+        // Input tokens read from a context cache
+        add_column_if_missing(&conn, "usage_daily", "cached_tokens", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Trigram index of the chunk text for fuzzy string search, filled from
+        // the existing chunks when first created
+        let has_trigram_index = conn
+            .query_row("SELECT 1 FROM sqlite_master WHERE name = 'chunks_trigram_fts'", [], |_| Ok(()))
+            .optional()
+            .map_err(|e| Error::Internal(format!("Failed to check trigram index: {}", e)))?
+            .is_some();
+        conn.execute_batch(r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS chunks_trigram_fts USING fts5(
+                content,
+                content='chunks_content',
+                content_rowid='rowid',
+                tokenize='trigram'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_trigram_ai AFTER INSERT ON chunks_content BEGIN
+                INSERT INTO chunks_trigram_fts(rowid, content) VALUES (NEW.rowid, NEW.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_trigram_ad AFTER DELETE ON chunks_content BEGIN
+                INSERT INTO chunks_trigram_fts(chunks_trigram_fts, rowid, content) VALUES ('delete', OLD.rowid, OLD.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_trigram_au AFTER UPDATE ON chunks_content BEGIN
+                INSERT INTO chunks_trigram_fts(chunks_trigram_fts, rowid, content) VALUES ('delete', OLD.rowid, OLD.content);
+                INSERT INTO chunks_trigram_fts(rowid, content) VALUES (NEW.rowid, NEW.content);
+            END;
+        "#).map_err(|e| Error::Internal(format!("Failed to create trigram index: {}", e)))?;
+        if !has_trigram_index {
+            conn.execute("INSERT INTO chunks_trigram_fts(chunks_trigram_fts) VALUES ('rebuild')", [])
+                .map_err(|e| Error::Internal(format!("Failed to build trigram index: {}", e)))?;
+        }
+
+        conn.execute_batch(r#"
+            CREATE INDEX IF NOT EXISTS idx_chunks_content_collection ON chunks_content(collection);
+            CREATE INDEX IF NOT EXISTS idx_chunks_content_parent_id ON chunks_content(parent_id);
+            CREATE INDEX IF NOT EXISTS idx_job_files_dead_letter ON job_files(dead_letter);
+            CREATE INDEX IF NOT EXISTS idx_job_files_spool_hash ON job_files(spool_hash);
+
+            -- Analyzed terms for chunks whose collection uses a non-standard analyzer
+            -- (rowid matches chunks_content.rowid)
+            CREATE VIRTUAL TABLE IF NOT EXISTS chunks_terms_fts USING fts5(
+                terms,
+                chunk_id UNINDEXED,
+                collection UNINDEXED
+            );
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_terms_ad AFTER DELETE ON chunks_content BEGIN
+                DELETE FROM chunks_terms_fts WHERE rowid = OLD.rowid;
+            END;
+
+            -- Extracted keywords and entities, one per line (rowid matches chunks_content.rowid)
+            CREATE VIRTUAL TABLE IF NOT EXISTS chunks_keywords_fts USING fts5(
+                keywords,
+                entities,
+                chunk_id UNINDEXED
+            );
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_keywords_ad AFTER DELETE ON chunks_content BEGIN
+                DELETE FROM chunks_keywords_fts WHERE rowid = OLD.rowid;
+            END;
+
+            -- Knowledge graph triples extracted from chunks (keys are normalized entity names)
+            CREATE TABLE IF NOT EXISTS graph_triples (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                subject TEXT NOT NULL,
+                relation TEXT NOT NULL,
+                object TEXT NOT NULL,
+                subject_key TEXT NOT NULL,
+                object_key TEXT NOT NULL,
+                chunk_id TEXT NOT NULL,
+                document_id TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_graph_triples_subject_key ON graph_triples(subject_key);
+            CREATE INDEX IF NOT EXISTS idx_graph_triples_object_key ON graph_triples(object_key);
+            CREATE INDEX IF NOT EXISTS idx_graph_triples_chunk_id ON graph_triples(chunk_id);
+
+            -- Chunks the graph builder has processed
+            CREATE TABLE IF NOT EXISTS graph_chunks (
+                chunk_id TEXT PRIMARY KEY,
+                triples INTEGER NOT NULL,
+                processed_at TEXT NOT NULL
+            );
+
+            CREATE TRIGGER IF NOT EXISTS chunks_content_graph_ad AFTER DELETE ON chunks_content BEGIN
+                DELETE FROM graph_triples WHERE chunk_id = OLD.id;
+                DELETE FROM graph_chunks WHERE chunk_id = OLD.id;
+            END;
+
+            -- Token usage per day ('' for calls without a collection or principal)
+            CREATE TABLE IF NOT EXISTS usage_daily (
+                day TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                collection TEXT NOT NULL DEFAULT '',
+                principal TEXT NOT NULL DEFAULT '',
+                calls INTEGER NOT NULL DEFAULT 0,
+                input_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                cost_usd REAL NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, provider, model, kind, collection, principal)
+            );
+
+            -- Documents replaced by a newer version of their file
+            CREATE TABLE IF NOT EXISTS document_versions (
+                document_id TEXT PRIMARY KEY,
+                filename TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                document_json TEXT NOT NULL,
+                superseded_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_document_versions_filename ON document_versions(filename, version);
+
+            -- Chunk text of superseded versions (not in the vector index or FTS)
+            CREATE TABLE IF NOT EXISTS document_version_chunks (
+                id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                file_type TEXT NOT NULL,
+                page_number INTEGER,
+                section_title TEXT,
+                char_start INTEGER NOT NULL,
+                char_end INTEGER NOT NULL,
+                collection TEXT,
+                parent_id TEXT,
+                kind TEXT NOT NULL DEFAULT 'text',
+                embedding_model TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_document_version_chunks_document_id ON document_version_chunks(document_id);
+
+            -- Saved queries (definition JSON) and the principal that saved them
+            CREATE TABLE IF NOT EXISTS saved_queries (
+                id TEXT PRIMARY KEY,
+                query_json TEXT NOT NULL,
+                owner_json TEXT,
+                created_at TEXT NOT NULL,
+                last_run_at TEXT
+            );
+
+            -- Reports generated by running saved queries
+            CREATE TABLE IF NOT EXISTS saved_query_reports (
+                id TEXT PRIMARY KEY,
+                saved_query_id TEXT NOT NULL,
+                report_json TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_saved_query_reports_query ON saved_query_reports(saved_query_id, created_at);
+
+            -- Cursor and last pass of each source connector
+            CREATE TABLE IF NOT EXISTS connector_state (
+                connector TEXT PRIMARY KEY,
+                cursor TEXT,
+                last_run_at TEXT,
+                last_error TEXT
+            );
+
+            -- Files synced by each connector and the documents they were ingested as
+            CREATE TABLE IF NOT EXISTS connector_items (
+                connector TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                version TEXT,
+                document_id TEXT,
+                synced_at TEXT NOT NULL,
+                PRIMARY KEY (connector, item_id)
+            );
+        "#)
+        .map_err(|e| Error::Internal(format!("Failed to run analyzer migrations: {}", e)))?;
+
         tracing::info!("Database migrations complete");
         Ok(())
     }
@@ -354,6 +703,30 @@ impl FileRegistryDb {
         Ok(count)
     }
 
+    /// Check that the database takes writes: take the write lock and let go
+    pub fn check_writable(&self) -> Result<()> {
+        self.conn
+            .lock()
+            .execute_batch("BEGIN IMMEDIATE; ROLLBACK;")
+            .map_err(|e| Error::Internal(format!("Database not writable: {}", e)))
+    }
+
+    /// Read through the chunk tables so their pages are cached before the
+    /// first query, returning the number of chunks
+    pub fn preload_chunks(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let chunks: i64 = conn
+            .query_row(
+                "SELECT COUNT(*), SUM(LENGTH(content)), SUM(LENGTH(filename)) FROM chunks_content",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| Error::Internal(format!("Failed to read chunks: {}", e)))?;
+        conn.query_row("SELECT SUM(LENGTH(block)) FROM chunks_fts_data", [], |row| row.get::<_, Option<i64>>(0))
+            .map_err(|e| Error::Internal(format!("Failed to read full-text index: {}", e)))?;
+        Ok(chunks as usize)
+    }
+
     /// Get file registry statistics
     pub fn get_stats(&self) -> Result<FileRegistryDbStats> {
         let conn = self.conn.lock();
@@ -390,6 +763,106 @@ impl FileRegistryDb {
         })
     }
 
+    /// A page of file records matching the query, with the number of matches
+    pub fn list_file_records_page(&self, query: &RegistryQuery<FileSort>) -> Result<RegistryPage<FileRecord>> {
+        let mut filters = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(ref status) = query.status {
+            filters.push("status = ?".to_string());
+            values.push(Value::Text(status.clone()));
+        }
+        if let Some(ref filename) = query.filename {
+            filters.push("filename LIKE ? ESCAPE '\\'".to_string());
+            values.push(Value::Text(like_pattern(filename)));
+        }
+        if !query.hidden_documents.is_empty() {
+            let placeholders = vec!["?"; query.hidden_documents.len()].join(", ");
+            filters.push(format!("(document_id IS NULL OR document_id NOT IN ({}))", placeholders));
+            values.extend(query.hidden_documents.iter().map(|id| Value::Text(id.to_string())));
+        }
+
+        let column = query.sort.column();
+        let after = query.after.as_ref().map(|(key, id)| {
+            let key = match query.sort {
+                FileSort::Size => Value::Integer(key.parse().unwrap_or(0)),
+                _ => Value::Text(key.clone()),
+            };
+            (key, Value::Text(id.clone()))
+        });
+        self.query_page(
+            "file_registry",
+            &filters,
+            values,
+            (column, "filename"),
+            after,
+            query,
+            row_to_file_record,
+        )
+    }
+
+    /// Count the rows matching `filters`, then select a page of them ordered
+    /// by `(sort column, id column)`
+    #[allow(clippy::too_many_arguments)]
+    fn query_page<T, S>(
+        &self,
+        table: &str,
+        filters: &[String],
+        values: Vec<Value>,
+        (column, id_column): (&str, &str),
+        after: Option<(Value, Value)>,
+        query: &RegistryQuery<S>,
+        map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+    ) -> Result<RegistryPage<T>> {
+        let conn = self.conn.lock();
+        let clause = |filters: &[String]| {
+            if filters.is_empty() { String::new() } else { format!(" WHERE {}", filters.join(" AND ")) }
+        };
+
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {}{}", table, clause(filters)),
+                rusqlite::params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(|e| Error::Internal(format!("Failed to count {}: {}", table, e)))?;
+
+        // Keyset pagination continues after the cursor; OFFSET applies otherwise
+        let (direction, comparison) = if query.descending { ("DESC", "<") } else { ("ASC", ">") };
+        let mut filters = filters.to_vec();
+        let mut values = values;
+        let offset = match after {
+            Some((key, id)) => {
+                filters.push(format!("({}, {}) {} (?, ?)", column, id_column, comparison));
+                values.push(key);
+                values.push(id);
+                0
+            }
+            None => query.offset,
+        };
+        // One extra row tells whether there's a next page
+        values.push(Value::Integer(query.limit.saturating_add(1).min(i64::MAX as usize) as i64));
+        values.push(Value::Integer(offset.min(i64::MAX as usize) as i64));
+
+        let sql = format!(
+            "SELECT * FROM {}{} ORDER BY {} {dir}, {} {dir} LIMIT ? OFFSET ?",
+            table,
+            clause(&filters),
+            column,
+            id_column,
+            dir = direction
+        );
+        let mut stmt = conn.prepare(&sql)
+            .map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+        let mut items: Vec<T> = stmt.query_map(rusqlite::params_from_iter(values.iter()), map)
+            .map_err(|e| Error::Internal(format!("Failed to list {}: {}", table, e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let has_more = items.len() > query.limit;
+        items.truncate(query.limit);
+        Ok(RegistryPage { items, total: total as usize, has_more })
+    }
+
     // ==================== Job Persistence Operations ====================
 
     /// Create a new job record
@@ -516,6 +989,43 @@ impl FileRegistryDb {
         Ok(records)
     }
 
+    /// A page of jobs matching the query, with the number of matches
+    ///
+    /// The filename filter matches the file being processed or any file of the job.
+    pub fn list_jobs_page(&self, query: &RegistryQuery<JobSort>) -> Result<RegistryPage<JobRecord>> {
+        let mut filters = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(ref status) = query.status {
+            filters.push("status = ?".to_string());
+            values.push(Value::Text(status.clone()));
+        }
+        if let Some(ref filename) = query.filename {
+            filters.push(
+                "(current_file LIKE ? ESCAPE '\\' OR EXISTS (SELECT 1 FROM job_files f \
+                 WHERE f.job_id = jobs.id AND f.filename LIKE ? ESCAPE '\\'))"
+                    .to_string(),
+            );
+            let pattern = like_pattern(filename);
+            values.push(Value::Text(pattern.clone()));
+            values.push(Value::Text(pattern));
+        }
+        if !query.hidden_documents.is_empty() {
+            let placeholders = vec!["?"; query.hidden_documents.len()].join(", ");
+            filters.push(format!(
+                "NOT EXISTS (SELECT 1 FROM job_files f JOIN file_registry r ON r.filename = f.filename \
+                 WHERE f.job_id = jobs.id AND r.document_id IN ({}))",
+                placeholders
+            ));
+            values.extend(query.hidden_documents.iter().map(|id| Value::Text(id.to_string())));
+        }
+
+        let after = query
+            .after
+            .as_ref()
+            .map(|(key, id)| (Value::Text(key.clone()), Value::Text(id.clone())));
+        self.query_page("jobs", &filters, values, (query.sort.column(), "id"), after, query, row_to_job_record)
+    }
+
     /// Delete old completed jobs (cleanup)
     pub fn cleanup_old_jobs(&self, days_to_keep: i64) -> Result<usize> {
         let conn = self.conn.lock();
@@ -540,8 +1050,8 @@ impl FileRegistryDb {
             r#"
             INSERT INTO job_files (
                 job_id, filename, file_size, content_hash, status, tier,
-                parser_method, error, started_at, completed_at, duration_ms, file_data
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                parser_method, error, started_at, completed_at, duration_ms, file_data, spool_hash
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
             ON CONFLICT(job_id, filename) DO UPDATE SET
                 status = excluded.status,
                 tier = excluded.tier,
@@ -564,12 +1074,25 @@ impl FileRegistryDb {
                 file.completed_at.map(|t| t.to_rfc3339()),
                 file.duration_ms.map(|d| d as i64),
                 file.file_data.as_deref(),
+                file.spool_hash,
             ],
         ).map_err(|e| Error::Internal(format!("Failed to add job file: {}", e)))?;
 
         Ok(())
     }
 
+    /// Point a job file at its spooled contents, dropping any stored blob
+    pub fn set_job_file_spooled(&self, job_id: Uuid, filename: &str, hash: &str) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "UPDATE job_files SET spool_hash = ?3, content_hash = ?3, file_data = NULL WHERE job_id = ?1 AND filename = ?2",
+            params![job_id.to_string(), filename, hash],
+        ).map_err(|e| Error::Internal(format!("Failed to update job file: {}", e)))?;
+
+        Ok(())
+    }
+
     /// Update job file status
     pub fn update_job_file_status(
         &self,
@@ -644,16 +1167,86 @@ impl FileRegistryDb {
         Ok(records)
     }
 
-    /// Clear file data blobs after processing (to save space)
-    pub fn clear_job_file_data(&self, job_id: Uuid) -> Result<()> {
+    /// Record a failed processing attempt of a job file
+    pub fn record_job_file_attempt(&self, job_id: Uuid, filename: &str, attempts: u32, error: &str) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "UPDATE job_files SET attempts = ?3, error = ?4 WHERE job_id = ?1 AND filename = ?2",
+            params![job_id.to_string(), filename, attempts as i64, error],
+        ).map_err(|e| Error::Internal(format!("Failed to record job file attempt: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Move a failed job file to the dead-letter list
+    pub fn dead_letter_job_file(&self, job_id: Uuid, filename: &str, stage: &str) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "UPDATE job_files SET dead_letter = 1, failed_stage = ?3 WHERE job_id = ?1 AND filename = ?2",
+            params![job_id.to_string(), filename, stage],
+        ).map_err(|e| Error::Internal(format!("Failed to dead-letter job file: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Files that exhausted their retries, most recent first
+    pub fn list_dead_letter_files(&self, limit: usize) -> Result<Vec<DeadLetterFile>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT job_id, filename, file_size, failed_stage, attempts, error, completed_at
+            FROM job_files
+            WHERE dead_letter = 1
+            ORDER BY completed_at DESC
+            LIMIT ?1
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let files = stmt.query_map(params![limit as i64], row_to_dead_letter_file)
+            .map_err(|e| Error::Internal(format!("Failed to list dead-letter files: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(files)
+    }
+
+    /// Clear file data blobs and spool references after processing
+    ///
+    /// Returns the spool hashes no job file references any more.
+    pub fn clear_job_file_data(&self, job_id: Uuid) -> Result<Vec<String>> {
         let conn = self.conn.lock();
 
+        let hashes: Vec<String> = conn
+            .prepare("SELECT DISTINCT spool_hash FROM job_files WHERE job_id = ?1 AND spool_hash IS NOT NULL")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![job_id.to_string()], |row| row.get(0))?
+                    .collect()
+            })
+            .map_err(|e| Error::Internal(format!("Failed to list spooled files: {}", e)))?;
+
         conn.execute(
-            "UPDATE job_files SET file_data = NULL WHERE job_id = ?1",
+            "UPDATE job_files SET file_data = NULL, spool_hash = NULL WHERE job_id = ?1",
             params![job_id.to_string()],
         ).map_err(|e| Error::Internal(format!("Failed to clear file data: {}", e)))?;
 
-        Ok(())
+        let mut released = Vec::new();
+        for hash in hashes {
+            let in_use: bool = conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM job_files WHERE spool_hash = ?1)",
+                    params![hash],
+                    |row| row.get(0),
+                )
+                .map_err(|e| Error::Internal(format!("Failed to check spooled file: {}", e)))?;
+            if !in_use {
+                released.push(hash);
+            }
+        }
+
+        Ok(released)
     }
 
     // ==================== GCS Sync Operations ====================
@@ -724,6 +1317,150 @@ impl FileRegistryDb {
         Ok(())
     }
 
+    /// Generations of the GCS objects as of their last sync
+    pub fn gcs_object_generations(&self) -> Result<HashMap<String, i64>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT object, generation FROM gcs_sync_objects")
+            .map_err(|e| Error::Internal(format!("Failed to prepare sync objects query: {}", e)))?;
+        let generations = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| Error::Internal(format!("Failed to query sync objects: {}", e)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()
+            .map_err(|e| Error::Internal(format!("Failed to read sync objects: {}", e)))?;
+        Ok(generations)
+    }
+
+    /// Record synced generations of GCS objects and forget removed ones
+    pub fn set_gcs_object_generations(&self, synced: &[(&str, i64)], removed: &[&str]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+        for (object, generation) in synced {
+            tx.execute(
+                "INSERT OR REPLACE INTO gcs_sync_objects (object, generation) VALUES (?1, ?2)",
+                params![object, generation],
+            ).map_err(|e| Error::Internal(format!("Failed to record sync object: {}", e)))?;
+        }
+        for object in removed {
+            tx.execute("DELETE FROM gcs_sync_objects WHERE object = ?1", params![object])
+                .map_err(|e| Error::Internal(format!("Failed to forget sync object: {}", e)))?;
+        }
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit sync objects: {}", e)))?;
+        Ok(())
+    }
+
+    /// Stored cursor and last pass of a connector (empty if it never ran)
+    pub fn connector_state(&self, connector: &str) -> Result<ConnectorState> {
+        let conn = self.conn.lock();
+        let state = conn
+            .query_row(
+                "SELECT cursor, last_run_at, last_error FROM connector_state WHERE connector = ?1",
+                params![connector],
+                |row| {
+                    Ok(ConnectorState {
+                        cursor: row.get(0)?,
+                        last_run_at: row.get::<_, Option<String>>(1)?
+                            .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                            .map(|at| at.with_timezone(&Utc)),
+                        last_error: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| Error::Internal(format!("Failed to read connector state: {}", e)))?;
+        Ok(state.unwrap_or_default())
+    }
+
+    /// Save the cursor and last pass of a connector
+    pub fn set_connector_state(&self, connector: &str, state: &ConnectorState) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO connector_state (connector, cursor, last_run_at, last_error) VALUES (?1, ?2, ?3, ?4)",
+            params![connector, state.cursor, state.last_run_at.map(|at| at.to_rfc3339()), state.last_error],
+        ).map_err(|e| Error::Internal(format!("Failed to save connector state: {}", e)))?;
+        Ok(())
+    }
+
+    /// Files synced by a connector, by their id at the source
+    pub fn connector_items(&self, connector: &str) -> Result<HashMap<String, SyncedItem>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT item_id, filename, version, document_id FROM connector_items WHERE connector = ?1")
+            .map_err(|e| Error::Internal(format!("Failed to prepare connector items query: {}", e)))?;
+        let items = stmt
+            .query_map(params![connector], |row| {
+                let item = SyncedItem {
+                    item_id: row.get(0)?,
+                    filename: row.get(1)?,
+                    version: row.get(2)?,
+                    document_id: row.get::<_, Option<String>>(3)?.and_then(|id| Uuid::parse_str(&id).ok()),
+                };
+                Ok((item.item_id.clone(), item))
+            })
+            .map_err(|e| Error::Internal(format!("Failed to query connector items: {}", e)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()
+            .map_err(|e| Error::Internal(format!("Failed to read connector items: {}", e)))?;
+        Ok(items)
+    }
+
+    /// Number of files synced by a connector
+    pub fn count_connector_items(&self, connector: &str) -> Result<usize> {
+        let conn = self.conn.lock();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM connector_items WHERE connector = ?1", params![connector], |row| row.get(0))
+            .map_err(|e| Error::Internal(format!("Failed to count connector items: {}", e)))?;
+        Ok(count as usize)
+    }
+
+    /// Record a file synced by a connector
+    pub fn upsert_connector_item(&self, connector: &str, item: &SyncedItem) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO connector_items (connector, item_id, filename, version, document_id, synced_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                connector,
+                item.item_id,
+                item.filename,
+                item.version,
+                item.document_id.map(|id| id.to_string()),
+                Utc::now().to_rfc3339(),
+            ],
+        ).map_err(|e| Error::Internal(format!("Failed to record connector item: {}", e)))?;
+        Ok(())
+    }
+
+    /// Forget a file removed from a connector's source
+    pub fn delete_connector_item(&self, connector: &str, item_id: &str) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "DELETE FROM connector_items WHERE connector = ?1 AND item_id = ?2",
+            params![connector, item_id],
+        ).map_err(|e| Error::Internal(format!("Failed to forget connector item: {}", e)))?;
+        Ok(())
+    }
+
+    /// Dimensions recorded for the vectors in the index
+    pub fn embedding_index_dimensions(&self) -> Result<Option<usize>> {
+        let conn = self.conn.lock();
+        conn.query_row("SELECT dimensions FROM embedding_index WHERE id = 1", [], |row| row.get::<_, i64>(0))
+            .optional()
+            .map(|dimensions| dimensions.map(|d| d as usize))
+            .map_err(|e| Error::Internal(format!("Failed to read embedding index dimensions: {}", e)))
+    }
+
+    /// Record the dimensions of the vectors in the index
+    pub fn set_embedding_index_dimensions(&self, dimensions: usize) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO embedding_index (id, dimensions, recorded_at) VALUES (1, ?1, ?2)",
+            params![dimensions as i64, Utc::now().to_rfc3339()],
+        ).map_err(|e| Error::Internal(format!("Failed to record embedding index dimensions: {}", e)))?;
+        Ok(())
+    }
+
     /// Get last sync status
     pub fn get_sync_status(&self) -> Result<Option<SyncStatus>> {
         let conn = self.conn.lock();
@@ -748,62 +1485,1434 @@ impl FileRegistryDb {
         Ok(status)
     }
 
-    // ==================== Chunk Content Operations (for FTS) ====================
+    // ==================== Retrieval Trace Operations ====================
 
-    /// Insert a chunk into the content table (triggers will sync to FTS)
-    pub fn insert_chunk_content(&self, chunk: &ChunkContentRecord) -> Result<()> {
+    /// Store a retrieval trace
+    pub fn insert_trace(&self, trace: &RetrievalTrace) -> Result<()> {
         let conn = self.conn.lock();
 
+        let candidates_json = serde_json::to_string(&trace.candidates)?;
+
         conn.execute(
             r#"
-            INSERT OR REPLACE INTO chunks_content (
-                id, document_id, chunk_index, content, filename, file_type,
-                page_number, section_title, char_start, char_end, created_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            INSERT OR REPLACE INTO retrieval_traces (
+                id, endpoint, question, top_k, similarity_threshold,
+                candidates_json, interaction_id, latency_ms, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
             "#,
             params![
-                chunk.id.to_string(),
-                chunk.document_id.to_string(),
-                chunk.chunk_index as i64,
-                chunk.content,
-                chunk.filename,
-                file_type_to_extension(&chunk.file_type),
-                chunk.page_number.map(|p| p as i64),
-                chunk.section_title,
-                chunk.char_start as i64,
-                chunk.char_end as i64,
-                Utc::now().to_rfc3339(),
+                trace.id.to_string(),
+                trace.endpoint,
+                trace.question,
+                trace.top_k as i64,
+                trace.similarity_threshold as f64,
+                candidates_json,
+                trace.interaction_id.map(|id| id.to_string()),
+                trace.latency_ms as i64,
+                trace.created_at.to_rfc3339(),
             ],
-        ).map_err(|e| Error::Internal(format!("Failed to insert chunk content: {}", e)))?;
+        ).map_err(|e| Error::Internal(format!("Failed to insert trace: {}", e)))?;
 
         Ok(())
     }
 
-    /// Insert multiple chunks (batch) with transaction for atomicity and performance
-    pub fn insert_chunks_content(&self, chunks: &[ChunkContentRecord]) -> Result<()> {
-        if chunks.is_empty() {
-            return Ok(());
-        }
+    /// List traces created at or after `since` (oldest first)
+    pub fn list_traces(&self, since: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<RetrievalTrace>> {
+        let conn = self.conn.lock();
 
-        let mut conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, endpoint, question, top_k, similarity_threshold,
+                   candidates_json, interaction_id, latency_ms, created_at
+            FROM retrieval_traces
+            WHERE ?1 IS NULL OR created_at >= ?1
+            ORDER BY created_at ASC
+            LIMIT ?2
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
 
-        // Use a transaction for better performance (10-50x faster for batch inserts)
-        let tx = conn.transaction()
-            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+        let traces = stmt.query_map(
+            params![since.map(|s| s.to_rfc3339()), limit as i64],
+            row_to_trace,
+        )
+        .map_err(|e| Error::Internal(format!("Failed to list traces: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
 
-        {
-            let mut stmt = tx.prepare(
+        Ok(traces)
+    }
+
+    /// Delete traces that retrieved chunks of the given documents
+    pub fn delete_traces_citing(&self, document_ids: &[Uuid]) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let mut deleted = 0;
+        for id in document_ids {
+            deleted += conn.execute(
+                "DELETE FROM retrieval_traces WHERE instr(candidates_json, ?1) > 0",
+                params![id.to_string()],
+            ).map_err(|e| Error::Internal(format!("Failed to delete traces: {}", e)))?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete traces older than N days
+    pub fn cleanup_old_traces(&self, days_to_keep: i64) -> Result<usize> {
+        let conn = self.conn.lock();
+        let cutoff = (Utc::now() - chrono::Duration::days(days_to_keep)).to_rfc3339();
+
+        let deleted = conn.execute(
+            "DELETE FROM retrieval_traces WHERE created_at < ?1",
+            params![cutoff],
+        ).map_err(|e| Error::Internal(format!("Failed to cleanup traces: {}", e)))?;
+
+        Ok(deleted)
+    }
+
+    // ==================== Answer Audit Operations ====================
+
+    /// Append an answer audit record
+    pub fn insert_audit_record(&self, record: &AuditRecord) -> Result<()> {
+        let conn = self.conn.lock();
+
+        let chunks_json = serde_json::to_string(&record.chunks)?;
+
+        conn.execute(
+            r#"
+            INSERT INTO audit_log (
+                id, endpoint, question, principal, collection, outcome, chunks_json,
+                prompt_sha256, model, answer, error, interaction_id, latency_ms, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            "#,
+            params![
+                record.id.to_string(),
+                record.endpoint,
+                record.question,
+                record.principal,
+                record.collection,
+                record.outcome,
+                chunks_json,
+                record.prompt_sha256,
+                record.model,
+                record.answer,
+                record.error,
+                record.interaction_id.map(|id| id.to_string()),
+                record.latency_ms as i64,
+                record.created_at.to_rfc3339(),
+            ],
+        ).map_err(|e| Error::Internal(format!("Failed to insert audit record: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// List audit records matching a filter (newest first)
+    pub fn list_audit_records(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, endpoint, question, principal, collection, outcome, chunks_json,
+                   prompt_sha256, model, answer, error, interaction_id, latency_ms, created_at
+            FROM audit_log
+            WHERE (?1 IS NULL OR created_at >= ?1)
+              AND (?2 IS NULL OR created_at < ?2)
+              AND (?3 IS NULL OR principal = ?3)
+              AND (?4 IS NULL OR interaction_id = ?4)
+            ORDER BY created_at DESC
+            LIMIT ?5
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let records = stmt.query_map(
+            params![
+                filter.since.map(|s| s.to_rfc3339()),
+                filter.until.map(|u| u.to_rfc3339()),
+                filter.principal,
+                filter.interaction_id.map(|id| id.to_string()),
+                filter.limit() as i64,
+            ],
+            row_to_audit_record,
+        )
+        .map_err(|e| Error::Internal(format!("Failed to list audit records: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(records)
+    }
+
+    /// Delete audit records that retrieved or cited chunks of the given documents
+    pub fn delete_audit_records_citing(&self, document_ids: &[Uuid]) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let mut deleted = 0;
+        for id in document_ids {
+            deleted += conn.execute(
+                "DELETE FROM audit_log WHERE instr(chunks_json, ?1) > 0",
+                params![id.to_string()],
+            ).map_err(|e| Error::Internal(format!("Failed to delete audit records: {}", e)))?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete audit records older than N days
+    pub fn cleanup_audit_log(&self, days_to_keep: i64) -> Result<usize> {
+        let conn = self.conn.lock();
+        let cutoff = (Utc::now() - chrono::Duration::days(days_to_keep)).to_rfc3339();
+
+        let deleted = conn.execute(
+            "DELETE FROM audit_log WHERE created_at < ?1",
+            params![cutoff],
+        ).map_err(|e| Error::Internal(format!("Failed to clean up audit log: {}", e)))?;
+
+        Ok(deleted)
+    }
+
+    // ==================== Guard Audit Operations ====================
+
+    /// Store guard events
+    pub fn insert_guard_events(&self, events: &[GuardEvent]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        for event in events {
+            tx.execute(
+                r#"
+                INSERT OR REPLACE INTO guard_events (
+                    id, kind, rules, excerpt, document_id, principal, blocked, created_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+                params![
+                    event.id.to_string(),
+                    event.kind.as_str(),
+                    event.rules.join(","),
+                    event.excerpt,
+                    event.document_id.map(|id| id.to_string()),
+                    event.principal,
+                    event.blocked,
+                    event.created_at.to_rfc3339(),
+                ],
+            ).map_err(|e| Error::Internal(format!("Failed to insert guard event: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit guard events: {}", e)))?;
+        Ok(())
+    }
+
+    /// List guard events created at or after `since` (newest first)
+    pub fn list_guard_events(&self, since: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<GuardEvent>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, kind, rules, excerpt, document_id, principal, blocked, created_at
+            FROM guard_events
+            WHERE ?1 IS NULL OR created_at >= ?1
+            ORDER BY created_at DESC
+            LIMIT ?2
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let events = stmt.query_map(
+            params![since.map(|s| s.to_rfc3339()), limit as i64],
+            row_to_guard_event,
+        )
+        .map_err(|e| Error::Internal(format!("Failed to list guard events: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(events)
+    }
+
+    /// Delete guard events (and their excerpts) of the given documents
+    pub fn delete_guard_events_for(&self, document_ids: &[Uuid]) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let mut deleted = 0;
+        for id in document_ids {
+            deleted += conn.execute(
+                "DELETE FROM guard_events WHERE document_id = ?1",
+                params![id.to_string()],
+            ).map_err(|e| Error::Internal(format!("Failed to delete guard events: {}", e)))?;
+        }
+
+        Ok(deleted)
+    }
+
+    // ==================== Webhook Operations ====================
+
+    /// Store a registered webhook
+    pub fn insert_webhook(&self, webhook: &RegisteredWebhook) -> Result<()> {
+        let conn = self.conn.lock();
+
+        let owner_json = webhook.owner.as_ref().map(serde_json::to_string).transpose()?;
+        conn.execute(
+            r#"
+            INSERT INTO webhooks (id, url, secret, events_json, created_at, owner_json)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                webhook.id.to_string(),
+                webhook.endpoint.url,
+                webhook.endpoint.secret,
+                serde_json::to_string(&webhook.endpoint.events)?,
+                webhook.created_at.to_rfc3339(),
+                owner_json,
+            ],
+        ).map_err(|e| Error::Internal(format!("Failed to insert webhook: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// List registered webhooks (oldest first)
+    pub fn list_webhooks(&self) -> Result<Vec<RegisteredWebhook>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, url, secret, events_json, created_at, owner_json FROM webhooks ORDER BY created_at ASC"
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let webhooks = stmt.query_map([], |row| {
+            let id: String = row.get(0)?;
+            let events_json: String = row.get(3)?;
+            let created_at: String = row.get(4)?;
+            let owner_json: Option<String> = row.get(5)?;
+            Ok(RegisteredWebhook {
+                id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
+                endpoint: WebhookEndpoint {
+                    url: row.get(1)?,
+                    secret: row.get(2)?,
+                    events: serde_json::from_str(&events_json).unwrap_or_default(),
+                },
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .map(|d| d.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                owner: owner_json.and_then(|json| serde_json::from_str(&json).ok()),
+            })
+        })
+        .map_err(|e| Error::Internal(format!("Failed to list webhooks: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(webhooks)
+    }
+
+    /// Delete a registered webhook, returning whether it existed
+    pub fn delete_webhook(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock();
+
+        let deleted = conn.execute(
+            "DELETE FROM webhooks WHERE id = ?1",
+            params![id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete webhook: {}", e)))?;
+
+        Ok(deleted > 0)
+    }
+
+    // ==================== Saved Query Operations ====================
+
+    /// Insert or replace a saved query
+    pub fn upsert_saved_query(&self, query: &SavedQuery) -> Result<()> {
+        let conn = self.conn.lock();
+
+        let owner_json = query.owner.as_ref().map(serde_json::to_string).transpose()?;
+        conn.execute(
+            r#"
+            INSERT INTO saved_queries (id, query_json, owner_json, created_at, last_run_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                query_json = excluded.query_json,
+                owner_json = excluded.owner_json,
+                last_run_at = excluded.last_run_at
+            "#,
+            params![
+                query.id.to_string(),
+                serde_json::to_string(query)?,
+                owner_json,
+                query.created_at.to_rfc3339(),
+                query.last_run_at.map(|t| t.to_rfc3339()),
+            ],
+        ).map_err(|e| Error::Internal(format!("Failed to save query: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// List saved queries (oldest first)
+    pub fn list_saved_queries(&self) -> Result<Vec<SavedQuery>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            "SELECT query_json, owner_json FROM saved_queries ORDER BY created_at ASC"
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let queries = stmt.query_map([], |row| {
+            let query_json: String = row.get(0)?;
+            let owner_json: Option<String> = row.get(1)?;
+            Ok((query_json, owner_json))
+        })
+        .map_err(|e| Error::Internal(format!("Failed to list saved queries: {}", e)))?
+        .filter_map(|r| r.ok())
+        .filter_map(|(query_json, owner_json)| {
+            let mut query: SavedQuery = serde_json::from_str(&query_json).ok()?;
+            query.owner = owner_json.and_then(|json| serde_json::from_str(&json).ok());
+            Some(query)
+        })
+        .collect();
+
+        Ok(queries)
+    }
+
+    /// Delete a saved query and its reports, returning whether it existed
+    pub fn delete_saved_query(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "DELETE FROM saved_query_reports WHERE saved_query_id = ?1",
+            params![id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete reports: {}", e)))?;
+        let deleted = conn.execute(
+            "DELETE FROM saved_queries WHERE id = ?1",
+            params![id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete saved query: {}", e)))?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Store a report, keeping only the newest `keep` of its saved query
+    pub fn insert_report(&self, report: &Report, keep: usize) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "INSERT INTO saved_query_reports (id, saved_query_id, report_json, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                report.id.to_string(),
+                report.saved_query_id.to_string(),
+                serde_json::to_string(report)?,
+                report.created_at.to_rfc3339(),
+            ],
+        ).map_err(|e| Error::Internal(format!("Failed to insert report: {}", e)))?;
+        conn.execute(
+            r#"
+            DELETE FROM saved_query_reports
+            WHERE saved_query_id = ?1 AND id NOT IN (
+                SELECT id FROM saved_query_reports WHERE saved_query_id = ?1
+                ORDER BY created_at DESC LIMIT ?2
+            )
+            "#,
+            params![report.saved_query_id.to_string(), keep.max(1) as i64],
+        ).map_err(|e| Error::Internal(format!("Failed to prune reports: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Delete reports whose answers cite any of the given documents
+    pub fn delete_reports_citing(&self, document_ids: &[Uuid]) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let mut deleted = 0;
+        for id in document_ids {
+            deleted += conn.execute(
+                "DELETE FROM saved_query_reports WHERE instr(report_json, ?1) > 0",
+                params![id.to_string()],
+            ).map_err(|e| Error::Internal(format!("Failed to delete reports: {}", e)))?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Reports of a saved query, newest first
+    pub fn list_reports(&self, saved_query_id: &Uuid, limit: usize) -> Result<Vec<Report>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            "SELECT report_json FROM saved_query_reports WHERE saved_query_id = ?1 ORDER BY created_at DESC LIMIT ?2"
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let reports = stmt.query_map(params![saved_query_id.to_string(), limit as i64], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::Internal(format!("Failed to list reports: {}", e)))?
+            .filter_map(|r| r.ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+
+        Ok(reports)
+    }
+
+    // ==================== Pending Embedding Operations ====================
+
+    /// Store chunks that could not be embedded
+    pub fn insert_pending_embeddings(&self, chunks: &[Chunk], error: &str) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        let now = Utc::now().to_rfc3339();
+        for chunk in chunks {
+            tx.execute(
+                r#"
+                INSERT OR REPLACE INTO pending_embeddings (
+                    chunk_id, document_id, chunk_json, attempts, last_error, created_at
+                ) VALUES (?1, ?2, ?3, 1, ?4, ?5)
+                "#,
+                params![
+                    chunk.id.to_string(),
+                    chunk.document_id.to_string(),
+                    serde_json::to_string(chunk)?,
+                    error,
+                    now,
+                ],
+            ).map_err(|e| Error::Internal(format!("Failed to insert pending embedding: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit pending embeddings: {}", e)))?;
+        Ok(())
+    }
+
+    /// Chunks waiting for an embedding, oldest first
+    pub fn list_pending_embeddings(&self, limit: usize, offset: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            "SELECT chunk_json FROM pending_embeddings ORDER BY created_at ASC, chunk_id ASC LIMIT ?1 OFFSET ?2"
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let chunks = stmt.query_map(params![limit as i64, offset as i64], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::Internal(format!("Failed to list pending embeddings: {}", e)))?
+            .filter_map(|r| r.ok())
+            .filter_map(|json| match serde_json::from_str::<Chunk>(&json) {
+                Ok(chunk) => Some(chunk),
+                Err(e) => {
+                    tracing::warn!("Skipping unreadable pending chunk: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Number of chunks waiting for an embedding
+    pub fn count_pending_embeddings(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM pending_embeddings", [], |row| row.get(0))
+            .map_err(|e| Error::Internal(format!("Failed to count pending embeddings: {}", e)))?;
+
+        Ok(count as usize)
+    }
+
+    /// Record another failed embedding attempt
+    pub fn record_pending_embedding_failure(&self, chunk_id: &Uuid, error: &str) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "UPDATE pending_embeddings SET attempts = attempts + 1, last_error = ?2 WHERE chunk_id = ?1",
+            params![chunk_id.to_string(), error],
+        ).map_err(|e| Error::Internal(format!("Failed to update pending embedding: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Remove a repaired chunk
+    pub fn delete_pending_embedding(&self, chunk_id: &Uuid) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute(
+            "DELETE FROM pending_embeddings WHERE chunk_id = ?1",
+            params![chunk_id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete pending embedding: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Remove the pending chunks of a deleted document
+    pub fn delete_pending_embeddings_for_document(&self, document_id: &Uuid) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let deleted = conn.execute(
+            "DELETE FROM pending_embeddings WHERE document_id = ?1",
+            params![document_id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete pending embeddings: {}", e)))?;
+
+        Ok(deleted)
+    }
+
+    // ==================== Knowledge Graph Operations ====================
+
+    /// Chunks not yet processed by the graph builder, oldest first
+    pub fn list_chunks_without_graph(&self, limit: usize, offset: usize) -> Result<Vec<ChunkContentRecord>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, document_id, chunk_index, content, filename, file_type,
+                   page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                   embedding_model
+            FROM chunks_content c
+            WHERE NOT EXISTS (SELECT 1 FROM graph_chunks g WHERE g.chunk_id = c.id)
+            ORDER BY c.rowid
+            LIMIT ?1 OFFSET ?2
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let chunks = stmt.query_map(params![limit as i64, offset as i64], row_to_chunk_content)
+            .map_err(|e| Error::Internal(format!("Failed to query chunks: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Chunk counts per collection and embedding model
+    ///
+    /// A `None` model means the chunk was stored before models were tracked.
+    /// Chunks waiting for embedding repair are not counted.
+    pub fn embedding_models_by_collection(&self) -> Result<Vec<(Option<String>, Option<String>, usize)>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT collection, embedding_model, COUNT(*)
+            FROM chunks_content
+            WHERE id NOT IN (SELECT chunk_id FROM pending_embeddings)
+            GROUP BY collection, embedding_model
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let counts = stmt.query_map([], |row| {
+            let count: i64 = row.get(2)?;
+            Ok((row.get(0)?, row.get(1)?, count as usize))
+        })
+            .map_err(|e| Error::Internal(format!("Failed to count chunk models: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(counts)
+    }
+
+    /// Number of chunks not yet processed by the graph builder
+    pub fn count_chunks_without_graph(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM chunks_content c WHERE NOT EXISTS (SELECT 1 FROM graph_chunks g WHERE g.chunk_id = c.id)",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| Error::Internal(format!("Failed to count chunks: {}", e)))?;
+
+        Ok(count as usize)
+    }
+
+    /// Replace a chunk's triples and mark it processed
+    pub fn replace_graph_triples(&self, chunk_id: &Uuid, document_id: &Uuid, triples: &[Triple]) -> Result<()> {
+        let mut conn = self.conn.lock();
+
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+
+        let now = Utc::now().to_rfc3339();
+        tx.execute("DELETE FROM graph_triples WHERE chunk_id = ?1", params![chunk_id.to_string()])
+            .map_err(|e| Error::Internal(format!("Failed to clear graph triples: {}", e)))?;
+        {
+            let mut insert = tx.prepare(
+                r#"
+                INSERT INTO graph_triples (subject, relation, object, subject_key, object_key, chunk_id, document_id, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#
+            ).map_err(|e| Error::Internal(format!("Failed to prepare statement: {}", e)))?;
+
+            for triple in triples {
+                insert.execute(params![
+                    triple.subject,
+                    triple.relation,
+                    triple.object,
+                    entity_key(&triple.subject),
+                    entity_key(&triple.object),
+                    chunk_id.to_string(),
+                    document_id.to_string(),
+                    now,
+                ]).map_err(|e| Error::Internal(format!("Failed to insert graph triple: {}", e)))?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO graph_chunks (chunk_id, triples, processed_at) VALUES (?1, ?2, ?3)",
+            params![chunk_id.to_string(), triples.len() as i64, now],
+        ).map_err(|e| Error::Internal(format!("Failed to mark chunk processed: {}", e)))?;
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Triples with one of the entity keys as subject or object
+    pub fn graph_edges(&self, keys: &[String], limit: usize) -> Result<Vec<GraphEdge>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.conn.lock();
+        let placeholders = vec!["?"; keys.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT subject, relation, object, chunk_id, document_id
+            FROM graph_triples
+            WHERE subject_key IN ({p}) OR object_key IN ({p})
+            ORDER BY id
+            LIMIT {limit}
+            "#,
+            p = placeholders,
+            limit = limit,
+        )).map_err(|e| Error::Internal(format!("Failed to prepare graph query: {}", e)))?;
+
+        let values: Vec<&String> = keys.iter().chain(keys.iter()).collect();
+        let edges = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+            let chunk_id: String = row.get(3)?;
+            let document_id: String = row.get(4)?;
+            Ok(GraphEdge {
+                subject: row.get(0)?,
+                relation: row.get(1)?,
+                object: row.get(2)?,
+                chunk_id: Uuid::parse_str(&chunk_id).unwrap_or_default(),
+                document_id: Uuid::parse_str(&document_id).unwrap_or_default(),
+            })
+        })
+        .map_err(|e| Error::Internal(format!("Failed to query graph triples: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(edges)
+    }
+
+    /// The entity keys among `candidates` that appear in the graph
+    pub fn known_graph_entities(&self, candidates: &[String]) -> Result<HashSet<String>> {
+        if candidates.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let conn = self.conn.lock();
+        let placeholders = vec!["?"; candidates.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT subject_key FROM graph_triples WHERE subject_key IN ({p})
+            UNION
+            SELECT object_key FROM graph_triples WHERE object_key IN ({p})
+            "#,
+            p = placeholders,
+        )).map_err(|e| Error::Internal(format!("Failed to prepare graph query: {}", e)))?;
+
+        let values: Vec<&String> = candidates.iter().chain(candidates.iter()).collect();
+        let known = stmt.query_map(rusqlite::params_from_iter(values), |row| row.get::<_, String>(0))
+            .map_err(|e| Error::Internal(format!("Failed to query graph entities: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(known)
+    }
+
+    /// Number of triples and of distinct entities in the graph
+    pub fn graph_counts(&self) -> Result<(usize, usize)> {
+        let conn = self.conn.lock();
+
+        conn.query_row(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM graph_triples),
+                (SELECT COUNT(*) FROM (SELECT subject_key FROM graph_triples UNION SELECT object_key FROM graph_triples))
+            "#,
+            [],
+            |row| Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)? as usize)),
+        ).map_err(|e| Error::Internal(format!("Failed to count graph triples: {}", e)))
+    }
+
+    /// Remove all triples so every chunk is processed again
+    pub fn clear_graph(&self) -> Result<()> {
+        let conn = self.conn.lock();
+
+        conn.execute_batch("DELETE FROM graph_triples; DELETE FROM graph_chunks;")
+            .map_err(|e| Error::Internal(format!("Failed to clear graph: {}", e)))?;
+
+        Ok(())
+    }
+
+    // ==================== Usage Operations ====================
+
+    /// Add usage counts to the daily totals
+    pub fn add_usage(&self, records: &[UsageRecord]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        for record in records {
+            tx.execute(
+                r#"
+                INSERT INTO usage_daily
+                    (day, provider, model, kind, collection, principal, calls, input_tokens, output_tokens, cost_usd, cached_tokens)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                ON CONFLICT(day, provider, model, kind, collection, principal) DO UPDATE SET
+                    calls = calls + excluded.calls,
+                    input_tokens = input_tokens + excluded.input_tokens,
+                    output_tokens = output_tokens + excluded.output_tokens,
+                    cost_usd = cost_usd + excluded.cost_usd,
+                    cached_tokens = cached_tokens + excluded.cached_tokens
+                "#,
+                params![
+                    record.day,
+                    record.provider,
+                    record.model,
+                    record.kind,
+                    record.collection.as_deref().unwrap_or(""),
+                    record.principal.as_deref().unwrap_or(""),
+                    record.calls as i64,
+                    record.input_tokens as i64,
+                    record.output_tokens as i64,
+                    record.cost_usd,
+                    record.cached_tokens as i64,
+                ],
+            ).map_err(|e| Error::Internal(format!("Failed to record usage: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit usage: {}", e)))?;
+        Ok(())
+    }
+
+    /// Usage totals between two days (inclusive, YYYY-MM-DD)
+    pub fn usage_rows(&self, from: &str, to: &str) -> Result<Vec<UsageRecord>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT day, provider, model, kind, collection, principal, calls, input_tokens, output_tokens, cost_usd,
+                cached_tokens
+            FROM usage_daily
+            WHERE day >= ?1 AND day <= ?2
+            ORDER BY day, provider, model, kind, collection, principal
+            "#,
+        ).map_err(|e| Error::Internal(format!("Failed to prepare usage query: {}", e)))?;
+
+        let rows = stmt.query_map(params![from, to], |row| {
+            let collection: String = row.get(4)?;
+            let principal: String = row.get(5)?;
+            Ok(UsageRecord {
+                day: row.get(0)?,
+                provider: row.get(1)?,
+                model: row.get(2)?,
+                kind: row.get(3)?,
+                collection: Some(collection).filter(|c| !c.is_empty()),
+                principal: Some(principal).filter(|p| !p.is_empty()),
+                calls: row.get::<_, i64>(6)? as u64,
+                input_tokens: row.get::<_, i64>(7)? as u64,
+                output_tokens: row.get::<_, i64>(8)? as u64,
+                cost_usd: row.get(9)?,
+                cached_tokens: row.get::<_, i64>(10)? as u64,
+            })
+        })
+        .map_err(|e| Error::Internal(format!("Failed to query usage: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(rows)
+    }
+
+    /// Cost since a day, by collection or principal ('' for global or unattributed)
+    pub fn usage_cost_since(&self, since: &str, scope: BudgetScope) -> Result<HashMap<String, f64>> {
+        let column = match scope {
+            BudgetScope::Global => "''",
+            BudgetScope::Collection => "collection",
+            BudgetScope::Principal => "principal",
+        };
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {c}, SUM(cost_usd) FROM usage_daily WHERE day >= ?1 GROUP BY {c}",
+            c = column,
+        )).map_err(|e| Error::Internal(format!("Failed to prepare usage query: {}", e)))?;
+
+        let costs = stmt.query_map(params![since], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))
+            .map_err(|e| Error::Internal(format!("Failed to query usage cost: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(costs)
+    }
+
+    // ==================== Chunk Content Operations (for FTS) ====================
+
+    /// Insert a chunk into the content table (triggers will sync to FTS)
+    pub fn insert_chunk_content(&self, chunk: &ChunkContentRecord) -> Result<()> {
+        let conn = self.conn.lock();
+        self.insert_chunk_row(&conn, &chunk.into(), &Utc::now().to_rfc3339())
+    }
+
+    /// Insert multiple chunks (batch) with transaction for atomicity and performance
+    pub fn insert_chunks_content(&self, chunks: &[ChunkContentRecord]) -> Result<()> {
+        self.insert_chunk_rows(chunks.iter().map(ChunkContentRow::from))
+    }
+
+    /// Insert chunk rows borrowed from their chunks (batch, in one transaction)
+    pub fn insert_chunk_rows<'a>(&self, rows: impl IntoIterator<Item = ChunkContentRow<'a>>) -> Result<()> {
+        let mut rows = rows.into_iter().peekable();
+        if rows.peek().is_none() {
+            return Ok(());
+        }
+
+        let mut conn = self.conn.lock();
+
+        // Use a transaction for better performance (10-50x faster for batch inserts)
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+
+        let now = Utc::now().to_rfc3339();
+        for row in rows {
+            self.insert_chunk_row(&tx, &row, &now)?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Insert one chunk row plus its analyzed terms (if the collection needs them)
+    /// and extracted keywords
+    fn insert_chunk_row(&self, conn: &Connection, chunk: &ChunkContentRow<'_>, now: &str) -> Result<()> {
+        // Drop terms of a row being replaced (REPLACE does not fire delete triggers)
+        for table in ["chunks_terms_fts", "chunks_keywords_fts"] {
+            conn.prepare_cached(&format!(
+                "DELETE FROM {} WHERE rowid = (SELECT rowid FROM chunks_content WHERE id = ?1)",
+                table
+            ))
+            .and_then(|mut stmt| stmt.execute(params![chunk.id.to_string()]))
+            .map_err(|e| Error::Internal(format!("Failed to clear chunk terms: {}", e)))?;
+        }
+
+        conn.prepare_cached(
+            r#"
+            INSERT OR REPLACE INTO chunks_content (
+                id, document_id, chunk_index, content, filename, file_type,
+                page_number, section_title, char_start, char_end, created_at, collection, parent_id, kind,
+                embedding_model
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+            "#
+        )
+        .and_then(|mut stmt| stmt.execute(params![
+            chunk.id.to_string(),
+            chunk.document_id.to_string(),
+            chunk.chunk_index as i64,
+            chunk.content,
+            chunk.filename,
+            file_type_to_extension(chunk.file_type),
+            chunk.page_number.map(|p| p as i64),
+            chunk.section_title,
+            chunk.char_start as i64,
+            chunk.char_end as i64,
+            now,
+            chunk.collection,
+            chunk.parent_id.map(|id| id.to_string()),
+            chunk.kind.as_str(),
+            chunk.embedding_model,
+        ]))
+        .map_err(|e| Error::Internal(format!("Failed to insert chunk content: {}", e)))?;
+
+        let analyzer = self.analyzers.for_collection(chunk.collection);
+        if !analyzer.is_standard() {
+            conn.prepare_cached(
+                "INSERT INTO chunks_terms_fts(rowid, terms, chunk_id, collection) VALUES (last_insert_rowid(), ?1, ?2, ?3)"
+            )
+            .and_then(|mut stmt| stmt.execute(params![
+                analyzer.index_text(chunk.content),
+                chunk.id.to_string(),
+                chunk.collection,
+            ]))
+            .map_err(|e| Error::Internal(format!("Failed to insert chunk terms: {}", e)))?;
+        }
+
+        if !chunk.keywords.is_empty() || !chunk.entities.is_empty() {
+            conn.prepare_cached(
+                "INSERT INTO chunks_keywords_fts(rowid, keywords, entities, chunk_id) \
+                 VALUES ((SELECT rowid FROM chunks_content WHERE id = ?3), ?1, ?2, ?3)"
+            )
+            .and_then(|mut stmt| stmt.execute(params![
+                chunk.keywords.join("\n"),
+                chunk.entities.join("\n"),
+                chunk.id.to_string(),
+            ]))
+            .map_err(|e| Error::Internal(format!("Failed to insert chunk keywords: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Chunks tagged with all of the given entities (case-insensitive phrase match)
+    pub fn chunk_ids_with_entities(&self, entities: &[String]) -> Result<HashSet<Uuid>> {
+        let phrases: Vec<String> = entities
+            .iter()
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .map(|e| format!("entities : {}", fts_phrase(e)))
+            .collect();
+        if phrases.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT chunk_id FROM chunks_keywords_fts WHERE chunks_keywords_fts MATCH ?1")
+            .map_err(|e| Error::Internal(format!("Failed to prepare entity query: {}", e)))?;
+        let ids = stmt.query_map(params![phrases.join(" AND ")], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::Internal(format!("Failed to query chunk entities: {}", e)))?
+            .filter_map(|r| r.ok())
+            .filter_map(|id| Uuid::parse_str(&id).ok())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// BM25 scores of the question's words against the keywords and entities
+    /// of the given chunks (higher is better; unmatched chunks are left out)
+    pub fn keyword_scores(&self, query: &str, chunk_ids: &[Uuid]) -> Result<HashMap<Uuid, f64>> {
+        let mut words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() > 2)
+            .map(|w| w.to_lowercase())
+            .collect();
+        words.sort();
+        words.dedup();
+        if words.is_empty() || chunk_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let fts_query = words.iter().map(|w| fts_phrase(w)).collect::<Vec<_>>().join(" OR ");
+        let placeholders = vec!["?"; chunk_ids.len()].join(", ");
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT chunk_id, bm25(chunks_keywords_fts) FROM chunks_keywords_fts \
+             WHERE chunks_keywords_fts MATCH ? AND chunk_id IN ({})",
+            placeholders
+        )).map_err(|e| Error::Internal(format!("Failed to prepare keyword query: {}", e)))?;
+
+        let mut values: Vec<String> = vec![fts_query];
+        values.extend(chunk_ids.iter().map(|id| id.to_string()));
+        let scores = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })
+        .map_err(|e| Error::Internal(format!("Failed to query chunk keywords: {}", e)))?
+        .filter_map(|r| r.ok())
+        .filter_map(|(id, score)| Some((Uuid::parse_str(&id).ok()?, -score)))
+        .collect();
+
+        Ok(scores)
+    }
+
+    /// Rebuild analyzed FTS terms for a collection (all chunks if `None`)
+    ///
+    /// Required after changing a collection's analyzer. Returns the number of
+    /// chunks indexed with a non-standard analyzer.
+    pub fn reindex_chunk_terms(&self, collection: Option<&str>) -> Result<usize> {
+        let mut conn = self.conn.lock();
+
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+
+        match collection {
+            Some(c) => tx.execute("DELETE FROM chunks_terms_fts WHERE collection = ?1", params![c]),
+            None => tx.execute("DELETE FROM chunks_terms_fts", []),
+        }
+        .map_err(|e| Error::Internal(format!("Failed to clear chunk terms: {}", e)))?;
+
+        let mut indexed = 0;
+        {
+            let mut select = tx.prepare(
+                "SELECT rowid, id, content, collection FROM chunks_content WHERE ?1 IS NULL OR collection = ?1"
+            ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+            let mut insert = tx.prepare(
+                "INSERT INTO chunks_terms_fts(rowid, terms, chunk_id, collection) VALUES (?1, ?2, ?3, ?4)"
+            ).map_err(|e| Error::Internal(format!("Failed to prepare statement: {}", e)))?;
+
+            let mut rows = select.query(params![collection])
+                .map_err(|e| Error::Internal(format!("Failed to query chunks: {}", e)))?;
+
+            while let Some(row) = rows.next()
+                .map_err(|e| Error::Internal(format!("Failed to read chunk: {}", e)))?
+            {
+                let rowid: i64 = row.get(0).map_err(|e| Error::Internal(e.to_string()))?;
+                let id: String = row.get(1).map_err(|e| Error::Internal(e.to_string()))?;
+                let content: String = row.get(2).map_err(|e| Error::Internal(e.to_string()))?;
+                let chunk_collection: Option<String> = row.get(3).map_err(|e| Error::Internal(e.to_string()))?;
+
+                let analyzer = self.analyzers.for_collection(chunk_collection.as_deref());
+                if analyzer.is_standard() {
+                    continue;
+                }
+
+                insert.execute(params![rowid, analyzer.index_text(&content), id, chunk_collection])
+                    .map_err(|e| Error::Internal(format!("Failed to insert chunk terms: {}", e)))?;
+                indexed += 1;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(indexed)
+    }
+
+    /// Get all chunk IDs in the FTS table (for migration deduplication)
+    pub fn get_all_chunk_ids(&self) -> Result<Vec<Uuid>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare("SELECT id FROM chunks_content")
+            .map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let ids = stmt.query_map([], |row| {
+            let id_str: String = row.get(0)?;
+            Ok(Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::new_v4()))
+        })
+        .map_err(|e| Error::Internal(format!("Failed to query chunk IDs: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(ids)
+    }
+
+    /// Full-text search across chunks
+    ///
+    /// Exact searches process the query with the collection's analyzer;
+    /// prefix, fuzzy and regex searches match the chunk text as stored. With
+    /// a collection, results are restricted to chunks in that collection.
+    /// Results carry the matched spans, and an FTS5 `snippet()` excerpt for
+    /// exact and prefix searches of the chunk text.
+    pub fn string_search_chunks(
+        &self,
+        query: &str,
+        limit: usize,
+        collection: Option<&str>,
+        mode: StringSearchMode,
+    ) -> Result<Vec<ChunkSearchResult>> {
+        let matcher = StringMatcher::new(query, mode)?;
+        if matcher.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (fts_table, fts_query, candidates) = match mode {
+            StringSearchMode::Exact => {
+                let (fts_table, fts_query) = self.exact_match(query, collection);
+                (fts_table, fts_query, limit)
+            }
+            StringSearchMode::Prefix => ("chunks_fts", matcher.prefix_query(), limit),
+            // Candidates sharing trigrams with the query, re-ranked by closeness
+            StringSearchMode::Fuzzy => ("chunks_trigram_fts", matcher.trigram_query(), limit * FUZZY_CANDIDATES),
+            StringSearchMode::Regex => return self.regex_search_chunks(&matcher, limit, collection),
+        };
+        let Some(fts_query) = fts_query else {
+            return Ok(Vec::new());
+        };
+
+        let snippet = if fts_table == "chunks_fts" {
+            format!("snippet(chunks_fts, 0, '<mark>', '</mark>', '...', {})", SNIPPET_TOKENS)
+        } else {
+            "NULL".to_string()
+        };
+
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT
+                c.id, c.document_id, c.chunk_index, c.content, c.filename, c.file_type,
+                c.page_number, c.section_title, c.char_start, c.char_end,
+                bm25({fts}) as score, {snippet}
+            FROM {fts} f
+            JOIN chunks_content c ON c.rowid = f.rowid
+            WHERE {fts} MATCH ?1 AND (?3 IS NULL OR c.collection = ?3)
+            ORDER BY score
+            LIMIT ?2
+            "#,
+            fts = fts_table,
+            snippet = snippet
+        )).map_err(|e| Error::Internal(format!("Failed to prepare FTS query: {}", e)))?;
+
+        let results = stmt.query_map(params![fts_query, candidates as i64, collection], |row| {
+            let score: f64 = row.get(10)?;
+            let mut result = row_to_chunk_search_result(row)?;
+            result.score = -score; // BM25 returns negative scores, lower is better
+            result.snippet = row.get(11)?;
+            Ok(result)
+        }).map_err(|e| Error::Internal(format!("Failed to execute FTS query: {}", e)))?;
+
+        let mut search_results = Vec::new();
+        for result in results {
+            match result {
+                Ok(mut r) => {
+                    if mode == StringSearchMode::Fuzzy {
+                        let Some(closeness) = matcher.fuzzy_score(&r.content) else {
+                            continue;
+                        };
+                        r.score = closeness;
+                    }
+                    r.spans = matcher.spans(&r.content);
+                    search_results.push(r);
+                }
+                Err(e) => tracing::warn!("Error reading search result: {}", e),
+            }
+        }
+
+        if mode == StringSearchMode::Fuzzy {
+            // Closest first, more matches breaking ties
+            search_results.sort_by(|a, b| {
+                b.score.total_cmp(&a.score).then_with(|| b.spans.len().cmp(&a.spans.len()))
+            });
+            search_results.truncate(limit);
+        }
+
+        Ok(search_results)
+    }
+
+    /// FTS table and MATCH expression of an exact string search
+    fn exact_match(&self, query: &str, collection: Option<&str>) -> (&'static str, Option<String>) {
+        let analyzer = self.analyzers.for_collection(collection);
+        // Standard analyzer searches raw content, others search analyzed terms
+        let fts_table = if analyzer.is_standard() { "chunks_fts" } else { "chunks_terms_fts" };
+        (fts_table, analyzer.match_query(query))
+    }
+
+    /// Matching chunks per document, file type and collection
+    ///
+    /// With a query, only chunks it finds in an exact string search count.
+    pub fn facet_counts(
+        &self,
+        query: Option<&str>,
+        collection: Option<&str>,
+        file_type: Option<&str>,
+    ) -> Result<Vec<FacetChunkCount>> {
+        let Some((joins, filters, values)) = self.facet_scope(query, collection, file_type) else {
+            return Ok(Vec::new());
+        };
+        let clause = if filters.is_empty() { String::new() } else { format!(" WHERE {}", filters.join(" AND ")) };
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.document_id, c.file_type, c.collection, COUNT(*) FROM chunks_content c{}{} \
+             GROUP BY c.document_id, c.file_type, c.collection",
+            joins,
+            clause
+        )).map_err(|e| Error::Internal(format!("Failed to prepare facet query: {}", e)))?;
+
+        let counts = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| {
+            let document_id: String = row.get(0)?;
+            let chunks: i64 = row.get(3)?;
+            Ok(FacetChunkCount {
+                document_id: Uuid::parse_str(&document_id).unwrap_or_else(|_| Uuid::new_v4()),
+                file_type: row.get(1)?,
+                collection: row.get(2)?,
+                chunks: chunks as usize,
+            })
+        })
+        .map_err(|e| Error::Internal(format!("Failed to count facets: {}", e)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+        Ok(counts)
+    }
+
+    /// Entities extracted from the matching chunks, as (document, entity)
+    /// pairs with one pair per chunk mentioning the entity
+    pub fn facet_entities(
+        &self,
+        query: Option<&str>,
+        collection: Option<&str>,
+        file_type: Option<&str>,
+    ) -> Result<Vec<(Uuid, String)>> {
+        let Some((joins, mut filters, values)) = self.facet_scope(query, collection, file_type) else {
+            return Ok(Vec::new());
+        };
+        filters.push("k.entities != ''".to_string());
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.document_id, k.entities FROM chunks_content c \
+             JOIN chunks_keywords_fts k ON k.rowid = c.rowid{} WHERE {}",
+            joins,
+            filters.join(" AND ")
+        )).map_err(|e| Error::Internal(format!("Failed to prepare entity facet query: {}", e)))?;
+
+        let rows: Vec<(String, String)> = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| Error::Internal(format!("Failed to query entity facets: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut mentions = Vec::new();
+        for (document_id, entities) in rows {
+            let Ok(document_id) = Uuid::parse_str(&document_id) else {
+                continue;
+            };
+            let mut seen = HashSet::new();
+            for entity in entities.lines().map(str::trim).filter(|e| !e.is_empty()) {
+                if seen.insert(entity_key(entity)) {
+                    mentions.push((document_id, entity.to_string()));
+                }
+            }
+        }
+        Ok(mentions)
+    }
+
+    /// Joins, filters and values selecting the chunks a facet aggregation
+    /// counts (`c` is `chunks_content`); `None` if the query has no
+    /// searchable terms
+    fn facet_scope(
+        &self,
+        query: Option<&str>,
+        collection: Option<&str>,
+        file_type: Option<&str>,
+    ) -> Option<(String, Vec<String>, Vec<Value>)> {
+        let mut joins = String::new();
+        let mut filters = Vec::new();
+        let mut values = Vec::new();
+
+        if let Some(query) = query.filter(|q| !q.trim().is_empty()) {
+            let (fts_table, fts_query) = self.exact_match(query, collection);
+            joins = format!(" JOIN {fts} f ON f.rowid = c.rowid", fts = fts_table);
+            filters.push(format!("{} MATCH ?", fts_table));
+            values.push(Value::Text(fts_query?));
+        }
+        if let Some(collection) = collection {
+            filters.push("c.collection = ?".to_string());
+            values.push(Value::Text(collection.to_string()));
+        }
+        if let Some(file_type) = file_type {
+            filters.push("c.file_type = ?".to_string());
+            values.push(Value::Text(file_type.to_lowercase()));
+        }
+        Some((joins, filters, values))
+    }
+
+    /// Scan the chunk text with a regex
+    ///
+    /// Scans at most `REGEX_SCAN_CHUNKS` chunks (oldest first); results are
+    /// the chunks with the most matches.
+    fn regex_search_chunks(
+        &self,
+        matcher: &StringMatcher,
+        limit: usize,
+        collection: Option<&str>,
+    ) -> Result<Vec<ChunkSearchResult>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT
+                id, document_id, chunk_index, content, filename, file_type,
+                page_number, section_title, char_start, char_end
+            FROM chunks_content
+            WHERE (?1 IS NULL OR collection = ?1)
+            ORDER BY rowid
+            LIMIT ?2
+            "#,
+        ).map_err(|e| Error::Internal(format!("Failed to prepare regex scan: {}", e)))?;
+
+        let rows = stmt.query_map(params![collection, REGEX_SCAN_CHUNKS as i64], row_to_chunk_search_result)
+            .map_err(|e| Error::Internal(format!("Failed to execute regex scan: {}", e)))?;
+
+        let mut search_results = Vec::new();
+        for row in rows {
+            match row {
+                Ok(mut r) => {
+                    r.spans = matcher.spans(&r.content);
+                    if !r.spans.is_empty() {
+                        r.score = r.spans.len() as f64;
+                        search_results.push(r);
+                    }
+                }
+                Err(e) => tracing::warn!("Error reading search result: {}", e),
+            }
+        }
+
+        search_results.sort_by(|a, b| b.spans.len().cmp(&a.spans.len()));
+        search_results.truncate(limit);
+        Ok(search_results)
+    }
+
+    /// IDs of a document's chunks
+    pub fn chunk_ids_for_document(&self, document_id: &Uuid) -> Result<Vec<Uuid>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT id FROM chunks_content WHERE document_id = ?1")
+            .map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+        let ids = stmt.query_map(params![document_id.to_string()], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::Internal(format!("Failed to query chunk IDs: {}", e)))?
+            .filter_map(|r| r.ok())
+            .filter_map(|id| Uuid::parse_str(&id).ok())
+            .collect();
+        Ok(ids)
+    }
+
+    /// Delete all chunks for a document
+    pub fn delete_chunks_by_document(&self, document_id: &Uuid) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let deleted = conn.execute(
+            "DELETE FROM chunks_content WHERE document_id = ?1",
+            params![document_id.to_string()],
+        ).map_err(|e| Error::Internal(format!("Failed to delete chunks: {}", e)))?;
+
+        Ok(deleted)
+    }
+
+    /// Delete everything stored for documents in one transaction: chunk
+    /// content (and with it graph triples), pending embeddings and file
+    /// records; returns the number of file records deleted
+    pub fn purge_documents(&self, document_ids: &[Uuid]) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        let mut file_records = 0;
+        for id in document_ids {
+            let id = id.to_string();
+            tx.execute("DELETE FROM chunks_content WHERE document_id = ?1", params![id])
+                .map_err(|e| Error::Internal(format!("Failed to delete chunks: {}", e)))?;
+            tx.execute("DELETE FROM pending_embeddings WHERE document_id = ?1", params![id])
+                .map_err(|e| Error::Internal(format!("Failed to delete pending embeddings: {}", e)))?;
+            file_records += tx.execute("DELETE FROM file_registry WHERE document_id = ?1", params![id])
+                .map_err(|e| Error::Internal(format!("Failed to delete file records: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
+        Ok(file_records)
+    }
+
+    // ==================== Document Versions ====================
+
+    /// Keep a replaced document and its chunk text as a superseded version
+    pub fn archive_document_version(&self, doc: &Document, chunks: &[ChunkContentRecord]) -> Result<()> {
+        let document_json = serde_json::to_string(doc)
+            .map_err(|e| Error::Internal(format!("Failed to serialize document: {}", e)))?;
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        tx.execute(
+            r#"
+            INSERT OR REPLACE INTO document_versions (document_id, filename, version, document_json, superseded_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            params![doc.id.to_string(), doc.filename, doc.version() as i64, document_json, Utc::now().to_rfc3339()],
+        ).map_err(|e| Error::Internal(format!("Failed to archive document version: {}", e)))?;
+
+        for chunk in chunks {
+            tx.execute(
                 r#"
-                INSERT OR REPLACE INTO chunks_content (
+                INSERT OR REPLACE INTO document_version_chunks (
                     id, document_id, chunk_index, content, filename, file_type,
-                    page_number, section_title, char_start, char_end, created_at
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
-                "#
-            ).map_err(|e| Error::Internal(format!("Failed to prepare statement: {}", e)))?;
-
-            let now = Utc::now().to_rfc3339();
-            for chunk in chunks {
-                stmt.execute(params![
+                    page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                    embedding_model
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                "#,
+                params![
                     chunk.id.to_string(),
                     chunk.document_id.to_string(),
                     chunk.chunk_index as i64,
@@ -814,133 +2923,408 @@ impl FileRegistryDb {
                     chunk.section_title,
                     chunk.char_start as i64,
                     chunk.char_end as i64,
-                    &now,
-                ]).map_err(|e| Error::Internal(format!("Failed to insert chunk: {}", e)))?;
-            }
+                    chunk.collection,
+                    chunk.parent_id.map(|id| id.to_string()),
+                    chunk.kind.as_str(),
+                    chunk.embedding_model,
+                ],
+            ).map_err(|e| Error::Internal(format!("Failed to archive chunk: {}", e)))?;
         }
 
         tx.commit()
             .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
-
         Ok(())
     }
 
-    /// Get all chunk IDs in the FTS table (for migration deduplication)
-    pub fn get_all_chunk_ids(&self) -> Result<Vec<Uuid>> {
+    /// Superseded versions of a file, newest first
+    pub fn list_document_versions(&self, filename: &str) -> Result<Vec<DocumentVersion>> {
         let conn = self.conn.lock();
 
-        let mut stmt = conn.prepare("SELECT id FROM chunks_content")
-            .map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+        let mut stmt = conn.prepare(
+            "SELECT document_json, superseded_at FROM document_versions WHERE filename = ?1 ORDER BY version DESC, superseded_at DESC"
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
 
-        let ids = stmt.query_map([], |row| {
-            let id_str: String = row.get(0)?;
-            Ok(Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::new_v4()))
-        })
-        .map_err(|e| Error::Internal(format!("Failed to query chunk IDs: {}", e)))?
-        .filter_map(|r| r.ok())
-        .collect();
+        let versions = stmt.query_map(params![filename], row_to_document_version)
+            .map_err(|e| Error::Internal(format!("Failed to list document versions: {}", e)))?
+            .filter_map(|r| r.ok())
+            .flatten()
+            .collect();
 
-        Ok(ids)
+        Ok(versions)
     }
 
-    /// Full-text search across chunks
-    pub fn string_search_chunks(&self, query: &str, limit: usize) -> Result<Vec<ChunkSearchResult>> {
+    /// Highest superseded version number of a file
+    pub fn latest_document_version(&self, filename: &str) -> Result<Option<u32>> {
+        let conn = self.conn.lock();
+
+        let version: Option<i64> = conn.query_row(
+            "SELECT MAX(version) FROM document_versions WHERE filename = ?1",
+            params![filename],
+            |row| row.get(0),
+        ).map_err(|e| Error::Internal(format!("Failed to get latest document version: {}", e)))?;
+
+        Ok(version.map(|v| v as u32))
+    }
+
+    /// A superseded version by its document id
+    pub fn get_document_version(&self, document_id: &Uuid) -> Result<Option<DocumentVersion>> {
         let conn = self.conn.lock();
 
-        // Use FTS5 match syntax for the query
-        let fts_query = format!("\"{}\"", query.replace('"', "\"\""));
+        let version = conn.query_row(
+            "SELECT document_json, superseded_at FROM document_versions WHERE document_id = ?1",
+            params![document_id.to_string()],
+            row_to_document_version,
+        ).optional()
+        .map_err(|e| Error::Internal(format!("Failed to get document version: {}", e)))?;
+
+        Ok(version.flatten())
+    }
+
+    /// Chunks of a superseded version in chunk order
+    pub fn list_version_chunks(&self, document_id: &Uuid) -> Result<Vec<ChunkContentRecord>> {
+        let conn = self.conn.lock();
 
         let mut stmt = conn.prepare(
             r#"
-            SELECT
-                c.id, c.document_id, c.chunk_index, c.content, c.filename, c.file_type,
-                c.page_number, c.section_title, c.char_start, c.char_end,
-                bm25(chunks_fts) as score
-            FROM chunks_fts f
-            JOIN chunks_content c ON c.rowid = f.rowid
-            WHERE chunks_fts MATCH ?1
-            ORDER BY score
-            LIMIT ?2
+            SELECT id, document_id, chunk_index, content, filename, file_type,
+                   page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                   embedding_model
+            FROM document_version_chunks
+            WHERE document_id = ?1
+            ORDER BY chunk_index
             "#
-        ).map_err(|e| Error::Internal(format!("Failed to prepare FTS query: {}", e)))?;
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
 
-        let results = stmt.query_map(params![fts_query, limit as i64], |row| {
-            let id: String = row.get(0)?;
-            let document_id: String = row.get(1)?;
-            let chunk_index: i64 = row.get(2)?;
-            let content: String = row.get(3)?;
-            let filename: String = row.get(4)?;
-            let file_type: String = row.get(5)?;
-            let page_number: Option<i64> = row.get(6)?;
-            let _section_title: Option<String> = row.get(7)?;
-            let char_start: i64 = row.get(8)?;
-            let char_end: i64 = row.get(9)?;
-            let score: f64 = row.get(10)?;
+        let chunks = stmt.query_map(params![document_id.to_string()], row_to_chunk_content)
+            .map_err(|e| Error::Internal(format!("Failed to query version chunks: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Drop all but the newest `keep` superseded versions of a file
+    pub fn prune_document_versions(&self, filename: &str, keep: usize) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to start transaction: {}", e)))?;
+
+        let dropped: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT document_id FROM document_versions WHERE filename = ?1 ORDER BY version DESC, superseded_at DESC LIMIT -1 OFFSET ?2"
+            ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+            let ids = stmt.query_map(params![filename, keep as i64], |row| row.get(0))
+                .map_err(|e| Error::Internal(format!("Failed to list document versions: {}", e)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            ids
+        };
+
+        for id in &dropped {
+            tx.execute("DELETE FROM document_version_chunks WHERE document_id = ?1", params![id])
+                .map_err(|e| Error::Internal(format!("Failed to delete version chunks: {}", e)))?;
+            tx.execute("DELETE FROM document_versions WHERE document_id = ?1", params![id])
+                .map_err(|e| Error::Internal(format!("Failed to delete document version: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
+        Ok(dropped.len())
+    }
+
+    /// Get chunk count for a document
+    pub fn get_chunks_count_for_document(&self, document_id: &Uuid) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM chunks_content WHERE document_id = ?1",
+            params![document_id.to_string()],
+            |row| row.get(0),
+        ).map_err(|e| Error::Internal(format!("Failed to count chunks: {}", e)))?;
+
+        Ok(count as usize)
+    }
+
+    /// Get total chunk count
+    pub fn get_total_chunks_count(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM chunks_content",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| Error::Internal(format!("Failed to count chunks: {}", e)))?;
+
+        Ok(count as usize)
+    }
+
+    /// Get all chunks of a document in chunk order
+    pub fn list_chunks_for_document(&self, document_id: &Uuid) -> Result<Vec<ChunkContentRecord>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, document_id, chunk_index, content, filename, file_type,
+                   page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                   embedding_model
+            FROM chunks_content
+            WHERE document_id = ?1
+            ORDER BY chunk_index
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let chunks = stmt.query_map(params![document_id.to_string()], row_to_chunk_content)
+            .map_err(|e| Error::Internal(format!("Failed to query chunks: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Get one chunk by ID
+    pub fn get_chunk_content(&self, id: &Uuid) -> Result<Option<ChunkContentRecord>> {
+        let conn = self.conn.lock();
+
+        conn.query_row(
+            r#"
+            SELECT id, document_id, chunk_index, content, filename, file_type,
+                   page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                   embedding_model
+            FROM chunks_content
+            WHERE id = ?1
+            "#,
+            params![id.to_string()],
+            row_to_chunk_content,
+        )
+        .optional()
+        .map_err(|e| Error::Internal(format!("Failed to get chunk: {}", e)))
+    }
+
+    /// Stored chunk JSON and vector location (byte offset, dimensions, count)
+    pub fn chunk_store_row(&self, id: &Uuid) -> Result<Option<(String, Option<(u64, usize, usize)>)>> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT chunk_json, vector_offset, vector_dims, vector_count FROM chunk_store WHERE chunk_id = ?1",
+            params![id.to_string()],
+            |row| {
+                let offset: Option<i64> = row.get(1)?;
+                let dims: i64 = row.get(2)?;
+                let count: i64 = row.get(3)?;
+                Ok((row.get(0)?, offset.map(|offset| (offset as u64, dims as usize, count as usize))))
+            },
+        )
+        .optional()
+        .map_err(|e| Error::Internal(format!("Failed to get stored chunk: {}", e)))
+    }
+
+    /// Store chunks with the location of their vectors
+    pub fn put_chunk_store_rows(&self, rows: &[(Uuid, String, Option<(u64, usize, usize)>)]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+        for (id, chunk_json, vectors) in rows {
+            let (offset, dims, count) = match vectors {
+                Some((offset, dims, count)) => (Some(*offset as i64), *dims as i64, *count as i64),
+                None => (None, 0, 0),
+            };
+            tx.execute(
+                "INSERT OR REPLACE INTO chunk_store (chunk_id, chunk_json, vector_offset, vector_dims, vector_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id.to_string(), chunk_json, offset, dims, count],
+            ).map_err(|e| Error::Internal(format!("Failed to store chunk: {}", e)))?;
+        }
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit stored chunks: {}", e)))?;
+        Ok(())
+    }
+
+    /// Forget the vectors of every stored chunk
+    pub fn clear_chunk_store_vectors(&self) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute("UPDATE chunk_store SET vector_offset = NULL, vector_dims = 0, vector_count = 0", [])
+            .map_err(|e| Error::Internal(format!("Failed to clear stored vectors: {}", e)))?;
+        Ok(())
+    }
+
+    /// Replace a chunk whose text changed
+    ///
+    /// The old row is deleted first so the delete triggers drop its text from
+    /// every index (and its graph triples, extracted from the old text).
+    pub fn replace_chunk_content(&self, chunk: &ChunkContentRecord) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
+
+        tx.execute("DELETE FROM chunks_content WHERE id = ?1", params![chunk.id.to_string()])
+            .map_err(|e| Error::Internal(format!("Failed to delete chunk: {}", e)))?;
+        self.insert_chunk_row(&tx, &chunk.into(), &Utc::now().to_rfc3339())?;
+
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))
+    }
+
+    /// Get the chunks of a parent window in chunk order
+    pub fn list_chunks_for_parent(&self, parent_id: &Uuid) -> Result<Vec<ChunkContentRecord>> {
+        let conn = self.conn.lock();
+
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, document_id, chunk_index, content, filename, file_type,
+                   page_number, section_title, char_start, char_end, collection, parent_id, kind,
+                   embedding_model
+            FROM chunks_content
+            WHERE parent_id = ?1
+            ORDER BY chunk_index
+            "#
+        ).map_err(|e| Error::Internal(format!("Failed to prepare query: {}", e)))?;
+
+        let chunks = stmt.query_map(params![parent_id.to_string()], row_to_chunk_content)
+            .map_err(|e| Error::Internal(format!("Failed to query chunks: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Replace the entire chunk content table in one transaction
+    ///
+    /// Used when swapping in a rebuilt index: searches see either the old or
+    /// the new chunks, never a mix.
+    pub fn replace_all_chunks_content(&self, chunks: &[ChunkContentRecord]) -> Result<()> {
+        let mut conn = self.conn.lock();
+
+        let tx = conn.transaction()
+            .map_err(|e| Error::Internal(format!("Failed to begin transaction: {}", e)))?;
 
-            Ok(ChunkSearchResult {
-                chunk_id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
-                document_id: Uuid::parse_str(&document_id).unwrap_or_else(|_| Uuid::new_v4()),
-                chunk_index: chunk_index as u32,
-                content,
-                filename,
-                file_type: extension_to_file_type(&file_type),
-                page_number: page_number.map(|p| p as u32),
-                char_start: char_start as usize,
-                char_end: char_end as usize,
-                score: -score, // BM25 returns negative scores, lower is better
-            })
-        }).map_err(|e| Error::Internal(format!("Failed to execute FTS query: {}", e)))?;
+        tx.execute("DELETE FROM chunks_content", [])
+            .map_err(|e| Error::Internal(format!("Failed to clear chunks: {}", e)))?;
 
-        let mut search_results = Vec::new();
-        for result in results {
-            match result {
-                Ok(r) => search_results.push(r),
-                Err(e) => tracing::warn!("Error reading search result: {}", e),
-            }
+        let now = Utc::now().to_rfc3339();
+        for chunk in chunks {
+            self.insert_chunk_row(&tx, &chunk.into(), &now)?;
         }
 
-        Ok(search_results)
-    }
-
-    /// Delete all chunks for a document
-    pub fn delete_chunks_by_document(&self, document_id: &Uuid) -> Result<usize> {
-        let conn = self.conn.lock();
+        tx.commit()
+            .map_err(|e| Error::Internal(format!("Failed to commit transaction: {}", e)))?;
 
-        let deleted = conn.execute(
-            "DELETE FROM chunks_content WHERE document_id = ?1",
-            params![document_id.to_string()],
-        ).map_err(|e| Error::Internal(format!("Failed to delete chunks: {}", e)))?;
+        Ok(())
+    }
 
-        Ok(deleted)
+    // ==================== Snapshots ====================
+
+    /// Copy the database to `target` with SQLite's online backup API
+    ///
+    /// A file copy of a WAL-mode database can miss committed pages still in
+    /// the WAL. The backup reads from its own connection in one step, so the
+    /// copy is a single consistent state and writers aren't blocked meanwhile.
+    /// Snapshots of an encrypted database are encrypted with the same key.
+    pub fn snapshot_to(&self, target: &Path) -> Result<()> {
+        let source_path = self.conn.lock().path().filter(|p| !p.is_empty()).map(str::to_string);
+        let key = self.key.as_ref();
+        let mut target = open_keyed(target, key, OpenFlags::default())
+            .map_err(|e| Error::Internal(format!("Failed to create snapshot: {}", e)))?;
+
+        match source_path {
+            Some(path) => {
+                let source = open_keyed(Path::new(&path), key, OpenFlags::SQLITE_OPEN_READ_ONLY)
+                    .map_err(|e| Error::Internal(format!("Failed to open database for snapshot: {}", e)))?;
+                copy_database(&source, &mut target)
+            }
+            // In-memory databases can only be read through their own connection
+            None => copy_database(&self.conn.lock(), &mut target),
+        }
     }
 
-    /// Get chunk count for a document
-    pub fn get_chunks_count_for_document(&self, document_id: &Uuid) -> Result<usize> {
-        let conn = self.conn.lock();
+    /// Replace the database at `target` with a snapshot
+    ///
+    /// Meant for a stopped server: a running one keeps its registries in
+    /// memory and would write them back. The snapshot is checked first. Both
+    /// are opened with `key` when the database is encrypted.
+    pub fn restore_snapshot(snapshot: &Path, target: &Path, key: Option<&EncryptionKey>) -> Result<()> {
+        let source = open_keyed(snapshot, key, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| Error::Internal(format!("Failed to open snapshot: {}", e)))?;
+        let check: String = source
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| Error::Internal(format!("Failed to check snapshot: {}", e)))?;
+        if check != "ok" {
+            return Err(Error::StorageCorruption(format!("Snapshot {} is corrupt: {}", snapshot.display(), check)));
+        }
 
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM chunks_content WHERE document_id = ?1",
-            params![document_id.to_string()],
-            |row| row.get(0),
-        ).map_err(|e| Error::Internal(format!("Failed to count chunks: {}", e)))?;
+        let mut target = open_keyed(target, key, OpenFlags::default())
+            .map_err(|e| Error::Internal(format!("Failed to open database: {}", e)))?;
+        copy_database(&source, &mut target)
+    }
+}
 
-        Ok(count as usize)
+/// Open a connection, keying it first when the database is encrypted
+///
+/// SQLCipher only checks the key on first access, so the schema is read
+/// right away to fail here on a wrong key rather than in a later query.
+fn open_keyed(path: &Path, key: Option<&EncryptionKey>, flags: OpenFlags) -> rusqlite::Result<Connection> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    if let Some(key) = key {
+        conn.execute_batch(&key.sqlcipher_pragma())?;
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
     }
+    Ok(conn)
+}
 
-    /// Get total chunk count
-    pub fn get_total_chunks_count(&self) -> Result<usize> {
-        let conn = self.conn.lock();
+/// Encrypt an existing unencrypted database file in place
+///
+/// The contents are exported into an encrypted copy that then replaces the
+/// original, so a failure part way leaves the original untouched.
+fn encrypt_plaintext_database(path: &Path, key: &EncryptionKey) -> Result<()> {
+    let mut header = [0u8; 16];
+    let is_plaintext = std::fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+        .is_ok_and(|_| header == SQLITE_HEADER);
+    if !is_plaintext {
+        return Ok(());
+    }
 
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM chunks_content",
-            [],
-            |row| row.get(0),
-        ).map_err(|e| Error::Internal(format!("Failed to count chunks: {}", e)))?;
+    let encrypted = path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted);
+    let conn = Connection::open(path)
+        .map_err(|e| Error::Internal(format!("Failed to open database: {}", e)))?;
+    conn.execute_batch(&format!(
+        "ATTACH DATABASE '{}' AS encrypted KEY {}; SELECT sqlcipher_export('encrypted'); DETACH DATABASE encrypted;",
+        encrypted.display().to_string().replace('\'', "''"),
+        key.sqlcipher_key()
+    ))
+    .map_err(|e| Error::Internal(format!("Failed to encrypt database: {}", e)))?;
+    drop(conn);
+
+    std::fs::rename(&encrypted, path)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = std::fs::remove_file(sidecar);
+    }
+    tracing::info!("Encrypted existing database {}", path.display());
+    Ok(())
+}
 
-        Ok(count as usize)
+/// Copy every page of `source` into `target` in one backup step
+fn copy_database(source: &Connection, target: &mut Connection) -> Result<()> {
+    let backup = Backup::new(source, target)
+        .map_err(|e| Error::Internal(format!("Failed to start backup: {}", e)))?;
+    loop {
+        match backup.step(-1).map_err(|e| Error::Internal(format!("Backup failed: {}", e)))? {
+            StepResult::Done => return Ok(()),
+            StepResult::More => {}
+            // Another connection holds a lock, try again shortly
+            _ => std::thread::sleep(std::time::Duration::from_millis(50)),
+        }
     }
 }
 
+/// A document replaced by a newer version of its file
+#[derive(Debug, Clone)]
+pub struct DocumentVersion {
+    pub document: Document,
+    pub superseded_at: DateTime<Utc>,
+}
+
 /// Record for inserting chunk content
 #[derive(Debug, Clone)]
 pub struct ChunkContentRecord {
@@ -954,6 +3338,99 @@ pub struct ChunkContentRecord {
     pub section_title: Option<String>,
     pub char_start: usize,
     pub char_end: usize,
+    pub collection: Option<String>,
+    pub parent_id: Option<Uuid>,
+    pub kind: ChunkKind,
+    /// Extracted keywords (indexed in `chunks_keywords_fts`)
+    pub keywords: Vec<String>,
+    /// Extracted named entities (indexed in `chunks_keywords_fts`)
+    pub entities: Vec<String>,
+    /// Model that embedded the chunk
+    pub embedding_model: Option<String>,
+}
+
+impl ChunkContentRecord {
+    /// Chunk with the stored text and source (no embedding or metadata)
+    pub fn to_chunk(&self) -> Chunk {
+        let mut source = crate::types::ChunkSource::text(self.filename.clone());
+        source.file_type = self.file_type.clone();
+        source.page_number = self.page_number;
+        source.section_title = self.section_title.clone();
+        source.kind = self.kind;
+
+        let mut chunk = Chunk::new(self.document_id, self.content.clone(), source, self.char_start, self.char_end, self.chunk_index);
+        chunk.id = self.id;
+        chunk.parent_id = self.parent_id;
+        chunk
+    }
+}
+
+/// Chunk content to insert, borrowed from a record or straight from the chunk
+/// so the text isn't copied on its way into SQLite
+#[derive(Debug, Clone)]
+pub struct ChunkContentRow<'a> {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub chunk_index: u32,
+    pub content: &'a str,
+    pub filename: &'a str,
+    pub file_type: &'a FileType,
+    pub page_number: Option<u32>,
+    pub section_title: Option<&'a str>,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub collection: Option<&'a str>,
+    pub parent_id: Option<Uuid>,
+    pub kind: ChunkKind,
+    pub keywords: Vec<String>,
+    pub entities: Vec<String>,
+    pub embedding_model: Option<&'a str>,
+}
+
+impl<'a> From<&'a ChunkContentRecord> for ChunkContentRow<'a> {
+    fn from(record: &'a ChunkContentRecord) -> Self {
+        Self {
+            id: record.id,
+            document_id: record.document_id,
+            chunk_index: record.chunk_index,
+            content: &record.content,
+            filename: &record.filename,
+            file_type: &record.file_type,
+            page_number: record.page_number,
+            section_title: record.section_title.as_deref(),
+            char_start: record.char_start,
+            char_end: record.char_end,
+            collection: record.collection.as_deref(),
+            parent_id: record.parent_id,
+            kind: record.kind,
+            keywords: record.keywords.clone(),
+            entities: record.entities.clone(),
+            embedding_model: record.embedding_model.as_deref(),
+        }
+    }
+}
+
+impl<'a> From<&'a Chunk> for ChunkContentRow<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        Self {
+            id: chunk.id,
+            document_id: chunk.document_id,
+            chunk_index: chunk.chunk_index,
+            content: &chunk.content,
+            filename: &chunk.source.filename,
+            file_type: &chunk.source.file_type,
+            page_number: chunk.source.page_number,
+            section_title: chunk.source.section_title.as_deref(),
+            char_start: chunk.char_start,
+            char_end: chunk.char_end,
+            collection: chunk.collection(),
+            parent_id: chunk.parent_id,
+            kind: chunk.source.kind,
+            keywords: chunk.keywords(),
+            entities: chunk.entities(),
+            embedding_model: chunk.embedding_model(),
+        }
+    }
 }
 
 /// Result from chunk string search
@@ -969,10 +3446,24 @@ pub struct ChunkSearchResult {
     pub char_start: usize,
     pub char_end: usize,
     pub score: f64,
+    /// FTS5 `snippet()` excerpt with `<mark>` highlights
+    pub snippet: Option<String>,
+    /// Byte ranges of the matches in `content`
+    pub spans: Vec<(usize, usize)>,
+}
+
+/// Chunks of one document counted by a facet aggregation
+#[derive(Debug, Clone)]
+pub struct FacetChunkCount {
+    pub document_id: Uuid,
+    /// File extension as stored with the chunks
+    pub file_type: String,
+    pub collection: Option<String>,
+    pub chunks: usize,
 }
 
 /// Database statistics
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub struct FileRegistryDbStats {
     pub total: usize,
     pub success: usize,
@@ -980,8 +3471,21 @@ pub struct FileRegistryDbStats {
     pub skipped: usize,
 }
 
+/// A job file that failed transiently on every attempt
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct DeadLetterFile {
+    pub job_id: Uuid,
+    pub filename: String,
+    pub file_size: u64,
+    /// Stage of the last failure (parsing, embedding, storing)
+    pub stage: Option<String>,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
 /// GCS sync status
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub struct SyncStatus {
     pub last_gcs_sync: Option<DateTime<Utc>>,
     pub files_synced: usize,
@@ -990,6 +3494,25 @@ pub struct SyncStatus {
 
 // Helper functions
 
+/// Add a column to an existing table if an older schema lacks it
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let columns: Vec<String> = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(|e| Error::Internal(format!("Failed to read schema of {}: {}", table, e)))?;
+
+    if !columns.iter().any(|c| c == column) {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])
+            .map_err(|e| Error::Internal(format!("Failed to add column {}.{}: {}", table, column, e)))?;
+        tracing::info!("Added column {}.{}", table, column);
+    }
+
+    Ok(())
+}
+
 fn status_to_string(status: &FileRecordStatus) -> &'static str {
     match status {
         FileRecordStatus::Success => "success",
@@ -1028,6 +3551,12 @@ fn file_type_to_extension(file_type: &FileType) -> &'static str {
         FileType::Ods => "ods",
         FileType::Epub => "epub",
         FileType::Image => "image",
+        FileType::Audio => "audio",
+        FileType::Video => "video",
+        FileType::Email => "email",
+        FileType::Mbox => "mbox",
+        FileType::Notebook => "notebook",
+        FileType::Rst => "rst",
         FileType::Code(_) => "code",
         FileType::Unknown => "unknown",
     }
@@ -1052,11 +3581,104 @@ fn extension_to_file_type(ext: &str) -> FileType {
         "ods" => FileType::Ods,
         "epub" => FileType::Epub,
         "image" | "png" | "jpg" | "jpeg" | "gif" | "webp" => FileType::Image,
+        "audio" | "mp3" | "wav" | "m4a" | "flac" | "ogg" => FileType::Audio,
+        "video" | "mp4" | "mov" | "mkv" | "webm" => FileType::Video,
+        "email" | "eml" | "msg" => FileType::Email,
+        "mbox" => FileType::Mbox,
+        "notebook" | "ipynb" => FileType::Notebook,
+        "rst" => FileType::Rst,
         "code" | "rs" | "py" | "js" | "ts" | "go" | "java" | "c" | "cpp" => FileType::Code(ext.to_string()),
         _ => FileType::Unknown,
     }
 }
 
+// ==================== Paged Listings ====================
+
+/// Filters, order and page of a registry listing
+#[derive(Debug, Clone)]
+pub struct RegistryQuery<S> {
+    /// Only rows with this status
+    pub status: Option<String>,
+    /// Only rows whose filename contains this (case-insensitive)
+    pub filename: Option<String>,
+    pub sort: S,
+    pub descending: bool,
+    pub limit: usize,
+    /// Rows to skip (ignored with `after`)
+    pub offset: usize,
+    /// Continue after this sort key and id (see `FileSort::key`, `JobSort::key`)
+    pub after: Option<(String, String)>,
+    /// Leave out rows of these documents (jobs with a file of one of them)
+    pub hidden_documents: Vec<Uuid>,
+}
+
+/// One page of a registry listing
+#[derive(Debug, Clone)]
+pub struct RegistryPage<T> {
+    pub items: Vec<T>,
+    /// Rows matching the filters (all pages)
+    pub total: usize,
+    /// Whether rows follow this page
+    pub has_more: bool,
+}
+
+/// Sort order of file listings (ties are broken by filename)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileSort {
+    Filename,
+    Size,
+    Date,
+}
+
+impl FileSort {
+    fn column(self) -> &'static str {
+        match self {
+            FileSort::Filename => "filename",
+            FileSort::Size => "file_size",
+            FileSort::Date => "last_processed_at",
+        }
+    }
+
+    /// Sort key of a record, as stored
+    pub fn key(self, record: &FileRecord) -> String {
+        match self {
+            FileSort::Filename => record.filename.clone(),
+            FileSort::Size => record.file_size.to_string(),
+            FileSort::Date => record.last_processed_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Sort order of job listings (ties are broken by job id)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobSort {
+    Created,
+    Updated,
+}
+
+impl JobSort {
+    fn column(self) -> &'static str {
+        match self {
+            JobSort::Created => "created_at",
+            JobSort::Updated => "updated_at",
+        }
+    }
+
+    /// Sort key of a job, as stored
+    pub fn key(self, job: &JobRecord) -> String {
+        match self {
+            JobSort::Created => job.created_at.to_rfc3339(),
+            JobSort::Updated => job.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+/// LIKE pattern matching names that contain `text` (with `\` as escape)
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
 // ==================== Job Record Types ====================
 
 /// Job status for persistence
@@ -1075,6 +3697,7 @@ pub enum PersistedJobStatus {
 pub enum PersistedJobStage {
     Queued,
     Uploading,
+    Scanning,
     Parsing,
     Chunking,
     Embedding,
@@ -1119,7 +3742,17 @@ pub struct JobRecord {
 pub struct JobOptions {
     pub chunk_size: Option<usize>,
     pub chunk_overlap: Option<usize>,
+    #[serde(default)]
+    pub chunk_size_unit: Option<crate::config::ChunkSizeUnit>,
     pub parallel_embeddings: usize,
+    #[serde(default)]
+    pub collection: Option<String>,
+    #[serde(default)]
+    pub acl: Vec<String>,
+    #[serde(default)]
+    pub retry: Option<crate::config::RetryConfig>,
+    #[serde(default)]
+    pub priority: crate::processing::JobPriority,
 }
 
 /// Job file record for persistence
@@ -1135,7 +3768,19 @@ pub struct JobFileRecord {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub duration_ms: Option<u64>,
+    /// Contents of files queued before uploads were spooled
     pub file_data: Option<Vec<u8>>,
+    /// Spool store key of the contents until the job finishes
+    #[serde(default)]
+    pub spool_hash: Option<String>,
+    /// Processing attempts made so far
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub failed_stage: Option<String>,
+    /// Failed transiently on every attempt
+    #[serde(default)]
+    pub dead_letter: bool,
 }
 
 impl JobRecord {
@@ -1175,8 +3820,26 @@ impl JobFileRecord {
             completed_at: None,
             duration_ms: None,
             file_data,
+            spool_hash: None,
+            attempts: 0,
+            failed_stage: None,
+            dead_letter: false,
+        }
+    }
+
+    /// A pending file whose contents are in the spool store under `hash`
+    pub fn spooled(filename: String, file_size: u64, hash: String) -> Self {
+        Self {
+            content_hash: Some(hash.clone()),
+            spool_hash: Some(hash),
+            ..Self::new(filename, file_size, None)
         }
     }
+
+    /// Whether the contents are still around to process the file again
+    pub fn has_data(&self) -> bool {
+        self.file_data.is_some() || self.spool_hash.is_some()
+    }
 }
 
 fn job_status_to_string(status: &PersistedJobStatus) -> &'static str {
@@ -1202,6 +3865,7 @@ fn job_stage_to_string(stage: &PersistedJobStage) -> &'static str {
     match stage {
         PersistedJobStage::Queued => "queued",
         PersistedJobStage::Uploading => "uploading",
+        PersistedJobStage::Scanning => "scanning",
         PersistedJobStage::Parsing => "parsing",
         PersistedJobStage::Chunking => "chunking",
         PersistedJobStage::Embedding => "embedding",
@@ -1215,6 +3879,7 @@ fn string_to_job_stage(s: &str) -> PersistedJobStage {
     match s {
         "queued" => PersistedJobStage::Queued,
         "uploading" => PersistedJobStage::Uploading,
+        "scanning" => PersistedJobStage::Scanning,
         "parsing" => PersistedJobStage::Parsing,
         "chunking" => PersistedJobStage::Chunking,
         "embedding" => PersistedJobStage::Embedding,
@@ -1304,6 +3969,10 @@ fn row_to_job_file_record(row: &rusqlite::Row) -> rusqlite::Result<JobFileRecord
     let completed_at_str: Option<String> = row.get(10)?;
     let duration_ms: Option<i64> = row.get(11)?;
     let file_data: Option<Vec<u8>> = row.get(12)?;
+    let attempts: i64 = row.get(13)?;
+    let failed_stage: Option<String> = row.get(14)?;
+    let dead_letter: i64 = row.get(15)?;
+    let spool_hash: Option<String> = row.get(16)?;
 
     Ok(JobFileRecord {
         filename,
@@ -1325,9 +3994,185 @@ fn row_to_job_file_record(row: &rusqlite::Row) -> rusqlite::Result<JobFileRecord
         }),
         duration_ms: duration_ms.map(|d| d as u64),
         file_data,
+        spool_hash,
+        attempts: attempts as u32,
+        failed_stage,
+        dead_letter: dead_letter != 0,
+    })
+}
+
+fn row_to_dead_letter_file(row: &rusqlite::Row) -> rusqlite::Result<DeadLetterFile> {
+    let job_id: String = row.get(0)?;
+    let file_size: i64 = row.get(2)?;
+    let attempts: i64 = row.get(4)?;
+    let failed_at: Option<String> = row.get(6)?;
+
+    Ok(DeadLetterFile {
+        job_id: Uuid::parse_str(&job_id).unwrap_or_default(),
+        filename: row.get(1)?,
+        file_size: file_size as u64,
+        stage: row.get(3)?,
+        attempts: attempts as u32,
+        error: row.get(5)?,
+        failed_at: failed_at.and_then(|s| {
+            DateTime::parse_from_rfc3339(&s)
+                .map(|d| d.with_timezone(&Utc))
+                .ok()
+        }),
+    })
+}
+
+fn row_to_trace(row: &rusqlite::Row) -> rusqlite::Result<RetrievalTrace> {
+    let id_str: String = row.get(0)?;
+    let top_k: i64 = row.get(3)?;
+    let similarity_threshold: f64 = row.get(4)?;
+    let candidates_json: String = row.get(5)?;
+    let interaction_id: Option<String> = row.get(6)?;
+    let latency_ms: i64 = row.get(7)?;
+    let created_at_str: String = row.get(8)?;
+
+    Ok(RetrievalTrace {
+        id: Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::new_v4()),
+        endpoint: row.get(1)?,
+        question: row.get(2)?,
+        top_k: top_k as usize,
+        similarity_threshold: similarity_threshold as f32,
+        candidates: serde_json::from_str(&candidates_json).unwrap_or_default(),
+        interaction_id: interaction_id.and_then(|s| Uuid::parse_str(&s).ok()),
+        latency_ms: latency_ms as u64,
+        created_at: DateTime::parse_from_rfc3339(&created_at_str)
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+fn row_to_audit_record(row: &rusqlite::Row) -> rusqlite::Result<AuditRecord> {
+    let id_str: String = row.get(0)?;
+    let chunks_json: String = row.get(6)?;
+    let interaction_id: Option<String> = row.get(11)?;
+    let latency_ms: i64 = row.get(12)?;
+    let created_at_str: String = row.get(13)?;
+
+    Ok(AuditRecord {
+        id: Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::new_v4()),
+        endpoint: row.get(1)?,
+        question: row.get(2)?,
+        principal: row.get(3)?,
+        collection: row.get(4)?,
+        outcome: row.get(5)?,
+        chunks: serde_json::from_str(&chunks_json).unwrap_or_default(),
+        prompt_sha256: row.get(7)?,
+        model: row.get(8)?,
+        answer: row.get(9)?,
+        error: row.get(10)?,
+        interaction_id: interaction_id.and_then(|s| Uuid::parse_str(&s).ok()),
+        latency_ms: latency_ms as u64,
+        created_at: DateTime::parse_from_rfc3339(&created_at_str)
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+fn row_to_guard_event(row: &rusqlite::Row) -> rusqlite::Result<GuardEvent> {
+    let id_str: String = row.get(0)?;
+    let kind: String = row.get(1)?;
+    let rules: String = row.get(2)?;
+    let document_id: Option<String> = row.get(4)?;
+    let created_at_str: String = row.get(7)?;
+
+    Ok(GuardEvent {
+        id: Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::new_v4()),
+        kind: GuardEventKind::parse(&kind),
+        rules: rules.split(',').filter(|r| !r.is_empty()).map(String::from).collect(),
+        excerpt: row.get(3)?,
+        document_id: document_id.and_then(|s| Uuid::parse_str(&s).ok()),
+        principal: row.get(5)?,
+        blocked: row.get(6)?,
+        created_at: DateTime::parse_from_rfc3339(&created_at_str)
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+/// A stored version, or `None` if its document no longer deserializes
+fn row_to_document_version(row: &rusqlite::Row) -> rusqlite::Result<Option<DocumentVersion>> {
+    let document_json: String = row.get(0)?;
+    let superseded_at: String = row.get(1)?;
+
+    let Ok(document) = serde_json::from_str::<Document>(&document_json) else {
+        return Ok(None);
+    };
+    Ok(Some(DocumentVersion {
+        document,
+        superseded_at: DateTime::parse_from_rfc3339(&superseded_at)
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    }))
+}
+
+fn row_to_chunk_content(row: &rusqlite::Row) -> rusqlite::Result<ChunkContentRecord> {
+    let id: String = row.get(0)?;
+    let document_id: String = row.get(1)?;
+    let chunk_index: i64 = row.get(2)?;
+    let file_type: String = row.get(5)?;
+    let page_number: Option<i64> = row.get(6)?;
+    let char_start: i64 = row.get(8)?;
+    let char_end: i64 = row.get(9)?;
+    let parent_id: Option<String> = row.get(11)?;
+    let kind: String = row.get(12)?;
+
+    Ok(ChunkContentRecord {
+        id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
+        document_id: Uuid::parse_str(&document_id).unwrap_or_else(|_| Uuid::new_v4()),
+        chunk_index: chunk_index as u32,
+        content: row.get(3)?,
+        filename: row.get(4)?,
+        file_type: extension_to_file_type(&file_type),
+        page_number: page_number.map(|p| p as u32),
+        section_title: row.get(7)?,
+        char_start: char_start as usize,
+        char_end: char_end as usize,
+        collection: row.get(10)?,
+        parent_id: parent_id.and_then(|id| Uuid::parse_str(&id).ok()),
+        kind: ChunkKind::from_str_or_text(&kind),
+        keywords: Vec::new(),
+        entities: Vec::new(),
+        embedding_model: row.get(13)?,
+    })
+}
+
+/// Chunk search result from the first ten `chunks_content` columns of a
+/// string search, without score, snippet or spans
+fn row_to_chunk_search_result(row: &rusqlite::Row) -> rusqlite::Result<ChunkSearchResult> {
+    let id: String = row.get(0)?;
+    let document_id: String = row.get(1)?;
+    let chunk_index: i64 = row.get(2)?;
+    let file_type: String = row.get(5)?;
+    let page_number: Option<i64> = row.get(6)?;
+    let char_start: i64 = row.get(8)?;
+    let char_end: i64 = row.get(9)?;
+
+    Ok(ChunkSearchResult {
+        chunk_id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
+        document_id: Uuid::parse_str(&document_id).unwrap_or_else(|_| Uuid::new_v4()),
+        chunk_index: chunk_index as u32,
+        content: row.get(3)?,
+        filename: row.get(4)?,
+        file_type: extension_to_file_type(&file_type),
+        page_number: page_number.map(|p| p as u32),
+        char_start: char_start as usize,
+        char_end: char_end as usize,
+        score: 0.0,
+        snippet: None,
+        spans: Vec::new(),
     })
 }
 
+/// Quote text as an FTS5 phrase
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
 fn row_to_file_record(row: &rusqlite::Row) -> rusqlite::Result<FileRecord> {
     let id_str: String = row.get(0)?;
     let filename: String = row.get(1)?;
@@ -1415,4 +4260,363 @@ mod tests {
         assert_eq!(stats.success, 1);
         assert_eq!(stats.failed, 1);
     }
+
+    #[test]
+    fn test_file_records_page() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        for (name, size) in [("a_report.pdf", 300), ("b-report.pdf", 100), ("notes.txt", 200), ("z_report.pdf", 50)] {
+            db.upsert_file_record(&FileRecord::success(
+                name.to_string(), name.to_string(), size, FileType::Pdf, Uuid::new_v4(), 1, None,
+            )).unwrap();
+        }
+
+        let mut query = RegistryQuery {
+            status: Some("success".to_string()),
+            filename: Some("REPORT".to_string()),
+            sort: FileSort::Size,
+            descending: false,
+            limit: 2,
+            offset: 0,
+            after: None,
+            hidden_documents: Vec::new(),
+        };
+        let first = db.list_file_records_page(&query).unwrap();
+        let names: Vec<_> = first.items.iter().map(|r| r.filename.as_str()).collect();
+        assert_eq!(names, ["z_report.pdf", "b-report.pdf"]);
+        assert_eq!((first.total, first.has_more), (3, true));
+
+        let last = &first.items[1];
+        query.after = Some((FileSort::Size.key(last), last.filename.clone()));
+        let second = db.list_file_records_page(&query).unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert_eq!(second.items[0].filename, "a_report.pdf");
+        assert!(!second.has_more);
+
+        // "_" is matched literally, not as a wildcard
+        query.filename = Some("_report".to_string());
+        query.after = None;
+        assert_eq!(db.list_file_records_page(&query).unwrap().total, 2);
+
+        // Files of hidden documents are left out of the page and the total
+        let hidden = db.get_file_record("z_report.pdf").unwrap().unwrap().document_id.unwrap();
+        query.filename = Some("report".to_string());
+        query.hidden_documents = vec![hidden];
+        let page = db.list_file_records_page(&query).unwrap();
+        assert_eq!(page.total, 2);
+        assert!(page.items.iter().all(|r| r.filename != "z_report.pdf"));
+    }
+
+    fn chunk_record(content: &str, collection: Option<&str>) -> ChunkContentRecord {
+        ChunkContentRecord {
+            id: Uuid::new_v4(),
+            document_id: Uuid::new_v4(),
+            chunk_index: 0,
+            content: content.to_string(),
+            filename: "doc.txt".to_string(),
+            file_type: FileType::Txt,
+            page_number: None,
+            section_title: None,
+            char_start: 0,
+            char_end: content.len(),
+            collection: collection.map(|c| c.to_string()),
+            parent_id: None,
+            kind: ChunkKind::Text,
+            keywords: Vec::new(),
+            entities: Vec::new(),
+            embedding_model: None,
+        }
+    }
+
+    #[test]
+    fn test_chunk_keywords() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let mut acme = chunk_record("Acme ships robots to Berlin", None);
+        acme.keywords = vec!["ships robots".to_string()];
+        acme.entities = vec!["Acme Corporation".to_string(), "Berlin".to_string()];
+        let mut globex = chunk_record("Globex signs in Berlin", None);
+        globex.keywords = vec!["supply agreement".to_string()];
+        globex.entities = vec!["Globex".to_string(), "Berlin".to_string()];
+        db.insert_chunks_content(&[acme.clone(), globex.clone(), chunk_record("untagged", None)]).unwrap();
+
+        let berlin = db.chunk_ids_with_entities(&["berlin".to_string()]).unwrap();
+        assert_eq!(berlin, HashSet::from([acme.id, globex.id]));
+        let both = db.chunk_ids_with_entities(&["Berlin".to_string(), "acme corporation".to_string()]).unwrap();
+        assert_eq!(both, HashSet::from([acme.id]));
+        assert!(db.chunk_ids_with_entities(&["Acme \"Corp".to_string()]).unwrap().is_empty());
+
+        let scores = db.keyword_scores("Which robots does it ship?", &[acme.id, globex.id]).unwrap();
+        assert!(scores.contains_key(&acme.id));
+        assert!(!scores.contains_key(&globex.id));
+
+        // Replacing a chunk replaces its keywords
+        acme.entities.clear();
+        db.insert_chunk_content(&acme).unwrap();
+        assert_eq!(db.chunk_ids_with_entities(&["berlin".to_string()]).unwrap(), HashSet::from([globex.id]));
+    }
+
+    #[test]
+    fn test_collection_analyzers() {
+        use crate::config::{AnalyzerConfig, AnalyzerKind, AnalyzersConfig};
+
+        let mut config = AnalyzersConfig::default();
+        config.collections.insert(
+            "de".to_string(),
+            AnalyzerConfig {
+                kind: AnalyzerKind::Compound,
+                compound_words: vec!["dampf".into(), "schiff".into()],
+                ..Default::default()
+            },
+        );
+        config.collections.insert(
+            "ja".to_string(),
+            AnalyzerConfig { kind: AnalyzerKind::Ngram, ..Default::default() },
+        );
+        let db = FileRegistryDb::in_memory()
+            .unwrap()
+            .with_analyzers(AnalyzerRegistry::from_config(&config).unwrap());
+
+        db.insert_chunks_content(&[
+            chunk_record("Das Dampfschiff legt ab", Some("de")),
+            chunk_record("東京都の人口", Some("ja")),
+            chunk_record("A plain English schiff", None),
+        ]).unwrap();
+
+        // Compound part matches inside the German collection only
+        let results = db.string_search_chunks("Schiff", 10, Some("de"), StringSearchMode::Exact).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].content.contains("Dampfschiff"));
+
+        // Substring of an unsegmented Japanese sentence
+        assert_eq!(db.string_search_chunks("京都", 10, Some("ja"), StringSearchMode::Exact).unwrap().len(), 1);
+
+        // Standard analyzer across all collections does not split compounds
+        assert_eq!(db.string_search_chunks("schiff", 10, None, StringSearchMode::Exact).unwrap().len(), 1);
+
+        // Reindex rebuilds terms and deletes clean them up
+        assert_eq!(db.reindex_chunk_terms(Some("ja")).unwrap(), 1);
+        assert_eq!(db.string_search_chunks("京都", 10, Some("ja"), StringSearchMode::Exact).unwrap().len(), 1);
+        assert_eq!(db.reindex_chunk_terms(None).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_facet_counts() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let mut invoice = chunk_record("Invoice for Acme robots", Some("finance"));
+        invoice.entities = vec!["Acme".to_string(), "ACME".to_string()];
+        let mut memo = chunk_record("Robots memo", None);
+        memo.file_type = FileType::Pdf;
+        memo.entities = vec!["Acme".to_string()];
+        db.insert_chunks_content(&[invoice, memo.clone(), chunk_record("Holiday plan", None)]).unwrap();
+
+        assert_eq!(db.facet_counts(None, None, None).unwrap().len(), 3);
+        assert_eq!(db.facet_counts(Some("robots"), None, None).unwrap().len(), 2);
+        let pdfs = db.facet_counts(Some("robots"), None, Some("PDF")).unwrap();
+        assert_eq!(pdfs.len(), 1);
+        assert_eq!(pdfs[0].document_id, memo.document_id);
+        assert_eq!(pdfs[0].file_type, "pdf");
+        let finance = db.facet_counts(None, Some("finance"), None).unwrap();
+        assert_eq!(finance[0].collection.as_deref(), Some("finance"));
+
+        // One mention per chunk, whatever the casing
+        assert_eq!(db.facet_entities(Some("robots"), None, None).unwrap().len(), 2);
+        assert!(db.facet_entities(Some("holiday"), None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace_chunk_content() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let mut chunk = chunk_record("Tbe quarterly rep0rt", None);
+        db.insert_chunk_content(&chunk).unwrap();
+
+        chunk.content = "The quarterly report".to_string();
+        db.replace_chunk_content(&chunk).unwrap();
+
+        assert_eq!(db.get_chunk_content(&chunk.id).unwrap().unwrap().content, "The quarterly report");
+        assert_eq!(db.string_search_chunks("report", 10, None, StringSearchMode::Exact).unwrap().len(), 1);
+        assert!(db.string_search_chunks("rep0rt", 10, None, StringSearchMode::Exact).unwrap().is_empty());
+        assert!(db.get_chunk_content(&Uuid::new_v4()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_insert_chunk_rows() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let mut chunk = Chunk::new(Uuid::new_v4(), "Borrowed text".to_string(), crate::types::ChunkSource::text("a.txt".to_string()), 0, 13, 0);
+        chunk.metadata.insert(crate::types::document::COLLECTION_METADATA_KEY.to_string(), serde_json::json!("finance"));
+        db.insert_chunk_rows([ChunkContentRow::from(&chunk)]).unwrap();
+        db.insert_chunk_rows(std::iter::empty()).unwrap();
+
+        let stored = db.get_chunk_content(&chunk.id).unwrap().unwrap();
+        assert_eq!((stored.content.as_str(), stored.collection.as_deref()), ("Borrowed text", Some("finance")));
+    }
+
+    #[test]
+    fn test_string_search_modes() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        db.insert_chunks_content(&[
+            chunk_record("Configure the retry policy before deployment", None),
+            chunk_record("Invoices are due within 30 days, ref INV-2024-117", None),
+        ]).unwrap();
+
+        let exact = db.string_search_chunks("retry policy", 10, None, StringSearchMode::Exact).unwrap();
+        assert_eq!(exact.len(), 1);
+        assert!(exact[0].snippet.as_deref().unwrap().contains("<mark>retry"));
+        assert_eq!(exact[0].spans, vec![(14, 26)]);
+
+        let prefix = db.string_search_chunks("config deploy", 10, None, StringSearchMode::Prefix).unwrap();
+        assert_eq!(prefix.len(), 1);
+        assert_eq!(prefix[0].spans.len(), 2);
+
+        let fuzzy = db.string_search_chunks("invoises", 10, None, StringSearchMode::Fuzzy).unwrap();
+        assert_eq!(fuzzy.len(), 1);
+        assert!(fuzzy[0].content.starts_with("Invoices"));
+
+        let regex = db.string_search_chunks(r"INV-\d{4}-\d+", 10, None, StringSearchMode::Regex).unwrap();
+        assert_eq!(regex.len(), 1);
+        assert_eq!(&regex[0].content[regex[0].spans[0].0..regex[0].spans[0].1], "INV-2024-117");
+        assert!(db.string_search_chunks("[unclosed", 10, None, StringSearchMode::Regex).is_err());
+    }
+
+    #[test]
+    fn test_dead_letter_files() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let job_id = Uuid::new_v4();
+        db.create_job(&JobRecord::new(job_id, 2, None)).unwrap();
+        for name in ["flaky.pdf", "broken.pdf"] {
+            db.add_job_file(job_id, &JobFileRecord::new(name.to_string(), 100, None)).unwrap();
+        }
+
+        db.record_job_file_attempt(job_id, "flaky.pdf", 3, "Embedding generation failed: 503").unwrap();
+        db.update_job_file_status(job_id, "flaky.pdf", JobFileStatus::Failed, Some("503"), None, Some(10)).unwrap();
+        db.dead_letter_job_file(job_id, "flaky.pdf", "embedding").unwrap();
+        db.update_job_file_status(job_id, "broken.pdf", JobFileStatus::Failed, Some("bad xref"), None, Some(10)).unwrap();
+
+        let dead = db.list_dead_letter_files(10).unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].job_id, job_id);
+        assert_eq!(dead[0].filename, "flaky.pdf");
+        assert_eq!(dead[0].stage.as_deref(), Some("embedding"));
+        assert_eq!(dead[0].attempts, 3);
+        assert!(dead[0].failed_at.is_some());
+
+        let files = db.get_job_files(job_id).unwrap();
+        assert!(files.iter().any(|f| f.filename == "flaky.pdf" && f.dead_letter));
+        assert!(files.iter().any(|f| f.filename == "broken.pdf" && !f.dead_letter && f.attempts == 0));
+    }
+
+    #[test]
+    fn test_clear_job_file_data_releases_unshared_spool() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let (shared, own) = ("a".repeat(64), "b".repeat(64));
+        for job_id in [first, second] {
+            db.create_job(&JobRecord::new(job_id, 2, None)).unwrap();
+            db.add_job_file(job_id, &JobFileRecord::spooled("shared.pdf".to_string(), 100, shared.clone())).unwrap();
+        }
+        db.add_job_file(first, &JobFileRecord::new("old.pdf".to_string(), 3, Some(b"old".to_vec()))).unwrap();
+        db.set_job_file_spooled(first, "old.pdf", &own).unwrap();
+
+        let files = db.get_pending_job_files(first).unwrap();
+        assert!(files.iter().all(|f| f.has_data() && f.file_data.is_none()));
+
+        assert_eq!(db.clear_job_file_data(first).unwrap(), vec![own]);
+        assert!(db.get_job_files(first).unwrap().iter().all(|f| !f.has_data()));
+        assert_eq!(db.clear_job_file_data(second).unwrap(), vec![shared]);
+    }
+
+    #[test]
+    fn test_pending_embeddings() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let doc_id = Uuid::new_v4();
+        let chunks: Vec<Chunk> = (0..3)
+            .map(|i| Chunk::new(doc_id, format!("chunk {}", i), crate::types::ChunkSource::text("a.txt".to_string()), 0, 7, i))
+            .collect();
+
+        db.insert_pending_embeddings(&chunks, "timeout").unwrap();
+        assert_eq!(db.count_pending_embeddings().unwrap(), 3);
+
+        let pending = db.list_pending_embeddings(10, 0).unwrap();
+        assert_eq!(pending.len(), 3);
+        assert!(pending.iter().all(|c| c.document_id == doc_id && c.embedding.is_empty()));
+        assert_eq!(db.list_pending_embeddings(10, 2).unwrap().len(), 1);
+
+        db.record_pending_embedding_failure(&chunks[0].id, "still down").unwrap();
+        db.delete_pending_embedding(&chunks[1].id).unwrap();
+        assert_eq!(db.count_pending_embeddings().unwrap(), 2);
+
+        assert_eq!(db.delete_pending_embeddings_for_document(&doc_id).unwrap(), 2);
+        assert_eq!(db.count_pending_embeddings().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_purge_documents() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let purged = chunk_record("Acme ships robots", None);
+        let kept = chunk_record("Globex makes widgets", None);
+        db.insert_chunks_content(&[purged.clone(), kept.clone()]).unwrap();
+        for (name, chunk) in [("purged.txt", &purged), ("kept.txt", &kept)] {
+            db.upsert_file_record(&FileRecord::success(
+                name.to_string(), name.to_string(), 10, FileType::Txt, chunk.document_id, 1, None,
+            )).unwrap();
+        }
+        let pending = Chunk::new(purged.document_id, "late".to_string(), crate::types::ChunkSource::text("purged.txt".to_string()), 0, 4, 1);
+        db.insert_pending_embeddings(&[pending], "timeout").unwrap();
+
+        assert_eq!(db.purge_documents(&[purged.document_id]).unwrap(), 1);
+        assert_eq!(db.get_chunks_count_for_document(&purged.document_id).unwrap(), 0);
+        assert_eq!(db.get_chunks_count_for_document(&kept.document_id).unwrap(), 1);
+        assert_eq!(db.count_pending_embeddings().unwrap(), 0);
+        assert!(db.get_file_record("purged.txt").unwrap().is_none());
+        assert!(db.get_file_record("kept.txt").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_document_versions() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        let mut ids = Vec::new();
+        for version in 1..=3 {
+            let mut doc = Document::new("doc.txt".to_string(), FileType::Txt, format!("hash{}", version), 10);
+            doc.set_version(version);
+            let mut chunk = chunk_record(&format!("text of version {}", version), None);
+            chunk.document_id = doc.id;
+            db.archive_document_version(&doc, &[chunk]).unwrap();
+            ids.push(doc.id);
+        }
+        assert_eq!(db.latest_document_version("doc.txt").unwrap(), Some(3));
+        assert_eq!(db.latest_document_version("other.txt").unwrap(), None);
+
+        assert_eq!(db.prune_document_versions("doc.txt", 2).unwrap(), 1);
+        let versions: Vec<u32> = db.list_document_versions("doc.txt").unwrap().iter().map(|v| v.document.version()).collect();
+        assert_eq!(versions, vec![3, 2]);
+        assert!(db.get_document_version(&ids[0]).unwrap().is_none());
+        assert!(db.list_version_chunks(&ids[0]).unwrap().is_empty());
+
+        let chunks = db.list_version_chunks(&ids[2]).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].to_chunk().content, "text of version 3");
+    }
+
+    #[test]
+    fn test_connector_state_and_items() {
+        let db = FileRegistryDb::in_memory().unwrap();
+        assert_eq!(db.connector_state("drive").unwrap(), ConnectorState::default());
+
+        let state = ConnectorState { cursor: Some("42".to_string()), last_run_at: Some(Utc::now()), last_error: None };
+        db.set_connector_state("drive", &state).unwrap();
+        assert_eq!(db.connector_state("drive").unwrap().cursor.as_deref(), Some("42"));
+
+        let item = SyncedItem {
+            item_id: "f1".to_string(),
+            filename: "drive/a.pdf".to_string(),
+            version: Some("v1".to_string()),
+            document_id: Some(Uuid::new_v4()),
+        };
+        db.upsert_connector_item("drive", &item).unwrap();
+        db.upsert_connector_item("other", &item).unwrap();
+        assert_eq!(db.connector_items("drive").unwrap().get("f1"), Some(&item));
+        assert_eq!(db.count_connector_items("drive").unwrap(), 1);
+
+        db.delete_connector_item("drive", "f1").unwrap();
+        assert!(db.connector_items("drive").unwrap().is_empty());
+        assert_eq!(db.count_connector_items("other").unwrap(), 1);
+    }
 }
@@ -0,0 +1,429 @@
+//! Encryption at rest
+//!
+//! One 256-bit key, loaded at startup from an environment variable or a file
+//! and optionally unwrapped with Cloud KMS, encrypts the registry database
+//! with SQLCipher and, with AES-256-GCM, spooled uploads, the chunk text kept
+//! in the local vector index and the originals, plain text and thumbnails of
+//! the local document store.
+//!
+//! Spooled files are encrypted in frames of 64 KiB so uploads are encrypted
+//! as they stream in: the magic `GRSPOOL1`, then per frame a 12-byte random
+//! nonce, the big-endian u32 length of the ciphertext and the ciphertext with
+//! its tag. A frame's associated data is its index and whether it is the last
+//! one, so frames can't be reordered, dropped or cut off unnoticed. Once
+//! encryption is enabled, files without the magic are refused rather than
+//! read as plaintext.
+
+use std::io::Read;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::config::{EncryptionConfig, GcpConfig};
+use crate::error::{Error, Result};
+
+/// Start of an encrypted spool file
+const MAGIC: &[u8] = b"GRSPOOL1";
+/// Plaintext bytes per frame
+const FRAME_SIZE: usize = 64 * 1024;
+const NONCE_LEN: usize = 12;
+
+/// The data encryption key
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    /// Load the key configured in `[encryption]`, `None` if encryption is disabled
+    pub async fn load(config: &EncryptionConfig, gcp: Option<&GcpConfig>) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        if !cfg!(feature = "encryption") {
+            return Err(Error::Config(
+                "Encryption at rest requires building with --features encryption".to_string(),
+            ));
+        }
+
+        let material = match std::env::var(&config.key_env) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => match config.key_file {
+                Some(ref path) => std::fs::read_to_string(path)
+                    .map_err(|e| Error::Config(format!("Failed to read encryption key {:?}: {}", path, e)))?,
+                None => {
+                    return Err(Error::Config(format!(
+                        "Encryption requires the key in ${} or encryption.key_file",
+                        config.key_env
+                    )))
+                }
+            },
+        };
+
+        let key = match config.kms_key {
+            Some(ref kms_key) => unwrap_with_kms(kms_key, material.trim(), gcp).await?,
+            None => material.trim().to_string(),
+        };
+        Self::from_base64(&key).map(Some)
+    }
+
+    /// Key from its base64 encoding
+    pub fn from_base64(text: &str) -> Result<Self> {
+        BASE64
+            .decode(text.trim())
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .map(Self)
+            .ok_or_else(|| Error::Config("Encryption key must be 32 bytes, base64-encoded".to_string()))
+    }
+
+    /// The key as a SQLCipher raw key literal, for `PRAGMA key` and `ATTACH ... KEY`
+    pub(crate) fn sqlcipher_key(&self) -> String {
+        format!("\"x'{}'\"", hex::encode(self.0))
+    }
+
+    /// Statement keying a SQLCipher connection with this key
+    pub(crate) fn sqlcipher_pragma(&self) -> String {
+        format!("PRAGMA key = {};", self.sqlcipher_key())
+    }
+}
+
+/// Unwrap a KMS-encrypted key, returning the base64 key
+#[cfg(feature = "gcp")]
+async fn unwrap_with_kms(kms_key: &str, wrapped: &str, gcp: Option<&GcpConfig>) -> Result<String> {
+    let gcp = gcp.ok_or_else(|| {
+        Error::Config("Unwrapping the encryption key with Cloud KMS needs [gcp] credentials".to_string())
+    })?;
+    let auth =
+        crate::providers::gcp::GcpAuth::from_service_account(&gcp.service_account_key_path, gcp.project_id.clone())?;
+
+    let response = reqwest::Client::new()
+        .post(format!("https://cloudkms.googleapis.com/v1/{}:decrypt", kms_key))
+        .bearer_auth(auth.get_token().await?)
+        .json(&serde_json::json!({ "ciphertext": wrapped }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Config(format!("Cloud KMS failed to unwrap the encryption key ({}): {}", status, body)));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct DecryptResponse {
+        plaintext: String,
+    }
+    let decrypted: DecryptResponse = response.json().await?;
+    let plaintext = BASE64
+        .decode(&decrypted.plaintext)
+        .map_err(|e| Error::Config(format!("Invalid Cloud KMS response: {}", e)))?;
+    // Either the raw 32-byte key or its base64 text was wrapped
+    Ok(if plaintext.len() == 32 {
+        BASE64.encode(&plaintext)
+    } else {
+        String::from_utf8_lossy(&plaintext).trim().to_string()
+    })
+}
+
+#[cfg(not(feature = "gcp"))]
+async fn unwrap_with_kms(_kms_key: &str, _wrapped: &str, _gcp: Option<&GcpConfig>) -> Result<String> {
+    Err(Error::Config("Unwrapping the encryption key with Cloud KMS requires the gcp feature".to_string()))
+}
+
+/// Encrypts and decrypts spooled files
+#[derive(Debug, Clone)]
+pub struct SpoolCipher {
+    key: EncryptionKey,
+}
+
+impl SpoolCipher {
+    pub fn new(key: EncryptionKey) -> Self {
+        Self { key }
+    }
+
+    /// Encryptor for one file
+    pub fn encryptor(&self) -> FrameEncryptor {
+        FrameEncryptor {
+            cipher: self.clone(),
+            buffer: Vec::new(),
+            index: 0,
+            started: false,
+        }
+    }
+
+    /// Encrypt a whole file
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut encryptor = self.encryptor();
+        let mut out = encryptor.update(data)?;
+        out.extend(encryptor.finish()?);
+        Ok(out)
+    }
+
+    /// Bytes needed to tell an encrypted spool file by its start
+    pub const HEADER_LEN: usize = MAGIC.len();
+
+    /// Whether `data` is an encrypted spool file
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    /// Decrypt a whole file
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let corrupt = |what: &str| Error::StorageCorruption(format!("Encrypted spool file {}", what));
+        let mut rest = data.strip_prefix(MAGIC).ok_or_else(|| corrupt("has no header"))?;
+        let mut plaintext = Vec::with_capacity(rest.len());
+        let mut index = 0u64;
+        loop {
+            if rest.len() < NONCE_LEN + 4 {
+                return Err(corrupt("is truncated"));
+            }
+            let (nonce, tail) = rest.split_at(NONCE_LEN);
+            let (len, tail) = tail.split_at(4);
+            let len = u32::from_be_bytes(len.try_into().expect("4 bytes")) as usize;
+            if tail.len() < len {
+                return Err(corrupt("is truncated"));
+            }
+            let (ciphertext, tail) = tail.split_at(len);
+            let last = tail.is_empty();
+            let frame = open_frame(&self.key, nonce, ciphertext, &associated_data(index, last))
+                .map_err(|_| corrupt("failed authentication (wrong key or tampered)"))?;
+            plaintext.extend_from_slice(&frame);
+            if last {
+                return Ok(plaintext);
+            }
+            rest = tail;
+            index += 1;
+        }
+    }
+
+    /// Decrypt a file frame by frame as it is read
+    pub fn decryptor<R: Read>(&self, inner: R) -> FrameDecryptor<R> {
+        FrameDecryptor {
+            cipher: self.clone(),
+            inner,
+            index: 0,
+            next_header: None,
+            frame: Vec::new(),
+            position: 0,
+            started: false,
+            finished: false,
+        }
+    }
+}
+
+/// Decrypts one file as it is read
+///
+/// Holds one frame of plaintext at a time. The header of the following frame
+/// is read ahead to know whether the current one is the last.
+pub struct FrameDecryptor<R: Read> {
+    cipher: SpoolCipher,
+    inner: R,
+    index: u64,
+    next_header: Option<[u8; NONCE_LEN + 4]>,
+    frame: Vec<u8>,
+    position: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl<R: Read> FrameDecryptor<R> {
+    fn next_frame(&mut self) -> Result<()> {
+        let corrupt = |what: &str| Error::StorageCorruption(format!("Encrypted spool file {}", what));
+        if !std::mem::replace(&mut self.started, true) {
+            let mut magic = [0u8; MAGIC.len()];
+            if read_full(&mut self.inner, &mut magic)? < MAGIC.len() || magic != MAGIC {
+                return Err(corrupt("has no header"));
+            }
+            self.next_header = self.read_header()?;
+        }
+        let header = self.next_header.take().ok_or_else(|| corrupt("is truncated"))?;
+        let (nonce, len) = header.split_at(NONCE_LEN);
+        let len = u32::from_be_bytes(len.try_into().expect("4 bytes")) as usize;
+        let mut ciphertext = vec![0u8; len];
+        if read_full(&mut self.inner, &mut ciphertext)? < len {
+            return Err(corrupt("is truncated"));
+        }
+        self.next_header = self.read_header()?;
+        let last = self.next_header.is_none();
+        self.frame = open_frame(&self.cipher.key, nonce, &ciphertext, &associated_data(self.index, last))
+            .map_err(|_| corrupt("failed authentication (wrong key or tampered)"))?;
+        self.position = 0;
+        self.index += 1;
+        self.finished = last;
+        Ok(())
+    }
+
+    /// Header of the next frame, `None` at the end of the file
+    fn read_header(&mut self) -> Result<Option<[u8; NONCE_LEN + 4]>> {
+        let mut header = [0u8; NONCE_LEN + 4];
+        match read_full(&mut self.inner, &mut header)? {
+            0 => Ok(None),
+            n if n < header.len() => Err(Error::StorageCorruption("Encrypted spool file is truncated".to_string())),
+            _ => Ok(Some(header)),
+        }
+    }
+}
+
+impl<R: Read> Read for FrameDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.frame.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_frame()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+        let n = buf.len().min(self.frame.len() - self.position);
+        buf[..n].copy_from_slice(&self.frame[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Read until `buf` is full or the end of input, returning the bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypts one file as it is written
+pub struct FrameEncryptor {
+    cipher: SpoolCipher,
+    buffer: Vec<u8>,
+    index: u64,
+    started: bool,
+}
+
+impl FrameEncryptor {
+    /// Take more plaintext, returning the bytes to write
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+        let mut out = self.header();
+        // Keep at least one byte back: the last frame is sealed by `finish`
+        while self.buffer.len() > FRAME_SIZE {
+            let rest = self.buffer.split_off(FRAME_SIZE);
+            let frame = std::mem::replace(&mut self.buffer, rest);
+            out.extend(self.seal(&frame, false)?);
+        }
+        Ok(out)
+    }
+
+    /// Seal the last frame, returning the bytes to write
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let mut out = self.header();
+        let frame = std::mem::take(&mut self.buffer);
+        out.extend(self.seal(&frame, true)?);
+        Ok(out)
+    }
+
+    fn header(&mut self) -> Vec<u8> {
+        if std::mem::replace(&mut self.started, true) {
+            Vec::new()
+        } else {
+            MAGIC.to_vec()
+        }
+    }
+
+    fn seal(&mut self, frame: &[u8], last: bool) -> Result<Vec<u8>> {
+        let (nonce, ciphertext) = seal_frame(&self.cipher.key, frame, &associated_data(self.index, last))?;
+        self.index += 1;
+        let mut out = Vec::with_capacity(NONCE_LEN + 4 + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&(ciphertext.len() as u32).to_be_bytes());
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+}
+
+fn associated_data(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&index.to_be_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+#[cfg(feature = "encryption")]
+fn seal_frame(key: &EncryptionKey, plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+    use aes_gcm::{Aes256Gcm, Key};
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| Error::Internal("Failed to encrypt spool frame".to_string()))?;
+    Ok((nonce.to_vec(), ciphertext))
+}
+
+#[cfg(feature = "encryption")]
+fn open_frame(key: &EncryptionKey, nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| Error::StorageCorruption("Spool frame failed authentication".to_string()))
+}
+
+// Keys can only be loaded with the feature, so these are never reached
+#[cfg(not(feature = "encryption"))]
+fn seal_frame(_key: &EncryptionKey, _plaintext: &[u8], _aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    Err(Error::Config("Encryption at rest requires building with --features encryption".to_string()))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_frame(_key: &EncryptionKey, _nonce: &[u8], _ciphertext: &[u8], _aad: &[u8]) -> Result<Vec<u8>> {
+    Err(Error::Config("Encryption at rest requires building with --features encryption".to_string()))
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip_and_detect_tampering() {
+        let cipher = SpoolCipher::new(EncryptionKey::from_base64(&BASE64.encode([7u8; 32])).unwrap());
+        let data: Vec<u8> = (0..FRAME_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+
+        let mut encryptor = cipher.encryptor();
+        let mut file = encryptor.update(&data[..1000]).unwrap();
+        file.extend(encryptor.update(&data[1000..]).unwrap());
+        file.extend(encryptor.finish().unwrap());
+        assert!(SpoolCipher::is_encrypted(&file));
+        assert_eq!(cipher.decrypt(&file).unwrap(), data);
+        assert_eq!(cipher.decrypt(&cipher.encrypt(&data).unwrap()).unwrap(), data);
+
+        let empty = cipher.encryptor().finish().unwrap();
+        assert!(cipher.decrypt(&empty).unwrap().is_empty());
+
+        let mut streamed = Vec::new();
+        cipher.decryptor(&file[..]).read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, data);
+        let mut streamed = Vec::new();
+        cipher.decryptor(&empty[..]).read_to_end(&mut streamed).unwrap();
+        assert!(streamed.is_empty());
+
+        // Dropping the last frame makes the previous one look last
+        let first_frame = MAGIC.len() + NONCE_LEN + 4 + FRAME_SIZE + 16;
+        assert!(cipher.decrypt(&file[..first_frame]).is_err());
+        assert!(cipher.decryptor(&file[..first_frame]).read_to_end(&mut Vec::new()).is_err());
+        let mut tampered = file.clone();
+        tampered[MAGIC.len() + NONCE_LEN + 10] ^= 1;
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        let other = SpoolCipher::new(EncryptionKey::from_base64(&BASE64.encode([8u8; 32])).unwrap());
+        assert!(other.decrypt(&file).is_err());
+    }
+}
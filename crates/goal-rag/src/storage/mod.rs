@@ -2,12 +2,25 @@
 //!
 //! Provides SQLite-based persistence for file registry and documents.
 
+pub mod backup;
+mod chunk_store;
 mod database;
+mod encryption;
+mod snapshot;
+mod spool;
 
 pub use database::{
     FileRegistryDb, FileRegistryDbStats, SyncStatus,
+    // Paged listings
+    FileSort, JobSort, RegistryPage, RegistryQuery,
     // Job persistence types
-    JobFileRecord, JobFileStatus, JobOptions, JobRecord, PersistedJobStage, PersistedJobStatus,
+    DeadLetterFile, JobFileRecord, JobFileStatus, JobOptions, JobRecord, PersistedJobStage, PersistedJobStatus,
     // Chunk content types (for FTS)
-    ChunkContentRecord, ChunkSearchResult,
+    ChunkContentRecord, ChunkContentRow, ChunkSearchResult, FacetChunkCount,
+    // Document history
+    DocumentVersion,
 };
+pub use chunk_store::ChunkStore;
+pub use encryption::{EncryptionKey, FrameEncryptor, SpoolCipher};
+pub use snapshot::{SnapshotInfo, SnapshotManager};
+pub use spool::{SpoolStore, SpoolWriter, SpooledData};
@@ -0,0 +1,217 @@
+//! Point-in-time snapshots of the SQLite registry
+//!
+//! Snapshots are taken with SQLite's online backup API (see
+//! [`FileRegistryDb::snapshot_to`]) into `rag_registry-<time>.db` files, on
+//! request or every `snapshots.interval_secs`. A snapshot is written under a
+//! temporary name and renamed when complete, so the directory only ever
+//! lists whole snapshots. Only the newest `snapshots.keep` are kept locally;
+//! with `snapshots.upload` each is also copied to the document store.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::SnapshotsConfig;
+use crate::error::{Error, Result};
+use crate::server::state::AppState;
+
+use super::FileRegistryDb;
+
+const PREFIX: &str = "rag_registry-";
+const SUFFIX: &str = ".db";
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// A snapshot in the snapshot directory
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SnapshotInfo {
+    /// File name, also what `restore-snapshot` takes
+    pub name: String,
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+    /// Document store URI of the uploaded copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploaded_to: Option<String>,
+}
+
+/// Takes, lists and prunes registry snapshots
+pub struct SnapshotManager {
+    config: SnapshotsConfig,
+    directory: PathBuf,
+}
+
+impl SnapshotManager {
+    /// Snapshots go to the configured directory, else `snapshots` in `storage_dir`
+    pub fn new(config: SnapshotsConfig, storage_dir: &Path) -> Self {
+        let directory = config.directory.clone().unwrap_or_else(|| storage_dir.join("snapshots"));
+        Self { config, directory }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Take a snapshot of the registry, then drop the oldest beyond `keep`
+    pub async fn take(&self, state: &AppState) -> Result<SnapshotInfo> {
+        let database = state.database().clone();
+        let directory = self.directory.clone();
+        let keep = self.config.keep;
+        #[allow(unused_mut)]
+        let mut info = tokio::task::spawn_blocking(move || {
+            let info = write_snapshot(&database, &directory)?;
+            prune(&directory, keep)?;
+            Ok::<_, Error>(info)
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??;
+
+        #[cfg(feature = "gcp")]
+        if self.config.upload {
+            use crate::providers::document_store::DocumentStoreProvider;
+
+            if let Some(store) = state.document_store() {
+                let data = tokio::fs::read(&info.path).await?;
+                match store.store_document(&uuid::Uuid::new_v4(), &info.name, &data).await {
+                    Ok(uri) => info.uploaded_to = Some(uri),
+                    Err(e) => tracing::warn!("Failed to upload snapshot {}: {}", info.name, e),
+                }
+            }
+        }
+
+        tracing::info!("Took registry snapshot {} ({} bytes)", info.name, info.size);
+        Ok(info)
+    }
+
+    /// Snapshots in the directory, newest first
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>> {
+        list_snapshots(&self.directory)
+    }
+
+    /// Delete the snapshots taken before `time`, returning how many
+    ///
+    /// Used by erasure: a snapshot holds the registry rows of every document
+    /// it was taken with. Copies uploaded to the document store are not
+    /// tracked and are left to the bucket's retention policy.
+    pub fn remove_before(&self, time: DateTime<Utc>) -> Result<usize> {
+        let stale: Vec<SnapshotInfo> =
+            self.list()?.into_iter().filter(|snapshot| snapshot.created_at < time).collect();
+        for snapshot in &stale {
+            std::fs::remove_file(&snapshot.path)?;
+        }
+        Ok(stale.len())
+    }
+
+    /// Take a snapshot every `interval`
+    pub fn spawn_periodic(self: &Arc<Self>, state: AppState, interval: Duration) {
+        let snapshots = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = snapshots.take(&state).await {
+                    tracing::warn!("Scheduled registry snapshot failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn write_snapshot(database: &FileRegistryDb, directory: &Path) -> Result<SnapshotInfo> {
+    std::fs::create_dir_all(directory)?;
+    let created_at = Utc::now();
+    let name = format!("{}{}{}", PREFIX, created_at.format(TIME_FORMAT), SUFFIX);
+    let path = directory.join(&name);
+    let partial = directory.join(format!("{}.partial", name));
+
+    if let Err(e) = database.snapshot_to(&partial) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, &path)?;
+
+    Ok(SnapshotInfo {
+        name,
+        size: std::fs::metadata(&path)?.len(),
+        path,
+        created_at,
+        uploaded_to: None,
+    })
+}
+
+fn list_snapshots(directory: &Path) -> Result<Vec<SnapshotInfo>> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(created_at) = snapshot_time(&name) else {
+            continue;
+        };
+        snapshots.push(SnapshotInfo {
+            size: entry.metadata()?.len(),
+            path: entry.path(),
+            name,
+            created_at,
+            uploaded_to: None,
+        });
+    }
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(snapshots)
+}
+
+/// Creation time of a snapshot file name (None for other files)
+fn snapshot_time(name: &str) -> Option<DateTime<Utc>> {
+    let time = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok().map(|time| time.and_utc())
+}
+
+fn prune(directory: &Path, keep: usize) -> Result<usize> {
+    let stale: Vec<SnapshotInfo> = list_snapshots(directory)?.into_iter().skip(keep.max(1)).collect();
+    for snapshot in &stale {
+        std::fs::remove_file(&snapshot.path)?;
+    }
+    Ok(stale.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_restore_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("rag_registry.db");
+        let db = FileRegistryDb::new(&db_path).unwrap();
+        db.upsert_file_record(&crate::types::FileRecord::success(
+            "a.txt".to_string(), "hash".to_string(), 1, crate::types::FileType::Txt, uuid::Uuid::new_v4(), 1, None,
+        )).unwrap();
+
+        let snapshots = dir.path().join("snapshots");
+        let first = write_snapshot(&db, &snapshots).unwrap();
+        db.delete_file_record("a.txt").unwrap();
+        drop(db);
+
+        FileRegistryDb::restore_snapshot(&first.path, &db_path, None).unwrap();
+        let restored = FileRegistryDb::new(&db_path).unwrap();
+        assert!(restored.get_file_record("a.txt").unwrap().is_some());
+
+        std::thread::sleep(Duration::from_millis(5));
+        write_snapshot(&restored, &snapshots).unwrap();
+        assert_eq!(prune(&snapshots, 1).unwrap(), 1);
+        let left = list_snapshots(&snapshots).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_ne!(left[0].name, first.name);
+    }
+}
@@ -0,0 +1,368 @@
+//! Content-addressed spool for uploaded files
+//!
+//! Uploads go to disk as soon as they are received instead of travelling
+//! with their job as bytes: each file is stored once as
+//! `<dir>/<hash[..2]>/<hash>`, keyed by the SHA-256 of its contents, and jobs
+//! (in memory, in SQLite and on a shared queue) only carry the hash. Workers
+//! memory-map a file when they get to it, so a large batch is read from disk
+//! as it is parsed rather than held in RAM from upload to completion.
+//!
+//! Files are written under a temporary name and renamed, and never modified
+//! afterwards. The job queue removes a file once no job file references it.
+//!
+//! With encryption at rest the files are encrypted as they are written (the
+//! hash is still that of the plaintext) and decrypted into memory when opened
+//! instead of being mapped. Large files that are only read front to back go
+//! through `reader`, which decrypts them a frame at a time. A plaintext file
+//! in an encrypted spool is refused: it was either spooled before encryption
+//! was enabled (drain the queue first) or put there by someone else.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::error::{Error, Result};
+
+use super::encryption::{FrameEncryptor, SpoolCipher};
+
+/// Spooled files addressed by content hash
+pub struct SpoolStore {
+    directory: PathBuf,
+    cipher: Option<SpoolCipher>,
+}
+
+impl SpoolStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory, cipher: None }
+    }
+
+    /// Encrypt files spooled from now on
+    pub fn with_cipher(mut self, cipher: Option<SpoolCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Spool `data`, returning its hash
+    pub fn put(&self, data: &[u8]) -> Result<String> {
+        self.put_reader(data).map(|(hash, _)| hash)
+    }
+
+    /// Spool everything read from `reader`, hashing on the way
+    ///
+    /// Returns the hash and size. Blocking: run it on a blocking thread for
+    /// anything but small inputs.
+    pub fn put_reader(&self, mut reader: impl Read) -> Result<(String, u64)> {
+        std::fs::create_dir_all(&self.directory)?;
+        let mut temp = tempfile::NamedTempFile::new_in(&self.directory)?;
+        let mut hasher = Sha256::new();
+        let mut encryptor = self.cipher.as_ref().map(SpoolCipher::encryptor);
+        let mut size = 0u64;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            match encryptor.as_mut() {
+                Some(encryptor) => temp.write_all(&encryptor.update(&buf[..n])?)?,
+                None => temp.write_all(&buf[..n])?,
+            }
+            size += n as u64;
+        }
+        if let Some(encryptor) = encryptor {
+            temp.write_all(&encryptor.finish()?)?;
+        }
+        temp.flush()?;
+
+        let hash = hex::encode(hasher.finalize());
+        let path = self.path(&hash)?;
+        // Identical contents are already there
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            temp.persist(&path).map_err(|e| Error::Io(e.error))?;
+        }
+        Ok((hash, size))
+    }
+
+    /// Start spooling a file that arrives in pieces
+    pub async fn writer(&self) -> Result<SpoolWriter> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let directory = self.directory.clone();
+        let (file, path) = tokio::task::spawn_blocking(move || tempfile::NamedTempFile::new_in(directory))
+            .await
+            .map_err(|e| Error::Internal(format!("Task join error: {}", e)))??
+            .into_parts();
+        Ok(SpoolWriter {
+            file: tokio::fs::File::from_std(file),
+            path,
+            target: self.directory.clone(),
+            hasher: Sha256::new(),
+            size: 0,
+            encryptor: self.cipher.as_ref().map(SpoolCipher::encryptor),
+            cipher: self.cipher.clone(),
+        })
+    }
+
+    /// Where the file of `hash` is (or would be) spooled
+    pub fn path(&self, hash: &str) -> Result<PathBuf> {
+        hashed_path(&self.directory, hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path(hash).map_or(false, |path| path.is_file())
+    }
+
+    /// Map the file of `hash` into memory
+    pub fn open(&self, hash: &str) -> Result<SpooledData> {
+        let file = self.open_file(hash)?;
+        if file.metadata()?.len() == 0 {
+            self.check_plaintext(hash)?;
+            return Ok(SpooledData(Contents::Empty));
+        }
+        // SAFETY: spooled files are renamed into place complete and never
+        // written again; removal only unlinks them, which leaves the mapping valid
+        let map = unsafe { Mmap::map(&file)? };
+        if !SpoolCipher::is_encrypted(&map) {
+            self.check_plaintext(hash)?;
+            return Ok(SpooledData(Contents::Mapped(map)));
+        }
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            Error::Config(format!("Spooled file {} is encrypted but encryption is not configured", hash))
+        })?;
+        Ok(SpooledData::decrypted(cipher.decrypt(&map)?))
+    }
+
+    /// Read the file of `hash` from the start
+    ///
+    /// Encrypted files are decrypted a frame at a time as they are read, so
+    /// unlike `open` this never holds the whole file in memory. Blocking.
+    pub fn reader(&self, hash: &str) -> Result<Box<dyn BufRead + Send>> {
+        let mut file = self.open_file(hash)?;
+        let mut head = Vec::with_capacity(SpoolCipher::HEADER_LEN);
+        (&mut file).take(SpoolCipher::HEADER_LEN as u64).read_to_end(&mut head)?;
+        let encrypted = SpoolCipher::is_encrypted(&head);
+        let source = std::io::Cursor::new(head).chain(file);
+        if !encrypted {
+            self.check_plaintext(hash)?;
+            return Ok(Box::new(BufReader::new(source)));
+        }
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            Error::Config(format!("Spooled file {} is encrypted but encryption is not configured", hash))
+        })?;
+        Ok(Box::new(BufReader::new(cipher.decryptor(source))))
+    }
+
+    /// Refuse a plaintext file when files are encrypted
+    fn check_plaintext(&self, hash: &str) -> Result<()> {
+        match self.cipher {
+            Some(_) => Err(Error::StorageCorruption(format!("Spooled file {} is not encrypted", hash))),
+            None => Ok(()),
+        }
+    }
+
+    fn open_file(&self, hash: &str) -> Result<std::fs::File> {
+        std::fs::File::open(self.path(hash)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::StorageCorruption(format!("Spooled file {} is missing", hash)),
+            _ => Error::Io(e),
+        })
+    }
+
+    /// Remove the file of `hash` (a missing file is not an error)
+    pub fn remove(&self, hash: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(hash)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A file being written to the spool, hashed as it is written
+///
+/// Dropping the writer discards what was written.
+pub struct SpoolWriter {
+    file: tokio::fs::File,
+    path: tempfile::TempPath,
+    target: PathBuf,
+    hasher: Sha256,
+    size: u64,
+    encryptor: Option<FrameEncryptor>,
+    cipher: Option<SpoolCipher>,
+}
+
+impl SpoolWriter {
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.hasher.update(data);
+        match self.encryptor.as_mut() {
+            Some(encryptor) => self.file.write_all(&encryptor.update(data)?).await?,
+            None => self.file.write_all(data).await?,
+        }
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Write out the end of the file
+    async fn finish(&mut self) -> Result<()> {
+        if let Some(encryptor) = self.encryptor.take() {
+            self.file.write_all(&encryptor.finish()?).await?;
+        }
+        self.file.flush().await?;
+        Ok(())
+    }
+
+    /// Bytes written so far
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Store the file under its hash, returning the hash and size
+    pub async fn commit(mut self) -> Result<(String, u64)> {
+        self.finish().await?;
+        let hash = hex::encode(self.hasher.finalize());
+        let path = hashed_path(&self.target, &hash)?;
+        // Identical contents are already there
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            self.path.persist(&path).map_err(|e| Error::Io(e.error))?;
+        }
+        Ok((hash, self.size))
+    }
+
+    /// Map the file without storing it, returning its hash and contents
+    ///
+    /// The file is removed right away; the contents stay readable until
+    /// they are dropped.
+    pub async fn into_data(mut self) -> Result<(String, SpooledData)> {
+        self.finish().await?;
+        let hash = hex::encode(self.hasher.finalize());
+        if self.size == 0 {
+            return Ok((hash, SpooledData(Contents::Empty)));
+        }
+        let file = self.file.into_std().await;
+        // SAFETY: the file is private to this writer and no longer written to
+        let map = unsafe { Mmap::map(&file)? };
+        match self.cipher {
+            Some(cipher) => Ok((hash, SpooledData::decrypted(cipher.decrypt(&map)?))),
+            None => Ok((hash, SpooledData(Contents::Mapped(map)))),
+        }
+    }
+}
+
+fn hashed_path(directory: &Path, hash: &str) -> Result<PathBuf> {
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(Error::Config(format!("Invalid spool hash '{}'", hash)));
+    }
+    Ok(directory.join(&hash[..2]).join(hash))
+}
+
+/// Contents of a spooled file, paged in from disk as they are read (or
+/// decrypted into memory)
+pub struct SpooledData(Contents);
+
+enum Contents {
+    Empty,
+    Mapped(Mmap),
+    Decrypted(Vec<u8>),
+}
+
+impl SpooledData {
+    fn decrypted(data: Vec<u8>) -> Self {
+        Self(Contents::Decrypted(data))
+    }
+}
+
+impl Deref for SpooledData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.0 {
+            Contents::Empty => &[],
+            Contents::Mapped(ref map) => map,
+            Contents::Decrypted(ref data) => data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_is_content_addressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = SpoolStore::new(dir.path().join("spool"));
+
+        let hash = spool.put(b"hello").unwrap();
+        assert_eq!(hash, hex::encode(Sha256::digest(b"hello")));
+        let (again, size) = spool.put_reader(&b"hello"[..]).unwrap();
+        assert_eq!((again.as_str(), size), (hash.as_str(), 5));
+        assert_eq!(&*spool.open(&hash).unwrap(), b"hello");
+        let mut read = Vec::new();
+        spool.reader(&hash).unwrap().read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"hello");
+
+        let empty = spool.put(b"").unwrap();
+        assert!(spool.open(&empty).unwrap().is_empty());
+
+        spool.remove(&hash).unwrap();
+        assert!(!spool.contains(&hash));
+        assert!(spool.open(&hash).is_err());
+        assert!(spool.path("../../etc/passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spool_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = SpoolStore::new(dir.path().to_path_buf());
+
+        let mut writer = spool.writer().await.unwrap();
+        writer.write(b"hel").await.unwrap();
+        writer.write(b"lo").await.unwrap();
+        let (hash, size) = writer.commit().await.unwrap();
+        assert_eq!((hash.as_str(), size), (spool.put(b"hello").unwrap().as_str(), 5));
+
+        let mut writer = spool.writer().await.unwrap();
+        writer.write(b"transient").await.unwrap();
+        let (hash, data) = writer.into_data().await.unwrap();
+        assert_eq!(&*data, b"transient");
+        assert!(!spool.contains(&hash));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_spool() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain = SpoolStore::new(dir.path().to_path_buf());
+        let legacy = plain.put(b"written before encryption").unwrap();
+
+        let key = crate::storage::EncryptionKey::from_base64(&BASE64.encode([7u8; 32])).unwrap();
+        let spool = SpoolStore::new(dir.path().to_path_buf()).with_cipher(Some(SpoolCipher::new(key)));
+        let hash = spool.put(b"secret").unwrap();
+        assert_eq!(hash, hex::encode(Sha256::digest(b"secret")));
+        assert!(SpoolCipher::is_encrypted(&std::fs::read(spool.path(&hash).unwrap()).unwrap()));
+        assert_eq!(&*spool.open(&hash).unwrap(), b"secret");
+        let mut read = Vec::new();
+        spool.reader(&hash).unwrap().read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"secret");
+        assert!(matches!(spool.open(&legacy), Err(Error::StorageCorruption(_))));
+        assert!(matches!(spool.reader(&legacy), Err(Error::StorageCorruption(_))));
+        let empty = plain.put(b"").unwrap();
+        assert!(spool.open(&empty).is_err());
+        assert!(plain.open(&hash).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Tracing subscriber setup and OpenTelemetry export
+//!
+//! Logs always go to stdout. With the `otel` feature and
+//! `observability.otlp_endpoint` set, spans are also exported over OTLP so a
+//! slow query shows up as one waterfall (HTTP request → embed → retrieve →
+//! generate) in Jaeger or Tempo. Ingestion jobs export one span per job with a
+//! child span per file, including files processed in parallel.
+
+use axum::http::Request;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::config::ObservabilityConfig;
+use crate::error::{Error, Result};
+
+/// Default log filter when `RUST_LOG` is not set
+const DEFAULT_FILTER: &str = "goal_rag=info,tower_http=debug";
+
+/// Keeps the span exporter alive; flushes pending spans on drop
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush OpenTelemetry spans: {}", e);
+            }
+        }
+    }
+}
+
+/// Install the global tracing subscriber
+///
+/// Must be called from within a Tokio runtime when OTLP export is enabled.
+pub fn init(config: &ObservabilityConfig) -> Result<TelemetryGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_FILTER.into());
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer());
+
+    #[cfg(feature = "otel")]
+    if let Some(ref endpoint) = config.otlp_endpoint {
+        use opentelemetry::trace::TracerProvider as _;
+
+        let provider = otel::build_provider(config, endpoint)?;
+        let tracer = provider.tracer(config.service_name.clone());
+        registry
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .try_init()
+            .map_err(|e| Error::Internal(format!("Failed to initialize tracing: {}", e)))?;
+
+        tracing::info!(
+            "Exporting traces to {} ({:?}, sample ratio {})",
+            endpoint,
+            config.otlp_protocol,
+            config.sample_ratio
+        );
+        return Ok(TelemetryGuard { provider: Some(provider) });
+    }
+
+    registry
+        .try_init()
+        .map_err(|e| Error::Internal(format!("Failed to initialize tracing: {}", e)))?;
+
+    #[cfg(not(feature = "otel"))]
+    if config.otlp_endpoint.is_some() {
+        tracing::warn!("observability.otlp_endpoint is set but the server was built without the `otel` feature");
+    }
+
+    Ok(TelemetryGuard {
+        #[cfg(feature = "otel")]
+        provider: None,
+    })
+}
+
+/// Root span for an HTTP request (used with `TraceLayer::make_span_with`)
+///
+/// Continues the caller's trace when the request carries a W3C
+/// `traceparent` header.
+pub fn make_request_span<B>(request: &Request<B>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format!("{} {}", request.method(), request.uri().path()),
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+    );
+
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&otel::HeaderExtractor(request.headers()))
+        });
+        span.set_parent(parent);
+    }
+
+    span
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use axum::http::HeaderMap;
+    use opentelemetry::{propagation::Extractor, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{
+        propagation::TraceContextPropagator,
+        trace::{Sampler, TracerProvider},
+        Resource,
+    };
+    use std::time::Duration;
+
+    use crate::config::{ObservabilityConfig, OtlpProtocol};
+    use crate::error::{Error, Result};
+
+    /// Build the batch-exporting tracer provider and register it globally
+    pub(super) fn build_provider(config: &ObservabilityConfig, endpoint: &str) -> Result<TracerProvider> {
+        let timeout = Duration::from_secs(config.timeout_secs.max(1));
+        let exporter = match config.otlp_protocol {
+            OtlpProtocol::Grpc => opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .with_timeout(timeout)
+                .build(),
+            OtlpProtocol::Http => {
+                // The HTTP exporter posts to the URL as given
+                let endpoint = endpoint.trim_end_matches('/');
+                let endpoint = if endpoint.ends_with("/v1/traces") {
+                    endpoint.to_string()
+                } else {
+                    format!("{}/v1/traces", endpoint)
+                };
+                opentelemetry_otlp::SpanExporter::builder()
+                    .with_http()
+                    .with_endpoint(endpoint)
+                    .with_timeout(timeout)
+                    .build()
+            }
+        }
+        .map_err(|e| Error::Config(format!("Failed to create OTLP exporter: {}", e)))?;
+
+        // Respect the caller's sampling decision, sample new traces by ratio
+        let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio.clamp(0.0, 1.0),
+        )));
+
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_sampler(sampler)
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                config.service_name.clone(),
+            )]))
+            .build();
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        opentelemetry::global::set_tracer_provider(provider.clone());
+
+        Ok(provider)
+    }
+
+    /// Reads W3C trace context from request headers
+    pub(super) struct HeaderExtractor<'a>(pub &'a HeaderMap);
+
+    impl Extractor for HeaderExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|v| v.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(|k| k.as_str()).collect()
+        }
+    }
+}
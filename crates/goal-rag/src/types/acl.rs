@@ -0,0 +1,89 @@
+//! Access control for documents
+//!
+//! A document's access list (set at ingest) names principal ids and roles;
+//! a caller can read the document if its id or one of its roles is listed.
+//! Documents without an access list are readable by every caller.
+
+use serde::{Deserialize, Serialize};
+
+use super::Document;
+
+/// An authenticated caller
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    /// User or service id
+    pub id: String,
+    /// Roles and groups the caller belongs to
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Sees every document regardless of access lists
+    #[serde(default)]
+    pub admin: bool,
+}
+
+impl Principal {
+    /// Create a principal with roles
+    pub fn new(id: impl Into<String>, roles: Vec<String>) -> Self {
+        Self {
+            id: id.into(),
+            roles,
+            admin: false,
+        }
+    }
+
+    /// Whether the caller may read a document
+    pub fn can_read(&self, doc: &Document) -> bool {
+        self.can_read_acl(&doc.acl())
+    }
+
+    /// Whether the caller may read a document with this access list
+    pub fn can_read_acl(&self, acl: &[impl AsRef<str>]) -> bool {
+        if self.admin {
+            return true;
+        }
+        acl.is_empty()
+            || acl
+                .iter()
+                .any(|entry| entry.as_ref() == self.id || self.roles.iter().any(|role| role == entry.as_ref()))
+    }
+
+    /// Stable key for the caller's visibility, used to separate cached answers
+    pub fn cache_key(&self) -> String {
+        if self.admin {
+            return "admin".to_string();
+        }
+        let mut roles = self.roles.clone();
+        roles.sort();
+        format!("{}:{}", self.id, roles.join(","))
+    }
+}
+
+/// Whether an optional caller may read a document (no caller = unrestricted)
+pub fn can_read(principal: Option<&Principal>, doc: &Document) -> bool {
+    principal.map_or(true, |p| p.can_read(doc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FileType;
+
+    #[test]
+    fn test_can_read() {
+        let mut doc = Document::new("plan.pdf".into(), FileType::Pdf, "hash".into(), 0);
+        let alice = Principal::new("alice", vec!["engineering".into()]);
+        let bob = Principal::new("bob", vec!["sales".into()]);
+        assert!(alice.can_read(&doc));
+        assert!(bob.can_read(&doc));
+
+        doc.set_acl(&["engineering".to_string(), "carol".to_string()]);
+        assert!(alice.can_read(&doc));
+        assert!(!bob.can_read(&doc));
+        assert!(Principal::new("carol", Vec::new()).can_read(&doc));
+        assert!(Principal { admin: true, ..bob.clone() }.can_read(&doc));
+        assert!(can_read(None, &doc));
+        assert!(!can_read(Some(&bob), &doc));
+        assert!(alice.can_read_acl(&["engineering"]));
+        assert!(!bob.can_read_acl(&[String::from("carol")]));
+    }
+}
@@ -2,10 +2,11 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Supported file types
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum FileType {
     /// PDF document
@@ -42,6 +43,18 @@ pub enum FileType {
     Epub,
     /// Image (for OCR) - requires tesseract
     Image,
+    /// Audio recording (transcribed with Whisper)
+    Audio,
+    /// Video recording (audio track transcribed with Whisper)
+    Video,
+    /// Email message (.eml, .msg)
+    Email,
+    /// Mailbox of email messages (.mbox)
+    Mbox,
+    /// Jupyter notebook (.ipynb)
+    Notebook,
+    /// reStructuredText
+    Rst,
     /// Source code file with language
     Code(String),
     /// Unknown file type
@@ -69,6 +82,12 @@ impl FileType {
             "ods" => Self::Ods,
             "epub" => Self::Epub,
             "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "tiff" | "tif" => Self::Image,
+            "mp3" | "wav" | "m4a" | "flac" | "ogg" | "opus" => Self::Audio,
+            "mp4" | "mov" | "mkv" | "webm" | "avi" => Self::Video,
+            "eml" | "msg" => Self::Email,
+            "mbox" => Self::Mbox,
+            "ipynb" => Self::Notebook,
+            "rst" | "rest" => Self::Rst,
             // Code files
             "rs" => Self::Code("rust".to_string()),
             "py" => Self::Code("python".to_string()),
@@ -102,7 +121,7 @@ impl FileType {
 
     /// Check if this format requires external tools
     pub fn requires_external_tools(&self) -> bool {
-        matches!(self, Self::Doc | Self::Ppt | Self::Xls | Self::Image | Self::Rtf | Self::Odt | Self::Odp | Self::Ods | Self::Epub)
+        matches!(self, Self::Doc | Self::Ppt | Self::Xls | Self::Image | Self::Audio | Self::Video | Self::Rtf | Self::Odt | Self::Odp | Self::Ods | Self::Epub)
     }
 
     /// Get display name
@@ -125,6 +144,12 @@ impl FileType {
             Self::Ods => "OpenDocument Spreadsheet",
             Self::Epub => "EPUB eBook",
             Self::Image => "Image",
+            Self::Audio => "Audio",
+            Self::Video => "Video",
+            Self::Email => "Email",
+            Self::Mbox => "Mailbox",
+            Self::Notebook => "Jupyter Notebook",
+            Self::Rst => "reStructuredText",
             Self::Code(lang) => lang.as_str(),
             Self::Unknown => "Unknown",
         }
@@ -148,12 +173,86 @@ impl FileType {
             Self::Odt | Self::Odp | Self::Ods => Some("pandoc or LibreOffice"),
             Self::Epub => Some("pandoc"),
             Self::Image => Some("tesseract OCR (apt install tesseract-ocr)"),
+            Self::Audio | Self::Video => Some("Whisper API or whisper.cpp, ffmpeg for video"),
             Self::Pdf => Some("poppler-utils (pdftotext) for complex PDFs, tesseract for scanned PDFs"),
             _ => None,
         }
     }
 }
 
+/// Metadata key holding the collection name on documents and chunks
+pub const COLLECTION_METADATA_KEY: &str = "collection";
+
+/// Metadata key holding the detected document language (ISO 639-3 code)
+pub const LANGUAGE_METADATA_KEY: &str = "language";
+
+/// Metadata key holding a document's access list (principal ids and roles)
+pub const ACL_METADATA_KEY: &str = "acl";
+
+/// Metadata key holding PII finding counts per kind and whether chunks were masked
+pub const PII_METADATA_KEY: &str = "pii";
+
+/// Metadata key holding extracted keywords on chunks
+pub const KEYWORDS_METADATA_KEY: &str = "keywords";
+
+/// Metadata key holding extracted named entities on chunks and the most frequent ones on documents
+pub const ENTITIES_METADATA_KEY: &str = "entities";
+
+/// Chunk metadata key holding the name of the model that embedded the chunk
+pub const EMBEDDING_MODEL_METADATA_KEY: &str = "embedding_model";
+
+/// Metadata key holding the name of the archive a document was extracted from
+pub const ARCHIVE_METADATA_KEY: &str = "archive";
+
+/// Metadata key holding a document's version number (1 for the first upload of a file)
+pub const VERSION_METADATA_KEY: &str = "version";
+
+/// Chunk metadata key holding the sender of an email message
+pub const EMAIL_FROM_METADATA_KEY: &str = "email_from";
+
+/// Chunk metadata key holding the date of an email message (RFC 3339 when parseable)
+pub const EMAIL_DATE_METADATA_KEY: &str = "email_date";
+
+/// Chunk metadata key holding the subject of an email message
+pub const EMAIL_SUBJECT_METADATA_KEY: &str = "email_subject";
+
+/// Chunk metadata keys kept in vector metadata for email query filters
+pub const EMAIL_METADATA_KEYS: [&str; 3] = [EMAIL_FROM_METADATA_KEY, EMAIL_DATE_METADATA_KEY, EMAIL_SUBJECT_METADATA_KEY];
+
+/// Metadata key holding the title from Markdown front matter or HTML meta tags
+pub const TITLE_METADATA_KEY: &str = "title";
+
+/// Metadata key holding the author from Markdown front matter or HTML meta tags
+pub const AUTHOR_METADATA_KEY: &str = "author";
+
+/// Metadata key holding the tags from Markdown front matter or HTML meta tags
+pub const TAGS_METADATA_KEY: &str = "tags";
+
+/// Metadata key holding the date (`YYYY-MM-DD` when parseable) from Markdown
+/// front matter or HTML meta tags
+pub const DATE_METADATA_KEY: &str = "date";
+
+/// Document metadata key holding when the source last modified the document
+/// (RFC 3339), set by connectors or by clients in the ingest metadata
+pub const SOURCE_MODIFIED_METADATA_KEY: &str = "source_modified_at";
+
+/// Document metadata keys copied onto its chunks (title, author, tags and date)
+pub const SOURCE_METADATA_KEYS: [&str; 4] = [TITLE_METADATA_KEY, AUTHOR_METADATA_KEY, TAGS_METADATA_KEY, DATE_METADATA_KEY];
+
+/// Chunk metadata keys kept in vector metadata for query filters (email
+/// fields, keywords, entities, and title, author, tags and date)
+pub const FILTER_METADATA_KEYS: [&str; 9] = [
+    EMAIL_FROM_METADATA_KEY,
+    EMAIL_DATE_METADATA_KEY,
+    EMAIL_SUBJECT_METADATA_KEY,
+    KEYWORDS_METADATA_KEY,
+    ENTITIES_METADATA_KEY,
+    TITLE_METADATA_KEY,
+    AUTHOR_METADATA_KEY,
+    TAGS_METADATA_KEY,
+    DATE_METADATA_KEY,
+];
+
 /// A document that has been ingested
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
@@ -218,6 +317,138 @@ impl Document {
             metadata: HashMap::new(),
         }
     }
+
+    /// Collection this document belongs to (selects the lexical search analyzer)
+    pub fn collection(&self) -> Option<&str> {
+        self.metadata.get(COLLECTION_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// When the source last modified the document, or when it was ingested
+    /// if the source didn't say
+    pub fn updated_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.metadata
+            .get(SOURCE_MODIFIED_METADATA_KEY)
+            .and_then(|v| v.as_str())
+            .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+            .map_or(self.ingested_at, |modified| modified.with_timezone(&chrono::Utc))
+    }
+
+    /// Assign the document to a collection
+    pub fn set_collection(&mut self, collection: Option<&str>) {
+        if let Some(collection) = collection {
+            self.metadata.insert(
+                COLLECTION_METADATA_KEY.to_string(),
+                serde_json::Value::String(collection.to_string()),
+            );
+        }
+    }
+
+    /// Principal ids and roles allowed to read the document (empty = everyone)
+    pub fn acl(&self) -> Vec<&str> {
+        self.metadata
+            .get(ACL_METADATA_KEY)
+            .and_then(|v| v.as_array())
+            .map(|entries| entries.iter().filter_map(|e| e.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Restrict the document to these principal ids and roles
+    pub fn set_acl(&mut self, acl: &[String]) {
+        if !acl.is_empty() {
+            self.metadata.insert(ACL_METADATA_KEY.to_string(), serde_json::json!(acl));
+        }
+    }
+
+    /// Version number (1 for the first upload of a file)
+    pub fn version(&self) -> u32 {
+        self.metadata
+            .get(VERSION_METADATA_KEY)
+            .and_then(|v| v.as_u64())
+            .map_or(1, |v| v as u32)
+    }
+
+    /// Record the version number
+    pub fn set_version(&mut self, version: u32) {
+        self.metadata.insert(VERSION_METADATA_KEY.to_string(), serde_json::json!(version));
+    }
+
+    /// Detected language of the document text (ISO 639-3 code)
+    pub fn language(&self) -> Option<&str> {
+        self.metadata.get(LANGUAGE_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Most frequent named entities in the document's chunks
+    pub fn entities(&self) -> Vec<String> {
+        string_list(self.metadata.get(ENTITIES_METADATA_KEY))
+    }
+
+    /// Title from front matter or meta tags
+    pub fn title(&self) -> Option<&str> {
+        self.metadata.get(TITLE_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Date from front matter or meta tags (`YYYY-MM-DD` when parseable)
+    pub fn date(&self) -> Option<&str> {
+        self.metadata.get(DATE_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Record the title, author, tags and date a parser found
+    /// (`ParsedDocument::metadata`); tags are stored as a list
+    pub fn set_source_metadata(&mut self, fields: &HashMap<String, String>) {
+        for key in SOURCE_METADATA_KEYS {
+            let Some(value) = fields.get(key) else {
+                continue;
+            };
+            let value = if key == TAGS_METADATA_KEY {
+                serde_json::json!(value.split(',').map(str::trim).filter(|t| !t.is_empty()).collect::<Vec<_>>())
+            } else {
+                serde_json::Value::String(value.clone())
+            };
+            self.metadata.insert(key.to_string(), value);
+        }
+    }
+
+    /// Detect and record the language of the document text
+    pub fn detect_language(&mut self, text: &str) {
+        if let Some(lang) = crate::ingestion::language::detect(text) {
+            self.metadata.insert(
+                LANGUAGE_METADATA_KEY.to_string(),
+                serde_json::Value::String(lang.code().to_string()),
+            );
+        }
+    }
+}
+
+/// What a chunk holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    /// Running text
+    #[default]
+    Text,
+    /// A table rendered as Markdown
+    Table,
+    /// A vision-model caption of an embedded image
+    Figure,
+}
+
+impl ChunkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Table => "table",
+            Self::Figure => "figure",
+        }
+    }
+
+    /// Parse a stored kind, defaulting to text
+    pub fn from_str_or_text(s: &str) -> Self {
+        match s {
+            "table" => Self::Table,
+            "figure" => Self::Figure,
+            _ => Self::Text,
+        }
+    }
 }
 
 /// Source information for a chunk (used for citations)
@@ -247,6 +478,12 @@ pub struct ChunkSource {
     pub line_end: Option<u32>,
     /// Function or class name (for code files)
     pub code_context: Option<String>,
+    /// Start and end seconds (for audio/video transcripts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_range: Option<(f64, f64)>,
+    /// Text or table chunk
+    #[serde(default)]
+    pub kind: ChunkKind,
 }
 
 impl ChunkSource {
@@ -265,6 +502,8 @@ impl ChunkSource {
             line_start: None,
             line_end: None,
             code_context: None,
+            time_range: None,
+            kind: ChunkKind::Text,
         }
     }
 
@@ -283,6 +522,8 @@ impl ChunkSource {
             line_start: None,
             line_end: None,
             code_context: None,
+            time_range: None,
+            kind: ChunkKind::Text,
         }
     }
 
@@ -301,6 +542,8 @@ impl ChunkSource {
             line_start: Some(line_start),
             line_end: Some(line_end),
             code_context: None,
+            time_range: None,
+            kind: ChunkKind::Text,
         }
     }
 
@@ -320,6 +563,14 @@ impl ChunkSource {
             parts.push(format!("Lines {}-{}", start, end));
         }
 
+        if let Some(context) = &self.code_context {
+            parts.push(format!("Defines: {}", context));
+        }
+
+        if let Some((start, end)) = self.time_range {
+            parts.push(format!("{}-{}", format_timestamp(start), format_timestamp(end)));
+        }
+
         if let Some(section) = &self.section_title {
             parts.push(format!("Section: {}", section));
         }
@@ -328,6 +579,29 @@ impl ChunkSource {
     }
 }
 
+/// Strings of a JSON array metadata value
+fn string_list(value: Option<&serde_json::Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+/// "Title (date)", "Title" or the filename
+pub fn source_name(filename: &str, title: Option<&str>, date: Option<&str>) -> String {
+    match (title, date) {
+        (Some(title), Some(date)) => format!("{} ({})", title, date),
+        (Some(title), None) => title.to_string(),
+        _ => filename.to_string(),
+    }
+}
+
+/// Format seconds as `HH:MM:SS` (fractions are truncated)
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", total / 3600, total / 60 % 60, total % 60)
+}
+
 /// A chunk of text from a document
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
@@ -350,6 +624,13 @@ pub struct Chunk {
     /// Additional metadata
     #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Embeddings of the windows of an over-length chunk (max-sim strategy);
+    /// `embedding` then holds their mean
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub sub_embeddings: Vec<Vec<f32>>,
+    /// Parent window this chunk belongs to (small-to-big retrieval)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub parent_id: Option<Uuid>,
 }
 
 impl Chunk {
@@ -372,9 +653,66 @@ impl Chunk {
             char_end,
             chunk_index,
             metadata: HashMap::new(),
+            sub_embeddings: Vec::new(),
+            parent_id: None,
+        }
+    }
+
+    /// Set embeddings from `EmbeddingProvider::embed_parts` output
+    pub fn set_embeddings(&mut self, mut embeddings: Vec<Vec<f32>>) {
+        if embeddings.len() <= 1 {
+            self.embedding = embeddings.pop().unwrap_or_default();
+            self.sub_embeddings.clear();
+        } else {
+            self.embedding = crate::providers::long_input::mean_pool(&embeddings);
+            self.sub_embeddings = embeddings;
         }
     }
 
+    /// Collection this chunk belongs to (copied from its document)
+    pub fn collection(&self) -> Option<&str> {
+        self.metadata.get(COLLECTION_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Title of the chunk's document, from front matter or meta tags
+    pub fn title(&self) -> Option<&str> {
+        self.metadata.get(TITLE_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Date of the chunk's document, from front matter or meta tags
+    pub fn date(&self) -> Option<&str> {
+        self.metadata.get(DATE_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Name to cite the chunk by: its document's title (and date) when known,
+    /// the filename otherwise
+    pub fn source_name(&self) -> String {
+        source_name(&self.source.filename, self.title(), self.date())
+    }
+
+    /// Model that embedded the chunk
+    pub fn embedding_model(&self) -> Option<&str> {
+        self.metadata.get(EMBEDDING_MODEL_METADATA_KEY).and_then(|v| v.as_str())
+    }
+
+    /// Record the model that embedded the chunk
+    pub fn set_embedding_model(&mut self, model: &str) {
+        self.metadata.insert(
+            EMBEDDING_MODEL_METADATA_KEY.to_string(),
+            serde_json::Value::String(model.to_string()),
+        );
+    }
+
+    /// Extracted keywords (empty unless keyword extraction is enabled)
+    pub fn keywords(&self) -> Vec<String> {
+        string_list(self.metadata.get(KEYWORDS_METADATA_KEY))
+    }
+
+    /// Extracted named entities (empty unless keyword extraction is enabled)
+    pub fn entities(&self) -> Vec<String> {
+        string_list(self.metadata.get(ENTITIES_METADATA_KEY))
+    }
+
     /// Convert to vector metadata for storage
     pub fn to_vector_metadata(&self) -> HashMap<String, serde_json::Value> {
         let mut meta = HashMap::new();
@@ -400,6 +738,46 @@ impl Chunk {
             meta.insert("line_end".to_string(), serde_json::json!(end));
         }
 
+        if let Some(context) = &self.source.code_context {
+            meta.insert("code_context".to_string(), serde_json::json!(context));
+        }
+
+        if let Some((start, end)) = self.source.time_range {
+            meta.insert("start_seconds".to_string(), serde_json::json!(start));
+            meta.insert("end_seconds".to_string(), serde_json::json!(end));
+        }
+
+        if let Some(parent_id) = self.parent_id {
+            meta.insert("parent_id".to_string(), serde_json::json!(parent_id.to_string()));
+        }
+
+        if self.source.kind != ChunkKind::Text {
+            meta.insert("kind".to_string(), serde_json::json!(self.source.kind));
+        }
+
+        for key in FILTER_METADATA_KEYS {
+            if let Some(value) = self.metadata.get(key) {
+                meta.insert(key.to_string(), value.clone());
+            }
+        }
+
         meta
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updated_at_prefers_source_modification() {
+        let mut doc = Document::new("policy.pdf".to_string(), FileType::Pdf, String::new(), 0);
+        assert_eq!(doc.updated_at(), doc.ingested_at);
+
+        doc.metadata.insert(SOURCE_MODIFIED_METADATA_KEY.to_string(), "2024-03-01T09:30:00+01:00".into());
+        assert_eq!(doc.updated_at(), "2024-03-01T08:30:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+
+        doc.metadata.insert(SOURCE_MODIFIED_METADATA_KEY.to_string(), "last tuesday".into());
+        assert_eq!(doc.updated_at(), doc.ingested_at);
+    }
+}
@@ -2,12 +2,13 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use super::FileType;
 
 /// Status of a file in the system
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum FileRecordStatus {
     /// File processed successfully
@@ -21,7 +22,7 @@ pub enum FileRecordStatus {
 }
 
 /// Reason why a file was skipped
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum SkipReason {
     /// Same file with same content already exists
@@ -35,7 +36,7 @@ pub enum SkipReason {
 }
 
 /// Record of a file that has been processed
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct FileRecord {
     /// Unique record ID
     pub id: Uuid,
@@ -208,14 +209,14 @@ impl FileRecord {
 }
 
 /// Request to check status of files before upload
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct FileCheckRequest {
     /// List of files to check
     pub files: Vec<FileCheckItem>,
 }
 
 /// Item in file check request
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct FileCheckItem {
     /// Filename
     pub filename: String,
@@ -227,7 +228,7 @@ pub struct FileCheckItem {
 }
 
 /// Status of a file for upload decision
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum FileUploadAdvice {
     /// File should be uploaded (new or modified)
@@ -239,7 +240,7 @@ pub enum FileUploadAdvice {
 }
 
 /// Response for file check request
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct FileCheckResponse {
     /// Status for each file
     pub files: Vec<FileCheckResult>,
@@ -248,7 +249,7 @@ pub struct FileCheckResponse {
 }
 
 /// Result for individual file check
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct FileCheckResult {
     /// Filename checked
     pub filename: String,
@@ -260,7 +261,7 @@ pub struct FileCheckResult {
 }
 
 /// Summary of file check
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct FileCheckSummary {
     /// Total files checked
     pub total_checked: usize,
@@ -273,7 +274,7 @@ pub struct FileCheckSummary {
 }
 
 /// Summary of a file record for API responses
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct FileRecordSummary {
     pub filename: String,
     pub status: FileRecordStatus,
@@ -1,14 +1,16 @@
 //! Core types for the RAG system
 
+pub mod acl;
 pub mod document;
 pub mod file_record;
 pub mod query;
 pub mod response;
 
-pub use document::{Chunk, ChunkSource, Document, FileType};
+pub use acl::Principal;
+pub use document::{Chunk, ChunkKind, ChunkSource, Document, FileType};
 pub use file_record::{
     FileCheckItem, FileCheckRequest, FileCheckResponse, FileCheckResult, FileCheckSummary,
     FileRecord, FileRecordStatus, FileRecordSummary, FileUploadAdvice, SkipReason,
 };
-pub use query::QueryRequest;
-pub use response::{Citation, QueryResponse};
+pub use query::{AnswerFormat, AnswerStrategy, PathIngestRequest, QueryRequest};
+pub use response::{AnswerProvenance, Citation, QueryResponse};
@@ -1,27 +1,55 @@
 //! Query request types
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-/// Type of query for routing between RAG and string search
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::config::ChunkSizeUnit;
+use crate::retrieval::RetrievalStrategy;
+use crate::types::document::{
+    AUTHOR_METADATA_KEY, EMAIL_DATE_METADATA_KEY, EMAIL_FROM_METADATA_KEY, EMAIL_SUBJECT_METADATA_KEY, TAGS_METADATA_KEY,
+    TITLE_METADATA_KEY,
+};
+use crate::types::{Chunk, FileType, Principal};
+
+/// Type of query for routing between RAG, string search and document lookup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryType {
     /// Full RAG query with semantic search and LLM answer generation
+    #[default]
     Question,
     /// Literal string search (word or phrase lookup)
     StringSearch,
+    /// Documents by name ("find the doc named X")
+    DocumentLookup,
 }
 
+/// Openings of a request for a document by name
+const LOOKUP_PREFIXES: &[&str] = &[
+    "find the document", "find document", "find the doc", "find doc", "find the file", "find file",
+    "show me the document", "show the document", "show me the file", "show the file",
+    "where is the document", "where is the file", "open the document", "open the file", "open document", "open file",
+    "get the document", "get the file", "look up the document", "look up the file",
+];
+
+/// Words introducing the name in a lookup
+const NAME_MARKERS: &[&str] = &["named", "called", "titled"];
+
 impl QueryType {
     /// Detect query type from input string
     ///
     /// Heuristics:
+    /// - DocumentLookup if: asks for a document or file by name, or is a bare filename
     /// - Question if: ends with ?, starts with question words (what/how/why/etc), or 5+ words
     /// - StringSearch otherwise (short phrases, single words)
     pub fn detect(input: &str) -> Self {
         let input = input.trim();
 
+        if Self::lookup_target(input).is_some() {
+            return Self::DocumentLookup;
+        }
+
         // Ends with question mark -> Question
         if input.ends_with('?') {
             return Self::Question;
@@ -51,10 +79,69 @@ impl QueryType {
         // Short phrase or single word -> string search
         Self::StringSearch
     }
+
+    /// Name a lookup query asks for, if it is one
+    ///
+    /// "find the doc named Q3 budget" gives "Q3 budget"; a bare filename
+    /// ("budget.xlsx") is its own target.
+    pub fn lookup_target(input: &str) -> Option<String> {
+        let input = input.trim().trim_end_matches(['?', '.', '!']);
+        let lower = input.to_lowercase();
+
+        let target = match LOOKUP_PREFIXES.iter().find(|prefix| lower.starts_with(*prefix)) {
+            Some(prefix) => {
+                let rest = input.get(prefix.len()..)?;
+                let rest = rest.strip_prefix('s').unwrap_or(rest);
+                if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                    return None;
+                }
+                // "find the document about X" is a question
+                let rest = rest.trim_start();
+                let rest_lower = rest.to_lowercase();
+                match NAME_MARKERS.iter().find(|marker| rest_lower.starts_with(&format!("{} ", marker))) {
+                    Some(marker) => rest.get(marker.len()..)?,
+                    None if looks_like_filename(rest) => rest,
+                    None => return None,
+                }
+            }
+            None if looks_like_filename(input) => input,
+            None => return None,
+        };
+
+        let target = target.trim().trim_matches(['"', '\'', '`']).trim();
+        (!target.is_empty()).then(|| target.to_string())
+    }
+}
+
+/// A single name with a known document extension ("report-2024.pdf")
+fn looks_like_filename(input: &str) -> bool {
+    let input = input.trim().trim_matches(['"', '\'', '`']);
+    if input.is_empty() || input.split_whitespace().count() > 3 {
+        return false;
+    }
+    std::path::Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| FileType::from_extension(ext).is_supported())
+}
+
+/// How a string search matches its query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StringSearchMode {
+    /// The query as a phrase (case-insensitive)
+    #[default]
+    Exact,
+    /// Words starting with each query word ("config" finds "configuration")
+    Prefix,
+    /// Words within a few typos of each query word
+    Fuzzy,
+    /// A regular expression over the chunk text
+    Regex,
 }
 
 /// Query request for RAG search
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct QueryRequest {
     /// The question to answer
     pub question: String,
@@ -75,13 +162,95 @@ pub struct QueryRequest {
     #[serde(default)]
     pub document_filter: Option<Vec<Uuid>>,
 
+    /// Answer from this version of a document only (see `/api/documents/{id}/versions`);
+    /// superseded versions are embedded on the fly
+    #[serde(default)]
+    pub document_version: Option<Uuid>,
+
+    /// Most chunks retrieved from any one document, at least 1 (default:
+    /// `retrieval.max_chunks_per_document`), so answers draw on several sources
+    #[serde(default)]
+    pub max_chunks_per_document: Option<usize>,
+
     /// Include raw chunks in response (default: false)
     #[serde(default)]
     pub include_chunks: bool,
 
+    /// Return the retrieval trace (scores, filters, packing and the rendered
+    /// prompt) with the answer (default: false)
+    #[serde(default)]
+    pub debug: bool,
+
     /// Stream the response (default: false)
     #[serde(default)]
     pub stream: bool,
+
+    /// Collection for lexical matching (uses its analyzer, restricts string search)
+    #[serde(default)]
+    pub collection: Option<String>,
+
+    /// Language to answer in (ISO code or English name, e.g. "de", "German")
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// Translate retrieved chunks in other languages before prompting (default: false)
+    #[serde(default)]
+    pub translate_context: bool,
+
+    /// How the question is expanded into search queries (default: standard)
+    #[serde(default)]
+    pub retrieval_strategy: RetrievalStrategy,
+
+    /// How the answer is generated from the retrieved chunks (default: standard)
+    #[serde(default)]
+    pub answer_strategy: AnswerStrategy,
+
+    /// Markup of the answer: markdown (default), plain text, or json, which
+    /// also returns the answer as `structured`
+    #[serde(default)]
+    pub format: AnswerFormat,
+
+    /// Only use chunks from email messages matching this filter
+    #[serde(default)]
+    pub email_filter: Option<EmailFilter>,
+
+    /// Only use chunks tagged with all of these entities (needs keyword extraction)
+    #[serde(default)]
+    pub entities: Vec<String>,
+
+    /// Only use chunks of documents whose title, author, tags or date match
+    /// this filter
+    #[serde(default)]
+    pub metadata_filter: Option<MetadataFilter>,
+
+    /// Experiment variant to answer with (assigned at random when experiments are enabled)
+    #[serde(default)]
+    pub variant: Option<String>,
+
+    /// Generation model to answer with (default: the variant's or the configured model);
+    /// falls back along the model's chain in `models.fallbacks`
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Prompt template to answer with (default: the collection's or the configured default)
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+
+    /// Values available to the prompt template as `metadata`
+    #[serde(default)]
+    pub prompt_metadata: std::collections::HashMap<String, serde_json::Value>,
+
+    /// How to handle the query (default: detected from the question)
+    #[serde(default)]
+    pub intent: Option<QueryType>,
+
+    /// Authenticated caller, set by the server (chunks of documents it can't read are dropped)
+    #[serde(skip)]
+    pub principal: Option<Principal>,
+
+    /// Cheaper generation model to answer with, set when a usage budget is spent
+    #[serde(skip)]
+    pub budget_model: Option<String>,
 }
 
 fn default_top_k() -> usize {
@@ -104,8 +273,27 @@ impl Default for QueryRequest {
             similarity_threshold: 0.20,  // Lower threshold to include more content
             rerank: true,
             document_filter: None,
+            document_version: None,
+            max_chunks_per_document: None,
             include_chunks: false,
+            debug: false,
             stream: false,
+            collection: None,
+            language: None,
+            translate_context: false,
+            retrieval_strategy: RetrievalStrategy::Standard,
+            answer_strategy: AnswerStrategy::Standard,
+            format: AnswerFormat::Markdown,
+            email_filter: None,
+            entities: Vec::new(),
+            metadata_filter: None,
+            variant: None,
+            model: None,
+            prompt_template: None,
+            prompt_metadata: std::collections::HashMap::new(),
+            intent: None,
+            principal: None,
+            budget_model: None,
         }
     }
 }
@@ -142,11 +330,330 @@ impl QueryRequest {
         self.include_chunks = true;
         self
     }
+
+    /// Answer in the given language
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Expand the question into paraphrases or a hypothetical answer for retrieval
+    pub fn with_retrieval_strategy(mut self, strategy: RetrievalStrategy) -> Self {
+        self.retrieval_strategy = strategy;
+        self
+    }
+
+    /// Answer from batches of the retrieved chunks
+    pub fn with_answer_strategy(mut self, strategy: AnswerStrategy) -> Self {
+        self.answer_strategy = strategy;
+        self
+    }
+
+    /// Answer in a format other than Markdown
+    pub fn with_format(mut self, format: AnswerFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Only use chunks from email messages matching the filter
+    pub fn with_email_filter(mut self, filter: EmailFilter) -> Self {
+        self.email_filter = Some(filter);
+        self
+    }
+
+    /// Only use chunks tagged with these entities
+    pub fn with_entities(mut self, entities: Vec<String>) -> Self {
+        self.entities = entities;
+        self
+    }
+
+    /// Only use chunks of documents matching the filter
+    pub fn with_metadata_filter(mut self, filter: MetadataFilter) -> Self {
+        self.metadata_filter = Some(filter);
+        self
+    }
+
+    /// Answer with an experiment variant
+    pub fn with_variant(mut self, variant: impl Into<String>) -> Self {
+        self.variant = Some(variant.into());
+        self
+    }
+
+    /// Answer with a prompt template
+    pub fn with_prompt_template(mut self, template: impl Into<String>) -> Self {
+        self.prompt_template = Some(template.into());
+        self
+    }
+
+    /// Only use documents this caller can read
+    pub fn with_principal(mut self, principal: Option<Principal>) -> Self {
+        self.principal = principal;
+        self
+    }
+
+    /// Question as given to the LLM, with the answer language and format instructions
+    pub fn prompt_question(&self) -> String {
+        let question = match self.language {
+            Some(ref language) => crate::generation::PromptBuilder::with_answer_language(&self.question, language),
+            None => self.question.clone(),
+        };
+        crate::generation::PromptBuilder::with_answer_format(&question, self.format)
+    }
+
+    /// Key for the answer cache (answers differ per language, collection, email, entity and metadata filters,
+    /// retrieval strategy, chunks per document, variant, model, prompt, answer strategy, format and caller)
+    pub fn cache_key(&self) -> String {
+        let mut key = match self.language {
+            Some(ref language) => format!("{}\n[lang:{}]", self.question, language.trim().to_lowercase()),
+            None => self.question.clone(),
+        };
+        if let Some(ref collection) = self.collection {
+            key.push_str(&format!("\n[collection:{}]", collection));
+        }
+        if let Some(ref filter) = self.email_filter {
+            key.push_str(&format!("\n[email:{}]", serde_json::to_string(filter).unwrap_or_default()));
+        }
+        if !self.entities.is_empty() {
+            let mut entities: Vec<String> = self.entities.iter().map(|e| e.trim().to_lowercase()).collect();
+            entities.sort();
+            key.push_str(&format!("\n[entities:{}]", entities.join("|")));
+        }
+        if let Some(ref filter) = self.metadata_filter {
+            key.push_str(&format!("\n[metadata:{}]", serde_json::to_string(filter).unwrap_or_default()));
+        }
+        if let Some(ref version) = self.document_version {
+            key.push_str(&format!("\n[version:{}]", version));
+        }
+        if self.retrieval_strategy != RetrievalStrategy::Standard {
+            key.push_str(&format!("\n[retrieval:{}]", self.retrieval_strategy.as_str()));
+        }
+        if let Some(max) = self.max_chunks_per_document {
+            key.push_str(&format!("\n[per_document:{}]", max));
+        }
+        if let Some(ref variant) = self.variant {
+            key.push_str(&format!("\n[variant:{}]", variant));
+        }
+        if self.answer_strategy != AnswerStrategy::Standard {
+            key.push_str(&format!("\n[strategy:{}]", self.answer_strategy.as_str()));
+        }
+        if self.format != AnswerFormat::Markdown {
+            key.push_str(&format!("\n[format:{}]", self.format.as_str()));
+        }
+        if let Some(model) = self.budget_model.as_ref().or(self.model.as_ref()) {
+            key.push_str(&format!("\n[model:{}]", model));
+        }
+        if let Some(ref template) = self.prompt_template {
+            key.push_str(&format!("\n[prompt:{}]", template));
+        }
+        if !self.prompt_metadata.is_empty() {
+            let metadata: std::collections::BTreeMap<_, _> = self.prompt_metadata.iter().collect();
+            key.push_str(&format!("\n[prompt_metadata:{}]", serde_json::to_string(&metadata).unwrap_or_default()));
+        }
+        if let Some(ref principal) = self.principal {
+            key.push_str(&format!("\n[principal:{}]", principal.cache_key()));
+        }
+        key
+    }
+}
+
+/// How the answer is generated from the retrieved chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerStrategy {
+    /// One prompt with the chunks that fit the context budget
+    #[default]
+    Standard,
+    /// Answer each batch of chunks separately, then merge the partial answers
+    MapReduce,
+    /// Answer the first batch, then revise the answer with each further batch
+    Refine,
+}
+
+impl AnswerStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::MapReduce => "map_reduce",
+            Self::Refine => "refine",
+        }
+    }
+}
+
+/// Markup of a generated answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerFormat {
+    /// Markdown (headings, lists, emphasis)
+    #[default]
+    Markdown,
+    /// Text without markup, for chat and email integrations
+    Plain,
+    /// Plain text answer, plus its key points and cited sources as `structured`
+    Json,
+}
+
+impl AnswerFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Plain => "plain",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Filter on the sender, subject and date of email chunks
+///
+/// Chunks from other documents never match. Text matches are
+/// case-insensitive substrings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EmailFilter {
+    /// Sender contains this text (name or address)
+    #[serde(default)]
+    pub from: Option<String>,
+
+    /// Subject contains this text
+    #[serde(default)]
+    pub subject: Option<String>,
+
+    /// Sent at or after this time
+    #[serde(default)]
+    pub after: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Sent before this time
+    #[serde(default)]
+    pub before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl EmailFilter {
+    /// Whether a chunk comes from a matching email message
+    pub fn matches(&self, chunk: &Chunk) -> bool {
+        let field = |key: &str| chunk.metadata.get(key).and_then(|v| v.as_str());
+        let contains = |key: &str, needle: &Option<String>| match needle {
+            Some(needle) => field(key).is_some_and(|value| value.to_lowercase().contains(&needle.to_lowercase())),
+            None => true,
+        };
+
+        if field(EMAIL_FROM_METADATA_KEY).is_none() && field(EMAIL_SUBJECT_METADATA_KEY).is_none() {
+            return false;
+        }
+        if !contains(EMAIL_FROM_METADATA_KEY, &self.from) || !contains(EMAIL_SUBJECT_METADATA_KEY, &self.subject) {
+            return false;
+        }
+
+        if self.after.is_none() && self.before.is_none() {
+            return true;
+        }
+        let Some(date) = field(EMAIL_DATE_METADATA_KEY)
+            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&chrono::Utc))
+        else {
+            return false;
+        };
+        !matches!(self.after, Some(after) if date < after) && !matches!(self.before, Some(before) if date >= before)
+    }
+}
+
+/// Filter on the title, author, tags and date of documents, taken from
+/// Markdown front matter and HTML meta tags
+///
+/// Chunks of documents without a filtered field never match. Title and
+/// author matches are case-insensitive substrings; all tags must be present.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MetadataFilter {
+    /// Title contains this text
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Author contains this text
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// Tagged with all of these (case-insensitive)
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Dated on or after this day
+    #[serde(default)]
+    pub after: Option<chrono::NaiveDate>,
+
+    /// Dated before this day
+    #[serde(default)]
+    pub before: Option<chrono::NaiveDate>,
+}
+
+impl MetadataFilter {
+    /// Whether a chunk comes from a matching document
+    pub fn matches(&self, chunk: &Chunk) -> bool {
+        let field = |key: &str| chunk.metadata.get(key).and_then(|v| v.as_str());
+        let contains = |key: &str, needle: &Option<String>| match needle {
+            Some(needle) => field(key).is_some_and(|value| value.to_lowercase().contains(&needle.to_lowercase())),
+            None => true,
+        };
+        if !contains(TITLE_METADATA_KEY, &self.title) || !contains(AUTHOR_METADATA_KEY, &self.author) {
+            return false;
+        }
+
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = chunk
+                .metadata
+                .get(TAGS_METADATA_KEY)
+                .and_then(|v| v.as_array())
+                .map(|tags| tags.iter().filter_map(|t| t.as_str()).map(str::to_lowercase).collect())
+                .unwrap_or_default();
+            if !self.tags.iter().all(|tag| tags.contains(&tag.trim().to_lowercase())) {
+                return false;
+            }
+        }
+
+        if self.after.is_none() && self.before.is_none() {
+            return true;
+        }
+        let Some(date) = chunk.date().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) else {
+            return false;
+        };
+        !matches!(self.after, Some(after) if date < after) && !matches!(self.before, Some(before) if date >= before)
+    }
+}
+
+/// Request to ingest a directory tree on the server
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PathIngestRequest {
+    /// Directory or file on the server, under one of
+    /// `processing.local_ingest_roots`
+    pub path: String,
+
+    /// Glob patterns of the files to ingest, relative to `path` (default:
+    /// every file). Patterns without a `/` match file names at any depth
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Glob patterns of files and directories to leave out, e.g.
+    /// `"**/node_modules/**"` or `"*.tmp"`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Follow symbolic links
+    #[serde(default)]
+    pub follow_links: bool,
+
+    /// Prefix of the filenames, which are the files' paths relative to `path`
+    #[serde(default)]
+    pub prefix: Option<String>,
+
+    /// Also queue files the registry has ingested with the same size and
+    /// no newer modification time (unchanged content is still skipped)
+    #[serde(default)]
+    pub force: bool,
+
+    /// Chunking, collection, access list, priority and retries of the job
+    #[serde(default)]
+    pub options: IngestOptions,
 }
 
 /// Ingest request options
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[derive(Default)]
+#[derive(Default, ToSchema)]
 pub struct IngestOptions {
     /// Custom chunk size (overrides config)
     pub chunk_size: Option<usize>,
@@ -154,12 +661,68 @@ pub struct IngestOptions {
     /// Custom chunk overlap (overrides config)
     pub chunk_overlap: Option<usize>,
 
+    /// Unit of chunk size and overlap (overrides config)
+    #[serde(default)]
+    pub chunk_size_unit: Option<ChunkSizeUnit>,
+
     /// Extract images and run OCR
     #[serde(default)]
     pub extract_images: bool,
 
     /// Custom metadata to attach to documents
     #[serde(default)]
+    #[schema(value_type = Object)]
     pub metadata: std::collections::HashMap<String, serde_json::Value>,
+
+    /// Collection to ingest into (selects the lexical search analyzer)
+    #[serde(default)]
+    pub collection: Option<String>,
+
+    /// Principal ids and roles allowed to read the documents (empty = everyone)
+    #[serde(default)]
+    pub acl: Vec<String>,
+
+    /// Retries for transient failures (async jobs only, overrides config)
+    #[serde(default)]
+    pub retry: Option<crate::config::RetryConfig>,
+
+    /// Scheduling lane of the job (async jobs only, default: interactive)
+    #[serde(default)]
+    pub priority: crate::processing::JobPriority,
+
+    /// Files about to be uploaded; those `/api/files/check` would skip are
+    /// dropped without being read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<crate::types::FileCheckItem>,
+
+    /// Passwords of encrypted PDFs by filename (tried before the configured
+    /// `[pdf_passwords]`; never stored)
+    #[serde(default, skip_serializing)]
+    pub passwords: std::collections::HashMap<String, String>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_covers_collection_and_retrieval() {
+        let request = QueryRequest::new("What is the refund policy?");
+        let key = request.cache_key();
+
+        let mut other = request.clone();
+        other.collection = Some("legal".to_string());
+        assert_ne!(other.cache_key(), key);
+
+        let mut other = request.clone();
+        other.retrieval_strategy = RetrievalStrategy::Hyde;
+        assert_ne!(other.cache_key(), key);
+
+        let mut other = request.clone();
+        other.max_chunks_per_document = Some(2);
+        assert_ne!(other.cache_key(), key);
+
+        assert_eq!(request.clone().cache_key(), key);
+    }
+}
@@ -1,12 +1,15 @@
 //! Response types for RAG queries
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::document::{Chunk, Document, FileType};
+use super::document::{format_timestamp, source_name, Chunk, Document, FileType};
+use super::query::QueryType;
+use crate::retrieval::QueryExplain;
 
 /// Citation from a source document
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Citation {
     /// Chunk ID
     pub chunk_id: Uuid,
@@ -14,6 +17,12 @@ pub struct Citation {
     pub document_id: Uuid,
     /// Source filename
     pub filename: String,
+    /// Document title from front matter or meta tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Document date (`YYYY-MM-DD` when parseable) from front matter or meta tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
     /// File type
     pub file_type: FileType,
     /// Page number (if applicable)
@@ -23,6 +32,11 @@ pub struct Citation {
     /// Line numbers for code files
     pub line_start: Option<u32>,
     pub line_end: Option<u32>,
+    /// Start and end seconds in the recording (audio/video)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_seconds: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_seconds: Option<f64>,
     /// Exact snippet from the source
     pub snippet: String,
     /// Snippet with highlighted query terms (<mark> tags)
@@ -37,6 +51,44 @@ pub struct Citation {
     /// URL to extracted plain text in GCS (authenticated access)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub plaintext_url: Option<String>,
+    /// Where the cited text is in the source, to open it there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
+}
+
+/// Where a citation's text is in its document ("open at citation")
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SourceLocation {
+    /// Original file (Range requests supported), with `#page=N` for PDFs
+    pub original: String,
+    /// Extracted plain text the offsets refer to
+    pub plaintext: String,
+    /// Cited page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    /// Byte offset where the cited text starts in the plain text
+    pub char_start: usize,
+    /// Byte offset where the cited text ends in the plain text
+    pub char_end: usize,
+}
+
+impl SourceLocation {
+    /// Location of a chunk in its document
+    pub fn of_chunk(chunk: &Chunk) -> Self {
+        let base = format!("/api/documents/{}", chunk.document_id);
+        let page = chunk.source.page_number;
+        let original = match page {
+            Some(page) if chunk.source.file_type == FileType::Pdf => format!("{}/original#page={}", base, page),
+            _ => format!("{}/original", base),
+        };
+        Self {
+            original,
+            plaintext: format!("{}/plaintext", base),
+            page,
+            char_start: chunk.char_start,
+            char_end: chunk.char_end,
+        }
+    }
 }
 
 impl Citation {
@@ -46,25 +98,40 @@ impl Citation {
             chunk_id: chunk.id,
             document_id: chunk.document_id,
             filename: chunk.source.filename.clone(),
+            title: chunk.title().map(String::from),
+            date: chunk.date().map(String::from),
             file_type: chunk.source.file_type.clone(),
             page_number: chunk.source.page_number,
             section_title: chunk.source.section_title.clone(),
             line_start: chunk.source.line_start,
             line_end: chunk.source.line_end,
+            start_seconds: chunk.source.time_range.map(|(start, _)| start),
+            end_seconds: chunk.source.time_range.map(|(_, end)| end),
             snippet: chunk.content.clone(),
             snippet_highlighted: chunk.content.clone(),
             similarity_score,
             rerank_score: None,
             document_url: None,
             plaintext_url: None,
+            location: Some(SourceLocation::of_chunk(chunk)),
         }
     }
 
-    /// Enrich citation with document URLs from metadata
+    /// Enrich citation with the document title, date and URLs from metadata
+    ///
+    /// Recording URLs get a media fragment (`#t=start,end`) so players open
+    /// at the cited passage.
     pub fn enrich_with_document(&mut self, document: &Document) {
+        if self.title.is_none() {
+            self.title = document.title().map(String::from);
+            self.date = document.date().map(String::from);
+        }
         if let Some(url) = document.metadata.get("original_uri") {
             if let Some(url_str) = url.as_str() {
-                self.document_url = Some(url_str.to_string());
+                self.document_url = Some(match (self.start_seconds, self.end_seconds) {
+                    (Some(start), Some(end)) => format!("{}#t={:.0},{:.0}", url_str, start.floor(), end.ceil()),
+                    _ => url_str.to_string(),
+                });
             }
         }
         if let Some(url) = document.metadata.get("plaintext_uri") {
@@ -74,9 +141,15 @@ impl Citation {
         }
     }
 
+    /// Name to cite the source by: "Title (date)" when the document has a
+    /// title, the filename otherwise
+    pub fn source_name(&self) -> String {
+        source_name(&self.filename, self.title.as_deref(), self.date.as_deref())
+    }
+
     /// Format citation for display in text
     pub fn format_inline(&self) -> String {
-        let mut parts = vec![self.filename.clone()];
+        let mut parts = vec![self.source_name()];
 
         if let Some(page) = self.page_number {
             parts.push(format!("Page {}", page));
@@ -86,6 +159,10 @@ impl Citation {
             parts.push(format!("Lines {}-{}", start, end));
         }
 
+        if let (Some(start), Some(end)) = (self.start_seconds, self.end_seconds) {
+            parts.push(format!("{}-{}", format_timestamp(start), format_timestamp(end)));
+        }
+
         format!("[Source: {}]", parts.join(", "))
     }
 
@@ -110,12 +187,18 @@ impl Citation {
 }
 
 /// Response from a RAG query
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct QueryResponse {
     /// Generated answer in clear language
     pub answer: String,
+    /// The answer with its key points (`json` format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured: Option<StructuredAnswer>,
     /// Citations with source snippets
     pub citations: Vec<Citation>,
+    /// Cited documents, most relevant first, with their citations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<CitedDocument>,
     /// Overall confidence score (0.0-1.0)
     pub confidence: f32,
     /// Processing time in milliseconds
@@ -124,12 +207,135 @@ pub struct QueryResponse {
     pub chunks_retrieved: usize,
     /// Number of chunks used in answer
     pub chunks_used: usize,
+    /// Retrieved chunks left out of the prompt to fit the token budget
+    #[serde(default)]
+    pub chunks_dropped: usize,
+    /// Chunks trimmed to fit the token budget
+    #[serde(default)]
+    pub chunks_truncated: usize,
     /// Interaction ID for feedback/learning
     #[serde(skip_serializing_if = "Option::is_none")]
     pub interaction_id: Option<Uuid>,
+    /// Experiment variant that served the answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Generation model that wrote the answer (a fallback when the requested one failed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
     /// Raw chunks (if include_chunks was true)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
     pub raw_chunks: Option<Vec<Chunk>>,
+    /// Signature binding the answer to its citations and corpus state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<AnswerProvenance>,
+    /// Set when no answer was generated because retrieval found too little
+    /// evidence (see `retrieval.answer_threshold`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insufficient_evidence: Option<InsufficientEvidence>,
+    /// How the query was handled
+    #[serde(default)]
+    pub intent: QueryType,
+    /// Documents found by a document lookup
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<DocumentSummary>,
+    /// Questions the retrieved chunks also answer (when `follow_ups` is enabled)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub follow_up_questions: Vec<String>,
+    /// Retrieval trace (if debug was true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<QueryExplain>,
+    /// The answer is a verified correction rather than generated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub curated: bool,
+}
+
+/// Answer of a query in the `json` format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StructuredAnswer {
+    /// The answer as plain text, with its citation markers
+    pub answer: String,
+    /// Key points of the answer, one sentence each
+    #[serde(default)]
+    pub bullet_points: Vec<String>,
+    /// Citations of the answer, as 1-based positions in the response's `citations`
+    #[serde(default)]
+    pub citations: Vec<usize>,
+}
+
+impl StructuredAnswer {
+    /// Structure an answer citing all of `citations`
+    pub fn new(answer: String, bullet_points: Vec<String>, citations: &[Citation]) -> Self {
+        Self { answer, bullet_points, citations: (1..=citations.len()).collect() }
+    }
+}
+
+/// A document cited in an answer
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CitedDocument {
+    pub document_id: Uuid,
+    pub filename: String,
+    /// Similarity of the document's best passage
+    pub relevance: f32,
+    /// Number of passages cited from the document
+    pub passages: usize,
+    /// Positions of the document's passages in `citations` (1-based)
+    pub citations: Vec<u32>,
+}
+
+impl CitedDocument {
+    /// Group citations by document, in order of each document's best passage
+    pub fn group(citations: &[Citation]) -> Vec<Self> {
+        let mut documents: Vec<Self> = Vec::new();
+        for (i, citation) in citations.iter().enumerate() {
+            let position = (i + 1) as u32;
+            match documents.iter_mut().find(|d| d.document_id == citation.document_id) {
+                Some(document) => {
+                    document.relevance = document.relevance.max(citation.similarity_score);
+                    document.passages += 1;
+                    document.citations.push(position);
+                }
+                None => documents.push(Self {
+                    document_id: citation.document_id,
+                    filename: citation.filename.clone(),
+                    relevance: citation.similarity_score,
+                    passages: 1,
+                    citations: vec![position],
+                }),
+            }
+        }
+        documents.sort_by(|a, b| b.relevance.total_cmp(&a.relevance));
+        documents
+    }
+}
+
+/// Why a question was left unanswered, with the chunks that came closest
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InsufficientEvidence {
+    /// Similarity the best chunk had to reach
+    pub threshold: f32,
+    /// Similarity of the best chunk retrieved
+    pub best_score: f32,
+    /// Best chunks retrieved, for the user to check by hand
+    pub near_misses: Vec<Citation>,
+}
+
+/// Signed provenance for a generated answer
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AnswerProvenance {
+    /// Signature algorithm ("hmac-sha256" or "ed25519")
+    pub algorithm: String,
+    /// Signing key identifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Hash of the indexed corpus when the answer was generated
+    pub corpus_snapshot_id: String,
+    /// SHA-256 of each cited chunk (id + text), in citation order
+    pub citation_hashes: Vec<String>,
+    /// When the answer was signed
+    pub signed_at: chrono::DateTime<chrono::Utc>,
+    /// Base64 signature
+    pub signature: String,
 }
 
 impl QueryResponse {
@@ -145,12 +351,25 @@ impl QueryResponse {
         Self {
             answer,
             confidence,
+            sources: CitedDocument::group(&citations),
             chunks_retrieved: citations.len(),
             chunks_used: citations.len(),
+            chunks_dropped: 0,
+            chunks_truncated: 0,
             citations,
             processing_time_ms,
             interaction_id: None,
+            variant: None,
+            model: None,
             raw_chunks: None,
+            provenance: None,
+            insufficient_evidence: None,
+            intent: QueryType::Question,
+            documents: Vec::new(),
+            follow_up_questions: Vec::new(),
+            debug: None,
+            curated: false,
+            structured: None,
         }
     }
 
@@ -159,18 +378,56 @@ impl QueryResponse {
         Self {
             answer: "I couldn't find relevant information in the documents to answer this question.".to_string(),
             citations: Vec::new(),
+            sources: Vec::new(),
             confidence: 0.0,
             processing_time_ms,
             chunks_retrieved: 0,
             chunks_used: 0,
+            chunks_dropped: 0,
+            chunks_truncated: 0,
             interaction_id: None,
+            variant: None,
+            model: None,
             raw_chunks: None,
+            provenance: None,
+            insufficient_evidence: None,
+            intent: QueryType::Question,
+            documents: Vec::new(),
+            follow_up_questions: Vec::new(),
+            debug: None,
+            curated: false,
+            structured: None,
+        }
+    }
+
+    /// Create a response listing the documents a lookup found
+    pub fn documents_found(target: &str, documents: Vec<DocumentSummary>, processing_time_ms: u64) -> Self {
+        let answer = match documents.len() {
+            1 => format!("Found 1 document matching \"{}\".", target),
+            n => format!("Found {} documents matching \"{}\".", n, target),
+        };
+        Self {
+            answer,
+            confidence: 1.0,
+            intent: QueryType::DocumentLookup,
+            documents,
+            ..Self::not_found(processing_time_ms)
+        }
+    }
+
+    /// Create a response declining to answer from weak evidence
+    pub fn insufficient_evidence(evidence: InsufficientEvidence, processing_time_ms: u64) -> Self {
+        Self {
+            answer: "I don't know: the documents don't contain enough evidence to answer this question.".to_string(),
+            chunks_retrieved: evidence.near_misses.len(),
+            insufficient_evidence: Some(evidence),
+            ..Self::not_found(processing_time_ms)
         }
     }
 }
 
 /// Response from document ingestion
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IngestResponse {
     /// Whether ingestion was successful
     pub success: bool,
@@ -183,10 +440,13 @@ pub struct IngestResponse {
     /// Any errors encountered (partial success)
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub errors: Vec<IngestError>,
+    /// Files dropped while being uploaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_uploads: Vec<SkippedUpload>,
 }
 
 /// Summary of an ingested document
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DocumentSummary {
     /// Document ID
     pub id: Uuid,
@@ -202,6 +462,9 @@ pub struct DocumentSummary {
     pub file_size: u64,
     /// Ingestion timestamp
     pub ingested_at: chrono::DateTime<chrono::Utc>,
+    /// Most frequent named entities (when keyword extraction is enabled)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<String>,
 }
 
 impl From<&Document> for DocumentSummary {
@@ -214,12 +477,13 @@ impl From<&Document> for DocumentSummary {
             total_chunks: doc.total_chunks,
             file_size: doc.file_size,
             ingested_at: doc.ingested_at,
+            entities: doc.entities(),
         }
     }
 }
 
 /// Error during ingestion
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IngestError {
     /// Filename that failed
     pub filename: String,
@@ -227,19 +491,101 @@ pub struct IngestError {
     pub error: String,
 }
 
+/// A file part dropped without being ingested
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SkippedUpload {
+    pub filename: String,
+    /// Why it was dropped (unchanged, same content as another file, ...)
+    pub reason: String,
+}
+
 /// Response for listing documents
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DocumentListResponse {
     /// List of documents
     pub documents: Vec<DocumentSummary>,
-    /// Total count
+    /// Total count (before pagination)
     pub total_count: usize,
+    /// Cursor of the next page (absent on the last page)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Result of a bulk deletion or collection purge (what would be deleted, for a dry run)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct BulkDeleteResponse {
+    pub dry_run: bool,
+    /// Deleted documents
+    pub document_ids: Vec<Uuid>,
+    /// Chunks removed from the vector store and chunk content
+    pub chunks: usize,
+    /// File registry rows removed
+    pub file_records: usize,
+    /// Original files removed from document storage (GCS)
+    pub stored_objects: usize,
+    /// Cached answers citing the documents
+    pub cached_answers: usize,
+}
+
+/// Result of restoring a knowledge base export
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ImportResponse {
+    /// Documents restored
+    pub documents: usize,
+    /// Filenames of documents skipped because they already exist
+    pub skipped_documents: Vec<String>,
+    /// Chunks restored
+    pub chunks: usize,
+    /// Chunks embedded again (exported without vectors or with another model)
+    pub reembedded_chunks: usize,
+    /// File records restored
+    pub file_records: usize,
+    /// File records skipped because the filename is already registered
+    pub skipped_file_records: usize,
+}
+
+/// One version of a document's file
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocumentVersionSummary {
+    #[serde(flatten)]
+    pub document: DocumentSummary,
+    /// Version number (1 for the first ingestion of the file)
+    pub version: u32,
+    /// Whether this is the document currently searched
+    pub current: bool,
+    /// When a newer version replaced it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub superseded_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Versions of a document's file, newest first
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocumentVersionsResponse {
+    pub filename: String,
+    pub versions: Vec<DocumentVersionSummary>,
+}
+
+/// Line diff between the text of two versions of a document
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VersionDiffResponse {
+    pub filename: String,
+    /// Older version
+    pub from: u32,
+    pub from_id: Uuid,
+    /// Newer version
+    pub to: u32,
+    pub to_id: Uuid,
+    /// Lines added and removed
+    pub added: usize,
+    pub removed: usize,
+    /// Unified diff of the reconstructed text
+    pub diff: String,
 }
 
 // ============ V2 API Response Types ============
 
 /// Status of a single file during ingestion (for V2 API)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum FileIngestStatus {
     /// New file successfully processed
@@ -305,7 +651,7 @@ impl FileIngestStatus {
 
 /// Summary statistics for ingestion
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[derive(Default)]
+#[derive(Default, ToSchema)]
 pub struct IngestSummary {
     /// Number of new files processed
     pub new_files: usize,
@@ -357,7 +703,7 @@ impl IngestSummary {
 }
 
 /// V2 Response from document ingestion with detailed status
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IngestResponseV2 {
     /// Whether any files were successfully processed
     pub success: bool,
@@ -387,7 +733,7 @@ impl IngestResponseV2 {
 // ============ String Search Types ============
 
 /// Result from string search (literal text matching)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct StringSearchResult {
     /// Chunk containing the match
     pub chunk_id: Uuid,
@@ -407,10 +753,12 @@ pub struct StringSearchResult {
     pub highlighted_snippet: String,
     /// Preview text around first match
     pub preview: String,
+    /// Excerpt around the best matches, highlighted with <mark> tags
+    pub highlighted_excerpt: String,
 }
 
 /// Response from string search
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct StringSearchResponse {
     /// Search query
     pub query: String,
@@ -444,7 +792,7 @@ impl StringSearchResponse {
 // ============ V2 API Response Types ============
 
 /// Query response type for V2 API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum QueryResponseType {
     /// Full RAG answer with LLM generation
@@ -454,15 +802,25 @@ pub enum QueryResponseType {
     },
     /// Literal string search (no LLM)
     StringSearch {
+        /// Chunks containing the phrase
         total_matches: usize,
         documents_matched: usize,
     },
+    /// Documents by name (no LLM)
+    DocumentLookup {
+        documents_matched: usize,
+    },
     /// No relevant information found
     NotFound,
+    /// Relevant-looking chunks, but none close enough to answer from
+    InsufficientEvidence {
+        threshold: f32,
+        best_score: f32,
+    },
 }
 
 /// Relevance indicator for citations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RelevanceInfo {
     /// Relevance score (0-100)
     pub score: u32,
@@ -489,7 +847,7 @@ impl RelevanceInfo {
 }
 
 /// Response metrics for V2 API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ResponseMetrics {
     /// Processing time in milliseconds
     pub processing_time_ms: u64,
@@ -500,7 +858,7 @@ pub struct ResponseMetrics {
 }
 
 /// Cache info for V2 API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CacheInfo {
     /// Whether response came from cache
     pub from_cache: bool,
@@ -509,8 +867,23 @@ pub struct CacheInfo {
     pub hit_count: Option<u32>,
 }
 
+/// Citation marker in a v2 answer
+///
+/// Offsets are byte offsets into the answer; the marker text is `[citation]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CitationMarker {
+    /// Start of the marker
+    pub start: usize,
+    /// End of the marker (exclusive)
+    pub end: usize,
+    /// `index` of the cited entry in `citations`
+    pub citation: u32,
+    /// Start of the sentence the citation backs (it ends at `start`)
+    pub claim_start: usize,
+}
+
 /// V2 Citation with frontend-friendly structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CitationV2 {
     /// Citation index (1-based for display)
     pub index: u32,
@@ -525,7 +898,7 @@ pub struct CitationV2 {
 }
 
 /// Source information for V2 citation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SourceInfoV2 {
     /// Document ID
     pub document_id: Uuid,
@@ -533,6 +906,10 @@ pub struct SourceInfoV2 {
     pub chunk_id: Uuid,
     /// Display filename
     pub filename: String,
+    /// "Title (date)" when the document has a title from front matter or
+    /// meta tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     /// File type
     pub file_type: String,
     /// Page number (if applicable)
@@ -541,13 +918,16 @@ pub struct SourceInfoV2 {
     /// Line range for code files
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lines: Option<String>,
+    /// Time range for audio/video (`HH:MM:SS-HH:MM:SS`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
     /// Section title
     #[serde(skip_serializing_if = "Option::is_none")]
     pub section: Option<String>,
 }
 
 /// Snippet information for V2 citation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SnippetInfoV2 {
     /// Plain text snippet
     pub text: String,
@@ -558,7 +938,7 @@ pub struct SnippetInfoV2 {
 }
 
 /// Document links for V2 citation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CitationLinks {
     /// URL to original document (authenticated GCS)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -566,6 +946,9 @@ pub struct CitationLinks {
     /// URL to extracted plain text (authenticated GCS)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub plaintext: Option<String>,
+    /// Where the cited text is in the source, to open it there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
 }
 
 impl CitationV2 {
@@ -590,9 +973,14 @@ impl CitationV2 {
                 document_id: citation.document_id,
                 chunk_id: citation.chunk_id,
                 filename: citation.filename.clone(),
+                title: citation.title.is_some().then(|| citation.source_name()),
                 file_type: citation.file_type.display_name().to_string(),
                 page: citation.page_number,
                 lines,
+                timestamp: match (citation.start_seconds, citation.end_seconds) {
+                    (Some(start), Some(end)) => Some(format!("{}-{}", format_timestamp(start), format_timestamp(end))),
+                    _ => None,
+                },
                 section: citation.section_title.clone(),
             },
             snippet: SnippetInfoV2 {
@@ -604,20 +992,32 @@ impl CitationV2 {
             links: CitationLinks {
                 document: citation.document_url.clone(),
                 plaintext: citation.plaintext_url.clone(),
+                location: citation.location.clone(),
             },
         }
     }
 }
 
 /// V2 Query Response (frontend-friendly format)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct QueryResponseV2 {
-    /// Generated answer
+    /// Generated answer, with `[n]` markers citing `citations`
     pub answer: String,
+    /// The answer with its key points (`json` format), its answer with `[n]`
+    /// markers too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured: Option<StructuredAnswer>,
+    /// Positions of the citation markers in `answer` (markers citing no
+    /// retrieved chunk are removed)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citation_markers: Vec<CitationMarker>,
     /// Query type and metadata
     pub query_type: QueryResponseType,
     /// Citations in V2 format
     pub citations: Vec<CitationV2>,
+    /// Cited documents, most relevant first (`citations` are their `index`es)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<CitedDocument>,
     /// Response metrics
     pub metrics: ResponseMetrics,
     /// Cache information
@@ -626,12 +1026,51 @@ pub struct QueryResponseV2 {
     /// Interaction ID for feedback
     #[serde(skip_serializing_if = "Option::is_none")]
     pub interaction_id: Option<Uuid>,
+    /// Experiment variant that served the answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Generation model that wrote the answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Signature binding the answer to its citations and corpus state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<AnswerProvenance>,
+    /// Closest chunks of an unanswered question (`insufficient_evidence`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub near_misses: Vec<CitationV2>,
+    /// Documents found by a document lookup
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<DocumentSummary>,
+    /// Questions the retrieved chunks also answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub follow_up_questions: Vec<String>,
+    /// Retrieval trace (if debug was true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<QueryExplain>,
+    /// The answer is a verified correction rather than generated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub curated: bool,
 }
 
 impl QueryResponseV2 {
     /// Create V2 response from standard response
     pub fn from_response(response: &QueryResponse, used_embeddings: bool, cache_info: Option<CacheInfo>) -> Self {
-        let query_type = if response.citations.is_empty() {
+        let query_type = if response.intent == QueryType::DocumentLookup {
+            QueryResponseType::DocumentLookup {
+                documents_matched: response.documents.len(),
+            }
+        } else if response.intent == QueryType::StringSearch {
+            let documents: std::collections::HashSet<Uuid> = response.citations.iter().map(|c| c.document_id).collect();
+            QueryResponseType::StringSearch {
+                total_matches: response.citations.len(),
+                documents_matched: documents.len(),
+            }
+        } else if let Some(ref evidence) = response.insufficient_evidence {
+            QueryResponseType::InsufficientEvidence {
+                threshold: evidence.threshold,
+                best_score: evidence.best_score,
+            }
+        } else if response.citations.is_empty() {
             QueryResponseType::NotFound
         } else {
             QueryResponseType::RagAnswer {
@@ -646,11 +1085,21 @@ impl QueryResponseV2 {
             .enumerate()
             .map(|(i, c)| CitationV2::from_citation(c, (i + 1) as u32))
             .collect();
+        let (answer, citation_markers) =
+            crate::generation::citation::align_citations(&response.answer, &response.citations);
+
+        let structured = response.structured.as_ref().map(|structured| StructuredAnswer {
+            answer: crate::generation::citation::align_citations(&structured.answer, &response.citations).0,
+            ..structured.clone()
+        });
 
         Self {
-            answer: response.answer.clone(),
+            answer,
+            structured,
+            citation_markers,
             query_type,
             citations,
+            sources: response.sources.clone(),
             metrics: ResponseMetrics {
                 processing_time_ms: response.processing_time_ms,
                 confidence: (response.confidence * 100.0).round() as u32,
@@ -658,68 +1107,46 @@ impl QueryResponseV2 {
             },
             cache_info,
             interaction_id: response.interaction_id,
+            variant: response.variant.clone(),
+            model: response.model.clone(),
+            provenance: response.provenance.clone(),
+            near_misses: response
+                .insufficient_evidence
+                .iter()
+                .flat_map(|evidence| evidence.near_misses.iter())
+                .enumerate()
+                .map(|(i, c)| CitationV2::from_citation(c, (i + 1) as u32))
+                .collect(),
+            documents: response.documents.clone(),
+            follow_up_questions: response.follow_up_questions.clone(),
+            debug: response.debug.clone(),
+            curated: response.curated,
         }
     }
+}
 
-    /// Create V2 response for string search
-    pub fn from_string_search(
-        answer: String,
-        results: &[StringSearchResult],
-        processing_time_ms: u64,
-    ) -> Self {
-        let total_matches: usize = results.iter().map(|r| r.match_count).sum();
-        let unique_docs: std::collections::HashSet<Uuid> = results.iter().map(|r| r.document_id).collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChunkSource;
 
-        let citations: Vec<CitationV2> = results
-            .iter()
-            .enumerate()
-            .map(|(i, r)| {
-                CitationV2 {
-                    index: (i + 1) as u32,
-                    source: SourceInfoV2 {
-                        document_id: r.document_id,
-                        chunk_id: r.chunk_id,
-                        filename: r.filename.clone(),
-                        file_type: r.file_type.display_name().to_string(),
-                        page: r.page_number,
-                        lines: None,
-                        section: None,
-                    },
-                    snippet: SnippetInfoV2 {
-                        text: r.preview.clone(),
-                        highlighted: r.highlighted_snippet.clone(),
-                        preview: if r.preview.len() > 100 {
-                            format!("{}...", &r.preview[..100])
-                        } else {
-                            r.preview.clone()
-                        },
-                    },
-                    relevance: RelevanceInfo {
-                        score: 100, // Exact match
-                        label: "Exact Match".to_string(),
-                    },
-                    links: CitationLinks {
-                        document: None,
-                        plaintext: None,
-                    },
-                }
-            })
-            .collect();
+    #[test]
+    fn test_cited_documents_grouped_by_best_passage() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let citation = |document_id: Uuid, filename: &str, score: f32| {
+            let chunk = Chunk::new(document_id, String::new(), ChunkSource::text(filename.to_string()), 0, 0, 0);
+            Citation::from_chunk(&chunk, score)
+        };
+        let citations = vec![citation(a, "a.txt", 0.6), citation(b, "b.txt", 0.8), citation(a, "a.txt", 0.9)];
 
-        Self {
-            answer,
-            query_type: QueryResponseType::StringSearch {
-                total_matches,
-                documents_matched: unique_docs.len(),
-            },
-            citations,
-            metrics: ResponseMetrics {
-                processing_time_ms,
-                confidence: if results.is_empty() { 0 } else { 100 },
-                used_embeddings: false,
-            },
-            cache_info: None,
-            interaction_id: None,
-        }
+        let documents = CitedDocument::group(&citations);
+        assert_eq!(documents.len(), 2);
+        assert_eq!((documents[0].document_id, documents[0].filename.as_str()), (a, "a.txt"));
+        assert_eq!((documents[0].relevance, documents[0].passages), (0.9, 2));
+        assert_eq!(documents[0].citations, vec![1, 3]);
+        assert_eq!((documents[1].document_id, documents[1].passages), (b, 1));
+        assert_eq!(documents[1].citations, vec![2]);
+
+        assert!(CitedDocument::group(&[]).is_empty());
     }
 }
@@ -0,0 +1,361 @@
+// Admin UI for the RAG server: plain JavaScript over the /api endpoints.
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+const keyInput = $("api-key");
+keyInput.value = localStorage.getItem("apiKey") || "";
+keyInput.addEventListener("change", () => localStorage.setItem("apiKey", keyInput.value.trim()));
+
+// API calls
+
+async function api(path, options = {}) {
+  const headers = new Headers(options.headers || {});
+  const key = keyInput.value.trim();
+  if (key) headers.set("Authorization", `Bearer ${key}`);
+  if (options.json !== undefined) {
+    headers.set("Content-Type", "application/json");
+    options.body = JSON.stringify(options.json);
+  }
+  const response = await fetch(`/api${path}`, { ...options, headers });
+  const body = response.headers.get("Content-Type")?.includes("json") ? await response.json() : await response.text();
+  if (!response.ok) {
+    throw new Error(body?.detail || body?.title || `${response.status} ${response.statusText}`);
+  }
+  return body;
+}
+
+function showError(error) {
+  const box = $("error");
+  box.textContent = error.message || String(error);
+  box.hidden = false;
+  clearTimeout(showError.timer);
+  showError.timer = setTimeout(() => (box.hidden = true), 6000);
+}
+
+function el(tag, attrs = {}, ...children) {
+  const node = document.createElement(tag);
+  for (const [name, value] of Object.entries(attrs)) {
+    if (name === "onclick") node.addEventListener("click", value);
+    else if (name === "html") node.innerHTML = value;
+    else node.setAttribute(name, value);
+  }
+  for (const child of children) {
+    if (child !== null && child !== undefined) node.append(child);
+  }
+  return node;
+}
+
+// Escape text, keeping the <mark> highlights the API adds
+function highlighted(text) {
+  const escaped = text.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
+  return escaped.replace(/&lt;(\/?)mark&gt;/g, "<$1mark>");
+}
+
+function formatSize(bytes) {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+// Tabs
+
+document.querySelectorAll("nav button").forEach((button) => {
+  button.addEventListener("click", () => {
+    document.querySelectorAll("nav button, .tab").forEach((node) => node.classList.remove("active"));
+    button.classList.add("active");
+    $(button.dataset.tab).classList.add("active");
+    if (button.dataset.tab === "jobs") loadJobs();
+    if (button.dataset.tab === "documents" && !$("docs-body").children.length) loadDocuments(true);
+  });
+});
+
+// Query
+
+$("query-form").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const submit = event.target.querySelector("button[type=submit]");
+  submit.disabled = true;
+  $("answer").replaceChildren(el("p", { class: "muted" }, "Thinking..."));
+  try {
+    const request = {
+      question: $("question").value.trim(),
+      top_k: Number($("top-k").value) || 15,
+      debug: $("debug").checked,
+    };
+    const collection = $("query-collection").value.trim();
+    if (collection) request.collection = collection;
+    renderAnswer(await api("/v2/query", { method: "POST", json: request }));
+  } catch (error) {
+    $("answer").replaceChildren();
+    showError(error);
+  } finally {
+    submit.disabled = false;
+  }
+});
+
+function renderAnswer(response) {
+  const metrics = response.metrics || {};
+  const card = el(
+    "div",
+    { class: "card" },
+    el("div", { class: "answer" }, response.answer),
+    el(
+      "p",
+      { class: "muted" },
+      `${metrics.processing_time_ms ?? "?"} ms, confidence ${metrics.confidence ?? "?"}%` +
+        (response.model ? `, ${response.model}` : "") +
+        (response.cache_info?.from_cache ? ", from cache" : "")
+    )
+  );
+
+  const citations = (response.citations || []).map((c) =>
+    el(
+      "div",
+      { class: "citation" },
+      el(
+        "strong",
+        {},
+        `[${c.index}] ${c.source.filename}` + (c.source.page ? `, page ${c.source.page}` : "") + ` (${c.relevance.score}%)`
+      ),
+      el("div", { html: highlighted(c.snippet.highlighted || c.snippet.text) })
+    )
+  );
+  const parts = [card];
+  if (citations.length) parts.push(el("div", { class: "card" }, el("h3", {}, "Sources"), ...citations));
+  if (response.follow_up_questions?.length) {
+    parts.push(
+      el(
+        "div",
+        { class: "card" },
+        el("h3", {}, "Follow-up questions"),
+        ...response.follow_up_questions.map((q) =>
+          el("button", { class: "plain", onclick: () => { $("question").value = q; } }, q)
+        )
+      )
+    );
+  }
+  if (response.debug) parts.push(renderTrace(response.debug));
+  $("answer").replaceChildren(...parts);
+}
+
+function renderTrace(trace) {
+  const rows = trace.candidates.map((c) =>
+    el(
+      "tr",
+      {},
+      el("td", {}, String(c.retrieval_rank + 1)),
+      el("td", {}, c.filename + (c.page_number ? ` p.${c.page_number}` : "")),
+      el("td", {}, c.vector_score.toFixed(3)),
+      el("td", {}, c.bm25_score != null ? c.bm25_score.toFixed(2) : ""),
+      el("td", {}, c.score.toFixed(3)),
+      el("td", {}, c.outcome.replace(/_/g, " ") + (c.filtered_by ? ` (${c.filtered_by})` : ""))
+    )
+  );
+  const filters = trace.filters.map((f) => `${f.name}: ${f.detail}` + (f.removed != null ? `, ${f.removed} removed` : ""));
+  return el(
+    "div",
+    { class: "card" },
+    el("h3", {}, "Retrieval trace"),
+    el(
+      "p",
+      { class: "muted" },
+      `Embedding ${trace.embedding_ms} ms, search ${trace.search_ms} ms` +
+        (trace.context_tokens != null ? `, ${trace.context_tokens} context tokens` : "")
+    ),
+    el("ul", {}, ...filters.map((f) => el("li", {}, f))),
+    el(
+      "table",
+      {},
+      el("thead", {}, el("tr", {}, ...["#", "Chunk", "Vector", "BM25", "Score", "Outcome"].map((h) => el("th", {}, h)))),
+      el("tbody", {}, ...rows)
+    ),
+    trace.prompt
+      ? el("details", {}, el("summary", {}, trace.prompt_redacted ? "Prompt (PII masked)" : "Prompt"), el("pre", {}, trace.prompt))
+      : null
+  );
+}
+
+// Upload
+
+let selectedFiles = [];
+
+function selectFiles(files) {
+  selectedFiles = [...selectedFiles, ...files];
+  $("selected").replaceChildren(...selectedFiles.map((f) => el("li", {}, `${f.name} (${formatSize(f.size)})`)));
+}
+
+$("files").addEventListener("change", (event) => selectFiles(event.target.files));
+const drop = $("drop");
+drop.addEventListener("dragover", (event) => {
+  event.preventDefault();
+  drop.classList.add("over");
+});
+drop.addEventListener("dragleave", () => drop.classList.remove("over"));
+drop.addEventListener("drop", (event) => {
+  event.preventDefault();
+  drop.classList.remove("over");
+  selectFiles(event.dataTransfer.files);
+});
+
+$("upload-form").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  if (!selectedFiles.length) return showError(new Error("No files selected"));
+  const submit = event.target.querySelector("button[type=submit]");
+  submit.disabled = true;
+  try {
+    const form = new FormData();
+    const collection = $("upload-collection").value.trim();
+    if (collection) form.append("options", JSON.stringify({ collection }));
+    selectedFiles.forEach((file) => form.append("files", file, file.name));
+    const result = await api("/ingest/async", { method: "POST", body: form });
+    const skipped = (result.skipped_uploads || []).map((s) => el("li", {}, `${s.filename}: ${s.reason}`));
+    $("upload-result").replaceChildren(
+      el("div", { class: "card" }, el("p", {}, result.message), skipped.length ? el("ul", {}, ...skipped) : null)
+    );
+    selectedFiles = [];
+    $("selected").replaceChildren();
+    $("files").value = "";
+    if (result.job_id) document.querySelector('nav button[data-tab="jobs"]').click();
+  } catch (error) {
+    showError(error);
+  } finally {
+    submit.disabled = false;
+  }
+});
+
+// Jobs
+
+async function loadJobs() {
+  try {
+    const list = await api("/jobs?limit=50");
+    $("jobs-stats").textContent =
+      `${list.pending} pending, ${list.processing} processing, ${list.complete} complete, ` +
+      `${list.failed} failed, ${list.worker_count} workers`;
+    $("jobs-body").replaceChildren(
+      ...list.jobs.map((job) =>
+        el(
+          "tr",
+          {},
+          el("td", {}, job.job_id.slice(0, 8)),
+          el("td", {}, job.status),
+          el("td", {}, job.stage),
+          el(
+            "td",
+            {},
+            el("div", { class: "progress" }, el("div", { style: `width: ${Math.round(job.percent_complete)}%` })),
+            `${Math.round(job.percent_complete)}%`
+          ),
+          el("td", {}, `${job.files_processed}/${job.total_files}` + (job.files_skipped ? `, ${job.files_skipped} skipped` : "")),
+          el(
+            "td",
+            {},
+            job.error || "",
+            ...(job.file_errors || []).map((e) => el("div", { class: "muted" }, `${e.filename}: ${e.error}`))
+          )
+        )
+      )
+    );
+  } catch (error) {
+    showError(error);
+  }
+}
+
+$("jobs-refresh").addEventListener("click", loadJobs);
+setInterval(() => {
+  if ($("jobs-auto").checked && $("jobs").classList.contains("active")) loadJobs();
+}, 2000);
+
+// Documents
+
+let docsCursor = null;
+
+async function loadDocuments(reset) {
+  try {
+    if (reset) {
+      docsCursor = null;
+      $("docs-body").replaceChildren();
+      $("chunks").replaceChildren();
+    }
+    const page = await api(`/documents?limit=50${docsCursor ? `&cursor=${encodeURIComponent(docsCursor)}` : ""}`);
+    docsCursor = page.next_cursor || null;
+    $("docs-more").hidden = !docsCursor;
+    $("docs-count").textContent = `${page.total_count} documents`;
+    $("docs-body").append(...page.documents.map(documentRow));
+    filterDocuments();
+  } catch (error) {
+    showError(error);
+  }
+}
+
+function documentRow(doc) {
+  const row = el(
+    "tr",
+    { class: "clickable", "data-filename": doc.filename.toLowerCase() },
+    el("td", {}, doc.filename),
+    el("td", {}, String(doc.file_type)),
+    el("td", {}, String(doc.total_chunks)),
+    el("td", {}, formatSize(doc.file_size)),
+    el("td", {}, new Date(doc.ingested_at).toLocaleString()),
+    el(
+      "td",
+      {},
+      el("button", {
+        class: "danger",
+        onclick: async (event) => {
+          event.stopPropagation();
+          if (!confirm(`Delete ${doc.filename} and its chunks?`)) return;
+          try {
+            await api(`/documents/${doc.id}`, { method: "DELETE" });
+            row.remove();
+          } catch (error) {
+            showError(error);
+          }
+        },
+      }, "Delete")
+    )
+  );
+  row.addEventListener("click", () => loadChunks(doc, null));
+  return row;
+}
+
+function filterDocuments() {
+  const filter = $("doc-filter").value.trim().toLowerCase();
+  for (const row of $("docs-body").children) {
+    row.hidden = filter && !row.dataset.filename.includes(filter);
+  }
+}
+
+async function loadChunks(doc, cursor) {
+  try {
+    const page = await api(`/documents/${doc.id}/chunks?limit=50${cursor ? `&cursor=${encodeURIComponent(cursor)}` : ""}`);
+    const chunks = page.chunks.map((chunk) =>
+      el(
+        "div",
+        { class: "chunk" },
+        el(
+          "div",
+          { class: "muted" },
+          `#${chunk.chunk_index}` + (chunk.page_number ? `, page ${chunk.page_number}` : "") +
+            (chunk.section_title ? `, ${chunk.section_title}` : "")
+        ),
+        chunk.content
+      )
+    );
+    const container = cursor ? $("chunks").querySelector(".card") : el("div", { class: "card" }, el("h3", {}, `${doc.filename}: ${page.total_count} chunks`));
+    container.querySelector(".more")?.remove();
+    container.append(...chunks);
+    if (page.next_cursor) {
+      container.append(el("button", { class: "plain more", onclick: () => loadChunks(doc, page.next_cursor) }, "More chunks"));
+    }
+    if (!cursor) {
+      $("chunks").replaceChildren(container);
+      container.scrollIntoView({ behavior: "smooth" });
+    }
+  } catch (error) {
+    showError(error);
+  }
+}
+
+$("docs-refresh").addEventListener("click", () => loadDocuments(true));
+$("docs-more").addEventListener("click", () => loadDocuments(false));
+$("doc-filter").addEventListener("input", filterDocuments);